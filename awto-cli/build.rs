@@ -0,0 +1,34 @@
+//! Build script embedding the organization-wide defaults of the cli, read from the file at `AWTO_DIST_CONFIG`.
+
+use std::{env, fs, path::Path};
+
+/// Environment variable holding the path of a TOML file of `awto.toml` keys, which seed the configuration of every app.
+const DIST_CONFIG_ENV: &str = "AWTO_DIST_CONFIG";
+
+fn main() {
+    println!("cargo:rerun-if-env-changed={}", DIST_CONFIG_ENV);
+
+    let contents = match env::var_os(DIST_CONFIG_ENV).filter(|path| !path.is_empty()) {
+        Some(path) => {
+            let path = Path::new(&path);
+            println!("cargo:rerun-if-changed={}", path.display());
+            let contents = fs::read_to_string(path).unwrap_or_else(|err| {
+                panic!(
+                    "could not read {} '{}': {}",
+                    DIST_CONFIG_ENV,
+                    path.display(),
+                    err
+                )
+            });
+            if let Err(err) = toml::from_str::<toml::value::Table>(&contents) {
+                panic!("invalid {} '{}': {}", DIST_CONFIG_ENV, path.display(), err);
+            }
+            contents
+        }
+        None => String::new(),
+    };
+
+    let out_dir = env::var_os("OUT_DIR").expect("cargo sets OUT_DIR");
+    fs::write(Path::new(&out_dir).join("dist-config.toml"), contents)
+        .expect("could not write dist-config.toml");
+}
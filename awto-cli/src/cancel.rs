@@ -0,0 +1,264 @@
+use std::{
+    future::{self, Future},
+    io,
+    process::Output,
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::Result;
+use log::warn;
+use tokio::{io::AsyncReadExt, process::Child, sync::watch, time};
+
+use crate::error::Error;
+
+/// Exit code of a cancelled command, which shells also report for processes interrupted by Ctrl-C.
+pub const CANCELLED_EXIT_CODE: i32 = 130;
+
+/// Cancellation of a running command, shared by its steps.
+///
+/// Steps which would leave files or processes behind when stopped at any point check it between their writes,
+/// and kill their child processes once it is cancelled.
+#[derive(Clone, Debug)]
+pub struct Cancellation {
+    sender: Arc<watch::Sender<bool>>,
+    receiver: watch::Receiver<bool>,
+}
+
+impl Cancellation {
+    pub fn new() -> Self {
+        let (sender, receiver) = watch::channel(false);
+        Cancellation {
+            sender: Arc::new(sender),
+            receiver,
+        }
+    }
+
+    /// Cancels the command, waking the steps waiting in [`Cancellation::cancelled`].
+    pub fn cancel(&self) {
+        // Sending cannot fail while `self` holds a receiver
+        let _ = self.sender.send(true);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.receiver.borrow()
+    }
+
+    /// Fails with [`Error::Cancelled`] once the command is cancelled, checked before starting a step.
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            return Err(Error::Cancelled.into());
+        }
+
+        Ok(())
+    }
+
+    /// Waits until the command is cancelled.
+    pub async fn cancelled(&self) {
+        let mut receiver = self.receiver.clone();
+        while !*receiver.borrow() {
+            if receiver.changed().await.is_err() {
+                future::pending::<()>().await;
+            }
+        }
+    }
+
+    /// Runs `step` until it completes, or drops it and fails with [`Error::Cancelled`] once the command is cancelled.
+    ///
+    /// Only steps which can stop at any point, such as waiting for changes, are run this way.
+    pub async fn run<T>(&self, step: impl Future<Output = Result<T>>) -> Result<T> {
+        tokio::select! {
+            result = step => result,
+            _ = self.cancelled() => Err(Error::Cancelled.into()),
+        }
+    }
+}
+
+impl Default for Cancellation {
+    fn default() -> Self {
+        Cancellation::new()
+    }
+}
+
+/// Cancels `cancellation` on the first Ctrl-C, so the command stops once it cleaned up,
+/// and exits right away with [`CANCELLED_EXIT_CODE`] on the second.
+pub fn cancel_on_ctrl_c(cancellation: Cancellation) {
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_err() {
+            return;
+        }
+        warn!("cancelling, press Ctrl-C again to exit immediately");
+        cancellation.cancel();
+
+        if tokio::signal::ctrl_c().await.is_ok() {
+            std::process::exit(CANCELLED_EXIT_CODE);
+        }
+    });
+}
+
+/// Kills `child` and waits for it to exit, so it is not left running or unreaped.
+pub async fn kill_child(child: &mut Child) {
+    // Fails when the child already exited, which is what killing it is for
+    let _ = child.kill().await;
+}
+
+/// Waits for `child` to exit, collecting its stdout like [`Child::wait_with_output`],
+/// or kills it and returns `None` once the command is cancelled.
+pub async fn child_output(child: Child, cancellation: &Cancellation) -> io::Result<Option<Output>> {
+    child_output_with_grace(child, cancellation, Duration::ZERO).await
+}
+
+/// Waits for `child` like [`child_output`], but once the command is cancelled, gives it `grace` to exit on its own
+/// before killing it, such as to save its progress on the Ctrl-C it received as well.
+pub async fn child_output_with_grace(
+    mut child: Child,
+    cancellation: &Cancellation,
+    grace: Duration,
+) -> io::Result<Option<Output>> {
+    let mut stdout = child.stdout.take();
+    let output = async {
+        let mut buf = Vec::new();
+        if let Some(stdout) = &mut stdout {
+            stdout.read_to_end(&mut buf).await?;
+        }
+        let status = child.wait().await?;
+        Ok(Output {
+            status,
+            stdout: buf,
+            stderr: Vec::new(),
+        })
+    };
+
+    let output = tokio::select! {
+        output = output => Some(output),
+        _ = cancellation.cancelled() => None,
+    };
+    match output {
+        Some(output) => output.map(Some),
+        None => {
+            if time::timeout(grace, child.wait()).await.is_err() {
+                kill_child(&mut child).await;
+            }
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::atomic::{AtomicBool, Ordering},
+        time::Duration,
+    };
+
+    use tokio::{process::Command, time};
+
+    use super::*;
+    use crate::error::ErrorCode;
+
+    /// Sets its flag when dropped, like the cleanup of a step.
+    struct Cleanup<'a>(&'a AtomicBool);
+
+    impl Drop for Cleanup<'_> {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    fn cancel_after(cancellation: &Cancellation, delay: Duration) {
+        let cancellation = cancellation.clone();
+        tokio::spawn(async move {
+            time::sleep(delay).await;
+            cancellation.cancel();
+        });
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancels_steps() {
+        let cancellation = Cancellation::new();
+        assert!(cancellation.check().is_ok());
+        cancel_after(&cancellation, Duration::from_secs(1));
+
+        let cleaned_up = AtomicBool::new(false);
+        let step = async {
+            let _cleanup = Cleanup(&cleaned_up);
+            time::sleep(Duration::from_secs(3600)).await;
+            Ok(())
+        };
+        let err = cancellation.run(step).await.unwrap_err();
+        assert_eq!(Error::code_of(&err), Some(ErrorCode::Cancelled));
+        assert!(cleaned_up.load(Ordering::SeqCst));
+        assert!(cancellation.is_cancelled());
+        assert!(cancellation.check().is_err());
+
+        // Steps which complete first are not cancelled
+        let cancellation = Cancellation::new();
+        cancel_after(&cancellation, Duration::from_secs(10));
+        assert_eq!(cancellation.run(async { Ok(1) }).await.unwrap(), 1);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn kills_children() {
+        let cancellation = Cancellation::new();
+        let child = Command::new("sleep")
+            .arg("60")
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        let pid = child.id().unwrap().to_string();
+        cancel_after(&cancellation, Duration::from_millis(50));
+
+        let output = time::timeout(Duration::from_secs(10), child_output(child, &cancellation))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(output.is_none());
+        // The child was reaped, so its pid no longer exists
+        let exists = std::process::Command::new("kill")
+            .args(["-0", &pid])
+            .stderr(std::process::Stdio::null())
+            .status()
+            .unwrap();
+        assert!(!exists.success());
+
+        let child = Command::new("echo")
+            .arg("done")
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        let output = child_output(child, &Cancellation::new())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(output.stdout, b"done\n");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn waits_for_children_within_grace() {
+        // Exits on its own shortly after the cancellation, within its grace
+        let cancellation = Cancellation::new();
+        let child = Command::new("sleep").arg("0.3").spawn().unwrap();
+        cancel_after(&cancellation, Duration::from_millis(50));
+        let start = time::Instant::now();
+        let output = child_output_with_grace(child, &cancellation, Duration::from_secs(10))
+            .await
+            .unwrap();
+        assert!(output.is_none());
+        assert!(start.elapsed() >= Duration::from_millis(250));
+
+        // Killed once its grace is over
+        let cancellation = Cancellation::new();
+        let child = Command::new("sleep").arg("60").spawn().unwrap();
+        cancel_after(&cancellation, Duration::from_millis(50));
+        let output = time::timeout(
+            Duration::from_secs(10),
+            child_output_with_grace(child, &cancellation, Duration::from_millis(100)),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert!(output.is_none());
+    }
+}
@@ -0,0 +1,320 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use clap::Parser;
+use tokio::fs;
+
+use crate::{
+    cancel::Cancellation,
+    config::{Config, CONFIG_PATH},
+    error::Error,
+    schema::{load_schema_models, SchemaModel},
+    util::WorkspaceContext,
+    Runnable,
+};
+
+use super::{
+    check_schema_package, clean_dir, compile_packages,
+    database::{DatabaseConfig, DatabasePackage},
+    database_url::{read_dotenv, resolve_database_url, DatabaseUrl, DOTENV_PATH},
+    ensure_package_dir_managed, package_manifest, prepare_awto_dir, render_schema_models, Generate,
+    PackageFiles, PackageLog, GENERATED_HEADER,
+};
+
+/// Compiles admin command line package managing the rows of the database tables
+#[derive(Clone, Parser)]
+pub struct Admin {
+    /// Keeps generated Rust code unformatted
+    #[clap(long)]
+    pub no_format: bool,
+    /// Interleaves generated code with comments telling which model and generator produced it
+    #[clap(long)]
+    pub annotate_sources: bool,
+    /// Overwrites package directories which were not generated by awto
+    #[clap(long)]
+    pub force: bool,
+    /// Fails after compiling when any warning was reported
+    #[clap(long)]
+    pub deny_warnings: bool,
+    /// Prints more information
+    #[clap(short, long)]
+    pub verbose: bool,
+    /// Number of warnings reported by the run
+    #[clap(skip)]
+    pub warnings: usize,
+}
+
+#[async_trait]
+impl Runnable for Admin {
+    async fn run(&mut self, cancellation: &Cancellation) -> Result<()> {
+        let commands: Vec<Box<dyn Generate>> = vec![Box::new(self.clone())];
+        compile_packages(
+            commands,
+            cancellation,
+            self.no_format,
+            self.annotate_sources,
+            self.deny_warnings,
+            &mut self.warnings,
+        )
+        .await
+    }
+
+    fn is_verbose(&self) -> bool {
+        self.verbose
+    }
+
+    fn warnings(&self) -> usize {
+        self.warnings
+    }
+}
+
+#[async_trait]
+impl Generate for Admin {
+    /// Generates the admin package, returning its name.
+    async fn generate(
+        &self,
+        workspace: &WorkspaceContext,
+        log: &mut PackageLog,
+    ) -> Result<Vec<String>> {
+        check_schema_package(workspace).await?;
+
+        let models = load_schema_models().await?;
+
+        let (config, package_name) = DatabaseConfig::load(workspace).await?;
+        let database_package = AdminPackage::database_package(&models, &package_name, &config);
+        if workspace
+            .package_by_name(&database_package.name())
+            .is_none()
+        {
+            return Err(Error::MissingPackage {
+                package: database_package.name(),
+                command: "awto compile database".to_string(),
+            }
+            .into());
+        }
+
+        config.check_visible(
+            &format!("package '{}'", AdminPackage::NAME),
+            &database_package,
+            database_package.tables(&models),
+            [],
+        )?;
+
+        prepare_awto_dir(self.force).await?;
+        ensure_package_dir_managed(AdminPackage::DIR, self.force).await?;
+
+        let package = AdminPackage { database_package };
+        let cargo_toml =
+            package_manifest(workspace, &package.render(Self::ADMIN_CARGO_TOML)).await?;
+        Self::prepare_admin_dir(&package, cargo_toml, log)
+            .await
+            .map_err(|source| Error::GeneratePackage {
+                package: AdminPackage::NAME.to_string(),
+                source,
+            })?;
+
+        Ok(vec![AdminPackage::NAME.to_string()])
+    }
+}
+
+impl Admin {
+    const ADMIN_CARGO_TOML: &'static str = include_str!("../templates/admin/Cargo.toml.template");
+    const ADMIN_BUILD: &'static str = include_str!("../templates/admin/build.rs.template");
+
+    async fn prepare_admin_dir(
+        package: &AdminPackage,
+        cargo_toml: String,
+        log: &mut PackageLog,
+    ) -> Result<()> {
+        let config = Config::load(CONFIG_PATH).await?;
+        let files = [
+            (AdminPackage::path("Cargo.toml"), cargo_toml),
+            (AdminPackage::path("build.rs"), render_schema_models(&package.render(Self::ADMIN_BUILD), &config.schema, &config.features)),
+            (
+                AdminPackage::path("src/main.rs"),
+                format!(
+                    "{}//! Command line tool managing the rows of the database tables, generated by awto.\n\n#![allow(deprecated)]\n\ninclude!(concat!(env!(\"OUT_DIR\"), \"/app.rs\"));\n",
+                    GENERATED_HEADER
+                ),
+            ),
+        ];
+
+        let mut package_files = PackageFiles::new(AdminPackage::DIR, AdminPackage::NAME).await;
+        if Path::new(AdminPackage::DIR).is_dir() {
+            let manifest_path = package_files.manifest_path();
+            let mut keep: Vec<&str> = files.iter().map(|(path, _)| path.as_str()).collect();
+            keep.push(&manifest_path);
+            clean_dir(AdminPackage::DIR, &keep)
+                .await
+                .with_context(|| format!("could not clean directory '{}'", AdminPackage::DIR))?;
+        }
+
+        let src_dir = AdminPackage::path("src");
+        fs::create_dir_all(&src_dir)
+            .await
+            .with_context(|| format!("could not create directory '{}'", src_dir))?;
+
+        for (path, content) in files {
+            package_files.write(&path, content, log).await?;
+        }
+        package_files.finish(log).await?;
+
+        Ok(())
+    }
+}
+
+/// The database managed by the compiled admin package, which commands running the admin connect to.
+pub struct AdminDatabase {
+    /// Name of the database, such as `main`.
+    pub name: String,
+    /// Environment variable the admin reads the url of the database from, such as `DATABASE_URL`.
+    pub url_var: String,
+    /// Url of the database resolved like `awto compile database` does.
+    pub url: Option<DatabaseUrl>,
+}
+
+impl AdminDatabase {
+    /// Resolves the database managed by the admin package of `workspace`,
+    /// failing with [`Error::MissingPackage`] when the admin was not compiled.
+    pub async fn resolve(
+        workspace: &WorkspaceContext,
+        models: &[SchemaModel],
+        database_url: Option<&str>,
+    ) -> Result<AdminDatabase> {
+        if workspace.package_by_name(AdminPackage::NAME).is_none() {
+            return Err(Error::MissingPackage {
+                package: AdminPackage::NAME.to_string(),
+                command: "awto compile admin".to_string(),
+            }
+            .into());
+        }
+
+        let (config, package_name) = DatabaseConfig::load(workspace).await?;
+        let package = AdminPackage::database_package(models, &package_name, &config);
+        let dotenv = read_dotenv(DOTENV_PATH).await?;
+        let url_file = package.url_file(&config);
+        let url_var = package.url_var();
+        let url = resolve_database_url(&url_var, database_url, &dotenv, url_file)
+            .await?
+            .map(|(url, _)| url);
+
+        Ok(AdminDatabase {
+            name: package.database_name().to_string(),
+            url_var,
+            url,
+        })
+    }
+
+    /// Returns whether the table of `model` is managed by the admin.
+    pub fn manages(&self, model: &SchemaModel) -> bool {
+        model.is_database_table
+            && model
+                .database
+                .as_deref()
+                .unwrap_or(DatabasePackage::DEFAULT_DATABASE)
+                == self.name
+    }
+}
+
+/// The generated admin package, managing the tables of a single database package.
+struct AdminPackage {
+    database_package: DatabasePackage,
+}
+
+impl AdminPackage {
+    const NAME: &'static str = "admin";
+    const DIR: &'static str = "./awto/admin";
+
+    fn path(path: &str) -> String {
+        format!("{}/{}", Self::DIR, path)
+    }
+
+    /// Returns the database package managed by the admin, the package of the main database with its entities.
+    fn database_package(
+        models: &[SchemaModel],
+        package_name: &str,
+        config: &DatabaseConfig,
+    ) -> DatabasePackage {
+        let database_packages = DatabasePackage::from_models(models, package_name, config);
+        database_packages
+            .iter()
+            .find(|package| package.database_name() == DatabasePackage::DEFAULT_DATABASE)
+            .or_else(|| database_packages.first())
+            .cloned()
+            .expect("a database package is always compiled")
+    }
+
+    /// Renders a template of the admin package, depending on the matching database package as `database`.
+    fn render(&self, template: &str) -> String {
+        let rendered = self.database_package.render(template);
+        if !self.database_package.is_renamed() {
+            return rendered;
+        }
+
+        let database_name = self.database_package.name();
+        rendered.replacen(
+            "database = { path = \"../database\", features",
+            &format!(
+                "database = {{ path = \"../{}\", package = \"{}\", features",
+                database_name, database_name
+            ),
+            1,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use awto_compile::compat::SeaOrmCompat;
+
+    use super::*;
+    use crate::compile::database::{Codegen, Localization, MapStorage, Temporal};
+
+    #[test]
+    fn render_package() {
+        let package = AdminPackage {
+            database_package: DatabasePackage {
+                package_name: "database".to_string(),
+                database: None,
+                localization: Localization::Columns,
+                map_storage: MapStorage::Jsonb,
+                temporal: Temporal::Trigger,
+                codegen: Codegen::Buildscript,
+                sea_orm: SeaOrmCompat::LATEST,
+            },
+        };
+
+        let cargo_toml = package.render(Admin::ADMIN_CARGO_TOML);
+        assert!(cargo_toml.contains(r#"name = "admin""#));
+        assert!(cargo_toml
+            .contains(r#"database = { path = "../database", features = ["bulk", "fake"] }"#));
+        assert!(package
+            .render(Admin::ADMIN_BUILD)
+            .contains("const DATABASE: Option<&str> = None;"));
+    }
+
+    #[test]
+    fn render_grouped_package() {
+        let package = AdminPackage {
+            database_package: DatabasePackage {
+                package_name: "acme-database".to_string(),
+                database: Some("main".to_string()),
+                localization: Localization::Table,
+                map_storage: MapStorage::Jsonb,
+                temporal: Temporal::Trigger,
+                codegen: Codegen::Buildscript,
+                sea_orm: SeaOrmCompat::LATEST,
+            },
+        };
+
+        let cargo_toml = package.render(Admin::ADMIN_CARGO_TOML);
+        assert!(cargo_toml.contains(r#"name = "admin""#));
+        assert!(cargo_toml.contains(
+            r#"database = { path = "../acme-database-main", package = "acme-database-main", features = ["bulk", "fake"] }"#
+        ));
+        let build = package.render(Admin::ADMIN_BUILD);
+        assert!(build.contains(r#"const DATABASE: Option<&str> = Some("main");"#));
+        assert!(build.contains("const LOCALIZATION: Localization = Localization::Table;"));
+    }
+}
@@ -0,0 +1,597 @@
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use std::process::Stdio;
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use clap::Parser;
+use log::info;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tokio::{fs, io::AsyncWriteExt};
+
+use crate::{
+    cancel::{child_output, Cancellation},
+    config::{Config, CONFIG_PATH},
+    error::Error,
+    util::{CargoFile, WorkspaceContext},
+    Runnable,
+};
+
+use super::{
+    build_awto_pkgs, check_files, check_schema_package, clean_dir,
+    database::ConnectConfig,
+    ensure_package_dir_managed,
+    manifest::{content_hash, MANIFEST_FILE},
+    package_manifest, prepare_awto_dir, render_schema_models, report_warnings, BuildOptions,
+    GenerationManifest, PackageFiles, PackageLog, GENERATED_HEADER,
+};
+
+/// Compiles files with an external generator, which reads the models of the schema as JSON from stdin
+/// and prints the files it generates as JSON to stdout
+#[derive(Clone, Parser)]
+pub struct Custom {
+    /// Executable of the generator, such as `./tools/avro-gen`
+    #[clap(long)]
+    pub generator: PathBuf,
+    /// Name of the directory in `./awto` the files are written into, instead of the file name of the generator
+    #[clap(long)]
+    pub name: Option<String>,
+    /// Option passed to the generator as `key=value`, which can be given multiple times
+    #[clap(long = "option", number_of_values = 1, parse(try_from_str = parse_option))]
+    pub options: Vec<(String, String)>,
+    /// Lists the files which are out of date instead of writing them, failing when there are any
+    #[clap(long)]
+    pub check: bool,
+    /// Keeps generated Rust code unformatted
+    #[clap(long)]
+    pub no_format: bool,
+    /// Interleaves generated code with comments telling which model and generator produced it
+    #[clap(long)]
+    pub annotate_sources: bool,
+    /// Overwrites package directories which were not generated by awto
+    #[clap(long)]
+    pub force: bool,
+    /// Fails after compiling when any warning was reported
+    #[clap(long)]
+    pub deny_warnings: bool,
+    /// Prints more information
+    #[clap(short, long)]
+    pub verbose: bool,
+    /// Number of warnings reported by the run
+    #[clap(skip)]
+    pub warnings: usize,
+}
+
+#[async_trait]
+impl Runnable for Custom {
+    async fn run(&mut self, cancellation: &Cancellation) -> Result<()> {
+        let name = self.output_name()?;
+        let dir = format!("./awto/{}", name);
+        let generator = format!("custom/{}", name);
+
+        let mut workspace = WorkspaceContext::load(".").await?;
+        check_schema_package(&workspace).await?;
+        prepare_awto_dir(self.force).await?;
+        ensure_package_dir_managed(&dir, self.force).await?;
+        check_dir_generator(&dir, &generator).await?;
+
+        let input = self.generator_input(&mut workspace, cancellation).await?;
+        let fingerprint = self.fingerprint(&input).await?;
+        if is_up_to_date(&dir, &fingerprint).await {
+            info!("files generated into '{}' are up to date", dir);
+            return Ok(());
+        }
+
+        let files = self.run_generator(&input, cancellation).await?;
+        if self.check {
+            return check_files(&dir, &files).await;
+        }
+
+        let is_crate = files.contains_key("Cargo.toml");
+        if let Some(cargo_toml) = files.get("Cargo.toml") {
+            self.check_crate_name(cargo_toml, &name)?;
+        }
+        cancellation.check()?;
+        let mut log = PackageLog::default();
+        let written = write_files(
+            &dir,
+            &generator,
+            &self.generator,
+            &fingerprint,
+            &files,
+            &mut log,
+        )
+        .await;
+        log.flush();
+        written.map_err(|source| Error::GeneratePackage {
+            package: name.clone(),
+            source,
+        })?;
+        info!("generated {} file(s) into '{}'", files.len(), dir);
+
+        if !is_crate {
+            return Ok(());
+        }
+        let mut warnings = Vec::new();
+        build_awto_pkgs(
+            &mut workspace,
+            &[name],
+            &[],
+            &ConnectConfig::default(),
+            cancellation,
+            BuildOptions {
+                no_format: self.no_format,
+                annotate_sources: self.annotate_sources,
+                force_narrowing: false,
+            },
+            &mut warnings,
+        )
+        .await?;
+        report_warnings(warnings, self.deny_warnings, &mut self.warnings).await
+    }
+
+    fn is_verbose(&self) -> bool {
+        self.verbose
+    }
+
+    fn warnings(&self) -> usize {
+        self.warnings
+    }
+}
+
+impl Custom {
+    const IR_CARGO_TOML: &'static str = include_str!("../templates/ir/Cargo.toml.template");
+    const IR_MAIN: &'static str = include_str!("../templates/ir/main.rs.template");
+
+    /// Returns the name of the output directory, defaulting to the file name of the generator without its extension.
+    fn output_name(&self) -> Result<String> {
+        let name = match &self.name {
+            Some(name) => name.clone(),
+            None => self
+                .generator
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .map(ToString::to_string)
+                .unwrap_or_default(),
+        };
+        let is_valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !is_valid || name == IrPackage::NAME {
+            return Err(self.error(anyhow!(
+                "'{}' cannot be the name of its output directory, pass --name",
+                name
+            )));
+        }
+
+        Ok(name)
+    }
+
+    fn error(&self, source: anyhow::Error) -> anyhow::Error {
+        Error::Generator {
+            generator: self.generator.display().to_string(),
+            source,
+        }
+        .into()
+    }
+
+    /// Generates and runs the `ir` package printing the models of the schema, returning them with the options of the generator.
+    async fn generator_input(
+        &self,
+        workspace: &mut WorkspaceContext,
+        cancellation: &Cancellation,
+    ) -> Result<serde_json::Value> {
+        let mut input = read_ir(workspace, self.force, cancellation).await?;
+        let options: BTreeMap<_, _> = self.options.iter().cloned().collect();
+        input["options"] = serde_json::json!({
+            "annotate_sources": self.annotate_sources,
+            "options": options,
+        });
+
+        Ok(input)
+    }
+
+    /// Returns the hash of the `input` and the executable of the generator, which produce the same files while unchanged.
+    async fn fingerprint(&self, input: &serde_json::Value) -> Result<String> {
+        let executable = fs::read(&self.generator)
+            .await
+            .with_context(|| format!("could not read file '{}'", self.generator.display()))
+            .map_err(|source| self.error(source))?;
+
+        let mut hasher = Sha256::new();
+        hasher.update(input.to_string());
+        hasher.update(executable);
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    /// Runs the generator with `input` on its stdin, returning the contents of the files it printed by their path.
+    ///
+    /// The generator is killed once `cancellation` is cancelled.
+    async fn run_generator(
+        &self,
+        input: &serde_json::Value,
+        cancellation: &Cancellation,
+    ) -> Result<BTreeMap<String, String>> {
+        let mut child = tokio::process::Command::new(&self.generator)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .context("could not start the generator")
+            .map_err(|source| self.error(source))?;
+
+        // Written concurrently, so a generator printing files before reading all of its input does not block
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let json = input.to_string();
+        let writer = tokio::spawn(async move {
+            let _ = stdin.write_all(json.as_bytes()).await;
+        });
+        let output = child_output(child, cancellation)
+            .await
+            .context("could not run the generator")
+            .map_err(|source| self.error(source))?
+            .ok_or(Error::Cancelled)?;
+        let _ = writer.await;
+        if !output.status.success() {
+            return Err(self.error(anyhow!("generator exited with {}", output.status)));
+        }
+
+        let version = input["version"].as_u64().unwrap_or_default();
+        parse_output(&output.stdout, version).map_err(|source| self.error(source))
+    }
+
+    /// Checks the `Cargo.toml` generated into the output directory names the package `name`,
+    /// as generated packages are built by the name of their directory.
+    fn check_crate_name(&self, cargo_toml: &str, name: &str) -> Result<()> {
+        let cargo_file: CargoFile = toml::from_str(cargo_toml)
+            .context("generated 'Cargo.toml' is invalid")
+            .map_err(|source| self.error(source))?;
+        match cargo_file.package {
+            Some(package) if package.name == name => Ok(()),
+            Some(package) => Err(self.error(anyhow!(
+                "generated package is named '{}', but must be named '{}' after its directory",
+                package.name,
+                name
+            ))),
+            None => Err(self.error(anyhow!("generated 'Cargo.toml' has no [package] section"))),
+        }
+    }
+}
+
+/// Generates and runs the `ir` package printing the models of the schema as the input of external generators,
+/// overwriting `./awto/ir` when it was not generated by awto and `force` is set.
+pub(crate) async fn read_ir(
+    workspace: &mut WorkspaceContext,
+    force: bool,
+    cancellation: &Cancellation,
+) -> Result<serde_json::Value> {
+    ensure_package_dir_managed(IrPackage::DIR, force).await?;
+    let mut log = PackageLog::default();
+    let written = IrPackage::write(workspace, &mut log).await;
+    log.flush();
+    written.map_err(|source| Error::GeneratePackage {
+        package: IrPackage::NAME.to_string(),
+        source,
+    })?;
+    workspace.add_member(&format!("awto/{}", IrPackage::NAME));
+    workspace.flush().await?;
+
+    let child = tokio::process::Command::new("cargo")
+        .current_dir("./awto")
+        .args(["run", "--quiet", "-p", IrPackage::NAME])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(Error::Cargo)?;
+    let output = child_output(child, cancellation)
+        .await
+        .map_err(Error::Cargo)?
+        .ok_or(Error::Cancelled)?;
+    if !output.status.success() {
+        return Err(Error::Build {
+            packages: vec![IrPackage::NAME.to_string()],
+            status: output.status,
+        }
+        .into());
+    }
+
+    serde_json::from_slice(&output.stdout).context("package 'ir' printed invalid JSON")
+}
+
+/// Parses an `--option key=value`.
+fn parse_option(option: &str) -> Result<(String, String), String> {
+    match option.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("option '{}' is not of the form key=value", option)),
+    }
+}
+
+/// Output printed by an external generator, matching `GeneratorOutput` of awto-compile.
+#[derive(Deserialize)]
+struct GeneratorOutput {
+    version: u64,
+    files: Vec<GeneratorFile>,
+}
+
+#[derive(Deserialize)]
+struct GeneratorFile {
+    path: String,
+    contents: String,
+}
+
+/// Parses the `stdout` of a generator given models of the IR `version`, returning the contents of its files by their path.
+fn parse_output(stdout: &[u8], version: u64) -> Result<BTreeMap<String, String>> {
+    let output: GeneratorOutput =
+        serde_json::from_slice(stdout).context("generator printed invalid JSON")?;
+    if output.version != version {
+        return Err(anyhow!(
+            "generator reads models of IR version {}, but awto wrote version {}",
+            output.version,
+            version
+        ));
+    }
+
+    let mut files = BTreeMap::new();
+    for file in output.files {
+        let path = Path::new(&file.path);
+        let is_relative = path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+        if file.path.is_empty() || !is_relative {
+            return Err(anyhow!(
+                "generated path '{}' is not inside the output directory",
+                file.path
+            ));
+        }
+        if file.path == MANIFEST_FILE {
+            return Err(anyhow!(
+                "generated path '{}' is reserved by awto",
+                file.path
+            ));
+        }
+        if files.insert(file.path.clone(), file.contents).is_some() {
+            return Err(anyhow!("generated path '{}' more than once", file.path));
+        }
+    }
+
+    Ok(files)
+}
+
+/// Fails when the directory `dir` holds files of a generator other than `generator`, such as a built-in package.
+async fn check_dir_generator(dir: &str, generator: &str) -> Result<()> {
+    let manifest = GenerationManifest::load(dir).await.unwrap_or_default();
+    match manifest
+        .files
+        .values()
+        .find(|file| file.generator != generator)
+    {
+        Some(file) => Err(Error::GeneratePackage {
+            package: dir.trim_start_matches("./awto/").to_string(),
+            source: anyhow!(
+                "directory '{}' holds files of generator '{}', pass --name",
+                dir,
+                file.generator
+            ),
+        }
+        .into()),
+        None => Ok(()),
+    }
+}
+
+/// Returns whether the files in `dir` were generated from the same input and executable, and were not edited since.
+async fn is_up_to_date(dir: &str, fingerprint: &str) -> bool {
+    let manifest = match GenerationManifest::load(dir).await {
+        Some(manifest) if !manifest.files.is_empty() => manifest,
+        _ => return false,
+    };
+
+    for (path, file) in &manifest.files {
+        if file.options.get("fingerprint").map(String::as_str) != Some(fingerprint) {
+            return false;
+        }
+        match fs::read(Path::new(dir).join(path)).await {
+            Ok(contents) if content_hash(&contents) == file.hash => {}
+            _ => return false,
+        }
+    }
+
+    true
+}
+
+/// Writes the generated `files` into `dir`, deleting everything else in it.
+async fn write_files(
+    dir: &str,
+    generator: &str,
+    executable: &Path,
+    fingerprint: &str,
+    files: &BTreeMap<String, String>,
+    log: &mut PackageLog,
+) -> Result<()> {
+    let mut package_files = PackageFiles::new(dir, generator)
+        .await
+        .option("executable", executable.display())
+        .option("fingerprint", fingerprint);
+    let paths: Vec<String> = files
+        .keys()
+        .map(|path| format!("{}/{}", dir, path))
+        .collect();
+    if Path::new(dir).is_dir() {
+        let manifest_path = package_files.manifest_path();
+        let mut keep: Vec<&str> = paths.iter().map(String::as_str).collect();
+        keep.push(&manifest_path);
+        clean_dir(dir, &keep)
+            .await
+            .with_context(|| format!("could not clean directory '{}'", dir))?;
+    }
+
+    for (path, contents) in paths.iter().zip(files.values()) {
+        if let Some(parent) = Path::new(path).parent() {
+            fs::create_dir_all(parent)
+                .await
+                .with_context(|| format!("could not create directory '{}'", parent.display()))?;
+        }
+        package_files.write(path, contents, log).await?;
+    }
+    package_files.finish(log).await
+}
+
+/// The generated package printing the models of the schema as the input of external generators.
+struct IrPackage;
+
+impl IrPackage {
+    const NAME: &'static str = "ir";
+    const DIR: &'static str = "./awto/ir";
+
+    async fn write(workspace: &WorkspaceContext, log: &mut PackageLog) -> Result<()> {
+        let src_dir = format!("{}/src", Self::DIR);
+        fs::create_dir_all(&src_dir)
+            .await
+            .with_context(|| format!("could not create directory '{}'", src_dir))?;
+
+        let config = Config::load(CONFIG_PATH).await?;
+        let mut package_files = PackageFiles::new(Self::DIR, Self::NAME).await;
+        package_files
+            .write(
+                &format!("{}/Cargo.toml", Self::DIR),
+                package_manifest(workspace, Custom::IR_CARGO_TOML).await?,
+                log,
+            )
+            .await?;
+        package_files
+            .write(
+                &format!("{}/main.rs", src_dir),
+                format!(
+                    "{}{}",
+                    GENERATED_HEADER,
+                    render_schema_models(Custom::IR_MAIN, &config.schema, &config.features)
+                ),
+                log,
+            )
+            .await?;
+        package_files.finish(log).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn options() {
+        assert_eq!(
+            parse_option("namespace=shop.events").unwrap(),
+            ("namespace".to_string(), "shop.events".to_string())
+        );
+        assert_eq!(
+            parse_option("empty=").unwrap(),
+            ("empty".to_string(), String::new())
+        );
+        assert!(parse_option("namespace").is_err());
+        assert!(parse_option("=shop").is_err());
+    }
+
+    #[test]
+    fn output() {
+        let stdout = br##"{"version": 1, "files": [
+            {"path": "schema/product.avsc", "contents": "{}", "kind": "other"},
+            {"path": "README.md", "contents": "# Avro", "kind": "markdown"}
+        ]}"##;
+        let files = parse_output(stdout, 1).unwrap();
+        assert_eq!(
+            files.keys().collect::<Vec<_>>(),
+            ["README.md", "schema/product.avsc"]
+        );
+        assert_eq!(files["README.md"], "# Avro");
+
+        let err = parse_output(stdout, 2).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "generator reads models of IR version 1, but awto wrote version 2"
+        );
+
+        for path in [
+            "../escape.rs",
+            "/etc/passwd",
+            "./src/lib.rs",
+            "",
+            MANIFEST_FILE,
+        ] {
+            let stdout = format!(
+                r#"{{"version": 1, "files": [{{"path": "{}", "contents": ""}}]}}"#,
+                path
+            );
+            assert!(parse_output(stdout.as_bytes(), 1).is_err(), "{}", path);
+        }
+        assert!(parse_output(b"not json", 1).is_err());
+    }
+
+    #[tokio::test]
+    async fn check_and_write() {
+        let dir = std::env::temp_dir().join(format!("awto-cli-custom-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir).await;
+        let dir = dir.to_str().unwrap().to_string();
+        let files: BTreeMap<_, _> = vec![
+            ("schema/product.avsc".to_string(), "{}".to_string()),
+            ("README.md".to_string(), "# Avro".to_string()),
+        ]
+        .into_iter()
+        .collect();
+
+        let err = check_files(&dir, &files).await.unwrap_err();
+        assert_eq!(
+            Error::code_of(&err),
+            Some(crate::error::ErrorCode::GeneratedOutdated)
+        );
+        assert!(!is_up_to_date(&dir, "0123").await);
+
+        let mut log = PackageLog::default();
+        write_files(
+            &dir,
+            "custom/avro-gen",
+            Path::new("./avro-gen"),
+            "0123",
+            &files,
+            &mut log,
+        )
+        .await
+        .unwrap();
+        check_files(&dir, &files).await.unwrap();
+        assert!(is_up_to_date(&dir, "0123").await);
+        assert!(!is_up_to_date(&dir, "4567").await);
+        check_dir_generator(&dir, "custom/avro-gen").await.unwrap();
+        assert!(check_dir_generator(&dir, "custom/other").await.is_err());
+
+        fs::write(format!("{}/README.md", dir), "# Edited")
+            .await
+            .unwrap();
+        assert!(!is_up_to_date(&dir, "0123").await);
+
+        let mut files = files;
+        files.remove("schema/product.avsc");
+        let err = check_files(&dir, &files).await.unwrap_err();
+        match err.downcast_ref::<Error>() {
+            Some(Error::GeneratedOutdated { files, .. }) => {
+                assert_eq!(files, &["README.md", "schema/product.avsc"])
+            }
+            _ => panic!("unexpected error {:?}", err),
+        }
+
+        write_files(
+            &dir,
+            "custom/avro-gen",
+            Path::new("./avro-gen"),
+            "4567",
+            &files,
+            &mut log,
+        )
+        .await
+        .unwrap();
+        assert!(!Path::new(&dir).join("schema/product.avsc").exists());
+        assert!(is_up_to_date(&dir, "4567").await);
+
+        fs::remove_dir_all(dir).await.unwrap();
+    }
+}
@@ -1,113 +1,2075 @@
-use std::path::Path;
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
 
 use anyhow::{anyhow, Context, Result};
 use async_trait::async_trait;
+use awto_compile::{
+    compat::SeaOrmCompat,
+    database::{ENTITIES_FILE, SOURCE_CODEGEN_ENV},
+};
 use clap::Parser;
-use log::info;
-use tokio::fs;
+use serde::Deserialize;
+use tokio::{fs, process::Command};
+use toml_edit::Document;
 
 use crate::{
-    compile::build_awto_pkg,
-    util::{add_package_to_workspace, CargoFile},
+    cancel::Cancellation,
+    compile::{
+        check_files, check_schema_package, clean_dir, compile_named_packages, compile_packages,
+        dry_run_files, ensure_package_dir_managed, generation_env,
+        manifest::{package_fingerprint, with_publication_header},
+        profile_phase,
+        profiling::{record_phase, Phase},
+        remove_stale_packages,
+        warning::Warning,
+        watch::{watch, WatchHooks},
+        BuildOptions, Generate, PackageFiles, PackageLog, GENERATED_HEADER,
+    },
+    config::{Config, CONFIG_PATH},
+    error::{Error, ErrorCode},
+    schema::{load_schema_models, SchemaModel},
+    util::{CargoPackage, WorkspaceContext},
     Runnable,
 };
 
-use super::prepare_awto_dir;
+use super::database_url::{read_dotenv, resolve_database_url, DatabaseUrl, DOTENV_PATH};
+use super::{package_manifest, prepare_awto_dir, render_schema_models};
 
 /// Compiles database package from app schema
-#[derive(Parser)]
+#[derive(Clone, Parser)]
 pub struct Database {
+    /// Only compiles the package of the given database
+    #[clap(long)]
+    pub only_db: Option<String>,
+    /// Url of the database to compile against, instead of `DATABASE_URL`
+    #[clap(long)]
+    pub database_url: Option<String>,
+    /// Regenerates the packages whenever the schema or `awto.toml` changes, until stopped
+    #[clap(long)]
+    pub watch: bool,
+    /// Shell command run after each successful regeneration in watch mode,
+    /// with `AWTO_CHANGED_MODELS` and `AWTO_PACKAGES` set to comma separated names
+    #[clap(long, requires = "watch")]
+    pub on_success: Option<String>,
+    /// File updated after each successful regeneration in watch mode, for other watchers to observe
+    #[clap(long, requires = "watch")]
+    pub signal_file: Option<PathBuf>,
+    /// Generates integration tests running against an ephemeral database
+    #[clap(long)]
+    pub with_test_harness: bool,
+    /// Generates criterion benchmarks of the generated queries
+    #[clap(long)]
+    pub with_benches: bool,
+    /// Keeps generated Rust code unformatted
+    #[clap(long)]
+    pub no_format: bool,
+    /// Interleaves generated code with comments telling which model and generator produced it
+    #[clap(long)]
+    pub annotate_sources: bool,
+    /// Overwrites package directories which were not generated by awto
+    #[clap(long)]
+    pub force: bool,
+    /// Lists the files which are out of date instead of writing them, failing when there are any
+    #[clap(long, conflicts_with = "watch")]
+    pub check: bool,
+    /// Lists the files which would be written or deleted without changing any, and builds no package
+    #[clap(long, conflicts_with_all = &["watch", "check"])]
+    pub dry_run: bool,
+    /// Alters columns narrowed by the schema even when rows of the database do not fit them,
+    /// truncating longer text and failing on null rows and values which cannot be converted
+    #[clap(long)]
+    pub force_narrowing: bool,
+    /// Fails after compiling when any warning was reported
+    #[clap(long)]
+    pub deny_warnings: bool,
+    /// Seconds each attempt to connect to the database may take, instead of `database.connect.timeout`
+    #[clap(long, value_name = "SECS")]
+    pub connect_timeout: Option<f64>,
+    /// Attempts to connect to the database after a failed first attempt, instead of `database.connect.retries`
+    #[clap(long, value_name = "N")]
+    pub connect_retries: Option<u32>,
+    /// Seconds waited before retrying to connect, doubled for every retry, instead of `database.connect.backoff`
+    #[clap(long, value_name = "SECS")]
+    pub connect_backoff: Option<f64>,
+    /// Seconds statements may run on the database, instead of `database.connect.statement_timeout`
+    #[clap(long, value_name = "SECS")]
+    pub statement_timeout: Option<f64>,
+    /// Seconds to wait for another build syncing the same database, instead of `database.connect.lock_timeout`
+    #[clap(long, value_name = "SECS")]
+    pub lock_timeout: Option<f64>,
     /// Prints more information
     #[clap(short, long)]
     pub verbose: bool,
+    /// Number of warnings reported by the run
+    #[clap(skip)]
+    pub warnings: usize,
 }
 
 #[async_trait]
 impl Runnable for Database {
-    async fn run(&mut self) -> Result<()> {
-        let cargo_file = CargoFile::load("./schema/Cargo.toml")
-            .await
-            .context("could not load schema Cargo.toml file from './schema/Cargo.toml'")?;
-        if cargo_file
-            .package
-            .as_ref()
-            .map(|package| package.name != "schema")
-            .unwrap_or(false)
-        {
-            match cargo_file.package {
-                Some(package) => {
-                    return Err(anyhow!(
-                        "schema package must be named 'schema' but is named '{}'",
-                        package.name
-                    ));
+    async fn run(&mut self, cancellation: &Cancellation) -> Result<()> {
+        if self.watch {
+            let hooks = WatchHooks {
+                on_success: self.on_success.clone(),
+                signal_file: self.signal_file.clone(),
+            };
+            return watch(&hooks, cancellation, || {
+                let command = self.clone();
+                let cancellation = cancellation.clone();
+                async move {
+                    let mut warnings = 0;
+                    let packages = compile_named_packages(
+                        vec![Box::new(command.clone())],
+                        &cancellation,
+                        command.no_format,
+                        command.annotate_sources,
+                        command.deny_warnings,
+                        &mut warnings,
+                    )
+                    .await?;
+                    let models = load_schema_models().await?;
+                    Ok((packages, models))
                 }
-                None => return Err(anyhow!("schema package must be named 'schema'")),
+            })
+            .await;
+        }
+
+        let commands: Vec<Box<dyn Generate>> = vec![Box::new(self.clone())];
+        compile_packages(
+            commands,
+            cancellation,
+            self.no_format,
+            self.annotate_sources,
+            self.deny_warnings,
+            &mut self.warnings,
+        )
+        .await
+    }
+
+    fn is_verbose(&self) -> bool {
+        self.verbose
+    }
+
+    fn warnings(&self) -> usize {
+        self.warnings
+    }
+}
+
+#[async_trait]
+impl Generate for Database {
+    /// Resolves the url of each database package compiled, which is only passed to their build scripts.
+    async fn database_urls(
+        &self,
+        workspace: &WorkspaceContext,
+        log: &mut PackageLog,
+    ) -> Result<Vec<(String, DatabaseUrl)>> {
+        let (mut packages, config, _) = self.packages(workspace).await?;
+        if let Some(only_db) = &self.only_db {
+            packages.retain(|package| package.database_name() == only_db);
+        }
+        if self.database_url.is_some() && packages.len() > 1 {
+            return Err(Error::AmbiguousDatabaseUrl.into());
+        }
+
+        let dotenv = read_dotenv(DOTENV_PATH).await?;
+        let mut urls = Vec::new();
+        for package in packages {
+            let var = package.url_var();
+            let url_file = package.url_file(&config);
+            match resolve_database_url(&var, self.database_url.as_deref(), &dotenv, url_file)
+                .await?
+            {
+                Some((url, origin)) => {
+                    log.debug(format_args!("using {} {} from {}", var, url, origin));
+                    urls.push((var, url));
+                }
+                None => log.debug(format_args!("no {} is set", var)),
             }
         }
 
-        prepare_awto_dir().await?;
+        Ok(urls)
+    }
 
-        Self::prepare_database_dir().await?;
-        add_package_to_workspace("awto/database").await?;
-        build_awto_pkg("database").await?;
+    /// Generates the database packages, returning their names,
+    /// or checks them with `--check` or lists their changes with `--dry-run` without building any.
+    async fn generate(
+        &self,
+        workspace: &WorkspaceContext,
+        log: &mut PackageLog,
+    ) -> Result<Vec<String>> {
+        let (mut packages, config, models) = self.packages(workspace).await?;
+        let writes = !self.check && !self.dry_run;
+        if !self.dry_run {
+            prepare_awto_dir(self.force).await?;
+        }
+        if writes {
+            remove_stale_packages(&packages, log).await?;
+        }
 
-        info!("compiled package 'database'");
+        if let Some(only_db) = &self.only_db {
+            packages.retain(|package| package.database_name() == only_db);
+        }
 
-        Ok(())
+        let mut names = Vec::new();
+        for package in packages {
+            let name = package.name();
+            for (enabled, consumer) in [
+                (self.with_test_harness, "the test harness"),
+                (self.with_benches, "the benchmarks"),
+            ] {
+                if enabled {
+                    config.check_visible(
+                        consumer,
+                        &package,
+                        package.tables(&models),
+                        package.tables(&models),
+                    )?;
+                }
+            }
+            ensure_package_dir_managed(&package.dir(), self.force).await?;
+
+            let prepared = self.prepare_database_dir(&package, &config, workspace, log);
+            profile_phase(&format!("prepare {}", name), prepared)
+                .await
+                .map_err(|source| match Error::code_of(&source) {
+                    Some(ErrorCode::GeneratedOutdated) => source,
+                    _ => Error::GeneratePackage {
+                        package: name.clone(),
+                        source,
+                    }
+                    .into(),
+                })?;
+            if writes {
+                names.push(name);
+            }
+        }
+
+        Ok(names)
     }
 
-    fn is_verbose(&self) -> bool {
-        self.verbose
+    fn connect_overrides(&self) -> ConnectConfig {
+        ConnectConfig {
+            timeout: self.connect_timeout,
+            retries: self.connect_retries,
+            backoff: self.connect_backoff,
+            statement_timeout: self.statement_timeout,
+            lock_timeout: self.lock_timeout,
+        }
+    }
+
+    fn force_narrowing(&self) -> bool {
+        self.force_narrowing
     }
 }
 
 impl Database {
-    const DATABASE_DIR: &'static str = "./awto/database";
-    const DATABASE_SRC_DIR: &'static str = "./awto/database/src";
-    const DATABASE_CARGO_PATH: &'static str = "./awto/database/Cargo.toml";
-    const DATABASE_CARGO_TOML_BYTES: &'static [u8] =
-        include_bytes!("../templates/database/Cargo.toml.template");
-    const DATABASE_BUILD_PATH: &'static str = "./awto/database/build.rs";
-    const DATABASE_BUILD_BYTES: &'static [u8] =
-        include_bytes!("../templates/database/build.rs.template");
-    const DATABASE_LIB_PATH: &'static str = "./awto/database/src/lib.rs";
-
-    async fn prepare_database_dir() -> Result<()> {
-        if Path::new(Self::DATABASE_DIR).is_dir() {
-            fs::remove_dir_all(Self::DATABASE_DIR)
-                .await
-                .with_context(|| format!("could not delete directory '{}'", Self::DATABASE_DIR))?;
+    /// Returns every database package of the schema of `workspace`, along with the database configuration
+    /// and the models of the schema.
+    async fn packages(
+        &self,
+        workspace: &WorkspaceContext,
+    ) -> Result<(Vec<DatabasePackage>, DatabaseConfig, Vec<SchemaModel>)> {
+        check_schema_package(workspace).await?;
+
+        let models = load_schema_models().await?;
+
+        let (config, package_name) = DatabaseConfig::load(workspace).await?;
+        let packages = DatabasePackage::from_models(&models, &package_name, &config);
+        if let Some(only_db) = &self.only_db {
+            if !packages
+                .iter()
+                .any(|package| package.database_name() == only_db)
+            {
+                return Err(Error::UnknownDatabase(only_db.clone()).into());
+            }
         }
 
-        fs::create_dir(Self::DATABASE_DIR)
+        Ok((packages, config, models))
+    }
+
+    const DATABASE_CARGO_TOML: &'static str =
+        include_str!("../templates/database/Cargo.toml.template");
+    const DATABASE_BUILD: &'static str = include_str!("../templates/database/build.rs.template");
+    const DATABASE_TEST_HARNESS_CARGO_TOML: &'static str =
+        include_str!("../templates/database/Cargo.toml.test-harness.template");
+    const DATABASE_TESTS_COMMON: &'static str =
+        include_str!("../templates/database/tests/common.rs.template");
+    const DATABASE_TESTS_SMOKE: &'static str =
+        include_str!("../templates/database/tests/smoke.rs.template");
+    const DATABASE_BENCHES_CARGO_TOML: &'static str =
+        include_str!("../templates/database/Cargo.toml.benches.template");
+    const DATABASE_BENCHES_COMMON: &'static str =
+        include_str!("../templates/database/benches/common.rs.template");
+    const DATABASE_BENCHES_QUERIES: &'static str =
+        include_str!("../templates/database/benches/queries.rs.template");
+
+    /// Returns the `Cargo.toml` file of `package` rendered from its templates, before the workspace is inherited.
+    fn cargo_toml(&self, package: &DatabasePackage, config: &DatabaseConfig) -> Result<String> {
+        let mut cargo_toml = package.render(Self::DATABASE_CARGO_TOML);
+        cargo_toml.push_str(&config.dependency_sections()?);
+        cargo_toml.push_str(&Self::dev_sections(
+            self.with_test_harness,
+            self.with_benches,
+        ));
+
+        Ok(cargo_toml)
+    }
+
+    /// Writes the files of `package` into its directory, or lists those which are out of date with `--check`
+    /// and those which would change with `--dry-run`.
+    ///
+    /// With `codegen = "source"`, the code generated by the codegen package of `package` is written into its sources
+    /// in place of its build script, see [`run_codegen`](Self::run_codegen).
+    async fn prepare_database_dir(
+        &self,
+        package: &DatabasePackage,
+        config: &DatabaseConfig,
+        workspace: &WorkspaceContext,
+        log: &mut PackageLog,
+    ) -> Result<()> {
+        let dir = package.dir();
+        let ext_dir = package.path("src/ext");
+
+        let mut lib_content = format!(
+            "{}#![doc = include_str!(concat!(env!(\"OUT_DIR\"), \"/docs.md\"))]\n#![allow(deprecated)]\n\n{}include!(concat!(env!(\"OUT_DIR\"), \"/app.rs\"));\n",
+            GENERATED_HEADER,
+            if config.visibility.sea_orm_reexport { "pub use sea_orm;\n\n" } else { "" }
+        );
+
+        let extensions = Self::extension_modules(&ext_dir, log).await?;
+        if !extensions.is_empty() {
+            writeln!(lib_content, "\nmod ext {{").unwrap();
+            for extension in extensions {
+                writeln!(lib_content, "    pub mod {};", extension).unwrap();
+            }
+            writeln!(lib_content, "}}").unwrap();
+        }
+
+        let mut package_files = PackageFiles::new(&dir, "database")
             .await
-            .with_context(|| format!("could not create directory '{}'", Self::DATABASE_DIR))?;
+            .option("with_test_harness", self.with_test_harness)
+            .option("with_benches", self.with_benches)
+            .option(
+                "localization",
+                format!("{:?}", package.localization).to_lowercase(),
+            )
+            .option(
+                "map_storage",
+                format!("{:?}", package.map_storage).to_lowercase(),
+            )
+            .option("temporal", format!("{:?}", package.temporal).to_lowercase())
+            .option("codegen", format!("{:?}", package.codegen).to_lowercase());
+        if let Some(published) = package_files.published() {
+            lib_content = with_publication_header(&lib_content, published);
+        }
+
+        let mut files = vec![(package.path("src/lib.rs"), lib_content)];
+        if self.with_test_harness {
+            files.extend(Self::test_files(package));
+        }
+        if self.with_benches {
+            files.extend(Self::bench_files(package));
+        }
+        let app_config = Config::load(CONFIG_PATH).await?;
+        let (schema, features) = (&app_config.schema, &app_config.features);
+        for (_, contents) in &mut files {
+            *contents = render_schema_models(contents, schema, features);
+        }
+        let build = render_schema_models(
+            &config.render_build(&package.render(Self::DATABASE_BUILD)),
+            schema,
+            features,
+        );
+
+        let cargo_toml = self.cargo_toml(package, config)?;
+        match package.codegen {
+            Codegen::Buildscript => {
+                files.insert(
+                    0,
+                    (
+                        package.path("Cargo.toml"),
+                        package_manifest(workspace, &cargo_toml).await?,
+                    ),
+                );
+                files.insert(1, (package.path("build.rs"), build));
+            }
+            Codegen::Source => {
+                let (cargo_toml, codegen_cargo_toml) = codegen_manifests(&cargo_toml, package)?;
+                files.insert(
+                    0,
+                    (
+                        package.path("Cargo.toml"),
+                        package_manifest(workspace, &cargo_toml).await?,
+                    ),
+                );
+                let codegen_cargo_toml = package_manifest(workspace, &codegen_cargo_toml).await?;
+                let generated = self
+                    .run_codegen(package, workspace, codegen_cargo_toml, build, log)
+                    .await?;
+                for (_, contents) in &mut files {
+                    *contents = inline_generated(contents, &generated);
+                }
+                files.extend(
+                    generated
+                        .into_iter()
+                        .filter(|(name, _)| name.starts_with("entities/"))
+                        .map(|(name, contents)| (package.path(&format!("src/{}", name)), contents)),
+                );
+            }
+        }
+
+        if self.check || self.dry_run {
+            let prefix = format!("{}/", dir);
+            let files: BTreeMap<String, String> = files
+                .into_iter()
+                .map(|(path, contents)| (path.trim_start_matches(&prefix).to_string(), contents))
+                .collect();
+            return match self.check {
+                true => check_files(&dir, &files).await,
+                false => dry_run_files(&dir, &files).await,
+            };
+        }
+
+        write_package_files(&dir, files, &[&ext_dir], &mut package_files, log).await?;
+        let published = package_files.published().cloned();
+        package_files.finish(log).await?;
+
+        if let Some(published) = published {
+            if package_fingerprint(&dir).await? != published.fingerprint {
+                log.warn(format_args!(
+                    "'{}' changed since v{} was published to the '{}' registry, run `awto package database` to publish the changes",
+                    package.name(),
+                    published.version,
+                    published.registry
+                ));
+            }
+        }
+
+        if self.with_test_harness {
+            log.debug(format_args!(
+                "generated test harness in '{}'",
+                package.path("tests")
+            ));
+        }
+        if self.with_benches {
+            log.debug(format_args!(
+                "generated benches in '{}'",
+                package.path("benches")
+            ));
+        }
 
-        fs::create_dir(Self::DATABASE_SRC_DIR)
+        Ok(())
+    }
+
+    /// Writes and builds the codegen package of `package`, whose main is the build script `main` of the package,
+    /// and runs it to generate the code of the package into the target directory, returning the generated files by name.
+    ///
+    /// The codegen package connects to and syncs the database like the build script, with the urls resolved for the command,
+    /// and the warnings it prints are added to `log`. The entities it generates are returned as `entities/<table>.rs`.
+    async fn run_codegen(
+        &self,
+        package: &DatabasePackage,
+        workspace: &WorkspaceContext,
+        cargo_toml: String,
+        main: String,
+        log: &mut PackageLog,
+    ) -> Result<BTreeMap<String, String>> {
+        let codegen_dir = package.codegen_dir();
+        let name = package.codegen_name();
+        ensure_package_dir_managed(&codegen_dir, self.force).await?;
+        let mut codegen_files = PackageFiles::new(&codegen_dir, "database/codegen").await;
+        let files = vec![
+            (format!("{}/Cargo.toml", codegen_dir), cargo_toml),
+            (
+                format!("{}/src/main.rs", codegen_dir),
+                format!("{}{}", GENERATED_HEADER, main),
+            ),
+        ];
+        write_package_files(&codegen_dir, files, &[], &mut codegen_files, log).await?;
+        codegen_files.finish(log).await?;
+
+        let mut workspace = workspace.clone();
+        workspace.add_member(&format!("awto/{}", name));
+        workspace.flush().await?;
+        let status = Command::new("cargo")
+            .current_dir("./awto")
+            .args(["build", "--quiet", "-p", &name])
+            .stdin(Stdio::null())
+            .status()
             .await
-            .with_context(|| format!("could not create directory '{}'", Self::DATABASE_SRC_DIR))?;
+            .map_err(Error::Cargo)?;
+        if !status.success() {
+            return Err(Error::Build {
+                packages: vec![name],
+                status,
+            }
+            .into());
+        }
+
+        // Build scripts generate into `OUT_DIR` from the directory of their package, which the codegen is given instead
+        let out_dir = workspace
+            .target_dir()
+            .join(CODEGEN_OUT_DIR)
+            .join(package.name());
+        for dir in [out_dir.as_path(), Path::new(&package.dir())] {
+            fs::create_dir_all(dir)
+                .await
+                .with_context(|| format!("could not create directory '{}'", dir.display()))?;
+        }
+        let canonicalize = |path: PathBuf| async move {
+            fs::canonicalize(&path)
+                .await
+                .with_context(|| format!("could not resolve path '{}'", path.display()))
+        };
+        let out_dir = canonicalize(out_dir).await?;
+        let package_dir = canonicalize(PathBuf::from(package.dir())).await?;
+        let executable = canonicalize(workspace.target_dir().join("debug"))
+            .await?
+            .join(format!("{}{}", name, env::consts::EXE_SUFFIX));
 
-        fs::write(Self::DATABASE_CARGO_PATH, Self::DATABASE_CARGO_TOML_BYTES)
+        let mut command = Command::new(executable);
+        command
+            .current_dir(&package_dir)
+            .env("OUT_DIR", &out_dir)
+            .env("CARGO_MANIFEST_DIR", &package_dir)
+            .env(SOURCE_CODEGEN_ENV, "1");
+        let options = BuildOptions {
+            no_format: self.no_format,
+            annotate_sources: self.annotate_sources,
+            force_narrowing: self.force_narrowing,
+        };
+        generation_env(&mut command, &self.connect_overrides(), options).await?;
+        let urls = self
+            .database_urls(&workspace, &mut PackageLog::default())
+            .await?;
+        for (var, url) in &urls {
+            command.env(var, url.as_str());
+        }
+        let output = command
+            .stdin(Stdio::null())
+            .output()
             .await
-            .with_context(|| format!("could not write file '{}'", Self::DATABASE_CARGO_PATH))?;
+            .map_err(Error::Cargo)?;
 
-        fs::write(Self::DATABASE_BUILD_PATH, Self::DATABASE_BUILD_BYTES)
+        let packages = [package.name()];
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        for message in stdout
+            .lines()
+            .filter_map(|line| line.strip_prefix("cargo:warning="))
+        {
+            if let Some(phase) = Phase::parse(message, &packages) {
+                record_phase(phase);
+            } else if let Some(warning) = Warning::parse(message, &packages) {
+                log.warning(warning);
+            } else {
+                log.warn(message);
+            }
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        for line in stderr.lines() {
+            let line = urls
+                .iter()
+                .fold(line.to_string(), |line, (_, url)| url.redact(&line));
+            if output.status.success() {
+                log.debug(line);
+            } else {
+                log.warn(line);
+            }
+        }
+        if !output.status.success() {
+            return Err(Error::Build {
+                packages: vec![name],
+                status: output.status,
+            }
+            .into());
+        }
+
+        let read = |path: PathBuf| async move {
+            fs::read_to_string(&path)
+                .await
+                .with_context(|| format!("could not read file '{}'", path.display()))
+        };
+        let mut generated = BTreeMap::new();
+        for file in CODEGEN_FILES {
+            generated.insert(file.to_string(), read(out_dir.join(file)).await?);
+        }
+        // Entities are only generated for the tables found in the database
+        for table in read(out_dir.join(ENTITIES_FILE)).await?.lines() {
+            let path = out_dir.join(format!("{}.rs", table));
+            if path.is_file() {
+                generated.insert(format!("entities/{}.rs", table), read(path).await?);
+            }
+        }
+
+        Ok(generated)
+    }
+
+    /// Returns the module names of user-owned extension files in the `ext_dir` directory.
+    ///
+    /// The extension directory is never deleted or rewritten by the cli.
+    /// The `ext` module is declared by the generated `lib.rs`, so a `mod.rs` in the directory is never compiled
+    /// and only warned about.
+    async fn extension_modules(ext_dir: &str, log: &mut PackageLog) -> Result<Vec<String>> {
+        if !Path::new(ext_dir).is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let mut extensions = Vec::new();
+        let mut entries = fs::read_dir(ext_dir)
             .await
-            .with_context(|| format!("could not write file '{}'", Self::DATABASE_BUILD_PATH))?;
-
-        let lib_content = concat!(
-            "// This file is automatically @generated by ",
-            env!("CARGO_PKG_NAME"),
-            " v",
-            env!("CARGO_PKG_VERSION"),
-            "\n\npub use sea_orm;\n\ninclude!(concat!(env!(\"OUT_DIR\"), \"/app.rs\"));\n"
-        )
-        .to_string();
+            .with_context(|| format!("could not read directory '{}'", ext_dir))?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().map(|ext| ext != "rs").unwrap_or(true) {
+                continue;
+            }
+            match path.file_stem().and_then(|stem| stem.to_str()) {
+                Some("mod") => log.warn(format_args!(
+                    "'{}' is not compiled, as the `ext` module is declared by the generated lib.rs; \
+                    move its items into the extension of a table, such as '{}/product.rs'",
+                    path.display(),
+                    ext_dir
+                )),
+                Some(name) => {
+                    log.debug(format_args!("found extension '{}'", path.display()));
+                    extensions.push(name.to_string());
+                }
+                None => {}
+            }
+        }
+        extensions.sort();
+
+        Ok(extensions)
+    }
+
+    /// Returns the sections of the manifest needed by the test harness and benches,
+    /// with the dev-dependencies of both merged into a single section.
+    fn dev_sections(with_test_harness: bool, with_benches: bool) -> String {
+        let templates = [
+            (with_test_harness, Self::DATABASE_TEST_HARNESS_CARGO_TOML),
+            (with_benches, Self::DATABASE_BENCHES_CARGO_TOML),
+        ];
+
+        let mut dev_dependencies = BTreeSet::new();
+        let mut other_sections = String::new();
+        for (_, template) in templates.iter().filter(|(enabled, _)| *enabled) {
+            let mut in_dev_dependencies = false;
+            for line in template.lines().filter(|line| !line.trim().is_empty()) {
+                if line.starts_with('[') {
+                    in_dev_dependencies = line == "[dev-dependencies]";
+                    if !in_dev_dependencies {
+                        writeln!(other_sections, "\n{}", line).unwrap();
+                    }
+                } else if in_dev_dependencies {
+                    dev_dependencies.insert(line);
+                } else {
+                    writeln!(other_sections, "{}", line).unwrap();
+                }
+            }
+        }
 
-        fs::write(Self::DATABASE_LIB_PATH, lib_content)
+        if dev_dependencies.is_empty() {
+            return other_sections;
+        }
+
+        let mut sections = "\n[dev-dependencies]\n".to_string();
+        for dependency in dev_dependencies {
+            writeln!(sections, "{}", dependency).unwrap();
+        }
+        sections.push_str(&other_sections);
+
+        sections
+    }
+
+    /// Returns the paths and contents of the files of the benches.
+    fn bench_files(package: &DatabasePackage) -> [(String, String); 2] {
+        let mut queries = String::new();
+        if package.is_renamed() {
+            writeln!(
+                queries,
+                "extern crate {} as database;\n",
+                package.lib_name()
+            )
+            .unwrap();
+        }
+        queries.push_str(Self::DATABASE_BENCHES_QUERIES);
+
+        [
+            (
+                package.path("benches/common/mod.rs"),
+                format!(
+                    "{}{}",
+                    GENERATED_HEADER,
+                    package.render(Self::DATABASE_BENCHES_COMMON)
+                ),
+            ),
+            (
+                package.path("benches/queries.rs"),
+                format!("{}{}", GENERATED_HEADER, queries),
+            ),
+        ]
+    }
+
+    /// Returns the paths and contents of the files of the test harness.
+    fn test_files(package: &DatabasePackage) -> [(String, String); 2] {
+        let mut smoke = String::new();
+        if package.is_renamed() {
+            writeln!(smoke, "extern crate {} as database;\n", package.lib_name()).unwrap();
+        }
+        smoke.push_str(Self::DATABASE_TESTS_SMOKE);
+
+        [
+            (
+                package.path("tests/common/mod.rs"),
+                format!(
+                    "{}{}",
+                    GENERATED_HEADER,
+                    package.render(Self::DATABASE_TESTS_COMMON)
+                ),
+            ),
+            (
+                package.path("tests/smoke.rs"),
+                format!("{}{}", GENERATED_HEADER, smoke),
+            ),
+        ]
+    }
+}
+
+/// Directory of the target directory the codegen packages generate into, with a directory per database package.
+const CODEGEN_OUT_DIR: &str = "awto-codegen";
+
+/// Files generated by the codegen packages besides the entities, which are inlined into the sources of the packages.
+const CODEGEN_FILES: [&str; 4] = ["app.rs", "app_tests.rs", "app_benches.rs", "docs.md"];
+
+/// Writes `files` into the package directory `dir` with `package_files`, deleting the other files of the directory
+/// except the paths in `keep`.
+async fn write_package_files(
+    dir: &str,
+    files: Vec<(String, String)>,
+    keep: &[&str],
+    package_files: &mut PackageFiles,
+    log: &mut PackageLog,
+) -> Result<()> {
+    if Path::new(dir).is_dir() {
+        let manifest_path = package_files.manifest_path();
+        let mut kept: Vec<&str> = files.iter().map(|(path, _)| path.as_str()).collect();
+        kept.extend(keep);
+        kept.push(&manifest_path);
+        clean_dir(dir, &kept)
             .await
-            .with_context(|| format!("could not write file '{}'", Self::DATABASE_LIB_PATH))?;
+            .with_context(|| format!("could not clean directory '{}'", dir))?;
+    }
+
+    for (path, content) in files {
+        if let Some(parent) = Path::new(&path).parent() {
+            fs::create_dir_all(parent)
+                .await
+                .with_context(|| format!("could not create directory '{}'", parent.display()))?;
+        }
+        package_files.write(&path, content, log).await?;
+    }
+
+    Ok(())
+}
+
+/// Splits the `Cargo.toml` file of `package` into the one of the package generated into its sources,
+/// which has no build dependencies, and the one of its codegen package, depending on them instead.
+fn codegen_manifests(cargo_toml: &str, package: &DatabasePackage) -> Result<(String, String)> {
+    let mut doc: Document = cargo_toml
+        .parse()
+        .context("invalid database package template")?;
+    let build_dependencies = doc
+        .as_table_mut()
+        .remove("build-dependencies")
+        .ok_or_else(|| anyhow!("the database package template has no build dependencies"))?;
+
+    let mut codegen: Document = format!(
+        "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2018\"\npublish = false\n",
+        package.codegen_name()
+    )
+    .parse()
+    .context("invalid codegen package name")?;
+    codegen["dependencies"] = build_dependencies;
+
+    Ok((doc.to_string(), codegen.to_string()))
+}
+
+/// Returns `source` with the files of `generated` it includes from `OUT_DIR` inlined, such as `app.rs`,
+/// and the markdown files it documents itself with turned into inner doc comments.
+fn inline_generated(source: &str, generated: &BTreeMap<String, String>) -> String {
+    let mut source = source.to_string();
+    for (name, contents) in generated {
+        let include = format!("include!(concat!(env!(\"OUT_DIR\"), \"/{}\"));\n", name);
+        source = source.replace(&include, contents);
+
+        let doc_include = format!(
+            "#![doc = include_str!(concat!(env!(\"OUT_DIR\"), \"/{}\"))]\n",
+            name
+        );
+        if source.contains(&doc_include) {
+            let doc: String = contents
+                .lines()
+                .map(|line| {
+                    if line.is_empty() {
+                        "//!\n".to_string()
+                    } else {
+                        format!("//! {}\n", line)
+                    }
+                })
+                .collect();
+            source = source.replace(&doc_include, &doc);
+        }
+    }
+
+    source
+}
+
+/// Configuration of the generated database packages, in the `[database]` section of `awto.toml`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    /// Name of the generated database package, defaulting to `<app>-database` for a workspace root
+    /// package named `<app>`, or `database` for a virtual workspace.
+    pub package_name: Option<String>,
+    /// Storage of the values of localized fields in locales other than their default locale.
+    pub localization: Localization,
+    /// File holding the url of the database, such as a mounted secret, read when `DATABASE_URL` is not set.
+    ///
+    /// When the schema declares databases, it holds the url of the `main` database.
+    pub url_file: Option<String>,
+    /// Files holding the urls of named databases, read when their variable such as `ANALYTICS_DATABASE_URL`
+    /// is not set, in the `[database.url_files]` section.
+    pub url_files: BTreeMap<String, String>,
+    /// Storage of the columns of `HashMap<String, String>` fields.
+    pub map_storage: MapStorage,
+    /// Recording of the history of `#[awto(temporal)]` tables.
+    pub temporal: Temporal,
+    /// Timeouts and retries of the connections of build scripts, in the `[database.connect]` section.
+    pub connect: ConnectConfig,
+    /// Paths of the derive macros added to the entity `Model` struct of every table, such as `acme_telemetry::Instrumented`.
+    pub extra_derives: Vec<String>,
+    /// Dependencies added to the generated database packages, such as the crates of `extra_derives`,
+    /// in the `[database.extra_dependencies]` section.
+    pub extra_dependencies: BTreeMap<String, toml::Value>,
+    /// Visibility of the generated entities and of the `sea_orm` re-export, in the `[database.visibility]` section.
+    pub visibility: VisibilityConfig,
+    /// Metadata of the packages published with `awto package database`, in the `[database.publish]` section.
+    pub publish: PublishConfig,
+    /// Where the code of the packages is generated, by their build scripts or into their sources.
+    pub codegen: Codegen,
+    /// Major version of sea-orm the packages are generated for, defaulting to the newest one awto supports.
+    pub sea_orm: SeaOrmCompat,
+}
+
+/// Metadata of the published database packages, set in the `[database.publish]` section.
+///
+/// Keys left unset are omitted from the `Cargo.toml` of the published packages.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PublishConfig {
+    /// Description of the packages, defaulting to one naming the database.
+    pub description: Option<String>,
+    /// SPDX license expression of the packages, such as `MIT OR Apache-2.0`.
+    pub license: Option<String>,
+    /// Url of the repository of the app.
+    pub repository: Option<String>,
+    /// Url of the homepage of the app.
+    pub homepage: Option<String>,
+    /// Authors of the packages.
+    pub authors: Vec<String>,
+}
+
+/// Visibility of the items of the generated database packages, set in the `[database.visibility]` section.
+///
+/// Tables with a `#[awto(visibility(...))]` attribute override `entities` and `active_models`.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VisibilityConfig {
+    /// Visibility of the `Entity`, `Model`, `Column`, `PrimaryKey` and `Relation` of every table.
+    pub entities: Visibility,
+    /// Visibility of the `ActiveModel` of every table.
+    pub active_models: Visibility,
+    /// Whether the package re-exports `sea_orm`.
+    pub sea_orm_reexport: bool,
+}
+
+impl Default for VisibilityConfig {
+    fn default() -> Self {
+        VisibilityConfig {
+            entities: Visibility::Public,
+            active_models: Visibility::Public,
+            sea_orm_reexport: true,
+        }
+    }
+}
+
+/// Visibility of generated items, set with `"pub"` or `"pub(crate)"`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+pub enum Visibility {
+    /// Visible to the crates depending on the package.
+    #[default]
+    #[serde(rename = "pub")]
+    Public,
+    /// Only visible within the package, such as to its `src/ext` modules.
+    #[serde(rename = "pub(crate)")]
+    Crate,
+}
+
+impl Visibility {
+    /// Parses the visibility of a `#[awto(visibility(...))]` attribute.
+    pub(crate) fn parse(s: &str) -> Option<Visibility> {
+        match s {
+            "pub" => Some(Visibility::Public),
+            "pub(crate)" => Some(Visibility::Crate),
+            _ => None,
+        }
+    }
+
+    fn variant(self) -> &'static str {
+        match self {
+            Visibility::Public => "Visibility::Public",
+            Visibility::Crate => "Visibility::Crate",
+        }
+    }
+}
+
+/// Environment variables read by the build scripts of generated database packages to connect to the database.
+const CONNECT_TIMEOUT_ENV: &str = "AWTO_CONNECT_TIMEOUT_MS";
+const CONNECT_RETRIES_ENV: &str = "AWTO_CONNECT_RETRIES";
+const CONNECT_BACKOFF_ENV: &str = "AWTO_CONNECT_BACKOFF_MS";
+const STATEMENT_TIMEOUT_ENV: &str = "AWTO_STATEMENT_TIMEOUT_MS";
+const LOCK_TIMEOUT_ENV: &str = "AWTO_LOCK_TIMEOUT_MS";
+
+/// Timeouts and retries of the connections to the database, set in the `[database.connect]` section
+/// or with flags of `awto compile database`.
+///
+/// Unset keys keep the defaults of the build scripts: 10 second attempts, 5 retries after a 0.5 second backoff,
+/// no statement timeout, and waiting 60 seconds for another build syncing the same database.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConnectConfig {
+    /// Seconds each connection attempt may take.
+    pub timeout: Option<f64>,
+    /// Number of attempts made after a failed first attempt.
+    pub retries: Option<u32>,
+    /// Seconds waited before the first retry, doubled for every other retry.
+    pub backoff: Option<f64>,
+    /// Seconds statements may run, set as the `statement_timeout` of every session, or 0 for no limit.
+    pub statement_timeout: Option<f64>,
+    /// Seconds to wait for another build syncing the tables of the same database to finish.
+    pub lock_timeout: Option<f64>,
+}
+
+impl ConnectConfig {
+    /// Returns the configuration with the keys set in `overrides` replaced.
+    pub(super) fn merge(&self, overrides: &ConnectConfig) -> ConnectConfig {
+        ConnectConfig {
+            timeout: overrides.timeout.or(self.timeout),
+            retries: overrides.retries.or(self.retries),
+            backoff: overrides.backoff.or(self.backoff),
+            statement_timeout: overrides.statement_timeout.or(self.statement_timeout),
+            lock_timeout: overrides.lock_timeout.or(self.lock_timeout),
+        }
+    }
+
+    /// Returns the environment variables of the keys which are set, holding their durations in milliseconds.
+    pub(super) fn env(&self) -> Result<Vec<(&'static str, String)>> {
+        fn millis(key: &str, secs: f64) -> Result<String> {
+            if !secs.is_finite() || secs < 0.0 {
+                return Err(anyhow::anyhow!(
+                    "`database.connect.{}` must be a positive number of seconds, got {}",
+                    key,
+                    secs
+                ));
+            }
+            Ok(((secs * 1000.0).round() as u64).to_string())
+        }
+
+        let mut env = Vec::new();
+        if let Some(timeout) = self.timeout {
+            env.push((CONNECT_TIMEOUT_ENV, millis("timeout", timeout)?));
+        }
+        if let Some(retries) = self.retries {
+            env.push((CONNECT_RETRIES_ENV, retries.to_string()));
+        }
+        if let Some(backoff) = self.backoff {
+            env.push((CONNECT_BACKOFF_ENV, millis("backoff", backoff)?));
+        }
+        if let Some(statement_timeout) = self.statement_timeout {
+            env.push((
+                STATEMENT_TIMEOUT_ENV,
+                millis("statement_timeout", statement_timeout)?,
+            ));
+        }
+        if let Some(lock_timeout) = self.lock_timeout {
+            env.push((LOCK_TIMEOUT_ENV, millis("lock_timeout", lock_timeout)?));
+        }
+
+        Ok(env)
+    }
+}
+
+/// Storage of the values of localized fields, set with `localization = "..."` in the `[database]` section.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Localization {
+    /// A nullable `<field>_<locale>` column per locale.
+    #[default]
+    Columns,
+    /// Rows of a `<table>_translations` table.
+    Table,
+}
+
+/// Storage of string maps, set with `map_storage = "..."` in the `[database]` section.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MapStorage {
+    /// A `jsonb` object, like maps of other values.
+    #[default]
+    Jsonb,
+    /// A map of the `hstore` extension, which the generated entities cannot decode.
+    Hstore,
+}
+
+impl MapStorage {
+    /// Returns the matching storage of awto-compile, which stores the columns of the models.
+    pub(crate) fn compiled(self) -> awto_compile::database::MapStorage {
+        match self {
+            MapStorage::Jsonb => awto_compile::database::MapStorage::Jsonb,
+            MapStorage::Hstore => awto_compile::database::MapStorage::Hstore,
+        }
+    }
+}
+
+/// Recording of the history of temporal tables, set with `temporal = "..."` in the `[database]` section.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Temporal {
+    /// Postgres triggers copying the previous version of every updated or deleted row.
+    #[default]
+    Trigger,
+    /// The generated update and delete queries, which miss changes made outside of them.
+    Portable,
+}
+
+/// Where the code of the database packages is generated, set with `codegen = "..."` in the `[database]` section.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Codegen {
+    /// Into `OUT_DIR` by the build script of each package, which connects to the database whenever it reruns.
+    #[default]
+    Buildscript,
+    /// Into the sources of each package by the cli, running a codegen package in place of the build script,
+    /// so the packages build without a database and their code can be committed and reviewed.
+    Source,
+}
+
+impl DatabaseConfig {
+    /// Returns the name of the database package, given the package of the root `Cargo.toml` file.
+    pub(super) fn package_name(&self, root_package: Option<&CargoPackage>) -> String {
+        match (&self.package_name, root_package) {
+            (Some(package_name), _) => package_name.clone(),
+            (None, Some(root_package)) => format!("{}-database", root_package.name),
+            (None, None) => DatabasePackage::DEFAULT_PACKAGE_NAME.to_string(),
+        }
+    }
+
+    const EXTRA_DERIVES_MARKER: &'static str = "const EXTRA_DERIVES: &[&str] = &[];";
+    const ENTITY_VISIBILITY_MARKER: &'static str =
+        "const ENTITY_VISIBILITY: Visibility = Visibility::Public;";
+    const ACTIVE_MODEL_VISIBILITY_MARKER: &'static str =
+        "const ACTIVE_MODEL_VISIBILITY: Visibility = Visibility::Public;";
+
+    /// Checks that `extra_derives` are paths of derive macros from the dependencies of the database package,
+    /// and that `extra_dependencies` do not replace its own dependencies.
+    pub(crate) fn validate(&self) -> Result<()> {
+        let template: toml::Value = toml::from_str(Database::DATABASE_CARGO_TOML)
+            .context("invalid database package template")?;
+        let dependencies = template
+            .get("dependencies")
+            .and_then(toml::Value::as_table)
+            .map(|dependencies| dependencies.keys().cloned().collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        for (name, value) in &self.extra_dependencies {
+            if name.is_empty()
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                return Err(anyhow!(
+                    "`database.extra_dependencies` has an invalid package name `{}`",
+                    name
+                ));
+            }
+            if dependencies.contains(name) {
+                return Err(anyhow!(
+                    "`database.extra_dependencies.{}` is already a dependency of the database package",
+                    name
+                ));
+            }
+            if !value.is_str() && !value.is_table() {
+                return Err(anyhow!(
+                    "`database.extra_dependencies.{}` must be a version or a table, such as `{{ version = \"0.3\" }}`",
+                    name
+                ));
+            }
+        }
+
+        for (i, derive) in self.extra_derives.iter().enumerate() {
+            let path = syn::parse_str::<syn::Path>(derive)
+                .ok()
+                .filter(|path| path.segments.iter().all(|segment| segment.arguments.is_empty()))
+                .ok_or_else(|| {
+                    anyhow!(
+                        "`database.extra_derives[{}]` must be the path of a derive macro, such as `acme_telemetry::Instrumented`, got `{}`",
+                        i,
+                        derive
+                    )
+                })?;
+            // Derives of the prelude, such as `Hash`, are single identifiers
+            if path.segments.len() < 2 {
+                continue;
+            }
+            let krate = path.segments[0].ident.to_string();
+            let declared = ["std", "core", "crate"].contains(&krate.as_str())
+                || dependencies
+                    .iter()
+                    .chain(self.extra_dependencies.keys())
+                    .any(|name| name.replace('-', "_") == krate);
+            if !declared {
+                return Err(anyhow!(
+                    "`database.extra_derives[{}]` uses crate `{}`, which must be added to [database.extra_dependencies]",
+                    i,
+                    krate
+                ));
+            }
+        }
 
         Ok(())
     }
+
+    /// Renders the build script of a database package with the `extra_derives` and the visibility of its entities.
+    fn render_build(&self, build: &str) -> String {
+        let mut build = build.to_string();
+        if !self.extra_derives.is_empty() {
+            let derives: Vec<String> = self
+                .extra_derives
+                .iter()
+                .map(|derive| format!("{:?}", derive))
+                .collect();
+            build = build.replacen(
+                Self::EXTRA_DERIVES_MARKER,
+                &format!("const EXTRA_DERIVES: &[&str] = &[{}];", derives.join(", ")),
+                1,
+            );
+        }
+        if self.visibility.entities != Visibility::Public {
+            build = build.replacen(
+                Self::ENTITY_VISIBILITY_MARKER,
+                &format!(
+                    "const ENTITY_VISIBILITY: Visibility = {};",
+                    self.visibility.entities.variant()
+                ),
+                1,
+            );
+        }
+        if self.visibility.active_models != Visibility::Public {
+            build = build.replacen(
+                Self::ACTIVE_MODEL_VISIBILITY_MARKER,
+                &format!(
+                    "const ACTIVE_MODEL_VISIBILITY: Visibility = {};",
+                    self.visibility.active_models.variant()
+                ),
+                1,
+            );
+        }
+
+        build
+    }
+
+    /// Checks that the items of `package` used by the generated package `consumer` are public:
+    /// its `sea_orm` re-export, the entities of `entities` and the active models of `active_models`.
+    pub(super) fn check_visible<'a>(
+        &self,
+        consumer: &str,
+        package: &DatabasePackage,
+        entities: impl IntoIterator<Item = &'a SchemaModel>,
+        active_models: impl IntoIterator<Item = &'a SchemaModel>,
+    ) -> Result<(), Error> {
+        let hidden = |item: String, setting: String| Error::HiddenDatabaseItem {
+            consumer: consumer.to_string(),
+            package: package.name(),
+            item,
+            setting,
+        };
+
+        if !self.visibility.sea_orm_reexport {
+            return Err(hidden(
+                "the `sea_orm` re-export".to_string(),
+                "`sea_orm_reexport = true` in [database.visibility]".to_string(),
+            ));
+        }
+        for model in entities {
+            if model.entity_visibility.unwrap_or(self.visibility.entities) == Visibility::Crate {
+                let setting = match model.entity_visibility {
+                    Some(_) => format!(
+                        "`#[awto(visibility(entity = \"pub\"))]` on `{}`",
+                        model.name
+                    ),
+                    None => "`entities = \"pub\"` in [database.visibility]".to_string(),
+                };
+                return Err(hidden(format!("the entity of `{}`", model.name), setting));
+            }
+        }
+        for model in active_models {
+            if model
+                .active_model_visibility
+                .unwrap_or(self.visibility.active_models)
+                == Visibility::Crate
+            {
+                let setting = match model.active_model_visibility {
+                    Some(_) => format!(
+                        "`#[awto(visibility(active_model = \"pub\"))]` on `{}`",
+                        model.name
+                    ),
+                    None => "`active_models = \"pub\"` in [database.visibility]".to_string(),
+                };
+                return Err(hidden(
+                    format!("the active model of `{}`", model.name),
+                    setting,
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns a `[dependencies.<name>]` section of the `Cargo.toml` file of database packages for each of the `extra_dependencies`.
+    fn dependency_sections(&self) -> Result<String> {
+        let mut sections = String::new();
+        for (name, value) in &self.extra_dependencies {
+            let table = match value {
+                toml::Value::String(version) => {
+                    let mut table = toml::value::Table::new();
+                    table.insert("version".to_string(), toml::Value::String(version.clone()));
+                    table
+                }
+                toml::Value::Table(table) => table.clone(),
+                _ => continue,
+            };
+            write!(
+                sections,
+                "\n[dependencies.{}]\n{}",
+                name,
+                toml::to_string(&table)
+                    .with_context(|| format!("invalid `database.extra_dependencies.{}`", name))?
+            )
+            .unwrap();
+        }
+
+        Ok(sections)
+    }
+
+    /// Loads the database configuration from `awto.toml`, along with the database package name
+    /// derived from the root package of `workspace`.
+    pub(crate) async fn load(workspace: &WorkspaceContext) -> Result<(DatabaseConfig, String)> {
+        let config = Config::load(CONFIG_PATH).await?;
+
+        let package_name = config.database.package_name(workspace.root_package());
+        Ok((config.database, package_name))
+    }
+}
+
+/// A generated database package.
+///
+/// Schemas without any `#[awto(database = "...")]` attribute compile into a single package named
+/// after the configured package name, otherwise a `<package name>-<database>` package is compiled for each database.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct DatabasePackage {
+    pub(super) package_name: String,
+    pub(super) database: Option<String>,
+    pub(super) localization: Localization,
+    pub(super) map_storage: MapStorage,
+    pub(super) temporal: Temporal,
+    pub(super) codegen: Codegen,
+    pub(super) sea_orm: SeaOrmCompat,
+}
+
+impl DatabasePackage {
+    /// Name of the database package when neither configured nor derived from the root package.
+    const DEFAULT_PACKAGE_NAME: &'static str = "database";
+    /// Name of the database of models without a `#[awto(database = "...")]` attribute.
+    pub(super) const DEFAULT_DATABASE: &'static str = "main";
+    const DATABASE_MARKER: &'static str = "const DATABASE: Option<&str> = None;";
+    /// Line of the templates replaced by the [`STORAGE`](Self::STORAGE) constants.
+    const STORAGE_MARKER: &'static str = "// STORAGE\n";
+    /// Constants of the storage of localized, map and temporal fields, shared by the templates reading the schema.
+    const STORAGE: &'static str = include_str!("../templates/storage.rs.template");
+    const LOCALIZATION_MARKER: &'static str =
+        "const LOCALIZATION: Localization = Localization::Columns;";
+    const MAP_STORAGE_MARKER: &'static str = "const MAP_STORAGE: MapStorage = MapStorage::Jsonb;";
+    const TEMPORAL_MARKER: &'static str = "const TEMPORAL: Temporal = Temporal::Trigger;";
+
+    pub(crate) fn from_models(
+        models: &[SchemaModel],
+        package_name: &str,
+        config: &DatabaseConfig,
+    ) -> Vec<DatabasePackage> {
+        let tables: Vec<_> = models
+            .iter()
+            .filter(|model| model.is_database_table)
+            .collect();
+        if tables.iter().all(|model| model.database.is_none()) {
+            return vec![DatabasePackage {
+                package_name: package_name.to_string(),
+                database: None,
+                localization: config.localization,
+                map_storage: config.map_storage,
+                temporal: config.temporal,
+                codegen: config.codegen,
+                sea_orm: config.sea_orm,
+            }];
+        }
+
+        tables
+            .into_iter()
+            .map(|model| {
+                model
+                    .database
+                    .clone()
+                    .unwrap_or_else(|| Self::DEFAULT_DATABASE.to_string())
+            })
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(|database| DatabasePackage {
+                package_name: package_name.to_string(),
+                database: Some(database),
+                localization: config.localization,
+                map_storage: config.map_storage,
+                temporal: config.temporal,
+                codegen: config.codegen,
+                sea_orm: config.sea_orm,
+            })
+            .collect()
+    }
+
+    pub(crate) fn database_name(&self) -> &str {
+        self.database.as_deref().unwrap_or(Self::DEFAULT_DATABASE)
+    }
+
+    /// Returns the database table models of `models` compiled into this package.
+    pub(crate) fn tables<'a>(
+        &'a self,
+        models: &'a [SchemaModel],
+    ) -> impl Iterator<Item = &'a SchemaModel> {
+        models.iter().filter(move |model| {
+            model.is_database_table
+                && (self.database.is_none()
+                    || model.database.as_deref().unwrap_or(Self::DEFAULT_DATABASE)
+                        == self.database_name())
+        })
+    }
+
+    /// Returns the file of `config` holding the url of the database, from `url_files` for a named database,
+    /// or `url_file` for the only database of the schema and the `main` database.
+    pub(crate) fn url_file<'a>(&self, config: &'a DatabaseConfig) -> Option<&'a str> {
+        match &self.database {
+            None => config.url_file.as_deref(),
+            Some(database) => config
+                .url_files
+                .get(database)
+                .map(String::as_str)
+                .or_else(|| {
+                    config
+                        .url_file
+                        .as_deref()
+                        .filter(|_| database == Self::DEFAULT_DATABASE)
+                }),
+        }
+    }
+
+    /// Returns the environment variable holding the url of the database, read by the build script.
+    pub(crate) fn url_var(&self) -> String {
+        match &self.database {
+            Some(database) => format!("{}_DATABASE_URL", database.to_uppercase()),
+            None => "DATABASE_URL".to_string(),
+        }
+    }
+
+    pub(crate) fn name(&self) -> String {
+        match &self.database {
+            Some(database) => format!("{}-{}", self.package_name, database),
+            None => self.package_name.clone(),
+        }
+    }
+
+    /// Returns whether this package is named differently from the `database` package of the templates.
+    pub(super) fn is_renamed(&self) -> bool {
+        self.name() != Self::DEFAULT_PACKAGE_NAME
+    }
+
+    fn lib_name(&self) -> String {
+        self.name().replace('-', "_")
+    }
+
+    pub(crate) fn dir(&self) -> String {
+        format!("./awto/{}", self.name())
+    }
+
+    fn path(&self, path: &str) -> String {
+        format!("{}/{}", self.dir(), path)
+    }
+
+    /// Returns whether the code of this package is generated into its sources rather than by its build script.
+    pub(crate) fn is_generated_into_sources(&self) -> bool {
+        self.codegen == Codegen::Source
+    }
+
+    /// Returns the name of the package generating the code of this package into its sources, see [`Codegen::Source`].
+    pub(crate) fn codegen_name(&self) -> String {
+        format!("{}-codegen", self.name())
+    }
+
+    fn codegen_dir(&self) -> String {
+        format!("./awto/{}", self.codegen_name())
+    }
+
+    /// Renders a template of the database package for this package's name, database, localization, map storage
+    /// and recording of history.
+    pub(super) fn render(&self, template: &str) -> String {
+        let mut rendered = template
+            .replacen(Self::STORAGE_MARKER, Self::STORAGE, 1)
+            .replacen(
+                "name = \"database\"",
+                &format!("name = \"{}\"", self.name()),
+                1,
+            );
+        if let Some(database) = &self.database {
+            rendered = rendered.replace(
+                Self::DATABASE_MARKER,
+                &format!("const DATABASE: Option<&str> = Some({:?});", database),
+            );
+        }
+        if self.localization == Localization::Table {
+            rendered = rendered.replace(
+                Self::LOCALIZATION_MARKER,
+                "const LOCALIZATION: Localization = Localization::Table;",
+            );
+        }
+        if self.map_storage == MapStorage::Hstore {
+            rendered = rendered.replace(
+                Self::MAP_STORAGE_MARKER,
+                "const MAP_STORAGE: MapStorage = MapStorage::Hstore;",
+            );
+        }
+        if self.temporal == Temporal::Portable {
+            rendered = rendered.replace(
+                Self::TEMPORAL_MARKER,
+                "const TEMPORAL: Temporal = Temporal::Portable;",
+            );
+        }
+        // Templates depend on the newest version of sea-orm
+        if self.sea_orm != SeaOrmCompat::LATEST {
+            rendered = rendered
+                .replace(SeaOrmCompat::LATEST.dependency(), self.sea_orm.dependency())
+                .replace(
+                    SeaOrmCompat::LATEST.build_dependency(),
+                    self.sea_orm.build_dependency(),
+                );
+        }
+
+        rendered
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::ErrorCode;
+
+    fn model(name: &str, database: Option<&str>, is_database_table: bool) -> SchemaModel {
+        SchemaModel {
+            name: name.to_string(),
+            database: database.map(str::to_string),
+            is_database_table,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn packages_without_databases() {
+        let packages = DatabasePackage::from_models(
+            &[
+                model("Product", None, true),
+                model("Event", Some("analytics"), false),
+            ],
+            "database",
+            &DatabaseConfig::default(),
+        );
+
+        assert_eq!(
+            packages,
+            [DatabasePackage {
+                package_name: "database".to_string(),
+                database: None,
+                localization: Localization::Columns,
+                map_storage: MapStorage::Jsonb,
+                temporal: Temporal::Trigger,
+                codegen: Codegen::Buildscript,
+                sea_orm: SeaOrmCompat::LATEST,
+            }]
+        );
+        assert_eq!(packages[0].dir(), "./awto/database");
+        assert!(!packages[0].is_renamed());
+    }
+
+    #[test]
+    fn packages_with_databases() {
+        let packages = DatabasePackage::from_models(
+            &[
+                model("Product", None, true),
+                model("Order", Some("main"), true),
+                model("Event", Some("analytics"), true),
+            ],
+            "database",
+            &DatabaseConfig::default(),
+        );
+
+        let names: Vec<_> = packages.iter().map(DatabasePackage::name).collect();
+        assert_eq!(names, ["database-analytics", "database-main"]);
+        assert!(packages[0]
+            .render(Database::DATABASE_BUILD)
+            .contains(r#"const DATABASE: Option<&str> = Some("analytics");"#));
+        assert!(packages[0]
+            .render(Database::DATABASE_CARGO_TOML)
+            .contains(r#"name = "database-analytics""#));
+    }
+
+    #[test]
+    fn url_files() {
+        let config: Config = toml::from_str(
+            "[database]\nurl_file = \"/run/secrets/db_url\"\n\n\
+            [database.url_files]\nanalytics = \"/run/secrets/analytics_url\"",
+        )
+        .unwrap();
+
+        let packages = DatabasePackage::from_models(
+            &[model("Product", None, true)],
+            "database",
+            &config.database,
+        );
+        assert_eq!(
+            packages[0].url_file(&config.database),
+            Some("/run/secrets/db_url")
+        );
+
+        let packages = DatabasePackage::from_models(
+            &[
+                model("Product", None, true),
+                model("Event", Some("analytics"), true),
+                model("Invoice", Some("billing"), true),
+            ],
+            "database",
+            &config.database,
+        );
+        let url_files: Vec<_> = packages
+            .iter()
+            .map(|package| (package.database_name(), package.url_file(&config.database)))
+            .collect();
+        assert_eq!(
+            url_files,
+            [
+                ("analytics", Some("/run/secrets/analytics_url")),
+                ("billing", None),
+                ("main", Some("/run/secrets/db_url")),
+            ]
+        );
+    }
+
+    #[test]
+    fn localization() {
+        let config: Config = toml::from_str("[database]\nlocalization = \"table\"").unwrap();
+        assert_eq!(config.database.localization, Localization::Table);
+        assert_eq!(
+            Config::default().database.localization,
+            Localization::Columns
+        );
+        assert!(toml::from_str::<Config>("[database]\nlocalization = \"rows\"").is_err());
+
+        let packages = DatabasePackage::from_models(
+            &[model("Product", None, true)],
+            "database",
+            &config.database,
+        );
+        assert!(packages[0]
+            .render(Database::DATABASE_BUILD)
+            .contains("const LOCALIZATION: Localization = Localization::Table;"));
+        assert!(Database::test_files(&packages[0])[0]
+            .1
+            .contains("const LOCALIZATION: Localization = Localization::Table;"));
+    }
+
+    #[test]
+    fn map_storage() {
+        let config: Config = toml::from_str("[database]\nmap_storage = \"hstore\"").unwrap();
+        assert_eq!(config.database.map_storage, MapStorage::Hstore);
+        assert_eq!(Config::default().database.map_storage, MapStorage::Jsonb);
+        assert!(toml::from_str::<Config>("[database]\nmap_storage = \"json\"").is_err());
+
+        let packages = DatabasePackage::from_models(
+            &[model("Product", None, true)],
+            "database",
+            &config.database,
+        );
+        assert!(packages[0]
+            .render(Database::DATABASE_BUILD)
+            .contains("const MAP_STORAGE: MapStorage = MapStorage::Hstore;"));
+        assert!(Database::bench_files(&packages[0])[0]
+            .1
+            .contains("const MAP_STORAGE: MapStorage = MapStorage::Hstore;"));
+        assert!(DatabasePackage::from_models(
+            &[model("Product", None, true)],
+            "database",
+            &DatabaseConfig::default()
+        )[0]
+        .render(Database::DATABASE_BUILD)
+        .contains("const MAP_STORAGE: MapStorage = MapStorage::Jsonb;"));
+    }
+
+    #[test]
+    fn temporal() {
+        let config: Config = toml::from_str("[database]\ntemporal = \"portable\"").unwrap();
+        assert_eq!(config.database.temporal, Temporal::Portable);
+        assert_eq!(Config::default().database.temporal, Temporal::Trigger);
+        assert!(toml::from_str::<Config>("[database]\ntemporal = \"audit\"").is_err());
+
+        let packages = DatabasePackage::from_models(
+            &[model("Product", None, true)],
+            "database",
+            &config.database,
+        );
+        assert!(packages[0]
+            .render(Database::DATABASE_BUILD)
+            .contains("const TEMPORAL: Temporal = Temporal::Portable;"));
+        assert!(Database::test_files(&packages[0])[0]
+            .1
+            .contains("const TEMPORAL: Temporal = Temporal::Portable;"));
+        assert!(DatabasePackage::from_models(
+            &[model("Product", None, true)],
+            "database",
+            &DatabaseConfig::default()
+        )[0]
+        .render(Database::DATABASE_BUILD)
+        .contains("const TEMPORAL: Temporal = Temporal::Trigger;"));
+    }
+
+    #[test]
+    fn storage_constants() {
+        let package =
+            DatabasePackage::from_models(&[], "database", &DatabaseConfig::default()).remove(0);
+
+        for template in [
+            Database::DATABASE_BUILD,
+            Database::DATABASE_TESTS_COMMON,
+            Database::DATABASE_BENCHES_COMMON,
+        ] {
+            assert!(template.contains(DatabasePackage::STORAGE_MARKER));
+            let rendered = package.render(template);
+            assert!(rendered.contains(DatabasePackage::STORAGE));
+            assert!(!rendered.contains(DatabasePackage::STORAGE_MARKER));
+        }
+    }
+
+    #[test]
+    fn extra_derives() {
+        let config: Config = toml::from_str(
+            r#"
+            [database]
+            extra_derives = ["acme_telemetry::Instrumented", "Hash", "serde::Serialize"]
+
+            [database.extra_dependencies]
+            acme-telemetry = { version = "0.3", features = ["entities"] }
+            uuid-ext = "1.2"
+            "#,
+        )
+        .unwrap();
+        let config = config.database;
+        config.validate().unwrap();
+
+        assert_eq!(
+            config.render_build(Database::DATABASE_BUILD),
+            Database::DATABASE_BUILD.replace(
+                "const EXTRA_DERIVES: &[&str] = &[];",
+                "const EXTRA_DERIVES: &[&str] = &[\"acme_telemetry::Instrumented\", \"Hash\", \"serde::Serialize\"];"
+            )
+        );
+        assert_eq!(
+            DatabaseConfig::default().render_build(Database::DATABASE_BUILD),
+            Database::DATABASE_BUILD
+        );
+
+        let sections = config.dependency_sections().unwrap();
+        assert_eq!(
+            sections,
+            "\n[dependencies.acme-telemetry]\nfeatures = [\"entities\"]\nversion = \"0.3\"\n\n[dependencies.uuid-ext]\nversion = \"1.2\"\n"
+        );
+        let manifest: toml::Value =
+            toml::from_str(&format!("{}{}", Database::DATABASE_CARGO_TOML, sections)).unwrap();
+        assert_eq!(
+            manifest["dependencies"]["acme-telemetry"]["version"].as_str(),
+            Some("0.3")
+        );
+        assert!(manifest["dependencies"].get("sea-orm").is_some());
+    }
+
+    #[test]
+    fn invalid_extra_derives() {
+        let error = |toml: &str| {
+            toml::from_str::<Config>(toml)
+                .unwrap()
+                .database
+                .validate()
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert_eq!(
+            error("[database]\nextra_derives = [\"Hash\", \"acme_telemetry::Instrumented\"]"),
+            "`database.extra_derives[1]` uses crate `acme_telemetry`, which must be added to [database.extra_dependencies]"
+        );
+        assert_eq!(
+            error("[database]\nextra_derives = [\"#[derive(Hash)]\"]"),
+            "`database.extra_derives[0]` must be the path of a derive macro, such as `acme_telemetry::Instrumented`, got `#[derive(Hash)]`"
+        );
+        assert_eq!(
+            error("[database.extra_dependencies]\nserde = \"1.0\""),
+            "`database.extra_dependencies.serde` is already a dependency of the database package"
+        );
+        assert!(error("[database.extra_dependencies]\nacme = 3")
+            .starts_with("`database.extra_dependencies.acme` must be a version or a table"));
+    }
+
+    #[test]
+    fn visibility() {
+        let config: Config = toml::from_str(
+            r#"
+            [database.visibility]
+            active_models = "pub(crate)"
+            sea_orm_reexport = false
+            "#,
+        )
+        .unwrap();
+        let config = config.database;
+        assert_eq!(
+            config.visibility,
+            VisibilityConfig {
+                entities: Visibility::Public,
+                active_models: Visibility::Crate,
+                sea_orm_reexport: false,
+            }
+        );
+        assert_eq!(
+            DatabaseConfig::default().visibility,
+            VisibilityConfig::default()
+        );
+        assert!(
+            toml::from_str::<Config>("[database.visibility]\nentities = \"pub(super)\"").is_err()
+        );
+
+        let build = config.render_build(Database::DATABASE_BUILD);
+        assert!(build.contains("const ENTITY_VISIBILITY: Visibility = Visibility::Public;"));
+        assert!(build.contains("const ACTIVE_MODEL_VISIBILITY: Visibility = Visibility::Crate;"));
+    }
+
+    #[test]
+    fn check_visible() {
+        let package =
+            DatabasePackage::from_models(&[], "database", &DatabaseConfig::default()).remove(0);
+        let product = model("Product", None, true);
+        let event = SchemaModel {
+            entity_visibility: Some(Visibility::Crate),
+            ..model("Event", None, true)
+        };
+        let error =
+            |config: &DatabaseConfig, entities: &[&SchemaModel], active_models: &[&SchemaModel]| {
+                config
+                    .check_visible(
+                        "package 'rest'",
+                        &package,
+                        entities.iter().copied(),
+                        active_models.iter().copied(),
+                    )
+                    .unwrap_err()
+                    .to_string()
+            };
+
+        let mut config = DatabaseConfig::default();
+        config
+            .check_visible("package 'rest'", &package, [&product], [&product])
+            .unwrap();
+        assert_eq!(
+            error(&config, &[&product, &event], &[]),
+            "package 'rest' uses the entity of `Event` of package 'database', which is not public, set `#[awto(visibility(entity = \"pub\"))]` on `Event`"
+        );
+
+        config.visibility.active_models = Visibility::Crate;
+        config
+            .check_visible("package 'rest'", &package, [&product], [])
+            .unwrap();
+        assert_eq!(
+            error(&config, &[&product], &[&product]),
+            "package 'rest' uses the active model of `Product` of package 'database', which is not public, set `active_models = \"pub\"` in [database.visibility]"
+        );
+
+        config.visibility.sea_orm_reexport = false;
+        let error = config
+            .check_visible("package 'rest'", &package, [], [])
+            .unwrap_err();
+        assert_eq!(error.code(), ErrorCode::HiddenDatabaseItem);
+        assert_eq!(
+            error.to_string(),
+            "package 'rest' uses the `sea_orm` re-export of package 'database', which is not public, set `sea_orm_reexport = true` in [database.visibility]"
+        );
+    }
+
+    #[test]
+    fn dev_sections() {
+        assert_eq!(Database::dev_sections(false, false), "");
+        assert_eq!(
+            Database::dev_sections(true, false),
+            Database::DATABASE_TEST_HARNESS_CARGO_TOML
+        );
+
+        let sections = Database::dev_sections(true, true);
+        assert_eq!(sections.matches("[dev-dependencies]").count(), 1);
+        assert_eq!(sections.matches("tokio =").count(), 1);
+        assert!(sections.contains("criterion = "));
+        assert!(sections.contains("testcontainers = "));
+        assert!(sections.ends_with("\n[[bench]]\nname = \"queries\"\nharness = false\n"));
+        let manifest: toml::Value = toml::from_str(&sections).unwrap();
+        assert!(manifest["dev-dependencies"].get("once_cell").is_some());
+
+        let sections = Database::dev_sections(false, true);
+        assert!(!sections.contains("testcontainers"));
+        assert!(sections.contains("[[bench]]"));
+
+        let package = DatabasePackage {
+            package_name: "database".to_string(),
+            database: Some("analytics".to_string()),
+            localization: Localization::Columns,
+            map_storage: MapStorage::Jsonb,
+            temporal: Temporal::Trigger,
+            codegen: Codegen::Buildscript,
+            sea_orm: SeaOrmCompat::LATEST,
+        };
+        let files = Database::bench_files(&package);
+        assert!(files[0]
+            .1
+            .contains(r#"const DATABASE: Option<&str> = Some("analytics");"#));
+        assert!(files[1]
+            .1
+            .contains("extern crate database_analytics as database;"));
+    }
+
+    #[test]
+    fn package_names() {
+        let root_package = CargoPackage {
+            name: "acme".to_string(),
+        };
+        let config = DatabaseConfig::default();
+        assert_eq!(config.package_name(None), "database");
+        assert_eq!(config.package_name(Some(&root_package)), "acme-database");
+
+        let config = DatabaseConfig {
+            package_name: Some("acme_db".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(config.package_name(Some(&root_package)), "acme_db");
+
+        let packages = DatabasePackage::from_models(
+            &[
+                model("Product", None, true),
+                model("Event", Some("analytics"), true),
+            ],
+            "acme_db",
+            &config,
+        );
+        let names: Vec<_> = packages.iter().map(DatabasePackage::name).collect();
+        assert_eq!(names, ["acme_db-analytics", "acme_db-main"]);
+        assert!(packages[0]
+            .render(Database::DATABASE_CARGO_TOML)
+            .contains(r#"name = "acme_db-analytics""#));
+
+        let package = DatabasePackage {
+            package_name: "acme_db".to_string(),
+            database: None,
+            localization: Localization::Columns,
+            map_storage: MapStorage::Jsonb,
+            temporal: Temporal::Trigger,
+            codegen: Codegen::Buildscript,
+            sea_orm: SeaOrmCompat::LATEST,
+        };
+        assert!(package.is_renamed());
+        assert!(package
+            .render(Database::DATABASE_CARGO_TOML)
+            .contains(r#"name = "acme_db""#));
+        assert!(Database::test_files(&package)[1]
+            .1
+            .contains("extern crate acme_db as database;"));
+    }
+
+    #[test]
+    fn connect() {
+        let config: DatabaseConfig = toml::from_str(
+            r#"
+            [connect]
+            timeout = 2.5
+            retries = 10
+            statement_timeout = 30
+            lock_timeout = 120
+            "#,
+        )
+        .unwrap();
+        let overrides = ConnectConfig {
+            retries: Some(0),
+            backoff: Some(0.1),
+            ..ConnectConfig::default()
+        };
+
+        assert_eq!(
+            config.connect.merge(&overrides).env().unwrap(),
+            [
+                (CONNECT_TIMEOUT_ENV, "2500".to_string()),
+                (CONNECT_RETRIES_ENV, "0".to_string()),
+                (CONNECT_BACKOFF_ENV, "100".to_string()),
+                (STATEMENT_TIMEOUT_ENV, "30000".to_string()),
+                (LOCK_TIMEOUT_ENV, "120000".to_string()),
+            ]
+        );
+        assert!(ConnectConfig::default().env().unwrap().is_empty());
+
+        let negative = ConnectConfig {
+            timeout: Some(-1.0),
+            ..ConnectConfig::default()
+        };
+        assert_eq!(
+            negative.env().unwrap_err().to_string(),
+            "`database.connect.timeout` must be a positive number of seconds, got -1"
+        );
+        assert!(toml::from_str::<DatabaseConfig>("[connect]\ntimeut = 1").is_err());
+    }
+
+    #[test]
+    fn codegen() {
+        let config: Config = toml::from_str("[database]\ncodegen = \"source\"").unwrap();
+        assert_eq!(config.database.codegen, Codegen::Source);
+        assert_eq!(Config::default().database.codegen, Codegen::Buildscript);
+        assert!(toml::from_str::<Config>("[database]\ncodegen = \"macro\"").is_err());
+
+        let packages = DatabasePackage::from_models(
+            &[model("Product", None, true)],
+            "acme-database",
+            &config.database,
+        );
+        assert!(packages[0].is_generated_into_sources());
+        assert_eq!(packages[0].codegen_dir(), "./awto/acme-database-codegen");
+
+        let cargo_toml = packages[0].render(Database::DATABASE_CARGO_TOML);
+        let (cargo_toml, codegen_cargo_toml) =
+            codegen_manifests(&cargo_toml, &packages[0]).unwrap();
+        let manifest: toml::Value = toml::from_str(&cargo_toml).unwrap();
+        assert!(manifest.get("build-dependencies").is_none());
+        assert!(manifest["dependencies"].get("sea-orm").is_some());
+        let manifest: toml::Value = toml::from_str(&codegen_cargo_toml).unwrap();
+        assert_eq!(
+            manifest["package"]["name"].as_str(),
+            Some("acme-database-codegen")
+        );
+        assert_eq!(manifest["package"]["publish"].as_bool(), Some(false));
+        assert!(manifest["dependencies"].get("sea-orm-build").is_some());
+        assert!(manifest["dependencies"].get("awto-compile").is_some());
+
+        let generated: BTreeMap<String, String> = [
+            ("app.rs", "mod entities {\n    pub mod product;\n}\n"),
+            ("docs.md", "Database entities.\n\n# Models\n"),
+        ]
+        .iter()
+        .map(|(name, contents)| (name.to_string(), contents.to_string()))
+        .collect();
+        let lib = format!(
+            "{}#![doc = include_str!(concat!(env!(\"OUT_DIR\"), \"/docs.md\"))]\n#![allow(deprecated)]\n\ninclude!(concat!(env!(\"OUT_DIR\"), \"/app.rs\"));\n",
+            GENERATED_HEADER
+        );
+        assert_eq!(
+            inline_generated(&lib, &generated),
+            format!(
+                "{}//! Database entities.\n//!\n//! # Models\n#![allow(deprecated)]\n\nmod entities {{\n    pub mod product;\n}}\n",
+                GENERATED_HEADER
+            )
+        );
+        let smoke = inline_generated(Database::DATABASE_TESTS_SMOKE, &generated);
+        assert_eq!(smoke, Database::DATABASE_TESTS_SMOKE);
+    }
+
+    #[test]
+    fn sea_orm() {
+        let config: Config = toml::from_str("[database]\nsea_orm = \"0.2\"").unwrap();
+        assert_eq!(config.database.sea_orm, SeaOrmCompat::V0_2);
+        assert_eq!(Config::default().database.sea_orm, SeaOrmCompat::LATEST);
+        assert!(toml::from_str::<Config>("[database]\nsea_orm = \"1\"").is_err());
+
+        // The templates are rendered once per supported version
+        for compat in SeaOrmCompat::ALL {
+            let config = DatabaseConfig {
+                sea_orm: compat,
+                ..DatabaseConfig::default()
+            };
+            let package = DatabasePackage::from_models(&[], "database", &config).remove(0);
+            let cargo_toml = package.render(Database::DATABASE_CARGO_TOML);
+            assert!(cargo_toml.contains(compat.dependency()), "{}", compat);
+            assert!(cargo_toml.contains(compat.build_dependency()), "{}", compat);
+            let manifest: toml::Value = toml::from_str(&cargo_toml).unwrap();
+            assert!(manifest["dependencies"].get("sea-orm").is_some());
+            assert!(manifest["build-dependencies"]
+                .get("sea-orm-build")
+                .is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn extension_modules() {
+        let dir = std::env::temp_dir().join(format!("awto-cli-extensions-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir).await;
+        let ext_dir = dir.join("src/ext");
+        let ext_dir = ext_dir.to_str().unwrap();
+        let mut log = PackageLog::default();
+        assert!(Database::extension_modules(ext_dir, &mut log)
+            .await
+            .unwrap()
+            .is_empty());
+
+        fs::create_dir_all(ext_dir).await.unwrap();
+        for file in ["product.rs", "employee.rs", "mod.rs", "notes.md"] {
+            fs::write(Path::new(ext_dir).join(file), "").await.unwrap();
+        }
+        assert_eq!(
+            Database::extension_modules(ext_dir, &mut log)
+                .await
+                .unwrap(),
+            ["employee", "product"]
+        );
+        let warnings: Vec<_> = log
+            .records
+            .iter()
+            .filter(|(level, _)| *level == log::Level::Warn)
+            .collect();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].1.contains("mod.rs' is not compiled"));
+
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
 }
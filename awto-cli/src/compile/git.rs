@@ -0,0 +1,269 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use log::{info, warn};
+use tokio::fs;
+
+use super::GeneratedFiles;
+
+/// Directory of the generated packages relative to the root of the workspace, as given to git.
+const GENERATED_DIR: &str = "awto/";
+
+/// Rule appended to the `.gitignore` of the workspace when the generated packages are ignored.
+const IGNORE_RULE: &str = "/awto/";
+
+/// A rule of an ignore file matching [`GENERATED_DIR`], as printed by `git check-ignore -v`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct IgnoreRule {
+    /// Ignore file declaring the rule, relative to the root of the repository.
+    source: String,
+    line: usize,
+    pattern: String,
+}
+
+impl IgnoreRule {
+    /// Parses a line printed by `git check-ignore -v`, `<source>:<line>:<pattern>\t<path>`.
+    fn parse(line: &str) -> Option<IgnoreRule> {
+        let (rule, _) = line.split_once('\t')?;
+        let (source, rule) = rule.split_once(':')?;
+        let (line, pattern) = rule.split_once(':')?;
+
+        Some(IgnoreRule {
+            source: source.to_string(),
+            line: line.parse().ok()?,
+            pattern: pattern.to_string(),
+        })
+    }
+
+    /// Returns whether the rule names the generated packages alone, so it can be removed without affecting other paths.
+    fn is_generated_dir(&self) -> bool {
+        matches!(self.pattern.as_str(), "awto" | "awto/" | "/awto" | "/awto/")
+    }
+}
+
+/// Checks the generated packages in `root` are committed or ignored as `policy` says, adopting the policy where
+/// the `.gitignore` of `root` disagrees with it and warning about what cannot be fixed without the user.
+///
+/// Ignored packages are added to the `.gitignore` of `root`, which is created when missing.
+/// Committed packages have the rule ignoring them removed from it, when it names them alone.
+/// The rules of every ignore file of the repository apply, including nested and global ones.
+/// Nothing is checked when `root` is not inside a git repository, or git is not installed.
+pub(crate) async fn check_generated_files(root: &Path, policy: GeneratedFiles) -> Result<()> {
+    let prefix = match git(root, &["rev-parse", "--show-prefix"]).await {
+        Ok(prefix) => prefix.trim().to_string(),
+        Err(_) => return Ok(()),
+    };
+    let gitignore = root.join(".gitignore");
+    let gitignore_source = format!("{}.gitignore", prefix);
+
+    match (policy, ignoring_rule(root).await?) {
+        (GeneratedFiles::Ignored, None) => {
+            let contents = read_gitignore(&gitignore).await?;
+            write_gitignore(&gitignore, &with_ignore_rule(&contents)).await?;
+            info!("added '{}' to .gitignore, as generated_files = \"ignored\" in awto.toml", IGNORE_RULE);
+        }
+        (GeneratedFiles::Committed, Some(rule)) if rule.source == gitignore_source && rule.is_generated_dir() => {
+            let contents = read_gitignore(&gitignore).await?;
+            write_gitignore(&gitignore, &without_line(&contents, rule.line)).await?;
+            info!(
+                "removed '{}' from .gitignore, as generated_files = \"committed\" in awto.toml",
+                rule.pattern
+            );
+        }
+        (GeneratedFiles::Committed, Some(rule)) => warn!(
+            "./awto is ignored by '{}' in {}:{}, although generated_files = \"committed\" in awto.toml; \
+            remove the rule or set generated_files = \"ignored\"",
+            rule.pattern, rule.source, rule.line
+        ),
+        _ => {}
+    }
+
+    // A rule of another ignore file may still apply after the `.gitignore` is patched
+    if policy == GeneratedFiles::Ignored && ignoring_rule(root).await?.is_none() {
+        warn!(
+            "./awto is not ignored, although generated_files = \"ignored\" in awto.toml; \
+            remove the rule unignoring it, which `git check-ignore --verbose --no-index --non-matching awto/` prints"
+        );
+    }
+
+    let (files, problem, fix) = match policy {
+        GeneratedFiles::Ignored => (
+            git(root, &["ls-files", "--", GENERATED_DIR]).await?,
+            "are committed, although generated_files = \"ignored\"",
+            "untrack them with `git rm -r --cached awto`",
+        ),
+        GeneratedFiles::Committed => (
+            git(
+                root,
+                &[
+                    "ls-files",
+                    "--others",
+                    "--exclude-standard",
+                    "--",
+                    GENERATED_DIR,
+                ],
+            )
+            .await?,
+            "are not committed, although generated_files = \"committed\"",
+            "commit them with `git add awto`",
+        ),
+    };
+    let count = files.lines().count();
+    if count > 0 {
+        warn!(
+            "{} files in ./awto {} in awto.toml; {}",
+            count, problem, fix
+        );
+    }
+
+    Ok(())
+}
+
+/// Returns the rule of the ignore files of the repository ignoring [`GENERATED_DIR`], if it is ignored.
+async fn ignoring_rule(root: &Path) -> Result<Option<IgnoreRule>> {
+    let output = tokio::process::Command::new("git")
+        .current_dir(root)
+        .args(["check-ignore", "--verbose", "--no-index", GENERATED_DIR])
+        .output()
+        .await
+        .context("could not run git")?;
+    match output.status.code() {
+        Some(0) => Ok(IgnoreRule::parse(
+            String::from_utf8_lossy(&output.stdout).trim_end(),
+        )),
+        Some(1) => Ok(None),
+        _ => Err(anyhow!(
+            "{}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+        .context("git check-ignore failed for './awto'"),
+    }
+}
+
+/// Runs git in `root` with `args`, returning what it printed.
+async fn git(root: &Path, args: &[&str]) -> Result<String> {
+    let output = tokio::process::Command::new("git")
+        .current_dir(root)
+        .args(args)
+        .output()
+        .await
+        .context("could not run git")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "{}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+        .context(format!("git {} failed", args[0]));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+async fn read_gitignore(path: &Path) -> Result<String> {
+    if !path.is_file() {
+        return Ok(String::new());
+    }
+
+    fs::read_to_string(path)
+        .await
+        .with_context(|| format!("could not read file '{}'", path.display()))
+}
+
+async fn write_gitignore(path: &Path, contents: &str) -> Result<()> {
+    fs::write(path, contents)
+        .await
+        .with_context(|| format!("could not write file '{}'", path.display()))
+}
+
+/// Returns `contents` of a `.gitignore` with [`IGNORE_RULE`] appended on a line of its own.
+fn with_ignore_rule(contents: &str) -> String {
+    let mut contents = contents.to_string();
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    contents.push_str(IGNORE_RULE);
+    contents.push('\n');
+
+    contents
+}
+
+/// Returns `contents` without its 1-based `line`.
+fn without_line(contents: &str, line: usize) -> String {
+    contents
+        .split_inclusive('\n')
+        .enumerate()
+        .filter(|(i, _)| i + 1 != line)
+        .map(|(_, line)| line)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ignore_rules() {
+        let rule = IgnoreRule::parse("sub/.gitignore:3:/awto/\tawto/").unwrap();
+        assert_eq!(
+            rule,
+            IgnoreRule {
+                source: "sub/.gitignore".to_string(),
+                line: 3,
+                pattern: "/awto/".to_string(),
+            }
+        );
+        assert!(rule.is_generated_dir());
+        assert!(!IgnoreRule::parse(".gitignore:1:*/\tawto/")
+            .unwrap()
+            .is_generated_dir());
+        assert_eq!(IgnoreRule::parse("awto/"), None);
+
+        assert_eq!(with_ignore_rule(""), "/awto/\n");
+        assert_eq!(with_ignore_rule("/target"), "/target\n/awto/\n");
+        assert_eq!(with_ignore_rule("/target\n"), "/target\n/awto/\n");
+        assert_eq!(without_line("/target\nawto\n.env", 2), "/target\n.env");
+    }
+
+    #[tokio::test]
+    async fn generated_files_policy() {
+        let dir = std::env::temp_dir().join(format!("awto-cli-git-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("awto/database")).unwrap();
+        std::fs::write(dir.join("awto/database/build.rs"), "fn main() {}\n").unwrap();
+
+        // Projects outside of git are not checked
+        check_generated_files(&dir, GeneratedFiles::Ignored)
+            .await
+            .unwrap();
+        assert!(!dir.join(".gitignore").exists());
+
+        git(&dir, &["init", "--quiet"]).await.unwrap();
+        std::fs::write(dir.join(".gitignore"), "/target").unwrap();
+        check_generated_files(&dir, GeneratedFiles::Ignored)
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join(".gitignore")).unwrap(),
+            "/target\n/awto/\n"
+        );
+        assert!(ignoring_rule(&dir).await.unwrap().is_some());
+        check_generated_files(&dir, GeneratedFiles::Ignored)
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join(".gitignore")).unwrap(),
+            "/target\n/awto/\n"
+        );
+
+        check_generated_files(&dir, GeneratedFiles::Committed)
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join(".gitignore")).unwrap(),
+            "/target\n"
+        );
+        assert_eq!(ignoring_rule(&dir).await.unwrap(), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
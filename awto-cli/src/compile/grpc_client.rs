@@ -0,0 +1,152 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use clap::Parser;
+use tokio::fs;
+
+use crate::{cancel::Cancellation, error::Error, util::WorkspaceContext, Runnable};
+
+use super::{
+    check_service_package, clean_dir, compile_packages, ensure_package_dir_managed,
+    package_manifest, prepare_awto_dir, Generate, PackageFiles, PackageLog, GENERATED_HEADER,
+};
+
+/// Compiles gRPC client package calling the protobuf services
+#[derive(Clone, Parser)]
+pub struct GrpcClient {
+    /// Keeps generated Rust code unformatted
+    #[clap(long)]
+    pub no_format: bool,
+    /// Interleaves generated code with comments telling which model and generator produced it
+    #[clap(long)]
+    pub annotate_sources: bool,
+    /// Overwrites package directories which were not generated by awto
+    #[clap(long)]
+    pub force: bool,
+    /// Fails after compiling when any warning was reported
+    #[clap(long)]
+    pub deny_warnings: bool,
+    /// Prints more information
+    #[clap(short, long)]
+    pub verbose: bool,
+    /// Number of warnings reported by the run
+    #[clap(skip)]
+    pub warnings: usize,
+}
+
+#[async_trait]
+impl Runnable for GrpcClient {
+    async fn run(&mut self, cancellation: &Cancellation) -> Result<()> {
+        let commands: Vec<Box<dyn Generate>> = vec![Box::new(self.clone())];
+        compile_packages(
+            commands,
+            cancellation,
+            self.no_format,
+            self.annotate_sources,
+            self.deny_warnings,
+            &mut self.warnings,
+        )
+        .await
+    }
+
+    fn is_verbose(&self) -> bool {
+        self.verbose
+    }
+
+    fn warnings(&self) -> usize {
+        self.warnings
+    }
+}
+
+#[async_trait]
+impl Generate for GrpcClient {
+    /// Generates the gRPC client package, returning its name.
+    async fn generate(
+        &self,
+        workspace: &WorkspaceContext,
+        log: &mut PackageLog,
+    ) -> Result<Vec<String>> {
+        check_service_package(workspace)?;
+
+        // The client shares the protobuf messages and conversions of the protobuf package
+        if workspace.package_by_name("protobuf").is_none() {
+            return Err(Error::MissingPackage {
+                package: "protobuf".to_string(),
+                command: "awto compile protobuf".to_string(),
+            }
+            .into());
+        }
+
+        prepare_awto_dir(self.force).await?;
+        ensure_package_dir_managed(Self::GRPC_CLIENT_DIR, self.force).await?;
+
+        let cargo_toml = package_manifest(workspace, Self::GRPC_CLIENT_CARGO_TOML).await?;
+        Self::prepare_grpc_client_dir(&cargo_toml, log)
+            .await
+            .map_err(|source| Error::GeneratePackage {
+                package: "grpc-client".to_string(),
+                source,
+            })?;
+
+        Ok(vec!["grpc-client".to_string()])
+    }
+}
+
+impl GrpcClient {
+    const GRPC_CLIENT_DIR: &'static str = "./awto/grpc-client";
+    const GRPC_CLIENT_SRC_DIR: &'static str = "./awto/grpc-client/src";
+    const GRPC_CLIENT_CARGO_PATH: &'static str = "./awto/grpc-client/Cargo.toml";
+    const GRPC_CLIENT_CARGO_TOML: &'static str =
+        include_str!("../templates/grpc-client/Cargo.toml.template");
+    const GRPC_CLIENT_BUILD_PATH: &'static str = "./awto/grpc-client/build.rs";
+    const GRPC_CLIENT_BUILD_BYTES: &'static [u8] =
+        include_bytes!("../templates/grpc-client/build.rs.template");
+    const GRPC_CLIENT_LIB_PATH: &'static str = "./awto/grpc-client/src/lib.rs";
+
+    async fn prepare_grpc_client_dir(cargo_toml: &str, log: &mut PackageLog) -> Result<()> {
+        let mut lib_content = GENERATED_HEADER.to_string();
+
+        writeln!(
+            lib_content,
+            r#"//! Clients of the gRPC services of the protobuf package, generated by awto.
+
+#![allow(deprecated)]
+
+include!(concat!(env!("OUT_DIR"), "/app.rs"));"#
+        )
+        .unwrap();
+
+        let files: [(&str, &[u8]); 3] = [
+            (Self::GRPC_CLIENT_CARGO_PATH, cargo_toml.as_bytes()),
+            (Self::GRPC_CLIENT_BUILD_PATH, Self::GRPC_CLIENT_BUILD_BYTES),
+            (Self::GRPC_CLIENT_LIB_PATH, lib_content.as_bytes()),
+        ];
+
+        let mut package_files = PackageFiles::new(Self::GRPC_CLIENT_DIR, "grpc-client").await;
+        if Path::new(Self::GRPC_CLIENT_DIR).is_dir() {
+            let manifest_path = package_files.manifest_path();
+            let mut keep: Vec<&str> = files.iter().map(|(path, _)| *path).collect();
+            keep.push(&manifest_path);
+            clean_dir(Self::GRPC_CLIENT_DIR, &keep)
+                .await
+                .with_context(|| {
+                    format!("could not clean directory '{}'", Self::GRPC_CLIENT_DIR)
+                })?;
+        }
+
+        fs::create_dir_all(Self::GRPC_CLIENT_SRC_DIR)
+            .await
+            .with_context(|| {
+                format!("could not create directory '{}'", Self::GRPC_CLIENT_SRC_DIR)
+            })?;
+
+        for (path, content) in files {
+            package_files.write(path, content, log).await?;
+        }
+        package_files.finish(log).await?;
+
+        Ok(())
+    }
+}
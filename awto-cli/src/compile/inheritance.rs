@@ -0,0 +1,282 @@
+//! Inheritance of the `edition`, `rust-version` and lints of the workspace by generated packages.
+//!
+//! The `Cargo.toml` files of generated packages are rendered from templates, which pin the edition the generated code
+//! is written for. When the root `Cargo.toml` file sets `[workspace.package]` keys or `[workspace.lints]`, generated
+//! packages inherit them like the other members, unless the `[packages]` section of `awto.toml` says otherwise.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use toml_edit::{Document, InlineTable, Item, Value};
+
+use crate::util::CargoWorkspace;
+
+/// Keys of `[package]` inherited from `[workspace.package]`, along with their key in the `[packages]` section.
+const INHERITED_KEYS: [(&str, &str); 2] =
+    [("edition", "edition"), ("rust-version", "rust_version")];
+
+/// The `[packages]` section of `awto.toml`, configuring the `Cargo.toml` files of every generated package.
+///
+/// Keys which are not set are inherited from the workspace when it sets them.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PackagesConfig {
+    /// Edition of the generated packages, instead of the edition of the workspace.
+    pub edition: Option<String>,
+    /// Minimum supported Rust version of the generated packages, instead of the one of the workspace.
+    pub rust_version: Option<String>,
+    /// Whether the generated packages inherit `[workspace.lints]`.
+    pub workspace_lints: bool,
+    /// Lints of the generated packages, such as `rust = { missing_docs = "allow" }`, instead of the ones of the workspace.
+    pub lints: toml::value::Table,
+}
+
+impl Default for PackagesConfig {
+    fn default() -> Self {
+        PackagesConfig {
+            edition: None,
+            rust_version: None,
+            workspace_lints: true,
+            lints: toml::value::Table::new(),
+        }
+    }
+}
+
+impl PackagesConfig {
+    /// Returns the value of the inherited `key` set in the configuration.
+    fn key(&self, key: &str) -> Option<&str> {
+        match key {
+            "edition" => self.edition.as_deref(),
+            "rust_version" => self.rust_version.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the `Cargo.toml` file of a generated package, given the one rendered from its template.
+///
+/// Configured keys are set as is, while keys the configuration leaves unset become `<key>.workspace = true`
+/// when `workspace` sets them. Its lints are inherited by a `[lints]` section with `workspace = true`,
+/// unless lints are configured or `workspace_lints` is turned off.
+pub fn inherit_workspace(
+    cargo_toml: &str,
+    workspace: Option<&CargoWorkspace>,
+    config: &PackagesConfig,
+) -> Result<String> {
+    let mut doc: Document = cargo_toml
+        .parse()
+        .context("could not parse the Cargo.toml file of the package")?;
+    let package = doc["package"]
+        .as_table_mut()
+        .context("the Cargo.toml file of the package has no [package] section")?;
+    for (key, config_key) in INHERITED_KEYS {
+        let (value, suffix) = match config.key(config_key) {
+            Some(value) => (Item::Value(Value::from(value)), " "),
+            None if workspace.is_some_and(|workspace| workspace.package.contains_key(key)) => {
+                (inherited(), "")
+            }
+            None => continue,
+        };
+        package[key] = value;
+        if let Some(decor) = package.key_decor_mut(key) {
+            decor.set_suffix(suffix);
+        }
+    }
+    doc.as_table_mut().remove("lints");
+
+    let lints = if !config.lints.is_empty() {
+        Some(config.lints.clone())
+    } else if config.workspace_lints && workspace.is_some_and(|workspace| workspace.lints.is_some())
+    {
+        Some(
+            vec![("workspace".to_string(), toml::Value::Boolean(true))]
+                .into_iter()
+                .collect(),
+        )
+    } else {
+        None
+    };
+
+    Ok(with_lints(doc.to_string(), lints.as_ref()))
+}
+
+/// Returns the `Cargo.toml` file of a generated package published outside the workspace, replacing the keys
+/// and lints it inherits from `workspace` by their values.
+pub fn resolve_inherited(cargo_toml: &str, workspace: Option<&CargoWorkspace>) -> Result<String> {
+    let mut doc: Document = cargo_toml
+        .parse()
+        .context("could not parse the Cargo.toml file of the package")?;
+    let package = doc["package"]
+        .as_table_mut()
+        .context("the Cargo.toml file of the package has no [package] section")?;
+    for (key, _) in INHERITED_KEYS {
+        if !package.get(key).is_some_and(is_inherited) {
+            continue;
+        }
+        let value = workspace
+            .and_then(|workspace| workspace.package.get(key))
+            .and_then(toml::Value::as_str)
+            .with_context(|| {
+                format!("`{}` is inherited, but the workspace does not set it", key)
+            })?;
+        package[key] = Item::Value(Value::from(value));
+        if let Some(decor) = package.key_decor_mut(key) {
+            decor.set_suffix(" ");
+        }
+    }
+
+    if !doc.as_table().get("lints").is_some_and(is_inherited) {
+        return Ok(doc.to_string());
+    }
+    doc.as_table_mut().remove("lints");
+    let lints = workspace
+        .and_then(|workspace| workspace.lints.as_ref())
+        .context("lints are inherited, but the workspace has no [workspace.lints] section")?;
+
+    Ok(with_lints(doc.to_string(), Some(lints)))
+}
+
+/// Returns `<key>.workspace = true`.
+fn inherited() -> Item {
+    let mut table = InlineTable::new();
+    table.insert("workspace", Value::from(true));
+    table.set_dotted(true);
+    Item::Value(Value::InlineTable(table))
+}
+
+/// Returns whether `item` is `{ workspace = true }`, in any form.
+fn is_inherited(item: &Item) -> bool {
+    item["workspace"].as_bool() == Some(true)
+}
+
+/// Appends a `[lints]` section of `lints` to `cargo_toml`.
+fn with_lints(mut cargo_toml: String, lints: Option<&toml::value::Table>) -> String {
+    if let Some(lints) = lints {
+        let section: toml::value::Table =
+            vec![("lints".to_string(), toml::Value::Table(lints.clone()))]
+                .into_iter()
+                .collect();
+        if !cargo_toml.ends_with("\n\n") {
+            cargo_toml.push('\n');
+        }
+        cargo_toml.push_str(&toml::to_string(&section).expect("lints are valid TOML"));
+    }
+
+    cargo_toml
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::WorkspaceContext;
+
+    const CARGO_TOML: &str = "[package]\nname = \"database\"\nversion = \"0.1.0\"\nedition = \"2018\"\n\n[dependencies]\nlog = \"0.4\"\n";
+
+    /// Root manifest of a workspace enforcing the edition, minimum Rust version and strict lints on its members.
+    const STRICT_WORKSPACE: &str = r#"[workspace]
+members = ["schema"]
+
+[workspace.package]
+edition = "2021"
+rust-version = "1.75"
+
+[workspace.lints.rust]
+unsafe_code = "deny"
+missing_docs = "warn"
+
+[workspace.lints.clippy]
+all = "warn"
+"#;
+
+    async fn strict_workspace() -> WorkspaceContext {
+        let dir = std::env::temp_dir().join(format!("awto-cli-inheritance-{}", std::process::id()));
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+        tokio::fs::create_dir_all(dir.join("schema")).await.unwrap();
+        tokio::fs::write(dir.join("Cargo.toml"), STRICT_WORKSPACE)
+            .await
+            .unwrap();
+        tokio::fs::write(
+            dir.join("schema/Cargo.toml"),
+            "[package]\nname = \"schema\"\n",
+        )
+        .await
+        .unwrap();
+
+        let workspace = WorkspaceContext::load(&dir).await.unwrap();
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+        workspace
+    }
+
+    #[tokio::test]
+    async fn strict_workspace_is_inherited() {
+        let workspace = strict_workspace().await;
+        let manifest = inherit_workspace(
+            CARGO_TOML,
+            workspace.root_workspace(),
+            &PackagesConfig::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            manifest,
+            "[package]\nname = \"database\"\nversion = \"0.1.0\"\nedition.workspace = true\nrust-version.workspace = true\n\n\
+             [dependencies]\nlog = \"0.4\"\n\n[lints]\nworkspace = true\n"
+        );
+        let parsed: toml::Value = toml::from_str(&manifest).unwrap();
+        assert_eq!(
+            parsed["package"]["edition"]["workspace"].as_bool(),
+            Some(true)
+        );
+
+        let published = resolve_inherited(&manifest, workspace.root_workspace()).unwrap();
+        assert!(published.contains("edition = \"2021\"\nrust-version = \"1.75\"\n"));
+        let published: toml::Value = toml::from_str(&published).unwrap();
+        assert_eq!(published["package"]["edition"].as_str(), Some("2021"));
+        assert_eq!(published["package"]["rust-version"].as_str(), Some("1.75"));
+        assert_eq!(
+            published["lints"]["rust"]["unsafe_code"].as_str(),
+            Some("deny")
+        );
+        assert_eq!(published["lints"]["clippy"]["all"].as_str(), Some("warn"));
+    }
+
+    #[tokio::test]
+    async fn configured_packages() {
+        let workspace = strict_workspace().await;
+        let config: PackagesConfig = toml::from_str(
+            r#"
+edition = "2018"
+lints = { rust = { missing_docs = "allow" } }
+"#,
+        )
+        .unwrap();
+
+        let manifest = inherit_workspace(CARGO_TOML, workspace.root_workspace(), &config).unwrap();
+        let parsed: toml::Value = toml::from_str(&manifest).unwrap();
+        assert_eq!(parsed["package"]["edition"].as_str(), Some("2018"));
+        assert_eq!(
+            parsed["package"]["rust-version"]["workspace"].as_bool(),
+            Some(true)
+        );
+        assert_eq!(
+            parsed["lints"]["rust"]["missing_docs"].as_str(),
+            Some("allow")
+        );
+        assert!(parsed["lints"].get("workspace").is_none());
+
+        let config = PackagesConfig {
+            workspace_lints: false,
+            ..PackagesConfig::default()
+        };
+        let manifest = inherit_workspace(CARGO_TOML, workspace.root_workspace(), &config).unwrap();
+        assert!(!manifest.contains("[lints]"));
+    }
+
+    #[test]
+    fn plain_workspace() {
+        let manifest = inherit_workspace(CARGO_TOML, None, &PackagesConfig::default()).unwrap();
+
+        assert_eq!(manifest, CARGO_TOML);
+        assert_eq!(resolve_inherited(&manifest, None).unwrap(), CARGO_TOML);
+        assert!(resolve_inherited("[package]\nedition.workspace = true\n", None).is_err());
+    }
+}
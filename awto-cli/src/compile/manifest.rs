@@ -0,0 +1,490 @@
+//! Generation manifests recording the provenance of generated files.
+//!
+//! The build scripts of generated packages write a `generation-manifest.json` next to the code they generate
+//! into `OUT_DIR`, which is read to tell which generator and models produced the code a cargo error points at.
+//! The cli writes a manifest of the same shape into each package directory, recording the hashes of the files
+//! it wrote so files edited locally are detected before being overwritten, and the last publication of the package.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::fs;
+
+use super::{write_generated_file, PackageLog, GENERATED_HEADER_PREFIX, SCHEMA_LIB_PATH};
+use crate::config::active_profile;
+use crate::schema::schema_fingerprint;
+
+/// Name of the manifest written next to generated files.
+pub(crate) const MANIFEST_FILE: &str = "generation-manifest.json";
+
+/// Option of the files of a package holding the [`schema_fingerprint`] they were generated from.
+pub(crate) const SCHEMA_FINGERPRINT_OPTION: &str = "schema_fingerprint";
+
+/// Start of the text of the comments interleaved into generated code by `--annotate-sources`.
+const ANNOTATION_MARKER: &str = "@awto:";
+
+/// Files generated into a directory, along with what produced them.
+///
+/// Matches the manifests written by awto-compile.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct GenerationManifest {
+    /// Version of the generator which wrote the manifest.
+    pub version: String,
+    /// Whether the files were generated with annotations.
+    #[serde(default)]
+    pub annotate_sources: bool,
+    /// Generated files by their path relative to the manifest.
+    pub files: BTreeMap<String, GeneratedFile>,
+    /// Last publication of the package to a registry with `awto package`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub published: Option<Publication>,
+}
+
+/// Publication of a generated package to a registry, recorded by `awto package --publish`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Publication {
+    /// Name of the registry, such as `acme`.
+    pub registry: String,
+    /// Version the package was published as.
+    pub version: String,
+    /// Version of the `schema` package it depends on, unless the schema was inlined.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema_version: Option<String>,
+    /// Fingerprint of the sources of the package when it was published, see [`package_fingerprint`].
+    pub fingerprint: String,
+}
+
+impl Publication {
+    /// Returns the line following the generated header of the lib of a published package.
+    pub fn header_line(&self) -> String {
+        format!(
+            "// Published to the '{}' registry as v{}\n",
+            self.registry, self.version
+        )
+    }
+}
+
+/// A file of a [`GenerationManifest`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct GeneratedFile {
+    /// Name of the generator producing the file, such as `database`.
+    pub generator: String,
+    /// Names of the models and services the file was produced from.
+    pub models: Vec<String>,
+    pub options: BTreeMap<String, String>,
+    /// Hash of the contents of the file.
+    pub hash: String,
+}
+
+impl GenerationManifest {
+    /// Reads the manifest in `dir`, returning `None` when there is none or it cannot be parsed.
+    pub async fn load(dir: impl AsRef<Path>) -> Option<Self> {
+        let contents = fs::read_to_string(dir.as_ref().join(MANIFEST_FILE))
+            .await
+            .ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+
+    /// Writes the manifest into `dir`.
+    pub async fn save(&self, dir: impl AsRef<Path>) -> Result<()> {
+        let path = dir.as_ref().join(MANIFEST_FILE);
+        let mut contents = serde_json::to_string_pretty(self)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+            .with_context(|| format!("could not serialize '{}'", path.display()))?;
+        contents.push('\n');
+
+        fs::write(&path, contents)
+            .await
+            .with_context(|| format!("could not write file '{}'", path.display()))
+    }
+
+    /// Returns the generator and models of `file`, such as `database v0.1.2 from Product, Category`.
+    pub fn describe(&self, file: &str) -> Option<String> {
+        let file = self.files.get(file)?;
+        let mut description = format!("{} v{}", file.generator, self.version);
+        if !file.models.is_empty() {
+            description.push_str(&format!(" from {}", file.models.join(", ")));
+        }
+
+        Some(description)
+    }
+}
+
+/// Returns the sha256 hash of `contents`.
+pub(crate) fn content_hash(contents: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(contents))
+}
+
+/// Returns `lib` with the header line of `publication` following its generated header,
+/// replacing the line of a previous publication.
+pub(crate) fn with_publication_header(lib: &str, publication: &Publication) -> String {
+    let (header, rest) = match lib.split_once('\n') {
+        Some((header, rest)) if header.starts_with(GENERATED_HEADER_PREFIX) => (header, rest),
+        _ => return lib.to_string(),
+    };
+    let rest = match rest.split_once('\n') {
+        Some((line, rest)) if line.starts_with("// Published to the ") => rest,
+        _ => rest,
+    };
+
+    format!("{}\n{}{}", header, publication.header_line(), rest)
+}
+
+/// Returns the fingerprint of the sources the package in `dir` is built from: the schema lib, the build script,
+/// the lib without its header, the extensions, the entities of packages generated into their sources and the dependencies.
+///
+/// It only changes when the published code would, so regenerating an unchanged package does not count as a change.
+pub(crate) async fn package_fingerprint(dir: &str) -> Result<String> {
+    let read = |path: String| async move {
+        fs::read_to_string(&path)
+            .await
+            .with_context(|| format!("could not read file '{}'", path))
+    };
+    let mut sources = vec![(
+        SCHEMA_LIB_PATH.to_string(),
+        read(SCHEMA_LIB_PATH.to_string()).await?,
+    )];
+    // Packages generated into their sources have no build script
+    let build = format!("{}/build.rs", dir);
+    if Path::new(&build).is_file() {
+        sources.push(("build.rs".to_string(), read(build).await?));
+    }
+
+    let lib = read(format!("{}/src/lib.rs", dir)).await?;
+    let lib: Vec<&str> = lib
+        .lines()
+        .skip_while(|line| line.starts_with("//"))
+        .collect();
+    sources.push(("src/lib.rs".to_string(), lib.join("\n")));
+
+    let cargo_toml: toml::Value = toml::from_str(&read(format!("{}/Cargo.toml", dir)).await?)
+        .with_context(|| format!("could not parse file '{}/Cargo.toml'", dir))?;
+    let dependencies = cargo_toml
+        .get("dependencies")
+        .map(ToString::to_string)
+        .unwrap_or_default();
+    sources.push(("dependencies".to_string(), dependencies));
+
+    for sub_dir in ["src/ext", "src/entities"] {
+        let sub_dir = Path::new(dir).join(sub_dir);
+        if !sub_dir.is_dir() {
+            continue;
+        }
+        let mut entries = fs::read_dir(&sub_dir)
+            .await
+            .with_context(|| format!("could not read directory '{}'", sub_dir.display()))?;
+        let mut paths = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().is_some_and(|extension| extension == "rs") {
+                paths.push(path.display().to_string());
+            }
+        }
+        paths.sort();
+        for path in paths {
+            let source = read(path.clone()).await?;
+            sources.push((path.trim_start_matches(dir).to_string(), source));
+        }
+    }
+
+    let mut hasher = Sha256::new();
+    for (name, source) in sources {
+        hasher.update(name.as_bytes());
+        hasher.update([0]);
+        hasher.update(source.as_bytes());
+        hasher.update([0]);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Files written into a package directory, recorded in the manifest of the directory once written.
+pub(super) struct PackageFiles {
+    dir: String,
+    generator: String,
+    options: BTreeMap<String, String>,
+    previous: GenerationManifest,
+    manifest: GenerationManifest,
+}
+
+impl PackageFiles {
+    /// Starts writing the files of `generator` into the package directory `dir`, reading its previous manifest.
+    ///
+    /// The active profile is recorded as an option of the files, along with the [`schema_fingerprint`]
+    /// when the schema can be read.
+    pub async fn new(dir: &str, generator: &str) -> Self {
+        let mut options = BTreeMap::new();
+        options.insert("profile".to_string(), active_profile());
+        if let Ok(fingerprint) = schema_fingerprint().await {
+            options.insert(SCHEMA_FINGERPRINT_OPTION.to_string(), fingerprint);
+        }
+
+        let previous = GenerationManifest::load(dir).await.unwrap_or_default();
+        PackageFiles {
+            dir: dir.to_string(),
+            generator: generator.to_string(),
+            options,
+            manifest: GenerationManifest {
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                annotate_sources: false,
+                files: BTreeMap::new(),
+                published: previous.published.clone(),
+            },
+            previous,
+        }
+    }
+
+    /// Returns the last publication of the package, which is kept in the manifest.
+    pub fn published(&self) -> Option<&Publication> {
+        self.manifest.published.as_ref()
+    }
+
+    /// Records an option the files are generated with.
+    pub fn option(mut self, name: &str, value: impl ToString) -> Self {
+        self.options.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Returns the path of the manifest, which is kept when cleaning the directory.
+    pub fn manifest_path(&self) -> String {
+        format!("{}/{}", self.dir, MANIFEST_FILE)
+    }
+
+    /// Writes `contents` to the file at `path` inside the directory like [`write_generated_file`].
+    ///
+    /// Warns when the file was edited since the cli last wrote it, as the edits are overwritten.
+    pub async fn write(
+        &mut self,
+        path: &str,
+        contents: impl AsRef<[u8]>,
+        log: &mut PackageLog,
+    ) -> Result<()> {
+        let contents = contents.as_ref();
+        let relative = path
+            .strip_prefix(&self.dir)
+            .unwrap_or(path)
+            .trim_start_matches('/')
+            .to_string();
+
+        if let Some(previous) = self.previous.files.get(&relative) {
+            match fs::read(path).await {
+                Ok(current) if content_hash(&current) != previous.hash => {
+                    log.warn(format_args!(
+                        "overwriting local edits of '{}', move them into an extension to keep them",
+                        path
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        write_generated_file(path, contents, log).await?;
+        self.manifest.files.insert(
+            relative,
+            GeneratedFile {
+                generator: self.generator.clone(),
+                models: Vec::new(),
+                options: self.options.clone(),
+                hash: content_hash(contents),
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Writes the manifest of the written files into the directory.
+    pub async fn finish(self, log: &mut PackageLog) -> Result<()> {
+        let mut contents = serde_json::to_string_pretty(&self.manifest)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+            .with_context(|| format!("could not serialize '{}'", self.manifest_path()))?;
+        contents.push('\n');
+
+        write_generated_file(&self.manifest_path(), contents, log).await
+    }
+}
+
+/// Returns a note telling what generated the code a cargo diagnostic points at in `line`,
+/// such as `  --> /app/awto/target/debug/build/database-0123/out/app.rs:12:5`.
+///
+/// Only code generated into a directory with a manifest is described, along with the annotation
+/// preceding the line when the code was generated with `--annotate-sources`.
+pub(super) async fn generated_source_note(line: &str) -> Option<String> {
+    let location = line.trim_start().strip_prefix("-->")?.trim();
+    let mut parts = location.rsplitn(3, ':');
+    let _column = parts.next()?;
+    let line_number: usize = parts.next()?.parse().ok()?;
+    let path = Path::new(parts.next()?);
+
+    let dir = path.parent()?;
+    let file = path.file_name()?.to_str()?;
+    let manifest = GenerationManifest::load(dir).await?;
+    let mut note = format!("   = note: generated by {}", manifest.describe(file)?);
+
+    if manifest.annotate_sources {
+        let source = fs::read_to_string(path).await.unwrap_or_default();
+        let annotation = source
+            .lines()
+            .take(line_number)
+            .filter_map(|line| {
+                let start = line.find(ANNOTATION_MARKER)?;
+                Some(line[start..].trim_end())
+            })
+            .last();
+        if let Some(annotation) = annotation {
+            note.push_str(&format!("\n   = note: {}", annotation));
+        }
+    }
+
+    Some(note)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn source_notes() {
+        let dir = std::env::temp_dir().join(format!("awto-cli-manifest-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir).await;
+        fs::create_dir_all(&dir).await.unwrap();
+
+        let manifest = r#"{
+  "version": "0.1.2",
+  "annotate_sources": true,
+  "files": {
+    "app.rs": {
+      "generator": "database",
+      "models": ["Product", "Category"],
+      "options": { "annotate_sources": "true", "format": "true" },
+      "hash": "0123"
+    }
+  }
+}
+"#;
+        fs::write(dir.join(MANIFEST_FILE), manifest).await.unwrap();
+        fs::write(
+            dir.join("app.rs"),
+            "// @awto: model=Product generator=database/entity v0.1.2\npub struct Product {\n    pub price: i64,\n}\n",
+        )
+        .await
+        .unwrap();
+
+        let line = format!("  --> {}:3:16", dir.join("app.rs").display());
+        assert_eq!(
+            generated_source_note(&line).await.unwrap(),
+            "   = note: generated by database v0.1.2 from Product, Category\n   = note: @awto: model=Product generator=database/entity v0.1.2"
+        );
+        let line = format!("  --> {}:3:16", dir.join("other.rs").display());
+        assert_eq!(generated_source_note(&line).await, None);
+        assert_eq!(generated_source_note("  --> src/lib.rs:3:16").await, None);
+        assert_eq!(
+            generated_source_note("error[E0308]: mismatched types").await,
+            None
+        );
+
+        fs::remove_dir_all(dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn local_edits() {
+        let dir =
+            std::env::temp_dir().join(format!("awto-cli-package-files-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir).await;
+        fs::create_dir_all(&dir).await.unwrap();
+        let dir = dir.to_str().unwrap().to_string();
+        let lib = format!("{}/lib.rs", dir);
+
+        let mut log = PackageLog::default();
+        let mut files = PackageFiles::new(&dir, "protobuf")
+            .await
+            .option("with_benches", false);
+        files.write(&lib, "pub mod app;\n", &mut log).await.unwrap();
+        files.finish(&mut log).await.unwrap();
+
+        let manifest = GenerationManifest::load(&dir).await.unwrap();
+        assert_eq!(manifest.files["lib.rs"].generator, "protobuf");
+        assert_eq!(manifest.files["lib.rs"].options["with_benches"], "false");
+        assert_eq!(
+            manifest.files["lib.rs"].hash,
+            content_hash(b"pub mod app;\n")
+        );
+
+        let mut log = PackageLog::default();
+        let mut files = PackageFiles::new(&dir, "protobuf").await;
+        files.write(&lib, "pub mod app;\n", &mut log).await.unwrap();
+        assert!(log
+            .records
+            .iter()
+            .all(|(level, _)| *level != log::Level::Warn));
+
+        fs::write(&lib, "pub mod app;\npub mod edited;\n")
+            .await
+            .unwrap();
+        let mut log = PackageLog::default();
+        let mut files = PackageFiles::new(&dir, "protobuf").await;
+        files.write(&lib, "pub mod app;\n", &mut log).await.unwrap();
+        assert!(log
+            .records
+            .iter()
+            .any(|(level, message)| *level == log::Level::Warn && message.contains("local edits")));
+
+        fs::remove_dir_all(dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn publications() {
+        let dir =
+            std::env::temp_dir().join(format!("awto-cli-publications-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir).await;
+        fs::create_dir_all(&dir).await.unwrap();
+
+        let publication = Publication {
+            registry: "acme".to_string(),
+            version: "1.4.0".to_string(),
+            schema_version: None,
+            fingerprint: "0123".to_string(),
+        };
+        let manifest = GenerationManifest {
+            published: Some(publication.clone()),
+            ..Default::default()
+        };
+        manifest.save(&dir).await.unwrap();
+        assert_eq!(GenerationManifest::load(&dir).await.unwrap(), manifest);
+
+        // The publication is kept by later generations
+        let files = PackageFiles::new(dir.to_str().unwrap(), "database").await;
+        assert_eq!(files.published(), Some(&publication));
+
+        let lib = format!(
+            "{} v0.1.2\n\ninclude!(\"app.rs\");\n",
+            GENERATED_HEADER_PREFIX
+        );
+        let published = with_publication_header(&lib, &publication);
+        assert_eq!(
+            published,
+            format!(
+                "{} v0.1.2\n// Published to the 'acme' registry as v1.4.0\n\ninclude!(\"app.rs\");\n",
+                GENERATED_HEADER_PREFIX
+            )
+        );
+        let publication = Publication {
+            version: "1.5.0".to_string(),
+            ..publication
+        };
+        assert_eq!(
+            with_publication_header(&published, &publication),
+            published.replace("v1.4.0", "v1.5.0")
+        );
+        assert_eq!(
+            with_publication_header("pub mod app;\n", &publication),
+            "pub mod app;\n"
+        );
+
+        fs::remove_dir_all(dir).await.unwrap();
+    }
+}
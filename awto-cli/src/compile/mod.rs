@@ -1,18 +1,74 @@
-use std::path::Path;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Instant;
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::{Context, Result};
 use async_trait::async_trait;
-use clap::{Parser, IntoApp};
-use tokio::fs;
+use clap::{IntoApp, Parser};
+use log::{debug, error, info, log, log_enabled, warn, Level};
+use tokio::{
+    fs,
+    io::{AsyncBufReadExt, AsyncReadExt, BufReader},
+};
 
-use crate::Runnable;
+use crate::{
+    cancel::{kill_child, Cancellation},
+    config::{Config, CONFIG_PATH, PROFILE_ENV},
+    error::{Error, ErrorCode},
+    schema::{
+        check_gated_relations, load_schema_models, manifest_json_schema, read_manifest,
+        schema_packages, ApiDocsOverlay, FeaturesConfig, SchemaConfig, SchemaPackage, SchemaSource,
+        MANIFEST_PACKAGE, MANIFEST_PACKAGE_DIR, MANIFEST_SCHEMA_FILE,
+    },
+    util::{remove_package_from_workspace, sync_nested_workspace, WorkspaceContext},
+    Runnable,
+};
 
-use self::database::Database;
+pub use self::admin::AdminDatabase;
+pub(crate) use self::custom::read_ir;
+pub(crate) use self::database::DatabasePackage;
+pub use self::database::{DatabaseConfig, PublishConfig, Visibility};
+pub(crate) use self::database_url::{read_dotenv, resolve_database_url, DOTENV_PATH};
+pub(crate) use self::inheritance::resolve_inherited;
+pub use self::inheritance::PackagesConfig;
+pub(crate) use self::manifest::{
+    content_hash, package_fingerprint, with_publication_header, GenerationManifest, Publication,
+    MANIFEST_FILE, SCHEMA_FINGERPRINT_OPTION,
+};
+pub use self::profiling::{enable_profiling, profile_phase, report_profile};
+pub use self::protobuf::ProtoConfig;
+pub use self::rest::{NamingConfig, RestConfig};
+pub use self::warning::{BuildConfig, GeneratedFiles, WARNING_IDS};
+pub(crate) use crate::schema::SCHEMA_LIB_PATH;
+
+use self::admin::Admin;
+use self::custom::Custom;
+use self::database::{ConnectConfig, Database};
+use self::database_url::DatabaseUrl;
+use self::git::check_generated_files;
+use self::grpc_client::GrpcClient;
+use self::manifest::{generated_source_note, PackageFiles};
+use self::profiling::Phase;
 use self::protobuf::Protobuf;
+use self::rest::{Rest, RestPackage};
+use self::warning::Warning;
 
+mod admin;
+mod custom;
 mod database;
+mod database_url;
+mod git;
+mod grpc_client;
+mod inheritance;
+mod manifest;
+mod profiling;
 mod protobuf;
+mod rest;
+mod warning;
+mod watch;
 
 /// Compiles app to generate packages
 #[derive(Parser)]
@@ -22,67 +78,1331 @@ pub struct Compile {
     pub all: bool,
     #[clap(subcommand)]
     pub subcmd: Option<SubCommand>,
+    /// Keeps generated Rust code unformatted
+    #[clap(long)]
+    pub no_format: bool,
+    /// Interleaves generated code with comments telling which model and generator produced it
+    #[clap(long)]
+    pub annotate_sources: bool,
+    /// Overwrites package directories which were not generated by awto
+    #[clap(long)]
+    pub force: bool,
+    /// Fails after compiling when any warning was reported
+    #[clap(long)]
+    pub deny_warnings: bool,
     /// Prints more information
     #[clap(short, long)]
     pub verbose: bool,
+    /// Number of warnings reported by the run
+    #[clap(skip)]
+    pub warnings: usize,
 }
 
 #[derive(Parser)]
 pub enum SubCommand {
     Database(Database),
     Protobuf(Protobuf),
+    GrpcClient(GrpcClient),
+    Rest(Rest),
+    Admin(Admin),
+    Custom(Custom),
 }
 
 #[async_trait]
 impl Runnable for Compile {
-    async fn run(&mut self) -> Result<()> {
+    async fn run(&mut self, cancellation: &Cancellation) -> Result<()> {
         if !self.all {
-            return Ok(Compile::into_app().print_help()?);
+            let mut help = Vec::new();
+            Compile::into_app().write_help(&mut help)?;
+            out!("{}", String::from_utf8_lossy(&help));
+            return Ok(());
         }
 
-        let mut database = Database {
+        let database = Box::new(Database {
+            only_db: None,
+            database_url: None,
+            watch: false,
+            on_success: None,
+            signal_file: None,
+            with_test_harness: false,
+            with_benches: false,
+            no_format: self.no_format,
+            annotate_sources: self.annotate_sources,
+            force: self.force,
+            check: false,
+            dry_run: false,
+            force_narrowing: false,
+            deny_warnings: self.deny_warnings,
+            connect_timeout: None,
+            connect_retries: None,
+            connect_backoff: None,
+            statement_timeout: None,
+            lock_timeout: None,
             verbose: self.verbose,
-        };
-        database.run().await?;
-
-        let mut protobuf = Protobuf {
+            warnings: 0,
+        });
+        let protobuf = Box::new(Protobuf {
+            no_format: self.no_format,
+            annotate_sources: self.annotate_sources,
+            force: self.force,
+            deny_warnings: self.deny_warnings,
+            with_buf_config: false,
+            check: false,
             verbose: self.verbose,
-        };
-        protobuf.run().await?;
+            warnings: 0,
+        });
+
+        compile_packages(
+            vec![database, protobuf],
+            cancellation,
+            self.no_format,
+            self.annotate_sources,
+            self.deny_warnings,
+            &mut self.warnings,
+        )
+        .await
+    }
+
+    fn warnings(&self) -> usize {
+        self.warnings
+    }
+}
+
+/// A compile command generating packages, which are built together once all of them are generated.
+#[async_trait]
+trait Generate: Send + Sync + 'static {
+    /// Generates the packages of the command in `workspace`, returning their names.
+    ///
+    /// Messages are logged to `log`, as commands may generate their packages concurrently.
+    async fn generate(
+        &self,
+        workspace: &WorkspaceContext,
+        log: &mut PackageLog,
+    ) -> Result<Vec<String>>;
+
+    /// Returns the urls of the databases the build scripts of the packages connect to, by environment variable.
+    ///
+    /// They are resolved before generating the packages, so a malformed url fails before any file is written.
+    async fn database_urls(
+        &self,
+        _workspace: &WorkspaceContext,
+        _log: &mut PackageLog,
+    ) -> Result<Vec<(String, DatabaseUrl)>> {
+        Ok(Vec::new())
+    }
+
+    /// Returns the connection settings of the command overriding the `[database.connect]` section of `awto.toml`.
+    fn connect_overrides(&self) -> ConnectConfig {
+        ConnectConfig::default()
+    }
 
-        Ok(())
+    /// Returns whether the build scripts of the packages narrow columns even when rows of the database do not fit them.
+    fn force_narrowing(&self) -> bool {
+        false
     }
 }
 
-async fn prepare_awto_dir() -> Result<()> {
+/// Messages logged while generating packages, which are printed once the generation is done.
+///
+/// Packages generated concurrently buffer their messages so they do not interleave,
+/// and are flushed in the order the commands were given.
+#[derive(Debug, Default)]
+struct PackageLog {
+    records: Vec<(Level, String)>,
+    /// Warnings printed by the generators run while generating the packages, reported along with those of the build.
+    warnings: Vec<Warning>,
+}
+
+impl PackageLog {
+    fn debug(&mut self, message: impl fmt::Display) {
+        self.records.push((Level::Debug, message.to_string()));
+    }
+
+    fn warn(&mut self, message: impl fmt::Display) {
+        self.records.push((Level::Warn, message.to_string()));
+    }
+
+    fn warning(&mut self, warning: Warning) {
+        if !self.warnings.contains(&warning) {
+            self.warnings.push(warning);
+        }
+    }
+
+    /// Logs the buffered messages in the order they were logged.
+    fn flush(&mut self) {
+        for (level, message) in self.records.drain(..) {
+            log!(level, "{}", message);
+        }
+    }
+}
+
+/// Generates the packages of `commands` concurrently, builds them with a single cargo invocation
+/// and prints their warnings, setting `count` to the number of warnings like [`report_warnings`].
+///
+/// The workspace is loaded once and shared by the commands.
+/// A command or package which fails does not stop the others, whose results are reported
+/// before failing with the first error.
+/// Once `cancellation` is cancelled, the packages being generated are finished so none is left half written,
+/// but they are not built.
+async fn compile_packages(
+    commands: Vec<Box<dyn Generate>>,
+    cancellation: &Cancellation,
+    no_format: bool,
+    annotate_sources: bool,
+    deny_warnings: bool,
+    count: &mut usize,
+) -> Result<()> {
+    compile_named_packages(
+        commands,
+        cancellation,
+        no_format,
+        annotate_sources,
+        deny_warnings,
+        count,
+    )
+    .await
+    .map(drop)
+}
+
+/// Compiles the packages of `commands` like [`compile_packages`], returning the names of the packages built.
+async fn compile_named_packages(
+    commands: Vec<Box<dyn Generate>>,
+    cancellation: &Cancellation,
+    no_format: bool,
+    annotate_sources: bool,
+    deny_warnings: bool,
+    count: &mut usize,
+) -> Result<Vec<String>> {
+    cancellation.check()?;
+    let mut workspace = WorkspaceContext::load(".").await?;
+    let shared = Arc::new(workspace.clone());
+    let connect = commands
+        .iter()
+        .fold(ConnectConfig::default(), |connect, command| {
+            connect.merge(&command.connect_overrides())
+        });
+    let force_narrowing = commands.iter().any(|command| command.force_narrowing());
+    let tasks: Vec<_> = commands
+        .into_iter()
+        .map(|command| {
+            let workspace = Arc::clone(&shared);
+            crate::sink::spawn(async move {
+                let mut log = PackageLog::default();
+                let packages = match command.database_urls(&workspace, &mut log).await {
+                    Ok(urls) => command
+                        .generate(&workspace, &mut log)
+                        .await
+                        .map(|names| (names, urls)),
+                    Err(err) => Err(err),
+                };
+                (log, packages)
+            })
+        })
+        .collect();
+
+    let mut packages = Vec::new();
+    let mut urls = Vec::new();
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+    for task in tasks {
+        let (mut log, result) = task
+            .await
+            .unwrap_or_else(|err| std::panic::resume_unwind(err.into_panic()));
+        log.flush();
+        warnings.append(&mut log.warnings);
+        match result {
+            Ok((names, database_urls)) => {
+                packages.extend(names);
+                urls.extend(database_urls);
+            }
+            Err(err) => errors.push(err),
+        }
+    }
+
+    cancellation.check()?;
+
+    let built = build_awto_pkgs(
+        &mut workspace,
+        &packages,
+        &urls,
+        &connect,
+        cancellation,
+        BuildOptions {
+            no_format,
+            annotate_sources,
+            force_narrowing,
+        },
+        &mut warnings,
+    )
+    .await;
+    match built {
+        Err(err) if Error::code_of(&err) == Some(ErrorCode::Cancelled) => return Err(err),
+        Err(err) => errors.push(err),
+        Ok(()) => {}
+    }
+    let report = report_warnings(warnings, deny_warnings, count).await;
+
+    let mut errors = errors.into_iter();
+    match errors.next() {
+        Some(err) => {
+            for other in errors {
+                error!("{:#}", other);
+            }
+            Err(err)
+        }
+        None => report.map(|()| packages),
+    }
+}
+
+/// Checks the package in `./schema` is a member of `workspace` named `schema`, or that every package of the
+/// `[schema]` section of `awto.toml` is a member of `workspace` when it lists any or reads a manifest,
+/// that the models of the enabled features do not use models of disabled ones, and that the api docs overlay
+/// describes models and fields of the schema.
+pub(crate) async fn check_schema_package(workspace: &WorkspaceContext) -> Result<()> {
+    let config = Config::load(CONFIG_PATH).await?;
+    if !config.schema.package_names().is_empty() {
+        if let Some(name) = config
+            .schema
+            .packages
+            .iter()
+            .find(|name| workspace.package_by_name(name).is_none())
+        {
+            return Err(Error::UnknownSchemaPackage(name.clone()).into());
+        }
+    } else {
+        let cargo_file =
+            workspace
+                .member_manifest(SCHEMA_DIR)
+                .ok_or_else(|| Error::SchemaManifest {
+                    path: format!("{}/Cargo.toml", SCHEMA_DIR),
+                    source: anyhow::anyhow!("package is not a member of the workspace"),
+                })?;
+        if let Some(package) = cargo_file
+            .package
+            .as_ref()
+            .filter(|package| package.name != "schema")
+        {
+            return Err(Error::SchemaPackageName(package.name.clone()).into());
+        }
+    }
+
+    // Generated code cannot refer to the models of disabled features, which are left out
+    let models = load_schema_models().await?;
+    check_gated_relations(&models, &config.features)?;
+
+    if let Some(path) = &config.api_docs.overlay {
+        ApiDocsOverlay::load(&config.api_docs)
+            .await?
+            .check(path, &models)?;
+    }
+
+    Ok(())
+}
+
+/// Checks the package in `./service` is a member of `workspace` named `service`.
+fn check_service_package(workspace: &WorkspaceContext) -> Result<()> {
+    let cargo_file =
+        workspace
+            .member_manifest(SERVICE_DIR)
+            .ok_or_else(|| Error::ServiceManifest {
+                path: format!("{}/Cargo.toml", SERVICE_DIR),
+                source: anyhow::anyhow!("package is not a member of the workspace"),
+            })?;
+    match &cargo_file.package {
+        Some(package) if package.name != "service" => {
+            Err(Error::ServicePackageName(package.name.clone()).into())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Returns the build script of the generated package in `dir`, or the main of its codegen package
+/// when the package is generated into its sources, which is empty for other packages.
+async fn generator_source(dir: &Path) -> String {
+    match fs::read_to_string(dir.join("build.rs")).await {
+        Ok(build) => build,
+        Err(_) => fs::read_to_string(dir.join("src/main.rs"))
+            .await
+            .unwrap_or_default(),
+    }
+}
+
+/// Regenerates and builds the packages previously generated in `./awto`, found by the calls in their build scripts,
+/// setting `count` to the number of warnings like [`compile_packages`].
+///
+/// Database packages keep their test harness and benchmarks.
+pub async fn recompile_packages(
+    no_format: bool,
+    cancellation: &Cancellation,
+    count: &mut usize,
+) -> Result<()> {
+    let mut build_scripts = Vec::new();
+    if Path::new("./awto").is_dir() {
+        let mut entries = fs::read_dir("./awto")
+            .await
+            .context("could not read directory './awto'")?;
+        while let Some(entry) = entries.next_entry().await? {
+            build_scripts.push((entry.path(), generator_source(&entry.path()).await));
+        }
+    }
+    let generated = |marker: &str| -> Vec<&PathBuf> {
+        build_scripts
+            .iter()
+            .filter(|(_, build)| build.contains(marker))
+            .map(|(dir, _)| dir)
+            .collect()
+    };
+
+    let mut commands: Vec<Box<dyn Generate>> = Vec::new();
+    let databases = generated("compile_database(");
+    if !databases.is_empty() {
+        commands.push(Box::new(Database {
+            only_db: None,
+            database_url: None,
+            watch: false,
+            on_success: None,
+            signal_file: None,
+            with_test_harness: databases.iter().any(|dir| dir.join("tests").is_dir()),
+            with_benches: databases.iter().any(|dir| dir.join("benches").is_dir()),
+            no_format,
+            annotate_sources: false,
+            force: false,
+            check: false,
+            dry_run: false,
+            force_narrowing: false,
+            deny_warnings: false,
+            connect_timeout: None,
+            connect_retries: None,
+            connect_backoff: None,
+            statement_timeout: None,
+            lock_timeout: None,
+            verbose: false,
+            warnings: 0,
+        }));
+    }
+    if !generated("compile_protobuf(").is_empty() {
+        commands.push(Box::new(Protobuf {
+            no_format,
+            annotate_sources: false,
+            force: false,
+            deny_warnings: false,
+            with_buf_config: false,
+            check: false,
+            verbose: false,
+            warnings: 0,
+        }));
+    }
+    let transcoding = generated("compile_rest_with_transcoding(");
+    if !generated("compile_rest(").is_empty() || !transcoding.is_empty() {
+        commands.push(Box::new(Rest {
+            no_format,
+            annotate_sources: false,
+            force: false,
+            deny_warnings: false,
+            with_transcoding: !transcoding.is_empty(),
+            verbose: false,
+            warnings: 0,
+        }));
+    }
+    if !generated("compile_grpc_client(").is_empty() {
+        commands.push(Box::new(GrpcClient {
+            no_format,
+            annotate_sources: false,
+            force: false,
+            deny_warnings: false,
+            verbose: false,
+            warnings: 0,
+        }));
+    }
+
+    if !generated("compile_admin(").is_empty() {
+        commands.push(Box::new(Admin {
+            no_format,
+            annotate_sources: false,
+            force: false,
+            deny_warnings: false,
+            verbose: false,
+            warnings: 0,
+        }));
+    }
+
+    if commands.is_empty() {
+        info!("no generated packages to recompile");
+        return Ok(());
+    }
+
+    compile_packages(commands, cancellation, no_format, false, false, count).await
+}
+
+/// Directory of the schema package.
+pub(crate) const SCHEMA_DIR: &str = "./schema";
+
+/// Dependency of the templates of generated packages on the `schema` package.
+const SCHEMA_DEPENDENCY: &str = "schema = { path = \"../../schema\" }";
+
+/// Directory of the service package.
+const SERVICE_DIR: &str = "./service";
+
+/// Header written at the top of every generated Rust file.
+pub(crate) const GENERATED_HEADER: &str = concat!(
+    "// This file is automatically @generated by ",
+    env!("CARGO_PKG_NAME"),
+    " v",
+    env!("CARGO_PKG_VERSION"),
+    "\n\n"
+);
+
+/// Start of [`GENERATED_HEADER`] shared by every version of the cli.
+pub(crate) const GENERATED_HEADER_PREFIX: &str = concat!(
+    "// This file is automatically @generated by ",
+    env!("CARGO_PKG_NAME")
+);
+
+const AWTO_README: &str = include_str!("../templates/README.md");
+
+/// Returns whether `dir` can be overwritten by the cli, because it does not exist, is empty,
+/// or contains the `marker_file` of a generated directory starting with `marker`.
+async fn is_awto_managed(dir: &Path, marker_file: &str, marker: &str) -> Result<bool> {
+    if !dir.exists() {
+        return Ok(true);
+    }
+
+    let mut entries = fs::read_dir(dir)
+        .await
+        .with_context(|| format!("could not read directory '{}'", dir.display()))?;
+    if entries.next_entry().await?.is_none() {
+        return Ok(true);
+    }
+
+    let content = fs::read_to_string(dir.join(marker_file))
+        .await
+        .unwrap_or_default();
+
+    Ok(content.starts_with(marker))
+}
+
+/// Fails with [`Error::UnmanagedDirectory`] unless the generated package directory `dir` can be
+/// overwritten, or `force` is set.
+///
+/// Directories with a generation manifest were written by the cli, otherwise the header of their `src/lib.rs` is checked.
+async fn ensure_package_dir_managed(dir: &str, force: bool) -> Result<()> {
+    if force
+        || GenerationManifest::load(dir).await.is_some()
+        || is_awto_managed(Path::new(dir), "src/lib.rs", GENERATED_HEADER_PREFIX).await?
+    {
+        return Ok(());
+    }
+
+    Err(Error::UnmanagedDirectory(dir.to_string()).into())
+}
+
+/// Returns the `Cargo.toml` file of a generated package rendered from its template, inheriting the `edition`,
+/// `rust-version` and lints of `workspace` as configured by the `[packages]` section of `awto.toml`.
+async fn package_manifest(workspace: &WorkspaceContext, cargo_toml: &str) -> Result<String> {
+    let config = Config::load(CONFIG_PATH).await?.packages;
+    let cargo_toml = schema_dependencies(cargo_toml, &schema_packages().await?);
+    inheritance::inherit_workspace(&cargo_toml, workspace.root_workspace(), &config)
+}
+
+/// Returns `cargo_toml` depending on each of the schema `packages` in place of the `schema` package,
+/// when the `[schema]` section of `awto.toml` lists any.
+fn schema_dependencies(cargo_toml: &str, packages: &[SchemaPackage]) -> String {
+    if packages.is_empty() {
+        return cargo_toml.to_string();
+    }
+
+    let dependencies: Vec<String> = packages
+        .iter()
+        .map(|package| {
+            format!(
+                "{} = {{ path = \"../../{}\" }}",
+                package.name,
+                package.dir.trim_start_matches("./")
+            )
+        })
+        .collect();
+    cargo_toml.replace(SCHEMA_DEPENDENCY, &dependencies.join("\n"))
+}
+
+/// Returns `source` reading the models of every schema package of `config`, merged by awto-compile,
+/// in place of the models of the `schema` package, when the `[schema]` section of `awto.toml` lists any
+/// or reads a manifest.
+///
+/// Either way, the models and fields of the features which are not enabled in `features` are left out.
+fn render_schema_models(source: &str, config: &SchemaConfig, features: &FeaturesConfig) -> String {
+    let names = config.package_names();
+    let models = if names.is_empty() {
+        "schema::MODELS[..].to_vec()".to_string()
+    } else {
+        let packages: Vec<String> = names
+            .iter()
+            .map(|name| format!("(\"{}\", &{}::MODELS[..])", name, name.replace('-', "_")))
+            .collect();
+        format!(
+            "awto_compile::schemas::merge_schemas(&[{}], awto_compile::schemas::{}).unwrap_or_else(|err| panic!(\"{{}}\", err))",
+            packages.join(", "),
+            config.duplicates.variant()
+        )
+    };
+    let models = format!(
+        "awto_compile::features::with_features({}, {}).unwrap_or_else(|err| panic!(\"{{}}\", err))",
+        models,
+        features.render()
+    );
+    source
+        .replace("&schema::MODELS[..]", &format!("&{}[..]", models))
+        .replace("schema::MODELS.to_vec()", &models)
+}
+
+/// Writes `./awto/README.md`, unless `./awto` is not managed by the cli and the write is not `force`d, moves the
+/// generated packages into or out of the nested workspace in `./awto` per `nested_workspace` in the `[build]` section,
+/// checks `./awto` is committed or ignored per `generated_files`, and writes the package of the manifest of the
+/// `[schema]` section, or removes it when the models are no longer read from a manifest.
+pub(crate) async fn prepare_awto_dir(force: bool) -> Result<()> {
     let awto_path = Path::new("./awto");
+    let readme_title = AWTO_README.lines().next().unwrap_or_default();
+    if !force && !is_awto_managed(awto_path, "README.md", readme_title).await? {
+        return Err(Error::UnmanagedDirectory("./awto".to_string()).into());
+    }
+
     if !awto_path.is_dir() {
-        fs::create_dir(awto_path)
+        fs::create_dir_all(awto_path)
             .await
-            .context("could not create directory './awto'")?;
+            .context("could not create directory './awto'")
+            .map_err(|source| Error::GeneratePackage {
+                package: "awto".to_string(),
+                source,
+            })?;
     }
 
-    fs::write("./awto/README.md", include_bytes!("../templates/README.md"))
+    fs::write("./awto/README.md", AWTO_README)
         .await
-        .context("could not write file './awto/README.md'")?;
+        .context("could not write file './awto/README.md'")
+        .map_err(|source| Error::GeneratePackage {
+            package: "awto".to_string(),
+            source,
+        })?;
+
+    let config = Config::load(CONFIG_PATH).await?;
+    sync_nested_workspace(Path::new("."), config.build.nested_workspace).await?;
+    if let Some(policy) = config.build.generated_files {
+        check_generated_files(Path::new("."), policy).await?;
+    }
+
+    if config.schema.source == SchemaSource::Manifest {
+        write_manifest_package(&config.schema, force).await?;
+    } else if GenerationManifest::load(MANIFEST_PACKAGE_DIR)
+        .await
+        .is_some()
+    {
+        fs::remove_dir_all(MANIFEST_PACKAGE_DIR)
+            .await
+            .with_context(|| format!("could not delete directory '{}'", MANIFEST_PACKAGE_DIR))?;
+        info!(
+            "removed package '{}', as the models are no longer read from a manifest",
+            MANIFEST_PACKAGE
+        );
+    }
 
     Ok(())
 }
 
-async fn build_awto_pkg(name: &str) -> Result<()> {
-    let status = tokio::process::Command::new("cargo")
+/// Dependencies of the generated package of the manifest.
+const MANIFEST_CARGO_TOML: &str = include_str!("../templates/schema-manifest/Cargo.toml.template");
+
+/// Writes the generated package declaring the models of the manifest of `config` with `schema!`, along with the
+/// JSON Schema of the manifest format, unless the package is not managed by the cli and the write is not `force`d.
+///
+/// The JSON Schema is written first, so editors can point out the problems of a manifest which does not validate.
+async fn write_manifest_package(config: &SchemaConfig, force: bool) -> Result<()> {
+    ensure_package_dir_managed(MANIFEST_PACKAGE_DIR, force).await?;
+    let src_dir = format!("{}/src", MANIFEST_PACKAGE_DIR);
+    fs::create_dir_all(&src_dir)
+        .await
+        .with_context(|| format!("could not create directory '{}'", src_dir))?;
+
+    let mut log = PackageLog::default();
+    let mut package_files = PackageFiles::new(MANIFEST_PACKAGE_DIR, MANIFEST_PACKAGE).await;
+    let mut json_schema = serde_json::to_string_pretty(&manifest_json_schema())?;
+    json_schema.push('\n');
+    package_files
+        .write(
+            &format!("{}/{}", MANIFEST_PACKAGE_DIR, MANIFEST_SCHEMA_FILE),
+            json_schema,
+            &mut log,
+        )
+        .await?;
+
+    let path = config.manifest_path();
+    let manifest = read_manifest(&path)
+        .await
+        .map_err(|source| Error::SchemaSource {
+            path: path.clone(),
+            source,
+        })?;
+    let workspace = WorkspaceContext::load(".").await?;
+    package_files
+        .write(
+            &format!("{}/Cargo.toml", MANIFEST_PACKAGE_DIR),
+            package_manifest(&workspace, MANIFEST_CARGO_TOML).await?,
+            &mut log,
+        )
+        .await?;
+    package_files
+        .write(
+            &format!("{}/lib.rs", src_dir),
+            format!("{}{}", GENERATED_HEADER, manifest.render(&path)),
+            &mut log,
+        )
+        .await?;
+    let finished = package_files.finish(&mut log).await;
+    log.flush();
+
+    finished
+}
+
+/// Deletes everything inside `dir` except the paths in `keep` and their parent directories.
+async fn clean_dir(dir: &str, keep: &[&str]) -> Result<()> {
+    let keep: Vec<PathBuf> = keep.iter().map(PathBuf::from).collect();
+    let mut dirs = vec![PathBuf::from(dir)];
+
+    while let Some(dir) = dirs.pop() {
+        let mut entries = fs::read_dir(&dir)
+            .await
+            .with_context(|| format!("could not read directory '{}'", dir.display()))?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if keep.contains(&path) {
+                continue;
+            }
+
+            let is_dir = entry.file_type().await?.is_dir();
+            if is_dir && keep.iter().any(|keep| keep.starts_with(&path)) {
+                dirs.push(path);
+            } else if is_dir {
+                fs::remove_dir_all(&path)
+                    .await
+                    .with_context(|| format!("could not delete directory '{}'", path.display()))?;
+            } else {
+                fs::remove_file(&path)
+                    .await
+                    .with_context(|| format!("could not delete file '{}'", path.display()))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Lists the files of `dir` which differ from the generated `files`, failing with [`Error::GeneratedOutdated`] when there are any.
+async fn check_files(dir: &str, files: &BTreeMap<String, String>) -> Result<()> {
+    let outdated = outdated_files(dir, files).await;
+    if outdated.is_empty() {
+        info!("files generated into '{}' are up to date", dir);
+        return Ok(());
+    }
+    for path in &outdated {
+        outln!("{}/{}", dir, path);
+    }
+
+    Err(Error::GeneratedOutdated {
+        dir: dir.to_string(),
+        files: outdated,
+    }
+    .into())
+}
+
+/// Lists the files of `dir` which would be written or deleted to generate `files`, without changing any.
+async fn dry_run_files(dir: &str, files: &BTreeMap<String, String>) -> Result<()> {
+    let outdated = outdated_files(dir, files).await;
+    if outdated.is_empty() {
+        info!("files generated into '{}' are up to date", dir);
+        return Ok(());
+    }
+    for path in &outdated {
+        outln!("{}/{}", dir, path);
+    }
+    info!(
+        "dry run, {} file(s) generated into '{}' would change",
+        outdated.len(),
+        dir
+    );
+
+    Ok(())
+}
+
+/// Returns the sorted paths of the files of `dir` which differ from the generated `files`,
+/// along with the files generated by a previous run which are no longer generated.
+async fn outdated_files(dir: &str, files: &BTreeMap<String, String>) -> Vec<String> {
+    let mut outdated = Vec::new();
+    for (path, contents) in files {
+        let current = fs::read(Path::new(dir).join(path)).await.ok();
+        if current.as_deref() != Some(contents.as_bytes()) {
+            outdated.push(path.clone());
+        }
+    }
+    // Files generated by a previous run which are no longer generated would be deleted
+    let manifest = GenerationManifest::load(dir).await.unwrap_or_default();
+    outdated.extend(
+        manifest
+            .files
+            .into_keys()
+            .filter(|path| !files.contains_key(path) && Path::new(dir).join(path).exists()),
+    );
+    outdated.sort();
+
+    outdated
+}
+
+/// Writes `contents` to the file at `path` unless it already has these contents.
+///
+/// Unchanged files keep their modification time, so cargo does not rebuild the generated package.
+async fn write_generated_file(
+    path: &str,
+    contents: impl AsRef<[u8]>,
+    log: &mut PackageLog,
+) -> Result<()> {
+    let contents = contents.as_ref();
+    if fs::read(path).await.ok().as_deref() == Some(contents) {
+        log.debug(format_args!("unchanged '{}'", path));
+        return Ok(());
+    }
+
+    fs::write(path, contents)
+        .await
+        .with_context(|| format!("could not write file '{}'", path))?;
+    log.debug(format_args!("wrote '{}'", path));
+
+    Ok(())
+}
+
+/// Removes generated database and REST packages which are no longer compiled from the schema,
+/// such as the packages named after a previous database package name, or the codegen packages of database packages
+/// no longer generated into their sources.
+///
+/// Packages containing user-owned extensions are kept, and the user is told how to remove them.
+async fn remove_stale_packages(packages: &[DatabasePackage], log: &mut PackageLog) -> Result<()> {
+    let mut names: BTreeSet<String> = packages.iter().map(DatabasePackage::name).collect();
+    names.extend(
+        packages
+            .iter()
+            .filter(|package| package.is_generated_into_sources())
+            .map(DatabasePackage::codegen_name),
+    );
+    names.extend(packages.iter().map(|database_package| {
+        RestPackage {
+            database_package: database_package.clone(),
+            with_transcoding: false,
+        }
+        .name()
+    }));
+
+    if !Path::new("./awto").is_dir() {
+        return Ok(());
+    }
+
+    let mut entries = fs::read_dir("./awto")
+        .await
+        .context("could not read directory './awto'")?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let dir = entry.path();
+        if names.contains(&name) {
+            continue;
+        }
+
+        let build = generator_source(&dir).await;
+        if !STALE_PACKAGE_MARKERS
+            .iter()
+            .any(|marker| build.contains(marker))
+            || !(is_awto_managed(&dir, "src/lib.rs", GENERATED_HEADER_PREFIX).await?
+                || is_awto_managed(&dir, "src/main.rs", GENERATED_HEADER_PREFIX).await?)
+        {
+            continue;
+        }
+
+        if dir.join("src/ext").is_dir() {
+            log.warn(format_args!(
+                "package '{}' is no longer generated, move its extensions in '{}' and remove 'awto/{}' from the workspace members",
+                name,
+                dir.join("src/ext").display(),
+                name
+            ));
+            continue;
+        }
+
+        remove_package_from_workspace(&format!("awto/{}", name)).await?;
+        fs::remove_dir_all(&dir)
+            .await
+            .with_context(|| format!("could not delete directory '{}'", dir.display()))?;
+        log.warn(format_args!(
+            "removed package '{}' which is no longer generated, update path dependencies on '{}'",
+            name,
+            dir.display()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Calls in the build scripts of generated database and REST packages, and in the mains of their codegen packages.
+const STALE_PACKAGE_MARKERS: [&str; 3] = [
+    "compile_database(",
+    "compile_rest(",
+    "compile_rest_with_transcoding(",
+];
+
+/// Environment variable read by the build scripts of generated packages to skip formatting their code.
+const NO_FORMAT_ENV: &str = "AWTO_NO_FORMAT";
+
+/// Environment variable read by the build scripts of generated packages to annotate their code.
+const ANNOTATE_SOURCES_ENV: &str = "AWTO_ANNOTATE_SOURCES";
+
+/// Environment variable read by the build scripts of generated database packages to narrow columns
+/// even when rows of the database do not fit them.
+const FORCE_NARROWING_ENV: &str = "AWTO_FORCE_NARROWING";
+
+/// Environment variable read by the build scripts of generated packages to print each retry to connect to the database,
+/// and the models whose code was regenerated.
+const VERBOSE_ENV: &str = "AWTO_VERBOSE";
+
+/// Environment variable holding the fingerprint of the configuration of the active profile,
+/// which reruns the build scripts of generated packages when it changes.
+const PROFILE_FINGERPRINT_ENV: &str = "AWTO_PROFILE_FINGERPRINT";
+
+/// Flags of the generated code passed to the build scripts of the packages.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BuildOptions {
+    /// Leaves the generated code unformatted.
+    pub no_format: bool,
+    /// Interleaves the generated code with comments telling which model and generator produced it.
+    pub annotate_sources: bool,
+    /// Alters the columns narrowed by the schema even when rows of the database do not fit them.
+    pub force_narrowing: bool,
+}
+
+/// Builds the generated `packages` with a single cargo invocation, adding the warnings printed by their build scripts to `warnings`.
+///
+/// The packages are added to the members of `workspace` before the build starts, writing the root `Cargo.toml` file once.
+/// Cargo keeps building the other packages when one of them fails, so the warnings of every package are added, and only the failed packages are in [`Error::Build`].
+/// The output of cargo is forwarded, except for these warnings, which are reported at the end of the run.
+/// The database `urls` are set in the environment of cargo, and redacted from its output, along with
+/// the active profile and its fingerprint, so switching profiles regenerates the code of the packages.
+/// The `[database.connect]` section of `awto.toml`, with the keys of `connect` overriding it, is set for the build scripts
+/// connecting to the databases, which print each retry in verbose mode.
+/// Errors pointing into generated code are followed by a note telling what generated it, read from its generation manifest.
+/// In nested mode, the packages are built in the nested workspace, after which the root workspace is checked to resolve.
+/// Cargo is killed once `cancellation` is cancelled, and waited for so the build does not outlive the cli.
+async fn build_awto_pkgs(
+    workspace: &mut WorkspaceContext,
+    packages: &[String],
+    urls: &[(String, DatabaseUrl)],
+    connect: &ConnectConfig,
+    cancellation: &Cancellation,
+    options: BuildOptions,
+    warnings: &mut Vec<Warning>,
+) -> Result<()> {
+    if packages.is_empty() {
+        return Ok(());
+    }
+
+    for name in packages {
+        workspace.add_member(&format!("awto/{}", name));
+    }
+    workspace.flush().await?;
+    debug!("building into '{}'", workspace.target_dir().display());
+
+    let mut command = tokio::process::Command::new("cargo");
+    generation_env(&mut command, connect, options).await?;
+    // Cargo only colors its output when writing to a terminal, which it no longer does once piped
+    if crate::sink::is_colored() {
+        command.arg("--color").arg("always");
+    }
+    for (var, url) in urls {
+        command.env(var, url.as_str());
+    }
+    command
         .current_dir("./awto")
         .arg("build")
-        .arg("-p")
-        .arg(name)
+        .arg("--keep-going");
+    for name in packages {
+        command.arg("-p").arg(name);
+    }
+
+    let start = Instant::now();
+    let mut child = command
         .stdout(Stdio::inherit())
-        .stderr(Stdio::inherit())
-        .status()
-        .await?;
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(Error::Cargo)?;
+
+    let mut failed = Vec::new();
+    let mut lines = BufReader::new(child.stderr.take().unwrap()).lines();
+    loop {
+        let line = tokio::select! {
+            line = lines.next_line() => line.map_err(Error::Cargo)?,
+            _ = cancellation.cancelled() => {
+                kill_child(&mut child).await;
+                return Err(Error::Cancelled.into());
+            }
+        };
+        let line = match line {
+            Some(line) => line,
+            None => break,
+        };
+        let line = urls.iter().fold(line, |line, (_, url)| url.redact(&line));
+        if let Some(phase) = Phase::parse(&line, packages) {
+            profiling::record_phase(phase);
+            continue;
+        }
+        // Cargo prints the warnings of a build script again when its package fails to compile
+        if let Some(warning) = Warning::parse(&line, packages) {
+            if !warnings.contains(&warning) {
+                warnings.push(warning);
+            }
+            continue;
+        }
+
+        if let Some(package) = failed_package(&line, packages) {
+            if !failed.contains(package) {
+                failed.push(package.clone());
+            }
+        }
+        errln!("{}", line);
+        if let Some(note) = generated_source_note(&line).await {
+            errln!("{}", note);
+        }
+    }
 
+    let status = tokio::select! {
+        status = child.wait() => status.map_err(Error::Cargo)?,
+        _ = cancellation.cancelled() => {
+            kill_child(&mut child).await;
+            return Err(Error::Cancelled.into());
+        }
+    };
+    profiling::record_elapsed("build", start);
+    if status.success() {
+        failed.clear();
+    } else if failed.is_empty() {
+        failed = packages.to_vec();
+    }
+
+    for name in packages.iter().filter(|name| !failed.contains(name)) {
+        info!("compiled package '{}'", name);
+    }
+    if !failed.is_empty() {
+        failed.sort_by_key(|name| packages.iter().position(|package| package == name));
+        return Err(Error::Build {
+            packages: failed,
+            status,
+        }
+        .into());
+    }
+
+    if workspace.is_nested() {
+        resolve_root_workspace(cancellation).await?;
+    }
+
+    Ok(())
+}
+
+/// Sets the environment read by the generators of the packages on `command`: the active profile and its fingerprint,
+/// the flags of `options`, the `[database.connect]` section of `awto.toml` with the keys of `connect` overriding it,
+/// and whether to print each retry to connect.
+async fn generation_env(
+    command: &mut tokio::process::Command,
+    connect: &ConnectConfig,
+    options: BuildOptions,
+) -> Result<()> {
+    let config = Config::load(CONFIG_PATH).await?;
+    command
+        .env(PROFILE_ENV, &config.profile)
+        .env(PROFILE_FINGERPRINT_ENV, &config.fingerprint);
+    if options.no_format {
+        command.env(NO_FORMAT_ENV, "1");
+    } else {
+        command.env_remove(NO_FORMAT_ENV);
+    }
+    if options.annotate_sources {
+        command.env(ANNOTATE_SOURCES_ENV, "1");
+    } else {
+        command.env_remove(ANNOTATE_SOURCES_ENV);
+    }
+    if options.force_narrowing {
+        command.env(FORCE_NARROWING_ENV, "1");
+    } else {
+        command.env_remove(FORCE_NARROWING_ENV);
+    }
+    command.envs(config.database.connect.merge(connect).env()?);
+    if log_enabled!(Level::Debug) {
+        command.env(VERBOSE_ENV, "1");
+    } else {
+        command.env_remove(VERBOSE_ENV);
+    }
+
+    Ok(())
+}
+
+/// Checks cargo resolves the root workspace, whose members depend by path on packages of the nested workspace,
+/// which fails when the root workspace does not exclude `./awto` or a path does not lead to a generated package.
+async fn resolve_root_workspace(cancellation: &Cancellation) -> Result<()> {
+    let mut child = tokio::process::Command::new("cargo")
+        .arg("metadata")
+        .arg("--format-version")
+        .arg("1")
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(Error::Cargo)?;
+
+    let mut stderr = child.stderr.take().unwrap();
+    let mut output = String::new();
+    let status = tokio::select! {
+        status = async {
+            stderr.read_to_string(&mut output).await?;
+            child.wait().await
+        } => status.map_err(Error::Cargo)?,
+        _ = cancellation.cancelled() => {
+            kill_child(&mut child).await;
+            return Err(Error::Cancelled.into());
+        }
+    };
     if !status.success() {
-        return Err(anyhow!("cargo build failed"));
+        return Err(Error::NestedWorkspace {
+            source: anyhow::anyhow!("{}", output.trim()).context(format!(
+                "cargo could not resolve the root workspace ({})",
+                status
+            )),
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Returns the package of `packages` which cargo reports as failed in `line`, such as
+/// ``error: could not compile `database` (lib)`` or ``error: failed to run custom build command for `database v0.1.0 (...)` ``.
+fn failed_package<'a>(line: &str, packages: &'a [String]) -> Option<&'a String> {
+    if !line.contains("could not compile") && !line.contains("failed to run custom build command") {
+        return None;
+    }
+
+    packages.iter().find(|name| {
+        line.contains(&format!("`{}`", name)) || line.contains(&format!("`{} v", name))
+    })
+}
+
+/// Prints the `warnings` of a compile command which are not allowed, and sets `count` to their number.
+///
+/// Fails with [`Error::WarningsDenied`] when there are any and warnings are denied by `deny_warnings`
+/// or the `[build]` section of `awto.toml`, once all of them have been printed.
+async fn report_warnings(
+    warnings: Vec<Warning>,
+    deny_warnings: bool,
+    count: &mut usize,
+) -> Result<()> {
+    let config = Config::load(CONFIG_PATH).await?;
+    let models = if warnings.iter().any(|warning| warning.model.is_some()) {
+        load_schema_models().await.unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+
+    let mut warnings: Vec<_> = warnings
+        .into_iter()
+        .filter(|warning| !warning.is_allowed(&models, &config.build))
+        .collect();
+    *count = warnings.len();
+    if warnings.is_empty() {
+        return Ok(());
+    }
+
+    outln!();
+    for warning in &mut warnings {
+        warning.locate(&models);
+        outln!("{}", warning);
     }
 
+    if deny_warnings || config.build.deny_warnings {
+        return Err(Error::WarningsDenied(warnings.len()).into());
+    }
+    warn!("compiled with {} warning(s)", warnings.len());
+
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn failed_packages() {
+        let packages = ["database".to_string(), "protobuf".to_string()];
+        assert_eq!(
+            failed_package(
+                "error: could not compile `protobuf` (lib) due to 2 previous errors",
+                &packages
+            ),
+            Some(&packages[1])
+        );
+        assert_eq!(
+            failed_package(
+                "error: failed to run custom build command for `database v0.1.0 (/app/awto/database)`",
+                &packages
+            ),
+            Some(&packages[0])
+        );
+        assert_eq!(
+            failed_package("error: could not compile `schema` (lib)", &packages),
+            None
+        );
+        assert_eq!(
+            failed_package("   Compiling database v0.1.0", &packages),
+            None
+        );
+    }
+
+    #[test]
+    fn schema_packages() {
+        let packages = [
+            SchemaPackage {
+                name: "schema-billing".to_string(),
+                dir: "./schemas/billing".to_string(),
+            },
+            SchemaPackage {
+                name: "schema-catalog".to_string(),
+                dir: "./schema-catalog".to_string(),
+            },
+        ];
+        let cargo_toml = "[dependencies]\nawto = \"0.1\"\nschema = { path = \"../../schema\" }\n";
+        assert_eq!(schema_dependencies(cargo_toml, &[]), cargo_toml);
+        assert_eq!(
+            schema_dependencies(cargo_toml, &packages),
+            "[dependencies]\nawto = \"0.1\"\nschema-billing = { path = \"../../schemas/billing\" }\n\
+            schema-catalog = { path = \"../../schema-catalog\" }\n"
+        );
+
+        let build = "database_models(schema::MODELS.to_vec(), DATABASE)";
+        let mut config = SchemaConfig::default();
+        let mut features = FeaturesConfig::default();
+        assert_eq!(
+            render_schema_models(build, &config, &features),
+            "database_models(awto_compile::features::with_features(schema::MODELS[..].to_vec(), &[])\
+            .unwrap_or_else(|err| panic!(\"{}\", err)), DATABASE)"
+        );
+
+        config.packages = vec!["schema-billing".to_string(), "schema-catalog".to_string()];
+        config.duplicates = crate::schema::Duplicates::Namespace;
+        features.enabled = vec!["enterprise".to_string()];
+        let models = "awto_compile::features::with_features(awto_compile::schemas::merge_schemas(&[\
+            (\"schema-billing\", &schema_billing::MODELS[..]), (\"schema-catalog\", &schema_catalog::MODELS[..])], \
+            awto_compile::schemas::Duplicates::Namespace).unwrap_or_else(|err| panic!(\"{}\", err)), &[\"enterprise\"])\
+            .unwrap_or_else(|err| panic!(\"{}\", err))";
+        assert_eq!(
+            render_schema_models(build, &config, &features),
+            format!("database_models({}, DATABASE)", models)
+        );
+        assert_eq!(
+            render_schema_models(
+                "GeneratorInput::new(&schema::MODELS[..])",
+                &config,
+                &features
+            ),
+            format!("GeneratorInput::new(&{}[..])", models)
+        );
+    }
+
+    /// Creates an empty directory for a test, removing what a previous run left behind.
+    async fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("awto-cli-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir).await;
+        fs::create_dir_all(dir.join("src")).await.unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn clean_dirs() {
+        let dir = test_dir("clean").await;
+        for file in [
+            "src/lib.rs",
+            "src/stale.rs",
+            "src/ext/product.rs",
+            "src/old/mod.rs",
+            "Cargo.toml",
+        ] {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).await.unwrap();
+            fs::write(path, "").await.unwrap();
+        }
+
+        let keep = [dir.join("src/ext"), dir.join("src/lib.rs")];
+        let keep: Vec<&str> = keep.iter().map(|path| path.to_str().unwrap()).collect();
+        clean_dir(dir.to_str().unwrap(), &keep).await.unwrap();
+        // Kept directories are left whole, and the parents of kept paths are cleaned rather than deleted
+        assert!(dir.join("src/ext/product.rs").is_file());
+        assert!(dir.join("src/lib.rs").is_file());
+        assert!(!dir.join("src/stale.rs").exists());
+        assert!(!dir.join("src/old").exists());
+        assert!(!dir.join("Cargo.toml").exists());
+
+        assert!(clean_dir(dir.join("missing").to_str().unwrap(), &[])
+            .await
+            .is_err());
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn managed_dirs() {
+        let empty = test_dir("empty").await;
+        fs::remove_dir(empty.join("src")).await.unwrap();
+        assert!(
+            is_awto_managed(&empty, "src/lib.rs", GENERATED_HEADER_PREFIX)
+                .await
+                .unwrap()
+        );
+        assert!(is_awto_managed(
+            &empty.join("missing"),
+            "src/lib.rs",
+            GENERATED_HEADER_PREFIX
+        )
+        .await
+        .unwrap());
+
+        let foreign = test_dir("foreign").await;
+        fs::write(foreign.join("src/lib.rs"), "pub fn hand_written() {}\n")
+            .await
+            .unwrap();
+        assert!(
+            !is_awto_managed(&foreign, "src/lib.rs", GENERATED_HEADER_PREFIX)
+                .await
+                .unwrap()
+        );
+        let err = ensure_package_dir_managed(foreign.to_str().unwrap(), false)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            Error::code_of(&err),
+            Some(crate::error::ErrorCode::UnmanagedDirectory)
+        );
+        ensure_package_dir_managed(foreign.to_str().unwrap(), true)
+            .await
+            .unwrap();
+
+        let generated = test_dir("generated").await;
+        let lib = format!(
+            "{}include!(concat!(env!(\"OUT_DIR\"), \"/app.rs\"));\n",
+            GENERATED_HEADER.replace(env!("CARGO_PKG_VERSION"), "0.0.1")
+        );
+        fs::write(generated.join("src/lib.rs"), lib).await.unwrap();
+        assert!(
+            is_awto_managed(&generated, "src/lib.rs", GENERATED_HEADER_PREFIX)
+                .await
+                .unwrap()
+        );
+
+        for dir in [empty, foreign, generated] {
+            fs::remove_dir_all(dir).await.unwrap();
+        }
+    }
+}
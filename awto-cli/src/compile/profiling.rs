@@ -0,0 +1,189 @@
+use std::{
+    env, fmt,
+    future::Future,
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use colored::Colorize;
+
+use super::warning::build_script_package;
+
+/// Environment variable read by the build scripts of generated packages to report the phases of their generation,
+/// set to a value changing on every run so they rerun.
+const PROFILE_GENERATION_ENV: &str = "AWTO_PROFILE_GENERATION";
+
+/// Start of the phases printed by awto-compile, `awto profile[database/app.rs] wall=1250ms peak_rss=98304kB`.
+const PROFILE_MARKER: &str = "awto profile[";
+
+/// Phases measured during the run, reported once it completed.
+static PHASES: Mutex<Vec<Phase>> = Mutex::new(Vec::new());
+
+/// A measured phase of generation, of the cli or of the build script of a generated package.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Phase {
+    /// Generated package whose build script ran the phase, or `None` for phases of the cli.
+    pub package: Option<String>,
+    /// Name of the phase, such as `database/app.rs` or `cli/build`.
+    pub name: String,
+    pub wall: Duration,
+    /// Peak resident memory of the process once the phase completed, in kilobytes, when known.
+    pub peak_rss_kb: Option<u64>,
+}
+
+impl Phase {
+    /// Parses the phase of a line of cargo output for the build of `packages`,
+    /// returning `None` for lines which are not phases printed by awto-compile.
+    pub fn parse(line: &str, packages: &[String]) -> Option<Phase> {
+        let marker = line.find(PROFILE_MARKER)?;
+        let (name, values) = line[marker + PROFILE_MARKER.len()..].split_once(']')?;
+        let value = |key: &str, unit: &str| {
+            values.split_whitespace().find_map(|value| {
+                value
+                    .strip_prefix(key)?
+                    .strip_suffix(unit)?
+                    .parse::<u64>()
+                    .ok()
+            })
+        };
+
+        Some(Phase {
+            package: build_script_package(&line[..marker], packages).cloned(),
+            name: name.to_string(),
+            wall: Duration::from_millis(value("wall=", "ms")?),
+            peak_rss_kb: value("peak_rss=", "kB"),
+        })
+    }
+}
+
+/// Formats the phase as a line of the report, such as `database  database/app.rs  1250ms  96.0 MB`.
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let peak_rss = self
+            .peak_rss_kb
+            .map(|kb| format!("{:.1} MB", kb as f64 / 1024.0))
+            .unwrap_or_else(|| "-".to_string());
+
+        write!(
+            f,
+            "{:<24} {:<32} {:>8}ms {:>10}",
+            self.package.as_deref().unwrap_or("awto"),
+            self.name,
+            self.wall.as_millis(),
+            peak_rss
+        )
+    }
+}
+
+/// Enables profiling for the run, in the cli and in the build scripts of generated packages.
+pub fn enable_profiling() {
+    let run = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_nanos())
+        .unwrap_or_default();
+    env::set_var(PROFILE_GENERATION_ENV, run.to_string());
+}
+
+pub fn is_profiling() -> bool {
+    env::var_os(PROFILE_GENERATION_ENV).is_some()
+}
+
+/// Records `phase` to be reported at the end of the run.
+pub fn record_phase(phase: Phase) {
+    PHASES
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .push(phase);
+}
+
+/// Runs the phase `name` of the cli, such as `read schema`, recording it when profiling is enabled.
+pub async fn profile_phase<T>(name: &str, phase: impl Future<Output = T>) -> T {
+    let start = Instant::now();
+    let output = phase.await;
+    record_elapsed(name, start);
+
+    output
+}
+
+/// Records the phase `name` of the cli which started at `start` and just completed, when profiling is enabled.
+pub fn record_elapsed(name: &str, start: Instant) {
+    if !is_profiling() {
+        return;
+    }
+
+    record_phase(Phase {
+        package: None,
+        name: format!("cli/{}", name),
+        wall: start.elapsed(),
+        peak_rss_kb: peak_rss_kb(),
+    });
+}
+
+/// Prints the recorded phases to stderr, in the order they completed.
+pub fn report_profile() {
+    let phases = PHASES.lock().unwrap_or_else(|err| err.into_inner());
+    if phases.is_empty() {
+        return;
+    }
+
+    errln!("\n{}", "profile of generation".bold());
+    errln!(
+        "{:<24} {:<32} {:>10} {:>10}",
+        "package",
+        "phase",
+        "wall",
+        "peak rss"
+    );
+    for phase in phases.iter() {
+        errln!("{}", phase);
+    }
+}
+
+/// Returns the peak resident memory of the cli in kilobytes, read from `/proc/self/status`,
+/// or `None` where it is unavailable.
+fn peak_rss_kb() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_phases() {
+        let packages = vec!["database".to_string(), "rest".to_string()];
+        let phase = Phase::parse(
+            "warning: database@0.1.0: awto profile[database/app.rs] wall=1250ms peak_rss=98304kB",
+            &packages,
+        )
+        .unwrap();
+
+        assert_eq!(phase.package.as_deref(), Some("database"));
+        assert_eq!(phase.name, "database/app.rs");
+        assert_eq!(phase.wall, Duration::from_millis(1250));
+        assert_eq!(phase.peak_rss_kb, Some(98304));
+        assert!(phase.to_string().contains("96.0 MB"));
+
+        let phase = Phase::parse(
+            "warning: rest@0.1.0: awto profile[database/sync] wall=3ms",
+            &packages,
+        )
+        .unwrap();
+        assert_eq!(phase.package.as_deref(), Some("rest"));
+        assert_eq!(phase.peak_rss_kb, None);
+        assert!(Phase::parse(
+            "warning: database@0.1.0: awto warning[empty_schema]: no tables",
+            &packages
+        )
+        .is_none());
+        assert!(Phase::parse("awto profile[database/app.rs] peak_rss=1kB", &packages).is_none());
+    }
+}
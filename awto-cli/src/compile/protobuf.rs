@@ -1,63 +1,122 @@
+use std::collections::BTreeMap;
 use std::fmt::Write;
 use std::path::Path;
 
 use anyhow::{anyhow, Context, Result};
 use async_trait::async_trait;
 use clap::Parser;
-use log::info;
+use serde::Deserialize;
 use tokio::fs;
 
 use crate::{
-    util::{add_package_to_workspace, CargoFile},
+    cancel::Cancellation,
+    config::{Config, CONFIG_PATH},
+    error::{Error, ErrorCode},
+    schema::ApiDocsOverlay,
+    util::WorkspaceContext,
     Runnable,
 };
 
-use super::{build_awto_pkg, prepare_awto_dir};
+use super::{
+    check_files, check_service_package, clean_dir, compile_packages, ensure_package_dir_managed,
+    package_manifest, prepare_awto_dir, render_schema_models, Generate, PackageFiles, PackageLog,
+    GENERATED_HEADER,
+};
 
 /// Compiles protobuf package from app service
-#[derive(Parser)]
+#[derive(Clone, Parser)]
 pub struct Protobuf {
+    /// Keeps generated Rust code unformatted
+    #[clap(long)]
+    pub no_format: bool,
+    /// Interleaves generated code with comments telling which model and generator produced it
+    #[clap(long)]
+    pub annotate_sources: bool,
+    /// Overwrites package directories which were not generated by awto
+    #[clap(long)]
+    pub force: bool,
+    /// Fails after compiling when any warning was reported
+    #[clap(long)]
+    pub deny_warnings: bool,
+    /// Writes a buf.yaml next to the exported protobuf files, exporting them into `proto` unless `proto.export_dir` is set
+    #[clap(long)]
+    pub with_buf_config: bool,
+    /// Lists the files which are out of date instead of writing them, failing when there are any
+    #[clap(long)]
+    pub check: bool,
     /// Prints more information
     #[clap(short, long)]
     pub verbose: bool,
+    /// Number of warnings reported by the run
+    #[clap(skip)]
+    pub warnings: usize,
 }
 
 #[async_trait]
 impl Runnable for Protobuf {
-    async fn run(&mut self) -> Result<()> {
-        let cargo_file = CargoFile::load("./service/Cargo.toml")
-            .await
-            .context("could not load service Cargo.toml file from './service/Cargo.toml'")?;
-        if cargo_file
-            .package
-            .as_ref()
-            .map(|package| package.name != "service")
-            .unwrap_or(false)
-        {
-            match cargo_file.package {
-                Some(package) => {
-                    return Err(anyhow!(
-                        "service package must be named 'service' but is named '{}'",
-                        package.name
-                    ));
-                }
-                None => return Err(anyhow!("service package must be named 'service'")),
-            }
-        }
+    async fn run(&mut self, cancellation: &Cancellation) -> Result<()> {
+        let commands: Vec<Box<dyn Generate>> = vec![Box::new(self.clone())];
+        compile_packages(
+            commands,
+            cancellation,
+            self.no_format,
+            self.annotate_sources,
+            self.deny_warnings,
+            &mut self.warnings,
+        )
+        .await
+    }
+
+    fn is_verbose(&self) -> bool {
+        self.verbose
+    }
 
-        prepare_awto_dir().await?;
+    fn warnings(&self) -> usize {
+        self.warnings
+    }
+}
 
-        Self::prepare_protobuf_dir().await?;
-        add_package_to_workspace("awto/protobuf").await?;
-        build_awto_pkg("protobuf").await?;
+#[async_trait]
+impl Generate for Protobuf {
+    /// Generates the protobuf package, returning its name, or checks it with `--check` without building it.
+    async fn generate(
+        &self,
+        workspace: &WorkspaceContext,
+        log: &mut PackageLog,
+    ) -> Result<Vec<String>> {
+        check_service_package(workspace)?;
 
-        info!("compiled package 'protobuf'");
+        let mut config = Config::load(CONFIG_PATH).await?.proto;
+        config.validate().map_err(|source| Error::Config {
+            path: CONFIG_PATH.to_string(),
+            source,
+        })?;
+        if self.with_buf_config && config.export_dir.is_none() {
+            config.export_dir = Some(ProtoConfig::DEFAULT_EXPORT_DIR.to_string());
+        }
 
-        Ok(())
-    }
+        prepare_awto_dir(self.force).await?;
+        ensure_package_dir_managed(Self::PROTOBUF_DIR, self.force).await?;
 
-    fn is_verbose(&self) -> bool {
-        self.verbose
+        let cargo_toml = package_manifest(workspace, Self::PROTOBUF_CARGO_TOML).await?;
+        Self::prepare_protobuf_dir(&config, &cargo_toml, self.check, log)
+            .await
+            .map_err(|source| match Error::code_of(&source) {
+                Some(ErrorCode::GeneratedOutdated) => source,
+                _ => Error::GeneratePackage {
+                    package: "protobuf".to_string(),
+                    source,
+                }
+                .into(),
+            })?;
+        if self.check {
+            return Ok(Vec::new());
+        }
+        if self.with_buf_config {
+            Self::write_buf_config(&config).await?;
+        }
+
+        Ok(vec!["protobuf".to_string()])
     }
 }
 
@@ -65,53 +124,363 @@ impl Protobuf {
     const PROTOBUF_DIR: &'static str = "./awto/protobuf";
     const PROTOBUF_SRC_DIR: &'static str = "./awto/protobuf/src";
     const PROTOBUF_CARGO_PATH: &'static str = "./awto/protobuf/Cargo.toml";
-    const PROTOBUF_CARGO_TOML_BYTES: &'static [u8] = include_bytes!("../templates/protobuf/Cargo.toml.template");
+    const PROTOBUF_CARGO_TOML: &'static str =
+        include_str!("../templates/protobuf/Cargo.toml.template");
     const PROTOBUF_BUILD_PATH: &'static str = "./awto/protobuf/build.rs";
-    const PROTOBUF_BUILD_BYTES: &'static [u8] = include_bytes!("../templates/protobuf/build.rs.template");
+    const PROTOBUF_BUILD: &'static str = include_str!("../templates/protobuf/build.rs.template");
     const PROTOBUF_LIB_PATH: &'static str = "./awto/protobuf/src/lib.rs";
 
-    async fn prepare_protobuf_dir() -> Result<()> {
-        if Path::new(Self::PROTOBUF_DIR).is_dir() {
-            fs::remove_dir_all(Self::PROTOBUF_DIR)
-                .await
-                .with_context(|| format!("could not delete directory '{}'", Self::PROTOBUF_DIR))?;
+    /// Writes the files of the protobuf package, or lists those which are out of date with `check`.
+    async fn prepare_protobuf_dir(
+        config: &ProtoConfig,
+        cargo_toml: &str,
+        check: bool,
+        log: &mut PackageLog,
+    ) -> Result<()> {
+        let mut lib_content = GENERATED_HEADER.to_string();
+
+        writeln!(
+            lib_content,
+            r#"//! Protobuf messages and gRPC services of the schema, generated by awto.
+
+#![allow(deprecated)]
+
+include!(concat!(env!("OUT_DIR"), "/app.rs"));"#
+        )
+        .unwrap();
+
+        let app_config = Config::load(CONFIG_PATH).await?;
+        let api_docs = ApiDocsOverlay::load(&app_config.api_docs).await?;
+        let build = render_schema_models(
+            &api_docs.render(&config.render(Self::PROTOBUF_BUILD)),
+            &app_config.schema,
+            &app_config.features,
+        );
+        let files: [(&str, &[u8]); 3] = [
+            (Self::PROTOBUF_CARGO_PATH, cargo_toml.as_bytes()),
+            (Self::PROTOBUF_BUILD_PATH, build.as_bytes()),
+            (Self::PROTOBUF_LIB_PATH, lib_content.as_bytes()),
+        ];
+        if check {
+            let prefix = format!("{}/", Self::PROTOBUF_DIR);
+            let files: BTreeMap<String, String> = files
+                .iter()
+                .map(|(path, contents)| {
+                    (
+                        path.trim_start_matches(&prefix).to_string(),
+                        String::from_utf8_lossy(contents).into_owned(),
+                    )
+                })
+                .collect();
+            return check_files(Self::PROTOBUF_DIR, &files).await;
         }
 
-        fs::create_dir(Self::PROTOBUF_DIR)
+        let mut package_files = PackageFiles::new(Self::PROTOBUF_DIR, "protobuf")
             .await
-            .with_context(|| format!("could not create directory '{}'", Self::PROTOBUF_DIR))?;
+            .option("versions", format!("{:?}", config.versions));
+        if Path::new(Self::PROTOBUF_DIR).is_dir() {
+            let manifest_path = package_files.manifest_path();
+            let mut keep: Vec<&str> = files.iter().map(|(path, _)| *path).collect();
+            keep.push(&manifest_path);
+            clean_dir(Self::PROTOBUF_DIR, &keep)
+                .await
+                .with_context(|| format!("could not clean directory '{}'", Self::PROTOBUF_DIR))?;
+        }
 
-        fs::create_dir(Self::PROTOBUF_SRC_DIR)
+        fs::create_dir_all(Self::PROTOBUF_SRC_DIR)
             .await
             .with_context(|| format!("could not create directory '{}'", Self::PROTOBUF_SRC_DIR))?;
 
-        fs::write(Self::PROTOBUF_CARGO_PATH, Self::PROTOBUF_CARGO_TOML_BYTES)
+        for (path, content) in files {
+            package_files.write(path, content, log).await?;
+        }
+        package_files.finish(log).await?;
+
+        Ok(())
+    }
+
+    /// Writes the `buf.yaml` of the export directory, which the protobuf package exports its files into when built.
+    async fn write_buf_config(config: &ProtoConfig) -> Result<()> {
+        let dir = config
+            .export_dir
+            .as_deref()
+            .unwrap_or(ProtoConfig::DEFAULT_EXPORT_DIR);
+        fs::create_dir_all(dir)
             .await
-            .with_context(|| format!("could not write file '{}'", Self::PROTOBUF_CARGO_PATH))?;
+            .with_context(|| format!("could not create directory '{}'", dir))?;
 
-        fs::write(Self::PROTOBUF_BUILD_PATH, Self::PROTOBUF_BUILD_BYTES)
+        let path = Path::new(dir).join("buf.yaml");
+        fs::write(&path, config.buf_config())
             .await
-            .with_context(|| format!("could not write file '{}'", Self::PROTOBUF_BUILD_PATH))?;
-
-        let mut lib_content = concat!(
-            "// This file is automatically @generated by ",
-            env!("CARGO_PKG_NAME"),
-            " v",
-            env!("CARGO_PKG_VERSION"),
-            "\n\n"
-        )
-        .to_string();
+            .with_context(|| format!("could not write '{}'", path.display()))
+    }
+}
 
-        writeln!(
-            lib_content,
-            r#"include!(concat!(env!("OUT_DIR"), "/app.rs"));"#
-        )
-        .unwrap();
+/// Configuration of the generated protobuf package, in the `[proto]` section of `awto.toml`.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProtoConfig {
+    /// Name of the protobuf package, such as `acme.api`.
+    pub package: String,
+    /// Versions of the package served side by side, such as `[1, 2]`, generating a package per version
+    /// such as `acme.api.v1`. Without versions a single unversioned package is generated.
+    pub versions: Vec<u32>,
+    /// `java_package` option of the protobuf files, such as `com.acme.api.{version}`.
+    pub java_package: Option<String>,
+    /// `go_package` option of the protobuf files, such as `github.com/acme/api/gen/{version}`.
+    pub go_package: Option<String>,
+    /// `csharp_namespace` option of the protobuf files, such as `Acme.Api`.
+    pub csharp_namespace: Option<String>,
+    /// Annotates methods with the `google.api.http` route they are transcoded to, for gRPC-JSON gateways.
+    pub http_annotations: bool,
+    /// Directory of the workspace the protobuf files are exported to when the package is built, such as `proto`.
+    pub export_dir: Option<String>,
+}
 
-        fs::write(Self::PROTOBUF_LIB_PATH, lib_content)
-            .await
-            .with_context(|| format!("could not write file '{}'", Self::PROTOBUF_LIB_PATH))?;
+impl Default for ProtoConfig {
+    fn default() -> Self {
+        ProtoConfig {
+            package: ProtoConfig::DEFAULT_PACKAGE.to_string(),
+            versions: Vec::new(),
+            java_package: None,
+            go_package: None,
+            csharp_namespace: None,
+            http_annotations: false,
+            export_dir: None,
+        }
+    }
+}
+
+impl ProtoConfig {
+    const DEFAULT_PACKAGE: &'static str = "app";
+    const PACKAGE_MARKER: &'static str = "const PACKAGE: &str = \"app\";";
+    const VERSIONS_MARKER: &'static str = "const VERSIONS: &[u32] = &[];";
+    const FILE_OPTIONS_MARKER: &'static str = "const FILE_OPTIONS: &[(&str, &str)] = &[];";
+    const HTTP_ANNOTATIONS_MARKER: &'static str = "const HTTP_ANNOTATIONS: bool = false;";
+    const EXPORT_DIR_MARKER: &'static str = "const EXPORT_DIR: Option<&str> = None;";
+    /// Directory the protobuf files are exported to by `--with-buf-config` when no `export_dir` is set.
+    const DEFAULT_EXPORT_DIR: &'static str = "proto";
+
+    /// Returns the file options which are set, in the order they are written into the protobuf files.
+    fn file_options(&self) -> Vec<(&'static str, &str)> {
+        [
+            ("java_package", &self.java_package),
+            ("go_package", &self.go_package),
+            ("csharp_namespace", &self.csharp_namespace),
+        ]
+        .iter()
+        .filter_map(|(name, value)| Some((*name, value.as_deref()?)))
+        .collect()
+    }
+
+    /// Ensures the package is a dotted name, the versions are distinct and start at 1,
+    /// and the file options can be written as protobuf strings.
+    fn validate(&self) -> Result<()> {
+        let valid_part = |part: &str| {
+            part.chars()
+                .next()
+                .map(|c| c.is_ascii_alphabetic())
+                .unwrap_or(false)
+                && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        };
+        if !self.package.split('.').all(valid_part) {
+            return Err(anyhow!(
+                "invalid `proto.package` `{}`, expected a name such as `acme.api`",
+                self.package
+            ));
+        }
+        if self.versions.contains(&0) {
+            return Err(anyhow!("invalid `proto.versions`, versions start at 1"));
+        }
+        if let Some(version) = self
+            .versions
+            .iter()
+            .enumerate()
+            .find_map(|(i, version)| self.versions[..i].contains(version).then_some(version))
+        {
+            return Err(anyhow!(
+                "invalid `proto.versions`, version {} is listed twice",
+                version
+            ));
+        }
+        for (name, value) in self.file_options() {
+            if value.is_empty() || value.contains(|c: char| c == '"' || c == '\\' || c.is_control())
+            {
+                return Err(anyhow!(
+                    "invalid `proto.{}` `{}`, expected a name without quotes",
+                    name,
+                    value
+                ));
+            }
+            if value.contains("{version}") && self.versions.is_empty() {
+                return Err(anyhow!(
+                    "invalid `proto.{}`, `{{version}}` can only be used with `proto.versions`",
+                    name
+                ));
+            }
+        }
 
         Ok(())
     }
+
+    /// Renders the build script of the protobuf package for this package name and versions.
+    fn render(&self, template: &str) -> String {
+        let mut versions = self.versions.clone();
+        versions.sort_unstable();
+
+        template
+            .replacen(
+                Self::PACKAGE_MARKER,
+                &format!("const PACKAGE: &str = {:?};", self.package),
+                1,
+            )
+            .replacen(
+                Self::VERSIONS_MARKER,
+                &format!("const VERSIONS: &[u32] = &{:?};", versions),
+                1,
+            )
+            .replacen(
+                Self::FILE_OPTIONS_MARKER,
+                &format!(
+                    "const FILE_OPTIONS: &[(&str, &str)] = &{:?};",
+                    self.file_options()
+                ),
+                1,
+            )
+            .replacen(
+                Self::HTTP_ANNOTATIONS_MARKER,
+                &format!("const HTTP_ANNOTATIONS: bool = {};", self.http_annotations),
+                1,
+            )
+            .replacen(
+                Self::EXPORT_DIR_MARKER,
+                // The build script runs in the package directory, two levels below the workspace
+                &format!(
+                    "const EXPORT_DIR: Option<&str> = {:?};",
+                    self.export_dir
+                        .as_ref()
+                        .map(|dir| Path::new("../..").join(dir).display().to_string())
+                ),
+                1,
+            )
+    }
+
+    /// Returns the `buf.yaml` linting the exported protobuf files with the `DEFAULT` rules of buf.
+    ///
+    /// Methods take and return the messages of schema models rather than a request and response per method,
+    /// so the rules naming them are left out, and so is `PACKAGE_VERSION_SUFFIX` for unversioned packages.
+    fn buf_config(&self) -> String {
+        let mut config = String::from(
+            "# Generated by awto, linting the protobuf files exported by the protobuf package.\n",
+        );
+        config.push_str("version: v1\nlint:\n  use:\n    - DEFAULT\n  except:\n");
+        let mut except = vec![
+            "RPC_REQUEST_STANDARD_NAME",
+            "RPC_RESPONSE_STANDARD_NAME",
+            "RPC_REQUEST_RESPONSE_UNIQUE",
+        ];
+        if self.versions.is_empty() {
+            except.push("PACKAGE_VERSION_SUFFIX");
+        }
+        for rule in except {
+            writeln!(config, "    - {}", rule).unwrap();
+        }
+        if self.http_annotations {
+            config.push_str("  ignore:\n    - google\n");
+        }
+
+        config
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn versioned_build() {
+        let config: Config =
+            toml::from_str("[proto]\npackage = \"acme.api\"\nversions = [2, 1]").unwrap();
+        assert!(config.proto.validate().is_ok());
+
+        let build = config.proto.render(Protobuf::PROTOBUF_BUILD);
+        assert!(build.contains(r#"const PACKAGE: &str = "acme.api";"#));
+        assert!(build.contains("const VERSIONS: &[u32] = &[1, 2];"));
+        assert_eq!(
+            ProtoConfig::default().render(Protobuf::PROTOBUF_BUILD),
+            Protobuf::PROTOBUF_BUILD
+        );
+
+        let invalid = |toml: &str| {
+            toml::from_str::<Config>(toml)
+                .unwrap()
+                .proto
+                .validate()
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            invalid("[proto]\npackage = \"acme..api\""),
+            "invalid `proto.package` `acme..api`, expected a name such as `acme.api`"
+        );
+        assert_eq!(
+            invalid("[proto]\nversions = [0, 1]"),
+            "invalid `proto.versions`, versions start at 1"
+        );
+        assert_eq!(
+            invalid("[proto]\nversions = [1, 2, 1]"),
+            "invalid `proto.versions`, version 1 is listed twice"
+        );
+        assert_eq!(
+            invalid("[proto]\ngo_package = \"github.com/acme/api/{version}\""),
+            "invalid `proto.go_package`, `{version}` can only be used with `proto.versions`"
+        );
+        assert_eq!(
+            invalid("[proto]\njava_package = 'com.\"acme\"'"),
+            "invalid `proto.java_package` `com.\"acme\"`, expected a name without quotes"
+        );
+    }
+
+    #[test]
+    fn file_options() {
+        let toml = r#"
+[proto]
+package = "acme.api"
+java_package = "com.acme.api"
+csharp_namespace = "Acme.Api"
+http_annotations = true
+export_dir = "proto"
+"#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert!(config.proto.validate().is_ok());
+
+        let build = config.proto.render(Protobuf::PROTOBUF_BUILD);
+        assert!(build.contains(
+            r#"const FILE_OPTIONS: &[(&str, &str)] = &[("java_package", "com.acme.api"), ("csharp_namespace", "Acme.Api")];"#
+        ));
+        assert!(build.contains("const HTTP_ANNOTATIONS: bool = true;"));
+        assert!(build.contains(r#"const EXPORT_DIR: Option<&str> = Some("../../proto");"#));
+
+        assert_eq!(
+            config.proto.buf_config(),
+            "# Generated by awto, linting the protobuf files exported by the protobuf package.
+version: v1
+lint:
+  use:
+    - DEFAULT
+  except:
+    - RPC_REQUEST_STANDARD_NAME
+    - RPC_RESPONSE_STANDARD_NAME
+    - RPC_REQUEST_RESPONSE_UNIQUE
+    - PACKAGE_VERSION_SUFFIX
+  ignore:
+    - google
+"
+        );
+        let versioned = ProtoConfig {
+            versions: vec![1],
+            ..ProtoConfig::default()
+        };
+        assert!(!versioned.buf_config().contains("PACKAGE_VERSION_SUFFIX"));
+    }
 }
@@ -0,0 +1,539 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use clap::Parser;
+use serde::Deserialize;
+use tokio::fs;
+
+use crate::{
+    cancel::Cancellation,
+    config::{Config, CONFIG_PATH},
+    error::Error,
+    schema::{load_schema_models, ApiDocsOverlay},
+    util::WorkspaceContext,
+    Runnable,
+};
+
+use super::{
+    check_schema_package, clean_dir, compile_packages,
+    database::{DatabaseConfig, DatabasePackage},
+    ensure_package_dir_managed, package_manifest, prepare_awto_dir, profile_phase,
+    remove_stale_packages, render_schema_models, Generate, PackageFiles, PackageLog,
+    GENERATED_HEADER,
+};
+
+/// Compiles REST router package from app schema
+#[derive(Clone, Parser)]
+pub struct Rest {
+    /// Keeps generated Rust code unformatted
+    #[clap(long)]
+    pub no_format: bool,
+    /// Interleaves generated code with comments telling which model and generator produced it
+    #[clap(long)]
+    pub annotate_sources: bool,
+    /// Overwrites package directories which were not generated by awto
+    #[clap(long)]
+    pub force: bool,
+    /// Fails after compiling when any warning was reported
+    #[clap(long)]
+    pub deny_warnings: bool,
+    /// Serves the protobuf services as JSON from the REST router of the main database
+    #[clap(long)]
+    pub with_transcoding: bool,
+    /// Prints more information
+    #[clap(short, long)]
+    pub verbose: bool,
+    /// Number of warnings reported by the run
+    #[clap(skip)]
+    pub warnings: usize,
+}
+
+#[async_trait]
+impl Runnable for Rest {
+    async fn run(&mut self, cancellation: &Cancellation) -> Result<()> {
+        let commands: Vec<Box<dyn Generate>> = vec![Box::new(self.clone())];
+        compile_packages(
+            commands,
+            cancellation,
+            self.no_format,
+            self.annotate_sources,
+            self.deny_warnings,
+            &mut self.warnings,
+        )
+        .await
+    }
+
+    fn is_verbose(&self) -> bool {
+        self.verbose
+    }
+
+    fn warnings(&self) -> usize {
+        self.warnings
+    }
+}
+
+#[async_trait]
+impl Generate for Rest {
+    /// Generates the REST router packages, returning their names.
+    async fn generate(
+        &self,
+        workspace: &WorkspaceContext,
+        log: &mut PackageLog,
+    ) -> Result<Vec<String>> {
+        check_schema_package(workspace).await?;
+
+        let models = load_schema_models().await?;
+
+        // Transcoded routes call the gRPC service traits of the protobuf package
+        if self.with_transcoding && workspace.package_by_name("protobuf").is_none() {
+            return Err(Error::MissingPackage {
+                package: "protobuf".to_string(),
+                command: "awto compile protobuf".to_string(),
+            }
+            .into());
+        }
+
+        prepare_awto_dir(self.force).await?;
+
+        let (config, package_name) = DatabaseConfig::load(workspace).await?;
+        let app_config = Config::load(CONFIG_PATH).await?;
+        let database_packages = DatabasePackage::from_models(&models, &package_name, &config);
+        remove_stale_packages(&database_packages, log).await?;
+
+        let transcoding_package = self.with_transcoding.then(|| {
+            database_packages
+                .iter()
+                .position(|package| package.database_name() == DatabasePackage::DEFAULT_DATABASE)
+                .unwrap_or(0)
+        });
+
+        let mut names = Vec::new();
+        for (i, database_package) in database_packages.into_iter().enumerate() {
+            let package = RestPackage {
+                database_package,
+                with_transcoding: transcoding_package == Some(i),
+            };
+            let name = package.name();
+            let routed = || {
+                package
+                    .database_package
+                    .tables(&models)
+                    .filter(|model| !model.skip_rest)
+            };
+            config.check_visible(
+                &format!("package '{}'", name),
+                &package.database_package,
+                routed(),
+                routed().filter(|model| !model.readonly),
+            )?;
+            ensure_package_dir_managed(&package.dir(), self.force).await?;
+
+            let cargo_toml =
+                package_manifest(workspace, &package.render(package.cargo_toml())).await?;
+            profile_phase(
+                &format!("prepare {}", name),
+                Self::prepare_rest_dir(&package, &app_config, cargo_toml, log),
+            )
+            .await
+            .map_err(|source| Error::GeneratePackage {
+                package: name.clone(),
+                source,
+            })?;
+            names.push(name);
+        }
+
+        Ok(names)
+    }
+}
+
+impl Rest {
+    const REST_CARGO_TOML: &'static str = include_str!("../templates/rest/Cargo.toml.template");
+    const REST_BUILD: &'static str = include_str!("../templates/rest/build.rs.template");
+    const REST_TRANSCODING_CARGO_TOML: &'static str =
+        include_str!("../templates/rest/Cargo.toml.transcoding.template");
+    const REST_TRANSCODING_BUILD: &'static str =
+        include_str!("../templates/rest/build.rs.transcoding.template");
+    const REST_TESTS_TRANSCODING: &'static str =
+        include_str!("../templates/rest/tests/transcoding.rs.template");
+
+    async fn prepare_rest_dir(
+        package: &RestPackage,
+        config: &Config,
+        cargo_toml: String,
+        log: &mut PackageLog,
+    ) -> Result<()> {
+        let dir = package.dir();
+        let build = if package.with_transcoding {
+            Self::REST_TRANSCODING_BUILD
+        } else {
+            Self::REST_BUILD
+        };
+
+        let api_docs = ApiDocsOverlay::load(&config.api_docs).await?;
+        let mut files = vec![
+            (package.path("Cargo.toml"), cargo_toml),
+            (
+                package.path("build.rs"),
+                render_schema_models(
+                    &api_docs.render(&config.naming.render(&config.rest.render(&package.render(build)))),
+                    &config.schema,
+                    &config.features,
+                ),
+            ),
+            (
+                package.path("src/lib.rs"),
+                format!(
+                    "{}//! REST routes of the tables of the database package, generated by awto.\n\n#![allow(deprecated)]\n\ninclude!(concat!(env!(\"OUT_DIR\"), \"/app.rs\"));\n",
+                    GENERATED_HEADER
+                ),
+            ),
+        ];
+        if package.with_transcoding {
+            files.push((
+                package.path("tests/transcoding.rs"),
+                format!("{}{}", GENERATED_HEADER, Self::REST_TESTS_TRANSCODING),
+            ));
+        }
+
+        let mut package_files = PackageFiles::new(&dir, "rest")
+            .await
+            .option("with_transcoding", package.with_transcoding);
+        if Path::new(&dir).is_dir() {
+            let manifest_path = package_files.manifest_path();
+            let mut keep: Vec<&str> = files.iter().map(|(path, _)| path.as_str()).collect();
+            keep.push(&manifest_path);
+            clean_dir(&dir, &keep)
+                .await
+                .with_context(|| format!("could not clean directory '{}'", dir))?;
+        }
+
+        for (path, content) in files {
+            if let Some(parent) = Path::new(&path).parent() {
+                fs::create_dir_all(parent).await.with_context(|| {
+                    format!("could not create directory '{}'", parent.display())
+                })?;
+            }
+            package_files.write(&path, content, log).await?;
+        }
+        package_files.finish(log).await?;
+
+        if package.with_transcoding {
+            log.debug(format_args!("generated transcoding routes in '{}'", dir));
+        }
+
+        Ok(())
+    }
+}
+
+/// Configuration of the generated REST packages, in the `[rest]` section of `awto.toml`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RestConfig {
+    /// Whether models without an `#[awto(authorize(...))]` attribute allow or deny every request.
+    pub authorization: Authorization,
+}
+
+/// Policy of models without an `#[awto(authorize(...))]` attribute, set with `authorization = "..."`
+/// in the `[rest]` section.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Authorization {
+    /// Every request is allowed, without checking a scope.
+    #[default]
+    Allow,
+    /// Every request is denied, whatever scopes it was granted.
+    Deny,
+}
+
+impl RestConfig {
+    const AUTHORIZATION_MARKER: &'static str =
+        "const DEFAULT_AUTHORIZATION: DefaultAuthorization = DefaultAuthorization::Allow;";
+
+    /// Renders the build script of a REST package for this default authorization.
+    fn render(&self, template: &str) -> String {
+        match self.authorization {
+            Authorization::Allow => template.to_string(),
+            Authorization::Deny => template.replacen(
+                Self::AUTHORIZATION_MARKER,
+                "const DEFAULT_AUTHORIZATION: DefaultAuthorization = DefaultAuthorization::Deny;",
+                1,
+            ),
+        }
+    }
+}
+
+/// Naming of generated resources, in the `[naming]` section of `awto.toml`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NamingConfig {
+    /// Plurals of lowercase words or whole table names, such as `person = "persons"`, in the `[naming.plurals]` section.
+    pub plurals: BTreeMap<String, String>,
+}
+
+impl NamingConfig {
+    const PLURALS_MARKER: &'static str = "const PLURALS: &[(&str, &str)] = &[];";
+
+    /// Checks that every plural is a name which can be used in paths.
+    pub(crate) fn validate(&self) -> Result<()> {
+        let is_name = |name: &str| {
+            !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        };
+
+        for (singular, plural) in &self.plurals {
+            if !is_name(singular) {
+                return Err(anyhow!(
+                    "`naming.plurals` has an invalid name `{}`",
+                    singular
+                ));
+            }
+            if !is_name(plural) {
+                return Err(anyhow!(
+                    "`naming.plurals.{}` must be a name of letters, digits, `_` and `-`, found `{}`",
+                    singular,
+                    plural
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Renders the build script of a REST package with these plurals.
+    fn render(&self, template: &str) -> String {
+        if self.plurals.is_empty() {
+            return template.to_string();
+        }
+
+        let plurals: Vec<String> = self
+            .plurals
+            .iter()
+            .map(|(singular, plural)| format!("({:?}, {:?})", singular, plural))
+            .collect();
+        template.replacen(
+            Self::PLURALS_MARKER,
+            &format!(
+                "const PLURALS: &[(&str, &str)] = &[{}];",
+                plurals.join(", ")
+            ),
+            1,
+        )
+    }
+}
+
+/// A generated REST package, exposing the tables of a single database package.
+pub(super) struct RestPackage {
+    pub(super) database_package: DatabasePackage,
+    /// Whether the router also serves the protobuf services as JSON.
+    pub(super) with_transcoding: bool,
+}
+
+impl RestPackage {
+    /// Returns the database package name with `database` replaced by `rest`, or suffixed with `-rest`
+    /// when it does not contain `database`.
+    pub(super) fn name(&self) -> String {
+        let package_name = &self.database_package.package_name;
+        let name = if package_name.contains("database") {
+            package_name.replacen("database", "rest", 1)
+        } else {
+            format!("{}-rest", package_name)
+        };
+
+        match &self.database_package.database {
+            Some(database) => format!("{}-{}", name, database),
+            None => name,
+        }
+    }
+
+    fn dir(&self) -> String {
+        format!("./awto/{}", self.name())
+    }
+
+    fn path(&self, path: &str) -> String {
+        format!("{}/{}", self.dir(), path)
+    }
+
+    /// Returns the template of the `Cargo.toml` file of the package.
+    fn cargo_toml(&self) -> &'static str {
+        if self.with_transcoding {
+            Rest::REST_TRANSCODING_CARGO_TOML
+        } else {
+            Rest::REST_CARGO_TOML
+        }
+    }
+
+    /// Renders a template of the REST package, depending on the matching database package as `database`.
+    fn render(&self, template: &str) -> String {
+        if !self.database_package.is_renamed() {
+            return self.database_package.render(template);
+        }
+
+        let database_name = self.database_package.name();
+        self.database_package
+            .render(template)
+            .replacen("name = \"rest\"", &format!("name = \"{}\"", self.name()), 1)
+            .replacen(
+                "database = { path = \"../database\" }",
+                &format!(
+                    "database = {{ path = \"../{}\", package = \"{}\" }}",
+                    database_name, database_name
+                ),
+                1,
+            )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use awto_compile::compat::SeaOrmCompat;
+
+    use super::*;
+    use crate::compile::database::{Codegen, Localization, MapStorage, Temporal};
+
+    #[test]
+    fn render_grouped_package() {
+        let package = RestPackage {
+            database_package: DatabasePackage {
+                package_name: "database".to_string(),
+                database: Some("analytics".to_string()),
+                localization: Localization::Columns,
+                map_storage: MapStorage::Jsonb,
+                temporal: Temporal::Trigger,
+                codegen: Codegen::Buildscript,
+                sea_orm: SeaOrmCompat::LATEST,
+            },
+            with_transcoding: false,
+        };
+
+        assert_eq!(package.dir(), "./awto/rest-analytics");
+        let cargo_toml = package.render(Rest::REST_CARGO_TOML);
+        assert!(cargo_toml.contains(r#"name = "rest-analytics""#));
+        assert!(cargo_toml.contains(
+            r#"database = { path = "../database-analytics", package = "database-analytics" }"#
+        ));
+        assert!(package
+            .render(Rest::REST_BUILD)
+            .contains(r#"const DATABASE: Option<&str> = Some("analytics");"#));
+    }
+
+    #[test]
+    fn render_renamed_package() {
+        let package = RestPackage {
+            database_package: DatabasePackage {
+                package_name: "acme-database".to_string(),
+                database: None,
+                localization: Localization::Columns,
+                map_storage: MapStorage::Jsonb,
+                temporal: Temporal::Trigger,
+                codegen: Codegen::Buildscript,
+                sea_orm: SeaOrmCompat::LATEST,
+            },
+            with_transcoding: false,
+        };
+
+        assert_eq!(package.dir(), "./awto/acme-rest");
+        let cargo_toml = package.render(Rest::REST_CARGO_TOML);
+        assert!(cargo_toml.contains(r#"name = "acme-rest""#));
+        assert!(cargo_toml
+            .contains(r#"database = { path = "../acme-database", package = "acme-database" }"#));
+        assert!(package
+            .render(Rest::REST_BUILD)
+            .contains("const DATABASE: Option<&str> = None;"));
+
+        let package = RestPackage {
+            database_package: DatabasePackage {
+                package_name: "database".to_string(),
+                database: None,
+                localization: Localization::Table,
+                map_storage: MapStorage::Jsonb,
+                temporal: Temporal::Trigger,
+                codegen: Codegen::Buildscript,
+                sea_orm: SeaOrmCompat::LATEST,
+            },
+            with_transcoding: false,
+        };
+        assert!(package
+            .render(Rest::REST_BUILD)
+            .contains("const LOCALIZATION: Localization = Localization::Table;"));
+
+        let package = RestPackage {
+            database_package: DatabasePackage {
+                package_name: "acme_db".to_string(),
+                database: Some("analytics".to_string()),
+                localization: Localization::Columns,
+                map_storage: MapStorage::Jsonb,
+                temporal: Temporal::Trigger,
+                codegen: Codegen::Buildscript,
+                sea_orm: SeaOrmCompat::LATEST,
+            },
+            with_transcoding: false,
+        };
+        assert_eq!(package.name(), "acme_db-rest-analytics");
+    }
+
+    #[test]
+    fn render_transcoding_package() {
+        let package = RestPackage {
+            database_package: DatabasePackage {
+                package_name: "database".to_string(),
+                database: Some("main".to_string()),
+                localization: Localization::Table,
+                map_storage: MapStorage::Jsonb,
+                temporal: Temporal::Trigger,
+                codegen: Codegen::Buildscript,
+                sea_orm: SeaOrmCompat::LATEST,
+            },
+            with_transcoding: true,
+        };
+
+        let cargo_toml = package.render(Rest::REST_TRANSCODING_CARGO_TOML);
+        assert!(cargo_toml.contains(r#"name = "rest-main""#));
+        assert!(cargo_toml.contains(r#"protobuf = { path = "../protobuf" }"#));
+        assert!(cargo_toml.contains(r#"service = { path = "../../service" }"#));
+
+        let build = package.render(Rest::REST_TRANSCODING_BUILD);
+        assert!(build.contains("compile_rest_with_transcoding("));
+        assert!(build.contains(r#"const DATABASE: Option<&str> = Some("main");"#));
+        assert!(build.contains("const LOCALIZATION: Localization = Localization::Table;"));
+    }
+
+    #[test]
+    fn default_authorization() {
+        let config: Config = toml::from_str("[rest]\nauthorization = \"deny\"").unwrap();
+        assert_eq!(config.rest.authorization, Authorization::Deny);
+        assert_eq!(Config::default().rest.authorization, Authorization::Allow);
+        assert!(toml::from_str::<Config>("[rest]\nauthorization = \"public\"").is_err());
+
+        for build in [Rest::REST_BUILD, Rest::REST_TRANSCODING_BUILD] {
+            assert!(config.rest.render(build).contains(
+                "const DEFAULT_AUTHORIZATION: DefaultAuthorization = DefaultAuthorization::Deny;"
+            ));
+            assert_eq!(RestConfig::default().render(build), build);
+        }
+    }
+
+    #[test]
+    fn plural_overrides() {
+        let config: Config =
+            toml::from_str("[naming.plurals]\nperson = \"persons\"\nOrderLine = \"order-lines\"")
+                .unwrap();
+        config.naming.validate().unwrap();
+
+        for build in [Rest::REST_BUILD, Rest::REST_TRANSCODING_BUILD] {
+            assert!(config
+                .naming
+                .render(build)
+                .contains(r#"const PLURALS: &[(&str, &str)] = &[("OrderLine", "order-lines"), ("person", "persons")];"#));
+            assert_eq!(NamingConfig::default().render(build), build);
+        }
+
+        let config: Config = toml::from_str("[naming.plurals]\nperson = \"\"").unwrap();
+        assert!(config.naming.validate().is_err());
+        let config: Config = toml::from_str("[naming.plurals]\nperson = \"people/all\"").unwrap();
+        assert!(config.naming.validate().is_err());
+        assert!(toml::from_str::<Config>("[naming]\nplural = {}").is_err());
+    }
+}
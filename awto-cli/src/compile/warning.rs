@@ -0,0 +1,258 @@
+use std::fmt;
+
+use colored::Colorize;
+use serde::Deserialize;
+
+use crate::schema::{Location, SchemaModel, SCHEMA_LIB_PATH};
+
+/// Identifiers of the warnings printed by the build scripts of generated packages,
+/// which are the `Warning` constants of awto-compile.
+pub const WARNING_IDS: [&str; 6] = [
+    "unknown_column_reference",
+    "unregistered_extension",
+    "deprecated_column_kept",
+    "empty_schema",
+    "unpartitioned_table",
+    "forced_narrowing",
+];
+
+/// Start of the warnings printed by awto-compile, `awto warning[id] Model.field: message`.
+const WARNING_MARKER: &str = "awto warning[";
+
+/// A warning printed by the build script of a generated package.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Warning {
+    pub id: String,
+    /// Generated package whose build printed the warning, if known.
+    pub package: Option<String>,
+    pub model: Option<String>,
+    pub field: Option<String>,
+    /// Location of the model or field in the schema lib, once found.
+    pub location: Option<Location>,
+    /// Path of the schema lib declaring the model, once found.
+    pub source: Option<String>,
+    pub message: String,
+}
+
+impl Warning {
+    /// Parses the warning of a line of cargo output for the build of `packages`,
+    /// returning `None` for lines which are not warnings of awto-compile.
+    ///
+    /// Cargo prefixes the warnings of build scripts with their package, as in `warning: database@0.1.0: ...`,
+    /// which tells which of the packages printed it.
+    pub fn parse(line: &str, packages: &[String]) -> Option<Warning> {
+        let marker = line.find(WARNING_MARKER)?;
+        let package = build_script_package(&line[..marker], packages);
+        let start = marker + WARNING_MARKER.len();
+        let (id, rest) = line[start..].split_once(']')?;
+        let (item, message) = match rest.strip_prefix(':') {
+            Some(message) => ("", message),
+            None => rest.split_once(':')?,
+        };
+        let mut item = item.trim().splitn(2, '.');
+        let model = item.next().filter(|model| !model.is_empty());
+        let field = item.next();
+
+        Some(Warning {
+            id: id.to_string(),
+            package: package.cloned(),
+            model: model.map(str::to_string),
+            field: field.map(str::to_string),
+            location: None,
+            source: None,
+            message: message.trim().to_string(),
+        })
+    }
+
+    /// Returns whether the warning is allowed by `config`,
+    /// or with `#[awto(allow(...))]` on its model or field in `models`.
+    pub fn is_allowed(&self, models: &[SchemaModel], config: &BuildConfig) -> bool {
+        if config.allow.contains(&self.id) {
+            return true;
+        }
+
+        let model = match models
+            .iter()
+            .find(|model| Some(&model.name) == self.model.as_ref())
+        {
+            Some(model) => model,
+            None => return false,
+        };
+        let field = model
+            .fields
+            .iter()
+            .find(|field| Some(&field.name) == self.field.as_ref());
+
+        model
+            .allow
+            .iter()
+            .chain(field.iter().flat_map(|field| &field.allow))
+            .any(|lint| *lint == self.id)
+    }
+
+    /// Sets the location of the warning to its field, or else its model, in `models`.
+    pub fn locate(&mut self, models: &[SchemaModel]) {
+        let model = match models
+            .iter()
+            .find(|model| Some(&model.name) == self.model.as_ref())
+        {
+            Some(model) => model,
+            None => return,
+        };
+        let field = model
+            .fields
+            .iter()
+            .find(|field| Some(&field.name) == self.field.as_ref());
+
+        self.location = Some(field.map_or(model.location, |field| field.location));
+        self.source = Some(model.lib_path().to_string());
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = format!("warning[{}]", self.id).yellow().bold();
+        match (&self.model, &self.field) {
+            (Some(model), Some(field)) => {
+                writeln!(f, "{}: `{}.{}` {}", level, model, field, self.message)?
+            }
+            (Some(model), None) => writeln!(f, "{}: `{}` {}", level, model, self.message)?,
+            _ => writeln!(f, "{}: {}", level, self.message)?,
+        }
+
+        match (self.location, &self.package) {
+            (Some(location), _) => {
+                let source = self.source.as_deref().unwrap_or(SCHEMA_LIB_PATH);
+                write!(f, "  {} {}:{}", "-->".blue().bold(), source, location)
+            }
+            (None, Some(package)) => write!(f, "  {} package '{}'", "-->".blue().bold(), package),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// Returns the package of `packages` whose build script printed a line starting with `prefix`,
+/// such as `warning: database@0.1.0: `, which is the only package when a single one is built.
+pub fn build_script_package<'a>(prefix: &str, packages: &'a [String]) -> Option<&'a String> {
+    match packages {
+        [package] => Some(package),
+        _ => packages
+            .iter()
+            .find(|package| prefix.contains(&format!(" {}@", package))),
+    }
+}
+
+/// The `[build]` section of `awto.toml`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BuildConfig {
+    /// Fails compile commands with warnings, like `--deny-warnings`.
+    pub deny_warnings: bool,
+    /// Warnings allowed everywhere, by identifier.
+    pub allow: Vec<String>,
+    /// Makes the generated packages members of a workspace of their own in `./awto`, which the root workspace excludes.
+    pub nested_workspace: bool,
+    /// Whether the generated packages in `./awto` are committed or ignored by git, unchecked when unset.
+    pub generated_files: Option<GeneratedFiles>,
+}
+
+/// Policy of the `[build]` section for the generated packages in `./awto`, set with `generated_files = "..."`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GeneratedFiles {
+    /// The packages are committed, so a checkout builds without running awto.
+    Committed,
+    /// The packages are ignored, and regenerated after every checkout.
+    Ignored,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_warnings() {
+        let warning = Warning::parse(
+            "warning: database@0.1.0: awto warning[unknown_column_reference] Product.price_cents: computed column references 'price_cent', which is not a column of table 'product'",
+            &["database".to_string()],
+        )
+        .unwrap();
+        assert_eq!(warning.id, "unknown_column_reference");
+        assert_eq!(warning.package.as_deref(), Some("database"));
+        assert_eq!(warning.model.as_deref(), Some("Product"));
+        assert_eq!(warning.field.as_deref(), Some("price_cents"));
+        assert_eq!(
+            warning.message,
+            "computed column references 'price_cent', which is not a column of table 'product'"
+        );
+
+        let warning = Warning::parse(
+            "warning: awto warning[unregistered_extension]: extension 'src/ext/order.rs' does not belong to a registered database table",
+            &["database".to_string()],
+        )
+        .unwrap();
+        assert_eq!(warning.id, "unregistered_extension");
+        assert_eq!(warning.package.as_deref(), Some("database"));
+        assert_eq!(warning.model, None);
+        assert_eq!(warning.field, None);
+
+        let packages = ["database".to_string(), "acme-database".to_string()];
+        let line = "warning: acme-database@0.1.0: awto warning[unregistered_extension]: extension 'src/ext/order.rs' does not belong to a registered database table";
+        let warning = Warning::parse(line, &packages).unwrap();
+        assert_eq!(warning.package.as_deref(), Some("acme-database"));
+        let line = "warning: awto warning[unregistered_extension]: extension 'src/ext/order.rs' does not belong to a registered database table";
+        assert_eq!(Warning::parse(line, &packages).unwrap().package, None);
+
+        assert_eq!(
+            Warning::parse("warning: unused variable: `x`", &packages),
+            None
+        );
+        assert_eq!(
+            Warning::parse("   Compiling database v0.1.0", &packages),
+            None
+        );
+    }
+
+    #[test]
+    fn allowed_warnings() {
+        let models = crate::schema::parse_schema_models(
+            r#"
+            schema! {
+                #[database_table]
+                pub struct Product {
+                    pub id: Uuid,
+                    #[awto(computed = "price_cent * 100", stored, allow(unknown_column_reference))]
+                    pub price_cents: i64,
+                    #[awto(computed = "x", stored)]
+                    pub other: i64,
+                }
+            }
+            "#,
+        )
+        .unwrap();
+        let warning = |field: &str| Warning {
+            id: "unknown_column_reference".to_string(),
+            package: Some("database".to_string()),
+            model: Some("Product".to_string()),
+            field: Some(field.to_string()),
+            location: None,
+            source: None,
+            message: String::new(),
+        };
+
+        let config = BuildConfig::default();
+        assert!(warning("price_cents").is_allowed(&models, &config));
+        assert!(!warning("other").is_allowed(&models, &config));
+
+        let config = BuildConfig {
+            allow: vec!["unknown_column_reference".to_string()],
+            ..BuildConfig::default()
+        };
+        assert!(warning("other").is_allowed(&models, &config));
+
+        let mut other = warning("other");
+        other.locate(&models);
+        assert_eq!(other.location, Some(models[0].fields[2].location));
+        assert_eq!(other.source.as_deref(), Some(SCHEMA_LIB_PATH));
+    }
+}
@@ -0,0 +1,354 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, Result};
+use log::{error, info, warn};
+use tokio::{fs, process::Command, time};
+
+use crate::{
+    cancel::Cancellation,
+    config::CONFIG_PATH,
+    error::{Error, ErrorCode},
+    schema::SchemaModel,
+};
+
+/// Directory of the schema sources watched for changes.
+const SCHEMA_SRC_DIR: &str = "./schema/src";
+
+/// Interval between checks of the watched files.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Time without further changes after which a series of saves is regenerated once.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// What to do after each successful regeneration in watch mode.
+#[derive(Clone, Debug, Default)]
+pub struct WatchHooks {
+    /// Shell command run with `AWTO_CHANGED_MODELS` and `AWTO_PACKAGES` set.
+    pub on_success: Option<String>,
+    /// File whose contents and modification time are updated.
+    pub signal_file: Option<PathBuf>,
+}
+
+/// Regenerates with `compile` whenever the schema sources or `awto.toml` change, until `cancellation` is cancelled.
+///
+/// `compile` returns the names of the packages it built along with the models of the schema.
+/// Failures are logged and the watcher waits for the next change, and [`WatchHooks`] run after each success.
+/// A cancelled compilation stops the watcher instead.
+pub async fn watch<F, Fut>(
+    hooks: &WatchHooks,
+    cancellation: &Cancellation,
+    mut compile: F,
+) -> Result<()>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<(Vec<String>, Vec<SchemaModel>)>>,
+{
+    let watched = [Path::new(SCHEMA_SRC_DIR), Path::new(CONFIG_PATH)];
+    let mut stamps = file_stamps(&watched).await?;
+    let mut previous_models = Vec::new();
+
+    loop {
+        match compile().await {
+            Ok((packages, models)) => {
+                let changed = changed_models(&previous_models, &models);
+                previous_models = models;
+                hooks.run(&changed, &packages).await;
+            }
+            Err(err) if Error::code_of(&err) == Some(ErrorCode::Cancelled) => return Err(err),
+            Err(err) => error!("{:#}", err),
+        }
+
+        info!(
+            "watching '{}' and '{}' for changes",
+            SCHEMA_SRC_DIR, CONFIG_PATH
+        );
+        stamps = cancellation
+            .run(wait_for_change(&watched, stamps, POLL_INTERVAL, DEBOUNCE))
+            .await?;
+        info!("schema changed, regenerating");
+    }
+}
+
+impl WatchHooks {
+    /// Runs the hooks after a successful regeneration, logging their failures without returning them.
+    pub async fn run(&self, changed_models: &[String], packages: &[String]) {
+        if let Some(command) = &self.on_success {
+            match shell_command(command)
+                .env("AWTO_CHANGED_MODELS", changed_models.join(","))
+                .env("AWTO_PACKAGES", packages.join(","))
+                .status()
+                .await
+            {
+                Ok(status) if status.success() => {}
+                Ok(status) => warn!("--on-success command `{}` failed ({})", command, status),
+                Err(err) => warn!("could not run --on-success command `{}`: {}", command, err),
+            }
+        }
+
+        if let Some(path) = &self.signal_file {
+            if let Err(err) = touch(path).await {
+                warn!(
+                    "could not update signal file '{}': {:#}",
+                    path.display(),
+                    err
+                );
+            }
+        }
+    }
+}
+
+#[cfg(not(windows))]
+fn shell_command(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+#[cfg(windows)]
+fn shell_command(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
+
+/// Writes the current time to `path`, so watchers of its modification time or contents see it change.
+async fn touch(path: &Path) -> Result<()> {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent)
+            .await
+            .with_context(|| format!("could not create directory '{}'", parent.display()))?;
+    }
+    fs::write(path, format!("{}\n", chrono::Utc::now().to_rfc3339())).await?;
+
+    Ok(())
+}
+
+/// Returns the names of the models added, removed or changed in `models` since `previous`, sorted.
+pub fn changed_models(previous: &[SchemaModel], models: &[SchemaModel]) -> Vec<String> {
+    let mut changed: Vec<_> = models
+        .iter()
+        .filter(|model| !previous.contains(model))
+        .chain(
+            previous
+                .iter()
+                .filter(|model| !models.iter().any(|other| other.name == model.name)),
+        )
+        .map(|model| model.name.clone())
+        .collect();
+    changed.sort();
+    changed.dedup();
+
+    changed
+}
+
+/// Modification time and length of every watched file, by path.
+type FileStamps = BTreeMap<PathBuf, (Option<SystemTime>, u64)>;
+
+/// Returns the stamps of the files at `paths`, reading directories recursively and skipping missing paths.
+async fn file_stamps(paths: &[&Path]) -> Result<FileStamps> {
+    let mut stamps = BTreeMap::new();
+    let mut pending: Vec<PathBuf> = paths.iter().map(|path| path.to_path_buf()).collect();
+    while let Some(path) = pending.pop() {
+        let metadata = match fs::metadata(&path).await {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(err) => {
+                return Err(err).with_context(|| format!("could not read '{}'", path.display()))
+            }
+        };
+        if metadata.is_dir() {
+            let mut entries = fs::read_dir(&path)
+                .await
+                .with_context(|| format!("could not read directory '{}'", path.display()))?;
+            while let Some(entry) = entries.next_entry().await? {
+                pending.push(entry.path());
+            }
+        } else {
+            stamps.insert(path, (metadata.modified().ok(), metadata.len()));
+        }
+    }
+
+    Ok(stamps)
+}
+
+/// Waits until the files at `paths` differ from `stamps` and then stay unchanged for `debounce`,
+/// checking them every `interval`, and returns their new stamps.
+async fn wait_for_change(
+    paths: &[&Path],
+    stamps: FileStamps,
+    interval: Duration,
+    debounce: Duration,
+) -> Result<FileStamps> {
+    let mut current = stamps.clone();
+    let mut quiet_since = None;
+    loop {
+        time::sleep(interval).await;
+        let next = file_stamps(paths).await?;
+        if next != current {
+            current = next;
+            quiet_since = Some(time::Instant::now());
+        } else if current != stamps
+            && quiet_since.is_some_and(|since: time::Instant| since.elapsed() >= debounce)
+        {
+            return Ok(current);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn model(name: &str, database: &str) -> SchemaModel {
+        SchemaModel {
+            name: name.to_string(),
+            database: Some(database.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn changed() {
+        let previous = vec![
+            model("Event", "main"),
+            model("Order", "main"),
+            model("Product", "main"),
+        ];
+        let models = vec![
+            model("Event", "analytics"),
+            model("Product", "main"),
+            model("Review", "main"),
+        ];
+
+        assert_eq!(
+            changed_models(&previous, &models),
+            ["Event", "Order", "Review"]
+        );
+        assert_eq!(
+            changed_models(&[], &previous),
+            ["Event", "Order", "Product"]
+        );
+        assert!(changed_models(&models, &models).is_empty());
+    }
+
+    #[tokio::test]
+    async fn hooks() {
+        let dir = std::env::temp_dir().join(format!("awto-cli-watch-hooks-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let output = dir.join("output");
+        let hooks = WatchHooks {
+            on_success: Some(format!(
+                "echo \"$AWTO_CHANGED_MODELS $AWTO_PACKAGES\" > '{}'",
+                output.display()
+            )),
+            signal_file: Some(dir.join("signal/reload")),
+        };
+        hooks
+            .run(
+                &["Event".to_string(), "Product".to_string()],
+                &["database".to_string()],
+            )
+            .await;
+
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "Event,Product database\n"
+        );
+        assert!(dir.join("signal/reload").is_file());
+
+        // A failing hook is reported without stopping the other hooks
+        std::fs::remove_file(dir.join("signal/reload")).unwrap();
+        let failing = WatchHooks {
+            on_success: Some("exit 3".to_string()),
+            ..hooks
+        };
+        failing.run(&[], &[]).await;
+        assert!(dir.join("signal/reload").is_file());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn debounced_changes() {
+        let dir =
+            std::env::temp_dir().join(format!("awto-cli-watch-debounce-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("models")).unwrap();
+        std::fs::write(dir.join("lib.rs"), "").unwrap();
+        let paths = [dir.as_path()];
+        let stamps = file_stamps(&paths).await.unwrap();
+
+        let writes = {
+            let dir = dir.clone();
+            tokio::spawn(async move {
+                for i in 0..5 {
+                    std::fs::write(dir.join("models/product.rs"), "x".repeat(i + 1)).unwrap();
+                    time::sleep(Duration::from_millis(20)).await;
+                }
+            })
+        };
+        let interval = Duration::from_millis(10);
+        let debounce = Duration::from_millis(200);
+        let changed = wait_for_change(&paths, stamps, interval, debounce)
+            .await
+            .unwrap();
+        writes.await.unwrap();
+
+        // The series of writes is seen once, after the last of them
+        assert_eq!(changed[&dir.join("models/product.rs")].1, 5);
+        let again = time::timeout(
+            Duration::from_millis(400),
+            wait_for_change(&paths, changed, interval, debounce),
+        )
+        .await;
+        assert!(again.is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn cancelled() {
+        let hooks = WatchHooks::default();
+        let cancellation = Cancellation::new();
+        let compiles = std::cell::Cell::new(0);
+        {
+            let cancellation = cancellation.clone();
+            tokio::spawn(async move {
+                time::sleep(Duration::from_millis(50)).await;
+                cancellation.cancel();
+            });
+        }
+
+        // Waiting for changes stops once cancelled
+        let result = time::timeout(
+            Duration::from_secs(10),
+            watch(&hooks, &cancellation, || {
+                compiles.set(compiles.get() + 1);
+                async { Ok((Vec::new(), Vec::new())) }
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            Error::code_of(&result.unwrap_err()),
+            Some(ErrorCode::Cancelled)
+        );
+        assert_eq!(compiles.get(), 1);
+
+        // So does a cancelled compilation, which is not retried
+        let result = watch(&hooks, &Cancellation::new(), || async {
+            Err(Error::Cancelled.into())
+        })
+        .await;
+        assert_eq!(
+            Error::code_of(&result.unwrap_err()),
+            Some(ErrorCode::Cancelled)
+        );
+    }
+}
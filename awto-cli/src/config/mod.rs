@@ -0,0 +1,528 @@
+use std::{
+    env, fmt,
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use anyhow::{anyhow, Context, Result};
+use clap::Parser;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tokio::fs;
+
+use crate::{
+    compile::{BuildConfig, DatabaseConfig, NamingConfig, PackagesConfig, ProtoConfig, RestConfig},
+    error::Error,
+    schema::{ApiDocsConfig, FeaturesConfig, LintConfig, SchemaConfig},
+    verify::VerifyConfig,
+};
+
+pub use self::show::Show;
+
+mod show;
+
+/// Inspects the configuration of awto.toml
+#[derive(Parser)]
+pub struct ConfigCommand {
+    #[clap(subcommand)]
+    pub subcmd: SubCommand,
+}
+
+#[derive(Parser)]
+pub enum SubCommand {
+    Show(Show),
+}
+
+/// Path of the optional configuration file of the app.
+pub const CONFIG_PATH: &str = "./awto.toml";
+
+/// Environment variable selecting the profile of the configuration, set by `--profile`.
+pub const PROFILE_ENV: &str = "AWTO_PROFILE";
+
+/// Profile used when none is selected.
+pub const DEFAULT_PROFILE: &str = "dev";
+
+/// Organization-wide defaults compiled into the cli from the file at `AWTO_DIST_CONFIG`, empty unless it was set.
+///
+/// They are the lowest layer of the configuration, below `awto.toml`, its profiles and flags.
+const DIST_CONFIG: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/dist-config.toml"));
+
+/// Whether the profile was selected with `--profile`, rather than with [`PROFILE_ENV`].
+static PROFILE_FLAG: AtomicBool = AtomicBool::new(false);
+
+/// Profiles which can be selected without a `[profile.<name>]` section.
+const BUILTIN_PROFILES: [&str; 3] = ["dev", "ci", "prod"];
+
+/// Keys which apply to every profile, rejected in `[profile.<name>]` sections.
+const GLOBAL_KEYS: [&str; 6] = [
+    "lint",
+    "profile",
+    "database.package_name",
+    "proto",
+    "schema",
+    "api_docs",
+];
+
+/// Selects the profile passed with `--profile`, setting [`PROFILE_ENV`] so the build scripts of generated packages
+/// see the same profile.
+pub fn select_profile(profile: &str) {
+    env::set_var(PROFILE_ENV, profile);
+    PROFILE_FLAG.store(true, Ordering::Relaxed);
+}
+
+/// Returns the selected profile, from `AWTO_PROFILE` or [`DEFAULT_PROFILE`].
+pub fn active_profile() -> String {
+    env::var(PROFILE_ENV)
+        .ok()
+        .filter(|profile| !profile.is_empty())
+        .unwrap_or_else(|| DEFAULT_PROFILE.to_string())
+}
+
+/// Configuration of the app, read from `awto.toml` in the root of the workspace.
+///
+/// The keys of the `[profile.<name>]` section of the active profile override the top-level keys,
+/// such as `[profile.prod.database]` overriding keys of `[database]`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Rules of `awto schema lint`, in the `[lint]` section.
+    pub lint: LintConfig,
+    /// Schema packages the models are read from, in the `[schema]` section.
+    pub schema: SchemaConfig,
+    /// Generated database packages, in the `[database]` section.
+    pub database: DatabaseConfig,
+    /// Warnings and workspace of compile commands, in the `[build]` section.
+    pub build: BuildConfig,
+    /// Generated protobuf package and its versions, in the `[proto]` section.
+    pub proto: ProtoConfig,
+    /// Generated REST packages, in the `[rest]` section.
+    pub rest: RestConfig,
+    /// Naming of generated resources, in the `[naming]` section.
+    pub naming: NamingConfig,
+    /// `Cargo.toml` files of every generated package, in the `[packages]` section.
+    pub packages: PackagesConfig,
+    /// Checks left out of `awto verify`, in the `[verify]` section.
+    pub verify: VerifyConfig,
+    /// Features whose models and fields are generated, in the `[features]` section.
+    pub features: FeaturesConfig,
+    /// Overlay file of the documentation of the generated APIs, in the `[api_docs]` section.
+    pub api_docs: ApiDocsConfig,
+    /// Name of the profile the configuration was loaded with.
+    #[serde(skip)]
+    pub profile: String,
+    /// Hash of the profile and the configuration it resolved to, which changes whenever the effective options do.
+    #[serde(skip)]
+    pub fingerprint: String,
+}
+
+impl Config {
+    /// Loads the configuration file at `path` with the [`active_profile`],
+    /// falling back to the default configuration when it does not exist.
+    pub async fn load(path: impl AsRef<Path>) -> Result<Config> {
+        let path = path.as_ref();
+        let profile = active_profile();
+
+        Self::read(path, &profile).await.map_err(|source| {
+            Error::Config {
+                path: path.display().to_string(),
+                source,
+            }
+            .into()
+        })
+    }
+
+    async fn read(path: &Path, profile: &str) -> Result<Config> {
+        let mut table = dist_table()?;
+        merge_tables(&mut table, file_table(path).await?);
+        Self::from_toml(table, profile)
+    }
+
+    /// Returns the configuration of `table` with the keys of the section of `profile` merged into it.
+    fn from_toml(mut table: toml::value::Table, profile: &str) -> Result<Config> {
+        let profiles = match table.remove("profile") {
+            Some(toml::Value::Table(profiles)) => profiles,
+            Some(_) => {
+                return Err(anyhow!(
+                    "invalid configuration: `profile` must be a table of profiles"
+                ))
+            }
+            None => toml::value::Table::new(),
+        };
+        for (name, section) in &profiles {
+            let section = section.as_table().ok_or_else(|| {
+                anyhow!("invalid configuration: `profile.{}` must be a table", name)
+            })?;
+            if let Some(key) = GLOBAL_KEYS.iter().find(|key| contains_key(section, key)) {
+                return Err(anyhow!(
+                    "`{}` cannot be set in [profile.{}], as it applies to every profile",
+                    key,
+                    name
+                ));
+            }
+        }
+
+        match profiles.get(profile) {
+            Some(toml::Value::Table(overrides)) => merge_tables(&mut table, overrides.clone()),
+            _ if BUILTIN_PROFILES.contains(&profile) => {}
+            _ => {
+                let mut known: Vec<&str> = BUILTIN_PROFILES.to_vec();
+                known.extend(
+                    profiles
+                        .keys()
+                        .map(String::as_str)
+                        .filter(|name| !BUILTIN_PROFILES.contains(name)),
+                );
+                return Err(anyhow!(
+                    "unknown profile `{}`, expected one of {}",
+                    profile,
+                    known.join(", ")
+                ));
+            }
+        }
+
+        let effective = toml::to_string(&table).context("invalid configuration")?;
+        let mut config: Config = toml::Value::Table(table)
+            .try_into()
+            .context("invalid configuration")?;
+        config.database.validate()?;
+        config.naming.validate()?;
+        config.profile = profile.to_string();
+        config.fingerprint = format!(
+            "{:x}",
+            Sha256::digest(format!("{}\n{}", profile, effective).as_bytes())
+        );
+
+        Ok(config)
+    }
+}
+
+/// Returns the organization-wide defaults compiled into the cli.
+fn dist_table() -> Result<toml::value::Table> {
+    toml::from_slice(DIST_CONFIG).context("invalid configuration compiled into the cli")
+}
+
+/// Returns the keys of the configuration file at `path`, which are empty when it does not exist.
+async fn file_table(path: &Path) -> Result<toml::value::Table> {
+    if !path.is_file() {
+        return Ok(toml::value::Table::new());
+    }
+
+    let bytes = fs::read(path).await.context("could not read file")?;
+    toml::from_slice(&bytes).context("invalid configuration")
+}
+
+/// Layer of the configuration a setting comes from, printed by `awto config show --origin`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Origin {
+    /// Defaults of awto itself.
+    Default,
+    /// Organization-wide defaults compiled into the cli.
+    Dist,
+    /// Top-level keys of `awto.toml`.
+    File,
+    /// Keys of the `[profile.<name>]` section of `awto.toml`, or of the defaults compiled into the cli.
+    Profile(String),
+    /// An environment variable, such as `AWTO_PROFILE`.
+    Env(&'static str),
+    /// A flag of the command line, such as `--profile`.
+    Flag(&'static str),
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::Default => write!(f, "default"),
+            Origin::Dist => write!(f, "dist defaults"),
+            Origin::File => write!(f, "awto.toml"),
+            Origin::Profile(profile) => write!(f, "profile {}", profile),
+            Origin::Env(var) => write!(f, "env {}", var),
+            Origin::Flag(flag) => write!(f, "flag {}", flag),
+        }
+    }
+}
+
+/// An effective setting of the configuration, by its dotted key such as `database.package_name`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Setting {
+    pub key: String,
+    pub value: toml::Value,
+    pub origin: Origin,
+}
+
+/// Returns every setting of the configuration file at `path` and of the defaults compiled into the cli with the
+/// [`active_profile`], along with the layer each comes from, sorted by key.
+pub async fn settings(path: impl AsRef<Path>) -> Result<Vec<Setting>> {
+    let path = path.as_ref();
+    let file = file_table(path).await.map_err(|source| Error::Config {
+        path: path.display().to_string(),
+        source,
+    })?;
+    // Loading the configuration validates it, which the layers alone do not
+    Config::load(path).await?;
+
+    let profile = active_profile();
+    let profile_origin = if PROFILE_FLAG.load(Ordering::Relaxed) {
+        Origin::Flag("--profile")
+    } else if env::var(PROFILE_ENV).is_ok_and(|profile| !profile.is_empty()) {
+        Origin::Env(PROFILE_ENV)
+    } else {
+        Origin::Default
+    };
+
+    Ok(layered_settings(
+        dist_table()?,
+        file,
+        &profile,
+        profile_origin,
+    ))
+}
+
+/// Returns the settings of the `dist` defaults overridden by the `file`, and then by the section of `profile` in either,
+/// with `profile` itself selected from `profile_origin`.
+fn layered_settings(
+    dist: toml::value::Table,
+    file: toml::value::Table,
+    profile: &str,
+    profile_origin: Origin,
+) -> Vec<Setting> {
+    let mut settings = std::collections::BTreeMap::new();
+    let mut profile_sections = Vec::new();
+    for (mut table, origin) in [(dist, Origin::Dist), (file, Origin::File)] {
+        if let Some(toml::Value::Table(mut profiles)) = table.remove("profile") {
+            if let Some(toml::Value::Table(section)) = profiles.remove(profile) {
+                profile_sections.push(section);
+            }
+        }
+        flatten(&table, "", &origin, &mut settings);
+    }
+    for section in profile_sections {
+        flatten(
+            &section,
+            "",
+            &Origin::Profile(profile.to_string()),
+            &mut settings,
+        );
+    }
+    settings.insert(
+        "profile".to_string(),
+        (toml::Value::String(profile.to_string()), profile_origin),
+    );
+
+    settings
+        .into_iter()
+        .map(|(key, (value, origin))| Setting { key, value, origin })
+        .collect()
+}
+
+/// Inserts the values of `table` into `settings` by their dotted key under `prefix`, replacing the ones of lower layers.
+fn flatten(
+    table: &toml::value::Table,
+    prefix: &str,
+    origin: &Origin,
+    settings: &mut std::collections::BTreeMap<String, (toml::Value, Origin)>,
+) {
+    for (key, value) in table {
+        let key = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match value {
+            toml::Value::Table(table) if !table.is_empty() => {
+                flatten(table, &key, origin, settings)
+            }
+            value => {
+                settings.insert(key, (value.clone(), origin.clone()));
+            }
+        }
+    }
+}
+
+/// Returns whether `table` contains the dotted `key`, such as `database.package_name`.
+fn contains_key(table: &toml::value::Table, key: &str) -> bool {
+    match key.split_once('.') {
+        Some((section, key)) => table
+            .get(section)
+            .and_then(toml::Value::as_table)
+            .is_some_and(|section| contains_key(section, key)),
+        None => table.contains_key(key),
+    }
+}
+
+/// Merges `overrides` into `table`, replacing values and merging nested tables.
+fn merge_tables(table: &mut toml::value::Table, overrides: toml::value::Table) {
+    for (key, value) in overrides {
+        match (table.get_mut(&key), value) {
+            (Some(toml::Value::Table(section)), toml::Value::Table(overrides)) => {
+                merge_tables(section, overrides)
+            }
+            (_, value) => {
+                table.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn config(toml: &str, profile: &str) -> Result<Config> {
+        Config::from_toml(toml::from_str(toml).unwrap(), profile)
+    }
+
+    const CONFIG: &str = r#"
+        [database]
+        package_name = "shop-database"
+        url_file = "/run/secrets/database_url"
+
+        [build]
+        allow = ["unregistered_extension"]
+
+        [profile.dev.database]
+        url_file = ".database_url"
+
+        [profile.prod]
+        build = { deny_warnings = true }
+        database = { localization = "table" }
+        "#;
+
+    #[test]
+    fn profile_overrides() {
+        let dev = config(CONFIG, "dev").unwrap();
+        assert_eq!(dev.profile, "dev");
+        assert_eq!(dev.database.url_file.as_deref(), Some(".database_url"));
+        assert_eq!(dev.database.package_name.as_deref(), Some("shop-database"));
+        assert!(!dev.build.deny_warnings);
+
+        let prod = config(CONFIG, "prod").unwrap();
+        assert_eq!(
+            prod.database.url_file.as_deref(),
+            Some("/run/secrets/database_url")
+        );
+        assert_eq!(format!("{:?}", prod.database.localization), "Table");
+        assert!(prod.build.deny_warnings);
+        assert_eq!(prod.build.allow, ["unregistered_extension"]);
+
+        let ci = config(CONFIG, "ci").unwrap();
+        assert_eq!(
+            ci.database.url_file.as_deref(),
+            Some("/run/secrets/database_url")
+        );
+        assert_ne!(ci.fingerprint, prod.fingerprint);
+        assert_ne!(ci.fingerprint, dev.fingerprint);
+        assert_eq!(ci.fingerprint, config(CONFIG, "ci").unwrap().fingerprint);
+    }
+
+    #[test]
+    fn profile_errors() {
+        let err = config("[profile.staging.lint]\nmax_columns = 12", "dev").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`lint` cannot be set in [profile.staging], as it applies to every profile"
+        );
+
+        let err = config("[profile.prod.database]\npackage_name = \"db\"", "dev").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`database.package_name` cannot be set in [profile.prod], as it applies to every profile"
+        );
+
+        let err = config("[profile.staging.build]\ndeny_warnings = true", "qa").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unknown profile `qa`, expected one of dev, ci, prod, staging"
+        );
+        assert_eq!(
+            config("[profile.staging.build]\ndeny_warnings = true", "staging")
+                .unwrap()
+                .profile,
+            "staging"
+        );
+
+        let err = config(
+            "[profile.prod.schema]\npackages = [\"schema-billing\"]",
+            "dev",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`schema` cannot be set in [profile.prod], as it applies to every profile"
+        );
+    }
+
+    #[test]
+    fn schema_packages() {
+        let config = config(
+            "[schema]\npackages = [\"schema-billing\", \"schema-catalog\"]\nduplicates = \"namespace\"",
+            "dev",
+        )
+        .unwrap();
+        assert_eq!(config.schema.packages, ["schema-billing", "schema-catalog"]);
+        assert_eq!(
+            config.schema.duplicates,
+            crate::schema::Duplicates::Namespace
+        );
+        assert_eq!(
+            Config::default().schema.duplicates,
+            crate::schema::Duplicates::Error
+        );
+        assert_eq!(
+            config.schema.package_names(),
+            ["schema-billing", "schema-catalog"]
+        );
+        assert!(Config::default().schema.package_names().is_empty());
+        assert_eq!(Config::default().schema.manifest_path(), "./models.yaml");
+
+        let manifest = self::config(
+            "[schema]\nsource = \"manifest\"\npath = \"catalog/models.json\"\npackages = [\"schema\"]",
+            "dev",
+        )
+        .unwrap();
+        assert_eq!(
+            manifest.schema.source,
+            crate::schema::SchemaSource::Manifest
+        );
+        assert_eq!(manifest.schema.manifest_path(), "./catalog/models.json");
+        assert_eq!(
+            manifest.schema.package_names(),
+            ["schema", "schema-manifest"]
+        );
+    }
+
+    #[test]
+    fn dist_defaults() {
+        let dist: toml::value::Table = toml::from_str(
+            "[database]\npackage_name = \"org-database\"\nurl_file = \"/etc/org/database_url\"\n\n\
+            [profile.ci.build]\ndeny_warnings = true",
+        )
+        .unwrap();
+        let file: toml::value::Table = toml::from_str(CONFIG).unwrap();
+
+        let mut table = dist.clone();
+        merge_tables(&mut table, file.clone());
+        let ci = Config::from_toml(table, "ci").unwrap();
+        assert_eq!(ci.database.package_name.as_deref(), Some("shop-database"));
+        assert!(ci.build.deny_warnings);
+        let dev = Config::from_toml(dist.clone(), "dev").unwrap();
+        assert_eq!(
+            dev.database.url_file.as_deref(),
+            Some("/etc/org/database_url")
+        );
+
+        let settings = layered_settings(dist, file, "dev", Origin::Flag("--profile"));
+        let origins: Vec<_> = settings
+            .iter()
+            .map(|setting| format!("{} = {}  # {}", setting.key, setting.value, setting.origin))
+            .collect();
+        assert_eq!(
+            origins,
+            [
+                "build.allow = [\"unregistered_extension\"]  # awto.toml",
+                "database.package_name = \"shop-database\"  # awto.toml",
+                "database.url_file = \".database_url\"  # profile dev",
+                "profile = \"dev\"  # flag --profile",
+            ]
+        );
+    }
+}
@@ -0,0 +1,61 @@
+use std::fmt::Write;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use clap::Parser;
+
+use crate::{cancel::Cancellation, Runnable};
+
+use super::{settings, Setting, CONFIG_PATH};
+
+/// Prints the effective settings of awto.toml, with the organization defaults and the selected profile applied
+#[derive(Parser)]
+pub struct Show {
+    /// Prints the layer each setting comes from, such as `awto.toml` or `profile ci`
+    #[clap(long)]
+    pub origin: bool,
+    /// Prints more information
+    #[clap(short, long)]
+    pub verbose: bool,
+}
+
+#[async_trait]
+impl Runnable for Show {
+    async fn run(&mut self, _cancellation: &Cancellation) -> Result<()> {
+        out!("{}", text(&settings(CONFIG_PATH).await?, self.origin));
+
+        Ok(())
+    }
+
+    fn is_verbose(&self) -> bool {
+        self.verbose
+    }
+}
+
+/// Renders each of `settings` as `key = value`, followed by its origin when `origin` is set.
+fn text(settings: &[Setting], origin: bool) -> String {
+    let width = settings
+        .iter()
+        .map(|setting| setting.key.len() + setting.value.to_string().len())
+        .max()
+        .unwrap_or(0);
+
+    let mut text = String::new();
+    for setting in settings {
+        let line = format!("{} = {}", setting.key, setting.value);
+        if origin {
+            writeln!(
+                text,
+                "{:<width$}  # {}",
+                line,
+                setting.origin,
+                width = width + 3
+            )
+            .unwrap();
+        } else {
+            writeln!(text, "{}", line).unwrap();
+        }
+    }
+
+    text
+}
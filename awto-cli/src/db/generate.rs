@@ -0,0 +1,225 @@
+use std::{
+    env,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use clap::Parser;
+use log::info;
+
+use super::{model_table, run_admin};
+use crate::{
+    cancel::Cancellation, compile::AdminDatabase, error::Error, schema::load_schema_models,
+    util::WorkspaceContext, Runnable,
+};
+
+/// Time the admin is given to save its checkpoint once cancelled, before it is killed.
+const CHECKPOINT_GRACE: Duration = Duration::from_secs(30);
+
+/// Inserts random rows into the table of a model with the admin package, such as to load test queries
+#[derive(Parser)]
+pub struct Generate {
+    /// Model whose table the rows are inserted into, such as `Product`
+    #[clap(long)]
+    pub model: String,
+    /// Number of rows inserted
+    #[clap(long, default_value = "1000")]
+    pub rows: u64,
+    /// Number of rows inserted by each transaction
+    #[clap(long, default_value = "5000")]
+    pub batch: usize,
+    /// Seed of the random values, the same seed generating the same rows into the same tables.
+    /// Chosen at random and printed by default
+    #[clap(long)]
+    pub seed: Option<u64>,
+    /// Probability of nullable fields being null, between 0 and 1
+    #[clap(long, default_value = "0.1", parse(try_from_str = parse_probability))]
+    pub null_probability: f64,
+    /// Number of rows inserted into each empty table which required fields reference
+    #[clap(long, default_value = "100")]
+    pub parent_rows: u64,
+    /// Saves the progress into this file every few batches and once cancelled, to resume the generation
+    #[clap(long)]
+    pub checkpoint: Option<PathBuf>,
+    /// Number of committed batches between saved checkpoints
+    #[clap(long, default_value = "1")]
+    pub checkpoint_every: usize,
+    /// Resumes the generation from its checkpoint, inserting the rows the interrupted generation would have
+    #[clap(long, requires = "checkpoint")]
+    pub resume: bool,
+    /// Url of the database, instead of `DATABASE_URL` or the variable of the database managed by the admin
+    #[clap(long)]
+    pub database_url: Option<String>,
+    /// Prints more information
+    #[clap(short, long)]
+    pub verbose: bool,
+}
+
+#[async_trait]
+impl Runnable for Generate {
+    async fn run(&mut self, cancellation: &Cancellation) -> Result<()> {
+        let models = load_schema_models().await?;
+        let workspace = WorkspaceContext::load(".").await?;
+        let database =
+            AdminDatabase::resolve(&workspace, &models, self.database_url.as_deref()).await?;
+        let (_, table) =
+            model_table(&models, &self.model, &database).ok_or_else(|| Error::UnknownModel {
+                model: self.model.clone(),
+                database: database.name.clone(),
+            })?;
+
+        let seed = self.seed.unwrap_or_else(random_seed);
+        if self.resume {
+            info!(
+                "resuming the generation of {} rows of table '{}'",
+                self.rows, table
+            );
+        } else {
+            info!(
+                "generating {} rows of table '{}' with seed {}",
+                self.rows, table, seed
+            );
+        }
+        // The admin saves its checkpoint on the Ctrl-C it receives as well
+        let grace = match self.checkpoint {
+            Some(_) => CHECKPOINT_GRACE,
+            None => Duration::ZERO,
+        };
+        let status = run_admin(
+            &self.admin_args(&table, seed)?,
+            &database,
+            cancellation,
+            grace,
+        )
+        .await?;
+        if !status.success() {
+            return Err(Error::GenerateRows { table, status }.into());
+        }
+
+        Ok(())
+    }
+
+    fn is_verbose(&self) -> bool {
+        self.verbose
+    }
+}
+
+impl Generate {
+    /// Returns the arguments of the `generate` command of the admin for `table`.
+    ///
+    /// The checkpoint is passed as an absolute path, as the admin runs in `./awto`.
+    fn admin_args(&self, table: &str, seed: u64) -> Result<Vec<String>> {
+        let mut args = vec![
+            table.to_string(),
+            "generate".to_string(),
+            format!("--rows={}", self.rows),
+            format!("--batch={}", self.batch),
+            format!("--seed={}", seed),
+            format!("--null-probability={}", self.null_probability),
+            format!("--parent-rows={}", self.parent_rows),
+        ];
+        if let Some(checkpoint) = &self.checkpoint {
+            args.push(format!(
+                "--checkpoint={}",
+                env::current_dir()?.join(checkpoint).display()
+            ));
+            args.push(format!("--checkpoint-every={}", self.checkpoint_every));
+            if self.resume {
+                args.push("--resume".to_string());
+            }
+        }
+
+        Ok(args)
+    }
+}
+
+/// Returns a seed changing on every run, printed so the rows can be generated again.
+fn random_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+/// Parses a `--null-probability` between 0 and 1.
+fn parse_probability(probability: &str) -> Result<f64, String> {
+    match probability.parse::<f64>() {
+        Ok(probability) if (0.0..=1.0).contains(&probability) => Ok(probability),
+        _ => Err(format!(
+            "probability '{}' is not a number between 0 and 1",
+            probability
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn admin_args() {
+        let generate = Generate::parse_from([
+            "generate", "--model", "Product", "--rows", "1000000", "--batch", "5000",
+        ]);
+        assert_eq!(
+            generate.admin_args("product", 42).unwrap(),
+            [
+                "product",
+                "generate",
+                "--rows=1000000",
+                "--batch=5000",
+                "--seed=42",
+                "--null-probability=0.1",
+                "--parent-rows=100",
+            ]
+        );
+        assert!(Generate::try_parse_from([
+            "generate",
+            "--model",
+            "Product",
+            "--null-probability",
+            "1.5"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn checkpoint_args() {
+        let generate = Generate::parse_from([
+            "generate",
+            "--model",
+            "Product",
+            "--checkpoint",
+            "/tmp/product.checkpoint",
+            "--checkpoint-every",
+            "10",
+            "--resume",
+        ]);
+        let args = generate.admin_args("product", 42).unwrap();
+        assert_eq!(
+            args[7..],
+            [
+                "--checkpoint=/tmp/product.checkpoint",
+                "--checkpoint-every=10",
+                "--resume"
+            ]
+        );
+
+        // Relative checkpoints are resolved against the directory awto runs in
+        let generate = Generate::parse_from([
+            "generate",
+            "--model",
+            "Product",
+            "--checkpoint",
+            "product.checkpoint",
+        ]);
+        let args = generate.admin_args("product", 42).unwrap();
+        let checkpoint = env::current_dir().unwrap().join("product.checkpoint");
+        assert_eq!(args[7], format!("--checkpoint={}", checkpoint.display()));
+        assert_eq!(args.len(), 9);
+
+        assert!(Generate::try_parse_from(["generate", "--model", "Product", "--resume"]).is_err());
+    }
+}
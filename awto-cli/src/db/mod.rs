@@ -0,0 +1,129 @@
+use std::{
+    process::{ExitStatus, Stdio},
+    time::Duration,
+};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use clap::Parser;
+use heck::SnakeCase;
+use log::debug;
+
+use crate::{
+    cancel::{child_output_with_grace, Cancellation},
+    compile::AdminDatabase,
+    error::Error,
+    schema::SchemaModel,
+    Runnable,
+};
+
+pub use self::{generate::Generate, partitions::Partitions};
+
+mod generate;
+pub mod partitions;
+
+/// Manages the rows of the app database
+#[derive(Parser)]
+pub struct Db {
+    #[clap(subcommand)]
+    pub subcmd: SubCommand,
+}
+
+#[derive(Parser)]
+pub enum SubCommand {
+    Generate(Generate),
+    Partitions(Partitions),
+}
+
+#[async_trait]
+impl Runnable for Db {
+    async fn run(&mut self, cancellation: &Cancellation) -> Result<()> {
+        match &mut self.subcmd {
+            SubCommand::Generate(generate) => generate.run(cancellation).await,
+            SubCommand::Partitions(partitions) => partitions.run(cancellation).await,
+        }
+    }
+}
+
+/// Returns the model named `name` along with its table, by its name in the schema or its table,
+/// when the table is managed by the admin.
+fn model_table<'a>(
+    models: &'a [SchemaModel],
+    name: &str,
+    database: &AdminDatabase,
+) -> Option<(&'a SchemaModel, String)> {
+    models
+        .iter()
+        .filter(|model| database.manages(model))
+        .map(|model| (model, model.name.to_snake_case()))
+        .find(|(model, table)| model.name == name || table == name)
+}
+
+/// Runs the admin package in `./awto` with `args`, connecting to `database`, and returns its exit status.
+///
+/// Once cancelled, the admin is given `grace` to exit on its own before it is killed.
+async fn run_admin(
+    args: &[String],
+    database: &AdminDatabase,
+    cancellation: &Cancellation,
+    grace: Duration,
+) -> Result<ExitStatus, Error> {
+    let mut command = tokio::process::Command::new("cargo");
+    command
+        .current_dir("./awto")
+        .args(["run", "--release", "--quiet", "-p", "admin", "--"])
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit());
+    if let Some(url) = &database.url {
+        debug!("using {} {}", database.url_var, url);
+        command.env(&database.url_var, url.as_str());
+    }
+
+    let child = command.spawn().map_err(Error::Cargo)?;
+    let output = child_output_with_grace(child, cancellation, grace)
+        .await
+        .map_err(Error::Cargo)?
+        .ok_or(Error::Cancelled)?;
+
+    Ok(output.status)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn model(name: &str, database: Option<&str>, is_database_table: bool) -> SchemaModel {
+        SchemaModel {
+            name: name.to_string(),
+            database: database.map(ToString::to_string),
+            is_database_table,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn model_tables() {
+        let models = vec![
+            model("Product", None, true),
+            model("OrderLine", None, true),
+            model("Event", Some("analytics"), true),
+            model("Address", None, false),
+        ];
+        let database = AdminDatabase {
+            name: "main".to_string(),
+            url_var: "DATABASE_URL".to_string(),
+            url: None,
+        };
+        let table = |name| model_table(&models, name, &database).map(|(_, table)| table);
+
+        assert_eq!(table("Product").as_deref(), Some("product"));
+        assert_eq!(table("OrderLine").as_deref(), Some("order_line"));
+        assert_eq!(table("order_line").as_deref(), Some("order_line"));
+        // Tables of other databases are not managed by the admin
+        assert_eq!(table("Event"), None);
+        assert_eq!(table("Address"), None);
+        assert_eq!(table("Customer"), None);
+    }
+}
@@ -0,0 +1,161 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use clap::Parser;
+use log::info;
+
+use super::{model_table, run_admin};
+use crate::{
+    cancel::Cancellation, compile::AdminDatabase, error::Error, schema::load_schema_models,
+    util::WorkspaceContext, Runnable,
+};
+
+/// Maintains the partitions of partitioned tables
+#[derive(Parser)]
+pub struct Partitions {
+    #[clap(subcommand)]
+    pub subcmd: SubCommand,
+}
+
+#[derive(Parser)]
+pub enum SubCommand {
+    Ensure(Ensure),
+}
+
+#[async_trait]
+impl Runnable for Partitions {
+    async fn run(&mut self, cancellation: &Cancellation) -> Result<()> {
+        match &mut self.subcmd {
+            SubCommand::Ensure(ensure) => ensure.run(cancellation).await,
+        }
+    }
+}
+
+/// Creates the missing partitions of a partitioned table with the admin package, such as ahead of the coming months
+#[derive(Parser)]
+pub struct Ensure {
+    /// Partitioned model whose partitions are created, such as `Event`
+    #[clap(long)]
+    pub model: String,
+    /// Month or day held by the last partition created, such as `2025-12` or `2025-12-31`
+    #[clap(long, parse(try_from_str = parse_date))]
+    pub through: NaiveDate,
+    /// Month or day held by the first partition created, the current one by default
+    #[clap(long, parse(try_from_str = parse_date))]
+    pub from: Option<NaiveDate>,
+    /// Url of the database, instead of `DATABASE_URL` or the variable of the database managed by the admin
+    #[clap(long)]
+    pub database_url: Option<String>,
+    /// Prints more information
+    #[clap(short, long)]
+    pub verbose: bool,
+}
+
+#[async_trait]
+impl Runnable for Ensure {
+    async fn run(&mut self, cancellation: &Cancellation) -> Result<()> {
+        let models = load_schema_models().await?;
+        let workspace = WorkspaceContext::load(".").await?;
+        let database =
+            AdminDatabase::resolve(&workspace, &models, self.database_url.as_deref()).await?;
+        let (model, table) =
+            model_table(&models, &self.model, &database).ok_or_else(|| Error::UnknownModel {
+                model: self.model.clone(),
+                database: database.name.clone(),
+            })?;
+        if !model.partitioned {
+            return Err(Error::NotPartitioned(model.name.clone()).into());
+        }
+
+        info!(
+            "creating partitions of table '{}' through {}",
+            table, self.through
+        );
+        let status = run_admin(
+            &self.admin_args(&table),
+            &database,
+            cancellation,
+            Duration::ZERO,
+        )
+        .await?;
+        if !status.success() {
+            return Err(Error::EnsurePartitions { table, status }.into());
+        }
+
+        Ok(())
+    }
+
+    fn is_verbose(&self) -> bool {
+        self.verbose
+    }
+}
+
+impl Ensure {
+    /// Returns the arguments of the `ensure-partitions` command of the admin for `table`.
+    fn admin_args(&self, table: &str) -> Vec<String> {
+        let mut args = vec![
+            table.to_string(),
+            "ensure-partitions".to_string(),
+            format!("--through={}", self.through),
+        ];
+        if let Some(from) = self.from {
+            args.push(format!("--from={}", from));
+        }
+
+        args
+    }
+}
+
+/// Parses a `--through` or `--from` date, where a month such as `2025-12` stands for its first day.
+fn parse_date(date: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .or_else(|_| NaiveDate::parse_from_str(&format!("{}-01", date), "%Y-%m-%d"))
+        .map_err(|_| {
+            format!(
+                "date '{}' is not a month such as 2025-12 or a day such as 2025-12-31",
+                date
+            )
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn admin_args() {
+        let ensure = Ensure::parse_from(["ensure", "--model", "Event", "--through", "2025-12"]);
+        assert_eq!(
+            ensure.admin_args("event"),
+            ["event", "ensure-partitions", "--through=2025-12-01"]
+        );
+
+        let ensure = Ensure::parse_from([
+            "ensure",
+            "--model",
+            "Event",
+            "--through",
+            "2025-12-31",
+            "--from",
+            "2024-01",
+        ]);
+        assert_eq!(
+            ensure.admin_args("event"),
+            [
+                "event",
+                "ensure-partitions",
+                "--through=2025-12-31",
+                "--from=2024-01-01"
+            ]
+        );
+        assert!(
+            Ensure::try_parse_from(["ensure", "--model", "Event", "--through", "2025-13"]).is_err()
+        );
+        assert!(
+            Ensure::try_parse_from(["ensure", "--model", "Event", "--through", "december"])
+                .is_err()
+        );
+    }
+}
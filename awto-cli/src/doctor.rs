@@ -0,0 +1,194 @@
+use std::{collections::HashMap, process::Stdio};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use awto_compile::compat::SeaOrmCompat;
+use clap::Parser;
+use log::{info, warn};
+use serde::Deserialize;
+
+use crate::{
+    cancel::{child_output, Cancellation},
+    config::{Config, CONFIG_PATH},
+    error::Error,
+    Runnable,
+};
+
+/// Checks the workspace for problems with the generated packages, such as conflicting versions of sea-orm
+#[derive(Parser)]
+pub struct Doctor {
+    /// Prints more information
+    #[clap(short, long)]
+    pub verbose: bool,
+}
+
+#[async_trait]
+impl Runnable for Doctor {
+    async fn run(&mut self, cancellation: &Cancellation) -> Result<()> {
+        let sea_orm = Config::load(CONFIG_PATH).await?.database.sea_orm;
+        let metadata = cargo_metadata(cancellation).await?;
+
+        let conflicts = sea_orm_conflicts(&metadata, sea_orm);
+        for conflict in &conflicts {
+            warn!(
+                "package '{}' depends on sea-orm {}, but the generated packages use sea-orm {}",
+                conflict.package, conflict.version, sea_orm
+            );
+        }
+        if !conflicts.is_empty() {
+            return Err(Error::SeaOrmConflict {
+                configured: sea_orm.to_string(),
+                count: conflicts.len(),
+            }
+            .into());
+        }
+
+        info!("the workspace uses sea-orm {} as configured", sea_orm);
+        Ok(())
+    }
+
+    fn is_verbose(&self) -> bool {
+        self.verbose
+    }
+}
+
+/// Output of `cargo metadata --format-version 1`, reduced to the resolved dependencies.
+#[derive(Debug, Deserialize)]
+struct Metadata {
+    packages: Vec<MetadataPackage>,
+    workspace_members: Vec<String>,
+    resolve: Option<MetadataResolve>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetadataPackage {
+    id: String,
+    name: String,
+    version: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetadataResolve {
+    nodes: Vec<MetadataNode>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetadataNode {
+    id: String,
+    deps: Vec<MetadataDep>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetadataDep {
+    pkg: String,
+}
+
+/// A member of the workspace depending on a version of sea-orm other than the configured one.
+#[derive(Debug, PartialEq, Eq)]
+struct SeaOrmConflict {
+    package: String,
+    version: String,
+}
+
+async fn cargo_metadata(cancellation: &Cancellation) -> Result<Metadata> {
+    let child = tokio::process::Command::new("cargo")
+        .args(["metadata", "--format-version", "1"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .map_err(Error::Cargo)?;
+    let output = child_output(child, cancellation)
+        .await
+        .map_err(Error::Cargo)?
+        .ok_or(Error::Cancelled)?;
+    if !output.status.success() {
+        anyhow::bail!("cargo metadata failed ({})", output.status);
+    }
+
+    serde_json::from_slice(&output.stdout).context("cargo metadata printed invalid metadata")
+}
+
+/// Returns the members of the workspace of `metadata` depending on a sea-orm which is not of version `sea_orm`.
+fn sea_orm_conflicts(metadata: &Metadata, sea_orm: SeaOrmCompat) -> Vec<SeaOrmConflict> {
+    let packages: HashMap<&str, &MetadataPackage> = metadata
+        .packages
+        .iter()
+        .map(|package| (package.id.as_str(), package))
+        .collect();
+    let nodes = metadata.resolve.iter().flat_map(|resolve| &resolve.nodes);
+
+    let mut conflicts = Vec::new();
+    for node in nodes.filter(|node| metadata.workspace_members.contains(&node.id)) {
+        let dependencies = node
+            .deps
+            .iter()
+            .filter_map(|dep| packages.get(dep.pkg.as_str()));
+        for dependency in dependencies.filter(|dependency| dependency.name == "sea-orm") {
+            if !sea_orm.matches(&dependency.version) {
+                conflicts.push(SeaOrmConflict {
+                    package: packages
+                        .get(node.id.as_str())
+                        .map_or_else(|| node.id.clone(), |package| package.name.clone()),
+                    version: dependency.version.clone(),
+                });
+            }
+        }
+    }
+
+    conflicts
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn conflicts() {
+        let metadata: Metadata = serde_json::from_str(
+            r#"{
+                "packages": [
+                    { "id": "service 0.1.0 (path+file:///app/service)", "name": "service", "version": "0.1.0" },
+                    { "id": "database 0.1.0 (path+file:///app/awto/database)", "name": "database", "version": "0.1.0" },
+                    { "id": "sea-orm 0.2.6 (git+https://github.com/Acidic9/sea-orm.git)", "name": "sea-orm", "version": "0.2.6" },
+                    { "id": "sea-orm 1.1.0 (registry+https://github.com/rust-lang/crates.io-index)", "name": "sea-orm", "version": "1.1.0" }
+                ],
+                "workspace_members": [
+                    "service 0.1.0 (path+file:///app/service)",
+                    "database 0.1.0 (path+file:///app/awto/database)"
+                ],
+                "resolve": {
+                    "nodes": [
+                        {
+                            "id": "service 0.1.0 (path+file:///app/service)",
+                            "deps": [{ "pkg": "sea-orm 1.1.0 (registry+https://github.com/rust-lang/crates.io-index)" }]
+                        },
+                        {
+                            "id": "database 0.1.0 (path+file:///app/awto/database)",
+                            "deps": [{ "pkg": "sea-orm 0.2.6 (git+https://github.com/Acidic9/sea-orm.git)" }]
+                        },
+                        {
+                            "id": "sea-orm 1.1.0 (registry+https://github.com/rust-lang/crates.io-index)",
+                            "deps": []
+                        }
+                    ]
+                }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            sea_orm_conflicts(&metadata, SeaOrmCompat::V0_2),
+            [SeaOrmConflict {
+                package: "service".to_string(),
+                version: "1.1.0".to_string(),
+            }]
+        );
+
+        let metadata = Metadata {
+            resolve: None,
+            ..metadata
+        };
+        assert!(sea_orm_conflicts(&metadata, SeaOrmCompat::V0_2).is_empty());
+    }
+}
@@ -0,0 +1,488 @@
+use std::{fmt, process::ExitStatus};
+
+use serde::{Serialize, Serializer};
+
+/// Errors produced by the cli.
+///
+/// Each variant has a stable [`ErrorCode`] which can be relied on by tools wrapping the cli,
+/// and explained in more detail with `awto explain <code>`.
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("could not load schema Cargo.toml file from '{path}'")]
+    SchemaManifest {
+        path: String,
+        #[source]
+        source: anyhow::Error,
+    },
+    #[error("schema package must be named 'schema' but is named '{0}'")]
+    SchemaPackageName(String),
+    #[error("schema package '{0}' listed in the [schema] section of awto.toml is not a member of the workspace")]
+    UnknownSchemaPackage(String),
+    #[error("no database table or sub table model has a field named '{0}', expected one of `Model` or `Model.field`")]
+    UnknownSchemaField(String),
+    #[error("--expand requires cargo-expand, install it with `cargo install cargo-expand` or use a nightly toolchain")]
+    ExpandUnavailable,
+    #[error("could not read the models of the expanded schema package '{package}'")]
+    Expand {
+        package: String,
+        #[source]
+        source: anyhow::Error,
+    },
+    #[error("could not load service Cargo.toml file from '{path}'")]
+    ServiceManifest {
+        path: String,
+        #[source]
+        source: anyhow::Error,
+    },
+    #[error("service package must be named 'service' but is named '{0}'")]
+    ServicePackageName(String),
+    #[error("could not read schema models from '{path}'")]
+    SchemaSource {
+        path: String,
+        #[source]
+        source: anyhow::Error,
+    },
+    #[error("no database named '{0}' is declared in the schema")]
+    UnknownDatabase(String),
+    #[error("could not load awto config file from '{path}'")]
+    Config {
+        path: String,
+        #[source]
+        source: anyhow::Error,
+    },
+    #[error("invalid {component} in the database url from {origin}: {message}")]
+    DatabaseUrl {
+        origin: String,
+        component: &'static str,
+        message: String,
+    },
+    #[error("no database table model named '{model}' in the database '{database}' managed by the admin package")]
+    UnknownModel { model: String, database: String },
+    #[error("model '{0}' is not partitioned, partition its table with `#[awto(partition(...))]`")]
+    NotPartitioned(String),
+    #[error("no database url is set, pass --database-url or set `{0}`")]
+    MissingDatabaseUrl(String),
+    #[error("schema lib '{0}' already exists, move it or pass --force to overwrite it")]
+    SchemaExists(String),
+    #[error("could not generate package '{package}'")]
+    GeneratePackage {
+        package: String,
+        #[source]
+        source: anyhow::Error,
+    },
+    #[error("directory '{0}' was not generated by awto, move it or pass --force to overwrite it")]
+    UnmanagedDirectory(String),
+    #[error("package '{package}' must be compiled first with `{command}`")]
+    MissingPackage { package: String, command: String },
+    #[error("external generator '{generator}' failed")]
+    Generator {
+        generator: String,
+        #[source]
+        source: anyhow::Error,
+    },
+    #[error("{} file(s) generated into '{dir}' are out of date, run the generator without --check", .files.len())]
+    GeneratedOutdated { dir: String, files: Vec<String> },
+    #[error("{consumer} uses {item} of package '{package}', which is not public, set {setting}")]
+    HiddenDatabaseItem {
+        consumer: String,
+        package: String,
+        item: String,
+        setting: String,
+    },
+    #[error("could not add package '{package}' to the workspace")]
+    Workspace {
+        package: String,
+        #[source]
+        source: anyhow::Error,
+    },
+    #[error("could not use the nested workspace of the generated packages in './awto'")]
+    NestedWorkspace {
+        #[source]
+        source: anyhow::Error,
+    },
+    #[error("{count} package(s) of the workspace depend on a sea-orm other than the configured {configured}")]
+    SeaOrmConflict { configured: String, count: usize },
+    #[error("generating rows of table '{table}' failed ({status})")]
+    GenerateRows { table: String, status: ExitStatus },
+    #[error("creating partitions of table '{table}' failed ({status})")]
+    EnsurePartitions { table: String, status: ExitStatus },
+    #[error("cargo build failed for package '{}' ({status})", .packages.join("', '"))]
+    Build {
+        packages: Vec<String>,
+        status: ExitStatus,
+    },
+    #[error("could not run cargo")]
+    Cargo(#[source] std::io::Error),
+    #[error("cargo publish failed for package '{package}' ({status})")]
+    Publish { package: String, status: ExitStatus },
+    #[error("compilation reported {0} warning(s), which are denied")]
+    WarningsDenied(usize),
+    #[error("schema lint found {0} error(s)")]
+    LintDenied(usize),
+    #[error("no schema snapshot found in './awto/snapshots'")]
+    NoSnapshot,
+    #[error("could not read schema snapshot from '{since}'")]
+    Snapshot {
+        since: String,
+        #[source]
+        source: anyhow::Error,
+    },
+    #[error("schema formatting would change {0} file(s), run `awto schema fmt`")]
+    SchemaUnformatted(usize),
+    #[error("the schema changed since snapshot '{0}', take a new one with `awto schema snapshot`")]
+    SnapshotOutdated(String),
+    #[error("{failed} of {checks} verify check(s) failed")]
+    VerifyFailed { failed: usize, checks: usize },
+    #[error("model '{name}' is declared in both schema packages '{first}' and '{second}'")]
+    DuplicateSchemaModel {
+        name: String,
+        first: String,
+        second: String,
+    },
+    #[error(
+        "the models are incompatible with dialect `{dialect}`: {count} degradation(s) are denied"
+    )]
+    DialectIncompatible { dialect: String, count: usize },
+    #[error("{item} at {location} uses {referenced} at {referenced_location}, which is only generated with feature '{feature}', gate both behind the same feature")]
+    GatedRelation {
+        item: String,
+        location: String,
+        referenced: String,
+        referenced_location: String,
+        feature: String,
+    },
+    #[error("the api docs of '{path}' do not match the schema:{problems}")]
+    InvalidApiDocs { path: String, problems: String },
+    #[error("could not find the version of awto which generated the project, pass it with --from")]
+    UnknownVersion,
+    #[error("unknown error code '{0}'")]
+    UnknownCode(String),
+    #[error("--database-url sets the url of a single database, select it with --only-db")]
+    AmbiguousDatabaseUrl,
+    #[error("cancelled")]
+    Cancelled,
+    #[error("{0}")]
+    InvalidArguments(String),
+}
+
+impl Error {
+    /// Returns the stable code of this error.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Error::SchemaManifest { .. } => ErrorCode::SchemaManifest,
+            Error::SchemaPackageName(_) => ErrorCode::SchemaPackageName,
+            Error::UnknownSchemaPackage(_) => ErrorCode::UnknownSchemaPackage,
+            Error::UnknownSchemaField(_) => ErrorCode::UnknownSchemaField,
+            Error::ExpandUnavailable => ErrorCode::ExpandUnavailable,
+            Error::Expand { .. } => ErrorCode::Expand,
+            Error::ServiceManifest { .. } => ErrorCode::ServiceManifest,
+            Error::ServicePackageName(_) => ErrorCode::ServicePackageName,
+            Error::SchemaSource { .. } => ErrorCode::SchemaSource,
+            Error::UnknownDatabase(_) => ErrorCode::UnknownDatabase,
+            Error::Config { .. } => ErrorCode::Config,
+            Error::DatabaseUrl { .. } => ErrorCode::DatabaseUrl,
+            Error::UnknownModel { .. } => ErrorCode::UnknownModel,
+            Error::NotPartitioned(_) => ErrorCode::NotPartitioned,
+            Error::MissingDatabaseUrl(_) => ErrorCode::MissingDatabaseUrl,
+            Error::SchemaExists(_) => ErrorCode::SchemaExists,
+            Error::GeneratePackage { .. } => ErrorCode::GeneratePackage,
+            Error::UnmanagedDirectory(_) => ErrorCode::UnmanagedDirectory,
+            Error::MissingPackage { .. } => ErrorCode::MissingPackage,
+            Error::Generator { .. } => ErrorCode::Generator,
+            Error::GeneratedOutdated { .. } => ErrorCode::GeneratedOutdated,
+            Error::HiddenDatabaseItem { .. } => ErrorCode::HiddenDatabaseItem,
+            Error::Workspace { .. } => ErrorCode::Workspace,
+            Error::Build { .. } => ErrorCode::Build,
+            Error::Cargo(_) => ErrorCode::Cargo,
+            Error::Publish { .. } => ErrorCode::Publish,
+            Error::NestedWorkspace { .. } => ErrorCode::NestedWorkspace,
+            Error::SeaOrmConflict { .. } => ErrorCode::SeaOrmConflict,
+            Error::WarningsDenied(_) => ErrorCode::WarningsDenied,
+            Error::LintDenied(_) => ErrorCode::LintDenied,
+            Error::NoSnapshot => ErrorCode::NoSnapshot,
+            Error::Snapshot { .. } => ErrorCode::Snapshot,
+            Error::SchemaUnformatted(_) => ErrorCode::SchemaUnformatted,
+            Error::SnapshotOutdated(_) => ErrorCode::SnapshotOutdated,
+            Error::VerifyFailed { .. } => ErrorCode::VerifyFailed,
+            Error::DuplicateSchemaModel { .. } => ErrorCode::DuplicateSchemaModel,
+            Error::DialectIncompatible { .. } => ErrorCode::DialectIncompatible,
+            Error::GatedRelation { .. } => ErrorCode::GatedRelation,
+            Error::InvalidApiDocs { .. } => ErrorCode::InvalidApiDocs,
+            Error::UnknownVersion => ErrorCode::UnknownVersion,
+            Error::GenerateRows { .. } => ErrorCode::GenerateRows,
+            Error::EnsurePartitions { .. } => ErrorCode::EnsurePartitions,
+            Error::UnknownCode(_) => ErrorCode::UnknownCode,
+            Error::AmbiguousDatabaseUrl => ErrorCode::AmbiguousDatabaseUrl,
+            Error::Cancelled => ErrorCode::Cancelled,
+            Error::InvalidArguments(_) => ErrorCode::InvalidArguments,
+        }
+    }
+
+    /// Finds the code of the first [`Error`] in the chain of `err`.
+    pub fn code_of(err: &anyhow::Error) -> Option<ErrorCode> {
+        err.chain()
+            .find_map(|cause| cause.downcast_ref::<Error>())
+            .map(Error::code)
+    }
+}
+
+/// Stable error codes, grouped by the stage of the cli they occur in:
+///
+/// - `E00xx` project layout
+/// - `E01xx` generating packages
+/// - `E02xx` building generated packages
+/// - `E03xx` checking the schema
+/// - `E04xx` upgrading projects
+/// - `E05xx` running generated packages
+/// - `E09xx` cli usage
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorCode {
+    SchemaManifest,
+    SchemaPackageName,
+    ServiceManifest,
+    ServicePackageName,
+    SchemaSource,
+    UnknownDatabase,
+    Config,
+    DatabaseUrl,
+    UnknownModel,
+    NotPartitioned,
+    MissingDatabaseUrl,
+    SchemaExists,
+    UnknownSchemaPackage,
+    UnknownSchemaField,
+    ExpandUnavailable,
+    Expand,
+    GeneratePackage,
+    Workspace,
+    UnmanagedDirectory,
+    MissingPackage,
+    Generator,
+    GeneratedOutdated,
+    HiddenDatabaseItem,
+    Build,
+    Cargo,
+    WarningsDenied,
+    Publish,
+    NestedWorkspace,
+    SeaOrmConflict,
+    LintDenied,
+    NoSnapshot,
+    Snapshot,
+    SchemaUnformatted,
+    SnapshotOutdated,
+    VerifyFailed,
+    DuplicateSchemaModel,
+    DialectIncompatible,
+    GatedRelation,
+    InvalidApiDocs,
+    UnknownVersion,
+    GenerateRows,
+    EnsurePartitions,
+    UnknownCode,
+    AmbiguousDatabaseUrl,
+    Cancelled,
+    InvalidArguments,
+}
+
+impl ErrorCode {
+    pub const ALL: [ErrorCode; 46] = [
+        ErrorCode::SchemaManifest,
+        ErrorCode::SchemaPackageName,
+        ErrorCode::ServiceManifest,
+        ErrorCode::ServicePackageName,
+        ErrorCode::SchemaSource,
+        ErrorCode::UnknownDatabase,
+        ErrorCode::Config,
+        ErrorCode::DatabaseUrl,
+        ErrorCode::UnknownModel,
+        ErrorCode::NotPartitioned,
+        ErrorCode::MissingDatabaseUrl,
+        ErrorCode::SchemaExists,
+        ErrorCode::UnknownSchemaPackage,
+        ErrorCode::UnknownSchemaField,
+        ErrorCode::ExpandUnavailable,
+        ErrorCode::Expand,
+        ErrorCode::GeneratePackage,
+        ErrorCode::Workspace,
+        ErrorCode::UnmanagedDirectory,
+        ErrorCode::MissingPackage,
+        ErrorCode::Generator,
+        ErrorCode::GeneratedOutdated,
+        ErrorCode::HiddenDatabaseItem,
+        ErrorCode::Build,
+        ErrorCode::Cargo,
+        ErrorCode::WarningsDenied,
+        ErrorCode::Publish,
+        ErrorCode::NestedWorkspace,
+        ErrorCode::SeaOrmConflict,
+        ErrorCode::LintDenied,
+        ErrorCode::NoSnapshot,
+        ErrorCode::Snapshot,
+        ErrorCode::SchemaUnformatted,
+        ErrorCode::SnapshotOutdated,
+        ErrorCode::VerifyFailed,
+        ErrorCode::DuplicateSchemaModel,
+        ErrorCode::DialectIncompatible,
+        ErrorCode::GatedRelation,
+        ErrorCode::InvalidApiDocs,
+        ErrorCode::UnknownVersion,
+        ErrorCode::GenerateRows,
+        ErrorCode::EnsurePartitions,
+        ErrorCode::UnknownCode,
+        ErrorCode::AmbiguousDatabaseUrl,
+        ErrorCode::Cancelled,
+        ErrorCode::InvalidArguments,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::SchemaManifest => "E0001",
+            ErrorCode::SchemaPackageName => "E0002",
+            ErrorCode::ServiceManifest => "E0003",
+            ErrorCode::ServicePackageName => "E0004",
+            ErrorCode::SchemaSource => "E0005",
+            ErrorCode::UnknownDatabase => "E0006",
+            ErrorCode::Config => "E0007",
+            ErrorCode::DatabaseUrl => "E0008",
+            ErrorCode::UnknownModel => "E0009",
+            ErrorCode::NotPartitioned => "E0010",
+            ErrorCode::MissingDatabaseUrl => "E0011",
+            ErrorCode::SchemaExists => "E0012",
+            ErrorCode::UnknownSchemaPackage => "E0013",
+            ErrorCode::UnknownSchemaField => "E0014",
+            ErrorCode::ExpandUnavailable => "E0015",
+            ErrorCode::Expand => "E0016",
+            ErrorCode::GeneratePackage => "E0101",
+            ErrorCode::Workspace => "E0102",
+            ErrorCode::UnmanagedDirectory => "E0103",
+            ErrorCode::MissingPackage => "E0104",
+            ErrorCode::Generator => "E0105",
+            ErrorCode::GeneratedOutdated => "E0106",
+            ErrorCode::HiddenDatabaseItem => "E0107",
+            ErrorCode::Build => "E0201",
+            ErrorCode::Cargo => "E0202",
+            ErrorCode::WarningsDenied => "E0203",
+            ErrorCode::Publish => "E0204",
+            ErrorCode::NestedWorkspace => "E0205",
+            ErrorCode::SeaOrmConflict => "E0206",
+            ErrorCode::LintDenied => "E0301",
+            ErrorCode::NoSnapshot => "E0302",
+            ErrorCode::Snapshot => "E0303",
+            ErrorCode::SchemaUnformatted => "E0304",
+            ErrorCode::SnapshotOutdated => "E0305",
+            ErrorCode::VerifyFailed => "E0306",
+            ErrorCode::DuplicateSchemaModel => "E0307",
+            ErrorCode::DialectIncompatible => "E0308",
+            ErrorCode::GatedRelation => "E0309",
+            ErrorCode::InvalidApiDocs => "E0310",
+            ErrorCode::UnknownVersion => "E0401",
+            ErrorCode::GenerateRows => "E0501",
+            ErrorCode::EnsurePartitions => "E0502",
+            ErrorCode::UnknownCode => "E0901",
+            ErrorCode::AmbiguousDatabaseUrl => "E0902",
+            ErrorCode::Cancelled => "E0903",
+            ErrorCode::InvalidArguments => "E0904",
+        }
+    }
+
+    /// Returns a longer description of the error along with remediation steps.
+    pub fn explanation(&self) -> &'static str {
+        match self {
+            ErrorCode::SchemaManifest => include_str!("explanations/E0001.md"),
+            ErrorCode::SchemaPackageName => include_str!("explanations/E0002.md"),
+            ErrorCode::ServiceManifest => include_str!("explanations/E0003.md"),
+            ErrorCode::ServicePackageName => include_str!("explanations/E0004.md"),
+            ErrorCode::SchemaSource => include_str!("explanations/E0005.md"),
+            ErrorCode::UnknownDatabase => include_str!("explanations/E0006.md"),
+            ErrorCode::Config => include_str!("explanations/E0007.md"),
+            ErrorCode::DatabaseUrl => include_str!("explanations/E0008.md"),
+            ErrorCode::UnknownModel => include_str!("explanations/E0009.md"),
+            ErrorCode::NotPartitioned => include_str!("explanations/E0010.md"),
+            ErrorCode::MissingDatabaseUrl => include_str!("explanations/E0011.md"),
+            ErrorCode::SchemaExists => include_str!("explanations/E0012.md"),
+            ErrorCode::UnknownSchemaPackage => include_str!("explanations/E0013.md"),
+            ErrorCode::UnknownSchemaField => include_str!("explanations/E0014.md"),
+            ErrorCode::ExpandUnavailable => include_str!("explanations/E0015.md"),
+            ErrorCode::Expand => include_str!("explanations/E0016.md"),
+            ErrorCode::GeneratePackage => include_str!("explanations/E0101.md"),
+            ErrorCode::Workspace => include_str!("explanations/E0102.md"),
+            ErrorCode::UnmanagedDirectory => include_str!("explanations/E0103.md"),
+            ErrorCode::MissingPackage => include_str!("explanations/E0104.md"),
+            ErrorCode::Generator => include_str!("explanations/E0105.md"),
+            ErrorCode::GeneratedOutdated => include_str!("explanations/E0106.md"),
+            ErrorCode::HiddenDatabaseItem => include_str!("explanations/E0107.md"),
+            ErrorCode::Build => include_str!("explanations/E0201.md"),
+            ErrorCode::Cargo => include_str!("explanations/E0202.md"),
+            ErrorCode::WarningsDenied => include_str!("explanations/E0203.md"),
+            ErrorCode::Publish => include_str!("explanations/E0204.md"),
+            ErrorCode::NestedWorkspace => include_str!("explanations/E0205.md"),
+            ErrorCode::SeaOrmConflict => include_str!("explanations/E0206.md"),
+            ErrorCode::LintDenied => include_str!("explanations/E0301.md"),
+            ErrorCode::NoSnapshot => include_str!("explanations/E0302.md"),
+            ErrorCode::Snapshot => include_str!("explanations/E0303.md"),
+            ErrorCode::SchemaUnformatted => include_str!("explanations/E0304.md"),
+            ErrorCode::SnapshotOutdated => include_str!("explanations/E0305.md"),
+            ErrorCode::VerifyFailed => include_str!("explanations/E0306.md"),
+            ErrorCode::DuplicateSchemaModel => include_str!("explanations/E0307.md"),
+            ErrorCode::DialectIncompatible => include_str!("explanations/E0308.md"),
+            ErrorCode::GatedRelation => include_str!("explanations/E0309.md"),
+            ErrorCode::InvalidApiDocs => include_str!("explanations/E0310.md"),
+            ErrorCode::UnknownVersion => include_str!("explanations/E0401.md"),
+            ErrorCode::GenerateRows => include_str!("explanations/E0501.md"),
+            ErrorCode::EnsurePartitions => include_str!("explanations/E0502.md"),
+            ErrorCode::UnknownCode => include_str!("explanations/E0901.md"),
+            ErrorCode::AmbiguousDatabaseUrl => include_str!("explanations/E0902.md"),
+            ErrorCode::Cancelled => include_str!("explanations/E0903.md"),
+            ErrorCode::InvalidArguments => include_str!("explanations/E0904.md"),
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<ErrorCode> {
+        let code = code.to_uppercase();
+        ErrorCode::ALL
+            .iter()
+            .copied()
+            .find(|error_code| error_code.as_str() == code)
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Codes are serialized as their string, such as `"E0001"`.
+impl Serialize for ErrorCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::Context;
+
+    use super::*;
+
+    #[test]
+    fn codes_are_unique() {
+        for code in ErrorCode::ALL {
+            assert_eq!(ErrorCode::from_code(code.as_str()), Some(code));
+            assert!(code.explanation().starts_with(&format!("# {}", code)));
+        }
+    }
+
+    #[test]
+    fn code_of_context_chain() {
+        let err = anyhow::Error::new(Error::SchemaPackageName("app".to_string()))
+            .context("could not compile");
+        assert_eq!(Error::code_of(&err), Some(ErrorCode::SchemaPackageName));
+
+        let err = Err::<(), _>(anyhow::anyhow!("io"))
+            .context("untyped")
+            .unwrap_err();
+        assert_eq!(Error::code_of(&err), None);
+    }
+}
@@ -0,0 +1,28 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use clap::Parser;
+
+use crate::{
+    cancel::Cancellation,
+    error::{Error, ErrorCode},
+    Runnable,
+};
+
+/// Prints a detailed explanation of an error code
+#[derive(Parser)]
+pub struct Explain {
+    /// Error code to explain, such as E0001
+    pub code: String,
+}
+
+#[async_trait]
+impl Runnable for Explain {
+    async fn run(&mut self, _cancellation: &Cancellation) -> Result<()> {
+        let code = ErrorCode::from_code(&self.code)
+            .ok_or_else(|| Error::UnknownCode(self.code.clone()))?;
+
+        out!("{}", code.explanation());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,384 @@
+//! <div align="center">
+//!   <h1>awto</h1>
+//!
+//!   <p>
+//!     <strong>Awtomate your 🦀 microservices with awto</strong>
+//!   </p>
+//!
+//! </div>
+//!
+//! # awto-cli
+//!
+//! Command-line-interface for compiling projects built with [`awto`](https://docs.rs/awto).
+//!
+//! Tools embedding the cli run its commands in-process with [`run_with_args`],
+//! capturing their output with an [`IoSink`]:
+//!
+//! ```no_run
+//! # async fn compile() -> Result<(), awto_cli::AwtoError> {
+//! let (sink, captured) = awto_cli::IoSink::capture();
+//! let args = ["awto", "compile", "database", "--dry-run"].map(String::from);
+//! let summary = awto_cli::run_with_args(args, sink).await?;
+//! println!("{} warning(s)\n{}", summary.warnings, captured.stdout());
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! See more on the [repository](https://github.com/awto-rs/awto).
+
+use anyhow::Result;
+use async_trait::async_trait;
+use clap::{ArgEnum, ErrorKind, Parser};
+use colored::Colorize;
+use compile::Compile;
+use config::ConfigCommand;
+use db::Db;
+use doctor::Doctor;
+use error::Error;
+use explain::Explain;
+use log::{debug, error, LevelFilter};
+use package::Package;
+use schema::{Diagnostics, Schema};
+use serde::Serialize;
+use status::Status;
+use upgrade::Upgrade;
+use verify::Verify;
+
+pub use cancel::{cancel_on_ctrl_c, Cancellation, CANCELLED_EXIT_CODE};
+pub use error::ErrorCode;
+pub use sink::{CapturedOutput, IoEvent, IoSink, Stream};
+
+#[macro_use]
+mod macros;
+
+mod cancel;
+mod compile;
+mod config;
+mod db;
+mod doctor;
+mod error;
+mod explain;
+mod package;
+mod schema;
+mod sink;
+mod status;
+mod upgrade;
+mod util;
+mod verify;
+
+/// Exit code of a run which failed because of invalid arguments, like the usage errors of other cli tools.
+const USAGE_EXIT_CODE: i32 = 2;
+
+/// Awto cli
+#[derive(Parser)]
+#[clap(name = "awto")]
+struct Opts {
+    /// Format of error messages
+    #[clap(arg_enum, long, default_value = "human")]
+    pub message_format: MessageFormat,
+    /// Profile of awto.toml to use, instead of `AWTO_PROFILE` or `dev`
+    #[clap(long, global = true)]
+    pub profile: Option<String>,
+    /// Reports the wall time and peak memory of each phase of generation
+    #[clap(long, global = true, hidden = true)]
+    pub profile_generation: bool,
+    /// Reads the models from the schema packages once their macros are expanded, with cargo-expand
+    #[clap(long, global = true)]
+    pub expand: bool,
+    #[clap(subcommand)]
+    pub subcmd: SubCommand,
+}
+
+#[derive(ArgEnum, Clone, Copy, PartialEq, Eq)]
+enum MessageFormat {
+    Human,
+    Json,
+}
+
+#[derive(Parser)]
+enum SubCommand {
+    Compile(Compile),
+    Config(ConfigCommand),
+    Db(Db),
+    Doctor(Doctor),
+    Explain(Explain),
+    Package(Package),
+    Schema(Schema),
+    Status(Status),
+    Upgrade(Upgrade),
+    Verify(Verify),
+}
+
+/// Status of a run, as printed by `--message-format json`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RunStatus {
+    Ok,
+    Failed,
+    Cancelled,
+}
+
+/// Summary of a successful run, printed as JSON by `--message-format json`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct RunSummary {
+    /// Always [`RunStatus::Ok`].
+    pub status: RunStatus,
+    /// Profile of awto.toml the run used.
+    pub profile: String,
+    /// Number of warnings reported by the run.
+    pub warnings: usize,
+}
+
+/// Error of a failed or cancelled run, printed as JSON by `--message-format json`.
+#[derive(thiserror::Error, Clone, Debug, PartialEq, Serialize)]
+#[error("{message}")]
+pub struct AwtoError {
+    /// [`RunStatus::Failed`] or [`RunStatus::Cancelled`].
+    pub status: RunStatus,
+    /// Stable code of the error, explained by `awto explain <code>`, when it has one.
+    pub code: Option<ErrorCode>,
+    pub message: String,
+    /// Messages of the errors which caused the error, outermost first.
+    pub causes: Vec<String>,
+    /// Problems found in the schema, with their `path`, `line`, `column`, `message` and `help`.
+    pub diagnostics: Option<serde_json::Value>,
+    /// Number of warnings reported by the run before it failed.
+    pub warnings: usize,
+    /// Profile of awto.toml the run used.
+    pub profile: String,
+}
+
+impl AwtoError {
+    /// Returns the exit code of the `awto` binary for the error.
+    pub fn exit_code(&self) -> i32 {
+        match self.code {
+            Some(ErrorCode::Cancelled) => CANCELLED_EXIT_CODE,
+            Some(ErrorCode::InvalidArguments) => USAGE_EXIT_CODE,
+            _ => 1,
+        }
+    }
+}
+
+/// Runs the cli with `args`, which start with the name of the binary like [`std::env::args`],
+/// writing everything the command prints or logs to `io`.
+///
+/// The run is never cancelled, see [`run_with_cancellation`].
+/// Commands run in the current directory, and `--profile`, `--expand` and `--profile-generation`
+/// are set for the rest of the process.
+pub async fn run_with_args(
+    args: impl IntoIterator<Item = String>,
+    io: IoSink,
+) -> Result<RunSummary, AwtoError> {
+    run_with_cancellation(args, io, &Cancellation::new()).await
+}
+
+/// Runs the cli with `args` like [`run_with_args`], stopping the command once `cancellation` is cancelled.
+pub async fn run_with_cancellation(
+    args: impl IntoIterator<Item = String>,
+    io: IoSink,
+    cancellation: &Cancellation,
+) -> Result<RunSummary, AwtoError> {
+    // Colors are disabled with `NO_COLOR` by `colored` itself, and here when the sink is not colored
+    if io.is_colored() {
+        colored::control::unset_override();
+    } else {
+        colored::control::set_override(false);
+    }
+
+    let opts = match Opts::try_parse_from(args) {
+        Ok(opts) => opts,
+        Err(err) => return sink::scope(io, LevelFilter::Info, async move { usage(err) }).await,
+    };
+    if let Some(profile) = &opts.profile {
+        config::select_profile(profile);
+    }
+    if opts.profile_generation {
+        compile::enable_profiling();
+    }
+    if opts.expand {
+        schema::enable_expansion();
+    }
+
+    let cmd = opts.subcmd.into_runnable();
+    let level = if cmd.is_verbose() {
+        LevelFilter::Debug
+    } else {
+        LevelFilter::Info
+    };
+
+    sink::scope(io, level, run(cmd, opts.message_format, cancellation)).await
+}
+
+/// Prints the help or version requested with `err`, or fails with the usage error `err`.
+fn usage(err: clap::Error) -> Result<RunSummary, AwtoError> {
+    let profile = config::active_profile();
+    if matches!(err.kind, ErrorKind::DisplayHelp | ErrorKind::DisplayVersion) {
+        out!("{}", err);
+        return Ok(RunSummary {
+            status: RunStatus::Ok,
+            profile,
+            warnings: 0,
+        });
+    }
+
+    let usage = err.to_string();
+    errln!("{}", usage.trim_end());
+    let message = usage
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .trim_start_matches("error: ");
+    let err = Error::InvalidArguments(message.to_string());
+    Err(AwtoError {
+        status: RunStatus::Failed,
+        code: Some(err.code()),
+        message: err.to_string(),
+        causes: Vec::new(),
+        diagnostics: None,
+        warnings: 0,
+        profile,
+    })
+}
+
+/// Runs `cmd`, printing its error in `message_format` when it fails.
+async fn run(
+    mut cmd: Box<dyn Runnable>,
+    message_format: MessageFormat,
+    cancellation: &Cancellation,
+) -> Result<RunSummary, AwtoError> {
+    let profile = config::active_profile();
+    debug!("using profile '{}'", profile);
+
+    let result = cmd.run(cancellation).await;
+    compile::report_profile();
+
+    let err = match result {
+        Ok(()) => {
+            let summary = RunSummary {
+                status: RunStatus::Ok,
+                profile,
+                warnings: cmd.warnings(),
+            };
+            if message_format == MessageFormat::Json {
+                outln!("{}", serde_json::to_string(&summary).unwrap());
+            }
+            return Ok(summary);
+        }
+        Err(err) => err,
+    };
+
+    let code = Error::code_of(&err);
+    let diagnostics = err
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<Diagnostics>());
+    // Wrappers tell cancellations apart from failures by their status and exit code
+    let status = match code {
+        Some(ErrorCode::Cancelled) => RunStatus::Cancelled,
+        _ => RunStatus::Failed,
+    };
+    let awto_error = AwtoError {
+        status,
+        code,
+        message: err.to_string(),
+        causes: err.chain().skip(1).map(|cause| cause.to_string()).collect(),
+        diagnostics: diagnostics.map(Diagnostics::to_json),
+        warnings: cmd.warnings(),
+        profile,
+    };
+
+    if message_format == MessageFormat::Json {
+        outln!("{}", serde_json::to_string(&awto_error).unwrap());
+        return Err(awto_error);
+    }
+    if status == RunStatus::Cancelled {
+        error!("cancelled");
+        return Err(awto_error);
+    }
+
+    if let Some(diagnostics) = diagnostics {
+        errln!("{}\n", diagnostics.render());
+    }
+    match code {
+        Some(code) => error!("{} {}", format!("[{}]", code).bold(), err),
+        None => error!("{}", err),
+    }
+    if cmd.is_verbose() {
+        let err_chain = err.chain().skip(1);
+        if err_chain.clone().next().is_some() {
+            errln!("{}", "\nCaused by:".italic().truecolor(190, 190, 190));
+        }
+        err_chain.for_each(|cause| errln!(" - {}", cause.to_string().truecolor(190, 190, 190)));
+    }
+    if let Some(code) = code {
+        errln!(
+            "\nFor more information about this error, try `awto explain {}`.",
+            code
+        );
+    }
+    #[cfg(not(debug_assertions))]
+    errln!(
+        "\nIf the problem persists, please submit an issue on the Github repository.\n{}",
+        "https://github.com/awto-rs/awto/issues/new".underline()
+    );
+
+    Err(awto_error)
+}
+
+impl SubCommand {
+    /// Returns the command to run.
+    fn into_runnable(self) -> Box<dyn Runnable> {
+        match self {
+            SubCommand::Compile(compile) => match compile.subcmd {
+                Some(compile::SubCommand::Database(database)) => runnable_cmd!(database),
+                Some(compile::SubCommand::Protobuf(protobuf)) => runnable_cmd!(protobuf),
+                Some(compile::SubCommand::GrpcClient(grpc_client)) => runnable_cmd!(grpc_client),
+                Some(compile::SubCommand::Rest(rest)) => runnable_cmd!(rest),
+                Some(compile::SubCommand::Admin(admin)) => runnable_cmd!(admin),
+                Some(compile::SubCommand::Custom(custom)) => runnable_cmd!(custom),
+                None => runnable_cmd!(compile),
+            },
+            SubCommand::Config(config) => match config.subcmd {
+                config::SubCommand::Show(show) => runnable_cmd!(show),
+            },
+            SubCommand::Db(db) => match db.subcmd {
+                db::SubCommand::Generate(generate) => runnable_cmd!(generate),
+                db::SubCommand::Partitions(partitions) => match partitions.subcmd {
+                    db::partitions::SubCommand::Ensure(ensure) => runnable_cmd!(ensure),
+                },
+            },
+            SubCommand::Doctor(doctor) => runnable_cmd!(doctor),
+            SubCommand::Explain(explain) => runnable_cmd!(explain),
+            SubCommand::Package(package) => match package.subcmd {
+                package::SubCommand::Database(database) => runnable_cmd!(database),
+            },
+            SubCommand::Schema(schema) => match schema.subcmd {
+                schema::SubCommand::Lint(lint) => runnable_cmd!(lint),
+                schema::SubCommand::Snapshot(snapshot) => runnable_cmd!(snapshot),
+                schema::SubCommand::Changes(changes) => runnable_cmd!(changes),
+                schema::SubCommand::Fmt(fmt) => runnable_cmd!(fmt),
+                schema::SubCommand::Import(import) => runnable_cmd!(import),
+                schema::SubCommand::Explain(explain) => runnable_cmd!(explain),
+                schema::SubCommand::Check(check) => runnable_cmd!(check),
+                schema::SubCommand::Dump(dump) => runnable_cmd!(dump),
+            },
+            SubCommand::Status(status) => runnable_cmd!(status),
+            SubCommand::Upgrade(upgrade) => runnable_cmd!(upgrade),
+            SubCommand::Verify(verify) => runnable_cmd!(verify),
+        }
+    }
+}
+
+#[async_trait]
+pub(crate) trait Runnable: Send {
+    /// Runs the command until it completes, or stops it with [`Error::Cancelled`] once `cancellation` is cancelled.
+    async fn run(&mut self, cancellation: &Cancellation) -> Result<()>;
+
+    fn is_verbose(&self) -> bool {
+        false
+    }
+
+    /// Returns the number of warnings reported by the run of the command.
+    fn warnings(&self) -> usize {
+        0
+    }
+}
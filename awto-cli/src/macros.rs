@@ -4,3 +4,30 @@ macro_rules! runnable_cmd {
         Box::new($name) as Box<dyn Runnable>
     };
 }
+
+/// Prints to the stdout of the sink of the run, like `print!`.
+macro_rules! out {
+    ($($arg:tt)*) => {
+        $crate::sink::write($crate::sink::Stream::Stdout, format_args!($($arg)*))
+    };
+}
+
+/// Prints a line to the stdout of the sink of the run, like `println!`.
+macro_rules! outln {
+    () => {
+        out!("\n")
+    };
+    ($($arg:tt)*) => {
+        out!("{}\n", format_args!($($arg)*))
+    };
+}
+
+/// Prints a line to the stderr of the sink of the run, like `eprintln!`.
+macro_rules! errln {
+    () => {
+        $crate::sink::write($crate::sink::Stream::Stderr, format_args!("\n"))
+    };
+    ($($arg:tt)*) => {
+        $crate::sink::write($crate::sink::Stream::Stderr, format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
@@ -0,0 +1,919 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use clap::{ArgEnum, Parser};
+use log::{debug, info};
+use quote::{quote, ToTokens};
+use tokio::fs;
+use toml_edit::{Array, Document, InlineTable, Value};
+
+use crate::{
+    cancel::{child_output, Cancellation},
+    compile::{
+        check_schema_package, content_hash, package_fingerprint, resolve_inherited,
+        with_publication_header, DatabaseConfig, DatabasePackage, GenerationManifest, Publication,
+        PublishConfig, SCHEMA_DIR, SCHEMA_LIB_PATH,
+    },
+    error::Error,
+    schema::{load_schema_models, parse_schema_structs, ROLE_ATTRIBUTES},
+    util::WorkspaceContext,
+    Runnable,
+};
+
+/// Prepares the database packages for publishing to a registry, checking what would be uploaded with `cargo publish --dry-run`
+#[derive(Parser)]
+pub struct Database {
+    /// Registry the packages are published to, as declared in `.cargo/config.toml`
+    #[clap(long)]
+    pub registry: String,
+    /// Version the packages are published as, such as `1.4.0`
+    #[clap(long, required_unless_present = "bump", conflicts_with = "bump")]
+    pub version: Option<Version>,
+    /// Bumps the last version published to the registry, or the version of the packages when they were never published
+    #[clap(arg_enum, long)]
+    pub bump: Option<Bump>,
+    /// Copies the schema structs used by the packages into them, instead of depending on the `schema` package of the registry
+    #[clap(long)]
+    pub inline_schema: bool,
+    /// Only packages the package of the given database
+    #[clap(long)]
+    pub only_db: Option<String>,
+    /// Publishes the packages, instead of only checking them
+    #[clap(long)]
+    pub publish: bool,
+    /// Prints more information
+    #[clap(short, long)]
+    pub verbose: bool,
+}
+
+/// Part of the version bumped with `--bump`.
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bump {
+    Major,
+    Minor,
+    Patch,
+}
+
+/// A `major.minor.patch` version of a package.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    major: u64,
+    minor: u64,
+    patch: u64,
+}
+
+impl Version {
+    /// Returns the version following this one, with the parts after the `bump`ed part reset.
+    fn bump(self, bump: Bump) -> Version {
+        match bump {
+            Bump::Major => Version {
+                major: self.major + 1,
+                minor: 0,
+                patch: 0,
+            },
+            Bump::Minor => Version {
+                minor: self.minor + 1,
+                patch: 0,
+                ..self
+            },
+            Bump::Patch => Version {
+                patch: self.patch + 1,
+                ..self
+            },
+        }
+    }
+}
+
+impl FromStr for Version {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<_> = s.split('.').map(str::parse::<u64>).collect();
+        match parts.as_slice() {
+            [Ok(major), Ok(minor), Ok(patch)] => Ok(Version {
+                major: *major,
+                minor: *minor,
+                patch: *patch,
+            }),
+            _ => Err(format!("version '{}' is not a version such as 1.4.0", s)),
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+#[async_trait]
+impl Runnable for Database {
+    async fn run(&mut self, cancellation: &Cancellation) -> Result<()> {
+        let workspace = WorkspaceContext::load(".").await?;
+        check_schema_package(&workspace).await?;
+        let models = load_schema_models().await?;
+        let (config, package_name) = DatabaseConfig::load(&workspace).await?;
+        let mut packages = DatabasePackage::from_models(&models, &package_name, &config);
+        if let Some(only_db) = &self.only_db {
+            packages.retain(|package| package.database_name() == only_db);
+            if packages.is_empty() {
+                return Err(Error::UnknownDatabase(only_db.clone()).into());
+            }
+        }
+
+        let mut manifests = Vec::new();
+        for package in &packages {
+            let manifest = GenerationManifest::load(package.dir())
+                .await
+                .ok_or_else(|| Error::MissingPackage {
+                    package: package.name(),
+                    command: "awto compile database".to_string(),
+                })?;
+            manifests.push(manifest);
+        }
+        let published = manifests
+            .iter()
+            .filter_map(|manifest| manifest.published.as_ref())
+            .filter(|published| published.registry == self.registry)
+            .collect::<Vec<_>>();
+        let current = read_package_version(&format!("{}/Cargo.toml", packages[0].dir())).await?;
+        let version = self.next_version(
+            published
+                .iter()
+                .filter_map(|published| published.version.parse().ok())
+                .max(),
+            current,
+        )?;
+
+        // The packages depend on the `schema` package of the registry in the version of the local schema
+        let mut schema_version = None;
+        let mut schema_packaged = false;
+        if !self.inline_schema {
+            let version = read_package_version(&format!("{}/Cargo.toml", SCHEMA_DIR))
+                .await?
+                .to_string();
+            if !published
+                .iter()
+                .any(|published| published.schema_version.as_ref() == Some(&version))
+            {
+                info!(
+                    "packaging 'schema' v{}, which the database packages depend on",
+                    version
+                );
+                self.cargo_publish(Path::new(SCHEMA_DIR), "schema", &[], cancellation)
+                    .await?;
+                schema_packaged = true;
+            }
+            schema_version = Some(version);
+        }
+        let mut schema_source = None;
+        if self.inline_schema {
+            let source = fs::read_to_string(SCHEMA_LIB_PATH)
+                .await
+                .with_context(|| format!("could not read file '{}'", SCHEMA_LIB_PATH))?;
+            parse_schema_structs(&source).map_err(|source| Error::SchemaSource {
+                path: SCHEMA_LIB_PATH.to_string(),
+                source,
+            })?;
+            schema_source = Some(source);
+        }
+
+        for (package, manifest) in packages.iter().zip(manifests) {
+            cancellation.check()?;
+            let publication = Publication {
+                registry: self.registry.clone(),
+                version: version.to_string(),
+                schema_version: schema_version.clone(),
+                fingerprint: package_fingerprint(&package.dir()).await?,
+            };
+            let staging = workspace
+                .target_dir()
+                .join("awto-package")
+                .join(package.name());
+            let files = self
+                .stage(
+                    package,
+                    &workspace,
+                    &config.publish,
+                    &publication,
+                    schema_source.as_deref(),
+                    &staging,
+                )
+                .await
+                .with_context(|| {
+                    format!(
+                        "could not prepare package '{}' for publishing",
+                        package.name()
+                    )
+                })?;
+
+            info!(
+                "{} '{}' v{} to the '{}' registry from '{}':",
+                if self.publish {
+                    "publishing"
+                } else {
+                    "would publish"
+                },
+                package.name(),
+                version,
+                self.registry,
+                staging.display()
+            );
+            for (path, len) in &files {
+                info!("  {} ({} bytes)", path, len);
+            }
+
+            // A schema which is not published yet cannot be resolved while verifying the package
+            let args: &[&str] = if schema_packaged && !self.publish {
+                &["--allow-dirty", "--no-verify"]
+            } else {
+                &["--allow-dirty"]
+            };
+            self.cargo_publish(&staging, &package.name(), args, cancellation)
+                .await?;
+
+            if self.publish {
+                record_publication(package, manifest, publication).await?;
+                info!(
+                    "published '{}' v{} to the '{}' registry",
+                    package.name(),
+                    version,
+                    self.registry
+                );
+            } else {
+                info!(
+                    "'{}' v{} is ready to be published, pass --publish to publish it",
+                    package.name(),
+                    version
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_verbose(&self) -> bool {
+        self.verbose
+    }
+}
+
+impl Database {
+    /// Returns the version to publish, given the last version `published` to the registry and the `current` version of the packages.
+    fn next_version(&self, published: Option<Version>, current: Version) -> Result<Version> {
+        let version = match (self.version, self.bump) {
+            (Some(version), _) => version,
+            (None, Some(bump)) => published.unwrap_or(current).bump(bump),
+            (None, None) => {
+                return Err(anyhow!(
+                    "pass the version to publish with --version or --bump"
+                ))
+            }
+        };
+        if let Some(published) = published.filter(|published| version <= *published) {
+            return Err(anyhow!(
+                "v{} was already published to the '{}' registry, pass a higher --version or --bump it",
+                published,
+                self.registry
+            ));
+        }
+
+        Ok(version)
+    }
+
+    /// Writes the package to publish into `staging`, returning the paths of its files along with their sizes.
+    ///
+    /// The code generated by the build script of the package is copied into its sources, so the published package
+    /// builds without a database, unless the package is already generated into its sources.
+    async fn stage(
+        &self,
+        package: &DatabasePackage,
+        workspace: &WorkspaceContext,
+        config: &PublishConfig,
+        publication: &Publication,
+        schema_source: Option<&str>,
+        staging: &Path,
+    ) -> Result<Vec<(String, u64)>> {
+        let read = |path: PathBuf| async move {
+            fs::read_to_string(&path)
+                .await
+                .with_context(|| format!("could not read file '{}'", path.display()))
+        };
+        let dir = PathBuf::from(package.dir());
+
+        // The published package is built outside the workspace, so keys inherited from it are resolved
+        let cargo_toml = resolve_inherited(
+            &read(dir.join("Cargo.toml")).await?,
+            workspace.root_workspace(),
+        )?;
+        let lib = publish_lib(
+            &read(dir.join("src/lib.rs")).await?,
+            publication,
+            schema_source.is_some(),
+        );
+        let mut files = vec![(
+            "Cargo.toml".to_string(),
+            publish_manifest(&cargo_toml, package, config, publication)?,
+        )];
+        // Packages generated into their sources already hold their code in their lib and entities
+        let (code_path, mut code) = if package.is_generated_into_sources() {
+            ("src/lib.rs", lib)
+        } else {
+            let out_dir = generated_out_dir(workspace.target_dir(), &package.name())
+                .await?
+                .ok_or_else(|| Error::MissingPackage {
+                    package: package.name(),
+                    command: "awto compile database".to_string(),
+                })?;
+            debug!("copying the code generated into '{}'", out_dir.display());
+            files.push(("src/lib.rs".to_string(), lib));
+            files.push((
+                "src/docs.md".to_string(),
+                read(out_dir.join("docs.md")).await?,
+            ));
+            ("src/app.rs", read(out_dir.join("app.rs")).await?)
+        };
+        if let Some(source) = schema_source {
+            let (inlined, names) = inline_schema_paths(&code);
+            code = inlined;
+            files.push(("src/schema.rs".to_string(), inline_schema(source, names)?));
+        }
+        files.push((code_path.to_string(), code));
+
+        for sub_dir in ["src/ext", "src/entities"] {
+            if !dir.join(sub_dir).is_dir() {
+                continue;
+            }
+            let mut entries = fs::read_dir(dir.join(sub_dir)).await.with_context(|| {
+                format!("could not read directory '{}'", dir.join(sub_dir).display())
+            })?;
+            while let Some(entry) = entries.next_entry().await? {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if name.ends_with(".rs") {
+                    files.push((format!("{}/{}", sub_dir, name), read(entry.path()).await?));
+                }
+            }
+        }
+
+        if staging.is_dir() {
+            fs::remove_dir_all(staging)
+                .await
+                .with_context(|| format!("could not delete directory '{}'", staging.display()))?;
+        }
+        let mut written = Vec::new();
+        for (path, contents) in files {
+            let file = staging.join(&path);
+            fs::create_dir_all(file.parent().unwrap())
+                .await
+                .with_context(|| {
+                    format!(
+                        "could not create directory '{}'",
+                        file.parent().unwrap().display()
+                    )
+                })?;
+            fs::write(&file, &contents)
+                .await
+                .with_context(|| format!("could not write file '{}'", file.display()))?;
+            written.push((path, contents.len() as u64));
+        }
+        if schema_source.is_some() {
+            format_file(&staging.join("src/schema.rs")).await;
+        }
+        written.sort();
+
+        Ok(written)
+    }
+
+    /// Runs `cargo publish` to the registry for the package in `dir` with `args`, with `--dry-run` unless `--publish` is passed.
+    async fn cargo_publish(
+        &self,
+        dir: &Path,
+        package: &str,
+        args: &[&str],
+        cancellation: &Cancellation,
+    ) -> Result<()> {
+        let mut command = tokio::process::Command::new("cargo");
+        command
+            .current_dir(dir)
+            .args(["publish", "--registry", &self.registry])
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::inherit())
+            .stderr(Stdio::inherit());
+        if !self.publish {
+            command.arg("--dry-run");
+        }
+
+        let child = command.spawn().map_err(Error::Cargo)?;
+        let output = child_output(child, cancellation)
+            .await
+            .map_err(Error::Cargo)?
+            .ok_or(Error::Cancelled)?;
+        if !output.status.success() {
+            return Err(Error::Publish {
+                package: package.to_string(),
+                status: output.status,
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads the version of the package of the `Cargo.toml` file at `path`.
+async fn read_package_version(path: &str) -> Result<Version> {
+    let contents = fs::read_to_string(path)
+        .await
+        .with_context(|| format!("could not read file '{}'", path))?;
+    let cargo_toml: toml::Value =
+        toml::from_str(&contents).with_context(|| format!("could not parse file '{}'", path))?;
+    let version = cargo_toml
+        .get("package")
+        .and_then(|package| package.get("version"))
+        .and_then(toml::Value::as_str)
+        .ok_or_else(|| anyhow!("'{}' does not set the version of its package", path))?;
+
+    version
+        .parse()
+        .map_err(|err: String| anyhow!("{} in '{}'", err, path))
+}
+
+/// Returns the `OUT_DIR` the build script of `package` last generated its code into, inside `target_dir`.
+async fn generated_out_dir(target_dir: &Path, package: &str) -> Result<Option<PathBuf>> {
+    let build_dir = target_dir.join("debug/build");
+    if !build_dir.is_dir() {
+        return Ok(None);
+    }
+
+    let mut entries = fs::read_dir(&build_dir)
+        .await
+        .with_context(|| format!("could not read directory '{}'", build_dir.display()))?;
+    let mut latest: Option<(SystemTime, PathBuf)> = None;
+    while let Some(entry) = entries.next_entry().await? {
+        // Directories are named after the package and a hash, such as `database-0123abcd`
+        let name = entry.file_name().to_string_lossy().to_string();
+        let is_package = name
+            .strip_prefix(package)
+            .and_then(|rest| rest.strip_prefix('-'))
+            .is_some_and(|hash| !hash.is_empty() && hash.chars().all(|c| c.is_ascii_hexdigit()));
+        if !is_package {
+            continue;
+        }
+        let out_dir = entry.path().join("out");
+        if let Ok(modified) = fs::metadata(out_dir.join("app.rs"))
+            .await
+            .and_then(|metadata| metadata.modified())
+        {
+            if latest.as_ref().is_none_or(|(latest, _)| modified > *latest) {
+                latest = Some((modified, out_dir));
+            }
+        }
+    }
+
+    Ok(latest.map(|(_, out_dir)| out_dir))
+}
+
+/// Returns the `Cargo.toml` file of the published package, given the one of the generated package.
+///
+/// The package gets its version, the metadata of `[database.publish]` and its own workspace. The build script,
+/// tests and benchmarks are left out, and the path dependency on `schema` is replaced by its published version,
+/// or removed when the schema is inlined.
+fn publish_manifest(
+    cargo_toml: &str,
+    package: &DatabasePackage,
+    config: &PublishConfig,
+    publication: &Publication,
+) -> Result<String> {
+    let mut doc: Document = cargo_toml
+        .parse()
+        .context("could not parse the Cargo.toml file of the package")?;
+    let description = config.description.clone().unwrap_or_else(|| {
+        format!(
+            "Entities and queries of the `{}` database, generated by awto",
+            package.database_name()
+        )
+    });
+
+    let manifest = doc.as_table_mut();
+    manifest.remove("build-dependencies");
+    manifest.remove("dev-dependencies");
+    manifest.remove("test");
+    manifest.remove("bench");
+    manifest.insert("workspace", toml_edit::table());
+
+    let package_table = &mut manifest["package"];
+    package_table["version"] = toml_edit::value(publication.version.as_str());
+    package_table["description"] = toml_edit::value(description);
+    for (key, value) in [
+        ("license", &config.license),
+        ("repository", &config.repository),
+        ("homepage", &config.homepage),
+    ] {
+        if let Some(value) = value {
+            package_table[key] = toml_edit::value(value.as_str());
+        }
+    }
+    if !config.authors.is_empty() {
+        package_table["authors"] = toml_edit::value(config.authors.iter().collect::<Array>());
+    }
+    package_table["publish"] =
+        toml_edit::value(Some(&publication.registry).into_iter().collect::<Array>());
+
+    let dependencies = manifest["dependencies"]
+        .as_table_like_mut()
+        .ok_or_else(|| anyhow!("the package has no dependencies"))?;
+    dependencies.remove("schema");
+    if let Some((name, _)) = dependencies.iter().find(|(_, dependency)| {
+        dependency
+            .as_table_like()
+            .is_some_and(|dependency| dependency.contains_key("path"))
+    }) {
+        return Err(anyhow!(
+            "dependency '{}' is a path dependency, which cannot be published, set its version in [database.extra_dependencies]",
+            name
+        ));
+    }
+    if let Some(schema_version) = &publication.schema_version {
+        let schema: InlineTable = vec![
+            ("version", Value::from(schema_version.as_str())),
+            ("registry", Value::from(publication.registry.as_str())),
+        ]
+        .into_iter()
+        .collect();
+        manifest["dependencies"]["schema"] = toml_edit::value(schema);
+    }
+
+    Ok(doc.to_string())
+}
+
+/// Returns the lib of the published package, given the one of the generated package: the code generated into
+/// `OUT_DIR` is included from the sources of the package, along with the inlined schema when `inline_schema` is set.
+fn publish_lib(lib: &str, publication: &Publication, inline_schema: bool) -> String {
+    let mut lib = with_publication_header(lib, publication)
+        .replace(
+            r#"include_str!(concat!(env!("OUT_DIR"), "/docs.md"))"#,
+            r#"include_str!("docs.md")"#,
+        )
+        .replace(
+            r#"include!(concat!(env!("OUT_DIR"), "/app.rs"))"#,
+            r#"include!("app.rs")"#,
+        );
+    if inline_schema {
+        lib.push_str("\npub mod schema;\n");
+    }
+
+    lib
+}
+
+/// Returns `code` with the paths into the `schema` package, such as `::schema::Product`, pointing into the `schema`
+/// module of the crate instead, along with the names of the structs used.
+fn inline_schema_paths(code: &str) -> (String, BTreeSet<String>) {
+    const PATH: &str = "::schema::";
+
+    let mut inlined = String::with_capacity(code.len());
+    let mut names = BTreeSet::new();
+    let mut rest = code;
+    while let Some(start) = rest.find(PATH) {
+        let (before, after) = rest.split_at(start);
+        inlined.push_str(before);
+        let after = &after[PATH.len()..];
+        if before.ends_with(|c: char| c.is_alphanumeric() || c == '_') {
+            inlined.push_str(PATH);
+        } else {
+            inlined.push_str("crate::schema::");
+            let name: String = after
+                .chars()
+                .take_while(|c| c.is_alphanumeric() || *c == '_')
+                .collect();
+            names.insert(name);
+        }
+        rest = after;
+    }
+    inlined.push_str(rest);
+
+    (inlined, names)
+}
+
+/// Returns the `schema` module of a package inlining the schema lib `source`, holding the structs of its `schema!` macros
+/// named in `names` along with the structs their fields use, with their awto attributes removed.
+///
+/// Newtypes keep their conversions from and into their inner type. The `use` items of the schema lib are kept,
+/// except for those of `awto`, whose prelude types are imported from `chrono` and `uuid` instead.
+fn inline_schema(source: &str, mut names: BTreeSet<String>) -> Result<String> {
+    let (uses, structs) = parse_schema_structs(source)?;
+    loop {
+        let used: Vec<String> = structs
+            .iter()
+            .filter(|item| names.contains(&item.ident.to_string()))
+            .flat_map(|item| item.fields.iter())
+            .flat_map(|field| {
+                field
+                    .ty
+                    .to_token_stream()
+                    .to_string()
+                    .split(|c: char| !c.is_alphanumeric() && c != '_')
+                    .map(str::to_string)
+                    .collect::<Vec<_>>()
+            })
+            .filter(|word| structs.iter().any(|item| item.ident == word))
+            .collect();
+        let count = names.len();
+        names.extend(used);
+        if names.len() == count {
+            break;
+        }
+    }
+
+    let items = structs
+        .iter()
+        .filter(|item| names.contains(&item.ident.to_string()))
+        .map(|item| {
+            let mut item = item.clone();
+            let newtype = item.attrs.iter().any(|attr| {
+                attr.path.is_ident("awto") && attr.tokens.to_string().contains("newtype")
+            });
+            item.attrs.retain(|attr| {
+                !attr.path.is_ident("awto")
+                    && !ROLE_ATTRIBUTES.iter().any(|role| attr.path.is_ident(role))
+            });
+            for field in &mut item.fields {
+                field.attrs.retain(|attr| !attr.path.is_ident("awto"));
+            }
+
+            match item.fields.iter().next().filter(|_| newtype) {
+                Some(field) => {
+                    let ident = &item.ident;
+                    let inner = &field.ty;
+                    quote!(
+                        #item
+
+                        impl ::std::convert::From<#ident> for #inner {
+                            fn from(val: #ident) -> Self {
+                                val.0
+                            }
+                        }
+
+                        impl ::std::convert::From<#inner> for #ident {
+                            fn from(val: #inner) -> Self {
+                                Self(val)
+                            }
+                        }
+                    )
+                }
+                None => item.to_token_stream(),
+            }
+        });
+    let uses = uses.iter().filter(|item_use| match &item_use.tree {
+        syn::UseTree::Path(path) => path.ident != "awto",
+        _ => true,
+    });
+
+    Ok(quote!(
+        #![doc = "Structs of the schema used by the package, copied from the `schema` package."]
+        #![allow(unused_imports)]
+
+        #( #uses )*
+        use self::prelude::*;
+
+        /// Types of the prelude of `awto` used by the schema.
+        mod prelude {
+            pub use ::chrono::{DateTime, FixedOffset};
+            pub use ::uuid::Uuid;
+        }
+
+        #( #items )*
+    )
+    .to_string())
+}
+
+/// Formats the Rust file at `path` with `rustfmt`, leaving it unformatted when `rustfmt` is unavailable.
+async fn format_file(path: &Path) {
+    let status = tokio::process::Command::new("rustfmt")
+        .args(["--edition", "2018"])
+        .arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .await;
+    if !matches!(status, Ok(status) if status.success()) {
+        debug!("could not format '{}'", path.display());
+    }
+}
+
+/// Records `publication` in the manifest of the generated package and in the header of its lib,
+/// so later generations warn when the package changes.
+async fn record_publication(
+    package: &DatabasePackage,
+    mut manifest: GenerationManifest,
+    publication: Publication,
+) -> Result<()> {
+    let lib_path = format!("{}/src/lib.rs", package.dir());
+    let lib = fs::read_to_string(&lib_path)
+        .await
+        .with_context(|| format!("could not read file '{}'", lib_path))?;
+    let published_lib = with_publication_header(&lib, &publication);
+    fs::write(&lib_path, &published_lib)
+        .await
+        .with_context(|| format!("could not write file '{}'", lib_path))?;
+
+    // Local edits of the lib are still reported when it is next generated
+    if let Some(file) = manifest.files.get_mut("src/lib.rs") {
+        if file.hash == content_hash(lib.as_bytes()) {
+            file.hash = content_hash(published_lib.as_bytes());
+        }
+    }
+    manifest.published = Some(publication);
+    manifest.save(package.dir()).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::compile::GENERATED_HEADER_PREFIX;
+    use crate::schema::SchemaModel;
+
+    fn publication(schema_version: Option<&str>) -> Publication {
+        Publication {
+            registry: "acme".to_string(),
+            version: "1.4.0".to_string(),
+            schema_version: schema_version.map(str::to_string),
+            fingerprint: String::new(),
+        }
+    }
+
+    fn package() -> DatabasePackage {
+        let model = SchemaModel {
+            name: "Product".to_string(),
+            is_database_table: true,
+            ..Default::default()
+        };
+        DatabasePackage::from_models(&[model], "acme-database", &DatabaseConfig::default())
+            .remove(0)
+    }
+
+    #[test]
+    fn versions() {
+        let version: Version = "1.3.2".parse().unwrap();
+        assert_eq!(version.bump(Bump::Major).to_string(), "2.0.0");
+        assert_eq!(version.bump(Bump::Minor).to_string(), "1.4.0");
+        assert_eq!(version.bump(Bump::Patch).to_string(), "1.3.3");
+        assert!("1.4".parse::<Version>().is_err());
+        assert!("1.4.0-beta".parse::<Version>().is_err());
+
+        let database = Database::parse_from(["database", "--registry", "acme", "--bump", "minor"]);
+        let current = "0.1.0".parse().unwrap();
+        assert_eq!(
+            database.next_version(None, current).unwrap().to_string(),
+            "0.2.0"
+        );
+        assert_eq!(
+            database
+                .next_version(Some(version), current)
+                .unwrap()
+                .to_string(),
+            "1.4.0"
+        );
+
+        let database =
+            Database::parse_from(["database", "--registry", "acme", "--version", "1.3.2"]);
+        assert!(database.next_version(Some(version), current).is_err());
+        assert!(Database::try_parse_from(["database", "--registry", "acme"]).is_err());
+        assert!(Database::try_parse_from([
+            "database",
+            "--registry",
+            "acme",
+            "--version",
+            "1.4.0",
+            "--bump",
+            "minor"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn manifests() {
+        let cargo_toml = r#"[package]
+name = "acme-database"
+version = "0.1.0"
+edition = "2018"
+
+[dependencies]
+log = "0.4"
+schema = { path = "../../schema" }
+
+[dependencies.acme-telemetry]
+version = "0.3"
+
+[build-dependencies]
+schema = { path = "../../schema" }
+
+[dev-dependencies]
+tokio = "1.12"
+"#;
+        let config = PublishConfig {
+            license: Some("MIT".to_string()),
+            authors: vec!["Acme".to_string()],
+            ..Default::default()
+        };
+
+        let manifest =
+            publish_manifest(cargo_toml, &package(), &config, &publication(Some("0.2.0"))).unwrap();
+        let manifest: toml::Value = toml::from_str(&manifest).unwrap();
+        assert_eq!(manifest["package"]["version"].as_str(), Some("1.4.0"));
+        assert_eq!(manifest["package"]["license"].as_str(), Some("MIT"));
+        assert_eq!(manifest["package"]["publish"][0].as_str(), Some("acme"));
+        assert_eq!(manifest["package"]["authors"][0].as_str(), Some("Acme"));
+        assert!(manifest["package"].get("repository").is_none());
+        assert_eq!(
+            manifest["dependencies"]["schema"]["version"].as_str(),
+            Some("0.2.0")
+        );
+        assert_eq!(
+            manifest["dependencies"]["schema"]["registry"].as_str(),
+            Some("acme")
+        );
+        assert_eq!(
+            manifest["dependencies"]["acme-telemetry"]["version"].as_str(),
+            Some("0.3")
+        );
+        assert!(manifest.get("build-dependencies").is_none());
+        assert!(manifest.get("dev-dependencies").is_none());
+        assert!(manifest.get("workspace").is_some());
+
+        let manifest =
+            publish_manifest(cargo_toml, &package(), &config, &publication(None)).unwrap();
+        assert!(!manifest.contains("schema"));
+
+        let cargo_toml = cargo_toml.replace("version = \"0.3\"", "path = \"../telemetry\"");
+        assert!(publish_manifest(&cargo_toml, &package(), &config, &publication(None)).is_err());
+    }
+
+    #[test]
+    fn libs() {
+        let lib = format!(
+            "{} v0.1.2\n\n#![doc = include_str!(concat!(env!(\"OUT_DIR\"), \"/docs.md\"))]\n\ninclude!(concat!(env!(\"OUT_DIR\"), \"/app.rs\"));\n",
+            GENERATED_HEADER_PREFIX
+        );
+
+        let published = publish_lib(&lib, &publication(None), true);
+        assert!(published.contains("// Published to the 'acme' registry as v1.4.0\n"));
+        assert!(published.contains("#![doc = include_str!(\"docs.md\")]"));
+        assert!(published.contains("include!(\"app.rs\");"));
+        assert!(published.ends_with("pub mod schema;\n"));
+        assert!(!publish_lib(&lib, &publication(None), false).contains("mod schema"));
+    }
+
+    #[test]
+    fn inlined_schema() {
+        let code = "impl From<::schema::Product> for Model {}\nlet _ = crate::other::schema::Name;\n/// [`Product`](schema::Product)";
+        let (inlined, names) = inline_schema_paths(code);
+        assert_eq!(
+            inlined,
+            "impl From<crate::schema::Product> for Model {}\nlet _ = crate::other::schema::Name;\n/// [`Product`](schema::Product)"
+        );
+        assert_eq!(names.into_iter().collect::<Vec<_>>(), ["Product"]);
+
+        let source = r#"
+            use awto::prelude::*;
+            use std::collections::HashMap;
+
+            schema! {
+                #[awto(newtype)]
+                pub struct Cents(pub i64);
+
+                #[database_table]
+                #[awto(audit)]
+                pub struct Product {
+                    pub id: Uuid,
+                    #[awto(default = 0)]
+                    pub price: Cents,
+                }
+
+                #[protobuf_message]
+                pub struct Empty {}
+            }
+            "#;
+        let schema =
+            inline_schema(source, vec!["Product".to_string()].into_iter().collect()).unwrap();
+        let file = syn::parse_file(&schema).unwrap();
+        let structs: Vec<_> = file
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                syn::Item::Struct(item) => Some(item.ident.to_string()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(structs, ["Cents", "Product"]);
+        assert!(schema.contains("use std :: collections :: HashMap"));
+        assert!(!schema.contains("use awto") && !schema.contains("# [awto"));
+        assert!(schema.contains("impl :: std :: convert :: From < Cents > for i64"));
+    }
+}
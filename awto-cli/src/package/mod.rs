@@ -0,0 +1,30 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use clap::Parser;
+
+use crate::{cancel::Cancellation, Runnable};
+
+pub use self::database::Database;
+
+mod database;
+
+/// Prepares generated packages for publishing to a registry
+#[derive(Parser)]
+pub struct Package {
+    #[clap(subcommand)]
+    pub subcmd: SubCommand,
+}
+
+#[derive(Parser)]
+pub enum SubCommand {
+    Database(Database),
+}
+
+#[async_trait]
+impl Runnable for Package {
+    async fn run(&mut self, cancellation: &Cancellation) -> Result<()> {
+        match &mut self.subcmd {
+            SubCommand::Database(database) => database.run(cancellation).await,
+        }
+    }
+}
@@ -0,0 +1,340 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use anyhow::{Context, Result};
+use awto_compile::{
+    api_docs::{check_example, ApiExample, ApiItem},
+    error::Error as CompileError,
+};
+use serde::Deserialize;
+use tokio::fs;
+
+use crate::error::Error;
+
+use super::SchemaModel;
+
+/// The `[api_docs]` section of `awto.toml`, pointing at the overlay file of the API documentation.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiDocsConfig {
+    /// Path of the overlay file, such as `api-docs.toml`, whose descriptions, examples and tags win over
+    /// the doc comments of the schema and service packages.
+    pub overlay: Option<String>,
+}
+
+/// An overlay file of the API documentation, such as:
+///
+/// ```toml
+/// [models.Product]
+/// description = "A product of the catalog."
+/// tags = ["catalog"]
+///
+/// [fields."Product.name"]
+/// examples = ["Chair"]
+///
+/// [operations."ProductService.FindProduct"]
+/// description = "Lists the products of an id."
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiDocsOverlay {
+    /// Models by name, such as `Product`.
+    pub models: BTreeMap<String, ItemDocs>,
+    /// Fields by model and name, such as `Product.name`.
+    pub fields: BTreeMap<String, FieldDocs>,
+    /// Methods of the services by service and name, such as `ProductService.FindProduct`.
+    pub operations: BTreeMap<String, ItemDocs>,
+}
+
+/// Documentation of a model or method in an [`ApiDocsOverlay`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ItemDocs {
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+}
+
+/// Documentation of a field in an [`ApiDocsOverlay`].
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FieldDocs {
+    pub description: Option<String>,
+    /// Example values, checked against the type of the field.
+    pub examples: Vec<Example>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Example {
+    Boolean(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+}
+
+impl Example {
+    fn as_api_example(&self) -> ApiExample<'_> {
+        match self {
+            Example::Boolean(b) => ApiExample::Boolean(*b),
+            Example::Integer(i) => ApiExample::Integer(*i),
+            Example::Float(f) => ApiExample::Float(*f),
+            Example::String(s) => ApiExample::String(s),
+        }
+    }
+}
+
+impl ApiDocsOverlay {
+    /// Line of the build scripts of the protobuf and REST packages replaced by the entries of the overlay.
+    const API_DOCS_MARKER: &'static str = "const API_DOCS: &[ApiDocOverride] = &[];";
+
+    /// Reads the overlay file of `config`, which is empty when the section sets none.
+    pub async fn load(config: &ApiDocsConfig) -> Result<Self> {
+        let path = match &config.overlay {
+            Some(path) => path,
+            None => return Ok(ApiDocsOverlay::default()),
+        };
+        let content = fs::read_to_string(path)
+            .await
+            .with_context(|| format!("could not read api docs '{}'", path))?;
+
+        toml::from_str(&content).with_context(|| format!("could not parse api docs '{}'", path))
+    }
+
+    /// Checks every entry describes a model or field of `models`, and that the examples of fields fit their type,
+    /// failing with all the problems found.
+    ///
+    /// Models and fields of every feature are known, enabled or not. Methods are checked by the build scripts of
+    /// the generated packages, as the cli does not read the services.
+    pub fn check(&self, path: &str, models: &[SchemaModel]) -> Result<(), Error> {
+        let mut problems = Vec::new();
+        let unknown = |item: String, kind: &str| CompileError::UnknownApiDocItem {
+            item,
+            kind: kind.to_string(),
+        };
+        for name in self.models.keys() {
+            if !models.iter().any(|model| model.name == *name) {
+                problems.push(unknown(name.clone(), "model").to_string());
+            }
+        }
+        for (item, docs) in &self.fields {
+            let field = split_item(item).and_then(|(model, field)| {
+                let model = models.iter().find(|other| other.name == model)?;
+                model.fields.iter().find(|other| other.name == field)
+            });
+            let field = match field {
+                Some(field) => field,
+                None => {
+                    problems.push(unknown(item.clone(), "field").to_string());
+                    continue;
+                }
+            };
+            for example in &docs.examples {
+                if let Err(reason) = check_example(&field.ty, &example.as_api_example()) {
+                    let err = CompileError::InvalidApiExample {
+                        item: item.clone(),
+                        example: example.as_api_example().to_string(),
+                        ty: field.ty.clone(),
+                        reason,
+                    };
+                    problems.push(err.to_string());
+                }
+            }
+        }
+        for item in self
+            .operations
+            .keys()
+            .filter(|item| split_item(item).is_none())
+        {
+            problems.push(format!(
+                "api docs describe method `{}`, which is not named `Service.Method`",
+                item
+            ));
+        }
+
+        if problems.is_empty() {
+            return Ok(());
+        }
+        Err(Error::InvalidApiDocs {
+            path: path.to_string(),
+            problems: problems
+                .iter()
+                .map(|problem| format!("\n  {}", problem))
+                .collect(),
+        })
+    }
+
+    /// Returns `template` with the entries of the overlay in place of its empty `API_DOCS`.
+    pub fn render(&self, template: &str) -> String {
+        let items = self
+            .models
+            .iter()
+            .map(|(name, docs)| {
+                (
+                    ApiItem::Model(name),
+                    docs.description.as_deref(),
+                    &[][..],
+                    &docs.tags[..],
+                )
+            })
+            .chain(self.fields.iter().filter_map(|(item, docs)| {
+                let (model, field) = split_item(item)?;
+                Some((
+                    ApiItem::Field(model, field),
+                    docs.description.as_deref(),
+                    &docs.examples[..],
+                    &[][..],
+                ))
+            }))
+            .chain(self.operations.iter().filter_map(|(item, docs)| {
+                let (service, method) = split_item(item)?;
+                Some((
+                    ApiItem::Operation(service, method),
+                    docs.description.as_deref(),
+                    &[][..],
+                    &docs.tags[..],
+                ))
+            }));
+
+        let mut entries = String::new();
+        for (item, description, examples, tags) in items {
+            let item = match item {
+                ApiItem::Model(name) => {
+                    format!("awto_compile::api_docs::ApiItem::Model({:?})", name)
+                }
+                ApiItem::Field(model, field) => format!(
+                    "awto_compile::api_docs::ApiItem::Field({:?}, {:?})",
+                    model, field
+                ),
+                ApiItem::Operation(service, method) => format!(
+                    "awto_compile::api_docs::ApiItem::Operation({:?}, {:?})",
+                    service, method
+                ),
+            };
+            let examples: Vec<String> = examples
+                .iter()
+                .map(|example| match example {
+                    Example::Boolean(b) => {
+                        format!("awto_compile::api_docs::ApiExample::Boolean({})", b)
+                    }
+                    Example::Integer(i) => {
+                        format!("awto_compile::api_docs::ApiExample::Integer({})", i)
+                    }
+                    Example::Float(f) => {
+                        format!("awto_compile::api_docs::ApiExample::Float({:?})", f)
+                    }
+                    Example::String(s) => {
+                        format!("awto_compile::api_docs::ApiExample::String({:?})", s)
+                    }
+                })
+                .collect();
+            write!(
+                entries,
+                "\n    ApiDocOverride {{\n        item: {},\n        description: {:?},\n        examples: &[{}],\n        tags: &{:?},\n    }},",
+                item,
+                description,
+                examples.join(", "),
+                tags,
+            )
+            .unwrap();
+        }
+        if entries.is_empty() {
+            return template.to_string();
+        }
+
+        template.replacen(
+            Self::API_DOCS_MARKER,
+            &format!("const API_DOCS: &[ApiDocOverride] = &[{}\n];", entries),
+            1,
+        )
+    }
+}
+
+/// Splits an item such as `Product.name` into its parent and name.
+fn split_item(item: &str) -> Option<(&str, &str)> {
+    item.split_once('.')
+        .filter(|(parent, name)| !parent.is_empty() && !name.is_empty() && !name.contains('.'))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::schema::parse_schema_models;
+
+    const SCHEMA: &str = r#"
+        schema! {
+            /// A product sold in the shop.
+            #[database_table]
+            pub struct Product {
+                pub id: Uuid,
+                pub name: String,
+                pub version: i32,
+            }
+        }
+    "#;
+
+    const OVERLAY: &str = r#"
+        [models.Product]
+        description = "A product of the catalog."
+        tags = ["catalog"]
+
+        [fields."Product.name"]
+        examples = ["Chair", "Desk"]
+
+        [operations."ProductService.FindProduct"]
+        description = "Lists the products of an id."
+    "#;
+
+    #[test]
+    fn check() {
+        let models = parse_schema_models(SCHEMA).unwrap();
+        let overlay: ApiDocsOverlay = toml::from_str(OVERLAY).unwrap();
+        assert!(overlay.check("api-docs.toml", &models).is_ok());
+
+        // Renamed items and examples of the wrong type are all reported
+        let overlay: ApiDocsOverlay = toml::from_str(
+            &OVERLAY
+                .replace("[models.Product]", "[models.Products]")
+                .replace(
+                    r#"examples = ["Chair", "Desk"]"#,
+                    "examples = [\"Chair\"]\n[fields.\"Product.version\"]\nexamples = [\"two\"]",
+                ),
+        )
+        .unwrap();
+        let err = overlay.check("api-docs.toml", &models).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the api docs of 'api-docs.toml' do not match the schema:\n  \
+            api docs describe model `Products`, which does not exist, update the entry if it was renamed\n  \
+            example \"two\" of `Product.version` does not fit its type i32: expected an integer"
+        );
+    }
+
+    #[test]
+    fn render() {
+        let overlay: ApiDocsOverlay = toml::from_str(OVERLAY).unwrap();
+        let template = "const API_DOCS: &[ApiDocOverride] = &[];";
+        assert_eq!(
+            overlay.render(template),
+            r#"const API_DOCS: &[ApiDocOverride] = &[
+    ApiDocOverride {
+        item: awto_compile::api_docs::ApiItem::Model("Product"),
+        description: Some("A product of the catalog."),
+        examples: &[],
+        tags: &["catalog"],
+    },
+    ApiDocOverride {
+        item: awto_compile::api_docs::ApiItem::Field("Product", "name"),
+        description: None,
+        examples: &[awto_compile::api_docs::ApiExample::String("Chair"), awto_compile::api_docs::ApiExample::String("Desk")],
+        tags: &[],
+    },
+    ApiDocOverride {
+        item: awto_compile::api_docs::ApiItem::Operation("ProductService", "FindProduct"),
+        description: Some("Lists the products of an id."),
+        examples: &[],
+        tags: &[],
+    },
+];"#
+        );
+        assert_eq!(ApiDocsOverlay::default().render(template), template);
+    }
+}
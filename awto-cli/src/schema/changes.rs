@@ -0,0 +1,427 @@
+use std::{fmt, path::Path};
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use clap::{ArgEnum, Parser};
+use colored::Colorize;
+
+use crate::{cancel::Cancellation, error::Error, Runnable};
+
+use super::{
+    load_schema_models, parse_schema_models, snapshot::SchemaSnapshot, SchemaModel, SCHEMA_LIB_PATH,
+};
+
+/// Prints the changes to the app schema since a snapshot
+#[derive(Parser)]
+pub struct Changes {
+    /// Snapshot file or git ref to compare against, defaulting to the latest snapshot
+    #[clap(long)]
+    pub since: Option<String>,
+    /// Format of the printed changes
+    #[clap(arg_enum, long, default_value = "text")]
+    pub format: ChangesFormat,
+    /// Prints more information
+    #[clap(short, long)]
+    pub verbose: bool,
+}
+
+#[derive(ArgEnum, Clone, Copy, PartialEq, Eq)]
+pub enum ChangesFormat {
+    Text,
+    Markdown,
+}
+
+#[async_trait]
+impl Runnable for Changes {
+    async fn run(&mut self, _cancellation: &Cancellation) -> Result<()> {
+        let models = load_schema_models().await?;
+        let (since, old_models) = match &self.since {
+            Some(since) if Path::new(since).is_file() => {
+                (since.clone(), SchemaSnapshot::read(since).await?.models)
+            }
+            Some(git_ref) => (git_ref.clone(), read_git_schema_models(git_ref).await?),
+            None => match SchemaSnapshot::latest().await? {
+                Some((path, snapshot)) => (path.display().to_string(), snapshot.models),
+                None => return Err(Error::NoSnapshot.into()),
+            },
+        };
+
+        let changes = diff_models(&old_models, &models);
+        match self.format {
+            ChangesFormat::Text => print_text(&since, &changes),
+            ChangesFormat::Markdown => out!("{}", markdown(&changes)),
+        }
+
+        Ok(())
+    }
+
+    fn is_verbose(&self) -> bool {
+        self.verbose
+    }
+}
+
+/// Reads the models of the schema lib at `git_ref` with `git show`.
+async fn read_git_schema_models(git_ref: &str) -> Result<Vec<SchemaModel>> {
+    read_git_source(git_ref)
+        .await
+        .and_then(|source| parse_schema_models(&source))
+        .map_err(|source| {
+            Error::Snapshot {
+                since: git_ref.to_string(),
+                source,
+            }
+            .into()
+        })
+}
+
+async fn read_git_source(git_ref: &str) -> Result<String> {
+    let output = tokio::process::Command::new("git")
+        .arg("show")
+        .arg(format!("{}:{}", git_ref, SCHEMA_LIB_PATH))
+        .output()
+        .await
+        .context("could not run git")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "{}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+        .context(format!("git show failed for '{}'", SCHEMA_LIB_PATH));
+    }
+
+    String::from_utf8(output.stdout).context("schema lib is not valid utf-8")
+}
+
+/// A change to the schema between two versions of its models.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+    ModelAdded {
+        model: String,
+    },
+    ModelRemoved {
+        model: String,
+    },
+    FieldAdded {
+        model: String,
+        field: String,
+        ty: String,
+    },
+    FieldRemoved {
+        model: String,
+        field: String,
+    },
+    FieldRetyped {
+        model: String,
+        field: String,
+        from: String,
+        to: String,
+    },
+    /// An attribute added to a model, or to one of its fields when `field` is set.
+    AttributeAdded {
+        model: String,
+        field: Option<String>,
+        attribute: String,
+    },
+    /// An attribute removed from a model, or from one of its fields when `field` is set.
+    AttributeRemoved {
+        model: String,
+        field: Option<String>,
+        attribute: String,
+    },
+}
+
+impl Change {
+    fn symbol(&self) -> char {
+        match self {
+            Change::ModelAdded { .. }
+            | Change::FieldAdded { .. }
+            | Change::AttributeAdded { .. } => '+',
+            Change::ModelRemoved { .. }
+            | Change::FieldRemoved { .. }
+            | Change::AttributeRemoved { .. } => '-',
+            Change::FieldRetyped { .. } => '~',
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let item = |model: &str, field: &Option<String>| match field {
+            Some(field) => format!("{}.{}", model, field),
+            None => model.to_string(),
+        };
+
+        match self {
+            Change::ModelAdded { model } => write!(f, "added model `{}`", model),
+            Change::ModelRemoved { model } => write!(f, "removed model `{}`", model),
+            Change::FieldAdded { model, field, ty } => {
+                write!(f, "added field `{}.{}` of type `{}`", model, field, ty)
+            }
+            Change::FieldRemoved { model, field } => {
+                write!(f, "removed field `{}.{}`", model, field)
+            }
+            Change::FieldRetyped {
+                model,
+                field,
+                from,
+                to,
+            } => write!(
+                f,
+                "changed type of `{}.{}` from `{}` to `{}`",
+                model, field, from, to
+            ),
+            Change::AttributeAdded {
+                model,
+                field,
+                attribute,
+            } => write!(
+                f,
+                "added attribute `{}` to `{}`",
+                attribute,
+                item(model, field)
+            ),
+            Change::AttributeRemoved {
+                model,
+                field,
+                attribute,
+            } => write!(
+                f,
+                "removed attribute `{}` from `{}`",
+                attribute,
+                item(model, field)
+            ),
+        }
+    }
+}
+
+/// Returns the changes from the `old` models to the `new` models, in the order of the `new` schema
+/// followed by removed models.
+pub fn diff_models(old: &[SchemaModel], new: &[SchemaModel]) -> Vec<Change> {
+    let mut changes = Vec::new();
+
+    for model in new {
+        let old_model = match old.iter().find(|old_model| old_model.name == model.name) {
+            Some(old_model) => old_model,
+            None => {
+                changes.push(Change::ModelAdded {
+                    model: model.name.clone(),
+                });
+                continue;
+            }
+        };
+
+        diff_attributes(
+            &model.name,
+            None,
+            &old_model.attributes,
+            &model.attributes,
+            &mut changes,
+        );
+
+        for field in &model.fields {
+            let old_field = match old_model
+                .fields
+                .iter()
+                .find(|old_field| old_field.name == field.name)
+            {
+                Some(old_field) => old_field,
+                None => {
+                    changes.push(Change::FieldAdded {
+                        model: model.name.clone(),
+                        field: field.name.clone(),
+                        ty: field.ty.clone(),
+                    });
+                    continue;
+                }
+            };
+
+            if old_field.ty != field.ty {
+                changes.push(Change::FieldRetyped {
+                    model: model.name.clone(),
+                    field: field.name.clone(),
+                    from: old_field.ty.clone(),
+                    to: field.ty.clone(),
+                });
+            }
+            diff_attributes(
+                &model.name,
+                Some(&field.name),
+                &old_field.attributes,
+                &field.attributes,
+                &mut changes,
+            );
+        }
+
+        for old_field in &old_model.fields {
+            if !model
+                .fields
+                .iter()
+                .any(|field| field.name == old_field.name)
+            {
+                changes.push(Change::FieldRemoved {
+                    model: model.name.clone(),
+                    field: old_field.name.clone(),
+                });
+            }
+        }
+    }
+
+    for old_model in old {
+        if !new.iter().any(|model| model.name == old_model.name) {
+            changes.push(Change::ModelRemoved {
+                model: old_model.name.clone(),
+            });
+        }
+    }
+
+    changes
+}
+
+fn diff_attributes(
+    model: &str,
+    field: Option<&str>,
+    old: &[String],
+    new: &[String],
+    changes: &mut Vec<Change>,
+) {
+    for attribute in old.iter().filter(|attribute| !new.contains(attribute)) {
+        changes.push(Change::AttributeRemoved {
+            model: model.to_string(),
+            field: field.map(str::to_string),
+            attribute: attribute.clone(),
+        });
+    }
+    for attribute in new.iter().filter(|attribute| !old.contains(attribute)) {
+        changes.push(Change::AttributeAdded {
+            model: model.to_string(),
+            field: field.map(str::to_string),
+            attribute: attribute.clone(),
+        });
+    }
+}
+
+fn print_text(since: &str, changes: &[Change]) {
+    if changes.is_empty() {
+        outln!("no schema changes since '{}'", since);
+        return;
+    }
+
+    outln!("{} schema changes since '{}':", changes.len(), since);
+    for change in changes {
+        let line = format!("{} {}", change.symbol(), change);
+        match change.symbol() {
+            '+' => outln!("{}", line.green()),
+            '-' => outln!("{}", line.red()),
+            _ => outln!("{}", line.yellow()),
+        }
+    }
+}
+
+/// Renders `changes` as a markdown section which can be pasted into a changelog.
+pub fn markdown(changes: &[Change]) -> String {
+    let mut markdown = "### Schema changes\n\n".to_string();
+    if changes.is_empty() {
+        markdown.push_str("No changes.\n");
+    }
+
+    for change in changes {
+        let sentence = change.to_string();
+        let mut chars = sentence.chars();
+        if let Some(first) = chars.next() {
+            markdown.push_str("- ");
+            markdown.extend(first.to_uppercase());
+            markdown.push_str(chars.as_str());
+            markdown.push('\n');
+        }
+    }
+
+    markdown
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn diff_schemas() {
+        let old = parse_schema_models(
+            r#"
+schema! {
+    #[database_table]
+    pub struct Product {
+        pub id: Uuid,
+        pub price: i32,
+        #[awto(unique)]
+        pub name: String,
+        pub legacy: bool,
+    }
+
+    #[protobuf_message]
+    pub struct ProductId {
+        pub id: Uuid,
+    }
+}
+"#,
+        )
+        .unwrap();
+        let new = parse_schema_models(
+            r#"
+schema! {
+    #[database_table]
+    #[awto(database = "main")]
+    pub struct Product {
+        pub id: Uuid,
+        pub price: i64,
+        #[awto(max_len = 120)]
+        pub name: String,
+        pub sku: Option<String>,
+    }
+
+    #[database_table]
+    pub struct Order {
+        pub id: Uuid,
+    }
+}
+"#,
+        )
+        .unwrap();
+
+        let changes: Vec<_> = diff_models(&old, &new)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                r#"added attribute `database = "main"` to `Product`"#,
+                "changed type of `Product.price` from `i32` to `i64`",
+                "removed attribute `unique` from `Product.name`",
+                "added attribute `max_len = 120` to `Product.name`",
+                "added field `Product.sku` of type `Option<String>`",
+                "removed field `Product.legacy`",
+                "added model `Order`",
+                "removed model `ProductId`",
+            ]
+        );
+        assert!(diff_models(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn markdown_changes() {
+        let changes = [
+            Change::ModelAdded {
+                model: "Order".to_string(),
+            },
+            Change::FieldRemoved {
+                model: "Product".to_string(),
+                field: "legacy".to_string(),
+            },
+        ];
+
+        assert_eq!(
+            markdown(&changes),
+            "### Schema changes\n\n- Added model `Order`\n- Removed field `Product.legacy`\n"
+        );
+        assert_eq!(markdown(&[]), "### Schema changes\n\nNo changes.\n");
+    }
+}
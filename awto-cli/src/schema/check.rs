@@ -0,0 +1,131 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use awto_compile::{
+    database::with_map_storage,
+    dialect::{compatibility_report, CompatibilityReport, Degradation, Dialect},
+    generator::GeneratorInput,
+};
+use clap::{ArgEnum, Parser};
+use log::{error, info, warn};
+
+use crate::{
+    cancel::Cancellation,
+    compile::{check_schema_package, prepare_awto_dir, read_ir},
+    config::{Config, CONFIG_PATH},
+    error::Error,
+    util::WorkspaceContext,
+    Runnable,
+};
+
+/// Checks which features of the models a sql dialect lacks, without generating the packages
+#[derive(Parser)]
+pub struct Check {
+    /// Dialect the models are checked against: `postgres`, `sqlite` or `mysql`
+    #[clap(long, default_value = "postgres")]
+    pub dialect: Dialect,
+    /// Fails when any feature degrades on the dialect, rather than only when one is not supported
+    #[clap(long)]
+    pub deny_degradations: bool,
+    /// Format of the printed report
+    #[clap(arg_enum, long, default_value = "text")]
+    pub format: CheckFormat,
+    /// Overwrites the `ir` package directory when it was not generated by awto
+    #[clap(long)]
+    pub force: bool,
+    /// Prints more information
+    #[clap(short, long)]
+    pub verbose: bool,
+}
+
+#[derive(ArgEnum, Clone, Copy, PartialEq, Eq)]
+pub enum CheckFormat {
+    Text,
+    Json,
+}
+
+#[async_trait]
+impl Runnable for Check {
+    async fn run(&mut self, cancellation: &Cancellation) -> Result<()> {
+        let mut workspace = WorkspaceContext::load(".").await?;
+        check_schema_package(&workspace).await?;
+        prepare_awto_dir(self.force).await?;
+        let input: GeneratorInput =
+            serde_json::from_value(read_ir(&mut workspace, self.force, cancellation).await?)
+                .context("package 'ir' printed invalid models")?;
+        let map_storage = Config::load(CONFIG_PATH).await?.database.map_storage;
+        let models = with_map_storage(input.models, map_storage.compiled());
+
+        let report = compatibility_report(&models, self.dialect);
+        match self.format {
+            CheckFormat::Text => {
+                for degradation in &report.degradations {
+                    match degradation.degradation {
+                        Degradation::Error => error!("{}", degradation),
+                        _ => warn!("{}", degradation),
+                    }
+                }
+                info!(
+                    "checked {} models against {}: {} degradations",
+                    models.len(),
+                    self.dialect,
+                    report.degradations.len()
+                );
+            }
+            CheckFormat::Json => outln!("{}", serde_json::to_string_pretty(&report)?),
+        }
+
+        let denied = denied_degradations(&report, self.deny_degradations);
+        if denied > 0 {
+            return Err(Error::DialectIncompatible {
+                dialect: self.dialect.id().to_string(),
+                count: denied,
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+
+    fn is_verbose(&self) -> bool {
+        self.verbose
+    }
+}
+
+/// Returns the number of degradations of `report` failing the check, which are all of them with `deny_degradations`.
+fn denied_degradations(report: &CompatibilityReport, deny_degradations: bool) -> usize {
+    if deny_degradations {
+        report.degradations.len()
+    } else {
+        report.errors().count()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use awto_compile::dialect::{AppliedDegradation, Feature, COMPATIBILITY_VERSION};
+
+    use super::*;
+
+    #[test]
+    fn denied() {
+        let degradation = |feature: Feature| AppliedDegradation {
+            model: "Event".to_string(),
+            field: None,
+            feature,
+            degradation: feature.degradation(Dialect::Sqlite).unwrap(),
+        };
+        let mut report = CompatibilityReport {
+            version: COMPATIBILITY_VERSION,
+            dialect: Dialect::Sqlite,
+            degradations: vec![degradation(Feature::Notify)],
+        };
+        assert_eq!(denied_degradations(&report, false), 0);
+        assert_eq!(denied_degradations(&report, true), 1);
+
+        report
+            .degradations
+            .push(degradation(Feature::RowLevelSecurity));
+        assert_eq!(denied_degradations(&report, false), 1);
+        assert_eq!(denied_degradations(&report, true), 2);
+    }
+}
@@ -0,0 +1,288 @@
+use std::fmt;
+
+use colored::Colorize;
+use syn::spanned::Spanned;
+
+use super::Location;
+
+/// Range of source code in the schema lib, from the start of its first token to the end of its last.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: Location,
+    pub end: Location,
+}
+
+impl Span {
+    pub fn of(tokens: &impl Spanned) -> Self {
+        let span = tokens.span();
+        Span {
+            start: Location::from(span.start()),
+            end: Location::from(span.end()),
+        }
+    }
+
+    /// Returns the span of the identifier `name` starting at `location`.
+    pub fn of_name(location: Location, name: &str) -> Self {
+        Span {
+            start: location,
+            end: Location {
+                line: location.line,
+                column: location.column + name.chars().count(),
+            },
+        }
+    }
+}
+
+/// A problem found while validating the schema lib, pointing at the code causing it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub message: String,
+    pub span: Span,
+    /// Short explanation printed under the span.
+    pub label: Option<String>,
+    /// Suggestion on how to fix the problem.
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn new(message: impl Into<String>, span: Span) -> Self {
+        Diagnostic {
+            message: message.into(),
+            span,
+            label: None,
+            help: None,
+        }
+    }
+
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    pub fn help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    /// Returns the diagnostics of each error of `err`, which may hold several.
+    pub fn from_syn(err: syn::Error) -> Vec<Self> {
+        err.into_iter()
+            .map(|err| {
+                // Messages of the schema macro hold their help after an empty line
+                let message = err.to_string();
+                let (message, help) = match message.split_once("\n\n") {
+                    Some((message, help)) => (message.to_string(), Some(help.to_string())),
+                    None => (message, None),
+                };
+                Diagnostic {
+                    message,
+                    span: Span::of(&err.span()),
+                    label: None,
+                    help,
+                }
+            })
+            .collect()
+    }
+}
+
+/// The diagnostics of a schema lib which failed validation, along with its source to render them.
+#[derive(Debug)]
+pub struct Diagnostics {
+    pub path: String,
+    pub source: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    /// Renders every diagnostic like rustc does, with the line of source they point at and a caret under their span.
+    pub fn render(&self) -> String {
+        let lines: Vec<&str> = self.source.lines().collect();
+        let gutter_width = self
+            .diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.span.start.line.to_string().len())
+            .max()
+            .unwrap_or(1);
+        let gutter = |line: &str| {
+            format!("{:>width$} |", line, width = gutter_width)
+                .blue()
+                .bold()
+        };
+
+        let mut rendered = String::new();
+        for diagnostic in &self.diagnostics {
+            let Span { start, end } = diagnostic.span;
+            rendered += &format!(
+                "{}{}\n",
+                "error".red().bold(),
+                format!(": {}", diagnostic.message).bold()
+            );
+            rendered += &format!(
+                "{:>width$}{} {}:{}\n",
+                "",
+                "-->".blue().bold(),
+                self.path,
+                start,
+                width = gutter_width
+            );
+
+            if let Some(line) = start.line.checked_sub(1).and_then(|index| lines.get(index)) {
+                let line_len = line.chars().count();
+                let caret_start = start.column.saturating_sub(1).min(line_len);
+                let caret_end = if end.line == start.line {
+                    end.column.saturating_sub(1).min(line_len)
+                } else {
+                    line_len
+                };
+                let carets = "^".repeat(caret_end.saturating_sub(caret_start).max(1));
+                let label = diagnostic
+                    .label
+                    .as_ref()
+                    .map(|label| format!(" {}", label))
+                    .unwrap_or_default();
+
+                rendered += &format!("{}\n", gutter(""));
+                rendered += &format!("{} {}\n", gutter(&start.line.to_string()), line);
+                rendered += &format!(
+                    "{} {}{}\n",
+                    gutter(""),
+                    " ".repeat(caret_start),
+                    format!("{}{}", carets, label).red().bold()
+                );
+            }
+
+            if let Some(help) = &diagnostic.help {
+                rendered += &format!("{}\n", gutter(""));
+                for (i, line) in help.lines().enumerate() {
+                    let prefix = if i == 0 { "= help:" } else { "       " };
+                    rendered += &format!(
+                        "{:width$} {} {}\n",
+                        "",
+                        prefix.bold(),
+                        line,
+                        width = gutter_width
+                    );
+                }
+            }
+            rendered.push('\n');
+        }
+
+        let count = self.diagnostics.len();
+        rendered += &format!(
+            "{}{}",
+            "error".red().bold(),
+            format!(
+                ": could not validate the schema due to {} previous error{}",
+                count,
+                if count == 1 { "" } else { "s" }
+            )
+            .bold()
+        );
+
+        rendered
+    }
+
+    /// Returns the diagnostics as json, for `--message-format json`.
+    pub fn to_json(&self) -> serde_json::Value {
+        self.diagnostics
+            .iter()
+            .map(|diagnostic| {
+                serde_json::json!({
+                    "message": diagnostic.message,
+                    "path": self.path,
+                    "line": diagnostic.span.start.line,
+                    "column": diagnostic.span.start.column,
+                    "end_line": diagnostic.span.end.line,
+                    "end_column": diagnostic.span.end.column,
+                    "label": diagnostic.label,
+                    "help": diagnostic.help,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Formats each diagnostic on its own line as `path:line:column: message`, for output which is not rendered.
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, diagnostic) in self.diagnostics.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(
+                f,
+                "{}:{}: {}",
+                self.path, diagnostic.span.start, diagnostic.message
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostics {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn render_diagnostics() {
+        colored::control::set_override(false);
+        let diagnostics = Diagnostics {
+            path: "./schema/src/lib.rs".to_string(),
+            source: "schema! {\n    pub struct Product {\n        pub price: Money,\n    }\n}\n"
+                .to_string(),
+            diagnostics: vec![
+                Diagnostic::new(
+                    "type `Money` is not supported by database tables",
+                    Span {
+                        start: Location {
+                            line: 3,
+                            column: 20,
+                        },
+                        end: Location {
+                            line: 3,
+                            column: 25,
+                        },
+                    },
+                )
+                .label("unsupported type")
+                .help("set the type of the column with `#[awto(db_type = \"...\")]`"),
+                Diagnostic::new(
+                    "model `Product` is declared more than once",
+                    Span::of_name(
+                        Location {
+                            line: 2,
+                            column: 16,
+                        },
+                        "Product",
+                    ),
+                ),
+            ],
+        };
+
+        assert_eq!(
+            diagnostics.render(),
+            r#"error: type `Money` is not supported by database tables
+ --> ./schema/src/lib.rs:3:20
+  |
+3 |         pub price: Money,
+  |                    ^^^^^ unsupported type
+  |
+  = help: set the type of the column with `#[awto(db_type = "...")]`
+
+error: model `Product` is declared more than once
+ --> ./schema/src/lib.rs:2:16
+  |
+2 |     pub struct Product {
+  |                ^^^^^^^
+
+error: could not validate the schema due to 2 previous errors"#
+        );
+        assert_eq!(
+            diagnostics.to_string(),
+            "./schema/src/lib.rs:3:20: type `Money` is not supported by database tables\n./schema/src/lib.rs:2:16: model `Product` is declared more than once"
+        );
+    }
+}
@@ -0,0 +1,48 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use clap::Parser;
+use serde::Serialize;
+
+use crate::{
+    cancel::Cancellation,
+    config::{Config, CONFIG_PATH},
+    Runnable,
+};
+
+use super::{
+    features::{enabled_models, omitted_items, OmittedItem},
+    load_schema_models, SchemaModel,
+};
+
+/// Prints the models generated with the features enabled for the profile as JSON, along with the models and fields
+/// left out and why
+#[derive(Parser)]
+pub struct Dump {}
+
+/// Output of `awto schema dump`.
+#[derive(Debug, Serialize)]
+struct SchemaDump {
+    profile: String,
+    /// Features enabled in the `[features]` section of `awto.toml` for the profile.
+    features: Vec<String>,
+    models: Vec<SchemaModel>,
+    omitted: Vec<OmittedItem>,
+}
+
+#[async_trait]
+impl Runnable for Dump {
+    async fn run(&mut self, _cancellation: &Cancellation) -> Result<()> {
+        let config = Config::load(CONFIG_PATH).await?;
+        let models = load_schema_models().await?;
+
+        let dump = SchemaDump {
+            profile: config.profile,
+            features: config.features.enabled.clone(),
+            models: enabled_models(&models, &config.features),
+            omitted: omitted_items(&models, &config.features),
+        };
+        outln!("{}", serde_json::to_string_pretty(&dump)?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,239 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::{Context, Result};
+use awto_compile::format::format_rust;
+use log::{debug, info};
+use tokio::{fs, process::Command};
+
+use crate::{compile::profile_phase, error::Error};
+
+use super::{parse_schema_models, schema_fingerprint, Diagnostics, SchemaModel};
+
+/// Directory of the schemas read from the expanded schema packages, one file per package.
+pub const EXPANDED_DIR: &str = "./awto/expanded";
+
+/// Type of the constants holding the tokens of each `schema!` invocation in the expanded code.
+const EXPANDED_SCHEMA_TYPE: &str = "ExpandedSchema";
+
+static EXPAND_FLAG: AtomicBool = AtomicBool::new(false);
+
+/// Reads the models of the schema packages from their expanded code, as passed with `--expand`.
+pub fn enable_expansion() {
+    EXPAND_FLAG.store(true, Ordering::Relaxed);
+}
+
+/// Returns whether the models are read from the expanded schema packages.
+pub fn is_expansion_enabled() -> bool {
+    EXPAND_FLAG.load(Ordering::Relaxed)
+}
+
+/// Reads the models of schema package `package` from its expanded code, finding the models of schemas
+/// written by other macros which the schema lib does not show.
+///
+/// The invocations of `schema!` found in the expanded code are written to `<EXPANDED_DIR>/<package>.rs`,
+/// along with the fingerprint of the schema sources, and read from there until the sources change,
+/// as expanding a package builds its dependencies. The locations of the models point into that file.
+pub(crate) async fn expand_schema_models(package: &str) -> Result<Vec<SchemaModel>> {
+    let path = format!("{}/{}.rs", EXPANDED_DIR, package);
+    let header = format!("// schema fingerprint: {}\n", schema_fingerprint().await?);
+    let cached = fs::read_to_string(&path)
+        .await
+        .ok()
+        .filter(|cached| cached.starts_with(&header));
+
+    let source = match cached {
+        Some(source) => {
+            debug!(
+                "reading the expanded schema of package '{}' from '{}'",
+                package, path
+            );
+            source
+        }
+        None => {
+            info!("expanding package '{}', which may take a while", package);
+            let expanded = profile_phase("expand schema", expand_package(package)).await?;
+            let source = format!(
+                "{}{}",
+                header,
+                expanded_schema_source(&expanded).map_err(|source| Error::Expand {
+                    package: package.to_string(),
+                    source,
+                })?
+            );
+            fs::create_dir_all(EXPANDED_DIR)
+                .await
+                .with_context(|| format!("could not create directory '{}'", EXPANDED_DIR))?;
+            fs::write(&path, &source)
+                .await
+                .with_context(|| format!("could not write file '{}'", path))?;
+            source
+        }
+    };
+
+    let mut models = parse_schema_models(&source)
+        .map_err(|err| match err.downcast::<Diagnostics>() {
+            Ok(diagnostics) => Diagnostics {
+                path: path.clone(),
+                ..diagnostics
+            }
+            .into(),
+            Err(err) => err,
+        })
+        .with_context(|| format!("could not parse the expanded schema '{}'", path))?;
+    for model in &mut models {
+        model.lib_path = Some(path.clone());
+    }
+
+    Ok(models)
+}
+
+/// Returns the expanded code of the lib of `package`, with `cargo expand`, or with `-Zunpretty=expanded`
+/// of a nightly toolchain when cargo-expand is not installed.
+async fn expand_package(package: &str) -> Result<String> {
+    let mut args = vec!["expand", "--lib", "--package", package];
+    if !cargo_expand_installed().await {
+        if !is_nightly().await {
+            return Err(Error::ExpandUnavailable.into());
+        }
+        args = vec![
+            "rustc",
+            "--lib",
+            "--package",
+            package,
+            "--profile=check",
+            "--",
+            "-Zunpretty=expanded",
+        ];
+    }
+
+    let output = Command::new("cargo")
+        .args(&args)
+        .output()
+        .await
+        .map_err(Error::Cargo)?;
+    if !output.status.success() {
+        return Err(Error::Expand {
+            package: package.to_string(),
+            source: anyhow::anyhow!("{}", String::from_utf8_lossy(&output.stderr).trim()),
+        }
+        .into());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+async fn cargo_expand_installed() -> bool {
+    Command::new("cargo")
+        .args(["expand", "--version"])
+        .output()
+        .await
+        .is_ok_and(|output| output.status.success())
+}
+
+async fn is_nightly() -> bool {
+    Command::new("rustc")
+        .arg("--version")
+        .output()
+        .await
+        .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).contains("nightly"))
+}
+
+/// Returns a schema lib declaring the models of every `schema!` invocation of the `expanded` code of a package,
+/// as kept by `schema!` in constants of [`EXPANDED_SCHEMA_TYPE`].
+fn expanded_schema_source(expanded: &str) -> Result<String> {
+    let (file, _) = super::parse::parse_file_resilient(expanded)
+        .context("could not parse the expanded code")?;
+    let mut schemas = Vec::new();
+    collect_expanded_schemas(&file.items, &mut schemas);
+    if schemas.is_empty() {
+        anyhow::bail!("no `schema!` invocation found in the expanded code");
+    }
+
+    let mut source = String::from("use awto::schema;\n");
+    for tokens in schemas {
+        source.push_str("\nschema! {\n");
+        for line in format_rust(&tokens).lines() {
+            if !line.is_empty() {
+                source.push_str("    ");
+            }
+            source.push_str(line);
+            source.push('\n');
+        }
+        source.push_str("}\n");
+    }
+
+    Ok(source)
+}
+
+fn collect_expanded_schemas(items: &[syn::Item], schemas: &mut Vec<String>) {
+    for item in items {
+        match item {
+            syn::Item::Const(item_const) if is_expanded_schema(&item_const.ty) => {
+                if let syn::Expr::Call(call) = &*item_const.expr {
+                    if let Some(syn::Expr::Lit(syn::ExprLit {
+                        lit: syn::Lit::Str(tokens),
+                        ..
+                    })) = call.args.first()
+                    {
+                        schemas.push(tokens.value());
+                    }
+                }
+            }
+            syn::Item::Mod(item_mod) => {
+                if let Some((_, items)) = &item_mod.content {
+                    collect_expanded_schemas(items, schemas);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn is_expanded_schema(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == EXPANDED_SCHEMA_TYPE),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn expanded_schemas() {
+        let expanded = r##"
+            #![feature(prelude_import)]
+            use awto::prelude::*;
+            pub struct Product {
+                pub id: Uuid,
+            }
+            #[doc(hidden)]
+            const _: awto::schema::ExpandedSchema = awto::schema::ExpandedSchema(
+                "# [database_table] pub struct Product { pub id : Uuid , # [awto (max_len = 120)] pub name : String , }",
+            );
+            pub mod billing {
+                const _: awto::schema::ExpandedSchema =
+                    awto::schema::ExpandedSchema("# [database_table] pub struct Invoice { pub id : Uuid , }");
+            }
+        "##;
+
+        let source = expanded_schema_source(expanded).unwrap();
+        assert!(source.starts_with("use awto::schema;\n\nschema! {\n"));
+        let models = parse_schema_models(&source).unwrap();
+        let names: Vec<_> = models.iter().map(|model| model.name.as_str()).collect();
+        assert_eq!(names, ["Product", "Invoice"]);
+        assert!(models[0].is_database_table);
+        assert_eq!(models[0].fields[1].attributes, ["max_len = 120"]);
+
+        let err = expanded_schema_source("pub struct Product {}").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "no `schema!` invocation found in the expanded code"
+        );
+    }
+}
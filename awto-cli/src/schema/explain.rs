@@ -0,0 +1,206 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use awto_compile::{
+    database::with_map_storage,
+    explain::{explain_model, DeclaredField, Explanation, FieldExplanation, EXPLANATION_VERSION},
+    generator::GeneratorInput,
+};
+use clap::{ArgEnum, Parser};
+
+use crate::{
+    cancel::Cancellation,
+    compile::{check_schema_package, prepare_awto_dir, read_ir},
+    config::{Config, CONFIG_PATH},
+    error::Error,
+    util::WorkspaceContext,
+    Runnable,
+};
+
+use super::{load_schema_models, SchemaModel};
+
+/// Explains how the type of each field of a model resolves to the type of its column
+#[derive(Parser)]
+pub struct Explain {
+    /// Model whose fields are explained, such as `Product`
+    #[clap(long, required_unless_present = "field", conflicts_with = "field")]
+    pub model: Option<String>,
+    /// Field explained, such as `Product.tags`
+    #[clap(long)]
+    pub field: Option<String>,
+    /// Format of the printed explanation
+    #[clap(arg_enum, long, default_value = "text")]
+    pub format: ExplainFormat,
+    /// Overwrites the `ir` package directory when it was not generated by awto
+    #[clap(long)]
+    pub force: bool,
+    /// Prints more information
+    #[clap(short, long)]
+    pub verbose: bool,
+}
+
+#[derive(ArgEnum, Clone, Copy, PartialEq, Eq)]
+pub enum ExplainFormat {
+    Text,
+    Json,
+}
+
+#[async_trait]
+impl Runnable for Explain {
+    async fn run(&mut self, cancellation: &Cancellation) -> Result<()> {
+        let item = self
+            .field
+            .clone()
+            .or_else(|| self.model.clone())
+            .unwrap_or_default();
+        let (model_name, field_name) = match item.split_once('.') {
+            Some((model, field)) => (model, Some(field)),
+            None => (item.as_str(), None),
+        };
+
+        // The fields are checked against the sources first, which fails faster than building the models
+        let models = load_schema_models().await?;
+        let model = models
+            .iter()
+            .find(|model| model.name == model_name)
+            .filter(|model| {
+                field_name.is_none_or(|name| model.fields.iter().any(|field| field.name == name))
+            })
+            .ok_or_else(|| Error::UnknownSchemaField(item.clone()))?;
+
+        let mut workspace = WorkspaceContext::load(".").await?;
+        check_schema_package(&workspace).await?;
+        prepare_awto_dir(self.force).await?;
+        let input: GeneratorInput =
+            serde_json::from_value(read_ir(&mut workspace, self.force, cancellation).await?)
+                .context("package 'ir' printed invalid models")?;
+        let map_storage = Config::load(CONFIG_PATH).await?.database.map_storage;
+        let ir_models = with_map_storage(input.models, map_storage.compiled());
+
+        let fields: Vec<_> = ir_models
+            .iter()
+            .filter(|ir_model| ir_model.name == model.name)
+            .flat_map(|ir_model| explain_model(ir_model, &declared_fields(model)))
+            .filter(|field| field_name.is_none_or(|name| field.field == name))
+            .collect();
+        if fields.is_empty() {
+            return Err(Error::UnknownSchemaField(item).into());
+        }
+
+        match self.format {
+            ExplainFormat::Text => out!("{}", text(&fields)),
+            ExplainFormat::Json => {
+                let explanation = Explanation {
+                    version: EXPLANATION_VERSION,
+                    fields,
+                };
+                outln!("{}", serde_json::to_string_pretty(&explanation)?);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_verbose(&self) -> bool {
+        self.verbose
+    }
+}
+
+/// Returns the fields of `model` as declared in the schema lib, by name.
+fn declared_fields(model: &SchemaModel) -> BTreeMap<String, DeclaredField> {
+    model
+        .fields
+        .iter()
+        .map(|field| {
+            let declared = DeclaredField {
+                ty: field.ty.clone(),
+                attributes: field.attributes.clone(),
+            };
+            (field.name.clone(), declared)
+        })
+        .collect()
+}
+
+/// Renders the steps of each of `fields` as aligned columns of their stage, value and rule.
+fn text(fields: &[FieldExplanation]) -> String {
+    let mut text = String::new();
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            text.push('\n');
+        }
+        writeln!(text, "{}.{}", field.model, field.field).unwrap();
+
+        let width = field
+            .steps
+            .iter()
+            .map(|step| step.value.len())
+            .max()
+            .unwrap_or(0);
+        for step in &field.steps {
+            writeln!(
+                text,
+                "  {:<13} {:<width$}  {}",
+                step.stage.name(),
+                step.value,
+                step.rule,
+                width = width
+            )
+            .unwrap();
+            if let Some(note) = &step.note {
+                writeln!(text, "  {:<13} note: {}", "", note).unwrap();
+            }
+        }
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod test {
+    use awto_compile::explain::{Stage, Step};
+
+    use super::*;
+
+    #[test]
+    fn text_explanation() {
+        let step = |stage, value: &str, rule: &str, note: Option<&str>| Step {
+            stage,
+            value: value.to_string(),
+            rule: rule.to_string(),
+            note: note.map(str::to_string),
+        };
+        let field = FieldExplanation {
+            model: "Product".to_string(),
+            field: "price".to_string(),
+            steps: vec![
+                step(Stage::DeclaredType, "Cents", "schema lib", None),
+                step(
+                    Stage::RustType,
+                    "i64",
+                    "#[awto(newtype)] `Cents`",
+                    Some("`Cents` is stored as its inner type"),
+                ),
+                step(Stage::IrType, "BigInt", "type registry: `i64`", None),
+                step(Stage::Default, "0", "#[awto(default = 0)]", None),
+            ],
+        };
+
+        assert_eq!(
+            text(&[field.clone(), field]),
+            "Product.price\n  \
+            declared type Cents   schema lib\n  \
+            rust type     i64     #[awto(newtype)] `Cents`\n  \
+            \x20             note: `Cents` is stored as its inner type\n  \
+            ir type       BigInt  type registry: `i64`\n  \
+            default       0       #[awto(default = 0)]\n\
+            \n\
+            Product.price\n  \
+            declared type Cents   schema lib\n  \
+            rust type     i64     #[awto(newtype)] `Cents`\n  \
+            \x20             note: `Cents` is stored as its inner type\n  \
+            ir type       BigInt  type registry: `i64`\n  \
+            default       0       #[awto(default = 0)]\n"
+        );
+    }
+}
@@ -0,0 +1,258 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+
+use super::{SchemaField, SchemaModel};
+
+/// The `[features]` section of `awto.toml`, enabling the models and fields marked `#[awto(feature = "...")]`.
+///
+/// Models and fields of the features which are not enabled are left out of every generated package.
+/// The section can be set per profile, such as `[profile.enterprise.features]`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeaturesConfig {
+    /// Features enabled for the profile, such as `["enterprise"]`.
+    pub enabled: Vec<String>,
+}
+
+impl FeaturesConfig {
+    /// Returns whether an item gated behind `feature` is generated.
+    pub fn is_enabled(&self, feature: Option<&str>) -> bool {
+        feature.is_none_or(|feature| self.enabled.iter().any(|enabled| enabled == feature))
+    }
+
+    /// Returns the Rust expression of the enabled features passed to `awto_compile::features::with_features`.
+    pub fn render(&self) -> String {
+        let enabled: Vec<_> = self
+            .enabled
+            .iter()
+            .map(|feature| format!("{:?}", feature))
+            .collect();
+        format!("&[{}]", enabled.join(", "))
+    }
+}
+
+/// A model or field of the schema left out of the generated packages, as printed by `awto schema dump`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct OmittedItem {
+    /// Name of the model, or `Model.field` for fields.
+    pub item: String,
+    /// Position of the item in its schema lib, such as `./schema/src/lib.rs:12:9`.
+    pub location: String,
+    pub reason: String,
+}
+
+/// Returns the models and fields of `models` whose feature is not enabled, in the order they are declared.
+///
+/// The fields of omitted models are not listed on their own.
+pub fn omitted_items(models: &[SchemaModel], features: &FeaturesConfig) -> Vec<OmittedItem> {
+    let mut omitted = Vec::new();
+    for model in models {
+        if let Some(feature) = model
+            .feature
+            .as_deref()
+            .filter(|feature| !features.is_enabled(Some(feature)))
+        {
+            omitted.push(OmittedItem {
+                item: model.name.clone(),
+                location: model_location(model),
+                reason: format!("feature '{}' is disabled", feature),
+            });
+            continue;
+        }
+        for field in &model.fields {
+            if let Some(feature) = field
+                .feature
+                .as_deref()
+                .filter(|feature| !features.is_enabled(Some(feature)))
+            {
+                omitted.push(OmittedItem {
+                    item: format!("{}.{}", model.name, field.name),
+                    location: field_location(model, field),
+                    reason: format!("feature '{}' is disabled", feature),
+                });
+            }
+        }
+    }
+
+    omitted
+}
+
+/// Returns `models` without the models and fields whose feature is not enabled.
+pub fn enabled_models(models: &[SchemaModel], features: &FeaturesConfig) -> Vec<SchemaModel> {
+    models
+        .iter()
+        .filter(|model| features.is_enabled(model.feature.as_deref()))
+        .map(|model| SchemaModel {
+            fields: model
+                .fields
+                .iter()
+                .filter(|field| features.is_enabled(field.feature.as_deref()))
+                .cloned()
+                .collect(),
+            ..model.clone()
+        })
+        .collect()
+}
+
+/// Checks the models and fields generated with the enabled `features` only use models which are generated as well,
+/// through the model they reference, their type, or the parent of a sub table.
+///
+/// The error names both the item and the model it uses, along with their locations.
+pub fn check_gated_relations(
+    models: &[SchemaModel],
+    features: &FeaturesConfig,
+) -> Result<(), Error> {
+    let omitted: Vec<(&SchemaModel, &str)> = models
+        .iter()
+        .filter_map(|model| {
+            let feature = model.feature.as_deref()?;
+            (!features.is_enabled(Some(feature))).then_some((model, feature))
+        })
+        .collect();
+    if omitted.is_empty() {
+        return Ok(());
+    }
+    let gated = |item: String, location: String, referenced: &SchemaModel, feature: &str| {
+        Error::GatedRelation {
+            item,
+            location,
+            referenced: format!("model `{}`", referenced.name),
+            referenced_location: model_location(referenced),
+            feature: feature.to_string(),
+        }
+    };
+
+    for model in models
+        .iter()
+        .filter(|model| features.is_enabled(model.feature.as_deref()))
+    {
+        let parent = model.attributes.iter().find_map(|attr| {
+            attr.strip_prefix("database_sub_table(")
+                .and_then(|parent| parent.strip_suffix(')'))
+        });
+        if let Some((referenced, feature)) = omitted
+            .iter()
+            .find(|(omitted, _)| Some(omitted.name.as_str()) == parent)
+        {
+            let item = format!("sub table `{}`", model.name);
+            return Err(gated(item, model_location(model), referenced, feature));
+        }
+
+        for field in model
+            .fields
+            .iter()
+            .filter(|field| features.is_enabled(field.feature.as_deref()))
+        {
+            let uses = |name: &str| {
+                field.references.as_deref() == Some(name)
+                    || field
+                        .ty
+                        .split(|c: char| !c.is_alphanumeric() && c != '_')
+                        .any(|ident| ident == name)
+            };
+            if let Some((referenced, feature)) =
+                omitted.iter().find(|(omitted, _)| uses(&omitted.name))
+            {
+                let item = format!("field `{}.{}`", model.name, field.name);
+                return Err(gated(
+                    item,
+                    field_location(model, field),
+                    referenced,
+                    feature,
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn model_location(model: &SchemaModel) -> String {
+    format!("{}:{}", model.lib_path(), model.location)
+}
+
+fn field_location(model: &SchemaModel, field: &SchemaField) -> String {
+    format!("{}:{}", model.lib_path(), field.location)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::schema::parse_schema_models;
+
+    const SCHEMA: &str = r#"
+        schema! {
+            #[database_table]
+            #[awto(feature = "enterprise")]
+            pub struct Team {
+                pub id: Uuid,
+            }
+
+            #[database_table]
+            pub struct Invoice {
+                pub id: Uuid,
+                #[awto(feature = "enterprise", references = (Team, "id"))]
+                pub team_id: Option<Uuid>,
+                #[awto(feature = "billing")]
+                pub total: i64,
+            }
+        }
+    "#;
+
+    fn features(enabled: &[&str]) -> FeaturesConfig {
+        FeaturesConfig {
+            enabled: enabled.iter().map(|feature| feature.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn omitted() {
+        let models = parse_schema_models(SCHEMA).unwrap();
+        assert_eq!(models[0].feature.as_deref(), Some("enterprise"));
+        assert_eq!(models[1].fields[1].feature.as_deref(), Some("enterprise"));
+
+        let items: Vec<_> = omitted_items(&models, &features(&["billing"]))
+            .into_iter()
+            .map(|omitted| (omitted.item, omitted.reason))
+            .collect();
+        assert_eq!(
+            items,
+            [
+                (
+                    "Team".to_string(),
+                    "feature 'enterprise' is disabled".to_string()
+                ),
+                (
+                    "Invoice.team_id".to_string(),
+                    "feature 'enterprise' is disabled".to_string()
+                ),
+            ]
+        );
+        assert!(omitted_items(&models, &features(&["billing", "enterprise"])).is_empty());
+
+        let enabled = enabled_models(&models, &features(&["billing"]));
+        assert_eq!(enabled.len(), 1);
+        assert_eq!(enabled[0].fields.len(), 2);
+    }
+
+    #[test]
+    fn gated_relations() {
+        let models = parse_schema_models(SCHEMA).unwrap();
+        assert!(check_gated_relations(&models, &features(&[])).is_ok());
+
+        // Ungating the field leaves it referencing the omitted team
+        let source = SCHEMA.replace(
+            r#"#[awto(feature = "enterprise", references"#,
+            "#[awto(references",
+        );
+        let models = parse_schema_models(&source).unwrap();
+        let err = check_gated_relations(&models, &features(&[])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "field `Invoice.team_id` at ./schema/src/lib.rs:13:21 uses model `Team` at ./schema/src/lib.rs:5:24, \
+            which is only generated with feature 'enterprise', gate both behind the same feature"
+        );
+        assert!(check_gated_relations(&models, &features(&["enterprise"])).is_ok());
+    }
+}
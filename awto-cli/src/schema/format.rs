@@ -0,0 +1,531 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use clap::Parser;
+use log::info;
+use proc_macro2::{LineColumn, TokenStream, TokenTree};
+use quote::ToTokens;
+use tokio::fs;
+
+use crate::{cancel::Cancellation, error::Error, Runnable};
+
+use super::{
+    diagnostic::{Diagnostic, Diagnostics},
+    parse, SchemaMacros, Structs,
+};
+
+/// Directory of the schema package sources which are formatted.
+const SCHEMA_SRC_DIR: &str = "./schema/src";
+
+/// Attributes read by the schema macro, ordered alphabetically after `#[derive(...)]`.
+const AWTO_ATTRIBUTES: [&str; 4] = [
+    "awto",
+    "database_sub_table",
+    "database_table",
+    "protobuf_message",
+];
+
+/// Formats the models of the app schema
+#[derive(Parser)]
+pub struct Fmt {
+    /// Lists the files which are not formatted instead of rewriting them, failing when there are any
+    #[clap(long)]
+    pub check: bool,
+    /// Prints more information
+    #[clap(short, long)]
+    pub verbose: bool,
+}
+
+#[async_trait]
+impl Runnable for Fmt {
+    async fn run(&mut self, _cancellation: &Cancellation) -> Result<()> {
+        let paths = source_paths(Path::new(SCHEMA_SRC_DIR)).await?;
+
+        let mut unformatted = Vec::new();
+        for path in &paths {
+            let source = fs::read_to_string(path)
+                .await
+                .with_context(|| format!("could not read file '{}'", path.display()))?;
+            let formatted = format_source(&source)
+                .map_err(|err| Diagnostics {
+                    path: path.display().to_string(),
+                    source: source.clone(),
+                    diagnostics: Diagnostic::from_syn(err),
+                })
+                .with_context(|| format!("could not parse file '{}'", path.display()))?;
+            if formatted == source {
+                continue;
+            }
+
+            if self.check {
+                outln!("{}", path.display());
+            } else {
+                fs::write(path, formatted)
+                    .await
+                    .with_context(|| format!("could not write file '{}'", path.display()))?;
+            }
+            unformatted.push(path);
+        }
+
+        if self.check && !unformatted.is_empty() {
+            return Err(Error::SchemaUnformatted(unformatted.len()).into());
+        }
+        info!(
+            "formatted {} of {} schema files",
+            unformatted.len(),
+            paths.len()
+        );
+
+        Ok(())
+    }
+
+    fn is_verbose(&self) -> bool {
+        self.verbose
+    }
+}
+
+/// Returns the paths of the Rust files in `dir` and its subdirectories, sorted.
+async fn source_paths(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let mut entries = fs::read_dir(&dir)
+            .await
+            .with_context(|| format!("could not read directory '{}'", dir.display()))?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if entry.file_type().await?.is_dir() {
+                dirs.push(path);
+            } else if path.extension().is_some_and(|extension| extension == "rs") {
+                paths.push(path);
+            }
+        }
+    }
+    paths.sort();
+
+    Ok(paths)
+}
+
+/// Formats the models of the `schema!` macros and the entries of the `register_services!` macros in `source`.
+///
+/// The attributes of models and fields are ordered with doc comments first, then `#[derive(...)]`,
+/// then the awto attributes alphabetically with their arguments sorted, then any other attributes.
+/// Every field is written on its own line. Only the text of these items is replaced, so the rest of
+/// `source` is kept byte for byte, and models holding comments are left as they are, as are items
+/// the parser does not support.
+pub fn format_source(source: &str) -> syn::Result<String> {
+    let (file, _) = parse::parse_file_resilient(source)?;
+    let macros = SchemaMacros::resolve(&file.items);
+    let source_map = SourceMap::new(source);
+
+    let mut edits = Vec::new();
+    collect_edits(&file.items, &macros, &source_map, &mut edits);
+
+    // Edits never overlap, so they are applied from the end to keep the offsets of earlier ones valid
+    edits.sort_by_key(|edit| edit.start);
+    let mut formatted = source.to_string();
+    for edit in edits.into_iter().rev() {
+        formatted.replace_range(edit.start..edit.end, &edit.text);
+    }
+
+    Ok(formatted)
+}
+
+/// Replacement of the source between two byte offsets.
+struct Edit {
+    start: usize,
+    end: usize,
+    text: String,
+}
+
+fn collect_edits(
+    items: &[syn::Item],
+    macros: &SchemaMacros,
+    source_map: &SourceMap,
+    edits: &mut Vec<Edit>,
+) {
+    for item in items {
+        match item {
+            syn::Item::Macro(item_macro) if item_macro.ident.is_none() => {
+                if macros.name_of(&item_macro.mac.path).is_some() {
+                    // Wrappers may take input other than structs, which is left as it is
+                    if let Ok(structs) = syn::parse2::<Structs>(item_macro.mac.tokens.clone()) {
+                        edits.extend(
+                            structs
+                                .0
+                                .iter()
+                                .filter_map(|item| format_struct(item, source_map)),
+                        );
+                    }
+                } else if item_macro
+                    .mac
+                    .path
+                    .segments
+                    .last()
+                    .is_some_and(|segment| segment.ident == "register_services")
+                {
+                    edits.extend(sort_registrations(&item_macro.mac.tokens, source_map));
+                }
+            }
+            syn::Item::Mod(syn::ItemMod {
+                content: Some((_, mod_items)),
+                ..
+            }) => collect_edits(mod_items, macros, source_map, edits),
+            _ => {}
+        }
+    }
+}
+
+/// Returns the canonical text of a model, unless it is already canonical or holds comments.
+fn format_struct(item: &syn::ItemStruct, source_map: &SourceMap) -> Option<Edit> {
+    let tokens = item.to_token_stream();
+    let (start, end) = source_map.range(tokens.clone())?;
+    if source_map.has_comments(tokens) {
+        return None;
+    }
+
+    // Models sharing a line with other code have no indentation to write their attributes and fields with
+    let line_start = source_map.source[..start].rfind('\n').map_or(0, |i| i + 1);
+    let indent = &source_map.source[line_start..start];
+    if !indent.chars().all(char::is_whitespace) {
+        return None;
+    }
+
+    let mut text = String::new();
+    for attr in ordered_attributes(&item.attrs, source_map)? {
+        text.push_str(&attr);
+        text.push('\n');
+        text.push_str(indent);
+    }
+
+    let header_start = match source_map.range(item.vis.to_token_stream()) {
+        Some((vis_start, _)) => vis_start,
+        None => source_map.offset(item.struct_token.span.start()),
+    };
+    match &item.fields {
+        syn::Fields::Named(fields) => {
+            let brace_start = source_map.offset(fields.brace_token.span.start());
+            text.push_str(source_map.source[header_start..brace_start].trim_end());
+            if fields.named.is_empty() {
+                text.push_str(" {}");
+            } else {
+                text.push_str(" {\n");
+                for field in &fields.named {
+                    for attr in ordered_attributes(&field.attrs, source_map)? {
+                        text.push_str(&format!("{}    {}\n", indent, attr));
+                    }
+                    let declaration = syn::Field {
+                        attrs: Vec::new(),
+                        ..field.clone()
+                    };
+                    let (field_start, field_end) =
+                        source_map.range(declaration.to_token_stream())?;
+                    text.push_str(&format!(
+                        "{}    {},\n",
+                        indent,
+                        &source_map.source[field_start..field_end]
+                    ));
+                }
+                text.push_str(indent);
+                text.push('}');
+            }
+        }
+        // Newtypes and unit structs keep their fields as they are written
+        syn::Fields::Unnamed(_) | syn::Fields::Unit => {
+            text.push_str(&source_map.source[header_start..end])
+        }
+    }
+
+    (source_map.source[start..end] != text).then_some(Edit { start, end, text })
+}
+
+/// Returns the text of `attrs` in canonical order, see [`format_source`].
+fn ordered_attributes(attrs: &[syn::Attribute], source_map: &SourceMap) -> Option<Vec<String>> {
+    let mut docs = Vec::new();
+    let mut derives = Vec::new();
+    let mut awto = Vec::new();
+    let mut others = Vec::new();
+    for attr in attrs {
+        let (start, end) = source_map.range(attr.to_token_stream())?;
+        let text = source_map.source[start..end].to_string();
+        if attr.path.is_ident("doc") {
+            docs.push(text);
+        } else if attr.path.is_ident("derive") {
+            derives.push(text);
+        } else if attr.path.is_ident("awto") {
+            awto.push(sorted_awto_attribute(attr, source_map).unwrap_or(text));
+        } else if AWTO_ATTRIBUTES.iter().any(|name| attr.path.is_ident(name)) {
+            awto.push(text);
+        } else {
+            others.push(text);
+        }
+    }
+    awto.sort();
+
+    Some(
+        docs.into_iter()
+            .chain(derives)
+            .chain(awto)
+            .chain(others)
+            .collect(),
+    )
+}
+
+/// Returns the text of an `#[awto(...)]` attribute with its arguments sorted by name.
+///
+/// Arguments keep the text they are written with, including the nested arguments of `fulltext(...)` and the like.
+fn sorted_awto_attribute(attr: &syn::Attribute, source_map: &SourceMap) -> Option<String> {
+    let mut tokens = attr.tokens.clone().into_iter();
+    let group = match (tokens.next(), tokens.next()) {
+        (Some(TokenTree::Group(group)), None)
+            if group.delimiter() == proc_macro2::Delimiter::Parenthesis =>
+        {
+            group
+        }
+        _ => return None,
+    };
+
+    let mut args = vec![TokenStream::new()];
+    for token in group.stream() {
+        match &token {
+            TokenTree::Punct(punct) if punct.as_char() == ',' => args.push(TokenStream::new()),
+            _ => args.last_mut().unwrap().extend(Some(token)),
+        }
+    }
+
+    let mut args = args
+        .into_iter()
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| {
+            let (start, end) = source_map.range(arg)?;
+            Some(source_map.source[start..end].to_string())
+        })
+        .collect::<Option<Vec<_>>>()?;
+    args.sort_by(|a, b| arg_name(a).cmp(arg_name(b)));
+
+    Some(format!("#[awto({})]", args.join(", ")))
+}
+
+/// Returns the name of an awto argument, such as `max_len` for `max_len = 40`.
+fn arg_name(arg: &str) -> &str {
+    let end = arg
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(arg.len());
+    &arg[..end]
+}
+
+/// Returns the edit sorting the services of `register_services!(...)` alphabetically, unless they already are.
+fn sort_registrations(tokens: &TokenStream, source_map: &SourceMap) -> Option<Edit> {
+    use syn::parse::Parser;
+
+    let services = syn::punctuated::Punctuated::<syn::Ident, syn::Token![,]>::parse_terminated
+        .parse2(tokens.clone())
+        .ok()?;
+    let (start, end) = source_map.range(tokens.clone())?;
+    if source_map.has_comments(tokens.clone()) {
+        return None;
+    }
+
+    let mut names: Vec<_> = services.iter().map(ToString::to_string).collect();
+    names.sort();
+    let mut text = names.join(", ");
+    if services.trailing_punct() {
+        text.push(',');
+    }
+
+    (source_map.source[start..end] != text).then_some(Edit { start, end, text })
+}
+
+/// Byte offsets of the line and column positions of tokens parsed from a source.
+struct SourceMap<'a> {
+    source: &'a str,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> SourceMap<'a> {
+    fn new(source: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+
+        SourceMap {
+            source,
+            line_starts,
+        }
+    }
+
+    /// Returns the byte offset of `position`, whose column counts characters.
+    fn offset(&self, position: LineColumn) -> usize {
+        let line_start = self.line_starts[position.line - 1];
+        self.source[line_start..]
+            .char_indices()
+            .nth(position.column)
+            .map_or(self.source.len(), |(i, _)| line_start + i)
+    }
+
+    /// Returns the byte range from the start of the first token of `tokens` to the end of the last.
+    fn range(&self, tokens: TokenStream) -> Option<(usize, usize)> {
+        let ranges = self.token_ranges(tokens);
+        let start = ranges.iter().map(|(start, _)| *start).min()?;
+        let end = ranges.iter().map(|(_, end)| *end).max()?;
+
+        Some((start, end))
+    }
+
+    /// Returns whether there is anything other than whitespace between the tokens of `tokens`,
+    /// which are comments as doc comments are tokens.
+    fn has_comments(&self, tokens: TokenStream) -> bool {
+        let mut ranges = self.token_ranges(tokens);
+        ranges.sort_unstable();
+
+        let mut end = match ranges.first() {
+            Some((_, end)) => *end,
+            None => return false,
+        };
+        for (next_start, next_end) in ranges {
+            if next_start > end && !self.source[end..next_start].trim().is_empty() {
+                return true;
+            }
+            end = end.max(next_end);
+        }
+
+        false
+    }
+
+    /// Returns the byte ranges of every token of `tokens`, including the delimiters of groups.
+    fn token_ranges(&self, tokens: TokenStream) -> Vec<(usize, usize)> {
+        let mut ranges = Vec::new();
+        for token in tokens {
+            if let TokenTree::Group(group) = &token {
+                for span in [group.span_open(), group.span_close()] {
+                    ranges.push((self.offset(span.start()), self.offset(span.end())));
+                }
+                ranges.extend(self.token_ranges(group.stream()));
+            } else {
+                let span = token.span();
+                ranges.push((self.offset(span.start()), self.offset(span.end())));
+            }
+        }
+
+        ranges
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const UNFORMATTED: &str = r#"use awto::prelude::*;
+
+// Helpers are   kept as they are written
+fn  price_of( product : &Product ) -> i64 { product.price   }
+
+schema! {
+    #[awto(versioned, order_by = created_at)]
+    #[protobuf_message]
+    #[database_table]
+    /// A product sold in the shop.
+    #[derive(Clone)]
+    pub struct Product {
+        pub id: Uuid, #[awto(unique, max_len = 40, fulltext_weight(b = 1, a = 2))] pub name: String,
+        /// Price in cents.
+        #[serde(rename = "cents")]
+        #[awto(default = 0)]
+        pub price: i64,
+    }
+
+    #[awto(newtype)]
+    #[derive(Clone, Copy)]
+    pub struct Cents(pub i64);
+
+    #[database_table]
+    pub struct Event {
+        // Written by the analytics pipeline
+        #[awto(unique, max_len = 40)] pub name: String,
+    }
+}
+
+impl  Product {   }
+
+register_services!(ProductService, EventService);
+"#;
+
+    const FORMATTED: &str = r#"use awto::prelude::*;
+
+// Helpers are   kept as they are written
+fn  price_of( product : &Product ) -> i64 { product.price   }
+
+schema! {
+    /// A product sold in the shop.
+    #[derive(Clone)]
+    #[awto(order_by = created_at, versioned)]
+    #[database_table]
+    #[protobuf_message]
+    pub struct Product {
+        pub id: Uuid,
+        #[awto(fulltext_weight(b = 1, a = 2), max_len = 40, unique)]
+        pub name: String,
+        /// Price in cents.
+        #[awto(default = 0)]
+        #[serde(rename = "cents")]
+        pub price: i64,
+    }
+
+    #[derive(Clone, Copy)]
+    #[awto(newtype)]
+    pub struct Cents(pub i64);
+
+    #[database_table]
+    pub struct Event {
+        // Written by the analytics pipeline
+        #[awto(unique, max_len = 40)] pub name: String,
+    }
+}
+
+impl  Product {   }
+
+register_services!(EventService, ProductService);
+"#;
+
+    #[test]
+    fn canonical_schema() {
+        assert_eq!(format_source(UNFORMATTED).unwrap(), FORMATTED);
+        assert_eq!(format_source(FORMATTED).unwrap(), FORMATTED);
+    }
+
+    #[test]
+    fn code_outside_models_untouched() {
+        let formatted = format_source(UNFORMATTED).unwrap();
+
+        // Everything up to the first model and after the last one is byte-identical
+        let prefix = &UNFORMATTED[..UNFORMATTED.find("    #[awto(versioned").unwrap()];
+        let suffix = &UNFORMATTED[UNFORMATTED
+            .find("\n    #[database_table]\n    pub struct Event")
+            .unwrap()..];
+        let suffix = &suffix[..suffix.find("register_services!").unwrap()];
+        assert!(formatted.starts_with(prefix));
+        assert!(formatted.contains(suffix));
+
+        // Models holding comments are left as they are
+        assert!(formatted.contains("        #[awto(unique, max_len = 40)] pub name: String,\n"));
+    }
+
+    #[test]
+    fn without_models() {
+        let source =
+            "fn main( ) {\n    println!(\"hi\")  ;\n}\n/// Kept\nstruct  Other { a: u8, b: u8 }\n";
+
+        assert_eq!(format_source(source).unwrap(), source);
+    }
+
+    #[test]
+    fn aliased_schema_macro() {
+        let source = "use awto::schema as models;\n\nmodels! {\n    #[database_table] #[derive(Debug)]\n    pub struct Product { pub id: Uuid }\n}\n";
+
+        assert_eq!(
+            format_source(source).unwrap(),
+            "use awto::schema as models;\n\nmodels! {\n    #[derive(Debug)]\n    #[database_table]\n    pub struct Product {\n        pub id: Uuid,\n    }\n}\n"
+        );
+    }
+}
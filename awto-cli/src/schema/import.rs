@@ -0,0 +1,143 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use awto_compile::import::{import_schema, SchemaImport};
+use clap::Parser;
+use log::{info, warn};
+use tokio::fs;
+
+use crate::{
+    cancel::Cancellation,
+    compile::{read_dotenv, resolve_database_url, DOTENV_PATH},
+    error::Error,
+    Runnable,
+};
+
+/// Variable holding the url of the imported database when `--database-url` is not passed.
+const DATABASE_URL_VAR: &str = "DATABASE_URL";
+
+/// Bootstraps the schema lib with a model for each table of an existing database
+#[derive(Parser)]
+pub struct Import {
+    /// Url of the imported database, instead of `DATABASE_URL`
+    #[clap(long)]
+    pub database_url: Option<String>,
+    /// Directory the `lib.rs` of the imported models is written into
+    #[clap(long, default_value = "./schema/src")]
+    pub out: PathBuf,
+    /// Overwrites an existing `lib.rs` in the output directory
+    #[clap(long)]
+    pub force: bool,
+    /// Prints more information
+    #[clap(short, long)]
+    pub verbose: bool,
+    /// Number of warnings reported by the run
+    #[clap(skip)]
+    pub warnings: usize,
+}
+
+#[async_trait]
+impl Runnable for Import {
+    async fn run(&mut self, cancellation: &Cancellation) -> Result<()> {
+        let path = self.out.join("lib.rs");
+        if path.exists() && !self.force {
+            return Err(Error::SchemaExists(path.display().to_string()).into());
+        }
+
+        let dotenv = read_dotenv(DOTENV_PATH).await?;
+        let (url, origin) = resolve_database_url(
+            DATABASE_URL_VAR,
+            self.database_url.as_deref(),
+            &dotenv,
+            None,
+        )
+        .await?
+        .ok_or_else(|| Error::MissingDatabaseUrl(DATABASE_URL_VAR.to_string()))?;
+        info!("importing tables of {} from {}", url, origin);
+        let import = cancellation
+            .run(async {
+                import_schema(url.as_str())
+                    .await
+                    .context("could not import the tables of the database")
+            })
+            .await?;
+
+        fs::create_dir_all(&self.out)
+            .await
+            .with_context(|| format!("could not create directory '{}'", self.out.display()))?;
+        fs::write(&path, import.render())
+            .await
+            .with_context(|| format!("could not write file '{}'", path.display()))?;
+        self.warnings += report_import(&import);
+        info!(
+            "imported {} tables into '{}'",
+            import.tables.len(),
+            path.display()
+        );
+
+        Ok(())
+    }
+
+    fn is_verbose(&self) -> bool {
+        self.verbose
+    }
+
+    fn warnings(&self) -> usize {
+        self.warnings
+    }
+}
+
+/// Warns about what could not be imported, which is left as `// TODO` comments, and returns the number of warnings.
+fn report_import(import: &SchemaImport) -> usize {
+    let mut warnings = 0;
+    for table in import.unnamed_tables() {
+        warn!(
+            "table '{}' is not imported, as no model name maps back to it",
+            table
+        );
+        warnings += 1;
+    }
+    for (column, data_type) in import.unmapped_columns() {
+        warn!(
+            "column '{}' is not imported, as its type `{}` has no field type",
+            column, data_type
+        );
+        warnings += 1;
+    }
+    if import.uses_chrono() {
+        warn!("imported models have chrono fields, add `chrono` to the dependencies of the schema package");
+        warnings += 1;
+    }
+
+    warnings
+}
+
+#[cfg(test)]
+mod test {
+    use awto_compile::import::{ImportedColumn, ImportedTable};
+
+    use super::*;
+
+    #[test]
+    fn imported_schema() {
+        let import = SchemaImport {
+            tables: vec![
+                ImportedTable {
+                    name: "Legacy".to_string(),
+                    columns: Vec::new(),
+                },
+                ImportedTable {
+                    name: "customer".to_string(),
+                    columns: vec![ImportedColumn::Unmapped {
+                        name: "location".to_string(),
+                        data_type: "point".to_string(),
+                    }],
+                },
+            ],
+        };
+
+        assert_eq!(report_import(&import), 2);
+        assert_eq!(report_import(&SchemaImport::default()), 0);
+    }
+}
@@ -0,0 +1,660 @@
+use std::{collections::BTreeMap, convert::TryFrom, fmt};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{Local, NaiveDate};
+use clap::{ArgEnum, Parser};
+use colored::Colorize;
+use log::info;
+use serde::Deserialize;
+
+use crate::{
+    cancel::Cancellation,
+    compile::WARNING_IDS,
+    config::{Config, CONFIG_PATH},
+    error::Error,
+    Runnable,
+};
+
+use super::{load_schema_models, Location, SchemaField, SchemaModel};
+
+/// Lints the models of the app schema
+#[derive(Parser)]
+pub struct Lint {
+    /// Reports findings of the given level as errors
+    #[clap(arg_enum, long)]
+    pub deny: Option<DenyLevel>,
+    /// Prints more information
+    #[clap(short, long)]
+    pub verbose: bool,
+    /// Number of warnings reported by the run
+    #[clap(skip)]
+    pub warnings: usize,
+}
+
+#[derive(ArgEnum, Clone, Copy, PartialEq, Eq)]
+pub enum DenyLevel {
+    Warnings,
+}
+
+#[async_trait]
+impl Runnable for Lint {
+    async fn run(&mut self, _cancellation: &Cancellation) -> Result<()> {
+        let models = load_schema_models().await?;
+        let config = Config::load(CONFIG_PATH).await?;
+
+        let mut findings = lint_models(&models, &config.lint, Local::today().naive_local());
+        if self.deny == Some(DenyLevel::Warnings) {
+            for finding in &mut findings {
+                finding.severity = Severity::Deny;
+            }
+        }
+
+        for finding in &findings {
+            outln!("{}", finding);
+        }
+
+        let errors = findings
+            .iter()
+            .filter(|finding| finding.severity == Severity::Deny)
+            .count();
+        self.warnings = findings.len() - errors;
+        info!(
+            "linted {} models: {} warnings, {} errors",
+            models.len(),
+            self.warnings,
+            errors
+        );
+        if errors > 0 {
+            return Err(Error::LintDenied(errors).into());
+        }
+
+        Ok(())
+    }
+
+    fn is_verbose(&self) -> bool {
+        self.verbose
+    }
+
+    fn warnings(&self) -> usize {
+        self.warnings
+    }
+}
+
+/// A lint rule, identified by its snake case name in `awto.toml` and `#[awto(allow(...))]`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Rule {
+    /// Database tables without an `id` primary key or a field marked `#[awto(primary_key)]`.
+    MissingPrimaryKey,
+    /// String columns without a `max_len`.
+    MissingMaxLen,
+    /// Foreign key columns which are not indexed.
+    MissingIndex,
+    /// Database tables with more columns than `max_columns`.
+    TooManyColumns,
+    /// Fields named after one of `vague_field_names`.
+    VagueFieldName,
+    /// Models not named in upper camel case, or fields not named in snake case.
+    NamingConvention,
+    /// Unknown rules or compile warnings in `#[awto(allow(...))]`.
+    UnknownLint,
+    /// Fields deprecated longer than `max_deprecation_days` ago, which are due to be deleted.
+    StaleDeprecation,
+    /// Fields named after one of `sensitive_field_names` without a `sensitivity`.
+    UnclassifiedSensitiveField,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 9] = [
+        Rule::MissingPrimaryKey,
+        Rule::MissingMaxLen,
+        Rule::MissingIndex,
+        Rule::TooManyColumns,
+        Rule::VagueFieldName,
+        Rule::NamingConvention,
+        Rule::UnknownLint,
+        Rule::StaleDeprecation,
+        Rule::UnclassifiedSensitiveField,
+    ];
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Rule::MissingPrimaryKey => "missing_primary_key",
+            Rule::MissingMaxLen => "missing_max_len",
+            Rule::MissingIndex => "missing_index",
+            Rule::TooManyColumns => "too_many_columns",
+            Rule::VagueFieldName => "vague_field_name",
+            Rule::NamingConvention => "naming_convention",
+            Rule::UnknownLint => "unknown_lint",
+            Rule::StaleDeprecation => "stale_deprecation",
+            Rule::UnclassifiedSensitiveField => "unclassified_sensitive_field",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Rule> {
+        Rule::ALL.iter().copied().find(|rule| rule.id() == id)
+    }
+}
+
+impl TryFrom<String> for Rule {
+    type Error = String;
+
+    fn try_from(id: String) -> Result<Self, Self::Error> {
+        Rule::from_id(&id).ok_or_else(|| format!("unknown lint rule `{}`", id))
+    }
+}
+
+/// Severity of the findings of a [`Rule`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Allow,
+    Warn,
+    Deny,
+}
+
+/// The `[lint]` section of `awto.toml`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LintConfig {
+    /// Severity of each rule, defaulting to [`Severity::Warn`].
+    pub rules: BTreeMap<Rule, Severity>,
+    /// Maximum number of columns of a table before `too_many_columns` is reported.
+    pub max_columns: usize,
+    /// Field names reported by `vague_field_name`.
+    pub vague_field_names: Vec<String>,
+    /// Number of days a field can be deprecated before `stale_deprecation` is reported.
+    pub max_deprecation_days: i64,
+    /// Words of field names reported by `unclassified_sensitive_field`, such as `password` in `password_hash`.
+    pub sensitive_field_names: Vec<String>,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        LintConfig {
+            rules: BTreeMap::new(),
+            max_columns: 30,
+            vague_field_names: vec!["data".to_string(), "info".to_string()],
+            max_deprecation_days: 90,
+            sensitive_field_names: [
+                "password",
+                "secret",
+                "token",
+                "ssn",
+                "api_key",
+                "private_key",
+            ]
+            .iter()
+            .map(|name| name.to_string())
+            .collect(),
+        }
+    }
+}
+
+impl LintConfig {
+    pub fn severity(&self, rule: Rule) -> Severity {
+        self.rules.get(&rule).copied().unwrap_or(Severity::Warn)
+    }
+}
+
+/// A violation of a [`Rule`] by a model or field.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    pub rule: Rule,
+    pub severity: Severity,
+    pub model: String,
+    pub field: Option<String>,
+    pub location: Location,
+    /// Path of the schema lib declaring the model.
+    pub source: String,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self.severity {
+            Severity::Deny => format!("error[{}]", self.rule.id()).red(),
+            _ => format!("warning[{}]", self.rule.id()).yellow(),
+        };
+        let item = match &self.field {
+            Some(field) => format!("{}.{}", self.model, field),
+            None => self.model.clone(),
+        };
+
+        writeln!(f, "{}: `{}` {}", level.bold(), item, self.message)?;
+        write!(
+            f,
+            "  {} {}:{}",
+            "-->".blue().bold(),
+            self.source,
+            self.location
+        )
+    }
+}
+
+/// Runs every enabled rule over `models`, omitting findings suppressed with `#[awto(allow(...))]`.
+///
+/// Findings are ordered by their schema lib and their location in it, with the age of deprecations counted up to `today`.
+pub fn lint_models(models: &[SchemaModel], config: &LintConfig, today: NaiveDate) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    for model in models {
+        let mut report = |rule: Rule, field: Option<&SchemaField>, message: String| {
+            let severity = config.severity(rule);
+            let allowed = model
+                .allow
+                .iter()
+                .chain(field.iter().flat_map(|field| &field.allow))
+                .any(|lint| lint == rule.id());
+            if severity == Severity::Allow || allowed {
+                return;
+            }
+
+            findings.push(Finding {
+                rule,
+                severity,
+                model: model.name.clone(),
+                field: field.map(|field| field.name.clone()),
+                location: field.map_or(model.location, |field| field.location),
+                source: model.lib_path().to_string(),
+                message,
+            });
+        };
+
+        for lint in model.allow.iter().filter(|lint| !is_known_lint(lint)) {
+            report(
+                Rule::UnknownLint,
+                None,
+                format!("allows unknown lint `{}`", lint),
+            );
+        }
+        for field in &model.fields {
+            for lint in field.allow.iter().filter(|lint| !is_known_lint(lint)) {
+                report(
+                    Rule::UnknownLint,
+                    Some(field),
+                    format!("allows unknown lint `{}`", lint),
+                );
+            }
+        }
+
+        if !is_upper_camel_case(&model.name) {
+            report(
+                Rule::NamingConvention,
+                None,
+                "should have an upper camel case name".to_string(),
+            );
+        }
+        for field in &model.fields {
+            if !is_snake_case(&field.name) {
+                report(
+                    Rule::NamingConvention,
+                    Some(field),
+                    "should have a snake case name".to_string(),
+                );
+            }
+            if config.vague_field_names.contains(&field.name) {
+                report(
+                    Rule::VagueFieldName,
+                    Some(field),
+                    "has a vague name, consider describing what it contains".to_string(),
+                );
+            }
+            let classified = field.sensitivity.is_some() || field.sensitive;
+            if let (Some(name), false) = (
+                sensitive_name(&field.name, &config.sensitive_field_names),
+                classified,
+            ) {
+                report(
+                    Rule::UnclassifiedSensitiveField,
+                    Some(field),
+                    format!(
+                        "is named after `{}` but has no sensitivity, \
+                        classify it with `#[awto(sensitivity = \"secret\")]`, `\"internal\"` or `\"public\"`",
+                        name
+                    ),
+                );
+            }
+            let since = field
+                .deprecated_since
+                .as_ref()
+                .and_then(|since| NaiveDate::parse_from_str(since, "%Y-%m-%d").ok());
+            if let Some(since) = since {
+                let days = (today - since).num_days();
+                if days > config.max_deprecation_days {
+                    report(
+                        Rule::StaleDeprecation,
+                        Some(field),
+                        format!(
+                            "has been deprecated for {} days, more than the maximum of {}, consider deleting it",
+                            days, config.max_deprecation_days
+                        ),
+                    );
+                }
+            }
+        }
+
+        if !model.is_database_table {
+            continue;
+        }
+
+        let has_primary_key = model.fields.iter().any(|field| {
+            field.name == "id"
+                || field
+                    .attributes
+                    .iter()
+                    .any(|attribute| attribute == "primary_key")
+        });
+        if !has_primary_key {
+            report(
+                Rule::MissingPrimaryKey,
+                None,
+                "has no `id: Uuid` primary key, nor a field marked #[awto(primary_key)]"
+                    .to_string(),
+            );
+        }
+        if model.fields.len() > config.max_columns {
+            report(
+                Rule::TooManyColumns,
+                None,
+                format!(
+                    "has {} columns, more than the maximum of {}",
+                    model.fields.len(),
+                    config.max_columns
+                ),
+            );
+        }
+        for field in &model.fields {
+            let is_string = field.ty == "String" || field.ty == "Option<String>";
+            if is_string && !field.has_max_len && field.db_type.is_none() {
+                report(
+                    Rule::MissingMaxLen,
+                    Some(field),
+                    "is a string column without a `max_len`".to_string(),
+                );
+            }
+            if let (Some(references), false) = (&field.references, field.unique) {
+                report(
+                    Rule::MissingIndex,
+                    Some(field),
+                    format!(
+                        "references `{}` but is not indexed, so joins scan the whole table",
+                        references
+                    ),
+                );
+            }
+        }
+    }
+
+    findings.sort_by(|a, b| (&a.source, a.location).cmp(&(&b.source, b.location)));
+    findings
+}
+
+/// Returns whether `lint` can be allowed, being either a [`Rule`] or a warning of compile commands.
+fn is_known_lint(lint: &str) -> bool {
+    Rule::from_id(lint).is_some() || WARNING_IDS.contains(&lint)
+}
+
+/// Returns the first of `names` whose words are words of the field `name`, such as `api_key` in `stripe_api_key`.
+fn sensitive_name<'a>(name: &str, names: &'a [String]) -> Option<&'a str> {
+    let words: Vec<_> = name.to_lowercase().split('_').map(str::to_string).collect();
+    names
+        .iter()
+        .find(|sensitive| {
+            let sensitive: Vec<_> = sensitive.split('_').collect();
+            words
+                .windows(sensitive.len())
+                .any(|window| window == sensitive.as_slice())
+        })
+        .map(String::as_str)
+}
+
+fn is_upper_camel_case(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_uppercase()) && !name.contains('_')
+}
+
+fn is_snake_case(name: &str) -> bool {
+    let name = name.strip_prefix("r#").unwrap_or(name);
+    !name.starts_with(|c: char| c.is_ascii_digit())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn field(name: &str, ty: &str) -> SchemaField {
+        SchemaField {
+            name: name.to_string(),
+            ty: ty.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd(2024, 10, 1)
+    }
+
+    fn rules(findings: &[Finding]) -> Vec<(Rule, Option<&str>)> {
+        findings
+            .iter()
+            .map(|finding| (finding.rule, finding.field.as_deref()))
+            .collect()
+    }
+
+    #[test]
+    fn parse_config() {
+        let config: Config = toml::from_str(
+            r#"
+            [lint]
+            max_columns = 12
+
+            [lint.rules]
+            missing_index = "deny"
+            vague_field_name = "allow"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.lint.max_columns, 12);
+        assert_eq!(config.lint.vague_field_names, ["data", "info"]);
+        assert_eq!(config.lint.severity(Rule::MissingIndex), Severity::Deny);
+        assert_eq!(config.lint.severity(Rule::VagueFieldName), Severity::Allow);
+        assert_eq!(config.lint.severity(Rule::MissingMaxLen), Severity::Warn);
+
+        assert!(toml::from_str::<Config>("[lint.rules]\nmissing_indexes = \"deny\"").is_err());
+    }
+
+    #[test]
+    fn lint_table() {
+        let models = [SchemaModel {
+            name: "Order".to_string(),
+            is_database_table: true,
+            fields: vec![
+                field("id", "Uuid"),
+                field("note", "Option<String>"),
+                SchemaField {
+                    has_max_len: true,
+                    ..field("code", "String")
+                },
+                SchemaField {
+                    references: Some("Product".to_string()),
+                    ..field("product_id", "Uuid")
+                },
+                field("data", "Vec<u8>"),
+                field("shippedAt", "DateTime<FixedOffset>"),
+            ],
+            ..Default::default()
+        }];
+
+        let findings = lint_models(&models, &LintConfig::default(), today());
+        assert_eq!(
+            rules(&findings),
+            [
+                (Rule::VagueFieldName, Some("data")),
+                (Rule::NamingConvention, Some("shippedAt")),
+                (Rule::MissingMaxLen, Some("note")),
+                (Rule::MissingIndex, Some("product_id")),
+            ]
+        );
+        assert!(findings
+            .iter()
+            .all(|finding| finding.severity == Severity::Warn));
+    }
+
+    #[test]
+    fn lint_config() {
+        let models = [SchemaModel {
+            name: "order_line".to_string(),
+            is_database_table: true,
+            fields: vec![field("name", "String"), field("info", "String")],
+            ..Default::default()
+        }];
+        let config = LintConfig {
+            rules: [
+                (Rule::MissingMaxLen, Severity::Allow),
+                (Rule::MissingPrimaryKey, Severity::Deny),
+            ]
+            .iter()
+            .copied()
+            .collect(),
+            max_columns: 1,
+            vague_field_names: vec!["name".to_string()],
+            max_deprecation_days: 90,
+            sensitive_field_names: Vec::new(),
+        };
+
+        let findings = lint_models(&models, &config, today());
+        assert_eq!(
+            rules(&findings),
+            [
+                (Rule::NamingConvention, None),
+                (Rule::VagueFieldName, Some("name")),
+                (Rule::MissingPrimaryKey, None),
+                (Rule::TooManyColumns, None),
+            ]
+        );
+        assert_eq!(findings[2].severity, Severity::Deny);
+    }
+
+    #[test]
+    fn lint_suppressions() {
+        let models = [SchemaModel {
+            name: "Product".to_string(),
+            is_database_table: true,
+            fields: vec![
+                field("id", "Uuid"),
+                SchemaField {
+                    allow: vec!["missing_max_len".to_string()],
+                    ..field("name", "String")
+                },
+                SchemaField {
+                    allow: vec!["missing_maxlen".to_string()],
+                    ..field("description", "String")
+                },
+                SchemaField {
+                    allow: vec!["unknown_column_reference".to_string()],
+                    ..field("data", "String")
+                },
+            ],
+            allow: vec!["vague_field_name".to_string()],
+            ..Default::default()
+        }];
+
+        let findings = lint_models(&models, &LintConfig::default(), today());
+        assert_eq!(
+            rules(&findings),
+            [
+                (Rule::UnknownLint, Some("description")),
+                (Rule::MissingMaxLen, Some("description")),
+                (Rule::MissingMaxLen, Some("data")),
+            ]
+        );
+    }
+
+    #[test]
+    fn lint_deprecations() {
+        let models = [SchemaModel {
+            name: "Employee".to_string(),
+            fields: vec![
+                SchemaField {
+                    deprecated: true,
+                    deprecated_since: Some("2024-06-01".to_string()),
+                    ..field("nickname", "Option<String>")
+                },
+                SchemaField {
+                    deprecated: true,
+                    deprecated_since: Some("2024-09-01".to_string()),
+                    ..field("title", "Option<String>")
+                },
+                SchemaField {
+                    deprecated: true,
+                    ..field("initials", "Option<String>")
+                },
+            ],
+            ..Default::default()
+        }];
+
+        let findings = lint_models(&models, &LintConfig::default(), today());
+        assert_eq!(
+            rules(&findings),
+            [(Rule::StaleDeprecation, Some("nickname"))]
+        );
+        assert_eq!(
+            findings[0].message,
+            "has been deprecated for 122 days, more than the maximum of 90, consider deleting it"
+        );
+
+        let config = LintConfig {
+            max_deprecation_days: 7,
+            ..Default::default()
+        };
+        let findings = lint_models(&models, &config, today());
+        assert_eq!(
+            rules(&findings),
+            [
+                (Rule::StaleDeprecation, Some("nickname")),
+                (Rule::StaleDeprecation, Some("title")),
+            ]
+        );
+    }
+
+    #[test]
+    fn lint_sensitive_fields() {
+        let models = [SchemaModel {
+            name: "Account".to_string(),
+            fields: vec![
+                field("password_hash", "String"),
+                field("stripe_api_key", "Option<String>"),
+                field("tokens_used", "i32"),
+                SchemaField {
+                    sensitivity: Some("public".to_string()),
+                    ..field("token_count", "i32")
+                },
+                SchemaField {
+                    sensitive: true,
+                    ..field("reset_token", "Option<String>")
+                },
+                field("api", "String"),
+            ],
+            ..Default::default()
+        }];
+
+        let findings = lint_models(&models, &LintConfig::default(), today());
+        assert_eq!(
+            rules(&findings),
+            [
+                (Rule::UnclassifiedSensitiveField, Some("password_hash")),
+                (Rule::UnclassifiedSensitiveField, Some("stripe_api_key")),
+            ]
+        );
+        assert_eq!(
+            findings[1].message,
+            "is named after `api_key` but has no sensitivity, \
+            classify it with `#[awto(sensitivity = \"secret\")]`, `\"internal\"` or `\"public\"`"
+        );
+    }
+}
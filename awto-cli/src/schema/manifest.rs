@@ -0,0 +1,1408 @@
+//! Schema manifests, YAML or JSON files declaring the models of the app in place of a Rust schema package,
+//! read with `source = "manifest"` in the `[schema]` section of `awto.toml`.
+//!
+//! The models of a manifest are rendered into the lib of the generated `schema-manifest` package, which generated
+//! packages depend on like any other schema package, so they go through `schema!` like hand-written models.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+};
+
+use anyhow::{Context, Result};
+use heck::{CamelCase, SnakeCase};
+use tokio::fs;
+use yaml_rust::{
+    parser::{Event, MarkedEventReceiver, Parser},
+    scanner::{Marker, TScalarStyle},
+};
+
+use super::{
+    diagnostic::{Diagnostic, Diagnostics, Span},
+    Location,
+};
+
+/// Name of the generated schema package holding the models of the manifest.
+pub const MANIFEST_PACKAGE: &str = "schema-manifest";
+
+/// Directory of the generated schema package holding the models of the manifest.
+pub const MANIFEST_PACKAGE_DIR: &str = "./awto/schema-manifest";
+
+/// File of the JSON Schema of the manifest format, written into [`MANIFEST_PACKAGE_DIR`] for editors to validate
+/// manifests with.
+pub const MANIFEST_SCHEMA_FILE: &str = "manifest.schema.json";
+
+/// Version of the manifest format, which manifests may declare with `version: 1`.
+pub const MANIFEST_VERSION: i64 = 1;
+
+/// Types of the type registry which fields of a manifest may have, as written in Rust.
+const FIELD_TYPES: [&str; 13] = [
+    "i16",
+    "i32",
+    "i64",
+    "f32",
+    "f64",
+    "String",
+    "Vec<u8>",
+    "NaiveDateTime",
+    "DateTime<FixedOffset>",
+    "NaiveDate",
+    "NaiveTime",
+    "bool",
+    "Uuid",
+];
+
+/// Types of fields which are not in `awto::prelude`, imported from chrono when used.
+const CHRONO_TYPES: [&str; 3] = ["NaiveDate", "NaiveDateTime", "NaiveTime"];
+
+/// Types of the registry which have no protobuf type, so fields of protobuf messages cannot have them.
+const NON_PROTOBUF_TYPES: [&str; 3] = ["i16", "NaiveDate", "NaiveTime"];
+
+/// Fields every database table model must declare, with their type.
+const REQUIRED_FIELDS: [(&str, &str); 3] = [
+    ("id", "Uuid"),
+    ("created_at", "DateTime<FixedOffset>"),
+    ("updated_at", "DateTime<FixedOffset>"),
+];
+
+/// Roles a model of a manifest may have, named like their attributes.
+const ROLES: [&str; 2] = ["database_table", "protobuf_message"];
+
+const MANIFEST_KEYS: [&str; 3] = ["version", "enums", "models"];
+const ENUM_KEYS: [&str; 3] = ["name", "description", "variants"];
+const MODEL_KEYS: [&str; 4] = ["name", "description", "roles", "fields"];
+const FIELD_KEYS: [&str; 11] = [
+    "name",
+    "description",
+    "type",
+    "nullable",
+    "max_len",
+    "unique",
+    "index",
+    "references",
+    "default",
+    "default_raw",
+    "sensitivity",
+];
+
+/// Sensitivities of fields, as set with `#[awto(sensitivity = "...")]`.
+const SENSITIVITIES: [&str; 3] = ["public", "internal", "secret"];
+
+/// Enums and models declared by a schema manifest.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Manifest {
+    pub enums: Vec<ManifestEnum>,
+    pub models: Vec<ManifestModel>,
+}
+
+/// An enum of a manifest, whose fields hold the name of one of its variants.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ManifestEnum {
+    pub name: String,
+    pub description: Option<String>,
+    pub variants: Vec<String>,
+}
+
+/// A model of a manifest.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ManifestModel {
+    pub name: String,
+    pub description: Option<String>,
+    pub database_table: bool,
+    pub protobuf_message: bool,
+    pub fields: Vec<ManifestField>,
+}
+
+/// A field of a [`ManifestModel`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ManifestField {
+    pub name: String,
+    pub description: Option<String>,
+    /// Type of the field, a type of the registry such as `i64`, or the name of an enum of the manifest.
+    pub ty: String,
+    pub nullable: bool,
+    pub max_len: Option<u64>,
+    pub unique: bool,
+    /// Model whose table the column references, along with the referenced field.
+    pub references: Option<(String, String)>,
+    pub default: Option<ManifestDefault>,
+    /// Sql expression of the default of the column, such as `gen_random_uuid()`.
+    pub default_raw: Option<String>,
+    /// Who may see the values of the field, one of [`SENSITIVITIES`].
+    pub sensitivity: Option<String>,
+}
+
+/// The default value of a [`ManifestField`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum ManifestDefault {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+/// Reads and validates the manifest at `path`.
+pub async fn read_manifest(path: &str) -> Result<Manifest> {
+    let source = fs::read_to_string(path)
+        .await
+        .with_context(|| format!("could not read file '{}'", path))?;
+
+    Ok(parse_manifest(path, &source)?)
+}
+
+/// Parses the manifest `source` read from `path`, in YAML or JSON.
+///
+/// The whole manifest is validated before failing, so the error holds the [`Diagnostics`] of every problem found,
+/// pointing at the lines of the manifest.
+pub fn parse_manifest(path: &str, source: &str) -> Result<Manifest, Diagnostics> {
+    let diagnostics = |diagnostics| Diagnostics {
+        path: path.to_string(),
+        source: source.to_string(),
+        diagnostics,
+    };
+
+    let mut builder = NodeBuilder::default();
+    if let Err(err) = Parser::new(source.chars()).load(&mut builder, false) {
+        // Scan errors hold their position after their message, which diagnostics print on their own
+        let message = err.to_string();
+        let message = message
+            .rsplit_once(" at line ")
+            .map_or(message.as_str(), |(message, _)| message);
+        let span = Span::of_name(location(err.marker()), " ");
+        return Err(diagnostics(vec![Diagnostic::new(
+            format!("invalid manifest: {}", message),
+            span,
+        )]));
+    }
+
+    let mut validator = Validator::default();
+    let manifest = match builder.root {
+        Some(root) => validator.manifest(&root),
+        None => Manifest::default(),
+    };
+    if validator.diagnostics.is_empty() {
+        Ok(manifest)
+    } else {
+        Err(diagnostics(validator.diagnostics))
+    }
+}
+
+impl Manifest {
+    /// Renders the source of a schema lib declaring the models of the manifest at `path` in a single `schema!` block.
+    ///
+    /// `schema!` has no enums, so fields of an enum are strings as long as its longest variant,
+    /// documented with the variants they hold. Floating point defaults are written as sql, like imported schemas do.
+    pub fn render(&self, path: &str) -> String {
+        let mut source = format!(
+            "//! Models of the schema manifest `{}`, generated by `awto`.\n\nuse awto::prelude::*;\n",
+            path.trim_start_matches("./")
+        );
+        let chrono_types = self.chrono_types();
+        match chrono_types.as_slice() {
+            [] => {}
+            [chrono_type] => writeln!(source, "use chrono::{};", chrono_type).unwrap(),
+            _ => writeln!(source, "use chrono::{{{}}};", chrono_types.join(", ")).unwrap(),
+        }
+
+        source.push_str("\nschema! {\n");
+        for (i, model) in self.models.iter().enumerate() {
+            if i > 0 {
+                source.push('\n');
+            }
+            self.render_model(&mut source, model);
+        }
+        source.push_str("}\n");
+
+        source
+    }
+
+    /// Returns the types of the fields which are imported from chrono, sorted.
+    fn chrono_types(&self) -> Vec<&'static str> {
+        CHRONO_TYPES
+            .iter()
+            .copied()
+            .filter(|chrono_ty| {
+                self.models
+                    .iter()
+                    .flat_map(|model| &model.fields)
+                    .any(|field| field.ty == *chrono_ty)
+            })
+            .collect()
+    }
+
+    fn render_model(&self, source: &mut String, model: &ManifestModel) {
+        render_description(source, "    ", model.description.as_deref());
+        if model.database_table {
+            source.push_str("    #[database_table]\n");
+        }
+        if model.protobuf_message {
+            source.push_str("    #[protobuf_message]\n");
+        }
+        writeln!(source, "    pub struct {} {{", model.name).unwrap();
+        for field in &model.fields {
+            self.render_field(source, field);
+        }
+        source.push_str("    }\n");
+    }
+
+    fn render_field(&self, source: &mut String, field: &ManifestField) {
+        let enumeration = self
+            .enums
+            .iter()
+            .find(|enumeration| enumeration.name == field.ty);
+        render_description(source, "        ", field.description.as_deref());
+        if let Some(enumeration) = enumeration {
+            if field.description.is_some() {
+                source.push_str("        ///\n");
+            }
+            let variants: Vec<String> = enumeration
+                .variants
+                .iter()
+                .map(|variant| format!("`{}`", variant))
+                .collect();
+            writeln!(
+                source,
+                "        /// One of {}, a `{}`.",
+                variants.join(", "),
+                enumeration.name
+            )
+            .unwrap();
+        }
+
+        let mut args = Vec::new();
+        let max_len = enumeration
+            .map(|enumeration| {
+                enumeration
+                    .variants
+                    .iter()
+                    .map(|variant| variant.chars().count() as u64)
+                    .max()
+                    .unwrap_or(0)
+            })
+            .or(field.max_len);
+        if let Some(max_len) = max_len {
+            args.push(format!("max_len = {}", max_len));
+        }
+        if field.unique {
+            args.push("unique".to_string());
+        }
+        if let Some((model, field)) = &field.references {
+            args.push(format!("references = ({}, {:?})", model, field));
+        }
+        match (&field.default, &field.default_raw) {
+            (Some(ManifestDefault::Bool(value)), _) => args.push(format!("default = {}", value)),
+            (Some(ManifestDefault::Int(value)), _) => args.push(format!("default = {}", value)),
+            (Some(ManifestDefault::Float(value)), _) => {
+                args.push(format!("default_raw = \"{:?}\"", value))
+            }
+            (Some(ManifestDefault::String(value)), _) => {
+                args.push(format!("default = {:?}", value))
+            }
+            (None, Some(expression)) => args.push(format!("default_raw = {:?}", expression)),
+            (None, None) => {}
+        }
+        if let Some(sensitivity) = &field.sensitivity {
+            args.push(format!("sensitivity = {:?}", sensitivity));
+        }
+        if !args.is_empty() {
+            writeln!(source, "        #[awto({})]", args.join(", ")).unwrap();
+        }
+
+        let ty = if enumeration.is_some() {
+            "String"
+        } else {
+            field.ty.as_str()
+        };
+        let ident = field_ident(&field.name).unwrap_or_default();
+        if field.nullable {
+            writeln!(source, "        pub {}: Option<{}>,", ident, ty).unwrap();
+        } else {
+            writeln!(source, "        pub {}: {},", ident, ty).unwrap();
+        }
+    }
+}
+
+/// Writes the lines of `description` as doc comments indented by `indent`.
+fn render_description(source: &mut String, indent: &str, description: Option<&str>) {
+    for line in description.unwrap_or_default().trim_end().lines() {
+        writeln!(source, "{}/// {}", indent, line).unwrap();
+    }
+}
+
+/// Returns the JSON Schema of the manifest format, which editors validate manifests with.
+pub fn manifest_json_schema() -> serde_json::Value {
+    let name = |description: &str, pattern: &str| serde_json::json!({ "type": "string", "pattern": pattern, "description": description });
+    let description = serde_json::json!({ "type": "string", "description": "Documentation of the generated code." });
+
+    serde_json::json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "awto schema manifest",
+        "description": "Models of an awto app, read with `source = \"manifest\"` in the [schema] section of awto.toml.",
+        "type": "object",
+        "additionalProperties": false,
+        "properties": {
+            "version": {
+                "const": MANIFEST_VERSION,
+                "description": "Version of the manifest format.",
+            },
+            "enums": {
+                "type": "array",
+                "items": { "$ref": "#/definitions/enum" },
+            },
+            "models": {
+                "type": "array",
+                "items": { "$ref": "#/definitions/model" },
+            },
+        },
+        "definitions": {
+            "enum": {
+                "type": "object",
+                "additionalProperties": false,
+                "required": ["name", "variants"],
+                "properties": {
+                    "name": name("Name of the enum, in camel case.", "^[A-Z][A-Za-z0-9]*$"),
+                    "description": description,
+                    "variants": {
+                        "type": "array",
+                        "minItems": 1,
+                        "uniqueItems": true,
+                        "items": { "type": "string", "minLength": 1 },
+                        "description": "Values the fields of the enum hold.",
+                    },
+                },
+            },
+            "model": {
+                "type": "object",
+                "additionalProperties": false,
+                "required": ["name", "fields"],
+                "properties": {
+                    "name": name("Name of the model, in camel case.", "^[A-Z][A-Za-z0-9]*$"),
+                    "description": description,
+                    "roles": {
+                        "type": "array",
+                        "uniqueItems": true,
+                        "items": { "enum": ROLES },
+                        "default": ["database_table"],
+                        "description": "What is generated for the model.",
+                    },
+                    "fields": {
+                        "type": "array",
+                        "items": { "$ref": "#/definitions/field" },
+                    },
+                },
+            },
+            "field": {
+                "type": "object",
+                "additionalProperties": false,
+                "required": ["name", "type"],
+                "properties": {
+                    "name": name("Name of the field, in snake case.", "^[a-z][a-z0-9_]*$"),
+                    "description": description,
+                    "type": {
+                        "type": "string",
+                        "description": "A type of the type registry, or the name of an enum of the manifest.",
+                        "examples": FIELD_TYPES,
+                    },
+                    "nullable": { "type": "boolean", "default": false },
+                    "max_len": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "description": "Maximum length of a `String` field.",
+                    },
+                    "unique": { "type": "boolean", "default": false },
+                    "index": {
+                        "const": false,
+                        "description": "Indexes other than unique ones are not supported yet.",
+                    },
+                    "references": {
+                        "type": "string",
+                        "pattern": "^[A-Z][A-Za-z0-9]*(\\.[a-z][a-z0-9_]*)?$",
+                        "description": "Model whose table the column references, as `Model` or `Model.field`.",
+                    },
+                    "default": {
+                        "type": ["boolean", "number", "string"],
+                        "description": "Default value of the field.",
+                    },
+                    "default_raw": {
+                        "type": "string",
+                        "description": "Sql expression of the default of the column.",
+                    },
+                    "sensitivity": {
+                        "enum": SENSITIVITIES,
+                        "description": "Who may see the values of the field.",
+                    },
+                },
+                "not": { "required": ["default", "default_raw"] },
+            },
+        },
+    })
+}
+
+/// Returns the location of `marker`, whose line starts at 1 and column at 0.
+fn location(marker: &Marker) -> Location {
+    Location {
+        line: marker.line(),
+        column: marker.col() + 1,
+    }
+}
+
+/// A node of a YAML document, along with its source.
+#[derive(Clone, Debug)]
+struct Node {
+    value: NodeValue,
+    span: Span,
+}
+
+#[derive(Clone, Debug)]
+enum NodeValue {
+    /// A scalar, which is plain when it is not quoted, so only plain `true` and `1` are a boolean and a number.
+    Scalar {
+        value: String,
+        plain: bool,
+    },
+    Sequence(Vec<Node>),
+    Mapping(Vec<(Node, Node)>),
+}
+
+impl Node {
+    /// Returns what the node is, for diagnostics.
+    fn kind(&self) -> &'static str {
+        match &self.value {
+            NodeValue::Scalar { .. } => "a scalar",
+            NodeValue::Sequence(_) => "a list",
+            NodeValue::Mapping(_) => "a mapping",
+        }
+    }
+}
+
+/// A sequence or mapping whose items are being read.
+#[derive(Debug)]
+struct OpenNode {
+    node: Node,
+    anchor: usize,
+    /// Key of the entry of a mapping whose value is being read.
+    key: Option<Node>,
+}
+
+/// Builds the [`Node`] tree of the first document of a YAML source from the events of its parser.
+#[derive(Debug, Default)]
+struct NodeBuilder {
+    open: Vec<OpenNode>,
+    /// Nodes declared with an anchor, such as `&timestamps`, which aliases repeat.
+    anchors: BTreeMap<usize, Node>,
+    root: Option<Node>,
+}
+
+impl NodeBuilder {
+    fn push(&mut self, node: Node, anchor: usize) {
+        if anchor > 0 {
+            self.anchors.insert(anchor, node.clone());
+        }
+        let open = match self.open.last_mut() {
+            Some(open) => open,
+            None => {
+                self.root.get_or_insert(node);
+                return;
+            }
+        };
+        match (&mut open.node.value, open.key.take()) {
+            (NodeValue::Sequence(items), _) => items.push(node),
+            (NodeValue::Mapping(entries), Some(key)) => entries.push((key, node)),
+            (NodeValue::Mapping(_), None) => open.key = Some(node),
+            (NodeValue::Scalar { .. }, _) => unreachable!("scalars are never open"),
+        }
+    }
+
+    fn open(&mut self, value: NodeValue, anchor: usize, mark: Marker) {
+        let node = Node {
+            value,
+            span: Span::of_name(location(&mark), " "),
+        };
+        self.open.push(OpenNode {
+            node,
+            anchor,
+            key: None,
+        });
+    }
+}
+
+impl MarkedEventReceiver for NodeBuilder {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        match event {
+            Event::Scalar(value, style, anchor, _) => {
+                let plain = style == TScalarStyle::Plain;
+                let text = if plain {
+                    value.clone()
+                } else {
+                    format!("'{}'", value)
+                };
+                let node = Node {
+                    value: NodeValue::Scalar { value, plain },
+                    span: Span::of_name(location(&mark), &text),
+                };
+                self.push(node, anchor);
+            }
+            Event::SequenceStart(anchor) => {
+                self.open(NodeValue::Sequence(Vec::new()), anchor, mark)
+            }
+            Event::MappingStart(anchor) => self.open(NodeValue::Mapping(Vec::new()), anchor, mark),
+            Event::SequenceEnd | Event::MappingEnd => {
+                if let Some(open) = self.open.pop() {
+                    self.push(open.node, open.anchor);
+                }
+            }
+            Event::Alias(anchor) => {
+                if let Some(node) = self.anchors.get(&anchor).cloned() {
+                    self.push(node, 0);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Reads the manifest out of its [`Node`] tree, collecting a diagnostic for each problem found.
+#[derive(Debug, Default)]
+struct Validator {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Validator {
+    fn error(&mut self, node: &Node, message: impl Into<String>) {
+        self.report(Diagnostic::new(message, node.span));
+    }
+
+    fn report(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// Returns the entries of the mapping `node` by key, reporting keys which are not one of `keys`.
+    fn mapping<'a>(
+        &mut self,
+        node: &'a Node,
+        what: &str,
+        keys: &[&str],
+    ) -> Option<BTreeMap<&'a str, &'a Node>> {
+        let entries = match &node.value {
+            NodeValue::Mapping(entries) => entries,
+            _ => {
+                self.error(
+                    node,
+                    format!("{} must be a mapping, found {}", what, node.kind()),
+                );
+                return None;
+            }
+        };
+
+        let mut mapping = BTreeMap::new();
+        for (key, value) in entries {
+            let name = match &key.value {
+                NodeValue::Scalar { value, .. } => value.as_str(),
+                _ => {
+                    self.error(key, format!("keys of {} must be scalars", what));
+                    continue;
+                }
+            };
+            if !keys.contains(&name) {
+                let expected: Vec<String> = keys.iter().map(|key| format!("`{}`", key)).collect();
+                self.report(
+                    Diagnostic::new(format!("unknown key `{}` of {}", name, what), key.span)
+                        .help(format!("expected one of {}", expected.join(", "))),
+                );
+            } else if mapping.insert(name, value).is_some() {
+                self.error(key, format!("key `{}` of {} is declared twice", name, what));
+            }
+        }
+
+        Some(mapping)
+    }
+
+    fn sequence<'a>(&mut self, node: &'a Node, what: &str) -> Option<&'a [Node]> {
+        match &node.value {
+            NodeValue::Sequence(items) => Some(items),
+            _ => {
+                self.error(
+                    node,
+                    format!("{} must be a list, found {}", what, node.kind()),
+                );
+                None
+            }
+        }
+    }
+
+    fn string<'a>(&mut self, node: &'a Node, what: &str) -> Option<&'a str> {
+        match &node.value {
+            NodeValue::Scalar { value, .. } => Some(value),
+            _ => {
+                self.error(
+                    node,
+                    format!("{} must be a string, found {}", what, node.kind()),
+                );
+                None
+            }
+        }
+    }
+
+    fn bool(&mut self, node: &Node, what: &str) -> Option<bool> {
+        match &node.value {
+            NodeValue::Scalar { value, plain: true } if value == "true" => Some(true),
+            NodeValue::Scalar { value, plain: true } if value == "false" => Some(false),
+            _ => {
+                self.error(node, format!("{} must be `true` or `false`", what));
+                None
+            }
+        }
+    }
+
+    fn integer(&mut self, node: &Node, what: &str) -> Option<i64> {
+        match &node.value {
+            NodeValue::Scalar { value, plain: true } if value.parse::<i64>().is_ok() => {
+                value.parse().ok()
+            }
+            _ => {
+                self.error(node, format!("{} must be an integer", what));
+                None
+            }
+        }
+    }
+
+    fn manifest(&mut self, root: &Node) -> Manifest {
+        let mut manifest = Manifest::default();
+        let entries = match self.mapping(root, "the manifest", &MANIFEST_KEYS) {
+            Some(entries) => entries,
+            None => return manifest,
+        };
+
+        if let Some(version) = entries.get("version") {
+            match self.integer(version, "`version`") {
+                Some(MANIFEST_VERSION) | None => {}
+                Some(other) => self.report(
+                    Diagnostic::new(
+                        format!("unsupported manifest version `{}`", other),
+                        version.span,
+                    )
+                    .help(format!(
+                        "this version of awto reads version {} of the manifest format",
+                        MANIFEST_VERSION
+                    )),
+                ),
+            }
+        }
+
+        let mut names = BTreeMap::new();
+        if let Some(enums) = entries
+            .get("enums")
+            .and_then(|node| self.sequence(node, "`enums`"))
+        {
+            for node in enums {
+                if let Some(enumeration) = self.enumeration(node, &mut names) {
+                    manifest.enums.push(enumeration);
+                }
+            }
+        }
+        let mut references = Vec::new();
+        if let Some(models) = entries
+            .get("models")
+            .and_then(|node| self.sequence(node, "`models`"))
+        {
+            for node in models {
+                if let Some(model) = self.model(node, &manifest.enums, &mut names, &mut references)
+                {
+                    manifest.models.push(model);
+                }
+            }
+        }
+
+        // References are checked once every model is read, as models may reference the models following them
+        for reference in references {
+            let model = manifest
+                .models
+                .iter()
+                .find(|model| model.name == reference.model);
+            let target = format!("{}.{}", reference.model, reference.field);
+            match model {
+                Some(model) if !model.database_table => self.error(
+                    reference.node,
+                    format!(
+                        "`{}` references `{}`, which is not a database table",
+                        reference.from, reference.model
+                    ),
+                ),
+                Some(model)
+                    if !model
+                        .fields
+                        .iter()
+                        .any(|field| field.name == reference.field) =>
+                {
+                    self.error(
+                        reference.node,
+                        format!(
+                            "`{}` references `{}`, which is not a field",
+                            reference.from, target
+                        ),
+                    )
+                }
+                Some(_) => {}
+                None => self.report(
+                    Diagnostic::new(
+                        format!(
+                            "`{}` references `{}`, which is not a model of the manifest",
+                            reference.from, reference.model
+                        ),
+                        reference.node.span,
+                    )
+                    .help("fields of a manifest can only reference the models of the manifest"),
+                ),
+            }
+        }
+
+        manifest
+    }
+
+    /// Reads the enum `node`, recording its name in `names` along with the names of the models.
+    fn enumeration(
+        &mut self,
+        node: &Node,
+        names: &mut BTreeMap<String, Span>,
+    ) -> Option<ManifestEnum> {
+        let entries = self.mapping(node, "an enum", &ENUM_KEYS)?;
+        let name = self.type_name(node, &entries, "an enum", names)?;
+        let description = entries
+            .get("description")
+            .and_then(|node| self.string(node, "`description`"))
+            .map(str::to_string);
+
+        let mut variants: Vec<String> = Vec::new();
+        let variants_node = match entries.get("variants") {
+            Some(variants_node) => *variants_node,
+            None => {
+                self.error(node, format!("enum `{}` has no `variants`", name));
+                return None;
+            }
+        };
+        for variant in self
+            .sequence(variants_node, "`variants`")
+            .unwrap_or_default()
+        {
+            match self.string(variant, "a variant") {
+                Some("") => self.error(variant, format!("variants of `{}` cannot be empty", name)),
+                Some(value) if variants.iter().any(|other| other == value) => self.error(
+                    variant,
+                    format!("variant `{}` of `{}` is declared twice", value, name),
+                ),
+                Some(value) => variants.push(value.to_string()),
+                None => {}
+            }
+        }
+        if variants.is_empty() {
+            self.error(variants_node, format!("enum `{}` has no variants", name));
+        }
+
+        Some(ManifestEnum {
+            name,
+            description,
+            variants,
+        })
+    }
+
+    /// Reads the `name` of the enum or model `node`, which must be a camel case identifier declared once in `names`.
+    fn type_name(
+        &mut self,
+        node: &Node,
+        entries: &BTreeMap<&str, &Node>,
+        what: &str,
+        names: &mut BTreeMap<String, Span>,
+    ) -> Option<String> {
+        let name_node = match entries.get("name") {
+            Some(name_node) => *name_node,
+            None => {
+                self.error(node, format!("{} has no `name`", what));
+                return None;
+            }
+        };
+        let name = self.string(name_node, "`name`")?;
+        if !is_ident(name) || name.to_camel_case() != name || FIELD_TYPES.contains(&name) {
+            self.report(
+                Diagnostic::new(
+                    format!("`{}` is not a valid name for {}", name, what),
+                    name_node.span,
+                )
+                .help("names of enums and models are camel case identifiers, such as `OrderLine`"),
+            );
+            return None;
+        }
+        if let Some(span) = names.get(name) {
+            self.report(
+                Diagnostic::new(format!("`{}` is declared twice", name), name_node.span)
+                    .label(format!("also declared on line {}", span.start.line)),
+            );
+            return None;
+        }
+        names.insert(name.to_string(), name_node.span);
+
+        Some(name.to_string())
+    }
+
+    /// Reads the model `node`, adding the references of its fields to `references`.
+    fn model<'a>(
+        &mut self,
+        node: &'a Node,
+        enums: &[ManifestEnum],
+        names: &mut BTreeMap<String, Span>,
+        references: &mut Vec<Reference<'a>>,
+    ) -> Option<ManifestModel> {
+        let entries = self.mapping(node, "a model", &MODEL_KEYS)?;
+        let name = self.type_name(node, &entries, "a model", names)?;
+        let description = entries
+            .get("description")
+            .and_then(|node| self.string(node, "`description`"))
+            .map(str::to_string);
+
+        let mut roles = BTreeSet::new();
+        match entries.get("roles") {
+            Some(roles_node) => {
+                for role in self.sequence(roles_node, "`roles`").unwrap_or_default() {
+                    match self.string(role, "a role") {
+                        Some(value) if ROLES.contains(&value) => {
+                            roles.insert(value);
+                        }
+                        Some(value) => self.report(
+                            Diagnostic::new(
+                                format!("unknown role `{}` of `{}`", value, name),
+                                role.span,
+                            )
+                            .help("expected `database_table` or `protobuf_message`"),
+                        ),
+                        None => {}
+                    }
+                }
+            }
+            None => {
+                roles.insert("database_table");
+            }
+        }
+
+        let mut fields = Vec::new();
+        let mut declared = BTreeMap::new();
+        match entries.get("fields") {
+            Some(fields_node) => {
+                for field_node in self.sequence(fields_node, "`fields`").unwrap_or_default() {
+                    let protobuf_message = roles.contains("protobuf_message");
+                    let field = self.field(
+                        field_node,
+                        &name,
+                        protobuf_message,
+                        enums,
+                        &mut declared,
+                        references,
+                    );
+                    if let Some(field) = field {
+                        fields.push(field);
+                    }
+                }
+            }
+            None => self.error(node, format!("model `{}` has no `fields`", name)),
+        }
+
+        let database_table = roles.contains("database_table");
+        let missing: Vec<String> = REQUIRED_FIELDS
+            .iter()
+            .filter(|(field_name, ty)| {
+                !fields.iter().any(|field: &ManifestField| {
+                    field.name == *field_name && field.ty == *ty && !field.nullable
+                })
+            })
+            .map(|(field_name, ty)| format!("`{}: {}`", field_name, ty))
+            .collect();
+        if database_table && !missing.is_empty() {
+            self.report(
+                Diagnostic::new(
+                    format!(
+                        "database table `{}` is missing {}",
+                        name,
+                        missing.join(", ")
+                    ),
+                    entries["name"].span,
+                )
+                .help(
+                    "database tables need `id: Uuid`, `created_at: DateTime<FixedOffset>` \
+                    and `updated_at: DateTime<FixedOffset>` fields",
+                ),
+            );
+        }
+
+        Some(ManifestModel {
+            name,
+            description,
+            database_table,
+            protobuf_message: roles.contains("protobuf_message"),
+            fields,
+        })
+    }
+
+    /// Reads the field `node` of `model`, which is a protobuf message with `protobuf_message`,
+    /// recording its name in `declared`.
+    fn field<'a>(
+        &mut self,
+        node: &'a Node,
+        model: &str,
+        protobuf_message: bool,
+        enums: &[ManifestEnum],
+        declared: &mut BTreeMap<String, Span>,
+        references: &mut Vec<Reference<'a>>,
+    ) -> Option<ManifestField> {
+        let entries = self.mapping(node, "a field", &FIELD_KEYS)?;
+        let name_node = match entries.get("name") {
+            Some(name_node) => *name_node,
+            None => {
+                self.error(node, format!("a field of `{}` has no `name`", model));
+                return None;
+            }
+        };
+        let name = self.string(name_node, "`name`")?;
+        if !is_ident(name) || name.to_snake_case() != name || field_ident(name).is_none() {
+            self.report(
+                Diagnostic::new(
+                    format!("`{}` is not a valid name for a field", name),
+                    name_node.span,
+                )
+                .help("names of fields are snake case identifiers, such as `unit_price`"),
+            );
+            return None;
+        }
+        if let Some(span) = declared.get(name) {
+            self.report(
+                Diagnostic::new(
+                    format!("field `{}` of `{}` is declared twice", name, model),
+                    name_node.span,
+                )
+                .label(format!("also declared on line {}", span.start.line)),
+            );
+            return None;
+        }
+        declared.insert(name.to_string(), name_node.span);
+
+        let qualified = format!("{}.{}", model, name);
+        let ty_node = match entries.get("type") {
+            Some(ty_node) => *ty_node,
+            None => {
+                self.error(node, format!("field `{}` has no `type`", qualified));
+                return None;
+            }
+        };
+        let ty = self.string(ty_node, "`type`")?.replace(' ', "");
+        let ty = match FIELD_TYPES
+            .iter()
+            .find(|field_ty| field_ty.replace(' ', "") == ty)
+        {
+            Some(field_ty) => field_ty.to_string(),
+            None if enums.iter().any(|enumeration| enumeration.name == ty) => ty,
+            None => {
+                let expected: Vec<String> =
+                    FIELD_TYPES.iter().map(|ty| format!("`{}`", ty)).collect();
+                self.report(
+                    Diagnostic::new(
+                        format!("unknown type `{}` of `{}`", ty, qualified),
+                        ty_node.span,
+                    )
+                    .help(format!(
+                        "expected an enum of the manifest, or one of {}",
+                        expected.join(", ")
+                    )),
+                );
+                return None;
+            }
+        };
+        if protobuf_message && NON_PROTOBUF_TYPES.contains(&ty.as_str()) {
+            self.report(
+                Diagnostic::new(
+                    format!(
+                        "`{}` is a `{}`, which protobuf messages cannot hold",
+                        qualified, ty
+                    ),
+                    ty_node.span,
+                )
+                .help("change its type, or remove the `protobuf_message` role of the model"),
+            );
+            return None;
+        }
+        let enumeration = enums.iter().find(|enumeration| enumeration.name == ty);
+
+        let mut field = ManifestField {
+            name: name.to_string(),
+            description: entries
+                .get("description")
+                .and_then(|node| self.string(node, "`description`"))
+                .map(str::to_string),
+            nullable: entries
+                .get("nullable")
+                .and_then(|node| self.bool(node, "`nullable`"))
+                .unwrap_or(false),
+            unique: entries
+                .get("unique")
+                .and_then(|node| self.bool(node, "`unique`"))
+                .unwrap_or(false),
+            ..ManifestField::default()
+        };
+        if let Some(max_len_node) = entries.get("max_len") {
+            match self.integer(max_len_node, "`max_len`") {
+                Some(_) if ty != "String" => self.error(
+                    max_len_node,
+                    format!("`{}` has a `max_len`, but is not a `String`", qualified),
+                ),
+                Some(max_len) if max_len < 1 => {
+                    self.error(max_len_node, "`max_len` must be at least 1")
+                }
+                Some(max_len) => field.max_len = Some(max_len as u64),
+                None => {}
+            }
+        }
+        if let Some(index_node) = entries.get("index") {
+            if self.bool(index_node, "`index`") == Some(true) {
+                self.report(
+                    Diagnostic::new(
+                        format!("`{}` is indexed, which `schema!` does not support yet", qualified),
+                        index_node.span,
+                    )
+                    .help("declare the field `unique: true`, which is indexed, or create the index outside of awto"),
+                );
+            }
+        }
+        if let Some(references_node) = entries.get("references") {
+            if let Some(target) = self.string(references_node, "`references`") {
+                let (target_model, target_field) = target.split_once('.').unwrap_or((target, "id"));
+                field.references = Some((target_model.to_string(), target_field.to_string()));
+                references.push(Reference {
+                    node: references_node,
+                    from: qualified.clone(),
+                    model: target_model.to_string(),
+                    field: target_field.to_string(),
+                });
+            }
+        }
+        match (entries.get("default"), entries.get("default_raw")) {
+            (Some(_), Some(default_raw_node)) => self.error(
+                default_raw_node,
+                format!("`{}` has both a `default` and a `default_raw`", qualified),
+            ),
+            (Some(default_node), None) => {
+                field.default = self.default_value(default_node, &qualified, &ty, enumeration)
+            }
+            (None, Some(default_raw_node)) => {
+                field.default_raw = self
+                    .string(default_raw_node, "`default_raw`")
+                    .map(str::to_string)
+            }
+            (None, None) => {}
+        }
+        if let Some(sensitivity_node) = entries.get("sensitivity") {
+            match self.string(sensitivity_node, "`sensitivity`") {
+                Some(sensitivity) if SENSITIVITIES.contains(&sensitivity) => {
+                    field.sensitivity = Some(sensitivity.to_string())
+                }
+                Some(sensitivity) => self.report(
+                    Diagnostic::new(
+                        format!("unknown sensitivity `{}` of `{}`", sensitivity, qualified),
+                        sensitivity_node.span,
+                    )
+                    .help(format!("expected one of {}", SENSITIVITIES.join(", "))),
+                ),
+                None => {}
+            }
+        }
+        field.ty = ty;
+
+        Some(field)
+    }
+
+    /// Reads the default `node` of the field `qualified` of type `ty`, which holds a variant of `enumeration` if any.
+    fn default_value(
+        &mut self,
+        node: &Node,
+        qualified: &str,
+        ty: &str,
+        enumeration: Option<&ManifestEnum>,
+    ) -> Option<ManifestDefault> {
+        let (value, plain) = match &node.value {
+            NodeValue::Scalar { value, plain } => (value.as_str(), *plain),
+            _ => {
+                self.error(
+                    node,
+                    format!(
+                        "the default of `{}` must be a scalar, found {}",
+                        qualified,
+                        node.kind()
+                    ),
+                );
+                return None;
+            }
+        };
+        let what = format!("the default of `{}`", qualified);
+
+        let default = match (ty, enumeration) {
+            (_, Some(enumeration))
+                if enumeration.variants.iter().any(|variant| variant == value) =>
+            {
+                ManifestDefault::String(value.to_string())
+            }
+            (_, Some(enumeration)) => {
+                let expected: Vec<String> = enumeration
+                    .variants
+                    .iter()
+                    .map(|variant| format!("`{}`", variant))
+                    .collect();
+                self.report(
+                    Diagnostic::new(format!("{} is not a variant of `{}`", what, ty), node.span)
+                        .help(format!("expected one of {}", expected.join(", "))),
+                );
+                return None;
+            }
+            ("bool", _) => ManifestDefault::Bool(self.bool(node, &what)?),
+            ("i16" | "i32" | "i64", _) => ManifestDefault::Int(self.integer(node, &what)?),
+            ("f32" | "f64", _) => match value.parse::<f64>() {
+                Ok(value) if plain && value.is_finite() => ManifestDefault::Float(value),
+                _ => {
+                    self.error(node, format!("{} must be a number", what));
+                    return None;
+                }
+            },
+            ("String", _) => ManifestDefault::String(value.to_string()),
+            _ => {
+                self.report(
+                    Diagnostic::new(format!("`{}` is a `{}`, which cannot have a `default`", qualified, ty), node.span)
+                        .help("set the sql expression of its default with `default_raw`, such as `default_raw: now()`"),
+                );
+                return None;
+            }
+        };
+
+        Some(default)
+    }
+}
+
+/// A reference of a field to a model, checked once every model of the manifest is read.
+#[derive(Debug)]
+struct Reference<'a> {
+    node: &'a Node,
+    /// Field declaring the reference, such as `Product.category_id`.
+    from: String,
+    model: String,
+    field: String,
+}
+
+/// Returns whether `name` is an identifier, such as `OrderLine` or `unit_price`.
+fn is_ident(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(first) if first.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Returns the identifier of the field `name`, such as `r#type` for `type`,
+/// or `None` when no identifier names it, such as for `self`.
+fn field_ident(name: &str) -> Option<String> {
+    if syn::parse_str::<syn::Ident>(name).is_ok() {
+        return Some(name.to_string());
+    }
+
+    let raw = format!("r#{}", name);
+    syn::parse_str::<syn::Ident>(&raw).ok().map(|_| raw)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MANIFEST: &str = r#"# Models of the catalog
+version: 1
+enums:
+  - name: Status
+    variants: [draft, live]
+models:
+  - name: Category
+    fields: &timestamps
+      - { name: id, type: Uuid }
+      - { name: created_at, type: DateTime<FixedOffset> }
+      - { name: updated_at, type: DateTime<FixedOffset> }
+  - name: Product
+    description: A product sold in the shop.
+    roles: [database_table, protobuf_message]
+    fields:
+      - { name: id, type: Uuid }
+      - { name: created_at, type: "DateTime<FixedOffset>" }
+      - { name: updated_at, type: "DateTime<FixedOffset>" }
+      - name: name
+        type: String
+        max_len: 120
+        unique: true
+      - { name: type, type: String, nullable: true }
+      - { name: status, type: Status, default: live }
+      - { name: price, type: f64, default: 2 }
+      - { name: released_at, type: NaiveDateTime, nullable: true, default_raw: "now()" }
+      - { name: category_id, type: Uuid, references: Category }
+      - { name: supplier_cost, type: i64, nullable: true, sensitivity: internal }
+"#;
+
+    fn messages(source: &str) -> Vec<(usize, String)> {
+        parse_manifest("models.yaml", source)
+            .unwrap_err()
+            .diagnostics
+            .into_iter()
+            .map(|diagnostic| (diagnostic.span.start.line, diagnostic.message))
+            .collect()
+    }
+
+    #[test]
+    fn rendered_manifest() {
+        let manifest = parse_manifest("models.yaml", MANIFEST).unwrap();
+        assert_eq!(manifest.enums[0].variants, ["draft", "live"]);
+        assert_eq!(manifest.models[0].fields.len(), 3);
+        assert_eq!(
+            manifest.models[1].fields[8].references,
+            Some(("Category".to_string(), "id".to_string()))
+        );
+
+        let rendered = manifest.render("./models.yaml");
+        let models = super::super::parse_schema_models(&rendered).unwrap();
+        assert_eq!(models[1].fields[5].ty, "String");
+        assert_eq!(models[1].fields[8].references.as_deref(), Some("Category"));
+        assert_eq!(
+            rendered,
+            r#"//! Models of the schema manifest `models.yaml`, generated by `awto`.
+
+use awto::prelude::*;
+use chrono::NaiveDateTime;
+
+schema! {
+    #[database_table]
+    pub struct Category {
+        pub id: Uuid,
+        pub created_at: DateTime<FixedOffset>,
+        pub updated_at: DateTime<FixedOffset>,
+    }
+
+    /// A product sold in the shop.
+    #[database_table]
+    #[protobuf_message]
+    pub struct Product {
+        pub id: Uuid,
+        pub created_at: DateTime<FixedOffset>,
+        pub updated_at: DateTime<FixedOffset>,
+        #[awto(max_len = 120, unique)]
+        pub name: String,
+        pub r#type: Option<String>,
+        /// One of `draft`, `live`, a `Status`.
+        #[awto(max_len = 5, default = "live")]
+        pub status: String,
+        #[awto(default_raw = "2.0")]
+        pub price: f64,
+        #[awto(default_raw = "now()")]
+        pub released_at: Option<NaiveDateTime>,
+        #[awto(references = (Category, "id"))]
+        pub category_id: Uuid,
+        #[awto(sensitivity = "internal")]
+        pub supplier_cost: Option<i64>,
+    }
+}
+"#
+        );
+    }
+
+    #[test]
+    fn json_manifest() {
+        let manifest = parse_manifest(
+            "models.json",
+            r#"{
+  "models": [
+    {"name": "Tag", "roles": ["protobuf_message"], "fields": [{"name": "label", "type": "String", "default": "new"}]}
+  ]
+}"#,
+        )
+        .unwrap();
+        assert_eq!(manifest.models[0].name, "Tag");
+        assert!(!manifest.models[0].database_table);
+        assert_eq!(
+            manifest.models[0].fields[0].default,
+            Some(ManifestDefault::String("new".to_string()))
+        );
+
+        assert_eq!(
+            parse_manifest("models.yaml", "").unwrap(),
+            Manifest::default()
+        );
+    }
+
+    #[test]
+    fn invalid_manifest() {
+        assert_eq!(
+            messages("models:\n  - name: Product\n    fields: [\n"),
+            [(
+                4,
+                "invalid manifest: while parsing a node, did not find expected node content"
+                    .to_string()
+            )]
+        );
+
+        let source = r#"version: 2
+models:
+  - name: Product
+    table: product
+    fields:
+      - { name: id, type: Uuid }
+      - { name: created_at, type: DateTime<FixedOffset> }
+      - { name: price, type: Money, index: true }
+      - { name: status, type: String, max_len: 0, default: 1 }
+      - { name: ready, type: bool, default: "yes", sensitivity: hidden }
+      - { name: id, type: i64 }
+      - { name: owner_id, type: Uuid, references: Owner, index: true }
+  - name: product
+    fields: []
+  - name: Tag
+    roles: [protobuf_message]
+    fields:
+      - { name: added_on, type: NaiveDate }
+"#;
+        assert_eq!(
+            messages(source),
+            [
+                (1, "unsupported manifest version `2`".to_string()),
+                (4, "unknown key `table` of a model".to_string()),
+                (8, "unknown type `Money` of `Product.price`".to_string()),
+                (9, "`max_len` must be at least 1".to_string()),
+                (
+                    10,
+                    "the default of `Product.ready` must be `true` or `false`".to_string()
+                ),
+                (
+                    10,
+                    "unknown sensitivity `hidden` of `Product.ready`".to_string()
+                ),
+                (11, "field `id` of `Product` is declared twice".to_string()),
+                (
+                    12,
+                    "`Product.owner_id` is indexed, which `schema!` does not support yet"
+                        .to_string()
+                ),
+                (
+                    3,
+                    "database table `Product` is missing `updated_at: DateTime<FixedOffset>`"
+                        .to_string()
+                ),
+                (13, "`product` is not a valid name for a model".to_string()),
+                (
+                    18,
+                    "`Tag.added_on` is a `NaiveDate`, which protobuf messages cannot hold"
+                        .to_string()
+                ),
+                (
+                    12,
+                    "`Product.owner_id` references `Owner`, which is not a model of the manifest"
+                        .to_string()
+                ),
+            ]
+        );
+
+        let diagnostics = parse_manifest("models.yaml", source).unwrap_err();
+        let span = diagnostics.diagnostics[2].span;
+        assert_eq!((span.start.column, span.end.column), (30, 35));
+    }
+}
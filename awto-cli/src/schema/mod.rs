@@ -0,0 +1,2537 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    path::Path,
+};
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use clap::Parser;
+use log::warn;
+use quote::ToTokens;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use syn::ext::IdentExt;
+use tokio::fs;
+
+use crate::{
+    cancel::Cancellation,
+    compile::{profile_phase, Visibility, GENERATED_HEADER},
+    config::{Config, CONFIG_PATH},
+    error::Error,
+    util::{CargoFile, WorkspaceContext},
+    Runnable,
+};
+
+pub use self::api_docs::{ApiDocsConfig, ApiDocsOverlay};
+pub use self::changes::Changes;
+pub use self::check::Check;
+pub use self::diagnostic::Diagnostics;
+pub use self::dump::Dump;
+pub use self::expand::{enable_expansion, is_expansion_enabled};
+pub use self::explain::Explain;
+pub use self::features::{check_gated_relations, FeaturesConfig};
+pub use self::format::Fmt;
+pub use self::import::Import;
+pub use self::lint::{Lint, LintConfig};
+pub use self::manifest::{
+    manifest_json_schema, read_manifest, MANIFEST_PACKAGE, MANIFEST_PACKAGE_DIR,
+    MANIFEST_SCHEMA_FILE,
+};
+pub use self::snapshot::Snapshot;
+
+use self::diagnostic::{Diagnostic, Span};
+
+mod api_docs;
+mod changes;
+mod check;
+mod diagnostic;
+mod dump;
+mod expand;
+mod explain;
+mod features;
+mod format;
+mod import;
+mod lint;
+mod manifest;
+mod parse;
+mod snapshot;
+
+/// Path of the schema lib read for the models of the app.
+pub const SCHEMA_LIB_PATH: &str = "./schema/src/lib.rs";
+
+/// Checks the app schema
+#[derive(Parser)]
+pub struct Schema {
+    #[clap(subcommand)]
+    pub subcmd: SubCommand,
+}
+
+#[derive(Parser)]
+pub enum SubCommand {
+    Lint(Lint),
+    Snapshot(Snapshot),
+    Changes(Changes),
+    Fmt(Fmt),
+    Import(Import),
+    Explain(Explain),
+    Check(Check),
+    Dump(Dump),
+}
+
+#[async_trait]
+impl Runnable for Schema {
+    async fn run(&mut self, cancellation: &Cancellation) -> Result<()> {
+        match &mut self.subcmd {
+            SubCommand::Lint(lint) => lint.run(cancellation).await,
+            SubCommand::Snapshot(snapshot) => snapshot.run(cancellation).await,
+            SubCommand::Changes(changes) => changes.run(cancellation).await,
+            SubCommand::Fmt(fmt) => fmt.run(cancellation).await,
+            SubCommand::Import(import) => import.run(cancellation).await,
+            SubCommand::Explain(explain) => explain.run(cancellation).await,
+            SubCommand::Check(check) => check.run(cancellation).await,
+            SubCommand::Dump(dump) => dump.run(cancellation).await,
+        }
+    }
+}
+
+/// A model declared in a `schema!` macro of the schema package.
+///
+/// Models are read from the source code of the schema package, which allows the cli to know about
+/// them without compiling the schema.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SchemaModel {
+    pub name: String,
+    /// Database set with `#[awto(database = "...")]`.
+    pub database: Option<String>,
+    /// Feature the model is generated with, set with `#[awto(feature = "...")]`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feature: Option<String>,
+    pub is_database_table: bool,
+    /// Whether the table is partitioned with `#[awto(partition(...))]`.
+    pub partitioned: bool,
+    /// Whether the table is marked `#[awto(readonly)]`.
+    #[serde(skip)]
+    pub readonly: bool,
+    /// Whether the table is managed outside of awto, marked `#[awto(external)]`.
+    #[serde(skip)]
+    pub external: bool,
+    /// Whether the table is marked `#[awto(skip_rest)]`.
+    #[serde(skip)]
+    pub skip_rest: bool,
+    /// Visibility of the entity set with `#[awto(visibility(entity = "..."))]`.
+    #[serde(skip)]
+    pub entity_visibility: Option<Visibility>,
+    /// Visibility of the active model set with `#[awto(visibility(active_model = "..."))]`.
+    #[serde(skip)]
+    pub active_model_visibility: Option<Visibility>,
+    pub fields: Vec<SchemaField>,
+    /// Lints suppressed with `#[awto(allow(...))]` for the whole model.
+    pub allow: Vec<String>,
+    /// Attributes of the model as written in the source, with `#[awto(...)]` split into its arguments.
+    pub attributes: Vec<String>,
+    /// Schema package declaring the model, when the models are read from the packages of the `[schema]` section.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub package: Option<String>,
+    /// Path of the schema lib declaring the model, when it is not [`SCHEMA_LIB_PATH`].
+    #[serde(skip)]
+    pub lib_path: Option<String>,
+    #[serde(skip)]
+    pub location: Location,
+}
+
+impl SchemaModel {
+    /// Returns the path of the schema lib declaring the model.
+    pub fn lib_path(&self) -> &str {
+        self.lib_path.as_deref().unwrap_or(SCHEMA_LIB_PATH)
+    }
+}
+
+/// A field of a [`SchemaModel`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SchemaField {
+    pub name: String,
+    /// Type as written in the source without whitespace, such as `Option<String>`.
+    pub ty: String,
+    /// Database type set with `#[awto(db_type = "...")]`.
+    pub db_type: Option<String>,
+    pub has_max_len: bool,
+    /// Maximum length set with `#[awto(max_len = ...)]`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_len: Option<u32>,
+    /// Sql expression filling the rows which do not fit the narrowed column of the field,
+    /// set with `#[awto(column(backfill = "..."))]`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backfill: Option<String>,
+    /// Model referenced with `#[awto(references = (Model, "column"))]`.
+    pub references: Option<String>,
+    pub unique: bool,
+    /// Whether the field is marked `#[awto(deprecated)]`.
+    pub deprecated: bool,
+    /// Date the field was deprecated, set with `#[awto(deprecated(since = "2024-06-01"))]`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deprecated_since: Option<String>,
+    /// Sensitivity set with `#[awto(sensitivity = "...")]`, such as `secret`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sensitivity: Option<String>,
+    /// Feature the field is generated with, set with `#[awto(feature = "...")]`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feature: Option<String>,
+    /// Whether the field is marked `#[awto(sensitive)]` or `#[serde(skip)]`.
+    #[serde(skip)]
+    pub sensitive: bool,
+    /// Lints suppressed with `#[awto(allow(...))]` for this field.
+    pub allow: Vec<String>,
+    /// Attributes of the field as written in the source, with `#[awto(...)]` split into its arguments.
+    pub attributes: Vec<String>,
+    /// Mixin declaring the field, when it was included with `#[awto(include = ...)]`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mixin: Option<String>,
+    #[serde(skip)]
+    pub location: Location,
+    #[serde(skip)]
+    pub ty_span: Span,
+}
+
+/// Position of an identifier in the schema lib.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Location {
+    fn of(ident: &syn::Ident) -> Self {
+        Location::from(ident.span().start())
+    }
+}
+
+impl From<proc_macro2::LineColumn> for Location {
+    fn from(line_column: proc_macro2::LineColumn) -> Self {
+        Location {
+            line: line_column.line,
+            column: line_column.column + 1,
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+struct Structs(Vec<syn::ItemStruct>);
+
+impl syn::parse::Parse for Structs {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let mut structs = Vec::new();
+        while !input.is_empty() {
+            structs.push(input.parse()?);
+        }
+
+        Ok(Structs(structs))
+    }
+}
+
+/// The `[schema]` section of `awto.toml`, listing the schema packages of apps whose models are split across several,
+/// or the manifest of apps declaring their models outside of Rust.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SchemaConfig {
+    /// Where the models are declared.
+    pub source: SchemaSource,
+    /// Path of the manifest with `source = "manifest"`, relative to the root of the workspace.
+    pub path: Option<String>,
+    /// Names of the packages the models are read from, such as `["schema-billing", "schema-catalog"]`,
+    /// instead of the `schema` package.
+    pub packages: Vec<String>,
+    /// Handling of models declared with the same name in several packages.
+    pub duplicates: Duplicates,
+}
+
+impl SchemaConfig {
+    /// Path of the manifest when the `[schema]` section has no `path`.
+    pub const DEFAULT_MANIFEST_PATH: &'static str = "models.yaml";
+
+    /// Returns the path of the manifest read with `source = "manifest"`.
+    pub fn manifest_path(&self) -> String {
+        let path = self.path.as_deref().unwrap_or(Self::DEFAULT_MANIFEST_PATH);
+        if path.starts_with('/') || path.starts_with("./") {
+            path.to_string()
+        } else {
+            format!("./{}", path)
+        }
+    }
+
+    /// Returns the names of the packages the models are read from, which are the [`packages`](Self::packages),
+    /// followed by the generated package of the manifest with `source = "manifest"`,
+    /// or nothing when the models are read from the `schema` package.
+    pub fn package_names(&self) -> Vec<String> {
+        let mut names = self.packages.clone();
+        if self.source == SchemaSource::Manifest {
+            names.push(MANIFEST_PACKAGE.to_string());
+        }
+
+        names
+    }
+}
+
+/// Where the models are declared, set with `source = "..."` in the `[schema]` section.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SchemaSource {
+    /// Rust schema packages, the `schema` package unless `packages` lists others.
+    #[default]
+    Rust,
+    /// A YAML or JSON manifest at `path`, merged with the schema packages `packages` lists.
+    Manifest,
+}
+
+/// Handling of models declared in several schema packages, set with `duplicates = "..."` in the `[schema]` section.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Duplicates {
+    /// Fails, naming both packages.
+    #[default]
+    Error,
+    /// Prefixes the models with their package, such as `BillingInvoice` for the `Invoice` of `schema-billing`.
+    Namespace,
+}
+
+impl Duplicates {
+    /// Returns the path of the matching variant of awto-compile, written into build scripts.
+    pub fn variant(&self) -> &'static str {
+        match self {
+            Duplicates::Error => "Duplicates::Error",
+            Duplicates::Namespace => "Duplicates::Namespace",
+        }
+    }
+}
+
+/// A schema package listed in the `[schema]` section of `awto.toml`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SchemaPackage {
+    pub name: String,
+    /// Directory of the package relative to the root of the workspace, such as `./schema-billing`.
+    pub dir: String,
+}
+
+impl SchemaPackage {
+    /// Returns the path of the lib of the package.
+    pub fn lib_path(&self) -> String {
+        format!("{}/src/lib.rs", self.dir)
+    }
+}
+
+/// Returns the schema packages listed in the `[schema]` section of `awto.toml`, found among the members of the
+/// workspace in the current directory, followed by the generated package of the manifest with `source = "manifest"`,
+/// or nothing when the models are read from the `schema` package.
+pub async fn schema_packages() -> Result<Vec<SchemaPackage>> {
+    let config = Config::load(CONFIG_PATH).await?.schema;
+    let mut packages = Vec::new();
+    if !config.packages.is_empty() {
+        let workspace = WorkspaceContext::load(".").await?;
+        for name in &config.packages {
+            let dir = workspace
+                .package_by_name(name)
+                .ok_or_else(|| Error::UnknownSchemaPackage(name.to_string()))?;
+            packages.push(SchemaPackage {
+                name: name.to_string(),
+                dir: format!("./{}", dir.display())
+                    .trim_end_matches('/')
+                    .to_string(),
+            });
+        }
+    }
+    if config.source == SchemaSource::Manifest {
+        packages.push(SchemaPackage {
+            name: MANIFEST_PACKAGE.to_string(),
+            dir: MANIFEST_PACKAGE_DIR.to_string(),
+        });
+    }
+
+    Ok(packages)
+}
+
+/// Reads the models of the schema lib, after checking the schema package is named `schema`.
+///
+/// With `--expand`, the models are read from the expanded code of the schema packages instead.
+///
+/// When the `[schema]` section of `awto.toml` lists schema packages, the models of each of them are read instead,
+/// and merged as [`merge_schema_models`] does. The models of a manifest are read from the manifest itself,
+/// as rendered into the lib of its generated package.
+pub async fn load_schema_models() -> Result<Vec<SchemaModel>> {
+    let packages = schema_packages().await?;
+    if !packages.is_empty() {
+        let config = Config::load(CONFIG_PATH).await?.schema;
+        let mut models = Vec::new();
+        for package in &packages {
+            let package_models = if package.name == MANIFEST_PACKAGE {
+                read_manifest_models(&config.manifest_path(), &package.lib_path()).await
+            } else if is_expansion_enabled() {
+                expand::expand_schema_models(&package.name).await
+            } else {
+                let lib_path = package.lib_path();
+                read_schema_models(&lib_path).await.map_err(|source| {
+                    Error::SchemaSource {
+                        path: lib_path,
+                        source,
+                    }
+                    .into()
+                })
+            }?;
+            models.push((package.name.as_str(), package_models));
+        }
+
+        return merge_schema_models(models, config.duplicates);
+    }
+
+    let cargo_file = CargoFile::load("./schema/Cargo.toml")
+        .await
+        .map_err(|source| Error::SchemaManifest {
+            path: "./schema/Cargo.toml".to_string(),
+            source,
+        })?;
+    if let Some(package) = cargo_file.package {
+        if package.name != "schema" {
+            return Err(Error::SchemaPackageName(package.name).into());
+        }
+    }
+
+    if is_expansion_enabled() {
+        return expand::expand_schema_models("schema").await;
+    }
+    let models = read_schema_models(SCHEMA_LIB_PATH)
+        .await
+        .map_err(|source| Error::SchemaSource {
+            path: SCHEMA_LIB_PATH.to_string(),
+            source,
+        })?;
+
+    Ok(models)
+}
+
+/// Returns the paths of the files [`load_schema_models`] reads the models from: the libs of the schema packages,
+/// or the schema lib, and the manifest with `source = "manifest"`.
+pub async fn schema_sources() -> Result<Vec<String>> {
+    let config = Config::load(CONFIG_PATH).await?.schema;
+    let mut sources = Vec::new();
+    for package in schema_packages().await? {
+        match package.name.as_str() {
+            MANIFEST_PACKAGE => sources.push(config.manifest_path()),
+            _ => sources.push(package.lib_path()),
+        }
+    }
+    if sources.is_empty() {
+        sources.push(SCHEMA_LIB_PATH.to_string());
+    }
+
+    Ok(sources)
+}
+
+/// Returns the sha256 hash of the [`schema_sources`].
+///
+/// Generated packages record it when they are written, telling `awto status` whether the schema changed since.
+pub async fn schema_fingerprint() -> Result<String> {
+    let mut hasher = Sha256::new();
+    for path in schema_sources().await? {
+        let source = fs::read(&path)
+            .await
+            .with_context(|| format!("could not read file '{}'", path))?;
+        hasher.update(path.as_bytes());
+        hasher.update([0]);
+        hasher.update(source);
+        hasher.update([0]);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Reads the models declared in `schema!` macros of the schema lib at `path`.
+pub async fn read_schema_models(path: impl AsRef<Path>) -> Result<Vec<SchemaModel>> {
+    let path = path.as_ref();
+    profile_phase("read schema", async {
+        let source = fs::read_to_string(path)
+            .await
+            .with_context(|| format!("could not read file '{}'", path.display()))?;
+
+        let mut models = parse_schema_models(&source)
+            .map_err(|err| match err.downcast::<Diagnostics>() {
+                Ok(diagnostics) => Diagnostics {
+                    path: path.display().to_string(),
+                    ..diagnostics
+                }
+                .into(),
+                Err(err) => err,
+            })
+            .with_context(|| format!("could not parse file '{}'", path.display()))?;
+        if path != Path::new(SCHEMA_LIB_PATH) {
+            for model in &mut models {
+                model.lib_path = Some(path.display().to_string());
+            }
+        }
+
+        Ok(models)
+    })
+    .await
+}
+
+/// Reads the models of the manifest at `path`, as declared by the lib rendered from it at `lib_path`,
+/// so their locations point into that lib.
+async fn read_manifest_models(path: &str, lib_path: &str) -> Result<Vec<SchemaModel>> {
+    let manifest = read_manifest(path)
+        .await
+        .map_err(|source| Error::SchemaSource {
+            path: path.to_string(),
+            source,
+        })?;
+    let mut models = parse_schema_models(&format!("{}{}", GENERATED_HEADER, manifest.render(path)))
+        .with_context(|| format!("could not parse the models rendered from '{}'", path))?;
+    for model in &mut models {
+        model.lib_path = Some(lib_path.to_string());
+    }
+
+    Ok(models)
+}
+
+/// Merges the models of each schema package of `packages`, recording the package of every model.
+///
+/// Models declared in several packages fail with [`Error::DuplicateSchemaModel`], or are prefixed with the namespace
+/// of their package with [`Duplicates::Namespace`], along with the references to them from their own package.
+pub fn merge_schema_models(
+    packages: Vec<(&str, Vec<SchemaModel>)>,
+    duplicates: Duplicates,
+) -> Result<Vec<SchemaModel>> {
+    let mut declared: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    for (package, models) in &packages {
+        for model in models {
+            declared
+                .entry(model.name.clone())
+                .or_default()
+                .push(package);
+        }
+    }
+    let duplicated: BTreeSet<&String> = declared
+        .iter()
+        .filter(|(_, packages)| packages.len() > 1)
+        .map(|(name, _)| name)
+        .collect();
+    if let (Duplicates::Error, Some(name)) = (duplicates, duplicated.iter().next()) {
+        return Err(Error::DuplicateSchemaModel {
+            name: name.to_string(),
+            first: declared[*name][0].to_string(),
+            second: declared[*name][1].to_string(),
+        }
+        .into());
+    }
+
+    let mut merged = Vec::new();
+    for (package, models) in &packages {
+        let namespace = awto_compile::schemas::namespace(package);
+        for model in models {
+            let mut model = model.clone();
+            model.package = Some(package.to_string());
+            if duplicated.contains(&model.name) {
+                model.name = format!("{}{}", namespace, model.name);
+            }
+            for field in &mut model.fields {
+                if let Some(references) = field
+                    .references
+                    .as_mut()
+                    .filter(|references| duplicated.contains(*references))
+                {
+                    *references = format!("{}{}", namespace, references);
+                }
+            }
+            merged.push(model);
+        }
+    }
+
+    Ok(merged)
+}
+
+/// Parses the models declared in `schema!` macros of the schema lib `source`.
+///
+/// The whole schema is validated before failing, so the error holds the [`Diagnostics`] of every problem found.
+///
+/// Items the parser does not support, such as functions using newer syntax, are skipped with a warning,
+/// unless they invoke `schema!` or declare a type the models use.
+pub fn parse_schema_models(source: &str) -> Result<Vec<SchemaModel>> {
+    let mut diagnostics = Vec::new();
+    let models = match parse::parse_file_resilient(source) {
+        Ok((file, skipped)) => {
+            let macros = SchemaMacros::resolve(&file.items);
+            let mut models = Vec::new();
+            let mut declared = DeclaredStructs::default();
+            collect_schema_models(
+                &file.items,
+                &macros,
+                &mut models,
+                &mut declared,
+                &mut diagnostics,
+            );
+            check_skipped_items(skipped, &macros, &models, &mut diagnostics);
+            check_duplicate_models(&models, &mut diagnostics);
+            check_registered_names(&file.items, &macros, &declared, &mut diagnostics);
+
+            if models.is_empty() && diagnostics.is_empty() {
+                if let Some(similar) = similar_macro(&file.items, &macros) {
+                    diagnostics.push(
+                        Diagnostic::new(
+                            format!("no `schema!` macro found, found `{}!(...)`", similar),
+                            Span::of(&similar),
+                        )
+                        .help("did you mean `awto::schema!`?"),
+                    );
+                } else if !has_schema_macro(&file.items, &macros) {
+                    // An empty `schema! {}` declares no models on purpose, while a missing one has no `MODELS` to compile
+                    let start = Location { line: 1, column: 1 };
+                    diagnostics.push(
+                        Diagnostic::new("no `schema!` macro found", Span { start, end: start })
+                            .help("declare the models in `awto::schema! { ... }`, which may be empty until the first model exists"),
+                    );
+                }
+            }
+
+            models
+        }
+        Err(err) => {
+            diagnostics.extend(Diagnostic::from_syn(err));
+            Vec::new()
+        }
+    };
+
+    if !diagnostics.is_empty() {
+        diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+        return Err(Diagnostics {
+            path: SCHEMA_LIB_PATH.to_string(),
+            source: source.to_string(),
+            diagnostics,
+        }
+        .into());
+    }
+
+    Ok(models)
+}
+
+/// Returns the structs declared in `schema!` macros of the schema lib `source` as they are written,
+/// along with the `use` items at the root of the lib.
+pub fn parse_schema_structs(source: &str) -> Result<(Vec<syn::ItemUse>, Vec<syn::ItemStruct>)> {
+    let (file, _) = parse::parse_file_resilient(source).context("invalid schema lib")?;
+    let macros = SchemaMacros::resolve(&file.items);
+    let mut declared = DeclaredStructs::default();
+    collect_schema_models(
+        &file.items,
+        &macros,
+        &mut Vec::new(),
+        &mut declared,
+        &mut Vec::new(),
+    );
+    if declared.incomplete {
+        return Err(anyhow!(
+            "the structs of a macro wrapping `schema!` cannot be read"
+        ));
+    }
+
+    let uses = file
+        .items
+        .into_iter()
+        .filter_map(|item| match item {
+            syn::Item::Use(item_use) => Some(item_use),
+            _ => None,
+        })
+        .collect();
+
+    Ok((uses, declared.structs))
+}
+
+/// Fails on the skipped items of the schema lib invoking a schema macro or declaring a type used by `models`,
+/// and warns about the others, which the models do not depend on.
+fn check_skipped_items(
+    skipped: Vec<parse::SkippedItem>,
+    macros: &SchemaMacros,
+    models: &[SchemaModel],
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    for item in skipped {
+        if item
+            .macro_name
+            .as_deref()
+            .is_some_and(|name| macros.contains(name))
+        {
+            diagnostics.extend(Diagnostic::from_syn(item.error));
+            continue;
+        }
+
+        let user = item.name.as_deref().and_then(|name| {
+            models.iter().find(|model| {
+                model.fields.iter().any(|field| {
+                    field.references.as_deref() == Some(name)
+                        || field
+                            .ty
+                            .split(|c: char| !c.is_alphanumeric() && c != '_')
+                            .any(|ident| ident == name)
+                })
+            })
+        });
+        match (&item.name, user) {
+            (Some(name), Some(model)) => {
+                for mut diagnostic in Diagnostic::from_syn(item.error) {
+                    diagnostic.message = format!(
+                        "could not parse `{}`, which model `{}` uses: {}",
+                        name, model.name, diagnostic.message
+                    );
+                    diagnostics.push(
+                        diagnostic.help("models can only use items the parser supports, move newer syntax out of them"),
+                    );
+                }
+            }
+            _ => {
+                let start = item.error.span().start();
+                warn!(
+                    "skipped {} at {}:{}:{}, which could not be parsed: {}",
+                    item.name
+                        .map_or_else(|| "an item".to_string(), |name| format!("`{}`", name)),
+                    SCHEMA_LIB_PATH,
+                    start.line,
+                    start.column + 1,
+                    item.error
+                );
+            }
+        }
+    }
+}
+
+/// Names invoking the `schema!` macro in a schema lib.
+struct SchemaMacros {
+    /// `schema` and the names it is imported as, such as `models` for `use awto::schema as models;`.
+    aliases: BTreeSet<String>,
+    /// Local `macro_rules!` macros expanding to an invocation of `schema!`, which are assumed to pass their input on.
+    wrappers: BTreeSet<String>,
+}
+
+impl SchemaMacros {
+    fn resolve(items: &[syn::Item]) -> Self {
+        let mut aliases = BTreeSet::new();
+        aliases.insert("schema".to_string());
+        for_each_item(items, &mut |item| {
+            if let syn::Item::Use(item_use) = item {
+                collect_use_aliases(&item_use.tree, &mut aliases);
+            }
+        });
+
+        let mut macros = SchemaMacros {
+            aliases,
+            wrappers: BTreeSet::new(),
+        };
+
+        // Wrappers can themselves be wrapped, so wrappers are collected until none are added
+        loop {
+            let mut wrappers = Vec::new();
+            for_each_item(items, &mut |item| {
+                if let syn::Item::Macro(item_macro) = item {
+                    match &item_macro.ident {
+                        Some(ident)
+                            if item_macro.mac.path.is_ident("macro_rules")
+                                && !macros.contains(&ident.to_string())
+                                && macros.is_invoked_in(item_macro.mac.tokens.clone()) =>
+                        {
+                            wrappers.push(ident.to_string())
+                        }
+                        _ => {}
+                    }
+                }
+            });
+
+            if wrappers.is_empty() {
+                return macros;
+            }
+            macros.wrappers.extend(wrappers);
+        }
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.aliases.contains(name) || self.wrappers.contains(name)
+    }
+
+    /// Returns the name of a schema macro invoked by `path`, accepting any leading segments such as `::awto::schema`.
+    fn name_of<'a>(&self, path: &'a syn::Path) -> Option<&'a syn::Ident> {
+        path.segments
+            .last()
+            .map(|segment| &segment.ident)
+            .filter(|ident| self.contains(&ident.to_string()))
+    }
+
+    /// Returns whether `tokens` contain an invocation of a schema macro, such as `awto::schema! { ... }`.
+    fn is_invoked_in(&self, tokens: proc_macro2::TokenStream) -> bool {
+        let mut previous_ident: Option<String> = None;
+        for token in tokens {
+            match token {
+                proc_macro2::TokenTree::Punct(punct) if punct.as_char() == '!' => {
+                    if let Some(ident) = previous_ident.take() {
+                        if self.contains(&ident) {
+                            return true;
+                        }
+                    }
+                }
+                proc_macro2::TokenTree::Ident(ident) => previous_ident = Some(ident.to_string()),
+                proc_macro2::TokenTree::Group(group) => {
+                    if self.is_invoked_in(group.stream()) {
+                        return true;
+                    }
+                    previous_ident = None;
+                }
+                _ => previous_ident = None,
+            }
+        }
+
+        false
+    }
+}
+
+/// Calls `f` for every item of `items`, including items of inline modules.
+fn for_each_item(items: &[syn::Item], f: &mut impl FnMut(&syn::Item)) {
+    for item in items {
+        f(item);
+        if let syn::Item::Mod(syn::ItemMod {
+            content: Some((_, mod_items)),
+            ..
+        }) = item
+        {
+            for_each_item(mod_items, f);
+        }
+    }
+}
+
+fn collect_use_aliases(tree: &syn::UseTree, aliases: &mut BTreeSet<String>) {
+    match tree {
+        syn::UseTree::Path(path) => collect_use_aliases(&path.tree, aliases),
+        syn::UseTree::Rename(rename) if rename.ident == "schema" => {
+            aliases.insert(rename.rename.to_string());
+        }
+        syn::UseTree::Group(group) => {
+            for tree in &group.items {
+                collect_use_aliases(tree, aliases);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Structs declared in the schema macros of a schema lib.
+#[derive(Default)]
+struct DeclaredStructs {
+    structs: Vec<syn::ItemStruct>,
+    /// Whether the input of a wrapper macro could not be read, so some declared structs may be missing.
+    incomplete: bool,
+}
+
+fn collect_schema_models(
+    items: &[syn::Item],
+    macros: &SchemaMacros,
+    models: &mut Vec<SchemaModel>,
+    declared: &mut DeclaredStructs,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    for item in items {
+        match item {
+            syn::Item::Macro(item_macro) if item_macro.ident.is_none() => {
+                let name = match macros.name_of(&item_macro.mac.path) {
+                    Some(name) => name.to_string(),
+                    None => continue,
+                };
+
+                match syn::parse2::<Structs>(item_macro.mac.tokens.clone()) {
+                    Ok(structs) => {
+                        models.extend(schema_models(&structs.0, diagnostics));
+                        declared.structs.extend(structs.0);
+                    }
+                    // Wrappers may transform their input, so only invocations of `schema!` itself must parse
+                    Err(_) if macros.wrappers.contains(&name) => declared.incomplete = true,
+                    Err(err) => diagnostics.extend(Diagnostic::from_syn(err)),
+                }
+            }
+            syn::Item::Mod(syn::ItemMod {
+                content: Some((_, mod_items)),
+                ..
+            }) => collect_schema_models(mod_items, macros, models, declared, diagnostics),
+            _ => {}
+        }
+    }
+}
+
+/// Reports models declared more than once, which would generate conflicting items.
+fn check_duplicate_models(models: &[SchemaModel], diagnostics: &mut Vec<Diagnostic>) {
+    for (i, model) in models.iter().enumerate() {
+        if let Some(first) = models[..i].iter().find(|first| first.name == model.name) {
+            diagnostics.push(
+                Diagnostic::new(
+                    format!("model `{}` is declared more than once", model.name),
+                    Span::of_name(model.location, &model.name),
+                )
+                .label("declared again here")
+                .help(format!(
+                    "`{}` is first declared at {}, rename or remove one of them",
+                    first.name, first.location
+                )),
+            );
+        }
+    }
+}
+
+/// Role attributes of the structs of `schema!` macros.
+pub(crate) const ROLE_ATTRIBUTES: [&str; 3] =
+    ["database_table", "database_sub_table", "protobuf_message"];
+
+/// Reports names of models given to `#[database_sub_table(...)]` and `#[awto(references = (...))]` which are not
+/// declared, such as `Prodcut` for a `Product` model, and structs with a role attribute outside of `schema!` macros,
+/// which would otherwise fail much later while building the generated packages.
+///
+/// Names declared in any schema macro, declared as structs or imported with `use` are accepted, as they may be re-exports.
+/// Items behind `#[cfg(...)]` are skipped, and nothing is reported when a wrapper macro or a glob import hides the
+/// declared names.
+fn check_registered_names(
+    items: &[syn::Item],
+    macros: &SchemaMacros,
+    declared: &DeclaredStructs,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let mut known: BTreeSet<String> = declared
+        .structs
+        .iter()
+        .map(|item| item.ident.unraw().to_string())
+        .collect();
+    // Glob imports and other macros may declare names which cannot be read from the source
+    let mut hidden = false;
+    let mut outside = Vec::new();
+    for_each_enabled_item(items, &mut |item| match item {
+        syn::Item::Use(item_use) => collect_use_names(&item_use.tree, &mut known, &mut hidden),
+        syn::Item::Struct(item) => {
+            known.insert(item.ident.unraw().to_string());
+            if let Some(role) = item
+                .attrs
+                .iter()
+                .find_map(|attr| ROLE_ATTRIBUTES.iter().find(|role| attr.path.is_ident(role)))
+            {
+                outside.push((item.ident.clone(), role));
+            }
+        }
+        syn::Item::Macro(item_macro) if item_macro.ident.is_none() => {
+            hidden |= macros.name_of(&item_macro.mac.path).is_none();
+        }
+        _ => {}
+    });
+
+    for (ident, role) in outside {
+        diagnostics.push(
+            Diagnostic::new(
+                format!(
+                    "struct `{}` is marked `#[{}]` but is not declared in a `schema!` macro",
+                    ident, role
+                ),
+                Span::of(&ident),
+            )
+            .label("not part of the schema")
+            .help(format!(
+                "move `{}` into `awto::schema! {{ ... }}` to generate code for it",
+                ident
+            )),
+        );
+    }
+    if declared.incomplete || hidden {
+        return;
+    }
+
+    for item in declared
+        .structs
+        .iter()
+        .filter(|item| !is_cfg_disabled(&item.attrs))
+    {
+        let mut names = Vec::new();
+        for attr in item
+            .attrs
+            .iter()
+            .filter(|attr| attr.path.is_ident("database_sub_table"))
+        {
+            if let Ok(parent) = attr.parse_args::<syn::Ident>() {
+                names.push((parent, "#[database_sub_table(...)]"));
+            }
+        }
+        for field in item
+            .fields
+            .iter()
+            .filter(|field| !is_cfg_disabled(&field.attrs))
+        {
+            for arg in awto_args(&field.attrs) {
+                match arg {
+                    AwtoArg::Assign(name, value) if name == "references" => {
+                        if let syn::Expr::Tuple(tuple) = &*value {
+                            if let Some(syn::Expr::Path(model)) = tuple.elems.first() {
+                                if let Some(model) = model.path.get_ident() {
+                                    names.push((model.clone(), "#[awto(references = (...))]"));
+                                }
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        for (name, attribute) in names {
+            let name_string = name.unraw().to_string();
+            if known.contains(&name_string) {
+                continue;
+            }
+
+            let similar = declared
+                .structs
+                .iter()
+                .map(|declared| declared.ident.unraw().to_string())
+                .filter(|declared| {
+                    edit_distance(&name_string.to_lowercase(), &declared.to_lowercase()) <= 2
+                })
+                .min_by_key(|declared| edit_distance(&name_string, declared));
+            let help = match similar {
+                Some(similar) => format!("did you mean `{}`?", similar),
+                None => format!(
+                    "declare `{}` in a `schema!` macro, or import it with `use`",
+                    name_string
+                ),
+            };
+            diagnostics.push(
+                Diagnostic::new(
+                    format!(
+                        "`{}` given to `{}` of `{}` is not declared",
+                        name_string,
+                        attribute,
+                        item.ident.unraw()
+                    ),
+                    Span::of(&name),
+                )
+                .label("not declared in a `schema!` macro")
+                .help(help),
+            );
+        }
+    }
+}
+
+/// Returns whether `attrs` contain a `#[cfg(...)]` attribute, which may leave the item out of the build.
+fn is_cfg_disabled(attrs: &[syn::Attribute]) -> bool {
+    attrs.iter().any(|attr| attr.path.is_ident("cfg"))
+}
+
+/// Calls `f` for every item of `items` and their inline modules, leaving out the items behind `#[cfg(...)]`.
+fn for_each_enabled_item(items: &[syn::Item], f: &mut impl FnMut(&syn::Item)) {
+    for item in items {
+        let attrs = match item {
+            syn::Item::Struct(item) => &item.attrs,
+            syn::Item::Use(item) => &item.attrs,
+            syn::Item::Macro(item) => &item.attrs,
+            syn::Item::Mod(item) => &item.attrs,
+            _ => continue,
+        };
+        if is_cfg_disabled(attrs) {
+            continue;
+        }
+
+        f(item);
+        if let syn::Item::Mod(syn::ItemMod {
+            content: Some((_, mod_items)),
+            ..
+        }) = item
+        {
+            for_each_enabled_item(mod_items, f);
+        }
+    }
+}
+
+/// Adds the names imported by `tree` to `names`, setting `has_glob` for glob imports other than those of `awto`.
+fn collect_use_names(tree: &syn::UseTree, names: &mut BTreeSet<String>, has_glob: &mut bool) {
+    match tree {
+        syn::UseTree::Path(path) if path.ident == "awto" => {}
+        syn::UseTree::Path(path) => collect_use_names(&path.tree, names, has_glob),
+        syn::UseTree::Name(name) => {
+            names.insert(name.ident.unraw().to_string());
+        }
+        syn::UseTree::Rename(rename) => {
+            names.insert(rename.rename.unraw().to_string());
+        }
+        syn::UseTree::Glob(_) => *has_glob = true,
+        syn::UseTree::Group(group) => {
+            for tree in &group.items {
+                collect_use_names(tree, names, has_glob);
+            }
+        }
+    }
+}
+
+/// Returns whether a schema macro is invoked in `items`, even without any model.
+fn has_schema_macro(items: &[syn::Item], macros: &SchemaMacros) -> bool {
+    let mut found = false;
+    for_each_item(items, &mut |item| match item {
+        syn::Item::Macro(item_macro) if item_macro.ident.is_none() => {
+            found |= macros.name_of(&item_macro.mac.path).is_some();
+        }
+        _ => {}
+    });
+
+    found
+}
+
+/// Returns the path of a macro invoked in `items` with a name similar to `schema`, such as `schemas`.
+fn similar_macro(items: &[syn::Item], macros: &SchemaMacros) -> Option<SimilarMacro> {
+    let mut similar = None;
+    for_each_item(items, &mut |item| match item {
+        syn::Item::Macro(item_macro) if similar.is_none() && item_macro.ident.is_none() => {
+            let path = &item_macro.mac.path;
+            let name = match path.segments.last() {
+                Some(segment) => segment.ident.to_string(),
+                None => return,
+            };
+            if !macros.contains(&name)
+                && (name.to_lowercase().contains("schema") || edit_distance(&name, "schema") <= 2)
+            {
+                similar = Some(SimilarMacro(path.clone()));
+            }
+        }
+        _ => {}
+    });
+
+    similar
+}
+
+/// Path of a macro with a name similar to `schema`, printed without whitespace such as `awto::schemas`.
+struct SimilarMacro(syn::Path);
+
+impl fmt::Display for SimilarMacro {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.0.to_token_stream().to_string().replace(' ', "")
+        )
+    }
+}
+
+impl ToTokens for SimilarMacro {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        self.0.to_tokens(tokens)
+    }
+}
+
+/// Returns the number of single character edits turning `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut distances: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut previous_diagonal = distances[0];
+        distances[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous_diagonal + usize::from(a_char != *b_char);
+            previous_diagonal = distances[j + 1];
+            distances[j + 1] = substitution.min(distances[j] + 1).min(distances[j + 1] + 1);
+        }
+    }
+
+    distances[b.len()]
+}
+
+/// Arguments of `#[awto(...)]` on models, mixins and newtypes.
+const MODEL_ARGS: [&str; 26] = [
+    "allow",
+    "authorize",
+    "database",
+    "entity_attrs",
+    "events",
+    "external",
+    "feature",
+    "fulltext",
+    "graphql",
+    "idempotent_create",
+    "include",
+    "mixin",
+    "newtype",
+    "notify",
+    "order_by",
+    "partition",
+    "projection",
+    "proto",
+    "readonly",
+    "rls",
+    "scope",
+    "skip_rest",
+    "temporal",
+    "upsert_on",
+    "versioned",
+    "visibility",
+];
+
+/// Arguments of `#[awto(...)]` on fields.
+const FIELD_ARGS: [&str; 21] = [
+    "allow",
+    "column",
+    "computed",
+    "db_type",
+    "default",
+    "default_raw",
+    "deprecated",
+    "fake",
+    "feature",
+    "localized",
+    "max_len",
+    "normalize",
+    "normalize_fn",
+    "optional_insert",
+    "primary_key",
+    "proto_type",
+    "references",
+    "sensitive",
+    "sensitivity",
+    "stored",
+    "unique",
+];
+
+/// Types of fields which database tables store without a `db_type`, as written in the source.
+const DATABASE_TYPES: [&str; 20] = [
+    "i16",
+    "i32",
+    "i64",
+    "f32",
+    "f64",
+    "String",
+    "Vec<u8>",
+    "chrono::NaiveDateTime",
+    "NaiveDateTime",
+    "chrono::DateTime<chrono::FixedOffset>",
+    "chrono::DateTime<FixedOffset>",
+    "DateTime<chrono::FixedOffset>",
+    "DateTime<FixedOffset>",
+    "chrono::NaiveDate",
+    "NaiveDate",
+    "chrono::NaiveTime",
+    "NaiveTime",
+    "bool",
+    "uuid::Uuid",
+    "Uuid",
+];
+
+/// Returns the models of the structs of a `schema!` macro, with the fields of the mixins they include appended,
+/// like the macro does.
+///
+/// Newtypes are not models, so they are left out. Problems found in the structs are added to `diagnostics`.
+fn schema_models(
+    structs: &[syn::ItemStruct],
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Vec<SchemaModel> {
+    let has_flag = |item: &syn::ItemStruct, flag: &str| {
+        awto_args(&item.attrs)
+            .iter()
+            .any(|arg| matches!(arg, AwtoArg::Flag(name) if name == flag))
+    };
+    for item in structs {
+        check_awto_args(&item.attrs, &MODEL_ARGS, "models", diagnostics);
+        check_entity_attrs(item, diagnostics);
+        for field in &item.fields {
+            check_awto_args(&field.attrs, &FIELD_ARGS, "fields", diagnostics);
+        }
+    }
+
+    let (newtypes, structs): (Vec<_>, Vec<_>) =
+        structs.iter().partition(|item| has_flag(item, "newtype"));
+    let (mixins, items): (Vec<_>, Vec<_>) = structs
+        .into_iter()
+        .partition(|item| has_flag(item, "mixin"));
+
+    items
+        .into_iter()
+        .map(|item| {
+            let mut model = schema_model(item);
+            model.fields =
+                expand_mixin_fields(item, model.fields, &mixins, &mut Vec::new(), diagnostics);
+            if model.is_database_table {
+                check_database_types(&model, &newtypes, diagnostics);
+            }
+            model
+        })
+        .collect()
+}
+
+/// Appends the fields of the mixins included by `item` to its `fields`.
+///
+/// `stack` holds the mixins being expanded, so mixins including each other are reported instead of recursing forever.
+fn expand_mixin_fields(
+    item: &syn::ItemStruct,
+    mut fields: Vec<SchemaField>,
+    mixins: &[&syn::ItemStruct],
+    stack: &mut Vec<String>,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Vec<SchemaField> {
+    let includes = awto_args(&item.attrs)
+        .into_iter()
+        .filter_map(|arg| match arg {
+            AwtoArg::Assign(name, value) if name == "include" => match *value {
+                syn::Expr::Path(path) => path.path.get_ident().cloned(),
+                _ => None,
+            },
+            _ => None,
+        });
+
+    for include in includes {
+        let name = include.to_string();
+        let mixin = match mixins.iter().find(|mixin| mixin.ident == include) {
+            Some(mixin) => mixin,
+            None => {
+                diagnostics.push(
+                    Diagnostic::new(
+                        format!("mixin `{}` included by `{}` not found", name, item.ident),
+                        Span::of(&include),
+                    )
+                    .label("not found in this `schema!` macro")
+                    .help(format!(
+                        "declare `{}` with `#[awto(mixin)]` in the same `schema!` macro",
+                        name
+                    )),
+                );
+                continue;
+            }
+        };
+        if stack.contains(&name) {
+            stack.push(name);
+            diagnostics.push(
+                Diagnostic::new(
+                    format!("mixins include each other: {}", stack.join(" -> ")),
+                    Span::of(&include),
+                )
+                .label("included again here"),
+            );
+            stack.pop();
+            continue;
+        }
+
+        let mixin_fields = mixin
+            .fields
+            .iter()
+            .filter_map(schema_field)
+            .map(|field| SchemaField {
+                mixin: Some(name.clone()),
+                ..field
+            })
+            .collect();
+        stack.push(name.clone());
+        let mixin_fields = expand_mixin_fields(mixin, mixin_fields, mixins, stack, diagnostics);
+        stack.pop();
+
+        for field in mixin_fields {
+            if let Some(existing) = fields.iter().find(|existing| existing.name == field.name) {
+                diagnostics.push(
+                    Diagnostic::new(
+                        format!(
+                            "field `{}` of `{}` is also declared in mixin `{}`",
+                            field.name,
+                            item.ident,
+                            field.mixin.as_deref().unwrap_or(&name)
+                        ),
+                        Span::of_name(existing.location, &existing.name),
+                    )
+                    .label(format!("also declared at {}", field.location))
+                    .help("rename the field, or remove it from the model or the mixin"),
+                );
+                continue;
+            }
+            fields.push(field);
+        }
+    }
+
+    fields
+}
+
+/// Reports the arguments of `#[awto(...)]` attributes which are not one of `known`, the arguments accepted on `target`.
+fn check_awto_args(
+    attrs: &[syn::Attribute],
+    known: &[&str],
+    target: &str,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("awto")) {
+        let args = match attr.parse_args_with(
+            syn::punctuated::Punctuated::<syn::Expr, syn::Token![,]>::parse_terminated,
+        ) {
+            Ok(args) => args,
+            Err(err) => {
+                diagnostics.extend(Diagnostic::from_syn(err));
+                continue;
+            }
+        };
+
+        for arg in &args {
+            let path = match arg {
+                syn::Expr::Path(path) => Some(&path.path),
+                syn::Expr::Assign(syn::ExprAssign { left, .. }) => match &**left {
+                    syn::Expr::Path(path) => Some(&path.path),
+                    _ => None,
+                },
+                syn::Expr::Call(call) => match &*call.func {
+                    syn::Expr::Path(path) => Some(&path.path),
+                    _ => None,
+                },
+                _ => None,
+            };
+            let name = match path.and_then(syn::Path::get_ident) {
+                Some(name) => name,
+                None => {
+                    diagnostics.push(
+                        Diagnostic::new("invalid `#[awto(...)]` argument", Span::of(arg))
+                            .help("expected `name`, `name = value` or `name(...)`"),
+                    );
+                    continue;
+                }
+            };
+            let name_string = name.to_string();
+            if known.contains(&name_string.as_str()) {
+                continue;
+            }
+
+            let similar = known
+                .iter()
+                .filter(|known| edit_distance(&name_string, known) <= 2)
+                .min_by_key(|known| edit_distance(&name_string, known));
+            let help = match similar {
+                Some(similar) => format!("did you mean `{}`?", similar),
+                None => format!(
+                    "expected one of {}",
+                    known
+                        .iter()
+                        .map(|known| format!("`{}`", known))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            };
+            diagnostics.push(
+                Diagnostic::new(
+                    format!(
+                        "unknown argument `{}` in `#[awto(...)]` of {}",
+                        name_string, target
+                    ),
+                    Span::of(name),
+                )
+                .label("unknown argument")
+                .help(help),
+            );
+        }
+    }
+}
+
+/// Reports the fields of the database table `model` with a type it cannot store, unless a `db_type` is set.
+///
+/// Fields of a newtype are stored like the inner type of one of `newtypes`.
+fn check_database_types(
+    model: &SchemaModel,
+    newtypes: &[&syn::ItemStruct],
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    for field in model.fields.iter().filter(|field| field.db_type.is_none()) {
+        let ty = field
+            .ty
+            .strip_prefix("Option<")
+            .and_then(|ty| ty.strip_suffix('>'))
+            .unwrap_or(&field.ty);
+        let newtype_inner = newtypes
+            .iter()
+            .find(|newtype| newtype.ident == ty)
+            .and_then(|newtype| newtype.fields.iter().next())
+            .map(|inner| inner.ty.to_token_stream().to_string().replace(' ', ""));
+        let ty = newtype_inner.as_deref().unwrap_or(ty);
+        if DATABASE_TYPES.contains(&ty) {
+            continue;
+        }
+
+        diagnostics.push(
+            Diagnostic::new(
+                format!(
+                    "type `{}` of `{}.{}` is not supported by database tables",
+                    ty, model.name, field.name
+                ),
+                field.ty_span,
+            )
+            .label("unsupported type")
+            .help(
+                "set the type of the column with `#[awto(db_type = \"...\")]`,\n\
+                 or use a supported type such as `String`, `i64`, `bool`, `Uuid` or `DateTime<FixedOffset>`",
+            ),
+        );
+    }
+}
+
+/// Reports the arguments of `#[awto(entity_attrs(...))]` of `item` which are not string literals of outer attributes,
+/// as they would be added to the generated entity verbatim.
+fn check_entity_attrs(item: &syn::ItemStruct, diagnostics: &mut Vec<Diagnostic>) {
+    let args = awto_args(&item.attrs)
+        .into_iter()
+        .filter_map(|arg| match arg {
+            AwtoArg::Call(name, args) if name == "entity_attrs" => Some(args),
+            _ => None,
+        });
+
+    for arg in args.flatten() {
+        let (message, label) = match &arg {
+            syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(lit),
+                ..
+            }) => match syn::parse::Parser::parse_str(syn::Attribute::parse_outer, &lit.value()) {
+                Ok(attrs) if !attrs.is_empty() => continue,
+                Ok(_) => (
+                    format!(
+                        "invalid entity attribute `{}` of `{}`",
+                        lit.value(),
+                        item.ident
+                    ),
+                    "no attribute".to_string(),
+                ),
+                Err(err) => (
+                    format!(
+                        "invalid entity attribute `{}` of `{}`",
+                        lit.value(),
+                        item.ident
+                    ),
+                    err.to_string(),
+                ),
+            },
+            _ => (
+                format!("invalid entity attribute of `{}`", item.ident),
+                "not a string".to_string(),
+            ),
+        };
+        diagnostics.push(
+            Diagnostic::new(message, Span::of(&arg))
+                .label(label)
+                .help("pass attributes as strings, such as `entity_attrs(\"#[serde(rename_all = \\\"camelCase\\\")]\")`"),
+        );
+    }
+}
+
+fn schema_model(item: &syn::ItemStruct) -> SchemaModel {
+    let mut database = None;
+    let mut feature = None;
+    let mut allow = Vec::new();
+    let mut partitioned = false;
+    let mut readonly = false;
+    let mut external = false;
+    let mut skip_rest = false;
+    let mut entity_visibility = None;
+    let mut active_model_visibility = None;
+    let is_database_table = item
+        .attrs
+        .iter()
+        .any(|attr| attr.path.is_ident("database_table"));
+
+    for arg in awto_args(&item.attrs) {
+        match arg {
+            AwtoArg::Assign(name, value) if name == "database" => database = lit_str(&value),
+            AwtoArg::Assign(name, value) if name == "feature" => feature = lit_str(&value),
+            AwtoArg::Allow(lints) => allow.extend(lints),
+            AwtoArg::Call(name, _) if name == "partition" => partitioned = true,
+            AwtoArg::Flag(name) if name == "readonly" => readonly = true,
+            AwtoArg::Flag(name) if name == "external" => {
+                readonly = true;
+                external = true;
+            }
+            AwtoArg::Flag(name) if name == "skip_rest" => skip_rest = true,
+            AwtoArg::Call(name, args) if name == "visibility" => {
+                for arg in &args {
+                    if let syn::Expr::Assign(syn::ExprAssign { left, right, .. }) = arg {
+                        let visibility = lit_str(right).and_then(|value| Visibility::parse(&value));
+                        match &**left {
+                            syn::Expr::Path(name) if name.path.is_ident("entity") => {
+                                entity_visibility = visibility
+                            }
+                            syn::Expr::Path(name) if name.path.is_ident("active_model") => {
+                                active_model_visibility = visibility
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    SchemaModel {
+        name: item.ident.unraw().to_string(),
+        database,
+        feature,
+        is_database_table,
+        partitioned,
+        readonly,
+        external,
+        skip_rest,
+        entity_visibility,
+        active_model_visibility,
+        fields: item.fields.iter().filter_map(schema_field).collect(),
+        allow,
+        attributes: attributes(&item.attrs),
+        package: None,
+        lib_path: None,
+        location: Location::of(&item.ident),
+    }
+}
+
+fn schema_field(field: &syn::Field) -> Option<SchemaField> {
+    let ident = field.ident.as_ref()?;
+    let mut schema_field = SchemaField {
+        name: ident.unraw().to_string(),
+        ty: field.ty.to_token_stream().to_string().replace(' ', ""),
+        attributes: attributes(&field.attrs),
+        location: Location::of(ident),
+        ty_span: Span::of(&field.ty),
+        sensitive: serde_skipped(&field.attrs),
+        ..Default::default()
+    };
+
+    for arg in awto_args(&field.attrs) {
+        match arg {
+            AwtoArg::Flag(name) if name == "unique" => schema_field.unique = true,
+            AwtoArg::Flag(name) if name == "sensitive" => schema_field.sensitive = true,
+            AwtoArg::Assign(name, value) if name == "sensitivity" => {
+                schema_field.sensitivity = lit_str(&value)
+            }
+            AwtoArg::Assign(name, value) if name == "max_len" => {
+                schema_field.has_max_len = true;
+                schema_field.max_len = match &*value {
+                    syn::Expr::Lit(syn::ExprLit {
+                        lit: syn::Lit::Int(lit),
+                        ..
+                    }) => lit.base10_parse().ok(),
+                    _ => None,
+                };
+            }
+            AwtoArg::Assign(name, value) if name == "db_type" => {
+                schema_field.db_type = lit_str(&value)
+            }
+            AwtoArg::Assign(name, value) if name == "feature" => {
+                schema_field.feature = lit_str(&value)
+            }
+            AwtoArg::Assign(name, value) if name == "references" => {
+                if let syn::Expr::Tuple(tuple) = &*value {
+                    if let Some(syn::Expr::Path(model)) = tuple.elems.first() {
+                        schema_field.references = model
+                            .path
+                            .get_ident()
+                            .map(|ident| ident.unraw().to_string());
+                    }
+                }
+            }
+            AwtoArg::Flag(name) if name == "deprecated" => schema_field.deprecated = true,
+            AwtoArg::Call(name, args) if name == "deprecated" => {
+                schema_field.deprecated = true;
+                schema_field.deprecated_since = args.iter().find_map(|arg| match arg {
+                    syn::Expr::Assign(syn::ExprAssign { left, right, .. }) => match &**left {
+                        syn::Expr::Path(name) if name.path.is_ident("since") => lit_str(right),
+                        _ => None,
+                    },
+                    _ => None,
+                });
+            }
+            AwtoArg::Call(name, args) if name == "column" => {
+                schema_field.backfill = args.iter().find_map(|arg| match arg {
+                    syn::Expr::Assign(syn::ExprAssign { left, right, .. }) => match &**left {
+                        syn::Expr::Path(name) if name.path.is_ident("backfill") => lit_str(right),
+                        _ => None,
+                    },
+                    _ => None,
+                });
+            }
+            AwtoArg::Allow(lints) => schema_field.allow.extend(lints),
+            _ => {}
+        }
+    }
+
+    Some(schema_field)
+}
+
+/// Returns whether `#[serde(skip)]` or `#[serde(skip_serializing)]` is in `attrs`.
+fn serde_skipped(attrs: &[syn::Attribute]) -> bool {
+    attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("serde"))
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(syn::Meta::List(list)) => Some(list.nested),
+            _ => None,
+        })
+        .flatten()
+        .any(|nested| {
+            matches!(nested, syn::NestedMeta::Meta(syn::Meta::Path(path))
+                if path.is_ident("skip") || path.is_ident("skip_serializing"))
+        })
+}
+
+/// Returns the attributes other than doc comments as written in the source, such as `database_table`,
+/// with the arguments of `#[awto(...)]` attributes returned separately, such as `max_len = 120`.
+fn attributes(attrs: &[syn::Attribute]) -> Vec<String> {
+    let mut attributes = Vec::new();
+    for attr in attrs {
+        if attr.path.is_ident("doc") {
+            continue;
+        }
+
+        let args = attr
+            .parse_args_with(
+                syn::punctuated::Punctuated::<syn::Expr, syn::Token![,]>::parse_terminated,
+            )
+            .ok()
+            .filter(|_| attr.path.is_ident("awto"));
+        match args {
+            Some(args) => attributes.extend(args.iter().map(tokens_string)),
+            None => attributes.push(format!(
+                "{}{}",
+                tokens_string(&attr.path),
+                tokens_string(&attr.tokens)
+            )),
+        }
+    }
+
+    attributes
+}
+
+/// Prints tokens the way they are usually written, such as `references = (Category, "id")`.
+fn tokens_string(tokens: &impl ToTokens) -> String {
+    let printed = tokens.to_token_stream().to_string();
+    let chars: Vec<char> = printed.chars().collect();
+
+    let mut string = String::with_capacity(printed.len());
+    for (i, c) in chars.iter().enumerate() {
+        if *c == ' ' {
+            let previous = i.checked_sub(1).map(|i| chars[i]);
+            let next = chars.get(i + 1).copied();
+            let joins_previous = matches!(next, Some(',' | ')' | ']' | ':' | '<' | '>'))
+                || (next == Some('(')
+                    && previous
+                        .map(|c| c.is_alphanumeric() || c == '_')
+                        .unwrap_or(false));
+            let joins_next = matches!(previous, Some('(' | '[' | ':' | '<'));
+            if joins_previous || joins_next {
+                continue;
+            }
+        }
+        string.push(*c);
+    }
+
+    string
+}
+
+fn lit_str(expr: &syn::Expr) -> Option<String> {
+    match expr {
+        syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Str(lit),
+            ..
+        }) => Some(lit.value()),
+        _ => None,
+    }
+}
+
+/// An argument of an `#[awto(...)]` attribute.
+enum AwtoArg {
+    /// `name`, such as `unique`.
+    Flag(String),
+    /// `name = value`, such as `max_len = 120`.
+    Assign(String, Box<syn::Expr>),
+    /// `allow(lint, ...)`.
+    Allow(Vec<String>),
+    /// `name(args, ...)` other than `allow`, such as `deprecated(since = "2024-06-01")`.
+    Call(String, Vec<syn::Expr>),
+    Other,
+}
+
+fn awto_args(attrs: &[syn::Attribute]) -> Vec<AwtoArg> {
+    attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("awto"))
+        .flat_map(|attr| {
+            // Arguments are parsed as expressions since some, such as `order_by = created_at`, are not valid meta items
+            attr.parse_args_with(
+                syn::punctuated::Punctuated::<syn::Expr, syn::Token![,]>::parse_terminated,
+            )
+            .into_iter()
+            .flatten()
+        })
+        .map(|arg| match arg {
+            syn::Expr::Path(path) => path
+                .path
+                .get_ident()
+                .map(|ident| AwtoArg::Flag(ident.to_string()))
+                .unwrap_or(AwtoArg::Other),
+            syn::Expr::Assign(syn::ExprAssign { left, right, .. }) => match *left {
+                syn::Expr::Path(name) => name
+                    .path
+                    .get_ident()
+                    .map(|ident| AwtoArg::Assign(ident.to_string(), right))
+                    .unwrap_or(AwtoArg::Other),
+                _ => AwtoArg::Other,
+            },
+            syn::Expr::Call(call) => match &*call.func {
+                syn::Expr::Path(name) if name.path.is_ident("allow") => AwtoArg::Allow(
+                    call.args
+                        .iter()
+                        .map(|lint| lint.to_token_stream().to_string())
+                        .collect(),
+                ),
+                syn::Expr::Path(name) => name
+                    .path
+                    .get_ident()
+                    .map(|ident| {
+                        AwtoArg::Call(ident.to_string(), call.args.iter().cloned().collect())
+                    })
+                    .unwrap_or(AwtoArg::Other),
+                _ => AwtoArg::Other,
+            },
+            _ => AwtoArg::Other,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_unsupported_items() {
+        let source = r#"
+use awto::prelude::*;
+
+const GREETING: &core::ffi::CStr = c"hello";
+
+#[cfg(feature = "gen")]
+fn numbers() -> impl Iterator<Item = u8> {
+    gen { yield 1; }
+}
+
+schema! {
+    pub struct Product {
+        pub id: Uuid,
+        pub total: Cents,
+    }
+}
+
+unsafe extern "C" {
+    pub safe fn abs(i: i32) -> i32;
+}
+
+pub fn first(values: &[u8]) -> u8 {
+    let Some(first) = values.first() else { return 0 };
+    *first
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Cents(pub i64);
+"#;
+        let models = parse_schema_models(source).unwrap();
+        assert_eq!(models.len(), 1);
+        assert_eq!(models[0].fields[1].ty, "Cents");
+
+        // A type the models use cannot be skipped
+        let err = parse_schema_models(&source.replace(
+            "pub struct Cents(pub i64);",
+            "pub struct Cents(#[doc = c\"cents\"] pub i64);",
+        ))
+        .unwrap_err();
+        let diagnostics = err.downcast_ref::<Diagnostics>().unwrap();
+        let messages: Vec<_> = diagnostics
+            .diagnostics
+            .iter()
+            .map(|diagnostic| {
+                (
+                    diagnostic.span.start.to_string(),
+                    diagnostic.message.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            messages,
+            [(
+                "28:26".to_string(),
+                "could not parse `Cents`, which model `Product` uses: unsupported literal `c\"cents\"`"
+            )]
+        );
+
+        // Neither can a `schema!` invocation
+        let err = parse_schema_models(&source.replace(
+            "pub id: Uuid,",
+            "pub id: Uuid,\n        #[doc = c\"total\"]",
+        ))
+        .unwrap_err();
+        let diagnostics = err.downcast_ref::<Diagnostics>().unwrap();
+        assert!(diagnostics.diagnostics[0]
+            .message
+            .starts_with("unsupported literal"));
+    }
+
+    #[test]
+    fn parse_model_visibility() {
+        let models = parse_schema_models(
+            r#"
+            use awto::prelude::*;
+
+            schema! {
+                #[database_table]
+                #[awto(readonly, visibility(entity = "pub", active_model = "pub(crate)"))]
+                pub struct Event {
+                    pub id: Uuid,
+                }
+
+                #[database_table]
+                #[awto(skip_rest)]
+                pub struct Product {
+                    pub id: Uuid,
+                }
+
+                #[database_table]
+                #[awto(external)]
+                pub struct LegacyUser {
+                    pub id: Uuid,
+                }
+            }
+            "#,
+        )
+        .unwrap();
+
+        assert!(models[0].readonly && !models[0].skip_rest && !models[0].external);
+        assert!(models[2].readonly && models[2].external);
+        assert_eq!(models[0].entity_visibility, Some(Visibility::Public));
+        assert_eq!(models[0].active_model_visibility, Some(Visibility::Crate));
+        assert!(!models[1].readonly && models[1].skip_rest);
+        assert_eq!(models[1].entity_visibility, None);
+    }
+
+    #[test]
+    fn parse_models() {
+        let models = parse_schema_models(
+            r#"
+            use awto::prelude::*;
+
+            schema! {
+                #[database_table]
+                pub struct Product {
+                    pub id: Uuid,
+                }
+
+                #[protobuf_message]
+                pub struct ProductId {
+                    pub id: Uuid,
+                }
+            }
+
+            mod analytics {
+                awto::schema! {
+                    #[database_table]
+                    #[awto(database = "analytics", order_by = created_at)]
+                    pub struct Event {
+                        pub id: Uuid,
+                    }
+                }
+            }
+            "#,
+        )
+        .unwrap();
+
+        let models: Vec<_> = models
+            .iter()
+            .map(|model| {
+                (
+                    model.name.as_str(),
+                    model.database.as_deref(),
+                    model.is_database_table,
+                )
+            })
+            .collect();
+        assert_eq!(
+            models,
+            [
+                ("Product", None, true),
+                ("ProductId", None, false),
+                ("Event", Some("analytics"), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_deprecated_fields() {
+        let models = parse_schema_models(
+            r#"
+            schema! {
+                #[database_table]
+                pub struct Employee {
+                    pub id: Uuid,
+                    #[awto(max_len = 40, deprecated(note = "use name", since = "2024-06-01"))]
+                    pub nickname: Option<String>,
+                    #[awto(deprecated)]
+                    pub title: Option<String>,
+                }
+            }
+            "#,
+        )
+        .unwrap();
+
+        let fields: Vec<_> = models[0]
+            .fields
+            .iter()
+            .map(|field| {
+                (
+                    field.name.as_str(),
+                    field.deprecated,
+                    field.deprecated_since.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                ("id", false, None),
+                ("nickname", true, Some("2024-06-01")),
+                ("title", true, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_mixins() {
+        let models = parse_schema_models(
+            r#"
+            schema! {
+                #[awto(mixin, include = Audit)]
+                pub struct Timestamps {
+                    pub created_at: DateTime<FixedOffset>,
+                    pub updated_at: DateTime<FixedOffset>,
+                }
+
+                #[awto(mixin)]
+                pub struct Audit {
+                    #[awto(max_len = 120)]
+                    pub created_by: String,
+                }
+
+                #[database_table]
+                #[awto(include = Timestamps)]
+                pub struct Product {
+                    pub id: Uuid,
+                }
+            }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(models.len(), 1);
+        let fields: Vec<_> = models[0]
+            .fields
+            .iter()
+            .map(|field| {
+                (
+                    field.name.as_str(),
+                    field.mixin.as_deref(),
+                    field.has_max_len,
+                )
+            })
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                ("id", None, false),
+                ("created_at", Some("Timestamps"), false),
+                ("updated_at", Some("Timestamps"), false),
+                ("created_by", Some("Audit"), true),
+            ]
+        );
+
+        let err = parse_schema_models(
+            "schema! {\n#[awto(mixin)]\npub struct Audit { pub id: Uuid }\n#[awto(include = Audit)]\npub struct Product { pub id: Uuid }\n}",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "./schema/src/lib.rs:5:26: field `id` of `Product` is also declared in mixin `Audit`"
+        );
+        let diagnostics = err.downcast_ref::<Diagnostics>().unwrap();
+        assert_eq!(
+            diagnostics.diagnostics[0].label.as_deref(),
+            Some("also declared at 3:24")
+        );
+
+        let err = parse_schema_models(
+            "schema! {\n#[awto(mixin, include = B)]\npub struct A {}\n#[awto(mixin, include = A)]\npub struct B {}\n#[awto(include = A)]\npub struct Product {}\n}",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "./schema/src/lib.rs:4:25: mixins include each other: A -> B -> A"
+        );
+
+        let err =
+            parse_schema_models("schema! {\n#[awto(include = Audit)]\npub struct Product {}\n}")
+                .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "./schema/src/lib.rs:2:18: mixin `Audit` included by `Product` not found"
+        );
+    }
+
+    #[test]
+    fn parse_newtypes() {
+        let models = parse_schema_models(
+            r#"
+            schema! {
+                #[awto(newtype)]
+                #[derive(Clone, Copy)]
+                pub struct Cents(pub i64);
+
+                #[database_table]
+                pub struct Product {
+                    pub id: Uuid,
+                    pub price: Cents,
+                }
+            }
+            "#,
+        )
+        .unwrap();
+
+        let names: Vec<_> = models.iter().map(|model| model.name.as_str()).collect();
+        assert_eq!(names, ["Product"]);
+    }
+
+    #[test]
+    fn parse_aliased_macros() {
+        let names = |source: &str| -> Vec<String> {
+            parse_schema_models(source)
+                .unwrap()
+                .into_iter()
+                .map(|model| model.name)
+                .collect()
+        };
+
+        assert_eq!(
+            names("use awto::schema as models;\nmodels! { pub struct Product { pub id: Uuid } }"),
+            ["Product"]
+        );
+        assert_eq!(
+            names(
+                "use awto::prelude::{schema as s, *};\nmod app { s! { pub struct Product { pub id: Uuid } } }"
+            ),
+            ["Product"]
+        );
+        assert_eq!(
+            names("::awto::schema! { pub struct Product { pub id: Uuid } }"),
+            ["Product"]
+        );
+        assert_eq!(
+            names(
+                r#"
+                macro_rules! app_schema {
+                    ($($item:item)*) => { awto::schema! { $($item)* } };
+                }
+                macro_rules! tables {
+                    ($($item:item)*) => { app_schema! { $($item)* } };
+                }
+                tables! { pub struct Product { pub id: Uuid } }
+                app_schema! { pub struct Order { pub id: Uuid } }
+                "#
+            ),
+            ["Product", "Order"]
+        );
+        assert!(parse_schema_models("other! { pub struct Product { pub id: Uuid } }").is_err());
+    }
+
+    #[test]
+    fn similar_macro_error() {
+        let err = parse_schema_models("awto::schemas! { pub struct Product { pub id: Uuid } }")
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "./schema/src/lib.rs:1:1: no `schema!` macro found, found `awto::schemas!(...)`"
+        );
+        let diagnostics = err.downcast_ref::<Diagnostics>().unwrap();
+        assert_eq!(
+            diagnostics.diagnostics[0].help.as_deref(),
+            Some("did you mean `awto::schema!`?")
+        );
+
+        let err = parse_schema_models("Schema! {}").unwrap_err();
+        assert!(err.to_string().contains("found `Schema!(...)`"));
+
+        let err = parse_schema_models("use awto::prelude::*;\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "./schema/src/lib.rs:1:1: no `schema!` macro found"
+        );
+        assert!(parse_schema_models("schema! {}").unwrap().is_empty());
+        assert!(
+            parse_schema_models("mod models {\n    awto::schema! {}\n}\n")
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(edit_distance("schema", "schema"), 0);
+        assert_eq!(edit_distance("shcema", "schema"), 2);
+    }
+
+    #[test]
+    fn parse_fields() {
+        let models = parse_schema_models(
+            r#"
+schema! {
+    #[database_table]
+    #[awto(allow(too_many_columns, vague_field_name))]
+    pub struct Product {
+        pub id: Uuid,
+        #[awto(max_len = 120, allow(missing_index))]
+        pub name: Option<String>,
+        #[awto(references = (Category, "id"), unique)]
+        pub category_id: Uuid,
+        #[awto(db_type = "text")]
+        pub description: String,
+    }
+}
+
+use catalog::Category;
+"#,
+        )
+        .unwrap();
+
+        let product = &models[0];
+        assert_eq!(product.allow, ["too_many_columns", "vague_field_name"]);
+        assert_eq!(
+            product.location,
+            Location {
+                line: 5,
+                column: 16
+            }
+        );
+        assert_eq!(
+            product.fields[1],
+            SchemaField {
+                name: "name".to_string(),
+                ty: "Option<String>".to_string(),
+                has_max_len: true,
+                max_len: Some(120),
+                allow: vec!["missing_index".to_string()],
+                attributes: vec![
+                    "max_len = 120".to_string(),
+                    "allow(missing_index)".to_string()
+                ],
+                location: Location {
+                    line: 8,
+                    column: 13
+                },
+                ty_span: Span {
+                    start: Location {
+                        line: 8,
+                        column: 19
+                    },
+                    end: Location {
+                        line: 8,
+                        column: 33
+                    },
+                },
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            product.attributes,
+            [
+                "database_table",
+                "allow(too_many_columns, vague_field_name)"
+            ]
+        );
+        assert_eq!(product.fields[2].references.as_deref(), Some("Category"));
+        assert_eq!(
+            product.fields[2].attributes,
+            [r#"references = (Category, "id")"#, "unique"]
+        );
+        assert!(product.fields[2].unique);
+        assert_eq!(product.fields[3].db_type.as_deref(), Some("text"));
+    }
+
+    #[test]
+    fn registered_names() {
+        let err = parse_schema_models(
+            r#"
+schema! {
+    #[database_table]
+    pub struct Product {
+        pub id: Uuid,
+        #[awto(references = (Categroy, "id"))]
+        pub category_id: Uuid,
+    }
+
+    #[database_sub_table(Prodcut)]
+    pub struct NewProduct {
+        pub name: String,
+    }
+}
+
+#[database_table]
+pub struct Category {
+    pub id: Uuid,
+}
+"#,
+        )
+        .unwrap_err();
+
+        let diagnostics = err.downcast_ref::<Diagnostics>().unwrap();
+        let messages: Vec<_> = diagnostics
+            .diagnostics
+            .iter()
+            .map(|diagnostic| {
+                (
+                    diagnostic.span.start.to_string(),
+                    diagnostic.message.as_str(),
+                    diagnostic.help.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                (
+                    "6:30".to_string(),
+                    "`Categroy` given to `#[awto(references = (...))]` of `Product` is not declared",
+                    Some("declare `Categroy` in a `schema!` macro, or import it with `use`"),
+                ),
+                (
+                    "10:26".to_string(),
+                    "`Prodcut` given to `#[database_sub_table(...)]` of `NewProduct` is not declared",
+                    Some("did you mean `Product`?"),
+                ),
+                (
+                    "17:12".to_string(),
+                    "struct `Category` is marked `#[database_table]` but is not declared in a `schema!` macro",
+                    Some("move `Category` into `awto::schema! { ... }` to generate code for it"),
+                ),
+            ]
+        );
+
+        // Imported, re-exported and cfg-disabled names are left alone
+        let source = r#"
+pub use catalog::{Category as Categroy};
+schema! {
+    #[database_table]
+    pub struct Product {
+        #[awto(references = (Categroy, "id"))]
+        pub category_id: Uuid,
+    }
+}
+#[cfg(test)]
+mod fixtures {
+    #[database_table]
+    pub struct Fixture {}
+}
+"#;
+        assert!(parse_schema_models(source).is_ok());
+        assert!(parse_schema_models(&source.replace(
+            "pub use catalog::{Category as Categroy};",
+            "use catalog::*;"
+        ))
+        .is_ok());
+        assert!(parse_schema_models(
+            &source.replace("pub use catalog::{Category as Categroy};", "")
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn collect_diagnostics() {
+        let err = parse_schema_models(
+            r#"
+schema! {
+    #[database_table]
+    pub struct Product {
+        pub id: Uuid,
+        pub price: Option<Money>,
+        #[awto(maxlen = 120)]
+        pub name: String,
+        #[awto(db_type = "Money")]
+        pub cost: Money,
+    }
+
+    #[awto(newtype)]
+    pub struct Cents(pub i64);
+
+    #[database_table]
+    #[awto(versioned, colour = "red")]
+    pub struct Product {
+        pub id: Uuid,
+        pub total: Cents,
+    }
+}
+"#,
+        )
+        .unwrap_err();
+
+        let diagnostics = err.downcast_ref::<Diagnostics>().unwrap();
+        let messages: Vec<_> = diagnostics
+            .diagnostics
+            .iter()
+            .map(|diagnostic| {
+                (
+                    diagnostic.span.start.to_string(),
+                    diagnostic.message.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                (
+                    "6:20".to_string(),
+                    "type `Money` of `Product.price` is not supported by database tables"
+                ),
+                (
+                    "7:16".to_string(),
+                    "unknown argument `maxlen` in `#[awto(...)]` of fields"
+                ),
+                (
+                    "17:23".to_string(),
+                    "unknown argument `colour` in `#[awto(...)]` of models"
+                ),
+                (
+                    "18:16".to_string(),
+                    "model `Product` is declared more than once"
+                ),
+            ]
+        );
+        assert_eq!(
+            diagnostics.diagnostics[0].span.end,
+            Location {
+                line: 6,
+                column: 33
+            }
+        );
+        assert_eq!(
+            diagnostics.diagnostics[1].help.as_deref(),
+            Some("did you mean `max_len`?")
+        );
+        assert!(diagnostics.diagnostics[2]
+            .help
+            .as_deref()
+            .unwrap()
+            .starts_with("expected one of `allow`, `authorize`, `database`"));
+
+        let err = parse_schema_models(
+            "schema! {
+    pub struct Product {
+        pub id Uuid,
+    }
+}",
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "./schema/src/lib.rs:3:16: expected `:`");
+    }
+
+    #[test]
+    fn invalid_entity_attrs() {
+        let source = r##"
+schema! {
+    #[database_table]
+    #[awto(entity_attrs("#[serde(rename_all = \"camelCase\")]", "#[serde(", "derive(Hash)"))]
+    pub struct Product {
+        pub id: Uuid,
+    }
+}
+"##;
+        let err = parse_schema_models(source).unwrap_err();
+
+        let diagnostics = err.downcast_ref::<Diagnostics>().unwrap();
+        let messages: Vec<_> = diagnostics
+            .diagnostics
+            .iter()
+            .map(|diagnostic| {
+                (
+                    diagnostic.span.start.to_string(),
+                    diagnostic.message.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                (
+                    "4:65".to_string(),
+                    "invalid entity attribute `#[serde(` of `Product`"
+                ),
+                (
+                    "4:77".to_string(),
+                    "invalid entity attribute `derive(Hash)` of `Product`"
+                ),
+            ]
+        );
+
+        let models =
+            parse_schema_models(&source.replace(r##", "#[serde(", "derive(Hash)""##, "")).unwrap();
+        assert_eq!(models[0].name, "Product");
+    }
+
+    #[test]
+    fn merged_schema_packages() {
+        let billing = parse_schema_models(
+            r#"
+schema! {
+    #[database_table]
+    pub struct Product {
+        pub id: Uuid,
+    }
+
+    #[database_table]
+    pub struct Invoice {
+        pub id: Uuid,
+        #[awto(references = (Product, "id"))]
+        pub product_id: Uuid,
+    }
+}
+"#,
+        )
+        .unwrap();
+        let catalog = parse_schema_models(
+            r#"
+schema! {
+    #[database_table]
+    pub struct Product {
+        pub id: Uuid,
+    }
+}
+"#,
+        )
+        .unwrap();
+        let packages = || {
+            vec![
+                ("schema-billing", billing.clone()),
+                ("schema-catalog", catalog.clone()),
+            ]
+        };
+
+        let err = merge_schema_models(packages(), Duplicates::Error).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "model 'Product' is declared in both schema packages 'schema-billing' and 'schema-catalog'"
+        );
+
+        let models = merge_schema_models(packages(), Duplicates::Namespace).unwrap();
+        let names: Vec<_> = models
+            .iter()
+            .map(|model| (model.name.as_str(), model.package.as_deref()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("BillingProduct", Some("schema-billing")),
+                ("Invoice", Some("schema-billing")),
+                ("CatalogProduct", Some("schema-catalog")),
+            ]
+        );
+        assert_eq!(
+            models[1].fields[1].references.as_deref(),
+            Some("BillingProduct")
+        );
+        assert_eq!(models[0].lib_path(), SCHEMA_LIB_PATH);
+    }
+}
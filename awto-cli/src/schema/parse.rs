@@ -0,0 +1,261 @@
+use proc_macro2::{Delimiter, TokenStream, TokenTree};
+use syn::parse::{discouraged::Speculative, ParseStream, Parser};
+
+/// Keywords declaring a named item, whose name follows them.
+const ITEM_KEYWORDS: [&str; 9] = [
+    "const", "enum", "fn", "mod", "static", "struct", "trait", "type", "union",
+];
+
+/// An item of a schema lib which could not be parsed, such as a function using syntax newer than the parser.
+pub(crate) struct SkippedItem {
+    /// Name of the item, unless it has none, such as an `impl`.
+    pub name: Option<String>,
+    /// Name of the macro invoked by the item, such as `schema` for `awto::schema! { ... }`.
+    pub macro_name: Option<String>,
+    pub error: syn::Error,
+}
+
+/// Parses the schema lib `source` one item at a time, skipping the items which fail to parse rather than the
+/// whole file, so syntax the parser does not know outside of the models does not prevent reading them.
+///
+/// Items of inline modules are parsed the same way. It is up to the caller to fail on skipped items the models need.
+pub(crate) fn parse_file_resilient(source: &str) -> syn::Result<(syn::File, Vec<SkippedItem>)> {
+    let source = source.strip_prefix('\u{feff}').unwrap_or(source);
+    let mut skipped = Vec::new();
+    let parser = |input: ParseStream| {
+        let attrs = input.call(syn::Attribute::parse_inner)?;
+        let items = parse_items(input, &mut skipped)?;
+        Ok(syn::File {
+            shebang: None,
+            attrs,
+            items,
+        })
+    };
+    let file = parser.parse_str(source)?;
+
+    Ok((file, skipped))
+}
+
+fn parse_items(input: ParseStream, skipped: &mut Vec<SkippedItem>) -> syn::Result<Vec<syn::Item>> {
+    let mut items = Vec::new();
+    while !input.is_empty() {
+        if let Some(item_mod) = parse_inline_mod(input, skipped)? {
+            items.push(syn::Item::Mod(item_mod));
+            continue;
+        }
+
+        // The parser panics on literals it does not know, such as C strings, so their items are skipped unparsed
+        let tokens = item_tokens(&input.fork())?;
+        let fork = input.fork();
+        let parsed = match unsupported_literal(tokens.clone()) {
+            Some(literal) => Err(syn::Error::new(
+                literal.span(),
+                format!("unsupported literal `{}`", literal),
+            )),
+            None => fork.parse::<syn::Item>(),
+        };
+        match parsed {
+            Ok(item) => {
+                input.advance_to(&fork);
+                items.push(item);
+            }
+            Err(error) => {
+                skipped.push(SkippedItem {
+                    name: item_name(tokens.clone()),
+                    macro_name: macro_name(tokens.clone()),
+                    error,
+                });
+                // Skips the tokens of the item, which cannot be empty
+                input.step(|cursor| {
+                    let mut rest = *cursor;
+                    for _ in 0..tokens.into_iter().count().max(1) {
+                        match rest.token_tree() {
+                            Some((_, next)) => rest = next,
+                            None => break,
+                        }
+                    }
+                    Ok(((), rest))
+                })?;
+            }
+        }
+    }
+
+    Ok(items)
+}
+
+/// Parses an inline module, such as `mod tests { ... }`, whose items are parsed one at a time as well.
+fn parse_inline_mod(
+    input: ParseStream,
+    skipped: &mut Vec<SkippedItem>,
+) -> syn::Result<Option<syn::ItemMod>> {
+    let fork = input.fork();
+    let header = (|| -> syn::Result<_> {
+        let attrs = fork.call(syn::Attribute::parse_outer)?;
+        let vis: syn::Visibility = fork.parse()?;
+        let mod_token: syn::Token![mod] = fork.parse()?;
+        let ident: syn::Ident = fork.parse()?;
+        Ok((attrs, vis, mod_token, ident))
+    })();
+    let (mut attrs, vis, mod_token, ident) = match header {
+        Ok(header) if fork.peek(syn::token::Brace) => header,
+        _ => return Ok(None),
+    };
+
+    let content;
+    let brace = syn::braced!(content in fork);
+    attrs.extend(content.call(syn::Attribute::parse_inner)?);
+    let items = parse_items(&content, skipped)?;
+    input.advance_to(&fork);
+
+    Ok(Some(syn::ItemMod {
+        attrs,
+        vis,
+        mod_token,
+        ident,
+        content: Some((brace, items)),
+        semi: None,
+    }))
+}
+
+/// Returns the tokens of the item at the start of `input`, which end with a `;` or a block not followed by one.
+fn item_tokens(input: ParseStream) -> syn::Result<TokenStream> {
+    input.step(|cursor| {
+        let mut tokens = TokenStream::new();
+        let mut rest = *cursor;
+        while let Some((tree, next)) = rest.token_tree() {
+            let ends = match &tree {
+                TokenTree::Punct(punct) => punct.as_char() == ';',
+                // `const X: Point = Point { x: 0 };` continues after its block
+                TokenTree::Group(group) => {
+                    group.delimiter() == Delimiter::Brace
+                        && !matches!(next.token_tree(), Some((TokenTree::Punct(punct), _)) if punct.as_char() == ';')
+                }
+                _ => false,
+            };
+            tokens.extend(std::iter::once(tree));
+            rest = next;
+            if ends {
+                break;
+            }
+        }
+        Ok((tokens, rest))
+    })
+}
+
+/// Returns the first literal of `tokens` the parser does not support.
+fn unsupported_literal(tokens: TokenStream) -> Option<proc_macro2::Literal> {
+    tokens.into_iter().find_map(|tree| match tree {
+        TokenTree::Literal(literal) => {
+            let text = literal.to_string();
+            (text.starts_with("c\"") || text.starts_with("cr\"") || text.starts_with("cr#"))
+                .then_some(literal)
+        }
+        TokenTree::Group(group) => unsupported_literal(group.stream()),
+        _ => None,
+    })
+}
+
+/// Returns the name of the item of `tokens`, such as `Cents` for `pub struct Cents(i64);`.
+fn item_name(tokens: TokenStream) -> Option<String> {
+    let mut tokens = tokens.into_iter().filter(
+        |tree| !matches!(tree, TokenTree::Group(group) if group.delimiter() == Delimiter::Bracket),
+    );
+    while let Some(tree) = tokens.next() {
+        match tree {
+            TokenTree::Ident(ident) if ITEM_KEYWORDS.contains(&ident.to_string().as_str()) => {
+                return match tokens.next() {
+                    Some(TokenTree::Ident(name)) => Some(name.to_string()),
+                    _ => None,
+                };
+            }
+            TokenTree::Group(_) => return None,
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Returns the name of the macro invoked by the item of `tokens`, such as `schema` for `awto::schema! { ... }`.
+fn macro_name(tokens: TokenStream) -> Option<String> {
+    let mut previous_ident = None;
+    for tree in tokens {
+        match tree {
+            TokenTree::Ident(ident) => previous_ident = Some(ident.to_string()),
+            TokenTree::Punct(punct) if punct.as_char() == '!' => return previous_ident,
+            TokenTree::Punct(punct) if punct.as_char() == ':' => {}
+            // Attributes of the item, such as `#[rustfmt::skip]`
+            TokenTree::Punct(punct) if punct.as_char() == '#' => previous_ident = None,
+            TokenTree::Group(group) if group.delimiter() == Delimiter::Bracket => {}
+            _ => return None,
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn skipped_items() {
+        let source = r#"
+#![allow(dead_code)]
+use awto::prelude::*;
+
+const GREETING: &core::ffi::CStr = c"hello";
+
+#[cfg(feature = "nightly")]
+fn numbers() -> impl Iterator<Item = u8> {
+    gen { yield 1; }
+}
+
+const ORIGIN: Point = Point { x: 0 };
+
+mod helpers {
+    unsafe extern "C" {
+        pub safe fn abs(i: i32) -> i32;
+    }
+
+    pub fn first(values: &[u8]) -> u8 {
+        let Some(first) = values.first() else { return 0 };
+        *first
+    }
+}
+
+impl Product {}
+"#;
+        let (file, skipped) = parse_file_resilient(source).unwrap();
+        assert_eq!(file.attrs.len(), 1);
+        let kinds: Vec<_> = file
+            .items
+            .iter()
+            .map(|item| match item {
+                syn::Item::Use(_) => "use",
+                syn::Item::Const(_) => "const",
+                syn::Item::Mod(item_mod) => {
+                    assert_eq!(item_mod.content.as_ref().unwrap().1.len(), 1);
+                    "mod"
+                }
+                syn::Item::Impl(_) => "impl",
+                _ => "other",
+            })
+            .collect();
+        assert_eq!(kinds, ["use", "const", "mod", "impl"]);
+
+        let names: Vec<_> = skipped.iter().map(|item| item.name.as_deref()).collect();
+        assert_eq!(names, [Some("GREETING"), Some("numbers"), None]);
+        assert_eq!(
+            skipped[0].error.to_string(),
+            "unsupported literal `c\"hello\"`"
+        );
+        assert_eq!(skipped[1].error.span().start().line, 9);
+        assert!(skipped.iter().all(|item| item.macro_name.is_none()));
+
+        let (_, skipped) =
+            parse_file_resilient("#[rustfmt::skip]\nawto::schema! { const C: &CStr = c\"x\"; }")
+                .unwrap();
+        assert_eq!(skipped[0].macro_name.as_deref(), Some("schema"));
+    }
+}
@@ -0,0 +1,199 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use clap::Parser;
+use log::info;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::{cancel::Cancellation, error::Error, Runnable};
+
+use super::{load_schema_models, Location, SchemaModel};
+
+/// Directory containing the schema snapshots of the app.
+pub const SNAPSHOTS_DIR: &str = "./awto/snapshots";
+
+/// Writes a snapshot of the app schema to ./awto/snapshots
+#[derive(Parser)]
+pub struct Snapshot {
+    /// Fails when the schema changed since the latest snapshot instead of writing one
+    #[clap(long)]
+    pub check: bool,
+    /// Prints more information
+    #[clap(short, long)]
+    pub verbose: bool,
+}
+
+#[async_trait]
+impl Runnable for Snapshot {
+    async fn run(&mut self, _cancellation: &Cancellation) -> Result<()> {
+        let snapshot = SchemaSnapshot::new(load_schema_models().await?);
+
+        let latest = SchemaSnapshot::latest().await?;
+        if let Some((path, latest)) = &latest {
+            if latest.models == snapshot.models {
+                info!("schema is unchanged since snapshot '{}'", path.display());
+                return Ok(());
+            }
+        }
+        if self.check {
+            return Err(match latest {
+                Some((path, _)) => Error::SnapshotOutdated(path.display().to_string()),
+                None => Error::NoSnapshot,
+            }
+            .into());
+        }
+
+        let path = snapshot.write().await?;
+        info!("wrote snapshot '{}'", path.display());
+
+        Ok(())
+    }
+
+    fn is_verbose(&self) -> bool {
+        self.verbose
+    }
+}
+
+/// The models of the app schema at a point in time, stored as JSON in [`SNAPSHOTS_DIR`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchemaSnapshot {
+    /// Version of the snapshot format.
+    pub version: u32,
+    /// Time the snapshot was taken, in RFC 3339 format.
+    pub created_at: String,
+    pub models: Vec<SchemaModel>,
+}
+
+impl SchemaSnapshot {
+    /// Version of the snapshot format written by this cli.
+    pub const VERSION: u32 = 1;
+
+    /// Creates a snapshot of `models` taken now.
+    ///
+    /// Locations are left out of snapshots, so moving code around in the schema lib does not change them.
+    pub fn new(mut models: Vec<SchemaModel>) -> Self {
+        for model in &mut models {
+            model.location = Location::default();
+            for field in &mut model.fields {
+                field.location = Location::default();
+                field.ty_span = Default::default();
+            }
+        }
+
+        SchemaSnapshot {
+            version: Self::VERSION,
+            created_at: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            models,
+        }
+    }
+
+    /// Reads the snapshot file at `path`.
+    pub async fn read(path: impl AsRef<Path>) -> Result<SchemaSnapshot> {
+        let path = path.as_ref();
+        Self::read_file(path).await.map_err(|source| {
+            Error::Snapshot {
+                since: path.display().to_string(),
+                source,
+            }
+            .into()
+        })
+    }
+
+    async fn read_file(path: &Path) -> Result<SchemaSnapshot> {
+        let bytes = fs::read(path).await.context("could not read file")?;
+        let snapshot: SchemaSnapshot =
+            serde_json::from_slice(&bytes).context("invalid snapshot")?;
+        if snapshot.version > Self::VERSION {
+            bail!(
+                "snapshot version {} is newer than the supported version {}, update the cli",
+                snapshot.version,
+                Self::VERSION
+            );
+        }
+
+        Ok(snapshot)
+    }
+
+    /// Returns the latest snapshot in [`SNAPSHOTS_DIR`] along with its path, if any.
+    ///
+    /// Snapshots are named after the time they were taken, so the latest snapshot sorts last.
+    pub async fn latest() -> Result<Option<(PathBuf, SchemaSnapshot)>> {
+        if !Path::new(SNAPSHOTS_DIR).is_dir() {
+            return Ok(None);
+        }
+
+        let mut latest: Option<PathBuf> = None;
+        let mut entries = fs::read_dir(SNAPSHOTS_DIR)
+            .await
+            .with_context(|| format!("could not read directory '{}'", SNAPSHOTS_DIR))?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().map(|ext| ext != "json").unwrap_or(true) {
+                continue;
+            }
+            if latest.as_ref().map(|latest| path > *latest).unwrap_or(true) {
+                latest = Some(path);
+            }
+        }
+
+        match latest {
+            Some(path) => {
+                let snapshot = Self::read(&path).await?;
+                Ok(Some((path, snapshot)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Writes the snapshot to a file in [`SNAPSHOTS_DIR`] named after the time it was taken.
+    async fn write(&self) -> Result<PathBuf> {
+        fs::create_dir_all(SNAPSHOTS_DIR)
+            .await
+            .with_context(|| format!("could not create directory '{}'", SNAPSHOTS_DIR))?;
+
+        let timestamp = chrono::DateTime::parse_from_rfc3339(&self.created_at)?;
+        let path =
+            Path::new(SNAPSHOTS_DIR).join(format!("{}.json", timestamp.format("%Y%m%dT%H%M%SZ")));
+        let mut json = serde_json::to_string_pretty(self)?;
+        json.push('\n');
+        fs::write(&path, json)
+            .await
+            .with_context(|| format!("could not write file '{}'", path.display()))?;
+
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::schema::parse_schema_models;
+
+    use super::*;
+
+    #[test]
+    fn snapshot_json() {
+        let models = parse_schema_models(
+            r#"
+schema! {
+    #[database_table]
+    pub struct Product {
+        pub id: Uuid,
+        #[awto(max_len = 120)]
+        pub name: String,
+    }
+}
+"#,
+        )
+        .unwrap();
+        let snapshot = SchemaSnapshot::new(models);
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert!(json.contains(r#""attributes":["max_len = 120"]"#));
+        assert!(!json.contains("location"));
+
+        let read: SchemaSnapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(read, snapshot);
+    }
+}
@@ -0,0 +1,302 @@
+use std::{
+    fmt,
+    future::Future,
+    io::{self, IsTerminal, Write},
+    sync::{Arc, Mutex, Once},
+};
+
+use colored::Colorize;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use tokio::task::JoinHandle;
+
+/// Stream of the process a command prints to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// Output of a run, as received by its [`IoSink`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IoEvent {
+    /// Text printed by a command, such as the files listed by `--check` or the JSON of `--message-format json`.
+    Output { stream: Stream, text: String },
+    /// Progress message logged by a command, such as the packages being built.
+    Log { level: Level, message: String },
+}
+
+/// Destination of everything a run prints or logs, in place of the stdout and stderr of the process.
+///
+/// Only the output of child processes the cli runs without reading it, such as `cargo publish`,
+/// is still written to the stdout and stderr of the process.
+#[derive(Clone)]
+pub struct IoSink {
+    handler: Arc<dyn Fn(IoEvent) + Send + Sync>,
+    colored: bool,
+}
+
+impl IoSink {
+    /// Returns a sink passing every event to `handler`, which is output without colors.
+    pub fn new(handler: impl Fn(IoEvent) + Send + Sync + 'static) -> Self {
+        IoSink {
+            handler: Arc::new(handler),
+            colored: false,
+        }
+    }
+
+    /// Returns the sink of the `awto` binary, writing output to stdout and stderr and logs to stderr.
+    ///
+    /// Output is colored when both are terminals, unless `NO_COLOR` is set, or when `CLICOLOR_FORCE` is set.
+    pub fn stdio() -> Self {
+        let colored = (io::stdout().is_terminal() && io::stderr().is_terminal())
+            || std::env::var_os("CLICOLOR_FORCE").is_some();
+
+        IoSink {
+            handler: Arc::new(write_stdio),
+            colored,
+        }
+    }
+
+    /// Returns a sink keeping every event in the returned [`CapturedOutput`].
+    pub fn capture() -> (Self, CapturedOutput) {
+        let captured = CapturedOutput::default();
+        let events = Arc::clone(&captured.events);
+        let sink = IoSink::new(move |event| events.lock().unwrap().push(event));
+
+        (sink, captured)
+    }
+
+    /// Returns whether the output of the sink is colored.
+    pub fn is_colored(&self) -> bool {
+        self.colored
+    }
+
+    fn emit(&self, event: IoEvent) {
+        (self.handler)(event)
+    }
+}
+
+impl fmt::Debug for IoSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IoSink")
+            .field("colored", &self.colored)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Events kept by a sink created with [`IoSink::capture`].
+#[derive(Clone, Debug, Default)]
+pub struct CapturedOutput {
+    events: Arc<Mutex<Vec<IoEvent>>>,
+}
+
+impl CapturedOutput {
+    /// Returns the events received so far, in the order they were received.
+    pub fn events(&self) -> Vec<IoEvent> {
+        self.events.lock().unwrap().clone()
+    }
+
+    /// Returns the text printed to stdout so far.
+    pub fn stdout(&self) -> String {
+        self.output(Stream::Stdout)
+    }
+
+    /// Returns the text printed to stderr so far, without the logged messages.
+    pub fn stderr(&self) -> String {
+        self.output(Stream::Stderr)
+    }
+
+    /// Returns the messages logged so far, along with their level.
+    pub fn logs(&self) -> Vec<(Level, String)> {
+        self.events()
+            .into_iter()
+            .filter_map(|event| match event {
+                IoEvent::Log { level, message } => Some((level, message)),
+                IoEvent::Output { .. } => None,
+            })
+            .collect()
+    }
+
+    fn output(&self, stream: Stream) -> String {
+        self.events()
+            .into_iter()
+            .filter_map(|event| match event {
+                IoEvent::Output { stream: of, text } if of == stream => Some(text),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+fn write_stdio(event: IoEvent) {
+    // Like `println!`, except that a closed pipe does not panic
+    let _ = match event {
+        IoEvent::Output {
+            stream: Stream::Stdout,
+            text,
+        } => io::stdout().lock().write_all(text.as_bytes()),
+        IoEvent::Output {
+            stream: Stream::Stderr,
+            text,
+        } => io::stderr().lock().write_all(text.as_bytes()),
+        IoEvent::Log { level, message } => {
+            let prefix = match level {
+                Level::Error => "error".red(),
+                Level::Warn => "warn".yellow(),
+                Level::Info => "info".blue(),
+                Level::Debug => "debug".purple(),
+                Level::Trace => "trace".cyan(),
+            }
+            .bold();
+            writeln!(io::stderr().lock(), "{} {}", prefix, message)
+        }
+    };
+}
+
+/// Sink of the run being served, along with the level of the messages it logs.
+#[derive(Clone)]
+struct RunSink {
+    sink: IoSink,
+    level: LevelFilter,
+}
+
+tokio::task_local! {
+    static RUN_SINK: RunSink;
+}
+
+/// Runs `future` with every message printed or logged by it written to `sink`,
+/// leaving out the messages logged at a level above `level`.
+pub(crate) async fn scope<F: Future>(sink: IoSink, level: LevelFilter, future: F) -> F::Output {
+    init_logger();
+    RUN_SINK.scope(RunSink { sink, level }, future).await
+}
+
+/// Spawns `future` like [`tokio::spawn`], writing what it prints or logs to the sink of the run spawning it.
+pub(crate) fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    match RUN_SINK.try_with(Clone::clone) {
+        Ok(run_sink) => tokio::spawn(RUN_SINK.scope(run_sink, future)),
+        Err(_) => tokio::spawn(future),
+    }
+}
+
+/// Runs `future` like [`scope`] with the sink of the run being served, printing its stdout to stderr instead,
+/// so it does not mix with output meant to be parsed, such as the JSON report of `awto verify`.
+pub(crate) async fn to_stderr<F: Future>(future: F) -> F::Output {
+    let run_sink = current();
+    let parent = run_sink.sink.clone();
+    let sink = IoSink {
+        handler: Arc::new(move |event| match event {
+            IoEvent::Output {
+                stream: Stream::Stdout,
+                text,
+            } => parent.emit(IoEvent::Output {
+                stream: Stream::Stderr,
+                text,
+            }),
+            event => parent.emit(event),
+        }),
+        colored: run_sink.sink.colored,
+    };
+
+    scope(sink, run_sink.level, future).await
+}
+
+/// Returns the sink of the run being served, or the sink of the process outside of any run.
+fn current() -> RunSink {
+    RUN_SINK.try_with(Clone::clone).unwrap_or_else(|_| RunSink {
+        sink: IoSink::stdio(),
+        level: LevelFilter::Info,
+    })
+}
+
+/// Returns whether the output of the run being served is colored.
+pub(crate) fn is_colored() -> bool {
+    current().sink.is_colored()
+}
+
+/// Writes `args` to `stream` of the sink of the run being served, used by `outln!` and its siblings.
+pub(crate) fn write(stream: Stream, args: fmt::Arguments) {
+    current().sink.emit(IoEvent::Output {
+        stream,
+        text: args.to_string(),
+    });
+}
+
+/// Logger writing the records of each run to its sink.
+struct SinkLogger;
+
+impl Log for SinkLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // sqlx logs every statement run by `awto schema import` at info level
+        if metadata.target().starts_with("sqlx") && metadata.level() > Level::Warn {
+            return false;
+        }
+        metadata.level() <= current().level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        current().sink.emit(IoEvent::Log {
+            level: record.level(),
+            message: record.args().to_string(),
+        });
+    }
+
+    fn flush(&self) {}
+}
+
+/// Installs the logger writing to the sinks of the runs, unless the process installed a logger of its own.
+fn init_logger() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        if log::set_logger(&SinkLogger).is_ok() {
+            log::set_max_level(LevelFilter::Debug);
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn captured_output() {
+        let (sink, captured) = IoSink::capture();
+        scope(sink, LevelFilter::Info, async {
+            outln!("{} file(s)", 2);
+            errln!("compiling");
+            log::info!("compiled");
+            log::debug!("left out");
+            spawn(async { out!("spawned") }).await.unwrap();
+        })
+        .await;
+
+        assert_eq!(captured.stdout(), "2 file(s)\nspawned");
+        assert_eq!(captured.stderr(), "compiling\n");
+        assert_eq!(captured.logs(), [(Level::Info, "compiled".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn stdout_to_stderr() {
+        let (sink, captured) = IoSink::capture();
+        scope(sink, LevelFilter::Info, async {
+            to_stderr(async {
+                outln!("report");
+                log::info!("checked");
+            })
+            .await;
+            outln!("{{}}");
+        })
+        .await;
+
+        assert_eq!(captured.stdout(), "{}\n");
+        assert_eq!(captured.stderr(), "report\n");
+        assert_eq!(captured.logs(), [(Level::Info, "checked".to_string())]);
+    }
+}
@@ -0,0 +1,168 @@
+use colored::Colorize;
+
+/// Lines of unchanged context printed around each change.
+const CONTEXT: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Line {
+    Same(usize, usize),
+    Removed(usize),
+    Added(usize),
+}
+
+/// Returns the unified diff of the file at `path` from `old` to `new`, or an empty string if they are equal.
+pub fn unified(path: &str, old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let lines = diff_lines(&old, &new);
+    let path = path.trim_start_matches("./");
+
+    let mut diff = String::new();
+    let changes: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Line::Same(..)))
+        .map(|(i, _)| i)
+        .collect();
+    if changes.is_empty() {
+        return diff;
+    }
+
+    diff += &format!(
+        "{}\n{}\n",
+        format!("--- a/{}", path).bold(),
+        format!("+++ b/{}", path).bold()
+    );
+    let mut hunk_start = 0;
+    while hunk_start < changes.len() {
+        // Changes separated by less than twice the context are printed in the same hunk
+        let mut hunk_end = hunk_start;
+        while hunk_end + 1 < changes.len()
+            && changes[hunk_end + 1] - changes[hunk_end] <= 2 * CONTEXT
+        {
+            hunk_end += 1;
+        }
+        let start = changes[hunk_start].saturating_sub(CONTEXT);
+        let end = (changes[hunk_end] + CONTEXT + 1).min(lines.len());
+        let hunk = &lines[start..end];
+
+        let old_start = old_position(&lines[..start]);
+        let new_start = new_position(&lines[..start]);
+        let old_len = hunk
+            .iter()
+            .filter(|line| !matches!(line, Line::Added(_)))
+            .count();
+        let new_len = hunk
+            .iter()
+            .filter(|line| !matches!(line, Line::Removed(_)))
+            .count();
+        diff += &format!(
+            "{}\n",
+            format!(
+                "@@ -{},{} +{},{} @@",
+                old_start + usize::from(old_len > 0),
+                old_len,
+                new_start + usize::from(new_len > 0),
+                new_len
+            )
+            .cyan()
+        );
+        for line in hunk {
+            match *line {
+                Line::Same(i, _) => diff += &format!(" {}\n", old[i]),
+                Line::Removed(i) => diff += &format!("{}\n", format!("-{}", old[i]).red()),
+                Line::Added(j) => diff += &format!("{}\n", format!("+{}", new[j]).green()),
+            }
+        }
+
+        hunk_start = hunk_end + 1;
+    }
+
+    diff
+}
+
+/// Returns the lines of `old` and `new` matched by their longest common subsequence.
+fn diff_lines(old: &[&str], new: &[&str]) -> Vec<Line> {
+    // lengths[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::with_capacity(old.len().max(new.len()));
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(Line::Same(i, j));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lengths[i + 1][j] >= lengths[i][j + 1]) {
+            lines.push(Line::Removed(i));
+            i += 1;
+        } else {
+            lines.push(Line::Added(j));
+            j += 1;
+        }
+    }
+
+    lines
+}
+
+/// Returns the number of lines of the old file in `lines`.
+fn old_position(lines: &[Line]) -> usize {
+    lines
+        .iter()
+        .filter(|line| !matches!(line, Line::Added(_)))
+        .count()
+}
+
+/// Returns the number of lines of the new file in `lines`.
+fn new_position(lines: &[Line]) -> usize {
+    lines
+        .iter()
+        .filter(|line| !matches!(line, Line::Removed(_)))
+        .count()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn unified_diff() {
+        colored::control::set_override(false);
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\n";
+        let new = "a\nb\nC\nd\ne\nf\ng\nh\ni\nj\nk\nl\nm\n";
+
+        assert_eq!(
+            unified("./schema/src/lib.rs", old, new),
+            "--- a/schema/src/lib.rs
++++ b/schema/src/lib.rs
+@@ -1,6 +1,6 @@
+ a
+ b
+-c
++C
+ d
+ e
+ f
+@@ -10,3 +10,4 @@
+ j
+ k
+ l
++m
+"
+        );
+        assert_eq!(
+            unified("awto.toml", "", "[database]\n"),
+            "--- a/awto.toml\n+++ b/awto.toml\n@@ -0,0 +1,1 @@\n+[database]\n"
+        );
+        assert_eq!(unified("awto.toml", old, old), "");
+    }
+}
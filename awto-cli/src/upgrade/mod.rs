@@ -0,0 +1,240 @@
+use std::{fmt, path::Path, str::FromStr};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use clap::Parser;
+use log::{info, warn};
+use tokio::fs;
+
+use crate::{
+    cancel::Cancellation,
+    compile::{recompile_packages, GENERATED_HEADER_PREFIX},
+    error::Error,
+    Runnable,
+};
+
+use self::steps::{ProjectFiles, Step, STEPS};
+
+mod diff;
+mod steps;
+
+/// Upgrades the project to this version of the cli
+#[derive(Parser)]
+pub struct Upgrade {
+    /// Version of the cli which generated the project, read from the generated packages by default
+    #[clap(long)]
+    pub from: Option<Version>,
+    /// Prints the planned edits as diffs without applying them
+    #[clap(long)]
+    pub dry_run: bool,
+    /// Keeps regenerated Rust code unformatted
+    #[clap(long)]
+    pub no_format: bool,
+    /// Prints more information
+    #[clap(short, long)]
+    pub verbose: bool,
+    /// Number of warnings reported by the run
+    #[clap(skip)]
+    pub warnings: usize,
+}
+
+#[async_trait]
+impl Runnable for Upgrade {
+    async fn run(&mut self, cancellation: &Cancellation) -> Result<()> {
+        let from = match self.from {
+            Some(from) => from,
+            None => generated_version(Path::new("./awto"))
+                .await?
+                .ok_or(Error::UnknownVersion)?,
+        };
+        let current = Version::current();
+        if from >= current {
+            info!("project is up to date with awto-cli v{}", current);
+            return Ok(());
+        }
+
+        info!("upgrading project from awto-cli v{} to v{}", from, current);
+        let steps: Vec<&Step> = STEPS
+            .iter()
+            .filter(|step| step.version > from && step.version <= current)
+            .collect();
+        let mut files = ProjectFiles::new(".");
+        for step in &steps {
+            info!("v{}: {}", step.version, step.description);
+            for rewrite in step.rewrites {
+                rewrite.apply(&mut files).await?;
+            }
+        }
+
+        if self.dry_run {
+            for (path, file) in files.changed() {
+                out!(
+                    "{}",
+                    diff::unified(
+                        path,
+                        file.original.as_deref().unwrap_or_default(),
+                        &file.contents
+                    )
+                );
+            }
+            print_follow_ups(&steps);
+            info!("dry run, no files were changed");
+            return Ok(());
+        }
+
+        // Files are written all together once started, as a project upgraded halfway does not compile
+        cancellation.check()?;
+        for (path, backup) in files.write(from).await? {
+            match backup {
+                Some(backup) => info!("edited '{}', the original is kept in '{}'", path, backup),
+                None => info!("created '{}'", path),
+            }
+        }
+        recompile_packages(self.no_format, cancellation, &mut self.warnings).await?;
+        info!("upgraded project to awto-cli v{}", current);
+        print_follow_ups(&steps);
+
+        Ok(())
+    }
+
+    fn is_verbose(&self) -> bool {
+        self.verbose
+    }
+
+    fn warnings(&self) -> usize {
+        self.warnings
+    }
+}
+
+/// Prints the changes of `steps` which could not be made automatically.
+fn print_follow_ups(steps: &[&Step]) {
+    for step in steps {
+        for follow_up in step.follow_ups {
+            warn!("manual follow-up for v{}: {}", step.version, follow_up);
+        }
+    }
+}
+
+/// A version of the cli, such as `0.1.2`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl Version {
+    pub const fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Version {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Returns the version of the running cli.
+    pub fn current() -> Self {
+        env!("CARGO_PKG_VERSION")
+            .parse()
+            .expect("version of the cli is valid")
+    }
+}
+
+/// Parses versions such as `0.1.2` or `v0.1.2`, ignoring pre-release and build metadata.
+impl FromStr for Version {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid version '{}', expected a version such as 0.1.2", s);
+        let version = s.trim().trim_start_matches('v');
+        let version = version.split(['-', '+']).next().unwrap_or_default();
+        let parts = version
+            .split('.')
+            .map(|part| part.parse::<u64>().map_err(|_| invalid()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        match parts.as_slice() {
+            [major, minor, patch] => Ok(Version::new(*major, *minor, *patch)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Returns the oldest version of the cli which generated the packages in `awto_dir`,
+/// read from the header of their `src/lib.rs`, or `None` if there are no generated packages.
+async fn generated_version(awto_dir: &Path) -> Result<Option<Version>> {
+    if !awto_dir.is_dir() {
+        return Ok(None);
+    }
+
+    let mut oldest: Option<Version> = None;
+    let mut entries = fs::read_dir(awto_dir)
+        .await
+        .with_context(|| format!("could not read directory '{}'", awto_dir.display()))?;
+    while let Some(entry) = entries.next_entry().await? {
+        let lib = fs::read_to_string(entry.path().join("src/lib.rs"))
+            .await
+            .unwrap_or_default();
+        let version = lib
+            .strip_prefix(GENERATED_HEADER_PREFIX)
+            .and_then(|header| header.strip_prefix(" v"))
+            .and_then(|header| header.split_whitespace().next())
+            .and_then(|version| version.parse::<Version>().ok());
+        if let Some(version) = version {
+            oldest = Some(oldest.map_or(version, |oldest| oldest.min(version)));
+        }
+    }
+
+    Ok(oldest)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_versions() {
+        assert_eq!("0.1.2".parse(), Ok(Version::new(0, 1, 2)));
+        assert_eq!("v1.10.0-beta.1".parse(), Ok(Version::new(1, 10, 0)));
+        assert!("0.1".parse::<Version>().is_err());
+        assert!("latest".parse::<Version>().is_err());
+        assert!(Version::new(0, 1, 10) > Version::new(0, 1, 9));
+        assert_eq!(Version::new(0, 1, 2).to_string(), "0.1.2");
+    }
+
+    #[tokio::test]
+    async fn read_generated_version() {
+        let dir = std::env::temp_dir().join(format!("awto-cli-upgrade-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir).await;
+        assert_eq!(generated_version(&dir).await.unwrap(), None);
+
+        for (package, version) in [("database", "0.1.1"), ("protobuf", "0.1.0"), ("other", "")] {
+            fs::create_dir_all(dir.join(package).join("src"))
+                .await
+                .unwrap();
+            let lib = if version.is_empty() {
+                "pub fn hand_written() {}\n".to_string()
+            } else {
+                format!(
+                    "{} v{}\n\npub use sea_orm;\n",
+                    GENERATED_HEADER_PREFIX, version
+                )
+            };
+            fs::write(dir.join(package).join("src/lib.rs"), lib)
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            generated_version(&dir).await.unwrap(),
+            Some(Version::new(0, 1, 0))
+        );
+
+        fs::remove_dir_all(dir).await.unwrap();
+    }
+}
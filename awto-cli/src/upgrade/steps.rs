@@ -0,0 +1,334 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+use proc_macro2::{Delimiter, LineColumn, TokenStream, TokenTree};
+use tokio::fs;
+
+use crate::{config::CONFIG_PATH, schema::SCHEMA_LIB_PATH, util::CargoFile};
+
+use super::Version;
+
+/// A change of a release of the cli, applied by `awto upgrade` to projects generated by an older version.
+pub struct Step {
+    /// Version of the cli introducing the change.
+    pub version: Version,
+    pub description: &'static str,
+    /// Edits of the project which are made automatically.
+    pub rewrites: &'static [Rewrite],
+    /// Changes which cannot be made automatically, printed at the end of the upgrade.
+    pub follow_ups: &'static [&'static str],
+}
+
+/// Steps of every release changing the layout of projects or the spelling of attributes, oldest first.
+///
+/// Releases with such changes append a step, which is applied to projects generated by an older version.
+pub const STEPS: &[Step] = &[Step {
+    version: Version::new(0, 1, 2),
+    description: "database packages of workspaces with a root package `<app>` are named `<app>-database`",
+    rewrites: &[Rewrite::KeepDatabasePackageName],
+    follow_ups: &[
+        "generated packages are cleaned on every compile, except for the `src/ext` directory of database packages; move code added to generated packages by hand there",
+    ],
+}];
+
+/// An automatic edit of the project.
+pub enum Rewrite {
+    /// Renames the argument `from` of `#[awto(...)]` attributes in the schema lib to `to`, such as `pk` to `primary_key`.
+    #[allow(dead_code)]
+    RenameArg {
+        from: &'static str,
+        to: &'static str,
+    },
+    /// Keeps the `database` package of projects with a root package, which are now named after it,
+    /// by setting `package_name` in the `[database]` section of `awto.toml`.
+    KeepDatabasePackageName,
+}
+
+impl Rewrite {
+    pub async fn apply(&self, files: &mut ProjectFiles) -> Result<()> {
+        match self {
+            Rewrite::RenameArg { from, to } => {
+                let source = files.read(SCHEMA_LIB_PATH).await?;
+                *source = rename_awto_arg(source, from, to)?;
+            }
+            Rewrite::KeepDatabasePackageName => {
+                let root_package = CargoFile::load(files.path("./Cargo.toml"))
+                    .await
+                    .ok()
+                    .and_then(|cargo_file| cargo_file.package);
+                if root_package.is_none() || !files.path("./awto/database").is_dir() {
+                    return Ok(());
+                }
+
+                let config = files.read(CONFIG_PATH).await?;
+                let mut doc: toml_edit::Document = config
+                    .parse()
+                    .with_context(|| format!("could not parse '{}'", CONFIG_PATH))?;
+                if doc["database"]["package_name"].is_none() {
+                    if doc["database"].is_none() {
+                        doc["database"] = toml_edit::table();
+                    }
+                    doc["database"]["package_name"] = toml_edit::value("database");
+                    *config = doc.to_string();
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Files of the project edited by the steps of an upgrade, which are only written once every step is applied.
+pub struct ProjectFiles {
+    root: PathBuf,
+    files: BTreeMap<String, EditedFile>,
+}
+
+/// A file edited by an upgrade.
+pub struct EditedFile {
+    /// Contents of the file before the upgrade, or `None` if it is created by the upgrade.
+    pub original: Option<String>,
+    pub contents: String,
+}
+
+impl ProjectFiles {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        ProjectFiles {
+            root: root.into(),
+            files: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the path of `path` relative to the root of the project.
+    pub fn path(&self, path: &str) -> PathBuf {
+        self.root.join(path)
+    }
+
+    /// Returns the contents of the file at `path`, including the edits of previous steps.
+    ///
+    /// Files which do not exist yet are empty, and are created if they are edited.
+    pub async fn read(&mut self, path: &str) -> Result<&mut String> {
+        if !self.files.contains_key(path) {
+            let full_path = self.path(path);
+            let original =
+                if full_path.exists() {
+                    Some(fs::read_to_string(&full_path).await.with_context(|| {
+                        format!("could not read file '{}'", full_path.display())
+                    })?)
+                } else {
+                    None
+                };
+            let contents = original.clone().unwrap_or_default();
+            self.files
+                .insert(path.to_string(), EditedFile { original, contents });
+        }
+
+        Ok(&mut self.files.get_mut(path).unwrap().contents)
+    }
+
+    /// Returns the files whose contents were changed by the upgrade, by path.
+    pub fn changed(&self) -> impl Iterator<Item = (&str, &EditedFile)> {
+        self.files
+            .iter()
+            .filter(|(_, file)| file.original.as_ref() != Some(&file.contents))
+            .map(|(path, file)| (path.as_str(), file))
+    }
+
+    /// Writes the changed files, after copying each existing file to a backup named after the version upgraded `from`,
+    /// such as `lib.rs.v0.1.1.bak`.
+    ///
+    /// Returns the path of each written file along with its backup.
+    pub async fn write(&self, from: Version) -> Result<Vec<(String, Option<String>)>> {
+        let mut written = Vec::new();
+        for (path, file) in self.changed() {
+            let full_path = self.path(path);
+            let backup = match &file.original {
+                Some(original) => {
+                    let backup = format!("{}.v{}.bak", path, from);
+                    write_file(&self.path(&backup), original).await?;
+                    Some(backup)
+                }
+                None => None,
+            };
+            write_file(&full_path, &file.contents).await?;
+            written.push((path.to_string(), backup));
+        }
+
+        Ok(written)
+    }
+}
+
+async fn write_file(path: &Path, contents: &str) -> Result<()> {
+    fs::write(path, contents)
+        .await
+        .with_context(|| format!("could not write file '{}'", path.display()))
+}
+
+/// Returns `source` with the arguments `from` of its `#[awto(...)]` attributes renamed to `to`.
+///
+/// The arguments are found in the tokens of the source, and only their identifiers are replaced,
+/// so the formatting and comments of the source are kept.
+fn rename_awto_arg(source: &str, from: &str, to: &str) -> Result<String> {
+    let tokens: TokenStream = source
+        .parse()
+        .map_err(|err| anyhow!("could not parse the schema lib: {}", err))?;
+    let mut positions = Vec::new();
+    collect_awto_args(tokens, from, &mut positions);
+
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let mut renamed = source.to_string();
+    for position in positions.iter().rev() {
+        let line_start = line_starts[position.line - 1];
+        let start = line_start
+            + source[line_start..]
+                .char_indices()
+                .nth(position.column)
+                .map(|(i, _)| i)
+                .unwrap_or_default();
+        renamed.replace_range(start..start + from.len(), to);
+    }
+
+    Ok(renamed)
+}
+
+/// Adds the start of each argument `name` of the `#[awto(...)]` attributes in `tokens` to `positions`, in source order.
+fn collect_awto_args(tokens: TokenStream, name: &str, positions: &mut Vec<LineColumn>) {
+    let tokens: Vec<TokenTree> = tokens.into_iter().collect();
+    for (i, token) in tokens.iter().enumerate() {
+        let group = match token {
+            TokenTree::Group(group) => group,
+            _ => continue,
+        };
+
+        let is_attribute = group.delimiter() == Delimiter::Bracket
+            && matches!(i.checked_sub(1).map(|i| &tokens[i]), Some(TokenTree::Punct(punct)) if punct.as_char() == '#');
+        let inner: Vec<TokenTree> = group.stream().into_iter().collect();
+        match inner.as_slice() {
+            [TokenTree::Ident(path), TokenTree::Group(args)]
+                if is_attribute && path == "awto" && args.delimiter() == Delimiter::Parenthesis =>
+            {
+                let mut is_arg_start = true;
+                for token in args.stream() {
+                    match &token {
+                        TokenTree::Punct(punct) if punct.as_char() == ',' => is_arg_start = true,
+                        TokenTree::Ident(ident) if is_arg_start && ident == name => {
+                            positions.push(ident.span().start());
+                            is_arg_start = false;
+                        }
+                        _ => is_arg_start = false,
+                    }
+                }
+            }
+            _ => collect_awto_args(group.stream(), name, positions),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rename_args() {
+        let source = r#"schema! {
+    // Prices are in cents
+    #[database_table]
+    pub struct Product {
+        #[awto(pk)]
+        pub id: Uuid,
+        #[awto(max_len = 120, pk, references = (pk, "id"))]
+        pub émoji_name: String,
+    }
+}
+"#;
+        let renamed = rename_awto_arg(source, "pk", "primary_key").unwrap();
+        assert_eq!(
+            renamed,
+            source
+                .replace("awto(pk)", "awto(primary_key)")
+                .replace("120, pk,", "120, primary_key,")
+        );
+        assert_eq!(
+            rename_awto_arg(&renamed, "pk", "primary_key").unwrap(),
+            renamed
+        );
+    }
+
+    #[tokio::test]
+    async fn keep_database_package_name() {
+        let dir =
+            std::env::temp_dir().join(format!("awto-cli-upgrade-steps-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir).await;
+        fs::create_dir_all(dir.join("awto/database")).await.unwrap();
+        fs::write(
+            dir.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"schema\"]\n",
+        )
+        .await
+        .unwrap();
+
+        // Virtual workspaces keep the `database` name
+        let mut files = ProjectFiles::new(&dir);
+        Rewrite::KeepDatabasePackageName
+            .apply(&mut files)
+            .await
+            .unwrap();
+        assert_eq!(files.changed().count(), 0);
+
+        fs::write(
+            dir.join("Cargo.toml"),
+            "[package]\nname = \"shop\"\n\n[workspace]\nmembers = [\"schema\"]\n",
+        )
+        .await
+        .unwrap();
+        fs::write(dir.join("awto.toml"), "[lint]\nmax_columns = 40\n")
+            .await
+            .unwrap();
+        let mut files = ProjectFiles::new(&dir);
+        Rewrite::KeepDatabasePackageName
+            .apply(&mut files)
+            .await
+            .unwrap();
+        let changed: Vec<_> = files
+            .changed()
+            .map(|(path, file)| (path, file.contents.as_str()))
+            .collect();
+        assert_eq!(
+            changed,
+            [(
+                CONFIG_PATH,
+                "[lint]\nmax_columns = 40\n\n[database]\npackage_name = \"database\"\n"
+            )]
+        );
+
+        let written = files.write(Version::new(0, 1, 1)).await.unwrap();
+        assert_eq!(
+            written,
+            [(
+                CONFIG_PATH.to_string(),
+                Some(format!("{}.v0.1.1.bak", CONFIG_PATH))
+            )]
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("awto.toml.v0.1.1.bak"))
+                .await
+                .unwrap(),
+            "[lint]\nmax_columns = 40\n"
+        );
+
+        // Applying the step again leaves the package name set by the user
+        let mut files = ProjectFiles::new(&dir);
+        Rewrite::KeepDatabasePackageName
+            .apply(&mut files)
+            .await
+            .unwrap();
+        assert_eq!(files.changed().count(), 0);
+
+        fs::remove_dir_all(dir).await.unwrap();
+    }
+}
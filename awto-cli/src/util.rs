@@ -1,12 +1,22 @@
-use std::{io::SeekFrom, path::Path};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    env,
+    ffi::OsString,
+    io::SeekFrom,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
 
 use anyhow::{anyhow, Context, Result};
 use serde::Deserialize;
+use tokio::sync::Mutex;
 use tokio::{
     fs,
     io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
 };
-use toml_edit::{Document, Value};
+use toml_edit::{Array, Document, Item, Value};
+
+use crate::error::Error;
 
 #[derive(Deserialize, Clone, Debug)]
 pub struct CargoFile {
@@ -21,21 +31,654 @@ pub struct CargoPackage {
 
 #[derive(Deserialize, Clone, Debug)]
 pub struct CargoWorkspace {
+    #[serde(default)]
     pub members: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Feature resolver of the workspace, which the nested workspace of generated packages uses too.
+    pub resolver: Option<String>,
+    /// Keys members inherit with `<key>.workspace = true`, from `[workspace.package]`.
+    #[serde(default)]
+    pub package: toml::value::Table,
+    /// Lints members inherit with `workspace = true` in their `[lints]` section, from `[workspace.lints]`.
+    pub lints: Option<toml::value::Table>,
 }
 
 impl CargoFile {
     pub async fn load(path: impl AsRef<Path>) -> Result<CargoFile> {
         let bytes = fs::read(path).await.context("file not found")?;
-        Ok(toml::from_slice(&bytes).context("Cargo.toml file corrupt")?)
+        toml::from_slice(&bytes).context("Cargo.toml file corrupt")
+    }
+}
+
+/// The cargo workspace the cli runs in, loaded once per invocation and shared by the compile commands.
+///
+/// Holds the root manifest, the members with their globs expanded, the packages of the members by name,
+/// and the target directory. Members added with [`add_member`](Self::add_member) are written to the root
+/// `Cargo.toml` file at once by [`flush`](Self::flush).
+///
+/// The generated packages are members of the nested workspace in `./awto` instead, when it is
+/// managed by [`sync_nested_workspace`].
+#[derive(Clone, Debug)]
+pub struct WorkspaceContext {
+    root: PathBuf,
+    manifest: CargoFile,
+    /// Whether the generated packages are members of the nested workspace in `./awto`.
+    nested: bool,
+    /// Paths of the members relative to the root, such as `awto/database`, including the members of the nested workspace.
+    members: BTreeSet<String>,
+    /// Manifests of the members by their path.
+    member_manifests: BTreeMap<String, CargoFile>,
+    /// Paths of the members by the name of their package.
+    packages: BTreeMap<String, String>,
+    target_dir: PathBuf,
+    /// Members added since the root manifest was last written.
+    pending: Vec<String>,
+}
+
+impl WorkspaceContext {
+    /// Loads the workspace whose root `Cargo.toml` file is in `root`, along with the manifests of its members.
+    ///
+    /// Members without a readable manifest, such as packages which were not generated yet, have no package.
+    pub async fn load(root: impl AsRef<Path>) -> Result<WorkspaceContext> {
+        let root = root.as_ref().to_path_buf();
+        let manifest = CargoFile::load(root.join("Cargo.toml"))
+            .await
+            .with_context(|| {
+                format!(
+                    "could not load root Cargo.toml file from '{}'",
+                    root.display()
+                )
+            })?;
+
+        let mut members = match &manifest.workspace {
+            Some(workspace) => expand_members(&root, workspace).await?,
+            None => BTreeSet::new(),
+        };
+        let nested = is_nested_workspace(&root).await;
+        if nested {
+            let nested_root = root.join(NESTED_WORKSPACE_DIR);
+            let nested_manifest = CargoFile::load(nested_root.join("Cargo.toml"))
+                .await
+                .with_context(|| {
+                    format!(
+                        "could not load nested Cargo.toml file from '{}'",
+                        nested_root.display()
+                    )
+                })?;
+            if let Some(workspace) = &nested_manifest.workspace {
+                for member in expand_members(&nested_root, workspace).await? {
+                    members.insert(format!("{}/{}", NESTED_WORKSPACE_DIR, member));
+                }
+            }
+        }
+
+        let mut member_manifests = BTreeMap::new();
+        let mut packages = BTreeMap::new();
+        if let Some(package) = &manifest.package {
+            packages.insert(package.name.clone(), String::new());
+        }
+        for member in &members {
+            if let Ok(member_manifest) = CargoFile::load(root.join(member).join("Cargo.toml")).await
+            {
+                if let Some(package) = &member_manifest.package {
+                    packages.insert(package.name.clone(), member.clone());
+                }
+                member_manifests.insert(member.clone(), member_manifest);
+            }
+        }
+
+        let target_dir = resolve_target_dir(&root, nested, env::var_os("CARGO_TARGET_DIR")).await;
+
+        Ok(WorkspaceContext {
+            root,
+            manifest,
+            nested,
+            members,
+            member_manifests,
+            packages,
+            target_dir,
+            pending: Vec::new(),
+        })
+    }
+
+    /// Returns the package of the root `Cargo.toml` file, which virtual workspaces do not have.
+    pub fn root_package(&self) -> Option<&CargoPackage> {
+        self.manifest.package.as_ref()
+    }
+
+    /// Returns the `[workspace]` section of the root `Cargo.toml` file.
+    pub fn root_workspace(&self) -> Option<&CargoWorkspace> {
+        self.manifest.workspace.as_ref()
+    }
+
+    /// Returns the directory of the package named `name`, relative to the root of the workspace.
+    ///
+    /// The directory of the root package is empty.
+    pub fn package_by_name(&self, name: &str) -> Option<&Path> {
+        self.packages.get(name).map(Path::new)
+    }
+
+    /// Returns the manifest of the member at `path`, such as `./schema`.
+    pub fn member_manifest(&self, path: &str) -> Option<&CargoFile> {
+        self.member_manifests.get(&normalize_member(path))
+    }
+
+    /// Returns whether the package at `path`, such as `./awto/database`, is a member of the workspace,
+    /// including members added but not yet written.
+    pub fn is_member(&self, path: &str) -> bool {
+        let path = normalize_member(path);
+        self.members.contains(&path) || self.pending.contains(&path)
+    }
+
+    /// Adds the package at `path` to the members of the workspace unless it is one already,
+    /// to be written to the root `Cargo.toml` file by [`flush`](Self::flush).
+    pub fn add_member(&mut self, path: &str) {
+        if !self.is_member(path) {
+            self.pending.push(normalize_member(path));
+        }
+    }
+
+    /// Returns whether the generated packages are members of the nested workspace in `./awto`.
+    pub fn is_nested(&self) -> bool {
+        self.nested
+    }
+
+    /// Returns the directory cargo builds the generated packages into.
+    pub fn target_dir(&self) -> &Path {
+        &self.target_dir
+    }
+
+    /// Writes the members added since the last flush to the root `Cargo.toml` file, editing it once.
+    ///
+    /// Members in `./awto` are written to the nested `Cargo.toml` file instead when the generated packages
+    /// were moved into the nested workspace, which may have happened since the workspace was loaded.
+    pub async fn flush(&mut self) -> Result<()> {
+        let _lock = workspace_lock().lock().await;
+        let nested = is_nested_workspace(&self.root).await;
+        if nested != self.nested {
+            self.nested = nested;
+            self.target_dir =
+                resolve_target_dir(&self.root, nested, env::var_os("CARGO_TARGET_DIR")).await;
+        }
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+
+        let (nested_pending, root_pending): (Vec<_>, Vec<_>) = self
+            .pending
+            .iter()
+            .map(String::as_str)
+            .partition(|member| nested && nested_member(member).is_some());
+        let nested_pending: Vec<_> = nested_pending
+            .into_iter()
+            .filter_map(nested_member)
+            .map(str::to_string)
+            .collect();
+        let root_pending: Vec<_> = root_pending.into_iter().map(str::to_string).collect();
+        for (manifest, pending) in [
+            (self.root.join("Cargo.toml"), &root_pending),
+            (nested_manifest_path(&self.root), &nested_pending),
+        ] {
+            if pending.is_empty() {
+                continue;
+            }
+            insert_workspace_members(&manifest, pending)
+                .await
+                .map_err(|source| Error::Workspace {
+                    package: self.pending.join("', '"),
+                    source,
+                })?;
+        }
+        self.members.extend(self.pending.drain(..));
+
+        Ok(())
+    }
+}
+
+/// Directory of the generated packages, which is a workspace of its own in nested mode.
+pub const NESTED_WORKSPACE_DIR: &str = "awto";
+
+/// First line of the `Cargo.toml` file of the nested workspace, telling it is managed by the cli.
+const NESTED_WORKSPACE_HEADER: &str =
+    "# Workspace of the packages generated by awto, managed by `nested_workspace` in awto.toml";
+
+/// Returns the path of the `Cargo.toml` file of the nested workspace of the workspace in `root`.
+fn nested_manifest_path(root: &Path) -> PathBuf {
+    root.join(NESTED_WORKSPACE_DIR).join("Cargo.toml")
+}
+
+/// Returns whether the generated packages of the workspace in `root` are members of the nested workspace.
+async fn is_nested_workspace(root: &Path) -> bool {
+    fs::read_to_string(nested_manifest_path(root))
+        .await
+        .map(|manifest| manifest.starts_with(NESTED_WORKSPACE_HEADER))
+        .unwrap_or(false)
+}
+
+/// Returns the member `path` of the root workspace relative to the nested workspace, when it is inside it.
+fn nested_member(path: &str) -> Option<&str> {
+    path.trim_start_matches("./")
+        .strip_prefix(NESTED_WORKSPACE_DIR)?
+        .strip_prefix('/')
+        .map(|member| member.trim_end_matches('/'))
+        .filter(|member| !member.is_empty())
+}
+
+/// Moves the generated packages of the workspace in `root` into the nested workspace in `./awto` when `nested`,
+/// or back into the root workspace otherwise.
+///
+/// In nested mode, the members in `./awto` move from the root `Cargo.toml` file to the nested one, which the root
+/// workspace excludes with `exclude = ["awto"]`. The nested workspace gets the `resolver`, `[workspace.package]` and
+/// `[workspace.lints]` of the root workspace, which generated packages inherit from. Leaving nested mode moves the
+/// members back and deletes the nested `Cargo.toml` and `Cargo.lock` files. Both are no-ops once done.
+pub async fn sync_nested_workspace(root: &Path, nested: bool) -> Result<()> {
+    let _lock = workspace_lock().lock().await;
+    let synced = if nested {
+        nest_workspace(root).await
+    } else if is_nested_workspace(root).await {
+        unnest_workspace(root).await
+    } else {
+        return Ok(());
+    };
+    synced.map_err(|source| Error::NestedWorkspace { source })?;
+
+    Ok(())
+}
+
+async fn nest_workspace(root: &Path) -> Result<()> {
+    let nested_path = nested_manifest_path(root);
+    let mut members = BTreeSet::new();
+    let mut exclude = BTreeSet::new();
+    if let Ok(nested_manifest) = fs::read_to_string(&nested_path).await {
+        if !nested_manifest.starts_with(NESTED_WORKSPACE_HEADER) {
+            return Err(anyhow!(
+                "'{}' was not written by awto, move it out of the directory of the generated packages",
+                nested_path.display()
+            ));
+        }
+        let nested_manifest: CargoFile =
+            toml::from_str(&nested_manifest).context("nested Cargo.toml file corrupt")?;
+        if let Some(workspace) = nested_manifest.workspace {
+            members.extend(workspace.members);
+            exclude.extend(workspace.exclude);
+        }
+    }
+
+    let root_path = root.join("Cargo.toml");
+    let buffer = fs::read_to_string(&root_path)
+        .await
+        .context("could not open root Cargo.toml file")?;
+    let mut doc: Document = buffer
+        .parse()
+        .context("could not parse root Cargo.toml file")?;
+    let workspace = doc
+        .as_table_mut()
+        .get_mut("workspace")
+        .and_then(|workspace| workspace.as_table_like_mut())
+        .ok_or_else(|| anyhow!("workspace does not exist in root Cargo.toml file"))?;
+    if let Some(root_members) = workspace
+        .get_mut("members")
+        .and_then(|members| members.as_array_mut())
+    {
+        take_nested_members(root_members, &mut members);
+    }
+    if !workspace.contains_key("exclude") {
+        workspace.insert("exclude", Item::Value(Value::Array(Array::new())));
+    }
+    let root_exclude = workspace
+        .get_mut("exclude")
+        .and_then(|exclude| exclude.as_array_mut())
+        .ok_or_else(|| anyhow!("workspace exclude of root Cargo.toml file is not an array"))?;
+    take_nested_members(root_exclude, &mut exclude);
+    insert_workspace_member(root_exclude, NESTED_WORKSPACE_DIR);
+
+    let root_manifest = doc.to_string();
+    if root_manifest != buffer {
+        fs::write(&root_path, &root_manifest).await?;
+    }
+
+    let root_workspace = toml::from_str::<CargoFile>(&root_manifest)
+        .context("Cargo.toml file corrupt")?
+        .workspace;
+    let nested_manifest = nested_workspace_manifest(&members, &exclude, root_workspace.as_ref());
+    if fs::read_to_string(&nested_path).await.ok().as_deref() != Some(nested_manifest.as_str()) {
+        fs::create_dir_all(root.join(NESTED_WORKSPACE_DIR)).await?;
+        fs::write(&nested_path, nested_manifest).await?;
+    }
+
+    Ok(())
+}
+
+async fn unnest_workspace(root: &Path) -> Result<()> {
+    let nested_path = nested_manifest_path(root);
+    let nested_workspace = CargoFile::load(&nested_path)
+        .await
+        .context("could not load nested Cargo.toml file")?
+        .workspace;
+    let (members, exclude) = nested_workspace
+        .map(|workspace| (workspace.members, workspace.exclude))
+        .unwrap_or_default();
+
+    let root_path = root.join("Cargo.toml");
+    let buffer = fs::read_to_string(&root_path)
+        .await
+        .context("could not open root Cargo.toml file")?;
+    let mut doc: Document = buffer
+        .parse()
+        .context("could not parse root Cargo.toml file")?;
+    let workspace = doc
+        .as_table_mut()
+        .get_mut("workspace")
+        .and_then(|workspace| workspace.as_table_like_mut())
+        .ok_or_else(|| anyhow!("workspace does not exist in root Cargo.toml file"))?;
+    if !workspace.contains_key("members") {
+        workspace.insert("members", Item::Value(Value::Array(Array::new())));
+    }
+    let root_members = workspace
+        .get_mut("members")
+        .and_then(|members| members.as_array_mut())
+        .ok_or_else(|| anyhow!("workspace members of root Cargo.toml file is not an array"))?;
+    for member in &members {
+        insert_workspace_member(
+            root_members,
+            &format!("{}/{}", NESTED_WORKSPACE_DIR, member),
+        );
+    }
+    if let Some(root_exclude) = workspace
+        .get_mut("exclude")
+        .and_then(|exclude| exclude.as_array_mut())
+    {
+        let index = root_exclude.iter().position(|excluded| {
+            excluded.as_str().map(normalize_member).as_deref() == Some(NESTED_WORKSPACE_DIR)
+        });
+        if let Some(index) = index {
+            root_exclude.remove(index);
+        }
+        for excluded in &exclude {
+            insert_workspace_member(
+                root_exclude,
+                &format!("{}/{}", NESTED_WORKSPACE_DIR, excluded),
+            );
+        }
+        if root_exclude.is_empty() {
+            workspace.remove("exclude");
+        }
+    }
+    fs::write(&root_path, doc.to_string()).await?;
+
+    fs::remove_file(&nested_path).await?;
+    match fs::remove_file(root.join(NESTED_WORKSPACE_DIR).join("Cargo.lock")).await {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Removes the entries of `array` inside the nested workspace, adding them to `nested` relative to it.
+fn take_nested_members(array: &mut Array, nested: &mut BTreeSet<String>) {
+    let mut index = 0;
+    while index < array.len() {
+        match array
+            .get(index)
+            .and_then(Value::as_str)
+            .and_then(nested_member)
+        {
+            Some(member) => {
+                nested.insert(member.to_string());
+                array.remove(index);
+            }
+            None => index += 1,
+        }
+    }
+}
+
+/// Returns the `Cargo.toml` file of the nested workspace of `members` and `exclude`, with the settings
+/// of the root `workspace` its members inherit.
+fn nested_workspace_manifest(
+    members: &BTreeSet<String>,
+    exclude: &BTreeSet<String>,
+    workspace: Option<&CargoWorkspace>,
+) -> String {
+    let strings = |entries: &BTreeSet<String>| {
+        toml::Value::Array(entries.iter().cloned().map(toml::Value::String).collect())
+    };
+    let mut section = toml::value::Table::new();
+    section.insert("members".to_string(), strings(members));
+    if !exclude.is_empty() {
+        section.insert("exclude".to_string(), strings(exclude));
+    }
+    if let Some(workspace) = workspace {
+        if let Some(resolver) = &workspace.resolver {
+            section.insert(
+                "resolver".to_string(),
+                toml::Value::String(resolver.clone()),
+            );
+        }
+        if !workspace.package.is_empty() {
+            section.insert(
+                "package".to_string(),
+                toml::Value::Table(workspace.package.clone()),
+            );
+        }
+        if let Some(lints) = &workspace.lints {
+            section.insert("lints".to_string(), toml::Value::Table(lints.clone()));
+        }
+    }
+    let manifest: toml::value::Table = vec![("workspace".to_string(), toml::Value::Table(section))]
+        .into_iter()
+        .collect();
+
+    format!(
+        "{}\n{}",
+        NESTED_WORKSPACE_HEADER,
+        toml::to_string(&manifest).expect("workspace is valid TOML")
+    )
+}
+
+/// Returns the member `path` relative to the root of the workspace, without a leading `./` or trailing `/`.
+fn normalize_member(path: &str) -> String {
+    path.trim_start_matches("./")
+        .trim_end_matches('/')
+        .to_string()
+}
+
+/// Returns the members of `workspace` in `root`, with their globs expanded and its excluded members removed.
+async fn expand_members(root: &Path, workspace: &CargoWorkspace) -> Result<BTreeSet<String>> {
+    let mut members = BTreeSet::new();
+    for pattern in &workspace.members {
+        members.extend(expand_member(root, pattern).await?);
+    }
+    for pattern in &workspace.exclude {
+        for excluded in expand_member(root, pattern).await? {
+            members.remove(&excluded);
+        }
     }
+
+    Ok(members)
 }
 
-pub async fn add_package_to_workspace(pkg: &str) -> Result<()> {
+/// Returns the members matching the member `pattern` of the root `Cargo.toml` file in `root`,
+/// such as `awto/*`, which only matches directories containing a `Cargo.toml` file.
+async fn expand_member(root: &Path, pattern: &str) -> Result<Vec<String>> {
+    let pattern = normalize_member(pattern);
+    if !pattern.contains(['*', '?']) {
+        return Ok(vec![pattern]);
+    }
+
+    let mut members = vec![String::new()];
+    for component in pattern.split('/') {
+        let mut expanded = Vec::new();
+        for member in members {
+            let join = |name: &str| {
+                if member.is_empty() {
+                    name.to_string()
+                } else {
+                    format!("{}/{}", member, name)
+                }
+            };
+            if !component.contains(['*', '?']) {
+                expanded.push(join(component));
+                continue;
+            }
+
+            let mut entries = match fs::read_dir(root.join(&member)).await {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            while let Some(entry) = entries.next_entry().await? {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if entry.file_type().await?.is_dir() && glob_matches(component, &name) {
+                    expanded.push(join(&name));
+                }
+            }
+        }
+        members = expanded;
+    }
+
+    members.retain(|member| root.join(member).join("Cargo.toml").is_file());
+    members.sort();
+
+    Ok(members)
+}
+
+/// Returns whether `name` matches the glob `pattern` of `*` and `?` wildcards.
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some('?') => {
+                p += 1;
+                n += 1;
+            }
+            Some(c) if *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Returns the target directory of the workspace in `root`, which is the `target_dir_env` variable
+/// `CARGO_TARGET_DIR` when set, the `build.target-dir` of the cargo config of the workspace, or `target`.
+///
+/// In `nested` mode, cargo builds in `./awto`, where it reads the cargo config of the nested workspace
+/// before the one of the root workspace, and defaults to `awto/target`.
+async fn resolve_target_dir(
+    root: &Path,
+    nested: bool,
+    target_dir_env: Option<OsString>,
+) -> PathBuf {
+    if let Some(target_dir) = target_dir_env {
+        return PathBuf::from(target_dir);
+    }
+
+    #[derive(Deserialize)]
+    struct CargoConfig {
+        build: Option<CargoBuildConfig>,
+    }
+
+    #[derive(Deserialize)]
+    struct CargoBuildConfig {
+        #[serde(rename = "target-dir")]
+        target_dir: Option<String>,
+    }
+
+    let build_dir = if nested {
+        root.join(NESTED_WORKSPACE_DIR)
+    } else {
+        root.to_path_buf()
+    };
+    for dir in [&build_dir, root] {
+        for config in [".cargo/config.toml", ".cargo/config"] {
+            let target_dir = fs::read(dir.join(config))
+                .await
+                .ok()
+                .and_then(|bytes| toml::from_slice::<CargoConfig>(&bytes).ok())
+                .and_then(|config| config.build?.target_dir);
+            if let Some(target_dir) = target_dir {
+                return dir.join(target_dir);
+            }
+        }
+    }
+
+    build_dir.join("target")
+}
+
+/// Removes the package at `pkg`, such as `awto/rest`, from the members of the workspace,
+/// or of the nested workspace when it is in there.
+pub async fn remove_package_from_workspace(pkg: &str) -> Result<()> {
+    let _lock = workspace_lock().lock().await;
+    let root = Path::new(".");
+    let removed = match nested_member(pkg) {
+        Some(member) if is_nested_workspace(root).await => {
+            remove_workspace_member(&nested_manifest_path(root), member).await
+        }
+        _ => remove_workspace_member(&root.join("Cargo.toml"), pkg).await,
+    };
+    removed.map_err(|source| Error::Workspace {
+        package: pkg.to_string(),
+        source,
+    })?;
+
+    Ok(())
+}
+
+/// Returns the lock held while editing the root `Cargo.toml` file,
+/// as packages are generated concurrently by `compile --all`.
+fn workspace_lock() -> &'static Mutex<()> {
+    static WORKSPACE_LOCK: OnceLock<Mutex<()>> = OnceLock::new();
+    WORKSPACE_LOCK.get_or_init(|| Mutex::new(()))
+}
+
+async fn remove_workspace_member(path: &Path, pkg: &str) -> Result<()> {
+    let buffer = fs::read_to_string(path)
+        .await
+        .context("could not open root Cargo.toml file")?;
+    let mut doc: Document = buffer
+        .parse()
+        .context("could not parse root Cargo.toml file")?;
+    let members = doc
+        .as_table_mut()
+        .get_mut("workspace")
+        .and_then(|workspace| workspace.as_table_like_mut())
+        .and_then(|workspace| workspace.get_mut("members"))
+        .and_then(|members| members.as_array_mut())
+        .ok_or_else(|| anyhow!("workspace does not exist in root Cargo.toml file"))?;
+
+    let index = members
+        .iter()
+        .position(|member| member.as_str() == Some(pkg));
+    if let Some(index) = index {
+        members.remove(index);
+        fs::write(path, doc.to_string()).await?;
+    }
+
+    Ok(())
+}
+
+async fn insert_workspace_members(path: &Path, pkgs: &[String]) -> Result<()> {
     let mut cargo_file = fs::OpenOptions::new()
         .read(true)
         .write(true)
-        .open("./Cargo.toml")
+        .open(path)
         .await
         .context("could not open root Cargo.toml file")?;
     let mut buffer = String::new();
@@ -51,61 +694,307 @@ pub async fn add_package_to_workspace(pkg: &str) -> Result<()> {
         .and_then(|members| members.as_array_mut())
         .ok_or_else(|| anyhow!("workspace does not exist in root Cargo.toml file"))?;
 
-    if !members.iter().any(|member| {
+    let mut changed = false;
+    for pkg in pkgs {
+        changed |= insert_workspace_member(members, pkg);
+    }
+
+    if changed {
+        cargo_file.set_len(0).await?;
+        cargo_file.seek(SeekFrom::Start(0)).await?;
+        cargo_file.write_all(doc.to_string().as_bytes()).await?;
+    }
+
+    Ok(())
+}
+
+/// Inserts `pkg` in the sorted `members`, returning whether it was not a member yet.
+fn insert_workspace_member(members: &mut Array, pkg: &str) -> bool {
+    if members.iter().any(|member| {
         member
             .as_str()
             .map(|member_str| member_str == pkg)
             .unwrap_or(false)
     }) {
-        let index = members
-            .iter()
-            .enumerate()
-            .find_map(|(i, member)| {
-                if member
-                    .as_str()
-                    .map(|member_str| member_str > pkg)
-                    .unwrap_or(false)
-                {
-                    Some(i)
-                } else {
-                    None
-                }
-            })
-            .unwrap_or_else(|| members.len());
-        let first_prefix = members
-            .get(0)
-            .and_then(|member| member.decor().prefix())
-            .map(|prefix| prefix.to_string());
-        let value: Value = pkg.into();
-        if first_prefix
-            .as_ref()
-            .map(|prefix| prefix.contains('\n'))
-            .unwrap_or(false)
-        {
-            members.insert_formatted(index, value.decorated(&first_prefix.unwrap(), ""));
-        } else if index == 0 {
-            if let Some(first_prefix) = members
-                .get(0)
-                .map(|member| member.decor())
-                .and_then(|decor| decor.prefix())
-                .map(|prefix| prefix.to_string())
+        return false;
+    }
+
+    let index = members
+        .iter()
+        .enumerate()
+        .find_map(|(i, member)| {
+            if member
+                .as_str()
+                .map(|member_str| member_str > pkg)
+                .unwrap_or(false)
             {
-                members.insert_formatted(index, value.decorated(&first_prefix, ""));
+                Some(i)
             } else {
-                members.insert_formatted(index, value.decorated("", ""));
-            }
-
-            if let Some(member) = members.get_mut(index + 1) {
-                member.decor_mut().set_prefix(" ")
+                None
             }
+        })
+        .unwrap_or_else(|| members.len());
+    let first_prefix = members
+        .get(0)
+        .and_then(|member| member.decor().prefix())
+        .map(|prefix| prefix.to_string());
+    let value: Value = pkg.into();
+    if first_prefix
+        .as_ref()
+        .map(|prefix| prefix.contains('\n'))
+        .unwrap_or(false)
+    {
+        members.insert_formatted(index, value.decorated(&first_prefix.unwrap(), ""));
+    } else if index == 0 {
+        if let Some(first_prefix) = members
+            .get(0)
+            .map(|member| member.decor())
+            .and_then(|decor| decor.prefix())
+            .map(|prefix| prefix.to_string())
+        {
+            members.insert_formatted(index, value.decorated(&first_prefix, ""));
         } else {
-            members.insert(index, value);
+            members.insert_formatted(index, value.decorated("", ""));
         }
 
-        cargo_file.set_len(0).await?;
-        cargo_file.seek(SeekFrom::Start(0)).await?;
-        cargo_file.write_all(doc.to_string().as_bytes()).await?;
+        if let Some(member) = members.get_mut(index + 1) {
+            member.decor_mut().set_prefix(" ")
+        }
+    } else {
+        members.insert(index, value);
     }
 
-    Ok(())
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Creates a workspace for a test from `files` of paths and contents, removing what a previous run left behind.
+    async fn fixture(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "awto-cli-workspace-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir).await;
+        for (path, contents) in files {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).await.unwrap();
+            fs::write(path, contents).await.unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn globs() {
+        assert!(glob_matches("*", "database"));
+        assert!(glob_matches("data*", "database"));
+        assert!(glob_matches("*-client", "grpc-client"));
+        assert!(glob_matches("d?t*e", "database"));
+        assert!(!glob_matches("data", "database"));
+        assert!(!glob_matches("*-client", "protobuf"));
+    }
+
+    #[tokio::test]
+    async fn virtual_workspace() {
+        let dir = fixture(
+            "virtual",
+            &[
+                ("Cargo.toml", "[workspace]\nmembers = [\"schema\", \"./service/\", \"awto/*\"]\nexclude = [\"awto/ignored\"]\n"),
+                ("schema/Cargo.toml", "[package]\nname = \"schema\"\n"),
+                ("service/Cargo.toml", "[package]\nname = \"service\"\n"),
+                ("awto/database/Cargo.toml", "[package]\nname = \"database\"\n"),
+                ("awto/protobuf/Cargo.toml", "[package]\nname = \"protobuf\"\n"),
+                ("awto/ignored/Cargo.toml", "[package]\nname = \"ignored\"\n"),
+                ("awto/README.md", "# Awto\n"),
+                ("awto/notes/todo.md", "- nothing\n"),
+            ],
+        )
+        .await;
+
+        let workspace = WorkspaceContext::load(&dir).await.unwrap();
+        assert!(workspace.root_package().is_none());
+        assert_eq!(
+            workspace.members.iter().collect::<Vec<_>>(),
+            ["awto/database", "awto/protobuf", "schema", "service"]
+        );
+        assert_eq!(
+            workspace.package_by_name("database"),
+            Some(Path::new("awto/database"))
+        );
+        assert_eq!(workspace.package_by_name("ignored"), None);
+        assert_eq!(
+            workspace
+                .member_manifest("./schema")
+                .unwrap()
+                .package
+                .as_ref()
+                .unwrap()
+                .name,
+            "schema"
+        );
+        assert!(workspace.is_member("./service"));
+        assert!(workspace.is_member("awto/protobuf/"));
+        assert!(!workspace.is_member("awto/ignored"));
+
+        fs::remove_dir_all(dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn root_package_workspace() {
+        let dir = fixture(
+            "root-package",
+            &[
+                ("Cargo.toml", "[package]\nname = \"shop\"\n\n[workspace]\nmembers = [\"crates/*/api\", \"schema\", \"missing\"]\n"),
+                ("schema/Cargo.toml", "[package]\nname = \"schema\"\n"),
+                ("crates/orders/api/Cargo.toml", "[package]\nname = \"orders-api\"\n"),
+                ("crates/users/api/Cargo.toml", "[package]\nname = \"users-api\"\n"),
+                ("crates/users/Cargo.toml", "[package]\nname = \"users\"\n"),
+                (".cargo/config.toml", "[build]\ntarget-dir = \"build\"\n"),
+            ],
+        )
+        .await;
+
+        let workspace = WorkspaceContext::load(&dir).await.unwrap();
+        assert_eq!(workspace.root_package().unwrap().name, "shop");
+        assert_eq!(workspace.package_by_name("shop"), Some(Path::new("")));
+        assert_eq!(
+            workspace.package_by_name("users-api"),
+            Some(Path::new("crates/users/api"))
+        );
+        assert_eq!(workspace.package_by_name("users"), None);
+        // Members which do not exist yet are still members, without a manifest
+        assert!(workspace.is_member("missing"));
+        assert!(workspace.member_manifest("missing").is_none());
+
+        assert_eq!(
+            resolve_target_dir(&dir, false, None).await,
+            dir.join("build")
+        );
+        assert_eq!(
+            resolve_target_dir(&dir, false, Some("/tmp/target".into())).await,
+            PathBuf::from("/tmp/target")
+        );
+        assert_eq!(
+            resolve_target_dir(&dir.join("schema"), false, None).await,
+            dir.join("schema/target")
+        );
+
+        fs::remove_dir_all(dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn added_members() {
+        let dir = fixture(
+            "added-members",
+            &[
+                (
+                    "Cargo.toml",
+                    "[workspace]\nmembers = [\n    \"awto/database\",\n    \"schema\",\n]\n",
+                ),
+                ("schema/Cargo.toml", "[package]\nname = \"schema\"\n"),
+            ],
+        )
+        .await;
+
+        let mut workspace = WorkspaceContext::load(&dir).await.unwrap();
+        workspace.add_member("./awto/database");
+        workspace.flush().await.unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("Cargo.toml")).await.unwrap(),
+            "[workspace]\nmembers = [\n    \"awto/database\",\n    \"schema\",\n]\n"
+        );
+
+        workspace.add_member("awto/rest");
+        workspace.add_member("./awto/grpc-client");
+        workspace.add_member("awto/rest");
+        assert!(workspace.is_member("awto/rest"));
+        assert_eq!(workspace.pending, ["awto/rest", "awto/grpc-client"]);
+        workspace.flush().await.unwrap();
+        assert!(workspace.pending.is_empty());
+        assert!(workspace.is_member("awto/grpc-client"));
+        assert_eq!(
+            fs::read_to_string(dir.join("Cargo.toml")).await.unwrap(),
+            "[workspace]\nmembers = [\n    \"awto/database\",\n    \"awto/grpc-client\",\n    \"awto/rest\",\n    \"schema\",\n]\n"
+        );
+
+        fs::remove_dir_all(dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn nested_workspace() {
+        let root_manifest = "[workspace]\nmembers = [\n    \"awto/database\",\n    \"awto/protobuf\",\n    \"schema\",\n]\nresolver = \"2\"\n\n[workspace.package]\nedition = \"2021\"\n";
+        let dir = fixture(
+            "nested",
+            &[
+                ("Cargo.toml", root_manifest),
+                ("schema/Cargo.toml", "[package]\nname = \"schema\"\n"),
+                (
+                    "awto/database/Cargo.toml",
+                    "[package]\nname = \"database\"\n",
+                ),
+                (
+                    "awto/protobuf/Cargo.toml",
+                    "[package]\nname = \"protobuf\"\n",
+                ),
+                ("awto/rest/Cargo.toml", "[package]\nname = \"rest\"\n"),
+            ],
+        )
+        .await;
+
+        sync_nested_workspace(&dir, true).await.unwrap();
+        sync_nested_workspace(&dir, true).await.unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("Cargo.toml")).await.unwrap(),
+            "[workspace]\nmembers = [\n    \"schema\",\n]\nresolver = \"2\"\nexclude = [\"awto\"]\n\n[workspace.package]\nedition = \"2021\"\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("awto/Cargo.toml")).await.unwrap(),
+            format!(
+                "{}\n[workspace]\nmembers = [\"database\", \"protobuf\"]\nresolver = \"2\"\n\n[workspace.package]\nedition = \"2021\"\n",
+                NESTED_WORKSPACE_HEADER
+            )
+        );
+
+        let mut workspace = WorkspaceContext::load(&dir).await.unwrap();
+        assert!(workspace.is_nested());
+        assert_eq!(
+            workspace.package_by_name("database"),
+            Some(Path::new("awto/database"))
+        );
+        assert!(workspace.is_member("awto/protobuf"));
+        assert_eq!(
+            resolve_target_dir(&dir, true, None).await,
+            dir.join("awto/target")
+        );
+        workspace.add_member("awto/rest");
+        workspace.flush().await.unwrap();
+        assert!(fs::read_to_string(dir.join("awto/Cargo.toml"))
+            .await
+            .unwrap()
+            .contains("members = [\"database\", \"protobuf\", \"rest\"]"));
+
+        fs::write(dir.join("awto/Cargo.lock"), "version = 3\n")
+            .await
+            .unwrap();
+        sync_nested_workspace(&dir, false).await.unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("Cargo.toml")).await.unwrap(),
+            "[workspace]\nmembers = [\n    \"awto/database\",\n    \"awto/protobuf\",\n    \"awto/rest\",\n    \"schema\",\n]\nresolver = \"2\"\n\n[workspace.package]\nedition = \"2021\"\n"
+        );
+        assert!(!dir.join("awto/Cargo.toml").exists());
+        assert!(!dir.join("awto/Cargo.lock").exists());
+        let workspace = WorkspaceContext::load(&dir).await.unwrap();
+        assert!(!workspace.is_nested());
+
+        fs::write(dir.join("awto/Cargo.toml"), "[workspace]\n")
+            .await
+            .unwrap();
+        assert!(sync_nested_workspace(&dir, true).await.is_err());
+        sync_nested_workspace(&dir, false).await.unwrap();
+
+        fs::remove_dir_all(dir).await.unwrap();
+    }
 }
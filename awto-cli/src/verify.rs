@@ -0,0 +1,478 @@
+use std::{fmt::Write, time::Instant};
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use clap::{ArgEnum, Parser};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cancel::Cancellation,
+    config::{Config, CONFIG_PATH},
+    error::{Error, ErrorCode},
+    sink, Opts, Runnable,
+};
+
+/// Version of the JSON printed with `--format json`, increased when fields are removed or change meaning.
+const VERIFY_VERSION: u32 = 1;
+
+/// A check run by `awto verify`, which is a command of the cli that changes nothing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VerifyCheck {
+    /// Name of the check in the report and in `verify.skip`, such as `lint`.
+    pub name: &'static str,
+    /// Arguments of the command, such as `["schema", "lint"]`.
+    pub args: &'static [&'static str],
+}
+
+/// Checks run by `awto verify`, in order.
+///
+/// Generators with a check of their own, such as a `--check` flag listing out of date files, register it here.
+pub const CHECKS: [VerifyCheck; 6] = [
+    VerifyCheck {
+        name: "schema",
+        args: &["schema", "check"],
+    },
+    VerifyCheck {
+        name: "lint",
+        args: &["schema", "lint"],
+    },
+    VerifyCheck {
+        name: "fmt",
+        args: &["schema", "fmt", "--check"],
+    },
+    VerifyCheck {
+        name: "snapshot",
+        args: &["schema", "snapshot", "--check"],
+    },
+    VerifyCheck {
+        name: "database",
+        args: &["compile", "database", "--check"],
+    },
+    VerifyCheck {
+        name: "proto",
+        args: &["compile", "protobuf", "--check"],
+    },
+];
+
+/// Runs every check of the project without changing anything, reporting all the failures at once
+#[derive(Parser)]
+pub struct Verify {
+    /// Format of the printed report
+    #[clap(arg_enum, long, default_value = "table")]
+    pub format: VerifyFormat,
+    /// Prints more information
+    #[clap(short, long)]
+    pub verbose: bool,
+    /// Number of warnings reported by the checks
+    #[clap(skip)]
+    pub warnings: usize,
+}
+
+#[derive(ArgEnum, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyFormat {
+    Table,
+    Json,
+    Junit,
+}
+
+/// The `[verify]` section of `awto.toml`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VerifyConfig {
+    /// Checks left out of `awto verify`, such as `["snapshot"]`.
+    pub skip: Vec<String>,
+}
+
+/// Outcome of a check, ordered from the least to the most severe.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum CheckStatus {
+    Skipped,
+    Passed,
+    Warned,
+    Failed,
+}
+
+impl CheckStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            CheckStatus::Skipped => "skipped",
+            CheckStatus::Passed => "passed",
+            CheckStatus::Warned => "warned",
+            CheckStatus::Failed => "failed",
+        }
+    }
+}
+
+/// Report printed by `awto verify`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+struct VerifyReport {
+    version: u32,
+    /// Most severe status of the checks.
+    status: CheckStatus,
+    checks: Vec<CheckReport>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+struct CheckReport {
+    name: String,
+    status: CheckStatus,
+    /// Wall time of the check in milliseconds, 0 when it was skipped.
+    duration_ms: u64,
+    warnings: usize,
+    /// Code of the error of a failed check, when it has one.
+    code: Option<ErrorCode>,
+    /// Message of the error of a failed check.
+    message: Option<String>,
+}
+
+#[async_trait]
+impl Runnable for Verify {
+    async fn run(&mut self, cancellation: &Cancellation) -> Result<()> {
+        let config = Config::load(CONFIG_PATH).await?;
+        check_skipped(&config.verify)?;
+
+        let mut checks = Vec::new();
+        for check in &CHECKS {
+            if config.verify.skip.iter().any(|name| name == check.name) {
+                checks.push(CheckReport {
+                    name: check.name.to_string(),
+                    status: CheckStatus::Skipped,
+                    duration_ms: 0,
+                    warnings: 0,
+                    code: None,
+                    message: None,
+                });
+                continue;
+            }
+
+            let mut cmd = runnable(check, self.verbose)?;
+            let start = Instant::now();
+            // Only the report is printed to stdout when it is meant to be parsed
+            let result = match self.format {
+                VerifyFormat::Table => cmd.run(cancellation).await,
+                VerifyFormat::Json | VerifyFormat::Junit => {
+                    sink::to_stderr(cmd.run(cancellation)).await
+                }
+            };
+            let duration_ms = start.elapsed().as_millis() as u64;
+            let warnings = cmd.warnings();
+            self.warnings += warnings;
+
+            let report = match result {
+                Ok(()) => CheckReport {
+                    name: check.name.to_string(),
+                    status: if warnings > 0 {
+                        CheckStatus::Warned
+                    } else {
+                        CheckStatus::Passed
+                    },
+                    duration_ms,
+                    warnings,
+                    code: None,
+                    message: None,
+                },
+                Err(err) if Error::code_of(&err) == Some(ErrorCode::Cancelled) => return Err(err),
+                Err(err) => CheckReport {
+                    name: check.name.to_string(),
+                    status: CheckStatus::Failed,
+                    duration_ms,
+                    warnings,
+                    code: Error::code_of(&err),
+                    message: Some(err.to_string()),
+                },
+            };
+            checks.push(report);
+        }
+
+        let report = VerifyReport {
+            version: VERIFY_VERSION,
+            status: checks
+                .iter()
+                .map(|check| check.status)
+                .max()
+                .unwrap_or(CheckStatus::Skipped),
+            checks,
+        };
+        match self.format {
+            VerifyFormat::Table => out!("{}", table(&report)),
+            VerifyFormat::Json => outln!("{}", serde_json::to_string_pretty(&report)?),
+            VerifyFormat::Junit => out!("{}", junit(&report)),
+        }
+
+        let failed = report
+            .checks
+            .iter()
+            .filter(|check| check.status == CheckStatus::Failed)
+            .count();
+        if failed > 0 {
+            return Err(Error::VerifyFailed {
+                failed,
+                checks: report.checks.len(),
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+
+    fn is_verbose(&self) -> bool {
+        self.verbose
+    }
+
+    fn warnings(&self) -> usize {
+        self.warnings
+    }
+}
+
+/// Fails when `verify.skip` names a check which does not exist.
+fn check_skipped(config: &VerifyConfig) -> Result<()> {
+    let unknown = config
+        .skip
+        .iter()
+        .find(|name| CHECKS.iter().all(|check| check.name != name.as_str()));
+    if let Some(name) = unknown {
+        let names: Vec<&str> = CHECKS.iter().map(|check| check.name).collect();
+        return Err(Error::Config {
+            path: CONFIG_PATH.to_string(),
+            source: anyhow!(
+                "unknown check `{}` in `verify.skip`, expected one of {}",
+                name,
+                names.join(", ")
+            ),
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Returns the command of `check`, parsed like the arguments of the cli.
+fn runnable(check: &VerifyCheck, verbose: bool) -> Result<Box<dyn Runnable>> {
+    let args = std::iter::once("awto")
+        .chain(check.args.iter().copied())
+        .chain(verbose.then_some("--verbose"));
+    let opts = Opts::try_parse_from(args)
+        .with_context(|| format!("invalid command of verify check `{}`", check.name))?;
+
+    Ok(opts.subcmd.into_runnable())
+}
+
+fn seconds(duration_ms: u64) -> String {
+    format!("{:.3}", duration_ms as f64 / 1000.0)
+}
+
+/// Returns the table of the checks of `report`, with their status and duration.
+fn table(report: &VerifyReport) -> String {
+    let header = ["check", "status", "duration", "details"];
+    let rows: Vec<[String; 4]> = report
+        .checks
+        .iter()
+        .map(|check| {
+            let details = match (check.status, &check.code, &check.message) {
+                (CheckStatus::Failed, Some(code), Some(message)) => {
+                    format!("[{}] {}", code, message)
+                }
+                (CheckStatus::Failed, None, Some(message)) => message.clone(),
+                (CheckStatus::Skipped, _, _) => "skipped in awto.toml".to_string(),
+                _ if check.warnings > 0 => format!("{} warning(s)", check.warnings),
+                _ => String::new(),
+            };
+            [
+                check.name.clone(),
+                check.status.as_str().to_string(),
+                match check.status {
+                    CheckStatus::Skipped => "-".to_string(),
+                    _ => format!("{}s", seconds(check.duration_ms)),
+                },
+                details,
+            ]
+        })
+        .collect();
+    let widths: Vec<usize> = (0..header.len())
+        .map(|i| {
+            rows.iter()
+                .map(|row| row[i].len())
+                .max()
+                .unwrap_or(0)
+                .max(header[i].len())
+        })
+        .collect();
+
+    let mut text = String::new();
+    let mut write_row = |cells: Vec<&str>| {
+        let line: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        writeln!(text, "{}", line.join("  ").trim_end()).unwrap();
+    };
+    write_row(header.to_vec());
+    for row in &rows {
+        write_row(row.iter().map(String::as_str).collect());
+    }
+
+    text
+}
+
+/// Returns the JUnit XML of `report`, with a test case per check, read by CI servers to annotate the run.
+fn junit(report: &VerifyReport) -> String {
+    let count = |status: CheckStatus| {
+        report
+            .checks
+            .iter()
+            .filter(|check| check.status == status)
+            .count()
+    };
+    let suite = format!(
+        r#"name="awto verify" tests="{}" failures="{}" skipped="{}" time="{}""#,
+        report.checks.len(),
+        count(CheckStatus::Failed),
+        count(CheckStatus::Skipped),
+        seconds(report.checks.iter().map(|check| check.duration_ms).sum()),
+    );
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    writeln!(xml, "<testsuites {}>", suite).unwrap();
+    writeln!(xml, "  <testsuite {}>", suite).unwrap();
+    for check in &report.checks {
+        write!(
+            xml,
+            r#"    <testcase classname="awto.verify" name="{}" time="{}""#,
+            xml_escape(&check.name),
+            seconds(check.duration_ms)
+        )
+        .unwrap();
+        match check.status {
+            CheckStatus::Passed => xml.push_str("/>\n"),
+            CheckStatus::Skipped => xml.push_str(
+                ">\n      <skipped message=\"skipped in awto.toml\"/>\n    </testcase>\n",
+            ),
+            CheckStatus::Warned => writeln!(
+                xml,
+                ">\n      <system-out>{} warning(s)</system-out>\n    </testcase>",
+                check.warnings
+            )
+            .unwrap(),
+            CheckStatus::Failed => {
+                let message = xml_escape(check.message.as_deref().unwrap_or_default());
+                let code = check
+                    .code
+                    .map(|code| code.to_string())
+                    .unwrap_or_else(|| "error".to_string());
+                writeln!(
+                    xml,
+                    ">\n      <failure type=\"{}\" message=\"{}\">{}</failure>\n    </testcase>",
+                    code, message, message
+                )
+                .unwrap();
+            }
+        }
+    }
+    xml.push_str("  </testsuite>\n</testsuites>\n");
+
+    xml
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn report() -> VerifyReport {
+        let check = |name: &str, status: CheckStatus, duration_ms: u64| CheckReport {
+            name: name.to_string(),
+            status,
+            duration_ms,
+            warnings: 0,
+            code: None,
+            message: None,
+        };
+        VerifyReport {
+            version: VERIFY_VERSION,
+            status: CheckStatus::Failed,
+            checks: vec![
+                check("proto", CheckStatus::Passed, 1250),
+                CheckReport {
+                    warnings: 2,
+                    ..check("lint", CheckStatus::Warned, 40)
+                },
+                CheckReport {
+                    code: Some(ErrorCode::SchemaUnformatted),
+                    message: Some(
+                        "schema formatting would change 1 file(s), run `awto schema fmt`"
+                            .to_string(),
+                    ),
+                    ..check("fmt", CheckStatus::Failed, 5)
+                },
+                check("snapshot", CheckStatus::Skipped, 0),
+            ],
+        }
+    }
+
+    #[test]
+    fn checks() {
+        for check in &CHECKS {
+            assert!(runnable(check, true).is_ok(), "{}", check.name);
+        }
+        assert!(check_skipped(&VerifyConfig {
+            skip: vec!["snapshot".to_string()],
+        })
+        .is_ok());
+
+        let err = check_skipped(&VerifyConfig {
+            skip: vec!["coverage".to_string()],
+        })
+        .unwrap_err();
+        assert_eq!(
+            format!("{:#}", err),
+            "could not load awto config file from './awto.toml': unknown check `coverage` in `verify.skip`, \
+            expected one of schema, lint, fmt, snapshot, database, proto"
+        );
+    }
+
+    #[test]
+    fn table_report() {
+        assert_eq!(
+            table(&report()),
+            "\
+check     status   duration  details
+proto     passed   1.250s
+lint      warned   0.040s    2 warning(s)
+fmt       failed   0.005s    [E0304] schema formatting would change 1 file(s), run `awto schema fmt`
+snapshot  skipped  -         skipped in awto.toml
+"
+        );
+    }
+
+    #[test]
+    fn junit_report() {
+        assert_eq!(
+            junit(&report()),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="awto verify" tests="4" failures="1" skipped="1" time="1.295">
+  <testsuite name="awto verify" tests="4" failures="1" skipped="1" time="1.295">
+    <testcase classname="awto.verify" name="proto" time="1.250"/>
+    <testcase classname="awto.verify" name="lint" time="0.040">
+      <system-out>2 warning(s)</system-out>
+    </testcase>
+    <testcase classname="awto.verify" name="fmt" time="0.005">
+      <failure type="E0304" message="schema formatting would change 1 file(s), run `awto schema fmt`">schema formatting would change 1 file(s), run `awto schema fmt`</failure>
+    </testcase>
+    <testcase classname="awto.verify" name="snapshot" time="0.000">
+      <skipped message="skipped in awto.toml"/>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#
+        );
+    }
+}
@@ -0,0 +1,134 @@
+use std::path::{Path, PathBuf};
+
+use awto_cli::{run_with_args, ErrorCode, IoSink, RunStatus};
+use log::Level;
+
+const SCHEMA_LIB: &str = r#"use awto::prelude::*;
+
+schema! {
+    #[database_table]
+    pub struct Product {
+        pub id: Uuid,
+        pub created_at: DateTime<FixedOffset>,
+        pub updated_at: DateTime<FixedOffset>,
+        #[awto(max_len = 120)]
+        pub name: String,
+    }
+}
+"#;
+
+/// Creates a workspace with a schema package, removing what a previous run left behind.
+fn fixture() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("awto-cli-run-with-args-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let files = [
+        ("Cargo.toml", "[workspace]\nmembers = [\"schema\"]\n"),
+        (
+            "schema/Cargo.toml",
+            "[package]\nname = \"schema\"\nversion = \"0.1.0\"\nedition = \"2018\"\n",
+        ),
+        ("schema/src/lib.rs", SCHEMA_LIB),
+    ];
+    for (path, contents) in files {
+        let path = dir.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+    dir
+}
+
+fn args(args: &[&str]) -> Vec<String> {
+    std::iter::once("awto")
+        .chain(args.iter().copied())
+        .map(String::from)
+        .collect()
+}
+
+// The commands run in the current directory, which is shared by the tests of the process, so there is a single test
+#[tokio::test]
+async fn compile_database_dry_run() {
+    let dir = fixture();
+    std::env::set_current_dir(&dir).unwrap();
+
+    let (sink, captured) = IoSink::capture();
+    let summary = run_with_args(args(&["compile", "database", "--dry-run"]), sink)
+        .await
+        .unwrap();
+    assert_eq!(summary.status, RunStatus::Ok);
+    assert_eq!(summary.warnings, 0);
+    assert_eq!(
+        captured.stdout(),
+        "./awto/database/Cargo.toml\n./awto/database/build.rs\n./awto/database/src/lib.rs\n"
+    );
+    assert_eq!(captured.stderr(), "");
+    assert_eq!(
+        captured.logs(),
+        [(
+            Level::Info,
+            "dry run, 3 file(s) generated into './awto/database' would change".to_string()
+        )]
+    );
+    assert!(!Path::new("awto").exists());
+
+    // Verbose runs log their debug messages as well
+    let (sink, captured) = IoSink::capture();
+    run_with_args(
+        args(&["compile", "database", "--dry-run", "--verbose"]),
+        sink,
+    )
+    .await
+    .unwrap();
+    assert!(captured
+        .logs()
+        .contains(&(Level::Debug, "using profile 'dev'".to_string())));
+
+    let (sink, captured) = IoSink::capture();
+    let args_json = args(&[
+        "--message-format",
+        "json",
+        "compile",
+        "database",
+        "--dry-run",
+    ]);
+    run_with_args(args_json, sink).await.unwrap();
+    assert!(captured
+        .stdout()
+        .ends_with("{\"status\":\"ok\",\"profile\":\"dev\",\"warnings\":0}\n"));
+
+    let (sink, captured) = IoSink::capture();
+    let err = run_with_args(
+        args(&["compile", "database", "--dry-run", "--only-db", "analytics"]),
+        sink,
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(err.status, RunStatus::Failed);
+    assert_eq!(err.code, Some(ErrorCode::UnknownDatabase));
+    assert_eq!(err.exit_code(), 1);
+    assert_eq!(
+        err.message,
+        "no database named 'analytics' is declared in the schema"
+    );
+    assert!(captured.stderr().contains("awto explain E0006"));
+    assert_eq!(
+        serde_json::to_value(&err).unwrap()["code"],
+        serde_json::Value::String("E0006".to_string())
+    );
+
+    let (sink, captured) = IoSink::capture();
+    let err = run_with_args(args(&["compile", "database", "--dry-rum"]), sink)
+        .await
+        .unwrap_err();
+    assert_eq!(err.code, Some(ErrorCode::InvalidArguments));
+    assert_eq!(err.exit_code(), 2);
+    assert!(captured.stderr().contains("USAGE:"));
+
+    std::env::set_current_dir(std::env::temp_dir()).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn runs_are_send() {
+    fn assert_send<T: Send>(_: T) {}
+    assert_send(run_with_args(args(&["status"]), IoSink::capture().0));
+}
@@ -0,0 +1,81 @@
+use std::path::PathBuf;
+
+use awto_cli::{run_with_args, ErrorCode, IoSink, RunStatus};
+
+const SCHEMA_LIB: &str = r#"use awto::prelude::*;
+
+schema! {
+    #[database_table]
+    pub struct Product {
+        pub id: Uuid,
+        pub created_at: DateTime<FixedOffset>,
+        pub updated_at: DateTime<FixedOffset>,
+        #[awto(max_len = 120)]
+        pub name: String,
+    }
+}
+"#;
+
+/// Overlay describing `Product.title`, a field the schema renamed to `name`.
+const STALE_API_DOCS: &str = r#"[models.Product]
+description = "A product of the catalog."
+
+[fields."Product.title"]
+examples = ["Chair"]
+"#;
+
+/// Creates a workspace with a schema package and an api docs overlay, removing what a previous run left behind.
+///
+/// Only the `schema` check runs, which checks the overlay before building the schema package.
+fn fixture() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("awto-cli-verify-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let files = [
+        ("Cargo.toml", "[workspace]\nmembers = [\"schema\"]\n"),
+        ("schema/Cargo.toml", "[package]\nname = \"schema\"\nversion = \"0.1.0\"\nedition = \"2018\"\n"),
+        ("schema/src/lib.rs", SCHEMA_LIB),
+        (
+            "awto.toml",
+            "[api_docs]\noverlay = \"api-docs.toml\"\n\n[verify]\nskip = [\"lint\", \"fmt\", \"snapshot\", \"database\", \"proto\"]\n",
+        ),
+        ("api-docs.toml", STALE_API_DOCS),
+    ];
+    for (path, contents) in files {
+        let path = dir.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+    dir
+}
+
+fn args(args: &[&str]) -> Vec<String> {
+    std::iter::once("awto")
+        .chain(args.iter().copied())
+        .map(String::from)
+        .collect()
+}
+
+#[tokio::test]
+async fn stale_api_docs() {
+    let dir = fixture();
+    std::env::set_current_dir(&dir).unwrap();
+
+    let (sink, captured) = IoSink::capture();
+    let err = run_with_args(args(&["verify", "--format", "json"]), sink)
+        .await
+        .unwrap_err();
+    assert_eq!(err.status, RunStatus::Failed);
+    assert_eq!(err.code, Some(ErrorCode::VerifyFailed));
+    let report: serde_json::Value = serde_json::from_str(&captured.stdout()).unwrap();
+    let schema = &report["checks"][0];
+    assert_eq!(schema["name"], "schema");
+    assert_eq!(schema["status"], "failed");
+    assert_eq!(
+        schema["message"],
+        "the api docs of 'api-docs.toml' do not match the schema:\n  \
+        api docs describe field `Product.title`, which does not exist, update the entry if it was renamed"
+    );
+
+    std::env::set_current_dir(std::env::temp_dir()).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,1143 @@
+use std::{env, fmt::Write};
+
+use awto::{
+    database::{DatabaseTable, DatabaseType, Temporal},
+    schema::{Model, Role, Sensitivity},
+};
+use proc_macro2::TokenStream;
+use quote::quote;
+
+use crate::{
+    database::is_fake_table,
+    format::format_rust,
+    provenance::{
+        annotate_sources, watch_profile, write_generated, Annotation, CommentStyle,
+        GenerationManifest,
+    },
+    util::{column_rust_type, column_variant_ident, rust_ident, schema_ty, TableKey},
+};
+
+const COMPILED_RUST_FILE: &str = "app.rs";
+
+/// Options, errors and output helpers shared by the commands of every table.
+const ADMIN_MODULE: &str = r#"
+use std::{fmt, fs, io, str::FromStr};
+
+/// Value printed in place of sensitive fields unless `--show-sensitive` is set.
+pub const MASK: &str = "********";
+
+/// Options of the admin command shared by the commands of every table.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Options {
+    /// Tenant the queries are scoped to by row-level security policies.
+    pub tenant_id: Option<::uuid::Uuid>,
+    /// Whether the values of sensitive fields are printed instead of [`MASK`], along with internal fields.
+    /// Secret fields are never printed.
+    pub show_sensitive: bool,
+}
+
+impl Options {
+    /// Returns the options of the transaction every command runs in.
+    pub fn txn(&self) -> ::database::tx::TxnOptions {
+        ::database::tx::TxnOptions {
+            tenant_id: self.tenant_id,
+            ..Default::default()
+        }
+    }
+}
+
+/// Error of an admin command, printed before exiting with a failure status.
+#[derive(Debug)]
+pub enum AdminError {
+    /// A query failed.
+    Db(::database::sea_orm::DbErr),
+    /// A file or the terminal could not be read or written.
+    Io(io::Error),
+    /// A row could not be serialized as JSON.
+    Json(::serde_json::Error),
+    /// An import or export failed.
+    Bulk(::database::bulk::BulkError),
+    /// The random rows could not be generated.
+    Fake(::database::fake::FakeError),
+    /// A `--filter` names an unknown field or has a value which is not of the field's type.
+    InvalidFilter(String),
+    /// A `--map` is not of the form `column=field`.
+    InvalidMapping(String),
+    /// No row has the given id.
+    NotFound(String),
+    /// The deletion was not confirmed.
+    Aborted,
+}
+
+impl fmt::Display for AdminError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdminError::Db(err) => write!(f, "database error: {}", err),
+            AdminError::Io(err) => write!(f, "{}", err),
+            AdminError::Json(err) => write!(f, "could not serialize row: {}", err),
+            AdminError::Bulk(err) => write!(f, "{}", err),
+            AdminError::Fake(err) => write!(f, "{}", err),
+            AdminError::InvalidFilter(err) => write!(f, "invalid filter: {}", err),
+            AdminError::InvalidMapping(mapping) => write!(f, "`{}` is not of the form `column=field`", mapping),
+            AdminError::NotFound(id) => write!(f, "no row with id `{}`", id),
+            AdminError::Aborted => write!(f, "aborted"),
+        }
+    }
+}
+
+impl AdminError {
+    /// Returns the status the admin exits with, which is 130 like for processes interrupted by Ctrl-C
+    /// once an import or generation saved its checkpoint.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            AdminError::Bulk(::database::bulk::BulkError::Checkpoint(
+                ::awto::checkpoint::CheckpointError::Interrupted { .. },
+            ))
+            | AdminError::Fake(::database::fake::FakeError::Checkpoint(
+                ::awto::checkpoint::CheckpointError::Interrupted { .. },
+            )) => 130,
+            _ => 1,
+        }
+    }
+}
+
+impl ::std::error::Error for AdminError {}
+
+impl From<::database::sea_orm::DbErr> for AdminError {
+    fn from(err: ::database::sea_orm::DbErr) -> Self {
+        AdminError::Db(err)
+    }
+}
+
+impl From<io::Error> for AdminError {
+    fn from(err: io::Error) -> Self {
+        AdminError::Io(err)
+    }
+}
+
+impl From<::serde_json::Error> for AdminError {
+    fn from(err: ::serde_json::Error) -> Self {
+        AdminError::Json(err)
+    }
+}
+
+impl From<::database::bulk::BulkError> for AdminError {
+    fn from(err: ::database::bulk::BulkError) -> Self {
+        AdminError::Bulk(err)
+    }
+}
+
+impl From<::database::fake::FakeError> for AdminError {
+    fn from(err: ::database::fake::FakeError) -> Self {
+        AdminError::Fake(err)
+    }
+}
+
+/// Output format of `export`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// CSV with a header of the exported fields.
+    Csv,
+    /// A JSON array of the rows.
+    Json,
+    /// JSON lines, a JSON object per row.
+    Jsonl,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(ExportFormat::Csv),
+            "json" => Ok(ExportFormat::Json),
+            "jsonl" => Ok(ExportFormat::Jsonl),
+            _ => Err(format!("unknown format `{}`, expected `csv`, `json` or `jsonl`", s)),
+        }
+    }
+}
+
+/// Input format of `import`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// CSV with a header of the imported columns.
+    Csv,
+    /// JSON lines, a JSON object per row.
+    Jsonl,
+}
+
+impl FromStr for ImportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(ImportFormat::Csv),
+            "jsonl" => Ok(ImportFormat::Jsonl),
+            _ => Err(format!("unknown format `{}`, expected `csv` or `jsonl`", s)),
+        }
+    }
+}
+
+/// Splits a `field=value` filter into its field and value.
+pub fn parse_filter(filter: &str) -> Result<(&str, &str), AdminError> {
+    filter
+        .split_once('=')
+        .map(|(field, value)| (field.trim(), value))
+        .ok_or_else(|| AdminError::InvalidFilter(format!("`{}` is not of the form `field=value`", filter)))
+}
+
+/// Returns the import options of `import`, reading the columns of `mappings` of the form `column=field`
+/// into their field.
+pub fn import_options(strict: bool, mappings: &[String]) -> Result<::database::bulk::ImportOptions, AdminError> {
+    let mut options = ::database::bulk::ImportOptions {
+        strict,
+        ..Default::default()
+    };
+    for mapping in mappings {
+        let (column, field) = mapping
+            .split_once('=')
+            .ok_or_else(|| AdminError::InvalidMapping(mapping.clone()))?;
+        options = options.map_column(column.trim(), field.trim());
+    }
+
+    Ok(options)
+}
+
+/// Returns the checkpointing of an import or generation saving its progress into the `checkpoint` file
+/// every `every` batches, or `None` without a file.
+///
+/// The first Ctrl-C stops the run after its current batch, saving a last checkpoint to resume it from.
+pub fn checkpointing(checkpoint: Option<String>, every: usize, resume: bool) -> Option<::awto::checkpoint::Checkpointing> {
+    let checkpointing = ::awto::checkpoint::Checkpointing {
+        every: every.max(1),
+        resume,
+        ..::awto::checkpoint::Checkpointing::new(checkpoint?)
+    };
+    let interrupted = checkpointing.clone();
+    ::tokio::spawn(async move {
+        if ::tokio::signal::ctrl_c().await.is_ok() {
+            eprintln!("\nstopping after the current batch, saving a checkpoint");
+            interrupted.interrupt();
+        }
+    });
+
+    Some(checkpointing)
+}
+
+/// Parses the `value` of a filter on `field`, whose column is of the sql type `ty`.
+pub fn coerce<T>(field: &str, value: &str, ty: &str) -> Result<T, AdminError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    value.parse().map_err(|err| {
+        AdminError::InvalidFilter(format!("`{}` is not a valid {} value for `{}`: {}", value, ty, field, err))
+    })
+}
+
+/// Returns the error of a filter on a field which is not in `fields`.
+pub fn unknown_field(field: &str, fields: &[&str]) -> AdminError {
+    AdminError::InvalidFilter(format!("unknown field `{}`, expected one of {}", field, fields.join(", ")))
+}
+
+/// Serializes `row` into a JSON object, replacing the values of the `sensitive` fields with [`MASK`]
+/// unless they are shown, along with the internal fields of the schema.
+pub fn to_json<T: ::serde::Serialize>(
+    row: &T,
+    sensitive: &[&str],
+    options: &Options,
+) -> Result<::serde_json::Value, AdminError> {
+    let mut value = if options.show_sensitive {
+        ::awto::schema::reveal_internal(|| ::serde_json::to_value(row))?
+    } else {
+        ::serde_json::to_value(row)?
+    };
+    if let ::serde_json::Value::Object(map) = &mut value {
+        if !options.show_sensitive {
+            for (key, value) in map.iter_mut() {
+                if sensitive.contains(&key.as_str()) && !value.is_null() {
+                    *value = ::serde_json::Value::String(MASK.to_string());
+                }
+            }
+        }
+    }
+
+    Ok(value)
+}
+
+/// Writes the JSON lines of `export_jsonl` as a JSON array.
+pub fn write_json(out: &mut dyn io::Write, jsonl: &[u8]) -> Result<(), AdminError> {
+    let rows = jsonl
+        .split(|byte| *byte == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(::serde_json::from_slice)
+        .collect::<Result<Vec<::serde_json::Value>, _>>()?;
+    writeln!(out, "{}", ::serde_json::to_string_pretty(&rows)?)?;
+
+    Ok(())
+}
+
+/// Reads the imported file at `path`, or stdin when it is `-`.
+pub fn read_input(path: &str) -> io::Result<Vec<u8>> {
+    if path == "-" {
+        let mut input = Vec::new();
+        io::Read::read_to_end(&mut io::stdin(), &mut input)?;
+        Ok(input)
+    } else {
+        fs::read(path)
+    }
+}
+
+/// Writes the rows left out of an import, followed by the number of imported rows.
+pub fn write_report(out: &mut dyn io::Write, report: &::database::bulk::ImportReport) -> io::Result<()> {
+    for error in &report.errors {
+        writeln!(out, "skipped {}", error)?;
+    }
+    writeln!(out, "imported {} of {} rows", report.inserted, report.rows)
+}
+
+/// Prints the progress of `generate` on stderr, on a line per table rewritten after every batch.
+pub fn print_progress(table: &str, inserted: u64, rows: u64) {
+    eprint!("\r{}: {}/{} rows", table, inserted, rows);
+    if inserted >= rows {
+        eprintln!();
+    }
+}
+
+/// Writes the number of rows generated into `table` and the rate they were inserted at.
+pub fn write_generated(out: &mut dyn io::Write, table: &str, rows: u64, elapsed: ::std::time::Duration) -> io::Result<()> {
+    let secs = elapsed.as_secs_f64();
+    let rate = if secs > 0.0 { rows as f64 / secs } else { rows as f64 };
+    writeln!(out, "generated {} {} rows in {:.1}s ({:.0} rows/s)", rows, table, secs, rate)
+}
+
+/// Asks `question` on stderr, returning whether it was answered with `y` or `yes`.
+pub fn confirm(question: &str) -> io::Result<bool> {
+    eprint!("{} [y/N] ", question);
+    io::Write::flush(&mut io::stderr())?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+"#;
+
+/// Compiles an admin command line app for the database tables of `models` into `OUT_DIR`,
+/// connecting to the database at the url of the `url_var` environment variable.
+pub fn compile_admin(models: Vec<Model>, url_var: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let out_dir = env::var("OUT_DIR").unwrap();
+
+    let annotate = annotate_sources();
+    watch_profile();
+    let compiler = AdminCompiler::new(models)
+        .with_url_var(url_var)
+        .with_annotations(annotate);
+    let mut manifest = GenerationManifest::new(annotate);
+
+    let code = format_rust(&compiler.compile_generated_code());
+    write_generated(
+        &out_dir,
+        COMPILED_RUST_FILE,
+        &code,
+        &mut manifest,
+        "admin",
+        &compiler.model_names(),
+    )?;
+    manifest.write(&out_dir)?;
+
+    Ok(())
+}
+
+/// Compiles a clap app looking up and managing the rows of the database tables of models, for support teams
+/// who would otherwise write sql.
+///
+/// Every database table gets a subcommand named after it, with the following commands:
+///
+/// - `admin product get <id>` prints a row as JSON
+/// - `admin product list --limit 20 --filter name=Chair` prints matching rows as JSON lines
+/// - `admin product delete <id>` deletes a row once confirmed, or with `--yes`
+/// - `admin product export --format csv|json|jsonl --column name` prints every matching row
+/// - `admin product import products.csv --map "Product Name=name"` imports the rows of a CSV or JSON lines file
+/// - `admin product generate --rows 100000 --seed 42` inserts random rows for load testing
+///
+/// Imports and generations given a `--checkpoint` file save their progress into it, and continue from it
+/// with `--resume` once interrupted, such as by Ctrl-C or a failed batch.
+///
+/// Exports and imports run the `export_*` and `import_*` functions of the `bulk` feature of the database crate,
+/// which admin apps enable. Imports report the invalid rows they leave out, or import no row with `--strict`.
+/// Generated rows are inserted by the `generate_rows` functions of the `fake` feature, which admin apps enable as well,
+/// and tables whose rows cannot be generated have no `generate` command.
+///
+/// Filters are validated against the columns of the table and parsed into the type of their column,
+/// and `field=null` matches the rows where a nullable column is `NULL`.
+/// Read-only tables have no `delete` and `import` commands, and deleting rows of tables recording events records a `deleted` event.
+///
+/// The values of `#[awto(sensitive)]` fields are masked unless `--show-sensitive` is set.
+/// Apps with row-level security policies get a `--tenant` option, scoping every query to a tenant.
+///
+/// # Examples
+///
+/// ```
+/// # use awto_compile::admin::AdminCompiler;
+/// # use awto::tests_cfg::*;
+/// let compiler = AdminCompiler::new(MODELS.to_vec());
+/// let code = compiler.compile_generated_code();
+///
+/// assert!(code.contains("pub struct Admin"));
+/// ```
+pub struct AdminCompiler {
+    models: Vec<Model>,
+    url_var: String,
+    annotate_sources: bool,
+}
+
+impl AdminCompiler {
+    /// Creates a new instance of [`AdminCompiler`].
+    pub fn new(models: Vec<Model>) -> AdminCompiler {
+        AdminCompiler {
+            models,
+            url_var: "DATABASE_URL".to_string(),
+            annotate_sources: false,
+        }
+    }
+
+    /// Sets the environment variable the database url is read from when `--database-url` is not given,
+    /// `DATABASE_URL` by default.
+    pub fn with_url_var(mut self, url_var: &str) -> Self {
+        self.url_var = url_var.to_string();
+        self
+    }
+
+    /// Interleaves the generated code with comments telling which model produced the commands of each table.
+    pub fn with_annotations(mut self, annotate_sources: bool) -> Self {
+        self.annotate_sources = annotate_sources;
+        self
+    }
+
+    /// Returns the names of the models with commands.
+    fn model_names(&self) -> Vec<&str> {
+        self.admin_tables()
+            .into_iter()
+            .map(|(model, _)| model.name.as_str())
+            .collect()
+    }
+
+    /// Compiles generated Rust code for the admin commands and the `main` function running them.
+    pub fn compile_generated_code(&self) -> String {
+        let mut code = ADMIN_MODULE.to_string();
+
+        let tables = self.admin_tables();
+        for (model, table) in &tables {
+            let annotation = Annotation::new("admin", &model.name);
+            code.push_str(&annotation.comment(CommentStyle::Slashes, self.annotate_sources));
+            write!(code, "{}", self.compile_commands(model, table)).unwrap();
+        }
+
+        let variants = tables.iter().map(|(model, table)| {
+            let ident = rust_ident(&model.name);
+            let module_ident = rust_ident(&table.name);
+            let name = &table.name;
+            let doc = format!(" Rows of the `{}` table", table.name);
+
+            quote!(
+                #[doc = #doc]
+                #[clap(name = #name, subcommand)]
+                #ident(#module_ident::Command)
+            )
+        });
+        let runs = tables.iter().map(|(model, _)| {
+            let ident = rust_ident(&model.name);
+            quote!(TableCommand::#ident(command) => command.run(db, options, out).await)
+        });
+
+        // Tenants only need to be set when a policy reads them
+        let is_tenanted = tables.iter().any(|(_, table)| !table.policies.is_empty());
+        let (tenant_arg, tenant_id) = if is_tenanted {
+            (
+                quote!(
+                    /// Tenant every query is scoped to by row-level security policies
+                    #[clap(long)]
+                    pub tenant: Option<::uuid::Uuid>,
+                ),
+                quote!(self.tenant),
+            )
+        } else {
+            (quote!(), quote!(None))
+        };
+
+        let url_var = &self.url_var;
+        let expanded = quote!(
+            /// Looks up and manages the rows of the database tables
+            #[derive(::clap::Parser)]
+            #[clap(name = "admin")]
+            pub struct Admin {
+                /// Url of the database
+                #[clap(long, env = #url_var)]
+                pub database_url: String,
+                #tenant_arg
+                /// Prints the values of sensitive fields instead of masking them, including internal fields
+                #[clap(long)]
+                pub show_sensitive: bool,
+                /// Command of the rows of a table
+                #[clap(subcommand)]
+                pub table: TableCommand,
+            }
+
+            /// Commands of the rows of each table.
+            #[derive(::clap::Parser)]
+            pub enum TableCommand {
+                #( #variants, )*
+            }
+
+            impl Admin {
+                /// Returns the options shared by the commands of every table.
+                pub fn options(&self) -> Options {
+                    Options {
+                        tenant_id: #tenant_id,
+                        show_sensitive: self.show_sensitive,
+                    }
+                }
+
+                /// Connects to the database and runs the command, writing its output to `out`.
+                pub async fn run(self, out: &mut dyn ::std::io::Write) -> Result<(), AdminError> {
+                    let db = ::database::sea_orm::Database::connect(&self.database_url).await?;
+                    let options = self.options();
+                    self.table.run(&db, &options, out).await
+                }
+            }
+
+            impl TableCommand {
+                /// Runs the command of the table, writing its output to `out`.
+                pub async fn run(
+                    self,
+                    db: &::database::sea_orm::DatabaseConnection,
+                    options: &Options,
+                    out: &mut dyn ::std::io::Write,
+                ) -> Result<(), AdminError> {
+                    match self {
+                        #( #runs, )*
+                    }
+                }
+            }
+
+            #[::tokio::main]
+            async fn main() {
+                let admin = <Admin as ::clap::Parser>::parse();
+                if let Err(err) = admin.run(&mut ::std::io::stdout()).await {
+                    eprintln!("error: {}", err);
+                    ::std::process::exit(err.exit_code());
+                }
+            }
+        );
+        write!(code, "{}", expanded).unwrap();
+
+        code
+    }
+
+    fn compile_commands(&self, model: &Model, table: &DatabaseTable) -> TokenStream {
+        let schema_ty = schema_ty(model);
+        let module_ident = rust_ident(&table.name);
+
+        let sensitive_fields = model
+            .fields
+            .iter()
+            .filter(|field| field.sensitive)
+            .map(|field| &field.name);
+
+        // Filtering by secret columns would tell their values apart, so they are not filterable
+        let filter_columns: Vec<_> = table
+            .columns
+            .iter()
+            .filter(|column| {
+                !model.fields.iter().any(|field| {
+                    field.name == column.name && field.sensitivity == Sensitivity::Secret
+                })
+            })
+            .collect();
+        let filter_fields: Vec<_> = filter_columns.iter().map(|column| &column.name).collect();
+        let filters = filter_columns.iter().map(|column| {
+            let name = &column.name;
+            let column_ident = column_variant_ident(&column.name);
+            let ty = match (&column.ty, column_rust_type(&column.ty)) {
+                // Bytes have no text representation to filter by
+                (DatabaseType::Binary, _) | (_, None) => {
+                    let message = format!("filtering by `{}` is not supported", column.name);
+                    return quote!(#name => return Err(super::AdminError::InvalidFilter(#message.to_string())));
+                }
+                (_, Some(ty)) => ty,
+            };
+            let sql_ty = column.ty.to_string();
+            let null_arm = column.nullable.then(|| quote!(#name if value == "null" => Column::#column_ident.is_null(),));
+
+            quote!(
+                #null_arm
+                #name => Column::#column_ident.eq(super::coerce::<#ty>(field, value, #sql_ty)?)
+            )
+        });
+
+        let portable = table.temporal == Some(Temporal::Portable);
+        let key = TableKey::of(table);
+        let key_column = &key.column;
+        let key_ty = &key.owned_ty;
+        let borrowed_id = key.borrowed(quote!(id));
+        // The id is still printed once the transaction moved its key
+        let (txn_key, txn_id) = if table.has_string_key() {
+            (Some(quote!(let key = id.clone();)), quote!(key))
+        } else {
+            (None, quote!(id))
+        };
+        let found_id = key.cloned(txn_id.clone());
+        let delete_rows = if table.events || portable {
+            let record_event = table
+                .events
+                .then(|| quote!(Entity::record_event(txn, ::database::outbox::EventKind::Deleted, &model).await?;));
+            let record_history = portable.then(|| {
+                quote!(Entity::record_history(txn, ::database::temporal::Operation::Delete, &model).await?;)
+            });
+            quote!(
+                let model = match Entity::find_by_id(#found_id).one(txn).await? {
+                    Some(model) => model,
+                    None => return Ok(0),
+                };
+                let result = Entity::delete_many().filter(Column::#key_column.eq(#txn_id)).exec(txn).await?;
+                #record_history
+                #record_event
+                Ok(result.rows_affected)
+            )
+        } else {
+            quote!(
+                let result = Entity::delete_many().filter(Column::#key_column.eq(#txn_id)).exec(txn).await?;
+                Ok(result.rows_affected)
+            )
+        };
+        let (import_variant, import_run) = if table.readonly {
+            (quote!(), quote!())
+        } else {
+            (
+                quote!(
+                    /// Imports the rows of a CSV or JSON lines file, reporting the invalid rows which are left out
+                    Import {
+                        /// File the rows are read from, or `-` for stdin
+                        #[clap(default_value = "-")]
+                        file: String,
+                        /// Input format, `csv` or `jsonl`
+                        #[clap(long, default_value = "csv")]
+                        format: super::ImportFormat,
+                        /// Fails without importing any row when a row is invalid
+                        #[clap(long)]
+                        strict: bool,
+                        /// Imports a column which is not named after its field, such as `Product Name=name`
+                        #[clap(long = "map", number_of_values = 1, multiple_occurrences = true)]
+                        mappings: Vec<String>,
+                        /// Commits every batch and saves the progress into this file, to resume the import once interrupted
+                        #[clap(long)]
+                        checkpoint: Option<String>,
+                        /// Number of committed batches between saved checkpoints
+                        #[clap(long, default_value = "1")]
+                        checkpoint_every: usize,
+                        /// Resumes the import from the rows after its checkpoint
+                        #[clap(long, requires = "checkpoint")]
+                        resume: bool,
+                    },
+                ),
+                quote!(
+                    Command::Import { file, format, strict, mappings, checkpoint, checkpoint_every, resume } => {
+                        let input = super::read_input(&file)?;
+                        let import = super::import_options(strict, &mappings)?;
+                        let report = match super::checkpointing(checkpoint, checkpoint_every, resume) {
+                            Some(checkpointing) => match format {
+                                super::ImportFormat::Csv => {
+                                    Entity::import_csv_checkpointed(db, input.as_slice(), &import, options.txn(), &checkpointing)
+                                        .await?
+                                }
+                                super::ImportFormat::Jsonl => {
+                                    Entity::import_jsonl_checkpointed(db, input.as_slice(), &import, options.txn(), &checkpointing)
+                                        .await?
+                                }
+                            },
+                            None => {
+                                db.run_txn(options.txn(), move |txn| {
+                                    Box::pin(async move {
+                                        let report = match format {
+                                            super::ImportFormat::Csv => Entity::import_csv(txn, input.as_slice(), &import).await?,
+                                            super::ImportFormat::Jsonl => {
+                                                Entity::import_jsonl(txn, input.as_slice(), &import).await?
+                                            }
+                                        };
+                                        Ok::<_, super::AdminError>(report)
+                                    })
+                                })
+                                .await?
+                            }
+                        };
+                        super::write_report(out, &report)?;
+                    }
+                ),
+            )
+        };
+        let (delete_variant, delete_run) = if table.readonly {
+            (quote!(), quote!())
+        } else {
+            let question = format!("Delete {} `{{}}`?", table.name);
+            let deleted = format!("deleted {} `{{}}`", table.name);
+            (
+                quote!(
+                    /// Deletes the row with an id, once confirmed
+                    Delete {
+                        /// Id of the row
+                        id: #key_ty,
+                        /// Deletes without asking for confirmation
+                        #[clap(long)]
+                        yes: bool,
+                    },
+                ),
+                quote!(
+                    Command::Delete { id, yes } => {
+                        if !yes && !super::confirm(&format!(#question, id))? {
+                            return Err(super::AdminError::Aborted);
+                        }
+
+                        #txn_key
+                        let rows_affected = db
+                            .run_txn(options.txn(), move |txn| {
+                                Box::pin(async move { #delete_rows })
+                            })
+                            .await
+                            .map_err(super::AdminError::Db)?;
+                        if rows_affected == 0 {
+                            return Err(super::AdminError::NotFound(id.to_string()));
+                        }
+                        writeln!(out, #deleted, id)?;
+                    }
+                ),
+            )
+        };
+
+        let (generate_variant, generate_run) = if is_fake_table(table) {
+            let name = &table.name;
+            (
+                quote!(
+                    /// Inserts random rows, filling the empty tables required fields reference first
+                    Generate {
+                        /// Number of rows inserted
+                        #[clap(long, default_value = "1000")]
+                        rows: u64,
+                        /// Number of rows inserted by each transaction
+                        #[clap(long, default_value = "5000")]
+                        batch: usize,
+                        /// Seed of the random values, the same seed generating the same rows
+                        #[clap(long, default_value = "0")]
+                        seed: u64,
+                        /// Probability of nullable fields being null
+                        #[clap(long, default_value = "0.1")]
+                        null_probability: f64,
+                        /// Number of rows inserted into each empty table required fields reference
+                        #[clap(long, default_value = "100")]
+                        parent_rows: u64,
+                        /// Saves the progress into this file, to resume the generation once interrupted
+                        #[clap(long)]
+                        checkpoint: Option<String>,
+                        /// Number of committed batches between saved checkpoints
+                        #[clap(long, default_value = "1")]
+                        checkpoint_every: usize,
+                        /// Resumes the generation from its checkpoint, continuing with the same random values
+                        #[clap(long, requires = "checkpoint")]
+                        resume: bool,
+                    },
+                ),
+                quote!(
+                    Command::Generate {
+                        rows,
+                        batch,
+                        seed,
+                        null_probability,
+                        parent_rows,
+                        checkpoint,
+                        checkpoint_every,
+                        resume,
+                    } => {
+                        let fake = ::database::fake::FakeOptions {
+                            rows,
+                            batch_size: batch,
+                            seed,
+                            null_probability,
+                            parent_rows,
+                            txn: options.txn(),
+                            checkpointing: super::checkpointing(checkpoint, checkpoint_every, resume),
+                        };
+                        let start = ::std::time::Instant::now();
+                        let inserted = Entity::generate_rows(db, &fake, super::print_progress).await?;
+                        super::write_generated(out, #name, inserted, start.elapsed())?;
+                    }
+                ),
+            )
+        } else {
+            (quote!(), quote!())
+        };
+
+        let (partitions_variant, partitions_run) = if table.partition.is_some() {
+            let created = format!("created partition {{}} of {}", table.name);
+            let existing = format!("partitions of {} exist through {{}}", table.name);
+            (
+                quote!(
+                    /// Creates the missing partitions through the one holding a date, such as `2025-12-01`
+                    EnsurePartitions {
+                        /// Date held by the last partition created
+                        #[clap(long)]
+                        through: ::chrono::NaiveDate,
+                        /// Date held by the first partition created, today by default
+                        #[clap(long)]
+                        from: Option<::chrono::NaiveDate>,
+                    },
+                ),
+                quote!(
+                    Command::EnsurePartitions { through, from } => {
+                        let from = from.unwrap_or_else(|| ::chrono::Utc::now().naive_utc().date());
+                        let created = Entity::ensure_partitions_between(db, from, through).await?;
+                        for partition in &created {
+                            writeln!(out, #created, partition)?;
+                        }
+                        if created.is_empty() {
+                            writeln!(out, #existing, through)?;
+                        }
+                    }
+                ),
+            )
+        } else {
+            (quote!(), quote!())
+        };
+
+        let module_doc = format!(" Commands of the rows of the `{}` table.", table.name);
+        quote!(
+            #[doc = #module_doc]
+            pub mod #module_ident {
+                use ::database::{
+                    #module_ident::{Column, Entity},
+                    sea_orm::{ColumnTrait, Condition, EntityTrait, QueryFilter, QueryTrait},
+                    tx::Transactional,
+                };
+
+                /// Fields masked unless `--show-sensitive` is set.
+                pub const SENSITIVE_FIELDS: &[&str] = &[#( #sensitive_fields ),*];
+
+                /// Fields rows can be filtered by.
+                pub const FILTER_FIELDS: &[&str] = &[#( #filter_fields ),*];
+
+                /// Commands of the rows of the table.
+                #[derive(::clap::Parser)]
+                pub enum Command {
+                    /// Prints the row with an id
+                    Get {
+                        /// Id of the row
+                        id: #key_ty,
+                    },
+                    /// Prints the rows matching the filters, one JSON object per line
+                    List {
+                        /// Maximum number of rows printed
+                        #[clap(long, default_value = "20")]
+                        limit: u64,
+                        /// Prints only the rows where a field has a value, such as `name=Chair`
+                        #[clap(long = "filter", number_of_values = 1, multiple_occurrences = true)]
+                        filters: Vec<String>,
+                    },
+                    #delete_variant
+                    /// Prints every row matching the filters
+                    Export {
+                        /// Output format, `csv`, `json` or `jsonl`
+                        #[clap(long, default_value = "json")]
+                        format: super::ExportFormat,
+                        /// Exports only the rows where a field has a value, such as `name=Chair`
+                        #[clap(long = "filter", number_of_values = 1, multiple_occurrences = true)]
+                        filters: Vec<String>,
+                        /// Exports only a field, in the order of the columns of CSV, every field by default
+                        #[clap(long = "column", number_of_values = 1, multiple_occurrences = true)]
+                        columns: Vec<String>,
+                    },
+                    #import_variant
+                    #generate_variant
+                    #partitions_variant
+                }
+
+                /// Returns the condition matching the rows of `filters`.
+                pub fn condition(filters: &[String]) -> Result<Condition, super::AdminError> {
+                    let mut condition = Condition::all();
+                    for filter in filters {
+                        let (field, value) = super::parse_filter(filter)?;
+                        let expr = match field {
+                            #( #filters, )*
+                            _ => return Err(super::unknown_field(field, FILTER_FIELDS)),
+                        };
+                        condition = condition.add(expr);
+                    }
+
+                    Ok(condition)
+                }
+
+                async fn find(
+                    db: &::database::sea_orm::DatabaseConnection,
+                    options: &super::Options,
+                    condition: Condition,
+                    limit: u64,
+                ) -> Result<Vec<::serde_json::Value>, super::AdminError> {
+                    let models = db
+                        .run_txn(options.txn(), move |txn| {
+                            Box::pin(async move {
+                                let mut select = Entity::find().filter(condition);
+                                QueryTrait::query(&mut select).limit(limit);
+                                select.all(txn).await
+                            })
+                        })
+                        .await?;
+
+                    models
+                        .into_iter()
+                        .map(|model| super::to_json(&#schema_ty::from(model), SENSITIVE_FIELDS, options))
+                        .collect()
+                }
+
+                impl Command {
+                    /// Runs the command, writing its output to `out`.
+                    pub async fn run(
+                        self,
+                        db: &::database::sea_orm::DatabaseConnection,
+                        options: &super::Options,
+                        out: &mut dyn ::std::io::Write,
+                    ) -> Result<(), super::AdminError> {
+                        match self {
+                            Command::Get { id } => {
+                                let row = find(db, options, Condition::all().add(Column::#key_column.eq(#borrowed_id)), 1)
+                                    .await?
+                                    .pop()
+                                    .ok_or_else(|| super::AdminError::NotFound(id.to_string()))?;
+                                writeln!(out, "{}", ::serde_json::to_string_pretty(&row)?)?;
+                            }
+                            Command::List { limit, filters } => {
+                                for row in find(db, options, condition(&filters)?, limit).await? {
+                                    writeln!(out, "{}", row)?;
+                                }
+                            }
+                            #delete_run
+                            Command::Export { format, filters, columns } => {
+                                let export = ::database::bulk::ExportOptions {
+                                    columns: (!columns.is_empty()).then(|| columns),
+                                    show_sensitive: options.show_sensitive,
+                                    condition: condition(&filters)?,
+                                    ..Default::default()
+                                };
+                                let rows = db
+                                    .run_txn(options.txn(), move |txn| {
+                                        Box::pin(async move {
+                                            let mut rows = Vec::new();
+                                            match format {
+                                                super::ExportFormat::Csv => Entity::export_csv(txn, &mut rows, &export).await?,
+                                                super::ExportFormat::Json | super::ExportFormat::Jsonl => {
+                                                    Entity::export_jsonl(txn, &mut rows, &export).await?
+                                                }
+                                            };
+                                            Ok::<_, super::AdminError>(rows)
+                                        })
+                                    })
+                                    .await?;
+                                match format {
+                                    super::ExportFormat::Json => super::write_json(out, &rows)?,
+                                    super::ExportFormat::Csv | super::ExportFormat::Jsonl => out.write_all(&rows)?,
+                                }
+                            }
+                            #import_run
+                            #generate_run
+                            #partitions_run
+                        }
+
+                        Ok(())
+                    }
+                }
+            }
+        )
+    }
+
+    /// Returns the models and their database tables which get commands.
+    fn admin_tables(&self) -> Vec<(&Model, &DatabaseTable)> {
+        self.models
+            .iter()
+            .flat_map(|model| {
+                model.roles.iter().filter_map(move |role| match role {
+                    Role::DatabaseTable(table) => Some((model, table)),
+                    _ => None,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use awto::tests_cfg::*;
+
+    use super::*;
+    use crate::util::undocumented_items;
+
+    #[test]
+    fn string_primary_keys() {
+        let code = AdminCompiler::new(string_keys::MODELS.to_vec()).compile_generated_code();
+
+        assert_eq!(
+            code.matches(r#"Get { # [doc = r" Id of the row"] id : String , }"#)
+                .count(),
+            2
+        );
+        assert!(code.contains("Condition :: all () . add (Column :: Code . eq (id . as_str ()))"));
+        assert!(code.contains("let key = id . clone () ;"));
+        assert!(code.contains("Entity :: delete_many () . filter (Column :: Code . eq (key))"));
+        assert!(code.contains("return Err (super :: AdminError :: NotFound (id . to_string ())) ;"));
+    }
+
+    #[test]
+    fn trees() {
+        let code = AdminCompiler::new(tree::MODELS.to_vec()).compile_generated_code();
+
+        syn::parse_file(&code).unwrap();
+        assert!(code.contains(
+            r#"# [clap (name = "category" , subcommand)] Category (category :: Command)"#
+        ));
+        // Nullable self references do not stop rows from being generated
+        assert_eq!(code.matches("Command :: Generate {").count(), 2);
+    }
+
+    #[test]
+    fn partitions() {
+        let code = AdminCompiler::new(partitioned::MODELS.to_vec()).compile_generated_code();
+
+        syn::parse_file(&code).unwrap();
+        assert!(code.contains("# [clap (long)] through : :: chrono :: NaiveDate ,"));
+        assert!(code.contains("Command :: EnsurePartitions { through , from } =>"));
+        assert!(code.contains(
+            "let created = Entity :: ensure_partitions_between (db , from , through) . await ? ;"
+        ));
+
+        let code = AdminCompiler::new(MODELS.to_vec()).compile_generated_code();
+        assert!(!code.contains("EnsurePartitions"));
+    }
+
+    #[test]
+    fn temporal_history() {
+        let models = crate::database::with_temporal(temporal::MODELS.to_vec(), Temporal::Portable);
+        let code = AdminCompiler::new(models).compile_generated_code();
+
+        syn::parse_file(&code).unwrap();
+        assert!(code.contains("Entity :: record_history (txn , :: database :: temporal :: Operation :: Delete , & model) . await ? ;"));
+        assert!(!code.contains("record_event"));
+
+        let code = AdminCompiler::new(temporal::MODELS.to_vec()).compile_generated_code();
+        assert!(!code.contains("record_history"));
+    }
+
+    #[test]
+    fn commands() {
+        let code = AdminCompiler::new(MODELS.to_vec()).compile_generated_code();
+
+        assert!(code
+            .contains(r#"# [clap (name = "product" , subcommand)] Product (product :: Command)"#));
+        assert!(
+            code.contains(r#"# [clap (long , env = "DATABASE_URL")] pub database_url : String"#)
+        );
+        assert!(code.contains("Entity :: export_csv (txn , & mut rows , & export) . await ?"));
+        assert!(code.contains("super :: to_json (& :: schema :: Product :: from (model) , SENSITIVE_FIELDS , options)"));
+        // Read-only tables cannot be deleted from
+        assert_eq!(code.matches("Command :: Delete { id , yes }").count(), 2);
+        assert_eq!(
+            code.matches("Command :: Import { file , format , strict , mappings , checkpoint , checkpoint_every , resume }").count(),
+            2
+        );
+        assert!(code.contains("pub tenant : Option < :: uuid :: Uuid >"));
+
+        let code = AdminCompiler::new(MODELS.to_vec())
+            .with_url_var("MAIN_DATABASE_URL")
+            .compile_generated_code();
+        assert!(code.contains(r#"env = "MAIN_DATABASE_URL""#));
+
+        // Tenants are only read by row-level security policies
+        let models = MODELS
+            .iter()
+            .filter(|model| model.name != "Event")
+            .cloned()
+            .collect();
+        let code = AdminCompiler::new(models).compile_generated_code();
+        assert!(!code.contains("pub tenant :"));
+        assert!(code.contains("tenant_id : None ,"));
+    }
+
+    #[test]
+    fn filters() {
+        let code = AdminCompiler::new(MODELS.to_vec()).compile_generated_code();
+
+        assert!(code.contains(r#""price" => Column :: Price . eq (super :: coerce :: < i64 > (field , value , "bigint") ?)"#));
+        assert!(code.contains(
+            r#""description" if value == "null" => Column :: Description . is_null () ,"#
+        ));
+        assert!(code.contains(r#""id" => Column :: Id . eq (super :: coerce :: < :: uuid :: Uuid > (field , value , "uuid") ?)"#));
+        assert!(code.contains(r#""properties" => return Err (super :: AdminError :: InvalidFilter ("filtering by `properties` is not supported" . to_string ()))"#));
+        assert!(code.contains("_ => return Err (super :: unknown_field (field , FILTER_FIELDS))"));
+    }
+
+    #[test]
+    fn sensitive_fields() {
+        let code = AdminCompiler::new(MODELS.to_vec()).compile_generated_code();
+
+        assert!(code.contains(r#"pub const SENSITIVE_FIELDS : & [& str] = & ["name"] ;"#));
+        assert!(code.contains("pub show_sensitive : bool"));
+
+        let code = AdminCompiler::new(sensitivity::MODELS.to_vec()).compile_generated_code();
+        assert!(code.contains(
+            r#"pub const SENSITIVE_FIELDS : & [& str] = & ["credit_limit" , "password_hash"] ;"#
+        ));
+        // Secret fields cannot be filtered by
+        assert!(code.contains(
+            r#"pub const FILTER_FIELDS : & [& str] = & ["id" , "created_at" , "updated_at" , "email" , "credit_limit"] ;"#
+        ));
+    }
+
+    #[test]
+    fn generate_rows() {
+        let code = AdminCompiler::new(MODELS.to_vec()).compile_generated_code();
+
+        assert!(code.contains("let inserted = Entity :: generate_rows (db , & fake , super :: print_progress) . await ? ;"));
+        assert!(code.contains(
+            r#"super :: write_generated (out , "employee" , inserted , start . elapsed ()) ?"#
+        ));
+        // Read-only tables cannot be generated
+        assert_eq!(code.matches("Command :: Generate {").count(), 2);
+    }
+
+    #[test]
+    fn checkpoints() {
+        let code = AdminCompiler::new(MODELS.to_vec()).compile_generated_code();
+
+        assert!(code.contains(
+            "checkpointing : super :: checkpointing (checkpoint , checkpoint_every , resume) ,"
+        ));
+        assert!(code.contains("Entity :: import_csv_checkpointed (db , input . as_slice () , & import , options . txn () , & checkpointing) . await ?"));
+        assert!(code.contains(r#"# [clap (long , requires = "checkpoint")] resume : bool"#));
+        assert!(code.contains(":: std :: process :: exit (err . exit_code ()) ;"));
+        assert!(code.contains("interrupted.interrupt();"));
+    }
+
+    #[test]
+    fn deleted_events() {
+        let code = AdminCompiler::new(MODELS.to_vec()).compile_generated_code();
+
+        assert!(code.contains("Entity :: record_event (txn , :: database :: outbox :: EventKind :: Deleted , & model)"));
+    }
+
+    #[test]
+    fn documented_code() {
+        for models in [
+            MODELS.to_vec(),
+            partitioned::MODELS.to_vec(),
+            tree::MODELS.to_vec(),
+        ] {
+            let code = AdminCompiler::new(models).compile_generated_code();
+
+            assert_eq!(undocumented_items(&code), Vec::<String>::new());
+        }
+    }
+}
@@ -0,0 +1,526 @@
+//! Descriptions, examples and tags of the models, fields and methods exposed by the generated APIs.
+//!
+//! They start out as the doc comments of the schema and service packages. Teams polishing the API documentation
+//! without owning the schema write an overlay file, referenced by the `[api_docs]` section of `awto.toml`, whose
+//! entries win over the doc comments. The protobuf, REST and transcoding generators all read the merged
+//! [`ApiDocs`], so the descriptions of a model cannot differ between them.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Write},
+};
+
+use awto::{protobuf::ProtobufService, schema::Model};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime};
+use uuid::Uuid;
+
+use crate::{
+    error::Error,
+    util::{escape_markdown, is_ty_vec, map_value_ty, strip_ty_option, strip_ty_vec},
+};
+
+/// A model, field or method described by an entry of the overlay file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ApiItem<'a> {
+    /// A model, such as `Product`.
+    Model(&'a str),
+    /// A field of a model, such as `Product.name`.
+    Field(&'a str, &'a str),
+    /// A method of a service, such as `ProductService.FindProduct`.
+    Operation(&'a str, &'a str),
+}
+
+impl fmt::Display for ApiItem<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiItem::Model(model) => write!(f, "{}", model),
+            ApiItem::Field(parent, name) | ApiItem::Operation(parent, name) => {
+                write!(f, "{}.{}", parent, name)
+            }
+        }
+    }
+}
+
+/// An example value of a field, as written in the overlay file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ApiExample<'a> {
+    String(&'a str),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+}
+
+impl fmt::Display for ApiExample<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiExample::String(s) => write!(f, "{:?}", s),
+            ApiExample::Integer(i) => write!(f, "{}", i),
+            ApiExample::Float(float) => write!(f, "{:?}", float),
+            ApiExample::Boolean(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+/// An entry of the overlay file, replacing the parts of the documentation of `item` it sets.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ApiDocOverride<'a> {
+    pub item: ApiItem<'a>,
+    pub description: Option<&'a str>,
+    /// Example values of a field, checked against the type of the field.
+    pub examples: &'a [ApiExample<'a>],
+    pub tags: &'a [&'a str],
+}
+
+/// Merged documentation of a model, field or method.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ApiDoc {
+    pub description: Option<String>,
+    /// Examples written as JSON values, such as `"Chair"` or `20`.
+    pub examples: Vec<String>,
+    pub tags: Vec<String>,
+}
+
+impl ApiDoc {
+    /// Returns the lines documenting the item: its description, followed by its examples and tags.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines: Vec<String> = self
+            .description
+            .iter()
+            .flat_map(|description| description.lines())
+            .map(str::to_string)
+            .collect();
+        let mut notes = Vec::new();
+        if !self.examples.is_empty() {
+            notes.push(format!("Examples: {}", self.examples.join(", ")));
+        }
+        if !self.tags.is_empty() {
+            notes.push(format!("Tags: {}", self.tags.join(", ")));
+        }
+        if !lines.is_empty() && !notes.is_empty() {
+            lines.push(String::new());
+        }
+        lines.extend(notes);
+
+        lines
+    }
+}
+
+/// Documentation of every model, field and method, from their doc comments merged with the overlay file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ApiDocs {
+    docs: BTreeMap<String, ApiDoc>,
+}
+
+impl ApiDocs {
+    /// Merges the `overrides` of the overlay file over the doc comments of `models` and `services`,
+    /// leaving out the entries of methods without `services`.
+    ///
+    /// Fails on the first problem of [`check_overrides`], except for entries of models which are not in `models`,
+    /// as they may have been left out by their feature. `awto schema check` reports those against every model.
+    pub fn new(
+        models: &[Model],
+        services: Option<&[ProtobufService]>,
+        overrides: &[ApiDocOverride],
+    ) -> Result<Self, Error> {
+        let known = |item: &ApiItem| match item {
+            ApiItem::Model(model) | ApiItem::Field(model, _) => {
+                models.iter().any(|other| other.name == *model)
+            }
+            ApiItem::Operation(..) => services.is_some(),
+        };
+        let overrides: Vec<ApiDocOverride> = overrides
+            .iter()
+            .filter(|entry| known(&entry.item))
+            .copied()
+            .collect();
+        if let Some(err) = check_overrides(models, services, &overrides)
+            .into_iter()
+            .next()
+        {
+            return Err(err);
+        }
+
+        let mut docs = BTreeMap::new();
+        let from_comment = |doc: &Option<String>| ApiDoc {
+            description: doc.clone(),
+            ..ApiDoc::default()
+        };
+        for model in models {
+            docs.insert(
+                ApiItem::Model(&model.name).to_string(),
+                from_comment(&model.doc),
+            );
+            for field in &model.fields {
+                docs.insert(
+                    ApiItem::Field(&model.name, &field.name).to_string(),
+                    from_comment(&field.doc),
+                );
+            }
+        }
+        for service in services.unwrap_or_default() {
+            for method in &service.methods {
+                docs.insert(
+                    ApiItem::Operation(&service.name, &method.name).to_string(),
+                    from_comment(&method.doc),
+                );
+            }
+        }
+
+        for entry in &overrides {
+            let doc = docs.entry(entry.item.to_string()).or_default();
+            if let Some(description) = entry.description {
+                doc.description = Some(description.to_string());
+            }
+            if !entry.examples.is_empty() {
+                doc.examples = entry
+                    .examples
+                    .iter()
+                    .map(|example| example.to_string())
+                    .collect();
+            }
+            if !entry.tags.is_empty() {
+                doc.tags = entry.tags.iter().map(|tag| tag.to_string()).collect();
+            }
+        }
+
+        Ok(ApiDocs { docs })
+    }
+
+    /// Returns the documentation of `item`, if it has any.
+    pub fn get(&self, item: ApiItem) -> Option<&ApiDoc> {
+        self.docs
+            .get(&item.to_string())
+            .filter(|doc| **doc != ApiDoc::default())
+    }
+
+    /// Returns the `#[doc]` of generated Rust code, its `summary` followed by the documentation of `item`
+    /// with markdown escaped.
+    pub(crate) fn rust_doc(&self, item: ApiItem, summary: &str) -> String {
+        let mut doc = summary.to_string();
+        if let Some(api_doc) = self.get(item) {
+            doc.push('\n');
+            for line in api_doc.lines() {
+                doc.push('\n');
+                if !line.is_empty() {
+                    write!(doc, " {}", escape_markdown(&line)).unwrap();
+                }
+            }
+        }
+
+        doc
+    }
+}
+
+/// Returns every problem of the `overrides` of the overlay file: entries of models, fields or methods which do not
+/// exist, usually because they were renamed, and examples which do not fit the type of their field.
+///
+/// Methods are only checked when `services` are given.
+pub fn check_overrides(
+    models: &[Model],
+    services: Option<&[ProtobufService]>,
+    overrides: &[ApiDocOverride],
+) -> Vec<Error> {
+    let mut problems = Vec::new();
+    for entry in overrides {
+        let unknown = |kind: &str| Error::UnknownApiDocItem {
+            item: entry.item.to_string(),
+            kind: kind.to_string(),
+        };
+        match entry.item {
+            ApiItem::Model(name) => {
+                if !models.iter().any(|model| model.name == name) {
+                    problems.push(unknown("model"));
+                }
+            }
+            ApiItem::Field(model, name) => {
+                let model = models.iter().find(|other| other.name == model);
+                if model
+                    .is_some_and(|model| model.omitted_fields.iter().any(|omitted| omitted == name))
+                {
+                    continue;
+                }
+                match model.and_then(|model| model.fields.iter().find(|field| field.name == name)) {
+                    Some(field) => {
+                        problems.extend(entry.examples.iter().filter_map(|example| {
+                            check_example(&field.ty, example).err().map(|reason| {
+                                Error::InvalidApiExample {
+                                    item: entry.item.to_string(),
+                                    example: example.to_string(),
+                                    ty: field.ty.clone(),
+                                    reason,
+                                }
+                            })
+                        }));
+                    }
+                    None => problems.push(unknown("field")),
+                }
+            }
+            ApiItem::Operation(service, name) => {
+                let services = match services {
+                    Some(services) => services,
+                    None => continue,
+                };
+                let exists = services
+                    .iter()
+                    .filter(|other| other.name == service)
+                    .any(|service| service.methods.iter().any(|method| method.name == name));
+                if !exists {
+                    problems.push(unknown("method"));
+                }
+            }
+        }
+    }
+
+    problems
+}
+
+/// Checks `example` is a value of a field of type `ty`, such as an integer within the range of an `i32`.
+///
+/// Examples of `Option` and `Vec` fields are values of their items. Types awto does not know,
+/// such as enums, accept any example.
+pub fn check_example(ty: &str, example: &ApiExample) -> Result<(), String> {
+    if map_value_ty(ty).is_some() {
+        return Err("maps have no example values".to_string());
+    }
+    let mut ty = strip_ty_option(ty);
+    if is_ty_vec(ty) && strip_ty_vec(ty) != "u8" {
+        ty = strip_ty_option(strip_ty_vec(ty));
+    }
+    let ty = ty.trim_start_matches("::");
+    let ty = ty
+        .strip_prefix("chrono::")
+        .or_else(|| ty.strip_prefix("uuid::"))
+        .or_else(|| ty.strip_prefix("std::string::"))
+        .unwrap_or(ty);
+
+    let integer_range = match ty {
+        "i8" => Some((i8::MIN as i64, i8::MAX as i64)),
+        "i16" => Some((i16::MIN as i64, i16::MAX as i64)),
+        "i32" => Some((i32::MIN as i64, i32::MAX as i64)),
+        "i64" | "isize" => Some((i64::MIN, i64::MAX)),
+        "u8" => Some((0, u8::MAX as i64)),
+        "u16" => Some((0, u16::MAX as i64)),
+        "u32" => Some((0, u32::MAX as i64)),
+        "u64" | "usize" => Some((0, i64::MAX)),
+        _ => None,
+    };
+    if let Some((min, max)) = integer_range {
+        return match example {
+            ApiExample::Integer(i) if (min..=max).contains(i) => Ok(()),
+            ApiExample::Integer(_) => Err(format!("it is out of the range of {}", ty)),
+            _ => Err("expected an integer".to_string()),
+        };
+    }
+
+    let string = match example {
+        ApiExample::String(s) => Some(*s),
+        _ => None,
+    };
+    let parses = |valid: bool, format: &str| {
+        if valid {
+            Ok(())
+        } else {
+            Err(format!("expected {}", format))
+        }
+    };
+    match ty {
+        "f32" | "f64" => match example {
+            ApiExample::Integer(_) | ApiExample::Float(_) => Ok(()),
+            _ => Err("expected a number".to_string()),
+        },
+        "bool" => match example {
+            ApiExample::Boolean(_) => Ok(()),
+            _ => Err("expected a boolean".to_string()),
+        },
+        "String" | "char" | "Vec<u8>" => parses(string.is_some(), "a string"),
+        "Uuid" => parses(
+            string.is_some_and(|s| Uuid::parse_str(s).is_ok()),
+            "a uuid string",
+        ),
+        "NaiveDate" => parses(
+            string.is_some_and(|s| s.parse::<NaiveDate>().is_ok()),
+            "a date string such as \"2024-06-01\"",
+        ),
+        "NaiveTime" => parses(
+            string.is_some_and(|s| s.parse::<NaiveTime>().is_ok()),
+            "a time string such as \"12:30:00\"",
+        ),
+        "NaiveDateTime" => parses(
+            string.is_some_and(|s| s.parse::<NaiveDateTime>().is_ok()),
+            "a date and time string such as \"2024-06-01T12:30:00\"",
+        ),
+        _ if ty.starts_with("DateTime<") => parses(
+            string.is_some_and(|s| DateTime::parse_from_rfc3339(s).is_ok()),
+            "an RFC 3339 string such as \"2024-06-01T12:30:00+02:00\"",
+        ),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use awto::{protobuf::IntoProtobufService, tests_cfg::*};
+
+    use super::*;
+    use crate::{protobuf::ProtobufCompiler, rest::RestCompiler, transcoding::TranscodingCompiler};
+
+    fn entry<'a>(item: ApiItem<'a>, description: Option<&'a str>) -> ApiDocOverride<'a> {
+        ApiDocOverride {
+            item,
+            description,
+            examples: &[],
+            tags: &[],
+        }
+    }
+
+    #[test]
+    fn merged() {
+        let services = [ProductService::protobuf_service()];
+        let overrides = [
+            ApiDocOverride {
+                tags: &["catalog"],
+                ..entry(ApiItem::Model("Product"), Some("A product of the catalog."))
+            },
+            ApiDocOverride {
+                examples: &[ApiExample::String("Chair"), ApiExample::String("Desk")],
+                ..entry(ApiItem::Field("Product", "name"), None)
+            },
+        ];
+        let docs = ApiDocs::new(&MODELS[..], Some(&services), &overrides).unwrap();
+
+        // The overlay wins over the doc comment of the model
+        let product = docs.get(ApiItem::Model("Product")).unwrap();
+        assert_eq!(
+            product.description.as_deref(),
+            Some("A product of the catalog.")
+        );
+        assert_eq!(
+            product.lines(),
+            ["A product of the catalog.", "", "Tags: catalog"]
+        );
+
+        // Parts the overlay leaves out keep the doc comment
+        let name = docs.get(ApiItem::Field("Product", "name")).unwrap();
+        assert_eq!(
+            name.description.as_deref(),
+            Some("Name of the product in its default locale.")
+        );
+        assert_eq!(name.examples, [r#""Chair""#, r#""Desk""#]);
+
+        let method = docs
+            .get(ApiItem::Operation("ProductService", "FindProduct"))
+            .unwrap();
+        assert_eq!(
+            method.description.as_deref(),
+            Some("Finds the products with the id.")
+        );
+        assert!(docs.get(ApiItem::Field("Product", "price")).is_none());
+    }
+
+    #[test]
+    fn unknown_items() {
+        let services = [ProductService::protobuf_service()];
+        let overrides = [
+            entry(ApiItem::Model("Products"), Some("Renamed.")),
+            entry(ApiItem::Field("Product", "title"), Some("Renamed.")),
+            entry(
+                ApiItem::Operation("ProductService", "GetProduct"),
+                Some("Renamed."),
+            ),
+        ];
+        let problems: Vec<_> = check_overrides(&MODELS[..], Some(&services), &overrides)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            problems,
+            [
+                "api docs describe model `Products`, which does not exist, update the entry if it was renamed",
+                "api docs describe field `Product.title`, which does not exist, update the entry if it was renamed",
+                "api docs describe method `ProductService.GetProduct`, which does not exist, update the entry if it was renamed",
+            ]
+        );
+        assert_eq!(check_overrides(&MODELS[..], None, &overrides).len(), 2);
+
+        // Models left out by their feature are not known to the generators
+        let err = ApiDocs::new(&MODELS[..], Some(&services), &overrides).unwrap_err();
+        assert!(err.to_string().contains("`Product.title`"));
+        assert!(ApiDocs::new(&MODELS[..], Some(&services), &overrides[..1]).is_ok());
+        assert!(ApiDocs::new(&MODELS[..], None, &[overrides[0], overrides[2]]).is_ok());
+    }
+
+    #[test]
+    fn examples() {
+        assert!(check_example("i32", &ApiExample::Integer(20)).is_ok());
+        assert_eq!(
+            check_example("i32", &ApiExample::String("20")).unwrap_err(),
+            "expected an integer"
+        );
+        assert_eq!(
+            check_example("Option<i32>", &ApiExample::Integer(1 << 40)).unwrap_err(),
+            "it is out of the range of i32"
+        );
+        assert!(check_example("f64", &ApiExample::Integer(20)).is_ok());
+        assert!(check_example("Vec<String>", &ApiExample::String("tag")).is_ok());
+        assert!(check_example("Uuid", &ApiExample::String("not a uuid")).is_err());
+        assert!(check_example(
+            "DateTime<FixedOffset>",
+            &ApiExample::String("2024-06-01T12:30:00+02:00")
+        )
+        .is_ok());
+        assert!(check_example("NaiveDate", &ApiExample::String("2024-06-01")).is_ok());
+        assert!(check_example("HashMap<String,String>", &ApiExample::String("a")).is_err());
+        assert!(check_example("Status", &ApiExample::Integer(1)).is_ok());
+
+        let overrides = [ApiDocOverride {
+            examples: &[ApiExample::String("twenty")],
+            ..entry(ApiItem::Field("Product", "version"), None)
+        }];
+        let err = ApiDocs::new(&MODELS[..], None, &overrides).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            r#"example "twenty" of `Product.version` does not fit its type i32: expected an integer"#
+        );
+    }
+
+    #[test]
+    fn generators() {
+        let services = [ProductService::protobuf_service()];
+        let overrides = [
+            ApiDocOverride {
+                tags: &["catalog"],
+                ..entry(ApiItem::Model("Product"), Some("A product of the catalog."))
+            },
+            ApiDocOverride {
+                examples: &[ApiExample::Integer(3)],
+                ..entry(ApiItem::Field("Product", "version"), None)
+            },
+            entry(
+                ApiItem::Operation("ProductService", "FindProduct"),
+                Some("Lists the products of an id."),
+            ),
+        ];
+        let docs = ApiDocs::new(&MODELS[..], Some(&services), &overrides).unwrap();
+
+        let proto = ProtobufCompiler::new(MODELS.to_vec(), services.to_vec())
+            .with_api_docs(docs.clone())
+            .compile_file();
+        assert!(proto
+            .contains("// A product of the catalog.\n//\n// Tags: catalog\nmessage Product {\n"));
+        assert!(
+            proto.contains("  // Name of the product in its default locale.\n  string name = 4;\n")
+        );
+        assert!(proto.contains("  // Examples: 3\n  int32 version = 8;\n"));
+        assert!(proto.contains("  // Lists the products of an id.\n  rpc FindProduct(ProductId) returns (ProductList);\n"));
+
+        let rest = RestCompiler::new(MODELS.to_vec())
+            .with_api_docs(docs.clone())
+            .compile_generated_code();
+        assert!(rest.contains(r#"Handlers of the routes of the `product` table.\n\n A product of the catalog.\n\n Tags: catalog""#));
+
+        let transcoding = TranscodingCompiler::new(services.to_vec())
+            .with_api_docs(docs)
+            .compile_generated_code();
+        assert!(transcoding.contains(r#"to `FindProduct`.\n\n Lists the products of an id.""#));
+    }
+}
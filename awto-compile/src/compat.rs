@@ -0,0 +1,124 @@
+//! Versions of sea-orm the generated database packages can be built against.
+//!
+//! Everything generated differently for a version of sea-orm goes through [`SeaOrmCompat`], such as the dependencies
+//! of the packages and the features they enable, so supporting a new major version is a matter of adding a variant
+//! and the differences of its api, while projects keep generating for the version they select.
+
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+/// A major version of sea-orm the generated packages support, selected with `sea_orm = "..."` in the `[database]`
+/// section of `awto.toml`.
+///
+/// Before 1.0, the minor version of sea-orm is its major version, as in cargo's version requirements.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum SeaOrmCompat {
+    /// sea-orm 0.2 from the fork providing `sea-orm-build`, which generates the entities of the tables in build scripts.
+    #[default]
+    #[serde(rename = "0.2")]
+    V0_2,
+}
+
+impl SeaOrmCompat {
+    /// Every supported version, oldest first.
+    pub const ALL: [SeaOrmCompat; 1] = [SeaOrmCompat::V0_2];
+
+    /// The newest supported version, which projects use unless they select another one.
+    pub const LATEST: SeaOrmCompat = SeaOrmCompat::V0_2;
+
+    /// Returns the version as written in `awto.toml`, such as `0.2`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SeaOrmCompat::V0_2 => "0.2",
+        }
+    }
+
+    /// Returns whether the version `version` of sea-orm, such as `0.2.6`, is of this major version.
+    pub fn matches(&self, version: &str) -> bool {
+        let mut parts = version.split(['.', '-', '+']);
+        let major = match (parts.next(), parts.next()) {
+            (Some("0"), Some(minor)) => format!("0.{}", minor),
+            (Some(major), _) => major.to_string(),
+            (None, _) => return false,
+        };
+
+        major == self.as_str()
+    }
+
+    /// Returns the `sea-orm` entry of the `[dependencies]` of the generated database packages.
+    pub fn dependency(&self) -> &'static str {
+        match self {
+            SeaOrmCompat::V0_2 => {
+                r#"sea-orm = { git = "https://github.com/Acidic9/sea-orm.git", branch = "feat/sea-orm-build", package = "sea-orm", features = [
+  "sqlx-postgres",
+  "runtime-tokio-rustls",
+  "macros",
+], default-features = false }"#
+            }
+        }
+    }
+
+    /// Returns the `sea-orm-build` entry of the `[build-dependencies]` of the generated database packages,
+    /// which generates the entities of the tables from the database.
+    pub fn build_dependency(&self) -> &'static str {
+        match self {
+            SeaOrmCompat::V0_2 => {
+                r#"sea-orm-build = { version = "0.2.6", git = "https://github.com/Acidic9/sea-orm.git", branch = "feat/sea-orm-build", package = "sea-orm-build", features = [
+  "postgres",
+  "runtime-tokio-rustls",
+], default-features = false }"#
+            }
+        }
+    }
+}
+
+impl fmt::Display for SeaOrmCompat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for SeaOrmCompat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SeaOrmCompat::ALL
+            .iter()
+            .copied()
+            .find(|compat| compat.as_str() == s)
+            .ok_or_else(|| {
+                let supported: Vec<String> = SeaOrmCompat::ALL
+                    .iter()
+                    .map(|compat| format!("`{}`", compat))
+                    .collect();
+                format!(
+                    "unsupported sea-orm version `{}`, expected {}",
+                    s,
+                    supported.join(", ")
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn versions() {
+        for compat in SeaOrmCompat::ALL {
+            assert_eq!(compat.as_str().parse::<SeaOrmCompat>(), Ok(compat));
+        }
+        assert_eq!(SeaOrmCompat::default(), SeaOrmCompat::LATEST);
+        assert_eq!(
+            "1".parse::<SeaOrmCompat>().unwrap_err(),
+            "unsupported sea-orm version `1`, expected `0.2`"
+        );
+
+        assert!(SeaOrmCompat::V0_2.matches("0.2.6"));
+        assert!(SeaOrmCompat::V0_2.matches("0.2.0-rc.1"));
+        assert!(!SeaOrmCompat::V0_2.matches("0.12.4"));
+        assert!(!SeaOrmCompat::V0_2.matches("1.0.0"));
+    }
+}
@@ -0,0 +1,259 @@
+//! Connections to databases which may still be starting, such as a database container started
+//! alongside the build in CI.
+//!
+//! The policy is read by build scripts from environment variables set by `awto compile database`,
+//! out of the `[database.connect]` section of `awto.toml` and its flags.
+
+use std::collections::hash_map::RandomState;
+use std::env;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::time::{Duration, Instant};
+
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::{ConnectOptions, Connection, Executor, PgPool};
+
+use crate::error::Error;
+
+/// Environment variable holding the milliseconds each connection attempt may take.
+pub const CONNECT_TIMEOUT_ENV: &str = "AWTO_CONNECT_TIMEOUT_MS";
+
+/// Environment variable holding the number of attempts made after a failed first attempt.
+pub const CONNECT_RETRIES_ENV: &str = "AWTO_CONNECT_RETRIES";
+
+/// Environment variable holding the milliseconds waited before the first retry, doubled for every other retry.
+pub const CONNECT_BACKOFF_ENV: &str = "AWTO_CONNECT_BACKOFF_MS";
+
+/// Environment variable holding the milliseconds statements may run, unlimited when unset.
+pub const STATEMENT_TIMEOUT_ENV: &str = "AWTO_STATEMENT_TIMEOUT_MS";
+
+/// Environment variable holding the milliseconds a sync waits for the lock of the database held by another sync.
+pub const LOCK_TIMEOUT_ENV: &str = "AWTO_LOCK_TIMEOUT_MS";
+
+/// Environment variable set to print each retry, and the models whose code was regenerated, as cargo warnings.
+pub const VERBOSE_ENV: &str = "AWTO_VERBOSE";
+
+/// Longest wait between two attempts, however many retries were made.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Timeouts and retries of the connections made to a database.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConnectPolicy {
+    /// Time each attempt may take to establish a connection.
+    pub timeout: Duration,
+    /// Number of attempts made after a failed first attempt.
+    pub retries: u32,
+    /// Wait before the first retry, doubled for every other retry with up to half of it left out at random.
+    pub backoff: Duration,
+    /// Time statements may run, set as the `statement_timeout` of every session.
+    pub statement_timeout: Option<Duration>,
+    /// Time a sync of the tables waits for another sync of the same database to finish, see [`SyncLock`](crate::lock::SyncLock).
+    pub lock_timeout: Duration,
+    /// Whether each retry is printed as a cargo warning.
+    pub verbose: bool,
+}
+
+impl Default for ConnectPolicy {
+    fn default() -> Self {
+        ConnectPolicy {
+            timeout: Duration::from_secs(10),
+            retries: 5,
+            backoff: Duration::from_millis(500),
+            statement_timeout: None,
+            lock_timeout: Duration::from_secs(60),
+            verbose: false,
+        }
+    }
+}
+
+impl ConnectPolicy {
+    /// Returns the policy set in the environment, with the [default](Self::default) of each unset or invalid variable.
+    pub fn from_env() -> Self {
+        fn millis(var: &str) -> Option<Duration> {
+            env::var(var).ok()?.parse().ok().map(Duration::from_millis)
+        }
+
+        let default = ConnectPolicy::default();
+        ConnectPolicy {
+            timeout: millis(CONNECT_TIMEOUT_ENV).unwrap_or(default.timeout),
+            retries: env::var(CONNECT_RETRIES_ENV)
+                .ok()
+                .and_then(|retries| retries.parse().ok())
+                .unwrap_or(default.retries),
+            backoff: millis(CONNECT_BACKOFF_ENV).unwrap_or(default.backoff),
+            statement_timeout: millis(STATEMENT_TIMEOUT_ENV).filter(|timeout| !timeout.is_zero()),
+            lock_timeout: millis(LOCK_TIMEOUT_ENV).unwrap_or(default.lock_timeout),
+            verbose: env::var_os(VERBOSE_ENV).is_some(),
+        }
+    }
+
+    /// Returns the wait before the retry following `attempt`, counted from 0, where `jitter` between 0 and 1
+    /// leaves out up to half of the exponential backoff so concurrent builds do not retry in lockstep.
+    pub fn delay(&self, attempt: u32, jitter: f64) -> Duration {
+        let backoff = self
+            .backoff
+            .checked_mul(2u32.saturating_pow(attempt))
+            .map_or(MAX_BACKOFF, |backoff| backoff.min(MAX_BACKOFF));
+
+        backoff.mul_f64(1.0 - jitter.clamp(0.0, 1.0) / 2.0)
+    }
+
+    /// Connects to the database at `uri`, retrying while it refuses connections, is starting up or does not answer in time.
+    ///
+    /// Failing authentication is not retried, as waiting does not fix a wrong password.
+    pub async fn connect(&self, uri: &str) -> Result<PgPool, Error> {
+        let options: PgConnectOptions = uri.parse().map_err(Error::Sqlx)?;
+        let start = Instant::now();
+        let mut attempt = 0;
+        loop {
+            let err = match tokio::time::timeout(self.timeout, options.connect()).await {
+                Ok(Ok(conn)) => {
+                    conn.close().await.map_err(Error::Sqlx)?;
+                    break;
+                }
+                Ok(Err(err)) => err,
+                Err(_) => sqlx::Error::Io(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("no connection within {:?}", self.timeout),
+                )),
+            };
+            if let Some(message) = authentication_failure(&err) {
+                return Err(Error::DatabaseAuthentication(message));
+            }
+            if !is_transient(&err) {
+                return Err(Error::Sqlx(err));
+            }
+            if attempt == self.retries {
+                return Err(Error::DatabaseUnreachable {
+                    attempts: attempt + 1,
+                    elapsed: start.elapsed(),
+                    source: err,
+                });
+            }
+
+            let delay = self.delay(attempt, jitter());
+            if self.verbose {
+                println!(
+                    "cargo:warning=awto: connection attempt {} of {} failed after {:.1?}: {}, retrying in {:.1?}",
+                    attempt + 1,
+                    self.retries + 1,
+                    start.elapsed(),
+                    err,
+                    delay
+                );
+            }
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+
+        let statement_timeout = self.statement_timeout;
+        PgPoolOptions::new()
+            .connect_timeout(self.timeout)
+            .after_connect(move |conn| {
+                Box::pin(async move {
+                    if let Some(timeout) = statement_timeout {
+                        conn.execute(
+                            format!("SET statement_timeout = {}", timeout.as_millis()).as_str(),
+                        )
+                        .await?;
+                    }
+                    Ok(())
+                })
+            })
+            .connect_with(options)
+            .await
+            .map_err(Error::Sqlx)
+    }
+}
+
+/// Returns the message of `err` when the database rejected the user or its password.
+fn authentication_failure(err: &sqlx::Error) -> Option<String> {
+    match err {
+        // invalid_password and invalid_authorization_specification
+        sqlx::Error::Database(err)
+            if matches!(err.code().as_deref(), Some("28P01") | Some("28000")) =>
+        {
+            Some(err.message().to_string())
+        }
+        _ => None,
+    }
+}
+
+/// Returns whether `err` may go away by connecting again, such as a refused connection or a database starting up.
+fn is_transient(err: &sqlx::Error) -> bool {
+    match err {
+        sqlx::Error::Io(err) => matches!(
+            err.kind(),
+            io::ErrorKind::ConnectionRefused
+                | io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::NotConnected
+                | io::ErrorKind::TimedOut
+                | io::ErrorKind::UnexpectedEof
+        ),
+        // cannot_connect_now, while the database system is starting up or shutting down
+        sqlx::Error::Database(err) => err.code().as_deref() == Some("57P03"),
+        sqlx::Error::PoolTimedOut => true,
+        _ => false,
+    }
+}
+
+/// Returns a random number between 0 and 1, from the randomly seeded hasher of the standard library.
+fn jitter() -> f64 {
+    let random = RandomState::new().build_hasher().finish();
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn delay() {
+        let policy = ConnectPolicy::default();
+        assert_eq!(policy.delay(0, 0.0), Duration::from_millis(500));
+        assert_eq!(policy.delay(0, 1.0), Duration::from_millis(250));
+        assert_eq!(policy.delay(3, 0.0), Duration::from_secs(4));
+        assert_eq!(policy.delay(3, 0.5), Duration::from_secs(3));
+        assert_eq!(policy.delay(10, 0.0), MAX_BACKOFF);
+        assert_eq!(policy.delay(u32::MAX, 0.0), MAX_BACKOFF);
+
+        for _ in 0..100 {
+            let jitter = jitter();
+            assert!((0.0..1.0).contains(&jitter));
+        }
+    }
+
+    #[test]
+    fn transient_errors() {
+        let refused = sqlx::Error::Io(io::Error::from(io::ErrorKind::ConnectionRefused));
+        assert!(is_transient(&refused));
+        assert!(authentication_failure(&refused).is_none());
+        assert!(is_transient(&sqlx::Error::PoolTimedOut));
+        assert!(!is_transient(&sqlx::Error::Io(io::Error::from(
+            io::ErrorKind::PermissionDenied
+        ))));
+        assert!(!is_transient(&sqlx::Error::RowNotFound));
+    }
+
+    #[tokio::test]
+    async fn connect_refused() {
+        // Nothing listens on port 1, which refuses connections right away
+        let policy = ConnectPolicy {
+            retries: 2,
+            backoff: Duration::from_millis(1),
+            ..ConnectPolicy::default()
+        };
+        let err = policy
+            .connect("postgres://postgres@127.0.0.1:1/awto")
+            .await
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("could not connect to the database after 3 attempts in "));
+        match err {
+            Error::DatabaseUnreachable { attempts, .. } => assert_eq!(attempts, 3),
+            err => panic!("expected an unreachable database, got {}", err),
+        }
+    }
+}
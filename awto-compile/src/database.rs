@@ -1,697 +1,13511 @@
-use std::{borrow::Cow, env, fmt::Write, io};
+use std::{
+    borrow::Cow,
+    collections::{BTreeSet, HashMap},
+    env,
+    fmt::Write,
+    io, iter,
+    path::Path,
+    time::Duration,
+};
 
 use awto::{
-    database::{DatabaseColumn, DatabaseDefault, DatabaseTable, DatabaseType},
-    schema::{Model, Role},
+    database::{
+        derived_name, derived_name_prefix, quote_ident, DatabaseColumn, DatabaseDefault,
+        DatabaseFulltext, DatabasePartition, DatabaseProjection, DatabaseTable, DatabaseType,
+        EntityVisibility, Fake, Normalizer, PartitionInterval, MAX_IDENTIFIER_LEN,
+    },
+    schema::{Deprecation, Model, Role, RustField, Sensitivity},
 };
-use proc_macro2::Literal;
+use heck::{CamelCase, MixedCase};
+use proc_macro2::{Literal, TokenStream};
 use quote::{format_ident, quote};
-use sqlx::{Executor, PgPool};
+use sqlx::{Executor, PgConnection, PgPool};
 use tokio_stream::StreamExt;
 
+pub use awto::database::{DefaultAuthorization, Localization, MapStorage, Temporal, Visibility};
+
 use crate::{
+    connect::ConnectPolicy,
     error::Error,
-    util::{is_ty_option, is_ty_vec, strip_ty_option},
+    format::{format_rust, format_sql, RustWriter},
+    generator::{FileKind, GeneratedFile, Generator, GeneratorOptions},
+    incremental::{database_roles, ModelCache},
+    lock::SyncLock,
+    naming::{pluralize_with, projection_plural},
+    narrowing::{Narrowing, NarrowingCheck, NarrowingViolation, FORCE_NARROWING_ENV},
+    profiling::GenerationProfile,
+    provenance::{
+        annotate_sources, watch_profile, write_generated, Annotation, CommentStyle,
+        GeneratedWriter, GenerationManifest, GENERATOR_VERSION,
+    },
+    scope::{check_scopes, compile_scope_condition, parse_scope, scope_arg_names, scope_param_ty},
+    util::{
+        column_rust_type, column_variant_ident, deprecated_attribute, deprecation_note,
+        escape_markdown, escape_markdown_cell, ignored_deprecated_value, into_field_value,
+        is_computed, is_optional_insert, is_ty_option, is_ty_vec, map_entity_ty, map_value_ty,
+        markdown_code, newtype_inner_value, rust_ident, schema_ty, sensitivity_serde_attr,
+        strip_ty_option, write_if_changed, TableKey,
+    },
+    warning::Warning,
 };
 
 const COMPILED_RUST_FILE: &str = "app.rs";
+const COMPILED_TESTS_FILE: &str = "app_tests.rs";
+const COMPILED_BENCHES_FILE: &str = "app_benches.rs";
+const COMPILED_DOCS_FILE: &str = "docs.md";
+const EXTENSIONS_DIR: &str = "src/ext";
+/// Length under which the `String` keys of a table are too short for its benchmarks to seed unique keys.
+const MIN_BENCH_KEY_LEN: i32 = 12;
+/// File in `OUT_DIR` listing the tables whose entities were generated by the previous build.
+pub const ENTITIES_FILE: &str = "entities.txt";
 
-#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
-pub struct CompileDatabaseResult {
-    pub queries_executed: usize,
-    pub rows_affected: u64,
-}
+/// Environment variable set by `awto compile database` when it writes the generated code into the sources of the package,
+/// which declare the entities as modules of their own files rather than including them from `OUT_DIR`.
+pub const SOURCE_CODEGEN_ENV: &str = "AWTO_SOURCE_CODEGEN";
 
-#[cfg(feature = "async")]
-pub async fn compile_database(
-    uri: &str,
-    models: Vec<Model>,
-) -> Result<CompileDatabaseResult, Box<dyn std::error::Error>> {
-    use tokio::fs;
+/// Generators recorded in the generation manifest for the files besides the entities.
+const COMPILED_FILE_GENERATORS: [(&str, &str); 3] = [
+    (COMPILED_TESTS_FILE, "database/tests"),
+    (COMPILED_BENCHES_FILE, "database/benches"),
+    (COMPILED_DOCS_FILE, "database/docs"),
+];
 
-    let out_dir = env::var("OUT_DIR").unwrap();
-    let pool = PgPool::connect(uri).await?;
-    let compiler = DatabaseCompiler::from_pool(&pool, models);
+/// Transaction helpers generated as `database::tx`.
+const TX_MODULE: &str = r#"
+/// Transactions running a callback, committing or rolling back depending on its result.
+pub mod tx {
+    use std::{fmt, future::Future, pin::Pin};
 
-    let generated_code = compiler.compile_generated_code();
-    if !generated_code.is_empty() {
-        let rs_path = format!("{}/{}", out_dir, COMPILED_RUST_FILE);
-        fs::write(rs_path, generated_code).await?;
-    }
+    use ::sea_orm::{ConnectionTrait, DatabaseConnection, DatabaseTransaction, DbErr, Statement};
 
-    compiler.append_sea_orm_models().await?;
+    /// Future returned by the callback of [`with_txn`].
+    pub type TxnFuture<'c, T, E> = Pin<Box<dyn Future<Output = Result<T, E>> + Send + 'c>>;
 
-    let sql = compiler.compile().await?;
-    if !sql.is_empty() {
-        let results = pool
-            .execute_many(sql.as_str())
-            .collect::<Result<Vec<_>, _>>()
-            .await?;
-        let queries_executed = results.len();
-        let rows_affected = results
-            .iter()
-            .fold(0, |acc, result| result.rows_affected() + acc);
+    /// Isolation level of a transaction started by [`with_txn_isolation`].
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum IsolationLevel {
+        /// Queries see the rows committed before they started, the default of Postgres.
+        ReadCommitted,
+        /// Queries see the rows committed before the transaction started.
+        RepeatableRead,
+        /// Transactions behave as if they ran one after another, failing when they cannot.
+        Serializable,
+    }
 
-        Ok(CompileDatabaseResult {
-            queries_executed,
-            rows_affected,
-        })
-    } else {
-        Ok(CompileDatabaseResult::default())
+    impl fmt::Display for IsolationLevel {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Self::ReadCommitted => write!(f, "READ COMMITTED"),
+                Self::RepeatableRead => write!(f, "REPEATABLE READ"),
+                Self::Serializable => write!(f, "SERIALIZABLE"),
+            }
+        }
     }
-}
 
-#[cfg(not(feature = "async"))]
-pub async fn compile_database(
-    uri: &str,
-    models: Vec<Model>,
-) -> Result<CompileDatabaseResult, Box<dyn std::error::Error>> {
-    use std::fs;
+    /// Options of a transaction started by [`Transactional::run_txn`].
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct TxnOptions {
+        /// Isolation level of the transaction, or `None` for the default of the database.
+        pub isolation: Option<IsolationLevel>,
+        /// Tenant set with [`super::tenant::set_tenant`] before the callback runs.
+        pub tenant_id: Option<::uuid::Uuid>,
+    }
 
-    let out_dir = env::var("OUT_DIR").unwrap();
-    let pool = PgPool::connect(uri).await?;
-    let compiler = DatabaseCompiler::from_pool(&pool, models);
+    /// A connection which transactions can be run on.
+    ///
+    /// Running a transaction on a [`DatabaseTransaction`] reuses it instead of opening a nested transaction,
+    /// in which case the isolation level is ignored.
+    pub trait Transactional: Sync {
+        /// Runs `callback` inside a transaction started with `options`, see [`with_txn`].
+        fn run_txn<'a, F, T, E>(
+            &'a self,
+            options: TxnOptions,
+            callback: F,
+        ) -> TxnFuture<'a, T, E>
+        where
+            F: for<'c> FnOnce(&'c DatabaseTransaction) -> TxnFuture<'c, T, E> + Send + 'a,
+            T: Send + 'a,
+            E: From<DbErr> + Send + 'a;
+    }
 
-    let generated_code = compiler.compile_generated_code();
-    if !generated_code.is_empty() {
-        let rs_path = format!("{}/{}", out_dir, COMPILED_RUST_FILE);
-        fs::write(rs_path, generated_code)?;
+    impl Transactional for DatabaseConnection {
+        fn run_txn<'a, F, T, E>(
+            &'a self,
+            options: TxnOptions,
+            callback: F,
+        ) -> TxnFuture<'a, T, E>
+        where
+            F: for<'c> FnOnce(&'c DatabaseTransaction) -> TxnFuture<'c, T, E> + Send + 'a,
+            T: Send + 'a,
+            E: From<DbErr> + Send + 'a,
+        {
+            Box::pin(async move {
+                let txn = self.begin().await?;
+                if let Some(isolation) = options.isolation {
+                    let sql = format!("SET TRANSACTION ISOLATION LEVEL {}", isolation);
+                    txn.execute(Statement::from_string(txn.get_database_backend(), sql))
+                        .await?;
+                }
+                if let Some(tenant_id) = options.tenant_id {
+                    super::tenant::set_tenant(&txn, tenant_id).await?;
+                }
+
+                match callback(&txn).await {
+                    Ok(value) => {
+                        txn.commit().await?;
+                        Ok(value)
+                    }
+                    Err(err) => {
+                        // The original error is more useful than a failed rollback,
+                        // which the database performs anyway once the connection is released.
+                        let _ = txn.rollback().await;
+                        Err(err)
+                    }
+                }
+            })
+        }
     }
 
-    compiler.append_sea_orm_models()?;
+    impl Transactional for DatabaseTransaction {
+        fn run_txn<'a, F, T, E>(
+            &'a self,
+            options: TxnOptions,
+            callback: F,
+        ) -> TxnFuture<'a, T, E>
+        where
+            F: for<'c> FnOnce(&'c DatabaseTransaction) -> TxnFuture<'c, T, E> + Send + 'a,
+            T: Send + 'a,
+            E: From<DbErr> + Send + 'a,
+        {
+            Box::pin(async move {
+                if let Some(tenant_id) = options.tenant_id {
+                    super::tenant::set_tenant(self, tenant_id).await?;
+                }
+                callback(self).await
+            })
+        }
+    }
 
-    let sql = compiler.compile().await?;
-    if !sql.is_empty() {
-        let results = pool
-            .execute_many(sql.as_str())
-            .collect::<Result<Vec<_>, _>>()
-            .await?;
-        let queries_executed = results.len();
-        let rows_affected = results
-            .iter()
-            .fold(0, |acc, result| result.rows_affected() + acc);
+    /// Runs `callback` inside a transaction, committing if it returns `Ok` and rolling back otherwise.
+    ///
+    /// When `db` is already a transaction, `callback` runs inside it and the outer call decides whether to commit.
+    pub async fn with_txn<C, F, T, E>(db: &C, callback: F) -> Result<T, E>
+    where
+        C: Transactional,
+        F: for<'c> FnOnce(&'c DatabaseTransaction) -> TxnFuture<'c, T, E> + Send,
+        T: Send,
+        E: From<DbErr> + Send,
+    {
+        db.run_txn(TxnOptions::default(), callback).await
+    }
 
-        Ok(CompileDatabaseResult {
-            queries_executed,
-            rows_affected,
-        })
-    } else {
-        Ok(CompileDatabaseResult::default())
+    /// Runs `callback` inside a transaction with the given isolation level.
+    ///
+    /// The isolation level is ignored when `db` is already a transaction.
+    pub async fn with_txn_isolation<C, F, T, E>(
+        db: &C,
+        isolation: IsolationLevel,
+        callback: F,
+    ) -> Result<T, E>
+    where
+        C: Transactional,
+        F: for<'c> FnOnce(&'c DatabaseTransaction) -> TxnFuture<'c, T, E> + Send,
+        T: Send,
+        E: From<DbErr> + Send,
+    {
+        db.run_txn(
+            TxnOptions {
+                isolation: Some(isolation),
+                ..TxnOptions::default()
+            },
+            callback,
+        )
+        .await
     }
 }
+"#;
 
-pub struct DatabaseCompiler<'pool> {
-    pool: Cow<'pool, PgPool>,
-    models: Vec<Model>,
-}
+/// Tenant helpers for row-level security policies generated as `database::tenant`.
+const TENANT_MODULE: &str = r#"
+/// Tenants of row-level security policies, set on the transactions of their queries.
+pub mod tenant {
+    use ::sea_orm::{ConnectionTrait, DatabaseTransaction, DbBackend, DbErr, Statement};
 
-impl<'pool> DatabaseCompiler<'pool> {
-    pub async fn connect(
-        uri: &str,
-        models: Vec<Model>,
-    ) -> Result<DatabaseCompiler<'_>, sqlx::Error> {
-        let pool = sqlx::PgPool::connect(uri).await?;
+    use super::tx::{Transactional, TxnFuture, TxnOptions};
 
-        Ok(DatabaseCompiler {
-            pool: Cow::Owned(pool),
-            models,
-        })
-    }
+    /// Setting holding the current tenant, read by policies with `current_setting('app.tenant_id')::uuid`.
+    pub const TENANT_SETTING: &str = "app.tenant_id";
 
-    pub fn from_pool(pool: &'pool PgPool, models: Vec<Model>) -> DatabaseCompiler<'pool> {
-        DatabaseCompiler {
-            pool: Cow::Borrowed(pool),
-            models,
+    /// Sets the current tenant of `txn` until it commits or rolls back, like `SET LOCAL`.
+    ///
+    /// The tenant is only set on transactions, as a session setting would remain on the pooled connection
+    /// and leak into queries of other tenants.
+    pub async fn set_tenant(txn: &DatabaseTransaction, tenant_id: ::uuid::Uuid) -> Result<(), DbErr> {
+        let backend = txn.get_database_backend();
+        if backend != DbBackend::Postgres {
+            return Err(DbErr::Custom(format!(
+                "row-level security is not supported on {:?}",
+                backend
+            )));
         }
+
+        txn.execute(Statement::from_sql_and_values(
+            backend,
+            "SELECT set_config($1, $2, true)",
+            vec![TENANT_SETTING.into(), tenant_id.to_string().into()],
+        ))
+        .await?;
+
+        Ok(())
     }
 
-    pub async fn compile(&self) -> Result<String, Error> {
-        let mut sql = String::new();
+    /// Runs `callback` inside a transaction scoped to `tenant_id`, like [`super::tx::with_txn`].
+    ///
+    /// When `db` is already a transaction, the tenant stays set for the rest of it.
+    pub async fn with_tenant_txn<C, F, T, E>(
+        db: &C,
+        tenant_id: ::uuid::Uuid,
+        callback: F,
+    ) -> Result<T, E>
+    where
+        C: Transactional,
+        F: for<'c> FnOnce(&'c DatabaseTransaction) -> TxnFuture<'c, T, E> + Send,
+        T: Send,
+        E: From<DbErr> + Send,
+    {
+        db.run_txn(
+            TxnOptions {
+                tenant_id: Some(tenant_id),
+                ..TxnOptions::default()
+            },
+            callback,
+        )
+        .await
+    }
+}
+"#;
 
-        for (_, table) in self.database_tables() {
-            let db_columns = self.fetch_table(table).await?;
+/// Outbox helpers generated inside the `database::outbox` entity module when any table records events.
+const OUTBOX_MODULE: &str = r#"
+    /// Change of a row recorded in the outbox.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum EventKind {
+        /// The row was inserted.
+        Created,
+        /// The row was updated.
+        Updated,
+        /// The row was deleted.
+        Deleted,
+    }
 
-            match db_columns {
-                Some(db_columns) => {
-                    writeln!(sql, "{}", self.write_sync_sql(table, &db_columns).await).unwrap();
-                }
-                None => {
-                    writeln!(sql, "{}", self.write_table_create_sql(table)).unwrap();
-                }
+    impl EventKind {
+        /// Returns the suffix of the event type, such as `created`.
+        pub fn as_str(&self) -> &'static str {
+            match self {
+                Self::Created => "created",
+                Self::Updated => "updated",
+                Self::Deleted => "deleted",
             }
         }
-
-        Ok(sql.trim().to_string())
     }
 
-    /// Compiles generated Rust code from schemas and services.
-    pub fn compile_generated_code(&self) -> String {
-        let mut code = String::new();
+    /// Records a change of the `aggregate_type` row `aggregate_id`, to be called in the transaction changing it.
+    ///
+    /// The event type is the aggregate type followed by the kind of change, such as `product.created`,
+    /// and the payload is the row serialized as JSON.
+    pub async fn record<'a, C, P>(
+        db: &'a C,
+        aggregate_type: &str,
+        kind: EventKind,
+        aggregate_id: ::uuid::Uuid,
+        payload: &P,
+    ) -> Result<(), ::sea_orm::DbErr>
+    where
+        C: ::sea_orm::ConnectionTrait<'a>,
+        P: ::serde::Serialize,
+    {
+        use ::sea_orm::ActiveModelTrait;
 
-        for (model, table) in self.database_tables() {
-            let ident = format_ident!("{}", model.name);
-            let db_module_ident = format_ident!("{}", table.name);
+        let payload = ::serde_json::to_string(payload).map_err(|err| {
+            ::sea_orm::DbErr::Custom(format!("could not serialize event payload: {}", err))
+        })?;
 
-            let mut from_schema_fields = Vec::new();
-            let mut from_db_fields = Vec::new();
+        ActiveModel {
+            event_type: ::sea_orm::Set(format!("{}.{}", aggregate_type, kind.as_str())),
+            aggregate_type: ::sea_orm::Set(aggregate_type.to_string()),
+            aggregate_id: ::sea_orm::Set(aggregate_id),
+            payload: ::sea_orm::Set(payload),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?;
 
-            for field in &model.fields {
-                let field_ident = format_ident!("{}", field.name);
+        Ok(())
+    }
 
-                let ty = strip_ty_option(&field.ty);
+    /// Marks up to `batch_size` unpublished events as published and returns them, oldest first.
+    ///
+    /// Relays should drain inside a transaction and publish the events before committing,
+    /// so events are only marked as published once they were relayed.
+    /// Events locked by the transaction of a concurrent relay are skipped.
+    pub async fn drain<'a, C>(db: &'a C, batch_size: u64) -> Result<Vec<Model>, ::sea_orm::DbErr>
+    where
+        C: ::sea_orm::ConnectionTrait<'a>,
+    {
+        use ::sea_orm::EntityTrait;
 
-                if is_ty_vec(ty) {
-                    from_schema_fields.push(
-                        quote!(#field_ident: val.#field_ident.into_iter().map(|v| v.into()).collect()),
-                    );
-                    from_db_fields.push(
-                        quote!(#field_ident: val.#field_ident.into_iter().map(|v| v.into()).collect()),
-                    );
-                } else {
-                    from_schema_fields.push(quote!(#field_ident: val.#field_ident.into()));
-                    from_db_fields.push(quote!(#field_ident: val.#field_ident.into()));
-                }
-            }
+        let backend = db.get_database_backend();
+        if backend != ::sea_orm::DbBackend::Postgres {
+            return Err(::sea_orm::DbErr::Custom(format!(
+                "draining the outbox is not supported on {:?}",
+                backend
+            )));
+        }
 
-            let expanded = quote!(
-                impl ::std::convert::From<crate::#db_module_ident::Model> for ::schema::#ident {
-                    #[allow(unused_variables)]
-                    fn from(val: crate::#db_module_ident::Model) -> Self {
-                        Self {
-                            #( #from_schema_fields, )*
-                        }
-                    }
-                }
+        let mut events = Entity::find()
+            .from_raw_sql(::sea_orm::Statement::from_sql_and_values(
+                backend,
+                r"UPDATE outbox SET published_at = NOW() WHERE id IN (
+                    SELECT id FROM outbox WHERE published_at IS NULL
+                    ORDER BY created_at, id LIMIT $1 FOR UPDATE SKIP LOCKED
+                ) RETURNING *",
+                vec![(batch_size as i64).into()],
+            ))
+            .all(db)
+            .await?;
+        events.sort_by(|a, b| (a.created_at, a.id).cmp(&(b.created_at, b.id)));
 
-                impl ::std::convert::From<::schema::#ident> for crate::#db_module_ident::Model {
-                    #[allow(unused_variables)]
-                    fn from(val: ::schema::#ident) -> Self {
-                        Self {
-                            #( #from_db_fields, )*
-                        }
-                    }
-                }
-            );
+        Ok(events)
+    }
+"#;
 
-            write!(code, "{}", expanded.to_string()).unwrap();
-        }
+/// Idempotency helpers generated inside the `database::idempotency_keys` entity module when any table has idempotent creates.
+const IDEMPOTENCY_MODULE: &str = r#"
+    use std::{fmt, time::Duration};
 
-        for (model, table) in self.database_sub_tables() {
-            let ident = format_ident!("{}", model.name);
-            let db_module_ident = format_ident!("{}", table.name);
+    use ::sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
 
-            let active_values = model.fields.iter().map(|field| {
-                let field_ident = format_ident!("{}", field.name);
-                
-                let self_field = if is_ty_option(&field.ty) {
-                    let db_field = table.columns.iter().find(|column| column.name == field.name).unwrap();
-                    match &db_field.default {
-                        Some(DatabaseDefault::Bool(b)) => quote!(self.#field_ident.unwrap_or(#b)),
-                        Some(DatabaseDefault::Float(f)) => {
-                            let f = Literal::i64_unsuffixed(*f);
-                            quote!(self.#field_ident.unwrap_or(#f))
-                        },
-                        Some(DatabaseDefault::Int(i)) => {
-                            let i = Literal::u64_unsuffixed(*i);
-                            quote!(self.#field_ident.unwrap_or(#i))
-                        },
-                        Some(DatabaseDefault::String(s)) => quote!(self.#field_ident.unwrap_or(#s)),
-                        _ => quote!(self.#field_ident),
-                    }
-                } else {
-                    quote!(self.#field_ident)
-                };
+    /// Time the keys of creates are replayed for, unless configured otherwise.
+    pub const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
 
-                quote!(
-                    #field_ident: ::sea_orm::entity::IntoActiveValue::into_active_value(#self_field).into()
-                )
-            });
-            
-            let expanded = quote!(
-                impl ::sea_orm::entity::IntoActiveModel<crate::#db_module_ident::ActiveModel> for ::schema::#ident {
-                    fn into_active_model(self) -> crate::#db_module_ident::ActiveModel {
-                        crate::#db_module_ident::ActiveModel {
-                            #( #active_values, )*
-                            ..Default::default()
-                        }
-                    }
-                }
-            );
+    /// Error of a create with an idempotency key.
+    #[derive(Debug)]
+    pub enum IdempotencyError {
+        /// The key was used by a request with another body, or to create a row of another table.
+        Conflict,
+        /// The database failed.
+        Db(::sea_orm::DbErr),
+    }
 
-            write!(code, "{}", expanded.to_string()).unwrap();
+    impl fmt::Display for IdempotencyError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Self::Conflict => write!(f, "idempotency key was already used by a different request"),
+                Self::Db(err) => write!(f, "database error: {}", err),
+            }
         }
-
-        code.trim().to_string()
     }
 
-    #[cfg(feature = "async")]
-    async fn append_sea_orm_models(&self) -> Result<(), io::Error> {
-        use tokio::fs;
-        use tokio::io::AsyncWriteExt;
-
-        let out_dir = env::var("OUT_DIR").unwrap();
-        let rs_path = format!("{}/{}", out_dir, COMPILED_RUST_FILE);
-
-        let mut file = fs::OpenOptions::new().append(true).open(&rs_path).await?;
+    impl ::std::error::Error for IdempotencyError {}
 
-        for (_, table) in self.database_tables() {
-            file.write(format!("pub mod {} {{", table.name).as_bytes()).await?;
-            file.write(format!(r#"    sea_orm::include_model!("{}");"#, table.name).as_bytes()).await?;
-            file.write(b"}").await?;
+    impl From<::sea_orm::DbErr> for IdempotencyError {
+        fn from(err: ::sea_orm::DbErr) -> Self {
+            Self::Db(err)
         }
+    }
 
-        Ok(())
+    /// Returns the hash of the body of a create, which retries with the same key must repeat.
+    ///
+    /// The FNV-1a hash only guards against a client reusing its own key by mistake, it is not cryptographic.
+    pub fn request_hash(body: &[u8]) -> String {
+        let hash = body
+            .iter()
+            .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3));
+
+        format!("{:016x}", hash)
     }
-    
-    #[cfg(not(feature = "async"))]
-    fn append_sea_orm_models(&self) -> Result<(), io::Error> {
-        use std::fs;
-        use std::io::Write;
 
-        let out_dir = env::var("OUT_DIR").unwrap();
-        let rs_path = format!("{}/{}", out_dir, COMPILED_RUST_FILE);
+    /// Claims `key` for the create of a `scope` row, to be called in the transaction inserting it.
+    ///
+    /// Returns the key of the row created by the first request with `key` when this request retries it,
+    /// or `None` when the row is to be inserted and recorded with [`complete`].
+    /// Keys are forgotten once they are older than `ttl`, and a request waits for a concurrent request
+    /// with the same key to commit or roll back.
+    pub async fn claim<'a, C>(
+        db: &'a C,
+        scope: &str,
+        key: &str,
+        request_hash: &str,
+        ttl: Duration,
+    ) -> Result<Option<String>, IdempotencyError>
+    where
+        C: ::sea_orm::ConnectionTrait<'a>,
+    {
+        let backend = db.get_database_backend();
+        if backend != ::sea_orm::DbBackend::Postgres {
+            return Err(::sea_orm::DbErr::Custom(format!("idempotency keys are not supported on {:?}", backend)).into());
+        }
 
-        let mut file = fs::OpenOptions::new().append(true).open(&rs_path)?;
+        if let Some(expired) = expired_before(ttl) {
+            Entity::delete_many()
+                .filter(Column::Key.eq(key))
+                .filter(Column::CreatedAt.lt(expired))
+                .exec(db)
+                .await?;
+        }
+        let claimed = db
+            .execute(::sea_orm::Statement::from_sql_and_values(
+                backend,
+                "INSERT INTO idempotency_keys (key, scope, request_hash) VALUES ($1, $2, $3) ON CONFLICT (key) DO NOTHING",
+                vec![key.into(), scope.into(), request_hash.into()],
+            ))
+            .await?
+            .rows_affected();
+        if claimed == 1 {
+            return Ok(None);
+        }
 
-        for (_, table) in self.database_tables() {
-            write!(file, "pub mod {} {{", table.name).unwrap();
-            write!(file, r#"    sea_orm::include_model!("{}");"#, table.name).unwrap();
-            write!(file, "}}").unwrap();
+        match Entity::find_by_id(key.to_string()).one(db).await? {
+            Some(claim) if claim.scope == scope && claim.request_hash == request_hash => match claim.row_key {
+                Some(row_key) => Ok(Some(row_key)),
+                None => Err(IdempotencyError::Conflict),
+            },
+            _ => Err(IdempotencyError::Conflict),
         }
+    }
+
+    /// Records `row_key`, the key of the row created by the request which claimed `key`, replayed to its retries.
+    pub async fn complete<'a, C>(db: &'a C, key: &str, row_key: &str) -> Result<(), ::sea_orm::DbErr>
+    where
+        C: ::sea_orm::ConnectionTrait<'a>,
+    {
+        Entity::update_many()
+            .col_expr(Column::RowKey, ::sea_orm::sea_query::Expr::value(row_key))
+            .filter(Column::Key.eq(key))
+            .exec(db)
+            .await?;
 
         Ok(())
     }
 
-    fn database_tables(&self) -> Vec<(&Model, &DatabaseTable)> {
-        self.models.iter().fold(Vec::new(), |mut acc, model| {
-            let roles = model
-                .roles
-                .iter()
-                .filter_map(|role| match role {
-                    Role::DatabaseTable(database_table) => Some((model, database_table)),
-                    _ => None,
-                })
-                .collect::<Vec<_>>();
-
-            acc.extend(roles);
+    /// Deletes the keys older than `ttl`, which are no longer replayed, and returns how many were deleted.
+    ///
+    /// Claiming an expired key replaces it already, so this only keeps the table from growing, such as in a periodic task.
+    pub async fn purge_expired<'a, C>(db: &'a C, ttl: Duration) -> Result<u64, ::sea_orm::DbErr>
+    where
+        C: ::sea_orm::ConnectionTrait<'a>,
+    {
+        let expired = match expired_before(ttl) {
+            Some(expired) => expired,
+            None => return Ok(0),
+        };
+        let result = Entity::delete_many().filter(Column::CreatedAt.lt(expired)).exec(db).await?;
 
-            acc
-        })
+        Ok(result.rows_affected)
     }
 
-    fn database_sub_tables(&self) -> Vec<(&Model, &DatabaseTable)> {
-        self.models.iter().fold(Vec::new(), |mut acc, model| {
-            let roles = model
-                .roles
-                .iter()
-                .filter_map(|role| match role {
-                    Role::DatabaseSubTable(database_sub_table) => Some((model, database_sub_table)),
-                    _ => None,
-                })
-                .collect::<Vec<_>>();
+    /// Returns the time before which keys expire, or `None` when `ttl` goes back further than time does.
+    fn expired_before(ttl: Duration) -> Option<::chrono::DateTime<::chrono::FixedOffset>> {
+        let ttl = ::chrono::Duration::from_std(ttl).ok()?;
+        ::chrono::Utc::now()
+            .checked_sub_signed(ttl)
+            .map(|expired| expired.with_timezone(&::chrono::FixedOffset::east(0)))
+    }
+"#;
 
-            acc.extend(roles);
+/// Name of the table events are recorded in by tables with `#[awto(events)]`.
+pub const OUTBOX_TABLE: &str = "outbox";
 
-            acc
-        })
+/// Returns a required column of a table generated by awto, such as the outbox.
+fn generated_column(name: &str, ty: DatabaseType) -> DatabaseColumn {
+    DatabaseColumn {
+        name: name.to_string(),
+        ty,
+        nullable: false,
+        default: None,
+        unique: false,
+        constraint: None,
+        primary_key: false,
+        references: None,
+        reference_constraint: None,
+        cross_database: false,
+        locales: Vec::new(),
+        computed: None,
+        normalize: Vec::new(),
+        fake: None,
+        backfill: None,
     }
+}
 
-    async fn fetch_table(
-        &self,
-        table: &DatabaseTable,
-    ) -> Result<Option<Vec<DatabaseColumn>>, Error> {
-        #[derive(Debug, sqlx::FromRow)]
-        struct ColumnsQuery {
-            column_name: String,
-            column_default: Option<String>,
-            is_nullable: String,
-            data_type: String,
-            character_maximum_length: Option<i32>,
-            is_primary_key: bool,
-            is_unique: bool,
-            reference: Option<String>,
-        }
-
-        let raw_columns: Vec<ColumnsQuery> = sqlx::query_as(FETCH_TABLE_QUERY)
-            .bind("public")
-            .bind(&table.name)
-            .fetch_all(&*self.pool)
-            .await
-            .map_err(Error::Sqlx)?;
+/// Returns the outbox table, which is created alongside the tables of the models when any of them records events.
+pub fn outbox_table() -> DatabaseTable {
+    let column = generated_column;
 
-        if raw_columns.is_empty() {
-            return Ok(None);
-        }
+    DatabaseTable {
+        name: OUTBOX_TABLE.to_string(),
+        columns: vec![
+            DatabaseColumn {
+                default: Some(DatabaseDefault::Raw("uuid_generate_v4()".to_string())),
+                primary_key: true,
+                ..column("id", DatabaseType::Uuid)
+            },
+            // Unlike `NOW()`, the clock advances within a transaction, which keeps its events ordered
+            DatabaseColumn {
+                default: Some(DatabaseDefault::Raw("clock_timestamp()".to_string())),
+                ..column("created_at", DatabaseType::Timestamptz)
+            },
+            column("event_type", DatabaseType::Text(Some(255))),
+            column("aggregate_type", DatabaseType::Text(Some(255))),
+            column("aggregate_id", DatabaseType::Uuid),
+            column("payload", DatabaseType::Text(None)),
+            DatabaseColumn {
+                nullable: true,
+                ..column("published_at", DatabaseType::Timestamptz)
+            },
+        ],
+        database: None,
+        order_by: None,
+        fulltext: None,
+        partition: None,
+        policies: Vec::new(),
+        projections: Vec::new(),
+        scopes: Vec::new(),
+        readonly: false,
+        external: false,
+        skip_rest: true,
+        upsert_on: None,
+        versioned: false,
+        events: false,
+        notify: false,
+        subscriptions: false,
+        idempotent_create: false,
+        temporal: None,
+        localization: Localization::default(),
+        authorization: None,
+        default_authorization: DefaultAuthorization::Allow,
+        entity_attrs: Vec::new(),
+        entity_derives: Vec::new(),
+        plural: None,
+        visibility: EntityVisibility::default(),
+    }
+}
 
-        let columns: Vec<DatabaseColumn> = raw_columns
-            .into_iter()
-            .map(|col| {
-                let column_name = col.column_name;
-                let character_maximum_length = col.character_maximum_length;
-
-                Ok(DatabaseColumn {
-                    name: column_name.clone(),
-                    ty: col
-                        .data_type
-                        .parse::<DatabaseType>()
-                        .map(|database_type| {
-                            if let Some(max_len) = character_maximum_length {
-                                if matches!(database_type, DatabaseType::Text(None)) {
-                                    return DatabaseType::Text(Some(max_len));
-                                }
-                            }
+/// Name of the table the idempotency keys of creates are stored in by tables with `#[awto(idempotent_create)]`.
+pub const IDEMPOTENCY_TABLE: &str = "idempotency_keys";
 
-                            database_type
-                        })
-                        .map_err(|_| Error::UnsupportedType(table.name.clone(), column_name))?,
-                    nullable: col.is_nullable == "YES",
-                    default: col.column_default.map(|def| {
-                        if def.starts_with('\'') {
-                            let s = def
-                                .strip_prefix('\'')
-                                .unwrap()
-                                .splitn(2, '\'')
-                                .next()
-                                .unwrap()
-                                .to_string();
-                            DatabaseDefault::String(s)
-                        } else if def == "true" {
-                            DatabaseDefault::Bool(true)
-                        } else if def == "false" {
-                            DatabaseDefault::Bool(false)
-                        } else if let Ok(num) = def.parse::<u64>() {
-                            DatabaseDefault::Int(num)
-                        } else if let Ok(num) = def.parse::<i64>() {
-                            DatabaseDefault::Float(num)
-                        } else {
-                            DatabaseDefault::Raw(def)
-                        }
-                    }),
-                    unique: col.is_unique,
-                    constraint: None,
-                    primary_key: col.is_primary_key,
-                    references: if let Some(references) = col.reference {
-                        let mut parts = references.splitn(2, ':');
-                        if let Some(references_table) = parts.next() {
-                            parts.next().map(|references_column| {
-                                (references_table.to_string(), references_column.to_string())
-                            })
-                        } else {
-                            None
-                        }
-                    } else {
-                        None
-                    },
-                })
-            })
-            .collect::<Result<_, _>>()?;
+/// Returns the idempotency keys table, which is created alongside the tables of the models when any of them has
+/// idempotent creates.
+pub fn idempotency_table() -> DatabaseTable {
+    let column = generated_column;
 
-        Ok(Some(columns))
+    // Like the outbox, the table is only used by generated code
+    DatabaseTable {
+        name: IDEMPOTENCY_TABLE.to_string(),
+        columns: vec![
+            DatabaseColumn {
+                primary_key: true,
+                ..column("key", DatabaseType::Text(Some(255)))
+            },
+            DatabaseColumn {
+                default: Some(DatabaseDefault::Raw("now()".to_string())),
+                ..column("created_at", DatabaseType::Timestamptz)
+            },
+            column("scope", DatabaseType::Text(Some(255))),
+            column("request_hash", DatabaseType::Text(Some(64))),
+            // Set once the row is created, in the same transaction as the key
+            DatabaseColumn {
+                nullable: true,
+                ..column("row_key", DatabaseType::Text(Some(255)))
+            },
+        ],
+        ..outbox_table()
     }
+}
 
-    fn write_table_create_sql(&self, table: &DatabaseTable) -> String {
-        let mut sql = String::new();
+/// Returns the names of the tables of `models` to generate sea-orm entities for,
+/// including the outbox table when any of them records events, and the idempotency keys table when any of them
+/// has idempotent creates.
+pub fn entity_table_names(models: &[Model]) -> Vec<String> {
+    let tables: Vec<&DatabaseTable> = models
+        .iter()
+        .flat_map(|model| &model.roles)
+        .filter_map(|role| match role {
+            Role::DatabaseTable(table) => Some(table),
+            _ => None,
+        })
+        .collect();
 
-        writeln!(sql, "CREATE TABLE IF NOT EXISTS {} (", table.name).unwrap();
+    let mut names: Vec<String> = tables.iter().map(|table| table.name.clone()).collect();
+    names.extend(
+        tables
+            .iter()
+            .filter_map(|table| translations_table(table))
+            .map(|table| table.name),
+    );
+    if tables.iter().any(|table| table.events) {
+        names.push(OUTBOX_TABLE.to_string());
+    }
+    if tables.iter().any(|table| table.idempotent_create) {
+        names.push(IDEMPOTENCY_TABLE.to_string());
+    }
 
-        for (i, column) in table.columns.iter().enumerate() {
-            write!(sql, "  {}", self.write_column_sql(column)).unwrap();
+    names
+}
 
-            if i < table.columns.len() - 1 {
-                writeln!(sql, ",").unwrap();
-            } else {
-                writeln!(sql).unwrap();
-            }
-        }
+/// Patches the sea-orm entities generated into `OUT_DIR`, to be called after the entities of `models` are generated.
+///
+/// Fields of deprecated model fields are marked `#[deprecated]`, fields of maps hold a `JsonMap` of their values,
+/// entities of tables with normalized columns normalize their active models before saving them,
+/// and the `Debug` of models with internal or secret fields masks their values.
+/// Entities which were not generated are skipped, and entities generated by previous builds for tables
+/// which are no longer compiled are deleted.
+pub fn patch_entities(models: &[Model]) -> io::Result<()> {
+    let out_dir =
+        env::var("OUT_DIR").map_err(|err| io::Error::new(io::ErrorKind::NotFound, err))?;
+    remove_stale_entities(Path::new(&out_dir), &entity_table_names(models))?;
 
-        writeln!(sql, ");").unwrap();
+    for model in models {
+        let table = model.roles.iter().find_map(|role| match role {
+            Role::DatabaseTable(table) => Some(table),
+            _ => None,
+        });
+        let table = match table {
+            Some(table) => table,
+            None => continue,
+        };
+        // Entity fields of keywords are raw or suffixed identifiers, such as `r#type`
+        let fields: Vec<(String, &Deprecation)> = model
+            .fields
+            .iter()
+            .filter_map(|field| {
+                Some((
+                    rust_ident(&field.name).to_string(),
+                    field.deprecated.as_ref()?,
+                ))
+            })
+            .collect();
+        let normalized = !table.readonly
+            && table
+                .columns
+                .iter()
+                .any(|column| !column.normalize.is_empty());
+        let maps: Vec<(String, String)> = model
+            .fields
+            .iter()
+            .filter_map(|field| {
+                let ty = map_entity_ty(&field.ty)?.to_string().replace(' ', "");
+                let ty = if is_ty_option(&field.ty) {
+                    format!("Option<{}>", ty)
+                } else {
+                    ty
+                };
+                Some((rust_ident(&field.name).to_string(), ty))
+            })
+            .collect();
+        let redacted: Vec<String> = model
+            .fields
+            .iter()
+            .filter(|field| field.sensitivity != Sensitivity::Public)
+            .map(|field| rust_ident(&field.name).to_string())
+            .collect();
+        let attributes = entity_attributes(table)?;
+        if fields.is_empty()
+            && !normalized
+            && maps.is_empty()
+            && attributes.is_empty()
+            && redacted.is_empty()
+        {
+            continue;
+        }
 
-        sql
+        let path = Path::new(&out_dir).join(format!("{}.rs", table.name));
+        let source = match std::fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+        };
+        let fields: Vec<(&str, &Deprecation)> = fields
+            .iter()
+            .map(|(name, deprecation)| (name.as_str(), *deprecation))
+            .collect();
+        let maps: Vec<(&str, String)> = maps
+            .iter()
+            .map(|(name, ty)| (name.as_str(), ty.clone()))
+            .collect();
+        let mut source = deprecate_fields(&source, &fields);
+        if !maps.is_empty() {
+            source = wrap_map_fields(&source, &maps);
+        }
+        if normalized {
+            source = normalize_before_save(&source);
+        }
+        if !attributes.is_empty() {
+            source = add_model_attributes(&source, &attributes);
+        }
+        if !redacted.is_empty() {
+            let redacted: Vec<&str> = redacted.iter().map(String::as_str).collect();
+            source = redact_debug(&source, &redacted);
+        }
+        write_if_changed(path, source)?;
     }
 
-    fn write_column_sql(&self, column: &DatabaseColumn) -> String {
-        let mut sql = String::new();
+    Ok(())
+}
 
-        write!(sql, "{} {}", column.name, column.ty,).unwrap();
+/// Deletes the entities generated into `out_dir` by previous builds for tables other than `tables`,
+/// such as the table of the last model removed from the schema, and records `tables` for the next build.
+fn remove_stale_entities(out_dir: &Path, tables: &[String]) -> io::Result<()> {
+    let path = out_dir.join(ENTITIES_FILE);
+    let previous = match std::fs::read_to_string(&path) {
+        Ok(previous) => previous,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err),
+    };
 
-        if !column.nullable {
-            write!(sql, " NOT NULL",).unwrap();
+    for table in previous
+        .lines()
+        .filter(|table| !tables.iter().any(|name| name == table))
+    {
+        match std::fs::remove_file(out_dir.join(format!("{}.rs", table))) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => {}
         }
+    }
 
-        if let Some(default) = &column.default {
-            write!(sql, " DEFAULT {}", default).unwrap();
+    let contents: String = tables.iter().map(|table| format!("{}\n", table)).collect();
+    write_if_changed(path, contents)
+}
+
+/// Returns the attributes added to the entity of `table`: a `#[derive(...)]` of its [`entity_derives`](DatabaseTable::entity_derives)
+/// followed by its [`entity_attrs`](DatabaseTable::entity_attrs), which are checked to be well-formed before they are written.
+fn entity_attributes(table: &DatabaseTable) -> io::Result<Vec<String>> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+    let mut attributes = Vec::new();
+
+    if !table.entity_derives.is_empty() {
+        for derive in &table.entity_derives {
+            syn::parse_str::<syn::Path>(derive).map_err(|err| {
+                invalid(format!(
+                    "invalid entity derive `{}` of table `{}`: {}",
+                    derive, table.name, err
+                ))
+            })?;
+        }
+        attributes.push(format!("#[derive({})]", table.entity_derives.join(", ")));
+    }
+    for attr in &table.entity_attrs {
+        let parsed =
+            syn::parse::Parser::parse_str(syn::Attribute::parse_outer, attr).map_err(|err| {
+                invalid(format!(
+                    "invalid entity attribute `{}` of table `{}`: {}",
+                    attr, table.name, err
+                ))
+            })?;
+        if parsed.is_empty() {
+            return Err(invalid(format!(
+                "empty entity attribute of table `{}`",
+                table.name
+            )));
         }
+        attributes.push(attr.trim().to_string());
+    }
 
-        if let Some(constraint) = &column.constraint {
-            write!(sql, " CHECK ({})", constraint).unwrap();
+    Ok(attributes)
+}
+
+/// Returns the entity `source` with `attributes` added to its `Model` struct, after the attributes of sea-orm
+/// so the derives they add come after `DeriveEntityModel`.
+fn add_model_attributes(source: &str, attributes: &[String]) -> String {
+    let mut added = String::with_capacity(source.len());
+
+    for line in source.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("pub struct Model") {
+            let indent = &line[..line.len() - trimmed.len()];
+            for attribute in attributes {
+                writeln!(added, "{}{}", indent, attribute).unwrap();
+            }
         }
 
-        if column.primary_key {
-            write!(sql, " PRIMARY KEY").unwrap();
+        added.push_str(line);
+        added.push('\n');
+    }
+
+    added
+}
+
+/// Returns the entity `source` with its empty `ActiveModelBehavior` implementation replaced
+/// by one normalizing active models before they are saved.
+fn normalize_before_save(source: &str) -> String {
+    let mut normalized = String::with_capacity(source.len());
+
+    for line in source.lines() {
+        if line.trim() == "impl ActiveModelBehavior for ActiveModel {}" {
+            let indent = &line[..line.len() - line.trim_start().len()];
+            writeln!(
+                normalized,
+                "{}impl ActiveModelBehavior for ActiveModel {{",
+                indent
+            )
+            .unwrap();
+            writeln!(
+                normalized,
+                "{}    fn before_save(self, _insert: bool) -> Result<Self, DbErr> {{",
+                indent
+            )
+            .unwrap();
+            writeln!(normalized, "{}        Ok(self.normalize())", indent).unwrap();
+            writeln!(normalized, "{}    }}", indent).unwrap();
+            writeln!(normalized, "{}}}", indent).unwrap();
+        } else {
+            normalized.push_str(line);
+            normalized.push('\n');
         }
+    }
+
+    normalized
+}
 
-        if let Some((table, col)) = &column.references {
-            write!(sql, " REFERENCES {}({})", table, col).unwrap();
+/// Returns the entity `source` with the types of the map `fields` of its `Model` struct replaced by their entity types,
+/// as sea-orm generates `Json` fields for them.
+fn wrap_map_fields(source: &str, fields: &[(&str, String)]) -> String {
+    let mut in_model = false;
+    let mut wrapped = String::with_capacity(source.len());
+
+    for line in source.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("pub struct Model") {
+            in_model = true;
+        } else if in_model && trimmed.starts_with('}') {
+            in_model = false;
+        } else if in_model {
+            let name = trimmed
+                .strip_prefix("pub ")
+                .and_then(|rest| rest.split(':').next())
+                .map(str::trim);
+            if let Some((name, ty)) = fields.iter().find(|(field, _)| Some(*field) == name) {
+                let indent = &line[..line.len() - trimmed.len()];
+                writeln!(wrapped, "{}pub {}: {},", indent, name, ty).unwrap();
+                continue;
+            }
         }
 
-        sql
+        wrapped.push_str(line);
+        wrapped.push('\n');
     }
 
-    async fn write_sync_sql(&self, table: &DatabaseTable, db_columns: &[DatabaseColumn]) -> String {
-        let mut sql = String::new();
+    wrapped
+}
 
-        for schema_col in &table.columns {
-            let db_col = match db_columns
-                .iter()
-                .find(|db_col| db_col.name == schema_col.name)
-            {
-                Some(db_col) => db_col,
-                None => {
-                    // Column does not exist in DB
-                    writeln!(
-                        sql,
-                        "ALTER TABLE {} ADD COLUMN {};",
-                        table.name,
-                        self.write_column_sql(schema_col)
-                    )
-                    .unwrap();
-                    continue;
-                }
-            };
+/// Returns the entity `source` with the derived `Debug` of its `Model` struct replaced by an implementation
+/// printing the values of `fields` as `********`.
+fn redact_debug(source: &str, fields: &[&str]) -> String {
+    let mut in_model = false;
+    let mut names = Vec::new();
+    let mut redacted = String::with_capacity(source.len());
 
-            // Check for type mismatch
-            if schema_col.ty != db_col.ty {
-                writeln!(
-                    sql,
-                    "ALTER TABLE {table} ALTER COLUMN {column} TYPE {ty} USING {column}::{ty};",
-                    table = table.name,
-                    column = schema_col.name,
-                    ty = schema_col.ty.to_string(),
-                )
-                .unwrap();
+    for line in source.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("#[derive(") && trimmed.contains("DeriveEntityModel") {
+            redacted.push_str(&line.replacen("Debug, ", "", 1));
+            redacted.push('\n');
+            continue;
+        } else if trimmed.starts_with("pub struct Model") {
+            in_model = true;
+        } else if in_model && trimmed.starts_with('}') {
+            in_model = false;
+        } else if in_model {
+            if let Some(name) = trimmed
+                .strip_prefix("pub ")
+                .and_then(|rest| rest.split(':').next())
+            {
+                names.push(name.trim().to_string());
             }
+        }
 
-            // Check for nullable mismatch
-            if schema_col.nullable != db_col.nullable {
-                if db_col.nullable {
-                    writeln!(
-                        sql,
-                        "ALTER TABLE {table} ALTER COLUMN {column} SET NOT NULL;",
-                        table = table.name,
-                        column = schema_col.name
-                    )
-                    .unwrap();
-                } else {
-                    writeln!(
-                        sql,
-                        "ALTER TABLE {table} ALTER COLUMN {column} DROP NOT NULL;",
-                        table = table.name,
-                        column = schema_col.name
-                    )
-                    .unwrap();
-                }
-            }
+        redacted.push_str(line);
+        redacted.push('\n');
+    }
 
-            // Check for default mismatch
-            if schema_col.default != db_col.default {
-                if let Some(default) = &schema_col.default {
-                    writeln!(
-                        sql,
-                        "ALTER TABLE {table} ALTER COLUMN {column} SET DEFAULT {default};",
-                        table = table.name,
-                        column = schema_col.name,
-                        default = default
-                    )
-                    .unwrap();
-                } else {
-                    writeln!(
-                        sql,
-                        "ALTER TABLE {table} ALTER COLUMN {column} DROP DEFAULT;",
-                        table = table.name,
-                        column = schema_col.name
-                    )
-                    .unwrap();
-                }
+    redacted.push_str("\nimpl std::fmt::Debug for Model {\n");
+    redacted.push_str("    #[allow(deprecated)]\n");
+    redacted.push_str("    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {\n");
+    redacted.push_str("        f.debug_struct(\"Model\")\n");
+    for name in &names {
+        let label = name.strip_prefix("r#").unwrap_or(name);
+        if fields.contains(&name.as_str()) {
+            writeln!(
+                redacted,
+                "            .field(\"{}\", &format_args!(\"********\"))",
+                label
+            )
+            .unwrap();
+        } else {
+            writeln!(
+                redacted,
+                "            .field(\"{}\", &self.{})",
+                label, name
+            )
+            .unwrap();
+        }
+    }
+    redacted.push_str("            .finish()\n    }\n}\n");
+
+    redacted
+}
+
+/// Returns the entity `source` with the `#[deprecated]` attribute added to `fields` of its `Model` struct.
+fn deprecate_fields(source: &str, fields: &[(&str, &Deprecation)]) -> String {
+    let mut in_model = false;
+    let mut deprecated = String::with_capacity(source.len());
+
+    for line in source.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("pub struct Model") {
+            in_model = true;
+        } else if in_model && trimmed.starts_with('}') {
+            in_model = false;
+        } else if in_model {
+            let name = trimmed
+                .strip_prefix("pub ")
+                .and_then(|rest| rest.split(':').next())
+                .map(str::trim);
+            let deprecation = fields
+                .iter()
+                .find(|(field, _)| Some(*field) == name)
+                .map(|(_, deprecation)| deprecation);
+            if let Some(deprecation) = deprecation {
+                let indent = &line[..line.len() - trimmed.len()];
+                deprecated.push_str(indent);
+                deprecated.push_str(&deprecated_attribute(deprecation));
+                deprecated.push('\n');
             }
+        }
 
-            // Check for unique mismatch
-            if schema_col.unique != db_col.unique {
-                if db_col.unique {
-                    writeln!(
-                        sql,
-                        "ALTER TABLE {table} DROP CONSTRAINT {table}_{column}_key;",
-                        table = table.name,
-                        column = schema_col.name
-                    )
-                    .unwrap();
-                } else {
-                    writeln!(
-                        sql,
-                        "ALTER TABLE {table} ADD CONSTRAINT {table}_{column}_key UNIQUE ({column});",
-                        table = table.name,
-                        column = schema_col.name
-                    )
-                    .unwrap();
-                }
+        deprecated.push_str(line);
+        deprecated.push('\n');
+    }
+
+    deprecated
+}
+
+/// Returns whether random rows of `table` can be generated, which requires it to be writable
+/// and every required column to have random values.
+pub(crate) fn is_fake_table(table: &DatabaseTable) -> bool {
+    !table.readonly
+        && table
+            .columns
+            .iter()
+            .filter(|column| {
+                !column.nullable && column.default.is_none() && column.computed.is_none()
+            })
+            .filter(|column| !column.primary_key || column.ty == DatabaseType::Uuid)
+            .all(|column| match column.references {
+                Some(_) if !column.cross_database => column_rust_type(&column.ty).is_some(),
+                _ => column.primary_key || DatabaseCompiler::fake_value(column).is_some(),
+            })
+}
+
+/// Returns the column the slugs of `column` are derived from, set with `slugify-from(...)`.
+fn slug_source(column: &DatabaseColumn) -> Option<&str> {
+    column
+        .normalize
+        .iter()
+        .find_map(|normalizer| match normalizer {
+            Normalizer::SlugifyFrom(source) => Some(source.as_str()),
+            _ => None,
+        })
+}
+
+/// Returns the path of the normalizer function `path` of the schema crate, such as `crate::normalize::phone`,
+/// as seen from generated packages.
+fn schema_function(path: &str) -> TokenStream {
+    let segments = path.split("::").skip(1).map(rust_ident);
+    quote!(::schema #( :: #segments )*)
+}
+
+/// Returns `models` with the localized columns of their tables stored with `localization`.
+pub fn with_localization(mut models: Vec<Model>, localization: Localization) -> Vec<Model> {
+    for role in models.iter_mut().flat_map(|model| &mut model.roles) {
+        if let Role::DatabaseTable(table) | Role::DatabaseSubTable(table) = role {
+            table.localization = localization;
+        }
+    }
+
+    models
+}
+
+/// Returns `models` with the previous versions of the rows of their tables with `#[awto(temporal)]` copied with `temporal`.
+pub fn with_temporal(mut models: Vec<Model>, temporal: Temporal) -> Vec<Model> {
+    for role in models.iter_mut().flat_map(|model| &mut model.roles) {
+        if let Role::DatabaseTable(table) | Role::DatabaseSubTable(table) = role {
+            if table.temporal.is_some() {
+                table.temporal = Some(temporal);
             }
+        }
+    }
 
-            // Check for references mismatch
-            if schema_col.references != db_col.references {
-                if let Some(references) = &schema_col.references {
-                    if db_col.references.is_some() {
-                        writeln!(
-                            sql,
-                            "ALTER TABLE {table} DROP CONSTRAINT {table}_{column}_fkey;",
-                            table = table.name,
-                            column = schema_col.name
-                        )
-                        .unwrap();
+    models
+}
+
+/// Returns `models` with the operations of their tables without a scope authorized by `default_authorization`.
+pub fn with_default_authorization(
+    mut models: Vec<Model>,
+    default_authorization: DefaultAuthorization,
+) -> Vec<Model> {
+    for role in models.iter_mut().flat_map(|model| &mut model.roles) {
+        if let Role::DatabaseTable(table) | Role::DatabaseSubTable(table) = role {
+            table.default_authorization = default_authorization;
+        }
+    }
+
+    models
+}
+
+/// Returns `models` with the derive macros at the paths of `entity_derives` added to the entities of their tables,
+/// such as `acme_telemetry::Instrumented`.
+pub fn with_entity_derives(mut models: Vec<Model>, entity_derives: &[&str]) -> Vec<Model> {
+    for role in models.iter_mut().flat_map(|model| &mut model.roles) {
+        if let Role::DatabaseTable(table) | Role::DatabaseSubTable(table) = role {
+            table.entity_derives = entity_derives
+                .iter()
+                .map(|derive| derive.to_string())
+                .collect();
+        }
+    }
+
+    models
+}
+
+/// Returns `models` with the items of the entities of their tables re-exported as `entities` and their
+/// active models as `active_models`, unless their model sets them with `#[awto(visibility(...))]`.
+pub fn with_visibility(
+    mut models: Vec<Model>,
+    entities: Visibility,
+    active_models: Visibility,
+) -> Vec<Model> {
+    for role in models.iter_mut().flat_map(|model| &mut model.roles) {
+        if let Role::DatabaseTable(table) = role {
+            table.visibility.entity.get_or_insert(entities);
+            table.visibility.active_model.get_or_insert(active_models);
+        }
+    }
+
+    models
+}
+
+/// Returns `models` with the plurals of their table names resolved with `overrides` of the plurals of words or whole names,
+/// such as `("person", "persons")`.
+pub fn with_plurals(mut models: Vec<Model>, overrides: &[(&str, &str)]) -> Vec<Model> {
+    if overrides.is_empty() {
+        return models;
+    }
+
+    for role in models.iter_mut().flat_map(|model| &mut model.roles) {
+        if let Role::DatabaseTable(table) | Role::DatabaseSubTable(table) = role {
+            table.plural = Some(pluralize_with(&table.name, overrides));
+        }
+    }
+
+    models
+}
+
+/// Returns `models` with the columns of their `HashMap<String, String>` fields stored with `map_storage`,
+/// which are `jsonb` columns like the columns of other maps unless it is [`MapStorage::Hstore`].
+pub fn with_map_storage(mut models: Vec<Model>, map_storage: MapStorage) -> Vec<Model> {
+    if map_storage == MapStorage::Jsonb {
+        return models;
+    }
+
+    for model in &mut models {
+        let string_maps: Vec<String> = model
+            .fields
+            .iter()
+            .filter(|field| map_value_ty(&field.ty) == Some("String"))
+            .map(|field| field.name.clone())
+            .collect();
+        for role in &mut model.roles {
+            if let Role::DatabaseTable(table) | Role::DatabaseSubTable(table) = role {
+                for column in &mut table.columns {
+                    if column.ty == DatabaseType::Jsonb && string_maps.contains(&column.name) {
+                        column.ty = DatabaseType::Hstore;
                     }
-                    writeln!(
-                        sql,
-                        "ALTER TABLE {table} ADD CONSTRAINT {table}_{column}_fkey FOREIGN KEY ({column}) REFERENCES {reference_table} ({reference_column});",
-                        table = table.name,
-                        column = schema_col.name,
-                        reference_table = references.0,
-                        reference_column = references.1,
-                    )
-                    .unwrap();
-                } else {
-                    writeln!(
-                        sql,
-                        "ALTER TABLE {table} DROP CONSTRAINT {table}_{column}_fkey;",
-                        table = table.name,
-                        column = schema_col.name
-                    )
-                    .unwrap();
                 }
             }
         }
+    }
 
-        // Delete columns that exist in db but don't exist in schema
-        db_columns
-            .iter()
-            .filter(|db_col| {
-                table
-                    .columns
-                    .iter()
-                    .all(|schema_col| schema_col.name != db_col.name)
-            })
-            .for_each(|db_col| {
-                writeln!(
-                    sql,
-                    "ALTER TABLE {table} DROP COLUMN {column};",
-                    table = table.name,
-                    column = db_col.name
-                )
-                .unwrap();
-            });
+    models
+}
 
-        sql
+/// Returns the table holding the translations of the localized columns of `table`,
+/// or `None` when it has no localized columns or stores them with [`Localization::Columns`].
+pub fn translations_table(table: &DatabaseTable) -> Option<DatabaseTable> {
+    if table.localization != Localization::Table || table.localized_columns().is_empty() {
+        return None;
     }
+
+    let column = |name: &str, ty: DatabaseType| DatabaseColumn {
+        name: name.to_string(),
+        ty,
+        nullable: false,
+        default: None,
+        unique: false,
+        constraint: None,
+        primary_key: false,
+        references: None,
+        reference_constraint: None,
+        cross_database: false,
+        locales: Vec::new(),
+        computed: None,
+        normalize: Vec::new(),
+        fake: None,
+        backfill: None,
+    };
+
+    Some(DatabaseTable {
+        name: table.translations_table_name(),
+        columns: vec![
+            DatabaseColumn {
+                default: Some(DatabaseDefault::Raw("uuid_generate_v4()".to_string())),
+                primary_key: true,
+                ..column("id", DatabaseType::Uuid)
+            },
+            DatabaseColumn {
+                references: Some((table.name.clone(), "id".to_string())),
+                reference_constraint: None,
+                cross_database: false,
+                ..column(&derived_name(&[&table.name], "id"), DatabaseType::Uuid)
+            },
+            column("locale", DatabaseType::Text(Some(35))),
+            column("field", DatabaseType::Text(Some(63))),
+            column("value", DatabaseType::Text(None)),
+        ],
+        database: table.database.clone(),
+        order_by: None,
+        fulltext: None,
+        partition: None,
+        policies: Vec::new(),
+        projections: Vec::new(),
+        scopes: Vec::new(),
+        readonly: false,
+        external: false,
+        skip_rest: true,
+        upsert_on: None,
+        versioned: false,
+        events: false,
+        notify: false,
+        subscriptions: false,
+        idempotent_create: false,
+        temporal: None,
+        localization: table.localization,
+        authorization: None,
+        default_authorization: table.default_authorization,
+        entity_attrs: Vec::new(),
+        entity_derives: Vec::new(),
+        plural: None,
+        visibility: EntityVisibility::default(),
+    })
 }
 
-const FETCH_TABLE_QUERY: &str = "
-SELECT column_name, column_default, is_nullable, data_type, character_maximum_length,
-(
-    SELECT
-        COUNT(*) > 0
-    FROM information_schema.table_constraints tco
-    JOIN information_schema.key_column_usage kcu 
-    ON kcu.constraint_name = tco.constraint_name
-    AND kcu.constraint_schema = tco.constraint_schema
-    AND kcu.constraint_name = tco.constraint_name
-    WHERE
-        tco.constraint_type = 'PRIMARY KEY' AND
-        kcu.table_schema = $1 AND
-        kcu.table_name = $2 AND
-        kcu.column_name = information_schema.columns.column_name
-) as is_primary_key,
-(
-    SELECT
-        COUNT(*) > 0
-    FROM information_schema.table_constraints tco
-    JOIN information_schema.key_column_usage kcu 
-    ON kcu.constraint_name = tco.constraint_name
-    AND kcu.constraint_schema = tco.constraint_schema
-    AND kcu.constraint_name = tco.constraint_name
-    WHERE
-        tco.constraint_type = 'UNIQUE' AND
-        kcu.table_schema = $1 AND
-        kcu.table_name = $2 AND
-        kcu.column_name = information_schema.columns.column_name
-) as is_unique,
-(
-    SELECT CONCAT(
-        rel_tco.table_name,
-        ':',
-        (
-            SELECT u.column_name
-            FROM information_schema.constraint_column_usage u
-            INNER JOIN information_schema.referential_constraints fk
-            ON
-                u.constraint_catalog = fk.unique_constraint_catalog AND
-                u.constraint_schema = fk.unique_constraint_schema AND
-                u.constraint_name = fk.unique_constraint_name
-            INNER JOIN information_schema.key_column_usage r
-            ON
-                r.constraint_catalog = fk.constraint_catalog AND
-                r.constraint_schema = fk.constraint_schema AND
-                r.constraint_name = fk.constraint_name
-            WHERE
-                fk.constraint_name = kcu.constraint_name AND
-                u.table_schema = kcu.table_schema AND
-                u.table_name = rel_tco.table_name
-        )
-    )
-    FROM information_schema.table_constraints tco
-    JOIN information_schema.key_column_usage kcu
-    ON
-        tco.constraint_schema = kcu.constraint_schema AND
-        tco.constraint_name = kcu.constraint_name
-    JOIN information_schema.referential_constraints rco
-    ON
-        tco.constraint_schema = rco.constraint_schema AND
-        tco.constraint_name = rco.constraint_name
-    JOIN information_schema.table_constraints rel_tco
-    ON
-        rco.unique_constraint_schema = rel_tco.constraint_schema AND
-        rco.unique_constraint_name = rel_tco.constraint_name
-    WHERE
-        tco.constraint_type = 'FOREIGN KEY' AND
-        kcu.table_name = $2 AND
-        kcu.column_name = information_schema.columns.column_name
-    GROUP BY
-        kcu.table_schema,
-        kcu.table_name,
-        rel_tco.table_name,
-        rel_tco.table_schema,
-        kcu.constraint_name
-    ORDER BY
-        kcu.table_schema,
-        kcu.table_name
-) as reference
-FROM information_schema.columns
-WHERE table_schema = $1
-AND table_name = $2;
-";
+/// Returns the table holding the previous versions of the rows of `table` when it has `#[awto(temporal)]`.
+///
+/// It has the columns of the table, without their constraints, followed by the `valid_from` and `valid_to` bounds of the
+/// version and the `operation` which ended it, `UPDATE` or `DELETE`. Versions are keyed by the `id` of their row and
+/// their `valid_to`, and columns keep their defaults, so adding a required column fills it in the previous versions too.
+pub fn history_table(table: &DatabaseTable) -> Option<DatabaseTable> {
+    table.temporal?;
+
+    let bound = |name: &str| DatabaseColumn {
+        name: name.to_string(),
+        ty: DatabaseType::Timestamptz,
+        nullable: false,
+        default: None,
+        unique: false,
+        constraint: None,
+        primary_key: name == "valid_to",
+        references: None,
+        reference_constraint: None,
+        cross_database: false,
+        locales: Vec::new(),
+        computed: None,
+        normalize: Vec::new(),
+        fake: None,
+        backfill: None,
+    };
+    let columns = with_locale_columns(table)
+        .columns
+        .into_iter()
+        .map(|column| DatabaseColumn {
+            default: if column.primary_key {
+                None
+            } else {
+                column.default
+            },
+            unique: false,
+            constraint: None,
+            references: None,
+            reference_constraint: None,
+            cross_database: false,
+            locales: Vec::new(),
+            computed: None,
+            normalize: Vec::new(),
+            fake: None,
+            backfill: None,
+            ..column
+        })
+        .chain([
+            bound("valid_from"),
+            bound("valid_to"),
+            DatabaseColumn {
+                ty: DatabaseType::Text(Some(6)),
+                primary_key: false,
+                ..bound("operation")
+            },
+        ])
+        .collect();
+
+    Some(DatabaseTable {
+        name: table.history_table_name(),
+        columns,
+        database: table.database.clone(),
+        order_by: None,
+        fulltext: None,
+        partition: None,
+        policies: Vec::new(),
+        projections: Vec::new(),
+        scopes: Vec::new(),
+        readonly: true,
+        external: false,
+        skip_rest: true,
+        upsert_on: None,
+        versioned: false,
+        events: false,
+        notify: false,
+        subscriptions: false,
+        idempotent_create: false,
+        temporal: None,
+        localization: table.localization,
+        authorization: None,
+        default_authorization: table.default_authorization,
+        entity_attrs: Vec::new(),
+        entity_derives: Vec::new(),
+        plural: None,
+        visibility: EntityVisibility::default(),
+    })
+}
+
+/// Returns `table` with a nullable column per locale of its localized columns stored with [`Localization::Columns`],
+/// following the column holding the default locale.
+fn with_locale_columns(table: &DatabaseTable) -> DatabaseTable {
+    let mut table = table.clone();
+    if table.localization != Localization::Columns {
+        return table;
+    }
+
+    table.columns = table
+        .columns
+        .into_iter()
+        .flat_map(|column| {
+            let locale_columns: Vec<_> = column
+                .locales
+                .iter()
+                .skip(1)
+                .map(|locale| DatabaseColumn {
+                    name: column.locale_column_name(locale),
+                    ty: column.ty.clone(),
+                    nullable: true,
+                    default: None,
+                    unique: false,
+                    constraint: None,
+                    primary_key: false,
+                    references: None,
+                    reference_constraint: None,
+                    cross_database: false,
+                    locales: Vec::new(),
+                    computed: None,
+                    normalize: Vec::new(),
+                    fake: None,
+                    backfill: None,
+                })
+                .collect();
+
+            iter::once(column).chain(locale_columns)
+        })
+        .collect();
+
+    table
+}
+
+/// Sql keywords which can appear in computed column expressions without referring to a column.
+const SQL_KEYWORDS: [&str; 36] = [
+    "all",
+    "and",
+    "any",
+    "array",
+    "as",
+    "at",
+    "between",
+    "case",
+    "collate",
+    "current_date",
+    "current_time",
+    "current_timestamp",
+    "distinct",
+    "else",
+    "end",
+    "escape",
+    "false",
+    "from",
+    "ilike",
+    "in",
+    "interval",
+    "is",
+    "like",
+    "not",
+    "null",
+    "or",
+    "precision",
+    "similar",
+    "some",
+    "then",
+    "time",
+    "to",
+    "true",
+    "varying",
+    "when",
+    "zone",
+];
+
+/// Returns the identifiers of `expression` which look like column names, in order of appearance.
+///
+/// String literals, keywords, function names, and type names following `::` or `AS` are skipped,
+/// so the identifiers can be compared with the columns of a table to find typos.
+fn computed_column_references(expression: &str) -> Vec<String> {
+    let chars: Vec<char> = expression.chars().collect();
+    let mut references: Vec<String> = Vec::new();
+    let mut previous = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\'' {
+            // Quotes are escaped by doubling them, which is read as two adjacent literals
+            i += 1;
+            while i < chars.len() && chars[i] != '\'' {
+                i += 1;
+            }
+            i += 1;
+            previous.clear();
+        } else if c == '"' {
+            let start = i + 1;
+            i = start;
+            while i < chars.len() && chars[i] != '"' {
+                i += 1;
+            }
+            let ident: String = chars[start..i.min(chars.len())].iter().collect();
+            if !references.contains(&ident) {
+                references.push(ident.clone());
+            }
+            i += 1;
+            previous = ident;
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len()
+                && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$')
+            {
+                i += 1;
+            }
+            let ident: String = chars[start..i].iter().collect::<String>().to_lowercase();
+            let is_function = chars[i..]
+                .iter()
+                .find(|c| !c.is_whitespace())
+                .map(|c| *c == '(')
+                .unwrap_or(false);
+            let is_type = previous == "::" || previous == "as";
+            let is_qualified = previous == ".";
+            if !is_function
+                && !is_type
+                && !is_qualified
+                && !SQL_KEYWORDS.contains(&ident.as_str())
+                && !references.contains(&ident)
+            {
+                references.push(ident.clone());
+            }
+            previous = ident;
+        } else if c.is_ascii_digit() {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '.') {
+                i += 1;
+            }
+            previous.clear();
+        } else if c.is_whitespace() {
+            i += 1;
+        } else if c == ':' && chars.get(i + 1) == Some(&':') {
+            i += 2;
+            previous = "::".to_string();
+        } else {
+            i += 1;
+            previous = c.to_string();
+        }
+    }
+
+    references
+}
+
+/// Returns a warning for every identifier of the computed columns of `table` which is not one of its columns.
+fn computed_column_warnings(model: &Model, table: &DatabaseTable) -> Vec<Warning> {
+    let columns = with_locale_columns(table).columns;
+    table
+        .columns
+        .iter()
+        .filter_map(|column| Some((column, column.computed.as_ref()?)))
+        .flat_map(|(column, expression)| {
+            computed_column_references(expression)
+                .into_iter()
+                .filter(|reference| columns.iter().all(|column| column.name != *reference))
+                .map(move |reference| Warning {
+                    id: Warning::UNKNOWN_COLUMN_REFERENCE,
+                    model: Some(model.name.clone()),
+                    field: Some(column.name.clone()),
+                    message: format!(
+                        "computed column references '{}', which is not a column of table '{}'",
+                        reference, table.name
+                    ),
+                })
+        })
+        .collect()
+}
+
+/// Keyset pagination helpers generated as `database::cursor`.
+const CURSOR_MODULE: &str = r#"
+/// Keyset pagination with encrypted cursors, following the Relay connection spec.
+pub mod cursor {
+    use std::{error, fmt};
+
+    use chacha20poly1305::{
+        aead::{Aead, NewAead, Payload},
+        ChaCha20Poly1305, Key, Nonce,
+    };
+    use hmac::{Hmac, Mac, NewMac};
+    use sha2::Sha256;
+
+    /// Number of items returned when neither `first` nor `last` is given.
+    pub const DEFAULT_PAGE_SIZE: u64 = 20;
+    /// Maximum number of items returned in a single page.
+    pub const MAX_PAGE_SIZE: u64 = 100;
+
+    /// Length of the nonce prefixed to the ciphertext of a cursor.
+    const NONCE_LEN: usize = 12;
+
+    /// Secret used to encrypt pagination cursors, so clients can neither read nor forge them.
+    #[derive(Clone)]
+    pub struct CursorKey {
+        cipher: ChaCha20Poly1305,
+        nonce_key: Vec<u8>,
+    }
+
+    impl CursorKey {
+        /// Creates a key encrypting cursors with keys derived from `secret`.
+        pub fn new(secret: impl Into<Vec<u8>>) -> Self {
+            let secret = secret.into();
+            let cipher_key = Self::derive(&secret, b"awto cursor encryption");
+
+            CursorKey {
+                cipher: ChaCha20Poly1305::new(Key::from_slice(&cipher_key)),
+                nonce_key: Self::derive(&secret, b"awto cursor nonce"),
+            }
+        }
+
+        /// Encodes the ordering key of a row of `query`, such as `product`, into an opaque cursor.
+        ///
+        /// `query` is authenticated with the cursor, which other queries reject.
+        pub fn encode(&self, query: &str, key: &::serde_json::Value) -> String {
+            let payload = ::serde_json::to_vec(key).expect("cursor keys are serializable");
+            // The nonce is derived from the query and the payload, so a nonce is never reused for another payload
+            let mut mac = Hmac::<Sha256>::new_from_slice(&self.nonce_key).expect("hmac accepts keys of any length");
+            mac.update(&(query.len() as u64).to_be_bytes());
+            mac.update(query.as_bytes());
+            mac.update(&payload);
+            let nonce = mac.finalize().into_bytes();
+            let nonce = Nonce::from_slice(&nonce[..NONCE_LEN]);
+            let ciphertext = self
+                .cipher
+                .encrypt(nonce, Payload { msg: &payload, aad: query.as_bytes() })
+                .expect("cursors fit in a single message");
+
+            format!(
+                "{}.{}",
+                ::base64::encode_config(nonce, ::base64::URL_SAFE_NO_PAD),
+                ::base64::encode_config(&ciphertext, ::base64::URL_SAFE_NO_PAD),
+            )
+        }
+
+        /// Decodes the ordering key of a cursor of `query`, verifying it was encrypted with this key for `query`.
+        pub fn decode(&self, query: &str, cursor: &str) -> Result<::serde_json::Value, CursorError> {
+            let (nonce, ciphertext) = cursor.split_once('.').ok_or(CursorError::Malformed)?;
+            let nonce = ::base64::decode_config(nonce, ::base64::URL_SAFE_NO_PAD)
+                .ok()
+                .filter(|nonce| nonce.len() == NONCE_LEN)
+                .ok_or(CursorError::Malformed)?;
+            let ciphertext = ::base64::decode_config(ciphertext, ::base64::URL_SAFE_NO_PAD)
+                .map_err(|_| CursorError::Malformed)?;
+
+            let payload = self
+                .cipher
+                .decrypt(Nonce::from_slice(&nonce), Payload { msg: &ciphertext, aad: query.as_bytes() })
+                .map_err(|_| CursorError::Invalid)?;
+
+            ::serde_json::from_slice(&payload).map_err(|_| CursorError::Malformed)
+        }
+
+        /// Derives a key for `purpose` from `secret`, keeping the keys of the cipher and the nonces apart.
+        fn derive(secret: &[u8], purpose: &[u8]) -> Vec<u8> {
+            let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("hmac accepts keys of any length");
+            mac.update(purpose);
+            mac.finalize().into_bytes().to_vec()
+        }
+    }
+
+    impl fmt::Debug for CursorKey {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_tuple("CursorKey").field(&"..").finish()
+        }
+    }
+
+    /// Error returned when a cursor cannot be decoded.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum CursorError {
+        /// The cursor is not an encoded ordering key.
+        Malformed,
+        /// The cursor was not encrypted with the key for the query, as it was forged, encrypted with another secret
+        /// or returned by another query.
+        Invalid,
+    }
+
+    impl fmt::Display for CursorError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Self::Malformed => write!(f, "malformed cursor"),
+                Self::Invalid => write!(f, "invalid cursor"),
+            }
+        }
+    }
+
+    impl error::Error for CursorError {}
+
+    /// Error returned by a paginated query.
+    #[derive(Debug)]
+    pub enum PageError {
+        /// The cursor of the arguments cannot be decoded.
+        Cursor(CursorError),
+        /// The query failed.
+        Db(::sea_orm::DbErr),
+    }
+
+    impl fmt::Display for PageError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Self::Cursor(err) => write!(f, "{}", err),
+                Self::Db(err) => write!(f, "{}", err),
+            }
+        }
+    }
+
+    impl error::Error for PageError {}
+
+    impl From<CursorError> for PageError {
+        fn from(err: CursorError) -> Self {
+            Self::Cursor(err)
+        }
+    }
+
+    impl From<::sea_orm::DbErr> for PageError {
+        fn from(err: ::sea_orm::DbErr) -> Self {
+            Self::Db(err)
+        }
+    }
+
+    /// Arguments of a paginated query, following the Relay connection spec.
+    ///
+    /// `first` and `after` page forwards, while `last` and `before` page backwards.
+    #[derive(Clone, Debug, Default, PartialEq, Eq, ::serde::Deserialize)]
+    pub struct PageArgs {
+        /// Number of items to return paging forwards.
+        pub first: Option<u64>,
+        /// Cursor of the item to page forwards after.
+        pub after: Option<String>,
+        /// Number of items to return paging backwards.
+        pub last: Option<u64>,
+        /// Cursor of the item to page backwards before.
+        pub before: Option<String>,
+    }
+
+    impl PageArgs {
+        /// Returns whether the query pages backwards.
+        pub fn is_backward(&self) -> bool {
+            self.last.is_some() || self.before.is_some()
+        }
+
+        /// Returns the cursor to start after, in the direction of the query.
+        pub fn cursor(&self) -> Option<&str> {
+            if self.is_backward() {
+                self.before.as_deref()
+            } else {
+                self.after.as_deref()
+            }
+        }
+
+        /// Returns the number of items to return, capped at [`MAX_PAGE_SIZE`].
+        pub fn size(&self) -> u64 {
+            let size = if self.is_backward() { self.last } else { self.first };
+            size.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+        }
+    }
+
+    /// A page of items returned by a paginated query.
+    #[derive(Clone, Debug, PartialEq, ::serde::Serialize)]
+    pub struct Page<T> {
+        /// Items of the page, in the order of the query.
+        pub items: Vec<T>,
+        /// Whether there are items after the last item of the page.
+        pub has_next_page: bool,
+        /// Whether there are items before the first item of the page.
+        pub has_previous_page: bool,
+        /// Cursor of the last item, to be passed as `after` to fetch the next page.
+        pub next_cursor: Option<String>,
+        /// Cursor of the first item, to be passed as `before` to fetch the previous page.
+        pub previous_cursor: Option<String>,
+    }
+
+    impl<T> Page<T> {
+        /// Creates a page from the rows of `query` fetched in the direction of `args`, with cursors of `query`.
+        ///
+        /// `rows` may contain one more row than the page size, which indicates another page exists.
+        pub fn from_rows(
+            key: &CursorKey,
+            query: &str,
+            args: &PageArgs,
+            mut rows: Vec<T>,
+            cursor_key: impl Fn(&T) -> ::serde_json::Value,
+        ) -> Self {
+            let has_more = rows.len() as u64 > args.size();
+            rows.truncate(args.size() as usize);
+            if args.is_backward() {
+                rows.reverse();
+            }
+
+            Page {
+                has_next_page: if args.is_backward() { args.before.is_some() } else { has_more },
+                has_previous_page: if args.is_backward() { has_more } else { args.after.is_some() },
+                next_cursor: rows.last().map(|row| key.encode(query, &cursor_key(row))),
+                previous_cursor: rows.first().map(|row| key.encode(query, &cursor_key(row))),
+                items: rows,
+            }
+        }
+
+        /// Converts the items of the page with `f`, keeping its cursors.
+        pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+            Page {
+                items: self.items.into_iter().map(f).collect(),
+                has_next_page: self.has_next_page,
+                has_previous_page: self.has_previous_page,
+                next_cursor: self.next_cursor,
+                previous_cursor: self.previous_cursor,
+            }
+        }
+    }
+
+    /// Deserializes the value of a cursor as the type of the field returned by `_field`.
+    pub fn decode_value<M, T>(
+        _field: fn(&M) -> &T,
+        value: ::serde_json::Value,
+    ) -> Result<T, CursorError>
+    where
+        T: ::serde::de::DeserializeOwned,
+    {
+        ::serde_json::from_value(value).map_err(|_| CursorError::Malformed)
+    }
+}
+"#;
+
+/// Optimistic locking errors of versioned tables, generated as `database::version`.
+const VERSION_MODULE: &str = r#"
+/// Optimistic locking of versioned tables, whose updates fail when the row changed since it was read.
+pub mod version {
+    use std::{error, fmt};
+
+    /// Error returned when a versioned row was updated since the version being updated was read.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct StaleVersionError {
+        /// Id of the row.
+        pub id: ::uuid::Uuid,
+        /// Version of the row being updated, which is no longer the stored version.
+        pub version: i32,
+    }
+
+    impl fmt::Display for StaleVersionError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(
+                f,
+                "row {} was modified since version {}",
+                self.id, self.version
+            )
+        }
+    }
+
+    impl error::Error for StaleVersionError {}
+
+    /// Error returned by the update of a versioned row.
+    #[derive(Debug)]
+    pub enum VersionedUpdateError {
+        /// The row was updated since the version being updated was read.
+        Stale(StaleVersionError),
+        /// The query failed.
+        Db(::sea_orm::DbErr),
+    }
+
+    impl fmt::Display for VersionedUpdateError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Self::Stale(err) => write!(f, "{}", err),
+                Self::Db(err) => write!(f, "{}", err),
+            }
+        }
+    }
+
+    impl error::Error for VersionedUpdateError {}
+
+    impl From<StaleVersionError> for VersionedUpdateError {
+        fn from(err: StaleVersionError) -> Self {
+            Self::Stale(err)
+        }
+    }
+
+    impl From<::sea_orm::DbErr> for VersionedUpdateError {
+        fn from(err: ::sea_orm::DbErr) -> Self {
+            Self::Db(err)
+        }
+    }
+
+    /// Stale versions are reported as `ABORTED`, so clients know to read the row again and retry.
+    #[cfg(feature = "tonic")]
+    impl From<VersionedUpdateError> for ::tonic::Status {
+        fn from(err: VersionedUpdateError) -> Self {
+            match err {
+                VersionedUpdateError::Stale(err) => ::tonic::Status::aborted(err.to_string()),
+                VersionedUpdateError::Db(err) => ::tonic::Status::internal(err.to_string()),
+            }
+        }
+    }
+}
+"#;
+
+/// Readiness checks generated as `database::health`.
+const HEALTH_MODULE: &str = r#"
+/// Readiness checks of the database.
+pub mod health {
+    use std::{error, fmt, time::Duration};
+
+    use ::sea_orm::{ConnectionTrait, DbErr, Statement};
+
+    /// Checks run by readiness probes, such as `GET /readyz` of the generated REST router.
+    ///
+    /// By default the database is checked with `SELECT 1`, which must respond within 2 seconds.
+    #[derive(Clone, Debug)]
+    pub struct HealthChecks {
+        timeout: Duration,
+        database: bool,
+    }
+
+    impl Default for HealthChecks {
+        fn default() -> Self {
+            HealthChecks {
+                timeout: Duration::from_secs(2),
+                database: true,
+            }
+        }
+    }
+
+    impl HealthChecks {
+        /// Sets how long each check may take before the app is reported as not ready.
+        pub fn timeout(mut self, timeout: Duration) -> Self {
+            self.timeout = timeout;
+            self
+        }
+
+        /// Sets whether the database is checked with `SELECT 1`.
+        pub fn database(mut self, enabled: bool) -> Self {
+            self.database = enabled;
+            self
+        }
+
+        /// Runs the enabled checks against `db`, returning the first failing check.
+        pub async fn check<'a, C>(&self, db: &'a C) -> Result<(), HealthError>
+        where
+            C: ConnectionTrait<'a>,
+        {
+            if self.database {
+                let query = db.execute(Statement::from_string(
+                    db.get_database_backend(),
+                    "SELECT 1".to_string(),
+                ));
+                ::tokio::time::timeout(self.timeout, query)
+                    .await
+                    .map_err(|_| HealthError::Timeout(self.timeout))??;
+            }
+
+            Ok(())
+        }
+    }
+
+    /// Error returned by a failing readiness check.
+    #[derive(Debug)]
+    pub enum HealthError {
+        /// The database did not respond within the timeout.
+        Timeout(Duration),
+        /// The database is unavailable.
+        Db(DbErr),
+    }
+
+    impl fmt::Display for HealthError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Self::Timeout(timeout) => {
+                    write!(f, "database did not respond within {:?}", timeout)
+                }
+                Self::Db(err) => write!(f, "database is unavailable: {}", err),
+            }
+        }
+    }
+
+    impl error::Error for HealthError {}
+
+    impl From<DbErr> for HealthError {
+        fn from(err: DbErr) -> Self {
+            Self::Db(err)
+        }
+    }
+}
+"#;
+
+/// Query metrics generated as `database::metrics`, recorded by the `*_metered` queries of every table.
+const METRICS_MODULE: &str = r#"
+/// Metrics of the queries of the tables, recorded with the macros of the `metrics` crate.
+///
+/// The metrics are labelled with the model and the operation of the query only, so their cardinality is bounded by the
+/// schema. They go to the recorder installed by an exporter, such as `metrics-exporter-prometheus`, which should
+/// call [`describe`] once installed and use [`DURATION_BUCKETS`] for [`QUERY_DURATION`].
+#[cfg(feature = "metrics")]
+pub mod metrics {
+    use std::{future::Future, time::Instant};
+
+    /// Counter of the queries run, labelled with `model` and `operation`.
+    pub const QUERIES: &str = "awto_db_queries_total";
+    /// Counter of the queries which failed, labelled with `model` and `operation`.
+    pub const QUERY_ERRORS: &str = "awto_db_query_errors_total";
+    /// Histogram of the duration of the queries in seconds, labelled with `model` and `operation`.
+    pub const QUERY_DURATION: &str = "awto_db_query_duration_seconds";
+    /// Buckets of [`QUERY_DURATION`] in seconds, from 1ms to 10s.
+    pub const DURATION_BUCKETS: &[f64] = &[0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+    /// Operation of a query, the `operation` label of its metrics.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Operation {
+        /// Finding a row by its id.
+        Find,
+        /// Listing the rows of a select.
+        List,
+        /// Inserting a row.
+        Insert,
+        /// Updating a row.
+        Update,
+        /// Deleting a row by its id.
+        Delete,
+    }
+
+    impl Operation {
+        /// Returns the value of the `operation` label, such as `find`.
+        pub fn as_str(self) -> &'static str {
+            match self {
+                Operation::Find => "find",
+                Operation::List => "list",
+                Operation::Insert => "insert",
+                Operation::Update => "update",
+                Operation::Delete => "delete",
+            }
+        }
+    }
+
+    /// Describes the metrics of the queries to the installed recorder.
+    pub fn describe() {
+        ::metrics::register_counter!(QUERIES, "Queries of the tables, by model and operation.");
+        ::metrics::register_counter!(QUERY_ERRORS, "Queries of the tables which failed, by model and operation.");
+        ::metrics::register_histogram!(
+            QUERY_DURATION,
+            ::metrics::Unit::Seconds,
+            "Duration of the queries of the tables, by model and operation."
+        );
+    }
+
+    /// Runs `query`, recording it as an `operation` of `model`.
+    pub async fn instrument<T, E, F>(model: &'static str, operation: Operation, query: F) -> Result<T, E>
+    where
+        F: Future<Output = Result<T, E>>,
+    {
+        let start = Instant::now();
+        let result = query.await;
+        record(model, operation, start.elapsed().as_secs_f64(), result.is_ok());
+
+        result
+    }
+
+    /// Records a query of `model` which took `seconds`.
+    pub fn record(model: &'static str, operation: Operation, seconds: f64, succeeded: bool) {
+        let operation = operation.as_str();
+        ::metrics::increment_counter!(QUERIES, "model" => model, "operation" => operation);
+        if !succeeded {
+            ::metrics::increment_counter!(QUERY_ERRORS, "model" => model, "operation" => operation);
+        }
+        ::metrics::histogram!(QUERY_DURATION, seconds, "model" => model, "operation" => operation);
+    }
+
+    #[cfg(test)]
+    mod test {
+        use std::sync::Mutex;
+
+        use ::metrics::{GaugeValue, Key, Recorder, Unit};
+
+        use super::*;
+
+        static RECORDED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+        /// Recorder keeping the name and labels of every recorded metric.
+        struct StubRecorder;
+
+        impl StubRecorder {
+            fn push(&self, key: &Key) {
+                let labels: Vec<_> = key
+                    .labels()
+                    .map(|label| format!("{}={}", label.key(), label.value()))
+                    .collect();
+                RECORDED.lock().unwrap().push(format!("{} {}", key.name(), labels.join(",")));
+            }
+        }
+
+        impl Recorder for StubRecorder {
+            fn register_counter(&self, _: &Key, _: Option<Unit>, _: Option<&'static str>) {}
+
+            fn register_gauge(&self, _: &Key, _: Option<Unit>, _: Option<&'static str>) {}
+
+            fn register_histogram(&self, _: &Key, _: Option<Unit>, _: Option<&'static str>) {}
+
+            fn increment_counter(&self, key: &Key, _: u64) {
+                self.push(key);
+            }
+
+            fn update_gauge(&self, _: &Key, _: GaugeValue) {}
+
+            fn record_histogram(&self, key: &Key, _: f64) {
+                self.push(key);
+            }
+        }
+
+        static RECORDER: StubRecorder = StubRecorder;
+
+        #[test]
+        fn records_queries() {
+            let _ = ::metrics::set_recorder(&RECORDER);
+            record("Product", Operation::Find, 0.002, true);
+            record("Product", Operation::Delete, 0.004, false);
+
+            let recorded = RECORDED.lock().unwrap();
+            assert_eq!(
+                *recorded,
+                [
+                    "awto_db_queries_total model=Product,operation=find",
+                    "awto_db_query_duration_seconds model=Product,operation=find",
+                    "awto_db_queries_total model=Product,operation=delete",
+                    "awto_db_query_errors_total model=Product,operation=delete",
+                    "awto_db_query_duration_seconds model=Product,operation=delete",
+                ]
+            );
+        }
+    }
+}
+"#;
+
+/// Batch insert and upsert helpers generated as `database::batch`.
+const BATCH_MODULE: &str = r#"
+/// Inserts and upserts of many rows, split into statements within the parameter limit of the database.
+pub mod batch {
+    use ::sea_orm::{
+        ActiveModelTrait, ConnectionTrait, DbBackend, DbErr, EntityName, EntityTrait, IdenStatic,
+        Insert, Iterable, QueryTrait, Statement,
+    };
+
+    /// Number of rows written by a single statement when no chunk size is given.
+    pub const DEFAULT_CHUNK_SIZE: usize = 1000;
+
+    /// Number of rows inserted and updated by an upsert.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct UpsertResult {
+        /// Number of rows inserted.
+        pub inserted: u64,
+        /// Number of existing rows updated.
+        pub updated: u64,
+    }
+
+    /// Returns the maximum number of bind parameters of a single statement.
+    pub fn max_parameters(backend: DbBackend) -> usize {
+        match backend {
+            DbBackend::Postgres | DbBackend::MySql => 65_535,
+            DbBackend::Sqlite => 999,
+        }
+    }
+
+    /// Conflict handling of upserts into a table.
+    #[doc(hidden)]
+    pub struct Upsert {
+        /// Unique column identifying the row to update.
+        pub target: &'static str,
+        /// Columns keeping their stored value when a row is updated.
+        pub keep: &'static [&'static str],
+        /// Column incremented when a row is updated.
+        pub version: Option<&'static str>,
+    }
+
+    #[doc(hidden)]
+    pub async fn insert<'a, C, A>(db: &'a C, models: Vec<A>, chunk_size: usize) -> Result<u64, DbErr>
+    where
+        C: ConnectionTrait<'a>,
+        A: ActiveModelTrait,
+    {
+        let mut inserted = 0;
+        for (_, statement) in insert_statements(db.get_database_backend(), models, chunk_size) {
+            inserted += db.execute(statement).await?.rows_affected();
+        }
+
+        Ok(inserted)
+    }
+
+    /// Inserts `models` like [`insert`], skipping the rows which conflict with existing rows on a unique column.
+    #[doc(hidden)]
+    pub async fn insert_new<'a, C, A>(db: &'a C, models: Vec<A>, chunk_size: usize) -> Result<u64, DbErr>
+    where
+        C: ConnectionTrait<'a>,
+        A: ActiveModelTrait,
+    {
+        let backend = db.get_database_backend();
+        if backend == DbBackend::MySql {
+            return Err(DbErr::Custom(format!(
+                "skipping conflicting rows is not supported on {:?}",
+                backend
+            )));
+        }
+
+        let mut inserted = 0;
+        for (_, mut statement) in insert_statements(backend, models, chunk_size) {
+            statement.sql.push_str(" ON CONFLICT DO NOTHING");
+            inserted += db.execute(statement).await?.rows_affected();
+        }
+
+        Ok(inserted)
+    }
+
+    #[doc(hidden)]
+    pub async fn upsert<'a, C, A>(
+        db: &'a C,
+        models: Vec<A>,
+        chunk_size: usize,
+        upsert: &Upsert,
+    ) -> Result<UpsertResult, DbErr>
+    where
+        C: ConnectionTrait<'a>,
+        A: ActiveModelTrait,
+    {
+        let backend = db.get_database_backend();
+        if backend != DbBackend::Postgres {
+            return Err(DbErr::Custom(format!(
+                "upserts are not supported on {:?}",
+                backend
+            )));
+        }
+
+        let table = <A::Entity as Default>::default().table_name().to_string();
+        let mut result = UpsertResult::default();
+        for (columns, mut statement) in insert_statements(backend, models, chunk_size) {
+            let mut assignments: Vec<_> = columns
+                .iter()
+                .filter(|column| *column != upsert.target && !upsert.keep.contains(&column.as_str()))
+                .map(|column| {
+                    if Some(column.as_str()) == upsert.version {
+                        format!("\"{}\" = \"{}\".\"{}\" + 1", column, table, column)
+                    } else {
+                        format!("\"{}\" = EXCLUDED.\"{}\"", column, column)
+                    }
+                })
+                .collect();
+            if assignments.is_empty() {
+                // Conflicting rows are only returned when they are updated
+                assignments.push(format!("\"{}\" = EXCLUDED.\"{}\"", upsert.target, upsert.target));
+            }
+            statement.sql.push_str(&format!(
+                " ON CONFLICT (\"{}\") DO UPDATE SET {} RETURNING (xmax = 0) AS inserted",
+                upsert.target,
+                assignments.join(", ")
+            ));
+
+            for row in db.query_all(statement).await? {
+                if row.try_get::<bool>("", "inserted")? {
+                    result.inserted += 1;
+                } else {
+                    result.updated += 1;
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Splits `models` into multi-row insert statements of at most `chunk_size` rows,
+    /// staying below the bind parameter limit of `backend`.
+    ///
+    /// Consecutive models setting the same columns share a statement, which is returned along with those columns.
+    fn insert_statements<A>(
+        backend: DbBackend,
+        models: Vec<A>,
+        chunk_size: usize,
+    ) -> Vec<(Vec<String>, Statement)>
+    where
+        A: ActiveModelTrait,
+    {
+        let mut statements = Vec::new();
+        let mut models = models.into_iter().peekable();
+        while let Some(first) = models.next() {
+            let columns = set_columns(&first);
+            let rows = chunk_size.min(max_parameters(backend) / columns.len().max(1)).max(1);
+
+            let mut chunk = vec![first];
+            while chunk.len() < rows {
+                match models.next_if(|model| set_columns(model) == columns) {
+                    Some(model) => chunk.push(model),
+                    None => break,
+                }
+            }
+            statements.push((columns, Insert::many(chunk).build(backend)));
+        }
+
+        statements
+    }
+
+    /// Returns the columns which are written when inserting `model`.
+    fn set_columns<A>(model: &A) -> Vec<String>
+    where
+        A: ActiveModelTrait,
+    {
+        <A::Entity as EntityTrait>::Column::iter()
+            .filter(|column| {
+                let value = model.get(*column);
+                value.is_set() || value.is_unchanged()
+            })
+            .map(|column| column.as_str().to_string())
+            .collect()
+    }
+}
+"#;
+
+/// Random rows for load testing generated as `database::fake`, behind the `fake` feature.
+const FAKE_MODULE: &str = r#"
+/// Random rows for load testing, inserted into tables in the order of their references.
+#[cfg(feature = "fake")]
+pub mod fake {
+    use std::{error, fmt};
+
+    use ::sea_orm::{ConnectionTrait, DbErr, Statement, TryGetable};
+
+    /// Number of rows of a referenced table whose values are sampled by the generated rows referencing it.
+    pub const REFERENCE_ROWS: u64 = 10_000;
+
+    /// First and last time of the random timestamps, from 2020 to 2025.
+    const TIMESTAMPS: (i64, i64) = (1_577_836_800, 1_735_689_600);
+
+    const FIRST_NAMES: &[&str] = &[
+        "Ada", "Alan", "Amara", "Ben", "Chloe", "Diego", "Elena", "Farah", "Grace", "Hiro", "Ines", "Jonas", "Kai",
+        "Lena", "Mateo", "Nadia", "Oscar", "Priya", "Quinn", "Rosa", "Sven", "Tara", "Umar", "Vera", "Wen", "Yara", "Zoe",
+    ];
+    const LAST_NAMES: &[&str] = &[
+        "Adams", "Berg", "Costa", "Dubois", "Evans", "Fischer", "Garcia", "Hughes", "Ito", "Jensen", "Kowalski", "Lopez",
+        "Meyer", "Nakamura", "Okafor", "Patel", "Rossi", "Silva", "Tanaka", "Urban", "Varga", "Weber", "Young", "Zhang",
+    ];
+    const COMPANY_SUFFIXES: &[&str] = &["Labs", "Group", "Systems", "Partners", "Works", "Studio", "Logistics", "Foods"];
+    const CITIES: &[&str] = &[
+        "Amsterdam", "Berlin", "Buenos Aires", "Cairo", "Denver", "Helsinki", "Jakarta", "Lagos", "Lisbon", "Melbourne",
+        "Montreal", "Nairobi", "Osaka", "Prague", "Seoul", "Toronto", "Valencia", "Zurich",
+    ];
+    const COUNTRIES: &[&str] = &[
+        "Argentina", "Australia", "Brazil", "Canada", "Egypt", "Finland", "Germany", "India", "Indonesia", "Japan",
+        "Kenya", "Mexico", "Netherlands", "Nigeria", "Portugal", "South Korea", "Spain", "Switzerland",
+    ];
+    const DOMAINS: &[&str] = &["example.com", "example.org", "example.net", "mail.test", "shop.test"];
+    const WORDS: &[&str] = &[
+        "amber", "anchor", "autumn", "basket", "breeze", "candle", "canyon", "cedar", "copper", "coral", "crystal",
+        "delta", "ember", "falcon", "feather", "garden", "glacier", "harbor", "hollow", "island", "jasper", "lantern",
+        "maple", "meadow", "mosaic", "nectar", "orbit", "pebble", "pepper", "prairie", "quartz", "river", "saddle",
+        "signal", "silver", "summit", "thistle", "timber", "tundra", "velvet", "willow", "winter",
+    ];
+
+    /// Options of the generation of random rows.
+    #[derive(Clone, Debug)]
+    pub struct FakeOptions {
+        /// Number of rows generated.
+        pub rows: u64,
+        /// Number of rows inserted by each transaction.
+        pub batch_size: usize,
+        /// Seed of the random values, the same seed generating the same values.
+        pub seed: u64,
+        /// Probability of nullable columns being `NULL`, between 0 and 1.
+        pub null_probability: f64,
+        /// Number of rows generated into each empty table required columns reference, before the rows referencing it.
+        pub parent_rows: u64,
+        /// Options of the transactions the rows are inserted in, such as their tenant.
+        pub txn: crate::tx::TxnOptions,
+        /// Sidecar file the progress is saved into, to resume the generation once interrupted.
+        /// The rows of the tables referenced first are not checkpointed.
+        pub checkpointing: Option<::awto::checkpoint::Checkpointing>,
+    }
+
+    impl Default for FakeOptions {
+        fn default() -> Self {
+            FakeOptions {
+                rows: 1000,
+                batch_size: 5000,
+                seed: 0,
+                null_probability: 0.1,
+                parent_rows: 100,
+                txn: Default::default(),
+                checkpointing: None,
+            }
+        }
+    }
+
+    /// Error of the generation of random rows.
+    #[derive(Debug)]
+    pub enum FakeError {
+        /// A query failed.
+        Db(DbErr),
+        /// A required column references a table without rows, which could not be generated either.
+        MissingReferences {
+            /// Table of the rows being generated.
+            table: &'static str,
+            /// Column referencing the table without rows.
+            column: &'static str,
+            /// Table without rows.
+            references: &'static str,
+        },
+        /// The checkpoint could not be read or saved, or the generation was interrupted after saving it.
+        Checkpoint(::awto::checkpoint::CheckpointError),
+    }
+
+    impl fmt::Display for FakeError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                FakeError::Db(err) => write!(f, "database error: {}", err),
+                FakeError::Checkpoint(err) => write!(f, "{}", err),
+                FakeError::MissingReferences { table, column, references } => write!(
+                    f,
+                    "cannot generate `{}` rows, as `{}` references `{}` which has no rows",
+                    table, column, references
+                ),
+            }
+        }
+    }
+
+    impl error::Error for FakeError {}
+
+    impl From<DbErr> for FakeError {
+        fn from(err: DbErr) -> Self {
+            FakeError::Db(err)
+        }
+    }
+
+    impl From<::awto::checkpoint::CheckpointError> for FakeError {
+        fn from(err: ::awto::checkpoint::CheckpointError) -> Self {
+            FakeError::Checkpoint(err)
+        }
+    }
+
+    /// Provider of realistic random text, set on columns with `#[awto(fake = "...")]`.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Fake {
+        /// First and last name, such as `Ada Lovelace`.
+        Name,
+        /// First name, such as `Ada`.
+        FirstName,
+        /// Last name, such as `Lovelace`.
+        LastName,
+        /// Email address at an example domain.
+        Email,
+        /// Lowercase first name followed by a number.
+        Username,
+        /// Phone number in the fictional `+1 555` range.
+        Phone,
+        /// Company name, such as `Berg Labs`.
+        Company,
+        /// Name of a city.
+        City,
+        /// Name of a country.
+        Country,
+        /// Url at an example domain.
+        Url,
+        /// A single lowercase word.
+        Word,
+        /// Capitalized sentence of a few words.
+        Sentence,
+        /// A few sentences.
+        Paragraph,
+        /// Lowercase words separated by dashes, for columns slugified from another column.
+        Slug,
+    }
+
+    /// Source of random values, which are the same for the same seed and table.
+    #[derive(Clone, Debug)]
+    pub struct Faker {
+        state: u64,
+    }
+
+    impl Faker {
+        /// Returns a faker of the values of `table`, so tables generated with the same seed get different values.
+        pub fn new(seed: u64, table: &str) -> Self {
+            let state = table
+                .bytes()
+                .fold(seed ^ 0xcbf2_9ce4_8422_2325, |state, byte| {
+                    (state ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+                });
+            Faker { state }
+        }
+
+        /// Returns a faker continuing with the values of the faker whose [`Faker::state`] is `state`.
+        pub fn from_state(state: u64) -> Self {
+            Faker { state }
+        }
+
+        /// Returns the state of the faker, saved into checkpoints to resume the generation of rows.
+        pub fn state(&self) -> u64 {
+            self.state
+        }
+
+        /// Returns the next random number, with splitmix64.
+        pub fn next_u64(&mut self) -> u64 {
+            self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = self.state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        }
+
+        /// Returns a random number below `n`, or 0 when `n` is 0.
+        pub fn below(&mut self, n: u64) -> u64 {
+            if n == 0 {
+                return 0;
+            }
+            self.next_u64() % n
+        }
+
+        /// Returns `true` with the probability `p`.
+        pub fn chance(&mut self, p: f64) -> bool {
+            ((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64) < p
+        }
+
+        /// Returns a random item of `items`, which must not be empty.
+        pub fn pick<'i, T>(&mut self, items: &'i [T]) -> &'i T {
+            &items[self.below(items.len() as u64) as usize]
+        }
+
+        /// Returns a random `SMALLINT` below 1000.
+        pub fn small_int(&mut self) -> i16 {
+            self.below(1000) as i16
+        }
+
+        /// Returns a random `INTEGER` below 100000.
+        pub fn integer(&mut self) -> i32 {
+            self.below(100_000) as i32
+        }
+
+        /// Returns a random `BIGINT` below 1000000.
+        pub fn big_int(&mut self) -> i64 {
+            self.below(1_000_000) as i64
+        }
+
+        /// Returns a random `REAL` with two decimals, below 1000.
+        pub fn float(&mut self) -> f32 {
+            self.below(100_000) as f32 / 100.0
+        }
+
+        /// Returns a random `DOUBLE PRECISION` with two decimals, below 1000.
+        pub fn double(&mut self) -> f64 {
+            self.below(100_000) as f64 / 100.0
+        }
+
+        /// Returns `true` or `false` with the same probability.
+        pub fn bool(&mut self) -> bool {
+            self.next_u64() & 1 == 1
+        }
+
+        /// Returns 8 random bytes.
+        pub fn bytes(&mut self) -> Vec<u8> {
+            self.next_u64().to_le_bytes().to_vec()
+        }
+
+        /// Returns a random version 4 uuid.
+        pub fn uuid(&mut self) -> ::uuid::Uuid {
+            let mut bytes = [0; 16];
+            bytes[..8].copy_from_slice(&self.next_u64().to_le_bytes());
+            bytes[8..].copy_from_slice(&self.next_u64().to_le_bytes());
+            ::uuid::Builder::from_bytes(bytes)
+                .set_variant(::uuid::Variant::RFC4122)
+                .set_version(::uuid::Version::Random)
+                .build()
+        }
+
+        /// Returns a random time between 2020 and 2025.
+        pub fn timestamp(&mut self) -> ::chrono::NaiveDateTime {
+            let (first, last) = TIMESTAMPS;
+            ::chrono::NaiveDateTime::from_timestamp(first + self.below((last - first) as u64) as i64, 0)
+        }
+
+        /// Returns a random time between 2020 and 2025 in UTC.
+        pub fn timestamptz(&mut self) -> ::chrono::DateTime<::chrono::FixedOffset> {
+            ::chrono::DateTime::from_utc(self.timestamp(), ::chrono::FixedOffset::east(0))
+        }
+
+        /// Returns a random date between 2020 and 2025.
+        pub fn date(&mut self) -> ::chrono::NaiveDate {
+            self.timestamp().date()
+        }
+
+        /// Returns a random time of day.
+        pub fn time(&mut self) -> ::chrono::NaiveTime {
+            self.timestamp().time()
+        }
+
+        /// Returns a random text of `fake` of at most `max_len` characters.
+        ///
+        /// Texts of unique columns are given the number of their row as `unique`, which they end with
+        /// so they stay unique however many rows are generated.
+        pub fn text(&mut self, fake: Fake, max_len: Option<usize>, unique: Option<u64>) -> String {
+            let first_name = *self.pick(FIRST_NAMES);
+            let last_name = *self.pick(LAST_NAMES);
+            let text = match fake {
+                Fake::Name => format!("{} {}", first_name, last_name),
+                Fake::FirstName => first_name.to_string(),
+                Fake::LastName => last_name.to_string(),
+                Fake::Email => {
+                    // The number of unique emails goes into their local part, keeping them valid
+                    let local = format!(
+                        "{}.{}{}",
+                        first_name.to_lowercase(),
+                        last_name.to_lowercase(),
+                        unique.map(|row| row.to_string()).unwrap_or_default()
+                    );
+                    let domain = format!("@{}", self.pick(DOMAINS));
+                    return fit(&local, max_len, &domain);
+                }
+                Fake::Username => format!("{}{}", first_name.to_lowercase(), self.below(1000)),
+                Fake::Phone => format!("+1 555 {:03} {:04}", self.below(1000), self.below(10_000)),
+                Fake::Company => format!("{} {}", last_name, self.pick(COMPANY_SUFFIXES)),
+                Fake::City => self.pick(CITIES).to_string(),
+                Fake::Country => self.pick(COUNTRIES).to_string(),
+                Fake::Url => format!("https://{}/{}", self.pick(DOMAINS), self.pick(WORDS)),
+                Fake::Word => self.pick(WORDS).to_string(),
+                Fake::Sentence => self.sentence(),
+                Fake::Paragraph => {
+                    let sentences = 2 + self.below(4);
+                    (0..sentences).map(|_| self.sentence()).collect::<Vec<_>>().join(" ")
+                }
+                Fake::Slug => {
+                    let words = 2 + self.below(3);
+                    (0..words).map(|_| *self.pick(WORDS)).collect::<Vec<_>>().join("-")
+                }
+            };
+            let suffix = unique.map(|row| format!("-{}", row)).unwrap_or_default();
+
+            fit(&text, max_len, &suffix)
+        }
+
+        fn sentence(&mut self) -> String {
+            let words = 4 + self.below(8);
+            let mut sentence = (0..words).map(|_| *self.pick(WORDS)).collect::<Vec<_>>().join(" ");
+            sentence[..1].make_ascii_uppercase();
+            sentence.push('.');
+            sentence
+        }
+    }
+
+    /// Returns `text` followed by `suffix`, truncating `text` so they are at most `max_len` characters long.
+    fn fit(text: &str, max_len: Option<usize>, suffix: &str) -> String {
+        let suffix_len = suffix.chars().count();
+        let text_len = match max_len {
+            Some(max_len) if suffix_len > max_len => {
+                return suffix.chars().skip(suffix_len - max_len).collect();
+            }
+            Some(max_len) => max_len - suffix_len,
+            None => usize::MAX,
+        };
+
+        let mut fitted: String = text.chars().take(text_len).collect();
+        fitted.truncate(fitted.trim_end().len());
+        fitted.push_str(suffix);
+        fitted
+    }
+
+    /// Returns the number of rows of `table`.
+    pub async fn row_count<'a, C>(db: &'a C, table: &str) -> Result<u64, DbErr>
+    where
+        C: ConnectionTrait<'a>,
+    {
+        let backend = db.get_database_backend();
+        let sql = format!("SELECT COUNT(*) AS count FROM \"{}\"", table);
+        let count = match db.query_one(Statement::from_string(backend, sql)).await? {
+            Some(row) => row.try_get::<i64>("", "count")?,
+            None => 0,
+        };
+
+        Ok(count as u64)
+    }
+
+    /// Returns the values of `column` of up to [`REFERENCE_ROWS`] rows of `table`, sampled by the rows referencing them.
+    pub async fn reference_values<'a, C, T>(db: &'a C, table: &str, column: &str) -> Result<Vec<T>, DbErr>
+    where
+        C: ConnectionTrait<'a>,
+        T: TryGetable,
+    {
+        let backend = db.get_database_backend();
+        let sql = format!("SELECT \"{}\" AS value FROM \"{}\" LIMIT {}", column, table, REFERENCE_ROWS);
+        db.query_all(Statement::from_string(backend, sql))
+            .await?
+            .iter()
+            .map(|row| row.try_get::<T>("", "value"))
+            .collect()
+    }
+}
+"#;
+
+/// CSV and JSON lines imports and exports generated as `database::bulk`, behind the `bulk` feature.
+const BULK_MODULE: &str = r#"
+/// Imports and exports of tables as CSV and JSON lines files.
+#[cfg(feature = "bulk")]
+pub mod bulk {
+    use std::{collections::HashMap, error, fmt, io};
+
+    use ::sea_orm::{Condition, DbErr};
+    use ::serde_json::{Map, Value};
+
+    /// Value exported in place of sensitive fields unless they are shown.
+    pub const MASK: &str = "********";
+
+    /// Options of the imports of CSV and JSON lines files.
+    #[derive(Clone, Debug)]
+    pub struct ImportOptions {
+        /// Whether the import fails on the first invalid row, instead of reporting it and importing the other rows.
+        pub strict: bool,
+        /// Number of rows written by a single insert statement.
+        pub chunk_size: usize,
+        /// Fields the columns not named after their field are imported into, by column name.
+        pub columns: HashMap<String, String>,
+        /// Whether columns which are not fields are left out, instead of failing the import.
+        pub ignore_unknown_columns: bool,
+    }
+
+    impl Default for ImportOptions {
+        fn default() -> Self {
+            ImportOptions {
+                strict: false,
+                chunk_size: super::batch::DEFAULT_CHUNK_SIZE,
+                columns: HashMap::new(),
+                ignore_unknown_columns: false,
+            }
+        }
+    }
+
+    impl ImportOptions {
+        /// Imports the values of the `column` of the file into `field`.
+        pub fn map_column(mut self, column: impl Into<String>, field: impl Into<String>) -> Self {
+            self.columns.insert(column.into(), field.into());
+            self
+        }
+
+        fn field<'a>(&'a self, column: &'a str) -> &'a str {
+            self.columns.get(column).map_or(column, String::as_str)
+        }
+    }
+
+    /// Options of the exports into CSV and JSON lines files.
+    #[derive(Clone, Debug)]
+    pub struct ExportOptions {
+        /// Fields exported, in the order of the columns of CSV files, or every field when `None`.
+        pub columns: Option<Vec<String>>,
+        /// Whether the values of sensitive fields are exported instead of [`MASK`], along with internal fields.
+        /// Secret fields are never exported.
+        pub show_sensitive: bool,
+        /// Condition of the exported rows, every row by default.
+        pub condition: Condition,
+        /// Number of rows read by a single query.
+        pub chunk_size: usize,
+    }
+
+    impl Default for ExportOptions {
+        fn default() -> Self {
+            ExportOptions {
+                columns: None,
+                show_sensitive: false,
+                condition: Condition::all(),
+                chunk_size: super::batch::DEFAULT_CHUNK_SIZE,
+            }
+        }
+    }
+
+    /// An invalid row of an imported file.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct RowError {
+        /// Line of the file the row starts on, counted from 1.
+        pub line: usize,
+        /// Field with the invalid value, or `None` when the whole row is invalid.
+        pub field: Option<String>,
+        /// Why the row is invalid.
+        pub reason: String,
+    }
+
+    impl fmt::Display for RowError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match &self.field {
+                Some(field) => write!(f, "line {}: `{}` {}", self.line, field, self.reason),
+                None => write!(f, "line {}: {}", self.line, self.reason),
+            }
+        }
+    }
+
+    /// Outcome of an import, listing the rows which were left out.
+    #[derive(Clone, Debug, Default, PartialEq, Eq)]
+    pub struct ImportReport {
+        /// Number of rows read from the file.
+        pub rows: usize,
+        /// Number of rows inserted.
+        pub inserted: u64,
+        /// Errors of the invalid rows, which were not inserted.
+        pub errors: Vec<RowError>,
+    }
+
+    /// Error failing an import or export.
+    #[derive(Debug)]
+    pub enum BulkError {
+        /// A query failed.
+        Db(DbErr),
+        /// The file could not be read or written.
+        Io(io::Error),
+        /// A row could not be converted to or from JSON.
+        Json(::serde_json::Error),
+        /// A column of an imported file is not a field of the table.
+        UnknownColumn {
+            /// Name of the column.
+            column: String,
+            /// Fields of the table.
+            fields: &'static [&'static str],
+        },
+        /// An exported column is not a field of the table.
+        UnknownField {
+            /// Name of the field.
+            field: String,
+            /// Fields of the table.
+            fields: &'static [&'static str],
+        },
+        /// An invalid row of a strict import.
+        Row(RowError),
+        /// The checkpoint could not be read or saved, or the import was interrupted after saving it.
+        Checkpoint(::awto::checkpoint::CheckpointError),
+    }
+
+    impl fmt::Display for BulkError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Self::Db(err) => write!(f, "database error: {}", err),
+                Self::Io(err) => write!(f, "{}", err),
+                Self::Json(err) => write!(f, "could not serialize row: {}", err),
+                Self::UnknownColumn { column, fields } => write!(
+                    f,
+                    "column `{}` is not a field, map it to one of {}",
+                    column,
+                    fields.join(", ")
+                ),
+                Self::UnknownField { field, fields } => {
+                    write!(f, "unknown field `{}`, expected one of {}", field, fields.join(", "))
+                }
+                Self::Row(err) => write!(f, "invalid row at {}", err),
+                Self::Checkpoint(err) => write!(f, "{}", err),
+            }
+        }
+    }
+
+    impl error::Error for BulkError {}
+
+    impl From<DbErr> for BulkError {
+        fn from(err: DbErr) -> Self {
+            Self::Db(err)
+        }
+    }
+
+    impl From<io::Error> for BulkError {
+        fn from(err: io::Error) -> Self {
+            Self::Io(err)
+        }
+    }
+
+    impl From<::serde_json::Error> for BulkError {
+        fn from(err: ::serde_json::Error) -> Self {
+            Self::Json(err)
+        }
+    }
+
+    impl From<::awto::checkpoint::CheckpointError> for BulkError {
+        fn from(err: ::awto::checkpoint::CheckpointError) -> Self {
+            Self::Checkpoint(err)
+        }
+    }
+
+    /// Value of a column of an imported row, as text of a CSV file or as JSON of a JSON lines file.
+    #[derive(Clone, Debug)]
+    enum Cell {
+        Text(String),
+        Json(Value),
+    }
+
+    /// A row of an imported file, with its values by field.
+    #[derive(Clone, Debug)]
+    pub struct Row {
+        /// Line of the file the row starts on, counted from 1.
+        pub line: usize,
+        /// Byte offset of the file past the row, which checkpointed imports resume from.
+        pub offset: u64,
+        cells: HashMap<String, Cell>,
+    }
+
+    impl Row {
+        fn error(&self, field: &str, reason: impl Into<String>) -> RowError {
+            RowError {
+                line: self.line,
+                field: Some(field.to_string()),
+                reason: reason.into(),
+            }
+        }
+    }
+
+    /// Parses the value of `field` of `row`, adding an error to `errors` when it is missing, not of type `T`
+    /// or longer than `max_len` characters.
+    ///
+    /// CSV cells are read as strings, then as JSON, so `42` is a number and `[1, 2]` an array, while empty cells are `null`.
+    #[doc(hidden)]
+    pub fn field<T>(row: &Row, field: &str, max_len: Option<usize>, errors: &mut Vec<RowError>) -> Option<T>
+    where
+        T: ::serde::de::DeserializeOwned,
+    {
+        let text = match row.cells.get(field) {
+            Some(Cell::Text(text)) => Some(text.as_str()),
+            Some(Cell::Json(Value::String(text))) => Some(text.as_str()),
+            _ => None,
+        };
+        if let (Some(text), Some(max_len)) = (text, max_len) {
+            if text.chars().count() > max_len {
+                errors.push(row.error(field, format!("must be at most {} characters", max_len)));
+                return None;
+            }
+        }
+
+        let parsed = match row.cells.get(field) {
+            None => ::serde_json::from_value(Value::Null).map_err(|_| "is required".to_string()),
+            Some(Cell::Json(value)) => ::serde_json::from_value(value.clone()).map_err(|err| err.to_string()),
+            Some(Cell::Text(text)) if text.is_empty() => ::serde_json::from_value(Value::Null)
+                .or_else(|_| ::serde_json::from_value(Value::String(String::new())))
+                .map_err(|_| "is required".to_string()),
+            Some(Cell::Text(text)) => ::serde_json::from_value(Value::String(text.clone())).or_else(|err| {
+                ::serde_json::from_str(text).map_err(|_| err.to_string())
+            }),
+        };
+
+        parsed.map_err(|reason| errors.push(row.error(field, reason))).ok()
+    }
+
+    /// Returns the rows of the CSV file `reader`, whose header names the fields of `fields` or the columns
+    /// mapped to them in `options`.
+    #[doc(hidden)]
+    pub fn csv_rows<R: io::BufRead>(
+        reader: R,
+        fields: &'static [&'static str],
+        options: &ImportOptions,
+    ) -> Result<impl Iterator<Item = Result<Row, BulkError>>, BulkError> {
+        csv_rows_from(reader, fields, options, 0)
+    }
+
+    /// Returns the rows of the CSV file `reader` like [`csv_rows`], starting with the row at the byte `offset`
+    /// of the file past its header.
+    #[doc(hidden)]
+    pub fn csv_rows_from<R: io::BufRead>(
+        reader: R,
+        fields: &'static [&'static str],
+        options: &ImportOptions,
+        offset: u64,
+    ) -> Result<impl Iterator<Item = Result<Row, BulkError>>, BulkError> {
+        let mut records = CsvRecords {
+            reader,
+            line: 1,
+            offset: 0,
+        };
+        let header = match records.next().transpose()? {
+            Some((_, _, mut header)) => {
+                // Spreadsheets often start files with a byte order mark
+                if let Some(first) = header.first_mut() {
+                    *first = first.trim_start_matches('\u{feff}').to_string();
+                }
+                header
+            }
+            None => Vec::new(),
+        };
+
+        let columns = header
+            .iter()
+            .map(|column| {
+                let field = options.field(column.trim());
+                if fields.contains(&field) {
+                    Ok(Some(field.to_string()))
+                } else if options.ignore_unknown_columns {
+                    Ok(None)
+                } else {
+                    Err(BulkError::UnknownColumn {
+                        column: column.clone(),
+                        fields,
+                    })
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        skip_lines(&mut records.reader, &mut records.line, &mut records.offset, offset)?;
+
+        Ok(records.map(move |record| {
+            let (line, offset, cells) = record?;
+            if cells.len() != columns.len() {
+                return Err(BulkError::Row(RowError {
+                    line,
+                    field: None,
+                    reason: format!("has {} cells, but the header has {} columns", cells.len(), columns.len()),
+                }));
+            }
+
+            let cells = columns
+                .iter()
+                .zip(cells)
+                .filter_map(|(field, cell)| Some((field.clone()?, Cell::Text(cell))))
+                .collect();
+            Ok(Row { line, offset, cells })
+        }))
+    }
+
+    /// Returns the rows of the JSON lines file `reader`, holding an object per line whose keys are the fields
+    /// of `fields` or the columns mapped to them in `options`.
+    #[doc(hidden)]
+    pub fn jsonl_rows<'o, R: io::BufRead + 'o>(
+        reader: R,
+        fields: &'static [&'static str],
+        options: &'o ImportOptions,
+    ) -> impl Iterator<Item = Result<Row, BulkError>> + 'o {
+        jsonl_rows_from(reader, fields, options, 0)
+    }
+
+    /// Returns the rows of the JSON lines file `reader` like [`jsonl_rows`], starting with the row at the byte
+    /// `offset` of the file.
+    #[doc(hidden)]
+    pub fn jsonl_rows_from<'o, R: io::BufRead + 'o>(
+        reader: R,
+        fields: &'static [&'static str],
+        options: &'o ImportOptions,
+        offset: u64,
+    ) -> impl Iterator<Item = Result<Row, BulkError>> + 'o {
+        let mut lines = Lines {
+            reader,
+            line: 1,
+            offset: 0,
+        };
+        let skipped = skip_lines(&mut lines.reader, &mut lines.line, &mut lines.offset, offset);
+        skipped
+            .err()
+            .map(|err| Err(err.into()))
+            .into_iter()
+            .chain(lines)
+            .filter(|line| !matches!(line, Ok((_, _, line)) if line.trim().is_empty()))
+            .map(move |line| {
+                let (line_number, offset, line) = line?;
+                let invalid = |reason: String| {
+                    BulkError::Row(RowError {
+                        line: line_number,
+                        field: None,
+                        reason,
+                    })
+                };
+                let object = match ::serde_json::from_str(&line) {
+                    Ok(Value::Object(object)) => object,
+                    Ok(_) => return Err(invalid("is not a JSON object".to_string())),
+                    Err(err) => return Err(invalid(format!("is not valid JSON: {}", err))),
+                };
+
+                let mut cells = HashMap::new();
+                for (key, value) in object {
+                    let field = options.field(&key);
+                    if fields.contains(&field) {
+                        cells.insert(field.to_string(), Cell::Json(value));
+                    } else if !options.ignore_unknown_columns {
+                        return Err(BulkError::Row(RowError {
+                            line: line_number,
+                            field: Some(key.clone()),
+                            reason: format!("is not a field, map it to one of {}", fields.join(", ")),
+                        }));
+                    }
+                }
+                Ok(Row {
+                    line: line_number,
+                    offset,
+                    cells,
+                })
+            })
+    }
+
+    /// Lines of a JSON lines file, along with their number and the byte offset past them.
+    struct Lines<R> {
+        reader: R,
+        line: usize,
+        offset: u64,
+    }
+
+    impl<R: io::BufRead> Iterator for Lines<R> {
+        type Item = Result<(usize, u64, String), BulkError>;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            let mut text = String::new();
+            match self.reader.read_line(&mut text) {
+                Ok(0) => None,
+                Ok(read) => {
+                    let line = self.line;
+                    self.line += 1;
+                    self.offset += read as u64;
+                    let len = text.trim_end_matches(&['\r', '\n'][..]).len();
+                    text.truncate(len);
+                    Some(Ok((line, self.offset, text)))
+                }
+                Err(err) => Some(Err(err.into())),
+            }
+        }
+    }
+
+    /// Records of a CSV file along with the line they start on and the byte offset past them,
+    /// reading quoted cells spanning several lines.
+    struct CsvRecords<R> {
+        reader: R,
+        line: usize,
+        offset: u64,
+    }
+
+    /// Skips the lines of `reader` before the byte offset `to`, which is past a line, counting them into `line`
+    /// and `offset`.
+    fn skip_lines<R: io::BufRead>(reader: &mut R, line: &mut usize, offset: &mut u64, to: u64) -> io::Result<()> {
+        let mut text = String::new();
+        while *offset < to {
+            text.clear();
+            match reader.read_line(&mut text)? {
+                0 => break,
+                read => {
+                    *line += 1;
+                    *offset += read as u64;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    impl<R: io::BufRead> Iterator for CsvRecords<R> {
+        type Item = Result<(usize, u64, Vec<String>), BulkError>;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            let mut start = self.line;
+            let mut cells = Vec::new();
+            let mut cell = String::new();
+            let mut quoted = false;
+            let mut text = String::new();
+            loop {
+                text.clear();
+                match self.reader.read_line(&mut text) {
+                    Ok(0) if quoted => {
+                        return Some(Err(BulkError::Row(RowError {
+                            line: start,
+                            field: None,
+                            reason: "has a quoted cell which is never closed".to_string(),
+                        })))
+                    }
+                    Ok(0) => return None,
+                    Ok(read) => {
+                        self.line += 1;
+                        self.offset += read as u64;
+                    }
+                    Err(err) => return Some(Err(err.into())),
+                }
+                if !quoted && cells.is_empty() && cell.is_empty() && text.trim().is_empty() {
+                    start = self.line;
+                    continue;
+                }
+
+                let mut chars = text.chars().peekable();
+                while let Some(c) = chars.next() {
+                    match (quoted, c) {
+                        (true, '"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            cell.push('"');
+                        }
+                        (true, '"') => quoted = false,
+                        (true, c) => cell.push(c),
+                        (false, '"') if cell.is_empty() => quoted = true,
+                        (false, ',') => cells.push(std::mem::take(&mut cell)),
+                        (false, '\r') | (false, '\n') => {}
+                        (false, c) => cell.push(c),
+                    }
+                }
+                if !quoted {
+                    cells.push(cell);
+                    return Some(Ok((start, self.offset, cells)));
+                }
+            }
+        }
+    }
+
+    /// Returns the exported fields of `options`, checked to be fields of `fields`.
+    #[doc(hidden)]
+    pub fn export_fields(fields: &'static [&'static str], options: &ExportOptions) -> Result<Vec<String>, BulkError> {
+        match &options.columns {
+            Some(columns) => columns
+                .iter()
+                .map(|column| match fields.contains(&column.as_str()) {
+                    true => Ok(column.clone()),
+                    false => Err(BulkError::UnknownField {
+                        field: column.clone(),
+                        fields,
+                    }),
+                })
+                .collect(),
+            None => Ok(fields.iter().map(|field| field.to_string()).collect()),
+        }
+    }
+
+    /// Serializes the exported `fields` of `row` into a JSON object, replacing the values of the `sensitive` fields
+    /// with [`MASK`] unless they are shown, along with the internal fields of the schema.
+    #[doc(hidden)]
+    pub fn export_row<T: ::serde::Serialize>(
+        row: &T,
+        fields: &[String],
+        sensitive: &[&str],
+        options: &ExportOptions,
+    ) -> Result<Map<String, Value>, BulkError> {
+        let value = if options.show_sensitive {
+            ::awto::schema::reveal_internal(|| ::serde_json::to_value(row))?
+        } else {
+            ::serde_json::to_value(row)?
+        };
+        let mut values = match value {
+            Value::Object(values) => values,
+            _ => Map::new(),
+        };
+
+        Ok(fields
+            .iter()
+            .filter_map(|field| {
+                let value = values.remove(field)?;
+                let masked = !options.show_sensitive && sensitive.contains(&field.as_str()) && !value.is_null();
+                Some((field.clone(), if masked { Value::String(MASK.to_string()) } else { value }))
+            })
+            .collect())
+    }
+
+    /// Writes a CSV line of `cells`, quoting cells containing a comma, quote or line break.
+    #[doc(hidden)]
+    pub fn write_csv_line<'a, W: io::Write>(writer: &mut W, cells: impl IntoIterator<Item = &'a str>) -> io::Result<()> {
+        let line: Vec<_> = cells
+            .into_iter()
+            .map(|cell| {
+                if cell.contains(|c| matches!(c, ',' | '"' | '\n' | '\r')) {
+                    format!("\"{}\"", cell.replace('"', "\"\""))
+                } else {
+                    cell.to_string()
+                }
+            })
+            .collect();
+        writeln!(writer, "{}", line.join(","))
+    }
+
+    /// Writes the values of `fields` of `row` as a CSV line, leaving missing and `null` values empty.
+    #[doc(hidden)]
+    pub fn write_csv_row<W: io::Write>(writer: &mut W, fields: &[String], row: &Map<String, Value>) -> io::Result<()> {
+        let cells: Vec<_> = fields
+            .iter()
+            .map(|field| match row.get(field) {
+                None | Some(Value::Null) => String::new(),
+                Some(Value::String(value)) => value.clone(),
+                Some(value) => value.to_string(),
+            })
+            .collect();
+        write_csv_line(writer, cells.iter().map(String::as_str))
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        const FIELDS: &[&str] = &["name", "price", "tags"];
+
+        fn read_csv(csv: &str, options: &ImportOptions) -> Vec<Result<Row, BulkError>> {
+            csv_rows(csv.as_bytes(), FIELDS, options).unwrap().collect()
+        }
+
+        #[test]
+        fn reads_csv() {
+            let options = ImportOptions::default().map_column("Product Name", "name");
+            let rows = read_csv(
+                "\u{feff}Product Name,price,tags\nchair,12,\"[\"\"a\"\"]\"\n\n\"multi\nline, \"\"quoted\"\"\",,\nshort\n",
+                &options,
+            );
+            assert_eq!(rows.len(), 3);
+
+            let mut errors = Vec::new();
+            let chair = rows[0].as_ref().unwrap();
+            assert_eq!(chair.line, 2);
+            assert_eq!(field::<String>(chair, "name", Some(5), &mut errors).as_deref(), Some("chair"));
+            assert_eq!(field::<i64>(chair, "price", None, &mut errors), Some(12));
+            assert_eq!(field::<Vec<String>>(chair, "tags", None, &mut errors), Some(vec!["a".to_string()]));
+
+            let multi = rows[1].as_ref().unwrap();
+            assert_eq!(multi.line, 4);
+            assert_eq!(field::<String>(multi, "name", None, &mut errors).as_deref(), Some("multi\nline, \"quoted\""));
+            assert_eq!(field::<Option<i64>>(multi, "price", None, &mut errors), Some(None));
+            assert!(errors.is_empty());
+
+            assert!(field::<i64>(multi, "price", None, &mut errors).is_none());
+            assert!(field::<String>(multi, "name", Some(5), &mut errors).is_none());
+            assert_eq!(
+                errors.iter().map(ToString::to_string).collect::<Vec<_>>(),
+                ["line 4: `price` is required", "line 4: `name` must be at most 5 characters"]
+            );
+            match &rows[2] {
+                Err(BulkError::Row(err)) => assert_eq!(err.to_string(), "line 6: has 1 cells, but the header has 3 columns"),
+                _ => panic!("expected a row error"),
+            }
+
+            match csv_rows("name,colour\n".as_bytes(), FIELDS, &ImportOptions::default()) {
+                Err(err) => assert_eq!(err.to_string(), "column `colour` is not a field, map it to one of name, price, tags"),
+                Ok(_) => panic!("expected an unknown column"),
+            }
+            let options = ImportOptions {
+                ignore_unknown_columns: true,
+                ..ImportOptions::default()
+            };
+            assert_eq!(read_csv("name,colour\nchair,red\n", &options).len(), 1);
+        }
+
+        #[test]
+        fn reads_jsonl() {
+            let options = ImportOptions::default();
+            let rows: Vec<_> =
+                jsonl_rows("{\"name\":\"chair\",\"price\":\"12\"}\n\n[]\n{\"size\":1}\n".as_bytes(), FIELDS, &options).collect();
+
+            let mut errors = Vec::new();
+            let chair = rows[0].as_ref().unwrap();
+            assert_eq!(field::<String>(chair, "name", None, &mut errors).as_deref(), Some("chair"));
+            assert!(field::<i64>(chair, "price", None, &mut errors).is_none());
+            assert_eq!(errors[0].to_string(), "line 1: `price` invalid type: string \"12\", expected i64");
+            assert!(matches!(&rows[1], Err(BulkError::Row(err)) if err.line == 3));
+            assert!(matches!(&rows[2], Err(BulkError::Row(err)) if err.field.as_deref() == Some("size")));
+        }
+
+        #[test]
+        fn writes_csv() {
+            let options = ExportOptions {
+                columns: Some(vec!["price".to_string(), "name".to_string()]),
+                ..ExportOptions::default()
+            };
+            let fields = export_fields(FIELDS, &options).unwrap();
+            let row = export_row(&::serde_json::json!({ "name": "a, \"b\"", "price": 12 }), &fields, &["name"], &options).unwrap();
+            assert_eq!(row["name"], MASK);
+
+            let mut out = Vec::new();
+            write_csv_line(&mut out, fields.iter().map(String::as_str)).unwrap();
+            write_csv_row(&mut out, &fields, &row).unwrap();
+            let shown = ExportOptions {
+                show_sensitive: true,
+                ..options.clone()
+            };
+            let row = export_row(&::serde_json::json!({ "name": "a, \"b\"", "price": null }), &fields, &["name"], &shown).unwrap();
+            write_csv_row(&mut out, &fields, &row).unwrap();
+            assert_eq!(String::from_utf8(out).unwrap(), "price,name\n12,********\n,\"a, \"\"b\"\"\"\n");
+
+            let options = ExportOptions {
+                columns: Some(vec!["colour".to_string()]),
+                ..ExportOptions::default()
+            };
+            assert_eq!(
+                export_fields(FIELDS, &options).unwrap_err().to_string(),
+                "unknown field `colour`, expected one of name, price, tags"
+            );
+        }
+    }
+}
+"#;
+
+/// Normalization helpers generated as `database::normalization` when any table has normalized columns.
+const NORMALIZATION_MODULE: &str = r#"
+/// Normalizers of the text columns with `#[awto(normalize = "...")]`.
+pub mod normalization {
+    /// Number of slugs tried by the inserts of unique slugs before giving up, `rust`, `rust-2` up to `rust-10`.
+    pub const MAX_SLUG_ATTEMPTS: u32 = 10;
+
+    /// Returns `value` as a lowercase slug of its alphanumeric characters separated by dashes,
+    /// such as `rust-in-2024` for `Rust in 2024!`.
+    pub fn slugify(value: &str) -> String {
+        let mut slug = String::with_capacity(value.len());
+        for c in value.chars() {
+            if c.is_alphanumeric() {
+                slug.extend(c.to_lowercase());
+            } else if !slug.is_empty() && !slug.ends_with('-') {
+                slug.push('-');
+            }
+        }
+
+        slug.trim_end_matches('-').to_string()
+    }
+
+    /// Returns the slug tried by the `attempt`th insert of a unique slug, `slug` itself first and `slug-2` after.
+    pub fn suffixed(slug: &str, attempt: u32) -> String {
+        if attempt <= 1 {
+            slug.to_string()
+        } else {
+            format!("{}-{}", slug, attempt)
+        }
+    }
+
+    /// Returns whether `err` is a violation of the unique `constraint`, such as `post_slug_key`.
+    pub fn is_unique_violation(err: &::sea_orm::DbErr, constraint: &str) -> bool {
+        let message = err.to_string();
+        message.contains("duplicate key") && message.contains(constraint)
+    }
+}
+"#;
+
+/// Partition maintenance generated as `database::partition` when any table is partitioned.
+const PARTITION_MODULE: &str = r#"
+/// Maintenance of the partitions of partitioned tables.
+pub mod partition {
+    use ::chrono::{Datelike, NaiveDate};
+    use ::sea_orm::{ConnectionTrait, DbErr, Statement};
+
+    /// Range of the values of the partition column held by each partition of a table.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Interval {
+        /// Each partition holds a day.
+        Day,
+        /// Each partition holds a calendar month.
+        Month,
+        /// Each partition holds a calendar year.
+        Year,
+    }
+
+    impl Interval {
+        /// Returns the first day of the partition holding `date`.
+        pub fn start(self, date: NaiveDate) -> NaiveDate {
+            match self {
+                Interval::Day => date,
+                Interval::Month => NaiveDate::from_ymd_opt(date.year(), date.month(), 1).unwrap(),
+                Interval::Year => NaiveDate::from_ymd_opt(date.year(), 1, 1).unwrap(),
+            }
+        }
+
+        /// Returns the first day of the partition following the partition starting at `start`.
+        pub fn next(self, start: NaiveDate) -> NaiveDate {
+            match self {
+                Interval::Day => start.succ_opt().unwrap(),
+                Interval::Month if start.month() == 12 => NaiveDate::from_ymd_opt(start.year() + 1, 1, 1).unwrap(),
+                Interval::Month => NaiveDate::from_ymd_opt(start.year(), start.month() + 1, 1).unwrap(),
+                Interval::Year => NaiveDate::from_ymd_opt(start.year() + 1, 1, 1).unwrap(),
+            }
+        }
+
+        /// Returns the name of the partition of `table` starting at `start`, such as `page_view_p2025_01`.
+        pub fn partition_name(self, table: &str, start: NaiveDate) -> String {
+            let suffix = match self {
+                Interval::Day => start.format("%Y_%m_%d"),
+                Interval::Month => start.format("%Y_%m"),
+                Interval::Year => start.format("%Y"),
+            };
+
+            format!("{}_p{}", table, suffix)
+        }
+    }
+
+    /// Creates the missing partitions of `table` from the partition holding `from` through the partition holding `through`,
+    /// returning the names of the partitions created.
+    ///
+    /// Existing partitions are left as they are, so it can run on every deploy or on a schedule.
+    pub async fn ensure_partitions<'a, C>(
+        db: &'a C,
+        table: &str,
+        interval: Interval,
+        from: NaiveDate,
+        through: NaiveDate,
+    ) -> Result<Vec<String>, DbErr>
+    where
+        C: ConnectionTrait<'a>,
+    {
+        let backend = db.get_database_backend();
+        let existing = partitions(db, table).await?;
+
+        let mut created = Vec::new();
+        let mut start = interval.start(from);
+        while start <= through {
+            let end = interval.next(start);
+            let name = interval.partition_name(table, start);
+            if !existing.contains(&name) {
+                let sql = format!(
+                    "CREATE TABLE IF NOT EXISTS \"{}\" PARTITION OF \"{}\" FOR VALUES FROM ('{}') TO ('{}')",
+                    name, table, start, end
+                );
+                db.execute(Statement::from_string(backend, sql)).await?;
+                created.push(name);
+            }
+            start = end;
+        }
+
+        Ok(created)
+    }
+
+    /// Returns the names of the partitions of `table`.
+    pub async fn partitions<'a, C>(db: &'a C, table: &str) -> Result<Vec<String>, DbErr>
+    where
+        C: ConnectionTrait<'a>,
+    {
+        let sql = "SELECT child.relname::text AS name FROM pg_inherits \
+            JOIN pg_class parent ON parent.oid = pg_inherits.inhparent \
+            JOIN pg_class child ON child.oid = pg_inherits.inhrelid \
+            JOIN pg_namespace ON pg_namespace.oid = parent.relnamespace \
+            WHERE parent.relname = $1 AND pg_namespace.nspname = current_schema()";
+        db.query_all(Statement::from_sql_and_values(db.get_database_backend(), sql, vec![table.into()]))
+            .await?
+            .iter()
+            .map(|row| row.try_get::<String>("", "name"))
+            .collect()
+    }
+}
+"#;
+
+/// Version histories generated as `database::temporal` when any table has `#[awto(temporal)]`.
+const TEMPORAL_MODULE: &str = r#"
+/// Previous versions of the rows of temporal tables, kept in their history tables.
+pub mod temporal {
+    use ::sea_orm::{ConnectionTrait, DbErr, EntityTrait, FromQueryResult, QueryResult, Statement, Value};
+
+    /// Change which ended a version of a row.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Operation {
+        /// The row was updated.
+        Update,
+        /// The row was deleted.
+        Delete,
+    }
+
+    impl Operation {
+        /// Returns the `operation` recorded in history tables.
+        pub fn as_str(&self) -> &'static str {
+            match self {
+                Operation::Update => "UPDATE",
+                Operation::Delete => "DELETE",
+            }
+        }
+
+        fn parse(operation: &str) -> Result<Operation, DbErr> {
+            match operation {
+                "UPDATE" => Ok(Operation::Update),
+                "DELETE" => Ok(Operation::Delete),
+                _ => Err(DbErr::Custom(format!("unknown history operation {:?}", operation))),
+            }
+        }
+    }
+
+    /// A version of a row, valid from `valid_from` until `valid_to`.
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Version<M> {
+        /// The row as it was during the version.
+        pub model: M,
+        /// Start of the version.
+        pub valid_from: ::chrono::DateTime<::chrono::FixedOffset>,
+        /// End of the version, or `None` for the current version of the row.
+        pub valid_to: Option<::chrono::DateTime<::chrono::FixedOffset>>,
+        /// Change which ended the version, or `None` for the current version of the row.
+        pub operation: Option<Operation>,
+    }
+
+    impl<M: FromQueryResult> FromQueryResult for Version<M> {
+        fn from_query_result(res: &QueryResult, pre: &str) -> Result<Self, DbErr> {
+            Ok(Version {
+                model: M::from_query_result(res, pre)?,
+                valid_from: res.try_get(pre, "valid_from")?,
+                valid_to: res.try_get(pre, "valid_to")?,
+                operation: res
+                    .try_get::<Option<String>>(pre, "operation")?
+                    .as_deref()
+                    .map(Operation::parse)
+                    .transpose()?,
+            })
+        }
+    }
+
+    /// Returns the version of the row `id` of `E` valid at `at`, selected by `sql` from its table and history table.
+    pub async fn as_of<'a, E, C>(
+        db: &'a C,
+        sql: &str,
+        id: ::uuid::Uuid,
+        at: ::chrono::DateTime<::chrono::FixedOffset>,
+    ) -> Result<Option<E::Model>, DbErr>
+    where
+        E: EntityTrait,
+        C: ConnectionTrait<'a>,
+    {
+        let statement = Statement::from_sql_and_values(db.get_database_backend(), sql, vec![id.into(), at.into()]);
+        E::find().from_raw_sql(statement).one(db).await
+    }
+
+    /// Returns every version of the row `id` of `E`, oldest first, selected by `sql` from its table and history table.
+    pub async fn history<'a, E, C>(db: &'a C, sql: &str, id: ::uuid::Uuid) -> Result<Vec<Version<E::Model>>, DbErr>
+    where
+        E: EntityTrait,
+        C: ConnectionTrait<'a>,
+    {
+        let statement = Statement::from_sql_and_values(db.get_database_backend(), sql, vec![id.into()]);
+        db.query_all(statement)
+            .await?
+            .iter()
+            .map(|row| Version::from_query_result(row, ""))
+            .collect()
+    }
+
+    /// Records a previous version of a row with `sql`, given the `values` of its columns.
+    pub async fn record<'a, C>(db: &'a C, sql: &str, mut values: Vec<Value>, operation: Operation) -> Result<(), DbErr>
+    where
+        C: ConnectionTrait<'a>,
+    {
+        let valid_to: ::chrono::DateTime<::chrono::FixedOffset> = ::chrono::Utc::now().into();
+        values.push(valid_to.into());
+        values.push(operation.as_str().into());
+        db.execute(Statement::from_sql_and_values(db.get_database_backend(), sql, values))
+            .await
+            .map(|_| ())
+    }
+}
+"#;
+
+/// Change notifications generated as `database::notify` when any table has `#[awto(notify)]`,
+/// followed by the `TABLES` notifying their changes.
+const NOTIFY_MODULE: &str = r#"
+/// Notifications of the changes of rows, sent with Postgres `LISTEN`/`NOTIFY`.
+pub mod notify {
+    use std::{sync::Arc, time::Duration};
+
+    use ::sea_orm::{ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter};
+    use ::sqlx::postgres::PgListener;
+    use ::tokio::{
+        sync::broadcast::{self, error::RecvError},
+        task::JoinHandle,
+    };
+
+    /// Number of notifications buffered for each subscriber, which misses the oldest ones once it falls behind.
+    const CAPACITY: usize = 1024;
+    /// Delay before listening again after losing the connection, doubled after every failed attempt.
+    const MIN_BACKOFF: Duration = Duration::from_millis(100);
+    /// Maximum delay between attempts to listen again.
+    const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+    /// Kind of change of a row.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Operation {
+        /// The row was inserted.
+        Created,
+        /// The row was updated.
+        Updated,
+        /// The row was deleted.
+        Deleted,
+    }
+
+    /// Change of the row `id` of `table`, sent by the trigger of the table on its [`channel`].
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct Notification {
+        /// Table of the row.
+        pub table: String,
+        /// Kind of change.
+        pub operation: Operation,
+        /// Id of the row.
+        pub id: ::uuid::Uuid,
+    }
+
+    impl Notification {
+        /// Parses the payload sent on the channel of `table`, such as `{"operation": "INSERT", "id": "..."}`.
+        pub fn parse(table: &str, payload: &str) -> Option<Self> {
+            let payload: ::serde_json::Value = ::serde_json::from_str(payload).ok()?;
+            let operation = match payload.get("operation")?.as_str()? {
+                "INSERT" => Operation::Created,
+                "UPDATE" => Operation::Updated,
+                "DELETE" => Operation::Deleted,
+                _ => return None,
+            };
+            let id = payload.get("id")?.as_str()?.parse().ok()?;
+
+            Some(Notification {
+                table: table.to_string(),
+                operation,
+                id,
+            })
+        }
+    }
+
+    /// Change of a row, holding the row as it was refetched after it was created or updated.
+    #[derive(Clone, Debug, PartialEq)]
+    pub enum Change<M> {
+        /// The row was inserted.
+        Created(M),
+        /// The row was updated.
+        Updated(M),
+        /// The row with the id was deleted.
+        Deleted(::uuid::Uuid),
+    }
+
+    /// Returns the channel the changes of `table` are sent on, such as `product_changes`.
+    pub fn channel(table: &str) -> String {
+        format!("{}_changes", table)
+    }
+
+    /// Listener of the changes of tables, fanning them out to every subscriber.
+    ///
+    /// The listener holds a connection of its own, which is listened on again with an increasing backoff
+    /// whenever it is lost. Changes are transient, so changes made while the connection was lost are missed.
+    /// It stops listening once it and every clone of it is dropped.
+    #[derive(Clone)]
+    pub struct Listener {
+        sender: broadcast::Sender<Notification>,
+        _task: Arc<Task>,
+    }
+
+    impl Listener {
+        /// Listens for the changes of `tables`, such as [`TABLES`], on the postgres database at `url`.
+        pub async fn connect(url: &str, tables: &[&str]) -> Result<Self, DbErr> {
+            if !::sea_orm::SqlxPostgresConnector::accepts(url) {
+                return Err(DbErr::Conn("change notifications are only supported on postgres".to_string()));
+            }
+
+            let channels: Vec<String> = tables.iter().map(|table| channel(table)).collect();
+            let listener = listen(url, &channels)
+                .await
+                .map_err(|err| DbErr::Conn(err.to_string()))?;
+            let (sender, _) = broadcast::channel(CAPACITY);
+            let task = ::tokio::spawn(run(listener, url.to_string(), channels, sender.clone()));
+
+            Ok(Listener {
+                sender,
+                _task: Arc::new(Task(task)),
+            })
+        }
+
+        /// Returns a receiver of the changes of every table listened for from now on.
+        pub fn subscribe(&self) -> broadcast::Receiver<Notification> {
+            self.sender.subscribe()
+        }
+    }
+
+    /// Task receiving the notifications, aborted once the last [`Listener`] is dropped.
+    struct Task(JoinHandle<()>);
+
+    impl Drop for Task {
+        fn drop(&mut self) {
+            self.0.abort();
+        }
+    }
+
+    async fn listen(url: &str, channels: &[String]) -> Result<PgListener, ::sqlx::Error> {
+        let mut listener = PgListener::connect(url).await?;
+        listener
+            .listen_all(channels.iter().map(String::as_str))
+            .await?;
+
+        Ok(listener)
+    }
+
+    async fn run(
+        mut listener: PgListener,
+        url: String,
+        channels: Vec<String>,
+        sender: broadcast::Sender<Notification>,
+    ) {
+        loop {
+            // A lost connection ends the notifications with `None` or an error
+            while let Ok(Some(notification)) = listener.try_recv().await {
+                let table = notification.channel().trim_end_matches("_changes");
+                match Notification::parse(table, notification.payload()) {
+                    // Sending only fails while nothing is subscribed
+                    Some(notification) => {
+                        let _ = sender.send(notification);
+                    }
+                    None => ::log::warn!(
+                        "ignoring invalid notification on channel `{}`: {}",
+                        notification.channel(),
+                        notification.payload()
+                    ),
+                }
+            }
+
+            let mut backoff = MIN_BACKOFF;
+            listener = loop {
+                ::log::warn!("lost the connection listening for changes, listening again in {:?}", backoff);
+                ::tokio::time::sleep(backoff).await;
+                match listen(&url, &channels).await {
+                    Ok(listener) => break listener,
+                    Err(err) => {
+                        ::log::warn!("could not listen for changes: {}", err);
+                        backoff = (backoff * 2).min(MAX_BACKOFF);
+                    }
+                }
+            };
+        }
+    }
+
+    /// Streams the changes of the rows of the entity `E` of `table` received by `listener`,
+    /// or of the row `id` only, refetching created and updated rows by their `id_column` with `db`.
+    ///
+    /// Rows deleted before they could be refetched are skipped, as their deletion follows.
+    pub fn changes<E>(
+        db: DatabaseConnection,
+        listener: &Listener,
+        table: &'static str,
+        id_column: E::Column,
+        id: Option<::uuid::Uuid>,
+    ) -> Result<impl ::futures_util::Stream<Item = Result<Change<E::Model>, DbErr>>, DbErr>
+    where
+        E: EntityTrait,
+    {
+        let backend = db.get_database_backend();
+        if backend != ::sea_orm::DbBackend::Postgres {
+            return Err(DbErr::Custom(format!(
+                "change notifications are not supported on {:?}",
+                backend
+            )));
+        }
+
+        let receiver = listener.subscribe();
+        Ok(::futures_util::stream::unfold((db, receiver), move |(db, mut receiver)| async move {
+            loop {
+                let notification = match receiver.recv().await {
+                    Ok(notification) => notification,
+                    Err(RecvError::Lagged(missed)) => {
+                        ::log::warn!("missed {} changes of `{}`", missed, table);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => return None,
+                };
+                if notification.table != table || id.map_or(false, |id| id != notification.id) {
+                    continue;
+                }
+
+                let change = match notification.operation {
+                    Operation::Deleted => Ok(Some(Change::Deleted(notification.id))),
+                    operation => E::find()
+                        .filter(id_column.eq(notification.id))
+                        .one(&db)
+                        .await
+                        .map(|model| {
+                            model.map(|model| match operation {
+                                Operation::Created => Change::Created(model),
+                                _ => Change::Updated(model),
+                            })
+                        }),
+                };
+                match change {
+                    Ok(Some(change)) => return Some((Ok(change), (db, receiver))),
+                    Ok(None) => continue,
+                    Err(err) => return Some((Err(err), (db, receiver))),
+                }
+            }
+        }))
+    }
+"#;
+
+/// Wrapper of map columns generated as `database::map` when any table has a `HashMap<String, T>` field.
+const MAP_MODULE: &str = r#"
+/// Map columns, stored as JSON objects.
+pub mod map {
+    use std::{
+        collections::HashMap,
+        ops::{Deref, DerefMut},
+    };
+
+    use ::sea_orm::{
+        sea_query::{ColumnType, Nullable, ValueType, ValueTypeErr},
+        DbErr, QueryResult, TryGetError, TryGetable, Value,
+    };
+    use ::serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+    /// Entity field of a `HashMap<String, T>` schema field, stored as a JSON object.
+    #[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(transparent)]
+    pub struct JsonMap<T>(pub HashMap<String, T>);
+
+    impl<T> Deref for JsonMap<T> {
+        type Target = HashMap<String, T>;
+
+        fn deref(&self) -> &Self::Target {
+            &self.0
+        }
+    }
+
+    impl<T> DerefMut for JsonMap<T> {
+        fn deref_mut(&mut self) -> &mut Self::Target {
+            &mut self.0
+        }
+    }
+
+    impl<T> From<HashMap<String, T>> for JsonMap<T> {
+        fn from(map: HashMap<String, T>) -> Self {
+            JsonMap(map)
+        }
+    }
+
+    impl<T> From<JsonMap<T>> for HashMap<String, T> {
+        fn from(map: JsonMap<T>) -> Self {
+            map.0
+        }
+    }
+
+    impl<T: Serialize> From<JsonMap<T>> for Value {
+        fn from(map: JsonMap<T>) -> Self {
+            let json = ::serde_json::to_value(&map.0).expect("map values serialize to JSON");
+            Value::Json(Some(Box::new(json)))
+        }
+    }
+
+    impl<T: Serialize> Nullable for JsonMap<T> {
+        fn null() -> Value {
+            Value::Json(None)
+        }
+    }
+
+    impl<T: DeserializeOwned> ValueType for JsonMap<T> {
+        fn try_from(value: Value) -> Result<Self, ValueTypeErr> {
+            match value {
+                Value::Json(Some(json)) => ::serde_json::from_value(*json).map(JsonMap).map_err(|_| ValueTypeErr),
+                _ => Err(ValueTypeErr),
+            }
+        }
+
+        fn type_name() -> String {
+            "JsonMap".to_string()
+        }
+
+        fn column_type() -> ColumnType {
+            ColumnType::JsonBinary
+        }
+    }
+
+    impl<T: DeserializeOwned> TryGetable for JsonMap<T> {
+        fn try_get(res: &QueryResult, pre: &str, col: &str) -> Result<Self, TryGetError> {
+            let json = <::serde_json::Value as TryGetable>::try_get(res, pre, col)?;
+            ::serde_json::from_value(json)
+                .map(JsonMap)
+                .map_err(|err| TryGetError::DbErr(DbErr::Query(format!("could not decode {}{}: {}", pre, col, err))))
+        }
+    }
+}
+"#;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CompileDatabaseResult {
+    pub queries_executed: usize,
+    pub rows_affected: u64,
+}
+
+/// Locale negotiation helpers generated as `database::locale` when any table has localized columns.
+const LOCALE_MODULE: &str = r#"
+/// Negotiation of the locales of localized columns.
+pub mod locale {
+    use std::cmp::Ordering;
+
+    /// Returns the `locales` to look up localized values in for `requested`, most preferred first,
+    /// ending with the default locale.
+    ///
+    /// `requested` is a single locale such as `de-AT`, or the value of an `Accept-Language` header such as
+    /// `de-AT, fr;q=0.8`. Requested locales fall back to their language, so `de-AT` matches `de`.
+    pub fn fallback_chain(requested: &str, locales: &[&'static str]) -> Vec<&'static str> {
+        let mut ranges: Vec<(&str, f32)> = requested
+            .split(',')
+            .filter_map(|range| {
+                let mut params = range.split(';');
+                let tag = params.next()?.trim();
+                let quality = params
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .and_then(|quality| quality.trim().parse().ok())
+                    .unwrap_or(1.0);
+
+                (!tag.is_empty() && tag != "*" && quality > 0.0).then(|| (tag, quality))
+            })
+            .collect();
+        ranges.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+
+        let mut chain = Vec::new();
+        for (tag, _) in ranges {
+            let language = tag.split('-').next().unwrap_or(tag);
+            for candidate in [tag, language].iter() {
+                if let Some(locale) = locales.iter().find(|locale| locale.eq_ignore_ascii_case(candidate)) {
+                    if !chain.contains(locale) {
+                        chain.push(*locale);
+                    }
+                }
+            }
+        }
+        if let Some(default) = locales.first() {
+            if !chain.contains(default) {
+                chain.push(*default);
+            }
+        }
+
+        chain
+    }
+}
+"#;
+
+/// Compiles the generated code of `models` into `OUT_DIR` and syncs the database at `uri`.
+///
+/// Generated files are only written when their content changed, so cargo does not rebuild unchanged packages,
+/// and the code of the models whose fingerprint did not change is reused, see [`ModelCache`].
+#[cfg(feature = "async")]
+pub async fn compile_database(
+    uri: &str,
+    models: Vec<Model>,
+) -> Result<CompileDatabaseResult, Box<dyn std::error::Error>> {
+    use tokio::fs;
+
+    use crate::provenance::MANIFEST_FILE;
+
+    let compiler = prepare_compiler(uri, models).await?;
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let manifest_path = Path::new(&out_dir).join(MANIFEST_FILE);
+
+    let previous = match fs::read_to_string(&manifest_path).await {
+        Ok(contents) => Some(GenerationManifest::parse(&contents)?),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+        Err(err) => return Err(err.into()),
+    };
+    let mut cache = ModelCache::new(
+        &compiler.models,
+        &compiler.cache_options(),
+        previous.as_ref(),
+    );
+    for (name, path) in cache.cached_paths(&out_dir) {
+        cache.reuse(&name, fs::read_to_string(path).await.ok());
+    }
+
+    let profile = GenerationProfile::from_env(compiler.name());
+    let mut manifest = GenerationManifest::new(compiler.annotate_sources);
+    compiler.write_files(&out_dir, &mut manifest, &profile, &mut cache)?;
+    report_regenerated(&cache);
+    cache.finish(&out_dir, &mut manifest)?;
+    let contents = manifest.to_json()?;
+    if fs::read_to_string(&manifest_path).await.ok().as_deref() != Some(contents.as_str()) {
+        fs::write(&manifest_path, contents).await?;
+    }
+
+    profile.async_phase("sync", compiler.sync()).await
+}
+
+/// Compiles the generated code of `models` into `OUT_DIR` and syncs the database at `uri`.
+///
+/// Generated files are only written when their content changed, so cargo does not rebuild unchanged packages,
+/// and the code of the models whose fingerprint did not change is reused, see [`ModelCache`].
+#[cfg(not(feature = "async"))]
+pub async fn compile_database(
+    uri: &str,
+    models: Vec<Model>,
+) -> Result<CompileDatabaseResult, Box<dyn std::error::Error>> {
+    use std::fs;
+
+    let compiler = prepare_compiler(uri, models).await?;
+    let out_dir = env::var("OUT_DIR").unwrap();
+
+    let previous = GenerationManifest::load(&out_dir)?;
+    let mut cache = ModelCache::new(
+        &compiler.models,
+        &compiler.cache_options(),
+        previous.as_ref(),
+    );
+    for (name, path) in cache.cached_paths(&out_dir) {
+        cache.reuse(&name, fs::read_to_string(path).ok());
+    }
+
+    let profile = GenerationProfile::from_env(compiler.name());
+    let mut manifest = GenerationManifest::new(compiler.annotate_sources);
+    compiler.write_files(&out_dir, &mut manifest, &profile, &mut cache)?;
+    report_regenerated(&cache);
+    cache.finish(&out_dir, &mut manifest)?;
+    manifest.write(&out_dir)?;
+
+    profile.async_phase("sync", compiler.sync()).await
+}
+
+/// Checks `models` and connects their compiler to the database at `uri`, configured by the environment of the build script.
+///
+/// The warnings of the tables are emitted, along with a warning when there is no table.
+async fn prepare_compiler(
+    uri: &str,
+    models: Vec<Model>,
+) -> Result<DatabaseCompiler<'static>, Box<dyn std::error::Error>> {
+    check_identifiers(&models)?;
+    check_projections(&models)?;
+    check_scopes(&models)?;
+    let annotate = annotate_sources();
+    watch_profile();
+    println!("cargo:rerun-if-env-changed={}", FORCE_NARROWING_ENV);
+    let compiler = DatabaseCompiler::connect(uri, models)
+        .await?
+        .with_annotations(annotate)
+        .with_entity_files(env::var_os(SOURCE_CODEGEN_ENV).is_some())
+        .with_force_narrowing(env::var_os(FORCE_NARROWING_ENV).is_some());
+
+    for (model, table) in compiler.database_tables() {
+        for warning in computed_column_warnings(model, table) {
+            warning.emit();
+        }
+    }
+    // An empty schema still compiles, so projects build before their first model exists
+    if compiler.database_tables().is_empty() {
+        Warning {
+            id: Warning::EMPTY_SCHEMA,
+            model: None,
+            field: None,
+            message: "the schema declares no database tables, the package only holds the modules shared by every table"
+                .to_string(),
+        }
+        .emit();
+    }
+
+    Ok(compiler)
+}
+
+/// Prints the models whose code was regenerated by the build, with the reason, when the build is verbose.
+fn report_regenerated(cache: &ModelCache) {
+    if ConnectPolicy::from_env().verbose {
+        for line in cache.report() {
+            println!("cargo:warning=awto: {}", line);
+        }
+    }
+}
+
+/// Creates or alters the tables of `models` in the database at `uri` without generating any code.
+///
+/// This is used by the generated test harness to prepare a fresh database.
+pub async fn sync_database(
+    uri: &str,
+    models: Vec<Model>,
+) -> Result<CompileDatabaseResult, Box<dyn std::error::Error>> {
+    let compiler = DatabaseCompiler::connect(uri, models).await?;
+
+    compiler.sync().await
+}
+
+/// Modules generated into the database package alongside the modules of the tables.
+const GENERATED_MODULES: [&str; 23] = [
+    "batch",
+    "bulk",
+    "cursor",
+    "ddl",
+    "entities",
+    "ext",
+    "fake",
+    "health",
+    IDEMPOTENCY_TABLE,
+    "locale",
+    "map",
+    "metrics",
+    "normalization",
+    "notify",
+    OUTBOX_TABLE,
+    "partition",
+    "patch",
+    "projection",
+    "subscription",
+    "temporal",
+    "tenant",
+    "tx",
+    "version",
+];
+
+/// Checks that the names of the tables of `models` and of their columns stay distinct once they are
+/// Rust identifiers, as keywords are written as `r#type` or `self_` and columns as camel cased `Column` variants.
+pub fn check_identifiers(models: &[Model]) -> Result<(), Error> {
+    fn check<'a>(
+        names: impl IntoIterator<Item = &'a str>,
+        ident: impl Fn(&str) -> String,
+        describe: impl Fn(&str, &str) -> String,
+    ) -> Result<(), Error> {
+        let mut idents: Vec<(String, &str)> = Vec::new();
+        for name in names {
+            let name_ident = ident(name);
+            if let Some((_, other)) = idents
+                .iter()
+                .find(|(other_ident, _)| *other_ident == name_ident)
+            {
+                return Err(Error::IdentifierCollision {
+                    names: describe(other, name),
+                    ident: name_ident,
+                });
+            }
+            idents.push((name_ident, name));
+        }
+
+        Ok(())
+    }
+
+    let tables: Vec<&DatabaseTable> = models
+        .iter()
+        .flat_map(|model| &model.roles)
+        .filter_map(|role| match role {
+            Role::DatabaseTable(table) => Some(table),
+            _ => None,
+        })
+        .collect();
+
+    for table in &tables {
+        let ident = rust_ident(&table.name).to_string();
+        if GENERATED_MODULES.contains(&ident.as_str()) {
+            return Err(Error::IdentifierCollision {
+                names: format!(
+                    "table `{}` and the generated `{}` module",
+                    table.name, ident
+                ),
+                ident,
+            });
+        }
+    }
+    check(
+        tables.iter().map(|table| table.name.as_str()),
+        |name| rust_ident(name).to_string(),
+        |first, second| format!("tables `{}` and `{}`", first, second),
+    )?;
+    for table in &tables {
+        let columns = || table.columns.iter().map(|column| column.name.as_str());
+        check(
+            columns(),
+            |name| rust_ident(name).to_string(),
+            |first, second| format!("columns `{}` and `{}` of `{}`", first, second, table.name),
+        )?;
+        check(
+            columns(),
+            |name| column_variant_ident(name).to_string(),
+            |first, second| format!("columns `{}` and `{}` of `{}`", first, second, table.name),
+        )?;
+        check(
+            table
+                .projections
+                .iter()
+                .map(|projection| projection.name.as_str()),
+            |name| format!("list_{}", projection_plural(&table.name, name)),
+            |first, second| {
+                format!(
+                    "projections `{}` and `{}` of `{}`",
+                    first, second, table.name
+                )
+            },
+        )?;
+    }
+    // Projections are generated into a single `projection` module
+    check(
+        tables
+            .iter()
+            .flat_map(|table| &table.projections)
+            .map(|projection| projection.name.as_str()),
+        str::to_string,
+        |first, second| format!("projections `{}` and `{}`", first, second),
+    )?;
+
+    check_database_names(&tables)
+}
+
+/// Checks that the columns selected by the projections of the tables of `models` have a type their structs can hold.
+pub fn check_projections(models: &[Model]) -> Result<(), Error> {
+    for model in models {
+        for role in &model.roles {
+            let table = match role {
+                Role::DatabaseTable(table) => table,
+                _ => continue,
+            };
+            for projection in &table.projections {
+                for column in &projection.columns {
+                    if projection_field_ty(model, table, column).is_none() {
+                        return Err(Error::UnsupportedProjectionColumn {
+                            projection: projection.name.clone(),
+                            column: format!("{}.{}", table.name, column),
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the type of the field of `column` of `table` in the struct of a projection, as in the entity of the table.
+fn projection_field_ty(model: &Model, table: &DatabaseTable, column: &str) -> Option<TokenStream> {
+    let column = table.columns.iter().find(|other| other.name == column)?;
+    let map_ty = model
+        .fields
+        .iter()
+        .find(|field| field.name == column.name)
+        .and_then(|field| map_entity_ty(&field.ty));
+    let ty = map_ty.or_else(|| column_rust_type(&column.ty))?;
+
+    Some(if column.nullable {
+        quote!(Option<#ty>)
+    } else {
+        ty
+    })
+}
+
+/// Checks that the names of `tables`, of their columns and of their policies are not truncated by Postgres,
+/// and that the names awto derives from them stay distinct once [shortened](derived_name).
+fn check_database_names(tables: &[&DatabaseTable]) -> Result<(), Error> {
+    fn check_len(name: &str, describe: impl Fn() -> String) -> Result<(), Error> {
+        if name.len() <= MAX_IDENTIFIER_LEN {
+            return Ok(());
+        }
+
+        Err(Error::IdentifierTooLong {
+            name: describe(),
+            len: name.len(),
+            limit: MAX_IDENTIFIER_LEN,
+        })
+    }
+
+    fn check_distinct(names: impl IntoIterator<Item = (String, String)>) -> Result<(), Error> {
+        let mut seen: Vec<(String, String)> = Vec::new();
+        for (name, describe) in names {
+            if let Some((_, other)) = seen.iter().find(|(other_name, _)| *other_name == name) {
+                return Err(Error::IdentifierCollision {
+                    names: format!("{} and {}", other, describe),
+                    ident: name,
+                });
+            }
+            seen.push((name, describe));
+        }
+
+        Ok(())
+    }
+
+    for table in tables {
+        check_len(&table.name, || format!("table `{}`", table.name))?;
+        for column in &table.columns {
+            check_len(&column.name, || {
+                format!("column `{}` of `{}`", column.name, table.name)
+            })?;
+        }
+        for policy in &table.policies {
+            check_len(&policy.name, || {
+                format!("policy `{}` of `{}`", policy.name, table.name)
+            })?;
+        }
+        // Partitions and notification channels are named by the generated package, with the longest suffixes below
+        if let Some(partition) = &table.partition {
+            let suffix = match partition.interval {
+                PartitionInterval::Day => "p2025_01_01",
+                PartitionInterval::Month => "p2025_01",
+                PartitionInterval::Year => "p2025",
+            };
+            let name = format!("{}_{}", table.name, suffix);
+            check_len(&name, || {
+                format!("partition `{}` of `{}`", name, table.name)
+            })?;
+        }
+        if table.notify {
+            let name = format!("{}_changes", table.name);
+            check_len(&name, || {
+                format!("notification channel `{}` of `{}`", name, table.name)
+            })?;
+        }
+    }
+
+    // Tables and the indexes of unique keys share the namespace of relations, while constraints are named per table
+    let created_tables: Vec<(DatabaseTable, String)> = tables
+        .iter()
+        .map(|table| {
+            (
+                with_locale_columns(table),
+                format!("table `{}`", table.name),
+            )
+        })
+        .chain(tables.iter().filter_map(|table| {
+            translations_table(table).map(|translations| {
+                (
+                    translations,
+                    format!("the translations table of `{}`", table.name),
+                )
+            })
+        }))
+        .chain(tables.iter().filter_map(|table| {
+            history_table(table)
+                .map(|history| (history, format!("the history table of `{}`", table.name)))
+        }))
+        .collect();
+    let relations = created_tables.iter().flat_map(|(table, describe)| {
+        let keys = table
+            .columns
+            .iter()
+            .filter(|column| column.unique)
+            .map(move |column| {
+                (
+                    derived_name(&[&table.name, &column.name], "key"),
+                    format!("the unique key of `{}.{}`", table.name, column.name),
+                )
+            });
+        let fulltext = table.fulltext.as_ref().map(|fulltext| {
+            (
+                DatabaseCompiler::fulltext_index_name(table, fulltext),
+                format!("the full-text search index of `{}`", table.name),
+            )
+        });
+
+        iter::once((table.name.clone(), describe.clone()))
+            .chain(keys)
+            .chain(fulltext)
+    });
+    // Translations tables are keyed on the translated row, locale and field
+    let translations_keys = tables.iter().filter_map(|table| {
+        translations_table(table).map(|translations| {
+            (
+                derived_name(&[&translations.name], "key"),
+                format!(
+                    "the unique key of the translations table of `{}`",
+                    table.name
+                ),
+            )
+        })
+    });
+    check_distinct(relations.chain(translations_keys))?;
+
+    let functions = tables.iter().flat_map(|table| {
+        let notify = table.notify.then(|| {
+            (
+                DatabaseCompiler::notify_trigger_name(table),
+                format!("the notification trigger of `{}`", table.name),
+            )
+        });
+        let history = table.temporal.is_some().then(|| {
+            (
+                DatabaseCompiler::history_trigger_name(table),
+                format!("the history trigger of `{}`", table.name),
+            )
+        });
+
+        notify.into_iter().chain(history)
+    });
+    check_distinct(functions)?;
+
+    for (table, _) in &created_tables {
+        check_distinct(table.columns.iter().map(|column| {
+            (
+                column.name.clone(),
+                format!("column `{}` of `{}`", column.name, table.name),
+            )
+        }))?;
+        check_distinct(table.columns.iter().flat_map(|column| {
+            let key = column.unique.then(|| {
+                (
+                    derived_name(&[&table.name, &column.name], "key"),
+                    format!("the unique key of `{}.{}`", table.name, column.name),
+                )
+            });
+            let foreign_key = column.references.as_ref().map(|_| {
+                (
+                    derived_name(&[&table.name, &column.name], "fkey"),
+                    format!("the foreign key of `{}.{}`", table.name, column.name),
+                )
+            });
+
+            key.into_iter().chain(foreign_key)
+        }))?;
+    }
+
+    Ok(())
+}
+
+/// Returns the models with database tables belonging to `database`, or all models if `database` is `None`.
+///
+/// Models without any database table, such as protobuf-only messages, are excluded when a database is given.
+pub fn database_models(models: Vec<Model>, database: Option<&str>) -> Vec<Model> {
+    let database = match database {
+        Some(database) => database,
+        None => return models,
+    };
+
+    models
+        .into_iter()
+        .filter(|model| {
+            model.roles.iter().any(|role| match role {
+                Role::DatabaseTable(table) | Role::DatabaseSubTable(table) => {
+                    table.database() == database
+                }
+                _ => false,
+            })
+        })
+        .collect()
+}
+
+/// Checks that the columns of `models` reference tables of another database only when they are marked `cross_database`,
+/// as foreign keys cannot reach across databases, and that the references marked so do reach another database.
+///
+/// References are checked across every model before [`database_models`] leaves out the tables of other databases.
+pub fn check_database_references(models: &[Model]) -> Result<(), Error> {
+    let tables: Vec<(&Model, &DatabaseTable)> = models
+        .iter()
+        .flat_map(|model| {
+            model.roles.iter().filter_map(move |role| match role {
+                Role::DatabaseTable(table) => Some((model, table)),
+                _ => None,
+            })
+        })
+        .collect();
+
+    for (model, table) in &tables {
+        for column in &table.columns {
+            let (referenced_table, referenced_column) = match &column.references {
+                Some(references) => references,
+                None => continue,
+            };
+            let (referenced, referenced_table) = match tables
+                .iter()
+                .find(|(_, other)| other.name == *referenced_table)
+            {
+                Some(referenced) => referenced,
+                None => continue,
+            };
+
+            // A text key fits in its references unless they are shorter
+            let matching_ty = |referenced_ty: &DatabaseType| match (&column.ty, referenced_ty) {
+                (DatabaseType::Text(len), DatabaseType::Text(referenced_len)) => {
+                    match (len, referenced_len) {
+                        (Some(len), Some(referenced_len)) => len >= referenced_len,
+                        (Some(_), None) => false,
+                        (None, _) => true,
+                    }
+                }
+                (ty, referenced_ty) => ty == referenced_ty,
+            };
+            if let Some(referenced_column) = referenced_table
+                .columns
+                .iter()
+                .find(|other| other.name == *referenced_column)
+            {
+                if !matching_ty(&referenced_column.ty) {
+                    return Err(Error::ReferenceTypeMismatch {
+                        column: column.name.clone(),
+                        model: model.name.clone(),
+                        ty: column.ty.to_string(),
+                        referenced: format!("{}.{}", referenced.name, referenced_column.name),
+                        referenced_ty: referenced_column.ty.to_string(),
+                    });
+                }
+            }
+
+            if table.database() != referenced_table.database() && !column.cross_database {
+                return Err(Error::CrossDatabaseReference {
+                    model: model.name.clone(),
+                    database: table.database().to_string(),
+                    referenced: referenced.name.clone(),
+                    referenced_database: referenced_table.database().to_string(),
+                });
+            }
+            if table.database() == referenced_table.database() && column.cross_database {
+                return Err(Error::SameDatabaseReference {
+                    column: column.name.clone(),
+                    model: model.name.clone(),
+                    referenced: referenced.name.clone(),
+                    database: table.database().to_string(),
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub struct DatabaseCompiler<'pool> {
+    pool: Cow<'pool, PgPool>,
+    models: Vec<Model>,
+    annotate_sources: bool,
+    /// Whether the entities are modules of their own files in `src/entities`, see [`with_entity_files`](Self::with_entity_files).
+    entity_files: bool,
+    /// Time [`sync`](Self::sync) waits for another sync of the database to finish.
+    lock_timeout: Duration,
+    /// Whether columns are narrowed even when rows do not fit them, see [`with_force_narrowing`](Self::with_force_narrowing).
+    force_narrowing: bool,
+    index: TableIndex,
+}
+
+/// Lookups of the tables of the models by name, built once so generating large schemas stays linear in their size.
+#[derive(Debug, Default)]
+struct TableIndex {
+    /// Position in the models of the model of each database table.
+    tables: HashMap<String, usize>,
+    /// Position in the models of the first model of each database sub table.
+    sub_tables: HashMap<String, usize>,
+    /// Columns of each table referenced by the columns of other tables.
+    referenced_columns: HashMap<String, BTreeSet<String>>,
+}
+
+impl TableIndex {
+    fn new(models: &[Model]) -> Self {
+        let mut index = TableIndex::default();
+        for (i, model) in models.iter().enumerate() {
+            for role in &model.roles {
+                match role {
+                    Role::DatabaseTable(table) => {
+                        index.tables.entry(table.name.clone()).or_insert(i);
+                        for (references_table, references_column) in table
+                            .columns
+                            .iter()
+                            .filter(|column| !column.cross_database)
+                            .filter_map(|column| column.references.as_ref())
+                        {
+                            index
+                                .referenced_columns
+                                .entry(references_table.clone())
+                                .or_default()
+                                .insert(references_column.clone());
+                        }
+                    }
+                    Role::DatabaseSubTable(table) => {
+                        index.sub_tables.entry(table.name.clone()).or_insert(i);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        index
+    }
+}
+
+/// Generates the entities, smoke tests, benchmarks and crate documentation of a database package.
+///
+/// The sql syncing the database is not generated, as it depends on the state of the database.
+impl Generator for DatabaseCompiler<'_> {
+    fn name(&self) -> &str {
+        "database"
+    }
+
+    fn generate(
+        &self,
+        models: &[Model],
+        options: &GeneratorOptions,
+    ) -> Result<Vec<GeneratedFile>, Error> {
+        let compiler = DatabaseCompiler {
+            pool: Cow::Borrowed(&*self.pool),
+            index: TableIndex::new(models),
+            models: models.to_vec(),
+            annotate_sources: options.annotate_sources,
+            entity_files: self.entity_files,
+            lock_timeout: self.lock_timeout,
+            force_narrowing: self.force_narrowing,
+        };
+        let code = compiler
+            .write_generated_code(Vec::new())
+            .map_err(Error::Io)?;
+        let code = String::from_utf8(code).expect("generated code is utf-8");
+
+        Ok(vec![
+            GeneratedFile::new(COMPILED_RUST_FILE, code, FileKind::Rust),
+            GeneratedFile::new(
+                COMPILED_TESTS_FILE,
+                format_rust(&compiler.compile_smoke_tests()),
+                FileKind::Rust,
+            ),
+            GeneratedFile::new(
+                COMPILED_BENCHES_FILE,
+                format_rust(&compiler.compile_benches()),
+                FileKind::Rust,
+            ),
+            GeneratedFile::new(
+                COMPILED_DOCS_FILE,
+                compiler.compile_crate_doc(),
+                FileKind::Markdown,
+            ),
+        ])
+    }
+}
+
+impl<'pool> DatabaseCompiler<'pool> {
+    /// Connects to the database at `uri` with the [`ConnectPolicy`] set in the environment.
+    pub async fn connect(
+        uri: &str,
+        models: Vec<Model>,
+    ) -> Result<DatabaseCompiler<'static>, Error> {
+        let policy = ConnectPolicy::from_env();
+        let pool = policy.connect(uri).await?;
+
+        Ok(DatabaseCompiler {
+            pool: Cow::Owned(pool),
+            index: TableIndex::new(&models),
+            models,
+            annotate_sources: false,
+            entity_files: false,
+            lock_timeout: policy.lock_timeout,
+            force_narrowing: false,
+        })
+    }
+
+    pub fn from_pool(pool: &'pool PgPool, models: Vec<Model>) -> DatabaseCompiler<'pool> {
+        DatabaseCompiler {
+            pool: Cow::Borrowed(pool),
+            index: TableIndex::new(&models),
+            models,
+            annotate_sources: false,
+            entity_files: false,
+            lock_timeout: ConnectPolicy::default().lock_timeout,
+            force_narrowing: false,
+        }
+    }
+
+    /// Waits up to `lock_timeout` for another sync of the database to finish before syncing, see [`SyncLock`].
+    pub fn with_lock_timeout(mut self, lock_timeout: Duration) -> Self {
+        self.lock_timeout = lock_timeout;
+        self
+    }
+
+    /// Alters the columns narrowed by the models even when rows of the database do not fit them, reporting these
+    /// rows as warnings instead of failing, see [`narrowing`](crate::narrowing).
+    pub fn with_force_narrowing(mut self, force_narrowing: bool) -> Self {
+        self.force_narrowing = force_narrowing;
+        self
+    }
+
+    /// Interleaves the generated Rust and sql with comments telling which model and field produced each chunk.
+    pub fn with_annotations(mut self, annotate_sources: bool) -> Self {
+        self.annotate_sources = annotate_sources;
+        self
+    }
+
+    /// Declares the entity of each table as a module of the `src/entities/<table>.rs` file of the package
+    /// instead of including it from `OUT_DIR`, for packages whose generated code is written into their sources.
+    pub fn with_entity_files(mut self, entity_files: bool) -> Self {
+        self.entity_files = entity_files;
+        self
+    }
+
+    /// Compiles the sql bringing the database in sync with the models.
+    ///
+    /// Fails with [`Error::NarrowingViolations`] when rows of the database do not fit the columns narrowed by the
+    /// models, unless their columns have a backfill or narrowing is forced.
+    pub async fn compile(&self) -> Result<String, Error> {
+        let mut sql = String::new();
+        let mut violations = Vec::new();
+
+        let tables = self.created_tables();
+        // Foreign keys are added once every table exists, so tables can reference tables created after them or themselves
+        let mut foreign_keys_sql = String::new();
+        for table in &tables {
+            let db_columns = self.fetch_table(table).await?;
+            let is_translations = self
+                .translations_tables()
+                .iter()
+                .any(|translations| translations.name == table.name);
+            let is_history = self
+                .history_tables()
+                .iter()
+                .any(|history| history.name == table.name);
+
+            let table_start = sql.len();
+            match db_columns {
+                Some(db_columns) => {
+                    if table.partition.is_some() && !self.fetch_is_partitioned(table).await? {
+                        self.warn_unpartitioned(table);
+                    }
+                    if is_history {
+                        writeln!(sql, "{}", self.write_history_sync_sql(table, &db_columns))
+                            .unwrap();
+                    } else {
+                        writeln!(
+                            sql,
+                            "{}",
+                            self.write_backfill_sql(table, &db_columns, &mut violations)
+                                .await?
+                        )
+                        .unwrap();
+                        writeln!(sql, "{}", self.write_sync_sql(table, &db_columns).await).unwrap();
+                    }
+                    if !is_translations {
+                        let db_set_null =
+                            self.fetch_set_null_foreign_keys(table, &db_columns).await?;
+                        write!(
+                            foreign_keys_sql,
+                            "{}",
+                            self.write_foreign_keys_sync_sql(table, &db_columns, &db_set_null)
+                        )
+                        .unwrap();
+                    }
+                    let db_indexes = self.fetch_fulltext_indexes(table).await?;
+                    writeln!(sql, "{}", self.write_fulltext_sync_sql(table, &db_indexes)).unwrap();
+                    let (rls_enabled, db_policies) = self.fetch_policies(table).await?;
+                    writeln!(
+                        sql,
+                        "{}",
+                        self.write_policy_sync_sql(table, rls_enabled, &db_policies)
+                    )
+                    .unwrap();
+                    let db_notify = self.fetch_notify_trigger(table).await?;
+                    writeln!(sql, "{}", self.write_notify_sync_sql(table, db_notify)).unwrap();
+                    let db_history_trigger = self.fetch_history_trigger(table).await?;
+                    writeln!(
+                        sql,
+                        "{}",
+                        self.write_history_trigger_sync_sql(table, db_history_trigger)
+                    )
+                    .unwrap();
+                    if is_translations {
+                        let db_key = self.fetch_translations_key(table).await?;
+                        writeln!(sql, "{}", self.write_translations_sync_sql(table, db_key))
+                            .unwrap();
+                    }
+                }
+                None => {
+                    writeln!(sql, "{}", self.write_table_create_sql(table)).unwrap();
+                    if !is_translations {
+                        write!(
+                            foreign_keys_sql,
+                            "{}",
+                            self.write_foreign_keys_sync_sql(table, &[], &[])
+                        )
+                        .unwrap();
+                    }
+                    writeln!(sql, "{}", self.write_fulltext_sync_sql(table, &[])).unwrap();
+                    writeln!(sql, "{}", self.write_policy_sync_sql(table, false, &[])).unwrap();
+                    writeln!(sql, "{}", self.write_notify_sync_sql(table, false)).unwrap();
+                    writeln!(
+                        sql,
+                        "{}",
+                        self.write_history_trigger_sync_sql(table, (false, None))
+                    )
+                    .unwrap();
+                    if is_translations {
+                        writeln!(
+                            sql,
+                            "{}",
+                            self.write_translations_sync_sql(table, (false, false))
+                        )
+                        .unwrap();
+                    }
+                }
+            }
+            if !sql[table_start..].trim().is_empty() {
+                sql.insert_str(
+                    table_start,
+                    &self.table_annotation(table, None, CommentStyle::Dashes),
+                );
+            }
+        }
+        sql.push_str(&foreign_keys_sql);
+
+        // Nothing is applied when a column cannot be narrowed, so a sync does not stop midway
+        if !violations.is_empty() {
+            return Err(Error::NarrowingViolations {
+                violations: violations
+                    .iter()
+                    .map(|violation| format!("\n  {}", violation))
+                    .collect(),
+            });
+        }
+
+        if !sql.trim().is_empty() {
+            sql.insert_str(0, &Self::write_extensions_sql(&tables));
+        }
+
+        Ok(format_sql(&sql))
+    }
+
+    /// Compiles and executes the sql required to bring the database in sync with the models.
+    ///
+    /// The changes are compiled and applied while holding the [`SyncLock`] of the database, so syncs running at once
+    /// apply them one after the other, failing with [`Error::SyncInProgress`] after waiting for the lock timeout.
+    pub async fn sync(&self) -> Result<CompileDatabaseResult, Box<dyn std::error::Error>> {
+        let mut lock = SyncLock::acquire(&self.pool, self.lock_timeout).await?;
+        let result = self.sync_locked(lock.connection()).await;
+        // A failed sync keeps its error, as the connection of a lock which cannot be released is closed
+        let released = lock.release().await;
+        let result = result?;
+        released?;
+
+        Ok(result)
+    }
+
+    /// Compiles the changes of the tables and applies them on `conn`, which holds the lock of the database.
+    async fn sync_locked(
+        &self,
+        conn: &mut PgConnection,
+    ) -> Result<CompileDatabaseResult, Box<dyn std::error::Error>> {
+        let sql = self.compile().await?;
+        if sql.is_empty() {
+            return Ok(CompileDatabaseResult::default());
+        }
+
+        let results = conn
+            .execute_many(sql.as_str())
+            .collect::<Result<Vec<_>, _>>()
+            .await?;
+        let queries_executed = results.len();
+        let rows_affected = results
+            .iter()
+            .fold(0, |acc, result| result.rows_affected() + acc);
+
+        Ok(CompileDatabaseResult {
+            queries_executed,
+            rows_affected,
+        })
+    }
+
+    /// Compiles generated Rust code from schemas and services.
+    pub fn compile_generated_code(&self) -> String {
+        // Only caching the code of a model fails, which is not done here
+        let code: String = self
+            .generated_code_units(|model| Ok(self.compile_model_code(model)))
+            .filter_map(Result::ok)
+            .collect();
+
+        code.trim().to_string()
+    }
+
+    /// Writes the generated Rust code of [`compile_generated_code`](Self::compile_generated_code), followed by the
+    /// sea-orm modules of the tables, into `out`.
+    ///
+    /// The code of each table is generated and formatted in batches as it is written, so only a batch is held in memory.
+    pub fn write_generated_code<W: io::Write>(&self, out: W) -> io::Result<W> {
+        self.write_generated_code_with(out, |model| Ok(self.compile_model_code(model)))
+    }
+
+    /// Writes the generated Rust code like [`write_generated_code`](Self::write_generated_code), with the code of each
+    /// model returned by `model_code`.
+    fn write_generated_code_with<'a, W: io::Write>(
+        &'a self,
+        out: W,
+        model_code: impl FnMut(&'a Model) -> io::Result<String> + 'a,
+    ) -> io::Result<W> {
+        let mut writer = RustWriter::new(out);
+        for unit in self.generated_code_units(model_code) {
+            writer.write_unit(&unit?)?;
+        }
+        writer.write_unit(&self.compile_sea_orm_modules()?)?;
+
+        writer.finish()
+    }
+
+    /// Writes the generated files into `out_dir`, adding them to `manifest` and reporting the phase of each to `profile`.
+    ///
+    /// Files are generated one at a time, and the code of the tables is streamed into `app.rs` as it is generated,
+    /// so the memory used does not grow with the generated code of every table.
+    /// The code of each model is reused from `cache` when its fingerprint did not change, and cached otherwise.
+    pub fn write_files(
+        &self,
+        out_dir: &str,
+        manifest: &mut GenerationManifest,
+        profile: &GenerationProfile,
+        cache: &mut ModelCache,
+    ) -> io::Result<()> {
+        let model_names: Vec<&str> = self
+            .database_tables()
+            .into_iter()
+            .map(|(model, _)| model.name.as_str())
+            .collect();
+
+        profile.phase(COMPILED_RUST_FILE, || {
+            let out = GeneratedWriter::create(out_dir, COMPILED_RUST_FILE)?;
+            let file = self.write_generated_code_with(out, |model| {
+                cache.code(out_dir, &model.name, || self.compile_model_code(model))
+            })?;
+            file.finish(manifest, self.name(), &model_names).map(drop)
+        })?;
+
+        let files: [(&str, &dyn Fn() -> String); 3] = [
+            (COMPILED_TESTS_FILE, &|| {
+                format_rust(&self.compile_smoke_tests())
+            }),
+            (COMPILED_BENCHES_FILE, &|| {
+                format_rust(&self.compile_benches())
+            }),
+            (COMPILED_DOCS_FILE, &|| self.compile_crate_doc()),
+        ];
+        for (path, generate) in files {
+            let generator = COMPILED_FILE_GENERATORS
+                .iter()
+                .find(|(name, _)| *name == path)
+                .map_or(self.name(), |(_, generator)| generator);
+            profile.phase(path, || {
+                write_generated(
+                    out_dir,
+                    path,
+                    &generate(),
+                    manifest,
+                    generator,
+                    &model_names,
+                )
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the version of the generator and the options changing the generated code of the models,
+    /// whose hash is part of their fingerprint, see [`ModelCache`].
+    pub fn cache_options(&self) -> String {
+        format!(
+            "{} annotate_sources={} entity_files={}",
+            GENERATOR_VERSION, self.annotate_sources, self.entity_files
+        )
+    }
+
+    /// Returns the units of the generated Rust code, generated as they are iterated: the modules shared by every table,
+    /// the code of each model with a database table returned by `model_code`, and the patches, projections, scopes,
+    /// statements, data loaders and subscriptions of the tables.
+    fn generated_code_units<'a>(
+        &'a self,
+        model_code: impl FnMut(&'a Model) -> io::Result<String> + 'a,
+    ) -> impl Iterator<Item = io::Result<String>> + 'a {
+        iter::once_with(move || Ok(self.compile_shared_modules()))
+            .chain(
+                self.models
+                    .iter()
+                    .filter(|model| database_roles(model).next().is_some())
+                    .map(model_code),
+            )
+            .chain(iter::once_with(move || {
+                Ok(self.compile_patches().to_string())
+            }))
+            .chain(iter::once_with(move || {
+                Ok(self.compile_projections().to_string())
+            }))
+            .chain(iter::once_with(move || {
+                Ok(self.compile_scopes().to_string())
+            }))
+            .chain(iter::once_with(move || {
+                Ok(self.compile_ddl_module().to_string())
+            }))
+            .chain(iter::once_with(move || {
+                Ok(self.compile_data_loaders().to_string())
+            }))
+            .chain(iter::once_with(move || {
+                Ok(self.compile_subscriptions().to_string())
+            }))
+    }
+
+    /// Compiles the code generated for `model`: the conversions and queries of each of its tables,
+    /// and the conversions of each of its sub tables.
+    fn compile_model_code(&self, model: &Model) -> String {
+        let mut code = String::new();
+        for role in &model.roles {
+            match role {
+                Role::DatabaseTable(table) => {
+                    code.push_str(&self.compile_conversions(model, table));
+                    code.push_str(&self.compile_table_queries(model, table));
+                }
+                Role::DatabaseSubTable(table) => {
+                    code.push_str(&self.compile_sub_table_conversions(model, table))
+                }
+                _ => {}
+            }
+        }
+
+        code
+    }
+
+    /// Compiles the modules shared by every table, such as `database::tx`.
+    fn compile_shared_modules(&self) -> String {
+        let mut code = TX_MODULE.to_string();
+        code.push_str(CURSOR_MODULE);
+        code.push_str(VERSION_MODULE);
+        code.push_str(BATCH_MODULE);
+        code.push_str(BULK_MODULE);
+        code.push_str(FAKE_MODULE);
+        code.push_str(HEALTH_MODULE);
+        code.push_str(METRICS_MODULE);
+        code.push_str(TENANT_MODULE);
+        if self
+            .database_tables()
+            .iter()
+            .any(|(_, table)| !table.localized_columns().is_empty())
+        {
+            code.push_str(LOCALE_MODULE);
+        }
+        if self.database_tables().iter().any(|(_, table)| {
+            table
+                .columns
+                .iter()
+                .any(|column| !column.normalize.is_empty())
+        }) {
+            code.push_str(NORMALIZATION_MODULE);
+        }
+        if self.database_tables().iter().any(|(model, _)| {
+            model
+                .fields
+                .iter()
+                .any(|field| map_value_ty(&field.ty).is_some())
+        }) {
+            code.push_str(MAP_MODULE);
+        }
+        if self
+            .database_tables()
+            .iter()
+            .any(|(_, table)| table.partition.is_some())
+        {
+            code.push_str(PARTITION_MODULE);
+        }
+        if self
+            .database_tables()
+            .iter()
+            .any(|(_, table)| table.temporal.is_some())
+        {
+            code.push_str(TEMPORAL_MODULE);
+        }
+        let notify_tables: Vec<_> = self
+            .database_tables()
+            .into_iter()
+            .filter(|(_, table)| table.notify)
+            .map(|(_, table)| table.name.clone())
+            .collect();
+        if !notify_tables.is_empty() {
+            code.push_str(NOTIFY_MODULE);
+            let tables = quote!(
+                /// Tables with `#[awto(notify)]`, whose changes are sent on their channels.
+                pub const TABLES: &[&str] = &[ #( #notify_tables ),* ];
+            );
+            write!(code, "{}}}", tables).unwrap();
+        }
+
+        code
+    }
+
+    /// Compiles the conversions between the schema struct of `model` and the entity of its `table`.
+    fn compile_conversions(&self, model: &Model, table: &DatabaseTable) -> String {
+        let mut code = String::new();
+        let schema_ty = schema_ty(model);
+        let db_module_ident = rust_ident(&table.name);
+
+        let mut from_schema_fields = Vec::new();
+        let mut from_db_fields = Vec::new();
+        let mut active_values = Vec::new();
+        let mut has_optional_insert = false;
+        let mut has_computed = false;
+        let mut has_deprecated = false;
+
+        // Schemas only hold the default locale, so converting them leaves the other locales unchanged
+        let locale_columns: Vec<_> = with_locale_columns(table)
+            .columns
+            .into_iter()
+            .filter(|column| {
+                table
+                    .columns
+                    .iter()
+                    .all(|table_column| table_column.name != column.name)
+            })
+            .collect();
+        let unset_locale_columns =
+            (!locale_columns.is_empty()).then(|| quote!(..Default::default()));
+
+        for field in &model.fields {
+            let field_ident = rust_ident(&field.name);
+
+            let ty = strip_ty_option(&field.ty);
+
+            let from_value = into_field_value(field, quote!(val.#field_ident));
+
+            // Deprecated fields are still read, but no longer written
+            if field.deprecated.is_some() {
+                has_deprecated = true;
+                from_schema_fields.push(quote!(#field_ident: #from_value));
+                let ignored =
+                    ignored_deprecated_value(&model.name, field, quote!(self.#field_ident));
+                active_values.push(quote!(#field_ident: #ignored));
+            } else if is_computed(field, table) {
+                has_computed = true;
+                from_schema_fields.push(quote!(#field_ident: #from_value));
+                active_values.push(quote!(#field_ident: ::sea_orm::ActiveValue::unset()));
+            } else if is_optional_insert(field, table) {
+                has_optional_insert = true;
+                from_schema_fields.push(quote!(#field_ident: Some(val.#field_ident.into())));
+                active_values.push(quote!(
+                    #field_ident: match self.#field_ident {
+                        Some(value) => ::sea_orm::Set(value.into()),
+                        None => ::sea_orm::ActiveValue::unset(),
+                    }
+                ));
+            } else if field.newtype.is_none() && is_ty_vec(ty) {
+                from_schema_fields.push(
+                    quote!(#field_ident: val.#field_ident.into_iter().map(|v| v.into()).collect()),
+                );
+                from_db_fields.push(
+                    quote!(#field_ident: val.#field_ident.into_iter().map(|v| v.into()).collect()),
+                );
+                active_values.push(quote!(
+                    #field_ident: ::sea_orm::Set(self.#field_ident.into_iter().map(|v| v.into()).collect())
+                ));
+            } else {
+                let self_value = into_field_value(field, quote!(self.#field_ident));
+                from_schema_fields.push(quote!(#field_ident: #from_value));
+                from_db_fields.push(quote!(#field_ident: #from_value));
+                active_values.push(quote!(#field_ident: ::sea_orm::Set(#self_value)));
+            }
+        }
+        // Fields of disabled features have no column, so the schema struct gets their default
+        for field in &model.omitted_fields {
+            let field_ident = rust_ident(field);
+            from_schema_fields.push(quote!(#field_ident: ::std::default::Default::default()));
+        }
+
+        // A model cannot be built from a schema leaving optional insert, computed or deprecated columns to the database,
+        // so their active models are built directly, leaving `None` values, computed and deprecated columns unset
+        let into_db = if has_optional_insert
+            || has_computed
+            || has_deprecated
+            || !locale_columns.is_empty()
+        {
+            quote!(
+                impl ::sea_orm::entity::IntoActiveModel<crate::#db_module_ident::ActiveModel> for #schema_ty {
+                    fn into_active_model(self) -> crate::#db_module_ident::ActiveModel {
+                        crate::#db_module_ident::ActiveModel {
+                            #( #active_values, )*
+                            #unset_locale_columns
+                        }
+                    }
+                }
+            )
+        } else {
+            quote!(
+                impl ::std::convert::From<#schema_ty> for crate::#db_module_ident::Model {
+                    #[allow(unused_variables)]
+                    fn from(val: #schema_ty) -> Self {
+                        Self {
+                            #( #from_db_fields, )*
+                        }
+                    }
+                }
+
+                impl ::sea_orm::entity::IntoActiveModel<crate::#db_module_ident::ActiveModel> for #schema_ty {
+                    fn into_active_model(self) -> crate::#db_module_ident::ActiveModel {
+                        crate::#db_module_ident::Model::from(self).into()
+                    }
+                }
+            )
+        };
+
+        let expanded = quote!(
+            impl ::std::convert::From<crate::#db_module_ident::Model> for #schema_ty {
+                #[allow(unused_variables)]
+                fn from(val: crate::#db_module_ident::Model) -> Self {
+                    Self {
+                        #( #from_schema_fields, )*
+                    }
+                }
+            }
+
+            #into_db
+        );
+
+        code.push_str(&self.table_annotation(table, None, CommentStyle::Slashes));
+        write!(code, "{}", expanded).unwrap();
+
+        code
+    }
+
+    /// Compiles the queries of the entity of `table`.
+    fn compile_table_queries(&self, model: &Model, table: &DatabaseTable) -> String {
+        let mut code = String::new();
+        code.push_str(&self.table_annotation(table, None, CommentStyle::Slashes));
+        write!(code, "{}", self.compile_page_query(model, table)).unwrap();
+        if !table.projections.is_empty() {
+            write!(code, "{}", self.compile_projection_queries(model, table)).unwrap();
+        }
+        write!(code, "{}", self.compile_relation_loaders(table)).unwrap();
+        write!(code, "{}", self.compile_referenced_queries(table)).unwrap();
+        write!(code, "{}", self.compile_tree_queries(table)).unwrap();
+        if !table.scopes.is_empty() {
+            write!(code, "{}", self.compile_scope_queries(model, table)).unwrap();
+        }
+        if let Some(fulltext) = &table.fulltext {
+            write!(code, "{}", self.compile_search_query(table, fulltext)).unwrap();
+        }
+        if let Some(partition) = &table.partition {
+            write!(code, "{}", self.compile_partition_queries(table, partition)).unwrap();
+        }
+        if table.notify {
+            write!(code, "{}", self.compile_notify_queries(model, table)).unwrap();
+        }
+        if let Some(temporal) = table.temporal {
+            write!(
+                code,
+                "{}",
+                self.compile_temporal_queries(model, table, temporal)
+            )
+            .unwrap();
+        }
+        if table.versioned {
+            write!(code, "{}", self.compile_versioned_update(model, table)).unwrap();
+        }
+        if table.events {
+            write!(code, "{}", self.compile_record_event(model, table)).unwrap();
+        }
+        if table.idempotent_create {
+            write!(code, "{}", self.compile_insert_idempotent(model, table)).unwrap();
+        }
+        write!(code, "{}", self.compile_bulk_queries(model, table)).unwrap();
+        write!(code, "{}", self.compile_metered_queries(model, table)).unwrap();
+        if !table.readonly {
+            write!(code, "{}", self.compile_batch_queries(model, table)).unwrap();
+            write!(code, "{}", self.compile_apply_patch(model, table)).unwrap();
+            if let Some(fake_rows) = self.compile_fake_rows(table) {
+                write!(code, "{}", fake_rows).unwrap();
+            }
+            if table
+                .columns
+                .iter()
+                .any(|column| !column.normalize.is_empty())
+            {
+                write!(code, "{}", self.compile_normalize(table)).unwrap();
+                write!(code, "{}", self.compile_unique_slug_inserts(table)).unwrap();
+            }
+        }
+        if !table.localized_columns().is_empty() {
+            write!(code, "{}", self.compile_localized_queries(model, table)).unwrap();
+        }
+
+        code
+    }
+
+    /// Compiles the conversion of the schema struct of `model` into an active model of its sub `table`.
+    fn compile_sub_table_conversions(&self, model: &Model, table: &DatabaseTable) -> String {
+        let mut code = String::new();
+        let schema_ty = schema_ty(model);
+        let db_module_ident = rust_ident(&table.name);
+
+        let active_values = model.fields.iter().filter(|field| !is_computed(field, table)).map(|field| {
+            let field_ident = rust_ident(&field.name);
+            if let Some((parent, deprecation)) = self.column_deprecation(table, &field.name) {
+                let field = RustField {
+                    deprecated: Some(deprecation.clone()),
+                    ..field.clone()
+                };
+                let ignored = ignored_deprecated_value(&parent.name, &field, quote!(self.#field_ident));
+                return quote!(#field_ident: #ignored);
+            }
+
+            let value = newtype_inner_value(field, quote!(self.#field_ident));
+            let self_field = if is_ty_option(&field.ty) {
+                let db_field = table.columns.iter().find(|column| column.name == field.name).unwrap();
+                match &db_field.default {
+                    Some(DatabaseDefault::Bool(b)) => quote!(#value.unwrap_or(#b)),
+                    Some(DatabaseDefault::Float(f)) => {
+                        let f = Literal::i64_unsuffixed(*f);
+                        quote!(#value.unwrap_or(#f))
+                    },
+                    Some(DatabaseDefault::Int(i)) => {
+                        let i = Literal::u64_unsuffixed(*i);
+                        quote!(#value.unwrap_or(#i))
+                    },
+                    Some(DatabaseDefault::String(s)) => quote!(#value.unwrap_or_else(|| #s.to_string())),
+                    // Not null columns without a literal default are filled by the database
+                    _ if !db_field.nullable => {
+                        return quote!(
+                            #field_ident: match self.#field_ident {
+                                Some(value) => ::sea_orm::Set(value.into()),
+                                None => ::sea_orm::ActiveValue::unset(),
+                            }
+                        );
+                    }
+                    _ => value,
+                }
+            } else {
+                value
+            };
+
+            quote!(
+                #field_ident: ::sea_orm::entity::IntoActiveValue::into_active_value(#self_field).into()
+            )
+        });
+
+        let expanded = quote!(
+            impl ::sea_orm::entity::IntoActiveModel<crate::#db_module_ident::ActiveModel> for #schema_ty {
+                fn into_active_model(self) -> crate::#db_module_ident::ActiveModel {
+                    crate::#db_module_ident::ActiveModel {
+                        #( #active_values, )*
+                        ..Default::default()
+                    }
+                }
+            }
+        );
+
+        code.push_str(&self.table_annotation(table, None, CommentStyle::Slashes));
+        write!(code, "{}", expanded).unwrap();
+
+        code
+    }
+
+    /// Compiles a full-text search query of a table, ordered by rank.
+    fn compile_search_query(
+        &self,
+        table: &DatabaseTable,
+        fulltext: &DatabaseFulltext,
+    ) -> TokenStream {
+        let db_module_ident = rust_ident(&table.name);
+        let query = format!("plainto_tsquery('{}'::regconfig, ?)", fulltext.language);
+        let filter = format!("{} @@ {}", fulltext.vector_expression(), query);
+        let rank = format!("ts_rank({}, {})", fulltext.vector_expression(), query);
+        let doc = format!(
+            " Returns a query of rows whose `{}` match `query`, most relevant first.",
+            fulltext.columns.join("`, `")
+        );
+
+        quote!(
+            impl crate::#db_module_ident::Entity {
+                #[doc = #doc]
+                pub fn search(query: &str) -> ::sea_orm::Select<Self> {
+                    use ::sea_orm::{EntityTrait, QueryFilter, QueryTrait};
+
+                    let mut select = Self::find().filter(
+                        ::sea_orm::sea_query::Expr::cust_with_values(#filter, vec![query]),
+                    );
+                    QueryTrait::query(&mut select).order_by_expr(
+                        ::sea_orm::sea_query::Expr::cust_with_values(#rank, vec![query]),
+                        ::sea_orm::Order::Desc,
+                    );
+
+                    select
+                }
+            }
+        )
+    }
+
+    /// Compiles a query of the rows of each scope of a table, along with its condition.
+    ///
+    /// Scopes are checked by [`check_scopes`] before compiling, so scopes whose condition does not parse are left out.
+    fn compile_scope_queries(&self, model: &Model, table: &DatabaseTable) -> TokenStream {
+        let db_module_ident = rust_ident(&table.name);
+        let queries = table.scopes.iter().filter_map(|scope| {
+            let condition =
+                compile_scope_condition(&db_module_ident, &parse_scope(table, scope).ok()?);
+            let ident = rust_ident(&scope.name);
+            let condition_ident = format_ident!("{}_condition", scope.name);
+            let param_idents: Vec<_> = scope
+                .params
+                .iter()
+                .map(|param| rust_ident(&param.name))
+                .collect();
+            let param_tys: Vec<_> = scope
+                .params
+                .iter()
+                .map(|param| scope_param_ty(&param.ty))
+                .collect();
+            let doc = format!(
+                " Returns a query of the `{}` rows in scope `{}`, where `{}`.",
+                model.name, scope.name, scope.condition
+            );
+            let condition_doc = format!(
+                " Returns the condition of the rows of [`{}`](Self::{}).",
+                scope.name, scope.name
+            );
+
+            Some(quote!(
+                #[doc = #doc]
+                pub fn #ident(#( #param_idents: #param_tys ),*) -> ::sea_orm::Select<Self> {
+                    use ::sea_orm::{EntityTrait, QueryFilter};
+
+                    Self::find().filter(Self::#condition_ident(#( #param_idents ),*))
+                }
+
+                #[doc = #condition_doc]
+                pub fn #condition_ident(#( #param_idents: #param_tys ),*) -> ::sea_orm::Condition {
+                    #( let #param_idents = ::sea_orm::sea_query::Value::from(#param_idents); )*
+
+                    #condition
+                }
+            ))
+        });
+
+        quote!(
+            impl crate::#db_module_ident::Entity {
+                #( #queries )*
+            }
+        )
+    }
+
+    /// Compiles the fetching of the version of the row `id` of `table` before changing it and its recording once changed,
+    /// which are empty unless the table keeps its history with [`Temporal::Portable`].
+    fn compile_record_previous(
+        table: &DatabaseTable,
+        operation: TokenStream,
+    ) -> (TokenStream, TokenStream) {
+        if table.temporal != Some(Temporal::Portable) {
+            return (TokenStream::new(), TokenStream::new());
+        }
+
+        (
+            quote!(let previous = Self::find_by_id(id).one(db).await?;),
+            quote!(
+                if let Some(previous) = &previous {
+                    Self::record_history(db, crate::temporal::Operation::#operation, previous).await?;
+                }
+            ),
+        )
+    }
+
+    /// Compiles an optimistically locked update of a versioned table.
+    fn compile_versioned_update(&self, model: &Model, table: &DatabaseTable) -> TokenStream {
+        let db_module_ident = rust_ident(&table.name);
+        let doc = format!(
+            " Updates the `{}` row of `active_model` if its `version` is unchanged, incrementing it.",
+            model.name
+        );
+        let record_event = table.events.then(
+            || quote!(Self::record_event(db, crate::outbox::EventKind::Updated, &model).await?;),
+        );
+        let (fetch_previous, record_previous) =
+            Self::compile_record_previous(table, quote!(Update));
+
+        quote!(
+            impl crate::#db_module_ident::Entity {
+                #[doc = #doc]
+                ///
+                /// Returns a [`StaleVersionError`](crate::version::StaleVersionError) when the row was updated
+                /// or deleted since the version of `active_model` was read.
+                pub async fn update_versioned<'a, C>(
+                    db: &'a C,
+                    mut active_model: crate::#db_module_ident::ActiveModel,
+                ) -> Result<crate::#db_module_ident::Model, crate::version::VersionedUpdateError>
+                where
+                    C: ::sea_orm::ConnectionTrait<'a>,
+                {
+                    use ::sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+
+                    let id = active_model.id.take().ok_or_else(|| {
+                        ::sea_orm::DbErr::Custom("versioned updates require an id".to_string())
+                    })?;
+                    let version = active_model.version.take().ok_or_else(|| {
+                        ::sea_orm::DbErr::Custom("versioned updates require a version".to_string())
+                    })?;
+                    active_model.version = ::sea_orm::Set(version + 1);
+                    #fetch_previous
+
+                    let result = Self::update_many()
+                        .set(active_model)
+                        .filter(crate::#db_module_ident::Column::Id.eq(id))
+                        .filter(crate::#db_module_ident::Column::Version.eq(version))
+                        .exec(db)
+                        .await?;
+                    if result.rows_affected == 0 {
+                        return Err(crate::version::StaleVersionError { id, version }.into());
+                    }
+                    #record_previous
+
+                    let model = Self::find_by_id(id)
+                        .one(db)
+                        .await?
+                        .ok_or_else(|| ::sea_orm::DbErr::RecordNotFound(id.to_string()))?;
+                    #record_event
+
+                    Ok(model)
+                }
+            }
+        )
+    }
+
+    /// Compiles the recording of changes of a table with `#[awto(events)]` in the outbox.
+    fn compile_record_event(&self, model: &Model, table: &DatabaseTable) -> TokenStream {
+        let schema_ty = schema_ty(model);
+        let db_module_ident = rust_ident(&table.name);
+        let aggregate_type = &table.name;
+        let doc = format!(
+            " Records `kind` of the `{}` row `model` in the outbox, to be called in the transaction changing it.",
+            model.name
+        );
+
+        quote!(
+            impl crate::#db_module_ident::Entity {
+                #[doc = #doc]
+                pub async fn record_event<'a, C>(
+                    db: &'a C,
+                    kind: crate::outbox::EventKind,
+                    model: &crate::#db_module_ident::Model,
+                ) -> Result<(), ::sea_orm::DbErr>
+                where
+                    C: ::sea_orm::ConnectionTrait<'a>,
+                {
+                    let payload = #schema_ty::from(model.clone());
+                    crate::outbox::record(db, #aggregate_type, kind, model.id, &payload).await
+                }
+            }
+        )
+    }
+
+    /// Compiles the insert of a table with `#[awto(idempotent_create)]`, which replays the row created by the first
+    /// request with an idempotency key to its retries.
+    fn compile_insert_idempotent(&self, model: &Model, table: &DatabaseTable) -> TokenStream {
+        let db_module_ident = rust_ident(&table.name);
+        let scope = &table.name;
+        let key_field = TableKey::of(table).field;
+        let doc = format!(
+            " Inserts the `{}` row `active_model` created with the idempotency `key`, unless a row was already created with it",
+            model.name
+        );
+        // Keys of rows are recorded as text, `String` keys as they are
+        let row_key = (!table.has_string_key()).then(|| {
+            quote!(let row_key = ::uuid::Uuid::parse_str(&row_key).map_err(|_| crate::idempotency_keys::IdempotencyError::Conflict)?;)
+        });
+        let record_event = table.events.then(
+            || quote!(Self::record_event(db, crate::outbox::EventKind::Created, model).await?;),
+        );
+
+        quote!(
+            impl crate::#db_module_ident::Entity {
+                #[doc = #doc]
+                /// in the last `ttl`, which is returned instead, to be called in a transaction.
+                ///
+                /// A key used by a request with another `request_hash` is a
+                /// [`Conflict`](crate::idempotency_keys::IdempotencyError::Conflict).
+                /// Returns `None` when the row created with the key was deleted since.
+                pub async fn insert_idempotent<'a, C>(
+                    db: &'a C,
+                    key: &str,
+                    request_hash: &str,
+                    ttl: ::std::time::Duration,
+                    active_model: crate::#db_module_ident::ActiveModel,
+                ) -> Result<Option<crate::#db_module_ident::Model>, crate::idempotency_keys::IdempotencyError>
+                where
+                    C: ::sea_orm::ConnectionTrait<'a>,
+                {
+                    use ::sea_orm::{ActiveModelTrait, EntityTrait};
+
+                    if let Some(row_key) = crate::idempotency_keys::claim(db, #scope, key, request_hash, ttl).await? {
+                        #row_key
+                        return Ok(Self::find_by_id(row_key).one(db).await?);
+                    }
+
+                    let inserted = active_model.insert(db).await?;
+                    let model = Self::find_by_id(inserted.#key_field.unwrap()).one(db).await?;
+                    if let Some(model) = &model {
+                        #record_event
+                        crate::idempotency_keys::complete(db, key, &model.#key_field.to_string()).await?;
+                    }
+
+                    Ok(model)
+                }
+            }
+        )
+    }
+
+    /// Compiles the queries of the localized columns of a table, resolving values with `database::locale::fallback_chain`.
+    fn compile_localized_queries(&self, model: &Model, table: &DatabaseTable) -> TokenStream {
+        let db_module_ident = rust_ident(&table.name);
+        let columns = table.localized_columns();
+        let translations = translations_table(table);
+
+        let localized_fields = columns.iter().map(|column| {
+            let name = &column.name;
+            let locales = &column.locales;
+            quote!((#name, &[#( #locales ),*]))
+        });
+
+        // Looks up the value of a locale other than the default locale of `column` in `model`
+        let translated_value = |column: &DatabaseColumn| match &translations {
+            Some(translations) => {
+                let translations_module_ident = rust_ident(&translations.name);
+                let row_field_ident = rust_ident(&translations.columns[1].name);
+                let name = &column.name;
+                quote!(
+                    translations
+                        .iter()
+                        .find(|translation: &&crate::#translations_module_ident::Model| {
+                            translation.#row_field_ident == model.id
+                                && translation.field == #name
+                                && translation.locale == locale
+                        })
+                        .map(|translation| translation.value.clone())
+                )
+            }
+            None => {
+                let arms = column.locales.iter().skip(1).map(|locale| {
+                    let locale_field_ident = rust_ident(&column.locale_column_name(locale));
+                    quote!(#locale => model.#locale_field_ident.clone(),)
+                });
+                quote!(
+                    match locale {
+                        #( #arms )*
+                        _ => None,
+                    }
+                )
+            }
+        };
+
+        let localize_columns = columns.iter().map(|column| {
+            let field_ident = rust_ident(&column.name);
+            let locales = &column.locales;
+            let default_locale = &column.locales[0];
+            let translated_value = translated_value(column);
+            let (default_value, assign) = if column.nullable {
+                (quote!(model.#field_ident.clone()), quote!(Some(value)))
+            } else {
+                (quote!(Some(model.#field_ident.clone())), quote!(value))
+            };
+
+            quote!(
+                let value = crate::locale::fallback_chain(locale, &[#( #locales ),*])
+                    .into_iter()
+                    .find_map(|locale| {
+                        if locale == #default_locale {
+                            #default_value
+                        } else {
+                            #translated_value
+                        }
+                    });
+                if let Some(value) = value {
+                    model.#field_ident = #assign;
+                }
+            )
+        });
+
+        let fetch_translations = translations.as_ref().map(|translations| {
+            let translations_module_ident = rust_ident(&translations.name);
+            let row_column_ident = column_variant_ident(&translations.columns[1].name);
+            quote!(
+                let translations = crate::#translations_module_ident::Entity::find()
+                    .filter(
+                        crate::#translations_module_ident::Column::#row_column_ident
+                            .is_in(models.iter().map(|model| model.id)),
+                    )
+                    .all(db)
+                    .await?;
+            )
+        });
+
+        let localize_doc = format!(
+            " Replaces the localized fields of the `{}` rows `models` with their values in the best match of `locale`.",
+            model.name
+        );
+        let find_doc = format!(
+            " Finds the `{}` row with the given `id`, with its localized fields in the best match of `locale`.",
+            model.name
+        );
+
+        let set_translation = (!table.readonly).then(|| self.compile_set_translation(model, table));
+
+        quote!(
+            impl crate::#db_module_ident::Entity {
+                /// Localized fields along with their locales, the first of which is the default locale.
+                pub const LOCALIZED_FIELDS: &'static [(&'static str, &'static [&'static str])] = &[
+                    #( #localized_fields, )*
+                ];
+
+                #[doc = #localize_doc]
+                ///
+                /// `locale` is a single locale or the value of an `Accept-Language` header,
+                /// falling back to the default locale of a field when it has no value in the requested locales.
+                #[allow(unused_variables)]
+                pub async fn localize<'a, C>(
+                    db: &'a C,
+                    mut models: Vec<crate::#db_module_ident::Model>,
+                    locale: &str,
+                ) -> Result<Vec<crate::#db_module_ident::Model>, ::sea_orm::DbErr>
+                where
+                    C: ::sea_orm::ConnectionTrait<'a>,
+                {
+                    #[allow(unused_imports)]
+                    use ::sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+
+                    if models.is_empty() {
+                        return Ok(models);
+                    }
+
+                    #fetch_translations
+                    for model in &mut models {
+                        #( { #localize_columns } )*
+                    }
+
+                    Ok(models)
+                }
+
+                #[doc = #find_doc]
+                pub async fn find_localized<'a, C>(
+                    db: &'a C,
+                    id: ::uuid::Uuid,
+                    locale: &str,
+                ) -> Result<Option<crate::#db_module_ident::Model>, ::sea_orm::DbErr>
+                where
+                    C: ::sea_orm::ConnectionTrait<'a>,
+                {
+                    use ::sea_orm::EntityTrait;
+
+                    match Self::find_by_id(id).one(db).await? {
+                        Some(model) => Ok(Self::localize(db, vec![model], locale).await?.pop()),
+                        None => Ok(None),
+                    }
+                }
+
+                #set_translation
+            }
+        )
+    }
+
+    /// Compiles setting the value of a localized field of a table in a locale.
+    fn compile_set_translation(&self, model: &Model, table: &DatabaseTable) -> TokenStream {
+        let db_module_ident = rust_ident(&table.name);
+        let columns = table.localized_columns();
+        let translations = translations_table(table);
+        let model_name = &model.name;
+
+        // Locales are matched case-insensitively, and stored as they are declared
+        let mut locales: Vec<&String> = columns.iter().flat_map(|column| &column.locales).collect();
+        locales.sort();
+        locales.dedup();
+        let locale_arms = locales.iter().map(|locale| {
+            let lowercase = locale.to_lowercase();
+            quote!(#lowercase => #locale,)
+        });
+
+        let mut column_arms = Vec::new();
+        for column in &columns {
+            let name = &column.name;
+            let default_locale = &column.locales[0];
+            let column_ident = column_variant_ident(&column.name);
+            if !column.nullable {
+                column_arms.push(quote!(
+                    (#name, #default_locale) if value.is_none() => {
+                        return Err(::sea_orm::DbErr::Custom(format!(
+                            "the value of `{}.{}` in the default locale `{}` cannot be removed",
+                            #model_name, field, locale
+                        )));
+                    }
+                ));
+            }
+            column_arms.push(quote!((#name, #default_locale) => Some(crate::#db_module_ident::Column::#column_ident),));
+
+            for locale in column.locales.iter().skip(1) {
+                column_arms.push(match translations {
+                    Some(_) => quote!((#name, #locale) => None,),
+                    None => {
+                        let locale_column_ident =
+                            column_variant_ident(&column.locale_column_name(locale));
+                        quote!((#name, #locale) => Some(crate::#db_module_ident::Column::#locale_column_ident),)
+                    }
+                });
+            }
+        }
+
+        let set_translated_value = match &translations {
+            Some(translations) => {
+                let upsert_sql = format!(
+                    "INSERT INTO {table} ({column}, locale, field, value) VALUES ($1, $2, $3, $4) \
+                    ON CONFLICT ({column}, locale, field) DO UPDATE SET value = EXCLUDED.value",
+                    table = quote_ident(&translations.name),
+                    column = quote_ident(&translations.columns[1].name),
+                );
+                let delete_sql = format!(
+                    "DELETE FROM {table} WHERE {column} = $1 AND locale = $2 AND field = $3",
+                    table = quote_ident(&translations.name),
+                    column = quote_ident(&translations.columns[1].name),
+                );
+                quote!(
+                    None => {
+                        let backend = db.get_database_backend();
+                        let statement = match value {
+                            Some(value) => ::sea_orm::Statement::from_sql_and_values(
+                                backend,
+                                #upsert_sql,
+                                vec![id.into(), locale.into(), field.into(), value.into()],
+                            ),
+                            None => ::sea_orm::Statement::from_sql_and_values(
+                                backend,
+                                #delete_sql,
+                                vec![id.into(), locale.into(), field.into()],
+                            ),
+                        };
+                        db.execute(statement).await?;
+                    }
+                )
+            }
+            None => quote!(None => unreachable!("locales are stored in columns"),),
+        };
+
+        let record_event = table.events.then(|| {
+            quote!(if let Some(model) = Self::find_by_id(id).one(db).await? {
+                Self::record_event(db, crate::outbox::EventKind::Updated, &model).await?;
+            })
+        });
+        // Values of the translations table are not part of the versions of rows
+        let (fetch_previous, record_previous) =
+            Self::compile_record_previous(table, quote!(Update));
+
+        let doc = format!(
+            " Sets the value of the localized `field` of the `{}` row `id` in `locale`, or removes it when `value` is `None`.",
+            model.name
+        );
+
+        quote!(
+            #[doc = #doc]
+            ///
+            /// Values in the default locale of a field are stored in the field itself.
+            /// Setting a field which is not localized in `locale` returns [`DbErr::Custom`](::sea_orm::DbErr::Custom).
+            pub async fn set_translation<'a, C>(
+                db: &'a C,
+                id: ::uuid::Uuid,
+                locale: &str,
+                field: &str,
+                value: Option<String>,
+            ) -> Result<(), ::sea_orm::DbErr>
+            where
+                C: ::sea_orm::ConnectionTrait<'a>,
+            {
+                use ::sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+
+                let not_localized = || {
+                    ::sea_orm::DbErr::Custom(format!(
+                        "`{}.{}` is not localized in `{}`",
+                        #model_name, field, locale
+                    ))
+                };
+                let locale = match locale.to_lowercase().as_str() {
+                    #( #locale_arms )*
+                    _ => return Err(not_localized()),
+                };
+                let column = match (field, locale) {
+                    #( #column_arms )*
+                    _ => return Err(not_localized()),
+                };
+
+                match column {
+                    Some(column) => {
+                        #fetch_previous
+                        Self::update_many()
+                            .col_expr(column, ::sea_orm::sea_query::Expr::value(value))
+                            .filter(crate::#db_module_ident::Column::Id.eq(id))
+                            .exec(db)
+                            .await?;
+                        #record_previous
+                    }
+                    #set_translated_value
+                }
+                #record_event
+
+                Ok(())
+            }
+        )
+    }
+
+    /// Compiles the CSV and JSON lines imports and exports of a table, behind the `bulk` feature.
+    ///
+    /// Imports read the fields of the first sub-table model of the table, or of its model, parsed into the types of
+    /// their columns, and are left out of read-only tables and tables with columns of types which cannot be parsed.
+    /// Fields of columns with a default or of `Option` fields are optional, leaving missing values to the database.
+    fn compile_bulk_queries(&self, model: &Model, table: &DatabaseTable) -> TokenStream {
+        let schema_ty = schema_ty(model);
+        let db_module_ident = rust_ident(&table.name);
+        let key_column = TableKey::of(table).column;
+        let export_fields = model
+            .fields
+            .iter()
+            .filter(|field| field.sensitivity != Sensitivity::Secret)
+            .map(|field| field.name.as_str());
+        let sensitive_fields = model
+            .fields
+            .iter()
+            .filter(|field| field.sensitive)
+            .map(|field| field.name.as_str());
+        let export_doc = format!(
+            " Writes the `{}` rows matching the condition of `options` as a CSV file, with a header of the exported fields,",
+            model.name
+        );
+        let export_jsonl_doc = format!(
+            " Writes the `{}` rows matching the condition of `options` as a JSON lines file, with an object per row,",
+            model.name
+        );
+
+        let import = (!table.readonly)
+            .then(|| self.compile_bulk_import(model, table))
+            .flatten();
+
+        quote!(
+            #[cfg(feature = "bulk")]
+            impl crate::#db_module_ident::Entity {
+                /// Fields written by exports, in the order of the columns of exported CSV files, which leave secret fields out.
+                pub const EXPORT_FIELDS: &'static [&'static str] = &[ #( #export_fields ),* ];
+                /// Fields whose values are exported as [`crate::bulk::MASK`] unless they are shown.
+                pub const SENSITIVE_FIELDS: &'static [&'static str] = &[ #( #sensitive_fields ),* ];
+
+                #[doc = #export_doc]
+                /// returning the number of exported rows.
+                pub async fn export_csv<'a, C, W>(
+                    db: &'a C,
+                    writer: &mut W,
+                    options: &crate::bulk::ExportOptions,
+                ) -> Result<u64, crate::bulk::BulkError>
+                where
+                    C: ::sea_orm::ConnectionTrait<'a>,
+                    W: ::std::io::Write,
+                {
+                    let fields = crate::bulk::export_fields(Self::EXPORT_FIELDS, options)?;
+                    crate::bulk::write_csv_line(writer, fields.iter().map(String::as_str))?;
+                    Self::export_rows(db, options, &fields, |row| crate::bulk::write_csv_row(writer, &fields, &row)).await
+                }
+
+                #[doc = #export_jsonl_doc]
+                /// returning the number of exported rows.
+                pub async fn export_jsonl<'a, C, W>(
+                    db: &'a C,
+                    writer: &mut W,
+                    options: &crate::bulk::ExportOptions,
+                ) -> Result<u64, crate::bulk::BulkError>
+                where
+                    C: ::sea_orm::ConnectionTrait<'a>,
+                    W: ::std::io::Write,
+                {
+                    let fields = crate::bulk::export_fields(Self::EXPORT_FIELDS, options)?;
+                    Self::export_rows(db, options, &fields, |row| {
+                        ::serde_json::to_writer(&mut *writer, &row)?;
+                        writeln!(writer)
+                    })
+                    .await
+                }
+
+                /// Passes the exported rows to `write`, reading them in pages of `chunk_size` rows ordered by their primary key.
+                async fn export_rows<'a, C, F>(
+                    db: &'a C,
+                    options: &crate::bulk::ExportOptions,
+                    fields: &[String],
+                    mut write: F,
+                ) -> Result<u64, crate::bulk::BulkError>
+                where
+                    C: ::sea_orm::ConnectionTrait<'a>,
+                    F: FnMut(::serde_json::Map<String, ::serde_json::Value>) -> ::std::io::Result<()>,
+                {
+                    use ::sea_orm::{EntityTrait, QueryFilter, QueryOrder};
+
+                    let mut pages = Self::find()
+                        .filter(options.condition.clone())
+                        .order_by_asc(crate::#db_module_ident::Column::#key_column)
+                        .paginate(db, options.chunk_size);
+                    let mut exported = 0;
+                    while let Some(models) = pages.fetch_and_next().await? {
+                        for model in models {
+                            let row = #schema_ty::from(model);
+                            write(crate::bulk::export_row(&row, fields, Self::SENSITIVE_FIELDS, options)?)?;
+                            exported += 1;
+                        }
+                    }
+
+                    Ok(exported)
+                }
+
+                #import
+            }
+        )
+    }
+
+    /// Compiles `import_csv` and `import_jsonl` of a table, or `None` if a field cannot be parsed into its column.
+    fn compile_bulk_import(&self, model: &Model, table: &DatabaseTable) -> Option<TokenStream> {
+        let db_module_ident = rust_ident(&table.name);
+        let import_model = self
+            .index
+            .sub_tables
+            .get(&table.name)
+            .map_or(model, |&i| &self.models[i]);
+
+        let mut import_fields = Vec::new();
+        let mut import_field_names = Vec::new();
+        let mut values = Vec::new();
+        let mut active_values = Vec::new();
+        for field in &import_model.fields {
+            let column = match table
+                .columns
+                .iter()
+                .find(|column| column.name == field.name)
+            {
+                Some(column) if column.computed.is_none() => column,
+                _ => continue,
+            };
+            if self.column_deprecation(table, &column.name).is_some() {
+                continue;
+            }
+
+            let ty = map_entity_ty(&field.ty).or_else(|| column_rust_type(&column.ty))?;
+            let field_ident = rust_ident(&field.name);
+            let field_name = &field.name;
+            let max_len = match column.ty {
+                DatabaseType::Text(Some(max_len)) => {
+                    let max_len = Literal::usize_unsuffixed(max_len as usize);
+                    quote!(Some(#max_len))
+                }
+                _ => quote!(None),
+            };
+
+            let optional = column.nullable || column.default.is_some() || is_ty_option(&field.ty);
+            let parse_ty = if optional { quote!(Option<#ty>) } else { ty };
+            values.push(quote!(
+                let #field_ident = crate::bulk::field::<#parse_ty>(row, #field_name, #max_len, &mut errors);
+            ));
+            active_values.push(match (optional, column.nullable) {
+                (false, _) => quote!(#field_ident: ::sea_orm::Set(#field_ident)),
+                // Null values are written to nullable columns without a default, which they would be filled with
+                (true, true) if column.default.is_none() => {
+                    quote!(#field_ident: ::sea_orm::Set(#field_ident))
+                }
+                (true, nullable) => {
+                    let value = if nullable {
+                        quote!(Some(value))
+                    } else {
+                        quote!(value)
+                    };
+                    quote!(
+                        #field_ident: match #field_ident {
+                            Some(value) => ::sea_orm::Set(#value),
+                            None => ::sea_orm::ActiveValue::unset(),
+                        }
+                    )
+                }
+            });
+            import_fields.push(field_ident);
+            import_field_names.push(field_name);
+        }
+
+        let import_doc = format!(
+            " Imports the rows of the CSV file `reader` into the `{}` table, whose header names the fields of",
+            table.name
+        );
+        let import_jsonl_doc = format!(
+            " Imports the rows of the JSON lines file `reader` into the `{}` table, with an object of fields per line",
+            table.name
+        );
+        let table_name = &table.name;
+
+        Some(quote!(
+            /// Fields read by imports, of which fields of columns with a default and `Option` fields are optional.
+            pub const IMPORT_FIELDS: &'static [&'static str] = &[ #( #import_field_names ),* ];
+
+            #[doc = #import_doc]
+            /// [`Self::IMPORT_FIELDS`] or the columns mapped to them in `options`.
+            ///
+            /// Valid rows are inserted in batches of `chunk_size` rows, while invalid rows are reported,
+            /// or fail the import when it is strict.
+            pub async fn import_csv<'a, C, R>(
+                db: &'a C,
+                reader: R,
+                options: &crate::bulk::ImportOptions,
+            ) -> Result<crate::bulk::ImportReport, crate::bulk::BulkError>
+            where
+                C: ::sea_orm::ConnectionTrait<'a>,
+                R: ::std::io::BufRead,
+            {
+                let rows = crate::bulk::csv_rows(reader, Self::IMPORT_FIELDS, options)?;
+                Self::import_rows(db, rows, options).await
+            }
+
+            #[doc = #import_jsonl_doc]
+            /// and inserted like the rows of [`Self::import_csv`].
+            pub async fn import_jsonl<'a, C, R>(
+                db: &'a C,
+                reader: R,
+                options: &crate::bulk::ImportOptions,
+            ) -> Result<crate::bulk::ImportReport, crate::bulk::BulkError>
+            where
+                C: ::sea_orm::ConnectionTrait<'a>,
+                R: ::std::io::BufRead,
+            {
+                let rows = crate::bulk::jsonl_rows(reader, Self::IMPORT_FIELDS, options);
+                Self::import_rows(db, rows, options).await
+            }
+
+            async fn import_rows<'a, C, I>(
+                db: &'a C,
+                rows: I,
+                options: &crate::bulk::ImportOptions,
+            ) -> Result<crate::bulk::ImportReport, crate::bulk::BulkError>
+            where
+                C: ::sea_orm::ConnectionTrait<'a>,
+                I: Iterator<Item = Result<crate::bulk::Row, crate::bulk::BulkError>>,
+            {
+                let mut report = crate::bulk::ImportReport::default();
+                let mut models = Vec::new();
+                for row in rows {
+                    report.rows += 1;
+                    let row = match row {
+                        Ok(row) => row,
+                        Err(crate::bulk::BulkError::Row(error)) if !options.strict => {
+                            report.errors.push(error);
+                            continue;
+                        }
+                        Err(err) => return Err(err),
+                    };
+
+                    match Self::import_row(&row) {
+                        Ok(model) => models.push(model),
+                        Err(mut errors) if options.strict => return Err(crate::bulk::BulkError::Row(errors.remove(0))),
+                        Err(mut errors) => report.errors.append(&mut errors),
+                    }
+
+                    if models.len() >= options.chunk_size {
+                        report.inserted += Self::insert_batch_chunked(db, models.drain(..), options.chunk_size).await?;
+                    }
+                }
+                if !models.is_empty() {
+                    report.inserted += Self::insert_batch_chunked(db, models, options.chunk_size).await?;
+                }
+
+                Ok(report)
+            }
+
+            /// Imports the rows of the CSV file `reader` like [`Self::import_csv`], committing every batch of
+            /// `chunk_size` rows in a transaction of its own and saving the progress into the checkpoint file of
+            /// `checkpointing` every few batches, when a batch fails and once interrupted.
+            ///
+            /// A resumed import reads the file from the byte offset of its checkpoint on, skipping the rows of
+            /// the batches committed after it which conflict with existing rows on a unique column.
+            /// Its report counts the rows read before the checkpoint, but only lists the invalid rows after it,
+            /// and strict imports keep the batches committed before their first invalid row.
+            pub async fn import_csv_checkpointed<C, R>(
+                db: &C,
+                reader: R,
+                options: &crate::bulk::ImportOptions,
+                txn: crate::tx::TxnOptions,
+                checkpointing: &::awto::checkpoint::Checkpointing,
+            ) -> Result<crate::bulk::ImportReport, crate::bulk::BulkError>
+            where
+                C: crate::tx::Transactional,
+                R: ::std::io::BufRead,
+            {
+                let checkpointer = ::awto::checkpoint::Checkpointer::start(Some(checkpointing), #table_name)?;
+                let offset = checkpointer.progress().offset;
+                let rows = crate::bulk::csv_rows_from(reader, Self::IMPORT_FIELDS, options, offset)?;
+                Self::import_rows_checkpointed(db, rows, options, txn, checkpointer).await
+            }
+
+            /// Imports the rows of the JSON lines file `reader` like [`Self::import_jsonl`], checkpointing
+            /// the import like [`Self::import_csv_checkpointed`].
+            pub async fn import_jsonl_checkpointed<C, R>(
+                db: &C,
+                reader: R,
+                options: &crate::bulk::ImportOptions,
+                txn: crate::tx::TxnOptions,
+                checkpointing: &::awto::checkpoint::Checkpointing,
+            ) -> Result<crate::bulk::ImportReport, crate::bulk::BulkError>
+            where
+                C: crate::tx::Transactional,
+                R: ::std::io::BufRead,
+            {
+                let checkpointer = ::awto::checkpoint::Checkpointer::start(Some(checkpointing), #table_name)?;
+                let offset = checkpointer.progress().offset;
+                let rows = crate::bulk::jsonl_rows_from(reader, Self::IMPORT_FIELDS, options, offset);
+                Self::import_rows_checkpointed(db, rows, options, txn, checkpointer).await
+            }
+
+            async fn import_rows_checkpointed<C, I>(
+                db: &C,
+                rows: I,
+                options: &crate::bulk::ImportOptions,
+                txn: crate::tx::TxnOptions,
+                mut checkpointer: ::awto::checkpoint::Checkpointer,
+            ) -> Result<crate::bulk::ImportReport, crate::bulk::BulkError>
+            where
+                C: crate::tx::Transactional,
+                I: Iterator<Item = Result<crate::bulk::Row, crate::bulk::BulkError>>,
+            {
+                let resumed = checkpointer.progress();
+                let mut report = crate::bulk::ImportReport {
+                    rows: resumed.rows as usize,
+                    inserted: resumed.inserted,
+                    errors: Vec::new(),
+                };
+                let mut offset = resumed.offset;
+                let mut models = Vec::new();
+                for row in rows {
+                    report.rows += 1;
+                    let row = match row {
+                        Ok(row) => row,
+                        Err(crate::bulk::BulkError::Row(error)) if !options.strict => {
+                            report.errors.push(error);
+                            continue;
+                        }
+                        Err(err) => {
+                            checkpointer.save()?;
+                            return Err(err);
+                        }
+                    };
+                    offset = row.offset;
+
+                    match Self::import_row(&row) {
+                        Ok(model) => models.push(model),
+                        Err(mut errors) if options.strict => {
+                            checkpointer.save()?;
+                            return Err(crate::bulk::BulkError::Row(errors.remove(0)));
+                        }
+                        Err(mut errors) => report.errors.append(&mut errors),
+                    }
+
+                    if models.len() >= options.chunk_size {
+                        let batch = ::std::mem::take(&mut models);
+                        Self::commit_import_batch(db, batch, options, txn, &mut checkpointer, &mut report, offset).await?;
+                    }
+                }
+                if !models.is_empty() {
+                    Self::commit_import_batch(db, models, options, txn, &mut checkpointer, &mut report, offset).await?;
+                }
+                checkpointer.finish()?;
+
+                Ok(report)
+            }
+
+            /// Inserts a batch of a checkpointed import in a transaction, recording the progress of the import
+            /// up to the byte `offset` of its file once committed.
+            async fn commit_import_batch<C>(
+                db: &C,
+                models: Vec<crate::#db_module_ident::ActiveModel>,
+                options: &crate::bulk::ImportOptions,
+                txn: crate::tx::TxnOptions,
+                checkpointer: &mut ::awto::checkpoint::Checkpointer,
+                report: &mut crate::bulk::ImportReport,
+                offset: u64,
+            ) -> Result<(), crate::bulk::BulkError>
+            where
+                C: crate::tx::Transactional,
+            {
+                let chunk_size = options.chunk_size;
+                let replaying = checkpointer.is_replaying();
+                let committed = db
+                    .run_txn(txn, move |txn| {
+                        Box::pin(async move {
+                            if replaying {
+                                Self::insert_new_batch_chunked(txn, models, chunk_size).await
+                            } else {
+                                Self::insert_batch_chunked(txn, models, chunk_size).await
+                            }
+                        })
+                    })
+                    .await;
+                report.inserted += match committed {
+                    Ok(inserted) => inserted,
+                    Err(err) => {
+                        checkpointer.save()?;
+                        return Err(err.into());
+                    }
+                };
+                checkpointer.commit(::awto::checkpoint::Progress {
+                    rows: report.rows as u64,
+                    inserted: report.inserted,
+                    offset,
+                    ..Default::default()
+                })?;
+
+                Ok(())
+            }
+
+            /// Converts an imported `row` into an active model, or returns the errors of its invalid fields.
+            fn import_row(
+                row: &crate::bulk::Row,
+            ) -> Result<crate::#db_module_ident::ActiveModel, Vec<crate::bulk::RowError>> {
+                let mut errors = Vec::new();
+                #( #values )*
+                match ( #( #import_fields, )* ) {
+                    ( #( Some(#import_fields), )* ) => Ok(crate::#db_module_ident::ActiveModel {
+                        #( #active_values, )*
+                        ..Default::default()
+                    }),
+                    _ => Err(errors),
+                }
+            }
+        ))
+    }
+
+    /// Compiles the queries of a table recording their metrics in `database::metrics`, behind the `metrics` feature.
+    ///
+    /// Read-only tables only get `find_metered` and `list_metered`.
+    fn compile_metered_queries(&self, model: &Model, table: &DatabaseTable) -> TokenStream {
+        let db_module_ident = rust_ident(&table.name);
+        let key = TableKey::of(table);
+        let TableKey {
+            column: key_column,
+            param_ty: key_ty,
+            ..
+        } = &key;
+        let owned_id = key.owned(quote!(id));
+        let model_name = &model.name;
+        let find_doc = format!(
+            " Finds the `{}` row with the given `id`, recording the query.",
+            model.name
+        );
+        let list_doc = format!(
+            " Lists the `{}` rows selected by `select`, recording the query.",
+            model.name
+        );
+
+        let mutations = (!table.readonly).then(|| {
+            let insert_doc = format!(" Inserts `item` as a `{}` row, recording the query.", model.name);
+            let update_doc = format!(" Updates the `{}` row of `item`, recording the query.", model.name);
+            let delete_doc = format!(
+                " Deletes the `{}` row with the given `id`, recording the query and returning the number of deleted rows.",
+                model.name
+            );
+            quote!(
+                #[doc = #insert_doc]
+                pub async fn insert_metered<'a, C, M>(
+                    db: &'a C,
+                    item: M,
+                ) -> Result<crate::#db_module_ident::ActiveModel, ::sea_orm::DbErr>
+                where
+                    C: ::sea_orm::ConnectionTrait<'a>,
+                    M: ::sea_orm::IntoActiveModel<crate::#db_module_ident::ActiveModel>,
+                {
+                    let query = ::sea_orm::ActiveModelTrait::insert(item.into_active_model(), db);
+                    crate::metrics::instrument(Self::METRICS_MODEL, crate::metrics::Operation::Insert, query).await
+                }
+
+                #[doc = #update_doc]
+                pub async fn update_metered<'a, C, M>(
+                    db: &'a C,
+                    item: M,
+                ) -> Result<crate::#db_module_ident::ActiveModel, ::sea_orm::DbErr>
+                where
+                    C: ::sea_orm::ConnectionTrait<'a>,
+                    M: ::sea_orm::IntoActiveModel<crate::#db_module_ident::ActiveModel>,
+                {
+                    let query = ::sea_orm::ActiveModelTrait::update(item.into_active_model(), db);
+                    crate::metrics::instrument(Self::METRICS_MODEL, crate::metrics::Operation::Update, query).await
+                }
+
+                #[doc = #delete_doc]
+                pub async fn delete_metered<'a, C>(db: &'a C, id: #key_ty) -> Result<u64, ::sea_orm::DbErr>
+                where
+                    C: ::sea_orm::ConnectionTrait<'a>,
+                {
+                    use ::sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+
+                    let query = async {
+                        let result = Self::delete_many()
+                            .filter(crate::#db_module_ident::Column::#key_column.eq(id))
+                            .exec(db)
+                            .await?;
+                        Ok(result.rows_affected)
+                    };
+                    crate::metrics::instrument(Self::METRICS_MODEL, crate::metrics::Operation::Delete, query).await
+                }
+            )
+        });
+
+        quote!(
+            #[cfg(feature = "metrics")]
+            impl crate::#db_module_ident::Entity {
+                /// Value of the `model` label of the metrics of the queries.
+                pub const METRICS_MODEL: &'static str = #model_name;
+
+                #[doc = #find_doc]
+                pub async fn find_metered<'a, C>(
+                    db: &'a C,
+                    id: #key_ty,
+                ) -> Result<Option<crate::#db_module_ident::Model>, ::sea_orm::DbErr>
+                where
+                    C: ::sea_orm::ConnectionTrait<'a>,
+                {
+                    use ::sea_orm::EntityTrait;
+
+                    let query = Self::find_by_id(#owned_id).one(db);
+                    crate::metrics::instrument(Self::METRICS_MODEL, crate::metrics::Operation::Find, query).await
+                }
+
+                #[doc = #list_doc]
+                pub async fn list_metered<'a, C>(
+                    db: &'a C,
+                    select: ::sea_orm::Select<Self>,
+                ) -> Result<Vec<crate::#db_module_ident::Model>, ::sea_orm::DbErr>
+                where
+                    C: ::sea_orm::ConnectionTrait<'a>,
+                {
+                    crate::metrics::instrument(Self::METRICS_MODEL, crate::metrics::Operation::List, select.all(db)).await
+                }
+
+                #mutations
+            }
+        )
+    }
+
+    /// Compiles the generation of random rows of a table for load testing, behind the `fake` feature,
+    /// or `None` when the table cannot be generated, see [`is_fake_table`].
+    ///
+    /// Text columns get values of their `#[awto(fake = "...")]` provider, or of the provider named like them,
+    /// and unique columns end with the number of their row. Columns referencing other tables get the values
+    /// of random rows of those tables, whose required ones are filled first when they are empty.
+    fn compile_fake_rows(&self, table: &DatabaseTable) -> Option<TokenStream> {
+        if !is_fake_table(table) {
+            return None;
+        }
+        let db_module_ident = rust_ident(&table.name);
+        let table_name = &table.name;
+
+        let mut reference_idents = Vec::new();
+        let mut reference_values = Vec::new();
+        let mut missing_references = Vec::new();
+        let mut active_values = Vec::new();
+        let mut numbered = false;
+        for column in &table.columns {
+            let column_ident = rust_ident(&column.name);
+            let column_name = &column.name;
+            // Uuid ids are generated too, so the same seed generates the same rows
+            if column.primary_key && column.ty == DatabaseType::Uuid {
+                active_values.push(quote!(#column_ident: ::sea_orm::Set(faker.uuid())));
+                continue;
+            }
+            // `String` keys get numbered values like unique columns
+            if (column.primary_key && !table.has_string_key())
+                || column.default.is_some()
+                || column.computed.is_some()
+                || self.column_deprecation(table, &column.name).is_some()
+            {
+                continue;
+            }
+
+            // Rows of other databases cannot be read, so cross database references get random values
+            if let (Some((references_table, references_column)), false) =
+                (&column.references, column.cross_database)
+            {
+                let ty = column_rust_type(&column.ty)?;
+                let values_ident = format_ident!("{}_references", column.name);
+                reference_values.push(quote!(
+                    crate::fake::reference_values::<_, #ty>(txn, #references_table, #references_column).await?
+                ));
+                let value = if column.nullable {
+                    quote!(if #values_ident.is_empty() || faker.chance(options.null_probability) {
+                        None
+                    } else {
+                        Some(faker.pick(&#values_ident).clone())
+                    })
+                } else {
+                    missing_references.push(quote!(
+                        if #values_ident.is_empty() {
+                            return Err(crate::fake::FakeError::MissingReferences {
+                                table: #table_name,
+                                column: #column_name,
+                                references: #references_table,
+                            });
+                        }
+                    ));
+                    quote!(faker.pick(&#values_ident).clone())
+                };
+                active_values.push(quote!(#column_ident: ::sea_orm::Set(#value)));
+                reference_idents.push(values_ident);
+                continue;
+            }
+
+            let value = match Self::fake_value(column) {
+                Some(value) => value,
+                // Columns of other types are left to be `NULL`, as tables requiring them cannot be generated
+                None => continue,
+            };
+            numbered |= column.unique || column.primary_key;
+            let value = if column.nullable {
+                quote!(if faker.chance(options.null_probability) { None } else { Some(#value) })
+            } else {
+                value
+            };
+            active_values.push(quote!(#column_ident: ::sea_orm::Set(#value)));
+        }
+
+        let row_ident = if numbered { quote!(row) } else { quote!(_) };
+        let parents = self.fake_parents(table);
+        let fill_parents = if parents.is_empty() {
+            quote!()
+        } else {
+            let parent_idents = parents.iter().map(|parent| rust_ident(parent));
+            quote!(
+                let parent_options = crate::fake::FakeOptions {
+                    rows: options.parent_rows,
+                    checkpointing: None,
+                    ..options.clone()
+                };
+                #(
+                    let count = db
+                        .run_txn(options.txn, |txn| Box::pin(crate::fake::row_count(txn, #parents)))
+                        .await?;
+                    if count == 0 {
+                        crate::#parent_idents::Entity::insert_fake_rows(db, &parent_options, &mut progress).await?;
+                    }
+                )*
+            )
+        };
+        let generate_doc = format!(
+            " Inserts `options.rows` random `{}` rows, in batches of `options.batch_size` rows with a transaction each,",
+            table.name
+        );
+
+        Some(quote!(
+            #[cfg(feature = "fake")]
+            impl crate::#db_module_ident::Entity {
+                /// Tables which required columns reference, in the order they are filled when empty.
+                pub const FAKE_PARENTS: &'static [&'static str] = &[ #( #parents ),* ];
+
+                #[doc = #generate_doc]
+                /// returning the number of inserted rows.
+                ///
+                /// The empty tables of [`Self::FAKE_PARENTS`] are filled with `options.parent_rows` rows first.
+                /// `progress` is called with the table, its inserted rows and its rows to insert after every batch.
+                ///
+                /// With `options.checkpointing`, the progress and the state of the faker are saved every few batches,
+                /// when a batch fails and once interrupted, and a resumed generation inserts the same rows
+                /// the interrupted one would have, as long as the tables they reference are unchanged.
+                pub async fn generate_rows<C, P>(
+                    db: &C,
+                    options: &crate::fake::FakeOptions,
+                    mut progress: P,
+                ) -> Result<u64, crate::fake::FakeError>
+                where
+                    C: crate::tx::Transactional,
+                    P: FnMut(&str, u64, u64),
+                {
+                    #fill_parents
+                    Self::insert_fake_rows(db, options, &mut progress).await
+                }
+
+                /// Inserts random rows like [`Self::generate_rows`], without filling the tables they reference.
+                pub async fn insert_fake_rows<C, P>(
+                    db: &C,
+                    options: &crate::fake::FakeOptions,
+                    progress: &mut P,
+                ) -> Result<u64, crate::fake::FakeError>
+                where
+                    C: crate::tx::Transactional,
+                    P: FnMut(&str, u64, u64),
+                {
+                    let mut checkpointer =
+                        ::awto::checkpoint::Checkpointer::start(options.checkpointing.as_ref(), #table_name)?;
+                    let (count, #( #reference_idents, )*) = db
+                        .run_txn(options.txn, |txn| {
+                            Box::pin(async move {
+                                Ok::<_, crate::fake::FakeError>((
+                                    crate::fake::row_count(txn, #table_name).await?,
+                                    #( #reference_values, )*
+                                ))
+                            })
+                        })
+                        .await?;
+                    #( #missing_references )*
+
+                    let (start, mut inserted, mut faker) = match checkpointer.resumed() {
+                        Some(progress) => (progress.start, progress.inserted, crate::fake::Faker::from_state(progress.state)),
+                        None => (count, 0, crate::fake::Faker::new(options.seed, #table_name)),
+                    };
+                    // Batches committed after the last checkpoint are generated again without inserting them,
+                    // so the faker continues where the interrupted generation stopped
+                    if count > start + inserted {
+                        for #row_ident in start + inserted..count {
+                            let _ = crate::#db_module_ident::ActiveModel {
+                                #( #active_values, )*
+                                ..Default::default()
+                            };
+                        }
+                        inserted = count - start;
+                    }
+                    while inserted < options.rows {
+                        let batch = (options.rows - inserted).min(options.batch_size.max(1) as u64);
+                        // Rows are numbered after the existing rows, keeping the values of unique columns unique
+                        let models: Vec<_> = (start + inserted..start + inserted + batch)
+                            .map(|#row_ident| crate::#db_module_ident::ActiveModel {
+                                #( #active_values, )*
+                                ..Default::default()
+                            })
+                            .collect();
+                        let committed = db
+                            .run_txn(options.txn, move |txn| {
+                                Box::pin(Self::insert_batch_chunked(txn, models, crate::batch::DEFAULT_CHUNK_SIZE))
+                            })
+                            .await;
+                        inserted += match committed {
+                            Ok(committed) => committed,
+                            Err(err) => {
+                                checkpointer.save()?;
+                                return Err(err.into());
+                            }
+                        };
+                        progress(#table_name, inserted, options.rows);
+                        checkpointer.commit(::awto::checkpoint::Progress {
+                            rows: inserted,
+                            inserted,
+                            state: faker.state(),
+                            start,
+                            ..Default::default()
+                        })?;
+                    }
+                    checkpointer.finish()?;
+
+                    Ok(inserted)
+                }
+            }
+        ))
+    }
+
+    /// Returns the expression of a random value of `column`, or `None` if its type has no random values.
+    fn fake_value(column: &DatabaseColumn) -> Option<TokenStream> {
+        let unique = (column.unique || column.primary_key).then(|| quote!(row));
+        let value = match &column.ty {
+            DatabaseType::SmallInt => {
+                unique.map_or_else(|| quote!(faker.small_int()), |row| quote!(#row as i16))
+            }
+            DatabaseType::Integer => {
+                unique.map_or_else(|| quote!(faker.integer()), |row| quote!(#row as i32))
+            }
+            DatabaseType::BigInt => {
+                unique.map_or_else(|| quote!(faker.big_int()), |row| quote!(#row as i64))
+            }
+            DatabaseType::Float => quote!(faker.float()),
+            DatabaseType::Double => quote!(faker.double()),
+            DatabaseType::Text(max_len) => {
+                let fake = match column.fake.or_else(|| Fake::of_column(&column.name)) {
+                    Some(fake) => format_ident!("{}", fake.to_string().to_camel_case()),
+                    None if slug_source(column).is_some() => format_ident!("Slug"),
+                    None if column.primary_key => format_ident!("Word"),
+                    None => format_ident!("Sentence"),
+                };
+                let max_len = match max_len {
+                    Some(max_len) => {
+                        let max_len = Literal::usize_unsuffixed(*max_len as usize);
+                        quote!(Some(#max_len))
+                    }
+                    None => quote!(None),
+                };
+                let unique = match unique {
+                    Some(row) => quote!(Some(#row)),
+                    None => quote!(None),
+                };
+                quote!(faker.text(crate::fake::Fake::#fake, #max_len, #unique))
+            }
+            DatabaseType::Binary => quote!(faker.bytes()),
+            DatabaseType::Timestamp => quote!(faker.timestamp()),
+            DatabaseType::Timestamptz => quote!(faker.timestamptz()),
+            DatabaseType::Date => quote!(faker.date()),
+            DatabaseType::Time => quote!(faker.time()),
+            DatabaseType::Bool => quote!(faker.bool()),
+            DatabaseType::Uuid => quote!(faker.uuid()),
+            DatabaseType::Jsonb => quote!(::std::default::Default::default()),
+            DatabaseType::Numeric(_)
+            | DatabaseType::Money
+            | DatabaseType::Timetz
+            | DatabaseType::Hstore => return None,
+        };
+
+        Some(value)
+    }
+
+    /// Returns the tables which the required columns of `table` reference, directly or through other tables,
+    /// parents first, leaving out the tables whose rows cannot be generated.
+    fn fake_parents(&self, table: &DatabaseTable) -> Vec<String> {
+        fn visit(
+            compiler: &DatabaseCompiler,
+            table: &DatabaseTable,
+            visiting: &mut Vec<String>,
+            parents: &mut Vec<String>,
+        ) {
+            visiting.push(table.name.clone());
+            for (references_table, _) in table
+                .columns
+                .iter()
+                .filter(|column| !column.nullable && !column.cross_database)
+                .filter_map(|column| column.references.as_ref())
+            {
+                // Cycles are broken at the table which closes them, whose rows must exist beforehand
+                if visiting.contains(references_table) || parents.contains(references_table) {
+                    continue;
+                }
+                let parent = compiler.index.tables.get(references_table).and_then(|&i| {
+                    compiler.models[i].roles.iter().find_map(|role| match role {
+                        Role::DatabaseTable(parent) if &parent.name == references_table => {
+                            Some(parent)
+                        }
+                        _ => None,
+                    })
+                });
+                if let Some(parent) = parent.filter(|parent| is_fake_table(parent)) {
+                    visit(compiler, parent, visiting, parents);
+                    parents.push(parent.name.clone());
+                }
+            }
+            visiting.pop();
+        }
+
+        let mut parents = Vec::new();
+        visit(self, table, &mut Vec::new(), &mut parents);
+        parents
+    }
+
+    /// Compiles chunked batch inserts and upserts of a table.
+    ///
+    /// Upserts are only compiled for tables with an [upsert target](DatabaseTable::upsert_target).
+    fn compile_batch_queries(&self, model: &Model, table: &DatabaseTable) -> TokenStream {
+        let db_module_ident = rust_ident(&table.name);
+        // Multi-row inserts bypass `ActiveModelBehavior::before_save`, so items are normalized beforehand
+        let normalize = table
+            .columns
+            .iter()
+            .any(|column| !column.normalize.is_empty())
+            .then(|| quote!(.map(crate::#db_module_ident::ActiveModel::normalize)));
+        let insert_doc = format!(
+            " Inserts `items` into the `{}` table with multi-row inserts, returning the number of inserted rows.",
+            table.name
+        );
+
+        let upsert = table.upsert_target().map(|target| {
+            let keep = [
+                table
+                    .primary_key()
+                    .map_or("id", |column| column.name.as_str()),
+                "created_at",
+            ];
+            let version = if table.versioned {
+                quote!(Some("version"))
+            } else {
+                quote!(None)
+            };
+            let upsert_doc = format!(
+                " Inserts `item`, or updates the `{}` row with the same `{}`.",
+                model.name, target
+            );
+            let upsert_batch_doc = format!(
+                " Inserts `items`, updating the `{}` rows with the same `{}` instead.",
+                model.name, target
+            );
+
+            quote!(
+                #[doc = #upsert_doc]
+                pub async fn upsert<'a, C, M>(
+                    db: &'a C,
+                    item: M,
+                ) -> Result<crate::batch::UpsertResult, ::sea_orm::DbErr>
+                where
+                    C: ::sea_orm::ConnectionTrait<'a>,
+                    M: ::sea_orm::IntoActiveModel<crate::#db_module_ident::ActiveModel>,
+                {
+                    Self::upsert_batch(db, ::std::iter::once(item)).await
+                }
+
+                #[doc = #upsert_batch_doc]
+                pub async fn upsert_batch<'a, C, M, I>(
+                    db: &'a C,
+                    items: I,
+                ) -> Result<crate::batch::UpsertResult, ::sea_orm::DbErr>
+                where
+                    C: ::sea_orm::ConnectionTrait<'a>,
+                    M: ::sea_orm::IntoActiveModel<crate::#db_module_ident::ActiveModel>,
+                    I: IntoIterator<Item = M>,
+                {
+                    Self::upsert_batch_chunked(db, items, crate::batch::DEFAULT_CHUNK_SIZE).await
+                }
+
+                /// Upserts `items` with statements of at most `chunk_size` rows.
+                pub async fn upsert_batch_chunked<'a, C, M, I>(
+                    db: &'a C,
+                    items: I,
+                    chunk_size: usize,
+                ) -> Result<crate::batch::UpsertResult, ::sea_orm::DbErr>
+                where
+                    C: ::sea_orm::ConnectionTrait<'a>,
+                    M: ::sea_orm::IntoActiveModel<crate::#db_module_ident::ActiveModel>,
+                    I: IntoIterator<Item = M>,
+                {
+                    let models = items
+                        .into_iter()
+                        .map(::sea_orm::IntoActiveModel::into_active_model)
+                        #normalize
+                        .collect();
+
+                    crate::batch::upsert(
+                        db,
+                        models,
+                        chunk_size,
+                        &crate::batch::Upsert {
+                            target: #target,
+                            keep: &[ #( #keep ),* ],
+                            version: #version,
+                        },
+                    )
+                    .await
+                }
+            )
+        });
+
+        quote!(
+            impl crate::#db_module_ident::Entity {
+                #[doc = #insert_doc]
+                ///
+                /// Items setting different columns, such as optional insert fields left `None`, are inserted by
+                /// separate statements.
+                pub async fn insert_batch<'a, C, M, I>(db: &'a C, items: I) -> Result<u64, ::sea_orm::DbErr>
+                where
+                    C: ::sea_orm::ConnectionTrait<'a>,
+                    M: ::sea_orm::IntoActiveModel<crate::#db_module_ident::ActiveModel>,
+                    I: IntoIterator<Item = M>,
+                {
+                    Self::insert_batch_chunked(db, items, crate::batch::DEFAULT_CHUNK_SIZE).await
+                }
+
+                /// Inserts `items` with statements of at most `chunk_size` rows.
+                ///
+                /// Statements contain fewer rows when needed to stay below the bind parameter limit of the database.
+                pub async fn insert_batch_chunked<'a, C, M, I>(
+                    db: &'a C,
+                    items: I,
+                    chunk_size: usize,
+                ) -> Result<u64, ::sea_orm::DbErr>
+                where
+                    C: ::sea_orm::ConnectionTrait<'a>,
+                    M: ::sea_orm::IntoActiveModel<crate::#db_module_ident::ActiveModel>,
+                    I: IntoIterator<Item = M>,
+                {
+                    let models = items
+                        .into_iter()
+                        .map(::sea_orm::IntoActiveModel::into_active_model)
+                        #normalize
+                        .collect();
+
+                    crate::batch::insert(db, models, chunk_size).await
+                }
+
+                /// Inserts `items` like [`Self::insert_batch_chunked`], skipping the items which conflict with
+                /// existing rows on a unique column, such as the rows of a resumed import inserted before it was
+                /// interrupted.
+                pub async fn insert_new_batch_chunked<'a, C, M, I>(
+                    db: &'a C,
+                    items: I,
+                    chunk_size: usize,
+                ) -> Result<u64, ::sea_orm::DbErr>
+                where
+                    C: ::sea_orm::ConnectionTrait<'a>,
+                    M: ::sea_orm::IntoActiveModel<crate::#db_module_ident::ActiveModel>,
+                    I: IntoIterator<Item = M>,
+                {
+                    let models = items
+                        .into_iter()
+                        .map(::sea_orm::IntoActiveModel::into_active_model)
+                        #normalize
+                        .collect();
+
+                    crate::batch::insert_new(db, models, chunk_size).await
+                }
+
+                #upsert
+            }
+        )
+    }
+
+    /// Compiles `ActiveModel::normalize` of a table with normalized columns, called before active models are saved.
+    ///
+    /// Slugs are derived first, from the values of their source columns as set, when they are left unset or empty.
+    /// The other normalizers are applied in order to set values, leaving unchanged values of updates alone.
+    fn compile_normalize(&self, table: &DatabaseTable) -> TokenStream {
+        let db_module_ident = rust_ident(&table.name);
+        let doc = format!(
+            " Normalizes the set values of the normalized columns of the `{}` table.",
+            table.name
+        );
+
+        let (slugs, others): (Vec<&DatabaseColumn>, Vec<&DatabaseColumn>) = table
+            .columns
+            .iter()
+            .filter(|column| !column.normalize.is_empty())
+            .partition(|column| slug_source(column).is_some());
+
+        let normalized = slugs.into_iter().chain(others).map(|column| {
+            let column_ident = rust_ident(&column.name);
+            let flatten = column.nullable.then(|| quote!(.flatten()));
+            let mut steps = Vec::new();
+
+            let condition = match slug_source(column) {
+                Some(source) => {
+                    let source_ident = rust_ident(source);
+                    let source_flatten = table
+                        .columns
+                        .iter()
+                        .find(|column| column.name == source)
+                        .filter(|column| column.nullable)
+                        .map(|_| quote!(.flatten()));
+                    steps.push(quote!(
+                        let value = value
+                            .filter(|value| !value.trim().is_empty())
+                            .or_else(|| self.#source_ident.clone().take() #source_flatten)
+                            .map(|value| crate::normalization::slugify(&value))
+                            .filter(|slug| !slug.is_empty());
+                    ));
+                    quote!(!self.#column_ident.is_unchanged())
+                }
+                None => quote!(self.#column_ident.is_set()),
+            };
+            for normalizer in &column.normalize {
+                match normalizer {
+                    Normalizer::Lowercase => {
+                        steps.push(quote!(let value = value.map(|value| value.to_lowercase());))
+                    }
+                    Normalizer::Trim => {
+                        steps.push(quote!(let value = value.map(|value| value.trim().to_string());))
+                    }
+                    Normalizer::SlugifyFrom(_) => {}
+                    Normalizer::Function(path) => {
+                        let path = schema_function(path);
+                        steps.push(quote!(let value = value.map(|value| #path(&value));));
+                    }
+                }
+            }
+            let set_value = if column.nullable {
+                quote!(Some(value))
+            } else {
+                quote!(value)
+            };
+
+            quote!(
+                if #condition {
+                    let value = self.#column_ident.clone().take() #flatten;
+                    #( #steps )*
+                    if let Some(value) = value {
+                        self.#column_ident = ::sea_orm::Set(#set_value);
+                    }
+                }
+            )
+        });
+
+        quote!(
+            impl crate::#db_module_ident::ActiveModel {
+                #[doc = #doc]
+                ///
+                /// Called by `ActiveModelBehavior::before_save`, and by batch inserts and upserts.
+                pub fn normalize(mut self) -> Self {
+                    #( #normalized )*
+                    self
+                }
+            }
+        )
+    }
+
+    /// Compiles an insert of every unique slug column of a table, retrying with suffixed slugs while they are taken.
+    fn compile_unique_slug_inserts(&self, table: &DatabaseTable) -> TokenStream {
+        let db_module_ident = rust_ident(&table.name);
+
+        let inserts = table
+            .columns
+            .iter()
+            .filter(|column| column.unique && slug_source(column).is_some())
+            .map(|column| {
+                let column_ident = rust_ident(&column.name);
+                let insert_ident = format_ident!("insert_unique_{}", column.name);
+                let constraint = derived_name(&[&table.name, &column.name], "key");
+                let flatten = column.nullable.then(|| quote!(.flatten()));
+                let set_value = if column.nullable { quote!(Some(slug)) } else { quote!(slug) };
+                let doc = format!(
+                    " Inserts `item`, suffixing its `{}` with `-2`, `-3` and so on while it is taken by another `{}` row.",
+                    column.name, table.name
+                );
+                let exhausted = format!(
+                    "no unique {} of {} found within {{}} attempts of '{{}}'",
+                    column.name, table.name
+                );
+
+                quote!(
+                    #[doc = #doc]
+                    ///
+                    /// Gives up after [`MAX_SLUG_ATTEMPTS`](crate::normalization::MAX_SLUG_ATTEMPTS) attempts.
+                    /// A failed insert aborts the surrounding transaction on postgres,
+                    /// so this should not be called within a transaction.
+                    pub async fn #insert_ident<'a, C, M>(
+                        db: &'a C,
+                        item: M,
+                    ) -> Result<crate::#db_module_ident::ActiveModel, ::sea_orm::DbErr>
+                    where
+                        C: ::sea_orm::ConnectionTrait<'a>,
+                        M: ::sea_orm::IntoActiveModel<crate::#db_module_ident::ActiveModel>,
+                    {
+                        let model = item.into_active_model().normalize();
+                        let base = model.#column_ident.clone().take() #flatten .unwrap_or_default();
+
+                        for attempt in 1..=crate::normalization::MAX_SLUG_ATTEMPTS {
+                            let slug = crate::normalization::suffixed(&base, attempt);
+                            let mut model = model.clone();
+                            model.#column_ident = ::sea_orm::Set(#set_value);
+                            match ::sea_orm::ActiveModelTrait::insert(model, db).await {
+                                Ok(model) => return Ok(model),
+                                Err(err) if crate::normalization::is_unique_violation(&err, #constraint) => continue,
+                                Err(err) => return Err(err),
+                            }
+                        }
+
+                        Err(::sea_orm::DbErr::Custom(format!(
+                            #exhausted,
+                            crate::normalization::MAX_SLUG_ATTEMPTS,
+                            base
+                        )))
+                    }
+                )
+            })
+            .collect::<Vec<_>>();
+        if inserts.is_empty() {
+            return TokenStream::new();
+        }
+
+        quote!(
+            impl crate::#db_module_ident::Entity {
+                #( #inserts )*
+            }
+        )
+    }
+
+    /// Compiles the `ddl` module, building the statements which create the tables as sea-query statements,
+    /// so the sql of the tables can be checked without connecting to a database.
+    ///
+    /// Columns are declared with the same sql as the tables created by [`compile`](Self::compile),
+    /// and foreign keys are built apart as they are only added once every table exists.
+    fn compile_ddl_module(&self) -> TokenStream {
+        let tables = self.created_tables();
+        let create_tables = tables.iter().map(|table| {
+            let table_name = &table.name;
+            let key_constraint = table.partition.is_some() || table.primary_key_columns().len() > 1;
+            let columns = table.columns.iter().map(|column| {
+                let name = &column.name;
+                let ty = column.ty.to_string();
+                let mut specs = Vec::new();
+                if !column.nullable {
+                    specs.push(quote!(.not_null()));
+                }
+                let extra: Vec<String> = vec![
+                    column.computed.as_ref().map(|computed| format!("GENERATED ALWAYS AS ({}) STORED", computed)),
+                    column.default.as_ref().map(|default| format!("DEFAULT {}", default)),
+                    column.constraint.as_ref().map(|constraint| format!("CHECK ({})", constraint)),
+                ]
+                .into_iter()
+                .flatten()
+                .collect();
+                if !extra.is_empty() {
+                    let extra = extra.join(" ");
+                    specs.push(quote!(.extra(#extra.to_string())));
+                }
+                if column.unique {
+                    specs.push(quote!(.unique_key()));
+                }
+                if column.primary_key && !key_constraint {
+                    specs.push(quote!(.primary_key()));
+                }
+
+                quote!(
+                    .col(
+                        ::sea_orm::sea_query::ColumnDef::new(::sea_orm::sea_query::Alias::new(#name))
+                            .custom(::sea_orm::sea_query::Alias::new(#ty))
+                            #( #specs )*
+                    )
+                )
+            });
+            let primary_key = key_constraint.then(|| {
+                let key_columns = table.primary_key_columns();
+                quote!(
+                    .index(
+                        ::sea_orm::sea_query::Index::create()
+                            .primary()
+                            #( .col(::sea_orm::sea_query::Alias::new(#key_columns)) )*
+                    )
+                )
+            });
+
+            quote!(
+                ::sea_orm::sea_query::Table::create()
+                    .table(::sea_orm::sea_query::Alias::new(#table_name))
+                    .if_not_exists()
+                    #( #columns )*
+                    #primary_key
+                    .to_owned()
+            )
+        });
+
+        let translations: Vec<String> = self
+            .translations_tables()
+            .into_iter()
+            .map(|table| table.name)
+            .collect();
+        let foreign_keys = tables
+            .iter()
+            .filter(|table| !translations.contains(&table.name))
+            .flat_map(|table| table.columns.iter().map(move |column| (table, column)))
+            .filter(|(_, column)| self.has_foreign_key(column))
+            .filter_map(|(table, column)| {
+                let (reference_table, reference_column) = column.references.as_ref()?;
+                let table_name = &table.name;
+                let column_name = &column.name;
+                let constraint = derived_name(&[&table.name, &column.name], "fkey");
+                let on_delete = column
+                    .nullable
+                    .then(|| quote!(.on_delete(::sea_orm::sea_query::ForeignKeyAction::SetNull)));
+
+                Some(quote!(
+                    ::sea_orm::sea_query::ForeignKey::create()
+                        .name(#constraint)
+                        .from(::sea_orm::sea_query::Alias::new(#table_name), ::sea_orm::sea_query::Alias::new(#column_name))
+                        .to(::sea_orm::sea_query::Alias::new(#reference_table), ::sea_orm::sea_query::Alias::new(#reference_column))
+                        #on_delete
+                        .to_owned()
+                ))
+            });
+
+        quote!(
+            /// Statements creating the tables of the database, built without connecting to it.
+            ///
+            /// The sql of the statements is rendered with a query builder, such as
+            /// `statement.to_string(::sea_orm::sea_query::PostgresQueryBuilder)`.
+            pub mod ddl {
+                /// Returns the statements creating the tables, in the order the build script creates them.
+                ///
+                /// Partitioned tables are created with `PARTITION BY RANGE` by the build script, which the statements
+                /// leave out, and foreign keys are created by [`foreign_key_statements`].
+                pub fn create_table_statements() -> Vec<::sea_orm::sea_query::TableCreateStatement>
+                {
+                    vec![ #( #create_tables ),* ]
+                }
+
+                /// Returns the statements adding the foreign keys of the tables, once every table exists.
+                pub fn foreign_key_statements(
+                ) -> Vec<::sea_orm::sea_query::ForeignKeyCreateStatement> {
+                    vec![ #( #foreign_keys ),* ]
+                }
+            }
+        )
+    }
+
+    /// Compiles the `patch` module, containing a patch struct of every writable table.
+    ///
+    /// Every column of a patch is optional, and nullable columns are wrapped in a second `Option`
+    /// so setting a column to null can be told apart from leaving it unchanged.
+    fn compile_patches(&self) -> TokenStream {
+        let patches = self
+            .database_tables()
+            .into_iter()
+            .filter(|(_, table)| !table.readonly)
+            .map(|(model, table)| {
+                let patch_ident = format_ident!("{}Patch", model.name);
+                let db_module_ident = rust_ident(&table.name);
+                let doc = format!(" Partial update of a `{}` row.", model.name);
+
+                let mut fields = Vec::new();
+                let mut active_values = Vec::new();
+                for column in table.patch_columns() {
+                    let field = model.fields.iter().find(|field| field.name == column.name);
+                    let map_ty = field.and_then(|field| map_entity_ty(&field.ty));
+                    let ty = match map_ty.or_else(|| column_rust_type(&column.ty)) {
+                        Some(ty) => ty,
+                        None => continue,
+                    };
+                    let field_ident = rust_ident(&column.name);
+                    // Secret values are accepted by patches, but never serialized back
+                    let skip = if field.is_some_and(|field| field.sensitivity == Sensitivity::Secret) {
+                        quote!(skip_serializing)
+                    } else {
+                        quote!(skip_serializing_if = "Option::is_none")
+                    };
+
+                    fields.push(if column.nullable {
+                        let field_doc = format!(" New value of `{}`, `Some(None)` setting it to `NULL`.", column.name);
+                        quote!(
+                            #[doc = #field_doc]
+                            #[serde(default, deserialize_with = "nullable", #skip)]
+                            pub #field_ident: Option<Option<#ty>>
+                        )
+                    } else {
+                        let field_doc = format!(" New value of `{}`.", column.name);
+                        quote!(
+                            #[doc = #field_doc]
+                            #[serde(default, #skip)]
+                            pub #field_ident: Option<#ty>
+                        )
+                    });
+                    if let Some((_, deprecation)) = self.column_deprecation(table, &column.name) {
+                        let message = format!(
+                            "ignoring deprecated field `{}.{}` of patch{}",
+                            model.name,
+                            column.name,
+                            deprecation_note(Some(deprecation))
+                        );
+                        active_values.push(quote!(
+                            #field_ident: {
+                                if patch.#field_ident.is_some() {
+                                    ::log::warn!(#message);
+                                }
+                                ::sea_orm::ActiveValue::unset()
+                            }
+                        ));
+                        continue;
+                    }
+                    active_values.push(quote!(
+                        #field_ident: patch.#field_ident.map_or_else(::sea_orm::ActiveValue::unset, ::sea_orm::Set)
+                    ));
+                }
+                if table.versioned {
+                    fields.push(quote!(
+                        /// Version of the row the patch was made for.
+                        pub version: i32
+                    ));
+                    active_values.push(quote!(version: ::sea_orm::Set(patch.version)));
+                }
+
+                quote!(
+                    #[doc = #doc]
+                    #[derive(Clone, Debug, Default, PartialEq, ::serde::Serialize, ::serde::Deserialize)]
+                    #[serde(deny_unknown_fields)]
+                    pub struct #patch_ident {
+                        #( #fields, )*
+                    }
+
+                    impl ::std::convert::From<#patch_ident> for crate::#db_module_ident::ActiveModel {
+                        #[allow(unused_variables)]
+                        fn from(patch: #patch_ident) -> Self {
+                            Self {
+                                #( #active_values, )*
+                                ..Default::default()
+                            }
+                        }
+                    }
+                )
+            });
+
+        quote!(
+            /// Partial updates of tables, only changing the columns present in a patch.
+            pub mod patch {
+                /// Deserializes a field of a patch which is present, including `null`, as `Some`.
+                pub fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+                where
+                    D: ::serde::Deserializer<'de>,
+                    T: ::serde::Deserialize<'de>,
+                {
+                    ::serde::Deserialize::deserialize(deserializer).map(Some)
+                }
+
+                #( #patches )*
+            }
+        )
+    }
+
+    /// Compiles a `projection` module with a struct of each projection of the tables, fetched by the list queries
+    /// of its table, or nothing when no table has projections.
+    fn compile_projections(&self) -> TokenStream {
+        let projections: Vec<_> = self
+            .database_tables()
+            .into_iter()
+            .flat_map(|(model, table)| {
+                table.projections.iter().map(move |projection| {
+                    let ident = format_ident!("{}", projection.name);
+                    let list = format!("list_{}", projection_plural(&table.name, &projection.name));
+                    let doc = format!(
+                        " `{}` of a `{}` row, fetched by [`{}`](crate::{}::Entity::{}).",
+                        projection.columns.join("`, `"),
+                        model.name,
+                        list,
+                        rust_ident(&table.name),
+                        list
+                    );
+                    let sensitive = |column: &String| {
+                        model
+                            .fields
+                            .iter()
+                            .any(|field| field.name == *column && field.sensitivity != Sensitivity::Public)
+                    };
+                    let fields = projection.columns.iter().filter_map(|column| {
+                        let ty = projection_field_ty(model, table, column)?;
+                        let field_ident = rust_ident(column);
+                        let field_doc = format!(" Value of `{}`.", column);
+                        let serde_attr = model
+                            .fields
+                            .iter()
+                            .find(|field| field.name == *column)
+                            .map(sensitivity_serde_attr);
+                        Some(quote!(
+                            #[doc = #field_doc]
+                            #serde_attr
+                            pub #field_ident: #ty
+                        ))
+                    });
+
+                    // Internal and secret columns print as `********`, like in the `Debug` of entities
+                    if !projection.columns.iter().any(sensitive) {
+                        return quote!(
+                            #[doc = #doc]
+                            #[derive(Clone, Debug, PartialEq, ::sea_orm::FromQueryResult, ::serde::Serialize, ::serde::Deserialize)]
+                            pub struct #ident {
+                                #( #fields, )*
+                            }
+                        );
+                    }
+                    let name = projection.name.as_str();
+                    let debug_fields = projection
+                        .columns
+                        .iter()
+                        .filter(|column| projection_field_ty(model, table, column).is_some())
+                        .map(|column| {
+                            let field_ident = rust_ident(column);
+                            if sensitive(column) {
+                                quote!(.field(#column, &format_args!("********")))
+                            } else {
+                                quote!(.field(#column, &self.#field_ident))
+                            }
+                        });
+
+                    quote!(
+                        #[doc = #doc]
+                        #[derive(Clone, PartialEq, ::sea_orm::FromQueryResult, ::serde::Serialize, ::serde::Deserialize)]
+                        pub struct #ident {
+                            #( #fields, )*
+                        }
+
+                        impl ::std::fmt::Debug for #ident {
+                            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                                f.debug_struct(#name)
+                                    #( #debug_fields )*
+                                    .finish()
+                            }
+                        }
+                    )
+                })
+            })
+            .collect();
+        if projections.is_empty() {
+            return TokenStream::new();
+        }
+
+        quote!(
+            /// Columns of tables fetched into structs of their own, instead of whole rows.
+            pub mod projection {
+                #( #projections )*
+            }
+        )
+    }
+
+    /// Compiles a `scope` module with a trait chaining the scopes of each table on its queries, and the arguments
+    /// selecting them in list endpoints, or nothing when no table has scopes.
+    fn compile_scopes(&self) -> TokenStream {
+        let scopes: Vec<_> = self
+            .database_tables()
+            .into_iter()
+            .filter(|(_, table)| !table.scopes.is_empty())
+            .map(|(model, table)| {
+                let db_module_ident = rust_ident(&table.name);
+                let trait_ident = format_ident!("{}Scopes", model.name);
+                let args_ident = format_ident!("{}ScopeArgs", model.name);
+                let trait_doc = format!(
+                    " Scopes of `{}` rows, chained on queries of the table such as `Entity::{}()`.",
+                    model.name, table.scopes[0].name
+                );
+                let args_doc = format!(
+                    " Scopes of `{}` rows selected by the query of list endpoints, such as `?{}`.",
+                    model.name,
+                    scope_arg_names(&table.scopes[0])
+                        .iter()
+                        .map(|arg| format!("{}=...", arg))
+                        .collect::<Vec<_>>()
+                        .join("&")
+                );
+
+                let mut methods = Vec::new();
+                let mut impls = Vec::new();
+                let mut fields = Vec::new();
+                let mut applied = Vec::new();
+                for scope in &table.scopes {
+                    let ident = rust_ident(&scope.name);
+                    let condition_ident = format_ident!("{}_condition", scope.name);
+                    let param_idents: Vec<_> = scope.params.iter().map(|param| rust_ident(&param.name)).collect();
+                    let param_tys: Vec<_> = scope.params.iter().map(|param| scope_param_ty(&param.ty)).collect();
+                    let doc = format!(" Keeps the rows of scope [`{0}`](crate::{1}::Entity::{0}).", scope.name, table.name);
+
+                    methods.push(quote!(
+                        #[doc = #doc]
+                        fn #ident(self, #( #param_idents: #param_tys ),*) -> Self;
+                    ));
+                    impls.push(quote!(
+                        fn #ident(self, #( #param_idents: #param_tys ),*) -> Self {
+                            use ::sea_orm::QueryFilter;
+
+                            self.filter(crate::#db_module_ident::Entity::#condition_ident(#( #param_idents ),*))
+                        }
+                    ));
+
+                    let arg_idents: Vec<_> = scope_arg_names(scope).iter().map(|arg| rust_ident(arg)).collect();
+                    if scope.params.is_empty() {
+                        let arg_ident = &arg_idents[0];
+                        let field_doc = format!(" Keeps the rows of scope `{}` when `true`.", scope.name);
+                        fields.push(quote!(
+                            #[doc = #field_doc]
+                            #[serde(default)]
+                            pub #arg_ident: bool
+                        ));
+                        applied.push(quote!(
+                            if self.#arg_ident {
+                                select = select.#ident();
+                            }
+                        ));
+                    } else {
+                        for ((arg_ident, param), ty) in arg_idents.iter().zip(&scope.params).zip(&param_tys) {
+                            let field_doc = format!(" Parameter `{}` of scope `{}`, which is kept when all its parameters are set.", param.name, scope.name);
+                            fields.push(quote!(
+                                #[doc = #field_doc]
+                                pub #arg_ident: Option<#ty>
+                            ));
+                        }
+                        applied.push(quote!(
+                            if let ( #( Some(#param_idents), )* ) = ( #( self.#arg_idents.clone(), )* ) {
+                                select = select.#ident(#( #param_idents ),*);
+                            }
+                        ));
+                    }
+                }
+
+                quote!(
+                    #[doc = #trait_doc]
+                    pub trait #trait_ident {
+                        #( #methods )*
+                    }
+
+                    impl #trait_ident for ::sea_orm::Select<crate::#db_module_ident::Entity> {
+                        #( #impls )*
+                    }
+
+                    #[doc = #args_doc]
+                    #[derive(Clone, Debug, Default, PartialEq, ::serde::Deserialize)]
+                    pub struct #args_ident {
+                        #( #fields, )*
+                    }
+
+                    impl #args_ident {
+                        /// Keeps the rows of `select` in the selected scopes.
+                        pub fn apply(
+                            &self,
+                            mut select: ::sea_orm::Select<crate::#db_module_ident::Entity>,
+                        ) -> ::sea_orm::Select<crate::#db_module_ident::Entity> {
+                            #( #applied )*
+
+                            select
+                        }
+                    }
+                )
+            })
+            .collect();
+        if scopes.is_empty() {
+            return TokenStream::new();
+        }
+
+        quote!(
+            /// Named filters of the rows of tables, chained on their queries.
+            pub mod scope {
+                #( #scopes )*
+            }
+        )
+    }
+
+    /// Compiles a partial update of a table, only changing the columns present in a patch.
+    fn compile_apply_patch(&self, model: &Model, table: &DatabaseTable) -> TokenStream {
+        let db_module_ident = rust_ident(&table.name);
+        let key = TableKey::of(table);
+        let patch_ident = format_ident!("{}Patch", model.name);
+        let doc = format!(
+            " Updates the columns present in `patch` of the `{}` row with the given `id`, returning `None` when it does not exist.",
+            model.name
+        );
+        let record_event = table.events.then(|| {
+            quote!(if let Some(model) = &model {
+                Self::record_event(db, crate::outbox::EventKind::Updated, model).await?;
+            })
+        });
+        // Versioned patches are recorded by `update_versioned`
+        let (fetch_previous, record_previous) =
+            Self::compile_record_previous(table, quote!(Update));
+
+        if table.versioned {
+            quote!(
+                impl crate::#db_module_ident::Entity {
+                    #[doc = #doc]
+                    ///
+                    /// Returns a [`StaleVersionError`](crate::version::StaleVersionError) when the row was updated
+                    /// since the version of `patch` was read.
+                    pub async fn apply_patch<'a, C>(
+                        db: &'a C,
+                        id: ::uuid::Uuid,
+                        patch: crate::patch::#patch_ident,
+                    ) -> Result<Option<crate::#db_module_ident::Model>, crate::version::VersionedUpdateError>
+                    where
+                        C: ::sea_orm::ConnectionTrait<'a>,
+                    {
+                        use ::sea_orm::EntityTrait;
+
+                        if Self::find_by_id(id).one(db).await?.is_none() {
+                            return Ok(None);
+                        }
+
+                        let mut active_model = crate::#db_module_ident::ActiveModel::from(patch);
+                        active_model.id = ::sea_orm::Set(id);
+                        active_model.updated_at = ::sea_orm::Set(::chrono::Utc::now().into());
+
+                        Self::update_versioned(db, active_model).await.map(Some)
+                    }
+                }
+            )
+        } else {
+            let TableKey {
+                column: key_column,
+                param_ty: key_ty,
+                ..
+            } = &key;
+            let owned_id = key.owned(quote!(id));
+            quote!(
+                impl crate::#db_module_ident::Entity {
+                    #[doc = #doc]
+                    pub async fn apply_patch<'a, C>(
+                        db: &'a C,
+                        id: #key_ty,
+                        patch: crate::patch::#patch_ident,
+                    ) -> Result<Option<crate::#db_module_ident::Model>, ::sea_orm::DbErr>
+                    where
+                        C: ::sea_orm::ConnectionTrait<'a>,
+                    {
+                        use ::sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+
+                        let mut active_model = crate::#db_module_ident::ActiveModel::from(patch);
+                        active_model.updated_at = ::sea_orm::Set(::chrono::Utc::now().into());
+                        #fetch_previous
+
+                        let result = Self::update_many()
+                            .set(active_model)
+                            .filter(crate::#db_module_ident::Column::#key_column.eq(id))
+                            .exec(db)
+                            .await?;
+                        if result.rows_affected == 0 {
+                            return Ok(None);
+                        }
+                        #record_previous
+
+                        let model = Self::find_by_id(#owned_id).one(db).await?;
+                        #record_event
+
+                        Ok(model)
+                    }
+                }
+            )
+        }
+    }
+
+    /// Compiles batched lookups of the relations of a table, resolving many keys in a single `IN (...)` query.
+    ///
+    /// Foreign key columns get a `load_by_<column>` function grouping the referencing rows by key (has-many),
+    /// and columns referenced by other tables get a `load_by_<column>` function returning the row of each key (belongs-to).
+    fn compile_relation_loaders(&self, table: &DatabaseTable) -> TokenStream {
+        let db_module_ident = rust_ident(&table.name);
+
+        let loaders = self.loaded_columns(table).into_iter().map(|(column, has_many, key_ty)| {
+            let fn_ident = format_ident!("load_by_{}", column.name);
+            let query_ident = format_ident!("load_by_{}_query", column.name);
+            let query_doc = format!(" Returns the query of [`{}`](Self::{}) loading the rows of a chunk of `keys`.", fn_ident, fn_ident);
+            let field_ident = rust_ident(&column.name);
+            let column_ident = column_variant_ident(&column.name);
+            let key = if column.nullable {
+                quote!(match row.#field_ident.clone() { Some(key) => key, None => continue })
+            } else {
+                quote!(row.#field_ident.clone())
+            };
+
+            let (doc, value_ty, insert) = if has_many {
+                (
+                    format!(" Loads the `{}` rows of each `{}` in `keys`, grouped by key.", table.name, column.name),
+                    quote!(Vec<crate::#db_module_ident::Model>),
+                    quote!(rows.entry(key).or_insert_with(Vec::new).push(row)),
+                )
+            } else {
+                (
+                    format!(" Loads the `{}` row of each `{}` in `keys`, omitting keys without a row.", table.name, column.name),
+                    quote!(crate::#db_module_ident::Model),
+                    quote!(rows.insert(key, row)),
+                )
+            };
+
+            quote!(
+                #[doc = #doc]
+                ///
+                /// Keys are looked up with a single query per chunk of keys fitting in the bind parameter limit.
+                pub async fn #fn_ident<'a, C>(
+                    db: &'a C,
+                    keys: &[#key_ty],
+                ) -> Result<::std::collections::HashMap<#key_ty, #value_ty>, ::sea_orm::DbErr>
+                where
+                    C: ::sea_orm::ConnectionTrait<'a>,
+                {
+                    let mut rows = ::std::collections::HashMap::new();
+                    for chunk in keys.chunks(crate::batch::max_parameters(db.get_database_backend())) {
+                        for row in Self::#query_ident(chunk).all(db).await? {
+                            let key = #key;
+                            #insert;
+                        }
+                    }
+
+                    Ok(rows)
+                }
+
+                #[doc = #query_doc]
+                pub fn #query_ident(keys: &[#key_ty]) -> ::sea_orm::Select<Self> {
+                    use ::sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+
+                    Self::find().filter(crate::#db_module_ident::Column::#column_ident.is_in(keys.iter().cloned()))
+                }
+            )
+        }).collect::<Vec<_>>();
+
+        if loaders.is_empty() {
+            return TokenStream::new();
+        }
+
+        quote!(
+            impl crate::#db_module_ident::Entity {
+                #( #loaders )*
+            }
+        )
+    }
+
+    /// Returns the columns of `table` getting a [`compile_relation_loaders`](Self::compile_relation_loaders) function,
+    /// along with whether they load the rows referencing a key (has-many) and the type of their keys.
+    fn loaded_columns<'a>(
+        &self,
+        table: &'a DatabaseTable,
+    ) -> Vec<(&'a DatabaseColumn, bool, TokenStream)> {
+        let referenced_columns: Vec<&str> = self
+            .index
+            .referenced_columns
+            .get(&table.name)
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect();
+
+        table
+            .columns
+            .iter()
+            .filter_map(|column| {
+                let has_many = column.references.is_some();
+                if !has_many && !referenced_columns.contains(&column.name.as_str()) {
+                    return None;
+                }
+                // Keys are hashed to group the loaded rows
+                let key_ty = match column.ty {
+                    DatabaseType::Float | DatabaseType::Double => return None,
+                    _ => column_rust_type(&column.ty)?,
+                };
+
+                Some((column, has_many, key_ty))
+            })
+            .collect()
+    }
+
+    /// Returns the name of the data loader of the `load_by_<column>` function of `table`, such as `CommentByCategoryIdLoader`.
+    fn data_loader_ident(table: &DatabaseTable, column: &DatabaseColumn) -> proc_macro2::Ident {
+        format_ident!(
+            "{}By{}Loader",
+            table.name.to_camel_case(),
+            column.name.to_camel_case()
+        )
+    }
+
+    /// Compiles the `database::loader` module of async-graphql data loaders, which batch the relation lookups
+    /// requested by the resolvers of a query into the functions of [`compile_relation_loaders`](Self::compile_relation_loaders).
+    fn compile_data_loaders(&self) -> TokenStream {
+        let mut loaders = Vec::new();
+        let mut registrations = Vec::new();
+        for (_, table) in self.database_tables() {
+            let db_module_ident = rust_ident(&table.name);
+            for (column, has_many, key_ty) in self.loaded_columns(table) {
+                let loader_ident = Self::data_loader_ident(table, column);
+                let fn_ident = format_ident!("load_by_{}", column.name);
+                let (doc, value_ty) = if has_many {
+                    (
+                        format!(" Loads the `{}` rows of each `{}`, for the resolvers of has-many relations.", table.name, column.name),
+                        quote!(Vec<crate::#db_module_ident::Model>),
+                    )
+                } else {
+                    (
+                        format!(" Loads the `{}` row of each `{}`, for the resolvers of belongs-to relations.", table.name, column.name),
+                        quote!(crate::#db_module_ident::Model),
+                    )
+                };
+
+                loaders.push(quote!(
+                    #[doc = #doc]
+                    #[derive(Clone, Debug)]
+                    pub struct #loader_ident {
+                        db: ::sea_orm::DatabaseConnection,
+                    }
+
+                    impl #loader_ident {
+                        /// Creates a loader running its queries on `db`.
+                        pub fn new(db: ::sea_orm::DatabaseConnection) -> Self {
+                            #loader_ident { db }
+                        }
+                    }
+
+                    #[::async_trait::async_trait]
+                    impl ::async_graphql::dataloader::Loader<#key_ty> for #loader_ident {
+                        type Value = #value_ty;
+                        type Error = LoadError;
+
+                        async fn load(
+                            &self,
+                            keys: &[#key_ty],
+                        ) -> Result<::std::collections::HashMap<#key_ty, Self::Value>, Self::Error> {
+                            crate::#db_module_ident::Entity::#fn_ident(&self.db, keys)
+                                .await
+                                .map_err(::std::sync::Arc::new)
+                        }
+                    }
+                ));
+                registrations
+                    .push(quote!(.data(config.data_loader(#loader_ident::new(db.clone())))));
+            }
+        }
+
+        quote!(
+            /// Data loaders of the relations of the tables, batching the lookups of the resolvers of a GraphQL query
+            /// into a query per relation.
+            #[cfg(feature = "graphql")]
+            pub mod loader {
+                use std::time::Duration;
+
+                /// Error of a batch, shared by the resolvers waiting on it.
+                pub type LoadError = ::std::sync::Arc<::sea_orm::DbErr>;
+
+                /// Batching of the data loaders added to a schema by [`register`].
+                ///
+                /// By default batches load up to 1000 keys, requested within 1ms of each other.
+                #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+                pub struct LoaderConfig {
+                    max_batch_size: usize,
+                    delay: Duration,
+                }
+
+                impl Default for LoaderConfig {
+                    fn default() -> Self {
+                        LoaderConfig {
+                            max_batch_size: 1000,
+                            delay: Duration::from_millis(1),
+                        }
+                    }
+                }
+
+                impl LoaderConfig {
+                    /// Sets the most keys loaded by a batch, the keys past it being loaded by another batch.
+                    pub fn max_batch_size(mut self, max_batch_size: usize) -> Self {
+                        self.max_batch_size = max_batch_size;
+                        self
+                    }
+
+                    /// Sets how long a loader waits for more keys before loading a batch.
+                    pub fn delay(mut self, delay: Duration) -> Self {
+                        self.delay = delay;
+                        self
+                    }
+
+                    /// Returns a data loader of `loader` batching its keys with this configuration.
+                    pub fn data_loader<T>(&self, loader: T) -> ::async_graphql::dataloader::DataLoader<T>
+                    where
+                        T: Send + Sync + 'static,
+                    {
+                        ::async_graphql::dataloader::DataLoader::new(loader)
+                            .max_batch_size(self.max_batch_size)
+                            .delay(self.delay)
+                    }
+                }
+
+                /// Adds a data loader of every relation to the data of the schema built by `builder`,
+                /// running their queries on `db`.
+                ///
+                /// Resolvers get the loaders from their context, such as
+                /// `ctx.data_unchecked::<DataLoader<ProductByIdLoader>>().load_one(id)`.
+                #[allow(unused_variables)]
+                pub fn register<Query, Mutation, Subscription>(
+                    builder: ::async_graphql::SchemaBuilder<Query, Mutation, Subscription>,
+                    db: &::sea_orm::DatabaseConnection,
+                    config: LoaderConfig,
+                ) -> ::async_graphql::SchemaBuilder<Query, Mutation, Subscription> {
+                    builder #( #registrations )*
+                }
+
+                #( #loaders )*
+            }
+        )
+    }
+
+    /// Compiles the `database::subscription` module of the async-graphql subscription root, streaming the changes
+    /// of the tables with `#[awto(graphql(subscriptions))]` from the [`notify`](Self::compile_notify_queries) streams,
+    /// or nothing when no table has subscriptions.
+    fn compile_subscriptions(&self) -> TokenStream {
+        let tables: Vec<_> = self
+            .database_tables()
+            .into_iter()
+            .filter(|(_, table)| table.subscriptions)
+            .collect();
+        if tables.is_empty() {
+            return TokenStream::new();
+        }
+
+        let mut changes = Vec::new();
+        let mut fields = Vec::new();
+        for (model, table) in tables {
+            let db_module_ident = rust_ident(&table.name);
+            let schema_ty = schema_ty(model);
+            let change_ident = format_ident!("{}Change", model.name);
+            let created_ident = format_ident!("{}_created", table.name);
+            let changed_ident = format_ident!("{}_changed", table.name);
+            let change_doc = format!(
+                " Change of a `{}` row, streamed by `{}`.",
+                model.name,
+                changed_ident.to_string().to_mixed_case()
+            );
+            let created_doc = format!(" Streams the `{}` rows created from now on.", model.name);
+            let changed_doc = format!(
+                " Streams the changes of the `{}` row `id` from now on, ending with its deletion.",
+                model.name
+            );
+
+            changes.push(quote!(
+                #[doc = #change_doc]
+                #[derive(Clone, Debug, ::async_graphql::SimpleObject)]
+                pub struct #change_ident {
+                    /// Kind of change.
+                    pub operation: Operation,
+                    /// Id of the row.
+                    pub id: ::async_graphql::ID,
+                    /// Row as it was refetched after it was created or updated, serialized like the schema struct,
+                    /// or `null` once it was deleted.
+                    pub row: Option<::async_graphql::Json<#schema_ty>>,
+                }
+
+                impl From<crate::notify::Change<crate::#db_module_ident::Model>> for #change_ident {
+                    fn from(change: crate::notify::Change<crate::#db_module_ident::Model>) -> Self {
+                        let (operation, id, row) = match change {
+                            crate::notify::Change::Created(row) => (Operation::Created, row.id, Some(row)),
+                            crate::notify::Change::Updated(row) => (Operation::Updated, row.id, Some(row)),
+                            crate::notify::Change::Deleted(id) => (Operation::Deleted, id, None),
+                        };
+
+                        #change_ident {
+                            operation,
+                            id: ::async_graphql::ID(id.to_string()),
+                            row: row.map(|row| ::async_graphql::Json(row.into())),
+                        }
+                    }
+                }
+            ));
+            fields.push(quote!(
+                #[doc = #created_doc]
+                async fn #created_ident(
+                    &self,
+                    ctx: &::async_graphql::Context<'_>,
+                ) -> ::async_graphql::Result<
+                    impl ::futures_util::Stream<Item = ::async_graphql::Result<::async_graphql::Json<#schema_ty>>>,
+                > {
+                    let (db, listener) = sources(ctx)?;
+                    let changes = crate::#db_module_ident::Entity::changes(db, listener).map_err(graphql_error)?;
+
+                    Ok(changes.filter_map(|change| async move {
+                        match change {
+                            Ok(crate::notify::Change::Created(row)) => Some(Ok(::async_graphql::Json(row.into()))),
+                            Ok(_) => None,
+                            Err(err) => Some(Err(graphql_error(err))),
+                        }
+                    }))
+                }
+
+                #[doc = #changed_doc]
+                async fn #changed_ident(
+                    &self,
+                    ctx: &::async_graphql::Context<'_>,
+                    id: ::async_graphql::ID,
+                ) -> ::async_graphql::Result<impl ::futures_util::Stream<Item = ::async_graphql::Result<#change_ident>>> {
+                    let (db, listener) = sources(ctx)?;
+                    let id: ::uuid::Uuid = id.parse()?;
+                    let changes = crate::#db_module_ident::Entity::changes_of(db, listener, id).map_err(graphql_error)?;
+
+                    // The stream ends once the row is deleted, as it cannot change anymore
+                    Ok(::futures_util::stream::unfold((Box::pin(changes), false), |(mut changes, deleted)| async move {
+                        if deleted {
+                            return None;
+                        }
+                        let change = changes.next().await?;
+                        let deleted = matches!(change, Ok(crate::notify::Change::Deleted(_)));
+
+                        Some((change.map(#change_ident::from).map_err(graphql_error), (changes, deleted)))
+                    }))
+                }
+            ));
+        }
+
+        quote!(
+            /// Subscription root of async-graphql streaming the changes of the tables with
+            /// `#[awto(graphql(subscriptions))]`, sent by their triggers through Postgres `LISTEN`/`NOTIFY`.
+            #[cfg(feature = "graphql")]
+            pub mod subscription {
+                use ::futures_util::StreamExt;
+
+                /// Kind of change of a row.
+                #[derive(Clone, Copy, Debug, PartialEq, Eq, ::async_graphql::Enum)]
+                pub enum Operation {
+                    /// The row was inserted.
+                    Created,
+                    /// The row was updated.
+                    Updated,
+                    /// The row was deleted.
+                    Deleted,
+                }
+
+                #( #changes )*
+
+                /// Subscriptions of the changes of the tables, reading the connection and the
+                /// [`Listener`](crate::notify::Listener) they are refetched with and received from the data of the schema,
+                /// added by [`register`].
+                ///
+                /// It can be the subscription root of a schema, or be merged with others with `#[derive(MergedSubscription)]`.
+                #[derive(Clone, Copy, Debug, Default)]
+                pub struct Subscription;
+
+                #[::async_graphql::Subscription]
+                impl Subscription {
+                    #( #fields )*
+                }
+
+                /// Adds the connection refetching the changed rows and the `listener` receiving their changes
+                /// to the data of the schema built by `builder`.
+                pub fn register<Query, Mutation, Subscription>(
+                    builder: ::async_graphql::SchemaBuilder<Query, Mutation, Subscription>,
+                    db: &::sea_orm::DatabaseConnection,
+                    listener: &crate::notify::Listener,
+                ) -> ::async_graphql::SchemaBuilder<Query, Mutation, Subscription> {
+                    builder.data(db.clone()).data(listener.clone())
+                }
+
+                fn sources<'a>(
+                    ctx: &::async_graphql::Context<'a>,
+                ) -> ::async_graphql::Result<(::sea_orm::DatabaseConnection, &'a crate::notify::Listener)> {
+                    let db = ctx.data::<::sea_orm::DatabaseConnection>()?.clone();
+                    let listener = ctx.data::<crate::notify::Listener>()?;
+
+                    Ok((db, listener))
+                }
+
+                fn graphql_error(err: ::sea_orm::DbErr) -> ::async_graphql::Error {
+                    ::async_graphql::Error::new(err.to_string())
+                }
+            }
+        )
+    }
+
+    /// Compiles a `find_<relation>` function on the model of a table for each of its foreign key columns,
+    /// finding the row it references, named after the column without its `_id` suffix.
+    ///
+    /// Rows may not reference any row through nullable columns, so the referenced row is always optional.
+    fn compile_referenced_queries(&self, table: &DatabaseTable) -> TokenStream {
+        let db_module_ident = rust_ident(&table.name);
+
+        let queries = table.columns.iter().filter_map(|column| {
+            let (referenced, referenced_column) = column.references.as_ref()?;
+            let relation = column.name.strip_suffix("_id").unwrap_or(&column.name);
+            let fn_ident = format_ident!("find_{}", relation);
+            let field_ident = rust_ident(&column.name);
+            let referenced_ident = rust_ident(referenced);
+            let referenced_column_ident = column_variant_ident(referenced_column);
+            let (doc, key) = if column.nullable {
+                (
+                    format!(" Finds the `{}` row referenced by `{}`, or `None` when it is null.", referenced, column.name),
+                    quote!(match self.#field_ident.clone() { Some(key) => key, None => return Ok(None) }),
+                )
+            } else {
+                (
+                    format!(" Finds the `{}` row referenced by `{}`.", referenced, column.name),
+                    quote!(self.#field_ident.clone()),
+                )
+            };
+
+            // The entity of a table of another database is in the package of that database, so it is a type parameter
+            if column.cross_database {
+                let doc = format!(
+                    " Finds the `{}` row referenced by `{}` in another database with `db`, a connection to that database{}.\n\n\
+                    `E` is the entity of the table in the package of that database, such as `database_analytics::{}::Entity`.\n\
+                    The reference has no foreign key, so the row may be missing.",
+                    referenced,
+                    column.name,
+                    if column.nullable { ", or `None` when it is null" } else { "" },
+                    referenced_ident,
+                );
+                return Some(quote!(
+                    #[doc = #doc]
+                    pub async fn #fn_ident<'a, E, C>(
+                        &self,
+                        db: &'a C,
+                    ) -> Result<Option<E::Model>, ::sea_orm::DbErr>
+                    where
+                        E: ::sea_orm::EntityTrait,
+                        C: ::sea_orm::ConnectionTrait<'a>,
+                    {
+                        use ::sea_orm::QueryFilter;
+
+                        let key = #key;
+                        E::find()
+                            .filter(::sea_orm::sea_query::Expr::col(::sea_orm::sea_query::Alias::new(#referenced_column)).eq(key))
+                            .one(db)
+                            .await
+                    }
+                ));
+            }
+
+            Some(quote!(
+                #[doc = #doc]
+                pub async fn #fn_ident<'a, C>(
+                    &self,
+                    db: &'a C,
+                ) -> Result<Option<crate::#referenced_ident::Model>, ::sea_orm::DbErr>
+                where
+                    C: ::sea_orm::ConnectionTrait<'a>,
+                {
+                    use ::sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+
+                    let key = #key;
+                    crate::#referenced_ident::Entity::find()
+                        .filter(crate::#referenced_ident::Column::#referenced_column_ident.eq(key))
+                        .one(db)
+                        .await
+                }
+            ))
+        }).collect::<Vec<_>>();
+
+        if queries.is_empty() {
+            return TokenStream::new();
+        }
+
+        quote!(
+            impl crate::#db_module_ident::Model {
+                #( #queries )*
+            }
+        )
+    }
+
+    /// Compiles the queries of the trees formed by the columns of a table referencing the table itself, such as `parent_id`.
+    ///
+    /// Each self reference gets a `find_children` function on the model, and `find_descendants` and `find_ancestors`
+    /// functions on the entity walking the tree in a single recursive query, bounded by a maximum depth so rows
+    /// referencing each other in a cycle cannot make them recurse forever.
+    /// Functions of tables with several self references are suffixed with `_by_<column>`.
+    fn compile_tree_queries(&self, table: &DatabaseTable) -> TokenStream {
+        let db_module_ident = rust_ident(&table.name);
+        let self_references = table.self_references();
+        let suffixed = self_references.len() > 1;
+
+        let queries = self_references.iter().filter_map(|column| {
+            let (_, referenced_column) = column.references.as_ref()?;
+            let referenced = table.columns.iter().find(|other| &other.name == referenced_column)?;
+            if referenced.nullable {
+                return None;
+            }
+            let key_ty = column_rust_type(&referenced.ty)?;
+            let suffix = if suffixed { format!("_by_{}", column.name) } else { String::new() };
+            let children_ident = format_ident!("find_children{}", suffix);
+            let descendants_ident = format_ident!("find_descendants{}", suffix);
+            let ancestors_ident = format_ident!("find_ancestors{}", suffix);
+            let column_ident = column_variant_ident(&column.name);
+            let referenced_field_ident = rust_ident(&referenced.name);
+
+            let name = quote_ident(&table.name);
+            let child = quote_ident(&column.name);
+            let parent = quote_ident(&referenced.name);
+            let descendants_sql = format!(
+                "WITH RECURSIVE tree AS (\
+                SELECT {table}.*, 1 AS awto_tree_depth FROM {table} WHERE {child} = $1 \
+                UNION ALL \
+                SELECT {table}.*, tree.awto_tree_depth + 1 FROM {table} JOIN tree ON {table}.{child} = tree.{parent} \
+                WHERE tree.awto_tree_depth < $2\
+                ) SELECT * FROM tree ORDER BY awto_tree_depth",
+                table = name,
+                child = child,
+                parent = parent,
+            );
+            let ancestors_sql = format!(
+                "WITH RECURSIVE tree AS (\
+                SELECT parent.*, 1 AS awto_tree_depth FROM {table} parent JOIN {table} child ON parent.{parent} = child.{child} \
+                WHERE child.{parent} = $1 \
+                UNION ALL \
+                SELECT parent.*, tree.awto_tree_depth + 1 FROM {table} parent JOIN tree ON parent.{parent} = tree.{child} \
+                WHERE tree.awto_tree_depth < $2\
+                ) SELECT * FROM tree ORDER BY awto_tree_depth",
+                table = name,
+                child = child,
+                parent = parent,
+            );
+            let children_doc = format!(" Finds the `{}` rows whose `{}` references this row.", table.name, column.name);
+            let descendants_doc = format!(
+                " Finds the `{}` rows below the row `key` through `{}`, down to `max_depth` levels, ordered by their depth.",
+                table.name, column.name
+            );
+            let ancestors_doc = format!(
+                " Finds the `{}` rows above the row `key` through `{}`, up to `max_depth` levels, starting from its parent.",
+                table.name, column.name
+            );
+
+            Some((
+                quote!(
+                    #[doc = #children_doc]
+                    pub async fn #children_ident<'a, C>(&self, db: &'a C) -> Result<Vec<crate::#db_module_ident::Model>, ::sea_orm::DbErr>
+                    where
+                        C: ::sea_orm::ConnectionTrait<'a>,
+                    {
+                        use ::sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+
+                        crate::#db_module_ident::Entity::find()
+                            .filter(crate::#db_module_ident::Column::#column_ident.eq(self.#referenced_field_ident.clone()))
+                            .all(db)
+                            .await
+                    }
+                ),
+                quote!(
+                    #[doc = #descendants_doc]
+                    ///
+                    /// Rows referencing each other in a cycle are returned again on every level until `max_depth`.
+                    pub async fn #descendants_ident<'a, C>(
+                        db: &'a C,
+                        key: #key_ty,
+                        max_depth: u32,
+                    ) -> Result<Vec<crate::#db_module_ident::Model>, ::sea_orm::DbErr>
+                    where
+                        C: ::sea_orm::ConnectionTrait<'a>,
+                    {
+                        Self::find_tree(db, #descendants_sql, key, max_depth).await
+                    }
+
+                    #[doc = #ancestors_doc]
+                    ///
+                    /// Rows referencing each other in a cycle are returned again on every level until `max_depth`.
+                    pub async fn #ancestors_ident<'a, C>(
+                        db: &'a C,
+                        key: #key_ty,
+                        max_depth: u32,
+                    ) -> Result<Vec<crate::#db_module_ident::Model>, ::sea_orm::DbErr>
+                    where
+                        C: ::sea_orm::ConnectionTrait<'a>,
+                    {
+                        Self::find_tree(db, #ancestors_sql, key, max_depth).await
+                    }
+                ),
+            ))
+        });
+        let (children, walks): (Vec<_>, Vec<_>) = queries.unzip();
+
+        if children.is_empty() {
+            return TokenStream::new();
+        }
+
+        quote!(
+            impl crate::#db_module_ident::Model {
+                #( #children )*
+            }
+
+            impl crate::#db_module_ident::Entity {
+                #( #walks )*
+
+                /// Runs a recursive query of the tree, binding `key` to `$1` and `max_depth` to `$2`.
+                async fn find_tree<'a, C, K>(
+                    db: &'a C,
+                    sql: &str,
+                    key: K,
+                    max_depth: u32,
+                ) -> Result<Vec<crate::#db_module_ident::Model>, ::sea_orm::DbErr>
+                where
+                    C: ::sea_orm::ConnectionTrait<'a>,
+                    K: Into<::sea_orm::Value>,
+                {
+                    use ::sea_orm::EntityTrait;
+
+                    if max_depth == 0 {
+                        return Ok(Vec::new());
+                    }
+                    let max_depth = max_depth.min(i32::MAX as u32) as i32;
+                    Self::find()
+                        .from_raw_sql(::sea_orm::Statement::from_sql_and_values(
+                            db.get_database_backend(),
+                            sql,
+                            vec![key.into(), max_depth.into()],
+                        ))
+                        .all(db)
+                        .await
+                }
+            }
+        )
+    }
+
+    /// Compiles the maintenance of the partitions of a partitioned table.
+    fn compile_partition_queries(
+        &self,
+        table: &DatabaseTable,
+        partition: &DatabasePartition,
+    ) -> TokenStream {
+        let db_module_ident = rust_ident(&table.name);
+        let name = &table.name;
+        let interval_ident = format_ident!("{}", partition.interval.as_str().to_camel_case());
+        let doc = format!(
+            " Creates the missing `{}` partitions of each {} from the current one through the one holding `through`,",
+            table.name,
+            partition.interval.as_str(),
+        );
+        let between_doc = format!(
+            " Creates the missing `{}` partitions of each {} from the one holding `from` through the one holding `through`,",
+            table.name,
+            partition.interval.as_str(),
+        );
+
+        quote!(
+            impl crate::#db_module_ident::Entity {
+                #[doc = #doc]
+                /// returning the names of the partitions created.
+                pub async fn ensure_partitions<'a, C>(db: &'a C, through: ::chrono::NaiveDate) -> Result<Vec<String>, ::sea_orm::DbErr>
+                where
+                    C: ::sea_orm::ConnectionTrait<'a>,
+                {
+                    let today = ::chrono::Utc::now().naive_utc().date();
+                    Self::ensure_partitions_between(db, today, through).await
+                }
+
+                #[doc = #between_doc]
+                /// returning the names of the partitions created.
+                pub async fn ensure_partitions_between<'a, C>(
+                    db: &'a C,
+                    from: ::chrono::NaiveDate,
+                    through: ::chrono::NaiveDate,
+                ) -> Result<Vec<String>, ::sea_orm::DbErr>
+                where
+                    C: ::sea_orm::ConnectionTrait<'a>,
+                {
+                    crate::partition::ensure_partitions(db, #name, crate::partition::Interval::#interval_ident, from, through).await
+                }
+            }
+        )
+    }
+
+    /// Compiles the streams of the changes of a table with `#[awto(notify)]`.
+    fn compile_notify_queries(&self, model: &Model, table: &DatabaseTable) -> TokenStream {
+        let db_module_ident = rust_ident(&table.name);
+        let name = &table.name;
+        let doc = format!(
+            " Streams the changes of `{}` rows received by `listener`,",
+            model.name
+        );
+        let of_doc = format!(
+            " Streams the changes of the `{}` row `id` received by `listener`,",
+            model.name
+        );
+
+        quote!(
+            impl crate::#db_module_ident::Entity {
+                #[doc = #doc]
+                /// refetching created and updated rows with `db`.
+                pub fn changes(
+                    db: ::sea_orm::DatabaseConnection,
+                    listener: &crate::notify::Listener,
+                ) -> Result<
+                    impl ::futures_util::Stream<Item = Result<crate::notify::Change<crate::#db_module_ident::Model>, ::sea_orm::DbErr>> + Send,
+                    ::sea_orm::DbErr,
+                > {
+                    crate::notify::changes::<Self>(db, listener, #name, crate::#db_module_ident::Column::Id, None)
+                }
+
+                #[doc = #of_doc]
+                /// refetching it with `db` after it was created or updated.
+                pub fn changes_of(
+                    db: ::sea_orm::DatabaseConnection,
+                    listener: &crate::notify::Listener,
+                    id: ::uuid::Uuid,
+                ) -> Result<
+                    impl ::futures_util::Stream<Item = Result<crate::notify::Change<crate::#db_module_ident::Model>, ::sea_orm::DbErr>> + Send,
+                    ::sea_orm::DbErr,
+                > {
+                    crate::notify::changes::<Self>(db, listener, #name, crate::#db_module_ident::Column::Id, Some(id))
+                }
+            }
+        )
+    }
+
+    /// Compiles the queries of the versions of the rows of a table with `#[awto(temporal)]`,
+    /// along with the recording of previous versions with [`Temporal::Portable`].
+    ///
+    /// The current version of a row is valid since the end of its last previous version, or since it was created.
+    fn compile_temporal_queries(
+        &self,
+        model: &Model,
+        table: &DatabaseTable,
+        temporal: Temporal,
+    ) -> TokenStream {
+        let db_module_ident = rust_ident(&table.name);
+        let columns = with_locale_columns(table).columns;
+        let name = quote_ident(&table.name);
+        let history = quote_ident(&table.history_table_name()).into_owned();
+        let column_list = columns
+            .iter()
+            .map(|column| quote_ident(&column.name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let current_valid_from = format!(
+            "COALESCE((SELECT MAX(valid_to) FROM {history} WHERE id = $1), {table}.created_at)",
+            history = history,
+            table = name
+        );
+
+        let as_of_sql = format!(
+            "SELECT {columns} FROM {history} WHERE id = $1 AND valid_from <= $2 AND valid_to > $2 \
+            UNION ALL SELECT {columns} FROM {table} WHERE id = $1 AND {valid_from} <= $2",
+            columns = column_list,
+            history = history,
+            table = name,
+            valid_from = current_valid_from,
+        );
+        let history_sql = format!(
+            "SELECT {columns}, valid_from, valid_to, operation FROM {history} WHERE id = $1 \
+            UNION ALL SELECT {columns}, {valid_from} AS valid_from, NULL AS valid_to, NULL AS operation FROM {table} WHERE id = $1 \
+            ORDER BY valid_from, valid_to NULLS LAST",
+            columns = column_list,
+            history = history,
+            table = name,
+            valid_from = current_valid_from,
+        );
+        let as_of_doc = format!(
+            " Returns the version of the `{}` row `id` at `at`, or `None` when it did not exist.",
+            model.name
+        );
+        let history_doc = format!(" Returns the versions of the `{}` row `id`, oldest first and ending with its current version.", model.name);
+
+        let record_history = (temporal == Temporal::Portable).then(|| {
+            let placeholders: Vec<_> = (1..=columns.len()).map(|i| format!("${}", i)).collect();
+            let position = |name: &str| columns.iter().position(|column| column.name == name).unwrap() + 1;
+            let record_sql = format!(
+                "INSERT INTO {history} ({columns}, valid_from, valid_to, operation) VALUES ({placeholders}, \
+                COALESCE((SELECT MAX(valid_to) FROM {history} WHERE id = ${id}), ${created_at}), ${valid_to}, ${operation})",
+                history = history,
+                columns = column_list,
+                placeholders = placeholders.join(", "),
+                id = position("id"),
+                created_at = position("created_at"),
+                valid_to = columns.len() + 1,
+                operation = columns.len() + 2,
+            );
+            let field_idents = columns.iter().map(|column| rust_ident(&column.name));
+            let doc = format!(
+                " Records `previous` as a version of its `{}` row ended by `operation`, to be called in the transaction changing it.",
+                model.name
+            );
+
+            quote!(
+                #[doc = #doc]
+                pub async fn record_history<'a, C>(
+                    db: &'a C,
+                    operation: crate::temporal::Operation,
+                    previous: &crate::#db_module_ident::Model,
+                ) -> Result<(), ::sea_orm::DbErr>
+                where
+                    C: ::sea_orm::ConnectionTrait<'a>,
+                {
+                    let values = vec![ #( previous.#field_idents.clone().into() ),* ];
+                    crate::temporal::record(db, #record_sql, values, operation).await
+                }
+            )
+        });
+
+        quote!(
+            impl crate::#db_module_ident::Entity {
+                #[doc = #as_of_doc]
+                pub async fn as_of<'a, C>(
+                    db: &'a C,
+                    id: ::uuid::Uuid,
+                    at: ::chrono::DateTime<::chrono::FixedOffset>,
+                ) -> Result<Option<crate::#db_module_ident::Model>, ::sea_orm::DbErr>
+                where
+                    C: ::sea_orm::ConnectionTrait<'a>,
+                {
+                    crate::temporal::as_of::<Self, _>(db, #as_of_sql, id, at).await
+                }
+
+                #[doc = #history_doc]
+                pub async fn history<'a, C>(
+                    db: &'a C,
+                    id: ::uuid::Uuid,
+                ) -> Result<Vec<crate::temporal::Version<crate::#db_module_ident::Model>>, ::sea_orm::DbErr>
+                where
+                    C: ::sea_orm::ConnectionTrait<'a>,
+                {
+                    crate::temporal::history::<Self, _>(db, #history_sql, id).await
+                }
+
+                #record_history
+            }
+        )
+    }
+
+    /// Compiles a keyset paginated query of a table, ordered by its `order_by` column and primary key.
+    fn compile_page_query(&self, model: &Model, table: &DatabaseTable) -> TokenStream {
+        let db_module_ident = rust_ident(&table.name);
+        let query = &table.name;
+        let key_columns: Vec<_> = table
+            .order_by
+            .iter()
+            .map(String::as_str)
+            .chain(iter::once(
+                table
+                    .primary_key()
+                    .map_or("id", |column| column.name.as_str()),
+            ))
+            .collect();
+
+        let field_idents: Vec<_> = key_columns
+            .iter()
+            .map(|column| rust_ident(column))
+            .collect();
+        let column_idents: Vec<_> = key_columns
+            .iter()
+            .map(|column| column_variant_ident(column))
+            .collect();
+        let order_description = key_columns.join("` and `");
+        let doc = format!(
+            " Fetches a page of `{}` rows ordered by `{}`.",
+            model.name, order_description
+        );
+
+        // Rows after the cursor compare greater on the first differing key column:
+        // `a > $a OR (a = $a AND id > $id)`
+        let cursor_condition = |op: TokenStream| {
+            let mut condition = TokenStream::new();
+            for (i, column_ident) in column_idents.iter().enumerate().rev() {
+                let field_ident = &field_idents[i];
+                condition = if i == column_idents.len() - 1 {
+                    quote!(crate::#db_module_ident::Column::#column_ident.#op(#field_ident.clone()))
+                } else {
+                    quote!(
+                        ::sea_orm::Condition::any()
+                            .add(crate::#db_module_ident::Column::#column_ident.#op(#field_ident.clone()))
+                            .add(
+                                ::sea_orm::Condition::all()
+                                    .add(crate::#db_module_ident::Column::#column_ident.eq(#field_ident.clone()))
+                                    .add(#condition)
+                            )
+                    )
+                };
+            }
+            condition
+        };
+        let after_condition = cursor_condition(quote!(gt));
+        let before_condition = cursor_condition(quote!(lt));
+
+        quote!(
+            impl crate::#db_module_ident::Entity {
+                #[doc = #doc]
+                ///
+                /// The cursors of the returned page are encrypted with `key`, and cursors of `args` are rejected
+                /// when they were not encrypted with the same key by a page of this table.
+                pub async fn page<'a, C>(
+                    db: &'a C,
+                    key: &crate::cursor::CursorKey,
+                    args: &crate::cursor::PageArgs,
+                ) -> Result<crate::cursor::Page<crate::#db_module_ident::Model>, crate::cursor::PageError>
+                where
+                    C: ::sea_orm::ConnectionTrait<'a>,
+                {
+                    let rows = Self::page_query(key, args)?.all(db).await?;
+
+                    Ok(crate::cursor::Page::from_rows(key, #query, args, rows, |row| {
+                        ::serde_json::json!([ #( row.#field_idents ),* ])
+                    }))
+                }
+
+                /// Fetches a page of the rows of `select`, such as the rows of a scope, like [`page`](Self::page).
+                pub async fn page_of<'a, C>(
+                    db: &'a C,
+                    select: ::sea_orm::Select<Self>,
+                    key: &crate::cursor::CursorKey,
+                    args: &crate::cursor::PageArgs,
+                ) -> Result<crate::cursor::Page<crate::#db_module_ident::Model>, crate::cursor::PageError>
+                where
+                    C: ::sea_orm::ConnectionTrait<'a>,
+                {
+                    let rows = Self::page_query_of(select, key, args)?.all(db).await?;
+
+                    Ok(crate::cursor::Page::from_rows(key, #query, args, rows, |row| {
+                        ::serde_json::json!([ #( row.#field_idents ),* ])
+                    }))
+                }
+
+                /// Returns the query of [`page`](Self::page), selecting a row more than the size of the page
+                /// to tell whether another page follows.
+                pub fn page_query(
+                    key: &crate::cursor::CursorKey,
+                    args: &crate::cursor::PageArgs,
+                ) -> Result<::sea_orm::Select<Self>, crate::cursor::PageError> {
+                    use ::sea_orm::EntityTrait;
+
+                    Self::page_query_of(Self::find(), key, args)
+                }
+
+                /// Returns the query of [`page_of`](Self::page_of), paging the rows of `select`.
+                pub fn page_query_of(
+                    select: ::sea_orm::Select<Self>,
+                    key: &crate::cursor::CursorKey,
+                    args: &crate::cursor::PageArgs,
+                ) -> Result<::sea_orm::Select<Self>, crate::cursor::PageError> {
+                    Self::cursor_query(select, key, #query, args)
+                }
+
+                /// Pages the rows of `select` from the cursor of `args`, which `query` must have returned.
+                fn cursor_query(
+                    select: ::sea_orm::Select<Self>,
+                    key: &crate::cursor::CursorKey,
+                    query: &str,
+                    args: &crate::cursor::PageArgs,
+                ) -> Result<::sea_orm::Select<Self>, crate::cursor::PageError> {
+                    use ::sea_orm::{ColumnTrait, QueryFilter, QueryOrder, QueryTrait};
+
+                    let order = if args.is_backward() {
+                        ::sea_orm::Order::Desc
+                    } else {
+                        ::sea_orm::Order::Asc
+                    };
+                    let mut select = select
+                        #( .order_by(crate::#db_module_ident::Column::#column_idents, order.clone()) )*;
+
+                    if let Some(cursor) = args.cursor() {
+                        let values: Vec<::serde_json::Value> = ::serde_json::from_value(key.decode(query, cursor)?)
+                            .map_err(|_| crate::cursor::CursorError::Malformed)?;
+                        let mut values = values.into_iter();
+                        #(
+                            let #field_idents = crate::cursor::decode_value(
+                                |model: &crate::#db_module_ident::Model| &model.#field_idents,
+                                values.next().ok_or(crate::cursor::CursorError::Malformed)?,
+                            )?;
+                        )*
+
+                        select = if args.is_backward() {
+                            select.filter(#before_condition)
+                        } else {
+                            select.filter(#after_condition)
+                        };
+                    }
+
+                    QueryTrait::query(&mut select).limit(args.size() + 1);
+
+                    Ok(select)
+                }
+            }
+        )
+    }
+
+    /// Compiles a paginated query of each projection of a table, selecting only its columns, in the order of
+    /// [`compile_page_query`](Self::compile_page_query), whose key columns every projection includes.
+    fn compile_projection_queries(&self, model: &Model, table: &DatabaseTable) -> TokenStream {
+        let db_module_ident = rust_ident(&table.name);
+        let key_idents: Vec<_> = table
+            .order_by
+            .iter()
+            .map(String::as_str)
+            .chain(iter::once(
+                table
+                    .primary_key()
+                    .map_or("id", |column| column.name.as_str()),
+            ))
+            .map(rust_ident)
+            .collect();
+
+        let queries = table.projections.iter().map(|projection: &DatabaseProjection| {
+            let ident = format_ident!("{}", projection.name);
+            let list_ident = format_ident!("list_{}", projection_plural(&table.name, &projection.name));
+            let query = format!("{}.{}", table.name, list_ident);
+            let column_idents = projection.columns.iter().map(|column| column_variant_ident(column));
+            let doc = format!(
+                " Fetches a page of `{}` rows like [`page`](Self::page), selecting only the columns of [`{}`](crate::projection::{}).",
+                model.name, projection.name, projection.name
+            );
+
+            quote!(
+                #[doc = #doc]
+                pub async fn #list_ident<'a, C>(
+                    db: &'a C,
+                    key: &crate::cursor::CursorKey,
+                    args: &crate::cursor::PageArgs,
+                ) -> Result<crate::cursor::Page<crate::projection::#ident>, crate::cursor::PageError>
+                where
+                    C: ::sea_orm::ConnectionTrait<'a>,
+                {
+                    use ::sea_orm::{EntityTrait, QuerySelect};
+
+                    let rows = Self::cursor_query(Self::find(), key, #query, args)?
+                        .select_only()
+                        #( .column(crate::#db_module_ident::Column::#column_idents) )*
+                        .into_model::<crate::projection::#ident>()
+                        .all(db)
+                        .await?;
+
+                    Ok(crate::cursor::Page::from_rows(key, #query, args, rows, |row| {
+                        ::serde_json::json!([ #( row.#key_idents ),* ])
+                    }))
+                }
+            )
+        });
+
+        quote!(
+            impl crate::#db_module_ident::Entity {
+                #( #queries )*
+            }
+        )
+    }
+
+    /// Compiles a `factories` module with a function per database table building an active model of it,
+    /// filling the required columns with placeholder values.
+    ///
+    /// Tables with required references also get a `<table>_with_parents` function, which inserts the referenced rows
+    /// with their own factories first and fills the columns referencing them.
+    ///
+    /// The factories are shared by the smoke tests and benchmarks.
+    pub fn compile_factories(&self) -> String {
+        let factories = self.database_tables().into_iter().map(|(_, table)| {
+            let db_module_ident = rust_ident(&table.name);
+            let doc = format!(" Builds a `{}` row with placeholder values.", table.name);
+            let (active_values, _) = Self::factory_values(table);
+            let with_parents = self
+                .factory_parents(table)
+                .ok()
+                .filter(|parents| !parents.is_empty())
+                .map(|parents| self.compile_factory_with_parents(table, &parents));
+
+            quote!(
+                #[doc = #doc]
+                #[allow(dead_code)]
+                pub fn #db_module_ident() -> ::database::#db_module_ident::ActiveModel {
+                    ::database::#db_module_ident::ActiveModel {
+                        #( #active_values, )*
+                        ..Default::default()
+                    }
+                }
+
+                #with_parents
+            )
+        });
+
+        let unique_key = self
+            .database_tables()
+            .into_iter()
+            .any(|(_, table)| table.has_string_key())
+            .then(|| {
+                quote!(
+                    /// Returns a key no other row built by the factories has, keeping the last `max_len` characters.
+                    fn unique_key(max_len: Option<usize>) -> String {
+                        static NEXT: ::std::sync::atomic::AtomicU64 =
+                            ::std::sync::atomic::AtomicU64::new(0);
+
+                        // Nanoseconds keep keys of earlier runs apart, the counter keeps keys of the same nanosecond apart
+                        let nanos = ::std::time::SystemTime::now()
+                            .duration_since(::std::time::UNIX_EPOCH)
+                            .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+                        let mut value = nanos.wrapping_add(
+                            NEXT.fetch_add(1, ::std::sync::atomic::Ordering::Relaxed),
+                        );
+                        let mut key = Vec::new();
+                        while value > 0 && key.len() < max_len.unwrap_or(usize::MAX) {
+                            key.push(
+                                b"0123456789abcdefghijklmnopqrstuvwxyz"[(value % 36) as usize],
+                            );
+                            value /= 36;
+                        }
+                        key.reverse();
+
+                        String::from_utf8(key).expect("keys are ascii")
+                    }
+                )
+            });
+
+        quote!(
+            mod factories {
+                #unique_key
+
+                #( #factories )*
+            }
+        )
+        .to_string()
+    }
+
+    /// Compiles the `<table>_with_parents` factory of `table`, inserting a row of each table of `parents` first.
+    fn compile_factory_with_parents(
+        &self,
+        table: &DatabaseTable,
+        parents: &[(&DatabaseColumn, &DatabaseTable)],
+    ) -> TokenStream {
+        let db_module_ident = rust_ident(&table.name);
+        let fn_ident = format_ident!("{}_with_parents", table.name);
+        let doc = format!(
+            " Builds a `{}` row with placeholder values, inserting the rows it references into `db` first.",
+            table.name
+        );
+        let (parent_idents, inserts): (Vec<_>, Vec<_>) = parents
+            .iter()
+            .map(|(column, parent)| {
+                let parent_ident = format_ident!("{}_row", column.name);
+                let parent_module_ident = rust_ident(&parent.name);
+                let factory = match self.factory_parents(parent) {
+                    Ok(grandparents) if !grandparents.is_empty() => {
+                        let fn_ident = format_ident!("{}_with_parents", parent.name);
+                        quote!(#fn_ident(db).await)
+                    }
+                    _ => quote!(#parent_module_ident()),
+                };
+                // Rows of partitioned tables can only be inserted once the partition holding them exists
+                let ensure_partitions = parent.partition.as_ref().map(|_| {
+                    quote!(
+                        ::database::#parent_module_ident::Entity::ensure_partitions(db, ::chrono::Utc::now().naive_utc().date())
+                            .await
+                            .expect("creating partitions failed");
+                    )
+                });
+                let message = format!("inserting the {} row referenced by {}.{} failed", parent.name, table.name, column.name);
+
+                (
+                    parent_ident.clone(),
+                    quote!(
+                        #ensure_partitions
+                        let #parent_ident = ::database::sea_orm::ActiveModelTrait::insert(#factory, db)
+                            .await
+                            .expect(#message);
+                    ),
+                )
+            })
+            .unzip();
+        let values = parents.iter().zip(&parent_idents).map(|((column, _), parent_ident)| {
+            let column_ident = rust_ident(&column.name);
+            let referenced_ident = rust_ident(&column.references.as_ref().expect("parents are referenced").1);
+            quote!(#column_ident: ::database::sea_orm::Set(#parent_ident.#referenced_ident.unwrap()))
+        });
+
+        quote!(
+            #[doc = #doc]
+            #[allow(dead_code)]
+            pub async fn #fn_ident(db: &::database::sea_orm::DatabaseConnection) -> ::database::#db_module_ident::ActiveModel {
+                #( #inserts )*
+
+                ::database::#db_module_ident::ActiveModel {
+                    #( #values, )*
+                    ..#db_module_ident()
+                }
+            }
+        )
+    }
+
+    /// Returns the columns of `table` whose factory inserts the row they reference first, along with the referenced tables,
+    /// or the reason rows of `table` cannot be built when a referenced row cannot be.
+    fn factory_parents<'a>(
+        &'a self,
+        table: &'a DatabaseTable,
+    ) -> Result<Vec<(&'a DatabaseColumn, &'a DatabaseTable)>, String> {
+        self.factory_parents_in(table, &mut vec![table.name.as_str()])
+    }
+
+    /// Returns the [`factory_parents`](Self::factory_parents) of `table`, referenced from the tables of `path`,
+    /// whose rows cannot be inserted before a row referencing them.
+    fn factory_parents_in<'a>(
+        &'a self,
+        table: &'a DatabaseTable,
+        path: &mut Vec<&'a str>,
+    ) -> Result<Vec<(&'a DatabaseColumn, &'a DatabaseTable)>, String> {
+        let tables = self.database_tables();
+        let mut parents = Vec::new();
+        for column in table
+            .columns
+            .iter()
+            .filter(|column| Self::is_factory_column(column) && !column.cross_database)
+        {
+            let referenced = match &column.references {
+                Some((referenced, _)) => referenced,
+                None => continue,
+            };
+            let requires_related_row =
+                || format!("column '{}' requires a related row", column.name);
+            let parent = tables
+                .iter()
+                .map(|(_, table)| *table)
+                .find(|parent| &parent.name == referenced && !path.contains(&parent.name.as_str()))
+                .filter(|parent| Self::factory_values(parent).1.is_none())
+                .ok_or_else(requires_related_row)?;
+            path.push(&parent.name);
+            self.factory_parents_in(parent, path)
+                .map_err(|_| requires_related_row())?;
+            path.pop();
+            parents.push((column, parent));
+        }
+
+        Ok(parents)
+    }
+
+    /// Returns the reason rows of `table` built by its factories cannot be inserted, if any.
+    fn factory_ignore_reason(&self, table: &DatabaseTable) -> Option<String> {
+        Self::factory_values(table)
+            .1
+            .or_else(|| self.factory_parents(table).err())
+    }
+
+    /// Returns the placeholder values of the required columns of a table,
+    /// along with the reason rows built from them cannot be inserted, if any.
+    ///
+    /// Normalized columns get placeholders their normalizers change, and slugs are left to be derived
+    /// from the placeholder of their source column. Columns referencing another row are left to
+    /// the [`factory_parents`](Self::factory_parents).
+    fn factory_values(table: &DatabaseTable) -> (Vec<TokenStream>, Option<String>) {
+        let mut ignore_reason = None;
+        let active_values = table
+            .columns
+            .iter()
+            .filter(|column| Self::is_factory_column(column))
+            .filter(|column| Self::derived_slug_source(table, column).is_none())
+            .filter(|column| column.references.is_none() || column.cross_database)
+            .filter_map(|column| {
+                let column_ident = rust_ident(&column.name);
+                match Self::placeholder_value(column) {
+                    // Maps are set directly, as the type of their default value is only known from the entity field
+                    Some(value) if column.ty == DatabaseType::Jsonb => Some(quote!(
+                        #column_ident: ::database::sea_orm::Set(#value)
+                    )),
+                    Some(value) => Some(quote!(
+                        #column_ident: ::database::sea_orm::entity::IntoActiveValue::into_active_value(#value).into()
+                    )),
+                    None => {
+                        ignore_reason = Some(format!(
+                            "column '{}' has no placeholder value for type {}",
+                            column.name, column.ty
+                        ));
+                        None
+                    }
+                }
+            })
+            .chain(table.primary_key().filter(|_| table.has_string_key()).map(|column| {
+                let column_ident = rust_ident(&column.name);
+                let max_len = match column.ty {
+                    DatabaseType::Text(Some(max_len)) => {
+                        let max_len = Literal::usize_unsuffixed(max_len as usize);
+                        quote!(Some(#max_len))
+                    }
+                    _ => quote!(None),
+                };
+                quote!(#column_ident: ::database::sea_orm::Set(unique_key(#max_len)))
+            }))
+            .collect();
+        // Databases created for tests have no external tables, as syncs never create them
+        if table.external {
+            ignore_reason = Some("table is external".to_string());
+        }
+
+        (active_values, ignore_reason)
+    }
+
+    /// Compiles one smoke test per database table, used by the generated test harness.
+    ///
+    /// Each test inserts a row built by its [factory](Self::compile_factories) and reads it back by its id.
+    /// Tables which can be upserted also get a test inserting a row in a batch and upserting it.
+    /// Tables with normalized columns also get a test checking the values their factory rows are saved with.
+    /// Versioned tables also get a test running two updates of the same version concurrently,
+    /// of which only one may apply.
+    /// Tables with idempotent creates also get a test submitting the same create twice, which must create one row.
+    /// Tables with columns which cannot be filled with a placeholder value are marked as ignored.
+    pub fn compile_smoke_tests(&self) -> String {
+        let mut code = self.compile_factories();
+        code.push('\n');
+
+        // The statements of the tables are built without a database, so their test runs without one
+        let prefixes = self
+            .created_tables()
+            .into_iter()
+            .map(|table| format!("CREATE TABLE IF NOT EXISTS \"{}\" (", table.name));
+        let expanded = quote!(
+            #[test]
+            fn create_table_statements() {
+                let statements: Vec<String> = ::database::ddl::create_table_statements()
+                    .iter()
+                    .map(|statement| {
+                        statement.to_string(::database::sea_orm::sea_query::PostgresQueryBuilder)
+                    })
+                    .collect();
+                let prefixes: Vec<&str> = vec![ #( #prefixes ),* ];
+                assert_eq!(statements.len(), prefixes.len());
+                for (statement, prefix) in statements.iter().zip(prefixes) {
+                    assert!(
+                        statement.starts_with(prefix),
+                        "unexpected statement {}",
+                        statement
+                    );
+                }
+            }
+        );
+        writeln!(code, "{}", expanded).unwrap();
+
+        for (_, table) in self.database_tables() {
+            let db_module_ident = rust_ident(&table.name);
+            let key_field = TableKey::of(table).field;
+            let test_ident = format_ident!("{}_insert_and_find", table.name);
+            let not_found_message = format!("inserted {} row was not found", table.name);
+
+            let ignore = self
+                .factory_ignore_reason(table)
+                .map(|reason| quote!(#[ignore = #reason]));
+            // Rows referencing other rows are built once the rows they reference are inserted
+            let factory = match self.factory_parents(table) {
+                Ok(parents) if !parents.is_empty() => {
+                    let fn_ident = format_ident!("{}_with_parents", table.name);
+                    quote!(factories::#fn_ident(&db).await)
+                }
+                _ => quote!(factories::#db_module_ident()),
+            };
+            // Rows of partitioned tables can only be inserted once the partition holding them exists
+            let ensure_partitions = table.partition.as_ref().map(|_| {
+                quote!(
+                    ::database::#db_module_ident::Entity::ensure_partitions(&db, ::chrono::Utc::now().naive_utc().date())
+                        .await
+                        .expect("creating partitions failed");
+                )
+            });
+
+            let expanded = quote!(
+                #[::tokio::test]
+                #ignore
+                async fn #test_ident() {
+                    let (db, _database) = common::test_db().await;
+                    #ensure_partitions
+
+                    let inserted = ::database::sea_orm::ActiveModelTrait::insert(#factory, &db)
+                        .await
+                        .expect("insert failed");
+
+                    let found = <::database::#db_module_ident::Entity as ::database::sea_orm::EntityTrait>::find_by_id(inserted.#key_field.unwrap())
+                        .one(&db)
+                        .await
+                        .expect("find failed");
+                    assert!(found.is_some(), #not_found_message);
+                }
+            );
+
+            writeln!(code, "{}", expanded).unwrap();
+
+            if table.upsert_target().is_some() {
+                let test_ident = format_ident!("{}_batch_insert_and_upsert", table.name);
+                // Factories build rows with a new `String` key each, while `Uuid` ids are left to the database
+                let active_model = if table.has_string_key() {
+                    quote!(let active_model = #factory;)
+                } else {
+                    quote!(
+                        let active_model = ::database::#db_module_ident::ActiveModel {
+                            id: ::database::sea_orm::Set(::uuid::Uuid::new_v4()),
+                            ..#factory
+                        };
+                    )
+                };
+
+                let expanded = quote!(
+                    #[::tokio::test]
+                    #ignore
+                    async fn #test_ident() {
+                        let (db, _database) = common::test_db().await;
+
+                        #active_model
+                        let inserted = ::database::#db_module_ident::Entity::insert_batch(&db, vec![active_model.clone()])
+                            .await
+                            .expect("batch insert failed");
+                        assert_eq!(inserted, 1);
+
+                        let result = ::database::#db_module_ident::Entity::upsert_batch(&db, vec![active_model])
+                            .await
+                            .expect("upsert failed");
+                        assert_eq!(result, ::database::batch::UpsertResult { inserted: 0, updated: 1 });
+                    }
+                );
+
+                writeln!(code, "{}", expanded).unwrap();
+            }
+
+            let normalized: Vec<_> = table
+                .columns
+                .iter()
+                .filter_map(|column| Some((column, Self::normalized_placeholder(table, column)?)))
+                .collect();
+            if !table.readonly && !normalized.is_empty() {
+                let test_ident = format_ident!("{}_normalized_before_save", table.name);
+                // Unique slugs may be suffixed when the factory row was inserted before
+                let unique_slug = normalized
+                    .iter()
+                    .map(|(column, _)| column)
+                    .find(|column| column.unique && slug_source(column).is_some());
+                let insert = match unique_slug {
+                    Some(column) => {
+                        let insert_ident = format_ident!("insert_unique_{}", column.name);
+                        quote!(::database::#db_module_ident::Entity::#insert_ident(&db, #factory))
+                    }
+                    None => quote!(::database::sea_orm::ActiveModelTrait::insert(#factory, &db)),
+                };
+                let assertions = normalized.iter().map(|(column, expected)| {
+                    let column_ident = rust_ident(&column.name);
+                    if Some(column) == unique_slug {
+                        quote!(assert!(found.#column_ident.starts_with(&#expected), "unexpected {}", found.#column_ident);)
+                    } else {
+                        quote!(assert_eq!(found.#column_ident, #expected);)
+                    }
+                });
+
+                let expanded = quote!(
+                    #[::tokio::test]
+                    #ignore
+                    async fn #test_ident() {
+                        let (db, _database) = common::test_db().await;
+                        #ensure_partitions
+
+                        let inserted = #insert.await.expect("insert failed");
+
+                        let found = <::database::#db_module_ident::Entity as ::database::sea_orm::EntityTrait>::find_by_id(inserted.#key_field.unwrap())
+                            .one(&db)
+                            .await
+                            .expect("find failed")
+                            .expect(#not_found_message);
+                        #( #assertions )*
+                    }
+                );
+
+                writeln!(code, "{}", expanded).unwrap();
+            }
+
+            if table.idempotent_create {
+                let test_ident = format_ident!("{}_idempotent_create_replays", table.name);
+
+                let expanded = quote!(
+                    #[::tokio::test]
+                    #ignore
+                    async fn #test_ident() {
+                        let (db, _database) = common::test_db().await;
+                        #ensure_partitions
+
+                        // Submits the create twice with the same key, as a client retrying after a timeout
+                        let key = ::uuid::Uuid::new_v4().to_string();
+                        let ttl = ::database::idempotency_keys::DEFAULT_TTL;
+                        let created = ::database::#db_module_ident::Entity::insert_idempotent(&db, &key, "request", ttl, #factory)
+                            .await
+                            .expect("create failed")
+                            .expect(#not_found_message);
+                        let replayed = ::database::#db_module_ident::Entity::insert_idempotent(&db, &key, "request", ttl, #factory)
+                            .await
+                            .expect("retried create failed");
+                        assert_eq!(replayed.map(|model| model.#key_field), Some(created.#key_field));
+
+                        let conflict = ::database::#db_module_ident::Entity::insert_idempotent(&db, &key, "another request", ttl, #factory).await;
+                        assert!(
+                            matches!(conflict, Err(::database::idempotency_keys::IdempotencyError::Conflict)),
+                            "expected a conflict, got {:?}",
+                            conflict
+                        );
+                    }
+                );
+
+                writeln!(code, "{}", expanded).unwrap();
+            }
+
+            if table.versioned {
+                let test_ident = format_ident!("{}_concurrent_update_is_stale", table.name);
+
+                let expanded = quote!(
+                    #[::tokio::test]
+                    #ignore
+                    async fn #test_ident() {
+                        let (db, _database) = common::test_db().await;
+                        #ensure_partitions
+
+                        let inserted = ::database::sea_orm::ActiveModelTrait::insert(#factory, &db)
+                            .await
+                            .expect("insert failed");
+
+                        // Both updates read version 1, so only one of them may apply
+                        let (first, second) = ::tokio::join!(
+                            ::database::#db_module_ident::Entity::update_versioned(&db, inserted.clone()),
+                            ::database::#db_module_ident::Entity::update_versioned(&db, inserted),
+                        );
+                        let (updated, stale) = match (first, second) {
+                            (Ok(updated), Err(err)) | (Err(err), Ok(updated)) => (updated, err),
+                            (first, second) => panic!("expected exactly one update to apply, got {:?} and {:?}", first, second),
+                        };
+                        assert_eq!(updated.version, 2);
+                        assert!(
+                            matches!(stale, ::database::version::VersionedUpdateError::Stale(ref err) if err.version == 1),
+                            "expected a stale version error, got {:?}",
+                            stale
+                        );
+                    }
+                );
+
+                writeln!(code, "{}", expanded).unwrap();
+            }
+
+            for (column, has_many, _) in self.loaded_columns(table) {
+                if let Some(expanded) = has_many
+                    .then(|| self.compile_data_loader_test(table, column))
+                    .flatten()
+                {
+                    writeln!(code, "{}", expanded).unwrap();
+                }
+            }
+        }
+
+        code.trim().to_string()
+    }
+
+    /// Compiles a test resolving the rows referencing 50 rows of the table referenced by `column` through their data loaders,
+    /// which must run a single query per direction of the relation, if rows of both tables can be built.
+    fn compile_data_loader_test(
+        &self,
+        table: &DatabaseTable,
+        column: &DatabaseColumn,
+    ) -> Option<TokenStream> {
+        if column.cross_database {
+            return None;
+        }
+        let (parent_name, parent_column) = column.references.as_ref()?;
+        let tables = self.database_tables();
+        let (_, parent) = tables
+            .iter()
+            .find(|(_, parent)| &parent.name == parent_name)?;
+        let (parent_column, _, _) = self
+            .loaded_columns(parent)
+            .into_iter()
+            .find(|(referenced, _, _)| &referenced.name == parent_column)?;
+        // Rows of partitioned tables need their partitions, which the smoke tests cover
+        if [table, *parent]
+            .iter()
+            .any(|table| table.partition.is_some() || self.factory_ignore_reason(table).is_some())
+        {
+            return None;
+        }
+
+        let factory = |table: &DatabaseTable| match self.factory_parents(table) {
+            Ok(parents) if !parents.is_empty() => {
+                let fn_ident = format_ident!("{}_with_parents", table.name);
+                quote!(factories::#fn_ident(&db).await)
+            }
+            _ => {
+                let fn_ident = rust_ident(&table.name);
+                quote!(factories::#fn_ident())
+            }
+        };
+        let (parent_factory, child_factory) = (factory(parent), factory(table));
+        let db_module_ident = rust_ident(&table.name);
+        let test_ident = format_ident!("{}_by_{}_loads_in_one_query", table.name, column.name);
+        let (column_ident, parent_column_ident) =
+            (rust_ident(&column.name), rust_ident(&parent_column.name));
+        let child_key = if column.nullable {
+            quote!(Some(key.clone()))
+        } else {
+            quote!(key.clone())
+        };
+        let children_loader = Self::data_loader_ident(table, column);
+        let parent_loader = Self::data_loader_ident(parent, parent_column);
+        let children_message = format!(
+            "loading the {} rows of 50 {} rows ran more than one query",
+            table.name, parent.name
+        );
+        let parent_message = format!(
+            "loading 50 {} rows referenced by {} ran more than one query",
+            parent.name, table.name
+        );
+
+        Some(quote!(
+            #[cfg(feature = "graphql")]
+            #[::tokio::test]
+            async fn #test_ident() {
+                let (db, _database) = common::test_db().await;
+                let mut keys = Vec::new();
+                for _ in 0..50 {
+                    let parent = ::database::sea_orm::ActiveModelTrait::insert(#parent_factory, &db)
+                        .await
+                        .expect("insert failed");
+                    let key = parent.#parent_column_ident.unwrap();
+                    let child = ::database::#db_module_ident::ActiveModel {
+                        #column_ident: ::database::sea_orm::Set(#child_key),
+                        ..#child_factory
+                    };
+                    ::database::sea_orm::ActiveModelTrait::insert(child, &db)
+                        .await
+                        .expect("insert failed");
+                    keys.push(key);
+                }
+                let config = ::database::loader::LoaderConfig::default();
+
+                // Every resolver requests its own key, which the loaders batch into one query
+                let loader = config.data_loader(::database::loader::#children_loader::new(db.clone()));
+                let queries = common::queries();
+                let children = ::futures_util::future::join_all(keys.iter().map(|key| loader.load_one(key.clone()))).await;
+                assert_eq!(common::queries() - queries, 1, #children_message);
+                for rows in children {
+                    assert_eq!(rows.expect("load failed").map_or(0, |rows| rows.len()), 1);
+                }
+
+                let loader = config.data_loader(::database::loader::#parent_loader::new(db.clone()));
+                let queries = common::queries();
+                let parents = ::futures_util::future::join_all(keys.iter().map(|key| loader.load_one(key.clone()))).await;
+                assert_eq!(common::queries() - queries, 1, #parent_message);
+                assert!(parents.into_iter().all(|row| row.expect("load failed").is_some()));
+            }
+        ))
+    }
+
+    /// Compiles the benchmarks of the generated queries of each database table, used by the generated benches.
+    ///
+    /// Each table gets benchmarks inserting a row, inserting a batch of rows, finding a row by its id,
+    /// and fetching a page of its rows after seeding the table, all with rows built by its [factory](Self::compile_factories).
+    /// Readonly tables, and tables whose rows cannot be built or would violate a unique constraint, are skipped.
+    pub fn compile_benches(&self) -> String {
+        let mut code = self.compile_factories();
+        code.push('\n');
+
+        let mut calls = Vec::new();
+        for (_, table) in self.database_tables() {
+            let table_name = &table.name;
+            let skip_reason = if table.readonly {
+                Some("table is readonly".to_string())
+            } else {
+                Self::factory_values(table).1.or_else(|| {
+                    // Benchmarks build their rows without a connection, so they cannot insert referenced rows first
+                    let referencing = table.columns.iter().find(|column| {
+                        Self::is_factory_column(column)
+                            && column.references.is_some()
+                            && !column.cross_database
+                    });
+                    if let Some(column) = referencing {
+                        return Some(format!("column '{}' requires a related row", column.name));
+                    }
+                    table
+                        .columns
+                        .iter()
+                        .find(|column| {
+                            column.unique && !column.primary_key && column.ty != DatabaseType::Uuid
+                        })
+                        .map(|column| format!("column '{}' is unique", column.name))
+                })
+            };
+            // Seeding needs more unique keys than short `String` keys have
+            let skip_reason = skip_reason.or_else(|| {
+                table
+                    .primary_key()
+                    .filter(|column| matches!(column.ty, DatabaseType::Text(Some(max_len)) if max_len < MIN_BENCH_KEY_LEN))
+                    .map(|column| format!("primary key '{}' is too short for unique keys", column.name))
+            });
+            if let Some(reason) = skip_reason {
+                let message = format!("skipping {} benchmarks: {}", table_name, reason);
+                calls.push(quote!(eprintln!(#message);));
+                continue;
+            }
+
+            let db_module_ident = rust_ident(&table.name);
+            let key = TableKey::of(table);
+            let key_field = &key.field;
+            let borrow_id = table
+                .has_string_key()
+                .then(|| quote!(let id = id.as_str();));
+            let owned_id = key.owned(quote!(id));
+            let bench_ident = format_ident!("bench_{}", table.name);
+            calls.push(quote!(#bench_ident(c, runtime, db);));
+            let ensure_partitions = table.partition.as_ref().map(|_| {
+                quote!(
+                    runtime
+                        .block_on(::database::#db_module_ident::Entity::ensure_partitions(db, ::chrono::Utc::now().naive_utc().date()))
+                        .expect("creating partitions failed");
+                )
+            });
+
+            let expanded = quote!(
+                fn #bench_ident(
+                    c: &mut ::criterion::Criterion,
+                    runtime: &::tokio::runtime::Runtime,
+                    db: &::database::sea_orm::DatabaseConnection,
+                ) {
+                    let mut group = c.benchmark_group(#table_name);
+                    runtime.block_on(common::truncate(db, #table_name));
+                    #ensure_partitions
+
+                    group.bench_function("insert", |b| {
+                        b.to_async(runtime).iter(|| async move {
+                            ::database::sea_orm::ActiveModelTrait::insert(factories::#db_module_ident(), db)
+                                .await
+                                .expect("insert failed")
+                        })
+                    });
+
+                    group.bench_function("insert_batch_1k", |b| {
+                        b.to_async(runtime).iter_batched(
+                            || (0..common::BATCH_ROWS).map(|_| factories::#db_module_ident()).collect::<Vec<_>>(),
+                            |models| async move {
+                                ::database::#db_module_ident::Entity::insert_batch(db, models)
+                                    .await
+                                    .expect("batch insert failed")
+                            },
+                            ::criterion::BatchSize::LargeInput,
+                        )
+                    });
+
+                    let inserted = runtime
+                        .block_on(::database::sea_orm::ActiveModelTrait::insert(factories::#db_module_ident(), db))
+                        .expect("insert failed");
+                    let id = inserted.#key_field.unwrap();
+                    #borrow_id
+                    group.bench_function("find_by_id", |b| {
+                        b.to_async(runtime).iter(|| async move {
+                            <::database::#db_module_ident::Entity as ::database::sea_orm::EntityTrait>::find_by_id(#owned_id)
+                                .one(db)
+                                .await
+                                .expect("find failed")
+                        })
+                    });
+
+                    runtime.block_on(async {
+                        common::truncate(db, #table_name).await;
+                        ::database::#db_module_ident::Entity::insert_batch(
+                            db,
+                            (0..common::SEED_ROWS).map(|_| factories::#db_module_ident()),
+                        )
+                        .await
+                        .expect("seeding failed");
+                    });
+                    let key = &::database::cursor::CursorKey::new("awto-bench");
+                    let args = &::database::cursor::PageArgs::default();
+                    group.bench_function("page_100k", |b| {
+                        b.to_async(runtime).iter(|| async move {
+                            ::database::#db_module_ident::Entity::page(db, key, args)
+                                .await
+                                .expect("page failed")
+                        })
+                    });
+
+                    group.finish();
+                }
+            );
+
+            writeln!(code, "{}", expanded).unwrap();
+        }
+
+        let expanded = quote!(
+            #[allow(unused_variables)]
+            fn benches(
+                c: &mut ::criterion::Criterion,
+                runtime: &::tokio::runtime::Runtime,
+                db: &::database::sea_orm::DatabaseConnection,
+            ) {
+                #( #calls )*
+            }
+        );
+        writeln!(code, "{}", expanded).unwrap();
+
+        code.trim().to_string()
+    }
+
+    /// Returns whether factories fill `column`, as it is required.
+    fn is_factory_column(column: &DatabaseColumn) -> bool {
+        !column.nullable
+            && column.default.is_none()
+            && !column.primary_key
+            && column.computed.is_none()
+    }
+
+    /// Returns the source column of the slugs of `column` when factories leave them to be derived from it.
+    fn derived_slug_source<'t>(
+        table: &'t DatabaseTable,
+        column: &DatabaseColumn,
+    ) -> Option<&'t DatabaseColumn> {
+        let source = slug_source(column)?;
+        table.columns.iter().find(|other| {
+            other.name == source
+                && Self::is_factory_column(other)
+                && other.references.is_none()
+                && Self::placeholder_text(other).is_some()
+        })
+    }
+
+    /// Returns the placeholder of a text column, which is uppercase and padded with spaces when
+    /// the column is lowercased and trimmed so the normalization shows.
+    fn placeholder_text(column: &DatabaseColumn) -> Option<String> {
+        let max_len = match column.ty {
+            DatabaseType::Text(max_len) => max_len,
+            _ => return None,
+        };
+        let len = max_len
+            .map(|max_len| max_len.max(0) as usize)
+            .unwrap_or(4)
+            .min(4);
+        let mut text = if column.normalize.contains(&Normalizer::Lowercase) {
+            "AWTO"[..len].to_string()
+        } else {
+            "awto"[..len].to_string()
+        };
+        if column.normalize.contains(&Normalizer::Trim) {
+            text = format!(" {} ", text);
+        }
+
+        Some(text)
+    }
+
+    /// Returns the value `column` is expected to be saved with by a row of its factory,
+    /// or `None` when it is not normalized or not filled by the factory.
+    fn normalized_placeholder(
+        table: &DatabaseTable,
+        column: &DatabaseColumn,
+    ) -> Option<TokenStream> {
+        if column.normalize.is_empty()
+            || !Self::is_factory_column(column)
+            || column.references.is_some()
+        {
+            return None;
+        }
+        let mut value = match Self::derived_slug_source(table, column) {
+            Some(source) => awto::database::slugify(&Self::placeholder_text(source)?),
+            None if slug_source(column).is_some() => return None,
+            None => Self::placeholder_text(column)?,
+        };
+
+        let mut expr = None;
+        for normalizer in &column.normalize {
+            expr = match (normalizer, expr) {
+                (Normalizer::SlugifyFrom(_), expr) => expr,
+                (Normalizer::Function(path), expr) => {
+                    let path = schema_function(path);
+                    let current = expr.unwrap_or_else(|| quote!(#value));
+                    Some(quote!(#path(&#current)))
+                }
+                (Normalizer::Lowercase, Some(expr)) => Some(quote!(#expr.to_lowercase())),
+                (Normalizer::Trim, Some(expr)) => Some(quote!(#expr.trim().to_string())),
+                (normalizer, None) => {
+                    value = normalizer.apply(&value).unwrap_or(value);
+                    None
+                }
+            };
+        }
+
+        Some(expr.unwrap_or_else(|| quote!(#value)))
+    }
+
+    fn placeholder_value(column: &DatabaseColumn) -> Option<proc_macro2::TokenStream> {
+        let value = match &column.ty {
+            DatabaseType::SmallInt => quote!(1i16),
+            DatabaseType::Integer => quote!(1i32),
+            DatabaseType::BigInt => quote!(1i64),
+            DatabaseType::Float => quote!(1.0f32),
+            DatabaseType::Double => quote!(1.0f64),
+            DatabaseType::Text(_) => {
+                let text = Self::placeholder_text(column)?;
+                quote!(#text.to_string())
+            }
+            DatabaseType::Binary => quote!(vec![0u8]),
+            DatabaseType::Timestamp => quote!(::chrono::Utc::now().naive_utc()),
+            DatabaseType::Timestamptz => {
+                quote!(::chrono::Utc::now().with_timezone(&::chrono::FixedOffset::east(0)))
+            }
+            DatabaseType::Date => quote!(::chrono::Utc::today().naive_utc()),
+            DatabaseType::Time => quote!(::chrono::Utc::now().time()),
+            DatabaseType::Bool => quote!(true),
+            DatabaseType::Uuid => quote!(::uuid::Uuid::new_v4()),
+            DatabaseType::Jsonb => quote!(::std::default::Default::default()),
+            DatabaseType::Numeric(_)
+            | DatabaseType::Money
+            | DatabaseType::Timetz
+            | DatabaseType::Hstore => return None,
+        };
+
+        Some(value)
+    }
+
+    /// Returns the documentation of the module of `table`, with the doc comment of its `model`,
+    /// a table of its columns and its relations to other tables.
+    fn module_doc(&self, model: &Model, table: &DatabaseTable) -> String {
+        let mut doc = format!(
+            "Entity of the `{}` table, generated from [`{}`](schema::{}).\n",
+            table.name, model.name, model.name
+        );
+        if let Some(model_doc) = &model.doc {
+            write!(doc, "\n{}\n", escape_markdown(model_doc)).unwrap();
+        }
+
+        doc.push_str("\n# Columns\n\n");
+        doc.push_str("| Column | Rust type | SQL type | Nullable | Default | Constraints |\n");
+        doc.push_str("| --- | --- | --- | --- | --- | --- |\n");
+        for column in with_locale_columns(table).columns {
+            let rust_type = match model.fields.iter().find(|field| field.name == column.name) {
+                Some(field) => markdown_code(&field.ty),
+                None => match column_rust_type(&column.ty) {
+                    Some(ty) => {
+                        let ty = ty.to_string().replace(' ', "").replace("<::", "<");
+                        let mut ty = ty.trim_start_matches("::").to_string();
+                        if column.nullable {
+                            ty = format!("Option<{}>", ty);
+                        }
+                        markdown_code(&ty)
+                    }
+                    None => String::new(),
+                },
+            };
+
+            let mut constraints = Vec::new();
+            if column.primary_key {
+                constraints.push("primary key".to_string());
+            }
+            if column.unique {
+                constraints.push("unique".to_string());
+            }
+            if let Some((table, col)) = &column.references {
+                let reference = markdown_code(&format!("{}({})", table, col));
+                if column.cross_database {
+                    constraints.push(format!(
+                        "references {} of another database, without a foreign key",
+                        reference
+                    ));
+                } else {
+                    let on_delete = if column.nullable {
+                        " on delete set null"
+                    } else {
+                        ""
+                    };
+                    constraints.push(format!("references {}{}", reference, on_delete));
+                }
+            }
+            if let Some(constraint) = &column.constraint {
+                constraints.push(format!("check {}", markdown_code(constraint)));
+            }
+            if let Some(computed) = &column.computed {
+                constraints.push(format!("generated as {}", markdown_code(computed)));
+            }
+            if !column.normalize.is_empty() {
+                let normalizers: Vec<String> = column
+                    .normalize
+                    .iter()
+                    .map(|normalizer| markdown_code(&normalizer.to_string()))
+                    .collect();
+                constraints.push(format!("normalized with {}", normalizers.join(", ")));
+            }
+            if let Some((_, deprecation)) = self.column_deprecation(table, &column.name) {
+                let since = deprecation
+                    .since
+                    .as_ref()
+                    .map(|since| format!(" since {}", since))
+                    .unwrap_or_default();
+                constraints.push(format!(
+                    "deprecated{}{}",
+                    since,
+                    escape_markdown_cell(&deprecation_note(Some(deprecation)))
+                ));
+            }
+
+            writeln!(
+                doc,
+                "| {} | {} | {} | {} | {} | {} |",
+                markdown_code(&column.name),
+                rust_type,
+                markdown_code(&column.ty.to_string()),
+                if column.nullable { "yes" } else { "no" },
+                column
+                    .default
+                    .as_ref()
+                    .map(|default| markdown_code(&default.to_string()))
+                    .unwrap_or_default(),
+                constraints.join(", "),
+            )
+            .unwrap();
+        }
+
+        if !table.scopes.is_empty() {
+            doc.push_str("\n# Scopes\n\n");
+            doc.push_str("| Scope | Parameters | Condition |\n");
+            doc.push_str("| --- | --- | --- |\n");
+            for scope in &table.scopes {
+                let params: Vec<String> = scope
+                    .params
+                    .iter()
+                    .map(|param| markdown_code(&format!("{}: {}", param.name, param.ty)))
+                    .collect();
+                writeln!(
+                    doc,
+                    "| [`{0}`]({1}::Entity::{0}) | {2} | {3} |",
+                    scope.name,
+                    rust_ident(&table.name),
+                    params.join(", "),
+                    markdown_code(&scope.condition),
+                )
+                .unwrap();
+            }
+        }
+
+        let mut relations = Vec::new();
+        for column in &table.columns {
+            if let Some((referenced, referenced_column)) = &column.references {
+                let relation = if column.nullable {
+                    "optionally references"
+                } else {
+                    "references"
+                };
+                // Tables of other databases are not in the crate, so they cannot be linked
+                if column.cross_database {
+                    relations.push(format!(
+                        "- `{}` {} `{}` of `{}` in another database",
+                        column.name, relation, referenced_column, referenced
+                    ));
+                } else {
+                    relations.push(format!(
+                        "- `{}` {} `{}` of [`{}`]",
+                        column.name, relation, referenced_column, referenced
+                    ));
+                }
+            }
+        }
+        for (_, other) in self.database_tables() {
+            for column in &other.columns {
+                if let Some((referenced, referenced_column)) = &column.references {
+                    if *referenced == table.name {
+                        relations.push(format!(
+                            "- `{}` is referenced by `{}` of [`{}`]",
+                            referenced_column, column.name, other.name
+                        ));
+                    }
+                }
+            }
+        }
+        if !relations.is_empty() {
+            write!(doc, "\n# Relations\n\n{}\n", relations.join("\n")).unwrap();
+        }
+
+        doc
+    }
+
+    /// Returns the documentation of the root of the generated crate, listing its models along with
+    /// the version of awto-compile and the options the crate was compiled with.
+    ///
+    /// It is written to `OUT_DIR` and included by the crate root, as included files cannot have inner attributes.
+    pub fn compile_crate_doc(&self) -> String {
+        let tables = self.database_tables();
+        let mut doc = String::from(
+            "Database entities and queries generated by awto from the models of the `schema` crate.\n\n",
+        );
+
+        doc.push_str("# Models\n\n");
+        doc.push_str("| Model | Module | Description |\n");
+        doc.push_str("| --- | --- | --- |\n");
+        for (model, table) in &tables {
+            // Only the first line of the doc comment, which is its summary, fits in a table
+            let summary = model
+                .doc
+                .as_deref()
+                .and_then(|doc| doc.lines().next())
+                .map(escape_markdown_cell)
+                .unwrap_or_default();
+            writeln!(
+                doc,
+                "| [`{0}`](schema::{0}) | [`{1}`] | {2} |",
+                model.name, table.name, summary
+            )
+            .unwrap();
+        }
+
+        let databases: BTreeSet<_> = tables.iter().map(|(_, table)| table.database()).collect();
+        let localization = match tables.first().map(|(_, table)| table.localization) {
+            Some(Localization::Table) => "translations tables",
+            _ => "columns",
+        };
+        doc.push_str("\n# Options\n\n");
+        writeln!(doc, "- awto-compile: `{}`", env!("CARGO_PKG_VERSION")).unwrap();
+        writeln!(
+            doc,
+            "- Databases: {}",
+            databases
+                .iter()
+                .map(|database| markdown_code(database))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+        .unwrap();
+        writeln!(doc, "- Localized fields stored in: {}", localization).unwrap();
+        if self.outbox_table().is_some() {
+            writeln!(doc, "- Outbox: [`{}`]", OUTBOX_TABLE).unwrap();
+        }
+        if self.idempotency_table().is_some() {
+            writeln!(doc, "- Idempotency keys: [`{}`]", IDEMPOTENCY_TABLE).unwrap();
+        }
+        if tables.iter().any(|(_, table)| table.notify) {
+            doc.push_str("- Change notifications: [`notify`]\n");
+        }
+        if tables.iter().any(|(_, table)| table.subscriptions) {
+            doc.push_str("- GraphQL subscriptions: [`subscription`]\n");
+        }
+        if tables.iter().any(|(_, table)| table.temporal.is_some()) {
+            doc.push_str("- Version history: [`temporal`]\n");
+        }
+
+        doc
+    }
+
+    /// Compiles a module per database table re-exporting the sea-orm entity generated for it,
+    /// along with the user-owned extension of the table if there is one.
+    ///
+    /// sea-orm declares the items of entities `pub`, so the entities are included into the private `entities` module
+    /// and their items re-exported with the [`visibility`](DatabaseTable::visibility) of the table.
+    /// Entities stay siblings, so the `super::<table>` paths of their relations resolve.
+    fn compile_sea_orm_modules(&self) -> Result<String, io::Error> {
+        let extensions = self.extension_modules()?;
+        let tables = self.database_tables();
+        let translations_tables = self.translations_tables();
+        let outbox_table = self.outbox_table();
+        let idempotency_table = self.idempotency_table();
+        let mut code = String::new();
+
+        write!(code, "mod entities {{").unwrap();
+        let entity_names = tables
+            .iter()
+            .map(|(_, table)| &table.name)
+            .chain(translations_tables.iter().map(|table| &table.name))
+            .chain(outbox_table.iter().map(|table| &table.name))
+            .chain(idempotency_table.iter().map(|table| &table.name));
+        for name in entity_names {
+            let ident = rust_ident(name);
+            if !self.entity_files {
+                write!(code, "pub mod {} {{", ident).unwrap();
+                write!(code, r#"    sea_orm::include_model!("{}");"#, name).unwrap();
+                write!(code, "}}").unwrap();
+            } else if ident == name.as_str() {
+                write!(code, "pub mod {};", ident).unwrap();
+            } else {
+                // Keywords are raw or suffixed identifiers, such as `r#type` or `self_`, which differ from the file name
+                write!(code, r#"#[path = "{}.rs"] pub mod {};"#, name, ident).unwrap();
+            }
+        }
+        write!(code, "}}").unwrap();
+
+        for (model, table) in tables {
+            let module_ident = rust_ident(&table.name);
+            write!(
+                code,
+                "#[doc = {}]",
+                Literal::string(&self.module_doc(model, table))
+            )
+            .unwrap();
+            write!(code, "pub mod {} {{", module_ident).unwrap();
+            // Items only used by some tables are re-exported all the same
+            write!(
+                code,
+                "    #[allow(unused_imports)] {} use crate::entities::{}::{{Column, Entity, Model, PrimaryKey, Relation}};",
+                table.visibility.entity().as_str(),
+                module_ident
+            )
+            .unwrap();
+            write!(
+                code,
+                "    #[allow(unused_imports)] {} use crate::entities::{}::ActiveModel;",
+                table.visibility.active_model().as_str(),
+                module_ident
+            )
+            .unwrap();
+            if extensions.contains(&table.name) {
+                write!(code, "    pub use crate::ext::{}::*;", module_ident).unwrap();
+            }
+            write!(code, "}}").unwrap();
+        }
+
+        for table in &translations_tables {
+            // The row column of translations tables references their table, whose name may be shortened in theirs
+            let (parent, _) = table.columns[1]
+                .references
+                .as_ref()
+                .expect("translations reference their table");
+            let doc = format!(
+                "Translations of the localized fields of [`{}`] in locales other than their default locale.",
+                parent
+            );
+            write!(code, "#[doc = {}]", Literal::string(&doc)).unwrap();
+            write!(code, "pub mod {} {{", rust_ident(&table.name)).unwrap();
+            write!(
+                code,
+                "    pub use crate::entities::{}::*;",
+                rust_ident(&table.name)
+            )
+            .unwrap();
+            write!(code, "}}").unwrap();
+        }
+
+        if outbox_table.is_some() {
+            let doc = "Changes of the rows of tables with `#[awto(events)]`, recorded in the same transaction as the change.";
+            write!(code, "#[doc = {}]", Literal::string(doc)).unwrap();
+            write!(code, "pub mod {} {{", OUTBOX_TABLE).unwrap();
+            write!(code, "    pub use crate::entities::{}::*;", OUTBOX_TABLE).unwrap();
+            code.push_str(OUTBOX_MODULE);
+            write!(code, "}}").unwrap();
+        }
+
+        if idempotency_table.is_some() {
+            let doc = "Keys of the creates of tables with `#[awto(idempotent_create)]`, claimed in the same transaction as the insert.";
+            write!(code, "#[doc = {}]", Literal::string(doc)).unwrap();
+            write!(code, "pub mod {} {{", IDEMPOTENCY_TABLE).unwrap();
+            write!(
+                code,
+                "    pub use crate::entities::{}::*;",
+                IDEMPOTENCY_TABLE
+            )
+            .unwrap();
+            code.push_str(IDEMPOTENCY_MODULE);
+            write!(code, "}}").unwrap();
+        }
+
+        Ok(code)
+    }
+
+    /// Returns every table created in the database, in the order they are created.
+    ///
+    /// Translations tables follow the tables they reference.
+    fn created_tables(&self) -> Vec<DatabaseTable> {
+        self.database_tables()
+            .into_iter()
+            .filter(|(_, table)| !table.external)
+            .map(|(_, table)| with_locale_columns(table))
+            .chain(self.outbox_table())
+            .chain(self.idempotency_table())
+            .chain(self.translations_tables())
+            .chain(self.history_tables())
+            .collect()
+    }
+
+    /// Returns whether a foreign key constraint is created for the reference of `column`.
+    ///
+    /// References to [external](DatabaseTable::external) tables have no constraint unless they set `constraint = true`,
+    /// as awto does not know whether the referenced table exists in every database.
+    /// [Cross database](DatabaseColumn::cross_database) references never have one.
+    fn has_foreign_key(&self, column: &DatabaseColumn) -> bool {
+        let (reference_table, _) = match &column.references {
+            Some(references) if !column.cross_database => references,
+            _ => return false,
+        };
+        column.reference_constraint.unwrap_or_else(|| {
+            !self
+                .database_tables()
+                .iter()
+                .any(|(_, table)| table.external && table.name == *reference_table)
+        })
+    }
+
+    /// Returns the outbox table when any table records events.
+    fn outbox_table(&self) -> Option<DatabaseTable> {
+        self.database_tables()
+            .iter()
+            .any(|(_, table)| table.events)
+            .then(outbox_table)
+    }
+
+    /// Returns the idempotency keys table when any table has idempotent creates.
+    fn idempotency_table(&self) -> Option<DatabaseTable> {
+        self.database_tables()
+            .iter()
+            .any(|(_, table)| table.idempotent_create)
+            .then(idempotency_table)
+    }
+
+    /// Returns the translations tables of the tables storing localized columns with [`Localization::Table`].
+    fn translations_tables(&self) -> Vec<DatabaseTable> {
+        self.database_tables()
+            .into_iter()
+            .filter_map(|(_, table)| translations_table(table))
+            .collect()
+    }
+
+    /// Returns the history tables of the tables with `#[awto(temporal)]`.
+    fn history_tables(&self) -> Vec<DatabaseTable> {
+        self.database_tables()
+            .into_iter()
+            .filter_map(|(_, table)| history_table(table))
+            .collect()
+    }
+
+    /// Returns the names of user-owned extension modules found in `src/ext` of the package being built.
+    ///
+    /// A cargo warning is emitted for every extension which does not belong to a database table.
+    fn extension_modules(&self) -> Result<Vec<String>, io::Error> {
+        let ext_dir = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join(EXTENSIONS_DIR);
+        if !ext_dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        println!("cargo:rerun-if-changed={}", ext_dir.display());
+
+        let mut extensions = Vec::new();
+        for entry in std::fs::read_dir(&ext_dir)? {
+            let path = entry?.path();
+            if path.extension().map(|ext| ext != "rs").unwrap_or(true) {
+                continue;
+            }
+            let name = match path.file_stem().and_then(|stem| stem.to_str()) {
+                // The cli warns about `mod.rs`, which is not compiled as the generated lib.rs declares the `ext` module
+                Some("mod") | None => continue,
+                Some(name) => name.to_string(),
+            };
+
+            if self
+                .database_tables()
+                .iter()
+                .all(|(_, table)| table.name != name)
+            {
+                Warning {
+                    id: Warning::UNREGISTERED_EXTENSION,
+                    model: None,
+                    field: None,
+                    message: format!(
+                        "extension '{}' does not belong to a registered database table",
+                        path.display()
+                    ),
+                }
+                .emit();
+            }
+
+            extensions.push(name);
+        }
+
+        Ok(extensions)
+    }
+
+    fn database_tables(&self) -> Vec<(&Model, &DatabaseTable)> {
+        self.models.iter().fold(Vec::new(), |mut acc, model| {
+            let roles = model
+                .roles
+                .iter()
+                .filter_map(|role| match role {
+                    Role::DatabaseTable(database_table) => Some((model, database_table)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+
+            acc.extend(roles);
+
+            acc
+        })
+    }
+
+    /// Returns the model of `table` along with the deprecation of its `column`, when the field of the column is deprecated.
+    fn column_deprecation(
+        &self,
+        table: &DatabaseTable,
+        column: &str,
+    ) -> Option<(&Model, &Deprecation)> {
+        let model = &self.models[*self.index.tables.get(&table.name)?];
+        let deprecation = model
+            .fields
+            .iter()
+            .find(|field| field.name == column)?
+            .deprecated
+            .as_ref()?;
+
+        Some((model, deprecation))
+    }
+
+    async fn fetch_table(
+        &self,
+        table: &DatabaseTable,
+    ) -> Result<Option<Vec<DatabaseColumn>>, Error> {
+        let rows = fetch_column_rows(&self.pool, &table.name).await?;
+        if rows.is_empty() {
+            return Ok(None);
+        }
+
+        let columns: Vec<DatabaseColumn> = rows
+            .into_iter()
+            .map(|row| {
+                let column_name = row.column_name.clone();
+                row.into_column()
+                    .ok_or_else(|| Error::UnsupportedType(table.name.clone(), column_name))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Some(columns))
+    }
+
+    /// Returns the names of the full-text search indexes created by awto for `table`.
+    async fn fetch_fulltext_indexes(&self, table: &DatabaseTable) -> Result<Vec<String>, Error> {
+        let index_names: Vec<(String,)> = sqlx::query_as(FETCH_INDEXES_QUERY)
+            .bind("public")
+            .bind(&table.name)
+            .fetch_all(&*self.pool)
+            .await
+            .map_err(Error::Sqlx)?;
+
+        let prefix = derived_name_prefix(&[&table.name, "fulltext"], "00000000_idx".len());
+        Ok(index_names
+            .into_iter()
+            .map(|(index_name,)| index_name)
+            .filter(|index_name| index_name.starts_with(&prefix))
+            .collect())
+    }
+
+    /// Returns the name of the full-text search index of `table`.
+    ///
+    /// The name contains a hash of the indexed expression, so changing the fields or language of the index
+    /// creates a new index instead of keeping a stale one.
+    fn fulltext_index_name(table: &DatabaseTable, fulltext: &DatabaseFulltext) -> String {
+        // FNV-1a, which unlike the std hasher is stable across Rust versions
+        let hash = fulltext
+            .vector_expression()
+            .bytes()
+            .fold(0x811c9dc5u32, |hash, byte| {
+                (hash ^ byte as u32).wrapping_mul(0x01000193)
+            });
+
+        derived_name(&[&table.name, "fulltext"], &format!("{:08x}_idx", hash))
+    }
+
+    fn write_fulltext_sync_sql(&self, table: &DatabaseTable, db_indexes: &[String]) -> String {
+        let mut sql = String::new();
+
+        let index_name = table
+            .fulltext
+            .as_ref()
+            .map(|fulltext| Self::fulltext_index_name(table, fulltext));
+
+        for db_index in db_indexes {
+            if Some(db_index) != index_name.as_ref() {
+                writeln!(sql, "DROP INDEX IF EXISTS {};", quote_ident(db_index)).unwrap();
+            }
+        }
+
+        if let (Some(fulltext), Some(index_name)) = (&table.fulltext, index_name) {
+            if !db_indexes.contains(&index_name) {
+                writeln!(
+                    sql,
+                    "CREATE INDEX IF NOT EXISTS {index} ON {table} USING GIN ({expression});",
+                    index = quote_ident(&index_name),
+                    table = quote_ident(&table.name),
+                    expression = fulltext.vector_expression(),
+                )
+                .unwrap();
+            }
+        }
+
+        sql
+    }
+
+    /// Returns whether the unique key of the translations `table` exists,
+    /// and whether its rows are deleted along with the row they translate.
+    async fn fetch_translations_key(&self, table: &DatabaseTable) -> Result<(bool, bool), Error> {
+        let index_names: Vec<(String,)> = sqlx::query_as(FETCH_INDEXES_QUERY)
+            .bind("public")
+            .bind(&table.name)
+            .fetch_all(&*self.pool)
+            .await
+            .map_err(Error::Sqlx)?;
+        let delete_action: Option<(String,)> = sqlx::query_as(FETCH_FOREIGN_KEY_DELETE_QUERY)
+            .bind("public")
+            .bind(&table.name)
+            .bind(derived_name(&[&table.name, &table.columns[1].name], "fkey"))
+            .fetch_optional(&*self.pool)
+            .await
+            .map_err(Error::Sqlx)?;
+
+        let key_name = derived_name(&[&table.name], "key");
+        Ok((
+            index_names
+                .into_iter()
+                .any(|(index_name,)| index_name == key_name),
+            delete_action
+                .map(|(action,)| action == "c")
+                .unwrap_or(false),
+        ))
+    }
+
+    /// Creates the unique key of the translations `table` on the translated row, locale and field,
+    /// and deletes its rows along with the row they translate.
+    ///
+    /// `db_key` holds whether the key exists and whether rows are already deleted in cascade.
+    fn write_translations_sync_sql(&self, table: &DatabaseTable, db_key: (bool, bool)) -> String {
+        let mut sql = String::new();
+        let (key_exists, cascades) = db_key;
+        let row_column = &table.columns[1];
+
+        if !key_exists {
+            writeln!(
+                sql,
+                "CREATE UNIQUE INDEX IF NOT EXISTS {key} ON {table} ({column}, locale, field);",
+                key = quote_ident(&derived_name(&[&table.name], "key")),
+                table = quote_ident(&table.name),
+                column = quote_ident(&row_column.name),
+            )
+            .unwrap();
+        }
+
+        if let (false, Some((reference_table, reference_column))) =
+            (cascades, &row_column.references)
+        {
+            writeln!(
+                sql,
+                "ALTER TABLE {table} DROP CONSTRAINT IF EXISTS {constraint}, \
+                ADD CONSTRAINT {constraint} FOREIGN KEY ({column}) REFERENCES {reference_table} ({reference_column}) ON DELETE CASCADE;",
+                table = quote_ident(&table.name),
+                constraint = quote_ident(&derived_name(&[&table.name, &row_column.name], "fkey")),
+                column = quote_ident(&row_column.name),
+                reference_table = quote_ident(reference_table),
+                reference_column = quote_ident(reference_column),
+            )
+            .unwrap();
+        }
+
+        sql
+    }
+
+    /// Returns whether `table` is partitioned in the database.
+    async fn fetch_is_partitioned(&self, table: &DatabaseTable) -> Result<bool, Error> {
+        let kind: Option<(String,)> = sqlx::query_as(FETCH_TABLE_KIND_QUERY)
+            .bind("public")
+            .bind(&table.name)
+            .fetch_optional(&*self.pool)
+            .await
+            .map_err(Error::Sqlx)?;
+
+        Ok(kind.map(|(kind,)| kind == "p").unwrap_or(false))
+    }
+
+    /// Warns that the partitioned `table` exists without partitions, as tables cannot be partitioned once created.
+    fn warn_unpartitioned(&self, table: &DatabaseTable) {
+        let model = self
+            .index
+            .tables
+            .get(&table.name)
+            .map(|&i| self.models[i].name.clone());
+        Warning {
+            id: Warning::UNPARTITIONED_TABLE,
+            model,
+            field: None,
+            message: format!(
+                "is partitioned, but its table `{}` was created without partitions and must be recreated to be partitioned",
+                table.name
+            ),
+        }
+        .emit();
+    }
+
+    /// Returns whether row-level security is enabled on `table`, along with the names of its policies.
+    async fn fetch_policies(&self, table: &DatabaseTable) -> Result<(bool, Vec<String>), Error> {
+        let rls_enabled: Option<(bool,)> = sqlx::query_as(FETCH_RLS_ENABLED_QUERY)
+            .bind("public")
+            .bind(&table.name)
+            .fetch_optional(&*self.pool)
+            .await
+            .map_err(Error::Sqlx)?;
+        let policy_names: Vec<(String,)> = sqlx::query_as(FETCH_POLICIES_QUERY)
+            .bind("public")
+            .bind(&table.name)
+            .fetch_all(&*self.pool)
+            .await
+            .map_err(Error::Sqlx)?;
+
+        Ok((
+            rls_enabled.map(|(enabled,)| enabled).unwrap_or(false),
+            policy_names
+                .into_iter()
+                .map(|(policy_name,)| policy_name)
+                .collect(),
+        ))
+    }
+
+    /// Enables row-level security on `table` and creates its missing policies.
+    ///
+    /// Policies are only created and never dropped or altered, as tables may have policies which are not managed by awto.
+    /// Renaming a policy creates it alongside the previous one, which must be dropped by hand.
+    fn write_policy_sync_sql(
+        &self,
+        table: &DatabaseTable,
+        rls_enabled: bool,
+        db_policies: &[String],
+    ) -> String {
+        let mut sql = String::new();
+        if table.policies.is_empty() {
+            return sql;
+        }
+
+        if !rls_enabled {
+            writeln!(
+                sql,
+                "ALTER TABLE {} ENABLE ROW LEVEL SECURITY;",
+                quote_ident(&table.name)
+            )
+            .unwrap();
+        }
+
+        for policy in &table.policies {
+            if !db_policies.contains(&policy.name) {
+                writeln!(
+                    sql,
+                    "CREATE POLICY {policy} ON {table} USING ({using});",
+                    policy = quote_ident(&policy.name),
+                    table = quote_ident(&table.name),
+                    using = policy.using,
+                )
+                .unwrap();
+            }
+        }
+
+        sql
+    }
+
+    /// Returns the name of the trigger and of the trigger function notifying the changes of `table`.
+    fn notify_trigger_name(table: &DatabaseTable) -> String {
+        derived_name(&[&table.name], "notify")
+    }
+
+    /// Returns whether the trigger notifying the changes of `table` exists.
+    async fn fetch_notify_trigger(&self, table: &DatabaseTable) -> Result<bool, Error> {
+        let trigger: Option<(String,)> = sqlx::query_as(FETCH_TRIGGER_QUERY)
+            .bind("public")
+            .bind(&table.name)
+            .bind(Self::notify_trigger_name(table))
+            .fetch_optional(&*self.pool)
+            .await
+            .map_err(Error::Sqlx)?;
+
+        Ok(trigger.is_some())
+    }
+
+    /// Creates the trigger notifying the changes of a table with `#[awto(notify)]` on its `{table}_changes` channel,
+    /// or drops it along with its function once the table no longer notifies its changes.
+    ///
+    /// The payload of the notifications is a JSON object holding the operation and the `id` of the row,
+    /// as notifications are limited to 8000 bytes.
+    fn write_notify_sync_sql(&self, table: &DatabaseTable, db_notify: bool) -> String {
+        let mut sql = String::new();
+        let trigger = Self::notify_trigger_name(table);
+
+        if table.notify && !db_notify {
+            let channel = format!("{}_changes", table.name).replace('\'', "''");
+            writeln!(
+                sql,
+                "CREATE OR REPLACE FUNCTION {trigger}() RETURNS trigger AS $$\n\
+                BEGIN\n  \
+                  IF TG_OP = 'DELETE' THEN\n    \
+                    PERFORM pg_notify('{channel}', json_build_object('operation', TG_OP, 'id', OLD.id)::text);\n  \
+                  ELSE\n    \
+                    PERFORM pg_notify('{channel}', json_build_object('operation', TG_OP, 'id', NEW.id)::text);\n  \
+                  END IF;\n  \
+                  RETURN NULL;\n\
+                END;\n\
+                $$ LANGUAGE plpgsql;",
+                trigger = quote_ident(&trigger),
+                channel = channel,
+            )
+            .unwrap();
+            writeln!(
+                sql,
+                "CREATE TRIGGER {trigger} AFTER INSERT OR UPDATE OR DELETE ON {table} FOR EACH ROW EXECUTE FUNCTION {trigger}();",
+                trigger = quote_ident(&trigger),
+                table = quote_ident(&table.name),
+            )
+            .unwrap();
+        } else if !table.notify && db_notify {
+            writeln!(
+                sql,
+                "DROP TRIGGER IF EXISTS {trigger} ON {table};",
+                trigger = quote_ident(&trigger),
+                table = quote_ident(&table.name),
+            )
+            .unwrap();
+            writeln!(sql, "DROP FUNCTION IF EXISTS {}();", quote_ident(&trigger)).unwrap();
+        }
+
+        sql
+    }
+
+    /// Returns the name of the trigger, and of its function, copying the previous versions of the rows of `table`.
+    fn history_trigger_name(table: &DatabaseTable) -> String {
+        derived_name(&[&table.name], "record_history")
+    }
+
+    /// Returns whether the trigger copying the previous versions of the rows of `table` exists,
+    /// along with the source of its function.
+    async fn fetch_history_trigger(
+        &self,
+        table: &DatabaseTable,
+    ) -> Result<(bool, Option<String>), Error> {
+        let trigger: Option<(String,)> = sqlx::query_as(FETCH_TRIGGER_QUERY)
+            .bind("public")
+            .bind(&table.name)
+            .bind(Self::history_trigger_name(table))
+            .fetch_optional(&*self.pool)
+            .await
+            .map_err(Error::Sqlx)?;
+        let source: Option<(String,)> = sqlx::query_as(FETCH_FUNCTION_SOURCE_QUERY)
+            .bind("public")
+            .bind(Self::history_trigger_name(table))
+            .fetch_optional(&*self.pool)
+            .await
+            .map_err(Error::Sqlx)?;
+
+        Ok((trigger.is_some(), source.map(|(source,)| source)))
+    }
+
+    /// Returns the body of the function copying the previous version of a row of `table` into its history table,
+    /// unless it already holds an identical version.
+    ///
+    /// The version was valid since the end of the version before it, or since the row was created.
+    fn history_trigger_body(table: &DatabaseTable) -> String {
+        let history = quote_ident(&table.history_table_name()).into_owned();
+        let columns: Vec<_> = table
+            .columns
+            .iter()
+            .map(|column| quote_ident(&column.name))
+            .collect();
+        let old_columns: Vec<_> = columns
+            .iter()
+            .map(|column| format!("OLD.{}", column))
+            .collect();
+
+        format!(
+            "BEGIN\n  \
+              IF TG_OP = 'UPDATE' AND OLD IS NOT DISTINCT FROM NEW THEN\n    \
+                RETURN NULL;\n  \
+              END IF;\n  \
+              INSERT INTO {history} ({columns}, valid_from, valid_to, operation)\n  \
+              VALUES ({old_columns}, COALESCE((SELECT MAX(valid_to) FROM {history} WHERE id = OLD.id), OLD.created_at), clock_timestamp(), TG_OP);\n  \
+              RETURN NULL;\n\
+            END;",
+            history = history,
+            columns = columns.join(", "),
+            old_columns = old_columns.join(", "),
+        )
+    }
+
+    /// Creates or replaces the trigger copying the previous versions of the rows of a table with `#[awto(temporal)]`
+    /// into its history table with [`Temporal::Trigger`], or drops it along with its function otherwise.
+    ///
+    /// The function is replaced whenever its source in the database differs, such as once a column was added.
+    fn write_history_trigger_sync_sql(
+        &self,
+        table: &DatabaseTable,
+        db_trigger: (bool, Option<String>),
+    ) -> String {
+        let mut sql = String::new();
+        let trigger = Self::history_trigger_name(table);
+        let (db_trigger_exists, db_source) = db_trigger;
+
+        if table.temporal == Some(Temporal::Trigger) {
+            let body = Self::history_trigger_body(table);
+            if db_source.as_deref().map(str::trim) != Some(body.as_str()) {
+                writeln!(
+                    sql,
+                    "CREATE OR REPLACE FUNCTION {trigger}() RETURNS trigger AS $$\n{body}\n$$ LANGUAGE plpgsql;",
+                    trigger = quote_ident(&trigger),
+                    body = body,
+                )
+                .unwrap();
+            }
+            if !db_trigger_exists {
+                writeln!(
+                    sql,
+                    "CREATE TRIGGER {trigger} AFTER UPDATE OR DELETE ON {table} FOR EACH ROW EXECUTE FUNCTION {trigger}();",
+                    trigger = quote_ident(&trigger),
+                    table = quote_ident(&table.name),
+                )
+                .unwrap();
+            }
+        } else if db_trigger_exists || db_source.is_some() {
+            writeln!(
+                sql,
+                "DROP TRIGGER IF EXISTS {trigger} ON {table};",
+                trigger = quote_ident(&trigger),
+                table = quote_ident(&table.name),
+            )
+            .unwrap();
+            writeln!(sql, "DROP FUNCTION IF EXISTS {}();", quote_ident(&trigger)).unwrap();
+        }
+
+        sql
+    }
+
+    /// Returns the sql altering the history table `table` to hold the columns of the table it keeps the versions of.
+    ///
+    /// Columns are never dropped, as previous versions keep their values: the columns which were dropped from the table
+    /// become nullable instead, and columns which became required in the table stay nullable, as previous versions may
+    /// hold nulls.
+    fn write_history_sync_sql(
+        &self,
+        table: &DatabaseTable,
+        db_columns: &[DatabaseColumn],
+    ) -> String {
+        let mut sql = String::new();
+
+        for schema_col in &table.columns {
+            let schema_col = match db_columns
+                .iter()
+                .find(|db_col| db_col.name == schema_col.name)
+            {
+                Some(db_col) if db_col.nullable => DatabaseColumn {
+                    nullable: true,
+                    ..schema_col.clone()
+                },
+                _ => schema_col.clone(),
+            };
+            sql.push_str(&self.write_column_sync_sql(table, &schema_col, db_columns));
+        }
+
+        let dropped = db_columns.iter().filter(|db_col| {
+            !db_col.nullable
+                && table
+                    .columns
+                    .iter()
+                    .all(|schema_col| schema_col.name != db_col.name)
+        });
+        for db_col in dropped {
+            writeln!(
+                sql,
+                "ALTER TABLE {table} ALTER COLUMN {column} DROP NOT NULL;",
+                table = quote_ident(&table.name),
+                column = quote_ident(&db_col.name)
+            )
+            .unwrap();
+        }
+
+        sql
+    }
+
+    /// Returns the sql creating the extensions required by the columns of `tables`,
+    /// which is a no-op for extensions which exist so they are not fetched like the tables.
+    fn write_extensions_sql(tables: &[DatabaseTable]) -> String {
+        let uses_hstore = tables
+            .iter()
+            .flat_map(|table| &table.columns)
+            .any(|column| column.ty == DatabaseType::Hstore);
+
+        if uses_hstore {
+            "CREATE EXTENSION IF NOT EXISTS hstore;\n".to_string()
+        } else {
+            String::new()
+        }
+    }
+
+    fn write_table_create_sql(&self, table: &DatabaseTable) -> String {
+        let mut sql = String::new();
+
+        writeln!(
+            sql,
+            "CREATE TABLE IF NOT EXISTS {} (",
+            quote_ident(&table.name)
+        )
+        .unwrap();
+
+        let key_constraint = table.partition.is_some() || table.primary_key_columns().len() > 1;
+        let mut definitions: Vec<String> = table
+            .columns
+            .iter()
+            .map(|column| {
+                let mut definition = String::new();
+                let annotation = self.column_annotation(table, column);
+                if !annotation.is_empty() {
+                    write!(definition, "  {}", annotation).unwrap();
+                }
+                // The primary key of partitioned tables is a table constraint including the partition column,
+                // like keys of several columns
+                let column_sql = if key_constraint && column.primary_key {
+                    self.write_column_sql(&DatabaseColumn {
+                        primary_key: false,
+                        ..column.clone()
+                    })
+                } else {
+                    self.write_column_sql(column)
+                };
+                write!(definition, "  {}", column_sql).unwrap();
+
+                definition
+            })
+            .collect();
+        if key_constraint {
+            let key_columns: Vec<_> = table
+                .primary_key_columns()
+                .into_iter()
+                .map(quote_ident)
+                .collect();
+            definitions.push(format!("  PRIMARY KEY ({})", key_columns.join(", ")));
+        }
+        writeln!(sql, "{}", definitions.join(",\n")).unwrap();
+
+        match &table.partition {
+            Some(partition) => writeln!(
+                sql,
+                ") PARTITION BY RANGE ({});",
+                quote_ident(&partition.column)
+            )
+            .unwrap(),
+            None => writeln!(sql, ");").unwrap(),
+        }
+
+        sql
+    }
+
+    /// Returns the annotation of the sql or code generated for `table`, or of its `column`,
+    /// which is empty unless annotations are enabled or when the table is not the table of a model.
+    fn table_annotation(
+        &self,
+        table: &DatabaseTable,
+        column: Option<&str>,
+        style: CommentStyle,
+    ) -> String {
+        let generator = match style {
+            CommentStyle::Dashes => "database/ddl",
+            CommentStyle::Slashes => "database/entity",
+        };
+        let position = self
+            .index
+            .tables
+            .get(&table.name)
+            .or_else(|| self.index.sub_tables.get(&table.name));
+        let model = match position {
+            Some(&i) if self.annotate_sources => &self.models[i],
+            _ => return String::new(),
+        };
+
+        let annotation = Annotation::new(generator, &model.name);
+        match column {
+            Some(column) => annotation.field(column).comment(style, true),
+            None => annotation.comment(style, true),
+        }
+    }
+
+    fn column_annotation(&self, table: &DatabaseTable, column: &DatabaseColumn) -> String {
+        self.table_annotation(table, Some(&column.name), CommentStyle::Dashes)
+    }
+
+    fn write_column_sql(&self, column: &DatabaseColumn) -> String {
+        let mut sql = String::new();
+
+        write!(sql, "{} {}", quote_ident(&column.name), column.ty,).unwrap();
+
+        if !column.nullable {
+            write!(sql, " NOT NULL",).unwrap();
+        }
+
+        if let Some(computed) = &column.computed {
+            write!(sql, " GENERATED ALWAYS AS ({}) STORED", computed).unwrap();
+        }
+
+        if let Some(default) = &column.default {
+            write!(sql, " DEFAULT {}", default).unwrap();
+        }
+
+        if let Some(constraint) = &column.constraint {
+            write!(sql, " CHECK ({})", constraint).unwrap();
+        }
+
+        if column.primary_key {
+            write!(sql, " PRIMARY KEY").unwrap();
+        }
+
+        sql
+    }
+
+    async fn write_sync_sql(&self, table: &DatabaseTable, db_columns: &[DatabaseColumn]) -> String {
+        let mut sql = String::new();
+
+        for schema_col in &table.columns {
+            let column_sql = self.write_column_sync_sql(table, schema_col, db_columns);
+            if !column_sql.is_empty() {
+                sql.push_str(&self.column_annotation(table, schema_col));
+                sql.push_str(&column_sql);
+            }
+        }
+
+        // Delete columns that exist in db but don't exist in schema
+        db_columns
+            .iter()
+            .filter(|db_col| {
+                table
+                    .columns
+                    .iter()
+                    .all(|schema_col| schema_col.name != db_col.name)
+            })
+            .for_each(|db_col| {
+                writeln!(
+                    sql,
+                    "ALTER TABLE {table} DROP COLUMN {column};",
+                    table = quote_ident(&table.name),
+                    column = quote_ident(&db_col.name)
+                )
+                .unwrap();
+            });
+
+        sql
+    }
+
+    /// Checks the rows of `table` fit the columns narrowed by its model, returning the sql applying the backfill of
+    /// the columns which rows do not fit before they are altered.
+    ///
+    /// The rows not fitting a column without a backfill are added to `violations`,
+    /// or reported as a warning when narrowing is forced.
+    async fn write_backfill_sql(
+        &self,
+        table: &DatabaseTable,
+        db_columns: &[DatabaseColumn],
+        violations: &mut Vec<NarrowingViolation>,
+    ) -> Result<String, Error> {
+        let mut sql = String::new();
+
+        let primary_key = table.columns.iter().find(|column| column.primary_key);
+        for schema_col in table
+            .columns
+            .iter()
+            .filter(|schema_col| schema_col.computed.is_none())
+        {
+            // Columns turned into generated columns are added again, so their rows are not altered
+            let db_col = match db_columns
+                .iter()
+                .find(|db_col| db_col.name == schema_col.name)
+            {
+                Some(db_col) if db_col.computed.is_none() => db_col,
+                _ => continue,
+            };
+            for narrowing in Narrowing::between(db_col, schema_col) {
+                let check = NarrowingCheck {
+                    table: table.name.clone(),
+                    column: schema_col.name.clone(),
+                    primary_key: primary_key.map(|column| column.name.clone()),
+                    narrowing,
+                };
+                let violation = match check.run(&self.pool).await? {
+                    Some(violation) => violation,
+                    None => continue,
+                };
+
+                match &schema_col.backfill {
+                    Some(backfill) => writeln!(
+                        sql,
+                        "UPDATE {table} SET {column} = ({backfill}) WHERE {condition};",
+                        table = quote_ident(&table.name),
+                        column = quote_ident(&schema_col.name),
+                        backfill = backfill,
+                        condition = check.narrowing.condition(&schema_col.name),
+                    )
+                    .unwrap(),
+                    None if self.force_narrowing => Warning {
+                        id: Warning::FORCED_NARROWING,
+                        model: self
+                            .index
+                            .tables
+                            .get(&table.name)
+                            .map(|&i| self.models[i].name.clone()),
+                        field: Some(schema_col.name.clone()),
+                        message: format!("is narrowed although {}", violation),
+                    }
+                    .emit(),
+                    None => violations.push(violation),
+                }
+            }
+        }
+
+        Ok(sql)
+    }
+
+    /// Returns the sql altering the column of `schema_col` in `table` when it differs from its `db_columns`.
+    fn write_column_sync_sql(
+        &self,
+        table: &DatabaseTable,
+        schema_col: &DatabaseColumn,
+        db_columns: &[DatabaseColumn],
+    ) -> String {
+        let mut sql = String::new();
+
+        let db_col = match db_columns
+            .iter()
+            .find(|db_col| db_col.name == schema_col.name)
+        {
+            Some(db_col) => db_col,
+            None => {
+                // Column does not exist in DB
+                writeln!(
+                    sql,
+                    "ALTER TABLE {} ADD COLUMN {};",
+                    quote_ident(&table.name),
+                    self.write_column_sql(schema_col)
+                )
+                .unwrap();
+                return sql;
+            }
+        };
+
+        // Columns cannot be turned into or out of generated columns, so they are added again.
+        // Changed expressions are not detected, as postgres returns them rewritten
+        if schema_col.computed.is_some() != db_col.computed.is_some() {
+            // Columns of deprecated fields are only dropped once the field is deleted
+            if let Some((model, _)) = self.column_deprecation(table, &schema_col.name) {
+                Warning {
+                    id: Warning::DEPRECATED_COLUMN_KEPT,
+                    model: Some(model.name.clone()),
+                    field: Some(schema_col.name.clone()),
+                    message: "is deprecated, so its column is kept instead of being dropped and added again, delete the field to drop it".to_string(),
+                }
+                .emit();
+                return sql;
+            }
+
+            writeln!(
+                sql,
+                "ALTER TABLE {table} DROP COLUMN {column};\nALTER TABLE {table} ADD COLUMN {column_sql};",
+                table = quote_ident(&table.name),
+                column = quote_ident(&schema_col.name),
+                column_sql = self.write_column_sql(schema_col),
+            )
+            .unwrap();
+            return sql;
+        }
+
+        // Check for type mismatch
+        if schema_col.ty != db_col.ty {
+            writeln!(
+                sql,
+                "ALTER TABLE {table} ALTER COLUMN {column} TYPE {ty} USING {column}::{ty};",
+                table = quote_ident(&table.name),
+                column = quote_ident(&schema_col.name),
+                ty = schema_col.ty.to_string(),
+            )
+            .unwrap();
+        }
+
+        // Check for nullable mismatch
+        if schema_col.nullable != db_col.nullable {
+            if db_col.nullable {
+                writeln!(
+                    sql,
+                    "ALTER TABLE {table} ALTER COLUMN {column} SET NOT NULL;",
+                    table = quote_ident(&table.name),
+                    column = quote_ident(&schema_col.name)
+                )
+                .unwrap();
+            } else {
+                writeln!(
+                    sql,
+                    "ALTER TABLE {table} ALTER COLUMN {column} DROP NOT NULL;",
+                    table = quote_ident(&table.name),
+                    column = quote_ident(&schema_col.name)
+                )
+                .unwrap();
+            }
+        }
+
+        // Check for default mismatch
+        if schema_col.default != db_col.default {
+            if let Some(default) = &schema_col.default {
+                writeln!(
+                    sql,
+                    "ALTER TABLE {table} ALTER COLUMN {column} SET DEFAULT {default};",
+                    table = quote_ident(&table.name),
+                    column = quote_ident(&schema_col.name),
+                    default = default
+                )
+                .unwrap();
+            } else {
+                writeln!(
+                    sql,
+                    "ALTER TABLE {table} ALTER COLUMN {column} DROP DEFAULT;",
+                    table = quote_ident(&table.name),
+                    column = quote_ident(&schema_col.name)
+                )
+                .unwrap();
+            }
+        }
+
+        // Check for unique mismatch
+        if schema_col.unique != db_col.unique {
+            if db_col.unique {
+                writeln!(
+                    sql,
+                    "ALTER TABLE {table} DROP CONSTRAINT {constraint};",
+                    table = quote_ident(&table.name),
+                    constraint =
+                        quote_ident(&derived_name(&[&table.name, &schema_col.name], "key")),
+                )
+                .unwrap();
+            } else {
+                writeln!(
+                    sql,
+                    "ALTER TABLE {table} ADD CONSTRAINT {constraint} UNIQUE ({column});",
+                    table = quote_ident(&table.name),
+                    column = quote_ident(&schema_col.name),
+                    constraint =
+                        quote_ident(&derived_name(&[&table.name, &schema_col.name], "key")),
+                )
+                .unwrap();
+            }
+        }
+
+        sql
+    }
+
+    /// Returns the foreign key columns of `table` in the database whose rows are set to null
+    /// when the row they reference is deleted.
+    async fn fetch_set_null_foreign_keys(
+        &self,
+        table: &DatabaseTable,
+        db_columns: &[DatabaseColumn],
+    ) -> Result<Vec<String>, Error> {
+        let mut set_null = Vec::new();
+        for db_col in db_columns
+            .iter()
+            .filter(|db_col| db_col.references.is_some())
+        {
+            let delete_action: Option<(String,)> = sqlx::query_as(FETCH_FOREIGN_KEY_DELETE_QUERY)
+                .bind("public")
+                .bind(&table.name)
+                .bind(derived_name(&[&table.name, &db_col.name], "fkey"))
+                .fetch_optional(&*self.pool)
+                .await
+                .map_err(Error::Sqlx)?;
+            if delete_action
+                .map(|(action,)| action == "n")
+                .unwrap_or(false)
+            {
+                set_null.push(db_col.name.clone());
+            }
+        }
+
+        Ok(set_null)
+    }
+
+    /// Adds, drops or replaces the foreign keys of `table` which differ from its `db_columns`.
+    ///
+    /// Foreign keys of nullable columns set them to null when the referenced row is deleted, which `db_set_null`
+    /// holds for the columns in the database. Their sql runs once every table exists, so tables can reference
+    /// themselves or tables created after them.
+    fn write_foreign_keys_sync_sql(
+        &self,
+        table: &DatabaseTable,
+        db_columns: &[DatabaseColumn],
+        db_set_null: &[String],
+    ) -> String {
+        let mut sql = String::new();
+
+        for schema_col in &table.columns {
+            let db_references = db_columns
+                .iter()
+                .find(|db_col| db_col.name == schema_col.name)
+                .and_then(|db_col| db_col.references.as_ref());
+            let set_null = db_set_null.contains(&schema_col.name);
+            let schema_references = schema_col
+                .references
+                .as_ref()
+                .filter(|_| self.has_foreign_key(schema_col));
+            if schema_references == db_references
+                && (db_references.is_none() || schema_col.nullable == set_null)
+            {
+                continue;
+            }
+
+            if db_references.is_some() {
+                writeln!(
+                    sql,
+                    "ALTER TABLE {table} DROP CONSTRAINT {constraint};",
+                    table = quote_ident(&table.name),
+                    constraint =
+                        quote_ident(&derived_name(&[&table.name, &schema_col.name], "fkey")),
+                )
+                .unwrap();
+            }
+            if let Some((reference_table, reference_column)) = schema_references {
+                writeln!(
+                    sql,
+                    "ALTER TABLE {table} ADD CONSTRAINT {constraint} FOREIGN KEY ({column}) REFERENCES {reference_table} ({reference_column}){on_delete};",
+                    table = quote_ident(&table.name),
+                    column = quote_ident(&schema_col.name),
+                    constraint = quote_ident(&derived_name(&[&table.name, &schema_col.name], "fkey")),
+                    reference_table = quote_ident(reference_table),
+                    reference_column = quote_ident(reference_column),
+                    on_delete = if schema_col.nullable { " ON DELETE SET NULL" } else { "" },
+                )
+                .unwrap();
+            }
+        }
+
+        sql
+    }
+}
+
+const FETCH_INDEXES_QUERY: &str = "
+SELECT indexname FROM pg_indexes WHERE schemaname = $1 AND tablename = $2
+";
+
+const FETCH_TABLE_KIND_QUERY: &str = "
+SELECT c.relkind::text FROM pg_class c
+JOIN pg_namespace n ON n.oid = c.relnamespace
+WHERE n.nspname = $1 AND c.relname = $2
+";
+
+const FETCH_RLS_ENABLED_QUERY: &str = "
+SELECT c.relrowsecurity FROM pg_class c
+JOIN pg_namespace n ON n.oid = c.relnamespace
+WHERE n.nspname = $1 AND c.relname = $2
+";
+
+const FETCH_FOREIGN_KEY_DELETE_QUERY: &str = "
+SELECT c.confdeltype::text FROM pg_constraint c
+JOIN pg_class t ON t.oid = c.conrelid
+JOIN pg_namespace n ON n.oid = t.relnamespace
+WHERE n.nspname = $1 AND t.relname = $2 AND c.conname = $3
+";
+
+const FETCH_TRIGGER_QUERY: &str = "
+SELECT t.tgname::text FROM pg_trigger t
+JOIN pg_class c ON c.oid = t.tgrelid
+JOIN pg_namespace n ON n.oid = c.relnamespace
+WHERE n.nspname = $1 AND c.relname = $2 AND t.tgname = $3
+";
+
+const FETCH_FUNCTION_SOURCE_QUERY: &str = "
+SELECT p.prosrc::text FROM pg_proc p
+JOIN pg_namespace n ON n.oid = p.pronamespace
+WHERE n.nspname = $1 AND p.proname = $2
+";
+
+const FETCH_POLICIES_QUERY: &str = "
+SELECT policyname FROM pg_policies WHERE schemaname = $1 AND tablename = $2
+";
+
+/// A column of a table as returned by [`FETCH_TABLE_QUERY`].
+#[derive(Debug, sqlx::FromRow)]
+pub(crate) struct ColumnRow {
+    pub column_name: String,
+    column_default: Option<String>,
+    is_nullable: String,
+    pub data_type: String,
+    character_maximum_length: Option<i32>,
+    is_primary_key: bool,
+    is_unique: bool,
+    reference: Option<String>,
+    is_generated: String,
+    generation_expression: Option<String>,
+}
+
+impl ColumnRow {
+    /// Converts the row into a column, or returns `None` when its type has no [`DatabaseType`].
+    pub fn into_column(self) -> Option<DatabaseColumn> {
+        let character_maximum_length = self.character_maximum_length;
+        let ty = self
+            .data_type
+            .parse::<DatabaseType>()
+            .ok()
+            .map(|database_type| {
+                if let Some(max_len) = character_maximum_length {
+                    if matches!(database_type, DatabaseType::Text(None)) {
+                        return DatabaseType::Text(Some(max_len));
+                    }
+                }
+
+                database_type
+            })?;
+        // Generated columns have no default, their expression is returned separately
+        let generation_expression = self.generation_expression;
+        let computed =
+            (self.is_generated == "ALWAYS").then(|| generation_expression.unwrap_or_default());
+
+        Some(DatabaseColumn {
+            name: self.column_name,
+            ty,
+            nullable: self.is_nullable == "YES",
+            default: self.column_default.map(|def| {
+                if def.starts_with('\'') {
+                    let s = def
+                        .strip_prefix('\'')
+                        .unwrap()
+                        .split('\'')
+                        .next()
+                        .unwrap()
+                        .to_string();
+                    DatabaseDefault::String(s)
+                } else if def == "true" {
+                    DatabaseDefault::Bool(true)
+                } else if def == "false" {
+                    DatabaseDefault::Bool(false)
+                } else if let Ok(num) = def.parse::<u64>() {
+                    DatabaseDefault::Int(num)
+                } else if let Ok(num) = def.parse::<i64>() {
+                    DatabaseDefault::Float(num)
+                } else {
+                    DatabaseDefault::Raw(def)
+                }
+            }),
+            unique: self.is_unique,
+            constraint: None,
+            primary_key: self.is_primary_key,
+            references: if let Some(references) = self.reference {
+                let mut parts = references.splitn(2, ':');
+                if let Some(references_table) = parts.next() {
+                    parts.next().map(|references_column| {
+                        (references_table.to_string(), references_column.to_string())
+                    })
+                } else {
+                    None
+                }
+            } else {
+                None
+            },
+            reference_constraint: None,
+            cross_database: false,
+            locales: Vec::new(),
+            computed,
+            normalize: Vec::new(),
+            fake: None,
+            backfill: None,
+        })
+    }
+}
+
+/// Returns the columns of `table` in the `public` schema, which are empty when the table does not exist.
+pub(crate) async fn fetch_column_rows(pool: &PgPool, table: &str) -> Result<Vec<ColumnRow>, Error> {
+    sqlx::query_as(FETCH_TABLE_QUERY)
+        .bind("public")
+        .bind(table)
+        .fetch_all(pool)
+        .await
+        .map_err(Error::Sqlx)
+}
+
+const FETCH_TABLE_QUERY: &str = "
+SELECT column_name, column_default, is_nullable,
+CASE WHEN data_type = 'USER-DEFINED' THEN udt_name::text ELSE data_type END AS data_type,
+character_maximum_length, is_generated, generation_expression,
+(
+    SELECT
+        COUNT(*) > 0
+    FROM information_schema.table_constraints tco
+    JOIN information_schema.key_column_usage kcu 
+    ON kcu.constraint_name = tco.constraint_name
+    AND kcu.constraint_schema = tco.constraint_schema
+    AND kcu.constraint_name = tco.constraint_name
+    WHERE
+        tco.constraint_type = 'PRIMARY KEY' AND
+        kcu.table_schema = $1 AND
+        kcu.table_name = $2 AND
+        kcu.column_name = information_schema.columns.column_name
+) as is_primary_key,
+(
+    SELECT
+        COUNT(*) > 0
+    FROM information_schema.table_constraints tco
+    JOIN information_schema.key_column_usage kcu 
+    ON kcu.constraint_name = tco.constraint_name
+    AND kcu.constraint_schema = tco.constraint_schema
+    AND kcu.constraint_name = tco.constraint_name
+    WHERE
+        tco.constraint_type = 'UNIQUE' AND
+        kcu.table_schema = $1 AND
+        kcu.table_name = $2 AND
+        kcu.column_name = information_schema.columns.column_name
+) as is_unique,
+(
+    SELECT CONCAT(
+        rel_tco.table_name,
+        ':',
+        (
+            SELECT u.column_name
+            FROM information_schema.constraint_column_usage u
+            INNER JOIN information_schema.referential_constraints fk
+            ON
+                u.constraint_catalog = fk.unique_constraint_catalog AND
+                u.constraint_schema = fk.unique_constraint_schema AND
+                u.constraint_name = fk.unique_constraint_name
+            INNER JOIN information_schema.key_column_usage r
+            ON
+                r.constraint_catalog = fk.constraint_catalog AND
+                r.constraint_schema = fk.constraint_schema AND
+                r.constraint_name = fk.constraint_name
+            WHERE
+                fk.constraint_name = kcu.constraint_name AND
+                u.table_schema = kcu.table_schema AND
+                u.table_name = rel_tco.table_name
+        )
+    )
+    FROM information_schema.table_constraints tco
+    JOIN information_schema.key_column_usage kcu
+    ON
+        tco.constraint_schema = kcu.constraint_schema AND
+        tco.constraint_name = kcu.constraint_name
+    JOIN information_schema.referential_constraints rco
+    ON
+        tco.constraint_schema = rco.constraint_schema AND
+        tco.constraint_name = rco.constraint_name
+    JOIN information_schema.table_constraints rel_tco
+    ON
+        rco.unique_constraint_schema = rel_tco.constraint_schema AND
+        rco.unique_constraint_name = rel_tco.constraint_name
+    WHERE
+        tco.constraint_type = 'FOREIGN KEY' AND
+        kcu.table_name = $2 AND
+        kcu.column_name = information_schema.columns.column_name
+    GROUP BY
+        kcu.table_schema,
+        kcu.table_name,
+        rel_tco.table_name,
+        rel_tco.table_schema,
+        kcu.constraint_name
+    ORDER BY
+        kcu.table_schema,
+        kcu.table_name
+) as reference
+FROM information_schema.columns
+WHERE table_schema = $1
+AND table_name = $2
+ORDER BY ordinal_position;
+";
+
+#[cfg(test)]
+mod test {
+    use awto::{database::IntoDatabaseTable, tests_cfg::*};
+
+    use super::*;
+    use crate::{
+        format::NO_FORMAT_ENV,
+        incremental::MODELS_DIR,
+        profiling::{peak_rss_kb, PROFILE_GENERATION_ENV, PROFILE_MARKER},
+        util::undocumented_items,
+    };
+
+    /// Returns a compiler of `models` for tests which only generate code, whose pool never connects.
+    fn compiler_of(models: Vec<Model>) -> DatabaseCompiler<'static> {
+        DatabaseCompiler {
+            pool: Cow::Owned(PgPool::connect_lazy("postgres://localhost/awto").unwrap()),
+            index: TableIndex::new(&models),
+            models,
+            annotate_sources: false,
+            entity_files: false,
+            lock_timeout: ConnectPolicy::default().lock_timeout,
+            force_narrowing: false,
+        }
+    }
+
+    #[test]
+    fn models_of_database() {
+        let names = |database| {
+            database_models(MODELS.to_vec(), database)
+                .into_iter()
+                .map(|model| model.name)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names(Some("main")), ["Product", "NewProduct", "Employee"]);
+        assert_eq!(names(Some("analytics")), ["Event"]);
+        assert_eq!(names(None).len(), MODELS.len());
+    }
+
+    #[tokio::test]
+    async fn generic_connections() {
+        let tx = match syn::parse_file(TX_MODULE).unwrap().items.remove(0) {
+            syn::Item::Mod(tx) => tx.content.unwrap().1,
+            item => panic!("expected the tx module, found {}", quote!(#item)),
+        };
+        let signatures: Vec<String> = tx
+            .iter()
+            .filter_map(|item| match item {
+                syn::Item::Fn(function) => {
+                    let (sig, where_clause) = (&function.sig, &function.sig.generics.where_clause);
+                    Some(format!(
+                        "{} {}",
+                        quote!(#sig).to_string().split(" where").next().unwrap(),
+                        quote!(#where_clause)
+                    ))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(signatures.len(), 2);
+        for signature in &signatures {
+            assert!(
+                signature.contains("< C , F , T , E > (db : & C ,"),
+                "{}",
+                signature
+            );
+            assert!(
+                signature.contains("where C : Transactional ,"),
+                "{}",
+                signature
+            );
+        }
+        let implementors: Vec<String> = tx
+            .iter()
+            .filter_map(|item| match item {
+                syn::Item::Impl(syn::ItemImpl {
+                    trait_: Some((_, path, _)),
+                    self_ty,
+                    ..
+                }) if quote!(#path).to_string() == "Transactional" => {
+                    Some(quote!(#self_ty).to_string())
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(implementors, ["DatabaseConnection", "DatabaseTransaction"]);
+
+        // Queries run on a connection or an open transaction alike, except the streams and data loaders holding their connection
+        fn check(sig: &syn::Signature, params: &mut Vec<String>) {
+            for input in &sig.inputs {
+                if let syn::FnArg::Typed(param) = input {
+                    let ty = &param.ty;
+                    if quote!(#ty).to_string().contains("DatabaseConnection") {
+                        params.push(sig.ident.to_string());
+                    }
+                }
+            }
+        }
+        fn connection_params(items: &[syn::Item], params: &mut Vec<String>) {
+            for item in items {
+                match item {
+                    syn::Item::Fn(function) => check(&function.sig, params),
+                    syn::Item::Impl(implementation) => {
+                        for item in &implementation.items {
+                            if let syn::ImplItem::Method(method) = item {
+                                check(&method.sig, params);
+                            }
+                        }
+                    }
+                    syn::Item::Mod(syn::ItemMod {
+                        content: Some((_, items)),
+                        ..
+                    }) => connection_params(items, params),
+                    _ => {}
+                }
+            }
+        }
+        let compiler = compiler_of(MODELS.to_vec());
+        let mut params = Vec::new();
+        connection_params(
+            &syn::parse_file(&compiler.compile_generated_code())
+                .unwrap()
+                .items,
+            &mut params,
+        );
+        params.sort();
+        params.dedup();
+        assert_eq!(params, ["changes", "changes_of", "new", "register"]);
+    }
+
+    #[tokio::test]
+    async fn page_queries() {
+        let compiler = compiler_of(MODELS.to_vec());
+
+        let code = compiler.compile_generated_code();
+
+        assert!(code.contains("pub mod cursor"));
+        assert!(code.contains(". order_by (crate :: product :: Column :: CreatedAt , order . clone ()) . order_by (crate :: product :: Column :: Id , order . clone ())"));
+        assert!(code.contains(":: serde_json :: json ! ([row . created_at , row . id])"));
+        assert!(code.contains(":: serde_json :: json ! ([row . id])"));
+        assert!(code.contains("pub fn page_query (key : & crate :: cursor :: CursorKey , args : & crate :: cursor :: PageArgs ,) -> Result < :: sea_orm :: Select < Self > , crate :: cursor :: PageError >"));
+        assert!(code.contains("let rows = Self :: page_query (key , args) ? . all (db) . await ?"));
+        assert!(code.contains("Self :: page_query_of (Self :: find () , key , args)"));
+        assert!(code.contains(
+            "let rows = Self :: page_query_of (select , key , args) ? . all (db) . await ?"
+        ));
+        // Cursors are authenticated with the query returning them
+        assert!(code.contains("Self :: cursor_query (select , key , \"product\" , args)"));
+        assert!(code.contains("key . decode (query , cursor) ?"));
+        assert!(code
+            .contains("crate :: cursor :: Page :: from_rows (key , \"product\" , args , rows ,"));
+        assert!(code.contains(
+            "Self :: cursor_query (Self :: find () , key , \"product.list_summaries\" , args) ?"
+        ));
+        assert!(code.contains("crate :: cursor :: Page :: from_rows (key , \"product.list_summaries\" , args , rows ,"));
+        assert!(code.contains(".encrypt(nonce, Payload { msg: &payload, aad: query.as_bytes() })"));
+    }
+
+    #[tokio::test]
+    async fn scopes() {
+        let compiler = compiler_of(MODELS.to_vec());
+
+        let code = compiler.compile_generated_code();
+        syn::parse_file(&code).unwrap();
+
+        assert!(code.contains("pub fn live () -> :: sea_orm :: Select < Self > { use :: sea_orm :: { EntityTrait , QueryFilter } ; Self :: find () . filter (Self :: live_condition ()) }"));
+        assert!(code.contains("pub fn recent (days : i64) -> :: sea_orm :: Select < Self >"));
+        assert!(code.contains("pub fn recent_condition (days : i64) -> :: sea_orm :: Condition { let days = :: sea_orm :: sea_query :: Value :: from (days) ;"));
+        assert!(code.contains("pub mod scope"));
+        assert!(code.contains("pub trait ProductScopes { # [doc = \" Keeps the rows of scope [`live`](crate::product::Entity::live).\"] fn live (self ,) -> Self ;"));
+        assert!(code.contains(
+            "impl ProductScopes for :: sea_orm :: Select < crate :: product :: Entity >"
+        ));
+        assert!(
+            code.contains("self . filter (crate :: product :: Entity :: recent_condition (days))")
+        );
+        assert!(code.contains("pub struct ProductScopeArgs { # [doc = \" Keeps the rows of scope `live` when `true`.\"] # [serde (default)] pub live : bool ,"));
+        assert!(code.contains("pub recent_days : Option < i64 > ,"));
+        assert!(code.contains("if let (Some (days) ,) = (self . recent_days . clone () ,) { select = select . recent (days) ; }"));
+        // Tables without scopes have no scope queries
+        assert!(!code.contains("EventScopes"));
+    }
+
+    #[tokio::test]
+    async fn projections() {
+        let compiler = compiler_of(MODELS.to_vec());
+
+        let code = compiler.compile_generated_code();
+        syn::parse_file(&code).unwrap();
+
+        assert!(code.contains("pub mod projection"));
+        assert!(code.contains(
+            "pub struct ProductSummary { # [doc = \" Value of `id`.\"] pub id : :: uuid :: Uuid"
+        ));
+        assert!(code.contains("pub price_cents : i64"));
+        assert!(code.contains("pub struct ProductPrice"));
+        assert!(code.contains("pub async fn list_summaries < 'a , C >"));
+        assert!(code.contains("pub async fn list_prices < 'a , C >"));
+        assert!(code.contains(
+            ". select_only () . column (crate :: product :: Column :: Id) . column (crate :: product :: Column :: CreatedAt) \
+            . column (crate :: product :: Column :: Price) . into_model :: < crate :: projection :: ProductPrice > ()"
+        ));
+
+        // Debug of projections masks internal and secret columns
+        assert!(
+            code.contains("# [derive (Clone , Debug , PartialEq , :: sea_orm :: FromQueryResult")
+        );
+        let mut models = MODELS.to_vec();
+        models[0]
+            .fields
+            .iter_mut()
+            .find(|field| field.name == "price")
+            .unwrap()
+            .sensitivity = Sensitivity::Secret;
+        let code = compiler_of(models).compile_generated_code();
+        syn::parse_file(&code).unwrap();
+        assert!(code.contains("pub struct ProductPrice"));
+        assert!(code.contains(
+            "impl :: std :: fmt :: Debug for ProductPrice { fn fmt (& self , f : & mut :: std :: fmt :: Formatter < '_ >) \
+            -> :: std :: fmt :: Result { f . debug_struct (\"ProductPrice\") . field (\"id\" , & self . id) \
+            . field (\"created_at\" , & self . created_at) . field (\"price\" , & format_args ! (\"********\")) . finish () } }"
+        ));
+        assert!(!code.contains("impl :: std :: fmt :: Debug for ProductSummary"));
+
+        let mut models = MODELS.to_vec();
+        check_projections(&models).unwrap();
+        if let Role::DatabaseTable(table) = &mut models[0].roles[0] {
+            table.projections.push(DatabaseProjection {
+                name: "Summary".to_string(),
+                columns: vec!["id".to_string()],
+            });
+        }
+        assert_eq!(
+            check_identifiers(&models).unwrap_err().to_string(),
+            "projections `ProductSummary` and `Summary` of `product` are both generated as `list_summaries`, rename one of them"
+        );
+    }
+
+    #[tokio::test]
+    async fn generated_files_parse() {
+        let schemas = [
+            ("shop", MODELS.to_vec()),
+            ("reserved", reserved::MODELS.to_vec()),
+            ("tree", tree::MODELS.to_vec()),
+            ("partitioned", partitioned::MODELS.to_vec()),
+            ("temporal", temporal::MODELS.to_vec()),
+            ("external", external::MODELS.to_vec()),
+            (
+                "cross_database",
+                database_models(cross_database::MODELS.to_vec(), Some("main")),
+            ),
+            ("string_keys", string_keys::MODELS.to_vec()),
+            ("idempotent", idempotent::MODELS.to_vec()),
+            ("narrowing", narrowing::MODELS.to_vec()),
+            (
+                "features",
+                crate::features::with_features(features::MODELS.to_vec(), &[]).unwrap(),
+            ),
+            ("empty", empty::MODELS.to_vec()),
+            ("sensitivity", sensitivity::MODELS.to_vec()),
+        ];
+
+        for (schema, models) in schemas {
+            for annotate_sources in [false, true] {
+                let options = GeneratorOptions {
+                    annotate_sources,
+                    ..GeneratorOptions::default()
+                };
+                let files = compiler_of(models.clone())
+                    .generate(&models, &options)
+                    .unwrap();
+
+                for file in files.iter().filter(|file| file.kind == FileKind::Rust) {
+                    if let Err(err) = syn::parse_file(&file.contents) {
+                        panic!(
+                            "{} of schema `{}` does not parse: {}",
+                            file.path, schema, err
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn generated_files() {
+        let compiler = compiler_of(MODELS.to_vec());
+        let options = GeneratorOptions {
+            annotate_sources: true,
+            ..GeneratorOptions::default()
+        };
+
+        let files = compiler.generate(&MODELS[..1], &options).unwrap();
+
+        assert_eq!(compiler.name(), "database");
+        let paths: Vec<_> = files
+            .iter()
+            .map(|file| (file.path.as_str(), file.kind))
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                ("app.rs", FileKind::Rust),
+                ("app_tests.rs", FileKind::Rust),
+                ("app_benches.rs", FileKind::Rust),
+                ("docs.md", FileKind::Markdown),
+            ]
+        );
+        assert!(files[0].contents.contains("pub mod product"));
+        assert!(files[0].contents.contains("@awto: model=Product"));
+        assert!(!files[0].contents.contains("pub mod employee"));
+    }
+
+    #[tokio::test]
+    async fn empty_schema() {
+        let compiler = compiler_of(empty::MODELS.to_vec());
+
+        assert!(compiler.database_tables().is_empty());
+        assert!(entity_table_names(&empty::MODELS[..]).is_empty());
+        for file in compiler
+            .generate(&empty::MODELS[..], &GeneratorOptions::default())
+            .unwrap()
+        {
+            if file.kind == FileKind::Rust {
+                syn::parse_file(&file.contents).unwrap();
+            }
+        }
+        let code = compiler.compile_generated_code();
+        assert!(code.contains("pub mod tx"));
+        assert!(!code.contains("pub mod locale"));
+    }
+
+    #[tokio::test]
+    async fn health_checks() {
+        let compiler = compiler_of(MODELS.to_vec());
+
+        let code = compiler.compile_generated_code();
+
+        assert!(code.contains("pub mod health"));
+        assert!(
+            code.contains("pub async fn check<'a, C>(&self, db: &'a C) -> Result<(), HealthError>")
+        );
+    }
+
+    #[tokio::test]
+    async fn tenant_helpers() {
+        let compiler = compiler_of(MODELS.to_vec());
+
+        let code = compiler.compile_generated_code();
+
+        assert!(code.contains("pub mod tenant"));
+        assert!(code.contains(r#"pub const TENANT_SETTING: &str = "app.tenant_id";"#));
+        assert!(code.contains("\"SELECT set_config($1, $2, true)\""));
+    }
+
+    #[tokio::test]
+    async fn batch_queries() {
+        let compiler = compiler_of(MODELS.to_vec());
+
+        let code = compiler.compile_generated_code();
+
+        assert!(code.contains("pub mod batch"));
+        assert_eq!(code.matches("pub async fn insert_batch <").count(), 2);
+        assert_eq!(code.matches("pub async fn upsert <").count(), 2);
+        assert!(code.contains(
+            r#"target : "id" , keep : & ["id" , "created_at"] , version : Some ("version")"#
+        ));
+        assert_eq!(
+            code.matches("pub async fn insert_new_batch_chunked <")
+                .count(),
+            2
+        );
+        assert!(code.contains(r#"statement.sql.push_str(" ON CONFLICT DO NOTHING");"#));
+    }
+
+    #[tokio::test]
+    async fn bulk_queries() {
+        let compiler = compiler_of(MODELS.to_vec());
+
+        let code = compiler.compile_generated_code();
+
+        assert!(code.contains("#[cfg(feature = \"bulk\")]\npub mod bulk {"));
+        assert_eq!(code.matches("pub async fn export_csv <").count(), 3);
+        // Read-only tables have no imports
+        assert_eq!(code.matches("pub async fn import_csv <").count(), 2);
+        assert!(code.contains(r#"IMPORT_FIELDS : & 'static [& 'static str] = & ["name" , "price" , "description" , "version"]"#));
+        assert!(code.contains(r#"let name = crate :: bulk :: field :: < String > (row , "name" , None , & mut errors) ;"#));
+        assert!(code.contains(r#"let description = crate :: bulk :: field :: < Option < String > > (row , "description" , Some (120) , & mut errors) ;"#));
+        assert!(code.contains("price : match price { Some (value) => :: sea_orm :: Set (value) , None => :: sea_orm :: ActiveValue :: unset () , }"));
+        // Deprecated fields are not imported, but still exported
+        assert!(code.contains(r#"IMPORT_FIELDS : & 'static [& 'static str] = & ["id" , "created_at" , "updated_at" , "name" , "manager_id"]"#));
+        assert!(code.contains(r#"EXPORT_FIELDS : & 'static [& 'static str] = & ["id" , "created_at" , "updated_at" , "name" , "manager_id" , "nickname"]"#));
+    }
+
+    #[tokio::test]
+    async fn metered_queries() {
+        let compiler = compiler_of(MODELS.to_vec());
+
+        let code = compiler.compile_generated_code();
+
+        assert!(code.contains("#[cfg(feature = \"metrics\")]\npub mod metrics {"));
+        assert_eq!(code.matches("pub async fn find_metered <").count(), 3);
+        // Read-only tables are not mutated
+        assert_eq!(code.matches("pub async fn delete_metered <").count(), 2);
+        assert!(code.contains(r#"pub const METRICS_MODEL : & 'static str = "Product" ;"#));
+        assert!(code.contains("crate :: metrics :: instrument (Self :: METRICS_MODEL , crate :: metrics :: Operation :: Insert , query) . await"));
+    }
+
+    #[tokio::test]
+    async fn fake_rows() {
+        let compiler = compiler_of(MODELS.to_vec());
+
+        let code = compiler.compile_generated_code();
+
+        assert!(code.contains("#[cfg(feature = \"fake\")]\npub mod fake {"));
+        // Read-only tables cannot be generated
+        assert_eq!(
+            code.matches("pub async fn generate_rows < C , P > (db : & C ,")
+                .count(),
+            2
+        );
+        assert_eq!(
+            code.matches("FAKE_PARENTS : & 'static [& 'static str] = & [] ;")
+                .count(),
+            2
+        );
+        assert!(code.contains("description : :: sea_orm :: Set (if faker . chance (options . null_probability) { None } else { Some (faker . text (crate :: fake :: Fake :: Paragraph , Some (120) , None)) })"));
+        assert!(code.contains("name : :: sea_orm :: Set (faker . text (crate :: fake :: Fake :: Name , Some (120) , None))"));
+        // Nullable references are sampled from the rows of the referenced table, which may be empty
+        assert!(code.contains("manager_id : :: sea_orm :: Set (if manager_id_references . is_empty () || faker . chance (options . null_probability) { None } else { Some (faker . pick (& manager_id_references) . clone ()) })"));
+        assert!(!is_fake_table(&Event::database_table()));
+    }
+
+    #[tokio::test]
+    async fn checkpointed_runs() {
+        let compiler = compiler_of(MODELS.to_vec());
+
+        let code = compiler.compile_generated_code();
+
+        // Generations resume with the faker where the interrupted generation stopped
+        assert!(code.contains("pub checkpointing: Option<::awto::checkpoint::Checkpointing>,"));
+        assert_eq!(
+            code.matches(r#"let mut checkpointer = :: awto :: checkpoint :: Checkpointer :: start (options . checkpointing . as_ref () , "product") ? ;"#).count(),
+            1
+        );
+        assert!(code.contains("Some (progress) => (progress . start , progress . inserted , crate :: fake :: Faker :: from_state (progress . state)) ,"));
+        assert!(code.contains("for _ in start + inserted .. count {"));
+        assert!(code
+            .contains("Err (err) => { checkpointer . save () ? ; return Err (err . into ()) ; }"));
+        assert!(code.contains("checkpointer . commit (:: awto :: checkpoint :: Progress { rows : inserted , inserted , state : faker . state () , start , .. Default :: default () }) ? ;"));
+
+        // Imports resume from the byte offset of their checkpoint
+        assert_eq!(
+            code.matches("pub async fn import_csv_checkpointed <")
+                .count(),
+            2
+        );
+        assert_eq!(
+            code.matches("pub async fn import_jsonl_checkpointed <")
+                .count(),
+            2
+        );
+        assert!(code.contains("let rows = crate :: bulk :: csv_rows_from (reader , Self :: IMPORT_FIELDS , options , offset) ? ;"));
+        assert!(code.contains("if replaying { Self :: insert_new_batch_chunked (txn , models , chunk_size) . await } else { Self :: insert_batch_chunked (txn , models , chunk_size) . await }"));
+        assert!(code.contains(
+            "skip_lines(&mut records.reader, &mut records.line, &mut records.offset, offset)?;"
+        ));
+    }
+
+    #[tokio::test]
+    async fn optional_insert() {
+        let compiler = compiler_of(MODELS.to_vec());
+
+        let code = compiler.compile_generated_code();
+
+        assert!(code.contains("status : Some (val . status . into ())"));
+        assert!(code.contains("status : match self . status { Some (value) => :: sea_orm :: Set (value . into ()) , None => :: sea_orm :: ActiveValue :: unset () , }"));
+        assert!(!code.contains(
+            "impl :: std :: convert :: From < :: schema :: Product > for crate :: product :: Model"
+        ));
+        assert!(code.contains(
+            "impl :: std :: convert :: From < :: schema :: Event > for crate :: event :: Model"
+        ));
+        assert!(code.contains("price : :: sea_orm :: entity :: IntoActiveValue :: into_active_value (self . price . map (| value | value . 0) . unwrap_or (0)) . into ()"));
+
+        let column = Product::database_table()
+            .columns
+            .into_iter()
+            .find(|column| column.name == "status")
+            .unwrap();
+        assert_eq!(
+            compiler.write_column_sql(&column),
+            "status character varying(20) NOT NULL DEFAULT 'draft'"
+        );
+    }
+
+    #[tokio::test]
+    async fn docs() {
+        let mut models = MODELS.to_vec();
+        let event = models
+            .iter_mut()
+            .find(|model| model.name == "Event")
+            .unwrap();
+        event.doc = Some("Events of [analytics](https://example.com) | kept for `90|days`, see [`Event::name`].\n\n```sql\nSELECT [1] | 2\n```".to_string());
+        if let Role::DatabaseTable(table) = &mut event.roles[0] {
+            table.columns.push(DatabaseColumn {
+                name: "product_id".to_string(),
+                ty: DatabaseType::Uuid,
+                nullable: true,
+                default: None,
+                unique: false,
+                constraint: Some(
+                    "product_id <> '00000000-0000-0000-0000-000000000000'".to_string(),
+                ),
+                primary_key: false,
+                references: Some(("product".to_string(), "id".to_string())),
+                reference_constraint: None,
+                cross_database: false,
+                locales: Vec::new(),
+                computed: None,
+                normalize: Vec::new(),
+                fake: None,
+                backfill: None,
+            });
+        }
+        let compiler = compiler_of(models);
+        let tables = compiler.database_tables();
+
+        assert_eq!(
+            compiler.module_doc(tables[1].0, tables[1].1),
+            "Entity of the `event` table, generated from [`Event`](schema::Event).
+
+Events of [analytics](https://example.com) \\| kept for `90|days`, see \\[`Event::name`\\].
+
+```sql
+SELECT [1] | 2
+```
+
+# Columns
+
+| Column | Rust type | SQL type | Nullable | Default | Constraints |
+| --- | --- | --- | --- | --- | --- |
+| `id` | `Uuid` | `uuid` | no | `uuid_generate_v4()` | primary key |
+| `created_at` | `DateTime<FixedOffset>` | `timestamp with time zone` | no | `NOW()` |  |
+| `updated_at` | `DateTime<FixedOffset>` | `timestamp with time zone` | no | `NOW()` |  |
+| `name` | `String` | `character varying` | no |  |  |
+| `properties` | `HashMap<String,String>` | `jsonb` | no |  |  |
+| `product_id` | `Option<uuid::Uuid>` | `uuid` | yes |  | references `product(id)` on delete set null, check `product_id <> '00000000-0000-0000-0000-000000000000'` |
+
+# Relations
+
+- `product_id` optionally references `id` of [`product`]
+"
+        );
+
+        let product_doc = compiler.module_doc(tables[0].0, tables[0].1);
+        assert!(product_doc.starts_with(
+            "Entity of the `product` table, generated from [`Product`](schema::Product).
+
+A product sold in the shop, priced in \\[`Cents`\\] with a `draft|live` status.
+
+Names and descriptions are translated, see \\[`Product::name`\\].
+"
+        ));
+        assert!(product_doc
+            .contains("| `name_de` | `Option<String>` | `character varying` | yes |  |  |\n"));
+        assert!(product_doc.contains(
+            "| `price_cents` | `i64` | `bigint` | no |  | generated as `price * 100` |\n"
+        ));
+        assert!(product_doc.contains(
+            "# Scopes
+
+| Scope | Parameters | Condition |
+| --- | --- | --- |
+| [`live`](product::Entity::live) |  | `status = 'live'` |
+| [`recent`](product::Entity::recent) | `days: i64` | `created_at > now() - interval '1 day' * :days` |
+"
+        ));
+        assert!(product_doc
+            .ends_with("# Relations\n\n- `id` is referenced by `product_id` of [`event`]\n"));
+
+        assert_eq!(
+            compiler.compile_crate_doc(),
+            format!(
+                "Database entities and queries generated by awto from the models of the `schema` crate.
+
+# Models
+
+| Model | Module | Description |
+| --- | --- | --- |
+| [`Product`](schema::Product) | [`product`] | A product sold in the shop, priced in \\[`Cents`\\] with a `draft\\|live` status. |
+| [`Event`](schema::Event) | [`event`] | Events of [analytics](https://example.com) \\| kept for `90\\|days`, see \\[`Event::name`\\]. |
+| [`Employee`](schema::Employee) | [`employee`] | An employee, managed by another employee unless they lead the shop. |
+
+# Options
+
+- awto-compile: `{}`
+- Databases: `analytics`, `main`
+- Localized fields stored in: columns
+- Outbox: [`outbox`]
+- Change notifications: [`notify`]
+- GraphQL subscriptions: [`subscription`]
+",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+
+        assert_eq!(markdown_code("a`b"), "``a`b``");
+        assert_eq!(markdown_code("`a|b"), "`` `a\\|b ``");
+    }
+
+    #[tokio::test]
+    async fn newtypes() {
+        let compiler = compiler_of(MODELS.to_vec());
+
+        let code = compiler.compile_generated_code();
+
+        let price = Product::database_table()
+            .columns
+            .into_iter()
+            .find(|column| column.name == "price")
+            .unwrap();
+        assert_eq!(price.ty, DatabaseType::BigInt);
+        assert!(code.contains("price : val . price . into ()"));
+        assert!(code.contains("price : :: sea_orm :: Set (self . price . into ())"));
+        assert!(code.contains(
+            "into_active_value (self . price . map (| value | value . 0) . unwrap_or (0))"
+        ));
+    }
+
+    #[tokio::test]
+    async fn deprecated_fields() {
+        let compiler = compiler_of(MODELS.to_vec());
+
+        let code = compiler.compile_generated_code();
+        assert!(code.contains("nickname : val . nickname . into ()"));
+        assert!(code.contains(
+            "nickname : { if self . nickname . is_some () { :: log :: warn ! (\"ignoring deprecated field `Employee.nickname`: use name\") ; } :: sea_orm :: ActiveValue :: unset () }"
+        ));
+        assert!(code.contains("if patch . nickname . is_some () { :: log :: warn ! (\"ignoring deprecated field `Employee.nickname` of patch: use name\") ; }"));
+        let tables = compiler.database_tables();
+        let (model, table) = tables
+            .iter()
+            .find(|(model, _)| model.name == "Employee")
+            .unwrap();
+        assert!(compiler.module_doc(model, table).contains(
+            "| `nickname` | `Option<String>` | `character varying(40)` | yes |  | deprecated since 2024-06-01: use name |\n"
+        ));
+
+        // Deprecated columns are kept when they would be dropped and added again
+        let mut models = MODELS.to_vec();
+        let product = models
+            .iter_mut()
+            .find(|model| model.name == "Product")
+            .unwrap();
+        product
+            .fields
+            .iter_mut()
+            .find(|field| field.name == "price_cents")
+            .unwrap()
+            .deprecated = Some(Deprecation::default());
+        let compiler = compiler_of(models);
+        let table = with_locale_columns(&Product::database_table());
+        let mut db_columns = table.columns.clone();
+        db_columns.last_mut().unwrap().computed = None;
+        assert_eq!(compiler.write_sync_sql(&table, &db_columns).await, "");
+    }
+
+    #[tokio::test]
+    async fn computed_columns() {
+        let compiler = compiler_of(MODELS.to_vec());
+
+        let code = compiler.compile_generated_code();
+
+        assert!(code.contains("price_cents : val . price_cents . into ()"));
+        assert!(code.contains("price_cents : :: sea_orm :: ActiveValue :: unset ()"));
+        assert!(!code.contains("pub price_cents : Option"));
+        assert!(!code.contains("self . price_cents"));
+        assert!(!compiler.compile_factories().contains("price_cents"));
+
+        let table = with_locale_columns(&Product::database_table());
+        let column = table.columns.last().unwrap();
+        assert_eq!(
+            compiler.write_column_sql(column),
+            "price_cents bigint NOT NULL GENERATED ALWAYS AS (price * 100) STORED"
+        );
+
+        let mut db_columns = table.columns.clone();
+        db_columns.last_mut().unwrap().computed = None;
+        assert_eq!(
+            compiler.write_sync_sql(&table, &db_columns).await,
+            "ALTER TABLE product DROP COLUMN price_cents;\n\
+            ALTER TABLE product ADD COLUMN price_cents bigint NOT NULL GENERATED ALWAYS AS (price * 100) STORED;\n"
+        );
+        db_columns.last_mut().unwrap().computed = Some("(price * 100)".to_string());
+        assert_eq!(compiler.write_sync_sql(&table, &db_columns).await, "");
+
+        assert_eq!(
+            computed_column_references(
+                "CAST(price AS double precision) / 100 + coalesce(\"Discount\", 0)::integer * length('it''s a, b')"
+            ),
+            ["price", "Discount"]
+        );
+        let model = MODELS.iter().find(|model| model.name == "Product").unwrap();
+        assert!(computed_column_warnings(model, &table).is_empty());
+        let mut table = table;
+        table.columns.last_mut().unwrap().computed =
+            Some("price_cent * 100 + name_de::integer".to_string());
+        let warnings = computed_column_warnings(model, &table);
+        assert_eq!(
+            warnings.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            ["awto warning[unknown_column_reference] Product.price_cents: computed column references 'price_cent', which is not a column of table 'product'"]
+        );
+    }
+
+    #[tokio::test]
+    async fn patches() {
+        let compiler = compiler_of(MODELS.to_vec());
+
+        let code = compiler.compile_generated_code();
+
+        assert!(code.contains("pub struct ProductPatch"));
+        assert!(!code.contains("pub struct EventPatch"));
+        assert!(code.contains("pub name : Option < String >"));
+        assert!(code.contains(r#"deserialize_with = "nullable""#));
+        assert!(code.contains("pub description : Option < Option < String >>"));
+        assert!(!code.contains("pub created_at : Option"));
+        assert!(code.contains("version : :: sea_orm :: Set (patch . version)"));
+        assert!(code.contains("Self :: update_versioned (db , active_model) . await . map (Some)"));
+    }
+
+    #[tokio::test]
+    async fn sensitive_fields() {
+        let compiler = compiler_of(sensitivity::MODELS.to_vec());
+
+        let code = compiler.compile_generated_code();
+        assert!(code.contains(
+            "pub const EXPORT_FIELDS : & 'static [& 'static str] = & [\"id\" , \"created_at\" , \"updated_at\" , \"email\" , \"credit_limit\"] ;"
+        ));
+        assert!(code.contains(
+            "pub const SENSITIVE_FIELDS : & 'static [& 'static str] = & [\"credit_limit\" , \"password_hash\"] ;"
+        ));
+        assert!(code.contains(
+            "# [serde (default , skip_serializing)] pub password_hash : Option < String > ,"
+        ));
+        assert!(code.contains("::awto::schema::reveal_internal(|| ::serde_json::to_value(row))?"));
+    }
+
+    #[tokio::test]
+    async fn feature_fields() {
+        let mut models = MODELS.to_vec();
+        models[0]
+            .fields
+            .iter_mut()
+            .find(|field| field.name == "status")
+            .unwrap()
+            .feature = Some("workflow".to_string());
+        let compiler = compiler_of(crate::features::with_features(models, &[]).unwrap());
+
+        let code = compiler.compile_generated_code();
+        assert!(!code.contains("pub status :"));
+        assert!(code.contains("status : :: std :: default :: Default :: default () ,"));
+    }
+
+    #[tokio::test]
+    async fn relation_loaders() {
+        let product = MODELS[0].clone();
+        let mut review = product.clone();
+        review.name = "Review".to_string();
+        if let Role::DatabaseTable(table) = &mut review.roles[0] {
+            table.name = "review".to_string();
+            table.fulltext = None;
+            table.columns.push(DatabaseColumn {
+                name: "product_id".to_string(),
+                ty: DatabaseType::Uuid,
+                nullable: false,
+                default: None,
+                unique: false,
+                constraint: None,
+                primary_key: false,
+                references: Some(("product".to_string(), "id".to_string())),
+                reference_constraint: None,
+                cross_database: false,
+                locales: vec![],
+                computed: None,
+                normalize: Vec::new(),
+                fake: None,
+                backfill: None,
+            });
+        }
+        let compiler = compiler_of(vec![product, review]);
+
+        let code = compiler.compile_generated_code();
+
+        assert!(code.contains("pub async fn load_by_id < 'a , C > (db : & 'a C , keys : & [:: uuid :: Uuid] ,) -> Result < :: std :: collections :: HashMap < :: uuid :: Uuid , crate :: product :: Model >"));
+        assert!(code.contains("pub async fn load_by_product_id < 'a , C > (db : & 'a C , keys : & [:: uuid :: Uuid] ,)"));
+        assert!(code.contains(
+            ":: std :: collections :: HashMap < :: uuid :: Uuid , Vec < crate :: review :: Model >"
+        ));
+        assert!(code.contains("pub fn load_by_product_id_query (keys : & [:: uuid :: Uuid]) -> :: sea_orm :: Select < Self >"));
+        assert!(code.contains(". filter (crate :: review :: Column :: ProductId . is_in (keys . iter () . cloned ()))"));
+        assert!(code
+            .contains("for row in Self :: load_by_product_id_query (chunk) . all (db) . await ?"));
+        assert_eq!(code.matches("pub async fn load_by_").count(), 2);
+    }
+
+    #[tokio::test]
+    async fn data_loaders() {
+        let compiler = compiler_of(tree::MODELS.to_vec());
+
+        let code = compiler.compile_data_loaders().to_string();
+        syn::parse_file(&code).unwrap();
+        assert!(code.contains("# [cfg (feature = \"graphql\")] pub mod loader {"));
+        assert!(code.contains(
+            ":: async_graphql :: dataloader :: DataLoader :: new (loader) . max_batch_size (self . max_batch_size) . delay (self . delay)"
+        ));
+        assert!(code.contains(
+            "impl :: async_graphql :: dataloader :: Loader < :: uuid :: Uuid > for CommentByCategoryIdLoader { \
+            type Value = Vec < crate :: comment :: Model > ;"
+        ));
+        assert!(code.contains(
+            "crate :: comment :: Entity :: load_by_category_id (& self . db , keys) . await . map_err (:: std :: sync :: Arc :: new)"
+        ));
+        assert!(code.contains("impl :: async_graphql :: dataloader :: Loader < :: uuid :: Uuid > for CategoryByIdLoader { type Value = crate :: category :: Model ;"));
+        assert!(code.contains(
+            "builder . data (config . data_loader (CategoryByIdLoader :: new (db . clone ()))) \
+            . data (config . data_loader (CategoryByParentIdLoader :: new (db . clone ())))"
+        ));
+        assert_eq!(code.matches(". data (config . data_loader (").count(), 5);
+        assert!(compiler
+            .compile_generated_code()
+            .contains("pub mod loader {"));
+
+        // Resolving the relations of 50 rows runs one query per direction
+        let tests = compiler.compile_smoke_tests();
+        syn::parse_file(&tests).unwrap();
+        assert!(tests.contains("# [cfg (feature = \"graphql\")] # [:: tokio :: test] async fn comment_by_category_id_loads_in_one_query ()"));
+        assert!(tests.contains(
+            ":: database :: comment :: ActiveModel { category_id : :: database :: sea_orm :: Set (key . clone ()) , \
+            .. factories :: comment_with_parents (& db) . await }"
+        ));
+        assert!(tests.contains(
+            "let loader = config . data_loader (:: database :: loader :: CommentByCategoryIdLoader :: new (db . clone ())) ; \
+            let queries = common :: queries () ; \
+            let children = :: futures_util :: future :: join_all (keys . iter () . map (| key | loader . load_one (key . clone ()))) . await ; \
+            assert_eq ! (common :: queries () - queries , 1 , \"loading the comment rows of 50 category rows ran more than one query\") ;"
+        ));
+        assert!(tests.contains("config . data_loader (:: database :: loader :: CategoryByIdLoader :: new (db . clone ()))"));
+        // Self references are loaded like other references
+        assert!(tests.contains(":: database :: category :: ActiveModel { parent_id : :: database :: sea_orm :: Set (Some (key . clone ())) , .. factories :: category () }"));
+        assert_eq!(tests.matches("_loads_in_one_query ()").count(), 3);
+    }
+
+    #[tokio::test]
+    async fn nullable_relations() {
+        let compiler = compiler_of(MODELS.to_vec());
+
+        let table = Employee::database_table();
+        assert_eq!(
+            compiler.write_table_create_sql(&table),
+            "CREATE TABLE IF NOT EXISTS employee (\n  \
+            id uuid NOT NULL DEFAULT uuid_generate_v4() PRIMARY KEY,\n  \
+            created_at timestamp with time zone NOT NULL DEFAULT NOW(),\n  \
+            updated_at timestamp with time zone NOT NULL DEFAULT NOW(),\n  \
+            name character varying(120) NOT NULL,\n  \
+            manager_id uuid,\n  \
+            nickname character varying(40)\n\
+            );\n"
+        );
+        assert_eq!(
+            compiler.write_foreign_keys_sync_sql(&table, &[], &[]),
+            "ALTER TABLE employee ADD CONSTRAINT employee_manager_id_fkey FOREIGN KEY (manager_id) REFERENCES employee (id) ON DELETE SET NULL;\n"
+        );
+        assert_eq!(
+            compiler.write_foreign_keys_sync_sql(
+                &table,
+                &table.columns,
+                &["manager_id".to_string()]
+            ),
+            ""
+        );
+        assert_eq!(
+            compiler.write_foreign_keys_sync_sql(&table, &table.columns, &[]),
+            "ALTER TABLE employee DROP CONSTRAINT employee_manager_id_fkey;\n\
+            ALTER TABLE employee ADD CONSTRAINT employee_manager_id_fkey FOREIGN KEY (manager_id) REFERENCES employee (id) ON DELETE SET NULL;\n"
+        );
+
+        let code = compiler.compile_generated_code();
+
+        assert!(code.contains("pub async fn find_manager < 'a , C > (& self , db : & 'a C ,) -> Result < Option < crate :: employee :: Model > , :: sea_orm :: DbErr >"));
+        assert!(code.contains("let key = match self . manager_id . clone () { Some (key) => key , None => return Ok (None) } ;"));
+        assert!(code.contains("crate :: employee :: Entity :: find () . filter (crate :: employee :: Column :: Id . eq (key)) . one (db) . await"));
+        assert!(code.contains("pub async fn load_by_manager_id < 'a , C >"));
+        assert!(code.contains("manager_id : val . manager_id . into ()"));
+
+        let tables = compiler.database_tables();
+        let (model, table) = tables
+            .iter()
+            .find(|(model, _)| model.name == "Employee")
+            .unwrap();
+        let doc = compiler.module_doc(model, table);
+        assert!(doc.contains("| `manager_id` | `Option<Uuid>` | `uuid` | yes |  | references `employee(id)` on delete set null |\n"));
+        assert!(doc.ends_with(
+            "# Relations\n\n\
+            - `manager_id` optionally references `id` of [`employee`]\n\
+            - `id` is referenced by `manager_id` of [`employee`]\n"
+        ));
+    }
+
+    #[tokio::test]
+    async fn trees() {
+        let compiler = compiler_of(tree::MODELS.to_vec());
+        let tables = compiler.database_tables();
+        let (_, category) = tables
+            .iter()
+            .find(|(model, _)| model.name == "Category")
+            .unwrap();
+
+        // Self references are added once the table exists
+        assert!(!compiler
+            .write_table_create_sql(category)
+            .contains("REFERENCES"));
+        assert_eq!(
+            compiler.write_foreign_keys_sync_sql(category, &[], &[]),
+            "ALTER TABLE category ADD CONSTRAINT category_parent_id_fkey FOREIGN KEY (parent_id) REFERENCES category (id) ON DELETE SET NULL;\n"
+        );
+
+        let code = compiler.compile_generated_code();
+        syn::parse_file(&code).unwrap();
+        assert!(code.contains("pub async fn find_parent < 'a , C > (& self , db : & 'a C ,) -> Result < Option < crate :: category :: Model > , :: sea_orm :: DbErr >"));
+        assert!(code.contains("pub async fn find_children < 'a , C > (& self , db : & 'a C) -> Result < Vec < crate :: category :: Model > , :: sea_orm :: DbErr >"));
+        assert!(code.contains("pub async fn find_descendants < 'a , C > (db : & 'a C , key : :: uuid :: Uuid , max_depth : u32 ,)"));
+        assert!(code.contains(
+            "WITH RECURSIVE tree AS (SELECT category.*, 1 AS awto_tree_depth FROM category WHERE parent_id = $1 \
+            UNION ALL SELECT category.*, tree.awto_tree_depth + 1 FROM category JOIN tree ON category.parent_id = tree.id \
+            WHERE tree.awto_tree_depth < $2) SELECT * FROM tree ORDER BY awto_tree_depth"
+        ));
+        assert!(code.contains("FROM category parent JOIN category child ON parent.id = child.parent_id WHERE child.id = $1"));
+        // References of other tables are not trees
+        assert_eq!(code.matches("pub async fn find_ancestors <").count(), 2);
+        assert!(code.contains(
+            ". filter (crate :: comment :: Column :: ReplyToId . eq (self . id . clone ()))"
+        ));
+    }
+
+    #[tokio::test]
+    async fn factories_with_parents() {
+        let compiler = compiler_of(tree::MODELS.to_vec());
+
+        // The category referenced by a comment is inserted first, optional references are left unset
+        let factories = compiler.compile_factories();
+        syn::parse_file(&factories).unwrap();
+        assert!(factories.contains("pub fn comment () -> :: database :: comment :: ActiveModel"));
+        assert!(!factories
+            .contains("category_id : :: database :: sea_orm :: entity :: IntoActiveValue"));
+        assert!(factories.contains(
+            "pub async fn comment_with_parents (db : & :: database :: sea_orm :: DatabaseConnection) -> :: database :: comment :: ActiveModel { \
+            let category_id_row = :: database :: sea_orm :: ActiveModelTrait :: insert (category () , db) . await \
+            . expect (\"inserting the category row referenced by comment.category_id failed\") ; \
+            :: database :: comment :: ActiveModel { category_id : :: database :: sea_orm :: Set (category_id_row . id . unwrap ()) , .. comment () } }"
+        ));
+        assert!(!factories.contains("fn category_with_parents"));
+
+        let smoke_tests = compiler.compile_smoke_tests();
+        syn::parse_file(&smoke_tests).unwrap();
+        assert!(!smoke_tests.contains("ignore"));
+        assert!(smoke_tests.contains(":: database :: sea_orm :: ActiveModelTrait :: insert (factories :: comment_with_parents (& db) . await , & db)"));
+        assert!(smoke_tests.contains(":: database :: sea_orm :: ActiveModelTrait :: insert (factories :: category () , & db)"));
+
+        // Benchmarks build their rows without a connection
+        let benches = compiler.compile_benches();
+        assert!(!benches.contains("fn bench_comment"));
+        assert!(benches.contains("eprintln ! (\"skipping comment benchmarks: column 'category_id' requires a related row\")"));
+
+        // A required reference to the same table cannot be inserted first
+        let mut self_referencing = tree::MODELS.to_vec();
+        if let Role::DatabaseTable(table) = &mut self_referencing[0].roles[0] {
+            table.columns[4].nullable = false;
+        }
+        let compiler = compiler_of(self_referencing);
+        assert!(!compiler.compile_factories().contains("_with_parents"));
+        let smoke_tests = compiler.compile_smoke_tests();
+        assert!(smoke_tests.contains("# [ignore = \"column 'parent_id' requires a related row\"] async fn category_insert_and_find ()"));
+        assert!(smoke_tests.contains("# [ignore = \"column 'category_id' requires a related row\"] async fn comment_insert_and_find ()"));
+    }
+
+    #[tokio::test]
+    async fn partitions() {
+        let compiler = compiler_of(partitioned::MODELS.to_vec());
+        let tables = compiler.database_tables();
+        let (_, page_view) = &tables[0];
+
+        assert_eq!(
+            compiler.write_table_create_sql(page_view),
+            "CREATE TABLE IF NOT EXISTS page_view (\n  \
+            id uuid NOT NULL DEFAULT uuid_generate_v4(),\n  \
+            created_at timestamp with time zone NOT NULL DEFAULT NOW(),\n  \
+            updated_at timestamp with time zone NOT NULL DEFAULT NOW(),\n  \
+            path character varying(2048) NOT NULL,\n  \
+            session_id uuid,\n  \
+            PRIMARY KEY (id, created_at)\n\
+            ) PARTITION BY RANGE (created_at);\n"
+        );
+
+        let code = compiler.compile_generated_code();
+        syn::parse_file(&code).unwrap();
+        assert!(code.contains("pub mod partition {"));
+        assert!(code.contains(
+            "pub async fn ensure_partitions < 'a , C > (db : & 'a C , through : :: chrono :: NaiveDate) -> Result < Vec < String > , :: sea_orm :: DbErr >"
+        ));
+        assert!(code.contains(
+            "crate :: partition :: ensure_partitions (db , \"page_view\" , crate :: partition :: Interval :: Month , from , through) . await"
+        ));
+        // Partitioned tables cannot be upserted, as their primary key alone is not unique
+        assert!(!code.contains("pub async fn upsert ("));
+
+        let code = compiler_of(MODELS.to_vec()).compile_generated_code();
+        assert!(!code.contains("pub mod partition {"));
+    }
+
+    #[tokio::test]
+    async fn ddl_statements() {
+        let compiler = compiler_of(MODELS.to_vec());
+
+        let code = compiler.compile_ddl_module().to_string();
+        syn::parse_file(&code).unwrap();
+        assert!(code.contains("pub fn create_table_statements () -> Vec < :: sea_orm :: sea_query :: TableCreateStatement >"));
+        assert!(code.contains(
+            ":: sea_orm :: sea_query :: Table :: create () . table (:: sea_orm :: sea_query :: Alias :: new (\"employee\")) . if_not_exists () \
+            . col (:: sea_orm :: sea_query :: ColumnDef :: new (:: sea_orm :: sea_query :: Alias :: new (\"id\")) \
+            . custom (:: sea_orm :: sea_query :: Alias :: new (\"uuid\")) . not_null () . extra (\"DEFAULT uuid_generate_v4()\" . to_string ()) . primary_key ())"
+        ));
+        assert!(code.contains(
+            ". col (:: sea_orm :: sea_query :: ColumnDef :: new (:: sea_orm :: sea_query :: Alias :: new (\"manager_id\")) \
+            . custom (:: sea_orm :: sea_query :: Alias :: new (\"uuid\"))) "
+        ));
+        assert!(code.contains(
+            ":: sea_orm :: sea_query :: ForeignKey :: create () . name (\"employee_manager_id_fkey\") \
+            . from (:: sea_orm :: sea_query :: Alias :: new (\"employee\") , :: sea_orm :: sea_query :: Alias :: new (\"manager_id\")) \
+            . to (:: sea_orm :: sea_query :: Alias :: new (\"employee\") , :: sea_orm :: sea_query :: Alias :: new (\"id\")) \
+            . on_delete (:: sea_orm :: sea_query :: ForeignKeyAction :: SetNull) . to_owned ()"
+        ));
+        // Every created table has a statement, in the order the build script creates them
+        let created: Vec<String> = compiler
+            .created_tables()
+            .into_iter()
+            .map(|table| table.name)
+            .collect();
+        let statements: Vec<&str> = code
+            .split(":: sea_orm :: sea_query :: Table :: create () . table (:: sea_orm :: sea_query :: Alias :: new (\"")
+            .skip(1)
+            .map(|statement| statement.split('"').next().unwrap())
+            .collect();
+        assert_eq!(statements, created);
+
+        let compiler = compiler_of(partitioned::MODELS.to_vec());
+        let code = compiler.compile_ddl_module().to_string();
+        assert!(code.contains(
+            ". index (:: sea_orm :: sea_query :: Index :: create () . primary () \
+            . col (:: sea_orm :: sea_query :: Alias :: new (\"id\")) . col (:: sea_orm :: sea_query :: Alias :: new (\"created_at\"))) . to_owned ()"
+        ));
+        assert!(!code.contains(". primary_key ()"));
+
+        let code = compiler_of(Vec::new()).compile_ddl_module().to_string();
+        assert!(code.contains("vec ! []"));
+    }
+
+    #[tokio::test]
+    async fn external_tables() {
+        let compiler = compiler_of(external::MODELS.to_vec());
+
+        // External tables are never created, and references to them only have a constraint when asked for
+        let created: Vec<String> = compiler
+            .created_tables()
+            .into_iter()
+            .map(|table| table.name)
+            .collect();
+        assert_eq!(created, ["ticket"]);
+        let ticket = external::Ticket::database_table();
+        assert_eq!(
+            compiler.write_foreign_keys_sync_sql(&ticket, &[], &[]),
+            "ALTER TABLE ticket ADD CONSTRAINT ticket_assignee_id_fkey FOREIGN KEY (assignee_id) REFERENCES legacy_user (id) ON DELETE SET NULL;\n"
+        );
+        let mut db_columns = ticket.columns.clone();
+        db_columns[4].references = None;
+        assert_eq!(
+            compiler.write_foreign_keys_sync_sql(
+                &ticket,
+                &db_columns,
+                &["assignee_id".to_string()]
+            ),
+            ""
+        );
+
+        let code = compiler.compile_ddl_module().to_string();
+        assert!(!code.contains("Alias :: new (\"legacy_user\")) . if_not_exists ()"));
+        assert!(!code.contains("ticket_reporter_id_fkey"));
+        assert!(code.contains("ticket_assignee_id_fkey"));
+
+        // Entities and read queries of external tables are still generated, without writes
+        let code = compiler.compile_generated_code();
+        syn::parse_file(&code).unwrap();
+        assert!(!code.contains("pub struct LegacyUserPatch"));
+        let tests = compiler.compile_smoke_tests();
+        assert!(tests
+            .contains("# [ignore = \"table is external\"] async fn legacy_user_insert_and_find"));
+    }
+
+    #[tokio::test]
+    async fn string_primary_keys() {
+        check_database_references(&string_keys::MODELS[..]).unwrap();
+
+        let mut longer = string_keys::MODELS.to_vec();
+        if let Role::DatabaseTable(table) = &mut longer[2].roles[0] {
+            table.columns[4].ty = DatabaseType::Text(Some(1));
+        }
+        assert_eq!(
+            check_database_references(&longer).unwrap_err().to_string(),
+            "`country_code` of `Address` is of type character varying(1), which cannot hold the character varying(2) \
+            values of `Country.code` it references, give them the same type"
+        );
+        if let Role::DatabaseTable(table) = &mut longer[2].roles[0] {
+            table.columns[4].ty = DatabaseType::Uuid;
+        }
+        assert!(check_database_references(&longer).is_err());
+
+        let compiler = compiler_of(string_keys::MODELS.to_vec());
+        let code = compiler.compile_generated_code();
+        syn::parse_file(&code).unwrap();
+        // Lookups take the key as a `&str`, rows with a `Uuid` id are unchanged
+        assert!(code.contains("pub async fn apply_patch < 'a , C > (db : & 'a C , id : & str , patch : crate :: patch :: CountryPatch ,)"));
+        assert!(code.contains("filter (crate :: country :: Column :: Code . eq (id))"));
+        assert!(code.contains("pub async fn apply_patch < 'a , C > (db : & 'a C , id : :: uuid :: Uuid , patch : crate :: patch :: AddressPatch ,)"));
+        assert!(code.contains("keys : & [String] ,) -> Result < :: std :: collections :: HashMap < String , crate :: country :: Model >"));
+
+        // Factories assign unique keys which fit in their column
+        let factories = compiler.compile_factories();
+        assert!(factories.contains("code : :: database :: sea_orm :: Set (unique_key (Some (2)))"));
+        assert!(factories.contains("slug : :: database :: sea_orm :: Set (unique_key (None))"));
+        assert!(compiler
+            .compile_smoke_tests()
+            .contains("inserted . code . unwrap ()"));
+        let compiler = compiler_of(MODELS.to_vec());
+        assert!(!compiler.compile_factories().contains("fn unique_key"));
+    }
+
+    #[tokio::test]
+    async fn cross_database_references() {
+        check_database_references(&cross_database::MODELS[..]).unwrap();
+        check_database_references(&MODELS[..]).unwrap();
+
+        let mut unmarked = cross_database::MODELS.to_vec();
+        if let Role::DatabaseTable(table) = &mut unmarked[1].roles[0] {
+            table.columns[4].cross_database = false;
+        }
+        assert_eq!(
+            check_database_references(&unmarked).unwrap_err().to_string(),
+            "`PageView` of database 'main' references `Visit` of database 'analytics', which cannot have a foreign key, \
+            mark the reference `cross_database`"
+        );
+        let mut same_database = cross_database::MODELS.to_vec();
+        if let Role::DatabaseTable(table) = &mut same_database[0].roles[0] {
+            table.database = None;
+        }
+        assert_eq!(
+            check_database_references(&same_database).unwrap_err().to_string(),
+            "`visit_id` of `PageView` is marked `cross_database`, but `Visit` belongs to the same database 'main', \
+            remove `cross_database`"
+        );
+
+        // Tables of other databases are left out, and references to them never have a foreign key
+        let compiler = compiler_of(database_models(
+            cross_database::MODELS.to_vec(),
+            Some("main"),
+        ));
+        let page_view = cross_database::PageView::database_table();
+        assert_eq!(
+            compiler.write_foreign_keys_sync_sql(&page_view, &[], &[]),
+            ""
+        );
+        assert!(!compiler.compile_ddl_module().to_string().contains("fkey"));
+
+        // The referenced row is found with a connection to the other database, through its entity
+        let code = compiler.compile_generated_code();
+        syn::parse_file(&code).unwrap();
+        assert!(code.contains("pub async fn find_visit < 'a , E , C > (& self , db : & 'a C ,) -> Result < Option < E :: Model > , :: sea_orm :: DbErr >"));
+        assert!(code.contains("E :: find () . filter (:: sea_orm :: sea_query :: Expr :: col (:: sea_orm :: sea_query :: Alias :: new (\"id\")) . eq (key)) . one (db) . await"));
+        assert!(code.contains("let key = match self . first_visit_id . clone () { Some (key) => key , None => return Ok (None) } ;"));
+        assert!(code.contains("pub async fn load_by_visit_id < 'a , C >"));
+        assert!(!code.contains("crate :: visit ::"));
+
+        let doc = compiler.module_doc(&cross_database::MODELS[1], &page_view);
+        assert!(
+            doc.contains("| references `visit(id)` of another database, without a foreign key |")
+        );
+        assert!(doc.contains("- `visit_id` references `id` of `visit` in another database"));
+        assert!(!compiler
+            .compile_smoke_tests()
+            .contains("requires a related row"));
+    }
+
+    #[tokio::test]
+    async fn notify() {
+        let compiler = compiler_of(MODELS.to_vec());
+        let product = Product::database_table();
+
+        let sql = compiler.write_notify_sync_sql(&product, false);
+        assert!(
+            sql.starts_with("CREATE OR REPLACE FUNCTION product_notify() RETURNS trigger AS $$\n")
+        );
+        assert!(sql.contains(
+            "PERFORM pg_notify('product_changes', json_build_object('operation', TG_OP, 'id', OLD.id)::text);"
+        ));
+        assert!(sql.ends_with(
+            "CREATE TRIGGER product_notify AFTER INSERT OR UPDATE OR DELETE ON product FOR EACH ROW EXECUTE FUNCTION product_notify();\n"
+        ));
+        assert_eq!(compiler.write_notify_sync_sql(&product, true), "");
+        assert_eq!(
+            compiler.write_notify_sync_sql(&Event::database_table(), false),
+            ""
+        );
+        assert_eq!(
+            compiler.write_notify_sync_sql(&DatabaseTable { notify: false, ..product }, true),
+            "DROP TRIGGER IF EXISTS product_notify ON product;\nDROP FUNCTION IF EXISTS product_notify();\n"
+        );
+
+        let code = compiler.compile_generated_code();
+        syn::parse_file(&code).unwrap();
+        assert!(code.contains("pub mod notify {"));
+        assert!(code.contains(r#"pub const TABLES : & [& str] = & ["product"] ;"#));
+        assert!(code.contains(
+            r#"crate :: notify :: changes :: < Self > (db , listener , "product" , crate :: product :: Column :: Id , Some (id))"#
+        ));
+        assert!(code.contains("change notifications are not supported on {:?}"));
+        assert_eq!(code.matches("pub fn changes (").count(), 1);
+        assert!(compiler
+            .compile_crate_doc()
+            .contains("- Change notifications: [`notify`]\n"));
+
+        let code = compiler_of(partitioned::MODELS.to_vec()).compile_generated_code();
+        assert!(!code.contains("pub mod notify {"));
+    }
+
+    #[tokio::test]
+    async fn subscriptions() {
+        let compiler = compiler_of(MODELS.to_vec());
+
+        let code = compiler.compile_generated_code();
+        syn::parse_file(&code).unwrap();
+        assert!(code.contains(r#"# [cfg (feature = "graphql")] pub mod subscription {"#));
+        assert!(code.contains("pub struct ProductChange {"));
+        assert!(code.contains(
+            "impl From < crate :: notify :: Change < crate :: product :: Model >> for ProductChange {"
+        ));
+        assert!(code.contains("async fn product_created ("));
+        assert!(code.contains("async fn product_changed (& self , ctx : & :: async_graphql :: Context < '_ > , id : :: async_graphql :: ID ,)"));
+        assert!(code.contains("crate :: product :: Entity :: changes_of (db , listener , id)"));
+        assert!(!code.contains("EventChange"));
+        assert!(compiler
+            .compile_crate_doc()
+            .contains("- GraphQL subscriptions: [`subscription`]\n"));
+
+        let compiler = compiler_of(partitioned::MODELS.to_vec());
+        assert!(!compiler
+            .compile_generated_code()
+            .contains("pub mod subscription {"));
+        assert!(!compiler
+            .compile_crate_doc()
+            .contains("GraphQL subscriptions"));
+    }
+
+    #[tokio::test]
+    async fn temporal() {
+        let compiler = compiler_of(temporal::MODELS.to_vec());
+        let contract = temporal::Contract::database_table();
+        let history = history_table(&contract).unwrap();
+
+        assert_eq!(
+            compiler.write_table_create_sql(&history),
+            "CREATE TABLE IF NOT EXISTS contract_history (\n  \
+            id uuid NOT NULL,\n  \
+            created_at timestamp with time zone NOT NULL DEFAULT NOW(),\n  \
+            updated_at timestamp with time zone NOT NULL DEFAULT NOW(),\n  \
+            title character varying(120) NOT NULL,\n  \
+            amount bigint NOT NULL DEFAULT 0,\n  \
+            signed_at timestamp with time zone,\n  \
+            valid_from timestamp with time zone NOT NULL,\n  \
+            valid_to timestamp with time zone NOT NULL,\n  \
+            operation character varying(6) NOT NULL,\n  \
+            PRIMARY KEY (id, valid_to)\n\
+            );\n"
+        );
+        assert!(history_table(&Product::database_table()).is_none());
+
+        // Previous versions keep the columns dropped from the table, which become nullable
+        let db_columns: Vec<_> = history
+            .columns
+            .iter()
+            .filter(|column| column.name != "signed_at")
+            .cloned()
+            .chain([DatabaseColumn {
+                name: "note".to_string(),
+                ..history.columns[3].clone()
+            }])
+            .collect();
+        assert_eq!(
+            compiler.write_history_sync_sql(&history, &db_columns),
+            "ALTER TABLE contract_history ADD COLUMN signed_at timestamp with time zone;\n\
+            ALTER TABLE contract_history ALTER COLUMN note DROP NOT NULL;\n"
+        );
+        assert_eq!(
+            compiler.write_history_sync_sql(&history, &history.columns),
+            ""
+        );
+
+        let sql = compiler.write_history_trigger_sync_sql(&contract, (false, None));
+        assert!(sql.starts_with(
+            "CREATE OR REPLACE FUNCTION contract_record_history() RETURNS trigger AS $$\n"
+        ));
+        assert!(sql.contains(
+            "INSERT INTO contract_history (id, created_at, updated_at, title, amount, signed_at, valid_from, valid_to, operation)"
+        ));
+        assert!(sql.contains(
+            "COALESCE((SELECT MAX(valid_to) FROM contract_history WHERE id = OLD.id), OLD.created_at), clock_timestamp(), TG_OP);"
+        ));
+        assert!(sql.ends_with(
+            "CREATE TRIGGER contract_record_history AFTER UPDATE OR DELETE ON contract FOR EACH ROW \
+            EXECUTE FUNCTION contract_record_history();\n"
+        ));
+        let source = DatabaseCompiler::history_trigger_body(&contract);
+        assert_eq!(
+            compiler
+                .write_history_trigger_sync_sql(&contract, (true, Some(format!("\n{}\n", source)))),
+            ""
+        );
+        assert!(compiler
+            .write_history_trigger_sync_sql(
+                &contract,
+                (true, Some("BEGIN RETURN NULL; END;".to_string()))
+            )
+            .starts_with("CREATE OR REPLACE FUNCTION contract_record_history()"));
+
+        let portable = DatabaseTable {
+            temporal: Some(Temporal::Portable),
+            ..contract.clone()
+        };
+        assert_eq!(
+            compiler.write_history_trigger_sync_sql(&portable, (true, Some(source))),
+            "DROP TRIGGER IF EXISTS contract_record_history ON contract;\n\
+            DROP FUNCTION IF EXISTS contract_record_history();\n"
+        );
+        assert_eq!(
+            compiler.write_history_trigger_sync_sql(&portable, (false, None)),
+            ""
+        );
+
+        let code = compiler.compile_generated_code();
+        syn::parse_file(&code).unwrap();
+        assert!(code.contains("pub mod temporal {"));
+        assert!(code.contains(
+            "pub async fn as_of < 'a , C > (db : & 'a C , id : :: uuid :: Uuid , at : :: chrono :: DateTime < :: chrono :: FixedOffset > ,)"
+        ));
+        assert!(code.contains("ORDER BY valid_from, valid_to NULLS LAST"));
+        assert!(!code.contains("pub async fn record_history <"));
+        assert!(compiler
+            .compile_crate_doc()
+            .contains("- Version history: [`temporal`]\n"));
+
+        let models = with_temporal(temporal::MODELS.to_vec(), Temporal::Portable);
+        let code = compiler_of(models).compile_generated_code();
+        syn::parse_file(&code).unwrap();
+        assert!(code.contains("pub async fn record_history <"));
+        assert!(code.contains("INSERT INTO contract_history (id, created_at, updated_at, title, amount, signed_at, valid_from, valid_to, operation)"));
+        assert!(code.contains(
+            "Self :: record_history (db , crate :: temporal :: Operation :: Update , previous) . await ?"
+        ));
+
+        let code = compiler_of(MODELS.to_vec()).compile_generated_code();
+        assert!(!code.contains("pub mod temporal {"));
+    }
+
+    #[tokio::test]
+    async fn reserved_identifiers() {
+        let models = reserved::MODELS.to_vec();
+        let compiler = compiler_of(models.clone());
+        let tables = compiler.database_tables();
+        let (_, order) = tables
+            .iter()
+            .find(|(model, _)| model.name == "Order")
+            .unwrap();
+        let (_, model) = tables
+            .iter()
+            .find(|(model, _)| model.name == "Model")
+            .unwrap();
+
+        assert_eq!(
+            compiler.write_table_create_sql(order),
+            "CREATE TABLE IF NOT EXISTS \"order\" (\n  \
+            id uuid NOT NULL DEFAULT uuid_generate_v4() PRIMARY KEY,\n  \
+            created_at timestamp with time zone NOT NULL DEFAULT NOW(),\n  \
+            updated_at timestamp with time zone NOT NULL DEFAULT NOW(),\n  \
+            type character varying(20) NOT NULL,\n  \
+            \"user\" uuid,\n  \
+            \"group\" integer NOT NULL,\n  \
+            match boolean NOT NULL\n\
+            );\n"
+        );
+        let db_columns: Vec<_> = order
+            .columns
+            .iter()
+            .map(|column| DatabaseColumn {
+                unique: false,
+                ..column.clone()
+            })
+            .collect();
+        assert_eq!(
+            compiler.write_column_sync_sql(order, &order.columns[3], &db_columns),
+            "ALTER TABLE \"order\" ADD CONSTRAINT order_type_key UNIQUE (type);\n"
+        );
+        assert_eq!(
+            compiler.write_foreign_keys_sync_sql(model, &[], &[]),
+            "ALTER TABLE model ADD CONSTRAINT model_order_fkey FOREIGN KEY (\"order\") REFERENCES \"order\" (id);\n"
+        );
+
+        let code = compiler.compile_generated_code();
+        syn::parse_file(&code).unwrap();
+        assert!(code.contains("r#type : val . r#type . into ()"));
+        assert!(code.contains("pub r#where : Option < Option < String >>"));
+        assert!(code.contains("crate :: model :: Column :: Order . is_in"));
+        assert!(code.contains(
+            "impl :: std :: convert :: From < :: schema :: Model > for crate :: model :: Model"
+        ));
+
+        check_identifiers(&models).unwrap();
+        let mut colliding = models.clone();
+        if let Role::DatabaseTable(table) = &mut colliding[0].roles[0] {
+            table.columns.push(DatabaseColumn {
+                name: "group_".to_string(),
+                ..table.columns[5].clone()
+            });
+        }
+        assert_eq!(
+            check_identifiers(&colliding).unwrap_err().to_string(),
+            "columns `group` and `group_` of `order` are both generated as `Group`, rename one of them"
+        );
+        let mut colliding = models;
+        if let Role::DatabaseTable(table) = &mut colliding[1].roles[0] {
+            table.name = "patch".to_string();
+        }
+        assert_eq!(
+            check_identifiers(&colliding).unwrap_err().to_string(),
+            "table `patch` and the generated `patch` module are both generated as `patch`, rename one of them"
+        );
+    }
+
+    #[tokio::test]
+    async fn long_identifiers() {
+        const LONG_TABLE: &str = "customer_subscription_invoice_line_items";
+
+        let with_employee_table = |f: &dyn Fn(&mut DatabaseTable)| {
+            let mut models = MODELS.to_vec();
+            let employee = models
+                .iter_mut()
+                .find(|model| model.name == "Employee")
+                .unwrap();
+            if let Role::DatabaseTable(table) = &mut employee.roles[0] {
+                f(table);
+            }
+            models
+        };
+        let models = with_employee_table(&|table| {
+            table.name = LONG_TABLE.to_string();
+            table.columns[4].name = "tenant_id_created_at".to_string();
+            table.columns[4].references = Some((LONG_TABLE.to_string(), "id".to_string()));
+        });
+
+        check_identifiers(&models).unwrap();
+        let compiler = compiler_of(models);
+        let tables = compiler.database_tables();
+        let (_, table) = tables
+            .iter()
+            .find(|(model, _)| model.name == "Employee")
+            .unwrap();
+        assert_eq!(
+            compiler.write_foreign_keys_sync_sql(table, &[], &[]),
+            "ALTER TABLE customer_subscription_invoice_line_items \
+            ADD CONSTRAINT customer_subscription_invoic_tenant_id_created_at_cebeee85_fkey \
+            FOREIGN KEY (tenant_id_created_at) REFERENCES customer_subscription_invoice_line_items (id) ON DELETE SET NULL;\n"
+        );
+        assert!(compiler
+            .compile_generated_code()
+            .contains("\"customer_subscription_invoic_tenant_id_created_at_cebeee85_fkey\""));
+
+        let too_long = with_employee_table(&|table| table.columns[3].name = "n".repeat(64));
+        assert_eq!(
+            check_identifiers(&too_long).unwrap_err().to_string(),
+            format!(
+                "column `{}` of `employee` is 64 bytes long, over the 63 bytes Postgres keeps of identifiers, shorten it",
+                "n".repeat(64)
+            )
+        );
+        let too_long = with_employee_table(&|table| {
+            table.name = format!("{}notify", "employee_".repeat(6));
+            table.notify = true;
+        });
+        assert_eq!(
+            check_identifiers(&too_long).unwrap_err().to_string(),
+            format!(
+                "notification channel `{0}_changes` of `{0}` is 68 bytes long, over the 63 bytes Postgres keeps of identifiers, shorten it",
+                format!("{}notify", "employee_".repeat(6))
+            )
+        );
+
+        // Derived names may collide with the names of other tables once shortened
+        let long_name = format!("{}_tenant_id_created_at", LONG_TABLE);
+        let history_name = derived_name(&[&long_name], "history");
+        let mut colliding = with_employee_table(&|table| {
+            table.name = long_name.clone();
+            table.temporal = Some(Temporal::default());
+        });
+        let event = colliding
+            .iter_mut()
+            .find(|model| model.name == "Event")
+            .unwrap();
+        if let Role::DatabaseTable(table) = &mut event.roles[0] {
+            table.name = history_name;
+        }
+        assert_eq!(
+            check_identifiers(&colliding).unwrap_err().to_string(),
+            "table `customer_subscription_invoice_line_items_tenan_c775369c_history` and \
+            the history table of `customer_subscription_invoice_line_items_tenant_id_created_at` are both generated as \
+            `customer_subscription_invoice_line_items_tenan_c775369c_history`, rename one of them"
+        );
+    }
+
+    #[tokio::test]
+    async fn annotated_sources() {
+        let compiler = compiler_of(MODELS.to_vec()).with_annotations(true);
+        let version = crate::provenance::GENERATOR_VERSION;
+
+        let table = Employee::database_table();
+        let sql = compiler.write_table_create_sql(&table);
+        assert!(sql.starts_with(&format!(
+            "CREATE TABLE IF NOT EXISTS employee (\n  \
+            -- @awto: model=Employee field=id generator=database/ddl v{}\n  \
+            id uuid NOT NULL",
+            version
+        )));
+        assert_eq!(
+            crate::provenance::strip_annotations(&sql),
+            compiler_of(MODELS.to_vec()).write_table_create_sql(&table)
+        );
+
+        let code = compiler.compile_generated_code();
+        assert!(code.contains(&format!(
+            "// @awto: model=Employee generator=database/entity v{}\n",
+            version
+        )));
+        assert!(!compiler_of(MODELS.to_vec())
+            .compile_generated_code()
+            .contains("@awto:"));
+    }
+
+    #[tokio::test]
+    async fn versioned_updates() {
+        let compiler = compiler_of(MODELS.to_vec());
+
+        let code = compiler.compile_generated_code();
+
+        assert!(code.contains("pub mod version"));
+        assert_eq!(code.matches("pub async fn update_versioned").count(), 1);
+        assert!(code.contains("active_model . version = :: sea_orm :: Set (version + 1)"));
+        assert!(code.contains(". filter (crate :: product :: Column :: Version . eq (version))"));
+        assert!(code.contains("crate :: version :: StaleVersionError { id , version }"));
+    }
+
+    #[tokio::test]
+    async fn outbox_events() {
+        let compiler = compiler_of(MODELS.to_vec());
+
+        let code = compiler.compile_generated_code();
+
+        assert_eq!(code.matches("pub async fn record_event").count(), 1);
+        assert!(code.contains(
+            r#"crate :: outbox :: record (db , "product" , kind , model . id , & payload) . await"#
+        ));
+        assert!(code.contains("Self :: record_event (db , crate :: outbox :: EventKind :: Updated , & model) . await ?"));
+        assert_eq!(
+            compiler.outbox_table().map(|table| table.name),
+            Some("outbox".to_string())
+        );
+        assert_eq!(
+            entity_table_names(&MODELS.to_vec()),
+            ["product", "event", "employee", "outbox"]
+        );
+
+        let models: Vec<_> = MODELS
+            .iter()
+            .filter(|model| model.name != "Product")
+            .cloned()
+            .collect();
+        assert_eq!(compiler_of(models.clone()).outbox_table(), None);
+        assert_eq!(entity_table_names(&models), ["event", "employee"]);
+
+        assert_eq!(
+            compiler.write_table_create_sql(&outbox_table()),
+            "CREATE TABLE IF NOT EXISTS outbox (\n  \
+            id uuid NOT NULL DEFAULT uuid_generate_v4() PRIMARY KEY,\n  \
+            created_at timestamp with time zone NOT NULL DEFAULT clock_timestamp(),\n  \
+            event_type character varying(255) NOT NULL,\n  \
+            aggregate_type character varying(255) NOT NULL,\n  \
+            aggregate_id uuid NOT NULL,\n  \
+            payload character varying NOT NULL,\n  \
+            published_at timestamp with time zone\n\
+            );\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn idempotent_creates() {
+        let compiler = compiler_of(idempotent::MODELS.to_vec());
+
+        let code = compiler.compile_generated_code();
+        syn::parse_file(&code).unwrap();
+        let modules = compiler.compile_sea_orm_modules().unwrap();
+        syn::parse_file(&modules).unwrap();
+        assert!(modules.contains(
+            "pub mod idempotency_keys {    pub use crate::entities::idempotency_keys::*;"
+        ));
+        assert!(modules.contains("ON CONFLICT (key) DO NOTHING"));
+        assert_eq!(code.matches("pub async fn insert_idempotent").count(), 2);
+        assert!(code.contains(r#"crate :: idempotency_keys :: claim (db , "order" , key , request_hash , ttl) . await ?"#));
+        assert!(code.contains("let row_key = :: uuid :: Uuid :: parse_str (& row_key)"));
+        assert_eq!(
+            code.matches(":: uuid :: Uuid :: parse_str (& row_key)")
+                .count(),
+            1
+        );
+        assert!(code.contains(
+            "Self :: record_event (db , crate :: outbox :: EventKind :: Created , model) . await ?"
+        ));
+        assert_eq!(
+            entity_table_names(&idempotent::MODELS.to_vec()),
+            ["order", "voucher", "outbox", "idempotency_keys"]
+        );
+        assert_eq!(
+            entity_table_names(&MODELS.to_vec()),
+            ["product", "event", "employee", "outbox"]
+        );
+
+        let smoke_tests = compiler.compile_smoke_tests();
+        assert!(smoke_tests.contains("async fn order_idempotent_create_replays"));
+        assert!(smoke_tests.contains("async fn voucher_idempotent_create_replays"));
+        assert!(!compiler_of(MODELS.to_vec())
+            .compile_smoke_tests()
+            .contains("idempotent_create_replays"));
+
+        assert_eq!(
+            compiler.write_table_create_sql(&idempotency_table()),
+            "CREATE TABLE IF NOT EXISTS idempotency_keys (\n  \
+            key character varying(255) NOT NULL PRIMARY KEY,\n  \
+            created_at timestamp with time zone NOT NULL DEFAULT now(),\n  \
+            scope character varying(255) NOT NULL,\n  \
+            request_hash character varying(64) NOT NULL,\n  \
+            row_key character varying(255)\n\
+            );\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn localized_columns() {
+        let compiler = compiler_of(MODELS.to_vec());
+
+        let code = compiler.compile_generated_code();
+
+        assert!(code.contains("pub mod locale"));
+        assert!(code.contains(r#"pub const LOCALIZED_FIELDS : & 'static [(& 'static str , & 'static [& 'static str])] = & [("name" , & ["en" , "de"]) , ("description" , & ["en" , "de" , "pt-BR"]) ,]"#));
+        assert!(code
+            .contains(r#"crate :: locale :: fallback_chain (locale , & ["en" , "de" , "pt-BR"])"#));
+        assert!(code.contains(r#""pt-BR" => model . description_pt_br . clone () ,"#));
+        assert_eq!(code.matches("pub async fn find_localized").count(), 1);
+        assert!(
+            code.contains(r#"("name" , "de") => Some (crate :: product :: Column :: NameDe) ,"#)
+        );
+        assert!(code.contains(r#"("name" , "en") if value . is_none () =>"#));
+        assert!(code.contains(r#""pt-br" => "pt-BR" ,"#));
+        assert!(!code.contains("product_translations"));
+
+        let names: Vec<_> = with_locale_columns(&Product::database_table())
+            .columns
+            .into_iter()
+            .map(|column| column.name)
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "id",
+                "created_at",
+                "updated_at",
+                "name",
+                "name_de",
+                "price",
+                "description",
+                "description_de",
+                "description_pt_br",
+                "status",
+                "version",
+                "price_cents",
+            ]
+        );
+        let table = with_locale_columns(&Product::database_table());
+        assert_eq!(
+            compiler.write_column_sql(&table.columns[4]),
+            "name_de character varying"
+        );
+
+        let models = with_localization(MODELS.to_vec(), Localization::Table);
+        assert!(models
+            .iter()
+            .flat_map(|model| &model.roles)
+            .all(|role| match role {
+                Role::DatabaseTable(table) | Role::DatabaseSubTable(table) => {
+                    table.localization == Localization::Table
+                }
+                _ => true,
+            }));
+        let table = models[0].roles.iter().find_map(|role| match role {
+            Role::DatabaseTable(table) => Some(table),
+            _ => None,
+        });
+        assert_eq!(with_locale_columns(table.unwrap()).columns.len(), 9);
+    }
+
+    #[tokio::test]
+    async fn translations_table_queries() {
+        let models = with_localization(MODELS.to_vec(), Localization::Table);
+        let compiler = compiler_of(models.clone());
+
+        let code = compiler.compile_generated_code();
+
+        assert!(code.contains("crate :: product_translations :: Entity :: find () . filter (crate :: product_translations :: Column :: ProductId . is_in (models . iter () . map (| model | model . id)) ,)"));
+        assert!(code.contains(r#"("name" , "de") => None ,"#));
+        assert!(code.contains(
+            "ON CONFLICT (product_id, locale, field) DO UPDATE SET value = EXCLUDED.value"
+        ));
+        assert!(!code.contains("NameDe"));
+        assert_eq!(
+            entity_table_names(&models),
+            [
+                "product",
+                "event",
+                "employee",
+                "product_translations",
+                "outbox"
+            ]
+        );
+        assert!(translations_table(&Event::database_table()).is_none());
+        assert!(translations_table(&Product::database_table()).is_none());
+
+        let table = compiler.translations_tables().remove(0);
+        assert_eq!(
+            compiler.write_table_create_sql(&table),
+            "CREATE TABLE IF NOT EXISTS product_translations (\n  \
+            id uuid NOT NULL DEFAULT uuid_generate_v4() PRIMARY KEY,\n  \
+            product_id uuid NOT NULL,\n  \
+            locale character varying(35) NOT NULL,\n  \
+            field character varying(63) NOT NULL,\n  \
+            value character varying NOT NULL\n\
+            );\n"
+        );
+        assert_eq!(
+            compiler.write_translations_sync_sql(&table, (false, false)),
+            "CREATE UNIQUE INDEX IF NOT EXISTS product_translations_key ON product_translations (product_id, locale, field);\n\
+            ALTER TABLE product_translations DROP CONSTRAINT IF EXISTS product_translations_product_id_fkey, \
+            ADD CONSTRAINT product_translations_product_id_fkey FOREIGN KEY (product_id) REFERENCES product (id) ON DELETE CASCADE;\n"
+        );
+        assert_eq!(
+            compiler.write_translations_sync_sql(&table, (true, true)),
+            ""
+        );
+    }
+
+    #[tokio::test]
+    async fn fulltext_index_sync() {
+        let compiler = compiler_of(MODELS.to_vec());
+        let table = Product::database_table();
+        let index_name =
+            DatabaseCompiler::fulltext_index_name(&table, table.fulltext.as_ref().unwrap());
+
+        let sql =
+            compiler.write_fulltext_sync_sql(&table, &["product_fulltext_0000_idx".to_string()]);
+        assert_eq!(
+            sql,
+            format!(
+                "DROP INDEX IF EXISTS product_fulltext_0000_idx;\n\
+                CREATE INDEX IF NOT EXISTS {} ON product USING GIN (to_tsvector('english'::regconfig, coalesce(name, '') || ' ' || coalesce(description, '')));\n",
+                index_name
+            )
+        );
+
+        assert_eq!(compiler.write_fulltext_sync_sql(&table, &[index_name]), "");
+        assert_eq!(
+            compiler.write_fulltext_sync_sql(&Event::database_table(), &[]),
+            ""
+        );
+    }
+
+    #[tokio::test]
+    async fn policy_sync() {
+        let compiler = compiler_of(MODELS.to_vec());
+        let table = Event::database_table();
+
+        assert_eq!(
+            compiler.write_policy_sync_sql(&table, false, &[]),
+            "ALTER TABLE event ENABLE ROW LEVEL SECURITY;\n\
+            CREATE POLICY recent_events ON event USING (created_at > now() - interval '90 days');\n"
+        );
+        assert_eq!(
+            compiler.write_policy_sync_sql(&table, true, &["legacy_events".to_string()]),
+            "CREATE POLICY recent_events ON event USING (created_at > now() - interval '90 days');\n"
+        );
+        assert_eq!(
+            compiler.write_policy_sync_sql(&table, true, &["recent_events".to_string()]),
+            ""
+        );
+        assert_eq!(
+            compiler.write_policy_sync_sql(&Product::database_table(), false, &[]),
+            ""
+        );
+    }
+
+    #[tokio::test]
+    async fn smoke_tests() {
+        let compiler = compiler_of(MODELS.to_vec());
+
+        let code = compiler.compile_smoke_tests();
+
+        assert!(code.contains("async fn product_insert_and_find ()"));
+        assert!(code.contains("name : :: database :: sea_orm :: entity :: IntoActiveValue :: into_active_value (\"awto\" . to_string ())"));
+        assert!(!code.contains("price :"));
+        assert!(!code.contains("ignore"));
+        assert!(code.contains("async fn product_concurrent_update_is_stale ()"));
+        assert!(code.contains("async fn product_batch_insert_and_upsert ()"));
+        assert!(!code.contains("async fn event_batch_insert_and_upsert ()"));
+        assert!(!code.contains("async fn event_concurrent_update_is_stale ()"));
+        assert!(code.contains("fn create_table_statements () { let statements : Vec < String > = :: database :: ddl :: create_table_statements ()"));
+        assert!(code.contains("\"CREATE TABLE IF NOT EXISTS \\\"product\\\" (\""));
+    }
+
+    #[tokio::test]
+    async fn benches() {
+        let compiler = compiler_of(MODELS.to_vec());
+
+        let factories = compiler.compile_factories();
+        assert!(factories.contains("pub fn product () -> :: database :: product :: ActiveModel"));
+        assert!(factories.contains("pub fn event () -> :: database :: event :: ActiveModel"));
+        assert!(factories.contains("properties : :: database :: sea_orm :: Set (:: std :: default :: Default :: default ())"));
+        assert!(compiler.compile_smoke_tests().starts_with(&factories));
+
+        let code = compiler.compile_benches();
+        assert!(code.starts_with(&factories));
+        assert!(code.contains("fn bench_product (c : & mut :: criterion :: Criterion"));
+        for bench in [
+            "\"insert\"",
+            "\"insert_batch_1k\"",
+            "\"find_by_id\"",
+            "\"page_100k\"",
+        ] {
+            assert!(code.contains(&format!("group . bench_function ({}", bench)));
+        }
+        assert!(code.contains("(0 .. common :: SEED_ROWS) . map (| _ | factories :: product ())"));
+        assert!(!code.contains("fn bench_event"));
+        assert!(code.contains("eprintln ! (\"skipping event benchmarks: table is readonly\")"));
+    }
+
+    #[test]
+    fn stale_entities() {
+        let out_dir = env::temp_dir().join(format!("awto-compile-entities-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&out_dir);
+        std::fs::create_dir_all(&out_dir).unwrap();
+        let tables =
+            |names: &[&str]| -> Vec<String> { names.iter().map(ToString::to_string).collect() };
+        for name in ["product", "employee", "app"] {
+            std::fs::write(out_dir.join(format!("{}.rs", name)), "").unwrap();
+        }
+
+        remove_stale_entities(&out_dir, &tables(&["product", "employee"])).unwrap();
+        assert!(out_dir.join("employee.rs").exists());
+
+        remove_stale_entities(&out_dir, &tables(&["product"])).unwrap();
+        assert!(!out_dir.join("employee.rs").exists());
+        assert!(out_dir.join("product.rs").exists());
+
+        // Removing the last model leaves no entity behind, and files of awto are never deleted
+        remove_stale_entities(&out_dir, &[]).unwrap();
+        assert!(!out_dir.join("product.rs").exists());
+        assert!(out_dir.join("app.rs").exists());
+        assert_eq!(
+            std::fs::read_to_string(out_dir.join(ENTITIES_FILE)).unwrap(),
+            ""
+        );
+
+        std::fs::remove_dir_all(out_dir).unwrap();
+    }
+
+    #[test]
+    fn deprecated_entity_fields() {
+        let deprecation = Deprecation {
+            note: Some("use name".to_string()),
+            since: Some("2024-06-01".to_string()),
+        };
+        let source = "#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]\n#[sea_orm(table_name = \"employee\")]\npub struct Model {\n    #[sea_orm(primary_key, auto_increment = false)]\n    pub id: Uuid,\n    pub nickname: Option<String>,\n    pub nickname_locale : Option<String>,\n}\n\npub struct Relation {\n    pub nickname: Option<String>,\n}\n";
+
+        assert_eq!(
+            deprecate_fields(source, &[("nickname", &deprecation), ("nickname_locale", &Deprecation::default())]),
+            "#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]\n#[sea_orm(table_name = \"employee\")]\npub struct Model {\n    #[sea_orm(primary_key, auto_increment = false)]\n    pub id: Uuid,\n    #[deprecated(since = \"2024-06-01\", note = \"use name\")]\n    pub nickname: Option<String>,\n    #[deprecated]\n    pub nickname_locale : Option<String>,\n}\n\npub struct Relation {\n    pub nickname: Option<String>,\n}\n"
+        );
+    }
+
+    #[test]
+    fn redacted_entity_debug() {
+        let source = "#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]\n#[sea_orm(table_name = \"account\")]\npub struct Model {\n    #[sea_orm(primary_key, auto_increment = false)]\n    pub id: Uuid,\n    pub r#type: String,\n    pub password_hash: String,\n}\n";
+
+        assert_eq!(
+            redact_debug(source, &["password_hash"]),
+            "#[derive(Clone, PartialEq, DeriveEntityModel)]\n#[sea_orm(table_name = \"account\")]\npub struct Model {\n    #[sea_orm(primary_key, auto_increment = false)]\n    pub id: Uuid,\n    pub r#type: String,\n    pub password_hash: String,\n}\n\n\
+            impl std::fmt::Debug for Model {\n    \
+                #[allow(deprecated)]\n    \
+                fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {\n        \
+                    f.debug_struct(\"Model\")\n            \
+                        .field(\"id\", &self.id)\n            \
+                        .field(\"type\", &self.r#type)\n            \
+                        .field(\"password_hash\", &format_args!(\"********\"))\n            \
+                        .finish()\n    \
+                }\n\
+            }\n"
+        );
+    }
+
+    #[test]
+    fn entity_model_attributes() {
+        let models =
+            with_entity_derives(MODELS.to_vec(), &["acme_telemetry::Instrumented", "Hash"]);
+        let table = |name: &str| {
+            models
+                .iter()
+                .find(|model| model.name == name)
+                .unwrap()
+                .roles
+                .iter()
+                .find_map(|role| match role {
+                    Role::DatabaseTable(table) => Some(table.clone()),
+                    _ => None,
+                })
+                .unwrap()
+        };
+        let attributes = entity_attributes(&table("Event")).unwrap();
+        assert_eq!(
+            attributes,
+            [
+                "#[derive(acme_telemetry::Instrumented, Hash)]",
+                "#[doc(alias = \"audit_event\")]",
+            ]
+        );
+        assert_eq!(
+            entity_attributes(&table("Product")).unwrap(),
+            ["#[derive(acme_telemetry::Instrumented, Hash)]"]
+        );
+        assert!(entity_attributes(&Product::database_table())
+            .unwrap()
+            .is_empty());
+
+        // The attributes come after those of sea-orm, right before the struct
+        let source = "#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]\n#[sea_orm(table_name = \"event\")]\npub struct Model {\n    #[sea_orm(primary_key, auto_increment = false)]\n    pub id: Uuid,\n}\n\n#[derive(Copy, Clone, Debug, EnumIter)]\npub enum Relation {}\n";
+        assert_eq!(
+            add_model_attributes(source, &attributes),
+            "#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]\n#[sea_orm(table_name = \"event\")]\n#[derive(acme_telemetry::Instrumented, Hash)]\n#[doc(alias = \"audit_event\")]\npub struct Model {\n    #[sea_orm(primary_key, auto_increment = false)]\n    pub id: Uuid,\n}\n\n#[derive(Copy, Clone, Debug, EnumIter)]\npub enum Relation {}\n"
+        );
+
+        let invalid = DatabaseTable {
+            entity_attrs: vec!["#[serde(rename_all = ".to_string()],
+            ..Product::database_table()
+        };
+        assert!(entity_attributes(&invalid)
+            .unwrap_err()
+            .to_string()
+            .starts_with("invalid entity attribute `#[serde(rename_all = ` of table `product`"));
+        let invalid = DatabaseTable {
+            entity_derives: vec!["acme telemetry".to_string()],
+            ..Product::database_table()
+        };
+        assert_eq!(
+            entity_attributes(&invalid).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+
+    #[tokio::test]
+    async fn map_columns() {
+        let column = |models: &[Model]| {
+            let table = models
+                .iter()
+                .find(|model| model.name == "Event")
+                .unwrap()
+                .roles[0]
+                .clone();
+            match table {
+                Role::DatabaseTable(table) => table
+                    .columns
+                    .into_iter()
+                    .find(|column| column.name == "properties")
+                    .unwrap(),
+                _ => unreachable!(),
+            }
+        };
+        assert_eq!(column(&MODELS.to_vec()).ty, DatabaseType::Jsonb);
+        assert_eq!(
+            column(&with_map_storage(MODELS.to_vec(), MapStorage::Jsonb)).ty,
+            DatabaseType::Jsonb
+        );
+        let models = with_map_storage(MODELS.to_vec(), MapStorage::Hstore);
+        assert_eq!(column(&models).ty, DatabaseType::Hstore);
+
+        let compiler = compiler_of(models);
+        let tables: Vec<_> = compiler
+            .database_tables()
+            .into_iter()
+            .map(|(_, table)| table.clone())
+            .collect();
+        assert_eq!(
+            DatabaseCompiler::write_extensions_sql(&tables),
+            "CREATE EXTENSION IF NOT EXISTS hstore;\n"
+        );
+        assert_eq!(
+            DatabaseCompiler::write_extensions_sql(&[Product::database_table()]),
+            ""
+        );
+        assert!(compiler
+            .write_table_create_sql(&tables[1])
+            .contains("  properties hstore NOT NULL\n"));
+
+        let code = compiler.compile_generated_code();
+        assert!(code.contains("pub mod map"));
+        assert!(code.contains("pub struct JsonMap<T>(pub HashMap<String, T>);"));
+        let compiler = compiler_of(database_models(MODELS.to_vec(), Some("main")));
+        assert!(!compiler.compile_generated_code().contains("pub mod map"));
+    }
+
+    #[tokio::test]
+    async fn entity_visibility() {
+        let models = with_visibility(MODELS.to_vec(), Visibility::Public, Visibility::Crate);
+        let compiler = compiler_of(models);
+
+        let code = compiler.compile_sea_orm_modules().unwrap();
+        syn::parse_file(&code).unwrap();
+        assert!(code.starts_with(
+            r#"mod entities {pub mod product {    sea_orm::include_model!("product");}"#
+        ));
+        let files_code = compiler_of(with_visibility(
+            MODELS.to_vec(),
+            Visibility::Public,
+            Visibility::Crate,
+        ))
+        .with_entity_files(true)
+        .compile_sea_orm_modules()
+        .unwrap();
+        syn::parse_file(&files_code).unwrap();
+        assert!(files_code.starts_with("mod entities {pub mod product;"));
+        assert!(!files_code.contains("include_model!"));
+        assert!(code.contains(
+            "pub mod product {    #[allow(unused_imports)] pub use crate::entities::product::{Column, Entity, Model, PrimaryKey, Relation};    #[allow(unused_imports)] pub(crate) use crate::entities::product::ActiveModel;}"
+        ));
+
+        // Tables keep the visibility of their `#[awto(visibility(...))]` attributes
+        let models = with_visibility(MODELS.to_vec(), Visibility::Crate, Visibility::Public);
+        let event = models
+            .iter()
+            .find(|model| model.name == "Event")
+            .and_then(|model| {
+                model.roles.iter().find_map(|role| match role {
+                    Role::DatabaseTable(table) => Some(table.visibility),
+                    _ => None,
+                })
+            })
+            .unwrap();
+        assert_eq!(event.entity(), Visibility::Crate);
+        assert_eq!(event.active_model(), Visibility::Crate);
+    }
+
+    #[test]
+    fn map_entity_fields() {
+        let source = "pub struct Model {\n    #[sea_orm(primary_key, auto_increment = false)]\n    pub id: Uuid,\n    #[sea_orm(column_type = \"JsonBinary\")]\n    pub properties: Json,\n}\n";
+
+        assert_eq!(
+            wrap_map_fields(source, &[("properties", "crate::map::JsonMap<String>".to_string())]),
+            "pub struct Model {\n    #[sea_orm(primary_key, auto_increment = false)]\n    pub id: Uuid,\n    #[sea_orm(column_type = \"JsonBinary\")]\n    pub properties: crate::map::JsonMap<String>,\n}\n"
+        );
+        assert_eq!(
+            map_entity_ty("Option<HashMap<String,i64>>")
+                .unwrap()
+                .to_string(),
+            "crate :: map :: JsonMap < i64 >"
+        );
+        assert!(map_entity_ty("Vec<String>").is_none());
+    }
+
+    #[tokio::test]
+    async fn normalized_columns() {
+        let mut models = MODELS.to_vec();
+        let employee = models
+            .iter_mut()
+            .find(|model| model.name == "Employee")
+            .unwrap();
+        let table = employee
+            .roles
+            .iter_mut()
+            .find_map(|role| match role {
+                Role::DatabaseTable(table) => Some(table),
+                _ => None,
+            })
+            .unwrap();
+        let name = table
+            .columns
+            .iter_mut()
+            .find(|column| column.name == "name")
+            .unwrap();
+        name.normalize
+            .push(Normalizer::Function("crate::normalize::name".to_string()));
+        let handle = DatabaseColumn {
+            name: "handle".to_string(),
+            ty: DatabaseType::Text(Some(40)),
+            unique: true,
+            normalize: vec![
+                Normalizer::SlugifyFrom("name".to_string()),
+                Normalizer::Lowercase,
+            ],
+            ..name.clone()
+        };
+        table.columns.push(handle);
+        let compiler = compiler_of(models);
+
+        let code = compiler.compile_generated_code();
+        assert!(code.contains("pub mod normalization {"));
+        assert!(code.contains("impl crate :: employee :: ActiveModel { # [doc = \" Normalizes the set values of the normalized columns of the `employee` table.\"]"));
+        assert!(code.contains("if ! self . handle . is_unchanged () { let value = self . handle . clone () . take () ; let value = value . filter (| value | ! value . trim () . is_empty ()) . or_else (|| self . name . clone () . take ()) . map (| value | crate :: normalization :: slugify (& value)) . filter (| slug | ! slug . is_empty ()) ; let value = value . map (| value | value . to_lowercase ()) ; if let Some (value) = value { self . handle = :: sea_orm :: Set (value) ; } }"));
+        assert!(code.contains("if self . name . is_set () { let value = self . name . clone () . take () ; let value = value . map (| value | value . trim () . to_string ()) ; let value = value . map (| value | :: schema :: normalize :: name (& value)) ;"));
+        assert!(code.contains("pub async fn insert_unique_handle < 'a , C , M >"));
+        assert!(code.contains("Err (err) if crate :: normalization :: is_unique_violation (& err , \"employee_handle_key\") => continue ,"));
+        assert!(code.contains(". map (:: sea_orm :: IntoActiveModel :: into_active_model) . map (crate :: employee :: ActiveModel :: normalize) . collect ()"));
+        assert!(!code.contains("crate :: product :: ActiveModel :: normalize"));
+
+        let tests = compiler.compile_smoke_tests();
+        assert!(tests.contains("async fn employee_normalized_before_save ()"));
+        assert!(tests.contains("let inserted = :: database :: employee :: Entity :: insert_unique_handle (& db , factories :: employee ()) . await"));
+        assert!(tests.contains(
+            "assert_eq ! (found . name , :: schema :: normalize :: name (& \"awto\")) ;"
+        ));
+        assert!(tests.contains("assert ! (found . handle . starts_with (& \"awto\")"));
+        assert!(tests.contains("name : :: database :: sea_orm :: entity :: IntoActiveValue :: into_active_value (\" awto \" . to_string ()) . into ()"));
+        assert!(!tests.contains("handle : :: database"));
+        assert!(!tests.contains("product_normalized_before_save"));
+
+        let tables = compiler.database_tables();
+        let (model, table) = tables
+            .iter()
+            .find(|(model, _)| model.name == "Employee")
+            .unwrap();
+        assert!(compiler
+            .module_doc(model, table)
+            .contains("| no |  | normalized with `trim`, `crate::normalize::name` |\n"));
+    }
+
+    #[test]
+    fn normalized_entities() {
+        let source = "#[derive(Copy, Clone, Debug, EnumIter)]\npub enum Relation {}\n\nimpl ActiveModelBehavior for ActiveModel {}\n";
+
+        assert_eq!(
+            normalize_before_save(source),
+            "#[derive(Copy, Clone, Debug, EnumIter)]\npub enum Relation {}\n\nimpl ActiveModelBehavior for ActiveModel {\n    fn before_save(self, _insert: bool) -> Result<Self, DbErr> {\n        Ok(self.normalize())\n    }\n}\n"
+        );
+    }
+
+    /// Number of models of the large schema generated by [`large_schema_generation`].
+    const LARGE_SCHEMA_MODELS: usize = 1000;
+
+    /// Wall time allowed to generate the files of the large schema, in unoptimized builds.
+    const LARGE_SCHEMA_WALL_MS: u64 = 10_000;
+
+    /// Peak memory allowed while generating the files of the large schema, in kilobytes.
+    const LARGE_SCHEMA_PEAK_RSS_KB: u64 = 64 * 1024;
+
+    /// Environment variable set in the process generating the large schema.
+    const LARGE_SCHEMA_ENV: &str = "AWTO_TEST_LARGE_SCHEMA";
+
+    #[tokio::test]
+    async fn large_schema_generation() {
+        if env::var_os(LARGE_SCHEMA_ENV).is_some() {
+            return generate_large_schema();
+        }
+        // Peak memory is only measured on Linux
+        if peak_rss_kb().is_none() {
+            return;
+        }
+
+        // The schema is generated by this test in a process of its own, so the other tests do not add to its peak memory
+        let output = std::process::Command::new(env::current_exe().unwrap())
+            .args([
+                "--exact",
+                "database::test::large_schema_generation",
+                "--nocapture",
+            ])
+            .env(LARGE_SCHEMA_ENV, "1")
+            .env(PROFILE_GENERATION_ENV, "1")
+            .env(NO_FORMAT_ENV, "1")
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            output.status.success(),
+            "{}{}",
+            stdout,
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let phases: Vec<(u64, u64)> = stdout
+            .lines()
+            .filter_map(|line| line.split_once(PROFILE_MARKER))
+            .map(|(_, phase)| {
+                let value = |key: &str, unit: &str| -> u64 {
+                    let start = phase.find(key).unwrap() + key.len();
+                    phase[start..]
+                        .split_whitespace()
+                        .next()
+                        .unwrap()
+                        .trim_end_matches(unit)
+                        .parse()
+                        .unwrap()
+                };
+                (value("wall=", "ms"), value("peak_rss=", "kB"))
+            })
+            .collect();
+        assert_eq!(phases.len(), 4, "{}", stdout);
+        let wall_ms: u64 = phases.iter().map(|(wall_ms, _)| wall_ms).sum();
+        let peak_rss_kb = phases
+            .iter()
+            .map(|(_, peak_rss_kb)| *peak_rss_kb)
+            .max()
+            .unwrap();
+        assert!(
+            wall_ms < LARGE_SCHEMA_WALL_MS,
+            "generating {} models took {}ms",
+            LARGE_SCHEMA_MODELS,
+            wall_ms
+        );
+        assert!(
+            peak_rss_kb < LARGE_SCHEMA_PEAK_RSS_KB,
+            "generating {} models used {}kB",
+            LARGE_SCHEMA_MODELS,
+            peak_rss_kb
+        );
+    }
+
+    fn generate_large_schema() {
+        let dir = env::temp_dir().join(format!("awto-large-schema-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let compiler = compiler_of(synthetic_models(LARGE_SCHEMA_MODELS));
+        let mut manifest = GenerationManifest::new(false);
+
+        compiler
+            .write_files(
+                dir.to_str().unwrap(),
+                &mut manifest,
+                &GenerationProfile::from_env("database"),
+                &mut ModelCache::default(),
+            )
+            .unwrap();
+
+        assert_eq!(manifest.files.len(), 4);
+        assert!(std::fs::read_to_string(dir.join(COMPILED_RUST_FILE))
+            .unwrap()
+            .contains("pub mod employee_0"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Writes the files of `models` into `dir` like [`compile_database`], reusing the cached code of the previous build,
+    /// and returns the lines printed by verbose builds.
+    fn cached_build(dir: &Path, models: Vec<Model>) -> Vec<String> {
+        let out_dir = dir.to_str().unwrap();
+        let compiler = compiler_of(models);
+
+        let previous = GenerationManifest::load(out_dir).unwrap();
+        let mut cache = ModelCache::new(
+            &compiler.models,
+            &compiler.cache_options(),
+            previous.as_ref(),
+        );
+        for (name, path) in cache.cached_paths(out_dir) {
+            cache.reuse(&name, std::fs::read_to_string(path).ok());
+        }
+        let mut manifest = GenerationManifest::new(false);
+        compiler
+            .write_files(
+                out_dir,
+                &mut manifest,
+                &GenerationProfile::from_env("database"),
+                &mut cache,
+            )
+            .unwrap();
+        let report = cache.report();
+        cache.finish(out_dir, &mut manifest).unwrap();
+        manifest.write(out_dir).unwrap();
+
+        report
+    }
+
+    fn modified(path: std::path::PathBuf) -> std::time::SystemTime {
+        std::fs::metadata(path).unwrap().modified().unwrap()
+    }
+
+    #[tokio::test]
+    async fn cached_model_code() {
+        let dir = env::temp_dir().join(format!("awto-model-cache-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut models = MODELS.to_vec();
+        models.extend(tree::MODELS.to_vec());
+        let cached = dir.join(MODELS_DIR);
+
+        let report = cached_build(&dir, models.clone());
+        assert!(report
+            .contains(&"regenerated `Category` because it was not generated before".to_string()));
+        let manifest = GenerationManifest::load(&dir).unwrap().unwrap();
+        let tables = manifest.models.len();
+        assert!(tables > 0);
+        assert_eq!(
+            manifest.models["Comment"]
+                .dependencies
+                .keys()
+                .collect::<Vec<_>>(),
+            ["Category"]
+        );
+        let product = modified(cached.join("Product.rs"));
+        let app = modified(dir.join(COMPILED_RUST_FILE));
+
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        let report = cached_build(&dir, models.clone());
+        assert_eq!(
+            report,
+            [format!(
+                "reused the generated code of {} unchanged models",
+                tables
+            )]
+        );
+        assert_eq!(modified(cached.join("Product.rs")), product);
+        assert_eq!(modified(dir.join(COMPILED_RUST_FILE)), app);
+        // Reused code compiles into the same file as generating every model
+        let generated = compiler_of(models.clone())
+            .write_generated_code(Vec::new())
+            .unwrap();
+        assert_eq!(
+            std::fs::read(dir.join(COMPILED_RUST_FILE)).unwrap(),
+            generated
+        );
+
+        std::fs::write(cached.join("Employee.rs"), "pub struct Edited;").unwrap();
+        let report = cached_build(&dir, models);
+        assert_eq!(
+            report[0],
+            "regenerated `Employee` because its cached code is missing or was modified"
+        );
+        assert_eq!(
+            std::fs::read(dir.join(COMPILED_RUST_FILE)).unwrap(),
+            generated
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn invalidated_dependents() {
+        let dir = env::temp_dir().join(format!("awto-model-dependents-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut models = MODELS.to_vec();
+        models.extend(tree::MODELS.to_vec());
+        let cached = dir.join(MODELS_DIR);
+
+        cached_build(&dir, models.clone());
+        let tables = GenerationManifest::load(&dir)
+            .unwrap()
+            .unwrap()
+            .models
+            .len();
+        let product = modified(cached.join("Product.rs"));
+
+        let category = models
+            .iter_mut()
+            .find(|model| model.name == "Category")
+            .unwrap();
+        category.doc = Some("A category of comments.".to_string());
+        let report = cached_build(&dir, models.clone());
+        assert_eq!(
+            report,
+            [
+                "regenerated `Category` because it changed".to_string(),
+                "regenerated `Comment` because its dependency `Category` changed".to_string(),
+                format!(
+                    "reused the generated code of {} unchanged models",
+                    tables - 2
+                ),
+            ]
+        );
+        assert_eq!(modified(cached.join("Product.rs")), product);
+
+        models.retain(|model| model.name != "Comment");
+        let report = cached_build(&dir, models);
+        assert_eq!(
+            report[0],
+            "regenerated `Category` because its dependency `Comment` changed"
+        );
+        assert!(!cached.join("Comment.rs").exists());
+        assert!(!GenerationManifest::load(&dir)
+            .unwrap()
+            .unwrap()
+            .models
+            .contains_key("Comment"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn documented_code() {
+        let schemas = vec![
+            MODELS.to_vec(),
+            with_localization(MODELS.to_vec(), Localization::Table),
+            partitioned::MODELS.to_vec(),
+            with_temporal(temporal::MODELS.to_vec(), Temporal::Portable),
+            tree::MODELS.to_vec(),
+            reserved::MODELS.to_vec(),
+        ];
+
+        for models in schemas {
+            let compiler = compiler_of(models);
+            let mut code = compiler.compile_generated_code();
+            code.push_str(&compiler.compile_sea_orm_modules().unwrap());
+
+            assert_eq!(undocumented_items(&code), Vec::<String>::new());
+        }
+    }
+}
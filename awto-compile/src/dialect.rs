@@ -0,0 +1,366 @@
+//! Capabilities of the sql dialects the models could be generated for, and how each feature degrades on the
+//! dialects lacking it.
+//!
+//! The generators target Postgres, whose features the schema can use freely. A schema generated for another dialect,
+//! such as SQLite for tests, loses the features the dialect lacks; [`compatibility_report`] lists every degradation
+//! applied to the models, so they are known before they are relied on.
+
+use std::{fmt, str::FromStr};
+
+use awto::{
+    database::{DatabaseTable, DatabaseType, Temporal},
+    schema::{Model, Role},
+};
+use serde::{Deserialize, Serialize};
+
+/// Version of the JSON of compatibility reports, raised whenever a field is changed or removed.
+pub const COMPATIBILITY_VERSION: u32 = 1;
+
+/// A sql dialect of the databases supported by sea-orm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Dialect {
+    Postgres,
+    Sqlite,
+    Mysql,
+}
+
+impl Dialect {
+    pub const ALL: [Dialect; 3] = [Dialect::Postgres, Dialect::Sqlite, Dialect::Mysql];
+
+    /// Returns the name of the dialect as passed to `--dialect`, such as `sqlite`.
+    pub fn id(&self) -> &'static str {
+        match self {
+            Dialect::Postgres => "postgres",
+            Dialect::Sqlite => "sqlite",
+            Dialect::Mysql => "mysql",
+        }
+    }
+}
+
+impl fmt::Display for Dialect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Dialect::Postgres => write!(f, "Postgres"),
+            Dialect::Sqlite => write!(f, "SQLite"),
+            Dialect::Mysql => write!(f, "MySQL"),
+        }
+    }
+}
+
+impl FromStr for Dialect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Dialect::ALL
+            .iter()
+            .copied()
+            .find(|dialect| dialect.id() == s)
+            .ok_or_else(|| {
+                format!(
+                    "unknown dialect `{}`, expected `postgres`, `sqlite` or `mysql`",
+                    s
+                )
+            })
+    }
+}
+
+/// A feature of the models which not every [`Dialect`] supports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    /// Columns of [`DatabaseType::Uuid`].
+    Uuid,
+    /// Columns of [`DatabaseType::Jsonb`], used for maps.
+    Jsonb,
+    /// Columns of [`DatabaseType::Hstore`], used for maps with `map_storage = "hstore"`.
+    Hstore,
+    /// Columns of [`DatabaseType::Money`].
+    Money,
+    /// Columns of [`DatabaseType::Timestamptz`].
+    Timestamptz,
+    /// Columns of [`DatabaseType::Timetz`].
+    Timetz,
+    /// Tables with `#[awto(fulltext(...))]`.
+    Fulltext,
+    /// Tables with `#[awto(partition(...))]`.
+    Partitioning,
+    /// Tables with row-level security policies.
+    RowLevelSecurity,
+    /// Tables with `#[awto(notify)]`.
+    Notify,
+    /// Tables with `#[awto(temporal)]` kept by a trigger.
+    HistoryTrigger,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 11] = [
+        Feature::Uuid,
+        Feature::Jsonb,
+        Feature::Hstore,
+        Feature::Money,
+        Feature::Timestamptz,
+        Feature::Timetz,
+        Feature::Fulltext,
+        Feature::Partitioning,
+        Feature::RowLevelSecurity,
+        Feature::Notify,
+        Feature::HistoryTrigger,
+    ];
+
+    /// Returns the snake case name of the feature, such as `row_level_security`.
+    pub fn id(&self) -> &'static str {
+        match self {
+            Feature::Uuid => "uuid",
+            Feature::Jsonb => "jsonb",
+            Feature::Hstore => "hstore",
+            Feature::Money => "money",
+            Feature::Timestamptz => "timestamptz",
+            Feature::Timetz => "timetz",
+            Feature::Fulltext => "fulltext",
+            Feature::Partitioning => "partitioning",
+            Feature::RowLevelSecurity => "row_level_security",
+            Feature::Notify => "notify",
+            Feature::HistoryTrigger => "history_trigger",
+        }
+    }
+
+    /// Returns how the feature degrades on `dialect`, or `None` when the dialect supports it.
+    pub fn degradation(&self, dialect: Dialect) -> Option<Degradation> {
+        use Degradation::*;
+        use Dialect::*;
+
+        let alternative = |to: &str| Some(Alternative { to: to.to_string() });
+        match (self, dialect) {
+            (_, Postgres) => None,
+            (Feature::Uuid, Sqlite) => alternative("text"),
+            (Feature::Uuid, Mysql) => alternative("binary(16)"),
+            (Feature::Jsonb, Sqlite) => alternative("text holding json"),
+            (Feature::Jsonb, Mysql) => alternative("json"),
+            (Feature::Hstore, Sqlite) => alternative("text holding json"),
+            (Feature::Hstore, Mysql) => alternative("json"),
+            (Feature::Money, _) => alternative("numeric"),
+            (Feature::Timestamptz, Sqlite) => alternative("text in RFC 3339"),
+            (Feature::Timestamptz, Mysql) => alternative("timestamp in UTC"),
+            (Feature::Timetz, Sqlite) => alternative("text"),
+            (Feature::Timetz, Mysql) => alternative("time without its offset"),
+            (Feature::Fulltext, Sqlite) => Some(Omit),
+            (Feature::Fulltext, Mysql) => alternative("a FULLTEXT index"),
+            (Feature::Partitioning, Sqlite) => Some(Omit),
+            (Feature::Partitioning, Mysql) => alternative("PARTITION BY RANGE of MySQL"),
+            // Rows would be visible to every tenant, which must not go unnoticed
+            (Feature::RowLevelSecurity, _) => Some(Error),
+            (Feature::Notify, _) => Some(Omit),
+            (Feature::HistoryTrigger, _) => {
+                alternative("portable history, with temporal = \"portable\"")
+            }
+        }
+    }
+
+    /// Returns the dialects supporting the feature as Postgres does.
+    pub fn dialects(&self) -> Vec<Dialect> {
+        Dialect::ALL
+            .iter()
+            .copied()
+            .filter(|dialect| self.degradation(*dialect).is_none())
+            .collect()
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.id())
+    }
+}
+
+/// Strategy applied to a [`Feature`] on a dialect lacking it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "strategy", rename_all = "snake_case")]
+pub enum Degradation {
+    /// The feature is mapped to an alternative of the dialect, which may behave differently.
+    Alternative { to: String },
+    /// The feature is left out, with a warning.
+    Omit,
+    /// The models cannot be generated for the dialect.
+    Error,
+}
+
+/// Degradations applied to the models for a dialect, printed by `awto schema check --format json`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompatibilityReport {
+    /// [`COMPATIBILITY_VERSION`] of the report.
+    pub version: u32,
+    pub dialect: Dialect,
+    pub degradations: Vec<AppliedDegradation>,
+}
+
+impl CompatibilityReport {
+    /// Returns the degradations which prevent generating the models for the dialect.
+    pub fn errors(&self) -> impl Iterator<Item = &AppliedDegradation> {
+        self.degradations
+            .iter()
+            .filter(|degradation| degradation.degradation == Degradation::Error)
+    }
+}
+
+/// A [`Degradation`] of a feature used by a model, or by one of its fields.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppliedDegradation {
+    pub model: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    pub feature: Feature,
+    #[serde(flatten)]
+    pub degradation: Degradation,
+}
+
+impl fmt::Display for AppliedDegradation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.field {
+            Some(field) => write!(f, "{}.{}: {}", self.model, field, self.feature)?,
+            None => write!(f, "{}: {}", self.model, self.feature)?,
+        }
+        match &self.degradation {
+            Degradation::Alternative { to } => write!(f, " is mapped to {}", to),
+            Degradation::Omit => write!(f, " is left out"),
+            Degradation::Error => write!(f, " is not supported"),
+        }
+    }
+}
+
+/// Returns the degradations applied to the tables of `models` for `dialect`, by model and field.
+pub fn compatibility_report(models: &[Model], dialect: Dialect) -> CompatibilityReport {
+    let degradations = models
+        .iter()
+        .flat_map(|model| {
+            model.roles.iter().filter_map(move |role| match role {
+                Role::DatabaseTable(table) => Some((model, table)),
+                _ => None,
+            })
+        })
+        .flat_map(|(model, table)| {
+            table_features(table)
+                .into_iter()
+                .filter_map(move |(field, feature)| {
+                    Some(AppliedDegradation {
+                        model: model.name.clone(),
+                        field,
+                        feature,
+                        degradation: feature.degradation(dialect)?,
+                    })
+                })
+        })
+        .collect();
+
+    CompatibilityReport {
+        version: COMPATIBILITY_VERSION,
+        dialect,
+        degradations,
+    }
+}
+
+/// Returns the features used by `table`, along with the column using each, if any.
+fn table_features(table: &DatabaseTable) -> Vec<(Option<String>, Feature)> {
+    let mut features: Vec<_> = table
+        .columns
+        .iter()
+        .filter_map(|column| {
+            let feature = match column.ty {
+                DatabaseType::Uuid => Feature::Uuid,
+                DatabaseType::Jsonb => Feature::Jsonb,
+                DatabaseType::Hstore => Feature::Hstore,
+                DatabaseType::Money => Feature::Money,
+                DatabaseType::Timestamptz => Feature::Timestamptz,
+                DatabaseType::Timetz => Feature::Timetz,
+                _ => return None,
+            };
+            Some((Some(column.name.clone()), feature))
+        })
+        .collect();
+
+    let table_features = [
+        (table.fulltext.is_some(), Feature::Fulltext),
+        (table.partition.is_some(), Feature::Partitioning),
+        (!table.policies.is_empty(), Feature::RowLevelSecurity),
+        (table.notify, Feature::Notify),
+        (
+            table.temporal == Some(Temporal::Trigger),
+            Feature::HistoryTrigger,
+        ),
+    ];
+    features.extend(
+        table_features
+            .iter()
+            .filter(|(used, _)| *used)
+            .map(|(_, feature)| (None, *feature)),
+    );
+
+    features
+}
+
+#[cfg(test)]
+mod test {
+    use awto::tests_cfg::{partitioned, MODELS};
+
+    use super::*;
+
+    #[test]
+    fn capabilities() {
+        assert_eq!("sqlite".parse(), Ok(Dialect::Sqlite));
+        assert!("oracle".parse::<Dialect>().is_err());
+        for feature in Feature::ALL {
+            assert_eq!(feature.degradation(Dialect::Postgres), None);
+            assert!(feature.dialects().contains(&Dialect::Postgres));
+        }
+        assert_eq!(Feature::RowLevelSecurity.dialects(), [Dialect::Postgres]);
+        assert_eq!(
+            Feature::Fulltext.degradation(Dialect::Sqlite),
+            Some(Degradation::Omit)
+        );
+    }
+
+    #[test]
+    fn reports() {
+        assert!(compatibility_report(&*MODELS, Dialect::Postgres)
+            .degradations
+            .is_empty());
+
+        let report = compatibility_report(&*MODELS, Dialect::Sqlite);
+        let product_id = report
+            .degradations
+            .iter()
+            .find(|degradation| {
+                degradation.model == "Product" && degradation.field.as_deref() == Some("id")
+            })
+            .unwrap();
+        assert_eq!(product_id.to_string(), "Product.id: uuid is mapped to text");
+        assert!(report
+            .degradations
+            .iter()
+            .any(|degradation| degradation.model == "Product"
+                && degradation.feature == Feature::Notify));
+        let errors: Vec<_> = report
+            .errors()
+            .map(|degradation| degradation.to_string())
+            .collect();
+        assert_eq!(errors, ["Event: row_level_security is not supported"]);
+
+        let report = compatibility_report(&*partitioned::MODELS, Dialect::Sqlite);
+        let partitioning = report
+            .degradations
+            .iter()
+            .find(|degradation| degradation.feature == Feature::Partitioning)
+            .unwrap();
+        assert_eq!(
+            partitioning.to_string(),
+            "PageView: partitioning is left out"
+        );
+        assert_eq!(report.errors().count(), 0);
+
+        let json = serde_json::to_value(partitioning).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({ "model": "PageView", "feature": "partitioning", "strategy": "omit" })
+        );
+    }
+}
@@ -4,4 +4,118 @@ pub enum Error {
     UnsupportedType(String, String),
     #[error("database error: {0}")]
     Sqlx(sqlx::Error),
+    #[error("proto.lock has no messages of v{0}, which are recorded while it is the latest version in proto.versions")]
+    MissingProtoVersion(u32),
+    #[error("{model} has proto version {version}, which is newer than the latest of proto.versions {versions}")]
+    UnknownProtoVersion {
+        model: String,
+        version: u32,
+        versions: String,
+    },
+    #[error(
+        "{model} changed since v{version} was locked, mark it `#[awto(proto(version = {latest}))]`"
+    )]
+    ChangedLockedMessage {
+        model: String,
+        version: u32,
+        latest: u32,
+    },
+    #[error("fields removed since v{version} need a `#[awto(proto(v{version}_default(field = \"...\")))]` on their model: {fields}")]
+    MissingProtoDefaults { version: u32, fields: String },
+    #[error("{names} are both generated as `{ident}`, rename one of them")]
+    IdentifierCollision { names: String, ident: String },
+    #[error("model `{name}` is declared in both `{first}` and `{second}`, rename one of them or set `duplicates = \"namespace\"` in the [schema] section of awto.toml")]
+    DuplicateModel {
+        name: String,
+        first: String,
+        second: String,
+    },
+    #[error(
+        "`{column}` of `{model}` references the `{table}` table, which no schema package declares"
+    )]
+    UnresolvedReference {
+        column: String,
+        model: String,
+        table: String,
+    },
+    #[error("`{column}` of `{model}` references the `{table}` table, which several schema packages declare, reference it from one of them")]
+    AmbiguousReference {
+        column: String,
+        model: String,
+        table: String,
+    },
+    #[error("`{model}` of database '{database}' references `{referenced}` of database '{referenced_database}', which cannot have a foreign key, mark the reference `cross_database`")]
+    CrossDatabaseReference {
+        model: String,
+        database: String,
+        referenced: String,
+        referenced_database: String,
+    },
+    #[error("`{column}` of `{model}` is marked `cross_database`, but `{referenced}` belongs to the same database '{database}', remove `cross_database`")]
+    SameDatabaseReference {
+        column: String,
+        model: String,
+        referenced: String,
+        database: String,
+    },
+    #[error("`{column}` of `{model}` is of type {ty}, which cannot hold the {referenced_ty} values of `{referenced}` it references, give them the same type")]
+    ReferenceTypeMismatch {
+        column: String,
+        model: String,
+        ty: String,
+        referenced: String,
+        referenced_ty: String,
+    },
+    #[error("projection `{projection}` selects `{column}`, whose type projections do not support, leave it out")]
+    UnsupportedProjectionColumn { projection: String, column: String },
+    #[error("scope `{scope}` of table `{table}` is invalid: {reason}")]
+    InvalidScope {
+        scope: String,
+        table: String,
+        reason: String,
+    },
+    #[error("{name} is {len} bytes long, over the {limit} bytes Postgres keeps of identifiers, shorten it")]
+    IdentifierTooLong {
+        name: String,
+        len: usize,
+        limit: usize,
+    },
+    #[error("{item} uses {referenced}, which is only generated with feature '{feature}', gate both behind the same feature")]
+    GatedReference {
+        item: String,
+        referenced: String,
+        feature: String,
+    },
+    #[error("api docs describe {kind} `{item}`, which does not exist, update the entry if it was renamed")]
+    UnknownApiDocItem { item: String, kind: String },
+    #[error("example {example} of `{item}` does not fit its type {ty}: {reason}")]
+    InvalidApiExample {
+        item: String,
+        example: String,
+        ty: String,
+        reason: String,
+    },
+    #[error("io error: {0}")]
+    Io(std::io::Error),
+    #[error("invalid generator JSON: {0}")]
+    Json(serde_json::Error),
+    #[error("models have IR version {version}, but the generator reads version {expected}")]
+    IrVersion { version: u32, expected: u32 },
+    #[error("could not authenticate to the database: {0}, check the user and password of its url")]
+    DatabaseAuthentication(String),
+    #[error(
+        "could not connect to the database after {attempts} attempts in {elapsed:.1?}: {source}"
+    )]
+    DatabaseUnreachable {
+        attempts: u32,
+        elapsed: std::time::Duration,
+        source: sqlx::Error,
+    },
+    #[error("another sync of the tables of database `{database}` is in progress, gave up waiting for it after {timeout:.1?}")]
+    SyncInProgress {
+        database: String,
+        timeout: std::time::Duration,
+    },
+    #[error("rows of the database do not fit the columns narrowed by the schema, give the fields a `#[awto(column(backfill = \"...\"))]` or pass `--force-narrowing`:{violations}")]
+    NarrowingViolations { violations: String },
 }
@@ -0,0 +1,556 @@
+//! Explanations of how the type of each field of a model resolves to the type of its column.
+//!
+//! The column types are read from the models of the schema, as the `schema!` macro resolved them,
+//! so an explanation shows the decisions of the generators rather than repeating them.
+//! Each field is explained as a chain of [`Step`]s, from its declared Rust type to the default of its column,
+//! along with the attribute or rule behind each step.
+
+use std::collections::BTreeMap;
+
+use awto::{
+    database::{DatabaseColumn, DatabaseDefault, DatabaseTable, DatabaseType},
+    schema::{Model, Role, RustField},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::util::{column_rust_type, is_ty_option, map_entity_ty, map_value_ty, strip_ty_option};
+
+/// Version of the JSON of explanations, raised whenever a field or step is changed or removed.
+pub const EXPLANATION_VERSION: u32 = 1;
+
+/// Field of a model as declared in the schema lib.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DeclaredField {
+    /// Type as written in the source, such as `Option<Cents>`.
+    pub ty: String,
+    /// Attributes of the field, with `#[awto(...)]` split into its arguments such as `max_len = 120`.
+    pub attributes: Vec<String>,
+}
+
+/// Explanations of the fields of models, printed by `awto schema explain --format json`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Explanation {
+    /// [`EXPLANATION_VERSION`] of the explanation.
+    pub version: u32,
+    pub fields: Vec<FieldExplanation>,
+}
+
+/// Resolution of the column of a field, in the order of its [`Stage`]s.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldExplanation {
+    pub model: String,
+    pub field: String,
+    pub steps: Vec<Step>,
+}
+
+impl FieldExplanation {
+    /// Returns the value the field resolved to at `stage`.
+    pub fn value(&self, stage: Stage) -> Option<&str> {
+        self.steps
+            .iter()
+            .find(|step| step.stage == stage)
+            .map(|step| step.value.as_str())
+    }
+}
+
+/// A decision made resolving the column of a field.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Step {
+    pub stage: Stage,
+    /// Value the field resolved to, such as `Text(Some(120))` for [`Stage::IrType`].
+    pub value: String,
+    /// Attribute or rule which decided the value, such as `#[awto(max_len = 120)]`.
+    pub rule: String,
+    /// Fallback or coercion applied, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+/// Stage of the resolution of the column of a field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    /// Type of the field as written in the schema lib.
+    DeclaredType,
+    /// Type the field is stored as, with newtypes replaced by their inner types.
+    RustType,
+    /// [`DatabaseType`] of the column in the models.
+    IrType,
+    /// Type of the column in Postgres.
+    ColumnType,
+    /// Type of the field of the generated entity.
+    EntityType,
+    Nullability,
+    Default,
+}
+
+impl Stage {
+    /// Returns the name of the stage as printed, such as `ir type`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::DeclaredType => "declared type",
+            Stage::RustType => "rust type",
+            Stage::IrType => "ir type",
+            Stage::ColumnType => "column type",
+            Stage::EntityType => "entity type",
+            Stage::Nullability => "nullability",
+            Stage::Default => "default",
+        }
+    }
+}
+
+/// Explains the columns of each field of `model`, which are empty when it is not a database table or sub table.
+///
+/// `declared` holds the fields as declared in the schema lib by name, which tell the attributes behind the decisions.
+/// Fields missing from it are explained from the models alone.
+pub fn explain_model(
+    model: &Model,
+    declared: &BTreeMap<String, DeclaredField>,
+) -> Vec<FieldExplanation> {
+    let table = match model.roles.iter().find_map(|role| match role {
+        Role::DatabaseTable(table) | Role::DatabaseSubTable(table) => Some(table),
+        Role::ProtobufMessage(_) => None,
+    }) {
+        Some(table) => table,
+        None => return Vec::new(),
+    };
+
+    model
+        .fields
+        .iter()
+        .filter_map(|field| {
+            let column = table
+                .columns
+                .iter()
+                .find(|column| column.name == field.name)?;
+            Some(FieldExplanation {
+                model: model.name.clone(),
+                field: field.name.clone(),
+                steps: explain_column(table, field, column, declared.get(&field.name)),
+            })
+        })
+        .collect()
+}
+
+fn explain_column(
+    table: &DatabaseTable,
+    field: &RustField,
+    column: &DatabaseColumn,
+    declared: Option<&DeclaredField>,
+) -> Vec<Step> {
+    let attributes = declared
+        .map(|declared| declared.attributes.as_slice())
+        .unwrap_or_default();
+    let attribute = |name: &str| {
+        attributes
+            .iter()
+            .find(|attribute| *attribute == name || attribute.starts_with(&format!("{} =", name)))
+            .map(|attribute| format!("#[awto({})]", attribute))
+    };
+    let step = |stage: Stage, value: String, rule: String, note: Option<String>| Step {
+        stage,
+        value,
+        rule,
+        note,
+    };
+
+    let declared_ty = declared.map_or_else(|| field.ty.clone(), |declared| declared.ty.clone());
+    let mut steps = vec![step(
+        Stage::DeclaredType,
+        declared_ty,
+        "schema lib".to_string(),
+        None,
+    )];
+
+    steps.push(match &field.newtype {
+        Some(newtype) => step(
+            Stage::RustType,
+            field.ty.clone(),
+            format!("#[awto(newtype)] `{}`", newtype),
+            Some(format!("`{}` is stored as its inner type", newtype)),
+        ),
+        None => step(
+            Stage::RustType,
+            field.ty.clone(),
+            "as declared".to_string(),
+            None,
+        ),
+    });
+
+    let inner_ty = strip_ty_option(&field.ty);
+    let option_note = is_ty_option(&field.ty)
+        .then(|| "`Option` is unwrapped, as it only decides nullability".to_string());
+    let (rule, note) = if let Some(db_type) = attribute("db_type") {
+        (db_type, Some("overrides the type registry".to_string()))
+    } else if let Some(value_ty) = map_value_ty(&field.ty) {
+        let note = match (&column.ty, value_ty) {
+            (DatabaseType::Hstore, _) => {
+                Some("string maps are stored as hstore with `map_storage = \"hstore\"`".to_string())
+            }
+            (_, "String") => Some(
+                "string maps are stored as hstore instead with `map_storage = \"hstore\"`"
+                    .to_string(),
+            ),
+            _ => None,
+        };
+        ("map field".to_string(), note.or(option_note))
+    } else {
+        (format!("type registry: `{}`", inner_ty), option_note)
+    };
+    steps.push(step(Stage::IrType, format!("{:?}", column.ty), rule, note));
+    match (&column.ty, attribute("max_len")) {
+        (DatabaseType::Text(Some(_)), Some(max_len)) => {
+            let ir_type = steps.last_mut().unwrap();
+            ir_type.rule = format!("{}, {}", ir_type.rule, max_len);
+        }
+        (DatabaseType::Text(None), _) => {
+            let ir_type = steps.last_mut().unwrap();
+            let note = "unbounded, as the field has no #[awto(max_len = ...)]".to_string();
+            ir_type.note = Some(match ir_type.note.take() {
+                Some(other) => format!("{}; {}", other, note),
+                None => note,
+            });
+        }
+        _ => {}
+    }
+
+    let column_note = column
+        .computed
+        .as_ref()
+        .map(|computed| format!("generated always as ({}) stored", computed))
+        .or_else(|| {
+            (!column.locales.is_empty())
+                .then(|| format!("localized in {}", column.locales.join(", ")))
+        });
+    steps.push(step(
+        Stage::ColumnType,
+        column.ty.to_string(),
+        "postgres".to_string(),
+        column_note,
+    ));
+
+    let entity_ty = match (&column.ty, map_entity_ty(&field.ty)) {
+        (DatabaseType::Hstore, _) => None,
+        (_, Some(map_ty)) => Some(map_ty.to_string()),
+        (ty, None) => column_rust_type(ty).map(|ty| ty.to_string()),
+    };
+    steps.push(match entity_ty {
+        Some(entity_ty) => {
+            let entity_ty = entity_ty
+                .replace(' ', "")
+                .trim_start_matches("::")
+                .replace("<::", "<");
+            let entity_ty = if column.nullable {
+                format!("Option<{}>", entity_ty)
+            } else {
+                entity_ty
+            };
+            step(Stage::EntityType, entity_ty, "sea-orm".to_string(), None)
+        }
+        None => step(
+            Stage::EntityType,
+            "none".to_string(),
+            "sea-orm".to_string(),
+            Some(format!(
+                "the generated entities cannot decode {} columns",
+                column.ty
+            )),
+        ),
+    });
+
+    let nullability = if column.nullable { "NULL" } else { "NOT NULL" }.to_string();
+    steps.push(if column.primary_key {
+        step(
+            Stage::Nullability,
+            nullability,
+            "primary key".to_string(),
+            None,
+        )
+    } else if column.nullable {
+        step(
+            Stage::Nullability,
+            nullability,
+            "`Option` field".to_string(),
+            None,
+        )
+    } else if let Some(optional_insert) =
+        attribute("optional_insert").filter(|_| is_ty_option(&field.ty))
+    {
+        step(
+            Stage::Nullability,
+            nullability,
+            optional_insert,
+            Some("inserts may omit the field, which its default then fills".to_string()),
+        )
+    } else {
+        step(
+            Stage::Nullability,
+            nullability,
+            "required field".to_string(),
+            None,
+        )
+    });
+
+    let default = column
+        .default
+        .as_ref()
+        .map_or_else(|| "none".to_string(), DatabaseDefault::to_string);
+    let rule = match column.name.as_str() {
+        "id" | "created_at" | "updated_at" if column.default.is_some() => {
+            format!("implicit default of `{}`", column.name)
+        }
+        "version" if table.versioned => "versioned table".to_string(),
+        _ => match attribute("default_raw").or_else(|| attribute("default")) {
+            Some(default) => default,
+            None if column.default.is_some() => "schema".to_string(),
+            None => "no default".to_string(),
+        },
+    };
+    steps.push(step(Stage::Default, default, rule, None));
+
+    steps
+}
+
+#[cfg(test)]
+mod test {
+    use awto::tests_cfg::MODELS;
+
+    use super::*;
+
+    fn explain(model: &str, declared: &[(&str, &str, &[&str])]) -> Vec<FieldExplanation> {
+        let model = MODELS.iter().find(|other| other.name == model).unwrap();
+        let declared = declared
+            .iter()
+            .map(|(name, ty, attributes)| {
+                let field = DeclaredField {
+                    ty: ty.to_string(),
+                    attributes: attributes
+                        .iter()
+                        .map(|attribute| attribute.to_string())
+                        .collect(),
+                };
+                (name.to_string(), field)
+            })
+            .collect();
+
+        explain_model(model, &declared)
+    }
+
+    /// The resolution of each field of the tables of the test models, which is the oracle of the type registry.
+    #[test]
+    fn type_registry() {
+        let fields: Vec<_> = MODELS
+            .iter()
+            .flat_map(|model| explain_model(model, &BTreeMap::new()))
+            .filter(|field| field.model == "Product" || field.model == "Event")
+            .map(|field| {
+                let [ir_type, column_type, entity_type, nullability] = [
+                    Stage::IrType,
+                    Stage::ColumnType,
+                    Stage::EntityType,
+                    Stage::Nullability,
+                ]
+                .map(|stage| field.value(stage).unwrap().to_string());
+                (
+                    format!("{}.{}", field.model, field.field),
+                    ir_type,
+                    column_type,
+                    entity_type,
+                    nullability,
+                )
+            })
+            .collect();
+        let expected = [
+            ("Product.id", "Uuid", "uuid", "uuid::Uuid", "NOT NULL"),
+            (
+                "Product.created_at",
+                "Timestamptz",
+                "timestamp with time zone",
+                "chrono::DateTime<chrono::FixedOffset>",
+                "NOT NULL",
+            ),
+            (
+                "Product.updated_at",
+                "Timestamptz",
+                "timestamp with time zone",
+                "chrono::DateTime<chrono::FixedOffset>",
+                "NOT NULL",
+            ),
+            (
+                "Product.name",
+                "Text(None)",
+                "character varying",
+                "String",
+                "NOT NULL",
+            ),
+            ("Product.price", "BigInt", "bigint", "i64", "NOT NULL"),
+            (
+                "Product.description",
+                "Text(Some(120))",
+                "character varying(120)",
+                "Option<String>",
+                "NULL",
+            ),
+            (
+                "Product.status",
+                "Text(Some(20))",
+                "character varying(20)",
+                "String",
+                "NOT NULL",
+            ),
+            ("Product.version", "Integer", "integer", "i32", "NOT NULL"),
+            ("Product.price_cents", "BigInt", "bigint", "i64", "NOT NULL"),
+            ("Event.id", "Uuid", "uuid", "uuid::Uuid", "NOT NULL"),
+            (
+                "Event.created_at",
+                "Timestamptz",
+                "timestamp with time zone",
+                "chrono::DateTime<chrono::FixedOffset>",
+                "NOT NULL",
+            ),
+            (
+                "Event.updated_at",
+                "Timestamptz",
+                "timestamp with time zone",
+                "chrono::DateTime<chrono::FixedOffset>",
+                "NOT NULL",
+            ),
+            (
+                "Event.name",
+                "Text(None)",
+                "character varying",
+                "String",
+                "NOT NULL",
+            ),
+            (
+                "Event.properties",
+                "Jsonb",
+                "jsonb",
+                "crate::map::JsonMap<String>",
+                "NOT NULL",
+            ),
+        ];
+        let expected: Vec<_> = expected
+            .iter()
+            .map(|(field, ir_type, column_type, entity_type, nullability)| {
+                (
+                    field.to_string(),
+                    ir_type.to_string(),
+                    column_type.to_string(),
+                    entity_type.to_string(),
+                    nullability.to_string(),
+                )
+            })
+            .collect();
+        assert_eq!(fields, expected);
+    }
+
+    #[test]
+    fn decisions() {
+        let fields = explain(
+            "Product",
+            &[
+                ("price", "Cents", &["default = 0"]),
+                (
+                    "status",
+                    "Option<String>",
+                    &["default = \"draft\"", "max_len = 20", "optional_insert"],
+                ),
+            ],
+        );
+        let steps = |name: &str| {
+            &fields
+                .iter()
+                .find(|field| field.field == name)
+                .unwrap()
+                .steps
+        };
+        let rules = |name: &str| -> Vec<(Stage, &str)> {
+            steps(name)
+                .iter()
+                .map(|step| (step.stage, step.rule.as_str()))
+                .collect()
+        };
+
+        assert_eq!(
+            rules("price"),
+            [
+                (Stage::DeclaredType, "schema lib"),
+                (Stage::RustType, "#[awto(newtype)] `Cents`"),
+                (Stage::IrType, "type registry: `i64`"),
+                (Stage::ColumnType, "postgres"),
+                (Stage::EntityType, "sea-orm"),
+                (Stage::Nullability, "required field"),
+                (Stage::Default, "#[awto(default = 0)]"),
+            ]
+        );
+        assert_eq!(steps("price")[0].value, "Cents");
+
+        assert_eq!(
+            rules("status")[2..],
+            [
+                (
+                    Stage::IrType,
+                    "type registry: `String`, #[awto(max_len = 20)]"
+                ),
+                (Stage::ColumnType, "postgres"),
+                (Stage::EntityType, "sea-orm"),
+                (Stage::Nullability, "#[awto(optional_insert)]"),
+                (Stage::Default, "#[awto(default = \"draft\")]"),
+            ]
+        );
+        assert_eq!(
+            steps("status")[2].note.as_deref(),
+            Some("`Option` is unwrapped, as it only decides nullability")
+        );
+
+        assert_eq!(rules("id")[6], (Stage::Default, "implicit default of `id`"));
+        assert_eq!(steps("id")[6].value, "uuid_generate_v4()");
+        assert_eq!(rules("version")[6], (Stage::Default, "versioned table"));
+        assert_eq!(
+            steps("name")[2].note.as_deref(),
+            Some("unbounded, as the field has no #[awto(max_len = ...)]")
+        );
+        assert_eq!(
+            steps("price_cents")[3].note.as_deref(),
+            Some("generated always as (price * 100) stored")
+        );
+
+        let event = explain("Event", &[]);
+        let properties = event
+            .iter()
+            .find(|field| field.field == "properties")
+            .unwrap();
+        assert_eq!(properties.steps[2].rule, "map field");
+        assert_eq!(
+            properties.steps[2].note.as_deref(),
+            Some("string maps are stored as hstore instead with `map_storage = \"hstore\"`")
+        );
+        assert!(explain("ProductId", &[]).is_empty());
+    }
+
+    #[test]
+    fn stable_json() {
+        let explanation = Explanation {
+            version: EXPLANATION_VERSION,
+            fields: explain("Product", &[])
+                .into_iter()
+                .filter(|field| field.field == "description")
+                .collect(),
+        };
+        let json = serde_json::to_value(&explanation).unwrap();
+        assert_eq!(json["version"], 1);
+        assert_eq!(json["fields"][0]["model"], "Product");
+        assert_eq!(json["fields"][0]["steps"][2]["stage"], "ir_type");
+        assert_eq!(json["fields"][0]["steps"][2]["value"], "Text(Some(120))");
+        assert_eq!(
+            json["fields"][0]["steps"][3]["note"],
+            "localized in en, de, pt-BR"
+        );
+        assert!(json["fields"][0]["steps"][0].get("note").is_none());
+        assert_eq!(
+            serde_json::from_value::<Explanation>(json).unwrap(),
+            explanation
+        );
+    }
+}
@@ -0,0 +1,340 @@
+//! Feature flags of a schema, gating models and fields behind `#[awto(feature = "...")]`.
+//!
+//! Apps shipping several editions from one schema, such as a community and an enterprise edition, mark the models
+//! and fields of each edition with its feature. Every generated package only sees the models and fields of the
+//! features enabled for the profile, as if the others were never declared.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use awto::{
+    protobuf::ProtobufType,
+    schema::{Model, Role},
+};
+
+use crate::error::Error;
+
+/// Returns whether an item gated behind `feature` is generated with the `enabled` features.
+pub fn is_enabled(feature: Option<&str>, enabled: &[&str]) -> bool {
+    feature.is_none_or(|feature| enabled.contains(&feature))
+}
+
+/// Leaves the models and fields whose feature is not `enabled` out of `models`.
+///
+/// The columns and protobuf message fields of omitted fields are removed along with them, and every omitted field
+/// is recorded in [`Model::omitted_fields`], so conversions into the schema struct set it to its default.
+/// Kept models and fields must not depend on omitted ones, such as a column referencing the table of an omitted
+/// model, as the generated code would not compile.
+pub fn with_features(models: Vec<Model>, enabled: &[&str]) -> Result<Vec<Model>, Error> {
+    let omitted_models: BTreeMap<&str, &str> = models
+        .iter()
+        .filter_map(|model| {
+            let feature = model.feature.as_deref()?;
+            (!is_enabled(Some(feature), enabled)).then_some((model.name.as_str(), feature))
+        })
+        .collect();
+    let mut omitted_tables = BTreeMap::new();
+    let mut omitted_columns = BTreeMap::new();
+    for model in &models {
+        let table = match database_table(model) {
+            Some(table) => table,
+            None => continue,
+        };
+        if let Some(feature) = omitted_models.get(model.name.as_str()) {
+            omitted_tables.insert(table, (model.name.as_str(), *feature));
+            continue;
+        }
+        for field in &model.fields {
+            if let Some(feature) = field
+                .feature
+                .as_deref()
+                .filter(|feature| !is_enabled(Some(feature), enabled))
+            {
+                omitted_columns
+                    .insert((table, field.name.as_str()), (model.name.as_str(), feature));
+            }
+        }
+    }
+
+    for model in models
+        .iter()
+        .filter(|model| !omitted_models.contains_key(model.name.as_str()))
+    {
+        let kept_field = |name: &str| {
+            model
+                .fields
+                .iter()
+                .find(|field| field.name == name)
+                .is_none_or(|field| is_enabled(field.feature.as_deref(), enabled))
+        };
+        let gated = |item: String, referenced: String, feature: &str| Error::GatedReference {
+            item,
+            referenced,
+            feature: feature.to_string(),
+        };
+
+        for role in &model.roles {
+            match role {
+                Role::DatabaseTable(table) => {
+                    for column in table
+                        .columns
+                        .iter()
+                        .filter(|column| kept_field(&column.name))
+                    {
+                        let (referenced_table, referenced_column) = match &column.references {
+                            Some(references) => references,
+                            None => continue,
+                        };
+                        let item = format!("field `{}` of `{}`", column.name, model.name);
+                        if let Some((referenced, feature)) =
+                            omitted_tables.get(referenced_table.as_str())
+                        {
+                            return Err(gated(item, format!("model `{}`", referenced), feature));
+                        }
+                        let key = (referenced_table.as_str(), referenced_column.as_str());
+                        if let Some((referenced, feature)) = omitted_columns.get(&key) {
+                            let referenced =
+                                format!("field `{}` of `{}`", referenced_column, referenced);
+                            return Err(gated(item, referenced, feature));
+                        }
+                    }
+
+                    // Columns the table itself is organized by cannot be left out
+                    let uses = table
+                        .columns
+                        .iter()
+                        .filter(|column| column.primary_key)
+                        .map(|column| {
+                            (
+                                format!("primary key of `{}`", model.name),
+                                column.name.as_str(),
+                            )
+                        })
+                        .chain(table.order_by.iter().map(|column| {
+                            (format!("`order_by` of `{}`", model.name), column.as_str())
+                        }))
+                        .chain(table.upsert_on.iter().map(|column| {
+                            (format!("`upsert_on` of `{}`", model.name), column.as_str())
+                        }))
+                        .chain(table.partition.iter().map(|partition| {
+                            (
+                                format!("partition of `{}`", model.name),
+                                partition.column.as_str(),
+                            )
+                        }))
+                        .chain(table.fulltext.iter().flat_map(|fulltext| {
+                            fulltext.columns.iter().map(|column| {
+                                (
+                                    format!("fulltext index of `{}`", model.name),
+                                    column.as_str(),
+                                )
+                            })
+                        }))
+                        .chain(table.projections.iter().flat_map(|projection| {
+                            projection.columns.iter().map(move |column| {
+                                (format!("projection `{}`", projection.name), column.as_str())
+                            })
+                        }));
+                    for (item, column) in uses {
+                        if let Some((_, feature)) =
+                            omitted_columns.get(&(table.name.as_str(), column))
+                        {
+                            let referenced = format!("field `{}` of `{}`", column, model.name);
+                            return Err(gated(item, referenced, feature));
+                        }
+                    }
+                }
+                Role::DatabaseSubTable(table) => {
+                    if let Some((referenced, feature)) = omitted_tables.get(table.name.as_str()) {
+                        let item = format!("sub-table `{}`", model.name);
+                        return Err(gated(item, format!("model `{}`", referenced), feature));
+                    }
+                }
+                Role::ProtobufMessage(message) => {
+                    for field in message
+                        .fields
+                        .iter()
+                        .filter(|field| kept_field(&field.name))
+                    {
+                        let referenced = match message_name(&field.ty) {
+                            Some(referenced) => referenced,
+                            None => continue,
+                        };
+                        if let Some(feature) = omitted_models.get(referenced) {
+                            let item = format!("field `{}` of `{}`", field.name, model.name);
+                            return Err(gated(item, format!("model `{}`", referenced), feature));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let omitted_models: BTreeSet<String> =
+        omitted_models.keys().map(|name| name.to_string()).collect();
+    let omitted_columns: BTreeSet<(String, String)> = omitted_columns
+        .keys()
+        .map(|(table, column)| (table.to_string(), column.to_string()))
+        .collect();
+
+    Ok(models
+        .into_iter()
+        .filter(|model| !omitted_models.contains(&model.name))
+        .map(|mut model| {
+            let (fields, omitted): (Vec<_>, Vec<_>) = model
+                .fields
+                .into_iter()
+                .partition(|field| is_enabled(field.feature.as_deref(), enabled));
+            model.fields = fields;
+            model
+                .omitted_fields
+                .extend(omitted.into_iter().map(|field| field.name));
+            let omitted_fields = &model.omitted_fields;
+
+            for role in &mut model.roles {
+                match role {
+                    Role::DatabaseTable(table) | Role::DatabaseSubTable(table) => {
+                        let name = table.name.clone();
+                        table.columns.retain(|column| {
+                            !omitted_columns.contains(&(name.clone(), column.name.clone()))
+                        });
+                    }
+                    Role::ProtobufMessage(message) => {
+                        message
+                            .fields
+                            .retain(|field| !omitted_fields.contains(&field.name));
+                    }
+                }
+            }
+
+            model
+        })
+        .collect())
+}
+
+fn database_table(model: &Model) -> Option<&str> {
+    model.roles.iter().find_map(|role| match role {
+        Role::DatabaseTable(table) => Some(table.name.as_str()),
+        _ => None,
+    })
+}
+
+/// Returns the name of the message of a protobuf field type, looking through repeated fields and maps.
+fn message_name(ty: &ProtobufType) -> Option<&str> {
+    match ty {
+        ProtobufType::Custom(message) => Some(&message.name),
+        ProtobufType::Repeated(ty) | ProtobufType::Map(ty) => message_name(ty),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use awto::tests_cfg::{features, MODELS};
+
+    use super::*;
+
+    fn gate(models: &mut [Model], model: &str, field: Option<&str>, feature: &str) {
+        let model = models.iter_mut().find(|other| other.name == model).unwrap();
+        match field {
+            Some(field) => {
+                model
+                    .fields
+                    .iter_mut()
+                    .find(|other| other.name == field)
+                    .unwrap()
+                    .feature = Some(feature.to_string());
+            }
+            None => model.feature = Some(feature.to_string()),
+        }
+    }
+
+    fn columns(model: &Model) -> Vec<&str> {
+        let table = model
+            .roles
+            .iter()
+            .find_map(|role| match role {
+                Role::DatabaseTable(table) => Some(table),
+                _ => None,
+            })
+            .unwrap();
+        table
+            .columns
+            .iter()
+            .map(|column| column.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn omitted_fields() {
+        let mut models = MODELS.to_vec();
+        gate(&mut models, "Product", Some("status"), "enterprise");
+
+        let disabled = with_features(models.clone(), &[]).unwrap();
+        let product = disabled
+            .iter()
+            .find(|model| model.name == "Product")
+            .unwrap();
+        assert!(!columns(product).contains(&"status"));
+        assert!(product.fields.iter().all(|field| field.name != "status"));
+        assert_eq!(product.omitted_fields, ["status"]);
+
+        let enabled = with_features(models, &["enterprise"]).unwrap();
+        let product = enabled
+            .iter()
+            .find(|model| model.name == "Product")
+            .unwrap();
+        assert!(columns(product).contains(&"status"));
+        assert!(product.omitted_fields.is_empty());
+    }
+
+    #[test]
+    fn omitted_models() {
+        let mut models = MODELS.to_vec();
+        gate(&mut models, "Employee", None, "hr");
+
+        let disabled = with_features(models.clone(), &["enterprise"]).unwrap();
+        assert_eq!(disabled.len(), MODELS.len() - 1);
+        assert!(disabled.iter().all(|model| model.name != "Employee"));
+        assert_eq!(with_features(models, &["hr"]).unwrap().len(), MODELS.len());
+    }
+
+    #[test]
+    fn gated_models() {
+        let models = with_features(features::MODELS.to_vec(), &[]).unwrap();
+        assert_eq!(models.len(), 1);
+        assert!(!columns(&models[0]).contains(&"team_id"));
+        assert_eq!(models[0].omitted_fields, ["team_id"]);
+
+        assert_eq!(
+            with_features(features::MODELS.to_vec(), &["enterprise"]).unwrap(),
+            &features::MODELS[..]
+        );
+    }
+
+    #[test]
+    fn gated_references() {
+        // Product lists hold products, so products cannot be left out without them
+        let mut models = MODELS.to_vec();
+        gate(&mut models, "Product", None, "catalog");
+        let err = with_features(models.clone(), &[]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "field `products` of `ProductList` uses model `Product`, which is only generated with feature 'catalog', \
+            gate both behind the same feature"
+        );
+
+        gate(&mut models, "ProductList", None, "catalog");
+        gate(&mut models, "NewProduct", None, "catalog");
+        let kept = with_features(models, &[]).unwrap();
+        assert_eq!(kept.len(), MODELS.len() - 3);
+
+        let mut models = MODELS.to_vec();
+        gate(&mut models, "Product", Some("description"), "enterprise");
+        let err = with_features(models, &[]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "fulltext index of `Product` uses field `description` of `Product`, which is only generated with \
+            feature 'enterprise', gate both behind the same feature"
+        );
+    }
+}
@@ -0,0 +1,195 @@
+//! Formatting of generated files, keeping them deterministic and readable in diffs.
+
+use std::{
+    env,
+    io::{self, Write},
+    process::{Command, Stdio},
+};
+
+/// Environment variable disabling the formatting of generated Rust code when set, such as by `awto compile --no-format`.
+pub const NO_FORMAT_ENV: &str = "AWTO_NO_FORMAT";
+
+/// Edition of the generated packages, used to format their code.
+const EDITION: &str = "2018";
+
+/// Length of the batches of units formatted at once by [`RustWriter`], in bytes.
+const FORMAT_BATCH_LEN: usize = 1 << 20;
+
+/// Formats generated Rust code with `rustfmt`, or with `prettyplease` when `rustfmt` is unavailable.
+///
+/// `rustfmt` picks up the `rustfmt.toml` of the project being built. The code is returned unchanged
+/// when formatting is disabled with [`NO_FORMAT_ENV`] or when it cannot be parsed.
+pub fn format_rust(code: &str) -> String {
+    if env::var_os(NO_FORMAT_ENV).is_some() {
+        return code.to_string();
+    }
+
+    rustfmt(code)
+        .or_else(|| prettyplease(code))
+        .unwrap_or_else(|| code.to_string())
+}
+
+/// Writer of generated Rust code made of independent units, such as the items generated for each table.
+///
+/// Units are formatted with [`format_rust`] in batches of about [`FORMAT_BATCH_LEN`] bytes, written as soon as
+/// a batch is full, so the code of large schemas is never held in memory at once.
+pub struct RustWriter<W: Write> {
+    out: W,
+    batch: String,
+    batch_len: usize,
+}
+
+impl<W: Write> RustWriter<W> {
+    pub fn new(out: W) -> Self {
+        RustWriter {
+            out,
+            batch: String::new(),
+            batch_len: FORMAT_BATCH_LEN,
+        }
+    }
+
+    /// Writes `unit`, which must consist of whole items.
+    pub fn write_unit(&mut self, unit: &str) -> io::Result<()> {
+        self.batch.push_str(unit);
+        self.batch.push('\n');
+        if self.batch.len() >= self.batch_len {
+            self.write_batch()?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the last batch and returns the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_batch()?;
+        self.out.flush()?;
+
+        Ok(self.out)
+    }
+
+    fn write_batch(&mut self) -> io::Result<()> {
+        let batch = self.batch.trim();
+        if !batch.is_empty() {
+            let formatted = format_rust(batch);
+            self.out.write_all(formatted.as_bytes())?;
+            if !formatted.ends_with('\n') {
+                self.out.write_all(b"\n")?;
+            }
+        }
+        self.batch.clear();
+
+        Ok(())
+    }
+}
+
+fn rustfmt(code: &str) -> Option<String> {
+    let rustfmt = env::var("RUSTFMT").unwrap_or_else(|_| "rustfmt".to_string());
+    let mut child = Command::new(rustfmt)
+        .args(["--edition", EDITION, "--emit", "stdout"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+
+    child.stdin.take()?.write_all(code.as_bytes()).ok()?;
+    let output = child.wait_with_output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    String::from_utf8(output.stdout).ok()
+}
+
+fn prettyplease(code: &str) -> Option<String> {
+    syn::parse_file(code)
+        .ok()
+        .map(|file| prettyplease::unparse(&file))
+}
+
+/// Formats a generated protobuf file, removing trailing whitespace and repeated blank lines.
+pub fn format_proto(proto: &str) -> String {
+    normalize_lines(proto, true)
+}
+
+/// Formats generated sql, removing trailing whitespace and blank lines.
+pub fn format_sql(sql: &str) -> String {
+    normalize_lines(sql, false)
+}
+
+/// Trims trailing whitespace of every line and ends the text with a single newline.
+///
+/// Consecutive blank lines are collapsed into one when `keep_blank_lines` is true, and removed otherwise.
+fn normalize_lines(text: &str, keep_blank_lines: bool) -> String {
+    let mut normalized = String::with_capacity(text.len());
+    let mut previous_blank = true;
+
+    for line in text.lines().map(str::trim_end) {
+        let blank = line.is_empty();
+        if blank && (previous_blank || !keep_blank_lines) {
+            continue;
+        }
+
+        normalized.push_str(line);
+        normalized.push('\n');
+        previous_blank = blank;
+    }
+
+    while normalized.ends_with("\n\n") {
+        normalized.pop();
+    }
+
+    normalized
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn format_rust_code() {
+        let code = "pub mod product{pub fn id()->u32{1}}";
+
+        assert_eq!(
+            format_rust(code),
+            "pub mod product {\n    pub fn id() -> u32 {\n        1\n    }\n}\n"
+        );
+        assert_eq!(prettyplease(code), Some(format_rust(code)));
+        assert_eq!(format_rust("pub fn {"), "pub fn {");
+    }
+
+    #[test]
+    fn write_units() {
+        let mut writer = RustWriter::new(Vec::new());
+        writer.batch_len = 64;
+        writer
+            .write_unit("pub mod product{pub fn id()->u32{1}}")
+            .unwrap();
+        writer.write_unit("").unwrap();
+        for _ in 0..10 {
+            writer.write_unit("pub struct Category;").unwrap();
+        }
+        writer.write_unit("pub fn {").unwrap();
+        let code = String::from_utf8(writer.finish().unwrap()).unwrap();
+
+        assert!(code.starts_with("pub mod product {\n    pub fn id() -> u32 {\n        1\n    }\n}\n\npub struct Category;\n"));
+        assert_eq!(code.matches("pub struct Category;\n").count(), 10);
+        // Batches which cannot be parsed are written unformatted
+        assert!(code.ends_with("pub fn {\n"));
+        assert_eq!(RustWriter::new(Vec::new()).finish().unwrap(), b"");
+    }
+
+    #[test]
+    fn format_text() {
+        let text = "\nmessage Product {  \n  string id = 1;\n}\n\n\n\nmessage Empty {}\n\n";
+
+        assert_eq!(
+            format_proto(text),
+            "message Product {\n  string id = 1;\n}\n\nmessage Empty {}\n"
+        );
+        assert_eq!(
+            format_sql(text),
+            "message Product {\n  string id = 1;\n}\nmessage Empty {}\n"
+        );
+    }
+}
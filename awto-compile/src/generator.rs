@@ -0,0 +1,195 @@
+//! Generators turning the models of a schema into files.
+//!
+//! Built-in generators implement [`Generator`], while external generators are executables run by
+//! `awto compile custom`, which write the models they read as JSON from stdin into files they print as JSON to stdout.
+//! Both sides of the JSON protocol are tagged with [`IR_VERSION`], so generators fail loudly instead of
+//! misreading models once their shape changes.
+
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+
+use awto::schema::Model;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+
+/// Version of the JSON shape of the models handed to external generators.
+///
+/// It is bumped whenever a field of the models is added, removed or changes its meaning.
+pub const IR_VERSION: u32 = 1;
+
+/// A generator of files from the models of a schema.
+pub trait Generator {
+    /// Name of the generator recorded in the generation manifest, such as `database`.
+    fn name(&self) -> &str;
+
+    /// Generates the files of `models`, with paths relative to the directory they are written into.
+    fn generate(
+        &self,
+        models: &[Model],
+        options: &GeneratorOptions,
+    ) -> Result<Vec<GeneratedFile>, Error>;
+}
+
+/// Options a generator is run with.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GeneratorOptions {
+    /// Whether generated code is interleaved with comments telling which model produced it.
+    pub annotate_sources: bool,
+    /// Options specific to the generator, set with `--option key=value`.
+    pub options: BTreeMap<String, String>,
+}
+
+/// A file produced by a generator.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GeneratedFile {
+    /// Path of the file relative to the output directory, such as `src/lib.rs`.
+    pub path: String,
+    pub contents: String,
+    pub kind: FileKind,
+}
+
+impl GeneratedFile {
+    pub fn new(path: impl Into<String>, contents: impl Into<String>, kind: FileKind) -> Self {
+        GeneratedFile {
+            path: path.into(),
+            contents: contents.into(),
+            kind,
+        }
+    }
+}
+
+/// Kind of the contents of a [`GeneratedFile`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileKind {
+    Rust,
+    Sql,
+    Markdown,
+    Proto,
+    Other,
+}
+
+/// Input written to the stdin of external generators.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GeneratorInput {
+    /// [`IR_VERSION`] of the models.
+    pub version: u32,
+    #[serde(default)]
+    pub options: GeneratorOptions,
+    pub models: Vec<Model>,
+}
+
+/// Output external generators print to their stdout.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GeneratorOutput {
+    /// [`IR_VERSION`] of the models the generator read.
+    pub version: u32,
+    pub files: Vec<GeneratedFile>,
+}
+
+impl GeneratorInput {
+    /// Returns the input of the current [`IR_VERSION`] handing `models` to an external generator.
+    pub fn new(models: &[Model]) -> Self {
+        GeneratorInput {
+            version: IR_VERSION,
+            options: GeneratorOptions::default(),
+            models: models.to_vec(),
+        }
+    }
+
+    /// Returns the input as pretty printed JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("models serialize to JSON")
+    }
+
+    /// Parses the input of an external generator, failing when it was written for another [`IR_VERSION`].
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        let input: GeneratorInput = serde_json::from_str(json).map_err(Error::Json)?;
+        check_version(input.version)?;
+
+        Ok(input)
+    }
+}
+
+/// Runs `generator` as an external generator, reading its [`GeneratorInput`] from stdin and
+/// printing its [`GeneratorOutput`] to stdout.
+///
+/// This is the whole `main` of an external generator written in Rust.
+pub fn run_external(generator: &impl Generator) -> Result<(), Error> {
+    let mut json = String::new();
+    io::stdin().read_to_string(&mut json).map_err(Error::Io)?;
+    let input = GeneratorInput::from_json(&json)?;
+
+    let output = GeneratorOutput {
+        version: IR_VERSION,
+        files: generator.generate(&input.models, &input.options)?,
+    };
+    let mut stdout = io::stdout();
+    serde_json::to_writer(&mut stdout, &output).map_err(Error::Json)?;
+    stdout.flush().map_err(Error::Io)
+}
+
+fn check_version(version: u32) -> Result<(), Error> {
+    if version != IR_VERSION {
+        return Err(Error::IrVersion {
+            version,
+            expected: IR_VERSION,
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use awto::tests_cfg::MODELS;
+
+    use super::*;
+
+    #[test]
+    fn input_round_trip() {
+        let mut input = GeneratorInput::new(&MODELS[..]);
+        input
+            .options
+            .options
+            .insert("namespace".to_string(), "shop".to_string());
+
+        let parsed = GeneratorInput::from_json(&input.to_json()).unwrap();
+        assert_eq!(parsed, input);
+        assert_eq!(parsed.models, MODELS.to_vec());
+    }
+
+    #[test]
+    fn input_version() {
+        let json = GeneratorInput::new(&MODELS[..]).to_json().replacen(
+            &format!("\"version\": {}", IR_VERSION),
+            "\"version\": 999",
+            1,
+        );
+        let err = GeneratorInput::from_json(&json).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "models have IR version 999, but the generator reads version {}",
+                IR_VERSION
+            )
+        );
+    }
+
+    #[test]
+    fn output_json() {
+        let output = GeneratorOutput {
+            version: IR_VERSION,
+            files: vec![GeneratedFile::new("schema.avsc", "{}", FileKind::Other)],
+        };
+        assert_eq!(
+            serde_json::to_string(&output).unwrap(),
+            format!(
+                r#"{{"version":{},"files":[{{"path":"schema.avsc","contents":"{{}}","kind":"other"}}]}}"#,
+                IR_VERSION
+            )
+        );
+    }
+}
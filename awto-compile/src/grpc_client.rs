@@ -0,0 +1,467 @@
+use std::{env, fmt::Write};
+
+use awto::protobuf::{ProtobufMethod, ProtobufService};
+use heck::SnakeCase;
+use quote::{format_ident, quote};
+
+use crate::{
+    format::format_rust,
+    provenance::{
+        annotate_sources, watch_profile, write_generated, Annotation, CommentStyle,
+        GenerationManifest,
+    },
+    util::rust_ident,
+};
+
+const COMPILED_RUST_FILE: &str = "app.rs";
+
+/// Prefixes of method names which only read data, so calling them again has no further effect.
+const IDEMPOTENT_PREFIXES: [&str; 9] = [
+    "Get", "Find", "List", "Search", "Count", "Check", "Read", "Fetch", "Lookup",
+];
+
+/// Connection configuration and errors shared by every generated client.
+const CLIENT_MODULE: &str = r#"
+use std::{fmt, time::Duration};
+
+pub use ::tonic::transport::{Certificate, Channel, ClientTlsConfig, Identity};
+
+/// Timeout of a single call when none is configured.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Timeout of connecting to the server when none is configured.
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Error returned by the generated clients.
+#[derive(Debug)]
+pub enum ClientError {
+    /// The endpoint is not a valid uri.
+    InvalidEndpoint(String),
+    /// The connection to the server failed.
+    Transport(::tonic::transport::Error),
+    /// The server responded with an error.
+    Status(::tonic::Status),
+    /// The response could not be converted into its schema struct.
+    InvalidResponse(String),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::InvalidEndpoint(err) => write!(f, "invalid endpoint: {}", err),
+            ClientError::Transport(err) => write!(f, "transport error: {}", err),
+            ClientError::Status(status) => write!(f, "{}: {}", status.code(), status.message()),
+            ClientError::InvalidResponse(err) => write!(f, "invalid response: {}", err),
+        }
+    }
+}
+
+impl ::std::error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn ::std::error::Error + 'static)> {
+        match self {
+            ClientError::Transport(err) => Some(err),
+            ClientError::Status(status) => Some(status),
+            _ => None,
+        }
+    }
+}
+
+impl From<::tonic::transport::Error> for ClientError {
+    fn from(err: ::tonic::transport::Error) -> Self {
+        ClientError::Transport(err)
+    }
+}
+
+impl From<::tonic::Status> for ClientError {
+    fn from(status: ::tonic::Status) -> Self {
+        ClientError::Status(status)
+    }
+}
+
+/// Configuration of the connection to a gRPC server.
+#[derive(Clone, Debug)]
+pub struct ClientConfig {
+    endpoint: String,
+    timeout: Duration,
+    connect_timeout: Duration,
+    tls: Option<ClientTlsConfig>,
+    #[cfg(feature = "retry")]
+    retry: retry::RetryPolicy,
+}
+
+impl ClientConfig {
+    /// Creates a configuration connecting to `endpoint`, such as `http://[::1]:50051`.
+    pub fn new(endpoint: impl Into<String>) -> Self {
+        ClientConfig {
+            endpoint: endpoint.into(),
+            timeout: DEFAULT_TIMEOUT,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            tls: None,
+            #[cfg(feature = "retry")]
+            retry: retry::RetryPolicy::default(),
+        }
+    }
+
+    /// Sets the timeout of every call.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the timeout of connecting to the server.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Connects to the server over TLS.
+    pub fn tls(mut self, tls: ClientTlsConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Sets the retries of idempotent calls.
+    #[cfg(feature = "retry")]
+    pub fn retry(mut self, retry: retry::RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Connects to the server, returning a channel which can be shared by clients.
+    pub async fn connect(&self) -> Result<Channel, ClientError> {
+        let mut endpoint = Channel::from_shared(self.endpoint.clone())
+            .map_err(|err| ClientError::InvalidEndpoint(err.to_string()))?
+            .timeout(self.timeout)
+            .connect_timeout(self.connect_timeout);
+        if let Some(tls) = &self.tls {
+            endpoint = endpoint.tls_config(tls.clone())?;
+        }
+
+        Ok(endpoint.connect().await?)
+    }
+}
+
+/// Connects to the server at `endpoint` with the default configuration.
+pub async fn connect(endpoint: impl Into<String>) -> Result<Channel, ClientError> {
+    ClientConfig::new(endpoint).connect().await
+}
+
+/// Retry policies of the clients, behind the `retry` feature.
+#[cfg(feature = "retry")]
+pub mod retry {
+    use std::{future::Future, time::Duration};
+
+    /// Retries of idempotent calls failing with a transient status, waiting with exponential backoff.
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub struct RetryPolicy {
+        /// Number of calls made after the first one failed.
+        pub max_retries: u32,
+        /// Time waited before the first retry.
+        pub initial_backoff: Duration,
+        /// Longest time waited between retries.
+        pub max_backoff: Duration,
+        /// Factor the time waited grows by after every retry.
+        pub multiplier: f64,
+    }
+
+    impl Default for RetryPolicy {
+        fn default() -> Self {
+            RetryPolicy {
+                max_retries: 3,
+                initial_backoff: Duration::from_millis(100),
+                max_backoff: Duration::from_secs(5),
+                multiplier: 2.0,
+            }
+        }
+    }
+
+    impl RetryPolicy {
+        /// Returns a policy which never retries.
+        pub fn none() -> Self {
+            RetryPolicy {
+                max_retries: 0,
+                ..Default::default()
+            }
+        }
+
+        /// Returns the time waited before retry number `retry`, starting at `0`.
+        pub fn backoff(&self, retry: u32) -> Duration {
+            let backoff = self.initial_backoff.as_secs_f64() * self.multiplier.powi(retry as i32);
+            Duration::from_secs_f64(backoff.min(self.max_backoff.as_secs_f64()))
+        }
+
+        /// Returns whether a call failing with `status` may succeed when it is retried.
+        pub fn is_retryable(status: &::tonic::Status) -> bool {
+            matches!(
+                status.code(),
+                ::tonic::Code::Unavailable
+                    | ::tonic::Code::DeadlineExceeded
+                    | ::tonic::Code::ResourceExhausted
+            )
+        }
+
+        /// Runs `call` until it succeeds, fails with a status which is not retryable, or runs out of retries.
+        pub async fn run<T, F, Fut>(&self, mut call: F) -> Result<T, ::tonic::Status>
+        where
+            F: FnMut() -> Fut,
+            Fut: Future<Output = Result<T, ::tonic::Status>>,
+        {
+            let mut retry = 0;
+            loop {
+                match call().await {
+                    Err(status) if retry < self.max_retries && Self::is_retryable(&status) => {
+                        ::tokio::time::sleep(self.backoff(retry)).await;
+                        retry += 1;
+                    }
+                    result => return result,
+                }
+            }
+        }
+    }
+}
+"#;
+
+/// Compiles gRPC clients of `services` into `OUT_DIR`, wrapping the tonic clients of the protobuf package.
+pub fn compile_grpc_client(
+    services: Vec<ProtobufService>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let out_dir = env::var("OUT_DIR").unwrap();
+
+    let annotate = annotate_sources();
+    watch_profile();
+    let compiler = GrpcClientCompiler::new(services).with_annotations(annotate);
+    let mut manifest = GenerationManifest::new(annotate);
+    let names: Vec<&str> = compiler
+        .services
+        .iter()
+        .map(|service| service.name.as_str())
+        .collect();
+
+    let code = format_rust(&compiler.compile_generated_code());
+    write_generated(
+        &out_dir,
+        COMPILED_RUST_FILE,
+        &code,
+        &mut manifest,
+        "grpc-client",
+        &names,
+    )?;
+    manifest.write(&out_dir)?;
+
+    Ok(())
+}
+
+/// Compiles gRPC clients taking and returning schema structs, for calling the protobuf services from other Rust services.
+///
+/// Every service gets a client named after it, such as `ProductServiceClient`, with a method per rpc.
+/// Requests are converted into protobuf messages and responses back into schema structs with the
+/// conversions of the protobuf package, so clients and server share the same protobuf definitions.
+///
+/// Methods starting with a read-only verb such as `Get`, `Find` or `List` are idempotent, and are retried
+/// with exponential backoff when the `retry` feature is enabled.
+///
+/// # Examples
+///
+/// ```
+/// # use awto_compile::grpc_client::GrpcClientCompiler;
+/// # use awto::tests_cfg::*;
+/// # use awto::protobuf::IntoProtobufService;
+/// let compiler = GrpcClientCompiler::new(vec![ProductService::protobuf_service()]);
+/// let code = compiler.compile_generated_code();
+///
+/// assert!(code.contains("pub struct ProductServiceClient"));
+/// ```
+pub struct GrpcClientCompiler {
+    services: Vec<ProtobufService>,
+    annotate_sources: bool,
+}
+
+impl GrpcClientCompiler {
+    /// Creates a new instance of [`GrpcClientCompiler`].
+    pub fn new(services: Vec<ProtobufService>) -> GrpcClientCompiler {
+        GrpcClientCompiler {
+            services,
+            annotate_sources: false,
+        }
+    }
+
+    /// Interleaves the generated code with comments telling which service produced each client.
+    pub fn with_annotations(mut self, annotate_sources: bool) -> Self {
+        self.annotate_sources = annotate_sources;
+        self
+    }
+
+    /// Compiles generated Rust code for the clients of every service.
+    pub fn compile_generated_code(&self) -> String {
+        let mut code = CLIENT_MODULE.to_string();
+
+        for service in &self.services {
+            let ident = format_ident!("{}Client", service.name);
+            let tonic_module_ident = format_ident!("{}_client", service.name.to_snake_case());
+            let doc = format!(
+                " Client of the `{}` gRPC service, taking and returning schema structs.",
+                service.name
+            );
+
+            let methods = service.methods.iter().map(|method| {
+                let name_ident = rust_ident(&method.name.to_snake_case());
+                let param_ident = rust_ident(&method.param.name);
+                let returns_ident = rust_ident(&method.returns.name);
+
+                let (doc, call) = if Self::is_idempotent(method) {
+                    (
+                        format!(" Calls `{}`, retrying transient failures.", method.name),
+                        quote!(
+                            #[cfg(feature = "retry")]
+                            let response = self
+                                .retry
+                                .run(|| {
+                                    let mut inner = self.inner.clone();
+                                    let request = request.clone();
+                                    async move { inner.#name_ident(request).await }
+                                })
+                                .await?;
+                            #[cfg(not(feature = "retry"))]
+                            let response = self.inner.clone().#name_ident(request).await?;
+                        ),
+                    )
+                } else {
+                    (
+                        format!(" Calls `{}`.", method.name),
+                        quote!(
+                            let response = self.inner.clone().#name_ident(request).await?;
+                        ),
+                    )
+                };
+
+                quote!(
+                    #[doc = #doc]
+                    pub async fn #name_ident(
+                        &self,
+                        request: ::schema::#param_ident,
+                    ) -> Result<::schema::#returns_ident, ClientError> {
+                        let request = ::protobuf::#param_ident::from(request);
+                        #call
+
+                        ::std::convert::TryFrom::try_from(response.into_inner()).map_err(
+                            |err: ::protobuf::TryFromProtoError| ClientError::InvalidResponse(err.to_string()),
+                        )
+                    }
+                )
+            });
+
+            let expanded = quote!(
+                #[doc = #doc]
+                #[derive(Clone, Debug)]
+                pub struct #ident {
+                    inner: ::protobuf::#tonic_module_ident::#ident<Channel>,
+                    #[cfg(feature = "retry")]
+                    retry: retry::RetryPolicy,
+                }
+
+                impl #ident {
+                    /// Creates a client calling the server over `channel`.
+                    pub fn new(channel: Channel) -> Self {
+                        #ident {
+                            inner: ::protobuf::#tonic_module_ident::#ident::new(channel),
+                            #[cfg(feature = "retry")]
+                            retry: retry::RetryPolicy::default(),
+                        }
+                    }
+
+                    /// Connects to the server at `endpoint` with the default configuration.
+                    pub async fn connect(endpoint: impl Into<String>) -> Result<Self, ClientError> {
+                        Self::connect_with(&ClientConfig::new(endpoint)).await
+                    }
+
+                    /// Connects to the server with `config`.
+                    pub async fn connect_with(config: &ClientConfig) -> Result<Self, ClientError> {
+                        #[allow(unused_mut)]
+                        let mut client = Self::new(config.connect().await?);
+                        #[cfg(feature = "retry")]
+                        {
+                            client.retry = config.retry;
+                        }
+
+                        Ok(client)
+                    }
+
+                    /// Sets the retries of idempotent calls.
+                    #[cfg(feature = "retry")]
+                    pub fn with_retry(mut self, retry: retry::RetryPolicy) -> Self {
+                        self.retry = retry;
+                        self
+                    }
+
+                    #( #methods )*
+                }
+            );
+
+            let annotation = Annotation::new("grpc-client", &service.name);
+            code.push_str(&annotation.comment(CommentStyle::Slashes, self.annotate_sources));
+            write!(code, "{}", expanded).unwrap();
+        }
+
+        code
+    }
+
+    /// Returns whether `method` starts with one of the [`IDEMPOTENT_PREFIXES`], followed by the next word.
+    fn is_idempotent(method: &ProtobufMethod) -> bool {
+        IDEMPOTENT_PREFIXES.iter().any(|prefix| {
+            method
+                .name
+                .strip_prefix(prefix)
+                .map(|rest| rest.chars().next().map(char::is_uppercase).unwrap_or(true))
+                .unwrap_or(false)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use awto::{protobuf::IntoProtobufService, tests_cfg::*};
+
+    use super::*;
+    use crate::util::undocumented_items;
+
+    #[test]
+    fn clients() {
+        let compiler = GrpcClientCompiler::new(vec![ProductService::protobuf_service()]);
+        let code = compiler.compile_generated_code();
+
+        assert!(code.contains(
+            "pub async fn connect(endpoint: impl Into<String>) -> Result<Channel, ClientError>"
+        ));
+        assert!(code.contains(
+            "inner : :: protobuf :: product_service_client :: ProductServiceClient < Channel >"
+        ));
+        assert!(code.contains("pub async fn find_product (& self , request : :: schema :: ProductId ,) -> Result < :: schema :: ProductList , ClientError >"));
+        assert!(code.contains("let request = :: protobuf :: ProductId :: from (request)"));
+        assert!(code.contains("# [cfg (feature = \"retry\")] let response = self . retry . run"));
+    }
+
+    #[test]
+    fn idempotent_methods() {
+        let mut method = ProductService::protobuf_service().methods.remove(0);
+        let mut is_idempotent = |name: &str| {
+            method.name = name.to_string();
+            GrpcClientCompiler::is_idempotent(&method)
+        };
+
+        assert!(is_idempotent("FindProduct"));
+        assert!(is_idempotent("ListProducts"));
+        assert!(is_idempotent("Get"));
+        assert!(!is_idempotent("CreateProduct"));
+        assert!(!is_idempotent("Getaway"));
+    }
+
+    #[test]
+    fn documented_code() {
+        let compiler = GrpcClientCompiler::new(vec![ProductService::protobuf_service()]);
+
+        assert_eq!(
+            undocumented_items(&compiler.compile_generated_code()),
+            Vec::<String>::new()
+        );
+    }
+}
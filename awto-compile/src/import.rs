@@ -0,0 +1,714 @@
+//! Introspection of an existing database into the models of a schema lib, so awto can be adopted for a database
+//! it did not create.
+//!
+//! Columns are read with the same query as syncing, so compiling the imported models against the database they
+//! were imported from changes nothing.
+
+use std::{collections::BTreeSet, fmt::Write};
+
+use awto::database::{derived_name, DatabaseColumn, DatabaseDefault, DatabaseType};
+use heck::{CamelCase, SnakeCase};
+use sqlx::PgPool;
+
+use crate::{
+    connect::ConnectPolicy,
+    database::{fetch_column_rows, IDEMPOTENCY_TABLE, OUTBOX_TABLE},
+    error::Error,
+    util::rust_ident,
+};
+
+/// Columns every database table model must have, with the type of their field.
+const REQUIRED_COLUMNS: [(&str, DatabaseType); 3] = [
+    ("id", DatabaseType::Uuid),
+    ("created_at", DatabaseType::Timestamptz),
+    ("updated_at", DatabaseType::Timestamptz),
+];
+
+/// Types of fields which are not in `awto::prelude`, imported from chrono when used.
+const CHRONO_TYPES: [&str; 3] = ["NaiveDate", "NaiveDateTime", "NaiveTime"];
+
+/// Tables of a database along with their columns.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SchemaImport {
+    pub tables: Vec<ImportedTable>,
+}
+
+/// A table of the database, which becomes a model of the schema.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImportedTable {
+    pub name: String,
+    pub columns: Vec<ImportedColumn>,
+}
+
+/// A column of an imported table.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ImportedColumn {
+    /// A column whose type has a field type.
+    Mapped(DatabaseColumn),
+    /// A column whose type awto does not support, such as `geometry`, which is left for the user to map.
+    Unmapped { name: String, data_type: String },
+}
+
+/// Connects to the database at `uri` with the [`ConnectPolicy`] set in the environment, and imports its tables.
+pub async fn import_schema(uri: &str) -> Result<SchemaImport, Error> {
+    let pool = ConnectPolicy::from_env().connect(uri).await?;
+
+    import_schema_from_pool(&pool).await
+}
+
+/// Imports the tables of the `public` schema of the database of `pool`.
+///
+/// Partitions, the outbox, the idempotency keys and the translations and history tables awto creates alongside
+/// other tables are left out, as they are declared by the models of their tables.
+pub async fn import_schema_from_pool(pool: &PgPool) -> Result<SchemaImport, Error> {
+    let names: Vec<(String,)> = sqlx::query_as(FETCH_TABLES_QUERY)
+        .bind("public")
+        .fetch_all(pool)
+        .await
+        .map_err(Error::Sqlx)?;
+    let names: BTreeSet<String> = names.into_iter().map(|(name,)| name).collect();
+    let is_companion = |name: &str| {
+        names.iter().any(|parent| {
+            ["translations", "history"]
+                .iter()
+                .any(|suffix| derived_name(&[parent], suffix) == name)
+        })
+    };
+
+    let mut tables = Vec::new();
+    for name in names
+        .iter()
+        .filter(|name| *name != OUTBOX_TABLE && *name != IDEMPOTENCY_TABLE && !is_companion(name))
+    {
+        let columns = fetch_column_rows(pool, name)
+            .await?
+            .into_iter()
+            .map(|row| {
+                let name = row.column_name.clone();
+                let data_type = row.data_type.clone();
+                match row.into_column() {
+                    Some(column) if field_type(&column.ty).is_some() => {
+                        ImportedColumn::Mapped(column)
+                    }
+                    Some(column) => ImportedColumn::Unmapped {
+                        name,
+                        data_type: column.ty.to_string(),
+                    },
+                    None => ImportedColumn::Unmapped { name, data_type },
+                }
+            })
+            .collect();
+        tables.push(ImportedTable {
+            name: name.clone(),
+            columns,
+        });
+    }
+
+    Ok(SchemaImport { tables })
+}
+
+impl SchemaImport {
+    /// Returns the `table.column` and type of each column which could not be mapped to a field.
+    pub fn unmapped_columns(&self) -> Vec<(String, &str)> {
+        self.tables
+            .iter()
+            .flat_map(|table| {
+                table.columns.iter().filter_map(move |column| match column {
+                    ImportedColumn::Unmapped { name, data_type } => {
+                        Some((format!("{}.{}", table.name, name), data_type.as_str()))
+                    }
+                    ImportedColumn::Mapped(_) => None,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the names of the tables which cannot be named by a model, such as `OrderLine` or `order-line`.
+    pub fn unnamed_tables(&self) -> Vec<&str> {
+        self.tables
+            .iter()
+            .filter(|table| table.model_name().is_none())
+            .map(|table| table.name.as_str())
+            .collect()
+    }
+
+    /// Returns whether the imported models have `chrono` fields which are not in `awto::prelude`,
+    /// for which the schema package must depend on chrono.
+    pub fn uses_chrono(&self) -> bool {
+        !self.chrono_types().is_empty()
+    }
+
+    /// Renders the source of a schema lib declaring a model for each table in a single `schema!` block.
+    ///
+    /// Whatever could not be imported is left as a `// TODO` comment where it would be declared.
+    pub fn render(&self) -> String {
+        let mut source = String::from(
+            "//! Models imported from the tables of an existing database with `awto schema import`.\n\nuse awto::prelude::*;\n",
+        );
+        let chrono_types = self.chrono_types();
+        match chrono_types.as_slice() {
+            [] => {}
+            [chrono_type] => writeln!(source, "use chrono::{};", chrono_type).unwrap(),
+            _ => writeln!(source, "use chrono::{{{}}};", chrono_types.join(", ")).unwrap(),
+        }
+
+        source.push_str("\nschema! {\n");
+        for (i, table) in self.tables.iter().enumerate() {
+            if i > 0 {
+                source.push('\n');
+            }
+            self.render_table(&mut source, table);
+        }
+        source.push_str("}\n");
+
+        source
+    }
+
+    /// Returns the types of the fields which are imported from chrono, sorted.
+    fn chrono_types(&self) -> Vec<&'static str> {
+        CHRONO_TYPES
+            .iter()
+            .copied()
+            .filter(|chrono_ty| {
+                self.tables
+                    .iter()
+                    .flat_map(|table| &table.columns)
+                    .any(|column| match column {
+                        ImportedColumn::Mapped(column) => field_type(&column.ty) == Some(chrono_ty),
+                        ImportedColumn::Unmapped { .. } => false,
+                    })
+            })
+            .collect()
+    }
+
+    fn render_table(&self, source: &mut String, table: &ImportedTable) {
+        let model_name = match table.model_name() {
+            Some(model_name) => model_name,
+            None => {
+                writeln!(
+                    source,
+                    "    // TODO: table `{}` has no model name which maps back to it, rename it to snake case to import it",
+                    table.name
+                )
+                .unwrap();
+                return;
+            }
+        };
+
+        let string_key = table.string_key();
+        let missing: Vec<String> = REQUIRED_COLUMNS
+            .iter()
+            .filter(|(name, _)| string_key.is_none() || *name != "id")
+            .filter(|(name, ty)| {
+                !table.columns.iter().any(|column| match column {
+                    ImportedColumn::Mapped(column) => {
+                        column.name == *name && column.ty == *ty && !column.nullable
+                    }
+                    ImportedColumn::Unmapped { .. } => false,
+                })
+            })
+            .map(|(name, ty)| format!("`{}: {}`", name, field_type(ty).unwrap()))
+            .collect();
+        if !missing.is_empty() {
+            writeln!(
+                source,
+                "    // TODO: database tables need {} columns, add them to the table",
+                missing.join(", ")
+            )
+            .unwrap();
+        }
+        writeln!(
+            source,
+            "    #[database_table]\n    pub struct {} {{",
+            model_name
+        )
+        .unwrap();
+        for column in &table.columns {
+            self.render_column(source, column, string_key);
+        }
+        source.push_str("    }\n");
+    }
+
+    fn render_column(
+        &self,
+        source: &mut String,
+        column: &ImportedColumn,
+        string_key: Option<&str>,
+    ) {
+        let column = match column {
+            ImportedColumn::Mapped(column) => column,
+            ImportedColumn::Unmapped { name, data_type } => {
+                writeln!(
+                    source,
+                    "        // TODO: column `{}` has type `{}`, which has no field type, change its type to import it",
+                    name, data_type
+                )
+                .unwrap();
+                return;
+            }
+        };
+        let ident = match field_ident(&column.name) {
+            Some(ident) => ident,
+            None => {
+                writeln!(
+                    source,
+                    "        // TODO: column `{}` cannot be named by a field, rename it to snake case to import it",
+                    column.name
+                )
+                .unwrap();
+                return;
+            }
+        };
+
+        let is_required = REQUIRED_COLUMNS
+            .iter()
+            .any(|(name, _)| *name == column.name);
+        let is_string_key = string_key == Some(column.name.as_str());
+        if column.primary_key && column.name != "id" && !is_string_key {
+            writeln!(
+                source,
+                "        // TODO: `{}` is part of the primary key of the table, while tables are keyed by `id`",
+                column.name
+            )
+            .unwrap();
+        }
+
+        let mut args = Vec::new();
+        if is_string_key {
+            args.push("primary_key".to_string());
+        }
+        if let DatabaseType::Text(Some(max_len)) = column.ty {
+            args.push(format!("max_len = {}", max_len));
+        }
+        if column.unique && !column.primary_key {
+            args.push("unique".to_string());
+        }
+        if let Some((references_table, references_column)) = &column.references {
+            match self.model_name(references_table) {
+                Some(model_name) => args.push(format!(
+                    "references = ({}, {:?})",
+                    model_name, references_column
+                )),
+                None => writeln!(
+                    source,
+                    "        // TODO: `{}` references table `{}`, which is not imported",
+                    column.name, references_table
+                )
+                .unwrap(),
+            }
+        }
+        match (&column.computed, &column.default) {
+            (Some(expression), _) => args.push(format!("computed = {:?}, stored", expression)),
+            // The defaults of required columns are always set by awto
+            (None, Some(_)) if is_required => {}
+            (None, Some(DatabaseDefault::Bool(value))) => args.push(format!("default = {}", value)),
+            (None, Some(DatabaseDefault::Int(value))) => args.push(format!("default = {}", value)),
+            (None, Some(DatabaseDefault::String(value))) => {
+                args.push(format!("default = {:?}", value))
+            }
+            (None, Some(DatabaseDefault::Float(value))) => {
+                args.push(format!("default_raw = \"{}\"", value))
+            }
+            (None, Some(DatabaseDefault::Raw(value))) => {
+                args.push(format!("default_raw = {:?}", value))
+            }
+            (None, None) => {}
+        }
+        if !args.is_empty() {
+            writeln!(source, "        #[awto({})]", args.join(", ")).unwrap();
+        }
+
+        let ty = field_type(&column.ty).unwrap();
+        if column.nullable {
+            writeln!(source, "        pub {}: Option<{}>,", ident, ty).unwrap();
+        } else {
+            writeln!(source, "        pub {}: {},", ident, ty).unwrap();
+        }
+    }
+
+    /// Returns the name of the model of the imported table `name`.
+    fn model_name(&self, name: &str) -> Option<String> {
+        self.tables
+            .iter()
+            .find(|table| table.name == name)?
+            .model_name()
+    }
+}
+
+impl ImportedColumn {
+    /// Returns the name of the column.
+    pub fn name(&self) -> &str {
+        match self {
+            ImportedColumn::Mapped(column) => &column.name,
+            ImportedColumn::Unmapped { name, .. } => name,
+        }
+    }
+}
+
+impl ImportedTable {
+    /// Returns the name of the model of the table, such as `OrderLine` for `order_line`,
+    /// or `None` when no model name is snake cased into the name of the table.
+    pub fn model_name(&self) -> Option<String> {
+        let model_name = self.name.to_camel_case();
+        (is_ident(&self.name) && !model_name.is_empty() && model_name.to_snake_case() == self.name)
+            .then_some(model_name)
+    }
+
+    /// Returns the name of the primary key of the table when it is a single text column,
+    /// which is imported as a `String` field marked `#[awto(primary_key)]` instead of an `id`.
+    pub fn string_key(&self) -> Option<&str> {
+        let mut keys = self.columns.iter().filter_map(|column| match column {
+            ImportedColumn::Mapped(column) if column.primary_key => Some(column),
+            _ => None,
+        });
+        match (keys.next(), keys.next()) {
+            (Some(key), None) if matches!(key.ty, DatabaseType::Text(_)) && key.name != "id" => {
+                Some(&key.name)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Returns the type of the fields of columns of type `ty`, or `None` when no field type maps to it.
+///
+/// This is the reverse of the types of columns of fields of the schema macro.
+pub fn field_type(ty: &DatabaseType) -> Option<&'static str> {
+    let field_type = match ty {
+        DatabaseType::SmallInt => "i16",
+        DatabaseType::Integer => "i32",
+        DatabaseType::BigInt => "i64",
+        DatabaseType::Float => "f32",
+        DatabaseType::Double => "f64",
+        DatabaseType::Text(_) => "String",
+        DatabaseType::Binary => "Vec<u8>",
+        DatabaseType::Timestamp => "NaiveDateTime",
+        DatabaseType::Timestamptz => "DateTime<FixedOffset>",
+        DatabaseType::Date => "NaiveDate",
+        DatabaseType::Time => "NaiveTime",
+        DatabaseType::Bool => "bool",
+        DatabaseType::Uuid => "Uuid",
+        DatabaseType::Numeric(_)
+        | DatabaseType::Money
+        | DatabaseType::Timetz
+        | DatabaseType::Jsonb
+        | DatabaseType::Hstore => return None,
+    };
+
+    Some(field_type)
+}
+
+/// Returns the identifier of the field of the column `name`, such as `r#type` for `type`,
+/// or `None` when the field would name another column, such as `self_` for `self`.
+fn field_ident(name: &str) -> Option<String> {
+    if !is_ident(name) {
+        return None;
+    }
+    let ident = rust_ident(name).to_string();
+    (ident.trim_start_matches("r#") == name).then_some(ident)
+}
+
+/// Returns whether `name` is a snake cased identifier, such as `order_line`.
+fn is_ident(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_lowercase() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+const FETCH_TABLES_QUERY: &str = "
+SELECT c.relname::text FROM pg_class c
+JOIN pg_namespace n ON n.oid = c.relnamespace
+WHERE n.nspname = $1 AND c.relkind IN ('r', 'p') AND NOT c.relispartition
+ORDER BY c.relname
+";
+
+#[cfg(test)]
+mod test {
+    use awto::{
+        database::{DatabaseTable, IntoDatabaseTable},
+        schema::{Model, Role},
+        tests_cfg::Employee,
+    };
+    use sqlx::{postgres::PgConnectOptions, ConnectOptions, Executor};
+
+    use super::*;
+    use crate::database::{outbox_table, DatabaseCompiler};
+
+    fn column(name: &str, ty: DatabaseType) -> ImportedColumn {
+        let mut column = Employee::database_table()
+            .columns
+            .into_iter()
+            .find(|column| column.name == "id")
+            .unwrap();
+        column.name = name.to_string();
+        column.ty = ty;
+        column.default = None;
+        column.primary_key = false;
+        ImportedColumn::Mapped(column)
+    }
+
+    fn with(column: ImportedColumn, f: impl FnOnce(&mut DatabaseColumn)) -> ImportedColumn {
+        match column {
+            ImportedColumn::Mapped(mut column) => {
+                f(&mut column);
+                ImportedColumn::Mapped(column)
+            }
+            unmapped => unmapped,
+        }
+    }
+
+    /// Returns the models declared by the rendered schema of `import`, with the columns the schema macro gives them.
+    fn declared_models(import: &SchemaImport) -> Vec<Model> {
+        import
+            .tables
+            .iter()
+            .filter_map(|table| {
+                let columns = table
+                    .columns
+                    .iter()
+                    .filter_map(|column| match column {
+                        ImportedColumn::Mapped(column) if field_ident(&column.name).is_some() => {
+                            Some(column)
+                        }
+                        _ => None,
+                    })
+                    .map(|column| DatabaseColumn {
+                        default: match column.name.as_str() {
+                            "id" => Some(DatabaseDefault::Raw("uuid_generate_v4()".to_string())),
+                            "created_at" | "updated_at" => {
+                                Some(DatabaseDefault::Raw("NOW()".to_string()))
+                            }
+                            _ => column.default.clone(),
+                        },
+                        primary_key: column.name == "id",
+                        ..column.clone()
+                    })
+                    .collect();
+
+                Some(Model {
+                    name: table.model_name()?,
+                    doc: None,
+                    roles: vec![Role::DatabaseTable(DatabaseTable {
+                        name: table.name.clone(),
+                        columns,
+                        skip_rest: false,
+                        ..outbox_table()
+                    })],
+                    fields: Vec::new(),
+                    origin: None,
+                    feature: None,
+                    omitted_fields: Vec::new(),
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn field_types() {
+        assert_eq!(field_type(&DatabaseType::Text(Some(120))), Some("String"));
+        assert_eq!(
+            field_type(&DatabaseType::Timestamptz),
+            Some("DateTime<FixedOffset>")
+        );
+        assert_eq!(field_type(&DatabaseType::Binary), Some("Vec<u8>"));
+        assert_eq!(field_type(&DatabaseType::Jsonb), None);
+        assert_eq!(field_type(&DatabaseType::Numeric(None)), None);
+
+        assert_eq!(field_ident("name").as_deref(), Some("name"));
+        assert_eq!(field_ident("type").as_deref(), Some("r#type"));
+        assert_eq!(field_ident("self"), None);
+        assert_eq!(field_ident("Name"), None);
+        assert_eq!(field_ident("first name"), None);
+    }
+
+    #[test]
+    fn rendered_schema() {
+        let required = || {
+            vec![
+                with(column("id", DatabaseType::Uuid), |column| {
+                    column.primary_key = true;
+                    column.default = Some(DatabaseDefault::Raw("uuid_generate_v4()".to_string()));
+                }),
+                with(column("created_at", DatabaseType::Timestamptz), |column| {
+                    column.default = Some(DatabaseDefault::Raw("now()".to_string()));
+                }),
+                column("updated_at", DatabaseType::Timestamptz),
+            ]
+        };
+        let import = SchemaImport {
+            tables: vec![
+                ImportedTable {
+                    name: "order_line".to_string(),
+                    columns: required()
+                        .into_iter()
+                        .chain(vec![
+                            with(column("sku", DatabaseType::Text(Some(40))), |column| {
+                                column.unique = true
+                            }),
+                            with(column("quantity", DatabaseType::Integer), |column| {
+                                column.default = Some(DatabaseDefault::Int(1))
+                            }),
+                            with(column("note", DatabaseType::Text(None)), |column| {
+                                column.nullable = true;
+                                column.default =
+                                    Some(DatabaseDefault::String("none \"yet\"".to_string()));
+                            }),
+                            with(column("shipped_on", DatabaseType::Date), |column| {
+                                column.nullable = true
+                            }),
+                            with(column("type", DatabaseType::Bool), |column| {
+                                column.default = Some(DatabaseDefault::Bool(false))
+                            }),
+                            with(column("parent_id", DatabaseType::Uuid), |column| {
+                                column.nullable = true;
+                                column.references =
+                                    Some(("order_line".to_string(), "id".to_string()));
+                            }),
+                            ImportedColumn::Unmapped {
+                                name: "location".to_string(),
+                                data_type: "geometry".to_string(),
+                            },
+                            column("self", DatabaseType::Integer),
+                        ])
+                        .collect(),
+                },
+                ImportedTable {
+                    name: "Legacy Orders".to_string(),
+                    columns: required(),
+                },
+                ImportedTable {
+                    name: "tag".to_string(),
+                    columns: vec![
+                        with(column("code", DatabaseType::Text(None)), |column| {
+                            column.primary_key = true
+                        }),
+                        with(column("total", DatabaseType::Integer), |column| {
+                            column.computed = Some("(1 + 1)".to_string())
+                        }),
+                    ],
+                },
+            ],
+        };
+
+        assert_eq!(
+            import.unmapped_columns(),
+            [("order_line.location".to_string(), "geometry")]
+        );
+        assert_eq!(import.unnamed_tables(), ["Legacy Orders"]);
+        assert!(import.uses_chrono());
+        assert_eq!(
+            import.render(),
+            r#"//! Models imported from the tables of an existing database with `awto schema import`.
+
+use awto::prelude::*;
+use chrono::NaiveDate;
+
+schema! {
+    #[database_table]
+    pub struct OrderLine {
+        pub id: Uuid,
+        pub created_at: DateTime<FixedOffset>,
+        pub updated_at: DateTime<FixedOffset>,
+        #[awto(max_len = 40, unique)]
+        pub sku: String,
+        #[awto(default = 1)]
+        pub quantity: i32,
+        #[awto(default = "none \"yet\"")]
+        pub note: Option<String>,
+        pub shipped_on: Option<NaiveDate>,
+        #[awto(default = false)]
+        pub r#type: bool,
+        #[awto(references = (OrderLine, "id"))]
+        pub parent_id: Option<Uuid>,
+        // TODO: column `location` has type `geometry`, which has no field type, change its type to import it
+        // TODO: column `self` cannot be named by a field, rename it to snake case to import it
+    }
+
+    // TODO: table `Legacy Orders` has no model name which maps back to it, rename it to snake case to import it
+
+    // TODO: database tables need `created_at: DateTime<FixedOffset>`, `updated_at: DateTime<FixedOffset>` columns, add them to the table
+    #[database_table]
+    pub struct Tag {
+        #[awto(primary_key)]
+        pub code: String,
+        #[awto(computed = "(1 + 1)", stored)]
+        pub total: i32,
+    }
+}
+"#
+        );
+        syn::parse_file(&import.render()).unwrap();
+    }
+
+    /// Imports tables created without awto from the database at `DATABASE_URL`, and checks that compiling the
+    /// imported models against it changes nothing. Skipped when `DATABASE_URL` is not set.
+    #[tokio::test]
+    async fn round_trip() {
+        let url = match std::env::var("DATABASE_URL") {
+            Ok(url) => url,
+            Err(_) => return,
+        };
+        let options: PgConnectOptions = url.parse().unwrap();
+        let mut conn = options.connect().await.unwrap();
+        conn.execute("DROP DATABASE IF EXISTS awto_import_round_trip")
+            .await
+            .unwrap();
+        conn.execute("CREATE DATABASE awto_import_round_trip")
+            .await
+            .unwrap();
+
+        let pool = PgPool::connect_with(options.database("awto_import_round_trip"))
+            .await
+            .unwrap();
+        pool.execute(
+            r#"
+            CREATE EXTENSION IF NOT EXISTS "uuid-ossp";
+            CREATE TABLE customer (
+                id uuid PRIMARY KEY DEFAULT uuid_generate_v4(),
+                created_at timestamptz NOT NULL DEFAULT now(),
+                updated_at timestamptz NOT NULL DEFAULT now(),
+                email varchar(120) NOT NULL UNIQUE,
+                name varchar,
+                active boolean NOT NULL DEFAULT true,
+                visits integer NOT NULL DEFAULT 0,
+                born_on date,
+                referred_by uuid REFERENCES customer (id) ON DELETE SET NULL
+            );
+            CREATE TABLE "order" (
+                id uuid PRIMARY KEY DEFAULT uuid_generate_v4(),
+                created_at timestamptz NOT NULL DEFAULT now(),
+                updated_at timestamptz NOT NULL DEFAULT now(),
+                customer_id uuid NOT NULL REFERENCES customer (id),
+                "type" varchar NOT NULL DEFAULT 'online',
+                total bigint NOT NULL,
+                total_with_tax double precision GENERATED ALWAYS AS (total * 1.2) STORED
+            );
+            "#,
+        )
+        .await
+        .unwrap();
+
+        let import = import_schema_from_pool(&pool).await.unwrap();
+        let source = import.render();
+        let models = declared_models(&import);
+        let sql = DatabaseCompiler::from_pool(&pool, models)
+            .compile()
+            .await
+            .unwrap();
+        pool.close().await;
+        conn.execute("DROP DATABASE awto_import_round_trip")
+            .await
+            .unwrap();
+
+        assert!(import.unmapped_columns().is_empty());
+        assert!(source.contains("pub struct Order {"));
+        assert!(source.contains("#[awto(max_len = 120, unique)]\n        pub email: String,"));
+        assert!(source.contains(
+            "#[awto(references = (Customer, \"id\"))]\n        pub referred_by: Option<Uuid>,"
+        ));
+        assert!(source.contains("#[awto(default = \"online\")]\n        pub r#type: String,"));
+        assert!(source.contains("#[awto(computed = \"((total)::numeric * 1.2)\", stored)]"));
+        assert_eq!(sql, "");
+    }
+}
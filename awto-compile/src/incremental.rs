@@ -0,0 +1,388 @@
+//! Incremental generation, reusing the generated code of the models which did not change since the previous build.
+//!
+//! The code generated for each model with a database table is cached in `OUT_DIR/models/<Model>.rs`, and the
+//! [`ModelFingerprint`] it was generated from is recorded in the [`GenerationManifest`]. A fingerprint covers the model,
+//! the version of the generator and the options changing the generated code, and the models its code depends on
+//! (see [`model_dependencies`]), so changing a model also regenerates the models related to it.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use awto::{
+    database::DatabaseTable,
+    schema::{Model, Role},
+};
+
+use crate::{
+    provenance::{content_hash, GenerationManifest, ModelFingerprint},
+    util::write_if_changed,
+};
+
+/// Directory of `OUT_DIR` holding the cached code of each model.
+pub const MODELS_DIR: &str = "models";
+
+/// Why the code of a model was regenerated rather than reused.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Reason {
+    /// The previous build did not generate the model.
+    New,
+    /// The version of the generator or the options changing the generated code changed.
+    Options,
+    /// The model changed.
+    Changed,
+    /// A model the code of the model depends on changed, was added or was removed.
+    Dependency(String),
+    /// The cached code of the model is missing or was modified.
+    Missing,
+}
+
+/// A model whose code was regenerated.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Regeneration {
+    pub model: String,
+    pub reason: Reason,
+}
+
+/// Formats the regeneration as `regenerated `Comment` because its dependency `Category` changed`.
+impl fmt::Display for Regeneration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "regenerated `{}` because ", self.model)?;
+        match &self.reason {
+            Reason::New => write!(f, "it was not generated before"),
+            Reason::Options => write!(f, "the generator or its options changed"),
+            Reason::Changed => write!(f, "it changed"),
+            Reason::Dependency(dependency) => write!(f, "its dependency `{}` changed", dependency),
+            Reason::Missing => write!(f, "its cached code is missing or was modified"),
+        }
+    }
+}
+
+/// Returns the tables and sub tables of `model`.
+pub fn database_roles(model: &Model) -> impl Iterator<Item = &DatabaseTable> {
+    model.roles.iter().filter_map(|role| match role {
+        Role::DatabaseTable(table) | Role::DatabaseSubTable(table) => Some(table),
+        _ => None,
+    })
+}
+
+/// Returns the names of the models the generated code of each model with a database table depends on.
+///
+/// The code of a model depends on the models of the tables it references, directly or through the tables they
+/// reference, as its fake rows insert rows of these tables first. It also depends on the models of the tables
+/// referencing its tables, which get loaders of their rows, and on the other models of its tables, such as the
+/// models of its sub tables.
+pub fn model_dependencies(models: &[Model]) -> BTreeMap<&str, BTreeSet<&str>> {
+    let mut owners: HashMap<&str, Vec<&str>> = HashMap::new();
+    let mut references: HashMap<&str, BTreeSet<&str>> = HashMap::new();
+    for model in models {
+        for table in database_roles(model) {
+            owners.entry(&table.name).or_default().push(&model.name);
+            references.entry(&table.name).or_default().extend(
+                table
+                    .columns
+                    .iter()
+                    .filter_map(|column| column.references.as_ref())
+                    .map(|(referenced, _)| referenced.as_str()),
+            );
+        }
+    }
+
+    let mut dependencies = BTreeMap::new();
+    for model in models {
+        let own: BTreeSet<&str> = database_roles(model)
+            .map(|table| table.name.as_str())
+            .collect();
+        if own.is_empty() {
+            continue;
+        }
+
+        let mut tables = own.clone();
+        let mut unvisited: Vec<&str> = own.iter().copied().collect();
+        while let Some(table) = unvisited.pop() {
+            for &referenced in references.get(table).into_iter().flatten() {
+                if tables.insert(referenced) {
+                    unvisited.push(referenced);
+                }
+            }
+        }
+        for (&table, referenced) in &references {
+            if !referenced.is_disjoint(&own) {
+                tables.insert(table);
+            }
+        }
+
+        let names = tables
+            .into_iter()
+            .flat_map(|table| owners.get(table).into_iter().flatten().copied())
+            .filter(|&name| name != model.name)
+            .collect();
+        dependencies.insert(model.name.as_str(), names);
+    }
+
+    dependencies
+}
+
+/// Cache of the generated code of the models, reusing the code of the models whose fingerprint did not change.
+///
+/// The previous fingerprints are read from the manifest of the previous build, after which the cached code of the
+/// unchanged models is read with [`reuse`](Self::reuse). The code of the other models is generated and cached by
+/// [`code`](Self::code). A default cache holds no fingerprint, and generates the code of every model without caching it.
+#[derive(Debug, Default)]
+pub struct ModelCache {
+    /// Fingerprints of the models, completed with the hash of their code as it is generated or reused.
+    fingerprints: BTreeMap<String, ModelFingerprint>,
+    /// Fingerprints of the models generated by the previous build.
+    previous: BTreeMap<String, ModelFingerprint>,
+    /// Code of the unchanged models read from the cache.
+    cached: HashMap<String, String>,
+    /// Models whose code is regenerated, with the reason.
+    regenerated: BTreeMap<String, Reason>,
+}
+
+impl ModelCache {
+    /// Compares the fingerprints of `models`, generated with the hash of their `options`, with those of the
+    /// `previous` manifest.
+    pub fn new(models: &[Model], options: &str, previous: Option<&GenerationManifest>) -> Self {
+        let hashes: HashMap<&str, String> = models
+            .iter()
+            .map(|model| (model.name.as_str(), content_hash(&format!("{:?}", model))))
+            .collect();
+        let options = content_hash(options);
+        let fingerprints: BTreeMap<String, ModelFingerprint> = model_dependencies(models)
+            .into_iter()
+            .map(|(name, dependencies)| {
+                let fingerprint = ModelFingerprint {
+                    model: hashes[name].clone(),
+                    options: options.clone(),
+                    dependencies: dependencies
+                        .into_iter()
+                        .map(|dependency| (dependency.to_string(), hashes[dependency].clone()))
+                        .collect(),
+                    code: String::new(),
+                };
+                (name.to_string(), fingerprint)
+            })
+            .collect();
+        let previous = previous
+            .map(|manifest| manifest.models.clone())
+            .unwrap_or_default();
+
+        let regenerated = fingerprints
+            .iter()
+            .filter_map(|(name, fingerprint)| {
+                Some((name.clone(), stale_reason(fingerprint, previous.get(name))?))
+            })
+            .collect();
+
+        ModelCache {
+            fingerprints,
+            previous,
+            cached: HashMap::new(),
+            regenerated,
+        }
+    }
+
+    /// Returns the names of the models whose fingerprint did not change, along with the path of their cached code in `out_dir`.
+    pub fn cached_paths(&self, out_dir: &str) -> Vec<(String, PathBuf)> {
+        self.fingerprints
+            .keys()
+            .filter(|name| !self.regenerated.contains_key(*name))
+            .map(|name| (name.clone(), cache_path(out_dir, name)))
+            .collect()
+    }
+
+    /// Reuses the cached `code` of the model `name` read from its [cached path](Self::cached_paths), unless it is
+    /// missing or its hash differs from the hash recorded by the previous build.
+    pub fn reuse(&mut self, name: &str, code: Option<String>) {
+        let previous_hash = self
+            .previous
+            .get(name)
+            .map(|fingerprint| fingerprint.code.as_str());
+        match code {
+            Some(code) if previous_hash == Some(content_hash(&code).as_str()) => {
+                self.cached.insert(name.to_string(), code);
+            }
+            _ => {
+                self.regenerated.insert(name.to_string(), Reason::Missing);
+            }
+        }
+    }
+
+    /// Returns the code of the model `name`, reused from the cache or generated by `generate` and cached in `out_dir`.
+    pub fn code(
+        &mut self,
+        out_dir: &str,
+        name: &str,
+        generate: impl FnOnce() -> String,
+    ) -> io::Result<String> {
+        let fingerprint = match self.fingerprints.get_mut(name) {
+            Some(fingerprint) => fingerprint,
+            None => return Ok(generate()),
+        };
+        if let Some(code) = self.cached.remove(name) {
+            fingerprint.code = content_hash(&code);
+            return Ok(code);
+        }
+
+        let code = generate();
+        let path = cache_path(out_dir, name);
+        fs::create_dir_all(path.parent().unwrap())?;
+        write_if_changed(&path, &code)?;
+        fingerprint.code = content_hash(&code);
+        self.regenerated
+            .entry(name.to_string())
+            .or_insert(Reason::Missing);
+
+        Ok(code)
+    }
+
+    /// Returns the models whose code was regenerated, with the reason, by name.
+    pub fn regenerated(&self) -> Vec<Regeneration> {
+        self.regenerated
+            .iter()
+            .map(|(model, reason)| Regeneration {
+                model: model.clone(),
+                reason: reason.clone(),
+            })
+            .collect()
+    }
+
+    /// Returns the lines printed by verbose builds: each regenerated model with the reason, and the number of
+    /// models whose code was reused.
+    pub fn report(&self) -> Vec<String> {
+        let mut lines: Vec<String> = self
+            .regenerated()
+            .iter()
+            .map(Regeneration::to_string)
+            .collect();
+        let reused = self.fingerprints.len() - self.regenerated.len();
+        if reused > 0 {
+            lines.push(format!(
+                "reused the generated code of {} unchanged models",
+                reused
+            ));
+        }
+
+        lines
+    }
+
+    /// Records the fingerprints of the models in `manifest`, and removes the cached code of the models of the
+    /// previous build which no longer exist from `out_dir`.
+    pub fn finish(self, out_dir: &str, manifest: &mut GenerationManifest) -> io::Result<()> {
+        for name in self
+            .previous
+            .keys()
+            .filter(|name| !self.fingerprints.contains_key(*name))
+        {
+            match fs::remove_file(cache_path(out_dir, name)) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+        }
+        manifest.models = self.fingerprints;
+
+        Ok(())
+    }
+}
+
+/// Returns why the code of a model with `fingerprint` is regenerated, or `None` when the code of the `previous` build is reused.
+fn stale_reason(
+    fingerprint: &ModelFingerprint,
+    previous: Option<&ModelFingerprint>,
+) -> Option<Reason> {
+    let previous = match previous {
+        Some(previous) => previous,
+        None => return Some(Reason::New),
+    };
+    if fingerprint.options != previous.options {
+        return Some(Reason::Options);
+    }
+    if fingerprint.model != previous.model {
+        return Some(Reason::Changed);
+    }
+
+    fingerprint
+        .dependencies
+        .keys()
+        .chain(previous.dependencies.keys())
+        .find(|name| fingerprint.dependencies.get(*name) != previous.dependencies.get(*name))
+        .map(|name| Reason::Dependency(name.clone()))
+}
+
+fn cache_path(out_dir: &str, name: &str) -> PathBuf {
+    Path::new(out_dir)
+        .join(MODELS_DIR)
+        .join(format!("{}.rs", name))
+}
+
+#[cfg(test)]
+mod test {
+    use awto::tests_cfg::{tree, MODELS};
+
+    use super::*;
+
+    #[test]
+    fn dependency_graph() {
+        let mut models = MODELS.to_vec();
+        models.extend(tree::MODELS.to_vec());
+        let dependencies = model_dependencies(&models);
+
+        // Comments reference their category, which loads its comments
+        assert_eq!(dependencies["Comment"], BTreeSet::from(["Category"]));
+        assert_eq!(dependencies["Category"], BTreeSet::from(["Comment"]));
+        // Referencing rows of its own table is no dependency
+        assert_eq!(dependencies["Employee"], BTreeSet::new());
+        assert!(!dependencies.contains_key("ProductId"));
+    }
+
+    #[test]
+    fn regeneration_reasons() {
+        let fingerprint = ModelFingerprint {
+            model: "model".to_string(),
+            options: "options".to_string(),
+            dependencies: BTreeMap::from([("Category".to_string(), "category".to_string())]),
+            code: String::new(),
+        };
+        let changed = |change: fn(&mut ModelFingerprint)| {
+            let mut previous = fingerprint.clone();
+            change(&mut previous);
+            stale_reason(&fingerprint, Some(&previous))
+        };
+
+        assert_eq!(stale_reason(&fingerprint, None), Some(Reason::New));
+        assert_eq!(changed(|_| {}), None);
+        assert_eq!(changed(|previous| previous.code = "code".to_string()), None);
+        assert_eq!(
+            changed(|previous| previous.options.clear()),
+            Some(Reason::Options)
+        );
+        assert_eq!(
+            changed(|previous| previous.model.clear()),
+            Some(Reason::Changed)
+        );
+        assert_eq!(
+            changed(|previous| previous.dependencies.clear()),
+            Some(Reason::Dependency("Category".to_string()))
+        );
+        assert_eq!(
+            changed(|previous| {
+                previous
+                    .dependencies
+                    .insert("Tag".to_string(), "tag".to_string());
+            }),
+            Some(Reason::Dependency("Tag".to_string()))
+        );
+
+        let regeneration = Regeneration {
+            model: "Comment".to_string(),
+            reason: Reason::Dependency("Category".to_string()),
+        };
+        assert_eq!(
+            regeneration.to_string(),
+            "regenerated `Comment` because its dependency `Category` changed"
+        );
+    }
+}
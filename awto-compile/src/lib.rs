@@ -13,7 +13,30 @@
 //!
 //! See more on the [repository](https://github.com/awto-rs/awto).
 
+pub mod admin;
+pub mod api_docs;
+pub mod compat;
+pub mod connect;
 pub mod database;
+pub mod dialect;
 pub mod error;
+pub mod explain;
+pub mod features;
+pub mod format;
+pub mod generator;
+pub mod grpc_client;
+pub mod import;
+pub mod incremental;
+pub mod lock;
+pub mod naming;
+pub mod narrowing;
+pub mod profiling;
+pub mod proto_lock;
 pub mod protobuf;
+pub mod provenance;
+pub mod rest;
+pub mod schemas;
+pub mod scope;
+pub mod transcoding;
 mod util;
+pub mod warning;
@@ -0,0 +1,166 @@
+//! Locks serializing the syncs of the tables of a database, so builds syncing the same database at once,
+//! such as the builds of several checkouts against a shared database in CI, do not apply the same changes twice.
+//!
+//! The lock is a session-level advisory lock keyed by the name of the database, so syncs of other databases
+//! on the same server do not wait for each other. It is held by a connection of its own and released when the
+//! sync is done, or by closing that connection when the sync fails or is cancelled.
+
+use std::time::{Duration, Instant};
+
+use sqlx::pool::PoolConnection;
+use sqlx::{PgConnection, PgPool, Postgres};
+
+use crate::error::Error;
+
+/// First key of the advisory locks taken by awto, `awto` in ASCII, leaving other locks of the database alone.
+pub const LOCK_CLASS: i32 = 0x6177_746f;
+
+/// Wait between two attempts to take a lock held by another sync.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Takes the lock of the current database, with the second key derived from its name.
+const TRY_LOCK_QUERY: &str = "SELECT pg_try_advisory_lock($1, hashtext(current_database()))";
+
+const UNLOCK_QUERY: &str = "SELECT pg_advisory_unlock($1, hashtext(current_database()))";
+
+/// The lock of a database held while syncing its tables, taken with [`SyncLock::acquire`].
+///
+/// A lock which is dropped without being [released](SyncLock::release), such as when the sync returned an error
+/// or its future was cancelled, closes its connection instead of returning it to the pool,
+/// which makes the database release the lock.
+#[derive(Debug)]
+pub struct SyncLock {
+    conn: Option<PoolConnection<Postgres>>,
+}
+
+impl SyncLock {
+    /// Takes the lock of the database of `pool`, waiting up to `timeout` while another sync holds it.
+    pub async fn acquire(pool: &PgPool, timeout: Duration) -> Result<SyncLock, Error> {
+        let mut conn = pool.acquire().await.map_err(Error::Sqlx)?;
+        let start = Instant::now();
+        loop {
+            let locked: bool = sqlx::query_scalar(TRY_LOCK_QUERY)
+                .bind(LOCK_CLASS)
+                .fetch_one(&mut conn)
+                .await
+                .map_err(Error::Sqlx)?;
+            if locked {
+                return Ok(SyncLock { conn: Some(conn) });
+            }
+
+            let waited = start.elapsed();
+            if waited >= timeout {
+                let database: String = sqlx::query_scalar("SELECT current_database()")
+                    .fetch_one(&mut conn)
+                    .await
+                    .map_err(Error::Sqlx)?;
+                return Err(Error::SyncInProgress { database, timeout });
+            }
+            tokio::time::sleep(POLL_INTERVAL.min(timeout - waited)).await;
+        }
+    }
+
+    /// Returns the connection holding the lock, on which the statements of the sync can run.
+    pub fn connection(&mut self) -> &mut PgConnection {
+        self.conn
+            .as_mut()
+            .expect("the connection is only taken when the lock is released")
+    }
+
+    /// Releases the lock, returning its connection to the pool.
+    pub async fn release(mut self) -> Result<(), Error> {
+        let mut conn = self
+            .conn
+            .take()
+            .expect("the connection is only taken when the lock is released");
+        let unlocked = sqlx::query_scalar::<_, bool>(UNLOCK_QUERY)
+            .bind(LOCK_CLASS)
+            .fetch_one(&mut conn)
+            .await;
+        match unlocked {
+            Ok(_) => Ok(()),
+            Err(err) => {
+                drop(conn.detach());
+                Err(Error::Sqlx(err))
+            }
+        }
+    }
+}
+
+impl Drop for SyncLock {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            drop(conn.detach());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use sqlx::Executor;
+
+    use super::*;
+
+    /// Fails a statement while holding the lock of the database, like a sync whose changes are rejected.
+    async fn failing_sync(pool: &PgPool) -> Result<(), Error> {
+        let mut lock = SyncLock::acquire(pool, Duration::from_secs(5)).await?;
+        lock.connection()
+            .execute("SELECT 1 / 0")
+            .await
+            .map_err(Error::Sqlx)?;
+        lock.release().await
+    }
+
+    /// Takes the lock of the database at `DATABASE_URL` from several pools, checking that syncs wait for each other
+    /// and that failed and cancelled syncs release it. Skipped when `DATABASE_URL` is not set.
+    #[tokio::test]
+    async fn serialized_syncs() {
+        let url = match std::env::var("DATABASE_URL") {
+            Ok(url) => url,
+            Err(_) => return,
+        };
+        let pool = PgPool::connect(&url).await.unwrap();
+        let other = PgPool::connect(&url).await.unwrap();
+
+        let lock = SyncLock::acquire(&pool, Duration::from_secs(5))
+            .await
+            .unwrap();
+        let err = SyncLock::acquire(&other, Duration::from_millis(300))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::SyncInProgress { .. }), "{}", err);
+        assert!(err
+            .to_string()
+            .starts_with("another sync of the tables of database `"));
+        lock.release().await.unwrap();
+        SyncLock::acquire(&other, Duration::ZERO)
+            .await
+            .unwrap()
+            .release()
+            .await
+            .unwrap();
+
+        assert!(matches!(failing_sync(&pool).await, Err(Error::Sqlx(_))));
+        SyncLock::acquire(&other, Duration::from_secs(5))
+            .await
+            .unwrap()
+            .release()
+            .await
+            .unwrap();
+
+        let cancelled = tokio::time::timeout(Duration::from_millis(300), async {
+            let _lock = SyncLock::acquire(&pool, Duration::from_secs(5))
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_secs(60)).await;
+        })
+        .await;
+        assert!(cancelled.is_err());
+        SyncLock::acquire(&other, Duration::from_secs(5))
+            .await
+            .unwrap()
+            .release()
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,327 @@
+//! Pluralization of the names of tables and resources in generated REST paths.
+
+use heck::SnakeCase;
+
+/// Nouns whose plural does not follow the rules of [`pluralize`], with their plural.
+const IRREGULAR: [(&str, &str); 58] = [
+    ("alumnus", "alumni"),
+    ("analysis", "analyses"),
+    ("appendix", "appendices"),
+    ("axis", "axes"),
+    ("cactus", "cacti"),
+    ("calf", "calves"),
+    ("child", "children"),
+    ("criterion", "criteria"),
+    ("curriculum", "curricula"),
+    ("datum", "data"),
+    ("die", "dice"),
+    ("echo", "echoes"),
+    ("elf", "elves"),
+    ("epoch", "epochs"),
+    ("focus", "foci"),
+    ("foot", "feet"),
+    ("fungus", "fungi"),
+    ("goose", "geese"),
+    ("half", "halves"),
+    ("hero", "heroes"),
+    ("index", "indices"),
+    ("knife", "knives"),
+    ("leaf", "leaves"),
+    ("life", "lives"),
+    ("loaf", "loaves"),
+    ("louse", "lice"),
+    ("man", "men"),
+    ("matrix", "matrices"),
+    ("medium", "media"),
+    ("memorandum", "memoranda"),
+    ("monarch", "monarchs"),
+    ("mouse", "mice"),
+    ("nucleus", "nuclei"),
+    ("ox", "oxen"),
+    ("passerby", "passersby"),
+    ("person", "people"),
+    ("phenomenon", "phenomena"),
+    ("potato", "potatoes"),
+    ("quiz", "quizzes"),
+    ("radius", "radii"),
+    ("scarf", "scarves"),
+    ("self", "selves"),
+    ("sheaf", "sheaves"),
+    ("shelf", "shelves"),
+    ("stadium", "stadiums"),
+    ("stimulus", "stimuli"),
+    ("stomach", "stomachs"),
+    ("syllabus", "syllabi"),
+    ("thief", "thieves"),
+    ("tomato", "tomatoes"),
+    ("tooth", "teeth"),
+    ("torpedo", "torpedoes"),
+    ("vertex", "vertices"),
+    ("veto", "vetoes"),
+    ("volcano", "volcanoes"),
+    ("wife", "wives"),
+    ("wolf", "wolves"),
+    ("woman", "women"),
+];
+
+/// Nouns whose plural is the same as their singular.
+const UNCOUNTABLE: [&str; 21] = [
+    "aircraft",
+    "bison",
+    "deer",
+    "equipment",
+    "feedback",
+    "fish",
+    "hardware",
+    "information",
+    "metadata",
+    "moose",
+    "news",
+    "offspring",
+    "police",
+    "rice",
+    "salmon",
+    "series",
+    "sheep",
+    "software",
+    "species",
+    "staff",
+    "trout",
+];
+
+/// Returns the plural of `name`, such as `categories` for `category` or `order_items` for `order_item`.
+///
+/// Only the last word of snake case and camel case names is pluralized, and acronyms such as `SKU` get a lowercase `s`.
+pub fn pluralize(name: &str) -> String {
+    pluralize_with(name, &[])
+}
+
+/// Returns the plural of `name` like [`pluralize`], with `overrides` of the plurals of lowercase words or whole names,
+/// such as `("person", "persons")`, taking precedence over the rules and dictionaries.
+pub fn pluralize_with(name: &str, overrides: &[(&str, &str)]) -> String {
+    if let Some((_, plural)) = overrides.iter().find(|(singular, _)| *singular == name) {
+        return plural.to_string();
+    }
+
+    let (prefix, word) = name.split_at(last_word_start(name));
+    if word.is_empty() {
+        return name.to_string();
+    }
+    if word.len() > 1
+        && word
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+    {
+        return format!("{}{}s", prefix, word);
+    }
+
+    let lowercase = word.to_lowercase();
+    let plural = match overrides
+        .iter()
+        .find(|(singular, _)| *singular == lowercase)
+    {
+        Some((_, plural)) => plural.to_string(),
+        None => pluralize_word(&lowercase),
+    };
+    if word.starts_with(|c: char| c.is_uppercase()) {
+        let mut chars = plural.chars();
+        let first = chars
+            .next()
+            .map(|c| c.to_uppercase().to_string())
+            .unwrap_or_default();
+        format!("{}{}{}", prefix, first, chars.as_str())
+    } else {
+        format!("{}{}", prefix, plural)
+    }
+}
+
+/// Returns the plural naming the list query of the projection named `projection` of `table`, such as `summaries`
+/// for `ProductSummary` of `product`, which leaves out the name of the table when the projection starts with it.
+pub fn projection_plural(table: &str, projection: &str) -> String {
+    let name = projection.to_snake_case();
+    let name = name
+        .strip_prefix(table)
+        .and_then(|name| name.strip_prefix('_'))
+        .unwrap_or(&name);
+
+    pluralize(name)
+}
+
+/// Returns the index of the last word of `name`, after its last `_` or `-`, or at its last uppercase letter.
+///
+/// A run of uppercase letters ending the name, such as the `SKU` of `ProductSKU`, is a single word.
+fn last_word_start(name: &str) -> usize {
+    let start = name.rfind(['_', '-', ' ']).map(|i| i + 1).unwrap_or(0);
+    let word = &name[start..];
+
+    let chars: Vec<(usize, char)> = word.char_indices().collect();
+    let is_acronym_char = |c: char| c.is_ascii_uppercase() || c.is_ascii_digit();
+    if chars.last().is_some_and(|(_, c)| c.is_ascii_uppercase()) {
+        let acronym_start = chars
+            .iter()
+            .rev()
+            .take_while(|(_, c)| is_acronym_char(*c))
+            .last()
+            .map(|(i, _)| *i)
+            .unwrap_or(0);
+        return start + acronym_start;
+    }
+
+    let word_start = chars
+        .iter()
+        .rev()
+        .find(|(_, c)| c.is_uppercase())
+        .map(|(i, _)| *i)
+        .unwrap_or(0);
+    start + word_start
+}
+
+/// Returns the plural of the lowercase `word`.
+fn pluralize_word(word: &str) -> String {
+    if UNCOUNTABLE.contains(&word) || IRREGULAR.iter().any(|(_, plural)| *plural == word) {
+        return word.to_string();
+    }
+    if let Some((_, plural)) = IRREGULAR.iter().find(|(singular, _)| *singular == word) {
+        return plural.to_string();
+    }
+
+    let ends_with_consonant_and = |suffix: char| {
+        let mut chars = word.chars().rev();
+        chars.next() == Some(suffix) && chars.next().is_some_and(|c| !"aeiou".contains(c))
+    };
+    if let Some(stem) = word.strip_suffix("sis") {
+        format!("{}ses", stem)
+    } else if word.ends_with('s')
+        || word.ends_with('x')
+        || word.ends_with('z')
+        || word.ends_with("ch")
+        || word.ends_with("sh")
+    {
+        format!("{}es", word)
+    } else if ends_with_consonant_and('y') {
+        format!("{}ies", &word[..word.len() - 1])
+    } else {
+        format!("{}s", word)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn pluralize_nouns() {
+        let nouns = [
+            ("product", "products"),
+            ("address", "addresses"),
+            ("category", "categories"),
+            ("day", "days"),
+            ("person", "people"),
+            ("status", "statuses"),
+            ("deer", "deer"),
+            ("sheep", "sheep"),
+            ("series", "series"),
+            ("species", "species"),
+            ("news", "news"),
+            ("child", "children"),
+            ("man", "men"),
+            ("woman", "women"),
+            ("human", "humans"),
+            ("mouse", "mice"),
+            ("goose", "geese"),
+            ("tooth", "teeth"),
+            ("foot", "feet"),
+            ("ox", "oxen"),
+            ("box", "boxes"),
+            ("tax", "taxes"),
+            ("index", "indices"),
+            ("matrix", "matrices"),
+            ("vertex", "vertices"),
+            ("analysis", "analyses"),
+            ("basis", "bases"),
+            ("crisis", "crises"),
+            ("criterion", "criteria"),
+            ("phenomenon", "phenomena"),
+            ("datum", "data"),
+            ("medium", "media"),
+            ("cactus", "cacti"),
+            ("radius", "radii"),
+            ("campus", "campuses"),
+            ("bus", "buses"),
+            ("quiz", "quizzes"),
+            ("waltz", "waltzes"),
+            ("church", "churches"),
+            ("wish", "wishes"),
+            ("stomach", "stomachs"),
+            ("epoch", "epochs"),
+            ("key", "keys"),
+            ("boy", "boys"),
+            ("company", "companies"),
+            ("leaf", "leaves"),
+            ("knife", "knives"),
+            ("wife", "wives"),
+            ("wolf", "wolves"),
+            ("shelf", "shelves"),
+            ("roof", "roofs"),
+            ("chef", "chefs"),
+            ("belief", "beliefs"),
+            ("safe", "safes"),
+            ("hero", "heroes"),
+            ("potato", "potatoes"),
+            ("photo", "photos"),
+            ("video", "videos"),
+            ("zoo", "zoos"),
+            ("radio", "radios"),
+            ("fish", "fish"),
+            ("equipment", "equipment"),
+            ("metadata", "metadata"),
+            ("people", "people"),
+            ("order_item", "order_items"),
+            ("sales_person", "sales_people"),
+            ("line_status", "line_statuses"),
+            ("OrderItem", "OrderItems"),
+            ("Person", "People"),
+            ("SKU", "SKUs"),
+            ("ProductSKU", "ProductSKUs"),
+            ("product_sku", "product_skus"),
+            ("API", "APIs"),
+        ];
+
+        let wrong: Vec<_> = nouns
+            .iter()
+            .map(|(singular, plural)| (*singular, pluralize(singular), *plural))
+            .filter(|(_, pluralized, plural)| pluralized != plural)
+            .collect();
+        assert!(wrong.is_empty(), "wrong plurals: {:?}", wrong);
+    }
+
+    #[test]
+    fn plural_overrides() {
+        let overrides = [
+            ("person", "persons"),
+            ("order_item", "order_lines"),
+            ("status", "status"),
+        ];
+
+        assert_eq!(pluralize_with("person", &overrides), "persons");
+        assert_eq!(pluralize_with("sales_person", &overrides), "sales_persons");
+        assert_eq!(pluralize_with("Person", &overrides), "Persons");
+        assert_eq!(pluralize_with("order_item", &overrides), "order_lines");
+        assert_eq!(pluralize_with("line_status", &overrides), "line_status");
+        assert_eq!(pluralize_with("product", &overrides), "products");
+    }
+
+    #[test]
+    fn projection_plurals() {
+        assert_eq!(projection_plural("product", "ProductSummary"), "summaries");
+        assert_eq!(projection_plural("order_item", "OrderItemPrice"), "prices");
+        assert_eq!(
+            projection_plural("product", "CatalogEntry"),
+            "catalog_entries"
+        );
+        assert_eq!(
+            projection_plural("product", "ProductionRun"),
+            "production_runs"
+        );
+    }
+}
@@ -0,0 +1,359 @@
+//! Pre-flight checks of the changes of columns narrowing the values they hold, such as a text column getting
+//! a shorter maximum length or a nullable column becoming `NOT NULL`.
+//!
+//! Altering such a column fails midway through a sync when rows do not fit it, or silently truncates them,
+//! so syncs count these rows first. A sync refuses to apply any change when some rows do not fit, unless the
+//! column has a `#[awto(column(backfill = "..."))]` expression, which is applied to those rows before the column
+//! is altered, or [`FORCE_NARROWING_ENV`] is set, such as by `awto compile database --force-narrowing`.
+
+use std::fmt;
+
+use awto::database::{quote_ident, DatabaseColumn, DatabaseType};
+use sqlx::PgPool;
+
+use crate::{connect::ConnectPolicy, error::Error};
+
+/// Environment variable set to apply narrowing changes of columns even when rows do not fit them.
+pub const FORCE_NARROWING_ENV: &str = "AWTO_FORCE_NARROWING";
+
+/// Number of primary keys of the rows not fitting a narrowed column reported along with their count.
+pub const NARROWING_SAMPLE_SIZE: usize = 5;
+
+/// Version of postgres from which `pg_input_is_valid` checks whether values can be cast to another type.
+const INPUT_VALIDATION_VERSION: i32 = 160000;
+
+const FETCH_SERVER_VERSION_QUERY: &str = "SELECT current_setting('server_version_num')::int";
+
+/// A change of a column which values of the column may not fit.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Narrowing {
+    /// A nullable column becomes `NOT NULL`.
+    NotNull,
+    /// A text column gets a maximum length shorter than its longest values may be.
+    MaxLen(i32),
+    /// A column changes to a type which its values may not be cast to.
+    Type(DatabaseType),
+}
+
+impl Narrowing {
+    /// Returns the narrowing changes of the column `db_col` of the database into `schema_col`.
+    pub fn between(db_col: &DatabaseColumn, schema_col: &DatabaseColumn) -> Vec<Narrowing> {
+        let mut narrowings = Vec::new();
+        if db_col.nullable && !schema_col.nullable {
+            narrowings.push(Narrowing::NotNull);
+        }
+        if let Some(narrowing) = Self::of_type(&db_col.ty, &schema_col.ty) {
+            narrowings.push(narrowing);
+        }
+
+        narrowings
+    }
+
+    /// Returns the narrowing of changing a column of type `from` to `to`, unless every value of `from` fits `to`.
+    pub fn of_type(from: &DatabaseType, to: &DatabaseType) -> Option<Narrowing> {
+        use DatabaseType::*;
+
+        match (from, to) {
+            _ if from == to => None,
+            (Text(Some(len)), Text(Some(max))) if len <= max => None,
+            (Text(_), Text(Some(max))) => Some(Narrowing::MaxLen(*max)),
+            // Every value has a text representation
+            (_, Text(None)) => None,
+            (SmallInt, Integer | BigInt | Numeric(None) | Double)
+            | (Integer, BigInt | Numeric(None) | Double)
+            | (BigInt, Numeric(None))
+            | (Numeric(_), Numeric(None))
+            | (Float, Double)
+            | (Timestamp | Date, Timestamp | Timestamptz)
+            | (Time, Timetz) => None,
+            _ => Some(Narrowing::Type(to.clone())),
+        }
+    }
+
+    /// Returns the narrowing of giving the text column of type `from` the maximum length `max_len`,
+    /// unless its maximum length is no longer.
+    pub fn of_max_len(from: &DatabaseType, max_len: i32) -> Option<Narrowing> {
+        Self::of_type(from, &DatabaseType::Text(Some(max_len)))
+            .filter(|narrowing| matches!(narrowing, Narrowing::MaxLen(_)))
+    }
+
+    /// Returns the sql condition of the rows whose value of `column` does not fit the narrowed column.
+    pub fn condition(&self, column: &str) -> String {
+        let column = quote_ident(column);
+        match self {
+            Narrowing::NotNull => format!("{} IS NULL", column),
+            Narrowing::MaxLen(max) => format!("char_length({}) > {}", column, max),
+            Narrowing::Type(ty) => format!(
+                "{column} IS NOT NULL AND NOT pg_input_is_valid({column}::text, '{ty}')",
+                column = column,
+                ty = ty,
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Narrowing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Narrowing::NotNull => write!(f, "null, but the column becomes NOT NULL"),
+            Narrowing::MaxLen(max) => write!(
+                f,
+                "longer than the new maximum length of {} characters",
+                max
+            ),
+            Narrowing::Type(ty) => write!(f, "not valid {} values", ty),
+        }
+    }
+}
+
+/// Rows of a table which do not fit a narrowing change of one of its columns.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NarrowingViolation {
+    pub table: String,
+    pub column: String,
+    pub narrowing: Narrowing,
+    /// Number of rows not fitting the column.
+    pub rows: i64,
+    /// Primary keys of the first of these rows, up to [`NARROWING_SAMPLE_SIZE`].
+    pub sample: Vec<String>,
+}
+
+impl fmt::Display for NarrowingViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}.{}: {} row(s) are {}",
+            self.table, self.column, self.rows, self.narrowing
+        )?;
+        if !self.sample.is_empty() {
+            write!(f, " (keys {}", self.sample.join(", "))?;
+            if self.rows > self.sample.len() as i64 {
+                write!(f, ", ...")?;
+            }
+            write!(f, ")")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A narrowing change of a column to check, along with the primary key of its table used to report offending rows.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NarrowingCheck {
+    pub table: String,
+    pub column: String,
+    pub primary_key: Option<String>,
+    pub narrowing: Narrowing,
+}
+
+impl NarrowingCheck {
+    /// Counts the rows of the database of `pool` not fitting the narrowed column, returning `None` when every row fits.
+    ///
+    /// Changes of type are only checked from postgres 16, which added `pg_input_is_valid`,
+    /// so they are assumed to fit on older servers.
+    pub async fn run(&self, pool: &PgPool) -> Result<Option<NarrowingViolation>, Error> {
+        if let Narrowing::Type(_) = self.narrowing {
+            let (version,): (i32,) = sqlx::query_as(FETCH_SERVER_VERSION_QUERY)
+                .fetch_one(pool)
+                .await
+                .map_err(Error::Sqlx)?;
+            if version < INPUT_VALIDATION_VERSION {
+                return Ok(None);
+            }
+        }
+
+        let table = quote_ident(&self.table);
+        let condition = self.narrowing.condition(&self.column);
+        let (rows,): (i64,) = sqlx::query_as(&format!(
+            "SELECT count(*) FROM {} WHERE {}",
+            table, condition
+        ))
+        .fetch_one(pool)
+        .await
+        .map_err(Error::Sqlx)?;
+        if rows == 0 {
+            return Ok(None);
+        }
+
+        let sample = match &self.primary_key {
+            Some(primary_key) => {
+                let keys: Vec<(String,)> = sqlx::query_as(&format!(
+                    "SELECT {key}::text FROM {table} WHERE {condition} ORDER BY {key} LIMIT {limit}",
+                    key = quote_ident(primary_key),
+                    table = table,
+                    condition = condition,
+                    limit = NARROWING_SAMPLE_SIZE,
+                ))
+                .fetch_all(pool)
+                .await
+                .map_err(Error::Sqlx)?;
+                keys.into_iter().map(|(key,)| key).collect()
+            }
+            None => Vec::new(),
+        };
+
+        Ok(Some(NarrowingViolation {
+            table: self.table.clone(),
+            column: self.column.clone(),
+            narrowing: self.narrowing.clone(),
+            rows,
+            sample,
+        }))
+    }
+}
+
+/// Connects to the database at `uri` with the [`ConnectPolicy`] set in the environment, and runs `checks`,
+/// returning the violations found in their order.
+pub async fn check_narrowings(
+    uri: &str,
+    checks: &[NarrowingCheck],
+) -> Result<Vec<NarrowingViolation>, Error> {
+    if checks.is_empty() {
+        return Ok(Vec::new());
+    }
+    let pool = ConnectPolicy::from_env().connect(uri).await?;
+
+    let mut violations = Vec::new();
+    for check in checks {
+        violations.extend(check.run(&pool).await?);
+    }
+
+    Ok(violations)
+}
+
+#[cfg(test)]
+mod test {
+    use awto::tests_cfg::narrowing;
+    use sqlx::{postgres::PgConnectOptions, ConnectOptions, Executor};
+
+    use crate::database::DatabaseCompiler;
+
+    use super::*;
+
+    #[test]
+    fn narrowing_types() {
+        use DatabaseType::*;
+
+        assert_eq!(
+            Narrowing::of_type(&Text(None), &Text(Some(50))),
+            Some(Narrowing::MaxLen(50))
+        );
+        assert_eq!(
+            Narrowing::of_type(&Text(Some(120)), &Text(Some(50))),
+            Some(Narrowing::MaxLen(50))
+        );
+        assert_eq!(Narrowing::of_type(&Text(Some(50)), &Text(Some(120))), None);
+        assert_eq!(Narrowing::of_type(&Text(Some(50)), &Text(None)), None);
+        assert_eq!(Narrowing::of_type(&Integer, &BigInt), None);
+        assert_eq!(
+            Narrowing::of_type(&BigInt, &Integer),
+            Some(Narrowing::Type(Integer))
+        );
+        assert_eq!(
+            Narrowing::of_type(&Text(None), &Uuid),
+            Some(Narrowing::Type(Uuid))
+        );
+        assert_eq!(
+            Narrowing::of_max_len(&Text(None), 50),
+            Some(Narrowing::MaxLen(50))
+        );
+        assert_eq!(Narrowing::of_max_len(&Uuid, 50), None);
+
+        assert_eq!(Narrowing::NotNull.condition("name"), "name IS NULL");
+        assert_eq!(
+            Narrowing::MaxLen(50).condition("name"),
+            "char_length(name) > 50"
+        );
+        assert_eq!(
+            Narrowing::Type(Uuid).condition("code"),
+            "code IS NOT NULL AND NOT pg_input_is_valid(code::text, 'uuid')"
+        );
+    }
+
+    #[test]
+    fn violation_message() {
+        let violation = NarrowingViolation {
+            table: "product".to_string(),
+            column: "name".to_string(),
+            narrowing: Narrowing::MaxLen(50),
+            rows: 7,
+            sample: vec!["3".to_string(), "8".to_string()],
+        };
+        assert_eq!(
+            violation.to_string(),
+            "product.name: 7 row(s) are longer than the new maximum length of 50 characters (keys 3, 8, ...)"
+        );
+    }
+
+    /// Syncs a model narrowing the columns of a table of the database at `DATABASE_URL` holding rows which do not fit,
+    /// checking the sync fails before applying anything unless forced, and backfills the column with a backfill.
+    /// Skipped when `DATABASE_URL` is not set.
+    #[tokio::test]
+    async fn narrowing_sync() {
+        let url = match std::env::var("DATABASE_URL") {
+            Ok(url) => url,
+            Err(_) => return,
+        };
+        let options: PgConnectOptions = url.parse().unwrap();
+        let mut conn = options.connect().await.unwrap();
+        conn.execute("DROP DATABASE IF EXISTS awto_narrowing_sync")
+            .await
+            .unwrap();
+        conn.execute("CREATE DATABASE awto_narrowing_sync")
+            .await
+            .unwrap();
+
+        let pool = PgPool::connect_with(options.database("awto_narrowing_sync"))
+            .await
+            .unwrap();
+        pool.execute(
+            r#"
+            CREATE TABLE product (
+                id uuid PRIMARY KEY,
+                created_at timestamptz NOT NULL DEFAULT now(),
+                updated_at timestamptz NOT NULL DEFAULT now(),
+                name varchar,
+                sku varchar NOT NULL,
+                note varchar
+            );
+            INSERT INTO product (id, name, sku, note) VALUES
+                ('00000000-0000-0000-0000-000000000001', NULL, 'SKU-000000001', 'fits'),
+                ('00000000-0000-0000-0000-000000000002', 'Chair', 'SKU-2', 'a note longer than twenty characters');
+            "#,
+        )
+        .await
+        .unwrap();
+
+        let models = narrowing::MODELS.to_vec();
+        let err = DatabaseCompiler::from_pool(&pool, models.clone())
+            .sync()
+            .await
+            .unwrap_err();
+        let forced = DatabaseCompiler::from_pool(&pool, models)
+            .with_force_narrowing(true)
+            .compile()
+            .await
+            .unwrap();
+        let (nulls,): (i64,) = sqlx::query_as("SELECT count(*) FROM product WHERE name IS NULL")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        pool.close().await;
+        conn.execute("DROP DATABASE awto_narrowing_sync")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            err.to_string(),
+            "rows of the database do not fit the columns narrowed by the schema, \
+            give the fields a `#[awto(column(backfill = \"...\"))]` or pass `--force-narrowing`:\n  \
+            product.name: 1 row(s) are null, but the column becomes NOT NULL (keys 00000000-0000-0000-0000-000000000001)\n  \
+            product.note: 1 row(s) are longer than the new maximum length of 20 characters (keys 00000000-0000-0000-0000-000000000002)"
+        );
+        assert_eq!(nulls, 1);
+        assert!(
+            forced.contains("UPDATE product SET sku = (left(sku, 8)) WHERE char_length(sku) > 8;")
+        );
+        assert!(forced.contains("ALTER TABLE product ALTER COLUMN name SET NOT NULL;"));
+    }
+}
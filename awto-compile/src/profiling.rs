@@ -0,0 +1,115 @@
+//! Profiling of the phases of generation, enabled by `awto --profile-generation`.
+//!
+//! Build scripts print the wall time and peak memory of each phase as cargo warnings,
+//! `awto profile[database/app.rs] wall=1250ms peak_rss=98304kB`, which `awto` reads back to report them at the end of the run.
+
+use std::{env, fs, future::Future, time::Instant};
+
+/// Environment variable enabling the profiling of generation when set, changing on every run of `awto --profile-generation`
+/// so the build scripts rerun.
+pub const PROFILE_GENERATION_ENV: &str = "AWTO_PROFILE_GENERATION";
+
+/// Start of the lines reporting a phase.
+pub const PROFILE_MARKER: &str = "awto profile[";
+
+/// Profile of the phases of a generator, which are only measured when [`PROFILE_GENERATION_ENV`] is set.
+#[derive(Clone, Debug)]
+pub struct GenerationProfile {
+    generator: String,
+    enabled: bool,
+}
+
+impl GenerationProfile {
+    /// Returns the profile of `generator`, such as `database`, telling cargo to rerun the build script
+    /// when profiling is enabled or disabled.
+    pub fn from_env(generator: &str) -> Self {
+        println!("cargo:rerun-if-env-changed={}", PROFILE_GENERATION_ENV);
+
+        GenerationProfile {
+            generator: generator.to_string(),
+            enabled: env::var_os(PROFILE_GENERATION_ENV).is_some(),
+        }
+    }
+
+    /// Runs `phase`, reporting its wall time and the peak memory of the build script once it completed.
+    pub fn phase<T>(&self, name: &str, phase: impl FnOnce() -> T) -> T {
+        if !self.enabled {
+            return phase();
+        }
+
+        let start = Instant::now();
+        let output = phase();
+        println!(
+            "cargo:warning={}",
+            self.line(name, start.elapsed().as_millis(), peak_rss_kb())
+        );
+
+        output
+    }
+
+    /// Awaits `phase` like [`phase`](Self::phase).
+    pub async fn async_phase<T>(&self, name: &str, phase: impl Future<Output = T>) -> T {
+        let start = Instant::now();
+        let output = phase.await;
+        if self.enabled {
+            println!(
+                "cargo:warning={}",
+                self.line(name, start.elapsed().as_millis(), peak_rss_kb())
+            );
+        }
+
+        output
+    }
+
+    fn line(&self, name: &str, wall_ms: u128, peak_rss_kb: Option<u64>) -> String {
+        let mut line = format!(
+            "{}{}/{}] wall={}ms",
+            PROFILE_MARKER, self.generator, name, wall_ms
+        );
+        if let Some(peak_rss_kb) = peak_rss_kb {
+            line.push_str(&format!(" peak_rss={}kB", peak_rss_kb));
+        }
+
+        line
+    }
+}
+
+/// Returns the peak resident memory of the process in kilobytes, read from `/proc/self/status`,
+/// or `None` where it is unavailable.
+pub fn peak_rss_kb() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn phase_lines() {
+        let profile = GenerationProfile {
+            generator: "database".to_string(),
+            enabled: false,
+        };
+
+        assert_eq!(profile.phase("app.rs", || 1), 1);
+        assert_eq!(
+            profile.line("app.rs", 1250, Some(98304)),
+            "awto profile[database/app.rs] wall=1250ms peak_rss=98304kB"
+        );
+        assert_eq!(
+            profile.line("sync", 3, None),
+            "awto profile[database/sync] wall=3ms"
+        );
+        if cfg!(target_os = "linux") {
+            assert!(peak_rss_kb().unwrap() > 0);
+        }
+    }
+}
@@ -0,0 +1,193 @@
+//! Lock file recording the messages of each version of a versioned protobuf package.
+//!
+//! The messages of the latest version are recorded whenever the protobuf package is compiled,
+//! and frozen once a newer version is added, so earlier versions keep being generated as they were served.
+
+use std::{collections::BTreeMap, fs, io, path::Path};
+
+use awto::protobuf::{ProtobufField, ProtobufMessage};
+use serde::{Deserialize, Serialize};
+
+use crate::util::write_if_changed;
+
+/// Messages of each version of a protobuf package, stored as JSON.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProtoLock {
+    /// Version of the lock file format.
+    pub version: u32,
+    /// Messages of the models of each version of the package, by version and model name.
+    pub packages: BTreeMap<u32, BTreeMap<String, LockedModel>>,
+}
+
+/// Messages generated for a model in a version of the package.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedModel {
+    pub message: LockedMessage,
+    /// The `<Model>Patch` message of writable tables.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub patch: Option<LockedMessage>,
+}
+
+/// A message as it is written in protobuf files.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedMessage {
+    pub name: String,
+    pub fields: Vec<LockedField>,
+}
+
+/// A field of a [`LockedMessage`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedField {
+    pub name: String,
+    /// Protobuf type of the field, such as `int64`, `repeated string` or `google.protobuf.Timestamp`.
+    pub ty: String,
+    pub required: bool,
+    #[serde(default)]
+    pub deprecated: bool,
+}
+
+impl ProtoLock {
+    /// Version of the lock file format written by this crate.
+    pub const VERSION: u32 = 1;
+
+    /// Reads the lock file at `path`, returning an empty lock when there is none.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Ok(ProtoLock {
+                    version: Self::VERSION,
+                    packages: BTreeMap::new(),
+                })
+            }
+            Err(err) => return Err(err),
+        };
+
+        let lock: ProtoLock = serde_json::from_str(&contents)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        if lock.version > Self::VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "proto lock version {} is newer than the supported version {}, update awto-compile",
+                    lock.version,
+                    Self::VERSION
+                ),
+            ));
+        }
+
+        Ok(lock)
+    }
+
+    /// Writes the lock file to `path`, unless it is unchanged.
+    pub fn write(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut contents = serde_json::to_string_pretty(self)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        contents.push('\n');
+
+        write_if_changed(path, contents)
+    }
+}
+
+impl LockedMessage {
+    /// Returns the field named `name`, if any.
+    pub fn field(&self, name: &str) -> Option<&LockedField> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+}
+
+impl From<&ProtobufMessage> for LockedMessage {
+    fn from(message: &ProtobufMessage) -> Self {
+        LockedMessage {
+            name: message.name.clone(),
+            fields: message.fields.iter().map(LockedField::from).collect(),
+        }
+    }
+}
+
+impl From<&ProtobufField> for LockedField {
+    fn from(field: &ProtobufField) -> Self {
+        LockedField {
+            name: field.name.clone(),
+            ty: field.ty.to_string(),
+            required: field.required,
+            deprecated: field.deprecated,
+        }
+    }
+}
+
+impl LockedField {
+    /// Returns whether values of the field convert into values of `other`, which have the same name and type.
+    pub fn maps_to(&self, other: &LockedField) -> bool {
+        self.name == other.name && self.ty == other.ty && self.required == other.required
+    }
+
+    /// Returns whether the field holds messages generated for models, rather than scalars or well-known types.
+    pub fn is_message(&self) -> bool {
+        let ty = self.ty.strip_prefix("repeated ").unwrap_or(&self.ty);
+        !ty.starts_with("google.protobuf.")
+            && !matches!(
+                ty,
+                "double"
+                    | "float"
+                    | "int32"
+                    | "int64"
+                    | "uint32"
+                    | "uint64"
+                    | "sint32"
+                    | "sint64"
+                    | "fixed32"
+                    | "fixed64"
+                    | "sfixed32"
+                    | "sfixed64"
+                    | "bool"
+                    | "string"
+                    | "bytes"
+            )
+    }
+
+    /// Returns whether the field is `repeated`.
+    pub fn is_repeated(&self) -> bool {
+        self.ty.starts_with("repeated ")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::env;
+
+    use awto::{protobuf::IntoProtobufMessage, tests_cfg::*};
+
+    use super::*;
+
+    #[test]
+    fn lock_roundtrip() {
+        let dir = env::temp_dir().join(format!("awto-proto-lock-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("proto.lock");
+        assert!(ProtoLock::load(&path).unwrap().packages.is_empty());
+
+        let mut lock = ProtoLock::load(&path).unwrap();
+        let mut models = BTreeMap::new();
+        models.insert(
+            "ProductList".to_string(),
+            LockedModel {
+                message: LockedMessage::from(&ProductList::protobuf_message()),
+                patch: None,
+            },
+        );
+        lock.packages.insert(1, models);
+        lock.write(&path).unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(contents.contains(r#""ty": "repeated Product""#));
+        assert!(!contents.contains("patch"));
+        assert_eq!(ProtoLock::load(&path).unwrap(), lock);
+
+        let field = &lock.packages[&1]["ProductList"].message.fields[0];
+        assert!(field.is_message() && field.is_repeated());
+        assert!(!LockedField::from(&Product::protobuf_message().fields[1]).is_message());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
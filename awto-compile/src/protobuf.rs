@@ -1,17 +1,915 @@
-use std::{env, fmt::Write};
+use std::{collections::BTreeMap, env, fmt::Write, path::Path};
 
 use awto::{
-    protobuf::{ProtobufField, ProtobufMessage, ProtobufMethod, ProtobufService},
-    schema::{Model, Role},
+    database::DatabaseTable,
+    protobuf::{
+        ProtobufDefault, ProtobufField, ProtobufMessage, ProtobufMethod, ProtobufService,
+        ProtobufType,
+    },
+    schema::{Model, Role, Sensitivity},
 };
 use heck::SnakeCase;
 use proc_macro2::TokenStream;
 use quote::{format_ident, quote};
 
-use crate::util::{is_ty_vec, strip_ty_option};
+use crate::{
+    api_docs::{ApiDoc, ApiDocOverride, ApiDocs, ApiItem},
+    error::Error,
+    format::{format_proto, format_rust},
+    proto_lock::{LockedField, LockedMessage, LockedModel, ProtoLock},
+    provenance::{
+        annotate_sources, watch_profile, write_generated, Annotation, CommentStyle,
+        GenerationManifest,
+    },
+    rest::RestCompiler,
+    transcoding::{HttpRule, TranscodingCompiler},
+    util::{
+        into_field_value, is_ty_option, is_ty_vec, map_value_ty, newtype_inner_value, rust_ident,
+        schema_ty, strip_ty_option, write_if_changed,
+    },
+};
 
 const COMPILED_PROTO_FILE: &str = "app.proto";
 const COMPILED_RUST_FILE: &str = "app.rs";
+const TONIC_OUT_DIR: &str = "tonic";
+
+/// Name of the protobuf package when none is configured.
+pub const DEFAULT_PACKAGE: &str = "app";
+
+/// Attribute of the messages generated by prost, which mirror the models documented in the schema.
+const MESSAGE_ATTRIBUTE: &str = "#[allow(missing_docs)]";
+
+/// `google/api/http.proto` of googleapis, defining the routes of the `google.api.http` option.
+const GOOGLE_API_HTTP_PROTO: &str = r#"syntax = "proto3";
+
+package google.api;
+
+option go_package = "google.golang.org/genproto/googleapis/api/annotations;annotations";
+option java_multiple_files = true;
+option java_outer_classname = "HttpProto";
+option java_package = "com.google.api";
+
+message Http {
+  repeated HttpRule rules = 1;
+  bool fully_decode_reserved_expansion = 2;
+}
+
+message HttpRule {
+  string selector = 1;
+  oneof pattern {
+    string get = 2;
+    string put = 3;
+    string post = 4;
+    string delete = 5;
+    string patch = 6;
+    CustomHttpPattern custom = 8;
+  }
+  string body = 7;
+  string response_body = 12;
+  repeated HttpRule additional_bindings = 11;
+}
+
+message CustomHttpPattern {
+  string kind = 1;
+  string path = 2;
+}
+"#;
+
+/// `google/api/annotations.proto` of googleapis, declaring the `google.api.http` option of methods.
+const GOOGLE_API_ANNOTATIONS_PROTO: &str = r#"syntax = "proto3";
+
+package google.api;
+
+import "google/api/http.proto";
+import "google/protobuf/descriptor.proto";
+
+option go_package = "google.golang.org/genproto/googleapis/api/annotations;annotations";
+option java_multiple_files = true;
+option java_outer_classname = "AnnotationsProto";
+option java_package = "com.google.api";
+
+extend google.protobuf.MethodOptions {
+  HttpRule http = 72295728;
+}
+"#;
+
+/// Standard `grpc.health.v1.Health` service generated as `protobuf::health`.
+const HEALTH_MODULE: &str = r#"
+/// The standard gRPC health service, reporting whether the services are ready.
+pub mod health {
+    use std::{fmt::Display, future::Future, time::Duration};
+
+    pub use ::tonic_health::proto::health_server::{Health, HealthServer};
+    pub use ::tonic_health::ServingStatus;
+
+    /// Interval between the readiness checks of [`health_service`] by default.
+    pub const DEFAULT_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+    /// Creates the standard `grpc.health.v1.Health` service to add to the server.
+    ///
+    /// The server is reported as `SERVING` while `check` succeeds and `NOT_SERVING` otherwise,
+    /// running `check` every `interval` on a background task. Checks of `database::health::HealthChecks`
+    /// are run with `move || { let (checks, db) = (checks.clone(), db.clone()); async move { checks.check(&db).await } }`.
+    pub fn health_service<F, Fut, E>(interval: Duration, check: F) -> HealthServer<impl Health>
+    where
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(), E>> + Send,
+        E: Display,
+    {
+        let (mut reporter, service) = ::tonic_health::server::health_reporter();
+        ::tokio::spawn(async move {
+            let mut ticker = ::tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let status = match check().await {
+                    Ok(()) => ServingStatus::Serving,
+                    Err(_) => ServingStatus::NotServing,
+                };
+                reporter.set_service_status("", status).await;
+            }
+        });
+
+        service
+    }
+}
+"#;
+
+/// Request logging layer of the server generated as `protobuf::logging`.
+const LOGGING_MODULE: &str = r#"
+/// Request logging of the gRPC server, added with [`RequestLogging::with_request_logging`].
+pub mod logging {
+    use std::{
+        fmt,
+        future::Future,
+        pin::Pin,
+        sync::Arc,
+        task::{Context, Poll},
+        time::{Duration, Instant},
+    };
+
+    use ::tonic::codegen::http::{HeaderMap, HeaderValue, Request, Response};
+
+    /// Metadata key carrying the correlation id of a call, generated when a call has none.
+    pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+    /// Options of [`RequestLoggingLayer`].
+    ///
+    /// Messages are never logged, as they are only available protobuf-encoded to the layer.
+    #[derive(Clone, Debug)]
+    pub struct LogOptions {
+        /// Level of the logged lines, `Info` by default.
+        pub level: ::log::Level,
+    }
+
+    impl Default for LogOptions {
+        fn default() -> Self {
+            LogOptions {
+                level: ::log::Level::Info,
+            }
+        }
+    }
+
+    /// A logged call, formatted as `<rpc> <status> <duration>ms request_id=<id>`,
+    /// such as `/app.ProductService/FindProduct Ok 3ms request_id=...`.
+    pub struct RequestLog {
+        /// Correlation id of the call.
+        pub request_id: String,
+        /// Path of the called method, such as `/app.ProductService/FindProduct`.
+        pub rpc: String,
+        /// Status of the response.
+        pub status: ::tonic::Code,
+        /// Time between receiving the call and sending the response headers.
+        pub duration: Duration,
+    }
+
+    impl fmt::Display for RequestLog {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(
+                f,
+                "{} {:?} {}ms request_id={}",
+                self.rpc,
+                self.status,
+                self.duration.as_millis(),
+                self.request_id
+            )
+        }
+    }
+
+    /// Returns the correlation id of a call, generating one when the call has none.
+    fn request_id(headers: &HeaderMap) -> HeaderValue {
+        headers.get(REQUEST_ID_HEADER).cloned().unwrap_or_else(|| {
+            HeaderValue::from_str(&::uuid::Uuid::new_v4().to_string()).expect("uuids are valid header values")
+        })
+    }
+
+    /// Layer logging every call of a server, with its duration, status and correlation id.
+    ///
+    /// The correlation id is read from the `x-request-id` metadata, or generated, and set on both the request
+    /// passed to the services and the response. The status is read from the response headers, where failed
+    /// unary calls report it, and is `Ok` otherwise.
+    #[derive(Clone)]
+    pub struct RequestLoggingLayer {
+        options: Arc<LogOptions>,
+    }
+
+    impl RequestLoggingLayer {
+        /// Creates a layer logging calls with `options`.
+        pub fn new(options: LogOptions) -> Self {
+            RequestLoggingLayer {
+                options: Arc::new(options),
+            }
+        }
+    }
+
+    impl<S> ::tower::Layer<S> for RequestLoggingLayer {
+        type Service = RequestLoggingService<S>;
+
+        fn layer(&self, inner: S) -> Self::Service {
+            RequestLoggingService {
+                inner,
+                options: self.options.clone(),
+            }
+        }
+    }
+
+    /// Service of [`RequestLoggingLayer`].
+    #[derive(Clone)]
+    pub struct RequestLoggingService<S> {
+        inner: S,
+        options: Arc<LogOptions>,
+    }
+
+    impl<S, ReqBody, ResBody> ::tower::Service<Request<ReqBody>> for RequestLoggingService<S>
+    where
+        S: ::tower::Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+        S::Future: Send + 'static,
+        ReqBody: Send + 'static,
+    {
+        type Response = Response<ResBody>;
+        type Error = S::Error;
+        type Future = Pin<Box<dyn Future<Output = Result<Response<ResBody>, S::Error>> + Send>>;
+
+        fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+            self.inner.poll_ready(cx)
+        }
+
+        fn call(&mut self, mut request: Request<ReqBody>) -> Self::Future {
+            // The ready service handles the request, leaving a clone for the next one
+            let clone = self.inner.clone();
+            let mut inner = std::mem::replace(&mut self.inner, clone);
+            let options = self.options.clone();
+
+            Box::pin(async move {
+                let start = Instant::now();
+                let request_id = request_id(request.headers());
+                request.headers_mut().insert(REQUEST_ID_HEADER, request_id.clone());
+                let rpc = request.uri().path().to_string();
+
+                let mut response = inner.call(request).await?;
+                let status = response
+                    .headers()
+                    .get("grpc-status")
+                    .map(|status| ::tonic::Code::from_bytes(status.as_bytes()))
+                    .unwrap_or(::tonic::Code::Ok);
+                let log = RequestLog {
+                    request_id: request_id.to_str().unwrap_or("-").to_string(),
+                    rpc,
+                    status,
+                    duration: start.elapsed(),
+                };
+                response.headers_mut().insert(REQUEST_ID_HEADER, request_id);
+                ::log::log!(options.level, "{}", log);
+
+                Ok(response)
+            })
+        }
+    }
+
+    /// Adds request logging to a server.
+    pub trait RequestLogging {
+        /// Logs every call with `options`, see [`RequestLoggingLayer`].
+        fn with_request_logging(self, options: LogOptions) -> ::tonic::transport::Server<RequestLoggingLayer>;
+    }
+
+    impl RequestLogging for ::tonic::transport::Server {
+        fn with_request_logging(self, options: LogOptions) -> ::tonic::transport::Server<RequestLoggingLayer> {
+            self.layer(RequestLoggingLayer::new(options))
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use std::{
+            convert::Infallible,
+            sync::{Mutex, Once},
+        };
+
+        use ::tower::{Layer, ServiceExt};
+
+        use super::*;
+
+        static LINES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+        struct CapturingLogger;
+
+        impl ::log::Log for CapturingLogger {
+            fn enabled(&self, _: &::log::Metadata) -> bool {
+                true
+            }
+
+            fn log(&self, record: &::log::Record) {
+                LINES.lock().unwrap().push(record.args().to_string());
+            }
+
+            fn flush(&self) {}
+        }
+
+        #[tokio::test]
+        async fn logs_status_and_request_id() {
+            static INIT: Once = Once::new();
+            INIT.call_once(|| {
+                ::log::set_logger(&CapturingLogger).unwrap();
+                ::log::set_max_level(::log::LevelFilter::Trace);
+            });
+
+            let service = RequestLoggingLayer::new(LogOptions::default()).layer(::tower::service_fn(
+                |request: Request<()>| async move {
+                    assert_eq!(request.headers()[REQUEST_ID_HEADER], "correlated");
+                    let mut response = Response::new(());
+                    response.headers_mut().insert("grpc-status", HeaderValue::from_static("5"));
+                    Ok::<_, Infallible>(response)
+                },
+            ));
+            let request = Request::builder()
+                .uri("/app.ProductService/FindProduct")
+                .header(REQUEST_ID_HEADER, "correlated")
+                .body(())
+                .unwrap();
+            let response = service.oneshot(request).await.unwrap();
+            assert_eq!(response.headers()[REQUEST_ID_HEADER], "correlated");
+
+            let lines = LINES.lock().unwrap();
+            assert!(lines
+                .iter()
+                .any(|line| line.starts_with("/app.ProductService/FindProduct NotFound ")
+                    && line.ends_with("request_id=correlated")));
+        }
+    }
+}
+"#;
+
+/// Request metrics layer of the server generated as `protobuf::metrics`, behind the `metrics` feature.
+const METRICS_MODULE: &str = r#"
+/// Request metrics of the gRPC server, recorded with the macros of the `metrics` crate once added with
+/// [`RequestMetrics::with_request_metrics`].
+///
+/// Calls are labelled with their rpc, such as `/app.ProductService/FindProduct`, and their status. Calls of rpcs the
+/// server does not implement are labelled [`UNKNOWN_RPC`], so the cardinality of the metrics is bounded by the services.
+/// Exporters should call [`describe`] once installed and use [`DURATION_BUCKETS`] for [`REQUEST_DURATION`].
+#[cfg(feature = "metrics")]
+pub mod metrics {
+    use std::{
+        future::Future,
+        pin::Pin,
+        task::{Context, Poll},
+        time::Instant,
+    };
+
+    use ::tonic::codegen::http::{Request, Response};
+
+    /// Counter of the calls, labelled with `rpc` and `status`.
+    pub const REQUESTS: &str = "awto_grpc_requests_total";
+    /// Histogram of the duration of the calls in seconds, labelled with `rpc` and `status`.
+    pub const REQUEST_DURATION: &str = "awto_grpc_request_duration_seconds";
+    /// Buckets of [`REQUEST_DURATION`] in seconds, from 1ms to 10s.
+    pub const DURATION_BUCKETS: &[f64] = &[0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+    /// Rpc label of calls of rpcs the server does not implement.
+    pub const UNKNOWN_RPC: &str = "unknown";
+
+    /// Describes the metrics of the calls to the installed recorder.
+    pub fn describe() {
+        ::metrics::register_counter!(REQUESTS, "Calls of the gRPC server, by rpc and status.");
+        ::metrics::register_histogram!(
+            REQUEST_DURATION,
+            ::metrics::Unit::Seconds,
+            "Duration of the calls of the gRPC server, by rpc and status."
+        );
+    }
+
+    /// Layer recording the count and duration of every call of a server.
+    ///
+    /// The status is read from the response headers like the status of `logging::RequestLoggingLayer`.
+    #[derive(Clone, Copy, Debug, Default)]
+    pub struct RequestMetricsLayer;
+
+    impl<S> ::tower::Layer<S> for RequestMetricsLayer {
+        type Service = RequestMetricsService<S>;
+
+        fn layer(&self, inner: S) -> Self::Service {
+            RequestMetricsService { inner }
+        }
+    }
+
+    /// Service of [`RequestMetricsLayer`].
+    #[derive(Clone)]
+    pub struct RequestMetricsService<S> {
+        inner: S,
+    }
+
+    impl<S, ReqBody, ResBody> ::tower::Service<Request<ReqBody>> for RequestMetricsService<S>
+    where
+        S: ::tower::Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+        S::Future: Send + 'static,
+        ReqBody: Send + 'static,
+    {
+        type Response = Response<ResBody>;
+        type Error = S::Error;
+        type Future = Pin<Box<dyn Future<Output = Result<Response<ResBody>, S::Error>> + Send>>;
+
+        fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+            self.inner.poll_ready(cx)
+        }
+
+        fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+            // The ready service handles the request, leaving a clone for the next one
+            let clone = self.inner.clone();
+            let mut inner = std::mem::replace(&mut self.inner, clone);
+
+            Box::pin(async move {
+                let start = Instant::now();
+                let rpc = request.uri().path().to_string();
+
+                let response = inner.call(request).await?;
+                let status = response
+                    .headers()
+                    .get("grpc-status")
+                    .map(|status| ::tonic::Code::from_bytes(status.as_bytes()))
+                    .unwrap_or(::tonic::Code::Ok);
+                let rpc = if status == ::tonic::Code::Unimplemented {
+                    UNKNOWN_RPC.to_string()
+                } else {
+                    rpc
+                };
+                let status = format!("{:?}", status);
+                let seconds = start.elapsed().as_secs_f64();
+                ::metrics::increment_counter!(REQUESTS, "rpc" => rpc.clone(), "status" => status.clone());
+                ::metrics::histogram!(REQUEST_DURATION, seconds, "rpc" => rpc, "status" => status);
+
+                Ok(response)
+            })
+        }
+    }
+
+    /// Adds request metrics to a server.
+    pub trait RequestMetrics {
+        /// Records the count and duration of every call, see [`RequestMetricsLayer`].
+        fn with_request_metrics(self) -> ::tonic::transport::Server<RequestMetricsLayer>;
+    }
+
+    impl RequestMetrics for ::tonic::transport::Server {
+        fn with_request_metrics(self) -> ::tonic::transport::Server<RequestMetricsLayer> {
+            self.layer(RequestMetricsLayer)
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use std::{convert::Infallible, sync::Mutex};
+
+        use ::metrics::{GaugeValue, Key, Recorder, Unit};
+        use ::tonic::codegen::http::HeaderValue;
+        use ::tower::{Layer, ServiceExt};
+
+        use super::*;
+
+        static RECORDED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+        /// Recorder keeping the name and labels of every recorded metric.
+        struct StubRecorder;
+
+        impl StubRecorder {
+            fn push(&self, key: &Key) {
+                let labels: Vec<_> = key
+                    .labels()
+                    .map(|label| format!("{}={}", label.key(), label.value()))
+                    .collect();
+                RECORDED.lock().unwrap().push(format!("{} {}", key.name(), labels.join(",")));
+            }
+        }
+
+        impl Recorder for StubRecorder {
+            fn register_counter(&self, _: &Key, _: Option<Unit>, _: Option<&'static str>) {}
+
+            fn register_gauge(&self, _: &Key, _: Option<Unit>, _: Option<&'static str>) {}
+
+            fn register_histogram(&self, _: &Key, _: Option<Unit>, _: Option<&'static str>) {}
+
+            fn increment_counter(&self, key: &Key, _: u64) {
+                self.push(key);
+            }
+
+            fn update_gauge(&self, _: &Key, _: GaugeValue) {}
+
+            fn record_histogram(&self, key: &Key, _: f64) {
+                self.push(key);
+            }
+        }
+
+        static RECORDER: StubRecorder = StubRecorder;
+
+        #[tokio::test]
+        async fn records_calls() {
+            let _ = ::metrics::set_recorder(&RECORDER);
+            let service = RequestMetricsLayer.layer(::tower::service_fn(|request: Request<()>| async move {
+                let mut response = Response::new(());
+                if request.uri().path() != "/app.ProductService/FindProduct" {
+                    response.headers_mut().insert("grpc-status", HeaderValue::from_static("12"));
+                }
+                Ok::<_, Infallible>(response)
+            }));
+            for path in ["/app.ProductService/FindProduct", "/app.ProductService/Unknown"] {
+                let request = Request::builder().uri(path).body(()).unwrap();
+                service.clone().oneshot(request).await.unwrap();
+            }
+
+            let recorded = RECORDED.lock().unwrap();
+            assert_eq!(
+                *recorded,
+                [
+                    "awto_grpc_requests_total rpc=/app.ProductService/FindProduct,status=Ok",
+                    "awto_grpc_request_duration_seconds rpc=/app.ProductService/FindProduct,status=Ok",
+                    "awto_grpc_requests_total rpc=unknown,status=Unimplemented",
+                    "awto_grpc_request_duration_seconds rpc=unknown,status=Unimplemented",
+                ]
+            );
+        }
+    }
+}
+"#;
+
+/// Authentication layer of the server generated as `protobuf::auth`.
+const AUTH_MODULE: &str = r#"
+/// Authentication of the calls of the gRPC server, added with [`Authenticate::with_auth`].
+///
+/// A server authenticating calls with a bearer token:
+///
+/// ```ignore
+/// use protobuf::auth::{AuthInterceptor, Authenticate, AuthOptions, Principal};
+/// use tonic::{metadata::MetadataMap, Status};
+///
+/// struct BearerToken(String);
+///
+/// impl AuthInterceptor for BearerToken {
+///     fn authenticate(&self, metadata: &MetadataMap) -> Result<Principal, Status> {
+///         let token = metadata
+///             .get("authorization")
+///             .and_then(|value| value.to_str().ok())
+///             .and_then(|value| value.strip_prefix("Bearer "));
+///         match token {
+///             Some(token) if token == self.0 => Ok(Principal::new("admin").with_scope("catalog:write")),
+///             _ => Err(Status::unauthenticated("invalid bearer token")),
+///         }
+///     }
+/// }
+///
+/// Server::builder()
+///     .with_auth(BearerToken(token), AuthOptions::default())
+///     .add_service(product_service)
+/// ```
+pub mod auth {
+    use std::{
+        future::Future,
+        pin::Pin,
+        sync::Arc,
+        task::{Context, Poll},
+    };
+
+    use ::tonic::{
+        body::BoxBody,
+        codegen::http::{Request, Response},
+        metadata::MetadataMap,
+        Status,
+    };
+
+    /// Path prefix of the rpcs of the standard `grpc.health.v1.Health` service.
+    pub const HEALTH_RPC_PREFIX: &str = "/grpc.health.v1.Health/";
+
+    ::tokio::task_local! {
+        static PRINCIPAL: Principal;
+    }
+
+    /// Caller of an rpc, authenticated by an [`AuthInterceptor`].
+    #[derive(Clone, Debug, Default, PartialEq, Eq)]
+    pub struct Principal {
+        /// Identifier of the caller, such as the subject of its token.
+        pub id: String,
+        /// Scopes granted to the caller, such as `catalog:write`.
+        pub scopes: Vec<String>,
+    }
+
+    impl Principal {
+        /// Creates a principal identified by `id`, without scopes.
+        pub fn new(id: impl Into<String>) -> Self {
+            Principal {
+                id: id.into(),
+                scopes: Vec::new(),
+            }
+        }
+
+        /// Grants `scope` to the principal.
+        pub fn with_scope(mut self, scope: impl Into<String>) -> Self {
+            self.scopes.push(scope.into());
+            self
+        }
+
+        /// Returns whether the principal is granted `scope`.
+        pub fn has_scope(&self, scope: &str) -> bool {
+            self.scopes.iter().any(|granted| granted == scope)
+        }
+
+        /// Returns the principal of the rpc being served, read by the methods of services which are not passed
+        /// their request, or `None` when the rpc was not authenticated.
+        pub fn current() -> Option<Principal> {
+            PRINCIPAL.try_with(Principal::clone).ok()
+        }
+
+        /// Returns the principal of `request`, or `None` when it was not authenticated.
+        pub fn of<T>(request: &::tonic::Request<T>) -> Option<&Principal> {
+            request.extensions().get::<Principal>()
+        }
+    }
+
+    /// Authenticates the calls of a server from their metadata, added with [`Authenticate::with_auth`].
+    pub trait AuthInterceptor: Send + Sync + 'static {
+        /// Returns the caller of an rpc with `metadata`, or the status rejecting the call,
+        /// such as `Status::unauthenticated("invalid bearer token")`.
+        fn authenticate(&self, metadata: &MetadataMap) -> Result<Principal, Status>;
+    }
+
+    /// Interceptor authenticating every call as the default [`Principal`], without scopes.
+    #[derive(Clone, Copy, Debug, Default)]
+    pub struct NoAuth;
+
+    impl AuthInterceptor for NoAuth {
+        fn authenticate(&self, _metadata: &MetadataMap) -> Result<Principal, Status> {
+            Ok(Principal::default())
+        }
+    }
+
+    /// Options of [`AuthLayer`].
+    #[derive(Clone, Debug)]
+    pub struct AuthOptions {
+        /// Whether the rpcs of the standard health service are served without authentication, `true` by default.
+        pub exempt_health: bool,
+    }
+
+    impl Default for AuthOptions {
+        fn default() -> Self {
+            AuthOptions { exempt_health: true }
+        }
+    }
+
+    /// Layer authenticating every call of a server with an [`AuthInterceptor`].
+    ///
+    /// The principal of an authenticated call is inserted in the extensions of its request, and returned by
+    /// [`Principal::current`] while the call is served. Calls the interceptor rejects are answered with its status.
+    #[derive(Clone)]
+    pub struct AuthLayer {
+        interceptor: Arc<dyn AuthInterceptor>,
+        options: Arc<AuthOptions>,
+    }
+
+    impl AuthLayer {
+        /// Creates a layer authenticating calls with `interceptor`.
+        pub fn new(interceptor: impl AuthInterceptor, options: AuthOptions) -> Self {
+            AuthLayer {
+                interceptor: Arc::new(interceptor),
+                options: Arc::new(options),
+            }
+        }
+    }
+
+    impl<S> ::tower::Layer<S> for AuthLayer {
+        type Service = AuthService<S>;
+
+        fn layer(&self, inner: S) -> Self::Service {
+            AuthService {
+                inner,
+                interceptor: self.interceptor.clone(),
+                options: self.options.clone(),
+            }
+        }
+    }
+
+    /// Service of [`AuthLayer`].
+    #[derive(Clone)]
+    pub struct AuthService<S> {
+        inner: S,
+        interceptor: Arc<dyn AuthInterceptor>,
+        options: Arc<AuthOptions>,
+    }
+
+    impl<S, ReqBody> ::tower::Service<Request<ReqBody>> for AuthService<S>
+    where
+        S: ::tower::Service<Request<ReqBody>, Response = Response<BoxBody>> + Clone + Send + 'static,
+        S::Future: Send + 'static,
+        ReqBody: Send + 'static,
+    {
+        type Response = Response<BoxBody>;
+        type Error = S::Error;
+        type Future = Pin<Box<dyn Future<Output = Result<Response<BoxBody>, S::Error>> + Send>>;
+
+        fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+            self.inner.poll_ready(cx)
+        }
+
+        fn call(&mut self, mut request: Request<ReqBody>) -> Self::Future {
+            // The ready service handles the request, leaving a clone for the next one
+            let clone = self.inner.clone();
+            let mut inner = std::mem::replace(&mut self.inner, clone);
+
+            if self.options.exempt_health && request.uri().path().starts_with(HEALTH_RPC_PREFIX) {
+                return Box::pin(inner.call(request));
+            }
+            let principal = match self.interceptor.authenticate(&MetadataMap::from_headers(request.headers().clone())) {
+                Ok(principal) => principal,
+                Err(status) => return Box::pin(async move { Ok(status.to_http()) }),
+            };
+            request.extensions_mut().insert(principal.clone());
+
+            Box::pin(PRINCIPAL.scope(principal, inner.call(request)))
+        }
+    }
+
+    /// Adds authentication to a server.
+    pub trait Authenticate {
+        /// Authenticates every call with `interceptor`, see [`AuthLayer`].
+        fn with_auth(self, interceptor: impl AuthInterceptor, options: AuthOptions) -> ::tonic::transport::Server<AuthLayer>;
+    }
+
+    impl Authenticate for ::tonic::transport::Server {
+        fn with_auth(self, interceptor: impl AuthInterceptor, options: AuthOptions) -> ::tonic::transport::Server<AuthLayer> {
+            self.layer(AuthLayer::new(interceptor, options))
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use ::tower::{Layer, ServiceExt};
+
+        use super::*;
+
+        /// The bearer token interceptor of the module documentation.
+        struct BearerToken(String);
+
+        impl AuthInterceptor for BearerToken {
+            fn authenticate(&self, metadata: &MetadataMap) -> Result<Principal, Status> {
+                let token = metadata
+                    .get("authorization")
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.strip_prefix("Bearer "));
+                match token {
+                    Some(token) if token == self.0 => Ok(Principal::new("admin").with_scope("catalog:write")),
+                    _ => Err(Status::unauthenticated("invalid bearer token")),
+                }
+            }
+        }
+
+        /// Calls `rpc` with `token` through `layer`, returning the `grpc-status` of the response
+        /// and the principal seen by the service.
+        async fn call(layer: &AuthLayer, rpc: &str, token: Option<&str>) -> (Option<String>, Option<Principal>) {
+            let service = layer.layer(::tower::service_fn(|request: Request<()>| async move {
+                let principal = Principal::current();
+                assert_eq!(request.extensions().get::<Principal>(), principal.as_ref());
+                let mut response = Response::new(::tonic::body::empty_body());
+                if let Some(principal) = principal {
+                    response.extensions_mut().insert(principal);
+                }
+                Ok::<_, ::std::convert::Infallible>(response)
+            }));
+            let mut request = Request::builder().uri(rpc);
+            if let Some(token) = token {
+                request = request.header("authorization", format!("Bearer {}", token));
+            }
+            let response = service.oneshot(request.body(()).unwrap()).await.unwrap();
+            let status = response
+                .headers()
+                .get("grpc-status")
+                .map(|status| status.to_str().unwrap().to_string());
+
+            (status, response.extensions().get::<Principal>().cloned())
+        }
+
+        #[tokio::test]
+        async fn bearer_token() {
+            let layer = AuthLayer::new(BearerToken("secret".to_string()), AuthOptions::default());
+
+            let (status, principal) = call(&layer, "/app.ProductService/FindProduct", Some("secret")).await;
+            assert_eq!(status, None);
+            let principal = principal.unwrap();
+            assert_eq!(principal.id, "admin");
+            assert!(principal.has_scope("catalog:write"));
+
+            // 16 is UNAUTHENTICATED
+            let (status, principal) = call(&layer, "/app.ProductService/FindProduct", Some("guess")).await;
+            assert_eq!((status.as_deref(), principal), (Some("16"), None));
+            let (status, _) = call(&layer, "/app.ProductService/FindProduct", None).await;
+            assert_eq!(status.as_deref(), Some("16"));
+        }
+
+        #[tokio::test]
+        async fn health_exemption() {
+            let rpc = "/grpc.health.v1.Health/Check";
+            let layer = AuthLayer::new(BearerToken("secret".to_string()), AuthOptions::default());
+            assert_eq!(call(&layer, rpc, None).await, (None, None));
+
+            let layer = AuthLayer::new(BearerToken("secret".to_string()), AuthOptions { exempt_health: false });
+            assert_eq!(call(&layer, rpc, None).await.0.as_deref(), Some("16"));
+        }
+
+        #[tokio::test]
+        async fn no_auth() {
+            let layer = AuthLayer::new(NoAuth, AuthOptions::default());
+            assert_eq!(
+                call(&layer, "/app.ProductService/FindProduct", None).await,
+                (None, Some(Principal::default()))
+            );
+            assert_eq!(Principal::current(), None);
+        }
+    }
+}
+"#;
+
+/// Idempotency keys of rpcs, generated as `idempotency` when a table has `#[awto(idempotent_create)]`.
+const IDEMPOTENCY_MODULE: &str = r#"
+/// Idempotency keys of the rpcs creating rows of tables with `#[awto(idempotent_create)]`.
+///
+/// Methods of services read the key of the rpc being served with [`IdempotencyKey::current`], and pass it to the
+/// `insert_idempotent` of the entity of the table, answering its `IdempotencyError::Conflict` with [`conflict`].
+pub mod idempotency {
+    use ::tonic::{metadata::MetadataMap, Status};
+
+    /// Metadata key of the idempotency key clients send to retry a create without inserting another row.
+    pub const IDEMPOTENCY_KEY_METADATA: &str = "idempotency-key";
+
+    /// Maximum length of an idempotency key.
+    pub const MAX_KEY_LEN: usize = 255;
+
+    ::tokio::task_local! {
+        static IDEMPOTENCY_KEY: Option<IdempotencyKey>;
+    }
+
+    /// Idempotency key of an rpc, along with its encoded request message.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct IdempotencyKey {
+        /// Value of the `idempotency-key` metadata.
+        pub key: String,
+        /// Request message of the rpc, hashed with `database::idempotency_keys::request_hash`,
+        /// which retries must repeat.
+        ///
+        /// Map fields may be encoded in another order by another server, so retries of their messages can conflict.
+        pub message: Vec<u8>,
+    }
+
+    impl IdempotencyKey {
+        /// Reads the idempotency key of an rpc with `metadata` and request `message`, which is `None` without one.
+        pub fn read(metadata: &MetadataMap, message: &impl ::prost::Message) -> Result<Option<Self>, Status> {
+            let key = match metadata.get(IDEMPOTENCY_KEY_METADATA) {
+                Some(value) => match value.to_str() {
+                    Ok(key) if !key.is_empty() && key.len() <= MAX_KEY_LEN => key.to_string(),
+                    _ => {
+                        return Err(Status::invalid_argument(format!(
+                            "`{}` must have 1 to {} visible ASCII characters",
+                            IDEMPOTENCY_KEY_METADATA, MAX_KEY_LEN
+                        )))
+                    }
+                },
+                None => return Ok(None),
+            };
+
+            Ok(Some(IdempotencyKey {
+                key,
+                message: message.encode_to_vec(),
+            }))
+        }
+
+        /// Returns the idempotency key of the rpc being served, or `None` when it has none.
+        pub fn current() -> Option<IdempotencyKey> {
+            IDEMPOTENCY_KEY.try_with(Clone::clone).ok().flatten()
+        }
+
+        /// Runs `f` with `key` as the key of the rpc being served.
+        pub async fn scope<F: ::std::future::Future>(key: Option<IdempotencyKey>, f: F) -> F::Output {
+            IDEMPOTENCY_KEY.scope(key, f).await
+        }
+    }
+
+    /// Returns the status of an rpc whose idempotency key was used by a request with another message.
+    pub fn conflict() -> Status {
+        Status::invalid_argument("idempotency key was already used by a different request")
+    }
+}
+"#;
 
 #[cfg(feature = "async")]
 pub fn compile_protobuf(
@@ -32,50 +930,289 @@ pub fn compile_protobuf(
 
     let proto = compiler.compile_file();
     let proto_path = format!("{}/{}", out_dir, COMPILED_PROTO_FILE);
-    fs::write(&proto_path, proto + "\n").await?;
+    fs::write(&proto_path, format_proto(&proto)).await?;
 
-    tonic_build::configure().compile(&[&proto_path], &[&out_dir])?;
+    tonic_build::configure()
+        .type_attribute(".", MESSAGE_ATTRIBUTE)
+        .compile(&[&proto_path], &[&out_dir])?;
 
+    let rs_path = format!("{}/{}", out_dir, COMPILED_RUST_FILE);
     let generated_code = compiler.compile_generated_code();
     if !generated_code.is_empty() {
-        let rs_path = format!("{}/{}", out_dir, COMPILED_RUST_FILE);
         let mut schema_file = fs::OpenOptions::new().append(true).open(&rs_path).await?;
 
         schema_file.write(generated_code.as_bytes()).await?;
         schema_file.sync_all().await?;
     }
 
+    let code = fs::read_to_string(&rs_path).await?;
+    fs::write(&rs_path, format_rust(&code)).await?;
+
     Ok(())
 }
 
+/// Compiles the protobuf file and generated code of `models` and `services` into `OUT_DIR`,
+/// as a single unversioned [`DEFAULT_PACKAGE`].
 #[cfg(not(feature = "async"))]
 pub fn compile_protobuf(
     models: Vec<Model>,
     services: Vec<ProtobufService>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    compile_protobuf_package(models, services, ProtobufPackage::new(DEFAULT_PACKAGE))
+}
+
+/// Name and versions of the generated protobuf package, set with the `[proto]` section of `awto.toml`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProtobufPackage {
+    /// Name of the package, such as `acme.api`.
+    pub name: String,
+    /// Versions served side by side, such as `[1, 2]`, generating a package per version such as `acme.api.v1`.
+    /// Without versions a single unversioned package is generated.
+    pub versions: Vec<u32>,
+    /// Path of the [`ProtoLock`] recording the messages of each version.
+    pub lock_path: String,
+    /// Options of the protobuf files for the code generators of other languages, such as `java_package`,
+    /// with `{version}` standing for the version of each package, such as `v1`.
+    pub file_options: Vec<(String, String)>,
+    /// Whether methods are annotated with the `google.api.http` route they are transcoded to.
+    pub http_annotations: bool,
+    /// Directory the protobuf files are exported to, in the directory of their package such as `acme/api/v1/api.proto`.
+    pub export_dir: Option<String>,
+    /// Entries of the overlay file of the `[api_docs]` section, merged over the doc comments of the schema.
+    pub api_docs: &'static [ApiDocOverride<'static>],
+}
+
+impl ProtobufPackage {
+    /// Creates an unversioned package named `name`.
+    pub fn new(name: &str) -> Self {
+        ProtobufPackage {
+            name: name.to_string(),
+            versions: Vec::new(),
+            lock_path: String::new(),
+            file_options: Vec::new(),
+            http_annotations: false,
+            export_dir: None,
+            api_docs: &[],
+        }
+    }
+
+    /// Serves `versions` of the package side by side, recording their messages in the lock file at `lock_path`.
+    pub fn with_versions(mut self, versions: &[u32], lock_path: &str) -> Self {
+        self.versions = versions.to_vec();
+        self.versions.sort_unstable();
+        self.versions.dedup();
+        self.lock_path = lock_path.to_string();
+        self
+    }
+
+    /// Sets `options` of the protobuf files, such as `("go_package", "github.com/acme/api/{version}")`.
+    pub fn with_file_options(mut self, options: &[(&str, &str)]) -> Self {
+        self.file_options = options
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        self
+    }
+
+    /// Annotates methods with the `google.api.http` route they are transcoded to, see [`ProtobufCompiler::with_http_annotations`].
+    pub fn with_http_annotations(mut self, http_annotations: bool) -> Self {
+        self.http_annotations = http_annotations;
+        self
+    }
+
+    /// Exports the protobuf files into `export_dir`, along with the `google/api` files they import.
+    pub fn with_export_dir(mut self, export_dir: Option<&str>) -> Self {
+        self.export_dir = export_dir.map(str::to_string);
+        self
+    }
+
+    /// Documents the messages, fields and methods with the `api_docs` entries of the overlay file, see [`ApiDocs`].
+    pub fn with_api_docs(mut self, api_docs: &'static [ApiDocOverride<'static>]) -> Self {
+        self.api_docs = api_docs;
+        self
+    }
+}
+
+/// Compiles the protobuf files and generated code of `models` and `services` into `OUT_DIR`.
+///
+/// Versioned packages are compiled into a module per version, such as `v1`, with the latest version reexported.
+/// Generated files are only written when their content changed, so cargo does not rebuild an unchanged package.
+#[cfg(not(feature = "async"))]
+pub fn compile_protobuf_package(
+    models: Vec<Model>,
+    services: Vec<ProtobufService>,
+    package: ProtobufPackage,
 ) -> Result<(), Box<dyn std::error::Error>> {
     use std::fs;
-    use std::io::Write;
 
     let out_dir = env::var("OUT_DIR").unwrap();
 
-    let compiler = ProtobufCompiler::new(models, services);
+    let annotate = annotate_sources();
+    watch_profile();
+    let api_docs = ApiDocs::new(&models, Some(&services), package.api_docs)?;
+    let compiler = ProtobufCompiler::new(models, services)
+        .with_package(&package.name)
+        .with_annotations(annotate)
+        .with_file_options(package.file_options.clone())
+        .with_http_annotations(package.http_annotations)
+        .with_api_docs(api_docs);
+    let mut manifest = GenerationManifest::new(annotate);
+    let names = compiler.model_names();
+
+    // tonic always rewrites its output, so it is compiled next to the final file
+    let tonic_dir = format!("{}/{}", out_dir, TONIC_OUT_DIR);
+    fs::create_dir_all(&tonic_dir)?;
+    if package.http_annotations {
+        write_google_api_protos(&out_dir)?;
+    }
+    // Package name and contents of every protobuf file, for the export directory
+    let mut exports = Vec::new();
+
+    let code = if package.versions.is_empty() {
+        let proto = format_proto(&compiler.compile_file());
+        let proto_path = format!("{}/{}", out_dir, COMPILED_PROTO_FILE);
+        write_generated(
+            &out_dir,
+            COMPILED_PROTO_FILE,
+            &proto,
+            &mut manifest,
+            "protobuf/proto",
+            &names,
+        )?;
+        exports.push((package.name.clone(), proto));
+
+        tonic_build::configure()
+            .type_attribute(".", MESSAGE_ATTRIBUTE)
+            .out_dir(&tonic_dir)
+            .compile(&[&proto_path], &[&out_dir])?;
+
+        let mut code = fs::read_to_string(format!("{}/{}.rs", tonic_dir, package.name))?;
+        code.push_str(&compiler.compile_generated_code());
+        code
+    } else {
+        println!("cargo:rerun-if-changed={}", package.lock_path);
+        let mut lock = ProtoLock::load(&package.lock_path)?;
+        let packages = compiler.versioned_packages(&package.versions, &lock)?;
+
+        let mut proto_paths = Vec::new();
+        for versioned in &packages {
+            let name = format!("{}.v{}", package.name, versioned.version);
+            let file = format!("{}.proto", name);
+            let proto =
+                format_proto(&compiler.compile_versioned_file(versioned, packages.last().unwrap()));
+            write_generated(
+                &out_dir,
+                &file,
+                &proto,
+                &mut manifest,
+                "protobuf/proto",
+                &names,
+            )?
+            .option("version", versioned.version);
+            proto_paths.push(format!("{}/{}", out_dir, file));
+            exports.push((name, proto));
+        }
 
-    let proto = compiler.compile_file();
-    let proto_path = format!("{}/{}", out_dir, COMPILED_PROTO_FILE);
-    fs::write(&proto_path, proto + "\n")?;
+        tonic_build::configure()
+            .type_attribute(".", MESSAGE_ATTRIBUTE)
+            .out_dir(&tonic_dir)
+            .compile(&proto_paths, std::slice::from_ref(&out_dir))?;
 
-    tonic_build::configure().compile(&[&proto_path], &[&out_dir])?;
+        let mut tonic_code = BTreeMap::new();
+        for versioned in &packages {
+            let path = format!("{}/{}.v{}.rs", tonic_dir, package.name, versioned.version);
+            tonic_code.insert(versioned.version, fs::read_to_string(path)?);
+        }
+        let code = compiler.compile_versioned_code(&packages, &tonic_code);
+
+        let latest = packages.last().unwrap();
+        lock.version = ProtoLock::VERSION;
+        lock.packages
+            .insert(latest.version, latest.models.iter().cloned().collect());
+        lock.write(&package.lock_path)?;
+
+        code
+    };
+
+    write_generated(
+        &out_dir,
+        COMPILED_RUST_FILE,
+        &format_rust(&code),
+        &mut manifest,
+        "protobuf",
+        &names,
+    )?;
+    manifest.write(&out_dir)?;
+
+    if let Some(export_dir) = &package.export_dir {
+        for (name, proto) in &exports {
+            let path = Path::new(export_dir).join(export_path(name));
+            fs::create_dir_all(path.parent().unwrap())?;
+            write_if_changed(path, proto)?;
+        }
+        if package.http_annotations {
+            write_google_api_protos(export_dir)?;
+        }
+    }
 
-    let generated_code = compiler.compile_generated_code();
-    if !generated_code.is_empty() {
-        let rs_path = format!("{}/{}", out_dir, COMPILED_RUST_FILE);
-        let mut schema_file = fs::OpenOptions::new().append(true).open(&rs_path)?;
+    Ok(())
+}
+
+/// Returns the path of the exported protobuf file of the package named `package`, such as `acme/api/v1/api.proto`.
+///
+/// Files are in the directory of their package and named after its last part before the version,
+/// as expected by the `PACKAGE_DIRECTORY_MATCH` and `FILE_LOWER_SNAKE_CASE` rules of buf.
+pub fn export_path(package: &str) -> String {
+    let parts: Vec<&str> = package.split('.').collect();
+    let is_version = |part: &&str| {
+        part.strip_prefix('v')
+            .is_some_and(|version| version.parse::<u32>().is_ok())
+    };
+    let file = parts
+        .iter()
+        .rev()
+        .find(|part| !is_version(part))
+        .unwrap_or(&package)
+        .to_snake_case();
+
+    format!("{}/{}.proto", parts.join("/"), file)
+}
+
+/// Writes the `google/api` protobuf files imported by the `google.api.http` options into `dir`.
+#[cfg(not(feature = "async"))]
+fn write_google_api_protos(dir: &str) -> std::io::Result<()> {
+    let google_api = Path::new(dir).join("google/api");
+    std::fs::create_dir_all(&google_api)?;
+    write_if_changed(google_api.join("http.proto"), GOOGLE_API_HTTP_PROTO)?;
+    write_if_changed(
+        google_api.join("annotations.proto"),
+        GOOGLE_API_ANNOTATIONS_PROTO,
+    )
+}
 
-        write!(schema_file, "{}", generated_code)?;
-        schema_file.sync_all()?;
+/// Messages of the models of a version of a protobuf package.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VersionedPackage {
+    pub version: u32,
+    /// Messages of each model, in the order of the schema for the latest version and by name for locked versions.
+    pub models: Vec<(String, LockedModel)>,
+}
+
+impl VersionedPackage {
+    /// Returns the messages of the model named `name`, if the version has any.
+    fn model(&self, name: &str) -> Option<&LockedModel> {
+        self.models
+            .iter()
+            .find(|(model, _)| model == name)
+            .map(|(_, model)| model)
     }
 
-    Ok(())
+    /// Returns whether the version has a message named `name`.
+    fn has_message(&self, name: &str) -> bool {
+        self.models.iter().any(|(_, model)| {
+            model.message.name == name || model.patch.iter().any(|patch| patch.name == name)
+        })
+    }
 }
 
 /// Compiles a protobuf schema from a slice of [`ProtobufMessage`]s and [`ProtobufService`]s.
@@ -97,6 +1234,7 @@ pub fn compile_protobuf(
 /// package app;
 ///
 /// import "google/protobuf/timestamp.proto";
+/// import "google/protobuf/field_mask.proto";
 ///
 /// message Product {
 ///   string id = 1;
@@ -105,6 +1243,9 @@ pub fn compile_protobuf(
 ///   string name = 4;
 ///   int64 price = 5;
 ///   optional string description = 6;
+///   optional string status = 7;
+///   int32 version = 8;
+///   int64 price_cents = 9;
 /// }
 ///
 /// message ProductId {
@@ -115,10 +1256,42 @@ pub fn compile_protobuf(
 ///   repeated Product products = 1;
 /// }
 ///
+/// message ProductAttributes {
+///   string id = 1;
+///   map<string, string> attributes = 2;
+///   map<string, int64> prices = 3;
+/// }
+///
 /// message NewProduct {
 ///   string name = 1;
 ///   optional int64 price = 2;
 ///   optional string description = 3;
+///   int32 version = 4;
+/// }
+///
+/// message Employee {
+///   string id = 1;
+///   google.protobuf.Timestamp created_at = 2;
+///   google.protobuf.Timestamp updated_at = 3;
+///   string name = 4;
+///   optional string manager_id = 5;
+///   optional string nickname = 6 [deprecated = true];
+/// }
+///
+/// message ProductPatch {
+///   optional string name = 1;
+///   optional int64 price = 2;
+///   optional string description = 3;
+///   optional string status = 4;
+///   int32 version = 5;
+///   google.protobuf.FieldMask update_mask = 6;
+/// }
+///
+/// message EmployeePatch {
+///   optional string name = 1;
+///   optional string manager_id = 2;
+///   optional string nickname = 3 [deprecated = true];
+///   google.protobuf.FieldMask update_mask = 4;
 /// }
 ///
 /// service ProductService {
@@ -128,27 +1301,93 @@ pub fn compile_protobuf(
 pub struct ProtobufCompiler {
     models: Vec<Model>,
     services: Vec<ProtobufService>,
+    package: String,
+    annotate_sources: bool,
+    file_options: Vec<(String, String)>,
+    http_annotations: bool,
+    api_docs: ApiDocs,
 }
 
 impl ProtobufCompiler {
     /// Creates a new instance of [`ProtobufCompiler`].
     pub fn new(models: Vec<Model>, services: Vec<ProtobufService>) -> ProtobufCompiler {
-        ProtobufCompiler { models, services }
+        ProtobufCompiler {
+            models,
+            services,
+            package: DEFAULT_PACKAGE.to_string(),
+            annotate_sources: false,
+            file_options: Vec::new(),
+            http_annotations: false,
+            api_docs: ApiDocs::default(),
+        }
+    }
+
+    /// Names the generated protobuf package `package` instead of [`DEFAULT_PACKAGE`].
+    pub fn with_package(mut self, package: &str) -> Self {
+        self.package = package.to_string();
+        self
+    }
+
+    /// Interleaves the generated protobuf file and Rust code with comments telling which model,
+    /// field or service produced each chunk.
+    pub fn with_annotations(mut self, annotate_sources: bool) -> Self {
+        self.annotate_sources = annotate_sources;
+        self
+    }
+
+    /// Writes `options` such as `("java_package", "com.acme.api")` into the protobuf files,
+    /// replacing `{version}` with the version of each package, such as `v1`.
+    pub fn with_file_options(mut self, options: Vec<(String, String)>) -> Self {
+        self.file_options = options;
+        self
+    }
+
+    /// Annotates every method with the `google.api.http` option of the route [`TranscodingCompiler`] serves it at,
+    /// importing `google/api/annotations.proto`.
+    ///
+    /// Paths of versioned packages start with their version, such as `/v2/products`.
+    pub fn with_http_annotations(mut self, http_annotations: bool) -> Self {
+        self.http_annotations = http_annotations;
+        self
+    }
+
+    /// Writes the descriptions, examples and tags of `api_docs` as comments of the messages, fields and methods
+    /// they document.
+    pub fn with_api_docs(mut self, api_docs: ApiDocs) -> Self {
+        self.api_docs = api_docs;
+        self
     }
 
     /// Compiles a protobuf file.
     pub fn compile_file(&self) -> String {
         let mut proto = String::new();
 
-        write!(proto, "{}", self.write_protobuf_header()).unwrap();
+        let messages: Vec<LockedMessage> = self
+            .all_protobuf_messages()
+            .into_iter()
+            .map(LockedMessage::from)
+            .chain(
+                self.patch_messages()
+                    .iter()
+                    .map(|(_, message)| message.into()),
+            )
+            .collect();
+        let header =
+            self.write_protobuf_header(&self.package, None, &messages, !self.services.is_empty());
+        write!(proto, "{}", header).unwrap();
         writeln!(proto).unwrap();
 
-        for message in self.all_protobuf_messages() {
+        for message in &messages {
             writeln!(proto, "{}", self.write_protobuf_message(message)).unwrap();
         }
 
         for service in &self.services {
-            writeln!(proto, "{}", self.write_protobuf_service(service)).unwrap();
+            writeln!(
+                proto,
+                "{}",
+                self.write_protobuf_service(service, &self.package, None)
+            )
+            .unwrap();
         }
 
         proto.trim().to_string()
@@ -156,13 +1395,25 @@ impl ProtobufCompiler {
 
     /// Compiles generated Rust code from schemas and services.
     pub fn compile_generated_code(&self) -> String {
+        let mut code = self.compile_common_code();
+        code.push_str(&self.compile_model_code());
+
+        code.trim().to_string()
+    }
+
+    /// Compiles the code shared by every version of the package, the conversion error, health service, request logging,
+    /// request metrics and authentication.
+    fn compile_common_code(&self) -> String {
         let mut code = String::new();
 
         write!(
             code,
             r#"
+/// Error converting a protobuf message into its schema model.
 pub enum TryFromProtoError {{
+    /// A string field does not hold a uuid.
     InvalidUuid,
+    /// A required message field is not set.
     MissingField(String),
 }}
 
@@ -174,23 +1425,109 @@ impl ::std::fmt::Display for TryFromProtoError {{
         }}
     }}
 }}
+
+/// Conversions of scalar values, such as the values of maps, cannot fail.
+impl ::std::convert::From<::std::convert::Infallible> for TryFromProtoError {{
+    fn from(err: ::std::convert::Infallible) -> Self {{
+        match err {{}}
+    }}
+}}
         "#
         )
         .unwrap();
+        code.push_str(HEALTH_MODULE);
+        code.push_str(LOGGING_MODULE);
+        code.push_str(METRICS_MODULE);
+        code.push_str(AUTH_MODULE);
+        if self.has_idempotent_creates() {
+            code.push_str(IDEMPOTENCY_MODULE);
+        }
+
+        code
+    }
+
+    /// Returns whether a table has `#[awto(idempotent_create)]`, whose rpcs read the idempotency keys of requests.
+    fn has_idempotent_creates(&self) -> bool {
+        self.models
+            .iter()
+            .flat_map(|model| &model.roles)
+            .any(|role| matches!(role, Role::DatabaseTable(table) if table.idempotent_create))
+    }
+
+    /// Returns the statements reading the idempotency key of `request` once converted into `inner`,
+    /// and `call` scoped with it, when a table has idempotent creates.
+    fn idempotent_call(&self, call: TokenStream) -> (TokenStream, TokenStream, TokenStream) {
+        if !self.has_idempotent_creates() {
+            return (quote!(), quote!(), call);
+        }
+
+        (
+            quote!(let metadata = request.metadata().clone();),
+            quote!(let idempotency_key = crate::idempotency::IdempotencyKey::read(&metadata, &inner)?;),
+            quote!(crate::idempotency::IdempotencyKey::scope(idempotency_key, async { Ok::<_, ::tonic::Status>(#call) }).await?),
+        )
+    }
+
+    /// Compiles the conversions between protobuf messages and schema structs, and the implementations of the services.
+    fn compile_model_code(&self) -> String {
+        let mut code = String::new();
 
         for (model, _) in self.protobuf_messages() {
-            let ident = format_ident!("{}", model.name);
+            let ident = rust_ident(&model.name);
+            let schema_ty = schema_ty(model);
 
             let mut from_rust_fields = Vec::new();
             let mut from_proto_fields = Vec::new();
+            let mut internal_fields = Vec::new();
 
             for field in &model.fields {
-                let field_ident = format_ident!("{}", field.name);
+                let field_ident = rust_ident(&field.name);
                 let field_ident_string = &field.name;
 
+                // Messages leave secret fields out, so schema structs converted from messages get their default
+                match field.sensitivity {
+                    Sensitivity::Public => {}
+                    Sensitivity::Internal => internal_fields.push(field_ident.clone()),
+                    Sensitivity::Secret => {
+                        from_proto_fields
+                            .push(quote!(#field_ident: ::std::default::Default::default()));
+                        continue;
+                    }
+                }
+
                 let ty = strip_ty_option(&field.ty);
+                let rust_value = newtype_inner_value(field, quote!(val.#field_ident));
+                let into_newtype = field.newtype.as_ref().map(|_| quote!(.into()));
+                let map_into_newtype = field
+                    .newtype
+                    .as_ref()
+                    .map(|_| quote!(.map(::std::convert::Into::into)));
 
                 match ty {
+                    "chrono::NaiveDateTime"
+                    | "NaiveDateTime"
+                    | "chrono::DateTime<chrono::FixedOffset>"
+                    | "chrono::DateTime<FixedOffset>"
+                    | "DateTime<chrono::FixedOffset>"
+                    | "DateTime<FixedOffset>"
+                        if is_ty_option(&field.ty) =>
+                    {
+                        // Optional timestamps are optional message fields, which are `None` when unset
+                        from_rust_fields.push(quote!(
+                            #field_ident: #rust_value.map(|value| ::prost_types::Timestamp {
+                                nanos: value.timestamp_nanos() as i32,
+                                seconds: value.timestamp(),
+                            })
+                        ));
+                        from_proto_fields.push(quote!(
+                            #field_ident: val.#field_ident.map(|value| {
+                                ::chrono::DateTime::from_utc(
+                                    ::chrono::naive::NaiveDateTime::from_timestamp(value.seconds, value.nanos as u32),
+                                    ::chrono::FixedOffset::east(0),
+                                )
+                            })#map_into_newtype
+                        ));
+                    }
                     "chrono::NaiveDateTime"
                     | "NaiveDateTime"
                     | "chrono::DateTime<chrono::FixedOffset>"
@@ -199,8 +1536,8 @@ impl ::std::fmt::Display for TryFromProtoError {{
                     | "DateTime<FixedOffset>" => {
                         from_rust_fields.push(quote!(
                             #field_ident: Some(::prost_types::Timestamp {
-                                nanos: val.#field_ident.timestamp_nanos() as i32,
-                                seconds: val.#field_ident.timestamp(),
+                                nanos: #rust_value.timestamp_nanos() as i32,
+                                seconds: #rust_value.timestamp(),
                             })
                         ));
                         from_proto_fields.push(quote!(
@@ -212,32 +1549,80 @@ impl ::std::fmt::Display for TryFromProtoError {{
                                     unwrapped_value.nanos as u32
                                 ),
                                 ::chrono::FixedOffset::east(0),
-                            )
+                            )#into_newtype
                         }
                     ));
                     }
+                    "uuid::Uuid" | "Uuid" if is_ty_option(&field.ty) => {
+                        from_rust_fields.push(quote!(
+                            #field_ident: #rust_value.map(|value| value.to_string())
+                        ));
+                        from_proto_fields.push(quote!(
+                            #field_ident: val.#field_ident
+                                .map(|value| ::uuid::Uuid::parse_str(&value))
+                                .transpose()
+                                .map_err(|_| TryFromProtoError::InvalidUuid)?#map_into_newtype
+                        ));
+                    }
                     "uuid::Uuid" | "Uuid" => {
                         from_rust_fields.push(quote!(
-                            #field_ident: val.#field_ident.to_string()
+                            #field_ident: #rust_value.to_string()
                         ));
                         from_proto_fields.push(quote!(
-                        #field_ident: ::uuid::Uuid::parse_str(&val.#field_ident).map_err(|_| TryFromProtoError::InvalidUuid)?
+                        #field_ident: ::uuid::Uuid::parse_str(&val.#field_ident).map_err(|_| TryFromProtoError::InvalidUuid)?#into_newtype
                     ));
                     }
+                    _ if map_value_ty(ty).is_some() => {
+                        let from_rust =
+                            quote!(.into_iter().map(|(key, value)| (key, value.into())).collect());
+                        let from_proto = quote!(
+                            val.#field_ident
+                                .into_iter()
+                                .map(|(key, value)| Ok((key, ::std::convert::TryFrom::try_from(value)?)))
+                                .collect::<Result<::std::collections::HashMap<_, _>, TryFromProtoError>>()?
+                        );
+                        // Map fields are never optional in protobuf, so empty maps are `None` in optional schema fields
+                        if is_ty_option(&field.ty) {
+                            from_rust_fields.push(quote!(#field_ident: val.#field_ident.unwrap_or_default()#from_rust));
+                            from_proto_fields.push(quote!(#field_ident: Some(#from_proto).filter(|map| !map.is_empty())));
+                        } else {
+                            from_rust_fields.push(quote!(#field_ident: val.#field_ident#from_rust));
+                            from_proto_fields.push(quote!(#field_ident: #from_proto));
+                        }
+                    }
                     _ => {
                         if is_ty_vec(ty) {
                             from_rust_fields.push(quote!(#field_ident: val.#field_ident.into_iter().map(|v| v.into()).collect()));
                             from_proto_fields.push(quote!(#field_ident: val.#field_ident.into_iter().map(|v| ::std::convert::TryFrom::try_from(v)).collect::<Result<_, _>>()?));
                         } else {
-                            from_rust_fields.push(quote!(#field_ident: val.#field_ident.into()));
-                            from_proto_fields.push(quote!(#field_ident: val.#field_ident.into()));
+                            let value = into_field_value(field, quote!(val.#field_ident));
+                            from_rust_fields.push(quote!(#field_ident: #value));
+                            from_proto_fields.push(quote!(#field_ident: #value));
                         }
                     }
                 }
             }
+            // Fields of disabled features have no message field, so the schema struct gets their default
+            for field in &model.omitted_fields {
+                let field_ident = rust_ident(field);
+                from_proto_fields.push(quote!(#field_ident: ::std::default::Default::default()));
+            }
+
+            // Internal fields are only sent within `awto::schema::reveal_internal`
+            let internal_from_rust = if internal_fields.is_empty() {
+                quote!(Self { #( #from_rust_fields, )* })
+            } else {
+                quote!(
+                    let mut message = Self { #( #from_rust_fields, )* };
+                    if ::awto::schema::internal_hidden(&message) {
+                        #( message.#internal_fields = ::std::default::Default::default(); )*
+                    }
+                    message
+                )
+            };
 
             let expanded = quote!(
-                impl ::std::convert::TryFrom<#ident> for ::schema::#ident {
+                impl ::std::convert::TryFrom<#ident> for #schema_ty {
                     type Error = TryFromProtoError;
 
                     #[allow(unused_variables)]
@@ -248,48 +1633,77 @@ impl ::std::fmt::Display for TryFromProtoError {{
                     }
                 }
 
-                impl ::std::convert::From<::schema::#ident> for #ident {
+                impl ::std::convert::From<#schema_ty> for #ident {
                     #[allow(unused_variables)]
-                    fn from(val: ::schema::#ident) -> Self {
-                        Self {
-                            #( #from_rust_fields, )*
+                    fn from(val: #schema_ty) -> Self {
+                        #internal_from_rust
+                    }
+                }
+            );
+
+            code.push_str(&self.annotation("protobuf/messages", &model.name));
+            write!(code, "{}", expanded).unwrap();
+        }
+
+        for (model, message) in self.patch_messages() {
+            let ident = rust_ident(&message.name);
+            let doc = format!(
+                " Returns whether `field` is changed by the patch of a `{}`.",
+                model.name
+            );
+            let presences = message
+                .fields
+                .iter()
+                .filter(|field| !field.required && field.ty != ProtobufType::FieldMask)
+                .map(|field| {
+                    let field_ident = rust_ident(&field.name);
+                    let field_name = &field.name;
+                    quote!(#field_name => self.#field_ident.is_some())
+                });
+
+            let expanded = quote!(
+                impl #ident {
+                    #[doc = #doc]
+                    ///
+                    /// Fields listed in `update_mask` are changed, and cleared when they have no value.
+                    /// Without an `update_mask`, every field with a value is changed.
+                    pub fn is_patched(&self, field: &str) -> bool {
+                        match &self.update_mask {
+                            Some(mask) => mask.paths.iter().any(|path| path == field),
+                            None => match field {
+                                #( #presences, )*
+                                _ => false,
+                            },
                         }
                     }
                 }
             );
 
-            write!(code, "{}", expanded.to_string()).unwrap();
+            code.push_str(&self.annotation("protobuf/messages", &model.name));
+            write!(code, "{}", expanded).unwrap();
         }
 
         for service in &self.services {
-            let ident = format_ident!("{}", service.name);
+            let ident = rust_ident(&service.name);
             let service_path: TokenStream = service.module_path.parse().unwrap();
             let service_server_name = format_ident!("{}_server", service.name.to_snake_case());
 
             let mut methods = Vec::new();
             for method in &service.methods {
-                let name_ident = format_ident!("{}", method.name.to_snake_case());
-                let param_ident = format_ident!("{}", method.param.name);
-                let returns_ident = format_ident!("{}", method.returns.name);
-
-                let expanded_call_method = if method.returns_result {
-                    if method.is_async {
-                        quote!(self.#name_ident(param).await?)
-                    } else {
-                        quote!(self.#name_ident(param)?)
-                    }
-                } else if method.is_async {
-                    quote!(self.#name_ident(param).await)
-                } else {
-                    quote!(self.#name_ident(param))
-                };
+                let name_ident = rust_ident(&method.name.to_snake_case());
+                let param_ident = rust_ident(&method.param.name);
+                let returns_ident = rust_ident(&method.returns.name);
+                let (read_metadata, read_key, expanded_call_method) =
+                    self.idempotent_call(Self::call_method(method));
 
                 methods.push(quote!(
                     async fn #name_ident(
                         &self,
                         request: ::tonic::Request<#param_ident>,
                     ) -> Result<::tonic::Response<#returns_ident>, ::tonic::Status> {
+                        #read_metadata
                         let inner = request.into_inner();
+                        #read_key
                         let param = ::std::convert::TryInto::try_into(inner).map_err(|err: TryFromProtoError| ::tonic::Status::invalid_argument(err.to_string()))?;
                         let value = #expanded_call_method;
                         Ok(::tonic::Response::new(value.into()))
@@ -304,12 +1718,486 @@ impl ::std::fmt::Display for TryFromProtoError {{
                 }
             );
 
-            write!(code, "{}", expanded.to_string()).unwrap();
+            code.push_str(&self.annotation("protobuf/services", &service.name));
+            write!(code, "{}", expanded).unwrap();
+        }
+
+        code
+    }
+
+    /// Returns the call of the service method implementing `method`, with its `param` already converted.
+    fn call_method(method: &ProtobufMethod) -> TokenStream {
+        let name_ident = rust_ident(&method.name.to_snake_case());
+
+        if method.returns_result {
+            if method.is_async {
+                quote!(self.#name_ident(param).await?)
+            } else {
+                quote!(self.#name_ident(param)?)
+            }
+        } else if method.is_async {
+            quote!(self.#name_ident(param).await)
+        } else {
+            quote!(self.#name_ident(param))
+        }
+    }
+
+    /// Returns the messages of every model, along with its patch message, as recorded in the lock file.
+    pub fn locked_models(&self) -> Vec<(String, LockedModel)> {
+        let patches = self.patch_messages();
+
+        self.protobuf_messages()
+            .into_iter()
+            .map(|(model, message)| {
+                let patch = patches
+                    .iter()
+                    .find(|(patched, _)| patched.name == model.name)
+                    .map(|(_, patch)| LockedMessage::from(patch));
+
+                (
+                    model.name.clone(),
+                    LockedModel {
+                        message: message.into(),
+                        patch,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// Returns the package of each of `versions`, the latest generated from the schema and earlier ones from `lock`.
+    ///
+    /// Models changed since an earlier version must be marked with a newer `#[awto(proto(version = ...))]`,
+    /// and give a default for each field of the earlier version which no longer maps to a field of the same type.
+    pub fn versioned_packages(
+        &self,
+        versions: &[u32],
+        lock: &ProtoLock,
+    ) -> Result<Vec<VersionedPackage>, Error> {
+        let latest = match versions.iter().max() {
+            Some(latest) => *latest,
+            None => return Ok(Vec::new()),
+        };
+        let current = self.locked_models();
+
+        for (model, message) in self.protobuf_messages() {
+            if message.version > latest {
+                return Err(Error::UnknownProtoVersion {
+                    model: model.name.clone(),
+                    version: message.version,
+                    versions: format!("{:?}", versions),
+                });
+            }
+        }
+
+        let mut packages = Vec::new();
+        for &version in versions.iter().filter(|version| **version < latest) {
+            let locked = lock
+                .packages
+                .get(&version)
+                .ok_or(Error::MissingProtoVersion(version))?;
+
+            let mut missing = Vec::new();
+            for (name, locked_model) in locked {
+                let (message, current_model) = match self
+                    .protobuf_messages()
+                    .into_iter()
+                    .zip(&current)
+                    .find(|((model, _), _)| model.name == *name)
+                {
+                    Some(((_, message), (_, current_model))) => (message, current_model),
+                    None => continue,
+                };
+
+                if message.version <= version {
+                    let unchanged = same_fields(&locked_model.message, &current_model.message)
+                        && match (&locked_model.patch, &current_model.patch) {
+                            (Some(locked_patch), Some(current_patch)) => {
+                                same_fields(locked_patch, current_patch)
+                            }
+                            (locked_patch, current_patch) => {
+                                locked_patch.is_none() && current_patch.is_none()
+                            }
+                        };
+                    if !unchanged {
+                        return Err(Error::ChangedLockedMessage {
+                            model: name.clone(),
+                            version,
+                            latest,
+                        });
+                    }
+                    continue;
+                }
+
+                for field in &locked_model.message.fields {
+                    let maps = current_model
+                        .message
+                        .field(&field.name)
+                        .map(|current_field| field.maps_to(current_field))
+                        .unwrap_or(false);
+                    if !maps && version_default(message, version, &field.name).is_none() {
+                        missing.push(format!("{}.{}", name, field.name));
+                    }
+                }
+            }
+
+            if !missing.is_empty() {
+                return Err(Error::MissingProtoDefaults {
+                    version,
+                    fields: missing.join(", "),
+                });
+            }
+
+            packages.push(VersionedPackage {
+                version,
+                models: locked
+                    .iter()
+                    .map(|(name, model)| (name.clone(), model.clone()))
+                    .collect(),
+            });
+        }
+
+        packages.push(VersionedPackage {
+            version: latest,
+            models: current,
+        });
+
+        Ok(packages)
+    }
+
+    /// Compiles the protobuf file of a version of the package, given the `latest` version.
+    ///
+    /// Services of earlier versions only contain the methods whose messages can be converted to the latest version.
+    pub fn compile_versioned_file(
+        &self,
+        package: &VersionedPackage,
+        latest: &VersionedPackage,
+    ) -> String {
+        let mut proto = String::new();
+
+        let name = format!("{}.v{}", self.package, package.version);
+        let messages: Vec<&LockedMessage> = package
+            .models
+            .iter()
+            .map(|(_, model)| &model.message)
+            .chain(
+                package
+                    .models
+                    .iter()
+                    .filter_map(|(_, model)| model.patch.as_ref()),
+            )
+            .collect();
+        let services: Vec<ProtobufService> = self
+            .services
+            .iter()
+            .filter_map(|service| {
+                let methods = Self::served_methods(service, package, latest);
+                (!methods.is_empty()).then(|| ProtobufService {
+                    methods: methods.into_iter().cloned().collect(),
+                    ..service.clone()
+                })
+            })
+            .collect();
+        let header = self.write_protobuf_header(
+            &name,
+            Some(package.version),
+            messages.iter().copied(),
+            !services.is_empty(),
+        );
+        write!(proto, "{}", header).unwrap();
+        writeln!(proto).unwrap();
+
+        for message in messages {
+            writeln!(proto, "{}", self.write_protobuf_message(message)).unwrap();
+        }
+
+        for service in &services {
+            writeln!(
+                proto,
+                "{}",
+                self.write_protobuf_service(service, &name, Some(package.version))
+            )
+            .unwrap();
+        }
+
+        proto.trim().to_string()
+    }
+
+    /// Compiles the generated Rust code of the versions of the package, given the code tonic generated for each.
+    ///
+    /// Each version is a module such as `v1`, with the latest version reexported. Earlier versions convert
+    /// their messages to and from the latest version, and implement their services by calling the service lib.
+    pub fn compile_versioned_code(
+        &self,
+        packages: &[VersionedPackage],
+        tonic_code: &BTreeMap<u32, String>,
+    ) -> String {
+        let latest = match packages.last() {
+            Some(latest) => latest,
+            None => return String::new(),
+        };
+        let mut code = self.compile_common_code();
+
+        for package in packages {
+            writeln!(
+                code,
+                "\n/// Messages and services of version {} of the package.",
+                package.version
+            )
+            .unwrap();
+            writeln!(code, "pub mod v{} {{", package.version).unwrap();
+            writeln!(
+                code,
+                "#[allow(unused_imports)]\nuse super::TryFromProtoError;"
+            )
+            .unwrap();
+            if let Some(tonic_code) = tonic_code.get(&package.version) {
+                code.push_str(tonic_code);
+            }
+            if package.version == latest.version {
+                code.push_str(&self.compile_model_code());
+            } else {
+                code.push_str(&self.compile_adapters(package, latest));
+            }
+            writeln!(code, "}}").unwrap();
         }
+        writeln!(code, "\npub use v{}::*;", latest.version).unwrap();
 
         code.trim().to_string()
     }
 
+    /// Compiles the conversions between the messages of an earlier version and the `latest` version,
+    /// and the implementations of the services of the earlier version.
+    fn compile_adapters(&self, package: &VersionedPackage, latest: &VersionedPackage) -> String {
+        let mut code = String::new();
+        let latest_module = format_ident!("v{}", latest.version);
+
+        for (name, locked_model) in &package.models {
+            let (current_model, message) = match (latest.model(name), self.protobuf_message(name)) {
+                (Some(current_model), Some(message)) => (current_model, message),
+                _ => continue,
+            };
+
+            let into_latest = Self::into_latest(
+                &locked_model.message,
+                &current_model.message,
+                &latest_module,
+            );
+            let from_latest = Self::from_latest(
+                &locked_model.message,
+                &current_model.message,
+                &latest_module,
+                |field| version_default(message, package.version, field),
+            );
+            let patch_into_latest = match (&locked_model.patch, &current_model.patch) {
+                (Some(locked_patch), Some(current_patch)) => Some(Self::into_latest(
+                    locked_patch,
+                    current_patch,
+                    &latest_module,
+                )),
+                _ => None,
+            };
+
+            code.push_str(&self.annotation("protobuf/adapters", name));
+            write!(
+                code,
+                "{}",
+                quote!(#into_latest #from_latest #patch_into_latest)
+            )
+            .unwrap();
+        }
+
+        for service in &self.services {
+            let methods = Self::served_methods(service, package, latest);
+            if methods.is_empty() {
+                continue;
+            }
+
+            let ident = rust_ident(&service.name);
+            let service_path: TokenStream = service.module_path.parse().unwrap();
+            let service_server_name = format_ident!("{}_server", service.name.to_snake_case());
+
+            let methods = methods.into_iter().map(|method| {
+                let name_ident = rust_ident(&method.name.to_snake_case());
+                let param_ident = rust_ident(&method.param.name);
+                let returns_ident = rust_ident(&method.returns.name);
+                let (read_metadata, read_key, expanded_call_method) = self.idempotent_call(Self::call_method(method));
+
+                quote!(
+                    async fn #name_ident(
+                        &self,
+                        request: ::tonic::Request<#param_ident>,
+                    ) -> Result<::tonic::Response<#returns_ident>, ::tonic::Status> {
+                        #read_metadata
+                        let inner: super::#latest_module::#param_ident = request.into_inner().into();
+                        #read_key
+                        let param = ::std::convert::TryInto::try_into(inner).map_err(|err: TryFromProtoError| ::tonic::Status::invalid_argument(err.to_string()))?;
+                        let value = #expanded_call_method;
+                        let value: super::#latest_module::#returns_ident = value.into();
+                        Ok(::tonic::Response::new(value.into()))
+                    }
+                )
+            });
+
+            let expanded = quote!(
+                #[::tonic::async_trait]
+                impl #service_server_name::#ident for #service_path::#ident {
+                    #( #methods )*
+                }
+            );
+
+            code.push_str(&self.annotation("protobuf/services", &service.name));
+            write!(code, "{}", expanded).unwrap();
+        }
+
+        code
+    }
+
+    /// Returns the conversion of a message of an earlier version into `latest`,
+    /// leaving fields without a field of the same type in the earlier version to their defaults.
+    fn into_latest(
+        message: &LockedMessage,
+        latest: &LockedMessage,
+        latest_module: &syn::Ident,
+    ) -> TokenStream {
+        let ident = rust_ident(&message.name);
+        let latest_ident = rust_ident(&latest.name);
+
+        let mut fields = Vec::new();
+        let mut defaulted = false;
+        for field in &latest.fields {
+            match message
+                .field(&field.name)
+                .filter(|from| from.maps_to(field))
+            {
+                Some(from) => {
+                    let field_ident = rust_ident(&field.name);
+                    let value = convert_field(from);
+                    fields.push(quote!(#field_ident: #value));
+                }
+                None => defaulted = true,
+            }
+        }
+        let rest = defaulted.then(|| quote!(..::std::default::Default::default()));
+
+        quote!(
+            impl ::std::convert::From<#ident> for super::#latest_module::#latest_ident {
+                #[allow(unused_variables)]
+                fn from(val: #ident) -> Self {
+                    Self {
+                        #( #fields, )*
+                        #rest
+                    }
+                }
+            }
+        )
+    }
+
+    /// Returns the conversion of a message of the latest version into `message` of an earlier version,
+    /// setting fields removed since the earlier version to their `default`.
+    fn from_latest<'a>(
+        message: &LockedMessage,
+        latest: &LockedMessage,
+        latest_module: &syn::Ident,
+        default: impl Fn(&str) -> Option<&'a ProtobufDefault>,
+    ) -> TokenStream {
+        let ident = rust_ident(&message.name);
+        let latest_ident = rust_ident(&latest.name);
+
+        let mut defaults = Vec::new();
+        let mut fields = Vec::new();
+        for field in &message.fields {
+            let field_ident = rust_ident(&field.name);
+            if latest
+                .field(&field.name)
+                .map(|to| field.maps_to(to))
+                .unwrap_or(false)
+            {
+                let value = convert_field(field);
+                fields.push(quote!(#field_ident: #value));
+            } else {
+                // Defaults are evaluated first, so they can read fields which are moved into the message
+                let value = default(&field.name)
+                    .and_then(|default| default.value.parse::<TokenStream>().ok())
+                    .unwrap_or_else(|| quote!(::std::default::Default::default()));
+                defaults.push(quote!(let #field_ident = #value;));
+                fields.push(quote!(#field_ident));
+            }
+        }
+
+        quote!(
+            impl ::std::convert::From<super::#latest_module::#latest_ident> for #ident {
+                #[allow(unused_variables)]
+                fn from(val: super::#latest_module::#latest_ident) -> Self {
+                    #( #defaults )*
+                    Self {
+                        #( #fields, )*
+                    }
+                }
+            }
+        )
+    }
+
+    /// Returns the methods of `service` in a version of the package.
+    ///
+    /// Methods of earlier versions need both their messages in the version and the latest version,
+    /// which they are converted to and from.
+    fn served_methods<'a>(
+        service: &'a ProtobufService,
+        package: &VersionedPackage,
+        latest: &VersionedPackage,
+    ) -> Vec<&'a ProtobufMethod> {
+        let converts = |name: &str, patch: bool| {
+            package.models.iter().any(|(model, locked)| {
+                let in_latest = latest.model(model);
+                (locked.message.name == name && in_latest.is_some())
+                    || (patch
+                        && locked
+                            .patch
+                            .iter()
+                            .any(|locked_patch| locked_patch.name == name)
+                        && in_latest
+                            .map(|current| current.patch.is_some())
+                            .unwrap_or(false))
+            })
+        };
+
+        service
+            .methods
+            .iter()
+            .filter(|method| {
+                if package.version == latest.version {
+                    package.has_message(&method.param.name)
+                        && package.has_message(&method.returns.name)
+                } else {
+                    converts(&method.param.name, true) && converts(&method.returns.name, false)
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the protobuf message of the model named `name`, if any.
+    fn protobuf_message(&self, name: &str) -> Option<&ProtobufMessage> {
+        self.protobuf_messages()
+            .into_iter()
+            .find(|(model, _)| model.name == name)
+            .map(|(_, message)| message)
+    }
+
+    /// Returns the annotation of the code generated for a model or service, which is empty unless annotations are enabled.
+    fn annotation(&self, generator: &str, model: &str) -> String {
+        Annotation::new(generator, model).comment(CommentStyle::Slashes, self.annotate_sources)
+    }
+
+    /// Returns the names of the models and services of the generated file.
+    fn model_names(&self) -> Vec<&str> {
+        self.protobuf_messages()
+            .into_iter()
+            .map(|(model, _)| model.name.as_str())
+            .chain(self.services.iter().map(|service| service.name.as_str()))
+            .collect()
+    }
+
     fn protobuf_messages(&self) -> Vec<(&Model, &ProtobufMessage)> {
         self.models.iter().fold(Vec::new(), |mut acc, model| {
             let roles = model
@@ -327,6 +2215,58 @@ impl ::std::fmt::Display for TryFromProtoError {{
         })
     }
 
+    /// Returns a `<Model>Patch` message for every writable database table which is also a protobuf message.
+    ///
+    /// Patches contain the optional fields of the table's patch columns, the `version` of versioned tables,
+    /// and an `update_mask` listing the fields to change.
+    fn patch_messages(&self) -> Vec<(&Model, ProtobufMessage)> {
+        self.protobuf_messages()
+            .into_iter()
+            .filter_map(|(model, protobuf_message)| {
+                let table = model.roles.iter().find_map(|role| match role {
+                    Role::DatabaseTable(table) if !table.readonly => Some(table),
+                    _ => None,
+                })?;
+
+                Some((model, Self::patch_message(table, protobuf_message)))
+            })
+            .collect()
+    }
+
+    fn patch_message(table: &DatabaseTable, protobuf_message: &ProtobufMessage) -> ProtobufMessage {
+        let patch_columns = table.patch_columns();
+        let mut fields: Vec<_> = protobuf_message
+            .fields
+            .iter()
+            .filter(|field| patch_columns.iter().any(|column| column.name == field.name))
+            .map(|field| ProtobufField {
+                required: false,
+                ..field.clone()
+            })
+            .collect();
+        if table.versioned {
+            fields.push(ProtobufField {
+                name: "version".to_string(),
+                ty: ProtobufType::Int32,
+                required: true,
+                deprecated: false,
+            });
+        }
+        fields.push(ProtobufField {
+            name: "update_mask".to_string(),
+            ty: ProtobufType::FieldMask,
+            required: true,
+            deprecated: false,
+        });
+
+        ProtobufMessage {
+            name: format!("{}Patch", protobuf_message.name),
+            fields,
+            version: protobuf_message.version,
+            defaults: Vec::new(),
+        }
+    }
+
     fn all_protobuf_messages(&self) -> Vec<&ProtobufMessage> {
         self.protobuf_messages()
             .iter()
@@ -334,24 +2274,75 @@ impl ::std::fmt::Display for TryFromProtoError {{
             .collect()
     }
 
-    fn write_protobuf_header(&self) -> String {
+    /// Writes the syntax, package, options and imports of a protobuf file holding `messages`, and services if `has_services`.
+    ///
+    /// Well-known types are only imported when a field uses them, as buf fails unused imports.
+    fn write_protobuf_header<'a>(
+        &self,
+        package: &str,
+        version: Option<u32>,
+        messages: impl IntoIterator<Item = &'a LockedMessage>,
+        has_services: bool,
+    ) -> String {
         let mut proto = String::new();
 
         writeln!(proto, r#"syntax = "proto3";"#).unwrap();
         writeln!(proto).unwrap();
-        writeln!(proto, r#"package app;"#).unwrap();
-        writeln!(proto).unwrap();
-        writeln!(proto, r#"import "google/protobuf/timestamp.proto";"#).unwrap();
+        writeln!(proto, r#"package {};"#, package).unwrap();
+
+        if !self.file_options.is_empty() {
+            writeln!(proto).unwrap();
+        }
+        for (name, value) in &self.file_options {
+            let value = match version {
+                Some(version) => value.replace("{version}", &format!("v{}", version)),
+                None => value.clone(),
+            };
+            writeln!(proto, r#"option {} = "{}";"#, name, value).unwrap();
+        }
+
+        let types: Vec<&str> = messages
+            .into_iter()
+            .flat_map(|message| &message.fields)
+            .map(|field| field.ty.as_str())
+            .collect();
+        let uses = |ty: &str| types.iter().any(|field_ty| field_ty.contains(ty));
+        let mut imports = Vec::new();
+        if self.http_annotations && has_services {
+            imports.push("google/api/annotations.proto");
+        }
+        if uses("google.protobuf.Timestamp") {
+            imports.push("google/protobuf/timestamp.proto");
+        }
+        if uses("google.protobuf.FieldMask") {
+            imports.push("google/protobuf/field_mask.proto");
+        }
+
+        if !imports.is_empty() {
+            writeln!(proto).unwrap();
+        }
+        for import in imports {
+            writeln!(proto, r#"import "{}";"#, import).unwrap();
+        }
 
         proto
     }
 
-    fn write_protobuf_message(&self, message: &ProtobufMessage) -> String {
+    fn write_protobuf_message(&self, message: &LockedMessage) -> String {
         let mut proto = String::new();
 
+        let annotation = Annotation::new("protobuf/proto", &message.name);
+        proto.push_str(&annotation.comment(CommentStyle::Slashes, self.annotate_sources));
+        proto.push_str(&self.doc_comment(ApiItem::Model(&message.name), ""));
         writeln!(proto, "message {} {{", message.name).unwrap();
 
         for (i, field) in message.fields.iter().enumerate() {
+            let annotation = annotation.clone().field(&field.name);
+            let comment = annotation.comment(CommentStyle::Slashes, self.annotate_sources);
+            if !comment.is_empty() {
+                write!(proto, "  {}", comment).unwrap();
+            }
+            proto.push_str(&self.doc_comment(ApiItem::Field(&message.name, &field.name), "  "));
             writeln!(proto, "  {}", self.write_protobuf_field(field, i)).unwrap();
         }
 
@@ -360,7 +2351,7 @@ impl ::std::fmt::Display for TryFromProtoError {{
         proto
     }
 
-    fn write_protobuf_field(&self, field: &ProtobufField, index: usize) -> String {
+    fn write_protobuf_field(&self, field: &LockedField, index: usize) -> String {
         let mut proto = String::new();
 
         if !field.required {
@@ -369,23 +2360,54 @@ impl ::std::fmt::Display for TryFromProtoError {{
 
         write!(
             proto,
-            "{ty} {name} = {num};",
+            "{ty} {name} = {num}",
             ty = field.ty,
             name = field.name,
             num = index + 1
         )
         .unwrap();
+        if field.deprecated {
+            write!(proto, " [deprecated = true]").unwrap();
+        }
+        write!(proto, ";").unwrap();
 
         proto
     }
 
-    fn write_protobuf_service(&self, service: &ProtobufService) -> String {
+    /// Writes `service` of the protobuf package named `package`, which is `version` of the package if it is versioned.
+    fn write_protobuf_service(
+        &self,
+        service: &ProtobufService,
+        package: &str,
+        version: Option<u32>,
+    ) -> String {
         let mut proto = String::new();
 
+        let annotation = Annotation::new("protobuf/proto", &service.name);
+        proto.push_str(&annotation.comment(CommentStyle::Slashes, self.annotate_sources));
         writeln!(proto, "service {} {{", service.name).unwrap();
 
+        let rules = if self.http_annotations {
+            TranscodingCompiler::new(Vec::new())
+                .with_package(package)
+                .with_plurals(RestCompiler::new(self.models.clone()).table_plurals())
+                .http_rules(service)
+        } else {
+            Vec::new()
+        };
         for method in &service.methods {
-            write!(proto, "  {}", self.write_protobuf_method(method)).unwrap();
+            let rule = rules
+                .iter()
+                .find(|(ruled, _)| ruled.name == method.name)
+                .map(|(_, rule)| rule);
+            proto
+                .push_str(&self.doc_comment(ApiItem::Operation(&service.name, &method.name), "  "));
+            write!(
+                proto,
+                "  {}",
+                self.write_protobuf_method(method, rule, version)
+            )
+            .unwrap();
         }
 
         writeln!(proto, "}}").unwrap();
@@ -393,18 +2415,414 @@ impl ::std::fmt::Display for TryFromProtoError {{
         proto
     }
 
-    fn write_protobuf_method(&self, method: &ProtobufMethod) -> String {
+    /// Returns the lines of the [`ApiDoc`] of `item` as comments indented by `indent`, or nothing when it has none.
+    fn doc_comment(&self, item: ApiItem, indent: &str) -> String {
+        let lines = self
+            .api_docs
+            .get(item)
+            .map(ApiDoc::lines)
+            .unwrap_or_default();
+        lines
+            .iter()
+            .map(|line| {
+                if line.is_empty() {
+                    format!("{}//\n", indent)
+                } else {
+                    format!("{}// {}\n", indent, line)
+                }
+            })
+            .collect()
+    }
+
+    /// Writes `method`, annotated with the `google.api.http` option of its transcoded `rule` if any.
+    fn write_protobuf_method(
+        &self,
+        method: &ProtobufMethod,
+        rule: Option<&HttpRule>,
+        version: Option<u32>,
+    ) -> String {
         let mut proto = String::new();
 
-        writeln!(
+        write!(
             proto,
-            "rpc {name}({param}) returns ({returns});",
+            "rpc {name}({param}) returns ({returns})",
             name = method.name,
             param = method.param.name,
             returns = method.returns.name,
         )
         .unwrap();
 
+        let rule = match rule {
+            Some(rule) => rule,
+            None => {
+                writeln!(proto, ";").unwrap();
+                return proto;
+            }
+        };
+        let mut path = rule.path.clone();
+        if let Some(field) = &rule.path_field {
+            path = path.replace(&format!(":{}", field), &format!("{{{}}}", field));
+        }
+        if let (Some(version), Some(resource)) = (version, path.strip_prefix("/v1/")) {
+            path = format!("/v{}/{}", version, resource);
+        }
+
+        writeln!(proto, " {{").unwrap();
+        writeln!(proto, "    option (google.api.http) = {{").unwrap();
+        writeln!(
+            proto,
+            r#"      {}: "{}""#,
+            rule.method.as_str().to_lowercase(),
+            path
+        )
+        .unwrap();
+        if rule.method.has_body() {
+            writeln!(proto, r#"      body: "*""#).unwrap();
+        }
+        writeln!(proto, "    }};").unwrap();
+        writeln!(proto, "  }}").unwrap();
+
         proto
     }
 }
+
+/// Returns whether the fields of `a` map to the fields of `b` in the same order.
+fn same_fields(a: &LockedMessage, b: &LockedMessage) -> bool {
+    a.name == b.name
+        && a.fields.len() == b.fields.len()
+        && a.fields.iter().zip(&b.fields).all(|(a, b)| a.maps_to(b))
+}
+
+/// Returns the default of `field` of `message` in `version`, if any.
+fn version_default<'a>(
+    message: &'a ProtobufMessage,
+    version: u32,
+    field: &str,
+) -> Option<&'a ProtobufDefault> {
+    message
+        .defaults
+        .iter()
+        .find(|default| default.version == version && default.field == field)
+}
+
+/// Returns the value of `field` of `val` converted into the same field of another version of its message.
+fn convert_field(field: &LockedField) -> TokenStream {
+    let field_ident = rust_ident(&field.name);
+
+    if !field.is_message() {
+        quote!(val.#field_ident)
+    } else if field.is_repeated() {
+        quote!(val.#field_ident.into_iter().map(::std::convert::Into::into).collect())
+    } else {
+        quote!(val.#field_ident.map(::std::convert::Into::into))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use awto::{protobuf::IntoProtobufService, tests_cfg::*};
+
+    use super::*;
+    use crate::util::undocumented_items;
+
+    #[test]
+    fn trees() {
+        let compiler = ProtobufCompiler::new(tree::MODELS.to_vec(), vec![]);
+
+        // Rows of trees reference their parent by id, messages never contain themselves
+        let file = compiler.compile_file();
+        assert!(file.contains("message Category {\n  string id = 1;\n  google.protobuf.Timestamp created_at = 2;\n  google.protobuf.Timestamp updated_at = 3;\n  string name = 4;\n  optional string parent_id = 5;\n}\n"));
+        assert!(file.contains("  optional string reply_to_id = 5;\n  string category_id = 6;\n"));
+
+        let code = compiler.compile_generated_code();
+        assert!(code.contains("parent_id : val . parent_id . map (| value | :: uuid :: Uuid :: parse_str (& value)) . transpose () . map_err (| _ | TryFromProtoError :: InvalidUuid) ?"));
+        assert!(code.contains("parent_id : val . parent_id . map (| value | value . to_string ())"));
+    }
+
+    #[test]
+    fn optional_fields() {
+        let compiler =
+            ProtobufCompiler::new(MODELS.to_vec(), vec![ProductService::protobuf_service()]);
+
+        let code = compiler.compile_generated_code();
+
+        assert!(code.contains("manager_id : val . manager_id . map (| value | :: uuid :: Uuid :: parse_str (& value)) . transpose () . map_err (| _ | TryFromProtoError :: InvalidUuid) ?"));
+        assert!(
+            code.contains("manager_id : val . manager_id . map (| value | value . to_string ())")
+        );
+        assert!(code.contains("id : val . id . to_string ()"));
+    }
+
+    #[test]
+    fn idempotent_creates() {
+        let models: Vec<_> = MODELS
+            .iter()
+            .chain(idempotent::MODELS.iter())
+            .cloned()
+            .collect();
+        let compiler = ProtobufCompiler::new(models, vec![ProductService::protobuf_service()]);
+
+        let code = compiler.compile_generated_code();
+        syn::parse_file(&code).unwrap();
+        assert!(code.contains("pub mod idempotency"));
+        assert!(code.contains("let idempotency_key = crate :: idempotency :: IdempotencyKey :: read (& metadata , & inner) ?"));
+        assert!(code.contains(
+            "crate :: idempotency :: IdempotencyKey :: scope (idempotency_key , async {"
+        ));
+
+        // Rpcs are unchanged without idempotent creates
+        let code = ProtobufCompiler::new(MODELS.to_vec(), vec![ProductService::protobuf_service()])
+            .compile_generated_code();
+        assert!(!code.contains("idempotency"));
+    }
+
+    #[test]
+    fn sensitive_fields() {
+        let compiler = ProtobufCompiler::new(sensitivity::MODELS.to_vec(), vec![]);
+
+        let file = compiler.compile_file();
+        assert!(!file.contains("password"));
+        assert!(file.contains("  optional int64 credit_limit = 5;\n}\n"));
+
+        // Secret fields are never sent, internal fields only within `reveal_internal`
+        let code = compiler.compile_generated_code();
+        assert!(!code.contains("val . password_hash"));
+        assert!(code.contains("password_hash : :: std :: default :: Default :: default () ,"));
+        assert!(code.contains("if :: awto :: schema :: internal_hidden (& message) { message . credit_limit = :: std :: default :: Default :: default () ; } message"));
+        assert!(code.contains("\"credit_limit\" => self . credit_limit . is_some () , _ => false"));
+    }
+
+    #[test]
+    fn feature_fields() {
+        let mut models = MODELS.to_vec();
+        models[0]
+            .fields
+            .iter_mut()
+            .find(|field| field.name == "status")
+            .unwrap()
+            .feature = Some("workflow".to_string());
+        let compiler =
+            ProtobufCompiler::new(crate::features::with_features(models, &[]).unwrap(), vec![]);
+
+        assert!(!compiler.compile_file().contains("status"));
+        let code = compiler.compile_generated_code();
+        assert!(!code.contains("val . status"));
+        assert!(code.contains("status : :: std :: default :: Default :: default () ,"));
+    }
+
+    /// Returns a lock of the current messages as v1, with a `title` of employees which is removed in v2.
+    fn v1_lock(compiler: &ProtobufCompiler) -> ProtoLock {
+        let mut models: BTreeMap<_, _> = compiler.locked_models().into_iter().collect();
+        let employee = models.get_mut("Employee").unwrap();
+        employee.message.fields.push(LockedField {
+            name: "title".to_string(),
+            ty: "string".to_string(),
+            required: true,
+            deprecated: false,
+        });
+
+        let mut lock = ProtoLock::default();
+        lock.packages.insert(1, models);
+        lock
+    }
+
+    #[test]
+    fn versioned_packages() {
+        let compiler =
+            ProtobufCompiler::new(MODELS.to_vec(), vec![ProductService::protobuf_service()])
+                .with_package("acme.api");
+        let packages = compiler
+            .versioned_packages(&[1, 2], &v1_lock(&compiler))
+            .unwrap();
+        let (v1, v2) = (&packages[0], &packages[1]);
+        assert_eq!((v1.version, v2.version), (1, 2));
+        assert_eq!(v2.models, compiler.locked_models());
+
+        let proto = compiler.compile_versioned_file(v1, v2);
+        assert!(proto.contains("package acme.api.v1;"));
+        assert!(proto.contains(
+            "  optional string nickname = 6 [deprecated = true];\n  string title = 7;\n}"
+        ));
+        assert!(proto.contains("rpc FindProduct(ProductId) returns (ProductList);"));
+        assert_eq!(
+            compiler.compile_versioned_file(v2, v2),
+            compiler
+                .compile_file()
+                .replace("package acme.api;", "package acme.api.v2;")
+        );
+
+        let mut tonic_code = BTreeMap::new();
+        tonic_code.insert(1, "pub struct Employee {}\n".to_string());
+        let code = compiler.compile_versioned_code(&packages, &tonic_code);
+        assert!(code.contains("pub mod v1 {\n#[allow(unused_imports)]\nuse super::TryFromProtoError;\npub struct Employee {}\n"));
+        assert!(code.ends_with("pub use v2::*;"));
+        assert!(code
+            .contains("impl :: std :: convert :: From < Employee > for super :: v2 :: Employee"));
+        assert!(code.contains("fn from (val : super :: v2 :: Employee) -> Self { let title = String :: new () ; Self { id : val . id ,"));
+        assert!(code.contains(
+            "impl :: std :: convert :: From < EmployeePatch > for super :: v2 :: EmployeePatch"
+        ));
+        assert!(code.contains("products : val . products . into_iter () . map (:: std :: convert :: Into :: into) . collect ()"));
+        assert!(code.contains(
+            "let inner : super :: v2 :: ProductId = request . into_inner () . into () ;"
+        ));
+        assert!(code.contains("let value : super :: v2 :: ProductList = value . into () ;"));
+    }
+
+    #[test]
+    fn http_annotations() {
+        let mut service = ProductService::protobuf_service();
+        let mut create = service.methods[0].clone();
+        create.name = "CreateProduct".to_string();
+        let mut archive = service.methods[0].clone();
+        archive.name = "ArchiveProduct".to_string();
+        service.methods.extend([create, archive]);
+
+        let options = vec![
+            (
+                "java_package".to_string(),
+                "com.acme.api.{version}".to_string(),
+            ),
+            (
+                "go_package".to_string(),
+                "github.com/acme/api/{version}".to_string(),
+            ),
+        ];
+        let compiler = ProtobufCompiler::new(MODELS.to_vec(), vec![service])
+            .with_package("acme.api")
+            .with_file_options(options)
+            .with_http_annotations(true);
+        let packages = compiler
+            .versioned_packages(&[1, 2], &v1_lock(&compiler))
+            .unwrap();
+        let proto = compiler.compile_versioned_file(&packages[1], &packages[1]);
+
+        assert!(proto.starts_with("syntax = \"proto3\";\n\npackage acme.api.v2;\n\noption java_package = \"com.acme.api.v2\";\noption go_package = \"github.com/acme/api/v2\";\n\nimport \"google/api/annotations.proto\";\nimport \"google/protobuf/timestamp.proto\";\n"));
+        assert!(proto.contains("  rpc FindProduct(ProductId) returns (ProductList) {\n    option (google.api.http) = {\n      get: \"/v2/products/{id}\"\n    };\n  }\n"));
+        assert!(proto.contains("      post: \"/v2/products\"\n      body: \"*\"\n"));
+        assert!(proto.contains(
+            "      post: \"/acme.api.v2.ProductService/ArchiveProduct\"\n      body: \"*\"\n"
+        ));
+
+        // Unused well-known types are not imported
+        let proto = ProtobufCompiler::new(Vec::new(), Vec::new()).compile_file();
+        assert_eq!(proto, "syntax = \"proto3\";\n\npackage app;");
+
+        assert_eq!(export_path("acme.api.v2"), "acme/api/v2/api.proto");
+        assert_eq!(export_path("app"), "app/app.proto");
+    }
+
+    #[test]
+    fn versioned_package_errors() {
+        let compiler =
+            ProtobufCompiler::new(MODELS.to_vec(), vec![ProductService::protobuf_service()]);
+
+        let err = compiler
+            .versioned_packages(&[1, 2], &ProtoLock::default())
+            .unwrap_err();
+        assert!(matches!(err, Error::MissingProtoVersion(1)));
+
+        let err = compiler
+            .versioned_packages(&[1], &ProtoLock::default())
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Employee has proto version 2, which is newer than the latest of proto.versions [1]"
+        );
+
+        let mut lock = v1_lock(&compiler);
+        let employee = lock
+            .packages
+            .get_mut(&1)
+            .unwrap()
+            .get_mut("Employee")
+            .unwrap();
+        employee.message.fields[3].ty = "bytes".to_string();
+        let err = compiler.versioned_packages(&[1, 2], &lock).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "fields removed since v1 need a `#[awto(proto(v1_default(field = \"...\")))]` on their model: Employee.name"
+        );
+
+        let mut lock = v1_lock(&compiler);
+        let product = lock
+            .packages
+            .get_mut(&1)
+            .unwrap()
+            .get_mut("Product")
+            .unwrap();
+        product.message.fields.pop();
+        let err = compiler.versioned_packages(&[1, 2], &lock).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Product changed since v1 was locked, mark it `#[awto(proto(version = 2))]`"
+        );
+    }
+
+    #[test]
+    fn annotated_sources() {
+        let compiler =
+            ProtobufCompiler::new(MODELS.to_vec(), vec![ProductService::protobuf_service()])
+                .with_annotations(true);
+        let version = crate::provenance::GENERATOR_VERSION;
+
+        let proto = compiler.compile_file();
+        assert!(proto.contains(&format!(
+            "// @awto: model=Product generator=protobuf/proto v{version}\nmessage Product {{\n  \
+            // @awto: model=Product field=id generator=protobuf/proto v{version}\n",
+            version = version
+        )));
+        assert!(proto.contains(&format!(
+            "// @awto: model=ProductService generator=protobuf/proto v{}\nservice",
+            version
+        )));
+
+        let code = compiler.compile_generated_code();
+        assert!(code.contains(&format!(
+            "// @awto: model=Product generator=protobuf/messages v{}",
+            version
+        )));
+        assert!(code.contains(&format!(
+            "// @awto: model=ProductService generator=protobuf/services v{}",
+            version
+        )));
+    }
+
+    #[test]
+    fn auth_module() {
+        let compiler =
+            ProtobufCompiler::new(MODELS.to_vec(), vec![ProductService::protobuf_service()]);
+
+        let code = compiler.compile_generated_code();
+        syn::parse_file(&code).unwrap();
+        assert!(code.contains("pub trait AuthInterceptor: Send + Sync + 'static {"));
+        assert!(code.contains(
+            "fn authenticate(&self, metadata: &MetadataMap) -> Result<Principal, Status>;"
+        ));
+        assert!(code.contains("impl Authenticate for ::tonic::transport::Server {"));
+        assert!(code.contains("AuthOptions { exempt_health: true }"));
+    }
+
+    #[test]
+    fn metrics_module() {
+        let compiler =
+            ProtobufCompiler::new(MODELS.to_vec(), vec![ProductService::protobuf_service()]);
+
+        let code = compiler.compile_generated_code();
+        syn::parse_file(&code).unwrap();
+        assert!(code.contains("#[cfg(feature = \"metrics\")]\npub mod metrics {"));
+        assert!(code.contains("impl RequestMetrics for ::tonic::transport::Server {"));
+        assert!(code.contains("UNKNOWN_RPC.to_string()"));
+    }
+
+    #[test]
+    fn documented_code() {
+        let compiler =
+            ProtobufCompiler::new(MODELS.to_vec(), vec![ProductService::protobuf_service()]);
+
+        assert_eq!(
+            undocumented_items(&compiler.compile_generated_code()),
+            Vec::<String>::new()
+        );
+    }
+}
@@ -0,0 +1,501 @@
+//! Provenance of generated files, telling which models, generator and options produced them.
+//!
+//! Every compile function writes a [`GenerationManifest`] next to its generated files in `OUT_DIR`.
+//! When [`ANNOTATE_SOURCES_ENV`] is set, such as by `awto compile --annotate-sources`, the generated Rust
+//! and sql are also interleaved with `// @awto: model=Product field=price generator=database/ddl v0.1.2` comments
+//! marking the chunk each model or field produced.
+
+use std::{
+    collections::BTreeMap,
+    env, fmt, fs,
+    io::{self, BufRead, BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{format::NO_FORMAT_ENV, util::write_if_changed};
+
+/// Environment variable enabling annotations of generated code when set, such as by `awto compile --annotate-sources`.
+pub const ANNOTATE_SOURCES_ENV: &str = "AWTO_ANNOTATE_SOURCES";
+
+/// Environment variable holding the `awto.toml` profile packages are compiled with, such as `prod`.
+pub const PROFILE_ENV: &str = "AWTO_PROFILE";
+
+/// Environment variable holding a hash of the effective configuration of the profile, set by `awto compile`.
+pub const PROFILE_FINGERPRINT_ENV: &str = "AWTO_PROFILE_FINGERPRINT";
+
+/// Name of the manifest written next to generated files.
+pub const MANIFEST_FILE: &str = "generation-manifest.json";
+
+/// Start of the text of every annotation comment.
+pub const ANNOTATION_MARKER: &str = "@awto:";
+
+/// Version of awto-compile, written in annotations and manifests.
+pub const GENERATOR_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Returns whether generated code is annotated, telling cargo to rerun the build script when this changes.
+pub fn annotate_sources() -> bool {
+    println!("cargo:rerun-if-env-changed={}", ANNOTATE_SOURCES_ENV);
+    env::var_os(ANNOTATE_SOURCES_ENV).is_some()
+}
+
+/// Tells cargo to rerun the build script when the profile or its configuration changes,
+/// so switching profiles regenerates the code.
+pub fn watch_profile() {
+    println!("cargo:rerun-if-env-changed={}", PROFILE_ENV);
+    println!("cargo:rerun-if-env-changed={}", PROFILE_FINGERPRINT_ENV);
+}
+
+/// Syntax of the comments of a generated file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CommentStyle {
+    /// `//` comments of Rust and protobuf files.
+    Slashes,
+    /// `--` comments of sql.
+    Dashes,
+}
+
+impl CommentStyle {
+    fn prefix(&self) -> &'static str {
+        match self {
+            CommentStyle::Slashes => "//",
+            CommentStyle::Dashes => "--",
+        }
+    }
+}
+
+/// The model, and optionally field, a chunk of generated code was produced for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Annotation<'a> {
+    /// Name of the generator producing the chunk, such as `database/ddl`.
+    pub generator: &'a str,
+    /// Name of the model or service the chunk was produced for.
+    pub model: &'a str,
+    pub field: Option<&'a str>,
+}
+
+impl<'a> Annotation<'a> {
+    pub fn new(generator: &'a str, model: &'a str) -> Self {
+        Annotation {
+            generator,
+            model,
+            field: None,
+        }
+    }
+
+    pub fn field(mut self, field: &'a str) -> Self {
+        self.field = Some(field);
+        self
+    }
+
+    /// Returns the annotation as a comment line, or an empty string when `enabled` is false.
+    pub fn comment(&self, style: CommentStyle, enabled: bool) -> String {
+        if enabled {
+            format!("{} {}\n", style.prefix(), self)
+        } else {
+            String::new()
+        }
+    }
+}
+
+/// Formats the annotation as `@awto: model=Product field=price generator=database/ddl v0.1.2`.
+impl fmt::Display for Annotation<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} model={}", ANNOTATION_MARKER, self.model)?;
+        if let Some(field) = self.field {
+            write!(f, " field={}", field)?;
+        }
+
+        write!(f, " generator={} v{}", self.generator, GENERATOR_VERSION)
+    }
+}
+
+/// Returns `code` without its annotation comment lines.
+pub fn strip_annotations(code: &str) -> String {
+    code.lines()
+        .filter(|line| !is_annotation(line))
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+fn is_annotation(line: &str) -> bool {
+    let line = line.trim_start();
+    [CommentStyle::Slashes, CommentStyle::Dashes]
+        .iter()
+        .any(|style| {
+            // Annotations of protobuf files become doc comments of the code generated from them
+            line.strip_prefix(style.prefix())
+                .map(|comment| {
+                    comment
+                        .trim_start_matches('/')
+                        .trim_start()
+                        .starts_with(ANNOTATION_MARKER)
+                })
+                .unwrap_or(false)
+        })
+}
+
+/// Returns the sha256 hash of `contents` without annotations, so annotated and plain output hash the same.
+pub fn content_hash(contents: &str) -> String {
+    let mut hasher = ContentHasher::default();
+    hasher.update(contents.as_bytes());
+    hasher.finish()
+}
+
+/// Hasher of contents written in chunks, hashing them like [`content_hash`].
+#[derive(Default)]
+struct ContentHasher {
+    hasher: Sha256,
+    /// Start of the line whose end was not written yet.
+    line: Vec<u8>,
+}
+
+impl ContentHasher {
+    fn update(&mut self, bytes: &[u8]) {
+        for chunk in bytes.split_inclusive(|&byte| byte == b'\n') {
+            self.line.extend_from_slice(chunk);
+            if self.line.ends_with(b"\n") {
+                self.hash_line();
+            }
+        }
+    }
+
+    /// Hashes the current line like [`strip_annotations`], with its line ending replaced by a newline.
+    fn hash_line(&mut self) {
+        let line = String::from_utf8_lossy(&self.line);
+        let line = line.strip_suffix('\n').unwrap_or(&line);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if !is_annotation(line) {
+            self.hasher.update(line.as_bytes());
+            self.hasher.update(b"\n");
+        }
+        self.line.clear();
+    }
+
+    fn finish(mut self) -> String {
+        if !self.line.is_empty() {
+            self.hash_line();
+        }
+
+        format!("{:x}", self.hasher.finalize())
+    }
+}
+
+/// Files generated into a directory, along with what produced them.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GenerationManifest {
+    /// Version of the generator which wrote the manifest.
+    pub version: String,
+    /// Whether the files were generated with annotations.
+    pub annotate_sources: bool,
+    /// Generated files by their path relative to the manifest.
+    pub files: BTreeMap<String, GeneratedFile>,
+    /// Fingerprints of the models whose generated code is cached, by name, see [`ModelCache`](crate::incremental::ModelCache).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub models: BTreeMap<String, ModelFingerprint>,
+}
+
+/// A file of a [`GenerationManifest`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GeneratedFile {
+    /// Name of the generator producing the file, such as `database`.
+    pub generator: String,
+    /// Names of the models and services the file was produced from.
+    pub models: Vec<String>,
+    pub options: BTreeMap<String, String>,
+    /// Hash of the contents of the file, see [`content_hash`].
+    pub hash: String,
+}
+
+/// Fingerprint of what the generated code of a model was generated from.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelFingerprint {
+    /// Hash of the model.
+    pub model: String,
+    /// Hash of the version of the generator and the options changing the generated code, such as annotations.
+    pub options: String,
+    /// Hashes of the models the generated code of the model depends on, by name.
+    pub dependencies: BTreeMap<String, String>,
+    /// Hash of the generated code of the model, see [`content_hash`].
+    pub code: String,
+}
+
+impl GenerationManifest {
+    /// Creates an empty manifest of files generated with or without annotations.
+    pub fn new(annotate_sources: bool) -> Self {
+        GenerationManifest {
+            version: GENERATOR_VERSION.to_string(),
+            annotate_sources,
+            files: BTreeMap::new(),
+            models: BTreeMap::new(),
+        }
+    }
+
+    /// Adds the file at `path` with `contents`, generated by `generator` from `models`.
+    ///
+    /// The options of the build, formatting, annotations and the profile when set, are recorded along with the file.
+    pub fn add(
+        &mut self,
+        path: &str,
+        generator: &str,
+        models: &[&str],
+        contents: &str,
+    ) -> &mut GeneratedFile {
+        self.add_hashed(path, generator, models, content_hash(contents))
+    }
+
+    /// Adds the file at `path` like [`add`](Self::add), with the [`content_hash`] of its contents.
+    fn add_hashed(
+        &mut self,
+        path: &str,
+        generator: &str,
+        models: &[&str],
+        hash: String,
+    ) -> &mut GeneratedFile {
+        let mut options = BTreeMap::new();
+        options.insert(
+            "annotate_sources".to_string(),
+            self.annotate_sources.to_string(),
+        );
+        options.insert(
+            "format".to_string(),
+            env::var_os(NO_FORMAT_ENV).is_none().to_string(),
+        );
+        for (name, var) in [
+            ("profile", PROFILE_ENV),
+            ("profile_fingerprint", PROFILE_FINGERPRINT_ENV),
+        ] {
+            if let Ok(value) = env::var(var) {
+                options.insert(name.to_string(), value);
+            }
+        }
+
+        self.files.insert(
+            path.to_string(),
+            GeneratedFile {
+                generator: generator.to_string(),
+                models: models.iter().map(|model| model.to_string()).collect(),
+                options,
+                hash,
+            },
+        );
+        self.files.get_mut(path).unwrap()
+    }
+
+    /// Reads the manifest in `dir`, returning `None` when there is none.
+    pub fn load(dir: impl AsRef<Path>) -> io::Result<Option<Self>> {
+        match fs::read_to_string(dir.as_ref().join(MANIFEST_FILE)) {
+            Ok(contents) => Self::parse(&contents).map(Some),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Parses the `contents` of a manifest file.
+    pub fn parse(contents: &str) -> io::Result<Self> {
+        serde_json::from_str(contents)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Writes the manifest into `dir`, unless it is unchanged.
+    pub fn write(&self, dir: impl AsRef<Path>) -> io::Result<()> {
+        write_if_changed(dir.as_ref().join(MANIFEST_FILE), self.to_json()?)
+    }
+
+    /// Returns the contents of the manifest file.
+    pub fn to_json(&self) -> io::Result<String> {
+        let mut contents = serde_json::to_string_pretty(self)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        contents.push('\n');
+
+        Ok(contents)
+    }
+}
+
+impl GeneratedFile {
+    /// Records an option the file was generated with.
+    pub fn option(&mut self, name: &str, value: impl ToString) -> &mut Self {
+        self.options.insert(name.to_string(), value.to_string());
+        self
+    }
+}
+
+/// Writes `contents` to the file `name` in `out_dir` and adds it to `manifest`, returning its entry.
+pub(crate) fn write_generated<'a>(
+    out_dir: &str,
+    name: &str,
+    contents: &str,
+    manifest: &'a mut GenerationManifest,
+    generator: &str,
+    models: &[&str],
+) -> io::Result<&'a mut GeneratedFile> {
+    write_if_changed(format!("{}/{}", out_dir, name), contents)?;
+
+    Ok(manifest.add(name, generator, models, contents))
+}
+
+/// Writer of a generated file streamed into `OUT_DIR`, hashing its contents as they are written.
+///
+/// The contents are written to a temporary file, which replaces the file once finished unless it holds the same
+/// contents, preserving its modification time like [`write_if_changed`].
+pub(crate) struct GeneratedWriter {
+    name: String,
+    path: PathBuf,
+    temp_path: PathBuf,
+    file: BufWriter<fs::File>,
+    hasher: ContentHasher,
+}
+
+impl GeneratedWriter {
+    /// Creates the temporary file of the file `name` in `out_dir`.
+    pub(crate) fn create(out_dir: &str, name: &str) -> io::Result<Self> {
+        let path = Path::new(out_dir).join(name);
+        let temp_path = Path::new(out_dir).join(format!("{}.tmp", name));
+
+        Ok(GeneratedWriter {
+            name: name.to_string(),
+            file: BufWriter::new(fs::File::create(&temp_path)?),
+            path,
+            temp_path,
+            hasher: ContentHasher::default(),
+        })
+    }
+
+    /// Moves the written contents to the file unless they are unchanged, and adds it to `manifest`, returning its entry.
+    pub(crate) fn finish<'a>(
+        self,
+        manifest: &'a mut GenerationManifest,
+        generator: &str,
+        models: &[&str],
+    ) -> io::Result<&'a mut GeneratedFile> {
+        let GeneratedWriter {
+            name,
+            path,
+            temp_path,
+            file,
+            hasher,
+        } = self;
+        file.into_inner()
+            .map_err(io::IntoInnerError::into_error)?
+            .sync_all()?;
+
+        if same_contents(&path, &temp_path)? {
+            fs::remove_file(&temp_path)?;
+        } else {
+            fs::rename(&temp_path, &path)?;
+        }
+
+        Ok(manifest.add_hashed(&name, generator, models, hasher.finish()))
+    }
+}
+
+impl io::Write for GeneratedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.file.write(buf)?;
+        self.hasher.update(&buf[..written]);
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Returns whether the files at `path` and `other` exist with the same contents, reading them in chunks.
+fn same_contents(path: &Path, other: &Path) -> io::Result<bool> {
+    let file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(err),
+    };
+    let other = fs::File::open(other)?;
+    if file.metadata()?.len() != other.metadata()?.len() {
+        return Ok(false);
+    }
+
+    let (mut file, mut other) = (BufReader::new(file), BufReader::new(other));
+    loop {
+        let chunk = file.fill_buf()?;
+        if chunk.is_empty() {
+            return Ok(true);
+        }
+        let len = chunk.len().min(other.fill_buf()?.len());
+        if len == 0 || chunk[..len] != other.buffer()[..len] {
+            return Ok(false);
+        }
+        file.consume(len);
+        other.consume(len);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn annotation_comments() {
+        let annotation = Annotation::new("database/ddl", "Product").field("price");
+        assert_eq!(
+            annotation.comment(CommentStyle::Dashes, true),
+            format!(
+                "-- @awto: model=Product field=price generator=database/ddl v{}\n",
+                GENERATOR_VERSION
+            )
+        );
+        assert_eq!(annotation.comment(CommentStyle::Slashes, false), "");
+        assert_eq!(
+            Annotation::new("rest/handlers", "Product").to_string(),
+            format!(
+                "@awto: model=Product generator=rest/handlers v{}",
+                GENERATOR_VERSION
+            )
+        );
+    }
+
+    #[test]
+    fn stripped_hash() {
+        let plain = "pub struct Product;\n-- not an annotation\n";
+        let annotated = format!(
+            "{}pub struct Product;\n    {}-- not an annotation\n",
+            Annotation::new("database/entity", "Product").comment(CommentStyle::Slashes, true),
+            Annotation::new("database/ddl", "Product")
+                .field("id")
+                .comment(CommentStyle::Dashes, true),
+        );
+
+        assert_eq!(strip_annotations(&annotated), plain);
+        assert_eq!(content_hash(&annotated), content_hash(plain));
+        assert_ne!(content_hash(plain), content_hash("pub struct Category;\n"));
+    }
+
+    #[test]
+    fn manifest_roundtrip() {
+        let dir = env::temp_dir().join(format!("awto-manifest-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        assert_eq!(GenerationManifest::load(&dir).unwrap(), None);
+
+        let mut manifest = GenerationManifest::new(true);
+        manifest
+            .add(
+                "app.rs",
+                "rest",
+                &["Product", "Category"],
+                "pub fn router() {}\n",
+            )
+            .option("transcoding", false);
+        manifest.write(&dir).unwrap();
+
+        let loaded = GenerationManifest::load(&dir).unwrap().unwrap();
+        assert_eq!(loaded, manifest);
+        let file = &loaded.files["app.rs"];
+        assert_eq!(file.models, ["Product", "Category"]);
+        assert_eq!(file.options["annotate_sources"], "true");
+        assert_eq!(file.options["transcoding"], "false");
+        assert_eq!(file.hash, content_hash("pub fn router() {}\n"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,2307 @@
+use std::{env, fmt::Write};
+
+use awto::{
+    database::{DatabaseTable, DatabaseType, Operation, RequiredScope, Temporal},
+    protobuf::ProtobufService,
+    schema::{Model, Role},
+};
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+
+use crate::{
+    api_docs::{ApiDocOverride, ApiDocs, ApiItem},
+    format::format_rust,
+    naming::{pluralize, projection_plural},
+    provenance::{
+        annotate_sources, watch_profile, write_generated, Annotation, CommentStyle,
+        GenerationManifest,
+    },
+    transcoding::TranscodingCompiler,
+    util::{
+        is_computed, is_optional_insert, is_ty_option, newtype_inner_value, rust_ident, schema_ty,
+        sensitive_fields_const, TableKey,
+    },
+};
+
+const COMPILED_RUST_FILE: &str = "app.rs";
+const COMPILED_TRANSCODING_TESTS_FILE: &str = "transcoding_tests.rs";
+
+/// Request logging layer of the router generated as `rest::logging`,
+/// with the `SENSITIVE_FIELDS` line replaced by the sensitive fields of the schema.
+const LOGGING_MODULE: &str = r#"
+/// Request and response logging, added to a router with [`RequestLogging::with_request_logging`].
+pub mod logging {
+    use std::{
+        convert::Infallible,
+        fmt,
+        future::Future,
+        pin::Pin,
+        sync::Arc,
+        task::{Context, Poll},
+        time::{Duration, Instant},
+    };
+
+    use ::axum::{
+        body::{boxed, Body, BoxBody, Bytes, Full, HttpBody},
+        http::{HeaderMap, HeaderValue, Request, Response, StatusCode},
+    };
+
+    /// Header carrying the correlation id of a request, generated when a request has none.
+    pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+    /// Value replacing the redacted fields of logged bodies.
+    pub const REDACTED: &str = "[REDACTED]";
+
+    SENSITIVE_FIELDS
+
+    /// Options of [`RequestLoggingLayer`].
+    #[derive(Clone, Debug)]
+    pub struct LogOptions {
+        /// Level of the logged lines, `Info` by default.
+        pub level: ::log::Level,
+        /// Whether request and response bodies are logged, which is off by default.
+        pub include_bodies: bool,
+        /// Fields redacted from logged JSON bodies at any depth, [`SENSITIVE_FIELDS`] by default.
+        pub redact: Vec<String>,
+    }
+
+    impl Default for LogOptions {
+        fn default() -> Self {
+            LogOptions {
+                level: ::log::Level::Info,
+                include_bodies: false,
+                redact: SENSITIVE_FIELDS.iter().map(|field| field.to_string()).collect(),
+            }
+        }
+    }
+
+    /// Replaces the values of the fields named in `fields` with [`REDACTED`], at any depth of `value`.
+    pub fn redact(value: &mut ::serde_json::Value, fields: &[String]) {
+        match value {
+            ::serde_json::Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    if fields.iter().any(|field| field == key) {
+                        *value = ::serde_json::Value::String(REDACTED.to_string());
+                    } else {
+                        redact(value, fields);
+                    }
+                }
+            }
+            ::serde_json::Value::Array(values) => {
+                for value in values {
+                    redact(value, fields);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Formats a body for logging, redacting JSON bodies and only giving the length of other bodies.
+    pub fn format_body(body: &[u8], fields: &[String]) -> String {
+        match ::serde_json::from_slice::<::serde_json::Value>(body) {
+            Ok(mut value) => {
+                redact(&mut value, fields);
+                value.to_string()
+            }
+            Err(_) if body.is_empty() => "-".to_string(),
+            Err(_) => format!("<{} bytes>", body.len()),
+        }
+    }
+
+    /// A logged request, formatted as `<method> <path> <status> <duration>ms request_id=<id>`
+    /// followed by the `request=` and `response=` bodies when they are logged.
+    pub struct RequestLog {
+        /// Correlation id of the request.
+        pub request_id: String,
+        /// Method of the request, such as `GET`.
+        pub method: String,
+        /// Path of the request, without its query.
+        pub path: String,
+        /// Status of the response.
+        pub status: StatusCode,
+        /// Time between receiving the request and sending the response.
+        pub duration: Duration,
+        /// Redacted body of the request, when request bodies are logged.
+        pub request_body: Option<String>,
+        /// Redacted body of the response, when response bodies are logged.
+        pub response_body: Option<String>,
+    }
+
+    impl fmt::Display for RequestLog {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(
+                f,
+                "{} {} {} {}ms request_id={}",
+                self.method,
+                self.path,
+                self.status.as_u16(),
+                self.duration.as_millis(),
+                self.request_id
+            )?;
+            if let Some(body) = &self.request_body {
+                write!(f, " request={}", body)?;
+            }
+            if let Some(body) = &self.response_body {
+                write!(f, " response={}", body)?;
+            }
+
+            Ok(())
+        }
+    }
+
+    /// Returns the correlation id of a request, generating one when the request has none.
+    fn request_id(headers: &HeaderMap) -> HeaderValue {
+        headers.get(REQUEST_ID_HEADER).cloned().unwrap_or_else(|| {
+            HeaderValue::from_str(&::uuid::Uuid::new_v4().to_string()).expect("uuids are valid header values")
+        })
+    }
+
+    async fn collect<B: HttpBody<Data = Bytes> + Unpin>(mut body: B) -> Result<Bytes, B::Error> {
+        let mut bytes = Vec::new();
+        while let Some(chunk) = body.data().await {
+            bytes.extend_from_slice(&chunk?);
+        }
+
+        Ok(bytes.into())
+    }
+
+    fn status_response(status: StatusCode) -> Response<BoxBody> {
+        let mut response = Response::new(boxed(Full::from(Bytes::new())));
+        *response.status_mut() = status;
+        response
+    }
+
+    /// Layer logging every request of a router, with its duration, status and correlation id.
+    ///
+    /// The correlation id is read from the `x-request-id` header, or generated, and set on both the request
+    /// passed to the handlers and the response.
+    #[derive(Clone)]
+    pub struct RequestLoggingLayer {
+        options: Arc<LogOptions>,
+    }
+
+    impl RequestLoggingLayer {
+        /// Creates a layer logging requests with `options`.
+        pub fn new(options: LogOptions) -> Self {
+            RequestLoggingLayer {
+                options: Arc::new(options),
+            }
+        }
+    }
+
+    impl<S> ::tower::Layer<S> for RequestLoggingLayer {
+        type Service = RequestLoggingService<S>;
+
+        fn layer(&self, inner: S) -> Self::Service {
+            RequestLoggingService {
+                inner,
+                options: self.options.clone(),
+            }
+        }
+    }
+
+    /// Service of [`RequestLoggingLayer`].
+    #[derive(Clone)]
+    pub struct RequestLoggingService<S> {
+        inner: S,
+        options: Arc<LogOptions>,
+    }
+
+    impl<S> ::tower::Service<Request<Body>> for RequestLoggingService<S>
+    where
+        S: ::tower::Service<Request<Body>, Response = Response<BoxBody>, Error = Infallible> + Clone + Send + 'static,
+        S::Future: Send + 'static,
+    {
+        type Response = Response<BoxBody>;
+        type Error = Infallible;
+        type Future = Pin<Box<dyn Future<Output = Result<Response<BoxBody>, Infallible>> + Send>>;
+
+        fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+            self.inner.poll_ready(cx)
+        }
+
+        fn call(&mut self, mut request: Request<Body>) -> Self::Future {
+            // The ready service handles the request, leaving a clone for the next one
+            let clone = self.inner.clone();
+            let mut inner = std::mem::replace(&mut self.inner, clone);
+            let options = self.options.clone();
+
+            Box::pin(async move {
+                let start = Instant::now();
+                let request_id = request_id(request.headers());
+                request.headers_mut().insert(REQUEST_ID_HEADER, request_id.clone());
+                let mut log = RequestLog {
+                    request_id: request_id.to_str().unwrap_or("-").to_string(),
+                    method: request.method().to_string(),
+                    path: request.uri().path().to_string(),
+                    status: StatusCode::OK,
+                    duration: Duration::default(),
+                    request_body: None,
+                    response_body: None,
+                };
+
+                if options.include_bodies {
+                    let (parts, body) = request.into_parts();
+                    let body = match collect(body).await {
+                        Ok(body) => body,
+                        Err(_) => return Ok(status_response(StatusCode::BAD_REQUEST)),
+                    };
+                    log.request_body = Some(format_body(&body, &options.redact));
+                    request = Request::from_parts(parts, Body::from(body));
+                }
+
+                let mut response = inner.call(request).await?;
+                response.headers_mut().insert(REQUEST_ID_HEADER, request_id);
+
+                if options.include_bodies {
+                    let (parts, body) = response.into_parts();
+                    let body = match collect(body).await {
+                        Ok(body) => body,
+                        Err(_) => return Ok(status_response(StatusCode::INTERNAL_SERVER_ERROR)),
+                    };
+                    log.response_body = Some(format_body(&body, &options.redact));
+                    response = Response::from_parts(parts, boxed(Full::from(body)));
+                }
+
+                log.status = response.status();
+                log.duration = start.elapsed();
+                ::log::log!(options.level, "{}", log);
+
+                Ok(response)
+            })
+        }
+    }
+
+    /// Adds request logging to a router.
+    pub trait RequestLogging {
+        /// Logs every request with `options`, see [`RequestLoggingLayer`].
+        fn with_request_logging(self, options: LogOptions) -> Self;
+    }
+
+    impl RequestLogging for ::axum::Router {
+        fn with_request_logging(self, options: LogOptions) -> Self {
+            self.layer(RequestLoggingLayer::new(options))
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use std::sync::{Mutex, Once};
+
+        use ::tower::ServiceExt;
+
+        use super::*;
+
+        static LINES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+        struct CapturingLogger;
+
+        impl ::log::Log for CapturingLogger {
+            fn enabled(&self, _: &::log::Metadata) -> bool {
+                true
+            }
+
+            fn log(&self, record: &::log::Record) {
+                LINES.lock().unwrap().push(record.args().to_string());
+            }
+
+            fn flush(&self) {}
+        }
+
+        fn capture_logs() {
+            static INIT: Once = Once::new();
+            INIT.call_once(|| {
+                ::log::set_logger(&CapturingLogger).unwrap();
+                ::log::set_max_level(::log::LevelFilter::Trace);
+            });
+        }
+
+        #[tokio::test]
+        async fn redacts_sensitive_fields() {
+            capture_logs();
+            let field = SENSITIVE_FIELDS.first().copied().unwrap_or("password");
+            let mut options = LogOptions {
+                include_bodies: true,
+                ..LogOptions::default()
+            };
+            options.redact.push(field.to_string());
+
+            let router = ::axum::Router::new()
+                .route("/echo", ::axum::routing::post(|body: String| async move { body }))
+                .with_request_logging(options);
+            let body = ::serde_json::json!({ field: "hunter2", "nested": [{ field: "hunter2" }], "kept": "visible" });
+            let request = Request::post("/echo")
+                .header(REQUEST_ID_HEADER, "correlated")
+                .body(Body::from(body.to_string()))
+                .unwrap();
+            let response = router.oneshot(request).await.unwrap();
+            assert_eq!(response.headers()[REQUEST_ID_HEADER], "correlated");
+
+            let lines = LINES.lock().unwrap();
+            let line = lines.iter().find(|line| line.contains("request_id=correlated")).unwrap();
+            assert!(line.starts_with("POST /echo 200 "));
+            assert!(line.contains(REDACTED) && line.contains("visible"));
+            assert!(!line.contains("hunter2"));
+        }
+
+        #[tokio::test]
+        async fn generates_request_ids() {
+            capture_logs();
+            let router = ::axum::Router::new()
+                .route("/", ::axum::routing::get(|| async { "hunter2" }))
+                .with_request_logging(LogOptions::default());
+            let response = router.oneshot(Request::get("/").body(Body::empty()).unwrap()).await.unwrap();
+
+            let request_id = response.headers()[REQUEST_ID_HEADER].to_str().unwrap().to_string();
+            assert!(::uuid::Uuid::parse_str(&request_id).is_ok());
+            let lines = LINES.lock().unwrap();
+            let line = lines.iter().find(|line| line.ends_with(&format!("request_id={}", request_id))).unwrap();
+            assert!(!line.contains("hunter2"));
+        }
+    }
+}
+"#;
+
+/// Request metrics layer of the router generated as `rest::metrics`, behind the `metrics` feature,
+/// with the `ROUTES` line replaced by the routes of the router.
+const METRICS_MODULE: &str = r#"
+/// Request metrics of the router, recorded with the macros of the `metrics` crate once added with
+/// [`RequestMetrics::with_request_metrics`].
+///
+/// Requests are labelled with their method, the route they matched, such as `/products/:id`, and their status,
+/// so the cardinality of the metrics is bounded by the routes rather than by the ids in paths.
+/// Exporters should call [`describe`] once installed and use [`DURATION_BUCKETS`] for [`REQUEST_DURATION`].
+#[cfg(feature = "metrics")]
+pub mod metrics {
+    use std::{
+        convert::Infallible,
+        future::Future,
+        pin::Pin,
+        task::{Context, Poll},
+        time::Instant,
+    };
+
+    use ::axum::{
+        body::{Body, BoxBody},
+        http::{Request, Response},
+    };
+
+    /// Counter of the requests, labelled with `method`, `route` and `status`.
+    pub const REQUESTS: &str = "awto_http_requests_total";
+    /// Histogram of the duration of the requests in seconds, labelled with `method`, `route` and `status`.
+    pub const REQUEST_DURATION: &str = "awto_http_request_duration_seconds";
+    /// Buckets of [`REQUEST_DURATION`] in seconds, from 1ms to 10s.
+    pub const DURATION_BUCKETS: &[f64] = &[0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+    /// Route label of requests matching none of [`ROUTES`].
+    pub const UNMATCHED: &str = "unmatched";
+
+    ROUTES
+
+    /// Describes the metrics of the requests to the installed recorder.
+    pub fn describe() {
+        ::metrics::register_counter!(REQUESTS, "Requests of the router, by method, route and status.");
+        ::metrics::register_histogram!(
+            REQUEST_DURATION,
+            ::metrics::Unit::Seconds,
+            "Duration of the requests of the router, by method, route and status."
+        );
+    }
+
+    /// Returns the route of [`ROUTES`] matching `path`, preferring routes with fewer parameters,
+    /// or [`UNMATCHED`].
+    pub fn route(path: &str) -> &'static str {
+        let segments: Vec<_> = path.trim_end_matches('/').split('/').collect();
+        ROUTES
+            .iter()
+            .filter(|route| {
+                let route: Vec<_> = route.split('/').collect();
+                route.len() == segments.len()
+                    && route
+                        .iter()
+                        .zip(&segments)
+                        .all(|(route, segment)| route.starts_with(':') || route == segment)
+            })
+            .min_by_key(|route| route.matches(':').count())
+            .copied()
+            .unwrap_or(UNMATCHED)
+    }
+
+    /// Layer recording the count and duration of every request of a router.
+    #[derive(Clone, Copy, Debug, Default)]
+    pub struct RequestMetricsLayer;
+
+    impl<S> ::tower::Layer<S> for RequestMetricsLayer {
+        type Service = RequestMetricsService<S>;
+
+        fn layer(&self, inner: S) -> Self::Service {
+            RequestMetricsService { inner }
+        }
+    }
+
+    /// Service of [`RequestMetricsLayer`].
+    #[derive(Clone)]
+    pub struct RequestMetricsService<S> {
+        inner: S,
+    }
+
+    impl<S> ::tower::Service<Request<Body>> for RequestMetricsService<S>
+    where
+        S: ::tower::Service<Request<Body>, Response = Response<BoxBody>, Error = Infallible> + Clone + Send + 'static,
+        S::Future: Send + 'static,
+    {
+        type Response = Response<BoxBody>;
+        type Error = Infallible;
+        type Future = Pin<Box<dyn Future<Output = Result<Response<BoxBody>, Infallible>> + Send>>;
+
+        fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+            self.inner.poll_ready(cx)
+        }
+
+        fn call(&mut self, request: Request<Body>) -> Self::Future {
+            // The ready service handles the request, leaving a clone for the next one
+            let clone = self.inner.clone();
+            let mut inner = std::mem::replace(&mut self.inner, clone);
+
+            Box::pin(async move {
+                let start = Instant::now();
+                let method = request.method().to_string();
+                let route = route(request.uri().path());
+
+                let response = inner.call(request).await?;
+                let status = response.status().as_u16().to_string();
+                let seconds = start.elapsed().as_secs_f64();
+                ::metrics::increment_counter!(REQUESTS, "method" => method.clone(), "route" => route, "status" => status.clone());
+                ::metrics::histogram!(REQUEST_DURATION, seconds, "method" => method, "route" => route, "status" => status);
+
+                Ok(response)
+            })
+        }
+    }
+
+    /// Adds request metrics to a router.
+    pub trait RequestMetrics {
+        /// Records the count and duration of every request, see [`RequestMetricsLayer`].
+        fn with_request_metrics(self) -> Self;
+    }
+
+    impl RequestMetrics for ::axum::Router {
+        fn with_request_metrics(self) -> Self {
+            self.layer(RequestMetricsLayer)
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use std::sync::Mutex;
+
+        use ::metrics::{GaugeValue, Key, Recorder, Unit};
+        use ::tower::ServiceExt;
+
+        use super::*;
+
+        static RECORDED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+        /// Recorder keeping the name and labels of every recorded metric.
+        struct StubRecorder;
+
+        impl StubRecorder {
+            fn push(&self, key: &Key) {
+                let labels: Vec<_> = key
+                    .labels()
+                    .map(|label| format!("{}={}", label.key(), label.value()))
+                    .collect();
+                RECORDED.lock().unwrap().push(format!("{} {}", key.name(), labels.join(",")));
+            }
+        }
+
+        impl Recorder for StubRecorder {
+            fn register_counter(&self, _: &Key, _: Option<Unit>, _: Option<&'static str>) {}
+
+            fn register_gauge(&self, _: &Key, _: Option<Unit>, _: Option<&'static str>) {}
+
+            fn register_histogram(&self, _: &Key, _: Option<Unit>, _: Option<&'static str>) {}
+
+            fn increment_counter(&self, key: &Key, _: u64) {
+                self.push(key);
+            }
+
+            fn update_gauge(&self, _: &Key, _: GaugeValue) {}
+
+            fn record_histogram(&self, key: &Key, _: f64) {
+                self.push(key);
+            }
+        }
+
+        static RECORDER: StubRecorder = StubRecorder;
+
+        #[tokio::test]
+        async fn records_requests() {
+            let _ = ::metrics::set_recorder(&RECORDER);
+            let router = ::axum::Router::new()
+                .route("/healthz", ::axum::routing::get(|| async { "ok" }))
+                .with_request_metrics();
+            router.oneshot(Request::get("/healthz").body(Body::empty()).unwrap()).await.unwrap();
+
+            let recorded = RECORDED.lock().unwrap();
+            assert_eq!(
+                *recorded,
+                [
+                    "awto_http_requests_total method=GET,route=/healthz,status=200",
+                    "awto_http_request_duration_seconds method=GET,route=/healthz,status=200",
+                ]
+            );
+            assert_eq!(route("/healthz/"), "/healthz");
+            assert_eq!(route("/unknown/path"), UNMATCHED);
+        }
+    }
+}
+"#;
+
+/// Authorization of the handlers of tables with `#[awto(authorize(...))]`, generated as `rest::authorize`.
+const AUTHORIZE_MODULE: &str = r#"
+/// Authorization of the operations of tables, added to a router with [`Authorize::with_authorizer`].
+pub mod authorize {
+    use std::{convert::Infallible, sync::Arc};
+
+    use ::axum::http::{HeaderMap, StatusCode};
+
+    /// An operation on the rows of a table.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Operation {
+        /// Listing and finding rows.
+        Read,
+        /// Creating, updating and translating rows.
+        Write,
+        /// Deleting rows.
+        Delete,
+    }
+
+    /// A request checked by an [`Authorizer`].
+    #[derive(Clone, Copy, Debug)]
+    pub struct Context<'a> {
+        /// Name of the table, such as `product`.
+        pub table: &'static str,
+        /// Operation run by the request.
+        pub operation: Operation,
+        /// Headers of the request, holding its credentials.
+        pub headers: &'a HeaderMap,
+    }
+
+    /// Denial of a request, responded with `403 Forbidden`.
+    #[derive(Clone, Debug, Default, PartialEq, Eq)]
+    pub struct Denied {
+        /// Reason included in the response, such as `missing scope catalog:write`.
+        pub reason: Option<String>,
+    }
+
+    /// Checks that requests are granted the scopes of the operations they run.
+    ///
+    /// Public operations are never checked.
+    pub trait Authorizer: Send + Sync + 'static {
+        /// Checks that the request of `ctx` is granted `scope`, returning why it is denied otherwise.
+        fn check(&self, ctx: &Context<'_>, scope: &str) -> Result<(), Denied>;
+    }
+
+    /// Authorizer of a router, added by [`Authorize::with_authorizer`].
+    #[derive(Clone)]
+    pub struct SharedAuthorizer(pub Arc<dyn Authorizer>);
+
+    /// Extractor of the authorizer and headers of a request, checking the scopes of handlers.
+    ///
+    /// Requests to a router without an authorizer are denied every scope.
+    pub struct Authorization {
+        authorizer: Option<SharedAuthorizer>,
+        headers: HeaderMap,
+    }
+
+    impl Authorization {
+        /// Creates the authorization of a request with `headers` to a router with `authorizer`.
+        pub fn new(authorizer: Option<SharedAuthorizer>, headers: HeaderMap) -> Self {
+            Authorization { authorizer, headers }
+        }
+
+        /// Checks that the request may run `operation` on `table`, which requires `scope`, or is denied without one.
+        pub fn check(&self, table: &'static str, operation: Operation, scope: Option<&str>) -> Result<(), super::ApiError> {
+            let ctx = Context {
+                table,
+                operation,
+                headers: &self.headers,
+            };
+            let result = match (scope, &self.authorizer) {
+                (Some(scope), Some(authorizer)) => authorizer.0.check(&ctx, scope),
+                _ => Err(Denied::default()),
+            };
+
+            result.map_err(|denied| {
+                (
+                    StatusCode::FORBIDDEN,
+                    ::axum::Json(::serde_json::json!({
+                        "error": denied.reason.unwrap_or_else(|| "forbidden".to_string()),
+                        "scope": scope,
+                    })),
+                )
+            })
+        }
+    }
+
+    #[::axum::async_trait]
+    impl<B: Send> ::axum::extract::FromRequest<B> for Authorization {
+        type Rejection = Infallible;
+
+        async fn from_request(req: &mut ::axum::extract::RequestParts<B>) -> Result<Self, Infallible> {
+            Ok(Authorization {
+                authorizer: req.extensions().and_then(|extensions| extensions.get::<SharedAuthorizer>()).cloned(),
+                headers: req.headers().cloned().unwrap_or_default(),
+            })
+        }
+    }
+
+    /// Adds an authorizer to a router.
+    pub trait Authorize {
+        /// Checks the scopes of requests with `authorizer`.
+        fn with_authorizer(self, authorizer: impl Authorizer) -> Self;
+    }
+
+    impl Authorize for ::axum::Router {
+        fn with_authorizer(self, authorizer: impl Authorizer) -> Self {
+            self.layer(::axum::AddExtensionLayer::new(SharedAuthorizer(Arc::new(authorizer))))
+        }
+    }
+
+    /// Header of the comma separated scopes granted to a request by [`TestAuthorizer`].
+    pub const TEST_SCOPES_HEADER: &str = "x-test-scopes";
+
+    /// Authorizer for tests, granting the scopes listed in the `x-test-scopes` header of requests.
+    #[derive(Clone, Copy, Debug, Default)]
+    pub struct TestAuthorizer;
+
+    impl Authorizer for TestAuthorizer {
+        fn check(&self, ctx: &Context<'_>, scope: &str) -> Result<(), Denied> {
+            let granted = ctx
+                .headers
+                .get(TEST_SCOPES_HEADER)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default();
+            if granted.split(',').any(|granted| granted.trim() == scope) {
+                Ok(())
+            } else {
+                Err(Denied {
+                    reason: Some(format!("missing scope {}", scope)),
+                })
+            }
+        }
+    }
+}
+"#;
+
+/// Idempotency keys of the creates of tables with `#[awto(idempotent_create)]`, generated as `rest::idempotency`.
+const IDEMPOTENCY_MODULE: &str = r#"
+/// Idempotency keys of creates, replayed for the time set with [`Idempotency::with_idempotency_ttl`].
+pub mod idempotency {
+    use std::time::Duration;
+
+    use ::axum::http::StatusCode;
+    use ::database::idempotency_keys::IdempotencyError;
+
+    /// Header of the key clients send to retry a create without inserting another row.
+    pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+    /// Maximum length of an idempotency key.
+    pub const MAX_KEY_LEN: usize = 255;
+
+    /// Time the keys of creates are replayed for, added by [`Idempotency::with_idempotency_ttl`].
+    #[derive(Clone, Copy, Debug)]
+    pub struct IdempotencyTtl(pub Duration);
+
+    /// Extractor of the idempotency key of a request, along with the time it is replayed for.
+    pub struct IdempotencyKey {
+        /// Value of the `Idempotency-Key` header, if the request has one.
+        pub key: Option<String>,
+        /// Time the key is replayed for, [`DEFAULT_TTL`](::database::idempotency_keys::DEFAULT_TTL) unless configured.
+        pub ttl: Duration,
+    }
+
+    impl IdempotencyKey {
+        /// Returns the hash of `body`, which retries with the same key must repeat.
+        ///
+        /// Keys of JSON objects are sorted, so retries may send their fields in another order.
+        pub fn request_hash(&self, body: &::serde_json::Value) -> String {
+            ::database::idempotency_keys::request_hash(&::serde_json::to_vec(body).unwrap_or_default())
+        }
+    }
+
+    #[::axum::async_trait]
+    impl<B: Send> ::axum::extract::FromRequest<B> for IdempotencyKey {
+        type Rejection = super::ApiError;
+
+        async fn from_request(req: &mut ::axum::extract::RequestParts<B>) -> Result<Self, super::ApiError> {
+            let ttl = req
+                .extensions()
+                .and_then(|extensions| extensions.get::<IdempotencyTtl>())
+                .map_or(::database::idempotency_keys::DEFAULT_TTL, |ttl| ttl.0);
+            let key = match req.headers().and_then(|headers| headers.get(IDEMPOTENCY_KEY_HEADER)) {
+                Some(value) => match value.to_str() {
+                    Ok(key) if !key.is_empty() && key.len() <= MAX_KEY_LEN => Some(key.to_string()),
+                    _ => {
+                        return Err((
+                            StatusCode::BAD_REQUEST,
+                            ::axum::Json(::serde_json::json!({
+                                "error": format!("`{}` must have 1 to {} visible ASCII characters", IDEMPOTENCY_KEY_HEADER, MAX_KEY_LEN),
+                            })),
+                        ))
+                    }
+                },
+                None => None,
+            };
+
+            Ok(IdempotencyKey { key, ttl })
+        }
+    }
+
+    /// Deserializes the JSON `body` of a create, once it was hashed.
+    pub fn parse_body<T: ::serde::de::DeserializeOwned>(body: ::serde_json::Value) -> Result<T, super::ApiError> {
+        ::serde_json::from_value(body).map_err(|err| {
+            (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ::axum::Json(::serde_json::json!({ "error": err.to_string() })),
+            )
+        })
+    }
+
+    /// Returns the response of a create failing with `err`, `422 Unprocessable Entity` when its key was used
+    /// by a request with another body.
+    pub fn error(err: IdempotencyError) -> super::ApiError {
+        match err {
+            IdempotencyError::Conflict => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ::axum::Json(::serde_json::json!({ "error": err.to_string() })),
+            ),
+            IdempotencyError::Db(err) => super::db_error(err),
+        }
+    }
+
+    /// Configures the idempotency keys of a router.
+    pub trait Idempotency {
+        /// Replays the creates of idempotency keys to the retries sent within `ttl`.
+        fn with_idempotency_ttl(self, ttl: Duration) -> Self;
+    }
+
+    impl Idempotency for ::axum::Router {
+        fn with_idempotency_ttl(self, ttl: Duration) -> Self {
+            self.layer(::axum::AddExtensionLayer::new(IdempotencyTtl(ttl)))
+        }
+    }
+}
+"#;
+
+/// Compiles an axum REST router for the database tables of `models` into `OUT_DIR`,
+/// documented with the `api_docs` entries of the overlay file.
+pub fn compile_rest(
+    models: Vec<Model>,
+    api_docs: &[ApiDocOverride],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let out_dir = env::var("OUT_DIR").unwrap();
+
+    let annotate = annotate_sources();
+    watch_profile();
+    let api_docs = ApiDocs::new(&models, None, api_docs)?;
+    let compiler = RestCompiler::new(models)
+        .with_annotations(annotate)
+        .with_api_docs(api_docs);
+    let mut manifest = GenerationManifest::new(annotate);
+
+    let code = format_rust(&compiler.compile_generated_code());
+    write_generated(
+        &out_dir,
+        COMPILED_RUST_FILE,
+        &code,
+        &mut manifest,
+        "rest",
+        &compiler.model_names(),
+    )?
+    .option("transcoding", false);
+    manifest.write(&out_dir)?;
+
+    Ok(())
+}
+
+/// Compiles an axum REST router for the database tables of `models` into `OUT_DIR`,
+/// along with routes serving the gRPC `services` as JSON and tests comparing them to the gRPC server,
+/// documented with the `api_docs` entries of the overlay file.
+///
+/// See [`TranscodingCompiler`] for the routes of the services.
+pub fn compile_rest_with_transcoding(
+    models: Vec<Model>,
+    services: Vec<ProtobufService>,
+    api_docs: &[ApiDocOverride],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let out_dir = env::var("OUT_DIR").unwrap();
+
+    let annotate = annotate_sources();
+    watch_profile();
+    let api_docs = ApiDocs::new(&models, Some(&services), api_docs)?;
+    let compiler = RestCompiler::new(models)
+        .with_annotations(annotate)
+        .with_api_docs(api_docs.clone());
+    let transcoding_compiler = TranscodingCompiler::new(services)
+        .with_annotations(annotate)
+        .with_plurals(compiler.table_plurals())
+        .with_api_docs(api_docs);
+    let mut manifest = GenerationManifest::new(annotate);
+    let mut names = compiler.model_names();
+    names.extend(transcoding_compiler.service_names());
+
+    let mut code = compiler.compile_generated_code();
+    code.push_str(&transcoding_compiler.compile_generated_code());
+    write_generated(
+        &out_dir,
+        COMPILED_RUST_FILE,
+        &format_rust(&code),
+        &mut manifest,
+        "rest",
+        &names,
+    )?
+    .option("transcoding", true);
+
+    let tests = format_rust(&transcoding_compiler.compile_tests());
+    write_generated(
+        &out_dir,
+        COMPILED_TRANSCODING_TESTS_FILE,
+        &tests,
+        &mut manifest,
+        "rest/transcoding-tests",
+        &transcoding_compiler.service_names(),
+    )?;
+    manifest.write(&out_dir)?;
+
+    Ok(())
+}
+
+/// Compiles axum handlers and a router exposing the database tables of models over REST.
+///
+/// The router serves `GET /healthz`, always responding with `200 OK`, and `GET /readyz`,
+/// responding with `503 Service Unavailable` when a check of `database::health::HealthChecks` fails.
+///
+/// Every database table gets the following routes, using the pluralized table name as the path:
+///
+/// - `GET /products?first=20&after=<cursor>`
+/// - `GET /products/:id`
+/// - `POST /products`
+/// - `PUT /products/:id`
+/// - `PATCH /products/:id`
+/// - `DELETE /products/:id`
+///
+/// Request bodies use the first `#[database_sub_table]` of a table, or the table's model itself.
+/// `PATCH` requests use the generated patch of a table, only changing the columns present in the body.
+/// Updates of `#[awto(versioned)]` tables respond with `409 Conflict` when the `version` of the body is stale,
+/// so their sub table is only used when it has a `version` field.
+/// Read-only tables only expose the `GET` routes, and tables marked with `#[awto(skip_rest)]` are left out.
+///
+/// Requests are logged once `logging::RequestLogging::with_request_logging` is called on the router,
+/// redacting the `#[awto(sensitive)]` and `#[serde(skip)]` fields from logged bodies.
+///
+/// With the `metrics` feature, the count and duration of requests are recorded by route once
+/// `metrics::RequestMetrics::with_request_metrics` is called on the router.
+///
+/// Handlers of tables with `#[awto(authorize(...))]` check the scope of their operation with the `authorize::Authorizer`
+/// added with `authorize::Authorize::with_authorizer`, responding with `403 Forbidden` when it denies the request
+/// or when no authorizer was added. Tables without the attribute follow their `DefaultAuthorization`.
+///
+/// Creates of tables with `#[awto(idempotent_create)]` with an `Idempotency-Key` header respond with the row created
+/// by the first request with the key, for 24 hours or the time set with `idempotency::Idempotency::with_idempotency_ttl`,
+/// and with `422 Unprocessable Entity` when the key was used with another body.
+///
+/// # Examples
+///
+/// ```
+/// # use awto_compile::rest::RestCompiler;
+/// # use awto::tests_cfg::*;
+/// let compiler = RestCompiler::new(MODELS.to_vec());
+/// let code = compiler.compile_generated_code();
+///
+/// assert!(code.contains("pub fn router"));
+/// ```
+pub struct RestCompiler {
+    models: Vec<Model>,
+    annotate_sources: bool,
+    api_docs: ApiDocs,
+}
+
+impl RestCompiler {
+    /// Creates a new instance of [`RestCompiler`].
+    pub fn new(models: Vec<Model>) -> RestCompiler {
+        RestCompiler {
+            models,
+            annotate_sources: false,
+            api_docs: ApiDocs::default(),
+        }
+    }
+
+    /// Interleaves the generated code with comments telling which model produced the handlers of each table.
+    pub fn with_annotations(mut self, annotate_sources: bool) -> Self {
+        self.annotate_sources = annotate_sources;
+        self
+    }
+
+    /// Documents the handlers of each table with the description and tags of its model in `api_docs`.
+    pub fn with_api_docs(mut self, api_docs: ApiDocs) -> Self {
+        self.api_docs = api_docs;
+        self
+    }
+
+    /// Returns the names of the models exposed by the router.
+    fn model_names(&self) -> Vec<&str> {
+        self.rest_tables()
+            .into_iter()
+            .map(|(model, _)| model.name.as_str())
+            .collect()
+    }
+
+    /// Compiles generated Rust code for the REST handlers and router.
+    pub fn compile_generated_code(&self) -> String {
+        let mut code = String::new();
+
+        write!(
+            code,
+            r#"
+/// Error returned by handlers, serialized as a JSON body.
+pub type ApiError = (::axum::http::StatusCode, ::axum::Json<::serde_json::Value>);
+
+/// A validation error of a single field in a request body.
+#[derive(Clone, Debug, ::serde::Serialize)]
+pub struct FieldError {{
+    /// Name of the field.
+    pub field: &'static str,
+    /// Why the value of the field is invalid.
+    pub message: String,
+}}
+
+fn db_error(err: ::database::sea_orm::DbErr) -> ApiError {{
+    (
+        ::axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+        ::axum::Json(::serde_json::json!({{ "error": err.to_string() }})),
+    )
+}}
+
+fn page_error(err: ::database::cursor::PageError) -> ApiError {{
+    match err {{
+        ::database::cursor::PageError::Cursor(err) => (
+            ::axum::http::StatusCode::BAD_REQUEST,
+            ::axum::Json(::serde_json::json!({{ "error": err.to_string() }})),
+        ),
+        ::database::cursor::PageError::Db(err) => db_error(err),
+    }}
+}}
+
+fn versioned_update_error(err: ::database::version::VersionedUpdateError) -> ApiError {{
+    match err {{
+        ::database::version::VersionedUpdateError::Stale(err) => (
+            ::axum::http::StatusCode::CONFLICT,
+            ::axum::Json(::serde_json::json!({{ "error": err.to_string() }})),
+        ),
+        ::database::version::VersionedUpdateError::Db(err) => db_error(err),
+    }}
+}}
+
+fn not_found() -> ApiError {{
+    (
+        ::axum::http::StatusCode::NOT_FOUND,
+        ::axum::Json(::serde_json::json!({{ "error": "not found" }})),
+    )
+}}
+
+fn validation_error(errors: Vec<FieldError>) -> ApiError {{
+    (
+        ::axum::http::StatusCode::UNPROCESSABLE_ENTITY,
+        ::axum::Json(::serde_json::json!({{ "errors": errors }})),
+    )
+}}
+
+/// Liveness probe, responding with `200 OK` while the server is running.
+pub async fn healthz() -> ::axum::http::StatusCode {{
+    ::axum::http::StatusCode::OK
+}}
+
+/// Readiness probe, responding with `503 Service Unavailable` when a health check fails.
+pub async fn readyz(
+    ::axum::extract::Extension(db): ::axum::extract::Extension<::database::sea_orm::DatabaseConnection>,
+    ::axum::extract::Extension(checks): ::axum::extract::Extension<::database::health::HealthChecks>,
+) -> Result<::axum::Json<::serde_json::Value>, ApiError> {{
+    checks.check(&db).await.map_err(|err| {{
+        (
+            ::axum::http::StatusCode::SERVICE_UNAVAILABLE,
+            ::axum::Json(::serde_json::json!({{ "error": err.to_string() }})),
+        )
+    }})?;
+
+    Ok(::axum::Json(::serde_json::json!({{ "status": "ready" }})))
+}}
+"#
+        )
+        .unwrap();
+        code.push_str(&LOGGING_MODULE.replacen(
+            "    SENSITIVE_FIELDS\n",
+            &sensitive_fields_const(&self.models).to_string(),
+            1,
+        ));
+        code.push_str(AUTHORIZE_MODULE);
+        if self
+            .rest_tables()
+            .iter()
+            .any(|(_, table)| table.idempotent_create)
+        {
+            code.push_str(IDEMPOTENCY_MODULE);
+        }
+
+        if self
+            .rest_tables()
+            .iter()
+            .any(|(_, table)| !table.localized_columns().is_empty())
+        {
+            write!(
+                code,
+                r#"
+/// Locale requested with the `locale` query parameter, taking precedence over the `Accept-Language` header.
+#[derive(Clone, Debug, Default, ::serde::Deserialize)]
+pub struct LocaleQuery {{
+    /// Requested locale, such as `nl-NL`.
+    pub locale: Option<String>,
+}}
+
+fn requested_locale(query: &LocaleQuery, headers: &::axum::http::HeaderMap) -> Option<String> {{
+    query.locale.clone().or_else(|| {{
+        headers
+            .get(::axum::http::header::ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+    }})
+}}
+"#
+            )
+            .unwrap();
+        }
+
+        let mut routes = Vec::new();
+        let mut route_paths = vec!["/healthz".to_string(), "/readyz".to_string()];
+        for (model, table) in self.rest_tables() {
+            let annotation = Annotation::new("rest/handlers", &model.name);
+            code.push_str(&annotation.comment(CommentStyle::Slashes, self.annotate_sources));
+            write!(code, "{}", self.compile_handlers(model, table)).unwrap();
+
+            let module_ident = rust_ident(&table.name);
+            let collection_path = format!("/{}", Self::collection_name(table));
+            let item_path = format!("{}/:id", collection_path);
+
+            if !table.readonly && !table.localized_columns().is_empty() {
+                let translations_path = format!("{}/translations/:locale", item_path);
+                route_paths.push(translations_path.clone());
+                routes.push(quote!(
+                    .route(#translations_path, ::axum::routing::put(#module_ident::translate))
+                ));
+            }
+            route_paths.push(collection_path.clone());
+            route_paths.push(item_path.clone());
+            routes.push(if table.readonly {
+                quote!(
+                    .route(#collection_path, ::axum::routing::get(#module_ident::list))
+                    .route(#item_path, ::axum::routing::get(#module_ident::find))
+                )
+            } else {
+                quote!(
+                    .route(#collection_path, ::axum::routing::get(#module_ident::list).post(#module_ident::create))
+                    .route(
+                        #item_path,
+                        ::axum::routing::get(#module_ident::find)
+                            .put(#module_ident::update)
+                            .patch(#module_ident::patch)
+                            .delete(#module_ident::delete),
+                    )
+                )
+            });
+            // Routes added later are matched first, so projections take precedence over the ids of items
+            for projection in &table.projections {
+                let plural = projection_plural(&table.name, &projection.name);
+                let projection_path = format!("{}/{}", collection_path, plural);
+                route_paths.push(projection_path.clone());
+                let list_ident = format_ident!("list_{}", plural);
+                routes.push(quote!(
+                    .route(#projection_path, ::axum::routing::get(#module_ident::#list_ident))
+                ));
+            }
+        }
+
+        let routes_const = quote!(
+            /// Paths of the routes of [`router`](super::router), the `route` label of their requests.
+            pub const ROUTES: &[&str] = &[ #( #route_paths ),* ];
+        );
+        code.push_str(&METRICS_MODULE.replacen("    ROUTES\n", &routes_const.to_string(), 1));
+
+        let expanded = quote!(
+            /// Creates a router exposing every database table of the schema, and the `/healthz` and `/readyz` probes.
+            ///
+            /// Pagination cursors returned by list endpoints are encrypted with `cursor_key`.
+            pub fn router(
+                db: ::database::sea_orm::DatabaseConnection,
+                cursor_key: ::database::cursor::CursorKey,
+            ) -> ::axum::Router {
+                router_with_health(db, cursor_key, ::database::health::HealthChecks::default())
+            }
+
+            /// Creates a router like [`router`], running `health_checks` on `GET /readyz`.
+            pub fn router_with_health(
+                db: ::database::sea_orm::DatabaseConnection,
+                cursor_key: ::database::cursor::CursorKey,
+                health_checks: ::database::health::HealthChecks,
+            ) -> ::axum::Router {
+                ::axum::Router::new()
+                    .route("/healthz", ::axum::routing::get(healthz))
+                    .route("/readyz", ::axum::routing::get(readyz))
+                    #( #routes )*
+                    .layer(::axum::AddExtensionLayer::new(db))
+                    .layer(::axum::AddExtensionLayer::new(cursor_key))
+                    .layer(::axum::AddExtensionLayer::new(health_checks))
+            }
+        );
+        write!(code, "{}", expanded).unwrap();
+        write!(code, "{}", self.compile_authorize_tests()).unwrap();
+
+        code.trim().to_string()
+    }
+
+    /// Compiles tests of the handlers of every table with an operation which is not public,
+    /// checking that its scope is required with the `authorize::TestAuthorizer`.
+    ///
+    /// Routes without a body are called without any scope, which must be denied before the database is used,
+    /// so the router is given a disconnected database.
+    fn compile_authorize_tests(&self) -> TokenStream {
+        let tests: Vec<_> = self
+            .rest_tables()
+            .into_iter()
+            .filter_map(|(_, table)| {
+                let mut operations = vec![Operation::Read];
+                if !table.readonly {
+                    operations.extend([Operation::Write, Operation::Delete]);
+                }
+                let table_name = &table.name;
+                let checks: Vec<_> = operations
+                    .into_iter()
+                    .filter_map(|operation| {
+                        let operation_ident = format_ident!("{}", format!("{:?}", operation));
+                        let check = match table.required_scope(operation) {
+                            RequiredScope::Public => return None,
+                            RequiredScope::Scope(scope) => quote!(
+                                assert!(authorization(#scope).check(#table_name, Operation::#operation_ident, Some(#scope)).is_ok());
+                                assert!(authorization("").check(#table_name, Operation::#operation_ident, Some(#scope)).is_err());
+                            ),
+                            RequiredScope::Denied => quote!(
+                                assert!(authorization("").check(#table_name, Operation::#operation_ident, None).is_err());
+                            ),
+                        };
+                        // Writes need a valid body to reach the handler
+                        let method = match operation {
+                            Operation::Read => "GET",
+                            Operation::Write => return Some(check),
+                            Operation::Delete => "DELETE",
+                        };
+                        let path = format!("/{}/00000000-0000-0000-0000-000000000000", Self::collection_name(table));
+
+                        Some(quote!(
+                            #check
+                            let response = router()
+                                .oneshot(
+                                    ::axum::http::Request::builder()
+                                        .method(#method)
+                                        .uri(#path)
+                                        .body(::axum::body::Body::empty())
+                                        .unwrap(),
+                                )
+                                .await
+                                .unwrap();
+                            assert_eq!(response.status(), ::axum::http::StatusCode::FORBIDDEN, "{} {}", #method, #path);
+                        ))
+                    })
+                    .collect();
+                if checks.is_empty() {
+                    return None;
+                }
+
+                let test_ident = format_ident!("authorize_{}", table.name);
+                Some(quote!(
+                    #[tokio::test]
+                    async fn #test_ident() {
+                        #( #checks )*
+                    }
+                ))
+            })
+            .collect();
+        if tests.is_empty() {
+            return quote!();
+        }
+
+        quote!(
+            #[cfg(test)]
+            mod authorize_test {
+                use ::tower::ServiceExt;
+
+                use super::authorize::*;
+
+                fn router() -> ::axum::Router {
+                    super::router(
+                        ::database::sea_orm::DatabaseConnection::default(),
+                        ::database::cursor::CursorKey::new("authorize"),
+                    )
+                    .with_authorizer(TestAuthorizer)
+                }
+
+                /// Returns the authorization of a request granted the comma separated `scopes`.
+                fn authorization(scopes: &str) -> Authorization {
+                    let mut headers = ::axum::http::HeaderMap::new();
+                    headers.insert(TEST_SCOPES_HEADER, scopes.parse().unwrap());
+                    Authorization::new(Some(SharedAuthorizer(::std::sync::Arc::new(TestAuthorizer))), headers)
+                }
+
+                #( #tests )*
+            }
+        )
+    }
+
+    fn compile_handlers(&self, model: &Model, table: &DatabaseTable) -> TokenStream {
+        let model_ty = schema_ty(model);
+        let module_ident = rust_ident(&table.name);
+        // Paths hold the `Uuid` id of a row, or its `String` key
+        let key = TableKey::of(table);
+        let TableKey {
+            field: key_field,
+            column: key_column,
+            owned_ty: key_ty,
+            ..
+        } = &key;
+        let borrowed_id = key.borrowed(quote!(id));
+        let cloned_id = key.cloned(quote!(id));
+        let (read_param, read_check) = Self::authorization(table, Operation::Read);
+        let (write_param, write_check) = Self::authorization(table, Operation::Write);
+        let (delete_param, delete_check) = Self::authorization(table, Operation::Delete);
+        let module_doc = self.api_docs.rust_doc(
+            ApiItem::Model(&model.name),
+            &format!(" Handlers of the routes of the `{}` table.", table.name),
+        );
+        // Lists of tables with scopes keep the rows of the scopes selected in the query, such as `?live=true`
+        let (scopes_param, page) = if table.scopes.is_empty() {
+            (
+                TokenStream::new(),
+                quote!(::database::#module_ident::Entity::page(&db, &key, &args)),
+            )
+        } else {
+            let args_ident = format_ident!("{}ScopeArgs", model.name);
+            (
+                quote!(::axum::extract::Query(scopes): ::axum::extract::Query<::database::scope::#args_ident>,),
+                quote!(::database::#module_ident::Entity::page_of(
+                    &db,
+                    scopes.apply(::database::#module_ident::Entity::find()),
+                    &key,
+                    &args,
+                )),
+            )
+        };
+
+        // Localized tables respond in the locale requested with `?locale=` or `Accept-Language`
+        let read_handlers = if table.localized_columns().is_empty() {
+            quote!(
+                /// Lists a page of the rows, continuing after or before the cursor of the arguments.
+                pub async fn list(
+                    #read_param
+                    ::axum::extract::Extension(db): ::axum::extract::Extension<::database::sea_orm::DatabaseConnection>,
+                    ::axum::extract::Extension(key): ::axum::extract::Extension<::database::cursor::CursorKey>,
+                    ::axum::extract::Query(args): ::axum::extract::Query<::database::cursor::PageArgs>,
+                    #scopes_param
+                ) -> Result<::axum::Json<::database::cursor::Page<#model_ty>>, super::ApiError> {
+                    #read_check
+                    let page = #page
+                        .await
+                        .map_err(super::page_error)?;
+
+                    Ok(::axum::Json(page.map(Into::into)))
+                }
+
+                /// Returns the row with the id, or `404 Not Found` when it does not exist.
+                pub async fn find(
+                    #read_param
+                    ::axum::extract::Extension(db): ::axum::extract::Extension<::database::sea_orm::DatabaseConnection>,
+                    ::axum::extract::Path(id): ::axum::extract::Path<#key_ty>,
+                ) -> Result<::axum::Json<#model_ty>, super::ApiError> {
+                    #read_check
+                    ::database::#module_ident::Entity::find_by_id(id)
+                        .one(&db)
+                        .await
+                        .map_err(super::db_error)?
+                        .map(|model| ::axum::Json(model.into()))
+                        .ok_or_else(super::not_found)
+                }
+            )
+        } else {
+            quote!(
+                /// Lists a page of the rows in the requested locale, continuing after or before the cursor of the arguments.
+                pub async fn list(
+                    #read_param
+                    ::axum::extract::Extension(db): ::axum::extract::Extension<::database::sea_orm::DatabaseConnection>,
+                    ::axum::extract::Extension(key): ::axum::extract::Extension<::database::cursor::CursorKey>,
+                    ::axum::extract::Query(args): ::axum::extract::Query<::database::cursor::PageArgs>,
+                    ::axum::extract::Query(locale): ::axum::extract::Query<super::LocaleQuery>,
+                    #scopes_param
+                    headers: ::axum::http::HeaderMap,
+                ) -> Result<::axum::Json<::database::cursor::Page<#model_ty>>, super::ApiError> {
+                    #read_check
+                    let mut page = #page
+                        .await
+                        .map_err(super::page_error)?;
+                    if let Some(locale) = super::requested_locale(&locale, &headers) {
+                        let items = ::std::mem::take(&mut page.items);
+                        page.items = ::database::#module_ident::Entity::localize(&db, items, &locale)
+                            .await
+                            .map_err(super::db_error)?;
+                    }
+
+                    Ok(::axum::Json(page.map(Into::into)))
+                }
+
+                /// Returns the row with the id in the requested locale, or `404 Not Found` when it does not exist.
+                pub async fn find(
+                    #read_param
+                    ::axum::extract::Extension(db): ::axum::extract::Extension<::database::sea_orm::DatabaseConnection>,
+                    ::axum::extract::Path(id): ::axum::extract::Path<#key_ty>,
+                    ::axum::extract::Query(locale): ::axum::extract::Query<super::LocaleQuery>,
+                    headers: ::axum::http::HeaderMap,
+                ) -> Result<::axum::Json<#model_ty>, super::ApiError> {
+                    #read_check
+                    match super::requested_locale(&locale, &headers) {
+                        Some(locale) => ::database::#module_ident::Entity::find_localized(&db, id, &locale).await,
+                        None => ::database::#module_ident::Entity::find_by_id(id).one(&db).await,
+                    }
+                    .map_err(super::db_error)?
+                    .map(|model| ::axum::Json(model.into()))
+                    .ok_or_else(super::not_found)
+                }
+            )
+        };
+        let projection_handlers = table.projections.iter().map(|projection| {
+            let projection_ident = format_ident!("{}", projection.name);
+            let list_ident = format_ident!("list_{}", projection_plural(&table.name, &projection.name));
+            let doc = format!(
+                " Lists a page of the `{}` projections of the rows, continuing after or before the cursor of the arguments.",
+                projection.name
+            );
+
+            quote!(
+                #[doc = #doc]
+                pub async fn #list_ident(
+                    #read_param
+                    ::axum::extract::Extension(db): ::axum::extract::Extension<::database::sea_orm::DatabaseConnection>,
+                    ::axum::extract::Extension(key): ::axum::extract::Extension<::database::cursor::CursorKey>,
+                    ::axum::extract::Query(args): ::axum::extract::Query<::database::cursor::PageArgs>,
+                ) -> Result<::axum::Json<::database::cursor::Page<::database::projection::#projection_ident>>, super::ApiError> {
+                    #read_check
+                    let page = ::database::#module_ident::Entity::#list_ident(&db, &key, &args)
+                        .await
+                        .map_err(super::page_error)?;
+
+                    Ok(::axum::Json(page))
+                }
+            )
+        });
+        let read_handlers = quote!(
+            #read_handlers
+            #( #projection_handlers )*
+        );
+
+        if table.readonly {
+            return quote!(
+                #[doc = #module_doc]
+                pub mod #module_ident {
+                    use ::database::sea_orm::EntityTrait;
+
+                    #read_handlers
+                }
+            );
+        }
+
+        // Updates of versioned tables need the version read by the client
+        let sub_model = self.sub_model(table).filter(|sub_model| {
+            !table.versioned || sub_model.fields.iter().any(|field| field.name == "version")
+        });
+        let (body_model, is_sub_model) = match sub_model {
+            Some(sub_model) => (sub_model, true),
+            None => (model, false),
+        };
+        let body_ty = schema_ty(body_model);
+
+        // Values of computed columns are ignored, so they are not validated
+        let validations: Vec<_> = body_model
+            .fields
+            .iter()
+            .filter_map(|field| {
+                let column = table
+                    .columns
+                    .iter()
+                    .find(|column| column.name == field.name && column.computed.is_none())?;
+                let max_len = match column.ty {
+                    DatabaseType::Text(Some(max_len)) => max_len as usize,
+                    _ => return None,
+                };
+                let field_ident = rust_ident(&field.name);
+                let field_name = &field.name;
+                let message = format!("must be at most {} characters", max_len);
+                let value = match (&field.newtype, is_ty_option(&field.ty)) {
+                    (None, true) => quote!(body.#field_ident.as_deref()),
+                    (None, false) => quote!(Some(body.#field_ident.as_str())),
+                    (Some(_), true) => {
+                        quote!(body.#field_ident.as_ref().map(|value| value.0.as_str()))
+                    }
+                    (Some(_), false) => quote!(Some(body.#field_ident.0.as_str())),
+                };
+
+                Some(quote!(
+                    if #value.map(|value| value.chars().count() > #max_len).unwrap_or(false) {
+                        errors.push(super::FieldError {
+                            field: #field_name,
+                            message: #message.to_string(),
+                        });
+                    }
+                ))
+            })
+            .collect();
+
+        // Optional insert fields may be left to the database on create, but full updates must set them
+        let update_validations = body_model
+            .fields
+            .iter()
+            .filter(|field| is_optional_insert(field, table))
+            .map(|field| {
+                let field_ident = rust_ident(&field.name);
+                let field_name = &field.name;
+
+                quote!(
+                    if body.#field_ident.is_none() {
+                        errors.push(super::FieldError {
+                            field: #field_name,
+                            message: "is required when updating".to_string(),
+                        });
+                    }
+                )
+            });
+
+        let patch_ident = format_ident!("{}Patch", model.name);
+        let patch_validations = table.patch_columns().into_iter().filter_map(|column| {
+            let max_len = match column.ty {
+                DatabaseType::Text(Some(max_len)) => max_len as usize,
+                _ => return None,
+            };
+            let field_ident = rust_ident(&column.name);
+            let field_name = &column.name;
+            let message = format!("must be at most {} characters", max_len);
+            let value = if column.nullable {
+                quote!(body.#field_ident.as_ref().and_then(|value| value.as_deref()))
+            } else {
+                quote!(body.#field_ident.as_deref())
+            };
+
+            Some(quote!(
+                if #value.map(|value| value.chars().count() > #max_len).unwrap_or(false) {
+                    errors.push(super::FieldError {
+                        field: #field_name,
+                        message: #message.to_string(),
+                    });
+                }
+            ))
+        });
+
+        let active_model = if is_sub_model {
+            quote!(
+                ::database::sea_orm::IntoActiveModel::<::database::#module_ident::ActiveModel>::into_active_model(body)
+            )
+        } else {
+            let active_values = body_model
+                .fields
+                .iter()
+                .filter(|field| {
+                    !matches!(field.name.as_str(), "id" | "created_at" | "updated_at")
+                        && !is_computed(field, table)
+                })
+                .map(|field| {
+                    let field_ident = rust_ident(&field.name);
+                    let value = newtype_inner_value(field, quote!(body.#field_ident));
+                    if is_optional_insert(field, table) {
+                        quote!(
+                            #field_ident: #value.map_or_else(
+                                ::database::sea_orm::ActiveValue::unset,
+                                ::database::sea_orm::Set,
+                            )
+                        )
+                    } else {
+                        quote!(
+                            #field_ident: ::database::sea_orm::IntoActiveValue::into_active_value(#value).into()
+                        )
+                    }
+                });
+            quote!(
+                ::database::#module_ident::ActiveModel {
+                    #( #active_values, )*
+                    ..Default::default()
+                }
+            )
+        };
+
+        // Changes are recorded in the outbox within the transaction of the change,
+        // versioned updates and patches record their own events
+        let record_event = |kind: TokenStream| {
+            table.events.then(|| {
+                quote!(
+                    if let Some(model) = &model {
+                        ::database::#module_ident::Entity::record_event(
+                            txn,
+                            ::database::outbox::EventKind::#kind,
+                            model,
+                        )
+                        .await?;
+                    }
+                )
+            })
+        };
+        let record_created = record_event(quote!(Created));
+        let record_updated = record_event(quote!(Updated));
+        let record_deleted = record_event(quote!(Deleted));
+
+        // Portable temporal tables copy the previous version of a row into its history table
+        let portable = table.temporal == Some(Temporal::Portable);
+        let record_history = |operation: TokenStream, previous: TokenStream| {
+            portable.then(|| {
+                quote!(
+                    if let Some(previous) = &#previous {
+                        ::database::#module_ident::Entity::record_history(
+                            txn,
+                            ::database::temporal::Operation::#operation,
+                            previous,
+                        )
+                        .await?;
+                    }
+                )
+            })
+        };
+        let delete_rows = if table.events || portable {
+            let record_deleted_history = record_history(quote!(Delete), quote!(model));
+            quote!(
+                let model = match ::database::#module_ident::Entity::find_by_id(id).one(txn).await? {
+                    Some(model) => Some(model),
+                    None => return Ok(::database::sea_orm::DeleteResult { rows_affected: 0 }),
+                };
+                let result = ::database::#module_ident::Entity::delete_many()
+                    .filter(::database::#module_ident::Column::Id.eq(id))
+                    .exec(txn)
+                    .await?;
+                #record_deleted_history
+                #record_deleted
+                Ok(result)
+            )
+        } else {
+            quote!(
+                ::database::#module_ident::Entity::delete_many()
+                    .filter(::database::#module_ident::Column::#key_column.eq(#borrowed_id))
+                    .exec(txn)
+                    .await
+            )
+        };
+
+        // New rows of versioned tables start at the column's default version
+        let (create_active_model, update_active_model, update_error) = if table.versioned {
+            (
+                quote!(
+                    let mut active_model = into_active_model(body);
+                    active_model.version = ::database::sea_orm::ActiveValue::unset();
+                ),
+                quote!(
+                    ::database::#module_ident::Entity::update_versioned(txn, active_model)
+                        .await
+                        .map(Some)
+                ),
+                quote!(super::versioned_update_error),
+            )
+        } else {
+            (
+                quote!(let active_model = into_active_model(body);),
+                {
+                    let record_updated_history = record_history(quote!(Update), quote!(previous));
+                    quote!(
+                        active_model.update(txn).await?;
+                        #record_updated_history
+
+                        let model = ::database::#module_ident::Entity::find_by_id(id).one(txn).await?;
+                        #record_updated
+                        Ok(model)
+                    )
+                },
+                quote!(super::db_error),
+            )
+        };
+
+        // Versioned updates record the history of portable temporal tables themselves
+        let find_previous = if portable && !table.versioned {
+            quote!(
+                let previous = ::database::#module_ident::Entity::find_by_id(id).one(txn).await?;
+                if previous.is_none() {
+                    return Ok(None);
+                }
+            )
+        } else {
+            quote!(
+                if ::database::#module_ident::Entity::find_by_id(#cloned_id).one(txn).await?.is_none() {
+                    return Ok(None);
+                }
+            )
+        };
+
+        let translate_handler = (!table.localized_columns().is_empty()).then(|| {
+            let required_fields = table
+                .localized_columns()
+                .into_iter()
+                .filter(|column| !column.nullable)
+                .map(|column| &column.name);
+
+            quote!(
+                /// Sets the values of localized fields in a locale, removing the values set to `null`.
+                pub async fn translate(
+                    #write_param
+                    ::axum::extract::Extension(db): ::axum::extract::Extension<::database::sea_orm::DatabaseConnection>,
+                    ::axum::extract::Path((id, locale)): ::axum::extract::Path<(::uuid::Uuid, String)>,
+                    ::axum::Json(body): ::axum::Json<::std::collections::BTreeMap<String, Option<String>>>,
+                ) -> Result<::axum::Json<#model_ty>, super::ApiError> {
+                    #write_check
+                    validate_translation(&locale, &body)?;
+
+                    ::database::tx::with_txn(&db, |txn| {
+                        Box::pin(async move {
+                            if ::database::#module_ident::Entity::find_by_id(id).one(txn).await?.is_none() {
+                                return Ok(None);
+                            }
+
+                            for (field, value) in body {
+                                ::database::#module_ident::Entity::set_translation(txn, id, &locale, &field, value).await?;
+                            }
+
+                            ::database::#module_ident::Entity::find_localized(txn, id, &locale).await
+                        })
+                    })
+                    .await
+                    .map_err(super::db_error)?
+                    .map(|model| ::axum::Json(model.into()))
+                    .ok_or_else(super::not_found)
+                }
+
+                fn validate_translation(
+                    locale: &str,
+                    body: &::std::collections::BTreeMap<String, Option<String>>,
+                ) -> Result<(), super::ApiError> {
+                    const REQUIRED_FIELDS: &[&str] = &[#( #required_fields ),*];
+
+                    for (field, value) in body {
+                        let locales = ::database::#module_ident::Entity::LOCALIZED_FIELDS
+                            .iter()
+                            .find(|(name, _)| *name == field.as_str())
+                            .map(|(_, locales)| *locales)
+                            .unwrap_or_default();
+                        let error = if !locales.iter().any(|name| name.eq_ignore_ascii_case(locale)) {
+                            format!("`{}` is not localized in `{}`", field, locale)
+                        } else if value.is_none()
+                            && REQUIRED_FIELDS.contains(&field.as_str())
+                            && locales[0].eq_ignore_ascii_case(locale)
+                        {
+                            format!("`{}` is required in the default locale `{}`", field, locales[0])
+                        } else {
+                            continue;
+                        };
+
+                        return Err((
+                            ::axum::http::StatusCode::UNPROCESSABLE_ENTITY,
+                            ::axum::Json(::serde_json::json!({ "error": error })),
+                        ));
+                    }
+
+                    Ok(())
+                }
+            )
+        });
+
+        let create_handler = if table.idempotent_create {
+            quote!(
+                /// Inserts a row, validating the body first.
+                ///
+                /// Retries of a request with an `Idempotency-Key` header respond with the row it created.
+                pub async fn create(
+                    #write_param
+                    ::axum::extract::Extension(db): ::axum::extract::Extension<::database::sea_orm::DatabaseConnection>,
+                    idempotency: super::idempotency::IdempotencyKey,
+                    ::axum::Json(body): ::axum::Json<::serde_json::Value>,
+                ) -> Result<(::axum::http::StatusCode, ::axum::Json<#model_ty>), super::ApiError> {
+                    #write_check
+                    let request_hash = idempotency.request_hash(&body);
+                    let body: #body_ty = super::idempotency::parse_body(body)?;
+                    validate(&body)?;
+
+                    ::database::tx::with_txn(&db, |txn| {
+                        Box::pin(async move {
+                            #create_active_model
+                            let model = match &idempotency.key {
+                                Some(key) => {
+                                    ::database::#module_ident::Entity::insert_idempotent(
+                                        txn,
+                                        key,
+                                        &request_hash,
+                                        idempotency.ttl,
+                                        active_model,
+                                    )
+                                    .await?
+                                }
+                                None => {
+                                    let inserted = active_model.insert(txn).await?;
+                                    let model = ::database::#module_ident::Entity::find_by_id(inserted.#key_field.unwrap())
+                                        .one(txn)
+                                        .await?;
+                                    #record_created
+                                    model
+                                }
+                            };
+                            Ok(model)
+                        })
+                    })
+                    .await
+                    .map_err(super::idempotency::error)?
+                    .map(|model| (::axum::http::StatusCode::CREATED, ::axum::Json(model.into())))
+                    .ok_or_else(super::not_found)
+                }
+            )
+        } else {
+            quote!(
+                /// Inserts a row, validating the body first.
+                pub async fn create(
+                    #write_param
+                    ::axum::extract::Extension(db): ::axum::extract::Extension<::database::sea_orm::DatabaseConnection>,
+                    ::axum::Json(body): ::axum::Json<#body_ty>,
+                ) -> Result<(::axum::http::StatusCode, ::axum::Json<#model_ty>), super::ApiError> {
+                    #write_check
+                    validate(&body)?;
+
+                    ::database::tx::with_txn(&db, |txn| {
+                        Box::pin(async move {
+                            #create_active_model
+                            let inserted = active_model.insert(txn).await?;
+                            let model = ::database::#module_ident::Entity::find_by_id(inserted.#key_field.unwrap())
+                                .one(txn)
+                                .await?;
+                            #record_created
+                            Ok(model)
+                        })
+                    })
+                    .await
+                    .map_err(super::db_error)?
+                    .map(|model| (::axum::http::StatusCode::CREATED, ::axum::Json(model.into())))
+                    .ok_or_else(super::not_found)
+                }
+            )
+        };
+
+        quote!(
+            #[doc = #module_doc]
+            pub mod #module_ident {
+                use ::database::sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter};
+
+                #read_handlers
+
+                #create_handler
+
+                /// Replaces the values of the row with the id, validating the body first.
+                pub async fn update(
+                    #write_param
+                    ::axum::extract::Extension(db): ::axum::extract::Extension<::database::sea_orm::DatabaseConnection>,
+                    ::axum::extract::Path(id): ::axum::extract::Path<#key_ty>,
+                    ::axum::Json(body): ::axum::Json<#body_ty>,
+                ) -> Result<::axum::Json<#model_ty>, super::ApiError> {
+                    #write_check
+                    validate_update(&body)?;
+
+                    ::database::tx::with_txn(&db, |txn| {
+                        Box::pin(async move {
+                            #find_previous
+
+                            let mut active_model = into_active_model(body);
+                            active_model.#key_field = ::database::sea_orm::Set(#cloned_id);
+                            active_model.updated_at = ::database::sea_orm::Set(
+                                ::chrono::Utc::now().with_timezone(&::chrono::FixedOffset::east(0)),
+                            );
+                            #update_active_model
+                        })
+                    })
+                    .await
+                    .map_err(#update_error)?
+                    .map(|model| ::axum::Json(model.into()))
+                    .ok_or_else(super::not_found)
+                }
+
+                /// Updates the fields present in the body of the row with the id.
+                pub async fn patch(
+                    #write_param
+                    ::axum::extract::Extension(db): ::axum::extract::Extension<::database::sea_orm::DatabaseConnection>,
+                    ::axum::extract::Path(id): ::axum::extract::Path<#key_ty>,
+                    ::axum::Json(body): ::axum::Json<::database::patch::#patch_ident>,
+                ) -> Result<::axum::Json<#model_ty>, super::ApiError> {
+                    #write_check
+                    validate_patch(&body)?;
+
+                    ::database::tx::with_txn(&db, |txn| {
+                        Box::pin(async move {
+                            ::database::#module_ident::Entity::apply_patch(txn, #borrowed_id, body).await
+                        })
+                    })
+                    .await
+                    .map_err(#update_error)?
+                    .map(|model| ::axum::Json(model.into()))
+                    .ok_or_else(super::not_found)
+                }
+
+                /// Deletes the row with the id.
+                pub async fn delete(
+                    #delete_param
+                    ::axum::extract::Extension(db): ::axum::extract::Extension<::database::sea_orm::DatabaseConnection>,
+                    ::axum::extract::Path(id): ::axum::extract::Path<#key_ty>,
+                ) -> Result<::axum::http::StatusCode, super::ApiError> {
+                    #delete_check
+                    let result = ::database::tx::with_txn(&db, |txn| {
+                        Box::pin(async move {
+                            #delete_rows
+                        })
+                    })
+                    .await
+                    .map_err(super::db_error)?;
+
+                    if result.rows_affected == 0 {
+                        return Err(super::not_found());
+                    }
+
+                    Ok(::axum::http::StatusCode::NO_CONTENT)
+                }
+
+                #translate_handler
+
+                #[allow(unused_variables, unused_mut)]
+                fn validate(body: &#body_ty) -> Result<(), super::ApiError> {
+                    let mut errors = Vec::new();
+                    #( #validations )*
+
+                    if errors.is_empty() {
+                        Ok(())
+                    } else {
+                        Err(super::validation_error(errors))
+                    }
+                }
+
+                #[allow(unused_variables, unused_mut)]
+                fn validate_update(body: &#body_ty) -> Result<(), super::ApiError> {
+                    let mut errors = Vec::new();
+                    #( #update_validations )*
+                    #( #validations )*
+
+                    if errors.is_empty() {
+                        Ok(())
+                    } else {
+                        Err(super::validation_error(errors))
+                    }
+                }
+
+                #[allow(unused_variables, unused_mut)]
+                fn validate_patch(body: &::database::patch::#patch_ident) -> Result<(), super::ApiError> {
+                    let mut errors = Vec::new();
+                    #( #patch_validations )*
+
+                    if errors.is_empty() {
+                        Ok(())
+                    } else {
+                        Err(super::validation_error(errors))
+                    }
+                }
+
+                fn into_active_model(body: #body_ty) -> ::database::#module_ident::ActiveModel {
+                    #active_model
+                }
+            }
+        )
+    }
+
+    /// Returns the parameter extracting the [`Authorization`](AUTHORIZE_MODULE) of the request in handlers running
+    /// `operation` on `table`, and the check of its scope, which are both empty when the operation is public.
+    fn authorization(table: &DatabaseTable, operation: Operation) -> (TokenStream, TokenStream) {
+        let scope = match table.required_scope(operation) {
+            RequiredScope::Public => return (quote!(), quote!()),
+            RequiredScope::Scope(scope) => quote!(Some(#scope)),
+            RequiredScope::Denied => quote!(None),
+        };
+        let table_name = &table.name;
+        let operation = format_ident!("{}", format!("{:?}", operation));
+
+        (
+            quote!(auth: super::authorize::Authorization,),
+            quote!(auth.check(#table_name, super::authorize::Operation::#operation, #scope)?;),
+        )
+    }
+
+    /// Returns the first model marked as a `#[database_sub_table]` of `table`.
+    fn sub_model(&self, table: &DatabaseTable) -> Option<&Model> {
+        self.models.iter().find(|model| {
+            model.roles.iter().any(|role| match role {
+                Role::DatabaseSubTable(sub_table) => sub_table.name == table.name,
+                _ => false,
+            })
+        })
+    }
+
+    fn rest_tables(&self) -> Vec<(&Model, &DatabaseTable)> {
+        self.models.iter().fold(Vec::new(), |mut acc, model| {
+            let roles = model
+                .roles
+                .iter()
+                .filter_map(|role| match role {
+                    Role::DatabaseTable(database_table) if !database_table.skip_rest => {
+                        Some((model, database_table))
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+
+            acc.extend(roles);
+
+            acc
+        })
+    }
+
+    /// Returns the path segment of the routes of `table`, which is the plural of its name.
+    fn collection_name(table: &DatabaseTable) -> String {
+        table
+            .plural
+            .clone()
+            .unwrap_or_else(|| pluralize(&table.name))
+    }
+
+    /// Returns the plurals of the names of the tables with a configured plural, for the transcoded routes named after them.
+    pub(crate) fn table_plurals(&self) -> Vec<(String, String)> {
+        self.rest_tables()
+            .into_iter()
+            .filter_map(|(_, table)| Some((table.name.clone(), table.plural.clone()?)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use awto::tests_cfg::*;
+
+    use super::*;
+    use crate::{
+        database::{with_default_authorization, with_plurals, with_temporal, DefaultAuthorization},
+        util::undocumented_items,
+    };
+
+    #[test]
+    fn string_primary_keys() {
+        let code = RestCompiler::new(string_keys::MODELS.to_vec()).compile_generated_code();
+
+        assert_eq!(
+            code.matches(":: axum :: extract :: Path (id) : :: axum :: extract :: Path < String >")
+                .count(),
+            8
+        );
+        assert!(code
+            .contains("filter (:: database :: country :: Column :: Code . eq (id . as_str ()))"));
+        assert!(
+            code.contains("active_model . code = :: database :: sea_orm :: Set (id . clone ()) ;")
+        );
+        assert!(code.contains(
+            ":: database :: country :: Entity :: apply_patch (txn , id . as_str () , body)"
+        ));
+        assert!(code.contains(":: database :: address :: Entity :: apply_patch (txn , id , body)"));
+    }
+
+    #[test]
+    fn idempotent_creates() {
+        let code = RestCompiler::new(idempotent::MODELS.to_vec()).compile_generated_code();
+
+        syn::parse_file(&code).unwrap();
+        assert!(code.contains("pub mod idempotency"));
+        assert_eq!(
+            code.matches("idempotency : super :: idempotency :: IdempotencyKey ,")
+                .count(),
+            2
+        );
+        assert!(code.contains(
+            "let body : :: schema :: Order = super :: idempotency :: parse_body (body) ?"
+        ));
+        assert!(code.contains(":: database :: order :: Entity :: insert_idempotent (txn , key , & request_hash , idempotency . ttl , active_model ,)"));
+        assert!(code.contains(". map_err (super :: idempotency :: error) ?"));
+
+        // Creates of other tables take their body as it is
+        let code = RestCompiler::new(MODELS.to_vec()).compile_generated_code();
+        assert!(!code.contains("idempotency"));
+    }
+
+    #[test]
+    fn trees() {
+        let code = RestCompiler::new(tree::MODELS.to_vec()).compile_generated_code();
+
+        syn::parse_file(&code).unwrap();
+        assert!(code.contains(
+            r#"route ("/categories/:id" , :: axum :: routing :: get (category :: find)"#
+        ));
+        assert!(
+            code.contains(r#"route ("/comments" , :: axum :: routing :: get (comment :: list)"#)
+        );
+    }
+
+    #[test]
+    fn plural_paths() {
+        let code = RestCompiler::new(with_plurals(MODELS.to_vec(), &[("employee", "staff")]))
+            .compile_generated_code();
+        assert!(code.contains(r#"route ("/staff" , :: axum :: routing :: get (employee :: list)"#));
+        assert!(
+            code.contains(r#"route ("/products" , :: axum :: routing :: get (product :: list)"#)
+        );
+        assert!(!code.contains(r#""/employees""#));
+
+        let compiler = RestCompiler::new(with_plurals(MODELS.to_vec(), &[("employee", "staff")]));
+        let plurals = compiler.table_plurals();
+        assert_eq!(plurals.len(), 3);
+        assert!(plurals.contains(&("employee".to_string(), "staff".to_string())));
+        assert!(plurals.contains(&("event".to_string(), "events".to_string())));
+        assert!(RestCompiler::new(MODELS.to_vec())
+            .table_plurals()
+            .is_empty());
+    }
+
+    #[test]
+    fn routes() {
+        let code = RestCompiler::new(MODELS.to_vec()).compile_generated_code();
+
+        assert!(code.contains(r#"route ("/products" , :: axum :: routing :: get (product :: list) . post (product :: create))"#));
+        assert!(code.contains(r#"route ("/events" , :: axum :: routing :: get (event :: list))"#));
+        assert!(
+            code.contains(r#"route ("/events/:id" , :: axum :: routing :: get (event :: find))"#)
+        );
+        assert!(code.contains(":: axum :: Json < :: schema :: NewProduct >"));
+        assert!(code.contains(r#"field : "description""#));
+        assert!(code.contains(":: database :: tx :: with_txn (& db"));
+    }
+
+    #[test]
+    fn health() {
+        let code = RestCompiler::new(MODELS.to_vec()).compile_generated_code();
+
+        assert!(code.contains(r#"route ("/healthz" , :: axum :: routing :: get (healthz))"#));
+        assert!(code.contains(r#"route ("/readyz" , :: axum :: routing :: get (readyz))"#));
+        assert!(code.contains("router_with_health (db , cursor_key , :: database :: health :: HealthChecks :: default ())"));
+        assert!(code.contains("StatusCode::SERVICE_UNAVAILABLE"));
+    }
+
+    #[test]
+    fn request_logging() {
+        let code = RestCompiler::new(MODELS.to_vec()).compile_generated_code();
+
+        assert!(code.contains(r#"pub const SENSITIVE_FIELDS : & [& str] = & ["name"] ;"#));
+        assert!(code.contains("impl RequestLogging for ::axum::Router"));
+        assert!(code.contains("redact(&mut value, fields);"));
+    }
+
+    #[test]
+    fn request_metrics() {
+        let code = RestCompiler::new(MODELS.to_vec()).compile_generated_code();
+
+        assert!(code.contains("#[cfg(feature = \"metrics\")]\npub mod metrics {"));
+        assert!(code.contains(
+            r#"& ["/healthz" , "/readyz" , "/products/:id/translations/:locale" , "/products" , "/products/:id" , "/products/summaries" , "/products/prices" , "/events" , "/events/:id" , "/employees" , "/employees/:id"] ;"#
+        ));
+        assert!(code.contains("impl RequestMetrics for ::axum::Router"));
+    }
+
+    #[test]
+    fn patch() {
+        let code = RestCompiler::new(MODELS.to_vec()).compile_generated_code();
+
+        assert!(code.contains(". patch (product :: patch)"));
+        assert!(code.contains(":: axum :: Json < :: database :: patch :: ProductPatch >"));
+        assert!(code
+            .contains("body . description . as_ref () . and_then (| value | value . as_deref ())"));
+    }
+
+    #[test]
+    fn versioned_update() {
+        let code = RestCompiler::new(MODELS.to_vec()).compile_generated_code();
+
+        assert!(code
+            .contains(":: database :: product :: Entity :: update_versioned (txn , active_model)"));
+        assert!(code.contains(". map_err (super :: versioned_update_error)"));
+        assert!(code.contains(
+            "active_model . version = :: database :: sea_orm :: ActiveValue :: unset ()"
+        ));
+    }
+
+    #[test]
+    fn outbox_events() {
+        let code = RestCompiler::new(MODELS.to_vec()).compile_generated_code();
+
+        assert!(code.contains(":: database :: product :: Entity :: record_event (txn , :: database :: outbox :: EventKind :: Created , model ,)"));
+        assert!(code.contains(":: database :: product :: Entity :: record_event (txn , :: database :: outbox :: EventKind :: Deleted , model ,)"));
+        // Versioned updates record their own events
+        assert!(!code.contains(":: database :: outbox :: EventKind :: Updated"));
+    }
+
+    #[test]
+    fn temporal_history() {
+        let code = RestCompiler::new(temporal::MODELS.to_vec()).compile_generated_code();
+        assert!(!code.contains("record_history"));
+
+        let code = RestCompiler::new(with_temporal(temporal::MODELS.to_vec(), Temporal::Portable))
+            .compile_generated_code();
+        syn::parse_file(&code).unwrap();
+        assert!(code.contains(":: database :: contract :: Entity :: record_history (txn , :: database :: temporal :: Operation :: Update , previous ,)"));
+        assert!(code.contains(":: database :: contract :: Entity :: record_history (txn , :: database :: temporal :: Operation :: Delete , previous ,)"));
+        assert!(code.contains("if let Some (previous) = & model {"));
+    }
+
+    #[test]
+    fn localized() {
+        let code = RestCompiler::new(MODELS.to_vec()).compile_generated_code();
+
+        assert!(code.contains("pub struct LocaleQuery"));
+        assert!(code.contains(r#"route ("/products/:id/translations/:locale" , :: axum :: routing :: put (product :: translate))"#));
+        assert!(code.contains("Some (locale) => :: database :: product :: Entity :: find_localized (& db , id , & locale) . await"));
+        assert!(
+            code.contains(":: database :: product :: Entity :: localize (& db , items , & locale)")
+        );
+        assert!(code.contains(r#"const REQUIRED_FIELDS : & [& str] = & ["name"] ;"#));
+        assert_eq!(
+            code.matches("headers : :: axum :: http :: HeaderMap")
+                .count(),
+            2
+        );
+
+        let models = MODELS
+            .iter()
+            .filter(|model| model.name == "Event")
+            .cloned()
+            .collect();
+        let code = RestCompiler::new(models).compile_generated_code();
+
+        assert!(!code.contains("LocaleQuery"));
+        assert!(!code.contains("translate"));
+    }
+
+    #[test]
+    fn optional_insert() {
+        let code = RestCompiler::new(MODELS.to_vec()).compile_generated_code();
+
+        assert!(code.contains("validate_update (& body) ?"));
+        assert!(code.contains(r#"if body . price . is_none () { errors . push (super :: FieldError { field : "price" , message : "is required when updating" . to_string () , }) ; }"#));
+
+        let models = MODELS
+            .iter()
+            .filter(|model| model.name != "NewProduct")
+            .cloned()
+            .collect();
+        let code = RestCompiler::new(models).compile_generated_code();
+
+        assert!(code.contains("status : body . status . map_or_else (:: database :: sea_orm :: ActiveValue :: unset , :: database :: sea_orm :: Set ,)"));
+        assert!(code.contains(r#"if body . status . is_none ()"#));
+        assert!(!code.contains(r#"if body . description . is_none ()"#));
+    }
+
+    #[test]
+    fn computed() {
+        let models = MODELS
+            .iter()
+            .filter(|model| model.name != "NewProduct")
+            .cloned()
+            .collect();
+        let code = RestCompiler::new(models).compile_generated_code();
+
+        assert!(code.contains(":: axum :: Json < :: schema :: Product >"));
+        assert!(code.contains("price : :: database :: sea_orm :: IntoActiveValue :: into_active_value (body . price . 0) . into ()"));
+        assert!(!code.contains("body . price_cents"));
+    }
+
+    #[test]
+    fn projections() {
+        let code = RestCompiler::new(MODELS.to_vec()).compile_generated_code();
+
+        assert!(code.contains(r#"route ("/products/summaries" , :: axum :: routing :: get (product :: list_summaries))"#));
+        assert!(code.contains(
+            r#"route ("/products/prices" , :: axum :: routing :: get (product :: list_prices))"#
+        ));
+        assert!(code.contains(
+            ":: axum :: Json < :: database :: cursor :: Page < :: database :: projection :: ProductSummary >>"
+        ));
+        assert!(code.contains(
+            ":: database :: product :: Entity :: list_summaries (& db , & key , & args)"
+        ));
+        // Projection routes come after the item routes, so they are matched before `/products/:id`
+        let item_route = code.find(r#"route ("/products/:id""#).unwrap();
+        assert!(code.find(r#"route ("/products/summaries""#).unwrap() > item_route);
+    }
+
+    #[test]
+    fn scopes() {
+        let code = RestCompiler::new(MODELS.to_vec()).compile_generated_code();
+
+        assert!(code.contains(
+            ":: axum :: extract :: Query (scopes) : :: axum :: extract :: Query < :: database :: scope :: ProductScopeArgs > ,"
+        ));
+        assert!(code.contains(
+            ":: database :: product :: Entity :: page_of (& db , scopes . apply (:: database :: product :: Entity :: find ()) , & key , & args ,)"
+        ));
+        // Tables without scopes are paged as a whole
+        assert!(code.contains(":: database :: event :: Entity :: page (& db , & key , & args)"));
+    }
+
+    #[test]
+    fn authorize() {
+        let code = RestCompiler::new(MODELS.to_vec()).compile_generated_code();
+
+        assert!(code.contains("pub trait Authorizer: Send + Sync + 'static"));
+        assert!(code.contains(r#"auth . check ("product" , super :: authorize :: Operation :: Write , Some ("catalog:write")) ?"#));
+        assert!(code.contains(r#"auth . check ("product" , super :: authorize :: Operation :: Delete , Some ("admin")) ?"#));
+        // Reading products is public, and events allow every request by default
+        assert!(!code.contains("super :: authorize :: Operation :: Read"));
+        assert!(code.contains("async fn authorize_product ()"));
+
+        let code = RestCompiler::new(with_default_authorization(
+            MODELS.to_vec(),
+            DefaultAuthorization::Deny,
+        ))
+        .compile_generated_code();
+
+        assert!(code.contains(
+            r#"auth . check ("event" , super :: authorize :: Operation :: Read , None) ?"#
+        ));
+        assert!(
+            !code.contains(r#"auth . check ("product" , super :: authorize :: Operation :: Read"#)
+        );
+    }
+
+    #[test]
+    fn documented_code() {
+        let schemas = vec![
+            MODELS.to_vec(),
+            with_default_authorization(MODELS.to_vec(), DefaultAuthorization::Deny),
+            with_temporal(temporal::MODELS.to_vec(), Temporal::Portable),
+            tree::MODELS.to_vec(),
+        ];
+
+        for models in schemas {
+            let code = RestCompiler::new(models).compile_generated_code();
+
+            assert_eq!(undocumented_items(&code), Vec::<String>::new());
+        }
+    }
+}
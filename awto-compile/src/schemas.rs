@@ -0,0 +1,275 @@
+//! Merging of the models of several schema packages into a single schema.
+//!
+//! Apps whose models are split across schema packages, such as `schema-billing` and `schema-catalog`,
+//! compile the models of every package together. Each merged model records the package it was declared in,
+//! so generated code refers to its struct in that package.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use awto::schema::{Model, ModelOrigin, Role};
+use heck::{CamelCase, SnakeCase};
+
+use crate::error::Error;
+
+/// Handling of models declared with the same name in several schema packages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Duplicates {
+    /// Merging fails, naming both packages.
+    Error,
+    /// Models are prefixed with the [`namespace`] of their package, such as `BillingInvoice`,
+    /// and their tables likewise, such as `billing_invoice`.
+    Namespace,
+}
+
+/// Returns the prefix of the namespaced models of `package`, its name in camel case without a `schema` prefix
+/// or suffix, such as `Billing` for `schema-billing`.
+pub fn namespace(package: &str) -> String {
+    let snake = package.to_snake_case();
+    let name = snake
+        .strip_prefix("schema_")
+        .or_else(|| snake.strip_suffix("_schema"))
+        .unwrap_or(&snake);
+
+    name.to_camel_case()
+}
+
+/// Merges the models of each schema package of `packages` into a single schema, in order.
+///
+/// Every model records its package as its [`ModelOrigin`]. Models declared in several packages are handled as
+/// `duplicates` says, and the columns referencing their tables are renamed along with them. Columns must reference
+/// tables of a package, as references across packages only resolve once the packages are merged.
+pub fn merge_schemas(
+    packages: &[(&str, &[Model])],
+    duplicates: Duplicates,
+) -> Result<Vec<Model>, Error> {
+    let mut declared: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for (package, models) in packages {
+        for model in models.iter() {
+            declared
+                .entry(model.name.as_str())
+                .or_default()
+                .push(package);
+        }
+    }
+    let duplicated: BTreeSet<&str> = declared
+        .iter()
+        .filter(|(_, packages)| packages.len() > 1)
+        .map(|(name, _)| *name)
+        .collect();
+    if let (Duplicates::Error, Some(name)) = (duplicates, duplicated.iter().next()) {
+        return Err(Error::DuplicateModel {
+            name: name.to_string(),
+            first: declared[name][0].to_string(),
+            second: declared[name][1].to_string(),
+        });
+    }
+
+    // Tables of namespaced models are renamed within their own package
+    let mut renamed: BTreeMap<(&str, String), String> = BTreeMap::new();
+    for (package, models) in packages {
+        for model in models
+            .iter()
+            .filter(|model| duplicated.contains(model.name.as_str()))
+        {
+            if let Some(table) = database_table(model) {
+                let name = format!("{}_{}", namespace(package).to_snake_case(), table);
+                renamed.insert((package, table.to_string()), name);
+            }
+        }
+    }
+    let mut tables: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for (package, models) in packages {
+        for table in models.iter().filter_map(database_table) {
+            tables.entry(table).or_default().push(package);
+        }
+    }
+
+    let mut merged = Vec::new();
+    for (package, models) in packages {
+        for model in models.iter() {
+            let mut model = model.clone();
+            model.origin = Some(ModelOrigin {
+                package: package.to_string(),
+                name: model.name.clone(),
+            });
+            if duplicated.contains(model.name.as_str()) {
+                model.name = format!("{}{}", namespace(package), model.name);
+            }
+
+            for role in &mut model.roles {
+                let table = match role {
+                    Role::DatabaseTable(table) | Role::DatabaseSubTable(table) => table,
+                    Role::ProtobufMessage(_) => continue,
+                };
+                if let Some(name) = renamed.get(&(*package, table.name.clone())) {
+                    table.name = name.clone();
+                }
+                for column in &mut table.columns {
+                    let (references, _) = match &mut column.references {
+                        Some(references) => references,
+                        None => continue,
+                    };
+                    if let Some(name) = renamed.get(&(*package, references.clone())) {
+                        *references = name.clone();
+                        continue;
+                    }
+                    match tables.get(references.as_str()).map(Vec::len) {
+                        Some(1) => {
+                            // Tables of namespaced models are renamed for other packages when a single package declares them
+                            let owner = tables[references.as_str()][0];
+                            if let Some(name) = renamed.get(&(owner, references.clone())) {
+                                *references = name.clone();
+                            }
+                        }
+                        Some(_) => {
+                            return Err(Error::AmbiguousReference {
+                                column: column.name.clone(),
+                                model: model.name.clone(),
+                                table: references.clone(),
+                            })
+                        }
+                        None => {
+                            return Err(Error::UnresolvedReference {
+                                column: column.name.clone(),
+                                model: model.name.clone(),
+                                table: references.clone(),
+                            })
+                        }
+                    }
+                }
+            }
+            merged.push(model);
+        }
+    }
+
+    Ok(merged)
+}
+
+/// Returns the name of the table of `model`, when it is a database table.
+fn database_table(model: &Model) -> Option<&str> {
+    model.roles.iter().find_map(|role| match role {
+        Role::DatabaseTable(table) => Some(table.name.as_str()),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use awto::tests_cfg::MODELS;
+
+    use super::*;
+
+    fn table(model: &Model) -> &awto::database::DatabaseTable {
+        model
+            .roles
+            .iter()
+            .find_map(|role| match role {
+                Role::DatabaseTable(table) | Role::DatabaseSubTable(table) => Some(table),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn namespaces() {
+        assert_eq!(namespace("schema-billing"), "Billing");
+        assert_eq!(namespace("catalog_schema"), "Catalog");
+        assert_eq!(namespace("inventory"), "Inventory");
+        assert_eq!(namespace("schema"), "Schema");
+    }
+
+    #[test]
+    fn merged_packages() {
+        let (staff, catalog): (Vec<_>, Vec<_>) = MODELS
+            .iter()
+            .cloned()
+            .partition(|model| model.name == "Employee");
+        let mut staff = staff;
+        if let Role::DatabaseTable(table) = &mut staff[0].roles[0] {
+            let manager_id = table
+                .columns
+                .iter()
+                .find(|column| column.name == "manager_id")
+                .unwrap();
+            table.columns.push(awto::database::DatabaseColumn {
+                name: "product_id".to_string(),
+                references: Some(("product".to_string(), "id".to_string())),
+                reference_constraint: None,
+                ..manager_id.clone()
+            });
+        }
+
+        let merged = merge_schemas(
+            &[("schema-catalog", &catalog), ("schema-staff", &staff)],
+            Duplicates::Error,
+        )
+        .unwrap();
+        assert_eq!(merged.len(), MODELS.len());
+        assert_eq!(
+            merged[0].origin,
+            Some(ModelOrigin {
+                package: "schema-catalog".to_string(),
+                name: "Product".to_string(),
+            })
+        );
+        assert_eq!(
+            merged.last().unwrap().origin.as_ref().unwrap().package,
+            "schema-staff"
+        );
+        assert_eq!(
+            crate::util::schema_ty(&merged[0]).to_string(),
+            ":: schema_catalog :: Product"
+        );
+        assert_eq!(table(merged.last().unwrap()), table(&staff[0]));
+
+        assert_eq!(
+            merge_schemas(&[("schema-staff", &staff)], Duplicates::Error).unwrap_err().to_string(),
+            "`product_id` of `Employee` references the `product` table, which no schema package declares"
+        );
+    }
+
+    #[test]
+    fn duplicate_models() {
+        let packages = [
+            ("schema-billing", &MODELS[..]),
+            ("schema-catalog", &MODELS[..]),
+        ];
+        assert_eq!(
+            merge_schemas(&packages, Duplicates::Error).unwrap_err().to_string(),
+            "model `Employee` is declared in both `schema-billing` and `schema-catalog`, rename one of them \
+            or set `duplicates = \"namespace\"` in the [schema] section of awto.toml"
+        );
+
+        let merged = merge_schemas(&packages, Duplicates::Namespace).unwrap();
+        let product = merged
+            .iter()
+            .find(|model| model.name == "BillingProduct")
+            .unwrap();
+        assert_eq!(table(product).name, "billing_product");
+        assert_eq!(product.origin.as_ref().unwrap().name, "Product");
+        assert_eq!(
+            crate::util::schema_ty(product).to_string(),
+            ":: schema_billing :: Product"
+        );
+        let new_product = merged
+            .iter()
+            .find(|model| model.name == "CatalogNewProduct")
+            .unwrap();
+        assert_eq!(table(new_product).name, "catalog_product");
+        let employee = merged
+            .iter()
+            .find(|model| model.name == "CatalogEmployee")
+            .unwrap();
+        let references: BTreeSet<_> = table(employee)
+            .columns
+            .iter()
+            .filter_map(|column| column.references.as_ref())
+            .map(|(table, _)| table.as_str())
+            .collect();
+        assert!(
+            references.iter().all(|table| table.starts_with("catalog_")),
+            "{:?}",
+            references
+        );
+    }
+}
@@ -0,0 +1,911 @@
+//! Conditions of the scopes of tables, parsed from sql into queries of the columns of the tables.
+//!
+//! A condition is a small subset of sql: comparisons, `AND`, `OR` and `NOT`, `IS [NOT] NULL`, `[NOT] IN (...)`,
+//! `[NOT] LIKE`, arithmetic, literals, `now()`, `current_date`, `current_timestamp`, `interval '...'` and the
+//! parameters of the scope as `:name`. Conditions are parsed when generating the database package, so a typo in a
+//! column fails generation rather than the queries of the scope.
+
+use std::{iter::Peekable, str::CharIndices};
+
+use awto::{
+    database::{DatabaseScope, DatabaseTable},
+    schema::{Model, Role},
+};
+use proc_macro2::{Ident, Literal, TokenStream};
+use quote::quote;
+
+use crate::{
+    error::Error,
+    util::{column_variant_ident, rust_ident},
+};
+
+/// Methods of generated entities, which scopes cannot be named as.
+const RESERVED_SCOPE_NAMES: [&str; 24] = [
+    "as_of",
+    "apply_patch",
+    "belongs_to",
+    "changes",
+    "delete",
+    "delete_by_id",
+    "delete_many",
+    "export_csv",
+    "export_jsonl",
+    "find",
+    "find_by_id",
+    "find_localized",
+    "has_many",
+    "has_one",
+    "history",
+    "import_csv",
+    "import_jsonl",
+    "insert",
+    "localize",
+    "page",
+    "search",
+    "table_name",
+    "update",
+    "upsert",
+];
+
+/// Prefixes of the names of generated entity methods, which scopes cannot start with.
+const RESERVED_SCOPE_PREFIXES: [&str; 6] =
+    ["find_", "insert_", "list_", "load_by_", "page_", "upsert_"];
+
+/// A parsed scope condition.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScopeExpr {
+    Column(String),
+    Param(String),
+    Text(String),
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    /// A value of the database, such as `now()`.
+    Function(ScopeFunction),
+    /// An interval such as `interval '1 day'`, of which the text is kept.
+    Interval(String),
+    Binary(Box<ScopeExpr>, BinaryOp, Box<ScopeExpr>),
+    Not(Box<ScopeExpr>),
+    IsNull {
+        expr: Box<ScopeExpr>,
+        negated: bool,
+    },
+    /// `expr IN (...)` of a list of literals and parameters.
+    InList {
+        expr: Box<ScopeExpr>,
+        list: Vec<ScopeExpr>,
+        negated: bool,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScopeFunction {
+    Now,
+    CurrentDate,
+    CurrentTimestamp,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryOp {
+    And,
+    Or,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Like,
+    NotLike,
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl BinaryOp {
+    /// Returns whether the operator combines conditions, rather than values.
+    fn is_logical(self) -> bool {
+        matches!(self, BinaryOp::And | BinaryOp::Or)
+    }
+}
+
+impl ScopeExpr {
+    /// Returns whether the expression is a condition combined with `AND`, `OR` or `NOT`.
+    fn is_logical(&self) -> bool {
+        match self {
+            ScopeExpr::Binary(_, op, _) => op.is_logical(),
+            ScopeExpr::Not(_) => true,
+            _ => false,
+        }
+    }
+
+    fn visit(&self, f: &mut impl FnMut(&ScopeExpr)) {
+        f(self);
+        match self {
+            ScopeExpr::Binary(left, _, right) => {
+                left.visit(f);
+                right.visit(f);
+            }
+            ScopeExpr::Not(expr) | ScopeExpr::IsNull { expr, .. } => expr.visit(f),
+            ScopeExpr::InList { expr, list, .. } => {
+                expr.visit(f);
+                list.iter().for_each(|item| item.visit(f));
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Text(String),
+    Number(String),
+    Param(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    Comma,
+}
+
+fn tokenize(sql: &str) -> Result<Vec<Token>, String> {
+    fn take_while(chars: &mut Peekable<CharIndices>, f: impl Fn(char) -> bool) -> String {
+        let mut taken = String::new();
+        while let Some(&(_, c)) = chars.peek() {
+            if !f(c) {
+                break;
+            }
+            taken.push(c);
+            chars.next();
+        }
+        taken
+    }
+
+    let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut tokens = Vec::new();
+    let mut chars = sql.char_indices().peekable();
+    while let Some(&(_, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            tokens.push(Token::Ident(take_while(&mut chars, is_ident)));
+        } else if c.is_ascii_digit() {
+            tokens.push(Token::Number(take_while(&mut chars, |c| {
+                c.is_ascii_digit() || c == '.'
+            })));
+        } else if c == ':' {
+            chars.next();
+            let name = take_while(&mut chars, is_ident);
+            if name.is_empty() {
+                return Err("expected the name of a parameter after `:`".to_string());
+            }
+            tokens.push(Token::Param(name));
+        } else if c == '\'' || c == '"' {
+            chars.next();
+            let mut text = String::new();
+            loop {
+                match chars.next() {
+                    // Quotes are escaped by doubling them
+                    Some((_, quote)) if quote == c => match chars.peek() {
+                        Some(&(_, next)) if next == c => {
+                            text.push(c);
+                            chars.next();
+                        }
+                        _ => break,
+                    },
+                    Some((_, other)) => text.push(other),
+                    None => {
+                        return Err(format!(
+                            "unterminated {}",
+                            if c == '\'' { "string" } else { "identifier" }
+                        ))
+                    }
+                }
+            }
+            tokens.push(if c == '\'' {
+                Token::Text(text)
+            } else {
+                Token::Ident(text)
+            });
+        } else {
+            chars.next();
+            let next = chars.peek().map(|&(_, next)| next);
+            let token = match (c, next) {
+                ('(', _) => Token::LParen,
+                (')', _) => Token::RParen,
+                (',', _) => Token::Comma,
+                ('<', Some('=')) | ('>', Some('=')) | ('<', Some('>')) | ('!', Some('=')) => {
+                    chars.next();
+                    Token::Op(match (c, next) {
+                        ('<', Some('=')) => "<=",
+                        ('>', Some('=')) => ">=",
+                        _ => "<>",
+                    })
+                }
+                ('=', _) => Token::Op("="),
+                ('<', _) => Token::Op("<"),
+                ('>', _) => Token::Op(">"),
+                ('+', _) => Token::Op("+"),
+                ('-', _) => Token::Op("-"),
+                ('*', _) => Token::Op("*"),
+                ('/', _) => Token::Op("/"),
+                _ => return Err(format!("unexpected `{}`", c)),
+            };
+            tokens.push(token);
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    /// Consumes the keyword `keyword` when it is next.
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let found =
+            matches!(self.peek(), Some(Token::Ident(ident)) if ident.eq_ignore_ascii_case(keyword));
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    /// Returns whether the keyword following the next token is `keyword`, as in `NOT IN`.
+    fn peek_second_keyword(&self, keyword: &str) -> bool {
+        matches!(self.tokens.get(self.pos + 1), Some(Token::Ident(ident)) if ident.eq_ignore_ascii_case(keyword))
+    }
+
+    fn expect(&mut self, expected: Token, describe: &str) -> Result<(), String> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            _ => Err(format!("expected {}", describe)),
+        }
+    }
+
+    fn or(&mut self) -> Result<ScopeExpr, String> {
+        let mut expr = self.and()?;
+        while self.keyword("or") {
+            expr = ScopeExpr::Binary(Box::new(expr), BinaryOp::Or, Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<ScopeExpr, String> {
+        let mut expr = self.not()?;
+        while self.keyword("and") {
+            expr = ScopeExpr::Binary(Box::new(expr), BinaryOp::And, Box::new(self.not()?));
+        }
+        Ok(expr)
+    }
+
+    fn not(&mut self) -> Result<ScopeExpr, String> {
+        if self.keyword("not") {
+            return Ok(ScopeExpr::Not(Box::new(self.not()?)));
+        }
+        self.predicate()
+    }
+
+    fn predicate(&mut self) -> Result<ScopeExpr, String> {
+        let left = self.sum()?;
+        let comparison = match self.peek() {
+            Some(Token::Op("=")) => Some(BinaryOp::Eq),
+            Some(Token::Op("<>")) => Some(BinaryOp::Ne),
+            Some(Token::Op("<")) => Some(BinaryOp::Lt),
+            Some(Token::Op("<=")) => Some(BinaryOp::Le),
+            Some(Token::Op(">")) => Some(BinaryOp::Gt),
+            Some(Token::Op(">=")) => Some(BinaryOp::Ge),
+            _ => None,
+        };
+        if let Some(op) = comparison {
+            self.pos += 1;
+            return Ok(ScopeExpr::Binary(Box::new(left), op, Box::new(self.sum()?)));
+        }
+
+        if self.keyword("is") {
+            let negated = self.keyword("not");
+            if !self.keyword("null") {
+                return Err("expected `NULL` after `IS`".to_string());
+            }
+            return Ok(ScopeExpr::IsNull {
+                expr: Box::new(left),
+                negated,
+            });
+        }
+        let negated = matches!(self.peek(), Some(Token::Ident(ident)) if ident.eq_ignore_ascii_case("not"))
+            && (self.peek_second_keyword("in") || self.peek_second_keyword("like"));
+        if negated {
+            self.pos += 1;
+        }
+        if self.keyword("like") {
+            let op = if negated {
+                BinaryOp::NotLike
+            } else {
+                BinaryOp::Like
+            };
+            return Ok(ScopeExpr::Binary(Box::new(left), op, Box::new(self.sum()?)));
+        }
+        if self.keyword("in") {
+            self.expect(Token::LParen, "`(` after `IN`")?;
+            let mut list = Vec::new();
+            loop {
+                let item = self.unary()?;
+                if !matches!(
+                    item,
+                    ScopeExpr::Param(_)
+                        | ScopeExpr::Text(_)
+                        | ScopeExpr::Integer(_)
+                        | ScopeExpr::Float(_)
+                        | ScopeExpr::Bool(_)
+                ) {
+                    return Err(
+                        "the list of `IN` can only hold literals and parameters".to_string()
+                    );
+                }
+                list.push(item);
+                match self.next() {
+                    Some(Token::Comma) => continue,
+                    Some(Token::RParen) => break,
+                    _ => return Err("expected `,` or `)` in the list of `IN`".to_string()),
+                }
+            }
+            return Ok(ScopeExpr::InList {
+                expr: Box::new(left),
+                list,
+                negated,
+            });
+        }
+
+        Ok(left)
+    }
+
+    fn sum(&mut self) -> Result<ScopeExpr, String> {
+        let mut expr = self.product()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Op("+")) => BinaryOp::Add,
+                Some(Token::Op("-")) => BinaryOp::Sub,
+                _ => return Ok(expr),
+            };
+            self.pos += 1;
+            expr = ScopeExpr::Binary(Box::new(expr), op, Box::new(self.product()?));
+        }
+    }
+
+    fn product(&mut self) -> Result<ScopeExpr, String> {
+        let mut expr = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Op("*")) => BinaryOp::Mul,
+                Some(Token::Op("/")) => BinaryOp::Div,
+                _ => return Ok(expr),
+            };
+            self.pos += 1;
+            expr = ScopeExpr::Binary(Box::new(expr), op, Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<ScopeExpr, String> {
+        if self.peek() == Some(&Token::Op("-")) {
+            self.pos += 1;
+            return match self.next() {
+                Some(Token::Number(number)) => parse_number(&format!("-{}", number)),
+                _ => Err("`-` can only negate numbers".to_string()),
+            };
+        }
+
+        match self.next() {
+            Some(Token::LParen) => {
+                let expr = self.or()?;
+                self.expect(Token::RParen, "`)`")?;
+                Ok(expr)
+            }
+            Some(Token::Number(number)) => parse_number(&number),
+            Some(Token::Text(text)) => Ok(ScopeExpr::Text(text)),
+            Some(Token::Param(name)) => Ok(ScopeExpr::Param(name)),
+            Some(Token::Ident(ident)) => {
+                let keyword = ident.to_ascii_lowercase();
+                if self.peek() == Some(&Token::LParen) {
+                    if keyword != "now" {
+                        return Err(format!(
+                            "unsupported function `{}`, expected `now()`",
+                            ident
+                        ));
+                    }
+                    self.pos += 1;
+                    self.expect(Token::RParen, "`)`")?;
+                    return Ok(ScopeExpr::Function(ScopeFunction::Now));
+                }
+                match keyword.as_str() {
+                    "true" => Ok(ScopeExpr::Bool(true)),
+                    "false" => Ok(ScopeExpr::Bool(false)),
+                    "current_date" => Ok(ScopeExpr::Function(ScopeFunction::CurrentDate)),
+                    "current_timestamp" => Ok(ScopeExpr::Function(ScopeFunction::CurrentTimestamp)),
+                    "interval" => match self.next() {
+                        Some(Token::Text(interval))
+                            if !interval.is_empty()
+                                && interval
+                                    .chars()
+                                    .all(|c| c.is_ascii_alphanumeric() || " .:-".contains(c)) =>
+                        {
+                            Ok(ScopeExpr::Interval(interval))
+                        }
+                        _ => Err("expected an interval such as `interval '1 day'`".to_string()),
+                    },
+                    "and" | "or" | "not" | "is" | "in" | "like" | "null" => {
+                        Err(format!("unexpected `{}`", ident))
+                    }
+                    _ => Ok(ScopeExpr::Column(ident)),
+                }
+            }
+            Some(Token::Op(op)) => Err(format!("unexpected `{}`", op)),
+            Some(Token::RParen) => Err("unexpected `)`".to_string()),
+            Some(Token::Comma) => Err("unexpected `,`".to_string()),
+            None => Err("unexpected end of the condition".to_string()),
+        }
+    }
+}
+
+fn parse_number(number: &str) -> Result<ScopeExpr, String> {
+    if number.contains('.') {
+        number
+            .parse()
+            .map(ScopeExpr::Float)
+            .map_err(|_| format!("invalid number `{}`", number))
+    } else {
+        number
+            .parse()
+            .map(ScopeExpr::Integer)
+            .map_err(|_| format!("invalid number `{}`", number))
+    }
+}
+
+/// Parses the condition of `scope` of `table`, checking that its columns are columns of the table and its
+/// parameters are the parameters of the scope, each of which it uses.
+pub fn parse_scope(table: &DatabaseTable, scope: &DatabaseScope) -> Result<ScopeExpr, String> {
+    let mut parser = Parser {
+        tokens: tokenize(&scope.condition)?,
+        pos: 0,
+    };
+    let expr = parser.or()?;
+    if let Some(token) = parser.peek() {
+        return Err(format!("unexpected {:?} after the condition", token));
+    }
+
+    let mut errors = Vec::new();
+    let mut used_params = Vec::new();
+    expr.visit(&mut |expr| match expr {
+        ScopeExpr::Column(column) if !table.columns.iter().any(|other| other.name == *column) => {
+            errors.push(format!("unknown column `{}`", column));
+        }
+        ScopeExpr::Param(param) if !scope.params.iter().any(|other| other.name == *param) => {
+            errors.push(format!("unknown parameter `:{}`", param));
+        }
+        ScopeExpr::Param(param) => used_params.push(param.clone()),
+        ScopeExpr::Binary(left, op, right)
+            if !op.is_logical() && (left.is_logical() || right.is_logical()) =>
+        {
+            errors.push("conditions can only be combined with `AND`, `OR` and `NOT`".to_string());
+        }
+        _ => {}
+    });
+    if let Some(unused) = scope
+        .params
+        .iter()
+        .find(|param| !used_params.contains(&param.name))
+    {
+        errors.push(format!("parameter `{}` is not used", unused.name));
+    }
+    match errors.into_iter().next() {
+        Some(error) => Err(error),
+        None => Ok(expr),
+    }
+}
+
+/// Checks that the scopes of the tables of `models` have valid conditions and names which do not collide
+/// with the methods of their entities or with each other.
+pub fn check_scopes(models: &[Model]) -> Result<(), Error> {
+    for model in models {
+        for role in &model.roles {
+            let table = match role {
+                Role::DatabaseTable(table) => table,
+                _ => continue,
+            };
+            let mut args = Vec::new();
+            for scope in &table.scopes {
+                let invalid = |reason: String| Error::InvalidScope {
+                    scope: scope.name.clone(),
+                    table: table.name.clone(),
+                    reason,
+                };
+                if RESERVED_SCOPE_NAMES.contains(&scope.name.as_str())
+                    || RESERVED_SCOPE_PREFIXES
+                        .iter()
+                        .any(|prefix| scope.name.starts_with(prefix))
+                    || scope.name.ends_with("_condition")
+                {
+                    return Err(invalid(
+                        "its name is taken by a method of the entity, rename it".to_string(),
+                    ));
+                }
+                for param in &scope.params {
+                    if scope_param_ty(&param.ty).is_none() {
+                        return Err(invalid(format!(
+                            "parameter `{}` has unsupported type `{}`",
+                            param.name, param.ty
+                        )));
+                    }
+                }
+                // Each scope is a field of the arguments of list endpoints, or one per parameter
+                for arg in scope_arg_names(scope) {
+                    if args.contains(&arg) {
+                        return Err(invalid(format!(
+                            "its argument `{}` is taken by another scope, rename it",
+                            arg
+                        )));
+                    }
+                    args.push(arg);
+                }
+                parse_scope(table, scope).map_err(invalid)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the names of the query arguments selecting `scope` in list endpoints: the name of the scope,
+/// or `<scope>_<param>` for each of its parameters.
+pub fn scope_arg_names(scope: &DatabaseScope) -> Vec<String> {
+    if scope.params.is_empty() {
+        vec![scope.name.clone()]
+    } else {
+        scope
+            .params
+            .iter()
+            .map(|param| format!("{}_{}", scope.name, param.name))
+            .collect()
+    }
+}
+
+/// Returns the type of a scope parameter of type `ty`, one of the types `#[awto(scope(...))]` accepts.
+pub fn scope_param_ty(ty: &str) -> Option<TokenStream> {
+    Some(match ty {
+        "bool" => quote!(bool),
+        "i32" => quote!(i32),
+        "i64" => quote!(i64),
+        "f64" => quote!(f64),
+        "String" => quote!(String),
+        "Uuid" => quote!(::uuid::Uuid),
+        "NaiveDate" => quote!(::chrono::NaiveDate),
+        "DateTime<Utc>" => quote!(::chrono::DateTime<::chrono::Utc>),
+        "DateTime<FixedOffset>" => quote!(::chrono::DateTime<::chrono::FixedOffset>),
+        _ => return None,
+    })
+}
+
+/// Compiles `expr` of a scope of the table of module `db_module_ident` into a `sea_orm::Condition`,
+/// with its parameters bound as `sea_query::Value`s of the same names.
+pub fn compile_scope_condition(db_module_ident: &Ident, expr: &ScopeExpr) -> TokenStream {
+    let condition = compile_expr(db_module_ident, expr);
+    if expr.is_logical() {
+        condition
+    } else {
+        quote!(::sea_orm::Condition::all().add(#condition))
+    }
+}
+
+fn compile_expr(db_module_ident: &Ident, expr: &ScopeExpr) -> TokenStream {
+    let compile = |expr: &ScopeExpr| compile_expr(db_module_ident, expr);
+    match expr {
+        ScopeExpr::Column(column) => {
+            let variant = column_variant_ident(column);
+            quote!(::sea_orm::sea_query::Expr::col(crate::#db_module_ident::Column::#variant).into_simple_expr())
+        }
+        ScopeExpr::Param(param) => {
+            let param = rust_ident(param);
+            quote!(::sea_orm::sea_query::SimpleExpr::Value(#param.clone()))
+        }
+        ScopeExpr::Text(_) | ScopeExpr::Integer(_) | ScopeExpr::Float(_) | ScopeExpr::Bool(_) => {
+            let value = compile_value(expr);
+            quote!(::sea_orm::sea_query::SimpleExpr::Value(#value))
+        }
+        ScopeExpr::Function(function) => {
+            let sql = match function {
+                ScopeFunction::Now => "now()",
+                ScopeFunction::CurrentDate => "CURRENT_DATE",
+                ScopeFunction::CurrentTimestamp => "CURRENT_TIMESTAMP",
+            };
+            quote!(::sea_orm::sea_query::Expr::cust(#sql))
+        }
+        ScopeExpr::Interval(interval) => {
+            let sql = format!("interval '{}'", interval);
+            quote!(::sea_orm::sea_query::Expr::cust(#sql))
+        }
+        ScopeExpr::Binary(left, op, right) if op.is_logical() => {
+            let combinator = match op {
+                BinaryOp::And => quote!(all),
+                _ => quote!(any),
+            };
+            let (left, right) = (compile(left), compile(right));
+            quote!(::sea_orm::Condition::#combinator().add(#left).add(#right))
+        }
+        ScopeExpr::Binary(left, op, right) => {
+            let op = match op {
+                BinaryOp::Eq => quote!(Equal),
+                BinaryOp::Ne => quote!(NotEqual),
+                BinaryOp::Lt => quote!(SmallerThan),
+                BinaryOp::Le => quote!(SmallerThanOrEqual),
+                BinaryOp::Gt => quote!(GreaterThan),
+                BinaryOp::Ge => quote!(GreaterThanOrEqual),
+                BinaryOp::Like => quote!(Like),
+                BinaryOp::NotLike => quote!(NotLike),
+                BinaryOp::Add => quote!(Add),
+                BinaryOp::Sub => quote!(Sub),
+                BinaryOp::Mul => quote!(Mul),
+                BinaryOp::Div => quote!(Div),
+                BinaryOp::And | BinaryOp::Or => unreachable!(),
+            };
+            let (left, right) = (compile(left), compile(right));
+            quote!(::sea_orm::sea_query::SimpleExpr::Binary(
+                Box::new(#left),
+                ::sea_orm::sea_query::BinOper::#op,
+                Box::new(#right),
+            ))
+        }
+        ScopeExpr::Not(expr) => {
+            let expr = compile(expr);
+            quote!(::sea_orm::Condition::all().add(#expr).not())
+        }
+        ScopeExpr::IsNull { expr, negated } => {
+            let expr = compile(expr);
+            let method = if *negated {
+                quote!(is_not_null)
+            } else {
+                quote!(is_null)
+            };
+            quote!(::sea_orm::sea_query::Expr::expr(#expr).#method())
+        }
+        ScopeExpr::InList {
+            expr,
+            list,
+            negated,
+        } => {
+            let expr = compile(expr);
+            let method = if *negated {
+                quote!(is_not_in)
+            } else {
+                quote!(is_in)
+            };
+            let values = list.iter().map(|item| match item {
+                ScopeExpr::Param(param) => {
+                    let param = rust_ident(param);
+                    quote!(#param.clone())
+                }
+                _ => compile_value(item),
+            });
+            quote!(::sea_orm::sea_query::Expr::expr(#expr).#method(vec![ #( #values ),* ]))
+        }
+    }
+}
+
+/// Compiles a literal of a condition into a `sea_query::Value`.
+fn compile_value(expr: &ScopeExpr) -> TokenStream {
+    let value = match expr {
+        ScopeExpr::Text(text) => quote!(#text),
+        ScopeExpr::Integer(integer) => {
+            let integer = Literal::i64_suffixed(*integer);
+            quote!(#integer)
+        }
+        ScopeExpr::Float(float) => {
+            let float = Literal::f64_suffixed(*float);
+            quote!(#float)
+        }
+        ScopeExpr::Bool(bool) => quote!(#bool),
+        _ => unreachable!("only literals are values"),
+    };
+
+    quote!(::sea_orm::sea_query::Value::from(#value))
+}
+
+#[cfg(test)]
+mod test {
+    use awto::{
+        database::{DatabaseScopeParam, IntoDatabaseTable},
+        tests_cfg::{Product, MODELS},
+    };
+
+    use super::*;
+
+    fn scope(condition: &str, params: &[&str]) -> DatabaseScope {
+        DatabaseScope {
+            name: "recent".to_string(),
+            params: params
+                .iter()
+                .map(|param| DatabaseScopeParam {
+                    name: param.to_string(),
+                    ty: "i64".to_string(),
+                })
+                .collect(),
+            condition: condition.to_string(),
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let table = Product::database_table();
+        let parse =
+            |condition: &str, params: &[&str]| parse_scope(&table, &scope(condition, params));
+
+        assert_eq!(
+            parse("created_at > now() - interval '1 day' * :days", &["days"]),
+            Ok(ScopeExpr::Binary(
+                Box::new(ScopeExpr::Column("created_at".to_string())),
+                BinaryOp::Gt,
+                Box::new(ScopeExpr::Binary(
+                    Box::new(ScopeExpr::Function(ScopeFunction::Now)),
+                    BinaryOp::Sub,
+                    Box::new(ScopeExpr::Binary(
+                        Box::new(ScopeExpr::Interval("1 day".to_string())),
+                        BinaryOp::Mul,
+                        Box::new(ScopeExpr::Param("days".to_string())),
+                    )),
+                )),
+            ))
+        );
+        assert_eq!(
+            parse(
+                "status = 'it''s' or not (price >= -1 and description is not null)",
+                &[]
+            ),
+            Ok(ScopeExpr::Binary(
+                Box::new(ScopeExpr::Binary(
+                    Box::new(ScopeExpr::Column("status".to_string())),
+                    BinaryOp::Eq,
+                    Box::new(ScopeExpr::Text("it's".to_string())),
+                )),
+                BinaryOp::Or,
+                Box::new(ScopeExpr::Not(Box::new(ScopeExpr::Binary(
+                    Box::new(ScopeExpr::Binary(
+                        Box::new(ScopeExpr::Column("price".to_string())),
+                        BinaryOp::Ge,
+                        Box::new(ScopeExpr::Integer(-1)),
+                    )),
+                    BinaryOp::And,
+                    Box::new(ScopeExpr::IsNull {
+                        expr: Box::new(ScopeExpr::Column("description".to_string())),
+                        negated: true,
+                    }),
+                )))),
+            ))
+        );
+        assert_eq!(
+            parse("status NOT IN ('draft', :status)", &["status"]),
+            Ok(ScopeExpr::InList {
+                expr: Box::new(ScopeExpr::Column("status".to_string())),
+                list: vec![
+                    ScopeExpr::Text("draft".to_string()),
+                    ScopeExpr::Param("status".to_string())
+                ],
+                negated: true,
+            })
+        );
+
+        assert_eq!(
+            parse("created > now()", &[]),
+            Err("unknown column `created`".to_string())
+        );
+        assert_eq!(
+            parse("price > :min", &[]),
+            Err("unknown parameter `:min`".to_string())
+        );
+        assert_eq!(
+            parse("price > 0", &["days"]),
+            Err("parameter `days` is not used".to_string())
+        );
+        assert_eq!(
+            parse("lower(name) = 'a'", &[]),
+            Err("unsupported function `lower`, expected `now()`".to_string())
+        );
+        assert_eq!(
+            parse("status = 'live", &[]),
+            Err("unterminated string".to_string())
+        );
+        assert_eq!(
+            parse("(price > 0 and version > 1) = true", &[]),
+            Err("conditions can only be combined with `AND`, `OR` and `NOT`".to_string())
+        );
+        assert!(parse("price > 0 price", &[]).is_err());
+    }
+
+    #[test]
+    fn compile() {
+        let table = Product::database_table();
+        let module = rust_ident(&table.name);
+        let live = compile_scope_condition(
+            &module,
+            &parse_scope(&table, &scope("status = 'live'", &[])).unwrap(),
+        );
+        assert_eq!(
+            live.to_string(),
+            quote!(
+                ::sea_orm::Condition::all().add(::sea_orm::sea_query::SimpleExpr::Binary(
+                    Box::new(
+                        ::sea_orm::sea_query::Expr::col(crate::product::Column::Status)
+                            .into_simple_expr()
+                    ),
+                    ::sea_orm::sea_query::BinOper::Equal,
+                    Box::new(::sea_orm::sea_query::SimpleExpr::Value(
+                        ::sea_orm::sea_query::Value::from("live")
+                    )),
+                ))
+            )
+            .to_string()
+        );
+
+        let recent = scope(
+            "created_at > now() - interval '1 day' * :days or status is null",
+            &["days"],
+        );
+        let recent =
+            compile_scope_condition(&module, &parse_scope(&table, &recent).unwrap()).to_string();
+        assert!(recent.starts_with(":: sea_orm :: Condition :: any ()"));
+        assert!(recent.contains(":: sea_orm :: sea_query :: Expr :: cust (\"interval '1 day'\")"));
+        assert!(recent.contains(":: sea_orm :: sea_query :: SimpleExpr :: Value (days . clone ())"));
+        assert!(recent.contains(". is_null ()"));
+    }
+
+    #[test]
+    fn check() {
+        check_scopes(&MODELS[..]).unwrap();
+
+        let mut model = MODELS
+            .iter()
+            .find(|model| model.name == "Product")
+            .unwrap()
+            .clone();
+        let mut set_scopes = |scopes: Vec<DatabaseScope>| {
+            for role in &mut model.roles {
+                if let Role::DatabaseTable(table) = role {
+                    table.scopes = scopes.clone();
+                }
+            }
+            check_scopes(&[model.clone()]).unwrap_err().to_string()
+        };
+        assert_eq!(
+            set_scopes(vec![scope("creatd_at > now()", &[])]),
+            "scope `recent` of table `product` is invalid: unknown column `creatd_at`"
+        );
+        assert_eq!(
+            set_scopes(vec![DatabaseScope {
+                name: "find".to_string(),
+                ..scope("price > 0", &[])
+            }]),
+            "scope `find` of table `product` is invalid: its name is taken by a method of the entity, rename it"
+        );
+        assert_eq!(
+            set_scopes(vec![
+                scope("created_at > now() - interval '1 day' * :days", &["days"]),
+                DatabaseScope {
+                    name: "recent_days".to_string(),
+                    ..scope("price > 0", &[])
+                },
+            ]),
+            "scope `recent_days` of table `product` is invalid: its argument `recent_days` is taken by another scope, rename it"
+        );
+    }
+}
@@ -0,0 +1,1255 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use awto::protobuf::{
+    ProtobufField, ProtobufMessage, ProtobufMethod, ProtobufService, ProtobufType,
+};
+use heck::{MixedCase, SnakeCase};
+use proc_macro2::{Literal, TokenStream};
+use quote::{format_ident, quote};
+
+use crate::{
+    api_docs::{ApiDocs, ApiItem},
+    naming::pluralize,
+    provenance::{Annotation, CommentStyle},
+    util::rust_ident,
+};
+
+/// Package of the generated protobuf file, prefixing the gRPC path of every method, unless set with
+/// [`TranscodingCompiler::with_package`].
+const PROTOBUF_PACKAGE: &str = "app";
+
+/// Prefixes of method names bound to a route of their resource, along with the HTTP method of the route.
+const VERBS: [(&str, HttpMethod); 6] = [
+    ("Get", HttpMethod::Get),
+    ("Find", HttpMethod::Get),
+    ("List", HttpMethod::Get),
+    ("Create", HttpMethod::Post),
+    ("Update", HttpMethod::Put),
+    ("Delete", HttpMethod::Delete),
+];
+
+/// Canonical JSON mapping of protobuf messages, and errors shared by every transcoded handler.
+const TRANSCODING_MODULE: &str = r#"
+use ::std::fmt;
+
+use ::axum::http::StatusCode;
+use ::serde_json::{Map, Value};
+
+use super::ApiError;
+
+/// Conversion of protobuf messages and their fields to and from the canonical JSON mapping of protobuf.
+pub trait ProtoJson: Sized {
+    /// Returns the JSON value of the message or field.
+    fn to_json(&self) -> Value;
+
+    /// Parses the JSON value of the field at `path`, such as `products.name`, or of a message when `path` is empty.
+    fn from_json(value: &Value, path: &str) -> Result<Self, JsonError>;
+}
+
+/// A JSON value which could not be parsed into a protobuf message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JsonError {
+    /// Path of the invalid field, such as `products.name`, or empty for the message itself.
+    pub path: String,
+    /// Why the value is invalid.
+    pub message: String,
+}
+
+impl JsonError {
+    fn new(path: &str, message: impl Into<String>) -> Self {
+        JsonError {
+            path: path.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "invalid value of field '{}': {}", self.path, self.message)
+        }
+    }
+}
+
+impl ::std::error::Error for JsonError {}
+
+/// Returns the HTTP status of a gRPC status code, following the mapping of `google.rpc.Code`.
+pub fn http_status(code: ::tonic::Code) -> StatusCode {
+    match code {
+        ::tonic::Code::Ok => StatusCode::OK,
+        ::tonic::Code::Cancelled => StatusCode::from_u16(499).unwrap(),
+        ::tonic::Code::Unknown => StatusCode::INTERNAL_SERVER_ERROR,
+        ::tonic::Code::InvalidArgument => StatusCode::BAD_REQUEST,
+        ::tonic::Code::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+        ::tonic::Code::NotFound => StatusCode::NOT_FOUND,
+        ::tonic::Code::AlreadyExists => StatusCode::CONFLICT,
+        ::tonic::Code::PermissionDenied => StatusCode::FORBIDDEN,
+        ::tonic::Code::ResourceExhausted => StatusCode::TOO_MANY_REQUESTS,
+        ::tonic::Code::FailedPrecondition => StatusCode::BAD_REQUEST,
+        ::tonic::Code::Aborted => StatusCode::CONFLICT,
+        ::tonic::Code::OutOfRange => StatusCode::BAD_REQUEST,
+        ::tonic::Code::Unimplemented => StatusCode::NOT_IMPLEMENTED,
+        ::tonic::Code::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        ::tonic::Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        ::tonic::Code::DataLoss => StatusCode::INTERNAL_SERVER_ERROR,
+        ::tonic::Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+    }
+}
+
+/// Returns the error of a failed call, with a `google.rpc.Status` body.
+fn status_error(status: ::tonic::Status) -> ApiError {
+    (
+        http_status(status.code()),
+        ::axum::Json(::serde_json::json!({
+            "code": status.code() as i32,
+            "message": status.message(),
+            "details": [],
+        })),
+    )
+}
+
+fn json_error(err: JsonError) -> ApiError {
+    status_error(::tonic::Status::invalid_argument(err.to_string()))
+}
+
+/// Returns the query parameters as a JSON object, nesting dotted names such as `product.name`
+/// and collecting repeated parameters into arrays.
+fn query_json(query: Vec<(String, String)>) -> Value {
+    let mut object = Map::new();
+    for (name, value) in query {
+        let mut names: Vec<&str> = name.split('.').collect();
+        let last = names.pop().unwrap_or_default();
+        let mut parent = &mut object;
+        for name in names {
+            let child = parent
+                .entry(name.to_string())
+                .or_insert_with(|| Value::Object(Map::new()));
+            if !child.is_object() {
+                *child = Value::Object(Map::new());
+            }
+            parent = child.as_object_mut().unwrap();
+        }
+
+        match parent.get_mut(last) {
+            Some(Value::Array(values)) => values.push(Value::String(value)),
+            Some(previous) => {
+                let previous = previous.take();
+                parent.insert(last.to_string(), Value::Array(vec![previous, Value::String(value)]));
+            }
+            None => {
+                parent.insert(last.to_string(), Value::String(value));
+            }
+        }
+    }
+
+    Value::Object(object)
+}
+
+/// Sets the field `name` of the request to the value of a path parameter.
+fn set_path_field(request: &mut Value, name: &str, value: String) -> Result<(), JsonError> {
+    request
+        .as_object_mut()
+        .ok_or_else(|| JsonError::new("", "expected an object"))?
+        .insert(name.to_string(), Value::String(value));
+
+    Ok(())
+}
+
+fn join_path(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", path, name)
+    }
+}
+
+fn as_object<'a>(value: &'a Value, path: &str) -> Result<&'a Map<String, Value>, JsonError> {
+    value
+        .as_object()
+        .ok_or_else(|| JsonError::new(path, "expected an object"))
+}
+
+/// Returns the value of the field with the JSON name `json_name` or the protobuf name `name`, unless it is null.
+fn field<'a>(object: &'a Map<String, Value>, json_name: &str, name: &str) -> Option<&'a Value> {
+    object
+        .get(json_name)
+        .or_else(|| object.get(name))
+        .filter(|value| !value.is_null())
+}
+
+/// Parses a field which is set to its default value when it is missing.
+fn required<T: ProtoJson + Default>(
+    object: &Map<String, Value>,
+    json_name: &str,
+    name: &str,
+    path: &str,
+) -> Result<T, JsonError> {
+    match field(object, json_name, name) {
+        Some(value) => T::from_json(value, &join_path(path, name)),
+        None => Ok(T::default()),
+    }
+}
+
+/// Parses a field which is `None` when it is missing.
+fn optional<T: ProtoJson>(
+    object: &Map<String, Value>,
+    json_name: &str,
+    name: &str,
+    path: &str,
+) -> Result<Option<T>, JsonError> {
+    field(object, json_name, name)
+        .map(|value| T::from_json(value, &join_path(path, name)))
+        .transpose()
+}
+
+/// Parses a repeated field, which is also given as a single value by query parameters.
+fn repeated<T: ProtoJson>(
+    object: &Map<String, Value>,
+    json_name: &str,
+    name: &str,
+    path: &str,
+) -> Result<Vec<T>, JsonError> {
+    let path = join_path(path, name);
+    match field(object, json_name, name) {
+        Some(Value::Array(values)) => values.iter().map(|value| T::from_json(value, &path)).collect(),
+        Some(value) => Ok(vec![T::from_json(value, &path)?]),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn parse_number<T: ::std::str::FromStr>(value: &Value, path: &str) -> Result<T, JsonError> {
+    let number = match value {
+        Value::Number(number) => number.to_string(),
+        Value::String(string) => string.clone(),
+        _ => return Err(JsonError::new(path, "expected a number")),
+    };
+
+    number
+        .parse()
+        .map_err(|_| JsonError::new(path, format!("invalid number '{}'", number)))
+}
+
+macro_rules! json_numbers {
+    ($( $ty:ty => $to_json:expr ),*) => {
+        $(
+            impl ProtoJson for $ty {
+                fn to_json(&self) -> Value {
+                    $to_json(*self)
+                }
+
+                fn from_json(value: &Value, path: &str) -> Result<Self, JsonError> {
+                    parse_number(value, path)
+                }
+            }
+        )*
+    };
+}
+
+// 64 bit integers are strings, as JSON numbers cannot hold all of their values
+json_numbers!(
+    i32 => Value::from,
+    u32 => Value::from,
+    i64 => |value: i64| Value::String(value.to_string()),
+    u64 => |value: u64| Value::String(value.to_string())
+);
+
+macro_rules! json_floats {
+    ($( $ty:ty ),*) => {
+        $(
+            impl ProtoJson for $ty {
+                fn to_json(&self) -> Value {
+                    if self.is_nan() {
+                        Value::String("NaN".to_string())
+                    } else if self.is_infinite() {
+                        Value::String(if *self > 0.0 { "Infinity" } else { "-Infinity" }.to_string())
+                    } else {
+                        Value::from(*self as f64)
+                    }
+                }
+
+                fn from_json(value: &Value, path: &str) -> Result<Self, JsonError> {
+                    match value.as_str() {
+                        Some("NaN") => Ok(<$ty>::NAN),
+                        Some("Infinity") => Ok(<$ty>::INFINITY),
+                        Some("-Infinity") => Ok(<$ty>::NEG_INFINITY),
+                        _ => parse_number(value, path),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+json_floats!(f32, f64);
+
+impl ProtoJson for bool {
+    fn to_json(&self) -> Value {
+        Value::Bool(*self)
+    }
+
+    fn from_json(value: &Value, path: &str) -> Result<Self, JsonError> {
+        match value {
+            Value::Bool(value) => Ok(*value),
+            Value::String(value) if value == "true" => Ok(true),
+            Value::String(value) if value == "false" => Ok(false),
+            _ => Err(JsonError::new(path, "expected a boolean")),
+        }
+    }
+}
+
+impl ProtoJson for String {
+    fn to_json(&self) -> Value {
+        Value::String(self.clone())
+    }
+
+    fn from_json(value: &Value, path: &str) -> Result<Self, JsonError> {
+        value
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| JsonError::new(path, "expected a string"))
+    }
+}
+
+/// Bytes are base64 strings, with the standard or url safe alphabet.
+impl ProtoJson for Vec<u8> {
+    fn to_json(&self) -> Value {
+        Value::String(::base64::encode(self))
+    }
+
+    fn from_json(value: &Value, path: &str) -> Result<Self, JsonError> {
+        let value = value
+            .as_str()
+            .ok_or_else(|| JsonError::new(path, "expected a base64 string"))?;
+        ::base64::decode_config(value, ::base64::STANDARD)
+            .or_else(|_| ::base64::decode_config(value, ::base64::URL_SAFE))
+            .map_err(|err| JsonError::new(path, format!("invalid base64: {}", err)))
+    }
+}
+
+/// Maps are JSON objects of their values.
+impl<T: ProtoJson> ProtoJson for ::std::collections::HashMap<String, T> {
+    fn to_json(&self) -> Value {
+        Value::Object(self.iter().map(|(key, value)| (key.clone(), value.to_json())).collect())
+    }
+
+    fn from_json(value: &Value, path: &str) -> Result<Self, JsonError> {
+        as_object(value, path)?
+            .iter()
+            .map(|(key, value)| Ok((key.clone(), T::from_json(value, &join_path(path, key))?)))
+            .collect()
+    }
+}
+
+/// Timestamps are RFC 3339 strings in UTC, such as `2021-11-04T09:30:00.500Z`.
+impl ProtoJson for ::prost_types::Timestamp {
+    fn to_json(&self) -> Value {
+        use ::chrono::TimeZone;
+
+        match ::chrono::Utc.timestamp_opt(self.seconds, self.nanos as u32).single() {
+            Some(timestamp) => Value::String(timestamp.to_rfc3339_opts(::chrono::SecondsFormat::AutoSi, true)),
+            None => Value::Null,
+        }
+    }
+
+    fn from_json(value: &Value, path: &str) -> Result<Self, JsonError> {
+        let value = value
+            .as_str()
+            .ok_or_else(|| JsonError::new(path, "expected an RFC 3339 timestamp"))?;
+        let timestamp = ::chrono::DateTime::parse_from_rfc3339(value)
+            .map_err(|err| JsonError::new(path, format!("invalid timestamp: {}", err)))?;
+
+        Ok(::prost_types::Timestamp {
+            seconds: timestamp.timestamp(),
+            nanos: timestamp.timestamp_subsec_nanos() as i32,
+        })
+    }
+}
+
+/// Field masks are comma separated paths in lower camel case, such as `name,updatedAt`.
+impl ProtoJson for ::prost_types::FieldMask {
+    fn to_json(&self) -> Value {
+        let paths: Vec<String> = self
+            .paths
+            .iter()
+            .map(|path| {
+                let mut camel = String::new();
+                let mut upper = false;
+                for c in path.chars() {
+                    match c {
+                        '_' => upper = true,
+                        c if upper => {
+                            camel.extend(c.to_uppercase());
+                            upper = false;
+                        }
+                        c => camel.push(c),
+                    }
+                }
+                camel
+            })
+            .collect();
+
+        Value::String(paths.join(","))
+    }
+
+    fn from_json(value: &Value, path: &str) -> Result<Self, JsonError> {
+        let value = value
+            .as_str()
+            .ok_or_else(|| JsonError::new(path, "expected a comma separated list of fields"))?;
+        let paths = value
+            .split(',')
+            .filter(|path| !path.is_empty())
+            .map(|path| {
+                let mut snake = String::new();
+                for c in path.chars() {
+                    if c.is_uppercase() {
+                        snake.push('_');
+                        snake.extend(c.to_lowercase());
+                    } else {
+                        snake.push(c);
+                    }
+                }
+                snake
+            })
+            .collect();
+
+        Ok(::prost_types::FieldMask { paths })
+    }
+}
+"#;
+
+/// Shared code of the generated transcoding tests, calling a transcoded route with a request message.
+const TESTS_MODULE: &str = r#"
+use ::std::sync::{Arc, Mutex};
+
+use ::rest::transcoding::ProtoJson;
+use ::serde_json::Value;
+use ::tower::ServiceExt;
+
+/// Requests received by a stub service, as JSON.
+type Requests = Arc<Mutex<Vec<Value>>>;
+
+/// Returns a query parameter of every scalar in `value`, naming the fields of nested messages with dots.
+fn query_params(prefix: &str, value: &Value, params: &mut Vec<String>) {
+    match value {
+        Value::Object(object) => {
+            for (name, value) in object {
+                let name = if prefix.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{}.{}", prefix, name)
+                };
+                query_params(&name, value, params);
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                query_params(prefix, value, params);
+            }
+        }
+        Value::String(value) => params.push(format!("{}={}", prefix, encode(value))),
+        Value::Null => {}
+        value => params.push(format!("{}={}", prefix, value)),
+    }
+}
+
+/// Percent-encodes the characters of `value` which are not unreserved.
+fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+            byte => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Calls the route at `path` of `router` with the JSON of a request message, returning the JSON of the response.
+///
+/// The field `path_field` is passed in the path, and the other fields in the body or the query.
+async fn call(router: &::axum::Router, method: &str, path: &str, path_field: Option<&str>, body: bool, request: Value) -> Value {
+    let mut request = request;
+    let mut uri = path.to_string();
+    if let Some(path_field) = path_field {
+        let value = request
+            .as_object_mut()
+            .and_then(|object| object.remove(path_field))
+            .unwrap_or_default();
+        let value = value.as_str().map(str::to_string).unwrap_or_else(|| value.to_string());
+        uri = uri.replace(&format!(":{}", path_field), &encode(&value));
+    }
+
+    let http_request = ::axum::http::Request::builder()
+        .method(method)
+        .header("content-type", "application/json");
+    let http_request = if body {
+        http_request
+            .uri(uri)
+            .body(::axum::body::Body::from(request.to_string()))
+    } else {
+        let mut params = Vec::new();
+        query_params("", &request, &mut params);
+        http_request
+            .uri(format!("{}?{}", uri, params.join("&")))
+            .body(::axum::body::Body::empty())
+    }
+    .unwrap();
+
+    let response = router.clone().oneshot(http_request).await.unwrap();
+    let status = response.status();
+    let body = ::hyper::body::to_bytes(response.into_body()).await.unwrap();
+    assert!(status.is_success(), "{} {} responded with {}: {:?}", method, path, status, body);
+
+    ::serde_json::from_slice(&body).unwrap()
+}
+
+/// Serves `service` over gRPC on a local port, returning its address.
+async fn serve_grpc<S>(service: S) -> String
+where
+    S: ::tower::Service<
+            ::axum::http::Request<::tonic::transport::Body>,
+            Response = ::axum::http::Response<::tonic::body::BoxBody>,
+            Error = ::tonic::codegen::Never,
+        > + ::tonic::transport::NamedService
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+{
+    let listener = ::tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    ::tokio::spawn(
+        ::tonic::transport::Server::builder()
+            .add_service(service)
+            .serve_with_incoming(::tokio_stream::wrappers::TcpListenerStream::new(listener)),
+    );
+
+    format!("http://{}", address)
+}
+"#;
+
+/// HTTP method of a transcoded route.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HttpMethod {
+    Get,
+    Post,
+    Put,
+    Delete,
+}
+
+impl HttpMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HttpMethod::Get => "GET",
+            HttpMethod::Post => "POST",
+            HttpMethod::Put => "PUT",
+            HttpMethod::Delete => "DELETE",
+        }
+    }
+
+    fn routing_fn(&self) -> TokenStream {
+        match self {
+            HttpMethod::Get => quote!(get),
+            HttpMethod::Post => quote!(post),
+            HttpMethod::Put => quote!(put),
+            HttpMethod::Delete => quote!(delete),
+        }
+    }
+
+    /// Returns whether requests of the method carry the message in their body, rather than in the query.
+    pub(crate) fn has_body(&self) -> bool {
+        matches!(self, HttpMethod::Post | HttpMethod::Put)
+    }
+}
+
+/// Route of a gRPC method transcoded to JSON over HTTP.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HttpRule {
+    pub method: HttpMethod,
+    /// Path of the route, with `:id` standing for the `id` field of the request.
+    pub path: String,
+    /// Field of the request passed in the path.
+    pub path_field: Option<String>,
+}
+
+/// Compiles routes serving protobuf services as JSON over HTTP, for clients which cannot call gRPC.
+///
+/// Every method of a service gets a route derived from its name:
+///
+/// - `GetProduct` and `FindProduct` as `GET /v1/products/{id}`, or `GET /v1/products` when the request has no `id`
+/// - `ListProducts` as `GET /v1/products`
+/// - `CreateProduct` as `POST /v1/products`
+/// - `UpdateProduct` as `PUT /v1/products/{id}`
+/// - `DeleteProduct` as `DELETE /v1/products/{id}`
+/// - any other method, or a method whose route is taken, as `POST /app.ProductService/ArchiveProduct`
+///
+/// `GET` and `DELETE` requests are read from the query, with dotted names such as `product.name` for nested fields,
+/// so methods whose request holds a message are served at their gRPC path instead.
+///
+/// Messages use the canonical JSON mapping of protobuf: camelCase field names, 64 bit integers as strings,
+/// timestamps as RFC 3339 strings and bytes as base64. Fields with a default value are included in responses.
+/// Handlers call the gRPC service trait of the protobuf package, which is implemented by the service lib,
+/// and respond with the HTTP status of the `google.rpc.Code` of failed calls.
+///
+/// # Examples
+///
+/// ```
+/// # use awto_compile::transcoding::TranscodingCompiler;
+/// # use awto::tests_cfg::*;
+/// # use awto::protobuf::IntoProtobufService;
+/// let compiler = TranscodingCompiler::new(vec![ProductService::protobuf_service()]);
+/// let code = compiler.compile_generated_code();
+///
+/// assert!(code.contains("pub fn product_service_routes"));
+/// ```
+pub struct TranscodingCompiler {
+    services: Vec<ProtobufService>,
+    package: String,
+    annotate_sources: bool,
+    plurals: BTreeMap<String, String>,
+    api_docs: ApiDocs,
+}
+
+impl TranscodingCompiler {
+    /// Creates a new instance of [`TranscodingCompiler`].
+    pub fn new(services: Vec<ProtobufService>) -> TranscodingCompiler {
+        TranscodingCompiler {
+            services,
+            package: PROTOBUF_PACKAGE.to_string(),
+            annotate_sources: false,
+            plurals: BTreeMap::new(),
+            api_docs: ApiDocs::default(),
+        }
+    }
+
+    /// Prefixes the gRPC paths of methods served at their gRPC path with `package` instead of [`PROTOBUF_PACKAGE`].
+    pub fn with_package(mut self, package: &str) -> Self {
+        self.package = package.to_string();
+        self
+    }
+
+    /// Uses `plurals` of resource names in the paths of methods, such as the plurals of the tables served by the REST router,
+    /// instead of pluralizing them.
+    pub fn with_plurals(mut self, plurals: impl IntoIterator<Item = (String, String)>) -> Self {
+        self.plurals.extend(plurals);
+        self
+    }
+
+    /// Interleaves the generated code with comments telling which message or service produced each chunk.
+    pub fn with_annotations(mut self, annotate_sources: bool) -> Self {
+        self.annotate_sources = annotate_sources;
+        self
+    }
+
+    /// Documents the handler of each method with its description and tags in `api_docs`.
+    pub fn with_api_docs(mut self, api_docs: ApiDocs) -> Self {
+        self.api_docs = api_docs;
+        self
+    }
+
+    /// Returns the names of the transcoded services.
+    pub(crate) fn service_names(&self) -> Vec<&str> {
+        self.services
+            .iter()
+            .map(|service| service.name.as_str())
+            .collect()
+    }
+
+    /// Compiles the `transcoding` module of the REST package.
+    pub fn compile_generated_code(&self) -> String {
+        let mut code = String::new();
+        writeln!(
+            code,
+            "/// Routers serving the gRPC services as JSON over HTTP."
+        )
+        .unwrap();
+        writeln!(code, "pub mod transcoding {{").unwrap();
+        code.push_str(TRANSCODING_MODULE);
+
+        for message in self.messages().values() {
+            code.push_str(&self.annotation(&message.name));
+            write!(code, "{}", Self::compile_proto_json(message)).unwrap();
+        }
+        for service in &self.services {
+            code.push_str(&self.annotation(&service.name));
+            write!(code, "{}", self.compile_routes(service)).unwrap();
+        }
+
+        writeln!(code, "}}").unwrap();
+        code
+    }
+
+    /// Compiles integration tests calling every method of the services over gRPC and over JSON,
+    /// checking both receive the same requests and respond with the same messages.
+    pub fn compile_tests(&self) -> String {
+        let mut code = TESTS_MODULE.to_string();
+
+        for message in self.messages().values() {
+            write!(code, "{}", Self::compile_sample(message)).unwrap();
+        }
+        for service in &self.services {
+            write!(code, "{}", self.compile_service_test(service)).unwrap();
+        }
+
+        code
+    }
+
+    /// Returns the annotation of the code generated for a message or service, which is empty unless annotations are enabled.
+    fn annotation(&self, model: &str) -> String {
+        Annotation::new("rest/transcoding", model)
+            .comment(CommentStyle::Slashes, self.annotate_sources)
+    }
+
+    /// Returns the route of every method of `service`, in the order of the methods.
+    ///
+    /// Methods whose route is taken by an earlier method are served at their gRPC path.
+    pub fn http_rules<'a>(
+        &self,
+        service: &'a ProtobufService,
+    ) -> Vec<(&'a ProtobufMethod, HttpRule)> {
+        let mut rules: Vec<(&ProtobufMethod, HttpRule)> = Vec::new();
+        for method in &service.methods {
+            let rule = self
+                .http_rule(method)
+                .filter(|rule| {
+                    !rules
+                        .iter()
+                        .any(|(_, other)| other.method == rule.method && other.path == rule.path)
+                })
+                .unwrap_or_else(|| HttpRule {
+                    method: HttpMethod::Post,
+                    path: format!("/{}.{}/{}", self.package, service.name, method.name),
+                    path_field: None,
+                });
+            rules.push((method, rule));
+        }
+
+        rules
+    }
+
+    /// Returns the route of `method` derived from the verb starting its name, if it starts with one of the [`VERBS`].
+    fn http_rule(&self, method: &ProtobufMethod) -> Option<HttpRule> {
+        let (verb, http_method, resource) = VERBS.iter().find_map(|(verb, http_method)| {
+            let resource = method.name.strip_prefix(verb)?;
+            resource
+                .starts_with(char::is_uppercase)
+                .then(|| (*verb, *http_method, resource.to_snake_case()))
+        })?;
+
+        let has_messages = method.param.fields.iter().any(|field| {
+            matches!(
+                &field.ty,
+                ProtobufType::Custom(_) | ProtobufType::Repeated(_) | ProtobufType::Map(_)
+                    if Self::holds_message(&field.ty)
+            )
+        });
+        if has_messages && !http_method.has_body() {
+            return None;
+        }
+
+        let collection = if verb == "List" {
+            // Listed resources are already plural, such as the `products` of `ListProducts`
+            let plural = self
+                .plurals
+                .iter()
+                .find(|(name, _)| pluralize(name) == resource)
+                .map(|(_, plural)| plural.clone())
+                .unwrap_or(resource);
+            format!("/v1/{}", plural)
+        } else {
+            let plural = self
+                .plurals
+                .get(&resource)
+                .cloned()
+                .unwrap_or_else(|| pluralize(&resource));
+            format!("/v1/{}", plural)
+        };
+        let has_id = method.param.fields.iter().any(|field| field.name == "id");
+        let item = |method| {
+            Some(HttpRule {
+                method,
+                path: format!("{}/:id", collection),
+                path_field: Some("id".to_string()),
+            })
+        };
+
+        match (verb, has_id) {
+            ("Get" | "Find", true) | ("Update" | "Delete", true) => item(http_method),
+            ("Get" | "Find" | "List" | "Create", _) => Some(HttpRule {
+                method: http_method,
+                path: collection,
+                path_field: None,
+            }),
+            _ => None,
+        }
+    }
+
+    fn holds_message(ty: &ProtobufType) -> bool {
+        match ty {
+            ProtobufType::Custom(_) => true,
+            ProtobufType::Repeated(inner) | ProtobufType::Map(inner) => Self::holds_message(inner),
+            _ => false,
+        }
+    }
+
+    /// Returns every message of the requests and responses of the services, including nested messages, by name.
+    fn messages(&self) -> BTreeMap<&str, &ProtobufMessage> {
+        fn add<'a>(
+            message: &'a ProtobufMessage,
+            messages: &mut BTreeMap<&'a str, &'a ProtobufMessage>,
+        ) {
+            if messages.insert(&message.name, message).is_some() {
+                return;
+            }
+            for field in &message.fields {
+                let mut ty = &field.ty;
+                while let ProtobufType::Repeated(inner) | ProtobufType::Map(inner) = ty {
+                    ty = inner;
+                }
+                if let ProtobufType::Custom(nested) = ty {
+                    add(nested, messages);
+                }
+            }
+        }
+
+        let mut messages = BTreeMap::new();
+        for method in self.services.iter().flat_map(|service| &service.methods) {
+            add(&method.param, &mut messages);
+            add(&method.returns, &mut messages);
+        }
+
+        messages
+    }
+
+    /// Returns whether prost represents `field` as an `Option`, which is left out of the JSON when it is `None`.
+    fn is_option(field: &ProtobufField) -> bool {
+        match &field.ty {
+            ProtobufType::Repeated(_) | ProtobufType::Map(_) => false,
+            ProtobufType::Custom(_) | ProtobufType::Timestamp | ProtobufType::FieldMask => true,
+            _ => !field.required,
+        }
+    }
+
+    fn compile_proto_json(message: &ProtobufMessage) -> TokenStream {
+        let ident = rust_ident(&message.name);
+
+        let mut to_json_fields = Vec::new();
+        let mut from_json_fields = Vec::new();
+        for field in &message.fields {
+            let field_ident = rust_ident(&field.name);
+            let name = &field.name;
+            let json_name = field.name.to_mixed_case();
+
+            if let ProtobufType::Repeated(_) = field.ty {
+                to_json_fields.push(quote!(
+                    object.insert(
+                        #json_name.to_string(),
+                        Value::Array(self.#field_ident.iter().map(ProtoJson::to_json).collect()),
+                    );
+                ));
+                from_json_fields
+                    .push(quote!(#field_ident: repeated(object, #json_name, #name, path)?));
+            } else if Self::is_option(field) {
+                to_json_fields.push(quote!(
+                    if let Some(value) = &self.#field_ident {
+                        object.insert(#json_name.to_string(), value.to_json());
+                    }
+                ));
+                from_json_fields
+                    .push(quote!(#field_ident: optional(object, #json_name, #name, path)?));
+            } else {
+                to_json_fields.push(quote!(
+                    object.insert(#json_name.to_string(), self.#field_ident.to_json());
+                ));
+                from_json_fields
+                    .push(quote!(#field_ident: required(object, #json_name, #name, path)?));
+            }
+        }
+
+        quote!(
+            impl ProtoJson for ::protobuf::#ident {
+                fn to_json(&self) -> Value {
+                    #[allow(unused_mut)]
+                    let mut object = Map::new();
+                    #( #to_json_fields )*
+                    Value::Object(object)
+                }
+
+                #[allow(unused_variables)]
+                fn from_json(value: &Value, path: &str) -> Result<Self, JsonError> {
+                    let object = as_object(value, path)?;
+                    Ok(Self {
+                        #( #from_json_fields, )*
+                    })
+                }
+            }
+        )
+    }
+
+    fn compile_routes(&self, service: &ProtobufService) -> TokenStream {
+        let routes_ident = format_ident!("{}_routes", service.name.to_snake_case());
+        let module_ident = rust_ident(&service.name.to_snake_case());
+        let trait_path = Self::trait_path(service);
+        let doc = format!(
+            " Creates a router serving the `{}` gRPC service as JSON over HTTP, calling `service` like the gRPC server does.",
+            service.name
+        );
+        let module_doc = format!(" Handlers of the routes of [`{}`].", routes_ident);
+
+        let rules = self.http_rules(service);
+        let handlers = rules.iter().map(|(method, rule)| {
+            let name_ident = rust_ident(&method.name.to_snake_case());
+            let param_ident = rust_ident(&method.param.name);
+            let doc = self.api_docs.rust_doc(
+                ApiItem::Operation(&service.name, &method.name),
+                &format!(" Transcodes `{} {}` to `{}`.", rule.method.as_str(), rule.path, method.name),
+            );
+
+            let path_arg = rule.path_field.as_ref().map(|_| {
+                quote!(::axum::extract::Path(path_value): ::axum::extract::Path<String>,)
+            });
+            let set_path_field = rule.path_field.as_ref().map(|path_field| {
+                quote!(set_path_field(&mut request, #path_field, path_value).map_err(json_error)?;)
+            });
+            let (input_arg, input) = if rule.method.has_body() {
+                (
+                    quote!(::axum::Json(body): ::axum::Json<Value>),
+                    quote!(body),
+                )
+            } else {
+                (
+                    quote!(::axum::extract::Query(query): ::axum::extract::Query<Vec<(String, String)>>),
+                    quote!(query_json(query)),
+                )
+            };
+
+            quote!(
+                #[doc = #doc]
+                pub async fn #name_ident<S: #trait_path>(
+                    ::axum::extract::Extension(service): ::axum::extract::Extension<::std::sync::Arc<S>>,
+                    #path_arg
+                    #input_arg,
+                ) -> Result<::axum::Json<Value>, ApiError> {
+                    #[allow(unused_mut)]
+                    let mut request = #input;
+                    #set_path_field
+                    let request = <::protobuf::#param_ident as ProtoJson>::from_json(&request, "").map_err(json_error)?;
+                    let response = #trait_path::#name_ident(&*service, ::tonic::Request::new(request))
+                        .await
+                        .map_err(status_error)?;
+
+                    Ok(::axum::Json(response.into_inner().to_json()))
+                }
+            )
+        });
+
+        let mut paths: Vec<(&str, Vec<TokenStream>)> = Vec::new();
+        for (method, rule) in &rules {
+            let name_ident = rust_ident(&method.name.to_snake_case());
+            let handler = quote!(#module_ident::#name_ident::<S>);
+            let routing_fn = rule.method.routing_fn();
+            match paths.iter_mut().find(|(path, _)| *path == rule.path) {
+                Some((_, method_routers)) => method_routers.push(quote!(.#routing_fn(#handler))),
+                None => paths.push((
+                    &rule.path,
+                    vec![quote!(::axum::routing::#routing_fn(#handler))],
+                )),
+            }
+        }
+        let routes = paths
+            .iter()
+            .map(|(path, method_routers)| quote!(.route(#path, #( #method_routers )*)));
+
+        quote!(
+            #[doc = #doc]
+            pub fn #routes_ident<S: #trait_path>(service: ::std::sync::Arc<S>) -> ::axum::Router {
+                ::axum::Router::new()
+                    #( #routes )*
+                    .layer(::axum::AddExtensionLayer::new(service))
+            }
+
+            #[doc = #module_doc]
+            pub mod #module_ident {
+                use super::*;
+
+                #( #handlers )*
+            }
+        )
+    }
+
+    /// Returns the path of the gRPC service trait generated by tonic in the protobuf package.
+    fn trait_path(service: &ProtobufService) -> TokenStream {
+        let ident = rust_ident(&service.name);
+        let server_module_ident = format_ident!("{}_server", service.name.to_snake_case());
+        quote!(::protobuf::#server_module_ident::#ident)
+    }
+
+    /// Compiles a function returning a message with a value in every field.
+    fn compile_sample(message: &ProtobufMessage) -> TokenStream {
+        let ident = rust_ident(&message.name);
+        let sample_ident = format_ident!("sample_{}", message.name.to_snake_case());
+        let fields = message.fields.iter().enumerate().map(|(i, field)| {
+            let field_ident = rust_ident(&field.name);
+            let value = Self::sample_value(&field.ty, &field.name, i + 1);
+            let value = match &field.ty {
+                ProtobufType::Repeated(_) => quote!(vec![#value]),
+                ProtobufType::Map(_) => {
+                    let key = &field.name;
+                    quote!(vec![(#key.to_string(), #value)].into_iter().collect())
+                }
+                _ if Self::is_option(field) => quote!(Some(#value)),
+                _ => value,
+            };
+            quote!(#field_ident: #value)
+        });
+
+        quote!(
+            fn #sample_ident() -> ::protobuf::#ident {
+                ::protobuf::#ident {
+                    #( #fields, )*
+                }
+            }
+        )
+    }
+
+    fn sample_value(ty: &ProtobufType, name: &str, index: usize) -> TokenStream {
+        let number = Literal::usize_unsuffixed(index);
+        match ty {
+            ProtobufType::Double | ProtobufType::Float => {
+                let float = Literal::f64_unsuffixed(index as f64 + 0.5);
+                quote!(#float)
+            }
+            ProtobufType::Int32
+            | ProtobufType::Int64
+            | ProtobufType::Uint32
+            | ProtobufType::Uint64
+            | ProtobufType::Sint32
+            | ProtobufType::Sint64
+            | ProtobufType::Fixed32
+            | ProtobufType::Fixed64
+            | ProtobufType::Sfixed32
+            | ProtobufType::Sfixed64 => quote!(#number),
+            ProtobufType::Bool => quote!(true),
+            ProtobufType::String => quote!(#name.to_string()),
+            ProtobufType::Bytes => quote!(#name.as_bytes().to_vec()),
+            ProtobufType::Repeated(inner) | ProtobufType::Map(inner) => {
+                Self::sample_value(inner, name, index)
+            }
+            ProtobufType::Timestamp => quote!(::prost_types::Timestamp {
+                seconds: 1_636_018_200 + #number,
+                nanos: 500_000_000,
+            }),
+            ProtobufType::FieldMask => quote!(::prost_types::FieldMask {
+                paths: vec![#name.to_string()],
+            }),
+            ProtobufType::Custom(message) => {
+                let sample_ident = format_ident!("sample_{}", message.name.to_snake_case());
+                quote!(#sample_ident())
+            }
+        }
+    }
+
+    fn compile_service_test(&self, service: &ProtobufService) -> TokenStream {
+        let stub_ident = format_ident!("Stub{}", service.name);
+        let test_ident = format_ident!("{}_transcoding", service.name.to_snake_case());
+        let routes_ident = format_ident!("{}_routes", service.name.to_snake_case());
+        let server_module_ident = format_ident!("{}_server", service.name.to_snake_case());
+        let client_module_ident = format_ident!("{}_client", service.name.to_snake_case());
+        let server_ident = format_ident!("{}Server", service.name);
+        let client_ident = format_ident!("{}Client", service.name);
+        let trait_path = Self::trait_path(service);
+        let doc = format!(
+            " Stub of the `{}` gRPC service, recording its requests and responding with sample messages.",
+            service.name
+        );
+
+        let stub_methods = service.methods.iter().map(|method| {
+            let name_ident = rust_ident(&method.name.to_snake_case());
+            let param_ident = rust_ident(&method.param.name);
+            let returns_ident = rust_ident(&method.returns.name);
+            let sample_ident = format_ident!("sample_{}", method.returns.name.to_snake_case());
+
+            quote!(
+                async fn #name_ident(
+                    &self,
+                    request: ::tonic::Request<::protobuf::#param_ident>,
+                ) -> Result<::tonic::Response<::protobuf::#returns_ident>, ::tonic::Status> {
+                    self.requests.lock().unwrap().push(request.into_inner().to_json());
+                    Ok(::tonic::Response::new(#sample_ident()))
+                }
+            )
+        });
+
+        let calls = self.http_rules(service).into_iter().map(|(method, rule)| {
+            let name_ident = rust_ident(&method.name.to_snake_case());
+            let sample_ident = format_ident!("sample_{}", method.param.name.to_snake_case());
+            let method_name = &method.name;
+            let http_method = rule.method.as_str();
+            let path = &rule.path;
+            let path_field = match &rule.path_field {
+                Some(path_field) => quote!(Some(#path_field)),
+                None => quote!(None),
+            };
+            let has_body = rule.method.has_body();
+
+            quote!(
+                let request = #sample_ident();
+                let grpc_response = client.#name_ident(request.clone()).await.unwrap().into_inner();
+                let json_response = call(&router, #http_method, #path, #path_field, #has_body, request.to_json()).await;
+                assert_eq!(json_response, grpc_response.to_json(), #method_name);
+            )
+        });
+
+        quote!(
+            #[doc = #doc]
+            #[derive(Clone, Default)]
+            struct #stub_ident {
+                requests: Requests,
+            }
+
+            #[::tonic::async_trait]
+            impl #trait_path for #stub_ident {
+                #( #stub_methods )*
+            }
+
+            #[::tokio::test]
+            async fn #test_ident() {
+                let grpc_service = #stub_ident::default();
+                let address = serve_grpc(::protobuf::#server_module_ident::#server_ident::new(grpc_service.clone())).await;
+                let mut client = ::protobuf::#client_module_ident::#client_ident::connect(address).await.unwrap();
+
+                let json_service = #stub_ident::default();
+                let router = ::rest::transcoding::#routes_ident(Arc::new(json_service.clone()));
+
+                #( #calls )*
+
+                assert_eq!(
+                    *json_service.requests.lock().unwrap(),
+                    *grpc_service.requests.lock().unwrap(),
+                    "requests received over JSON differ from the requests received over gRPC"
+                );
+            }
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use awto::{protobuf::IntoProtobufService, tests_cfg::*};
+
+    use super::*;
+    use crate::util::undocumented_items;
+
+    fn method(name: &str, param: &ProtobufMessage) -> ProtobufMethod {
+        ProtobufMethod {
+            is_async: true,
+            name: name.to_string(),
+            doc: None,
+            param: param.clone(),
+            returns: param.clone(),
+            returns_result: true,
+        }
+    }
+
+    #[test]
+    fn http_rules() {
+        let mut service = ProductService::protobuf_service();
+        let product_id = service.methods[0].param.clone();
+        let product_list = service.methods[0].returns.clone();
+        service.methods.extend([
+            method("ListProducts", &product_id),
+            method("CreateProduct", &product_list),
+            method("UpdateProduct", &product_id),
+            method("DeleteProduct", &product_id),
+            method("GetProduct", &product_id),
+            method("ArchiveProduct", &product_id),
+            method("ListProductsByIds", &product_list),
+        ]);
+
+        let compiler = TranscodingCompiler::new(Vec::new());
+        let rules: Vec<_> = compiler
+            .http_rules(&service)
+            .into_iter()
+            .map(|(method, rule)| (method.name.as_str(), rule.method.as_str(), rule.path))
+            .collect();
+        assert_eq!(
+            rules,
+            [
+                ("FindProduct", "GET", "/v1/products/:id".to_string()),
+                ("ListProducts", "GET", "/v1/products".to_string()),
+                ("CreateProduct", "POST", "/v1/products".to_string()),
+                ("UpdateProduct", "PUT", "/v1/products/:id".to_string()),
+                ("DeleteProduct", "DELETE", "/v1/products/:id".to_string()),
+                (
+                    "GetProduct",
+                    "POST",
+                    "/app.ProductService/GetProduct".to_string()
+                ),
+                (
+                    "ArchiveProduct",
+                    "POST",
+                    "/app.ProductService/ArchiveProduct".to_string()
+                ),
+                (
+                    "ListProductsByIds",
+                    "POST",
+                    "/app.ProductService/ListProductsByIds".to_string()
+                ),
+            ]
+        );
+
+        // Resources named after tables use their plurals
+        let compiler = TranscodingCompiler::new(Vec::new())
+            .with_plurals([("product".to_string(), "catalog".to_string())]);
+        let rules = compiler.http_rules(&service);
+        assert_eq!(rules[0].1.path, "/v1/catalog/:id");
+        assert_eq!(rules[1].1.path, "/v1/catalog");
+    }
+
+    #[test]
+    fn proto_json() {
+        let compiler = TranscodingCompiler::new(vec![ProductService::protobuf_service()]);
+        let code = compiler.compile_generated_code();
+
+        assert!(code.contains("impl ProtoJson for :: protobuf :: ProductList"));
+        assert!(
+            code.contains(r#"object . insert ("createdAt" . to_string () , value . to_json ())"#)
+        );
+        assert!(code.contains(
+            r#"price_cents : required (object , "priceCents" , "price_cents" , path) ?"#
+        ));
+        assert!(code.contains(
+            r#"description : optional (object , "description" , "description" , path) ?"#
+        ));
+        assert!(code.contains(r#"products : repeated (object , "products" , "products" , path) ?"#));
+        assert!(code.contains(
+            r#"route ("/v1/products/:id" , :: axum :: routing :: get (product_service :: find_product :: < S >))"#
+        ));
+        assert!(code.contains(
+            ":: protobuf :: product_service_server :: ProductService :: find_product (& * service , :: tonic :: Request :: new (request))"
+        ));
+    }
+
+    #[test]
+    fn tests() {
+        let compiler = TranscodingCompiler::new(vec![ProductService::protobuf_service()]);
+        let code = compiler.compile_tests();
+
+        assert!(code.contains("fn sample_product () -> :: protobuf :: Product"));
+        assert!(code.contains("products : vec ! [sample_product ()]"));
+        assert!(code.contains(r#"call (& router , "GET" , "/v1/products/:id" , Some ("id") , false , request . to_json ())"#));
+        assert!(code.contains("async fn product_service_transcoding ()"));
+    }
+
+    #[test]
+    fn documented_code() {
+        let compiler = TranscodingCompiler::new(vec![ProductService::protobuf_service()]);
+
+        assert_eq!(
+            undocumented_items(&compiler.compile_generated_code()),
+            Vec::<String>::new()
+        );
+    }
+}
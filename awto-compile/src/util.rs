@@ -1,5 +1,131 @@
+use std::{fs, io, path::Path};
+
+use awto::{
+    database::{DatabaseTable, DatabaseType},
+    schema::{Deprecation, Model, RustField, Sensitivity},
+};
+use heck::CamelCase;
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::{format_ident, quote};
+
 const OPTION_PREFIXES: [&str; 3] = ["std::option::Option<", "option::Option<", "Option<"];
 const VEC_PREFIXES: [&str; 3] = ["std::vec::Vec<", "vec::Vec<", "Vec<"];
+const MAP_PREFIXES: [&str; 3] = [
+    "std::collections::HashMap<String,",
+    "collections::HashMap<String,",
+    "HashMap<String,",
+];
+
+/// Keywords which are written as raw identifiers when used as names, such as `r#type`.
+const RUST_KEYWORDS: [&str; 48] = [
+    "as", "async", "await", "break", "const", "continue", "dyn", "else", "enum", "extern", "false",
+    "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+    "return", "static", "struct", "trait", "true", "type", "union", "unsafe", "use", "where",
+    "while", "abstract", "become", "box", "do", "final", "macro", "override", "priv", "try",
+    "typeof", "unsized", "virtual", "yield",
+];
+
+/// Keywords which cannot be raw identifiers, so they are suffixed with an underscore like sea-orm and prost do.
+const RUST_SPECIAL_KEYWORDS: [&str; 4] = ["crate", "self", "Self", "super"];
+
+/// Returns the Rust identifier of a model, field, table or column `name`,
+/// such as `r#type` for `type` and `self_` for `self`, matching the fields of generated sea-orm entities.
+pub fn rust_ident(name: &str) -> Ident {
+    let name = name.trim_start_matches("r#");
+    if RUST_KEYWORDS.contains(&name) {
+        Ident::new_raw(name, Span::call_site())
+    } else if RUST_SPECIAL_KEYWORDS.contains(&name) {
+        format_ident!("{}_", name)
+    } else {
+        format_ident!("{}", name)
+    }
+}
+
+/// Returns the path of the struct of `model` in its schema package, such as `::schema::Product`,
+/// or `::schema_billing::Invoice` for a model merged from the `schema-billing` package.
+pub fn schema_ty(model: &Model) -> TokenStream {
+    match &model.origin {
+        Some(origin) => {
+            let krate = rust_ident(&origin.package.replace('-', "_"));
+            let ident = rust_ident(&origin.name);
+            quote!(::#krate::#ident)
+        }
+        None => {
+            let ident = rust_ident(&model.name);
+            quote!(::schema::#ident)
+        }
+    }
+}
+
+/// Returns the identifier of the `Column` variant of the sea-orm entity column `name`, such as `Type` for `type`.
+pub fn column_variant_ident(name: &str) -> Ident {
+    rust_ident(&name.trim_start_matches("r#").to_camel_case())
+}
+
+/// Primary key of a database table as generated lookups take it, `id: ::uuid::Uuid` unless the table has
+/// a `String` key marked `#[awto(primary_key)]`, such as `code`, which lookups take as `&str`.
+pub struct TableKey {
+    /// Field of the key in models and active models, such as `id`.
+    pub field: Ident,
+    /// Variant of the key in the `Column` enum of the entity, such as `Id`.
+    pub column: Ident,
+    /// Type of the key taken by lookups, `::uuid::Uuid` or `&str`.
+    pub param_ty: TokenStream,
+    /// Owned type of the key, `::uuid::Uuid` or `String`, as extracted from request paths.
+    pub owned_ty: TokenStream,
+    is_string: bool,
+}
+
+impl TableKey {
+    pub fn of(table: &DatabaseTable) -> Self {
+        let name = table
+            .primary_key()
+            .map_or("id", |column| column.name.as_str());
+        let is_string = table.has_string_key();
+        TableKey {
+            field: rust_ident(name),
+            column: column_variant_ident(name),
+            param_ty: if is_string {
+                quote!(&str)
+            } else {
+                quote!(::uuid::Uuid)
+            },
+            owned_ty: if is_string {
+                quote!(String)
+            } else {
+                quote!(::uuid::Uuid)
+            },
+            is_string,
+        }
+    }
+
+    /// Returns the key `expr` taken by a lookup as the owned value `find_by_id` and `Set` take, such as `id.to_string()`.
+    pub fn owned(&self, expr: TokenStream) -> TokenStream {
+        if self.is_string {
+            quote!(#expr.to_string())
+        } else {
+            expr
+        }
+    }
+
+    /// Returns the owned key `expr` as lookups and column filters take it, such as `id.as_str()`.
+    pub fn borrowed(&self, expr: TokenStream) -> TokenStream {
+        if self.is_string {
+            quote!(#expr.as_str())
+        } else {
+            expr
+        }
+    }
+
+    /// Returns a copy of the owned key `expr`, such as `id.clone()`, for keys used again afterwards.
+    pub fn cloned(&self, expr: TokenStream) -> TokenStream {
+        if self.is_string {
+            quote!(#expr.clone())
+        } else {
+            expr
+        }
+    }
+}
 
 pub fn strip_ty_option(ty: &str) -> &str {
     for prefix in OPTION_PREFIXES {
@@ -18,3 +144,418 @@ pub fn is_ty_option(ty: &str) -> bool {
 pub fn is_ty_vec(ty: &str) -> bool {
     VEC_PREFIXES.iter().any(|prefix| ty.starts_with(prefix))
 }
+
+/// Returns the item type of a `Vec<T>` type, or `ty` itself.
+pub fn strip_ty_vec(ty: &str) -> &str {
+    VEC_PREFIXES
+        .iter()
+        .find_map(|prefix| ty.strip_prefix(prefix)?.strip_suffix('>'))
+        .unwrap_or(ty)
+}
+
+/// Returns the value type of a `HashMap<String, T>` field type, or of an `Option` of it.
+pub fn map_value_ty(ty: &str) -> Option<&str> {
+    let ty = strip_ty_option(ty);
+    MAP_PREFIXES
+        .iter()
+        .find_map(|prefix| ty.strip_prefix(prefix)?.strip_suffix('>'))
+}
+
+/// Returns the type of the generated entity field of a map field, a `JsonMap` of its values, without its `Option`.
+pub fn map_entity_ty(ty: &str) -> Option<TokenStream> {
+    let value_ty: TokenStream = map_value_ty(ty)?.parse().ok()?;
+    Some(quote!(crate::map::JsonMap<#value_ty>))
+}
+
+/// Returns the `SENSITIVE_FIELDS` constant of generated request logging, listing the names of the `#[awto(sensitive)]`,
+/// `#[serde(skip)]`, internal and secret fields of `models` once each, sorted.
+pub fn sensitive_fields_const(models: &[Model]) -> TokenStream {
+    let mut names: Vec<_> = models
+        .iter()
+        .flat_map(|model| &model.fields)
+        .filter(|field| field.sensitive)
+        .map(|field| field.name.as_str())
+        .collect();
+    names.sort_unstable();
+    names.dedup();
+
+    quote!(
+        /// Names of the fields marked with `#[awto(sensitive)]`, `#[serde(skip)]` or an internal or secret
+        /// `#[awto(sensitivity = "...")]` in the schema.
+        pub const SENSITIVE_FIELDS: &[&str] = &[ #( #names ),* ];
+    )
+}
+
+/// Returns the `#[serde(...)]` attribute of a generated struct field holding the values of `field`,
+/// which skips secret values and internal values outside of `awto::schema::reveal_internal` when serialized.
+pub fn sensitivity_serde_attr(field: &RustField) -> TokenStream {
+    match field.sensitivity {
+        Sensitivity::Public => TokenStream::new(),
+        Sensitivity::Internal => {
+            quote!(#[serde(skip_serializing_if = "::awto::schema::internal_hidden")])
+        }
+        Sensitivity::Secret => quote!(#[serde(skip_serializing)]),
+    }
+}
+
+/// Returns true if `field` is an `Option` written to a NOT NULL column of `table`, such as fields marked
+/// `#[awto(optional_insert)]`, which are left unset on insert when `None` so the database fills them.
+pub fn is_optional_insert(field: &RustField, table: &DatabaseTable) -> bool {
+    is_ty_option(&field.ty)
+        && table
+            .columns
+            .iter()
+            .any(|column| column.name == field.name && !column.nullable)
+}
+
+/// Returns `value` converted into the type of `field` with `Into`, such as from or into the inner type of a newtype.
+///
+/// Options of newtypes are converted with `Option::map`, as an option does not convert into an option of another type.
+pub fn into_field_value(field: &RustField, value: TokenStream) -> TokenStream {
+    if (field.newtype.is_some() || map_value_ty(&field.ty).is_some()) && is_ty_option(&field.ty) {
+        quote!(#value.map(::std::convert::Into::into))
+    } else {
+        quote!(#value.into())
+    }
+}
+
+/// Returns `value` of `field` unwrapped from its newtype, or `value` itself if the field is not a newtype.
+///
+/// The inner value is read from the public field of the newtype, as its type is not known where it is used.
+pub fn newtype_inner_value(field: &RustField, value: TokenStream) -> TokenStream {
+    match (&field.newtype, is_ty_option(&field.ty)) {
+        (None, _) => value,
+        (Some(_), true) => quote!(#value.map(|value| value.0)),
+        (Some(_), false) => quote!(#value.0),
+    }
+}
+
+/// Returns true if `field` is written to a computed column of `table`, which is never set by generated code.
+pub fn is_computed(field: &RustField, table: &DatabaseTable) -> bool {
+    table
+        .columns
+        .iter()
+        .any(|column| column.name == field.name && column.computed.is_some())
+}
+
+/// Returns the active value of the deprecated `field` of `model`, which is no longer written,
+/// logging a warning when its `value` is ignored.
+pub fn ignored_deprecated_value(model: &str, field: &RustField, value: TokenStream) -> TokenStream {
+    let message = format!(
+        "ignoring deprecated field `{}.{}`{}",
+        model,
+        field.name,
+        deprecation_note(field.deprecated.as_ref())
+    );
+    if is_ty_option(&field.ty) {
+        quote!({
+            if #value.is_some() {
+                ::log::warn!(#message);
+            }
+            ::sea_orm::ActiveValue::unset()
+        })
+    } else {
+        quote!({
+            ::log::warn!(#message);
+            ::sea_orm::ActiveValue::unset()
+        })
+    }
+}
+
+/// Returns the note of `deprecation` following a message, such as `: use display_name`, or an empty string.
+pub fn deprecation_note(deprecation: Option<&Deprecation>) -> String {
+    deprecation
+        .and_then(|deprecation| deprecation.note.as_ref())
+        .map(|note| format!(": {}", note))
+        .unwrap_or_default()
+}
+
+/// Returns the `#[deprecated(...)]` attribute of a field with `deprecation`.
+pub fn deprecated_attribute(deprecation: &Deprecation) -> String {
+    let mut args = Vec::new();
+    if let Some(since) = &deprecation.since {
+        args.push(format!("since = {:?}", since));
+    }
+    if let Some(note) = &deprecation.note {
+        args.push(format!("note = {:?}", note));
+    }
+
+    if args.is_empty() {
+        "#[deprecated]".to_string()
+    } else {
+        format!("#[deprecated({})]", args.join(", "))
+    }
+}
+
+/// Returns the Rust type of the generated entity field of a column, if it is supported.
+pub fn column_rust_type(ty: &DatabaseType) -> Option<TokenStream> {
+    let rust_type = match ty {
+        DatabaseType::SmallInt => quote!(i16),
+        DatabaseType::Integer => quote!(i32),
+        DatabaseType::BigInt => quote!(i64),
+        DatabaseType::Float => quote!(f32),
+        DatabaseType::Double => quote!(f64),
+        DatabaseType::Text(_) => quote!(String),
+        DatabaseType::Binary => quote!(Vec<u8>),
+        DatabaseType::Timestamp => quote!(::chrono::NaiveDateTime),
+        DatabaseType::Timestamptz => quote!(::chrono::DateTime<::chrono::FixedOffset>),
+        DatabaseType::Date => quote!(::chrono::NaiveDate),
+        DatabaseType::Time => quote!(::chrono::NaiveTime),
+        DatabaseType::Bool => quote!(bool),
+        DatabaseType::Uuid => quote!(::uuid::Uuid),
+        // Entity fields of maps depend on their values, see `map_entity_ty`
+        DatabaseType::Numeric(_)
+        | DatabaseType::Money
+        | DatabaseType::Timetz
+        | DatabaseType::Jsonb
+        | DatabaseType::Hstore => return None,
+    };
+
+    Some(rust_type)
+}
+
+/// Returns `text` as markdown code, fenced with more backticks than it contains in a row,
+/// and with pipes escaped so it can be used in table cells.
+pub fn markdown_code(text: &str) -> String {
+    let longest_run = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest_run + 1);
+    let padding = if text.starts_with('`') || text.ends_with('`') {
+        " "
+    } else {
+        ""
+    };
+
+    format!("{0}{1}{2}{1}{0}", fence, padding, text.replace('|', "\\|"))
+}
+
+/// Escapes `text` written as markdown by users, such as doc comments, to be included in generated docs.
+///
+/// Brackets are escaped so they are not resolved as links to items which are not in scope of the generated crate,
+/// and pipes and angle brackets so they do not break tables or turn into html. Code is left unchanged.
+pub fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    let mut in_fence = false;
+    for (i, line) in text.lines().enumerate() {
+        if i > 0 {
+            escaped.push('\n');
+        }
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+        if in_fence || line.trim_start().starts_with("```") {
+            escaped.push_str(line);
+            continue;
+        }
+
+        let mut code_fence: Option<usize> = None;
+        let mut chars = line.chars().peekable();
+        let mut rest = line;
+        while let Some(c) = chars.next() {
+            let current = rest;
+            rest = &rest[c.len_utf8()..];
+
+            // Links to urls are kept, as they do not depend on the items in scope
+            if c == '[' && code_fence.is_none() {
+                if let Some(link) = url_link(current) {
+                    escaped.push_str(link);
+                    for _ in link.chars().skip(1) {
+                        let c = chars.next().unwrap();
+                        rest = &rest[c.len_utf8()..];
+                    }
+                    continue;
+                }
+            }
+
+            if c == '`' {
+                let mut run = 1;
+                while chars.peek() == Some(&'`') {
+                    chars.next();
+                    rest = &rest[1..];
+                    run += 1;
+                }
+                code_fence = match code_fence {
+                    None => Some(run),
+                    Some(fence) if fence == run => None,
+                    fence => fence,
+                };
+                escaped.push_str(&"`".repeat(run));
+                continue;
+            }
+
+            if code_fence.is_none() && matches!(c, '\\' | '[' | ']' | '|' | '<' | '>') {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+    }
+
+    escaped
+}
+
+/// Returns the markdown link to a url at the start of `text`, such as `[docs](https://docs.rs)`.
+fn url_link(text: &str) -> Option<&str> {
+    let label_end = text.find("](")?;
+    if text[1..label_end].contains('[') {
+        return None;
+    }
+    let target_end = label_end + text[label_end..].find(')')?;
+    let target = &text[label_end + 2..target_end];
+    (target.contains("://") || target.starts_with("mailto:")).then(|| &text[..=target_end])
+}
+
+/// Escapes a single line of `text` written by users, like [`escape_markdown`], to be used in a table cell.
+///
+/// Pipes are also escaped in code, as they end the cell anywhere in the line.
+pub fn escape_markdown_cell(text: &str) -> String {
+    let escaped = escape_markdown(text);
+    let mut cell = String::with_capacity(escaped.len());
+    let mut previous = None;
+    for c in escaped.chars() {
+        if c == '|' && previous != Some('\\') {
+            cell.push('\\');
+        }
+        cell.push(c);
+        previous = Some(c);
+    }
+
+    cell
+}
+
+/// Writes `contents` to the file at `path` unless it already contains them, preserving its modification time.
+pub fn write_if_changed(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let path = path.as_ref();
+    let contents = contents.as_ref();
+    if fs::read(path)
+        .map(|existing| existing == contents)
+        .unwrap_or(false)
+    {
+        return Ok(());
+    }
+
+    fs::write(path, contents)
+}
+
+/// Returns the paths of the public items of generated `code` without doc comments, which fail `missing_docs`.
+///
+/// Items of private modules are skipped, as they are not reachable from other crates,
+/// and so are the items generated by the derives and macros of other crates.
+#[cfg(test)]
+pub fn undocumented_items(code: &str) -> Vec<String> {
+    fn is_pub(vis: &syn::Visibility) -> bool {
+        matches!(vis, syn::Visibility::Public(_))
+    }
+
+    fn check(attrs: &[syn::Attribute], path: String, undocumented: &mut Vec<String>) {
+        if !attrs.iter().any(|attr| attr.path.is_ident("doc")) {
+            undocumented.push(path);
+        }
+    }
+
+    /// Checks the named fields, as the fields of tuple structs and variants are not linted.
+    fn check_fields<'f>(
+        fields: impl IntoIterator<Item = &'f syn::Field>,
+        path: &str,
+        undocumented: &mut Vec<String>,
+    ) {
+        for field in fields {
+            if let Some(ident) = &field.ident {
+                check(&field.attrs, format!("{}.{}", path, ident), undocumented);
+            }
+        }
+    }
+
+    fn check_items(items: &[syn::Item], path: &str, undocumented: &mut Vec<String>) {
+        for item in items {
+            match item {
+                syn::Item::Mod(item) if is_pub(&item.vis) => {
+                    let path = format!("{}::{}", path, item.ident);
+                    check(&item.attrs, path.clone(), undocumented);
+                    if let Some((_, items)) = &item.content {
+                        check_items(items, &path, undocumented);
+                    }
+                }
+                syn::Item::Struct(item) if is_pub(&item.vis) => {
+                    let path = format!("{}::{}", path, item.ident);
+                    check(&item.attrs, path.clone(), undocumented);
+                    check_fields(
+                        item.fields.iter().filter(|field| is_pub(&field.vis)),
+                        &path,
+                        undocumented,
+                    );
+                }
+                syn::Item::Enum(item) if is_pub(&item.vis) => {
+                    let path = format!("{}::{}", path, item.ident);
+                    check(&item.attrs, path.clone(), undocumented);
+                    for variant in &item.variants {
+                        let path = format!("{}::{}", path, variant.ident);
+                        check(&variant.attrs, path.clone(), undocumented);
+                        check_fields(&variant.fields, &path, undocumented);
+                    }
+                }
+                syn::Item::Trait(item) if is_pub(&item.vis) => {
+                    let path = format!("{}::{}", path, item.ident);
+                    check(&item.attrs, path.clone(), undocumented);
+                    for item in &item.items {
+                        if let syn::TraitItem::Method(method) = item {
+                            check(
+                                &method.attrs,
+                                format!("{}::{}", path, method.sig.ident),
+                                undocumented,
+                            );
+                        }
+                    }
+                }
+                syn::Item::Impl(item) if item.trait_.is_none() => {
+                    let self_ty = &item.self_ty;
+                    let path = format!("{}::{}", path, quote!(#self_ty));
+                    for item in &item.items {
+                        match item {
+                            syn::ImplItem::Method(method) if is_pub(&method.vis) => {
+                                check(
+                                    &method.attrs,
+                                    format!("{}::{}", path, method.sig.ident),
+                                    undocumented,
+                                );
+                            }
+                            syn::ImplItem::Const(constant) if is_pub(&constant.vis) => {
+                                check(
+                                    &constant.attrs,
+                                    format!("{}::{}", path, constant.ident),
+                                    undocumented,
+                                );
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                syn::Item::Fn(item) if is_pub(&item.vis) => check(
+                    &item.attrs,
+                    format!("{}::{}", path, item.sig.ident),
+                    undocumented,
+                ),
+                syn::Item::Const(item) if is_pub(&item.vis) => check(
+                    &item.attrs,
+                    format!("{}::{}", path, item.ident),
+                    undocumented,
+                ),
+                syn::Item::Static(item) if is_pub(&item.vis) => check(
+                    &item.attrs,
+                    format!("{}::{}", path, item.ident),
+                    undocumented,
+                ),
+                syn::Item::Type(item) if is_pub(&item.vis) => check(
+                    &item.attrs,
+                    format!("{}::{}", path, item.ident),
+                    undocumented,
+                ),
+                _ => {}
+            }
+        }
+    }
+
+    let file = syn::parse_file(code).expect("generated code is valid Rust");
+    let mut undocumented = Vec::new();
+    check_items(&file.items, "crate", &mut undocumented);
+
+    undocumented
+}
@@ -0,0 +1,51 @@
+use std::fmt;
+
+/// A problem found while compiling a generated package which does not stop the compilation.
+///
+/// Warnings are printed by the build scripts of generated packages as cargo warnings,
+/// in a format read back by `awto compile` to summarize them at the end of the run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Warning {
+    /// Stable identifier of the warning, such as `unknown_column_reference`, used to allow it.
+    pub id: &'static str,
+    /// Name of the model the warning is about, if any.
+    pub model: Option<String>,
+    /// Name of the field of [`model`](Self::model) the warning is about, if any.
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl Warning {
+    /// Computed columns referencing a name which is not a column of their table.
+    pub const UNKNOWN_COLUMN_REFERENCE: &'static str = "unknown_column_reference";
+    /// Extension files of a database package not named after one of its tables.
+    pub const UNREGISTERED_EXTENSION: &'static str = "unregistered_extension";
+    /// Columns of deprecated fields kept by sync instead of being dropped.
+    pub const DEPRECATED_COLUMN_KEPT: &'static str = "deprecated_column_kept";
+    /// Database packages compiled from a schema without any database table.
+    pub const EMPTY_SCHEMA: &'static str = "empty_schema";
+    /// Partitioned models whose table exists without partitions.
+    pub const UNPARTITIONED_TABLE: &'static str = "unpartitioned_table";
+    /// Columns narrowed by a forced sync although rows of the database do not fit them.
+    pub const FORCED_NARROWING: &'static str = "forced_narrowing";
+
+    /// Prints the warning as a cargo warning of the running build script.
+    pub fn emit(&self) {
+        println!("cargo:warning={}", self);
+    }
+}
+
+/// Formats the warning as `awto warning[id] Model.field: message`, leaving out the model and field when unset.
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "awto warning[{}]", self.id)?;
+        if let Some(model) = &self.model {
+            write!(f, " {}", model)?;
+            if let Some(field) = &self.field {
+                write!(f, ".{}", field)?;
+            }
+        }
+
+        write!(f, ": {}", self.message)
+    }
+}
@@ -1,31 +1,968 @@
 use better_bae::FromAttributes;
+use proc_macro2::{TokenStream, TokenTree};
 use quote::ToTokens;
 use syn::spanned::Spanned;
 
 #[derive(Default, FromAttributes)]
 #[bae("awto")]
-pub struct RootAttrs {}
+pub struct RootAttrs {
+    pub database: Option<syn::LitStr>,
+    pub events: Option<()>,
+    pub external: Option<()>,
+    pub feature: Option<syn::LitStr>,
+    pub idempotent_create: Option<()>,
+    pub notify: Option<()>,
+    pub order_by: Option<syn::Ident>,
+    pub readonly: Option<()>,
+    pub skip_rest: Option<()>,
+    pub temporal: Option<()>,
+    pub upsert_on: Option<syn::Ident>,
+    pub versioned: Option<()>,
+}
+
+/// Arguments of `#[awto(fulltext(fields(...), language = "..."))]`.
+pub struct FulltextAttr {
+    pub fields: Vec<syn::Ident>,
+    pub language: Option<syn::LitStr>,
+}
+
+impl syn::parse::Parse for FulltextAttr {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let content;
+        syn::parenthesized!(content in input);
+
+        let mut fields = None;
+        let mut language = None;
+        while !content.is_empty() {
+            let ident: syn::Ident = content.parse()?;
+            if ident == "fields" {
+                let fields_content;
+                syn::parenthesized!(fields_content in content);
+                fields = Some(
+                    fields_content
+                        .parse_terminated::<_, syn::Token![,]>(
+                            <syn::Ident as syn::parse::Parse>::parse,
+                        )?
+                        .into_iter()
+                        .collect::<Vec<_>>(),
+                );
+            } else if ident == "language" {
+                content.parse::<syn::Token![=]>()?;
+                language = Some(content.parse()?);
+            } else {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    "expected `fields(...)` or `language = \"...\"`",
+                ));
+            }
+
+            if !content.is_empty() {
+                content.parse::<syn::Token![,]>()?;
+            }
+        }
+
+        let fields = fields
+            .filter(|fields| !fields.is_empty())
+            .ok_or_else(|| input.error("fulltext requires at least one field in `fields(...)`"))?;
+
+        Ok(FulltextAttr { fields, language })
+    }
+}
+
+impl FulltextAttr {
+    /// Removes `fulltext(...)` from the `#[awto(...)]` attributes and parses it.
+    ///
+    /// Its nested arguments are not supported by [`RootAttrs`], which expects `name = value` pairs.
+    pub fn take_from_attributes(attrs: &mut [syn::Attribute]) -> syn::Result<Option<Self>> {
+        take_args(attrs, "fulltext")
+            .into_iter()
+            .last()
+            .map(syn::parse2)
+            .transpose()
+    }
+}
+
+/// Arguments of `#[awto(partition(by = "range", column = created_at, interval = "month"))]`.
+pub struct PartitionAttr {
+    pub by: Option<syn::LitStr>,
+    pub column: syn::Ident,
+    pub interval: Option<syn::LitStr>,
+}
+
+impl syn::parse::Parse for PartitionAttr {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let content;
+        syn::parenthesized!(content in input);
+
+        let mut by = None;
+        let mut column = None;
+        let mut interval = None;
+        while !content.is_empty() {
+            let ident: syn::Ident = content.parse()?;
+            content.parse::<syn::Token![=]>()?;
+            if ident == "by" {
+                by = Some(content.parse()?);
+            } else if ident == "column" {
+                column = Some(content.parse()?);
+            } else if ident == "interval" {
+                interval = Some(content.parse()?);
+            } else {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    "expected `by = \"range\"`, `column = field` or `interval = \"...\"`",
+                ));
+            }
+
+            if !content.is_empty() {
+                content.parse::<syn::Token![,]>()?;
+            }
+        }
+
+        let column = column.ok_or_else(|| input.error("partition requires a `column = field`"))?;
+
+        Ok(PartitionAttr {
+            by,
+            column,
+            interval,
+        })
+    }
+}
+
+impl PartitionAttr {
+    /// Removes `partition(...)` from the `#[awto(...)]` attributes and parses it.
+    pub fn take_from_attributes(attrs: &mut [syn::Attribute]) -> syn::Result<Option<Self>> {
+        take_args(attrs, "partition")
+            .into_iter()
+            .last()
+            .map(syn::parse2)
+            .transpose()
+    }
+}
+
+/// Arguments of `#[awto(visibility(entity = "pub", active_model = "pub(crate)"))]`.
+pub struct VisibilityAttr {
+    pub entity: Option<syn::LitStr>,
+    pub active_model: Option<syn::LitStr>,
+}
+
+impl syn::parse::Parse for VisibilityAttr {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let content;
+        syn::parenthesized!(content in input);
+
+        let mut entity = None;
+        let mut active_model = None;
+        while !content.is_empty() {
+            let ident: syn::Ident = content.parse()?;
+            content.parse::<syn::Token![=]>()?;
+            if ident == "entity" {
+                entity = Some(content.parse()?);
+            } else if ident == "active_model" {
+                active_model = Some(content.parse()?);
+            } else {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    "expected `entity = \"...\"` or `active_model = \"...\"`",
+                ));
+            }
+
+            if !content.is_empty() {
+                content.parse::<syn::Token![,]>()?;
+            }
+        }
+
+        Ok(VisibilityAttr {
+            entity,
+            active_model,
+        })
+    }
+}
+
+impl VisibilityAttr {
+    /// Removes `visibility(...)` from the `#[awto(...)]` attributes and parses it.
+    pub fn take_from_attributes(attrs: &mut [syn::Attribute]) -> syn::Result<Option<Self>> {
+        take_args(attrs, "visibility")
+            .into_iter()
+            .last()
+            .map(syn::parse2)
+            .transpose()
+    }
+}
+
+/// Arguments of `#[awto(graphql(subscriptions))]`.
+pub struct GraphqlAttr {
+    pub subscriptions: bool,
+}
+
+impl syn::parse::Parse for GraphqlAttr {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let content;
+        syn::parenthesized!(content in input);
+
+        let mut subscriptions = false;
+        while !content.is_empty() {
+            let ident: syn::Ident = content.parse()?;
+            if ident == "subscriptions" {
+                subscriptions = true;
+            } else {
+                return Err(syn::Error::new(ident.span(), "expected `subscriptions`"));
+            }
+
+            if !content.is_empty() {
+                content.parse::<syn::Token![,]>()?;
+            }
+        }
+
+        Ok(GraphqlAttr { subscriptions })
+    }
+}
+
+impl GraphqlAttr {
+    /// Removes `graphql(...)` from the `#[awto(...)]` attributes and parses it.
+    pub fn take_from_attributes(attrs: &mut [syn::Attribute]) -> syn::Result<Option<Self>> {
+        take_args(attrs, "graphql")
+            .into_iter()
+            .last()
+            .map(syn::parse2)
+            .transpose()
+    }
+}
+
+/// Arguments of `#[awto(rls(policy = "...", using = "..."))]`.
+pub struct RlsAttr {
+    pub policy: syn::LitStr,
+    pub using: syn::LitStr,
+}
+
+impl syn::parse::Parse for RlsAttr {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let content;
+        syn::parenthesized!(content in input);
+
+        let mut policy = None;
+        let mut using = None;
+        while !content.is_empty() {
+            let ident: syn::Ident = content.parse()?;
+            if ident == "policy" {
+                content.parse::<syn::Token![=]>()?;
+                policy = Some(content.parse()?);
+            } else if ident == "using" {
+                content.parse::<syn::Token![=]>()?;
+                using = Some(content.parse()?);
+            } else {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    "expected `policy = \"...\"` or `using = \"...\"`",
+                ));
+            }
+
+            if !content.is_empty() {
+                content.parse::<syn::Token![,]>()?;
+            }
+        }
+
+        let policy = policy.ok_or_else(|| input.error("rls requires a `policy = \"...\"` name"))?;
+        let using =
+            using.ok_or_else(|| input.error("rls requires a `using = \"...\"` expression"))?;
+
+        Ok(RlsAttr { policy, using })
+    }
+}
+
+impl RlsAttr {
+    /// Removes each `rls(...)` from the `#[awto(...)]` attributes and parses them.
+    pub fn take_from_attributes(attrs: &mut [syn::Attribute]) -> syn::Result<Vec<Self>> {
+        take_args(attrs, "rls")
+            .into_iter()
+            .map(syn::parse2)
+            .collect()
+    }
+}
+
+/// Arguments of `#[awto(projection(name = "...", fields(...)))]`, of which a model can have several.
+pub struct ProjectionAttr {
+    pub name: syn::LitStr,
+    pub fields: Vec<syn::Ident>,
+}
+
+impl syn::parse::Parse for ProjectionAttr {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let content;
+        syn::parenthesized!(content in input);
+
+        let mut name = None;
+        let mut fields = None;
+        while !content.is_empty() {
+            let ident: syn::Ident = content.parse()?;
+            if ident == "name" {
+                content.parse::<syn::Token![=]>()?;
+                name = Some(content.parse()?);
+            } else if ident == "fields" {
+                let fields_content;
+                syn::parenthesized!(fields_content in content);
+                fields = Some(
+                    fields_content
+                        .parse_terminated::<_, syn::Token![,]>(
+                            <syn::Ident as syn::parse::Parse>::parse,
+                        )?
+                        .into_iter()
+                        .collect::<Vec<_>>(),
+                );
+            } else {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    "expected `name = \"...\"` or `fields(...)`",
+                ));
+            }
+
+            if !content.is_empty() {
+                content.parse::<syn::Token![,]>()?;
+            }
+        }
+
+        let name = name
+            .ok_or_else(|| input.error("projection requires a `name = \"...\"` of its struct"))?;
+        let fields = fields.filter(|fields| !fields.is_empty()).ok_or_else(|| {
+            input.error("projection requires at least one field in `fields(...)`")
+        })?;
+
+        Ok(ProjectionAttr { name, fields })
+    }
+}
+
+impl ProjectionAttr {
+    /// Removes each `projection(...)` from the `#[awto(...)]` attributes and parses them.
+    pub fn take_from_attributes(attrs: &mut [syn::Attribute]) -> syn::Result<Vec<Self>> {
+        take_args(attrs, "projection")
+            .into_iter()
+            .map(syn::parse2)
+            .collect()
+    }
+}
+
+/// Arguments of `#[awto(scope(name = "..."))]` or `#[awto(scope(name(param: Type, ...) = "..."))]`,
+/// of which a model can have several.
+pub struct ScopeAttr {
+    pub name: syn::Ident,
+    pub params: Vec<(syn::Ident, syn::Type)>,
+    pub condition: syn::LitStr,
+}
+
+impl syn::parse::Parse for ScopeAttr {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let content;
+        syn::parenthesized!(content in input);
+
+        let name: syn::Ident = content.parse()?;
+        let mut params = Vec::new();
+        if content.peek(syn::token::Paren) {
+            let params_content;
+            syn::parenthesized!(params_content in content);
+            while !params_content.is_empty() {
+                let param: syn::Ident = params_content.parse()?;
+                params_content.parse::<syn::Token![:]>()?;
+                let ty: syn::Type = params_content.parse()?;
+                params.push((param, ty));
+
+                if !params_content.is_empty() {
+                    params_content.parse::<syn::Token![,]>()?;
+                }
+            }
+            if params.is_empty() {
+                return Err(syn::Error::new(
+                    name.span(),
+                    "scope parameters must be listed as `name(param: Type, ...)`, or left out",
+                ));
+            }
+        }
+        content.parse::<syn::Token![=]>()?;
+        let condition: syn::LitStr = content.parse()?;
+        if !content.is_empty() {
+            return Err(content.error("expected a single `name = \"...\"` per scope"));
+        }
+
+        Ok(ScopeAttr {
+            name,
+            params,
+            condition,
+        })
+    }
+}
+
+impl ScopeAttr {
+    /// Removes each `scope(...)` from the `#[awto(...)]` attributes and parses them.
+    pub fn take_from_attributes(attrs: &mut [syn::Attribute]) -> syn::Result<Vec<Self>> {
+        take_args(attrs, "scope")
+            .into_iter()
+            .map(syn::parse2)
+            .collect()
+    }
+}
+
+/// Arguments of `#[awto(authorize(read = "...", write = "...", delete = "..."))]`, which are all optional.
+pub struct AuthorizeAttr {
+    pub read: Option<syn::LitStr>,
+    pub write: Option<syn::LitStr>,
+    pub delete: Option<syn::LitStr>,
+}
+
+impl syn::parse::Parse for AuthorizeAttr {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let content;
+        syn::parenthesized!(content in input);
+
+        let mut authorize = AuthorizeAttr {
+            read: None,
+            write: None,
+            delete: None,
+        };
+        while !content.is_empty() {
+            let ident: syn::Ident = content.parse()?;
+            let scope = if ident == "read" {
+                &mut authorize.read
+            } else if ident == "write" {
+                &mut authorize.write
+            } else if ident == "delete" {
+                &mut authorize.delete
+            } else {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    "expected `read = \"...\"`, `write = \"...\"` or `delete = \"...\"`",
+                ));
+            };
+            content.parse::<syn::Token![=]>()?;
+            let value: syn::LitStr = content.parse()?;
+            if value.value().trim().is_empty() || value.value().contains(char::is_whitespace) {
+                return Err(syn::Error::new(
+                    value.span(),
+                    "scopes must be non-empty and without whitespace, such as \"catalog:write\" or \"public\"",
+                ));
+            }
+            *scope = Some(value);
+
+            if !content.is_empty() {
+                content.parse::<syn::Token![,]>()?;
+            }
+        }
+
+        if authorize.read.is_none() && authorize.write.is_none() && authorize.delete.is_none() {
+            return Err(
+                input.error("authorize requires at least one of `read`, `write` or `delete`")
+            );
+        }
+
+        Ok(authorize)
+    }
+}
+
+impl AuthorizeAttr {
+    /// Removes `authorize(...)` from the `#[awto(...)]` attributes and parses it.
+    pub fn take_from_attributes(attrs: &mut [syn::Attribute]) -> syn::Result<Option<Self>> {
+        take_args(attrs, "authorize")
+            .into_iter()
+            .last()
+            .map(syn::parse2)
+            .transpose()
+    }
+}
+
+/// Arguments of `#[awto(entity_attrs("...", ...))]`, each holding outer attributes added verbatim to the generated entity.
+pub struct EntityAttrsAttr {
+    pub attrs: Vec<syn::LitStr>,
+}
+
+impl syn::parse::Parse for EntityAttrsAttr {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let content;
+        syn::parenthesized!(content in input);
+
+        let attrs: Vec<syn::LitStr> = content
+            .parse_terminated::<_, syn::Token![,]>(<syn::LitStr as syn::parse::Parse>::parse)?
+            .into_iter()
+            .collect();
+        if attrs.is_empty() {
+            return Err(input.error(
+                "entity_attrs requires at least one attribute, such as \"#[serde(rename_all = \\\"camelCase\\\")]\"",
+            ));
+        }
+        for attr in &attrs {
+            let parsed = syn::parse::Parser::parse_str(syn::Attribute::parse_outer, &attr.value())
+                .map_err(|err| {
+                    syn::Error::new(
+                        attr.span(),
+                        format!("invalid entity attribute `{}`: {}", attr.value(), err),
+                    )
+                })?;
+            if parsed.is_empty() {
+                return Err(syn::Error::new(
+                    attr.span(),
+                    "entity_attrs must hold attributes, such as \"#[serde(rename_all = \\\"camelCase\\\")]\"",
+                ));
+            }
+        }
+
+        Ok(EntityAttrsAttr { attrs })
+    }
+}
+
+impl EntityAttrsAttr {
+    /// Removes each `entity_attrs(...)` from the `#[awto(...)]` attributes and parses them, in their order.
+    pub fn take_from_attributes(attrs: &mut [syn::Attribute]) -> syn::Result<Vec<syn::LitStr>> {
+        let taken = take_args(attrs, "entity_attrs")
+            .into_iter()
+            .map(syn::parse2::<EntityAttrsAttr>)
+            .collect::<syn::Result<Vec<_>>>()?;
+
+        Ok(taken.into_iter().flat_map(|taken| taken.attrs).collect())
+    }
+}
+
+/// Arguments of `#[awto(localized(locales = "..."))]` on a field.
+pub struct LocalizedAttr {
+    /// Comma separated locales, starting with the default locale.
+    pub locales: syn::LitStr,
+}
+
+impl syn::parse::Parse for LocalizedAttr {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let content;
+        syn::parenthesized!(content in input);
+
+        let ident: syn::Ident = content.parse()?;
+        if ident != "locales" {
+            return Err(syn::Error::new(
+                ident.span(),
+                "expected `locales = \"...\"`",
+            ));
+        }
+        content.parse::<syn::Token![=]>()?;
+        let locales = content.parse()?;
+        if !content.is_empty() {
+            content.parse::<syn::Token![,]>()?;
+        }
+        if !content.is_empty() {
+            return Err(content.error("localized only accepts `locales = \"...\"`"));
+        }
+
+        Ok(LocalizedAttr { locales })
+    }
+}
+
+impl LocalizedAttr {
+    /// Removes `localized(...)` from the `#[awto(...)]` attributes of a field and parses it.
+    ///
+    /// Its nested arguments are not supported by [`ItemAttrs`], which expects `name = value` pairs.
+    pub fn take_from_attributes(attrs: &mut [syn::Attribute]) -> syn::Result<Option<Self>> {
+        take_args(attrs, "localized")
+            .into_iter()
+            .last()
+            .map(syn::parse2)
+            .transpose()
+    }
+}
+
+/// Arguments of `#[awto(column(backfill = "..."))]` on a field.
+pub struct ColumnAttr {
+    /// Sql expression setting the rows which do not fit a narrowed column before it is altered.
+    pub backfill: syn::LitStr,
+}
+
+impl syn::parse::Parse for ColumnAttr {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let content;
+        syn::parenthesized!(content in input);
+
+        let ident: syn::Ident = content.parse()?;
+        if ident != "backfill" {
+            return Err(syn::Error::new(
+                ident.span(),
+                "expected `backfill = \"...\"`",
+            ));
+        }
+        content.parse::<syn::Token![=]>()?;
+        let backfill: syn::LitStr = content.parse()?;
+        if !content.is_empty() {
+            content.parse::<syn::Token![,]>()?;
+        }
+        if !content.is_empty() {
+            return Err(content.error("column only accepts `backfill = \"...\"`"));
+        }
+        if backfill.value().trim().is_empty() {
+            return Err(syn::Error::new(
+                backfill.span(),
+                "backfill must be a sql expression",
+            ));
+        }
+
+        Ok(ColumnAttr { backfill })
+    }
+}
+
+impl ColumnAttr {
+    /// Removes `column(...)` from the `#[awto(...)]` attributes of a field and parses it.
+    pub fn take_from_attributes(attrs: &mut [syn::Attribute]) -> syn::Result<Option<Self>> {
+        take_args(attrs, "column")
+            .into_iter()
+            .last()
+            .map(syn::parse2)
+            .transpose()
+    }
+}
+
+/// Arguments of `#[awto(deprecated(note = "...", since = "..."))]` on a field, which are both optional.
+pub struct DeprecatedAttr {
+    pub note: Option<syn::LitStr>,
+    /// Date the field was deprecated on, as `YYYY-MM-DD`.
+    pub since: Option<syn::LitStr>,
+}
+
+impl syn::parse::Parse for DeprecatedAttr {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let mut note = None;
+        let mut since = None;
+        if input.is_empty() {
+            return Ok(DeprecatedAttr { note, since });
+        }
+
+        let content;
+        syn::parenthesized!(content in input);
+        while !content.is_empty() {
+            let ident: syn::Ident = content.parse()?;
+            if ident != "note" && ident != "since" {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    "expected `note = \"...\"` or `since = \"...\"`",
+                ));
+            }
+            content.parse::<syn::Token![=]>()?;
+            let value: syn::LitStr = content.parse()?;
+            if ident == "note" {
+                note = Some(value);
+            } else if is_date(&value.value()) {
+                since = Some(value);
+            } else {
+                return Err(syn::Error::new(
+                    value.span(),
+                    "since must be a date such as \"2024-06-01\"",
+                ));
+            }
+
+            if !content.is_empty() {
+                content.parse::<syn::Token![,]>()?;
+            }
+        }
+
+        Ok(DeprecatedAttr { note, since })
+    }
+}
+
+impl DeprecatedAttr {
+    /// Removes `deprecated` or `deprecated(...)` from the `#[awto(...)]` attributes of a field and parses it.
+    pub fn take_from_attributes(attrs: &mut [syn::Attribute]) -> syn::Result<Option<Self>> {
+        take_args(attrs, "deprecated")
+            .into_iter()
+            .last()
+            .map(syn::parse2)
+            .transpose()
+    }
+
+    /// Expands the deprecation into an `awto::schema::Deprecation`.
+    pub fn expand(&self) -> TokenStream {
+        let optional = |lit: &Option<syn::LitStr>| match lit {
+            Some(lit) => quote::quote!(Some(#lit.to_string())),
+            None => quote::quote!(None),
+        };
+        let note = optional(&self.note);
+        let since = optional(&self.since);
+
+        quote::quote!(awto::schema::Deprecation {
+            note: #note,
+            since: #since,
+        })
+    }
+}
+
+/// Arguments of `#[awto(proto(version = 2, v1_default(field = "...")))]` on a model, which are both optional.
+pub struct ProtoAttr {
+    /// Version of the protobuf package the message was last changed in, 1 when unset.
+    pub version: Option<syn::LitInt>,
+    /// Values of fields removed since earlier versions, as `(version, field, value)`.
+    pub defaults: Vec<(u32, syn::Ident, syn::LitStr)>,
+}
+
+impl syn::parse::Parse for ProtoAttr {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let content;
+        syn::parenthesized!(content in input);
+
+        let mut version: Option<syn::LitInt> = None;
+        let mut defaults = Vec::new();
+        while !content.is_empty() {
+            let ident: syn::Ident = content.parse()?;
+            let default_version = ident
+                .to_string()
+                .strip_prefix('v')
+                .and_then(|rest| rest.strip_suffix("_default"))
+                .and_then(|number| number.parse::<u32>().ok());
+            if ident == "version" {
+                content.parse::<syn::Token![=]>()?;
+                let lit: syn::LitInt = content.parse()?;
+                if lit.base10_parse::<u32>()? == 0 {
+                    return Err(syn::Error::new(lit.span(), "versions start at 1"));
+                }
+                version = Some(lit);
+            } else if let Some(default_version) = default_version {
+                let fields_content;
+                syn::parenthesized!(fields_content in content);
+                while !fields_content.is_empty() {
+                    let field: syn::Ident = fields_content.parse()?;
+                    fields_content.parse::<syn::Token![=]>()?;
+                    let value: syn::LitStr = fields_content.parse()?;
+                    value.parse::<syn::Expr>().map_err(|err| {
+                        syn::Error::new(
+                            value.span(),
+                            format!("default must be a Rust expression: {}", err),
+                        )
+                    })?;
+                    defaults.push((default_version, field, value));
+
+                    if !fields_content.is_empty() {
+                        fields_content.parse::<syn::Token![,]>()?;
+                    }
+                }
+            } else {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    "expected `version = ...` or `v1_default(field = \"...\")`",
+                ));
+            }
+
+            if !content.is_empty() {
+                content.parse::<syn::Token![,]>()?;
+            }
+        }
+
+        let message_version = match &version {
+            Some(version) => version.base10_parse::<u32>()?,
+            None => 1,
+        };
+        if let Some((_, field, _)) = defaults.iter().find(|(default_version, _, _)| {
+            *default_version == 0 || *default_version >= message_version
+        }) {
+            return Err(syn::Error::new(
+                field.span(),
+                format!(
+                    "defaults are only used by versions before the version of the message, which is {}",
+                    message_version
+                ),
+            ));
+        }
+
+        Ok(ProtoAttr { version, defaults })
+    }
+}
+
+impl ProtoAttr {
+    /// Removes `proto(...)` from the `#[awto(...)]` attributes of a model and parses it.
+    pub fn take_from_attributes(attrs: &mut [syn::Attribute]) -> syn::Result<Option<Self>> {
+        take_args(attrs, "proto")
+            .into_iter()
+            .last()
+            .map(syn::parse2)
+            .transpose()
+    }
+
+    /// Expands the version of the message, 1 when unset.
+    pub fn expand_version(attr: &Option<Self>) -> syn::Result<TokenStream> {
+        let version = match attr.as_ref().and_then(|attr| attr.version.as_ref()) {
+            Some(version) => version.base10_parse::<u32>()?,
+            None => 1,
+        };
+
+        Ok(quote::quote!(#version))
+    }
+
+    /// Expands the defaults of removed fields into `awto::protobuf::ProtobufDefault`s.
+    pub fn expand_defaults(attr: &Option<Self>) -> TokenStream {
+        let defaults =
+            attr.iter()
+                .flat_map(|attr| &attr.defaults)
+                .map(|(version, field, value)| {
+                    let field = field.to_string();
+                    quote::quote!(awto::protobuf::ProtobufDefault {
+                        version: #version,
+                        field: #field.to_string(),
+                        value: #value.to_string(),
+                    })
+                });
+
+        quote::quote!(vec![ #( #defaults ),* ])
+    }
+}
+
+/// Returns whether `value` is a date written as `YYYY-MM-DD`.
+fn is_date(value: &str) -> bool {
+    let parts: Vec<&str> = value.split('-').collect();
+    matches!(parts.as_slice(), [year, month, day]
+        if year.len() == 4 && month.len() == 2 && day.len() == 2
+            && parts.iter().all(|part| part.chars().all(|c| c.is_ascii_digit()))
+            && (1..=12).contains(&month.parse::<u32>().unwrap())
+            && (1..=31).contains(&day.parse::<u32>().unwrap()))
+}
+
+/// Marker of a mixin, `#[awto(mixin)]`, whose fields are spliced into the models including it.
+pub struct MixinAttr;
+
+impl MixinAttr {
+    /// Removes `mixin` from the `#[awto(...)]` attributes, returning whether it was present.
+    pub fn take_from_attributes(attrs: &mut [syn::Attribute]) -> syn::Result<bool> {
+        let taken = take_args(attrs, "mixin");
+        if let Some(tokens) = taken.iter().find(|tokens| !tokens.is_empty()) {
+            return Err(syn::Error::new(
+                tokens.span(),
+                "mixin does not take arguments",
+            ));
+        }
+
+        Ok(!taken.is_empty())
+    }
+}
+
+/// Marker of a newtype, `#[awto(newtype)]`, whose fields are stored like its inner type.
+pub struct NewtypeAttr;
+
+impl NewtypeAttr {
+    /// Removes `newtype` from the `#[awto(...)]` attributes, returning whether it was present.
+    pub fn take_from_attributes(attrs: &mut [syn::Attribute]) -> syn::Result<bool> {
+        let taken = take_args(attrs, "newtype");
+        if let Some(tokens) = taken.iter().find(|tokens| !tokens.is_empty()) {
+            return Err(syn::Error::new(
+                tokens.span(),
+                "newtype does not take arguments",
+            ));
+        }
+
+        Ok(!taken.is_empty())
+    }
+}
+
+/// A mixin included with `#[awto(include = Mixin)]`.
+pub struct IncludeAttr(pub syn::Ident);
+
+impl syn::parse::Parse for IncludeAttr {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        input.parse::<syn::Token![=]>()?;
+        Ok(IncludeAttr(input.parse()?))
+    }
+}
+
+impl IncludeAttr {
+    /// Removes each `include = ...` from the `#[awto(...)]` attributes and parses them.
+    pub fn take_from_attributes(attrs: &mut [syn::Attribute]) -> syn::Result<Vec<Self>> {
+        take_args(attrs, "include")
+            .into_iter()
+            .map(syn::parse2)
+            .collect()
+    }
+}
+
+/// Lint suppressions of `#[awto(allow(...))]`, read by `awto schema lint`.
+pub struct AllowAttr;
+
+impl AllowAttr {
+    /// Removes `allow(...)` from the `#[awto(...)]` attributes, ensuring it only lists lint identifiers.
+    ///
+    /// Suppressions are only read from the source by the cli, so they are not part of the expanded models.
+    pub fn strip_from_attributes(attrs: &mut [syn::Attribute]) -> syn::Result<()> {
+        for tokens in take_args(attrs, "allow") {
+            let parser = |input: syn::parse::ParseStream| {
+                let content;
+                syn::parenthesized!(content in input);
+                content
+                    .parse_terminated::<_, syn::Token![,]>(<syn::Ident as syn::parse::Parse>::parse)
+            };
+            syn::parse::Parser::parse2(parser, tokens)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Removes the arguments starting with `name` from the `#[awto(...)]` attributes,
+/// returning the tokens following `name` of each argument.
+fn take_args(attrs: &mut [syn::Attribute], name: &str) -> Vec<TokenStream> {
+    let mut taken = Vec::new();
+
+    for attr in attrs.iter_mut().filter(|attr| attr.path.is_ident("awto")) {
+        let group = match attr.tokens.clone().into_iter().next() {
+            Some(TokenTree::Group(group)) => group,
+            _ => continue,
+        };
+
+        let mut args: Vec<Vec<TokenTree>> = vec![Vec::new()];
+        for token in group.stream() {
+            match &token {
+                TokenTree::Punct(punct) if punct.as_char() == ',' => args.push(Vec::new()),
+                _ => args.last_mut().unwrap().push(token),
+            }
+        }
+
+        let (named_args, other_args): (Vec<_>, Vec<_>) = args
+            .into_iter()
+            .filter(|arg| !arg.is_empty())
+            .partition(|arg| matches!(arg.first(), Some(TokenTree::Ident(ident)) if ident == name));
+        if !named_args.is_empty() {
+            taken.extend(
+                named_args
+                    .into_iter()
+                    .map(|arg| arg.into_iter().skip(1).collect::<TokenStream>()),
+            );
+
+            let other_args = other_args
+                .into_iter()
+                .map(|arg| arg.into_iter().collect::<TokenStream>());
+            attr.tokens = quote::quote!(( #( #other_args ),* ));
+        }
+    }
+
+    taken
+}
 
 #[derive(Default, FromAttributes)]
 #[bae("awto")]
 pub struct ItemAttrs {
+    pub computed: Option<syn::LitStr>,
     pub db_type: Option<syn::LitStr>,
     pub default: Option<syn::Lit>,
     pub default_raw: Option<syn::LitStr>,
+    pub fake: Option<syn::LitStr>,
+    pub feature: Option<syn::LitStr>,
     pub max_len: Option<syn::LitInt>,
+    pub normalize: Option<syn::LitStr>,
+    pub normalize_fn: Option<syn::LitStr>,
+    pub optional_insert: Option<()>,
+    pub primary_key: Option<()>,
     pub proto_type: Option<syn::LitStr>,
-    pub references: Option<KeyVal<syn::Ident, syn::LitStr>>,
+    pub references: Option<ReferencesAttr>,
+    pub sensitive: Option<()>,
+    pub sensitivity: Option<syn::LitStr>,
+    pub stored: Option<()>,
     pub unique: Option<()>,
 }
 
+/// Arguments of `#[awto(references = (Model, "column", constraint = true))]` on a field,
+/// whose `constraint` is optional, or `#[awto(references = (Model, "column", cross_database))]`.
 #[derive(Debug)]
-pub struct KeyVal<K, V>(pub K, pub V);
+pub struct ReferencesAttr {
+    pub table: syn::Ident,
+    pub column: syn::LitStr,
+    /// Whether a foreign key constraint is created, which is decided by the referenced table when unset.
+    pub constraint: Option<syn::LitBool>,
+    /// Whether the referenced table belongs to another database, which never gets a foreign key constraint.
+    pub cross_database: Option<syn::Ident>,
+}
 
-impl<K, V> syn::parse::Parse for KeyVal<K, V>
-where
-    K: syn::parse::Parse,
-    V: syn::parse::Parse,
-{
+impl syn::parse::Parse for ReferencesAttr {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
         let lookahead = input.lookahead1();
         if !lookahead.peek(syn::token::Paren) {
@@ -36,27 +973,51 @@ where
         let group_span = group.span();
         let mut elems = group.elems.into_iter();
 
-        let first: K = syn::parse2(
+        let table: syn::Ident = syn::parse2(
             elems
                 .next()
                 .ok_or_else(|| syn::Error::new(group_span, "expected group (..., ...)"))?
                 .into_token_stream(),
         )?;
 
-        let second: V = syn::parse2(
+        let column: syn::LitStr = syn::parse2(
             elems
                 .next()
                 .ok_or_else(|| syn::Error::new(group_span, "expected group (..., ...)"))?
                 .into_token_stream(),
         )?;
 
+        let (constraint, cross_database) = match elems.next() {
+            Some(syn::Expr::Assign(syn::ExprAssign { left, right, .. })) if matches!(&*left, syn::Expr::Path(path) if path.path.is_ident("constraint")) => {
+                (
+                    Some(syn::parse2::<syn::LitBool>(right.into_token_stream())?),
+                    None,
+                )
+            }
+            Some(syn::Expr::Path(path)) if path.path.is_ident("cross_database") => {
+                (None, path.path.get_ident().cloned())
+            }
+            Some(elem) => {
+                return Err(syn::Error::new(
+                    elem.span(),
+                    "expected `constraint = true`, `constraint = false` or `cross_database`",
+                ))
+            }
+            None => (None, None),
+        };
+
         if elems.next().is_some() {
             return Err(syn::Error::new(
                 group_span,
-                "expected group of only 2 items (..., ...)",
+                "expected group of at most 3 items (Model, \"column\", constraint = ... or cross_database)",
             ));
         }
 
-        Ok(KeyVal(first, second))
+        Ok(ReferencesAttr {
+            table,
+            column,
+            constraint,
+            cross_database,
+        })
     }
 }
@@ -39,14 +39,23 @@ pub fn protobuf_service(
 
 #[proc_macro]
 pub fn schema(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    // Kept in the expansion, so `awto --expand` finds the models of schemas written by other macros
+    let tokens = input.to_string();
     let input = syn::parse_macro_input!(
         input as <crate::proc_macros::Schema as crate::util::ProcMacro>::Input
     );
 
     match <crate::proc_macros::Schema as crate::util::ProcMacro>::new(input) {
-        Ok(proc) => crate::util::ProcMacro::expand(proc)
-            .unwrap_or_else(syn::Error::into_compile_error)
+        Ok(proc) => match crate::util::ProcMacro::expand(proc) {
+            Ok(expanded) => quote::quote!(
+                #expanded
+
+                #[doc(hidden)]
+                const _: awto::schema::ExpandedSchema = awto::schema::ExpandedSchema(#tokens);
+            )
             .into(),
+            Err(err) => err.into_compile_error().into(),
+        },
         Err(err) => err.into_compile_error(None),
     }
 }
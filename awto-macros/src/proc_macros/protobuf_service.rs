@@ -3,9 +3,12 @@ use std::iter::FromIterator;
 use heck::CamelCase;
 use proc_macro2::TokenStream;
 use quote::{format_ident, quote, quote_spanned};
-use syn::spanned::Spanned;
+use syn::{ext::IdentExt, spanned::Spanned};
 
-use crate::{error::Error, util::ProcMacro};
+use crate::{
+    error::Error,
+    util::{doc_comment, ProcMacro},
+};
 
 pub struct ProtobufService {
     ident: syn::Ident,
@@ -43,13 +46,17 @@ impl ProtobufService {
                     validators.extend(method_validators);
 
                     let is_async = method.sig.asyncness.is_some();
+                    let doc = match doc_comment(&method.attrs) {
+                        Some(doc) => quote!(Some(#doc.to_string())),
+                        None => quote!(None),
+                    };
 
                     // Validate parameter
                     let param_type_validator_ident =
                         format_ident!("{}_{}_ParamTypeValidator", ident, name);
                     validators.push(quote_spanned!(
                             param.span()=>
-                                #[allow(non_camel_case_types)]
+                                #[allow(dead_code, non_camel_case_types)]
                                 trait #param_type_validator_ident: awto::protobuf::IntoProtobufMessage {}
                                 impl #param_type_validator_ident for #param {}
                         ));
@@ -59,7 +66,7 @@ impl ProtobufService {
                         format_ident!("{}_{}_ReturnTypeValidator", ident, name);
                     validators.push(quote_spanned!(
                         returns.span()=>
-                            #[allow(non_camel_case_types)]
+                            #[allow(dead_code, non_camel_case_types)]
                             trait #return_type_validator_ident: awto::protobuf::IntoProtobufMessage {}
                             impl #return_type_validator_ident for #returns {}
                     ));
@@ -68,6 +75,7 @@ impl ProtobufService {
                         awto::protobuf::ProtobufMethod {
                             is_async: #is_async,
                             name: #name.to_string(),
+                            doc: #doc,
                             param: <#param as awto::protobuf::IntoProtobufMessage>::protobuf_message(),
                             returns: <#returns as awto::protobuf::IntoProtobufMessage>::protobuf_message(),
                             returns_result: #is_result,
@@ -97,7 +105,7 @@ impl ProtobufService {
         &'a self,
         method: &'a syn::ImplItemMethod,
     ) -> syn::Result<MethodInfo<'a>> {
-        let name = method.sig.ident.to_string().to_camel_case();
+        let name = method.sig.ident.unraw().to_string().to_camel_case();
         let mut validators = Vec::new();
 
         let mut inputs = method.sig.inputs.iter();
@@ -185,7 +193,7 @@ impl ProtobufService {
                                 let return_type_result_validator_ident = format_ident!("{}_{}_ReturnTypeResultValidator", self.ident, name);
                                 validators.push(quote_spanned!(
                                     second.span()=>
-                                        #[allow(non_camel_case_types)]
+                                        #[allow(dead_code, non_camel_case_types)]
                                         trait #return_type_result_validator_ident: ::std::convert::Into<::tonic::Status> {}
                                         impl #return_type_result_validator_ident for #second {}
                                 ));
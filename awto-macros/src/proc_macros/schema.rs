@@ -1,20 +1,33 @@
 use std::{fmt, iter::FromIterator};
 
+use better_bae::TryFromAttributes;
 use proc_macro2::TokenStream;
 use quote::{quote, ToTokens};
-use syn::spanned::Spanned;
+use syn::{ext::IdentExt, spanned::Spanned};
 
 use crate::{
+    attributes::{
+        AllowAttr, AuthorizeAttr, ColumnAttr, DeprecatedAttr, EntityAttrsAttr, FulltextAttr,
+        GraphqlAttr, ItemAttrs, LocalizedAttr, PartitionAttr, ProtoAttr, RlsAttr, RootAttrs,
+        VisibilityAttr,
+    },
     error::Error,
     proc_macros::schema::{
-        database_table::DatabaseTableModel, protobuf_message::ProtobufMessageModel,
+        database_table::DatabaseTableModel,
+        newtype::{field_newtype, resolve_item, take_newtypes, Newtype},
+        protobuf_message::ProtobufMessageModel,
     },
-    util::ProcMacro,
+    util::{doc_comment, ProcMacro},
 };
 
 mod database_table;
+mod mixin;
+mod newtype;
 mod protobuf_message;
 
+/// Name of the database which models belong to when no `#[awto(database = "...")]` is given.
+const DEFAULT_DATABASE: &str = "main";
+
 pub struct Structs(pub Vec<syn::ItemStruct>);
 
 impl syn::parse::Parse for Structs {
@@ -28,6 +41,41 @@ impl syn::parse::Parse for Structs {
     }
 }
 
+/// Who may see the values of a field, set with `#[awto(sensitivity = "...")]`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Sensitivity {
+    Public,
+    Internal,
+    Secret,
+}
+
+impl Sensitivity {
+    /// Parses the `sensitivity` of a field, which is public without one.
+    pub(crate) fn parse(sensitivity: Option<&syn::LitStr>) -> syn::Result<Self> {
+        match sensitivity.map(|lit| (lit, lit.value())) {
+            None => Ok(Sensitivity::Public),
+            Some((_, value)) if value == "public" => Ok(Sensitivity::Public),
+            Some((_, value)) if value == "internal" => Ok(Sensitivity::Internal),
+            Some((_, value)) if value == "secret" => Ok(Sensitivity::Secret),
+            Some((lit, value)) => Err(syn::Error::new(
+                lit.span(),
+                format!(
+                    "unknown sensitivity `{}`, expected `public`, `internal` or `secret`",
+                    value
+                ),
+            )),
+        }
+    }
+
+    fn expand(self) -> TokenStream {
+        match self {
+            Sensitivity::Public => quote!(awto::schema::Sensitivity::Public),
+            Sensitivity::Internal => quote!(awto::schema::Sensitivity::Internal),
+            Sensitivity::Secret => quote!(awto::schema::Sensitivity::Secret),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 enum Role {
     DatabaseTable,
@@ -35,9 +83,6 @@ enum Role {
     ProtobufMessage,
 }
 
-#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
-struct RoleFromStrError;
-
 impl fmt::Display for Role {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -54,15 +99,22 @@ struct Item {
 }
 
 pub struct Schema {
+    newtypes: Vec<Newtype>,
     items: Vec<Item>,
 }
 
 impl Schema {
-    fn parse_input(input: Structs) -> syn::Result<Vec<Item>> {
-        input
-            .0
+    fn parse_input(input: Structs) -> syn::Result<(Vec<Newtype>, Vec<Item>)> {
+        let (newtypes, structs) = take_newtypes(input.0)?;
+
+        let items = mixin::expand_mixins(structs)?
             .into_iter()
-            .map(|item| {
+            .map(|mut item| {
+                AllowAttr::strip_from_attributes(&mut item.attrs)?;
+                for field in &mut item.fields {
+                    AllowAttr::strip_from_attributes(&mut field.attrs)?;
+                }
+
                 let roles: Vec<_> = item
                     .attrs
                     .iter()
@@ -111,7 +163,9 @@ impl Schema {
 
                 Ok(Item { roles, item })
             })
-            .collect()
+            .collect::<syn::Result<_>>()?;
+
+        Ok((newtypes, items))
     }
 
     fn parse_models(&self) -> syn::Result<TokenStream> {
@@ -119,7 +173,7 @@ impl Schema {
             .items
             .iter()
             .map(|item| {
-                let item_ident = item.item.ident.to_string();
+                let item_ident = item.item.ident.unraw().to_string();
 
                 let roles: Vec<_> = item
                     .roles
@@ -127,10 +181,12 @@ impl Schema {
                     .map(|role| {
                         let expanded = match role {
                             Role::DatabaseTable => {
-                                let database_table =
-                                    DatabaseTableModel::new(item.item.clone(), false)
-                                        .map_err(|err| err.into_syn_error(item.item.span()))?
-                                        .expand()?;
+                                let database_table = DatabaseTableModel::new(
+                                    resolve_item(&item.item, &self.newtypes),
+                                    false,
+                                )
+                                .map_err(|err| err.into_syn_error(item.item.span()))?
+                                .expand()?;
 
                                 quote!(awto::schema::Role::DatabaseTable(#database_table))
                             }
@@ -143,17 +199,22 @@ impl Schema {
                                         syn::Error::new(parent_ident.span(), "parent not found")
                                     })?;
 
-                                let database_table =
-                                    DatabaseTableModel::new(parent.item.clone(), false)
-                                        .map_err(|err| err.into_syn_error(item.item.span()))?
-                                        .expand()?;
+                                let database_table = DatabaseTableModel::new(
+                                    resolve_item(&parent.item, &self.newtypes),
+                                    false,
+                                )
+                                .map_err(|err| err.into_syn_error(item.item.span()))?
+                                .expand()?;
 
                                 quote!(awto::schema::Role::DatabaseSubTable(#database_table))
                             }
                             Role::ProtobufMessage => {
-                                let protobuf_message = ProtobufMessageModel::new(item.item.clone())
-                                    .map_err(|err| err.into_syn_error(item.item.span()))?
-                                    .expand()?;
+                                let protobuf_message = ProtobufMessageModel::new(resolve_item(
+                                    &item.item,
+                                    &self.newtypes,
+                                ))
+                                .map_err(|err| err.into_syn_error(item.item.span()))?
+                                .expand()?;
 
                                 quote!(awto::schema::Role::ProtobufMessage(#protobuf_message) )
                             }
@@ -163,23 +224,91 @@ impl Schema {
                     })
                     .collect::<Result<_, _>>()?;
 
-                let rust_fields = item.item.fields.iter().map(|field| {
-                    let field_ident_string = field.ident.as_ref().unwrap().to_string();
-                    let mut field_ty_string = field.ty.to_token_stream().to_string();
-                    field_ty_string.retain(|c| c != ' ');
+                // Fields are described by the types they are stored as, along with their newtype
+                let resolved = resolve_item(&item.item, &self.newtypes);
+                let rust_fields: Vec<_> = item
+                    .item
+                    .fields
+                    .iter()
+                    .zip(&resolved.fields)
+                    .map(|(field, resolved)| {
+                        let field_ident_string = field.ident.as_ref().unwrap().unraw().to_string();
+                        let mut field_ty_string = resolved.ty.to_token_stream().to_string();
+                        field_ty_string.retain(|c| c != ' ');
+                        let newtype = match field_newtype(&field.ty, &self.newtypes) {
+                            Some(newtype) => {
+                                let newtype = newtype.item.ident.to_string();
+                                quote!(Some(#newtype.to_string()))
+                            }
+                            None => quote!(None),
+                        };
+                        let mut attrs = field.attrs.clone();
+                        LocalizedAttr::take_from_attributes(&mut attrs)?;
+                        ColumnAttr::take_from_attributes(&mut attrs)?;
+                        let deprecated = match DeprecatedAttr::take_from_attributes(&mut attrs)? {
+                            Some(deprecated) => {
+                                let deprecation = deprecated.expand();
+                                quote!(Some(#deprecation))
+                            }
+                            None => quote!(None),
+                        };
+                        let field_attrs = ItemAttrs::try_from_attributes(&attrs)?;
+                        let sensitivity = Sensitivity::parse(
+                            field_attrs
+                                .as_ref()
+                                .and_then(|attrs| attrs.sensitivity.as_ref()),
+                        )?;
+                        let sensitive = field_attrs
+                            .as_ref()
+                            .map(|attrs| attrs.sensitive.is_some())
+                            .unwrap_or(false)
+                            || serde_skipped(&field.attrs)
+                            || sensitivity != Sensitivity::Public;
+                        let sensitivity = sensitivity.expand();
+                        let feature = expand_feature(
+                            field_attrs
+                                .as_ref()
+                                .and_then(|attrs| attrs.feature.as_ref()),
+                        )?;
+                        let doc = match doc_comment(&field.attrs) {
+                            Some(doc) => quote!(Some(#doc.to_string())),
+                            None => quote!(None),
+                        };
+
+                        Ok(quote!(
+                            awto::schema::RustField {
+                                name: #field_ident_string.to_string(),
+                                doc: #doc,
+                                ty: #field_ty_string.to_string(),
+                                newtype: #newtype,
+                                deprecated: #deprecated,
+                                sensitive: #sensitive,
+                                sensitivity: #sensitivity,
+                                feature: #feature,
+                            }
+                        ))
+                    })
+                    .collect::<syn::Result<_>>()?;
 
-                    quote!(
-                        awto::schema::RustField {
-                            name: #field_ident_string.to_string(),
-                            ty: #field_ty_string.to_string(),
-                        }
-                    )
-                });
+                let doc = match doc_comment(&item.item.attrs) {
+                    Some(doc) => quote!(Some(#doc.to_string())),
+                    None => quote!(None),
+                };
+
+                let feature = expand_feature(
+                    root_attrs(&item.item)?
+                        .and_then(|attrs| attrs.feature)
+                        .as_ref(),
+                )?;
 
                 Result::<_, syn::Error>::Ok(quote!(
                     awto::schema::Model {
+                        doc: #doc,
                         fields: vec![ #( #rust_fields ),* ],
                         name: #item_ident.to_string(),
+                        origin: None,
+                        feature: #feature,
+                        omitted_fields: Vec::new(),
                         roles: vec![ #( #roles ),* ],
                     }
                 ))
@@ -208,10 +337,12 @@ impl Schema {
                     .map(|role| {
                         let expanded = match role {
                             Role::DatabaseTable => {
-                                let database_table =
-                                    DatabaseTableModel::new(item.item.clone(), false)
-                                        .map_err(|err| err.into_syn_error(item.item.span()))?
-                                        .expand()?;
+                                let database_table = DatabaseTableModel::new(
+                                    resolve_item(&item.item, &self.newtypes),
+                                    false,
+                                )
+                                .map_err(|err| err.into_syn_error(item.item.span()))?
+                                .expand()?;
 
                                 quote!(
                                     impl awto::database::IntoDatabaseTable for #item_ident {
@@ -225,9 +356,12 @@ impl Schema {
                                 quote!()
                             }
                             Role::ProtobufMessage => {
-                                let protobuf_message = ProtobufMessageModel::new(item.item.clone())
-                                    .map_err(|err| err.into_syn_error(item.item.span()))?
-                                    .expand()?;
+                                let protobuf_message = ProtobufMessageModel::new(resolve_item(
+                                    &item.item,
+                                    &self.newtypes,
+                                ))
+                                .map_err(|err| err.into_syn_error(item.item.span()))?
+                                .expand()?;
 
                                 quote!(
                                     impl awto::protobuf::IntoProtobufMessage for #item_ident {
@@ -252,62 +386,394 @@ impl Schema {
         Ok(quote!(#( #model_impls )*))
     }
 
+    /// Ensures database tables only reference tables belonging to another database when the reference is marked
+    /// `cross_database`, and that references marked so belong to another database.
+    fn validate_references(&self) -> syn::Result<()> {
+        let database_of = |item: &Item| -> syn::Result<Option<String>> {
+            Ok(root_attrs(&item.item)?
+                .and_then(|attrs| attrs.database)
+                .map(|database| database.value()))
+        };
+        let is_partitioned = |item: &Item| -> syn::Result<bool> {
+            Ok(PartitionAttr::take_from_attributes(&mut item.item.attrs.clone())?.is_some())
+        };
+
+        for item in &self.items {
+            if !item.roles.contains(&Role::DatabaseTable) {
+                continue;
+            }
+            let database = database_of(item)?;
+
+            for field in &item.item.fields {
+                let mut attrs = field.attrs.clone();
+                LocalizedAttr::take_from_attributes(&mut attrs)?;
+                DeprecatedAttr::take_from_attributes(&mut attrs)?;
+                ColumnAttr::take_from_attributes(&mut attrs)?;
+                let references = match ItemAttrs::try_from_attributes(&attrs)?
+                    .and_then(|attrs| attrs.references)
+                {
+                    Some(references) => references,
+                    None => continue,
+                };
+                let referenced = match self
+                    .items
+                    .iter()
+                    .find(|other| other.item.ident == references.table)
+                {
+                    Some(referenced) => referenced,
+                    None => continue,
+                };
+
+                let referenced_database = database_of(referenced)?;
+                let cross_database = references.cross_database.is_some();
+                if referenced_database != database && !cross_database {
+                    return Err(syn::Error::new(
+                        references.table.span(),
+                        format!(
+                            "`{}` belongs to database '{}' but `{}` belongs to database '{}'\n\n\
+                            foreign keys cannot reference tables of other databases, mark the reference \
+                            `#[awto(references = ({}, \"{}\", cross_database))]`",
+                            item.item.ident,
+                            database.as_deref().unwrap_or(DEFAULT_DATABASE),
+                            referenced.item.ident,
+                            referenced_database.as_deref().unwrap_or(DEFAULT_DATABASE),
+                            referenced.item.ident,
+                            references.column.value(),
+                        ),
+                    ));
+                }
+                if let (Some(cross_database), true) =
+                    (&references.cross_database, referenced_database == database)
+                {
+                    return Err(syn::Error::new(
+                        cross_database.span(),
+                        format!(
+                            "`{}` and `{}` both belong to database '{}', remove `cross_database`",
+                            item.item.ident,
+                            referenced.item.ident,
+                            database.as_deref().unwrap_or(DEFAULT_DATABASE),
+                        ),
+                    ));
+                }
+                if is_partitioned(referenced)? && !cross_database {
+                    return Err(syn::Error::new(
+                        references.table.span(),
+                        format!(
+                            "`{}` references `{}`, which is partitioned\n\nforeign keys cannot reference partitioned tables, \
+                            as their primary key includes the partition column",
+                            item.item.ident, referenced.item.ident,
+                        ),
+                    ));
+                }
+
+                // Newtypes of keys, such as `ProductId`, must be the newtypes of the fields referencing them
+                let referenced_field = referenced.item.fields.iter().find(|other| {
+                    other.ident.as_ref().map(|ident| ident.unraw().to_string())
+                        == Some(references.column.value())
+                });
+                if let (Some(newtype), Some(referenced_newtype)) = (
+                    field_newtype(&field.ty, &self.newtypes),
+                    referenced_field.and_then(|other| field_newtype(&other.ty, &self.newtypes)),
+                ) {
+                    if newtype.item.ident != referenced_newtype.item.ident {
+                        return Err(syn::Error::new(
+                            field.ty.span(),
+                            format!(
+                                "`{}` is a `{}` but references `{}.{}`, which is a `{}`",
+                                field.ident.as_ref().unwrap(),
+                                newtype.item.ident,
+                                referenced.item.ident,
+                                references.column.value(),
+                                referenced_newtype.item.ident,
+                            ),
+                        ));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Keeps the values of internal and secret fields out of the `Serialize` and `Debug` derives of the structs.
+    ///
+    /// Secret fields are skipped when serialized, and internal fields outside of `awto::schema::reveal_internal`.
+    /// The derived `Debug` of structs with such fields is replaced by the returned implementations,
+    /// printing their values as `********`.
+    fn protect_sensitive_fields(&mut self) -> syn::Result<TokenStream> {
+        let mut debug_impls = Vec::new();
+
+        for item in &mut self.items {
+            let sensitivities = item
+                .item
+                .fields
+                .iter()
+                .map(field_sensitivity)
+                .collect::<syn::Result<Vec<_>>>()?;
+            if sensitivities
+                .iter()
+                .all(|sensitivity| *sensitivity == Sensitivity::Public)
+            {
+                continue;
+            }
+
+            if derives(&item.item.attrs, "Serialize") {
+                for (field, sensitivity) in item.item.fields.iter_mut().zip(&sensitivities) {
+                    let skip = match sensitivity {
+                        _ if serde_skipped(&field.attrs) => continue,
+                        Sensitivity::Public => continue,
+                        Sensitivity::Internal => {
+                            quote!(skip_serializing_if = "awto::schema::internal_hidden")
+                        }
+                        Sensitivity::Secret => quote!(skip_serializing),
+                    };
+                    field.attrs.push(syn::parse_quote!(#[serde(#skip)]));
+                }
+            }
+
+            if remove_derive(&mut item.item.attrs, "Debug") {
+                let ident = &item.item.ident;
+                let name = ident.unraw().to_string();
+                let (impl_generics, ty_generics, where_clause) =
+                    item.item.generics.split_for_impl();
+                let fields =
+                    item.item
+                        .fields
+                        .iter()
+                        .zip(&sensitivities)
+                        .map(|(field, sensitivity)| {
+                            let field_ident = field.ident.as_ref().unwrap();
+                            let field_name = field_ident.unraw().to_string();
+                            match sensitivity {
+                                Sensitivity::Public => {
+                                    quote!(.field(#field_name, &self.#field_ident))
+                                }
+                                _ => quote!(.field(#field_name, &::std::format_args!("********"))),
+                            }
+                        });
+
+                debug_impls.push(quote!(
+                    impl #impl_generics ::std::fmt::Debug for #ident #ty_generics #where_clause {
+                        fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                            f.debug_struct(#name)
+                                #( #fields )*
+                                .finish()
+                        }
+                    }
+                ));
+            }
+        }
+
+        Ok(quote!(#( #debug_impls )*))
+    }
+
     fn strip_attributes(&mut self) {
         for item in &mut self.items {
             item.item.attrs.retain(|attr| {
                 !attr
-                    .parse_meta()
-                    .map(|meta| match meta {
-                        syn::Meta::Path(path) => path
-                            .get_ident()
-                            .map(|ident| ident == "database_table" || ident == "protobuf_message")
-                            .unwrap_or(false),
-                        syn::Meta::List(list) => list
-                            .path
-                            .get_ident()
-                            .map(|ident| ident == "database_sub_table")
-                            .unwrap_or(false),
-                        _ => false,
+                    .path
+                    .get_ident()
+                    .map(|ident| {
+                        ident == "database_table"
+                            || ident == "protobuf_message"
+                            || ident == "database_sub_table"
+                            || ident == "awto"
                     })
                     .unwrap_or(false)
             });
 
+            // Other field attributes, such as `#[serde(skip)]`, are kept for the derives of the struct
             for field in &mut item.item.fields {
-                field.attrs.retain(|attr| {
-                    attr.parse_meta()
-                        .map(|meta| match meta {
-                            syn::Meta::Path(p) => p.is_ident("awto"),
-                            _ => false,
-                        })
-                        .unwrap_or(false)
-                })
+                field.attrs.retain(|attr| !attr.path.is_ident("awto"))
             }
         }
     }
 }
 
+/// Parses the `#[awto(...)]` arguments of a model, leaving out its nested attributes.
+fn root_attrs(item: &syn::ItemStruct) -> syn::Result<Option<RootAttrs>> {
+    let mut attrs = item.attrs.clone();
+    FulltextAttr::take_from_attributes(&mut attrs)?;
+    PartitionAttr::take_from_attributes(&mut attrs)?;
+    RlsAttr::take_from_attributes(&mut attrs)?;
+    AuthorizeAttr::take_from_attributes(&mut attrs)?;
+    EntityAttrsAttr::take_from_attributes(&mut attrs)?;
+    ProtoAttr::take_from_attributes(&mut attrs)?;
+    VisibilityAttr::take_from_attributes(&mut attrs)?;
+    GraphqlAttr::take_from_attributes(&mut attrs)?;
+
+    RootAttrs::try_from_attributes(&attrs)
+}
+
+/// Expands the `feature` of a model or field, which must name a feature when given.
+fn expand_feature(feature: Option<&syn::LitStr>) -> syn::Result<TokenStream> {
+    match feature {
+        Some(lit) if lit.value().trim().is_empty() => {
+            Err(syn::Error::new(lit.span(), "feature names cannot be empty"))
+        }
+        Some(lit) => {
+            let feature = lit.value();
+            Ok(quote!(Some(#feature.to_string())))
+        }
+        None => Ok(quote!(None)),
+    }
+}
+
+/// Returns the sensitivity of `field` set with `#[awto(sensitivity = "...")]`.
+fn field_sensitivity(field: &syn::Field) -> syn::Result<Sensitivity> {
+    let mut attrs = field.attrs.clone();
+    LocalizedAttr::take_from_attributes(&mut attrs)?;
+    DeprecatedAttr::take_from_attributes(&mut attrs)?;
+    ColumnAttr::take_from_attributes(&mut attrs)?;
+    let attrs = ItemAttrs::try_from_attributes(&attrs)?;
+
+    Sensitivity::parse(attrs.as_ref().and_then(|attrs| attrs.sensitivity.as_ref()))
+}
+
+/// Returns the paths derived by the `#[derive(...)]` attribute `attr`, or `None` for other attributes.
+fn derived_paths(attr: &syn::Attribute) -> Option<Vec<syn::Path>> {
+    if !attr.path.is_ident("derive") {
+        return None;
+    }
+
+    attr.parse_args_with(syn::punctuated::Punctuated::<syn::Path, syn::Token![,]>::parse_terminated)
+        .ok()
+        .map(|paths| paths.into_iter().collect())
+}
+
+/// Returns whether a `#[derive(...)]` of `attrs` derives `name`, such as `Serialize` or `serde::Serialize`.
+fn derives(attrs: &[syn::Attribute], name: &str) -> bool {
+    attrs
+        .iter()
+        .filter_map(derived_paths)
+        .flatten()
+        .any(|path| {
+            path.segments
+                .last()
+                .is_some_and(|segment| segment.ident == name)
+        })
+}
+
+/// Removes `name` from the `#[derive(...)]` attributes of `attrs`, returning whether it was derived.
+fn remove_derive(attrs: &mut [syn::Attribute], name: &str) -> bool {
+    let mut removed = false;
+    for attr in attrs.iter_mut() {
+        let paths = match derived_paths(attr) {
+            Some(paths) => paths,
+            None => continue,
+        };
+        let kept: Vec<_> = paths
+            .iter()
+            .filter(|path| {
+                path.segments
+                    .last()
+                    .is_none_or(|segment| segment.ident != name)
+            })
+            .collect();
+        if kept.len() < paths.len() {
+            removed = true;
+            *attr = syn::parse_quote!(#[derive(#( #kept ),*)]);
+        }
+    }
+
+    removed
+}
+
+/// Returns whether `#[serde(skip)]` or `#[serde(skip_serializing)]` is in `attrs`.
+fn serde_skipped(attrs: &[syn::Attribute]) -> bool {
+    attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("serde"))
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(syn::Meta::List(list)) => Some(list.nested),
+            _ => None,
+        })
+        .flatten()
+        .any(|nested| {
+            matches!(nested, syn::NestedMeta::Meta(syn::Meta::Path(path))
+                if path.is_ident("skip") || path.is_ident("skip_serializing"))
+        })
+}
+
 impl ProcMacro for Schema {
     type Input = Structs;
 
     fn new(input: Self::Input) -> Result<Self, Error> {
-        let items = Self::parse_input(input).map_err(Error::Syn)?;
+        let (newtypes, items) = Self::parse_input(input).map_err(Error::Syn)?;
 
-        Ok(Schema { items })
+        Ok(Schema { newtypes, items })
     }
 
     fn expand(mut self) -> syn::Result<TokenStream> {
+        self.validate_references()?;
+
         let models_cosnt = self.parse_models()?;
         let model_impls = self.impl_models()?;
+        let debug_impls = self.protect_sensitive_fields()?;
 
         self.strip_attributes();
 
+        let newtypes = self.newtypes.iter().map(Newtype::expand);
         let items = self.items.into_iter().map(|item| item.item);
-        let expanded_input = quote!(#( #items )*);
+        let expanded_input = quote!(#( #newtypes )* #( #items )*);
 
         Ok(TokenStream::from_iter([
             models_cosnt,
             model_impls,
+            debug_impls,
             expanded_input,
         ]))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn stripped_attributes() {
+        let input: Structs = syn::parse2(quote! {
+            #[database_table]
+            #[awto(database = "main")]
+            #[derive(Serialize)]
+            pub struct Product {
+                /// Name of the product.
+                #[awto(max_len = 120)]
+                #[serde(rename = "title")]
+                pub name: String,
+                #[awto]
+                #[serde(skip)]
+                pub notes: String,
+            }
+        })
+        .unwrap();
+        let (newtypes, items) = Schema::parse_input(input).unwrap();
+        let mut schema = Schema { newtypes, items };
+        schema.strip_attributes();
+
+        // Role and awto attributes are read by the macro, the others are kept for the derives of the struct
+        let item = &schema.items[0].item;
+        let attrs = |attrs: &[syn::Attribute]| -> Vec<String> {
+            attrs
+                .iter()
+                .map(|attr| attr.to_token_stream().to_string())
+                .collect()
+        };
+        assert_eq!(attrs(&item.attrs), ["# [derive (Serialize)]"]);
+        let fields: Vec<_> = item
+            .fields
+            .iter()
+            .map(|field| attrs(&field.attrs))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                vec![
+                    r#"# [doc = r" Name of the product."]"#.to_string(),
+                    "# [serde (rename = \"title\")]".to_string()
+                ],
+                vec!["# [serde (skip)]".to_string()],
+            ]
+        );
+    }
+}
@@ -1,33 +1,116 @@
+use better_bae::TryFromAttributes;
 use heck::SnakeCase;
 use proc_macro2::TokenStream;
 use quote::{quote, ToTokens};
-use syn::spanned::Spanned;
+use syn::{ext::IdentExt, spanned::Spanned};
 
 use crate::{
-    attributes::ItemAttrs,
+    attributes::{
+        AuthorizeAttr, ColumnAttr, DeprecatedAttr, EntityAttrsAttr, FulltextAttr, GraphqlAttr,
+        ItemAttrs, LocalizedAttr, PartitionAttr, ProjectionAttr, ProtoAttr, RlsAttr, RootAttrs,
+        ScopeAttr, VisibilityAttr,
+    },
     error::Error,
-    util::{parse_fields, Field},
+    util::{map_value_type, parse_fields, Field},
 };
 
+/// Text search configuration used when `#[awto(fulltext(...))]` has no `language`.
+const DEFAULT_FULLTEXT_LANGUAGE: &str = "simple";
+
+/// Types of the parameters of `#[awto(scope(...))]`, which the generated queries bind as values.
+const SCOPE_PARAM_TYPES: [&str; 9] = [
+    "bool",
+    "i32",
+    "i64",
+    "f64",
+    "String",
+    "Uuid",
+    "NaiveDate",
+    "DateTime<Utc>",
+    "DateTime<FixedOffset>",
+];
+
 pub struct DatabaseTableModel {
+    attrs: RootAttrs,
+    fulltext: Option<FulltextAttr>,
+    partition: Option<PartitionAttr>,
+    visibility: Option<VisibilityAttr>,
+    graphql: Option<GraphqlAttr>,
+    policies: Vec<RlsAttr>,
+    projections: Vec<ProjectionAttr>,
+    scopes: Vec<ScopeAttr>,
+    authorize: Option<AuthorizeAttr>,
+    entity_attrs: Vec<syn::LitStr>,
     fields: Vec<Field<ItemAttrs>>,
+    /// `#[awto(localized(...))]` of each field, in the order of `fields`.
+    localized: Vec<Option<LocalizedAttr>>,
+    /// `#[awto(deprecated(...))]` of each field, in the order of `fields`.
+    deprecated: Vec<Option<DeprecatedAttr>>,
+    /// `#[awto(column(...))]` of each field, in the order of `fields`.
+    column: Vec<Option<ColumnAttr>>,
     ident: syn::Ident,
     is_sub_model: bool,
 }
 
 impl DatabaseTableModel {
-    pub fn new(item: syn::ItemStruct, is_sub_model: bool) -> Result<Self, Error> {
-        let punctuated_fields = match item.fields {
+    pub fn new(mut item: syn::ItemStruct, is_sub_model: bool) -> Result<Self, Error> {
+        let mut punctuated_fields = match item.fields {
             syn::Fields::Named(named) => named.named,
             _ => return Err(Error::FieldsNotNamed),
         };
 
+        let localized = punctuated_fields
+            .iter_mut()
+            .map(|field| LocalizedAttr::take_from_attributes(&mut field.attrs))
+            .collect::<syn::Result<_>>()
+            .map_err(Error::Syn)?;
+        let deprecated = punctuated_fields
+            .iter_mut()
+            .map(|field| DeprecatedAttr::take_from_attributes(&mut field.attrs))
+            .collect::<syn::Result<_>>()
+            .map_err(Error::Syn)?;
+        let column = punctuated_fields
+            .iter_mut()
+            .map(|field| ColumnAttr::take_from_attributes(&mut field.attrs))
+            .collect::<syn::Result<_>>()
+            .map_err(Error::Syn)?;
         let fields = parse_fields::<ItemAttrs>(punctuated_fields)?;
 
+        let fulltext = FulltextAttr::take_from_attributes(&mut item.attrs).map_err(Error::Syn)?;
+        let partition = PartitionAttr::take_from_attributes(&mut item.attrs).map_err(Error::Syn)?;
+        let visibility =
+            VisibilityAttr::take_from_attributes(&mut item.attrs).map_err(Error::Syn)?;
+        let graphql = GraphqlAttr::take_from_attributes(&mut item.attrs).map_err(Error::Syn)?;
+        let policies = RlsAttr::take_from_attributes(&mut item.attrs).map_err(Error::Syn)?;
+        let projections =
+            ProjectionAttr::take_from_attributes(&mut item.attrs).map_err(Error::Syn)?;
+        let scopes = ScopeAttr::take_from_attributes(&mut item.attrs).map_err(Error::Syn)?;
+        let authorize = AuthorizeAttr::take_from_attributes(&mut item.attrs).map_err(Error::Syn)?;
+        let entity_attrs =
+            EntityAttrsAttr::take_from_attributes(&mut item.attrs).map_err(Error::Syn)?;
+        // Protobuf versions are read by the protobuf message of the model
+        ProtoAttr::take_from_attributes(&mut item.attrs).map_err(Error::Syn)?;
+        let attrs = RootAttrs::try_from_attributes(&item.attrs)
+            .map_err(Error::Syn)?
+            .unwrap_or_default();
+
         let ident = item.ident;
 
         Ok(DatabaseTableModel {
+            attrs,
+            fulltext,
+            partition,
+            visibility,
+            graphql,
+            policies,
+            projections,
+            scopes,
+            authorize,
+            entity_attrs,
             fields,
+            localized,
+            deprecated,
+            column,
             ident,
             is_sub_model,
         })
@@ -42,9 +125,577 @@ impl DatabaseTableModel {
 
 impl DatabaseTableModel {
     fn expand_database_table(&self) -> syn::Result<TokenStream> {
-        let Self { fields, ident, .. } = self;
+        let Self {
+            attrs,
+            fields,
+            ident,
+            ..
+        } = self;
 
-        let table_name = ident.to_string().to_snake_case();
+        let table_name = ident.unraw().to_string().to_snake_case();
+
+        let database = match &attrs.database {
+            Some(database) => {
+                let value = database.value();
+                if !value.starts_with(|c: char| c.is_ascii_lowercase())
+                    || !value
+                        .chars()
+                        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+                {
+                    return Err(syn::Error::new(
+                        database.span(),
+                        "database names must start with a lowercase letter and only contain lowercase letters, digits and underscores",
+                    ));
+                }
+                quote!(Some(#database.to_string()))
+            }
+            None => quote!(None),
+        };
+        let order_by = match &attrs.order_by {
+            Some(order_by) => {
+                let field = fields
+                    .iter()
+                    .find(|field| field.field.ident.as_ref() == Some(order_by))
+                    .ok_or_else(|| {
+                        syn::Error::new(
+                            order_by.span(),
+                            "order_by must be the name of a field of the model",
+                        )
+                    })?;
+                if Self::is_type_option(&field.field.ty) {
+                    return Err(syn::Error::new(
+                        order_by.span(),
+                        "order_by cannot be an Option",
+                    ));
+                }
+                let order_by = order_by.unraw().to_string();
+                quote!(Some(#order_by.to_string()))
+            }
+            None => quote!(None),
+        };
+        let upsert_on = match &attrs.upsert_on {
+            Some(upsert_on) => {
+                if attrs.readonly.is_some() || attrs.external.is_some() {
+                    return Err(syn::Error::new(
+                        upsert_on.span(),
+                        "readonly models cannot be upserted",
+                    ));
+                }
+                let field = fields
+                    .iter()
+                    .find(|field| field.field.ident.as_ref() == Some(upsert_on))
+                    .ok_or_else(|| {
+                        syn::Error::new(
+                            upsert_on.span(),
+                            "upsert_on must be the name of a field of the model",
+                        )
+                    })?;
+                if *upsert_on != "id"
+                    && field.attrs.unique.is_none()
+                    && field.attrs.primary_key.is_none()
+                {
+                    return Err(syn::Error::new(
+                        upsert_on.span(),
+                        "upsert_on must be a field marked with #[awto(unique)]",
+                    ));
+                }
+                if Self::is_type_option(&field.field.ty) {
+                    return Err(syn::Error::new(
+                        upsert_on.span(),
+                        "upsert_on cannot be an Option",
+                    ));
+                }
+                let upsert_on = upsert_on.unraw().to_string();
+                quote!(Some(#upsert_on.to_string()))
+            }
+            None => quote!(None),
+        };
+        let fulltext = match &self.fulltext {
+            Some(fulltext) => {
+                let mut columns = Vec::new();
+                for field_ident in &fulltext.fields {
+                    let field = fields
+                        .iter()
+                        .find(|field| field.field.ident.as_ref() == Some(field_ident))
+                        .ok_or_else(|| {
+                            syn::Error::new(
+                                field_ident.span(),
+                                "fulltext fields must be fields of the model",
+                            )
+                        })?;
+                    let is_text = match &field.attrs.db_type {
+                        Some(db_type) => db_type.value() == "Text",
+                        None => Self::rust_to_db_type(&field.field.ty)
+                            .map(|ty| ty.to_string().ends_with(":: Text"))
+                            .unwrap_or(false),
+                    };
+                    if !is_text {
+                        return Err(syn::Error::new(
+                            field_ident.span(),
+                            "fulltext fields must be text columns",
+                        ));
+                    }
+                    columns.push(field_ident.unraw().to_string());
+                }
+
+                let language = match &fulltext.language {
+                    Some(language) => {
+                        if language.value().is_empty()
+                            || !language
+                                .value()
+                                .chars()
+                                .all(|c| c.is_ascii_lowercase() || c == '_')
+                        {
+                            return Err(syn::Error::new(
+                                language.span(),
+                                "fulltext language must be the name of a postgres text search configuration",
+                            ));
+                        }
+                        language.value()
+                    }
+                    None => DEFAULT_FULLTEXT_LANGUAGE.to_string(),
+                };
+
+                quote!(Some(awto::database::DatabaseFulltext {
+                    columns: vec![ #( #columns.to_string(), )* ],
+                    language: #language.to_string(),
+                }))
+            }
+            None => quote!(None),
+        };
+        let partition = match &self.partition {
+            Some(partition) => {
+                if self.is_sub_model {
+                    return Err(syn::Error::new(
+                        partition.column.span(),
+                        "only database tables can be partitioned",
+                    ));
+                }
+                if let Some(by) = &partition.by {
+                    if by.value() != "range" {
+                        return Err(syn::Error::new(
+                            by.span(),
+                            "only range partitioning is supported, `by = \"range\"`",
+                        ));
+                    }
+                }
+                let field = fields
+                    .iter()
+                    .find(|field| field.field.ident.as_ref() == Some(&partition.column))
+                    .ok_or_else(|| {
+                        syn::Error::new(
+                            partition.column.span(),
+                            "partition column must be the name of a field of the model",
+                        )
+                    })?;
+                if Self::is_type_option(&field.field.ty) {
+                    return Err(syn::Error::new(
+                        partition.column.span(),
+                        "partition column cannot be an Option, as rows are placed in partitions by its value",
+                    ));
+                }
+                let db_type = match &field.attrs.db_type {
+                    Some(db_type) => Some(db_type.value()),
+                    None => Self::rust_to_db_type(&field.field.ty)
+                        .and_then(|ty| ty.to_string().rsplit(' ').next().map(str::to_string)),
+                };
+                if !matches!(
+                    db_type.as_deref(),
+                    Some("Timestamptz" | "Timestamp" | "Date")
+                ) {
+                    return Err(syn::Error::new(
+                        partition.column.span(),
+                        "partition column must be a date or timestamp",
+                    ));
+                }
+                let interval = match &partition.interval {
+                    Some(interval) => match interval.value().as_str() {
+                        "day" => quote!(Day),
+                        "month" => quote!(Month),
+                        "year" => quote!(Year),
+                        _ => {
+                            return Err(syn::Error::new(
+                                interval.span(),
+                                "partition interval must be \"day\", \"month\" or \"year\"",
+                            ))
+                        }
+                    },
+                    None => quote!(Month),
+                };
+                // Unique constraints of partitioned tables must include the partition column
+                if let Some(field) = fields.iter().find(|field| field.attrs.unique.is_some()) {
+                    return Err(syn::Error::new(
+                        field.field.span(),
+                        "partitioned models cannot have unique fields, as postgres only enforces uniqueness within a partition",
+                    ));
+                }
+                if let Some(upsert_on) = &attrs.upsert_on {
+                    return Err(syn::Error::new(
+                        upsert_on.span(),
+                        "partitioned models cannot be upserted",
+                    ));
+                }
+
+                let column = partition.column.unraw().to_string();
+                quote!(Some(awto::database::DatabasePartition {
+                    column: #column.to_string(),
+                    interval: awto::database::PartitionInterval::#interval,
+                }))
+            }
+            None => quote!(None),
+        };
+        let visibility = match &self.visibility {
+            Some(visibility) => {
+                if self.is_sub_model {
+                    return Err(syn::Error::new(
+                        self.ident.span(),
+                        "only database tables have entities whose visibility can be set",
+                    ));
+                }
+                let parse = |visibility: &Option<syn::LitStr>| match visibility {
+                    Some(lit) => match lit.value().replace(' ', "").as_str() {
+                        "pub" => Ok(quote!(Some(awto::database::Visibility::Public))),
+                        "pub(crate)" => Ok(quote!(Some(awto::database::Visibility::Crate))),
+                        _ => Err(syn::Error::new(
+                            lit.span(),
+                            "visibility must be \"pub\" or \"pub(crate)\"",
+                        )),
+                    },
+                    None => Ok(quote!(None)),
+                };
+                let (entity, active_model) =
+                    (parse(&visibility.entity)?, parse(&visibility.active_model)?);
+                quote!(awto::database::EntityVisibility {
+                    entity: #entity,
+                    active_model: #active_model,
+                })
+            }
+            None => quote!(awto::database::EntityVisibility::default()),
+        };
+        let mut policy_names = Vec::new();
+        let policies = self
+            .policies
+            .iter()
+            .map(|rls| {
+                let name = rls.policy.value();
+                if name.is_empty()
+                    || name.starts_with(|c: char| c.is_ascii_digit())
+                    || !name
+                        .chars()
+                        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+                {
+                    return Err(syn::Error::new(
+                        rls.policy.span(),
+                        "rls policy must be a lowercase sql identifier",
+                    ));
+                }
+                if policy_names.contains(&name) {
+                    return Err(syn::Error::new(
+                        rls.policy.span(),
+                        "rls policy names must be unique within a model",
+                    ));
+                }
+                if rls.using.value().trim().is_empty() {
+                    return Err(syn::Error::new(
+                        rls.using.span(),
+                        "rls using must be a sql expression",
+                    ));
+                }
+                policy_names.push(name.clone());
+
+                let using = rls.using.value();
+                Ok(quote!(awto::database::DatabasePolicy {
+                    name: #name.to_string(),
+                    using: #using.to_string(),
+                }))
+            })
+            .collect::<syn::Result<Vec<_>>>()?;
+        let mut projection_names = Vec::new();
+        let projections = self
+            .projections
+            .iter()
+            .map(|projection| {
+                let name = projection.name.value();
+                if self.is_sub_model {
+                    return Err(syn::Error::new(
+                        projection.name.span(),
+                        "only database tables can have projections",
+                    ));
+                }
+                if syn::parse_str::<syn::Ident>(&name).is_err() || !name.starts_with(|c: char| c.is_ascii_uppercase()) {
+                    return Err(syn::Error::new(
+                        projection.name.span(),
+                        "projection name must be the name of a struct, such as \"ProductSummary\"",
+                    ));
+                }
+                if *ident == name || projection_names.contains(&name) {
+                    return Err(syn::Error::new(
+                        projection.name.span(),
+                        "projection names must differ from each other and from the model",
+                    ));
+                }
+                projection_names.push(name.clone());
+
+                let mut columns = Vec::new();
+                for field_ident in &projection.fields {
+                    if !fields.iter().any(|field| field.field.ident.as_ref() == Some(field_ident)) {
+                        return Err(syn::Error::new(
+                            field_ident.span(),
+                            "projection fields must be fields of the model",
+                        ));
+                    }
+                    let column = field_ident.unraw().to_string();
+                    if columns.contains(&column) {
+                        return Err(syn::Error::new(
+                            field_ident.span(),
+                            "projection fields must be listed once",
+                        ));
+                    }
+                    columns.push(column);
+                }
+                // Pages of projections continue after the key of their last row, like pages of rows
+                let order_by = attrs.order_by.as_ref().map(|order_by| order_by.unraw().to_string());
+                for key in order_by.iter().map(String::as_str).chain(std::iter::once("id")) {
+                    if !columns.iter().any(|column| column == key) {
+                        return Err(syn::Error::new(
+                            projection.name.span(),
+                            format!("projection fields must include `{}`, which pages of the projection are ordered by", key),
+                        ));
+                    }
+                }
+
+                Ok(quote!(awto::database::DatabaseProjection {
+                    name: #name.to_string(),
+                    columns: vec![ #( #columns.to_string() ),* ],
+                }))
+            })
+            .collect::<syn::Result<Vec<_>>>()?;
+        let mut scope_names = Vec::new();
+        let scopes = self
+            .scopes
+            .iter()
+            .map(|scope| {
+                let name = scope.name.unraw().to_string();
+                if self.is_sub_model {
+                    return Err(syn::Error::new(
+                        scope.name.span(),
+                        "only database tables can have scopes",
+                    ));
+                }
+                if name != name.to_snake_case() {
+                    return Err(syn::Error::new(
+                        scope.name.span(),
+                        "scope name must be snake case, such as `published`",
+                    ));
+                }
+                if scope_names.contains(&name) {
+                    return Err(syn::Error::new(
+                        scope.name.span(),
+                        "scope names must differ from each other",
+                    ));
+                }
+                scope_names.push(name.clone());
+
+                let mut param_names = Vec::new();
+                let params = scope
+                    .params
+                    .iter()
+                    .map(|(param, ty)| {
+                        let param_name = param.unraw().to_string();
+                        if param_names.contains(&param_name) {
+                            return Err(syn::Error::new(
+                                param.span(),
+                                "scope parameters must differ from each other",
+                            ));
+                        }
+                        param_names.push(param_name.clone());
+                        let ty = ty.to_token_stream().to_string().replace(' ', "");
+                        if !SCOPE_PARAM_TYPES.contains(&ty.as_str()) {
+                            return Err(syn::Error::new(
+                                param.span(),
+                                format!(
+                                    "scope parameters must be one of `{}`",
+                                    SCOPE_PARAM_TYPES.join("`, `")
+                                ),
+                            ));
+                        }
+
+                        Ok(quote!(awto::database::DatabaseScopeParam {
+                            name: #param_name.to_string(),
+                            ty: #ty.to_string(),
+                        }))
+                    })
+                    .collect::<syn::Result<Vec<_>>>()?;
+                if scope.condition.value().trim().is_empty() {
+                    return Err(syn::Error::new(
+                        scope.condition.span(),
+                        "scope condition must be a sql expression",
+                    ));
+                }
+
+                let condition = scope.condition.value();
+                Ok(quote!(awto::database::DatabaseScope {
+                    name: #name.to_string(),
+                    params: vec![ #( #params ),* ],
+                    condition: #condition.to_string(),
+                }))
+            })
+            .collect::<syn::Result<Vec<_>>>()?;
+        let authorization = match &self.authorize {
+            Some(authorize) => {
+                let scope = |scope: &Option<syn::LitStr>| match scope {
+                    Some(scope) => quote!(Some(#scope.to_string())),
+                    None => quote!(None),
+                };
+                let (read, write, delete) = (
+                    scope(&authorize.read),
+                    scope(&authorize.write),
+                    scope(&authorize.delete),
+                );
+                quote!(Some(awto::database::Authorization {
+                    read: #read,
+                    write: #write,
+                    delete: #delete,
+                }))
+            }
+            None => quote!(None),
+        };
+        let entity_attrs = &self.entity_attrs;
+        // Subscriptions stream the changes the table notifies
+        let subscriptions = self
+            .graphql
+            .as_ref()
+            .is_some_and(|graphql| graphql.subscriptions);
+        let external = attrs.external.is_some();
+        if external {
+            // Syncs leave external tables alone, so nothing needing their ddl can be declared
+            let unsupported = [
+                (attrs.events.is_some(), "record events"),
+                (attrs.notify.is_some() || subscriptions, "notify changes"),
+                (attrs.temporal.is_some(), "keep the history of their rows"),
+                (self.fulltext.is_some(), "have a full-text search index"),
+                (self.partition.is_some(), "be partitioned"),
+                (
+                    !self.policies.is_empty(),
+                    "have row-level security policies",
+                ),
+            ];
+            if let Some((_, what)) = unsupported.iter().find(|(declared, _)| *declared) {
+                return Err(syn::Error::new(
+                    self.ident.span(),
+                    format!("external models cannot {}\n\nexternal tables are managed outside of awto, which never alters them", what),
+                ));
+            }
+        }
+        let readonly = attrs.readonly.is_some() || external;
+        let skip_rest = attrs.skip_rest.is_some();
+        let versioned = attrs.versioned.is_some();
+        let events = attrs.events.is_some();
+        if events && readonly {
+            return Err(syn::Error::new(
+                self.ident.span(),
+                "readonly models cannot record events",
+            ));
+        }
+        if events && self.is_sub_model {
+            return Err(syn::Error::new(
+                self.ident.span(),
+                "events can only be recorded for database tables",
+            ));
+        }
+        let notify = attrs.notify.is_some() || subscriptions;
+        if notify && self.is_sub_model {
+            return Err(syn::Error::new(
+                self.ident.span(),
+                "only changes of database tables can be notified",
+            ));
+        }
+        let idempotent_create = attrs.idempotent_create.is_some();
+        if idempotent_create && (readonly || self.is_sub_model) {
+            return Err(syn::Error::new(
+                self.ident.span(),
+                "only database tables which are not readonly can have idempotent creates",
+            ));
+        }
+        let temporal = attrs.temporal.is_some();
+        if temporal && self.is_sub_model {
+            return Err(syn::Error::new(
+                self.ident.span(),
+                "only database tables can keep the history of their rows",
+            ));
+        }
+        let temporal = if temporal {
+            quote!(Some(awto::database::Temporal::Trigger))
+        } else {
+            quote!(None)
+        };
+
+        // The primary key is `id: Uuid`, unless a `String` field is marked `#[awto(primary_key)]`,
+        // such as the `code` of a country, whose value the application assigns
+        let key_fields: Vec<_> = fields
+            .iter()
+            .filter(|field| {
+                field.attrs.primary_key.is_some() || field.field.ident.as_ref().unwrap() == "id"
+            })
+            .collect();
+        if let Some(field) = fields
+            .iter()
+            .find(|field| field.attrs.primary_key.is_some())
+        {
+            if self.is_sub_model {
+                return Err(syn::Error::new(
+                    field.field.span(),
+                    "only database tables have a primary key",
+                ));
+            }
+            if key_fields.len() > 1 {
+                let candidates: Vec<_> = key_fields
+                    .iter()
+                    .map(|field| format!("`{}`", field.field.ident.as_ref().unwrap().unraw()))
+                    .collect();
+                return Err(syn::Error::new(
+                    key_fields[1].field.span(),
+                    format!(
+                        "database models must have exactly one primary key, found {}\n\nmark a single `String` field with #[awto(primary_key)], or remove it to use `id: Uuid`",
+                        candidates.join(", ")
+                    ),
+                ));
+            }
+            let field_str = field
+                .field
+                .ty
+                .to_token_stream()
+                .to_string()
+                .replace(' ', "");
+            if field_str != "String" && field_str != "std::string::String" {
+                return Err(syn::Error::new(
+                    field.field.ty.span(),
+                    "primary_key can only be used on `String` fields, other models use `id: Uuid`",
+                ));
+            }
+
+            // Generated code looks these up by their `Uuid` id
+            let unsupported = [
+                (versioned, "be versioned"),
+                (events, "record events"),
+                (notify, "notify changes"),
+                (attrs.temporal.is_some(), "keep the history of their rows"),
+                (
+                    self.localized.iter().any(Option::is_some),
+                    "have localized fields",
+                ),
+            ];
+            if let Some((_, what)) = unsupported.iter().find(|(declared, _)| *declared) {
+                return Err(syn::Error::new(
+                    field.field.span(),
+                    format!("models with a `String` primary key cannot {}", what),
+                ));
+            }
+        }
+        let key_name = key_fields
+            .first()
+            .map(|field| field.field.ident.as_ref().unwrap().unraw().to_string())
+            .unwrap_or_else(|| "id".to_string());
 
         if !self.is_sub_model {
             macro_rules! check_field_exists {
@@ -67,15 +718,35 @@ impl DatabaseTableModel {
                 };
             }
 
-            check_field_exists!("id", "Uuid");
+            if key_fields.is_empty() {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    "database models must have an `id: Uuid` column, or a `String` field marked #[awto(primary_key)]",
+                ));
+            }
             check_field_exists!("created_at", "DateTime<FixedOffset>");
             check_field_exists!("updated_at", "DateTime<FixedOffset>");
+
+            if versioned
+                && !fields
+                    .iter()
+                    .any(|field| field.field.ident.as_ref().unwrap() == "version")
+            {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    "versioned database models must have a `version: i32` column",
+                ));
+            }
         }
 
         let columns = fields
             .iter()
-            .map(|field| {
-                let name = field.field.ident.as_ref().unwrap().to_string();
+            .zip(&self.localized)
+            .zip(&self.deprecated)
+            .zip(&self.column)
+            .map(|(((field, localized), deprecated), column)| {
+                let name = field.field.ident.as_ref().unwrap().unraw().to_string();
+                let primary_key = name == key_name;
 
                 let field_str = field.field.ty.to_token_stream().to_string().replace(' ', "");
                 if name == "id" && field_str != "uuid::Uuid" && field_str != "Uuid" {
@@ -97,6 +768,10 @@ impl DatabaseTableModel {
                 {
                     return Err(syn::Error::new(field.field.ty.span(), "`updated_at` must be of type `DateTime<FixedOffset>`"));
                 }
+                let is_version = versioned && name == "version";
+                if is_version && field_str != "i32" {
+                    return Err(syn::Error::new(field.field.ty.span(), "`version` of versioned models must be of type `i32`"));
+                }
 
                 let mut ty = if let Some(db_type) = &field.attrs.db_type {
                     if let Ok(db_type) = db_type.value().parse::<TokenStream>() {
@@ -104,6 +779,9 @@ impl DatabaseTableModel {
                     } else {
                         return Err(syn::Error::new(db_type.span(), "invalid db_type"));
                     }
+                } else if map_value_type(&field.field.ty)?.is_some() {
+                    // String maps are stored as hstore instead with `MapStorage::Hstore`
+                    quote!(awto::database::DatabaseType::Jsonb)
                 } else if let Some(db_type) = Self::rust_to_db_type(&field.field.ty) {
                     db_type
                 } else {
@@ -125,9 +803,18 @@ impl DatabaseTableModel {
                     ty = quote!(#ty(None));
                 }
 
-                let nullable = Self::is_type_option(&field.field.ty);
-                if nullable && name == "id" {
-                    return Err(syn::Error::new(field.field.ty.span(), "`id` cannot be an Option"));
+                let optional_insert = field.attrs.optional_insert.is_some();
+                if optional_insert && !Self::is_type_option(&field.field.ty) {
+                    return Err(syn::Error::new(field.field.ty.span(), "optional_insert can only be used on Option fields"));
+                }
+                if optional_insert && (primary_key || name == "created_at" || name == "updated_at" || is_version) {
+                    return Err(syn::Error::new(field.field.ty.span(), format!("`{}` cannot be marked as optional_insert", name)));
+                }
+
+                // Optional insert columns are NOT NULL, and filled by their default or a trigger when omitted
+                let nullable = Self::is_type_option(&field.field.ty) && !optional_insert;
+                if nullable && primary_key {
+                    return Err(syn::Error::new(field.field.ty.span(), format!("`{}` cannot be an Option", name)));
                 }
                 if nullable && name == "created_at" {
                     return Err(syn::Error::new(field.field.ty.span(), "`created_at` cannot be an Option"));
@@ -137,8 +824,8 @@ impl DatabaseTableModel {
                 }
 
                 let verify_id_created_at_updated_at_custom_default = || {
-                    if name == "id" {
-                        return Err(syn::Error::new(field.field.ty.span(), "`id` cannot have a custom default"));
+                    if primary_key {
+                        return Err(syn::Error::new(field.field.ty.span(), format!("`{}` cannot have a custom default", name)));
                     }
                     if name == "created_at" {
                         return Err(syn::Error::new(field.field.ty.span(), "`created_at` cannot have a custom default"));
@@ -146,6 +833,9 @@ impl DatabaseTableModel {
                     if name == "updated_at" {
                         return Err(syn::Error::new(field.field.ty.span(), "`updated_at` cannot have a custom default"));
                     }
+                    if is_version {
+                        return Err(syn::Error::new(field.field.ty.span(), "`version` of versioned models cannot have a custom default"));
+                    }
                     Ok(())
                 };
 
@@ -171,11 +861,13 @@ impl DatabaseTableModel {
                     default = quote!(Some(awto::database::DatabaseDefault::Raw("uuid_generate_v4()".to_string())))
                 } else if name == "created_at" || name == "updated_at" {
                     default = quote!(Some(awto::database::DatabaseDefault::Raw("NOW()".to_string())))
+                } else if is_version {
+                    default = quote!(Some(awto::database::DatabaseDefault::Int(1)))
                 }
 
                 let unique = field.attrs.unique.is_some();
-                if unique && name == "id" {
-                    return Err(syn::Error::new(field.field.ty.span(), "`id` cannot be marked as unique"));
+                if unique && primary_key {
+                    return Err(syn::Error::new(field.field.ty.span(), format!("`{}` cannot be marked as unique", name)));
                 }
                 if unique && name == "created_at" {
                     return Err(syn::Error::new(field.field.ty.span(), "`created_at` cannot be marked as unique"));
@@ -183,10 +875,13 @@ impl DatabaseTableModel {
                 if unique && name == "updated_at" {
                     return Err(syn::Error::new(field.field.ty.span(), "`updated_at` cannot be marked as unique"));
                 }
+                if unique && is_version {
+                    return Err(syn::Error::new(field.field.ty.span(), "`version` of versioned models cannot be marked as unique"));
+                }
 
                 let references = if let Some(references) = &field.attrs.references {
-                    if name == "id" {
-                        return Err(syn::Error::new(field.field.ty.span(), "`id` cannot reference another table"));
+                    if primary_key {
+                        return Err(syn::Error::new(field.field.ty.span(), format!("`{}` cannot reference another table", name)));
                     }
                     if name == "created_at" {
                         return Err(syn::Error::new(field.field.ty.span(), "`created_at` cannot reference another table"));
@@ -194,37 +889,145 @@ impl DatabaseTableModel {
                     if name == "updated_at" {
                         return Err(syn::Error::new(field.field.ty.span(), "`updated_at` cannot reference another table"));
                     }
+                    if is_version {
+                        return Err(syn::Error::new(field.field.ty.span(), "`version` of versioned models cannot reference another table"));
+                    }
 
-                    let references_table = &references.0;
-                    let references_table_string = references.0.to_string();
-                    let references_column = references.1.value();
+                    let references_table = &references.table;
+                    let references_table_string = references.table.to_string();
+                    let references_column = references.column.value();
 
-                    quote!({
-                        if !<#references_table as awto::database::IntoDatabaseMessage>::database_message()
-                            .columns
+                    // Tables referencing themselves, such as employees referencing their manager, are checked here,
+                    // as building their own table to look up the column would recurse forever
+                    if references_table == ident {
+                        if !fields
                             .iter()
-                            .any(|column| column.name == #references_column)
+                            .any(|other| other.field.ident.as_ref().map(|ident| ident.unraw().to_string()) == Some(references_column.clone()))
                         {
-                            panic!(concat!(
-                                "[error] ",
-                                file!(),
-                                ": column '",
-                                #references_column,
-                                "' does not exist on table ",
-                                #references_table_string
-                            ))
+                            return Err(syn::Error::new(
+                                references.column.span(),
+                                format!("column '{}' does not exist on table {}", references_column, ident),
+                            ));
                         }
 
-                        Some((
-                            <#references_table as awto::database::IntoDatabaseMessage>::database_message().name,
-                            #references_column.to_string(),
-                        ))
-                    })
+                        quote!(Some((#table_name.to_string(), #references_column.to_string())))
+                    } else {
+                        quote!({
+                            if !<#references_table as awto::database::IntoDatabaseTable>::database_table()
+                                .columns
+                                .iter()
+                                .any(|column| column.name == #references_column)
+                            {
+                                panic!(concat!(
+                                    "[error] ",
+                                    file!(),
+                                    ": column '",
+                                    #references_column,
+                                    "' does not exist on table ",
+                                    #references_table_string
+                                ))
+                            }
+
+                            Some((
+                                <#references_table as awto::database::IntoDatabaseTable>::database_table().name,
+                                #references_column.to_string(),
+                            ))
+                        })
+                    }
                 } else {
                     quote!(None)
                 };
 
-                let primary_key = name == "id";
+                let reference_constraint = match field.attrs.references.as_ref().and_then(|references| references.constraint.as_ref()) {
+                    Some(constraint) => quote!(Some(#constraint)),
+                    None => quote!(None),
+                };
+
+                let cross_database = field
+                    .attrs
+                    .references
+                    .as_ref()
+                    .map(|references| references.cross_database.is_some())
+                    .unwrap_or(false);
+
+                let locales = match localized {
+                    Some(localized) => {
+                        if primary_key || name == "created_at" || name == "updated_at" || is_version {
+                            return Err(syn::Error::new(localized.locales.span(), format!("`{}` cannot be localized", name)));
+                        }
+                        if !db_type_is_text {
+                            return Err(syn::Error::new(localized.locales.span(), "only text fields can be localized"));
+                        }
+                        Self::parse_locales(&localized.locales)?
+                    }
+                    None => Vec::new(),
+                };
+
+                let computed = match &field.attrs.computed {
+                    Some(computed) => {
+                        if primary_key || name == "created_at" || name == "updated_at" || is_version {
+                            return Err(syn::Error::new(computed.span(), format!("`{}` cannot be computed", name)));
+                        }
+                        if field.attrs.stored.is_none() {
+                            return Err(syn::Error::new(
+                                computed.span(),
+                                "computed columns must be marked as stored\n\npostgres only supports stored generated columns, add `stored` to #[awto(computed = \"...\", stored)]",
+                            ));
+                        }
+                        if field.attrs.default.is_some() || field.attrs.default_raw.is_some() {
+                            return Err(syn::Error::new(computed.span(), "computed columns cannot have a default"));
+                        }
+                        if optional_insert {
+                            return Err(syn::Error::new(computed.span(), "computed columns cannot be marked as optional_insert"));
+                        }
+                        if field.attrs.references.is_some() {
+                            return Err(syn::Error::new(computed.span(), "computed columns cannot reference another table"));
+                        }
+                        if localized.is_some() {
+                            return Err(syn::Error::new(computed.span(), "computed columns cannot be localized"));
+                        }
+                        if computed.value().trim().is_empty() {
+                            return Err(syn::Error::new(computed.span(), "computed must be a sql expression"));
+                        }
+
+                        let expression = computed.value();
+                        quote!(Some(#expression.to_string()))
+                    }
+                    None => {
+                        if field.attrs.stored.is_some() {
+                            return Err(syn::Error::new(field.field.ty.span(), "stored can only be used on computed columns"));
+                        }
+                        quote!(None)
+                    }
+                };
+
+                let normalize = Self::parse_normalizers(field, fields, db_type_is_text)?;
+                let fake = Self::parse_fake(field, db_type_is_text)?;
+
+                let backfill = match column {
+                    Some(column) => {
+                        if field.attrs.computed.is_some() {
+                            return Err(syn::Error::new(column.backfill.span(), "computed columns cannot have a backfill"));
+                        }
+                        let expression = column.backfill.value();
+                        quote!(Some(#expression.to_string()))
+                    }
+                    None => quote!(None),
+                };
+
+                if deprecated.is_some() {
+                    if primary_key || name == "created_at" || name == "updated_at" || is_version {
+                        return Err(syn::Error::new(field.field.ty.span(), format!("`{}` cannot be deprecated", name)));
+                    }
+                    // Generated inserts no longer write deprecated fields, so their columns must be filled without them
+                    let has_default = field.attrs.default.is_some() || field.attrs.default_raw.is_some();
+                    if !nullable && !optional_insert && !has_default && field.attrs.computed.is_none() {
+                        return Err(syn::Error::new(
+                            field.field.ty.span(),
+                            "deprecated fields must be an Option or have a default\n\ngenerated inserts no longer write deprecated fields, so their column must be nullable or filled by its default",
+                        ));
+                    }
+                }
 
                 Ok(quote!(
                     awto::database::DatabaseColumn {
@@ -236,6 +1039,13 @@ impl DatabaseTableModel {
                         constraint: None,
                         primary_key: #primary_key,
                         references: #references,
+                        reference_constraint: #reference_constraint,
+                        cross_database: #cross_database,
+                        locales: vec![ #( #locales.to_string() ),* ],
+                        computed: #computed,
+                        normalize: vec![ #( #normalize ),* ],
+                        fake: #fake,
+                        backfill: #backfill,
                     }
                 ))
             })
@@ -245,10 +1055,227 @@ impl DatabaseTableModel {
             awto::database::DatabaseTable {
                 name: #table_name.to_string(),
                 columns: vec![ #( #columns, )* ],
+                database: #database,
+                order_by: #order_by,
+                fulltext: #fulltext,
+                partition: #partition,
+                policies: vec![ #( #policies, )* ],
+                projections: vec![ #( #projections, )* ],
+                scopes: vec![ #( #scopes, )* ],
+                readonly: #readonly,
+                external: #external,
+                skip_rest: #skip_rest,
+                upsert_on: #upsert_on,
+                versioned: #versioned,
+                events: #events,
+                notify: #notify,
+                subscriptions: #subscriptions,
+                idempotent_create: #idempotent_create,
+                temporal: #temporal,
+                localization: awto::database::Localization::Columns,
+                authorization: #authorization,
+                default_authorization: awto::database::DefaultAuthorization::Allow,
+                entity_attrs: vec![ #( #entity_attrs.to_string() ),* ],
+                entity_derives: vec![],
+                plural: None,
+                visibility: #visibility,
             }
         ))
     }
 
+    /// Parses the provider of `#[awto(fake = "...")]` of `field`, such as `email`, which only text fields can have.
+    fn parse_fake(field: &Field<ItemAttrs>, is_text: bool) -> syn::Result<TokenStream> {
+        const FAKES: [(&str, &str); 13] = [
+            ("name", "Name"),
+            ("first_name", "FirstName"),
+            ("last_name", "LastName"),
+            ("email", "Email"),
+            ("username", "Username"),
+            ("phone", "Phone"),
+            ("company", "Company"),
+            ("city", "City"),
+            ("country", "Country"),
+            ("url", "Url"),
+            ("word", "Word"),
+            ("sentence", "Sentence"),
+            ("paragraph", "Paragraph"),
+        ];
+
+        let fake = match &field.attrs.fake {
+            Some(fake) => fake,
+            None => return Ok(quote!(None)),
+        };
+        if !is_text {
+            return Err(syn::Error::new(
+                fake.span(),
+                "only text fields can have a fake provider",
+            ));
+        }
+        if field.attrs.computed.is_some() {
+            return Err(syn::Error::new(
+                fake.span(),
+                "computed columns cannot have a fake provider",
+            ));
+        }
+
+        let value = fake.value();
+        match FAKES.iter().find(|(name, _)| *name == value) {
+            Some((_, variant)) => {
+                let variant = syn::Ident::new(variant, fake.span());
+                Ok(quote!(Some(awto::database::Fake::#variant)))
+            }
+            None => Err(syn::Error::new(
+                fake.span(),
+                format!(
+                    "unknown fake provider `{}`, expected one of {}",
+                    value,
+                    FAKES
+                        .iter()
+                        .map(|(name, _)| format!("`{}`", name))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            )),
+        }
+    }
+
+    /// Parses the comma separated normalizers of `#[awto(normalize = "...")]` of `field`, such as `trim, lowercase`,
+    /// followed by the function of `#[awto(normalize_fn = "...")]`.
+    ///
+    /// `slugify-from(...)` must name another text field of `fields`.
+    fn parse_normalizers(
+        field: &Field<ItemAttrs>,
+        fields: &[Field<ItemAttrs>],
+        is_text: bool,
+    ) -> syn::Result<Vec<TokenStream>> {
+        let mut normalizers = Vec::new();
+        let span = match (&field.attrs.normalize, &field.attrs.normalize_fn) {
+            (Some(normalize), _) => normalize.span(),
+            (None, Some(normalize_fn)) => normalize_fn.span(),
+            (None, None) => return Ok(normalizers),
+        };
+        if !is_text {
+            return Err(syn::Error::new(span, "only text fields can be normalized"));
+        }
+        if field.attrs.computed.is_some() {
+            return Err(syn::Error::new(
+                span,
+                "computed columns cannot be normalized",
+            ));
+        }
+
+        if let Some(normalize) = &field.attrs.normalize {
+            for normalizer in normalize.value().split(',').map(str::trim) {
+                let normalizer = match normalizer {
+                    "lowercase" => quote!(awto::database::Normalizer::Lowercase),
+                    "trim" => quote!(awto::database::Normalizer::Trim),
+                    _ => {
+                        let source = normalizer
+                            .strip_prefix("slugify-from(")
+                            .and_then(|rest| rest.strip_suffix(')'))
+                            .map(str::trim)
+                            .ok_or_else(|| {
+                                syn::Error::new(
+                                    normalize.span(),
+                                    format!(
+                                        "unknown normalizer `{}`, expected `lowercase`, `trim` or `slugify-from(field)`",
+                                        normalizer
+                                    ),
+                                )
+                            })?;
+                        let source_field = fields
+                            .iter()
+                            .find(|other| {
+                                other
+                                    .field
+                                    .ident
+                                    .as_ref()
+                                    .map(|ident| ident.unraw().to_string())
+                                    .as_deref()
+                                    == Some(source)
+                            })
+                            .ok_or_else(|| {
+                                syn::Error::new(
+                                    normalize.span(),
+                                    format!("field '{}' does not exist", source),
+                                )
+                            })?;
+                        if source_field.field.ident == field.field.ident {
+                            return Err(syn::Error::new(
+                                normalize.span(),
+                                "fields cannot be slugified from themselves",
+                            ));
+                        }
+                        let source_is_text = match &source_field.attrs.db_type {
+                            Some(db_type) => db_type.value() == "Text",
+                            None => Self::rust_to_db_type(&source_field.field.ty)
+                                .is_some_and(|ty| ty.to_string().ends_with(":: Text")),
+                        };
+                        if !source_is_text {
+                            return Err(syn::Error::new(
+                                normalize.span(),
+                                format!("fields can only be slugified from text fields, and '{}' is not one", source),
+                            ));
+                        }
+                        quote!(awto::database::Normalizer::SlugifyFrom(#source.to_string()))
+                    }
+                };
+                normalizers.push(normalizer);
+            }
+        }
+
+        if let Some(normalize_fn) = &field.attrs.normalize_fn {
+            let path: syn::Path = normalize_fn.parse()?;
+            if path.segments.len() < 2 || path.segments[0].ident != "crate" {
+                return Err(syn::Error::new(
+                    normalize_fn.span(),
+                    "normalize_fn must be the path of a function of the schema crate, such as \"crate::normalize::phone\"",
+                ));
+            }
+            let path_string = path.to_token_stream().to_string().replace(' ', "");
+            // Checks the signature of the function while compiling the schema, rather than the database package
+            normalizers.push(quote!(awto::database::Normalizer::Function({
+                let _: fn(&str) -> String = #path;
+                #path_string.to_string()
+            })));
+        }
+
+        Ok(normalizers)
+    }
+
+    /// Parses the comma separated locales of `#[awto(localized(locales = "..."))]`,
+    /// such as `en` or `pt-BR`, which are also used in column names.
+    fn parse_locales(locales: &syn::LitStr) -> syn::Result<Vec<String>> {
+        let mut parsed: Vec<String> = Vec::new();
+        for locale in locales.value().split(',').map(str::trim) {
+            let mut subtags = locale.split('-');
+            let language = subtags.next().unwrap_or_default();
+            let is_valid = (2..=3).contains(&language.len())
+                && language.chars().all(|c| c.is_ascii_lowercase())
+                && subtags.all(|subtag| {
+                    !subtag.is_empty() && subtag.chars().all(|c| c.is_ascii_alphanumeric())
+                });
+            if !is_valid {
+                return Err(syn::Error::new(
+                    locales.span(),
+                    format!("invalid locale `{}`\n\nlocales must be language tags such as `en` or `pt-BR`", locale),
+                ));
+            }
+            if parsed
+                .iter()
+                .any(|other| other.eq_ignore_ascii_case(locale))
+            {
+                return Err(syn::Error::new(
+                    locales.span(),
+                    format!("locale `{}` is listed more than once", locale),
+                ));
+            }
+            parsed.push(locale.to_string());
+        }
+
+        Ok(parsed)
+    }
+
     fn is_type_option(ty: &syn::Type) -> bool {
         match ty {
             syn::Type::Path(path) => path
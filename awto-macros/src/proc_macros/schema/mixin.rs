@@ -0,0 +1,128 @@
+use crate::attributes::{IncludeAttr, MixinAttr};
+
+/// A struct marked with `#[awto(mixin)]`, whose fields are spliced into the models including it.
+struct Mixin {
+    item: syn::ItemStruct,
+    includes: Vec<syn::Ident>,
+}
+
+/// Removes the mixins from `structs` and splices their fields into the structs including them.
+///
+/// Included fields keep their attributes and follow the fields of the including struct, in the order of the includes.
+pub fn expand_mixins(structs: Vec<syn::ItemStruct>) -> syn::Result<Vec<syn::ItemStruct>> {
+    let mut mixins = Vec::new();
+    let mut models = Vec::new();
+    for mut item in structs {
+        let is_mixin = MixinAttr::take_from_attributes(&mut item.attrs)?;
+        let includes = IncludeAttr::take_from_attributes(&mut item.attrs)?
+            .into_iter()
+            .map(|include| include.0)
+            .collect::<Vec<_>>();
+
+        if is_mixin {
+            if let Some(attr) = item.attrs.iter().find(|attr| {
+                ["database_table", "database_sub_table", "protobuf_message"]
+                    .iter()
+                    .any(|role| attr.path.is_ident(role))
+            }) {
+                return Err(syn::Error::new(
+                    attr.path.get_ident().unwrap().span(),
+                    "mixins cannot have a role attribute\n\nmixins are not models themselves, include them in models with #[awto(include = ...)]",
+                ));
+            }
+            if !matches!(item.fields, syn::Fields::Named(_)) {
+                return Err(syn::Error::new(
+                    item.ident.span(),
+                    "mixin fields must be named",
+                ));
+            }
+            mixins.push(Mixin { item, includes });
+        } else {
+            models.push((item, includes));
+        }
+    }
+
+    models
+        .into_iter()
+        .map(|(mut item, includes)| {
+            if includes.is_empty() {
+                return Ok(item);
+            }
+
+            let fields = expand_fields(&item, &includes, &mixins, &mut Vec::new())?;
+            if let syn::Fields::Named(named) = &mut item.fields {
+                named.named = fields.into_iter().collect();
+            }
+
+            Ok(item)
+        })
+        .collect()
+}
+
+/// Returns the fields of `item` followed by the fields of its `includes`.
+///
+/// `stack` holds the mixins being expanded, so a mixin including itself is reported instead of recursing forever.
+fn expand_fields(
+    item: &syn::ItemStruct,
+    includes: &[syn::Ident],
+    mixins: &[Mixin],
+    stack: &mut Vec<syn::Ident>,
+) -> syn::Result<Vec<syn::Field>> {
+    let mut fields: Vec<syn::Field> = item.fields.iter().cloned().collect();
+
+    for include in includes {
+        let mixin = mixins
+            .iter()
+            .find(|mixin| mixin.item.ident == *include)
+            .ok_or_else(|| {
+                syn::Error::new(
+                    include.span(),
+                    format!(
+                        "mixin `{}` not found\n\nmixins must be declared with #[awto(mixin)] in the same schema! block",
+                        include
+                    ),
+                )
+            })?;
+
+        if stack.contains(include) {
+            let cycle: Vec<_> = stack
+                .iter()
+                .chain(Some(include))
+                .map(ToString::to_string)
+                .collect();
+            return Err(syn::Error::new(
+                include.span(),
+                format!("mixins include each other: {}", cycle.join(" -> ")),
+            ));
+        }
+
+        stack.push(include.clone());
+        let mixin_fields = expand_fields(&mixin.item, &mixin.includes, mixins, stack)?;
+        stack.pop();
+
+        for field in mixin_fields {
+            let field_ident = field.ident.as_ref().unwrap();
+            if let Some(existing) = fields
+                .iter()
+                .find(|existing| existing.ident.as_ref() == Some(field_ident))
+            {
+                let mut err = syn::Error::new(
+                    existing.ident.as_ref().unwrap().span(),
+                    format!(
+                        "field `{}` of `{}` is also declared in mixin `{}`",
+                        field_ident, item.ident, include
+                    ),
+                );
+                err.combine(syn::Error::new(
+                    field_ident.span(),
+                    format!("`{}` is declared in mixin `{}` here", field_ident, include),
+                ));
+                return Err(err);
+            }
+
+            fields.push(field);
+        }
+    }
+
+    Ok(fields)
+}
@@ -0,0 +1,196 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::spanned::Spanned;
+
+use crate::attributes::NewtypeAttr;
+
+/// A single field tuple struct marked with `#[awto(newtype)]`, such as `pub struct Cents(pub i64);`.
+///
+/// Fields of a newtype are stored and sent like its inner type, which the newtype converts from and into.
+pub struct Newtype {
+    pub item: syn::ItemStruct,
+    pub inner: syn::Type,
+}
+
+impl Newtype {
+    /// Returns the struct of the newtype along with its conversions from and into its inner type.
+    pub fn expand(&self) -> TokenStream {
+        let Self { item, inner } = self;
+        let ident = &item.ident;
+        let mut item = item.clone();
+        item.attrs.retain(|attr| !attr.path.is_ident("awto"));
+
+        quote!(
+            #item
+
+            impl ::std::convert::From<#ident> for #inner {
+                fn from(val: #ident) -> Self {
+                    val.0
+                }
+            }
+
+            impl ::std::convert::From<#inner> for #ident {
+                fn from(val: #inner) -> Self {
+                    Self(val)
+                }
+            }
+        )
+    }
+}
+
+/// Removes the newtypes from `structs`, returning them along with the remaining structs.
+pub fn take_newtypes(
+    structs: Vec<syn::ItemStruct>,
+) -> syn::Result<(Vec<Newtype>, Vec<syn::ItemStruct>)> {
+    let mut newtypes = Vec::new();
+    let mut items = Vec::new();
+    for mut item in structs {
+        if !NewtypeAttr::take_from_attributes(&mut item.attrs)? {
+            items.push(item);
+            continue;
+        }
+
+        if let Some(attr) = item.attrs.iter().find(|attr| {
+            ["database_table", "database_sub_table", "protobuf_message"]
+                .iter()
+                .any(|role| attr.path.is_ident(role))
+        }) {
+            return Err(syn::Error::new(
+                attr.path.get_ident().unwrap().span(),
+                "newtypes cannot have a role attribute\n\nnewtypes are not models themselves, use them as the type of fields",
+            ));
+        }
+        if !matches!(item.vis, syn::Visibility::Public(_)) {
+            return Err(syn::Error::new(item.ident.span(), "struct must be public"));
+        }
+        if !item.generics.params.is_empty() {
+            return Err(syn::Error::new(
+                item.generics.span(),
+                "newtypes cannot be generic",
+            ));
+        }
+        let field = match &item.fields {
+            syn::Fields::Unnamed(fields) if fields.unnamed.len() == 1 => fields.unnamed[0].clone(),
+            _ => {
+                return Err(syn::Error::new(
+                    item.ident.span(),
+                    "newtypes must be tuple structs with a single field, such as `pub struct Cents(pub i64);`",
+                ))
+            }
+        };
+        if !matches!(field.vis, syn::Visibility::Public(_)) {
+            return Err(syn::Error::new(
+                field.ty.span(),
+                "the field of a newtype must be public, as generated code reads it",
+            ));
+        }
+        if option_inner(&field.ty).is_some() {
+            return Err(syn::Error::new(
+                field.ty.span(),
+                "newtypes cannot wrap an Option\n\nwrap the inner type and use an Option of the newtype instead",
+            ));
+        }
+
+        newtypes.push(Newtype {
+            item,
+            inner: field.ty,
+        });
+    }
+
+    for newtype in &newtypes {
+        if let Some(nested) = newtypes
+            .iter()
+            .find(|other| mentions(&newtype.inner, &other.item.ident))
+        {
+            return Err(syn::Error::new(
+                newtype.inner.span(),
+                format!(
+                    "newtype `{}` wraps newtype `{}`, nested newtypes are not supported\n\nwrap the inner type of `{}` directly",
+                    newtype.item.ident, nested.item.ident, nested.item.ident
+                ),
+            ));
+        }
+    }
+
+    Ok((newtypes, items))
+}
+
+/// Returns `item` with the newtypes of its field types replaced by their inner types,
+/// such as `Option<Cents>` by `Option<i64>`, which is how the fields are stored.
+pub fn resolve_item(item: &syn::ItemStruct, newtypes: &[Newtype]) -> syn::ItemStruct {
+    let mut item = item.clone();
+    for field in &mut item.fields {
+        field.ty = resolve_type(&field.ty, newtypes);
+    }
+
+    item
+}
+
+/// Returns the newtype of a field of type `ty`, which is either the newtype or an `Option` of it.
+pub fn field_newtype<'a>(ty: &syn::Type, newtypes: &'a [Newtype]) -> Option<&'a Newtype> {
+    let ty = option_inner(ty).unwrap_or(ty);
+    newtypes.iter().find(|newtype| match ty {
+        syn::Type::Path(path) => path.qself.is_none() && path.path.is_ident(&newtype.item.ident),
+        _ => false,
+    })
+}
+
+fn resolve_type(ty: &syn::Type, newtypes: &[Newtype]) -> syn::Type {
+    let mut ty = ty.clone();
+    if let syn::Type::Path(path) = &mut ty {
+        if let Some(newtype) = newtypes
+            .iter()
+            .find(|newtype| path.qself.is_none() && path.path.is_ident(&newtype.item.ident))
+        {
+            return newtype.inner.clone();
+        }
+
+        for segment in &mut path.path.segments {
+            if let syn::PathArguments::AngleBracketed(args) = &mut segment.arguments {
+                for arg in &mut args.args {
+                    if let syn::GenericArgument::Type(arg) = arg {
+                        *arg = resolve_type(arg, newtypes);
+                    }
+                }
+            }
+        }
+    }
+
+    ty
+}
+
+/// Returns the type wrapped by `ty` if it is an `Option`.
+fn option_inner(ty: &syn::Type) -> Option<&syn::Type> {
+    let segment = match ty {
+        syn::Type::Path(path) => path.path.segments.last()?,
+        _ => return None,
+    };
+    if segment.ident != "Option" {
+        return None;
+    }
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(args) => args.args.iter().find_map(|arg| match arg {
+            syn::GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+/// Returns whether `ident` appears in `ty`, such as `Cents` in `Vec<Cents>`.
+fn mentions(ty: &syn::Type, ident: &syn::Ident) -> bool {
+    quote!(#ty)
+        .into_iter()
+        .any(|token| token_mentions(token, ident))
+}
+
+fn token_mentions(token: proc_macro2::TokenTree, ident: &syn::Ident) -> bool {
+    match token {
+        proc_macro2::TokenTree::Ident(token) => token == *ident,
+        proc_macro2::TokenTree::Group(group) => group
+            .stream()
+            .into_iter()
+            .any(|token| token_mentions(token, ident)),
+        _ => false,
+    }
+}
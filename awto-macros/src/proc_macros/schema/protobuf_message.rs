@@ -1,30 +1,52 @@
 use proc_macro2::TokenStream;
-use quote::{format_ident, quote};
-use syn::spanned::Spanned;
+use quote::{format_ident, quote, ToTokens};
+use syn::{ext::IdentExt, spanned::Spanned};
 
 use crate::{
-    attributes::ItemAttrs,
+    attributes::{ColumnAttr, DeprecatedAttr, ItemAttrs, LocalizedAttr, ProtoAttr},
     error::Error,
-    util::{parse_fields, Field},
+    util::{map_value_type, parse_fields, Field},
 };
 
+use super::Sensitivity;
+
 pub struct ProtobufMessageModel {
     fields: Vec<Field<ItemAttrs>>,
+    /// Whether each field is marked with `#[awto(deprecated)]`, in the order of `fields`.
+    deprecated: Vec<bool>,
     ident: syn::Ident,
+    proto: Option<ProtoAttr>,
 }
 
 impl ProtobufMessageModel {
-    pub fn new(item: syn::ItemStruct) -> Result<Self, Error> {
-        let punctuated_fields = match item.fields {
+    pub fn new(mut item: syn::ItemStruct) -> Result<Self, Error> {
+        let proto = ProtoAttr::take_from_attributes(&mut item.attrs).map_err(Error::Syn)?;
+        let mut punctuated_fields = match item.fields {
             syn::Fields::Named(named) => named.named,
             _ => return Err(Error::FieldsNotNamed),
         };
 
+        // Localized fields are a single string in protobuf messages, in the locale of the request
+        let mut deprecated = Vec::new();
+        for field in &mut punctuated_fields {
+            LocalizedAttr::take_from_attributes(&mut field.attrs).map_err(Error::Syn)?;
+            ColumnAttr::take_from_attributes(&mut field.attrs).map_err(Error::Syn)?;
+            deprecated.push(
+                DeprecatedAttr::take_from_attributes(&mut field.attrs)
+                    .map_err(Error::Syn)?
+                    .is_some(),
+            );
+        }
         let fields = parse_fields::<ItemAttrs>(punctuated_fields)?;
 
         let ident = item.ident;
 
-        Ok(ProtobufMessageModel { fields, ident })
+        Ok(ProtobufMessageModel {
+            fields,
+            deprecated,
+            ident,
+            proto,
+        })
     }
 
     pub fn expand(self) -> syn::Result<TokenStream> {
@@ -36,20 +58,55 @@ impl ProtobufMessageModel {
 
 impl ProtobufMessageModel {
     fn expand_protobuf_message(&self) -> syn::Result<TokenStream> {
-        let Self { fields, ident } = self;
+        let Self {
+            fields,
+            deprecated,
+            ident,
+            proto,
+        } = self;
 
-        let name = ident.to_string();
+        let name = ident.unraw().to_string();
 
+        // Secret fields are never sent, so messages leave them out
         let fields = fields
             .iter()
-            .map(|field| {
-                let name = field.field.ident.as_ref().unwrap().to_string();
+            .zip(deprecated)
+            .filter(|(field, _)| {
+                !matches!(Sensitivity::parse(field.attrs.sensitivity.as_ref()), Ok(Sensitivity::Secret))
+            })
+            .map(|(field, deprecated)| {
+                let name = field.field.ident.as_ref().unwrap().unraw().to_string();
+                // Messages containing themselves have no end, rows of trees reference their parent by id instead
+                if Self::contains_ident(field.field.ty.to_token_stream(), ident) {
+                    return Err(syn::Error::new(
+                        field.field.ty.span(),
+                        format!(
+                            "`{model}` cannot contain itself, reference it by id instead, \
+                            such as `parent_id: Option<Uuid>` with `#[awto(references = ({model}, \"id\"))]`",
+                            model = ident.unraw()
+                        ),
+                    ));
+                }
+                let map_value_ty = map_value_type(&field.field.ty)?;
                 let ty = if let Some(proto_type) = &field.attrs.proto_type {
                     if let Ok(proto_type) = proto_type.value().parse::<TokenStream>() {
                         quote!(awto::protobuf::ProtobufType::#proto_type)
                     } else {
                         return Err(syn::Error::new(proto_type.span(), "invalid proto_type"));
                     }
+                } else if let Some(value_ty) = map_value_ty {
+                    let value_str = value_ty.to_token_stream().to_string().replace(' ', "");
+                    match Self::rust_str_to_proto_type(&value_str) {
+                        Some(value_type) if !value_type.to_string().starts_with("Repeated") => {
+                            quote!(awto::protobuf::ProtobufType::Map(::std::boxed::Box::new(awto::protobuf::ProtobufType::#value_type)))
+                        }
+                        _ => {
+                            return Err(syn::Error::new(
+                                value_ty.span(),
+                                "map values must be scalars, strings, bytes or messages",
+                            ))
+                        }
+                    }
                 } else if let Some(proto_type) = Self::rust_to_proto_type(&field.field.ty) {
                     proto_type
                 } else {
@@ -58,26 +115,44 @@ impl ProtobufMessageModel {
                         "type is not suppoerted",
                     ));
                 };
-                let required = !Self::is_type_option(&field.field.ty);
+                // Map fields cannot be optional, an empty map is the same as an unset map
+                let required = !Self::is_type_option(&field.field.ty) || map_value_ty.is_some();
 
                 Ok(quote!(
                     awto::protobuf::ProtobufField {
                         name: #name.to_string(),
                         ty: #ty,
                         required: #required,
+                        deprecated: #deprecated,
                     }
                 ))
             })
             .collect::<Result<Vec<_>, _>>()?;
 
+        let version = ProtoAttr::expand_version(proto)?;
+        let defaults = ProtoAttr::expand_defaults(proto);
+
         Ok(quote!(
             awto::protobuf::ProtobufMessage {
                 name: #name.to_string(),
                 fields: vec![ #( #fields, )* ],
+                version: #version,
+                defaults: #defaults,
             }
         ))
     }
 
+    /// Returns whether `tokens` of a type name the type `ident`, or `Self`, such as `Option<Box<Category>>`.
+    fn contains_ident(tokens: TokenStream, ident: &syn::Ident) -> bool {
+        tokens.into_iter().any(|token| match token {
+            proc_macro2::TokenTree::Ident(token) => {
+                token == "Self" || token.unraw() == ident.unraw()
+            }
+            proc_macro2::TokenTree::Group(group) => Self::contains_ident(group.stream(), ident),
+            _ => false,
+        })
+    }
+
     fn is_type_option(ty: &syn::Type) -> bool {
         match ty {
             syn::Type::Path(path) => path
@@ -1,5 +1,6 @@
 use better_bae::TryFromAttributes;
 use proc_macro2::TokenStream;
+use quote::ToTokens;
 use syn::Token;
 
 use crate::error::Error;
@@ -37,3 +38,75 @@ where
         })
         .collect::<Result<_, _>>()
 }
+
+/// Returns the value type of a `HashMap<String, T>` field type, or of an `Option` of it.
+///
+/// Maps are stored as JSON objects and protobuf `map<string, T>` fields, so keys other than `String` are an error.
+pub fn map_value_type(ty: &syn::Type) -> syn::Result<Option<&syn::Type>> {
+    let mut segment = match ty {
+        syn::Type::Path(path) => path.path.segments.last(),
+        _ => None,
+    };
+    if let Some(option) = segment.filter(|segment| segment.ident == "Option") {
+        segment = match generic_types(option).as_slice() {
+            [syn::Type::Path(path)] => path.path.segments.last(),
+            _ => None,
+        };
+    }
+    let segment = match segment {
+        Some(segment) if segment.ident == "HashMap" => segment,
+        _ => return Ok(None),
+    };
+
+    match generic_types(segment).as_slice() {
+        [key, value] => {
+            let key_str = key.to_token_stream().to_string().replace(' ', "");
+            if key_str != "String" && key_str != "std::string::String" {
+                return Err(syn::Error::new_spanned(
+                    key,
+                    format!("map keys must be `String`, found `{}`", key_str),
+                ));
+            }
+            Ok(Some(value))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Returns the doc comment of the `#[doc = "..."]` attributes in `attrs`,
+/// with the space following `///` removed from each line.
+pub fn doc_comment(attrs: &[syn::Attribute]) -> Option<String> {
+    let lines: Vec<_> = attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("doc"))
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(syn::Meta::NameValue(syn::MetaNameValue {
+                lit: syn::Lit::Str(lit),
+                ..
+            })) => Some(lit.value()),
+            _ => None,
+        })
+        .collect();
+    let lines: Vec<_> = lines
+        .iter()
+        .flat_map(|line| line.split('\n'))
+        .map(|line| line.strip_prefix(' ').unwrap_or(line))
+        .collect();
+
+    let doc = lines.join("\n").trim().to_string();
+    (!doc.is_empty()).then_some(doc)
+}
+
+fn generic_types(segment: &syn::PathSegment) -> Vec<&syn::Type> {
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(arguments) => arguments
+            .args
+            .iter()
+            .filter_map(|argument| match argument {
+                syn::GenericArgument::Type(ty) => Some(ty),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
@@ -0,0 +1,466 @@
+//! Checkpoints of long running imports and generations of rows, so they can be resumed once interrupted.
+//!
+//! The `import_*_checkpointed` and `generate_rows` functions of the generated database crate save their progress
+//! into a sidecar file every few committed batches, when their run fails, and when it is interrupted.
+//! A run resuming from the checkpoint continues after the last row it records.
+
+use std::{
+    error, fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+/// Where and how often the progress of an import or generation of rows is saved.
+#[derive(Clone, Debug)]
+pub struct Checkpointing {
+    /// Sidecar file the checkpoint is saved into, such as `products.csv.checkpoint`.
+    pub path: PathBuf,
+    /// Number of committed batches between saved checkpoints.
+    pub every: usize,
+    /// Whether the run continues from the checkpoint saved at `path`, instead of starting over.
+    pub resume: bool,
+    /// Stops the run after its current batch once set, such as on Ctrl-C, saving a last checkpoint.
+    pub interrupted: Arc<AtomicBool>,
+}
+
+impl Checkpointing {
+    /// Returns the checkpointing of a run into `path`, saved after every batch.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Checkpointing {
+            path: path.into(),
+            every: 1,
+            resume: false,
+            interrupted: Arc::default(),
+        }
+    }
+
+    /// Stops the run after its current batch.
+    pub fn interrupt(&self) {
+        self.interrupted.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_interrupted(&self) -> bool {
+        self.interrupted.load(Ordering::SeqCst)
+    }
+}
+
+/// Progress of a run, as of its last committed batch.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Progress {
+    /// Number of rows read from the imported file, or generated.
+    pub rows: u64,
+    /// Number of rows inserted.
+    pub inserted: u64,
+    /// Byte offset of the imported file past the last row read.
+    pub offset: u64,
+    /// State of the random number generator of generated rows.
+    pub state: u64,
+    /// Number of the first generated row, which is the number of rows the table held before.
+    pub start: u64,
+}
+
+/// Progress of a run into a table, saved into its checkpoint file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// Table the rows are inserted into.
+    pub table: String,
+    pub progress: Progress,
+}
+
+impl Checkpoint {
+    /// Reads the checkpoint saved at `path`, or `None` when there is none.
+    pub fn read(path: &Path) -> Result<Option<Checkpoint>, CheckpointError> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut checkpoint = Checkpoint::default();
+        for (i, line) in text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+        {
+            let invalid = || CheckpointError::Invalid {
+                path: path.to_path_buf(),
+                line: i + 1,
+            };
+            let (key, value) = line.split_once('=').ok_or_else(invalid)?;
+            let (key, value) = (key.trim(), value.trim());
+            let number = || value.parse::<u64>().map_err(|_| invalid());
+            match key {
+                "table" => checkpoint.table = value.to_string(),
+                "rows" => checkpoint.progress.rows = number()?,
+                "inserted" => checkpoint.progress.inserted = number()?,
+                "offset" => checkpoint.progress.offset = number()?,
+                "state" => checkpoint.progress.state = number()?,
+                "start" => checkpoint.progress.start = number()?,
+                _ => return Err(invalid()),
+            }
+        }
+
+        Ok(Some(checkpoint))
+    }
+
+    /// Saves the checkpoint at `path`, through a temporary file renamed over it so it is never left half written.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let Progress {
+            rows,
+            inserted,
+            offset,
+            state,
+            start,
+        } = self.progress;
+        let text = format!(
+            "table = {}\nrows = {}\ninserted = {}\noffset = {}\nstate = {}\nstart = {}\n",
+            self.table, rows, inserted, offset, state, start
+        );
+
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+        fs::write(&temp, text)?;
+        fs::rename(&temp, path)
+    }
+}
+
+/// Error of the checkpoints of a run.
+#[derive(Debug)]
+pub enum CheckpointError {
+    /// The checkpoint file could not be read or written.
+    Io(io::Error),
+    /// A line of the checkpoint file is not of the form `key = value`.
+    Invalid { path: PathBuf, line: usize },
+    /// The run is resumed, but no checkpoint was saved.
+    Missing { path: PathBuf },
+    /// The run is resumed from the checkpoint of a run into another table.
+    OtherTable {
+        path: PathBuf,
+        /// Table of the checkpoint.
+        table: String,
+        /// Table of the resumed run.
+        expected: String,
+    },
+    /// The run was interrupted, after saving a checkpoint to resume it from.
+    Interrupted { path: PathBuf, inserted: u64 },
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckpointError::Io(err) => write!(f, "could not save checkpoint: {}", err),
+            CheckpointError::Invalid { path, line } => {
+                write!(
+                    f,
+                    "invalid checkpoint at {}:{}, expected `key = value`",
+                    path.display(),
+                    line
+                )
+            }
+            CheckpointError::Missing { path } => {
+                write!(f, "no checkpoint to resume from at {}", path.display())
+            }
+            CheckpointError::OtherTable {
+                path,
+                table,
+                expected,
+            } => write!(
+                f,
+                "checkpoint at {} is of table `{}`, not `{}`",
+                path.display(),
+                table,
+                expected
+            ),
+            CheckpointError::Interrupted { path, inserted } => write!(
+                f,
+                "interrupted after inserting {} rows, resume from the checkpoint saved at {}",
+                inserted,
+                path.display()
+            ),
+        }
+    }
+}
+
+impl error::Error for CheckpointError {}
+
+impl From<io::Error> for CheckpointError {
+    fn from(err: io::Error) -> Self {
+        CheckpointError::Io(err)
+    }
+}
+
+/// Progress of a run, saved every [`Checkpointing::every`] committed batches.
+///
+/// Runs without checkpointing keep track of their progress without saving it.
+#[derive(Debug)]
+pub struct Checkpointer {
+    checkpointing: Option<Checkpointing>,
+    checkpoint: Checkpoint,
+    resumed: bool,
+    /// Batches committed since the run started or resumed.
+    batches: usize,
+}
+
+impl Checkpointer {
+    /// Starts keeping track of a run into `table`, reading the checkpoint it resumes from.
+    pub fn start(
+        checkpointing: Option<&Checkpointing>,
+        table: &str,
+    ) -> Result<Self, CheckpointError> {
+        let mut checkpoint = Checkpoint {
+            table: table.to_string(),
+            progress: Progress::default(),
+        };
+        let resumed = match checkpointing.filter(|checkpointing| checkpointing.resume) {
+            Some(checkpointing) => {
+                let path = &checkpointing.path;
+                let saved = Checkpoint::read(path)?
+                    .ok_or_else(|| CheckpointError::Missing { path: path.clone() })?;
+                if saved.table != table {
+                    return Err(CheckpointError::OtherTable {
+                        path: path.clone(),
+                        table: saved.table,
+                        expected: table.to_string(),
+                    });
+                }
+                checkpoint = saved;
+                true
+            }
+            None => false,
+        };
+
+        Ok(Checkpointer {
+            checkpointing: checkpointing.cloned(),
+            checkpoint,
+            resumed,
+            batches: 0,
+        })
+    }
+
+    /// Returns the progress saved by the interrupted run, when resuming one.
+    pub fn resumed(&self) -> Option<Progress> {
+        self.resumed.then_some(self.checkpoint.progress)
+    }
+
+    /// Returns the progress as of the last committed batch.
+    pub fn progress(&self) -> Progress {
+        self.checkpoint.progress
+    }
+
+    /// Returns whether the next batch may already have been inserted by the interrupted run.
+    ///
+    /// Interrupted runs may commit up to `every - 1` batches after their last checkpoint, which are inserted
+    /// again by the resumed run, skipping the rows they already inserted.
+    pub fn is_replaying(&self) -> bool {
+        match &self.checkpointing {
+            Some(checkpointing) => self.resumed && self.batches + 1 < checkpointing.every,
+            None => false,
+        }
+    }
+
+    /// Records the `progress` of a committed batch, saving it when due.
+    ///
+    /// Fails with [`CheckpointError::Interrupted`] once the run is interrupted, after saving it.
+    pub fn commit(&mut self, progress: Progress) -> Result<(), CheckpointError> {
+        self.checkpoint.progress = progress;
+        self.batches += 1;
+        let checkpointing = match &self.checkpointing {
+            Some(checkpointing) => checkpointing,
+            None => return Ok(()),
+        };
+
+        let interrupted = checkpointing.is_interrupted();
+        if interrupted || self.batches.is_multiple_of(checkpointing.every.max(1)) {
+            self.checkpoint.write(&checkpointing.path)?;
+        }
+        if interrupted {
+            return Err(CheckpointError::Interrupted {
+                path: checkpointing.path.clone(),
+                inserted: progress.inserted,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Saves the progress as of the last committed batch, such as when the next batch failed.
+    pub fn save(&self) -> Result<(), CheckpointError> {
+        if let Some(checkpointing) = &self.checkpointing {
+            self.checkpoint.write(&checkpointing.path)?;
+        }
+
+        Ok(())
+    }
+
+    /// Removes the checkpoint of the completed run, which cannot be resumed anymore.
+    pub fn finish(self) -> Result<(), CheckpointError> {
+        let checkpointing = match &self.checkpointing {
+            Some(checkpointing) => checkpointing,
+            None => return Ok(()),
+        };
+        match fs::remove_file(&checkpointing.path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const ROWS: u64 = 100;
+    const BATCH_SIZE: u64 = 7;
+
+    fn checkpointing(name: &str, every: usize) -> Checkpointing {
+        let path =
+            std::env::temp_dir().join(format!("awto-checkpoint-{}-{}", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        Checkpointing {
+            every,
+            ..Checkpointing::new(path)
+        }
+    }
+
+    fn next(state: &mut u64) -> u64 {
+        *state = state
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1);
+        *state >> 33
+    }
+
+    /// Generates `ROWS` random rows into `table` in batches, like `generate_rows`, committing `kill_after` batches
+    /// at most before the run is killed without saving a checkpoint.
+    fn generate(
+        table: &mut Vec<u64>,
+        checkpointing: &Checkpointing,
+        kill_after: Option<usize>,
+    ) -> Result<bool, CheckpointError> {
+        let mut checkpointer = Checkpointer::start(Some(checkpointing), "product")?;
+        let (start, mut inserted, mut state) = match checkpointer.resumed() {
+            Some(progress) => (progress.start, progress.inserted, progress.state),
+            None => (table.len() as u64, 0, 42),
+        };
+        // Batches committed after the checkpoint are generated again without inserting them
+        for _ in start + inserted..table.len() as u64 {
+            next(&mut state);
+            inserted += 1;
+        }
+
+        let mut batches = 0;
+        while inserted < ROWS {
+            if kill_after == Some(batches) {
+                return Ok(false);
+            }
+            let batch = (ROWS - inserted).min(BATCH_SIZE);
+            table.extend((0..batch).map(|_| next(&mut state)));
+            inserted += batch;
+            batches += 1;
+            checkpointer.commit(Progress {
+                rows: inserted,
+                inserted,
+                state,
+                start,
+                ..Default::default()
+            })?;
+        }
+        checkpointer.finish()?;
+
+        Ok(true)
+    }
+
+    #[test]
+    fn resume_after_kill() {
+        let mut expected = vec![1, 2];
+        let uninterrupted = checkpointing("uninterrupted", 3);
+        assert!(generate(&mut expected, &uninterrupted, None).unwrap());
+        assert_eq!(expected.len() as u64, ROWS + 2);
+        assert!(!uninterrupted.path.exists());
+
+        // Killed after 5 batches, of which the last 2 were committed after the checkpoint of the third
+        let checkpointing = checkpointing("killed", 3);
+        let mut table = vec![1, 2];
+        assert!(!generate(&mut table, &checkpointing, Some(5)).unwrap());
+        let saved = Checkpoint::read(&checkpointing.path).unwrap().unwrap();
+        assert_eq!(saved.progress.inserted, 3 * BATCH_SIZE);
+        assert_eq!(table.len() as u64, 2 + 5 * BATCH_SIZE);
+
+        let resumed = Checkpointing {
+            resume: true,
+            ..checkpointing.clone()
+        };
+        assert!(generate(&mut table, &resumed, None).unwrap());
+        assert_eq!(table, expected);
+        assert!(!checkpointing.path.exists());
+    }
+
+    #[test]
+    fn resume_after_interruption() {
+        let mut expected = Vec::new();
+        assert!(generate(&mut expected, &checkpointing("expected", 4), None).unwrap());
+
+        let checkpointing = checkpointing("interrupted", 4);
+        checkpointing.interrupt();
+        let mut table = Vec::new();
+        let err = generate(&mut table, &checkpointing, None).unwrap_err();
+        assert!(matches!(
+            err,
+            CheckpointError::Interrupted {
+                inserted: BATCH_SIZE,
+                ..
+            }
+        ));
+        // The checkpoint is flushed on interruption, even though it was not due
+        let saved = Checkpoint::read(&checkpointing.path).unwrap().unwrap();
+        assert_eq!(saved.progress.inserted, BATCH_SIZE);
+
+        let resumed = Checkpointing {
+            resume: true,
+            interrupted: Arc::default(),
+            ..checkpointing
+        };
+        assert!(generate(&mut table, &resumed, None).unwrap());
+        assert_eq!(table, expected);
+    }
+
+    #[test]
+    fn resume_errors() {
+        let checkpointing = Checkpointing {
+            resume: true,
+            ..checkpointing("errors", 1)
+        };
+        let err = Checkpointer::start(Some(&checkpointing), "product").unwrap_err();
+        assert!(matches!(err, CheckpointError::Missing { .. }));
+
+        let checkpoint = Checkpoint {
+            table: "category".to_string(),
+            progress: Progress {
+                rows: 12,
+                inserted: 10,
+                offset: 345,
+                ..Default::default()
+            },
+        };
+        checkpoint.write(&checkpointing.path).unwrap();
+        assert_eq!(
+            Checkpoint::read(&checkpointing.path).unwrap(),
+            Some(checkpoint)
+        );
+        let err = Checkpointer::start(Some(&checkpointing), "product").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "checkpoint at {} is of table `category`, not `product`",
+                checkpointing.path.display()
+            )
+        );
+
+        fs::write(&checkpointing.path, "table = product\nrows: 12\n").unwrap();
+        let err = Checkpointer::start(Some(&checkpointing), "product").unwrap_err();
+        assert!(matches!(err, CheckpointError::Invalid { line: 2, .. }));
+        fs::remove_file(&checkpointing.path).unwrap();
+    }
+}
@@ -1,6 +1,7 @@
-use std::{fmt, str};
+use std::{borrow::Cow, fmt, str};
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DatabaseType {
     SmallInt,
     Integer,
@@ -18,6 +19,10 @@ pub enum DatabaseType {
     Timetz,
     Bool,
     Uuid,
+    /// Binary JSON, used for `HashMap<String, T>` fields.
+    Jsonb,
+    /// Map of strings of the `hstore` extension, used for `HashMap<String, String>` fields with [`MapStorage::Hstore`].
+    Hstore,
 }
 
 pub struct DatabaseTypeFromStrError;
@@ -43,6 +48,8 @@ impl str::FromStr for DatabaseType {
             "time with time zone" | "timetz" => Self::Timetz,
             "boolean" | "bool" => Self::Bool,
             "uuid" => Self::Uuid,
+            "jsonb" => Self::Jsonb,
+            "hstore" => Self::Hstore,
             _ => return Err(DatabaseTypeFromStrError),
         };
         Ok(database_type)
@@ -70,11 +77,14 @@ impl fmt::Display for DatabaseType {
             Self::Timetz => write!(f, "time with time zone"),
             Self::Bool => write!(f, "boolean"),
             Self::Uuid => write!(f, "uuid"),
+            Self::Jsonb => write!(f, "jsonb"),
+            Self::Hstore => write!(f, "hstore"),
         }
     }
 }
 
 #[derive(Clone, Debug, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DatabaseDefault {
     Bool(bool),
     Float(i64),
@@ -117,12 +127,13 @@ impl fmt::Display for DatabaseDefault {
             DatabaseDefault::Float(val) => write!(f, "{}", val),
             DatabaseDefault::Int(val) => write!(f, "{}", val),
             DatabaseDefault::Raw(val) => write!(f, "{}", val),
-            DatabaseDefault::String(val) => write!(f, "\"{}\"", val),
+            DatabaseDefault::String(val) => write!(f, "'{}'", val.replace('\'', "''")),
         }
     }
 }
 
 #[derive(Clone, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DatabaseColumn {
     pub name: String,
     pub ty: DatabaseType,
@@ -132,16 +143,828 @@ pub struct DatabaseColumn {
     pub constraint: Option<String>,
     pub primary_key: bool,
     pub references: Option<(String, String)>,
+    /// Whether syncs create a foreign key constraint for [`references`](Self::references),
+    /// set with `#[awto(references = (Model, "column", constraint = true))]`.
+    ///
+    /// When unset, references get a constraint unless they reference an [external](DatabaseTable::external) table.
+    pub reference_constraint: Option<bool>,
+    /// Whether [`references`](Self::references) is a table of another database,
+    /// set with `#[awto(references = (Model, "column", cross_database))]`.
+    ///
+    /// Such references never get a foreign key constraint, and the referenced row is found with a connection to the other database.
+    pub cross_database: bool,
+    /// Locales of a localized column, set with `#[awto(localized(locales = "..."))]`.
+    ///
+    /// The column itself holds the value of the first locale, which is the default locale.
+    pub locales: Vec<String>,
+    /// Sql expression of a generated column, set with `#[awto(computed = "...", stored)]`.
+    ///
+    /// Computed columns are read-only, so they are never written by generated inserts and updates.
+    pub computed: Option<String>,
+    /// Normalizations applied to the values of the column before they are saved, in order,
+    /// set with `#[awto(normalize = "...")]` and `#[awto(normalize_fn = "...")]`.
+    pub normalize: Vec<Normalizer>,
+    /// Provider of the random values of a text column inserted by `awto db generate`, set with `#[awto(fake = "...")]`.
+    pub fake: Option<Fake>,
+    /// Sql expression setting the rows which do not fit the column when a sync narrows it,
+    /// set with `#[awto(column(backfill = "..."))]`.
+    ///
+    /// The expression is applied to those rows only, before the column is altered.
+    pub backfill: Option<String>,
+}
+
+impl DatabaseColumn {
+    /// Returns the name of the column holding the value of `locale` when localized with [`Localization::Columns`].
+    pub fn locale_column_name(&self, locale: &str) -> String {
+        derived_name(&[&self.name], &locale.to_lowercase().replace('-', "_"))
+    }
+}
+
+/// A normalization of the values of a text column applied before they are saved.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Normalizer {
+    /// Converts the value to lowercase, `lowercase`.
+    Lowercase,
+    /// Removes leading and trailing whitespace, `trim`.
+    Trim,
+    /// Slugifies the value, deriving it from the named column when it is unset or empty, `slugify-from(title)`.
+    SlugifyFrom(String),
+    /// Calls a `fn(&str) -> String` of the schema crate, set with `#[awto(normalize_fn = "crate::normalize::phone")]`.
+    Function(String),
+}
+
+impl Normalizer {
+    /// Returns `value` normalized by a builtin normalizer, or `None` for [`Normalizer::Function`].
+    pub fn apply(&self, value: &str) -> Option<String> {
+        match self {
+            Normalizer::Lowercase => Some(value.to_lowercase()),
+            Normalizer::Trim => Some(value.trim().to_string()),
+            Normalizer::SlugifyFrom(_) => Some(slugify(value)),
+            Normalizer::Function(_) => None,
+        }
+    }
+}
+
+/// Formats the normalizer as written in `#[awto(normalize = "...")]`, or the path of its function.
+impl fmt::Display for Normalizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Normalizer::Lowercase => write!(f, "lowercase"),
+            Normalizer::Trim => write!(f, "trim"),
+            Normalizer::SlugifyFrom(column) => write!(f, "slugify-from({})", column),
+            Normalizer::Function(path) => write!(f, "{}", path),
+        }
+    }
+}
+
+/// A provider of realistic random text, such as names or email addresses, used by `awto db generate`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Fake {
+    Name,
+    FirstName,
+    LastName,
+    Email,
+    Username,
+    Phone,
+    Company,
+    City,
+    Country,
+    Url,
+    Word,
+    Sentence,
+    Paragraph,
+}
+
+impl Fake {
+    pub const ALL: [Fake; 13] = [
+        Fake::Name,
+        Fake::FirstName,
+        Fake::LastName,
+        Fake::Email,
+        Fake::Username,
+        Fake::Phone,
+        Fake::Company,
+        Fake::City,
+        Fake::Country,
+        Fake::Url,
+        Fake::Word,
+        Fake::Sentence,
+        Fake::Paragraph,
+    ];
+
+    /// Returns the provider of the values of a text column named `column` without `#[awto(fake = "...")]`,
+    /// such as [`Fake::Email`] for `email`, or `None` when its name is not the name of a provider.
+    pub fn of_column(column: &str) -> Option<Fake> {
+        column.parse().ok()
+    }
+}
+
+pub struct FakeFromStrError;
+
+impl str::FromStr for Fake {
+    type Err = FakeFromStrError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Fake::ALL
+            .iter()
+            .copied()
+            .find(|fake| fake.to_string() == s)
+            .ok_or(FakeFromStrError)
+    }
+}
+
+/// Formats the provider as written in `#[awto(fake = "...")]`, such as `first_name`.
+impl fmt::Display for Fake {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Fake::Name => "name",
+            Fake::FirstName => "first_name",
+            Fake::LastName => "last_name",
+            Fake::Email => "email",
+            Fake::Username => "username",
+            Fake::Phone => "phone",
+            Fake::Company => "company",
+            Fake::City => "city",
+            Fake::Country => "country",
+            Fake::Url => "url",
+            Fake::Word => "word",
+            Fake::Sentence => "sentence",
+            Fake::Paragraph => "paragraph",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Returns `value` as a lowercase slug of its alphanumeric characters separated by dashes,
+/// such as `rust-in-2024` for `Rust in 2024!`.
+pub fn slugify(value: &str) -> String {
+    let mut slug = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    slug.trim_end_matches('-').to_string()
+}
+
+/// Postgres keywords which cannot be used as table or column names without quoting.
+const SQL_RESERVED_WORDS: &[&str] = &[
+    "all",
+    "analyse",
+    "analyze",
+    "and",
+    "any",
+    "array",
+    "as",
+    "asc",
+    "asymmetric",
+    "authorization",
+    "between",
+    "bigint",
+    "binary",
+    "bit",
+    "boolean",
+    "both",
+    "case",
+    "cast",
+    "char",
+    "character",
+    "check",
+    "coalesce",
+    "collate",
+    "collation",
+    "column",
+    "concurrently",
+    "constraint",
+    "create",
+    "cross",
+    "current_catalog",
+    "current_date",
+    "current_role",
+    "current_schema",
+    "current_time",
+    "current_timestamp",
+    "current_user",
+    "dec",
+    "decimal",
+    "default",
+    "deferrable",
+    "desc",
+    "distinct",
+    "do",
+    "else",
+    "end",
+    "except",
+    "exists",
+    "extract",
+    "false",
+    "fetch",
+    "float",
+    "for",
+    "foreign",
+    "freeze",
+    "from",
+    "full",
+    "grant",
+    "greatest",
+    "group",
+    "grouping",
+    "having",
+    "ilike",
+    "in",
+    "initially",
+    "inner",
+    "inout",
+    "int",
+    "integer",
+    "intersect",
+    "interval",
+    "into",
+    "is",
+    "isnull",
+    "join",
+    "lateral",
+    "leading",
+    "least",
+    "left",
+    "like",
+    "limit",
+    "localtime",
+    "localtimestamp",
+    "national",
+    "natural",
+    "nchar",
+    "none",
+    "normalize",
+    "not",
+    "notnull",
+    "null",
+    "nullif",
+    "numeric",
+    "offset",
+    "on",
+    "only",
+    "or",
+    "order",
+    "out",
+    "outer",
+    "overlaps",
+    "overlay",
+    "placing",
+    "position",
+    "precision",
+    "primary",
+    "real",
+    "references",
+    "returning",
+    "right",
+    "row",
+    "select",
+    "session_user",
+    "setof",
+    "similar",
+    "smallint",
+    "some",
+    "substring",
+    "symmetric",
+    "table",
+    "tablesample",
+    "then",
+    "time",
+    "timestamp",
+    "to",
+    "trailing",
+    "treat",
+    "trim",
+    "true",
+    "union",
+    "unique",
+    "user",
+    "using",
+    "values",
+    "varchar",
+    "variadic",
+    "verbose",
+    "when",
+    "where",
+    "window",
+    "with",
+    "xmlattributes",
+    "xmlconcat",
+    "xmlelement",
+    "xmlexists",
+    "xmlforest",
+    "xmlnamespaces",
+    "xmlparse",
+    "xmlpi",
+    "xmlroot",
+    "xmlserialize",
+    "xmltable",
+];
+
+/// Returns `name` as a sql identifier, double quoting reserved words such as `"order"` and names
+/// Postgres would otherwise fold or reject.
+pub fn quote_ident(name: &str) -> Cow<'_, str> {
+    let plain = name.starts_with(|c: char| c.is_ascii_lowercase() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '$');
+    if plain && !SQL_RESERVED_WORDS.contains(&name) {
+        Cow::Borrowed(name)
+    } else {
+        Cow::Owned(format!("\"{}\"", name.replace('"', "\"\"")))
+    }
+}
+
+/// Longest identifier Postgres keeps, in bytes, silently truncating longer ones.
+pub const MAX_IDENTIFIER_LEN: usize = 63;
+
+/// Length of the hash inserted in shortened names, in hexadecimal digits.
+const DERIVED_HASH_LEN: usize = 8;
+
+/// Returns the name awto derives from the names of the schema for a table, column, constraint, index
+/// or trigger, such as `product_category_id_fkey` for `parts` `["product", "category_id"]` and `suffix` `fkey`.
+///
+/// Names longer than [`MAX_IDENTIFIER_LEN`] are shortened instead of being truncated by Postgres, which would
+/// make distinct names collide: every part is cut to the same length, and a hash of the full name is inserted
+/// before the suffix, such as `customer_subscription_invoic_tenant_id_created_at_cebeee85_fkey`.
+/// Existing constraints and indexes are named this way, so the shortening must never change.
+pub fn derived_name(parts: &[&str], suffix: &str) -> String {
+    let name = format!("{}_{}", parts.join("_"), suffix);
+    if name.len() <= MAX_IDENTIFIER_LEN {
+        return name;
+    }
+
+    // FNV-1a, which unlike the std hasher is stable across Rust versions
+    let hash = name.bytes().fold(0x811c9dc5u32, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x01000193)
+    });
+
+    format!(
+        "{}{:08x}_{}",
+        derived_name_prefix(parts, suffix.len()),
+        hash,
+        suffix
+    )
+}
+
+/// Returns the start of the names [derived](derived_name) from `parts` with a suffix of `suffix_len` bytes,
+/// which is `parts` joined by underscores, or their shortened parts when the names are too long.
+pub fn derived_name_prefix(parts: &[&str], suffix_len: usize) -> String {
+    let joined = parts.join("_");
+    if joined.len() + 1 + suffix_len <= MAX_IDENTIFIER_LEN {
+        return format!("{}_", joined);
+    }
+
+    // Longest length of the parts keeping the shortened parts, the hash and the suffix within the limit
+    let separators = parts.len().saturating_sub(1);
+    let budget = MAX_IDENTIFIER_LEN.saturating_sub(DERIVED_HASH_LEN + 2 + suffix_len + separators);
+    let part_len = (0..=budget)
+        .rev()
+        .find(|len| parts.iter().map(|part| part.len().min(*len)).sum::<usize>() <= budget)
+        .unwrap_or(0);
+
+    parts
+        .iter()
+        .map(|part| {
+            let mut end = part.len().min(part_len);
+            while !part.is_char_boundary(end) {
+                end -= 1;
+            }
+            part[..end].trim_end_matches('_')
+        })
+        .filter(|part| !part.is_empty())
+        .fold(String::new(), |prefix, part| prefix + part + "_")
+}
+
+/// Strategy of storing the values of localized columns in locales other than the default locale.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Localization {
+    /// A nullable `<column>_<locale>` column per locale.
+    #[default]
+    Columns,
+    /// Rows of a `<table>_translations` table, unique by row, locale and field.
+    Table,
+}
+
+/// Copying of the previous versions of the rows of tables with `#[awto(temporal)]` into their history table.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Temporal {
+    /// A trigger copying the row on every update and delete, whichever client changes it.
+    #[default]
+    Trigger,
+    /// The generated queries copying the row before changing it, for databases where triggers cannot be created.
+    Portable,
+}
+
+/// Storage of the columns of `HashMap<String, String>` fields.
+///
+/// Maps of other values are always stored as [`DatabaseType::Jsonb`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MapStorage {
+    /// A `jsonb` object, read and written by the generated entities.
+    #[default]
+    Jsonb,
+    /// A map of the `hstore` extension, which sea-orm cannot decode, for tables read by other services.
+    Hstore,
+}
+
+/// Visibility of an item re-exported by the generated database package.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Visibility {
+    /// `pub`, usable by the application and the other generated packages.
+    #[default]
+    Public,
+    /// `pub(crate)`, only usable by the generated code and extensions of the database package.
+    Crate,
+}
+
+impl Visibility {
+    /// Parses a visibility written as in Rust, `pub` or `pub(crate)`.
+    pub fn parse(visibility: &str) -> Option<Self> {
+        match visibility.replace(' ', "").as_str() {
+            "pub" => Some(Visibility::Public),
+            "pub(crate)" => Some(Visibility::Crate),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Visibility::Public => "pub",
+            Visibility::Crate => "pub(crate)",
+        }
+    }
+}
+
+/// Visibility of the items of the entity of a table, set with `#[awto(visibility(...))]`
+/// or configured for every table of the app when unset.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EntityVisibility {
+    /// Visibility of the `Entity`, `Model`, `Column`, `PrimaryKey` and `Relation` of the table.
+    pub entity: Option<Visibility>,
+    /// Visibility of the `ActiveModel` of the table.
+    pub active_model: Option<Visibility>,
+}
+
+impl EntityVisibility {
+    pub fn entity(&self) -> Visibility {
+        self.entity.unwrap_or_default()
+    }
+
+    pub fn active_model(&self) -> Visibility {
+        self.active_model.unwrap_or_default()
+    }
+}
+
+/// A full-text search index over text columns, set with `#[awto(fulltext(fields(...), language = "..."))]`.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DatabaseFulltext {
+    pub columns: Vec<String>,
+    /// Postgres text search configuration, such as `english`.
+    pub language: String,
+}
+
+impl DatabaseFulltext {
+    /// Returns the sql expression of the `tsvector` searched by the index.
+    pub fn vector_expression(&self) -> String {
+        let columns: Vec<_> = self
+            .columns
+            .iter()
+            .map(|column| format!("coalesce({}, '')", quote_ident(column)))
+            .collect();
+
+        format!(
+            "to_tsvector('{}'::regconfig, {})",
+            self.language,
+            columns.join(" || ' ' || ")
+        )
+    }
+}
+
+/// Range partitioning of a table by a date or timestamp column, set with
+/// `#[awto(partition(by = "range", column = created_at, interval = "month"))]`.
+///
+/// The primary key of a partitioned table includes its partition column, as postgres requires of unique constraints.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DatabasePartition {
+    pub column: String,
+    /// Range of the values of the partition column held by each partition.
+    pub interval: PartitionInterval,
+}
+
+/// Range of the values held by each partition of a [`DatabasePartition`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PartitionInterval {
+    Day,
+    Month,
+    Year,
+}
+
+impl PartitionInterval {
+    /// Returns the name of the interval in `#[awto(partition(interval = "..."))]`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PartitionInterval::Day => "day",
+            PartitionInterval::Month => "month",
+            PartitionInterval::Year => "year",
+        }
+    }
+}
+
+/// A row-level security policy, set with `#[awto(rls(policy = "...", using = "..."))]`.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DatabasePolicy {
+    pub name: String,
+    /// Sql expression rows must satisfy to be visible, such as `tenant_id = current_setting('app.tenant_id')::uuid`.
+    pub using: String,
+}
+
+/// Columns of a table fetched into a struct of their own, set with `#[awto(projection(name = "...", fields(...)))]`.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DatabaseProjection {
+    /// Name of the generated struct, such as `ProductSummary`.
+    pub name: String,
+    /// Columns selected into the struct, in the order of its fields.
+    pub columns: Vec<String>,
+}
+
+/// A named filter of the rows of a table, set with `#[awto(scope(name = "..."))]`,
+/// or `#[awto(scope(name(param: Type, ...) = "..."))]` when it has parameters.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DatabaseScope {
+    pub name: String,
+    /// Parameters of the scope, referenced in its condition as `:name`.
+    pub params: Vec<DatabaseScopeParam>,
+    /// Sql condition rows of the scope satisfy, such as `created_at > now() - interval '1 day' * :days`.
+    ///
+    /// It is parsed into a query of the columns of the table when generating the database package.
+    pub condition: String,
+}
+
+/// A parameter of a [`DatabaseScope`].
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DatabaseScopeParam {
+    pub name: String,
+    /// Rust type of the parameter, such as `i64`.
+    pub ty: String,
+}
+
+/// Scopes required to run operations on a table, set with
+/// `#[awto(authorize(read = "...", write = "...", delete = "..."))]`.
+///
+/// The scope [`PUBLIC_SCOPE`] allows an operation without any check.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Authorization {
+    pub read: Option<String>,
+    /// Scope of creating and updating rows.
+    pub write: Option<String>,
+    /// Scope of deleting rows, which is the [`write`](Self::write) scope when unset.
+    pub delete: Option<String>,
+}
+
+/// Scope allowing an operation without checking the request, such as `read = "public"`.
+pub const PUBLIC_SCOPE: &str = "public";
+
+/// An operation on the rows of a table which is authorized.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Operation {
+    Read,
+    Write,
+    Delete,
+}
+
+/// Authorization of the operations of tables without a scope for them, configured for every table of the app.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DefaultAuthorization {
+    /// Operations are allowed without any check.
+    #[default]
+    Allow,
+    /// Operations are always denied.
+    Deny,
+}
+
+/// Scope required to run an operation on a table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RequiredScope<'a> {
+    /// The operation is allowed without any check.
+    Public,
+    /// The operation is allowed when the request is granted the scope.
+    Scope(&'a str),
+    /// The operation is always denied.
+    Denied,
 }
 
 pub trait IntoDatabaseTable {
     fn database_table() -> DatabaseTable;
 }
 
+/// Name of the database which tables belong to when no `#[awto(database = "...")]` is given.
+pub const DEFAULT_DATABASE: &str = "main";
+
 #[derive(Clone, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DatabaseTable {
     pub name: String,
     pub columns: Vec<DatabaseColumn>,
+    /// Name of the database this table belongs to, set with `#[awto(database = "...")]`.
+    pub database: Option<String>,
+    /// Column ordering paginated queries together with the primary key, set with `#[awto(order_by = column)]`.
+    pub order_by: Option<String>,
+    /// Full-text search index of the table.
+    pub fulltext: Option<DatabaseFulltext>,
+    /// Range partitioning of the table, set with `#[awto(partition(...))]`.
+    pub partition: Option<DatabasePartition>,
+    /// Row-level security policies of the table.
+    ///
+    /// Row-level security is enabled on tables with at least one policy.
+    pub policies: Vec<DatabasePolicy>,
+    /// Projections of the table fetched by list queries instead of whole rows.
+    pub projections: Vec<DatabaseProjection>,
+    /// Named filters of the rows of the table, generated as queries of its entity.
+    pub scopes: Vec<DatabaseScope>,
+    /// Whether the table is read-only, set with `#[awto(readonly)]`.
+    ///
+    /// Generated REST routers only expose `GET` endpoints for read-only tables, such as views.
+    pub readonly: bool,
+    /// Whether the table is managed outside of awto, such as by another service, set with `#[awto(external)]`.
+    ///
+    /// External tables are read-only, and syncs never create or alter them.
+    pub external: bool,
+    /// Whether the table is left out of generated REST routers, set with `#[awto(skip_rest)]`.
+    pub skip_rest: bool,
+    /// Unique column used as the conflict target of upserts instead of the primary key,
+    /// set with `#[awto(upsert_on = column)]`.
+    pub upsert_on: Option<String>,
+    /// Whether updates of the table are guarded by its `version` column, set with `#[awto(versioned)]`.
+    ///
+    /// Generated updates only apply when the stored version matches the version being updated,
+    /// and increment it.
+    pub versioned: bool,
+    /// Whether changes of the table are recorded in the outbox, set with `#[awto(events)]`.
+    pub events: bool,
+    /// Whether changes of the table are sent as Postgres notifications on its `{table}_changes` channel,
+    /// set with `#[awto(notify)]`.
+    pub notify: bool,
+    /// Whether the changes of the table are streamed by subscriptions of the GraphQL subscription root
+    /// generated behind the `graphql` feature, set with `#[awto(graphql(subscriptions))]`, which implies `notify`.
+    pub subscriptions: bool,
+    /// Whether creates of the table can be retried with an idempotency key, which replays the row created by
+    /// the first request instead of inserting another, set with `#[awto(idempotent_create)]`.
+    pub idempotent_create: bool,
+    /// Tracking of the previous versions of the rows of the table in its `{table}_history` table,
+    /// set with `#[awto(temporal)]` and configured for every table of the app.
+    pub temporal: Option<Temporal>,
+    /// Strategy of storing the values of localized columns, configured for every table of the app.
+    pub localization: Localization,
+    /// Scopes required to run operations on the table.
+    pub authorization: Option<Authorization>,
+    /// Authorization of the operations without a scope, configured for every table of the app.
+    pub default_authorization: DefaultAuthorization,
+    /// Attributes added verbatim to the generated entity `Model` struct, set with `#[awto(entity_attrs("..."))]`.
+    pub entity_attrs: Vec<String>,
+    /// Paths of the derive macros added to the generated entity `Model` struct, configured for every table of the app.
+    pub entity_derives: Vec<String>,
+    /// Plural of the table name in generated REST paths, resolved with the plurals configured for the app,
+    /// or derived from the name when unset.
+    pub plural: Option<String>,
+    /// Visibility of the items of the entity of the table re-exported by the generated database package.
+    pub visibility: EntityVisibility,
+}
+
+impl DatabaseTable {
+    /// Returns the name of the database this table belongs to.
+    pub fn database(&self) -> &str {
+        self.database.as_deref().unwrap_or(DEFAULT_DATABASE)
+    }
+
+    /// Returns the columns which can be changed by a partial update.
+    ///
+    /// The primary key, the `created_at` and `updated_at` timestamps, and the `version` of versioned tables
+    /// are managed by generated code instead, and computed columns by the database.
+    pub fn patch_columns(&self) -> Vec<&DatabaseColumn> {
+        self.columns
+            .iter()
+            .filter(|column| {
+                !column.primary_key
+                    && column.name != "created_at"
+                    && column.name != "updated_at"
+                    && !(self.versioned && column.name == "version")
+                    && column.computed.is_none()
+            })
+            .collect()
+    }
+
+    /// Returns the localized columns of the table.
+    pub fn localized_columns(&self) -> Vec<&DatabaseColumn> {
+        self.columns
+            .iter()
+            .filter(|column| !column.locales.is_empty())
+            .collect()
+    }
+
+    /// Returns the columns referencing rows of the table itself, such as the `parent_id` of a tree.
+    pub fn self_references(&self) -> Vec<&DatabaseColumn> {
+        self.columns
+            .iter()
+            .filter(|column| matches!(&column.references, Some((table, _)) if table == &self.name))
+            .collect()
+    }
+
+    /// Returns the columns of the primary key constraint of the table,
+    /// which includes the partition column of partitioned tables.
+    pub fn primary_key_columns(&self) -> Vec<&str> {
+        let mut columns: Vec<&str> = self
+            .columns
+            .iter()
+            .filter(|column| column.primary_key)
+            .map(|column| column.name.as_str())
+            .collect();
+        if let Some(partition) = &self.partition {
+            if !columns.contains(&partition.column.as_str()) {
+                columns.push(&partition.column);
+            }
+        }
+
+        columns
+    }
+
+    /// Returns the primary key column of the table, which is `id` unless a `String` field is marked `#[awto(primary_key)]`.
+    pub fn primary_key(&self) -> Option<&DatabaseColumn> {
+        self.columns.iter().find(|column| column.primary_key)
+    }
+
+    /// Returns whether the primary key of the table is a `String` the application assigns, rather than a generated `Uuid`.
+    pub fn has_string_key(&self) -> bool {
+        self.primary_key()
+            .is_some_and(|column| matches!(column.ty, DatabaseType::Text(_)))
+    }
+
+    /// Returns the name of the table holding the previous versions of the rows of tables with `#[awto(temporal)]`.
+    pub fn history_table_name(&self) -> String {
+        derived_name(&[&self.name], "history")
+    }
+
+    /// Returns the name of the table holding the translations of the localized columns with [`Localization::Table`].
+    pub fn translations_table_name(&self) -> String {
+        derived_name(&[&self.name], "translations")
+    }
+
+    /// Returns the scope required to run `operation` on the table, following the [default](Self::default_authorization)
+    /// when the table has no scope for it.
+    pub fn required_scope(&self, operation: Operation) -> RequiredScope<'_> {
+        let scope = self
+            .authorization
+            .as_ref()
+            .and_then(|authorization| match operation {
+                Operation::Read => authorization.read.as_deref(),
+                Operation::Write => authorization.write.as_deref(),
+                Operation::Delete => authorization
+                    .delete
+                    .as_deref()
+                    .or(authorization.write.as_deref()),
+            });
+
+        match (scope, self.default_authorization) {
+            (Some(PUBLIC_SCOPE), _) | (None, DefaultAuthorization::Allow) => RequiredScope::Public,
+            (Some(scope), _) => RequiredScope::Scope(scope),
+            (None, DefaultAuthorization::Deny) => RequiredScope::Denied,
+        }
+    }
+
+    /// Returns the column upserts conflict on, or `None` when the table cannot be upserted.
+    ///
+    /// Read-only tables cannot be upserted, nor can partitioned tables, whose primary key alone is not unique.
+    /// Other tables conflict on their primary key by default.
+    pub fn upsert_target(&self) -> Option<&str> {
+        if self.readonly || self.partition.is_some() {
+            return None;
+        }
+
+        self.upsert_on
+            .as_deref()
+            .or_else(|| self.primary_key().map(|column| column.name.as_str()))
+    }
 }
 
 #[cfg(test)]
@@ -154,6 +977,272 @@ mod test {
         assert_eq!(Product::database_table().name, "product");
     }
 
+    #[test]
+    fn table_database() {
+        assert_eq!(Product::database_table().database(), DEFAULT_DATABASE);
+        assert_eq!(Event::database_table().database(), "analytics");
+    }
+
+    #[test]
+    fn table_order_by() {
+        assert_eq!(
+            Product::database_table().order_by.as_deref(),
+            Some("created_at")
+        );
+        assert_eq!(Event::database_table().order_by, None);
+    }
+
+    #[test]
+    fn table_fulltext() {
+        let fulltext = Product::database_table().fulltext.unwrap();
+        assert_eq!(fulltext.columns, ["name", "description"]);
+        assert_eq!(fulltext.language, "english");
+        assert_eq!(
+            fulltext.vector_expression(),
+            "to_tsvector('english'::regconfig, coalesce(name, '') || ' ' || coalesce(description, ''))"
+        );
+        assert_eq!(Event::database_table().fulltext, None);
+    }
+
+    #[test]
+    fn table_policies() {
+        assert_eq!(
+            Event::database_table().policies,
+            [DatabasePolicy {
+                name: "recent_events".to_string(),
+                using: "created_at > now() - interval '90 days'".to_string(),
+            }]
+        );
+        assert!(Product::database_table().policies.is_empty());
+    }
+
+    #[test]
+    fn table_projections() {
+        let columns = |columns: &[&str]| columns.iter().map(|column| column.to_string()).collect();
+        assert_eq!(
+            Product::database_table().projections,
+            [
+                DatabaseProjection {
+                    name: "ProductSummary".to_string(),
+                    columns: columns(&["id", "created_at", "name", "price_cents"]),
+                },
+                DatabaseProjection {
+                    name: "ProductPrice".to_string(),
+                    columns: columns(&["id", "created_at", "price"]),
+                },
+            ]
+        );
+        assert!(Event::database_table().projections.is_empty());
+    }
+
+    #[test]
+    fn table_scopes() {
+        assert_eq!(
+            Product::database_table().scopes,
+            [
+                DatabaseScope {
+                    name: "live".to_string(),
+                    params: vec![],
+                    condition: "status = 'live'".to_string(),
+                },
+                DatabaseScope {
+                    name: "recent".to_string(),
+                    params: vec![DatabaseScopeParam {
+                        name: "days".to_string(),
+                        ty: "i64".to_string(),
+                    }],
+                    condition: "created_at > now() - interval '1 day' * :days".to_string(),
+                },
+            ]
+        );
+        assert!(Event::database_table().scopes.is_empty());
+    }
+
+    #[test]
+    fn table_rest_access() {
+        let product = Product::database_table();
+        assert!(!product.readonly);
+        assert!(!product.skip_rest);
+
+        let event = Event::database_table();
+        assert!(event.readonly);
+        assert!(!event.skip_rest);
+    }
+
+    #[test]
+    fn table_entity_attrs() {
+        assert_eq!(
+            Event::database_table().entity_attrs,
+            ["#[doc(alias = \"audit_event\")]"]
+        );
+        assert!(Product::database_table().entity_attrs.is_empty());
+        assert!(Product::database_table().entity_derives.is_empty());
+    }
+
+    #[test]
+    fn table_authorization() {
+        let product = Product::database_table();
+        assert_eq!(
+            product.required_scope(Operation::Read),
+            RequiredScope::Public
+        );
+        assert_eq!(
+            product.required_scope(Operation::Write),
+            RequiredScope::Scope("catalog:write")
+        );
+        assert_eq!(
+            product.required_scope(Operation::Delete),
+            RequiredScope::Scope("admin")
+        );
+
+        let event = Event::database_table();
+        assert_eq!(event.authorization, None);
+        assert_eq!(event.required_scope(Operation::Read), RequiredScope::Public);
+        let event = DatabaseTable {
+            authorization: Some(Authorization {
+                write: Some("events:write".to_string()),
+                ..Authorization::default()
+            }),
+            default_authorization: DefaultAuthorization::Deny,
+            ..event
+        };
+        assert_eq!(event.required_scope(Operation::Read), RequiredScope::Denied);
+        assert_eq!(
+            event.required_scope(Operation::Delete),
+            RequiredScope::Scope("events:write")
+        );
+    }
+
+    #[test]
+    fn table_self_references() {
+        let names = |table: DatabaseTable| -> Vec<String> {
+            table
+                .self_references()
+                .iter()
+                .map(|column| column.name.clone())
+                .collect()
+        };
+
+        assert_eq!(names(tree::Category::database_table()), ["parent_id"]);
+        // References of other tables are not self references
+        assert_eq!(names(tree::Comment::database_table()), ["reply_to_id"]);
+        assert_eq!(names(Employee::database_table()), ["manager_id"]);
+        assert!(names(Product::database_table()).is_empty());
+    }
+
+    #[test]
+    fn table_partition() {
+        let page_view = partitioned::PageView::database_table();
+        assert_eq!(
+            page_view.partition,
+            Some(DatabasePartition {
+                column: "created_at".to_string(),
+                interval: PartitionInterval::Month,
+            })
+        );
+        assert_eq!(page_view.primary_key_columns(), ["id", "created_at"]);
+        // The primary key alone is not unique across partitions
+        assert_eq!(page_view.upsert_target(), None);
+
+        assert_eq!(Product::database_table().partition, None);
+        assert_eq!(Product::database_table().primary_key_columns(), ["id"]);
+    }
+
+    #[test]
+    fn table_patch_columns() {
+        let product = Product::database_table();
+        let names: Vec<_> = product
+            .patch_columns()
+            .into_iter()
+            .map(|column| column.name.as_str())
+            .collect();
+        assert_eq!(names, ["name", "price", "description", "status"]);
+    }
+
+    #[test]
+    fn table_upsert_target() {
+        let product = Product::database_table();
+        assert_eq!(product.upsert_on, None);
+        assert_eq!(product.upsert_target(), Some("id"));
+
+        let product = DatabaseTable {
+            upsert_on: Some("name".to_string()),
+            ..product
+        };
+        assert_eq!(product.upsert_target(), Some("name"));
+
+        assert_eq!(Event::database_table().upsert_target(), None);
+    }
+
+    #[test]
+    fn table_versioned() {
+        let product = Product::database_table();
+        assert!(product.versioned);
+        let version = product
+            .columns
+            .iter()
+            .find(|column| column.name == "version")
+            .unwrap();
+        assert_eq!(version.ty, DatabaseType::Integer);
+        assert_eq!(version.default, Some(DatabaseDefault::Int(1)));
+
+        assert!(!Event::database_table().versioned);
+    }
+
+    #[test]
+    fn table_events() {
+        assert!(Product::database_table().events);
+        assert!(!Event::database_table().events);
+    }
+
+    #[test]
+    fn table_notify() {
+        assert!(Product::database_table().notify);
+        assert!(Product::database_table().subscriptions);
+        assert!(!Event::database_table().notify);
+        assert!(!Event::database_table().subscriptions);
+    }
+
+    #[test]
+    fn table_temporal() {
+        let contract = temporal::Contract::database_table();
+        assert_eq!(contract.temporal, Some(Temporal::Trigger));
+        assert_eq!(contract.history_table_name(), "contract_history");
+        assert_eq!(Product::database_table().temporal, None);
+    }
+
+    #[test]
+    fn table_visibility() {
+        let product = Product::database_table();
+        assert_eq!(product.visibility, EntityVisibility::default());
+        assert_eq!(product.visibility.entity(), Visibility::Public);
+
+        let event = Event::database_table();
+        assert_eq!(event.visibility.entity, None);
+        assert_eq!(event.visibility.active_model, Some(Visibility::Crate));
+        assert_eq!(event.visibility.active_model().as_str(), "pub(crate)");
+        assert_eq!(Visibility::parse("pub(crate)"), Some(Visibility::Crate));
+        assert_eq!(Visibility::parse("pub(super)"), None);
+    }
+
+    #[test]
+    fn table_localized() {
+        let product = Product::database_table();
+        assert_eq!(product.localization, Localization::Columns);
+        let names: Vec<_> = product
+            .localized_columns()
+            .into_iter()
+            .map(|column| column.name.as_str())
+            .collect();
+        assert_eq!(names, ["name", "description"]);
+        assert_eq!(product.translations_table_name(), "product_translations");
+
+        let description = product.localized_columns()[1];
+        assert_eq!(description.locale_column_name("pt-BR"), "description_pt_br");
+
+        assert!(Event::database_table().localized_columns().is_empty());
+    }
+
     #[test]
     fn columns() {
         let columns = Product::database_table().columns;
@@ -167,6 +1256,13 @@ mod test {
                 constraint: None,
                 primary_key: true,
                 references: None,
+                reference_constraint: None,
+                cross_database: false,
+                locales: vec![],
+                computed: None,
+                normalize: vec![],
+                fake: None,
+                backfill: None,
             },
             DatabaseColumn {
                 name: "created_at".to_string(),
@@ -177,6 +1273,13 @@ mod test {
                 constraint: None,
                 primary_key: false,
                 references: None,
+                reference_constraint: None,
+                cross_database: false,
+                locales: vec![],
+                computed: None,
+                normalize: vec![],
+                fake: None,
+                backfill: None,
             },
             DatabaseColumn {
                 name: "updated_at".to_string(),
@@ -187,6 +1290,13 @@ mod test {
                 constraint: None,
                 primary_key: false,
                 references: None,
+                reference_constraint: None,
+                cross_database: false,
+                locales: vec![],
+                computed: None,
+                normalize: vec![],
+                fake: None,
+                backfill: None,
             },
             DatabaseColumn {
                 name: "name".to_string(),
@@ -197,6 +1307,13 @@ mod test {
                 constraint: None,
                 primary_key: false,
                 references: None,
+                reference_constraint: None,
+                cross_database: false,
+                locales: vec!["en".to_string(), "de".to_string()],
+                computed: None,
+                normalize: vec![],
+                fake: None,
+                backfill: None,
             },
             DatabaseColumn {
                 name: "price".to_string(),
@@ -207,6 +1324,13 @@ mod test {
                 constraint: None,
                 primary_key: false,
                 references: None,
+                reference_constraint: None,
+                cross_database: false,
+                locales: vec![],
+                computed: None,
+                normalize: vec![],
+                fake: None,
+                backfill: None,
             },
             DatabaseColumn {
                 name: "description".to_string(),
@@ -217,8 +1341,184 @@ mod test {
                 constraint: None,
                 primary_key: false,
                 references: None,
+                reference_constraint: None,
+                cross_database: false,
+                locales: vec!["en".to_string(), "de".to_string(), "pt-BR".to_string()],
+                computed: None,
+                normalize: vec![],
+                fake: Some(Fake::Paragraph),
+                backfill: None,
+            },
+            DatabaseColumn {
+                name: "status".to_string(),
+                ty: DatabaseType::Text(Some(20)),
+                nullable: false,
+                default: Some(DatabaseDefault::String("draft".to_string())),
+                unique: false,
+                constraint: None,
+                primary_key: false,
+                references: None,
+                reference_constraint: None,
+                cross_database: false,
+                locales: vec![],
+                computed: None,
+                normalize: vec![],
+                fake: None,
+                backfill: None,
+            },
+            DatabaseColumn {
+                name: "version".to_string(),
+                ty: DatabaseType::Integer,
+                nullable: false,
+                default: Some(DatabaseDefault::Int(1)),
+                unique: false,
+                constraint: None,
+                primary_key: false,
+                references: None,
+                reference_constraint: None,
+                cross_database: false,
+                locales: vec![],
+                computed: None,
+                normalize: vec![],
+                fake: None,
+                backfill: None,
+            },
+            DatabaseColumn {
+                name: "price_cents".to_string(),
+                ty: DatabaseType::BigInt,
+                nullable: false,
+                default: None,
+                unique: false,
+                constraint: None,
+                primary_key: false,
+                references: None,
+                reference_constraint: None,
+                cross_database: false,
+                locales: vec![],
+                computed: Some("price * 100".to_string()),
+                normalize: vec![],
+                fake: None,
+                backfill: None,
             },
         ];
         assert_eq!(columns, expected);
     }
+
+    #[test]
+    fn quoted_identifiers() {
+        assert_eq!(quote_ident("product_id"), "product_id");
+        assert_eq!(quote_ident("order"), "\"order\"");
+        assert_eq!(quote_ident("user"), "\"user\"");
+        assert_eq!(quote_ident("Order"), "\"Order\"");
+        assert_eq!(quote_ident("odd\"name"), "\"odd\"\"name\"");
+
+        // Raw identifiers are recorded without their prefix
+        let order = reserved::Order::database_table();
+        assert_eq!(order.name, "order");
+        assert_eq!(order.columns[3].name, "type");
+    }
+
+    /// Shortened names name existing constraints and indexes, so these must never change.
+    #[test]
+    fn derived_names() {
+        assert_eq!(
+            derived_name(&["product", "category_id"], "fkey"),
+            "product_category_id_fkey"
+        );
+        let longest = "a".repeat(MAX_IDENTIFIER_LEN - 4);
+        assert_eq!(derived_name(&[&longest], "key"), format!("{}_key", longest));
+
+        let golden = [
+            (
+                derived_name(
+                    &[
+                        "customer_subscription_invoice_line_items",
+                        "tenant_id_created_at",
+                    ],
+                    "fkey",
+                ),
+                "customer_subscription_invoic_tenant_id_created_at_cebeee85_fkey",
+            ),
+            (
+                derived_name(
+                    &[
+                        "customer_subscription_invoice_line_items",
+                        "tenant_id_created_at",
+                    ],
+                    "key",
+                ),
+                "customer_subscription_invoice_tenant_id_created_at_7e7c53fb_key",
+            ),
+            (
+                derived_name(
+                    &["customer_subscription_invoice_line_items_tenant_id_created_at"],
+                    "history",
+                ),
+                "customer_subscription_invoice_line_items_tenan_c775369c_history",
+            ),
+            (
+                derived_name(&["x", &"y".repeat(80)], "fkey"),
+                "x_yyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyy_8b343610_fkey",
+            ),
+            // Parts are cut between characters
+            (
+                derived_name(&["ünïcode_".repeat(6).as_str(), "id"], "key"),
+                "ünïcode_ünïcode_ünïcode_ünïcode_ünïco_id_6c9d52fb_key",
+            ),
+        ];
+        for (name, expected) in &golden {
+            assert_eq!(name, expected);
+            assert!(name.len() <= MAX_IDENTIFIER_LEN);
+        }
+        assert_eq!(
+            derived_name_prefix(
+                &[
+                    "customer_subscription_invoice_line_items_archive",
+                    "fulltext"
+                ],
+                12
+            ),
+            "customer_subscription_invoice_li_fulltext_"
+        );
+    }
+
+    #[test]
+    fn normalizers() {
+        assert_eq!(
+            slugify("  Rust in 2024: Ünïcode & more!  "),
+            "rust-in-2024-ünïcode-more"
+        );
+        assert_eq!(slugify("already-a-slug"), "already-a-slug");
+        assert_eq!(
+            Normalizer::Lowercase.apply(" Ada@Example.com"),
+            Some(" ada@example.com".to_string())
+        );
+        assert_eq!(Normalizer::Trim.apply(" Ada "), Some("Ada".to_string()));
+        assert_eq!(
+            Normalizer::Function("crate::normalize::phone".to_string()).apply("Ada"),
+            None
+        );
+        assert_eq!(
+            Normalizer::SlugifyFrom("title".to_string()).to_string(),
+            "slugify-from(title)"
+        );
+
+        let employee = Employee::database_table();
+        let name = employee
+            .columns
+            .iter()
+            .find(|column| column.name == "name")
+            .unwrap();
+        assert_eq!(name.normalize, [Normalizer::Trim]);
+    }
+
+    #[test]
+    fn fakes() {
+        for fake in Fake::ALL {
+            assert_eq!(fake.to_string().parse::<Fake>().ok(), Some(fake));
+        }
+        assert_eq!(Fake::FirstName.to_string(), "first_name");
+        assert_eq!(Fake::of_column("email"), Some(Fake::Email));
+        assert_eq!(Fake::of_column("title"), None);
+    }
 }
@@ -17,6 +17,7 @@
 pub use awto_macros as macros;
 pub use lazy_static;
 
+pub mod checkpoint;
 pub mod database;
 pub mod prelude;
 pub mod protobuf;
@@ -4,6 +4,7 @@ use std::{
 };
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ProtobufType {
     Double,
     Float,
@@ -21,7 +22,10 @@ pub enum ProtobufType {
     String,
     Bytes,
     Repeated(Box<ProtobufType>),
+    /// A `map<string, T>` of the value type, for `HashMap<String, T>` fields.
+    Map(Box<ProtobufType>),
     Timestamp,
+    FieldMask,
     Custom(ProtobufMessage),
 }
 
@@ -34,6 +38,12 @@ impl str::FromStr for ProtobufType {
         if let Some(inner) = s.strip_prefix("repeated ") {
             return Ok(Self::Repeated(Box::new(inner.parse()?)));
         }
+        if let Some(value) = s
+            .strip_prefix("map<string, ")
+            .and_then(|rest| rest.strip_suffix('>'))
+        {
+            return Ok(Self::Map(Box::new(value.parse()?)));
+        }
         let protobuf_type = match s {
             "double" => Self::Double,
             "float" => Self::Float,
@@ -51,6 +61,7 @@ impl str::FromStr for ProtobufType {
             "String" => Self::String,
             "bytes" => Self::Bytes,
             "google.protobuf.Timestamp" => Self::Timestamp,
+            "google.protobuf.FieldMask" => Self::FieldMask,
             _ => return Err(ProtobufTypeFromStrError),
         };
         Ok(protobuf_type)
@@ -76,23 +87,35 @@ impl fmt::Display for ProtobufType {
             Self::String => write!(f, "string"),
             Self::Bytes => write!(f, "bytes"),
             Self::Repeated(inner) => write!(f, "repeated {}", inner),
+            Self::Map(value) => write!(f, "map<string, {}>", value),
             Self::Timestamp => write!(f, "google.protobuf.Timestamp"),
+            Self::FieldMask => write!(f, "google.protobuf.FieldMask"),
             Self::Custom(inner) => write!(f, "{}", inner.name),
         }
     }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ProtobufField {
     pub name: String,
     pub ty: ProtobufType,
     pub required: bool,
+    /// Whether the field is marked `[deprecated = true]`, set with `#[awto(deprecated)]`.
+    pub deprecated: bool,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ProtobufMethod {
     pub is_async: bool,
     pub name: String,
+    /// Doc comment of the method, with the leading space of each line removed.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub doc: Option<String>,
     pub param: ProtobufMessage,
     pub returns: ProtobufMessage,
     pub returns_result: bool,
@@ -107,6 +130,7 @@ pub trait IntoProtobufMessage {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ProtobufSchema {
     pub name: String,
     pub fields: Vec<ProtobufField>,
@@ -114,12 +138,30 @@ pub struct ProtobufSchema {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ProtobufMessage {
     pub name: String,
     pub fields: Vec<ProtobufField>,
+    /// Version of the protobuf package the message was last changed in, set with `#[awto(proto(version = 2))]`.
+    pub version: u32,
+    /// Values of fields removed since earlier versions, set with `#[awto(proto(v1_default(field = "...")))]`.
+    pub defaults: Vec<ProtobufDefault>,
+}
+
+/// Value of a field which was removed since an earlier version of a message.
+///
+/// The value is a Rust expression of the type of the field in that version,
+/// which can read the message of the current version as `val`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProtobufDefault {
+    pub version: u32,
+    pub field: String,
+    pub value: String,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ProtobufService {
     pub methods: Vec<ProtobufMethod>,
     pub module_path: String,
@@ -137,6 +179,39 @@ mod test {
         assert_eq!(Product::protobuf_message().name, "Product");
     }
 
+    #[test]
+    fn message_version() {
+        assert_eq!(Product::protobuf_message().version, 1);
+        assert!(Product::protobuf_message().defaults.is_empty());
+
+        let employee = Employee::protobuf_message();
+        assert_eq!(employee.version, 2);
+        assert_eq!(
+            employee.defaults,
+            vec![ProtobufDefault {
+                version: 1,
+                field: "title".to_string(),
+                value: "String::new()".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn map_fields() {
+        let fields = ProductAttributes::protobuf_message().fields;
+        let prices = ProtobufType::Map(Box::new(ProtobufType::Int64));
+
+        assert_eq!(
+            fields[1].ty,
+            ProtobufType::Map(Box::new(ProtobufType::String))
+        );
+        assert_eq!(fields[2].ty, prices);
+        // Maps are never optional, even for optional fields
+        assert!(fields[2].required);
+        assert_eq!(prices.to_string(), "map<string, int64>");
+        assert!(matches!("map<string, int64>".parse::<ProtobufType>(), Ok(ty) if ty == prices));
+    }
+
     #[test]
     fn columns() {
         let fields = Product::protobuf_message().fields;
@@ -146,31 +221,55 @@ mod test {
                 name: "id".to_string(),
                 ty: ProtobufType::String,
                 required: true,
+                deprecated: false,
             },
             ProtobufField {
                 name: "created_at".to_string(),
                 ty: ProtobufType::Timestamp,
                 required: true,
+                deprecated: false,
             },
             ProtobufField {
                 name: "updated_at".to_string(),
                 ty: ProtobufType::Timestamp,
                 required: true,
+                deprecated: false,
             },
             ProtobufField {
                 name: "name".to_string(),
                 ty: ProtobufType::String,
                 required: true,
+                deprecated: false,
             },
             ProtobufField {
                 name: "price".to_string(),
                 ty: ProtobufType::Int64,
                 required: true,
+                deprecated: false,
             },
             ProtobufField {
                 name: "description".to_string(),
                 ty: ProtobufType::String,
                 required: false,
+                deprecated: false,
+            },
+            ProtobufField {
+                name: "status".to_string(),
+                ty: ProtobufType::String,
+                required: false,
+                deprecated: false,
+            },
+            ProtobufField {
+                name: "version".to_string(),
+                ty: ProtobufType::Int32,
+                required: true,
+                deprecated: false,
+            },
+            ProtobufField {
+                name: "price_cents".to_string(),
+                ty: ProtobufType::Int64,
+                required: true,
+                deprecated: false,
             },
         ];
         assert_eq!(fields, expected);
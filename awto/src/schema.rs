@@ -1,7 +1,14 @@
+use std::cell::Cell;
+
 use crate::database::DatabaseTable;
 use crate::protobuf::ProtobufMessage;
 
+thread_local! {
+    static INTERNAL_REVEALED: Cell<bool> = const { Cell::new(false) };
+}
+
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Role {
     /// An item which will be used to create database tables
     DatabaseTable(DatabaseTable),
@@ -14,14 +21,142 @@ pub enum Role {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RustField {
     pub name: String,
+    /// Doc comment of the field, with the leading space of each line removed.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub doc: Option<String>,
+    /// Type the field is stored as, with newtypes replaced by their inner types, such as `Option<i64>`.
     pub ty: String,
+    /// Name of the `#[awto(newtype)]` wrapping the value of the field, such as `Cents` for an `Option<Cents>`.
+    pub newtype: Option<String>,
+    /// Deprecation of the field, set with `#[awto(deprecated(note = "...", since = "..."))]`.
+    pub deprecated: Option<Deprecation>,
+    /// Whether values of the field are redacted from request logs,
+    /// set with `#[awto(sensitive)]`, implied by `#[serde(skip)]` or by an internal or secret `sensitivity`.
+    pub sensitive: bool,
+    /// Sensitivity of the values of the field, set with `#[awto(sensitivity = "...")]`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub sensitivity: Sensitivity,
+    /// Feature the field is generated with, set with `#[awto(feature = "...")]`.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub feature: Option<String>,
+}
+
+/// Who may see the values of a field, from the least to the most restricted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Sensitivity {
+    /// Shown wherever the model is, the default.
+    #[default]
+    Public,
+    /// Left out of serialized schema structs outside of [`reveal_internal`], and masked by the admin and exports
+    /// unless sensitive values are shown.
+    Internal,
+    /// Never serialized nor shown: left out of protobuf messages and serialized schema structs,
+    /// and always masked by the admin and exports.
+    Secret,
+}
+
+impl Sensitivity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Sensitivity::Public => "public",
+            Sensitivity::Internal => "internal",
+            Sensitivity::Secret => "secret",
+        }
+    }
+}
+
+/// Runs `f` with the internal fields of schema structs included when they are serialized, such as to respond
+/// to a caller granted the scope of internal fields, which are left out otherwise.
+pub fn reveal_internal<T>(f: impl FnOnce() -> T) -> T {
+    struct Reset(bool);
+
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            INTERNAL_REVEALED.with(|revealed| revealed.set(self.0));
+        }
+    }
+
+    let _reset = Reset(INTERNAL_REVEALED.with(|revealed| revealed.replace(true)));
+    f()
+}
+
+/// Returns whether internal fields are left out of serialized schema structs, outside of [`reveal_internal`].
+///
+/// Used by the `#[serde(skip_serializing_if = "...")]` attribute `schema!` adds to internal fields.
+#[doc(hidden)]
+pub fn internal_hidden<T: ?Sized>(_: &T) -> bool {
+    !INTERNAL_REVEALED.with(Cell::get)
+}
+
+/// Tokens of a `schema!` invocation, which `schema!` keeps in an unnamed constant.
+///
+/// `awto --expand` reads the models of the schema crate from them once its macros are expanded,
+/// which finds the models of schemas written by other macros.
+#[doc(hidden)]
+pub struct ExpandedSchema(pub &'static str);
+
+/// Deprecation of a field which is being removed.
+///
+/// Deprecated fields are still read and exposed, but no longer written by generated inserts and updates,
+/// and their columns are kept until the field is deleted from the schema.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Deprecation {
+    /// What to use instead, such as `use display_name`.
+    pub note: Option<String>,
+    /// Date the field was deprecated on, such as `2024-06-01`.
+    pub since: Option<String>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Model {
     pub name: String,
+    /// Doc comment of the model, with the leading space of each line removed.
+    pub doc: Option<String>,
     pub roles: Vec<Role>,
     pub fields: Vec<RustField>,
+    /// Schema package declaring the model, set when the models of several schema packages are merged.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub origin: Option<ModelOrigin>,
+    /// Feature the model is generated with, set with `#[awto(feature = "...")]`.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub feature: Option<String>,
+    /// Fields of the schema struct left out of every generated package as their feature is disabled,
+    /// which conversions into the struct set to their default.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub omitted_fields: Vec<String>,
+}
+
+/// Schema package a model was declared in, along with the name of its struct there.
+///
+/// The name of the model differs from the name of its struct when it was namespaced by its package,
+/// such as the `Invoice` struct of `schema-billing` merged as the `BillingInvoice` model.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ModelOrigin {
+    /// Name of the schema package, such as `schema-billing`.
+    pub package: String,
+    /// Name of the struct of the model in the schema package.
+    pub name: String,
 }
@@ -1,20 +1,56 @@
 use crate as awto;
 use crate::prelude::*;
 use chrono::Local;
+use std::collections::HashMap;
 use tonic::Status;
 
 schema! {
+    #[awto(newtype)]
+    #[derive(Clone, Copy, Debug, Default, PartialEq)]
+    pub struct Cents(pub i64);
+
+    /// A product sold in the shop, priced in [`Cents`] with a `draft|live` status.
+    ///
+    /// Names and descriptions are translated, see [`Product::name`].
     #[database_table]
     #[protobuf_message]
+    #[awto(order_by = created_at, versioned, events, fulltext(fields(name, description), language = "english"))]
+    #[awto(graphql(subscriptions))]
+    #[awto(authorize(read = "public", write = "catalog:write", delete = "admin"))]
+    #[awto(projection(name = "ProductSummary", fields(id, created_at, name, price_cents)))]
+    #[awto(projection(name = "ProductPrice", fields(id, created_at, price)))]
+    #[awto(scope(live = "status = 'live'"))]
+    #[awto(scope(recent(days: i64) = "created_at > now() - interval '1 day' * :days"))]
     pub struct Product {
         pub id: Uuid,
         pub created_at: DateTime<FixedOffset>,
         pub updated_at: DateTime<FixedOffset>,
+        /// Name of the product in its default locale.
+        #[awto(localized(locales = "en,de"))]
         pub name: String,
         #[awto(default = 0)]
-        pub price: i64,
-        #[awto(max_len = 120)]
+        pub price: Cents,
+        #[awto(max_len = 120, localized(locales = "en,de,pt-BR"), fake = "paragraph")]
         pub description: Option<String>,
+        #[awto(default = "draft", max_len = 20, optional_insert)]
+        pub status: Option<String>,
+        pub version: i32,
+        #[awto(computed = "price * 100", stored)]
+        pub price_cents: i64,
+    }
+
+    #[database_table]
+    #[awto(database = "analytics", readonly)]
+    #[awto(rls(policy = "recent_events", using = "created_at > now() - interval '90 days'"))]
+    #[awto(entity_attrs("#[doc(alias = \"audit_event\")]"))]
+    #[awto(visibility(active_model = "pub(crate)"))]
+    pub struct Event {
+        pub id: Uuid,
+        pub created_at: DateTime<FixedOffset>,
+        pub updated_at: DateTime<FixedOffset>,
+        #[awto(allow(missing_max_len))]
+        pub name: String,
+        pub properties: HashMap<String, String>,
     }
 
     #[protobuf_message]
@@ -27,20 +63,46 @@ schema! {
         pub products: Vec<Product>,
     }
 
+    #[protobuf_message]
+    pub struct ProductAttributes {
+        pub id: Uuid,
+        pub attributes: HashMap<String, String>,
+        pub prices: Option<HashMap<String, Cents>>,
+    }
+
     #[protobuf_message]
     #[database_sub_table(Product)]
     pub struct NewProduct {
         pub name: String,
-        pub price: Option<i64>,
+        pub price: Option<Cents>,
         pub description: Option<String>,
+        pub version: i32,
+    }
+
+    /// An employee, managed by another employee unless they lead the shop.
+    #[database_table]
+    #[protobuf_message]
+    #[awto(proto(version = 2, v1_default(title = "String::new()")))]
+    pub struct Employee {
+        pub id: Uuid,
+        pub created_at: DateTime<FixedOffset>,
+        pub updated_at: DateTime<FixedOffset>,
+        #[awto(max_len = 120, normalize = "trim", sensitive)]
+        pub name: String,
+        #[awto(references = (Employee, "id"))]
+        pub manager_id: Option<Uuid>,
+        #[awto(max_len = 40, deprecated(note = "use name", since = "2024-06-01"))]
+        pub nickname: Option<String>,
     }
 }
 
 #[derive(Default)]
 pub struct ProductService;
 
+#[allow(clippy::result_large_err)]
 #[protobuf_service]
 impl ProductService {
+    /// Finds the products with the id.
     pub fn find_product(&self, request: ProductId) -> Result<ProductList, Status> {
         if request.id == Uuid::default() {
             Ok(ProductList {
@@ -49,8 +111,11 @@ impl ProductService {
                     created_at: Local::now().with_timezone(&FixedOffset::east(0)),
                     updated_at: Local::now().with_timezone(&FixedOffset::east(0)),
                     name: "1".to_string(),
-                    price: 20,
+                    price: Cents(20),
                     description: None,
+                    status: Some("draft".to_string()),
+                    version: 1,
+                    price_cents: 2000,
                 }],
             })
         } else {
@@ -59,6 +124,36 @@ impl ProductService {
     }
 }
 
+/// Returns `count` models made of numbered copies of [`MODELS`], such as `Product12` with its `product_12` table,
+/// used to measure generating large schemas.
+///
+/// The columns of each copy reference the tables of the same copy, like the models they were copied from.
+pub fn synthetic_models(count: usize) -> Vec<Model> {
+    (0..)
+        .flat_map(|copy| MODELS.iter().map(move |model| synthetic_model(model, copy)))
+        .take(count)
+        .collect()
+}
+
+fn synthetic_model(model: &Model, copy: usize) -> Model {
+    let mut model = model.clone();
+    model.name = format!("{}{}", model.name, copy);
+    for role in &mut model.roles {
+        if let Role::DatabaseTable(table) | Role::DatabaseSubTable(table) = role {
+            table.name = format!("{}_{}", table.name, copy);
+            for (references_table, _) in table
+                .columns
+                .iter_mut()
+                .filter_map(|column| column.references.as_mut())
+            {
+                *references_table = format!("{}_{}", references_table, copy);
+            }
+        }
+    }
+
+    model
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -67,4 +162,544 @@ mod test {
     fn models() {
         println!("{:#?}", &*MODELS);
     }
+
+    #[test]
+    fn synthetic() {
+        let models = synthetic_models(MODELS.len() + 2);
+
+        assert_eq!(models.len(), MODELS.len() + 2);
+        assert_eq!(models[0].name, "Product0");
+        assert_eq!(models[MODELS.len()].name, "Product1");
+        let employee = models
+            .iter()
+            .find(|model| model.name == "Employee0")
+            .unwrap();
+        let table = employee
+            .roles
+            .iter()
+            .find_map(|role| match role {
+                Role::DatabaseTable(table) => Some(table),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(table.name, "employee_0");
+        let manager_id = table
+            .columns
+            .iter()
+            .find(|column| column.name == "manager_id")
+            .unwrap();
+        assert_eq!(
+            manager_id.references,
+            Some(("employee_0".to_string(), "id".to_string()))
+        );
+    }
+}
+
+/// Models whose names are reserved words of sql, Rust or sea-orm.
+pub mod reserved {
+    use crate as awto;
+    use crate::prelude::*;
+
+    schema! {
+        #[database_table]
+        #[protobuf_message]
+        pub struct Order {
+            pub id: Uuid,
+            pub created_at: DateTime<FixedOffset>,
+            pub updated_at: DateTime<FixedOffset>,
+            #[awto(max_len = 20, unique)]
+            pub r#type: String,
+            pub user: Option<Uuid>,
+            pub group: i32,
+            pub r#match: bool,
+        }
+
+        #[database_table]
+        pub struct Model {
+            pub id: Uuid,
+            pub created_at: DateTime<FixedOffset>,
+            pub updated_at: DateTime<FixedOffset>,
+            #[awto(references = (Order, "id"))]
+            pub order: Uuid,
+            #[awto(max_len = 20)]
+            pub r#where: Option<String>,
+        }
+    }
+}
+
+/// A schema of trees, whose rows reference rows of their own table.
+pub mod tree {
+    use crate as awto;
+    use crate::prelude::*;
+
+    schema! {
+        #[database_table]
+        #[protobuf_message]
+        pub struct Category {
+            pub id: Uuid,
+            pub created_at: DateTime<FixedOffset>,
+            pub updated_at: DateTime<FixedOffset>,
+            #[awto(max_len = 60)]
+            pub name: String,
+            #[awto(references = (Category, "id"))]
+            pub parent_id: Option<Uuid>,
+        }
+
+        #[database_table]
+        #[protobuf_message]
+        pub struct Comment {
+            pub id: Uuid,
+            pub created_at: DateTime<FixedOffset>,
+            pub updated_at: DateTime<FixedOffset>,
+            pub body: String,
+            #[awto(references = (Comment, "id"))]
+            pub reply_to_id: Option<Uuid>,
+            #[awto(references = (Category, "id"))]
+            pub category_id: Uuid,
+        }
+    }
+}
+
+/// A schema of a high-volume table partitioned by month.
+pub mod partitioned {
+    use crate as awto;
+    use crate::prelude::*;
+
+    schema! {
+        #[database_table]
+        #[protobuf_message]
+        #[awto(partition(by = "range", column = created_at, interval = "month"))]
+        pub struct PageView {
+            pub id: Uuid,
+            pub created_at: DateTime<FixedOffset>,
+            pub updated_at: DateTime<FixedOffset>,
+            #[awto(max_len = 2048)]
+            pub path: String,
+            pub session_id: Option<Uuid>,
+        }
+    }
+}
+
+/// A schema of a table keeping the history of its rows.
+pub mod temporal {
+    use crate as awto;
+    use crate::prelude::*;
+
+    schema! {
+        #[database_table]
+        #[protobuf_message]
+        #[awto(temporal)]
+        pub struct Contract {
+            pub id: Uuid,
+            pub created_at: DateTime<FixedOffset>,
+            pub updated_at: DateTime<FixedOffset>,
+            #[awto(max_len = 120, unique)]
+            pub title: String,
+            #[awto(default = 0)]
+            pub amount: i64,
+            pub signed_at: Option<DateTime<FixedOffset>>,
+        }
+    }
+}
+
+/// A schema mapping a table managed by another system, referenced by a table of awto.
+pub mod external {
+    use crate as awto;
+    use crate::prelude::*;
+
+    schema! {
+        #[database_table]
+        #[protobuf_message]
+        #[awto(external)]
+        pub struct LegacyUser {
+            pub id: Uuid,
+            pub created_at: DateTime<FixedOffset>,
+            pub updated_at: DateTime<FixedOffset>,
+            #[awto(max_len = 120)]
+            pub login: String,
+        }
+
+        #[database_table]
+        #[protobuf_message]
+        pub struct Ticket {
+            pub id: Uuid,
+            pub created_at: DateTime<FixedOffset>,
+            pub updated_at: DateTime<FixedOffset>,
+            #[awto(max_len = 200)]
+            pub subject: String,
+            #[awto(references = (LegacyUser, "id"))]
+            pub reporter_id: Uuid,
+            #[awto(references = (LegacyUser, "id", constraint = true))]
+            pub assignee_id: Option<Uuid>,
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        #[test]
+        fn external_tables() {
+            let legacy_user = LegacyUser::database_table();
+            assert!(legacy_user.external && legacy_user.readonly);
+
+            let ticket = Ticket::database_table();
+            assert!(!ticket.external);
+            assert_eq!(ticket.columns[4].reference_constraint, None);
+            assert_eq!(ticket.columns[5].reference_constraint, Some(true));
+        }
+    }
+}
+
+/// A schema of two databases, whose page views reference the visits recorded in the analytics database.
+pub mod cross_database {
+    use crate as awto;
+    use crate::prelude::*;
+
+    schema! {
+        #[database_table]
+        #[awto(database = "analytics")]
+        pub struct Visit {
+            pub id: Uuid,
+            pub created_at: DateTime<FixedOffset>,
+            pub updated_at: DateTime<FixedOffset>,
+            #[awto(max_len = 200)]
+            pub referrer: Option<String>,
+        }
+
+        #[database_table]
+        pub struct PageView {
+            pub id: Uuid,
+            pub created_at: DateTime<FixedOffset>,
+            pub updated_at: DateTime<FixedOffset>,
+            #[awto(max_len = 200)]
+            pub path: String,
+            #[awto(references = (Visit, "id", cross_database))]
+            pub visit_id: Uuid,
+            #[awto(references = (Visit, "id", cross_database))]
+            pub first_visit_id: Option<Uuid>,
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        #[test]
+        fn cross_database_references() {
+            let page_view = PageView::database_table();
+            assert_eq!(page_view.database(), "main");
+            assert_eq!(
+                page_view.columns[4].references,
+                Some(("visit".to_string(), "id".to_string()))
+            );
+            assert!(page_view.columns[4].cross_database && page_view.columns[5].cross_database);
+            assert!(!Visit::database_table().columns[3].cross_database);
+        }
+    }
+}
+
+/// A schema of countries keyed by their code, which the application assigns, and the addresses referencing them.
+pub mod string_keys {
+    use crate as awto;
+    use crate::prelude::*;
+
+    schema! {
+        #[database_table]
+        #[protobuf_message]
+        pub struct Country {
+            #[awto(primary_key, max_len = 2)]
+            pub code: String,
+            pub created_at: DateTime<FixedOffset>,
+            pub updated_at: DateTime<FixedOffset>,
+            #[awto(max_len = 120)]
+            pub name: String,
+        }
+
+        #[database_table]
+        #[protobuf_message]
+        pub struct Page {
+            #[awto(primary_key)]
+            pub slug: String,
+            pub created_at: DateTime<FixedOffset>,
+            pub updated_at: DateTime<FixedOffset>,
+            pub title: String,
+        }
+
+        #[database_table]
+        #[protobuf_message]
+        pub struct Address {
+            pub id: Uuid,
+            pub created_at: DateTime<FixedOffset>,
+            pub updated_at: DateTime<FixedOffset>,
+            #[awto(max_len = 200)]
+            pub street: String,
+            #[awto(references = (Country, "code"), max_len = 2)]
+            pub country_code: String,
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+        use crate::database::DatabaseType;
+
+        #[test]
+        fn string_primary_keys() {
+            let country = Country::database_table();
+            assert_eq!(country.primary_key_columns(), ["code"]);
+            assert_eq!(
+                country.primary_key().unwrap().ty,
+                DatabaseType::Text(Some(2))
+            );
+            assert_eq!(country.primary_key().unwrap().default, None);
+            assert_eq!(
+                Page::database_table().primary_key().unwrap().ty,
+                DatabaseType::Text(None)
+            );
+
+            let address = Address::database_table();
+            assert_eq!(address.primary_key().unwrap().name, "id");
+            assert_eq!(
+                address.columns[4].references,
+                Some(("country".to_string(), "code".to_string()))
+            );
+        }
+    }
+}
+
+/// A schema of orders and vouchers, whose creates may be retried with an idempotency key.
+pub mod idempotent {
+    use crate as awto;
+    use crate::prelude::*;
+
+    schema! {
+        #[database_table]
+        #[protobuf_message]
+        #[awto(idempotent_create, events)]
+        pub struct Order {
+            pub id: Uuid,
+            pub created_at: DateTime<FixedOffset>,
+            pub updated_at: DateTime<FixedOffset>,
+            pub total_cents: i64,
+        }
+
+        #[database_table]
+        #[protobuf_message]
+        #[awto(idempotent_create)]
+        pub struct Voucher {
+            #[awto(primary_key, max_len = 32)]
+            pub code: String,
+            pub created_at: DateTime<FixedOffset>,
+            pub updated_at: DateTime<FixedOffset>,
+            pub amount_cents: i64,
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        #[test]
+        fn idempotent_creates() {
+            assert!(Order::database_table().idempotent_create);
+            assert!(Voucher::database_table().idempotent_create);
+            assert!(!crate::tests_cfg::Product::database_table().idempotent_create);
+        }
+    }
+}
+
+/// A model narrowing the columns of an existing table, one of which is backfilled.
+pub mod narrowing {
+    use crate as awto;
+    use crate::prelude::*;
+
+    schema! {
+        #[database_table]
+        pub struct Product {
+            pub id: Uuid,
+            pub created_at: DateTime<FixedOffset>,
+            pub updated_at: DateTime<FixedOffset>,
+            pub name: String,
+            #[awto(max_len = 8, column(backfill = "left(sku, 8)"))]
+            pub sku: String,
+            #[awto(max_len = 20)]
+            pub note: Option<String>,
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        #[test]
+        fn backfills() {
+            let columns = Product::database_table().columns;
+            let backfill = |name: &str| {
+                columns
+                    .iter()
+                    .find(|column| column.name == name)
+                    .unwrap()
+                    .backfill
+                    .clone()
+            };
+            assert_eq!(backfill("sku"), Some("left(sku, 8)".to_string()));
+            assert_eq!(backfill("note"), None);
+        }
+    }
+}
+
+/// Models and fields of an enterprise edition, gated behind its feature.
+pub mod features {
+    use crate as awto;
+    use crate::prelude::*;
+
+    schema! {
+        #[database_table]
+        #[protobuf_message]
+        #[awto(feature = "enterprise")]
+        pub struct Team {
+            pub id: Uuid,
+            pub created_at: DateTime<FixedOffset>,
+            pub updated_at: DateTime<FixedOffset>,
+            #[awto(max_len = 120)]
+            pub name: String,
+        }
+
+        #[database_table]
+        #[protobuf_message]
+        pub struct Invoice {
+            pub id: Uuid,
+            pub created_at: DateTime<FixedOffset>,
+            pub updated_at: DateTime<FixedOffset>,
+            pub total: i64,
+            #[awto(feature = "enterprise", references = (Team, "id"))]
+            pub team_id: Option<Uuid>,
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        #[test]
+        fn features() {
+            assert_eq!(MODELS[0].feature.as_deref(), Some("enterprise"));
+            assert_eq!(MODELS[1].feature, None);
+            assert_eq!(MODELS[1].fields[4].feature.as_deref(), Some("enterprise"));
+            assert!(MODELS[1].fields[..4]
+                .iter()
+                .all(|field| field.feature.is_none()));
+        }
+    }
+}
+
+/// A schema without models, as in projects created before their first model exists.
+pub mod empty {
+    use crate as awto;
+    use crate::prelude::*;
+
+    schema! {}
+}
+
+/// Models with internal and secret fields, whose values must not leak through serialized or printed models.
+#[cfg(feature = "serde")]
+pub mod sensitivity {
+    use crate as awto;
+    use crate::prelude::*;
+
+    schema! {
+        #[database_table]
+        #[protobuf_message]
+        #[derive(Clone, Debug)]
+        pub struct Account {
+            pub id: Uuid,
+            pub created_at: DateTime<FixedOffset>,
+            pub updated_at: DateTime<FixedOffset>,
+            #[awto(max_len = 120, unique)]
+            pub email: String,
+            #[awto(sensitivity = "internal")]
+            pub credit_limit: Option<i64>,
+            #[awto(max_len = 200, sensitivity = "secret")]
+            pub password_hash: String,
+        }
+
+        #[protobuf_message]
+        #[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+        pub struct Credentials {
+            pub username: String,
+            #[awto(sensitivity = "internal")]
+            pub quota: i64,
+            #[awto(sensitivity = "secret")]
+            pub password: String,
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use crate::schema::{reveal_internal, Sensitivity};
+
+        use super::*;
+
+        #[test]
+        fn sensitive_fields() {
+            let fields = &MODELS[0].fields;
+            assert_eq!(fields[3].sensitivity, Sensitivity::Public);
+            assert!(!fields[3].sensitive);
+            assert_eq!(fields[4].sensitivity, Sensitivity::Internal);
+            assert_eq!(fields[5].sensitivity, Sensitivity::Secret);
+            assert!(fields[4].sensitive && fields[5].sensitive);
+
+            // Secret fields are left out of protobuf messages
+            let message = Credentials::protobuf_message();
+            let names: Vec<_> = message
+                .fields
+                .iter()
+                .map(|field| field.name.as_str())
+                .collect();
+            assert_eq!(names, ["username", "quota"]);
+        }
+
+        #[test]
+        fn serialized_fields() {
+            let credentials = Credentials {
+                username: "ada".to_string(),
+                quota: 10,
+                password: "hunter2".to_string(),
+            };
+
+            let serialized = serde_json::to_value(&credentials).unwrap();
+            assert_eq!(serialized, serde_json::json!({ "username": "ada" }));
+            let revealed = reveal_internal(|| serde_json::to_value(&credentials)).unwrap();
+            assert_eq!(
+                revealed,
+                serde_json::json!({ "username": "ada", "quota": 10 })
+            );
+            assert_eq!(serde_json::to_value(&credentials).unwrap(), serialized);
+
+            // Secret values are still read, such as from requests setting them
+            let read: Credentials =
+                serde_json::from_str(r#"{"username":"ada","quota":1,"password":"hunter2"}"#)
+                    .unwrap();
+            assert_eq!(read.password, "hunter2");
+
+            assert_eq!(
+                format!("{:?}", credentials),
+                r#"Credentials { username: "ada", quota: ********, password: ******** }"#
+            );
+            let account = Account {
+                id: Uuid::default(),
+                created_at: DateTime::parse_from_rfc3339("2024-06-01T00:00:00Z").unwrap(),
+                updated_at: DateTime::parse_from_rfc3339("2024-06-01T00:00:00Z").unwrap(),
+                email: "ada@example.com".to_string(),
+                credit_limit: Some(500),
+                password_hash: "$argon2id$".to_string(),
+            };
+            let debug = format!("{:?}", account);
+            assert!(debug.contains(
+                r#"email: "ada@example.com", credit_limit: ********, password_hash: ********"#
+            ));
+            assert!(!debug.contains("argon2") && !debug.contains("500"));
+        }
+    }
 }